@@ -7,8 +7,7 @@ use std::{
 };
 use anyhow::{Context, Result};
 use clap::Parser;
-use compiler::compile_source;
-use plugin_api::CompiledModule;
+use compiler::compile_module;
 
 /// T-Lang compiler frontend
 #[derive(Parser)]
@@ -30,7 +29,7 @@ fn main() -> Result<()> {
         .with_context(|| format!("reading `{}`", opt.input.display()))?;
 
     // Compile it to bytecode
-    let module: CompiledModule = compile_source(&src)
+    let module = compile_module(&src, &opt.input.to_string_lossy())
         .with_context(|| format!("compiling `{}`", opt.input.display()))?;
 
     // Make sure output directory exists
@@ -48,7 +47,7 @@ fn main() -> Result<()> {
     // Create and write it
     let mut f = File::create(&out_file)
         .with_context(|| format!("creating output file `{}`", out_file.display()))?;
-    for instr in &module.instructions() {
+    for instr in module.instructions() {
         writeln!(f, "{instr:?}")?;
     }
 
@@ -55,8 +55,8 @@ fn compile_hello_creates_bytecode() -> Result<(), Box<dyn std::error::Error>> {
         path
     );
     assert!(
-        bytecode.contains("CallPrint"),
-        "didn't see a CallPrint instruction in {:?}",
+        bytecode.contains("PrintStr"),
+        "didn't see a PrintStr instruction in {:?}",
         path
     );
 
@@ -0,0 +1,13 @@
+// File: compiler/fuzz/fuzz_targets/parse.rs
+//! `Parser::parse` and the recovering parse must return for any text.
+
+#![no_main]
+
+use compiler::Parser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let parser = Parser::new(String::from_utf8_lossy(data).into_owned());
+    let _ = parser.parse();
+    let _ = parser.parse_with_recovery();
+});
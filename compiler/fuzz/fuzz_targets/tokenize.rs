@@ -0,0 +1,11 @@
+// File: compiler/fuzz/fuzz_targets/tokenize.rs
+//! `tokenize` must return, token list or error, for any text.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data).into_owned();
+    let _ = shared::tokenize(source);
+});
@@ -1,39 +1,174 @@
-use shared::ast::*;
+// File: compiler/src/ast_transform.rs
+//! AST transform plugins.
+//!
+//! Transforms run between parsing and type checking. Each transform declares
+//! a priority (lower runs first) and, optionally, the names of transforms that
+//! must run before it. Per-transform configuration comes from
+//! `CompilerOptions::transforms`, keyed by transform name.
 
-pub trait ASTVisitor {
-    fn visit_ast(&mut self, ast: &AST) {
-        for item in &ast.items {
-            self.visit_item(item);
+use shared::{Program, Result, TlError};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Per-transform configuration supplied through `CompilerOptions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformConfig {
+    /// Whether the transform runs at all.
+    pub enabled: bool,
+    /// Free-form `key = value` options interpreted by the transform.
+    pub options: HashMap<String, String>,
+}
+
+impl Default for TransformConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            options: HashMap::new(),
         }
     }
+}
+
+impl TransformConfig {
+    /// Look up an option value.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str)
+    }
+}
+
+/// A plugin that rewrites the program between parsing and type checking.
+pub trait AstTransform: Send + Sync {
+    /// Unique transform name, used for ordering and configuration lookup.
+    fn name(&self) -> &'static str;
+
+    /// Scheduling priority; lower values run first.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Names of transforms that must run before this one.
+    fn run_after(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Rewrite the program.
+    fn transform(&self, program: Program, config: &TransformConfig) -> Result<Program>;
+}
+
+/// Registry of all available transforms.
+static TRANSFORMS: Mutex<Vec<&'static dyn AstTransform>> = Mutex::new(Vec::new());
+
+fn transforms() -> MutexGuard<'static, Vec<&'static dyn AstTransform>> {
+    TRANSFORMS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Register a transform so every subsequent compilation runs it.
+pub fn register_transform<T: AstTransform + 'static>(transform: T) {
+    let static_ref: &'static dyn AstTransform = Box::leak(Box::new(transform));
+    transforms().push(static_ref);
+}
 
-    fn visit_item(&mut self, item: &Item) {
-        match item {
-            Item::Module { items, .. } => {
-                for sub in items { self.visit_item(sub) }
+/// List all registered transforms in registration order.
+pub fn list_transforms() -> Vec<&'static dyn AstTransform> {
+    transforms().clone()
+}
+
+/// Order transforms so that every `run_after` dependency comes first, breaking
+/// ties by priority and then by name. Dependencies on transforms that are not
+/// present are ignored; cycles are an error.
+pub fn order_transforms<'a>(
+    transforms: &[&'a dyn AstTransform],
+) -> Result<Vec<&'a dyn AstTransform>> {
+    let mut pending: Vec<&'a dyn AstTransform> = transforms.to_vec();
+    pending.sort_by(|a, b| {
+        a.priority()
+            .cmp(&b.priority())
+            .then_with(|| a.name().cmp(b.name()))
+    });
+
+    let mut ordered: Vec<&'a dyn AstTransform> = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        // Pick the first (by priority) transform whose dependencies have all run.
+        let ready = pending.iter().position(|t| {
+            t.run_after().iter().all(|dep| {
+                ordered.iter().any(|done| done.name() == *dep)
+                    || !pending.iter().any(|p| p.name() == *dep)
+            })
+        });
+        match ready {
+            Some(idx) => ordered.push(pending.remove(idx)),
+            None => {
+                let names: Vec<&str> = pending.iter().map(|t| t.name()).collect();
+                return Err(TlError::internal(format!(
+                    "cyclic AST transform ordering between: {}",
+                    names.join(", ")
+                )));
             }
-            // recurse into signatures, expressions, etc…
-            _ => {}
         }
     }
+    Ok(ordered)
 }
 
-pub trait ASTTransformer {
-    fn transform_ast(&mut self, ast: AST) -> AST {
-        AST {
-            items: ast.items.into_iter().map(|i| self.transform_item(i)).collect(),
-            ..ast
+/// Run `transforms` over `program` in dependency/priority order.
+///
+/// Transforms without an entry in `configs` run with the default configuration;
+/// disabled transforms are skipped.
+pub fn run_transforms(
+    program: Program,
+    transforms: &[&dyn AstTransform],
+    configs: &HashMap<String, TransformConfig>,
+) -> Result<Program> {
+    let default_config = TransformConfig::default();
+    let mut program = program;
+    for transform in order_transforms(transforms)? {
+        let config = configs.get(transform.name()).unwrap_or(&default_config);
+        if config.enabled {
+            program = transform.transform(program, config)?;
         }
     }
+    Ok(program)
+}
 
-    fn transform_item(&mut self, item: Item) -> Item {
-        match item {
-            Item::Module { name, attrs, items, span } => {
-                let items = items.into_iter().map(|i| self.transform_item(i)).collect();
-                Item::Module { name, attrs, items, span }
-            }
-            // clone‑and‑recurse every variant…
-            other => other,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str, i32, &'static [&'static str]);
+
+    impl AstTransform for Named {
+        fn name(&self) -> &'static str {
+            self.0
         }
+
+        fn priority(&self) -> i32 {
+            self.1
+        }
+
+        fn run_after(&self) -> &[&'static str] {
+            self.2
+        }
+
+        fn transform(&self, program: Program, _config: &TransformConfig) -> Result<Program> {
+            Ok(program)
+        }
+    }
+
+    fn names(order: &[&dyn AstTransform]) -> Vec<&'static str> {
+        order.iter().map(|t| t.name()).collect()
+    }
+
+    #[test]
+    fn orders_by_priority_then_dependencies() {
+        let a = Named("a", 10, &[]);
+        let b = Named("b", 0, &["c"]);
+        let c = Named("c", 5, &[]);
+        let order = order_transforms(&[&a, &b, &c]).unwrap();
+        assert_eq!(names(&order), vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let a = Named("a", 0, &["b"]);
+        let b = Named("b", 0, &["a"]);
+        assert!(order_transforms(&[&a, &b]).is_err());
     }
 }
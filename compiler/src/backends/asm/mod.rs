@@ -1,21 +1,19 @@
 // File: compiler/src/backends/asm/mod.rs
-//! Assembly codegen backend: reads our IR debug‐text and emits
+//! Assembly codegen backend: decodes our IR and emits
 //! an x86-64 assembly listing with the IR instructions preserved as comments.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct AsmBackend;
 
 impl Backend<CompiledModule> for AsmBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = module.decode()?;
 
         // 2. Begin assembly listing
         let mut asm = String::new();
@@ -24,13 +22,13 @@ impl Backend<CompiledModule> for AsmBackend {
         asm.push_str("_start:\n");
 
         // 3. Emit each IR instruction as a comment
-        for line in ir.lines() {
-            let inst = line.trim();
-            if inst.is_empty() || inst == "Nop" {
+        for instr in &instrs {
+            if *instr == Instruction::Nop {
                 continue;
             }
+            let inst = format!("{:?}", instr);
             asm.push_str("    # ");
-            asm.push_str(inst);
+            asm.push_str(&inst);
             asm.push('\n');
         }
 
@@ -39,7 +37,10 @@ impl Backend<CompiledModule> for AsmBackend {
         asm.push_str("    xor rdi, rdi     # status 0\n");
         asm.push_str("    syscall\n");
 
-        Ok(Box::new(asm.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.s", asm)
+            .with_build_command("as -msyntax=intel -mnaked-reg -o main.o main.s")
+            .with_build_command("ld -o main main.o")
+            .with_build_command("./main"))
     }
 
     fn name(&self) -> &'static str {
@@ -55,7 +56,7 @@ static ASM_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_ASM_REG: fn() = {
+pub(crate) static FORCE_ASM_REG: fn() = {
     fn init() {
         Lazy::force(&ASM_REG);
     }
@@ -0,0 +1,222 @@
+// File: compiler/src/backends/c/embedded.rs
+//! Embedded (`--profile embedded`) flavour of the C backend.
+//!
+//! The program allocates nothing: values are locals of `main`, strings
+//! stay in read-only storage, and nothing from the C library that might
+//! allocate is used. All output goes through a
+//! single `tlang_write(data, len)` hook. Hosted targets get a `stdout`
+//! implementation of it; bare-metal targets get a `startup.c` with a weak
+//! stub for the board support package to override, plus a `link.ld`.
+//! Extern functions are declared and globals defined as in the hosted
+//! profile; the firmware links the functions' definitions in.
+
+use super::super::target::TargetInfo;
+use plugin_api::{BackendConfig, BackendError, CompiledArtifact, DebugInfo};
+use shared::tir::Module;
+
+/// Stack reserved by the linker script when `BackendConfig::stack_size` is unset.
+pub const DEFAULT_STACK_SIZE: usize = 4 * 1024;
+
+/// Formatting helpers shared by every embedded program; no libc, no libm.
+const RUNTIME: &str = r#"static size_t tlang_strlen(const char *s) {
+    size_t n = 0;
+    while (s[n]) n++;
+    return n;
+}
+
+static void tlang_print_str(const char *s) { tlang_write(s, tlang_strlen(s)); }
+
+static void tlang_print_int(int64_t v) {
+    char buf[24];
+    size_t n = sizeof buf;
+    uint64_t u = v < 0 ? (uint64_t)0 - (uint64_t)v : (uint64_t)v;
+    do {
+        buf[--n] = (char)('0' + u % 10);
+        u /= 10;
+    } while (u);
+    if (v < 0) buf[--n] = '-';
+    tlang_write(buf + n, sizeof buf - n);
+}
+
+/* Six fractional digits, rounded, like printf("%.6f"); NaN and the
+   infinities are spelled as the hosted backends spell them. */
+static void tlang_print_float(double v) {
+    char buf[64];
+    size_t n = 0;
+    double p = 1.0;
+    if (v != v) {
+        tlang_write("NaN", 3);
+        return;
+    }
+    if (v < 0) {
+        buf[n++] = '-';
+        v = -v;
+    }
+    if (v > 1.7976931348623157e308) {
+        buf[n++] = 'i';
+        buf[n++] = 'n';
+        buf[n++] = 'f';
+        tlang_write(buf, n);
+        return;
+    }
+    v += 0.0000005;
+    while (p * 10.0 <= v) p *= 10.0;
+    for (; p >= 1.0 && n < sizeof buf - 8; p /= 10.0) {
+        int d = (int)(v / p);
+        if (d > 9) d = 9;
+        buf[n++] = (char)('0' + d);
+        v -= d * p;
+    }
+    buf[n++] = '.';
+    for (int i = 0; i < 6; i++) {
+        int d;
+        v *= 10.0;
+        d = (int)v;
+        if (d > 9) d = 9;
+        buf[n++] = (char)('0' + d);
+        v -= d;
+    }
+    tlang_write(buf, n);
+}
+
+"#;
+
+/// Generate the embedded program, plus startup code and a linker script for
+/// bare-metal targets. `declarations` declares the extern functions and
+/// defines the globals.
+pub fn compile(
+    name: &str,
+    tir: &Module,
+    debug: &DebugInfo,
+    declarations: &str,
+    target: Option<&TargetInfo>,
+    options: &super::COptions,
+    config: &BackendConfig,
+) -> Result<CompiledArtifact, BackendError> {
+    let bare_metal = target.is_some_and(|t| t.bare_metal);
+
+    // 1. Header and output hook
+    let mut code = String::from("// Generated by T-Lang C backend (embedded profile)\n");
+    code.push_str(&super::target_header(target));
+    code.push_str("#include <stddef.h>\n#include <stdint.h>\n");
+    if bare_metal {
+        code.push_str("\n/* Provided by startup.c or the board support package. */\n");
+        code.push_str("void tlang_write(const char *data, size_t len);\n\n");
+    } else {
+        code.push_str("#include <stdio.h>\n\n");
+        code.push_str(
+            "static void tlang_write(const char *data, size_t len) { fwrite(data, 1, len, stdout); }\n\n",
+        );
+    }
+    code.push_str(RUNTIME);
+    code.push_str(declarations);
+
+    // 2. Translate the functions, the entry function as `main`
+    code.push_str(&super::functions(tir, debug, options, false)?);
+
+    // 3. Package, with startup code for bare metal
+    let artifact = CompiledArtifact::source(name, "main.c", code);
+    let flags = super::debug_flags(debug);
+    let Some(target) = target.filter(|t| t.bare_metal) else {
+        let artifact = match target {
+            Some(target) => artifact.with_build_command(format!(
+                "{} -std={} -Os{} -o main main.c",
+                super::cross_cc(target),
+                options.std,
+                flags
+            )),
+            None => artifact
+                .with_build_command(format!("cc -std={} -Os{} -o main main.c", options.std, flags))
+                .with_build_command("./main"),
+        };
+        return Ok(artifact);
+    };
+
+    let stack_size = config.stack_size.unwrap_or(DEFAULT_STACK_SIZE);
+    let riscv = target.arch.starts_with("riscv");
+    Ok(artifact
+        .with_file("startup.c", startup_c(target, riscv).into_bytes())
+        .with_file("link.ld", linker_script(target, riscv, stack_size).into_bytes())
+        .with_build_command(format!(
+            "{} -std={} -Os{} -ffreestanding -nostdlib -T link.ld -o main.elf main.c startup.c -lgcc",
+            super::cross_cc(target),
+            options.std,
+            flags
+        )))
+}
+
+/// Reset handler that initializes `.data`/`.bss` and calls `main`.
+fn startup_c(target: &TargetInfo, riscv: bool) -> String {
+    let mut code = format!(
+        "/* Generated by T-Lang: bare-metal startup for {} */\n\
+         #include <stddef.h>\n\
+         #include <stdint.h>\n\n\
+         extern uint32_t _sidata, _sdata, _edata, _sbss, _ebss, _estack;\n\
+         int main(void);\n\n\
+         /* Override to route program output to a UART, semihosting, ... */\n\
+         __attribute__((weak)) void tlang_write(const char *data, size_t len) {{\n\
+         \x20   (void)data;\n\
+         \x20   (void)len;\n\
+         }}\n\n\
+         void Reset_Handler(void) {{\n\
+         \x20   uint32_t *src = &_sidata;\n\
+         \x20   uint32_t *dst = &_sdata;\n\
+         \x20   while (dst < &_edata) *dst++ = *src++;\n\
+         \x20   for (dst = &_sbss; dst < &_ebss;) *dst++ = 0;\n\
+         \x20   main();\n\
+         \x20   for (;;) {{\n\
+         \x20   }}\n\
+         }}\n\n",
+        target.triple
+    );
+    if riscv {
+        code.push_str(
+            "/* Entry point: set up the stack, then reset. */\n\
+             __attribute__((naked, section(\".vectors\"))) void _start(void) {\n\
+             \x20   __asm__ volatile(\"la sp, _estack\\n\\tj Reset_Handler\");\n\
+             }\n",
+        );
+    } else {
+        code.push_str(
+            "/* Cortex-M vector table: initial stack pointer, then reset. */\n\
+             __attribute__((section(\".vectors\"), used))\n\
+             void (*const tlang_vectors[])(void) = {\n\
+             \x20   (void (*)(void))&_estack,\n\
+             \x20   Reset_Handler,\n\
+             };\n",
+        );
+    }
+    code
+}
+
+/// Minimal memory layout; adjust `MEMORY` to the actual part.
+fn linker_script(target: &TargetInfo, riscv: bool, stack_size: usize) -> String {
+    let (flash, ram, entry) = if riscv {
+        ("0x20000000", "0x80000000", "_start")
+    } else {
+        ("0x08000000", "0x20000000", "Reset_Handler")
+    };
+    format!(
+        "/* Generated by T-Lang: memory layout for {triple}; adjust MEMORY to your part. */\n\
+         MEMORY\n\
+         {{\n\
+         \x20 FLASH (rx)  : ORIGIN = {flash}, LENGTH = 256K\n\
+         \x20 RAM   (rwx) : ORIGIN = {ram}, LENGTH = 64K\n\
+         }}\n\n\
+         _stack_size = {stack_size:#x};\n\
+         ENTRY({entry})\n\n\
+         SECTIONS\n\
+         {{\n\
+         \x20 .text : {{ KEEP(*(.vectors)) *(.text*) *(.rodata*) }} > FLASH\n\
+         \x20 _sidata = LOADADDR(.data);\n\
+         \x20 .data : {{ _sdata = .; *(.data*) _edata = .; }} > RAM AT > FLASH\n\
+         \x20 .bss (NOLOAD) : {{ _sbss = .; *(.bss*) *(COMMON) _ebss = .; }} > RAM\n\
+         \x20 .stack (NOLOAD) : {{ . = ALIGN(8); . += _stack_size; _estack = .; }} > RAM\n\
+         }}\n",
+        triple = target.triple,
+        flash = flash,
+        ram = ram,
+        stack_size = stack_size,
+        entry = entry
+    )
+}
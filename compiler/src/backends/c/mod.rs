@@ -1,82 +1,720 @@
 // File: compiler/src/backends/c/mod.rs
 //! C codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone C program
-//! that replays the instructions on two stacks (ints and strings) and prints values.
+//! Lifts the module's TIR (`CompiledModule::tir`) and emits a standalone C
+//! program whose `main` is the entry function: each TIR value becomes a C
+//! local `tlV<n>`, integers as `int64_t`, floats as `double`, booleans as
+//! `_Bool` and strings as `const char *`, with constants written inline.
+//! Each TIR local is a `tlL<n>` declared at the top, and a block control
+//! jumps to is a `tlB<n>:` label, with `goto`s for the jumps and branches.
+//! A `Switch` is a C `switch`, or, with cases dense enough for a jump
+//! table, a `goto` through a table of the labels' addresses.
+//! Every other function of the module is a `static` C function `tlF_<name>`
+//! ahead of `main`, whose parameters are its first locals and which returns
+//! its result local; an `Exit` in one ends the program from there.
+//!
+//! With a target triple in the `BackendConfig`, the program refuses to build
+//! for any other architecture and the build command cross-compiles with the
+//! matching ABI flags. The embedded profile is generated by `embedded`.
+//!
+//! When the module carries `DebugInfo`, each source line starts with a
+//! `#line` directive naming the `.t` file and the build passes `-g`, so a
+//! debugger steps through the original program.
+//!
+//! Backend options: `c_std` picks the C standard the program is built
+//! with (`c99`, the default, `c11`, `c17` or `c23`), and
+//! `emit_line_directives=false` leaves the `#line` directives out, so a
+//! debugger steps through the generated C instead.
+//!
+//! Extern functions get an `extern` prototype ahead of `main`, and a
+//! `CallExtern` converts its arguments to their C types and calls the
+//! function; a string it returns is copied, since the function may reuse
+//! its buffer. Globals follow the prototypes as
+//! `static` variables of their C types, `const` unless they are mutable.
+//! A program calling `tstd`'s functions, whose names start `tlang_`, is
+//! built with `tstd`'s static library, `libtstd.a`, which the build
+//! commands expect beside `main.c`.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+mod embedded;
+
+use super::target::TargetInfo;
+use plugin_api::{
+    register_backend, Backend, BackendCapabilities, BackendConfig, CompiledArtifact,
+    CompiledModule, BackendError, Constant, DebugInfo, ExternFunction, FfiType, Global, Profile,
+};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
+use shared::tir::{ArithOp, BlockId, Function, InstId, InstKind, Module, Terminator};
+use std::collections::HashSet;
+
+/// Comment and architecture check placed at the top of cross-compiled programs.
+fn target_header(target: Option<&TargetInfo>) -> String {
+    let Some(target) = target else {
+        return String::new();
+    };
+    let mut header = format!("// Target: {}\n", target.triple);
+    if let Some(guard) = &target.c_guard {
+        header.push_str(&format!(
+            "#if !({})\n#error \"generated for {}\"\n#endif\n",
+            guard, target.triple
+        ));
+    }
+    header
+}
+
+/// C standards the generated code builds under.
+const C_STANDARDS: &[&str] = &["c99", "c11", "c17", "c23"];
+
+/// What the backend options of a `BackendConfig` ask of the C backend.
+struct COptions {
+    /// Standard passed to the compiler as `-std=`
+    std: String,
+    /// Whether source lines start with `#line` directives
+    line_directives: bool,
+}
+
+impl COptions {
+    fn from_config(config: &BackendConfig) -> Result<Self, BackendError> {
+        let std = config.option("c_std").unwrap_or("c99");
+        if !C_STANDARDS.contains(&std) {
+            return Err(BackendError::Generic(format!(
+                "backend option c_std must be one of {}, not {}",
+                C_STANDARDS.join(", "),
+                std
+            )));
+        }
+        Ok(COptions {
+            std: std.to_string(),
+            line_directives: config.bool_option("emit_line_directives", true)?,
+        })
+    }
+
+    /// `#line` directive for row `row` of the line table, if there is one
+    /// and directives are wanted.
+    fn line_directive(&self, debug: &DebugInfo, row: u32) -> Option<String> {
+        if !self.line_directives {
+            return None;
+        }
+        let file = debug.source_file.as_deref()?;
+        let row = debug.line_info.get(row as usize)?;
+        Some(format!("#line {} \"{}\"\n", row.line, super::escape_str(file)))
+    }
+}
+
+/// Spell an `int64_t` constant; `INT64_C(-9223372036854775808)` negates a
+/// literal that does not fit.
+fn int_literal(n: i64) -> String {
+    if n == i64::MIN {
+        "INT64_MIN".to_string()
+    } else {
+        format!("INT64_C({})", n)
+    }
+}
+
+/// Extra compiler flags for a module: `-g` when there is debug info to emit.
+fn debug_flags(debug: &DebugInfo) -> &'static str {
+    if debug.is_empty() {
+        ""
+    } else {
+        " -g"
+    }
+}
+
+/// What the build links after `main.c`: `tstd`'s static library, and the
+/// system libraries it needs, if `externs` has any of its functions.
+fn runtime_flags(externs: &[ExternFunction]) -> &'static str {
+    if externs.iter().any(|function| function.name.starts_with("tlang_")) {
+        " libtstd.a -lpthread -ldl -lm"
+    } else {
+        ""
+    }
+}
+
+/// The C type values of `ty` are passed as.
+fn c_type(ty: FfiType) -> Result<&'static str, BackendError> {
+    Ok(match ty {
+        FfiType::Int { bits: 8, signed: true } => "int8_t",
+        FfiType::Int { bits: 16, signed: true } => "int16_t",
+        FfiType::Int { bits: 32, signed: true } => "int32_t",
+        FfiType::Int { bits: 64, signed: true } => "int64_t",
+        FfiType::Int { bits: 8, signed: false } => "uint8_t",
+        FfiType::Int { bits: 16, signed: false } => "uint16_t",
+        FfiType::Int { bits: 32, signed: false } => "uint32_t",
+        FfiType::Int { bits: 64, signed: false } => "uint64_t",
+        FfiType::Float { bits: 32 } => "float",
+        FfiType::Float { bits: 64 } => "double",
+        FfiType::Bool => "_Bool",
+        FfiType::Str => "const char *",
+        other => return Err(BackendError::InvalidIr(format!("no C type for {:?}", other))),
+    })
+}
+
+/// `ty name`, without a space after a `*`.
+fn declarator(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+/// `extern` prototypes of `externs`, one per line.
+fn prototypes(externs: &[ExternFunction]) -> Result<String, BackendError> {
+    let mut code = String::new();
+    for function in externs {
+        let mut params = function
+            .params
+            .iter()
+            .map(|&ty| c_type(ty).map(str::to_string))
+            .collect::<Result<Vec<_>, _>>()?;
+        if function.variadic {
+            params.push("...".to_string());
+        } else if params.is_empty() {
+            params.push("void".to_string());
+        }
+        // Strings come back as `char *`, as from `getenv` or `strerror`.
+        let ret = match function.return_type {
+            Some(FfiType::Str) => "char *",
+            Some(ty) => c_type(ty)?,
+            None => "void",
+        };
+        let name = format!("{}({})", function.name, params.join(", "));
+        code.push_str(&format!("extern {};\n", declarator(ret, &name)));
+    }
+    if !code.is_empty() {
+        code.push('\n');
+    }
+    Ok(code)
+}
+
+/// `static` definitions of `globals`, one per line.
+fn definitions(globals: &[Global]) -> Result<String, BackendError> {
+    let mut code = String::new();
+    for global in globals {
+        let ty = match (global.ty, global.mutable) {
+            (FfiType::Str, true) => "const char *".to_string(),
+            (FfiType::Str, false) => "const char *const".to_string(),
+            (ty, true) => c_type(ty)?.to_string(),
+            (ty, false) => format!("const {}", c_type(ty)?),
+        };
+        let value = constant(&global.value)?;
+        code.push_str(&format!("static {} = {};\n", declarator(&ty, &global.name), value));
+    }
+    if !code.is_empty() {
+        code.push('\n');
+    }
+    Ok(code)
+}
+
+/// `value` as a C expression.
+fn constant(value: &Constant) -> Result<String, BackendError> {
+    Ok(match value {
+        Constant::Int(n) => int_literal(*n),
+        Constant::Float(f) => super::float_literal(*f)?,
+        Constant::Bool(b) => (*b as i32).to_string(),
+        Constant::Str(s) => format!("\"{}\"", super::escape_str(s)),
+    })
+}
+
+/// The C type a value of `ty` is held in.
+fn value_type(ty: FfiType) -> &'static str {
+    match ty {
+        FfiType::Int { .. } => "int64_t",
+        FfiType::Float { .. } => "double",
+        FfiType::Bool => "_Bool",
+        FfiType::Str => "const char *",
+    }
+}
+
+/// The signature of the C function the module's function `function` is.
+fn signature(function: &Function) -> String {
+    let params: Vec<String> = function
+        .param_types()
+        .iter()
+        .enumerate()
+        .map(|(i, &ty)| declarator(value_type(ty), &format!("tlL{}", i)))
+        .collect();
+    let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+    let name = format!("tlF_{}({})", function.name, params);
+    format!("static {}", declarator(function.result_type().map_or("void", value_type), &name))
+}
+
+/// The C functions of `tir`: one for each function but the entry, declared
+/// first so that they can call each other, then `main` for the entry.
+/// Embedded programs have no `exit`, so a function other than `main` ends
+/// the program by jumping back to `main` through `tlang_exit`.
+fn functions(tir: &Module, debug: &DebugInfo, options: &COptions, hosted: bool) -> Result<String, BackendError> {
+    let (entry, callees) = tir
+        .functions
+        .split_first()
+        .ok_or_else(|| BackendError::InvalidIr("the module has no functions".to_string()))?;
+    let mut code = String::new();
+    for function in callees {
+        code.push_str(&format!("{};\n", signature(function)));
+    }
+    if !callees.is_empty() {
+        if !hosted {
+            code.push_str("static void *tlang_exit[5];\nstatic int tlang_exit_code;\n");
+        }
+        code.push('\n');
+    }
+    for function in callees {
+        code.push_str(&format!("{} {{\n", signature(function)));
+        code.push_str(&function_body(function, debug, options, hosted, false)?);
+        match function.result {
+            Some(result) => code.push_str(&format!("    return tlL{};\n}}\n\n", result.0)),
+            None => code.push_str("}\n\n"),
+        }
+    }
+    code.push_str("int main(void) {\n");
+    if !hosted && !callees.is_empty() {
+        code.push_str("    if (__builtin_setjmp(tlang_exit)) return tlang_exit_code;\n");
+    }
+    code.push_str(&function_body(entry, debug, options, hosted, true)?);
+    code.push_str("    return 0;\n}\n");
+    Ok(code)
+}
+
+/// The C statements of the function `function`, which is `main` if it is
+/// the `entry`. Hosted programs print with `printf` and copy strings with
+/// `strdup`; embedded ones print through `tlang_print_*` and allocate
+/// nothing.
+fn function_body(
+    function: &Function,
+    debug: &DebugInfo,
+    options: &COptions,
+    hosted: bool,
+    entry: bool,
+) -> Result<String, BackendError> {
+    let body = Body { function, used: function.used_values(), hosted, entry };
+    let live = function.live_insts();
+    let mut code = String::new();
+    for (i, &ty) in function.locals.iter().enumerate().skip(function.params) {
+        let zero = match ty {
+            FfiType::Float { .. } => "0.0",
+            FfiType::Str => "\"\"",
+            FfiType::Int { .. } | FfiType::Bool => "0",
+        };
+        code.push_str(&format!("    {} = {};\n", declarator(value_type(ty), &format!("tlL{}", i)), zero));
+    }
+
+    // Terminators first, to know which blocks need a label.
+    let mut targets = HashSet::new();
+    let terminators = function
+        .iter_blocks()
+        .map(|(id, block)| body.terminator(id, &block.terminator, &mut targets))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The `#line` of a row goes before the first statement it emits.
+    let mut row = None;
+    let statement = |code: &mut String, row: &mut Option<u32>, text: String| {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(directive) = row.take().and_then(|row| options.line_directive(debug, row)) {
+            code.push_str(&directive);
+        }
+        code.push_str(&text);
+    };
+    for ((id, block), terminator) in function.iter_blocks().zip(terminators) {
+        if targets.contains(&id) {
+            code.push_str(&format!("tlB{}:;\n", id.0));
+        }
+        for &inst in &block.insts {
+            match &function.inst(inst).kind {
+                InstKind::Marker(n) => {
+                    row.get_or_insert(*n);
+                }
+                _ if live.contains(&inst) => statement(&mut code, &mut row, body.inst(inst)?),
+                _ => {}
+            }
+        }
+        statement(&mut code, &mut row, terminator);
+    }
+    Ok(code)
+}
+
+/// Translates the instructions of one function.
+struct Body<'f> {
+    function: &'f Function,
+    /// Values something reads
+    used: HashSet<InstId>,
+    hosted: bool,
+    /// Whether the function is `main`
+    entry: bool,
+}
+
+impl Body<'_> {
+    fn ty(&self, value: InstId) -> Result<FfiType, BackendError> {
+        self.function
+            .ty(value)
+            .ok_or_else(|| BackendError::InvalidIr(format!("{:?} has no value to read", value)))
+    }
+
+    /// `value` as a C expression: its literal for a constant, else its local.
+    fn operand(&self, value: InstId) -> Result<String, BackendError> {
+        match &self.function.inst(value).kind {
+            InstKind::Const(value) => constant(value),
+            _ => Ok(format!("tlV{}", value.0)),
+        }
+    }
+
+    /// The statement ending the program with exit code `code`.
+    fn exit(&self, code: &str) -> String {
+        match (self.entry, self.hosted) {
+            (true, _) => format!("return {};", code),
+            (false, true) => format!("exit({});", code),
+            (false, false) => format!("{{ tlang_exit_code = {}; __builtin_longjmp(tlang_exit, 1); }}", code),
+        }
+    }
+
+    /// `tlV<n> = expr;`, declaring it with its type.
+    fn define(&self, inst: InstId, expr: &str) -> Result<String, BackendError> {
+        let name = format!("tlV{}", inst.0);
+        Ok(format!("    {} = {};\n", declarator(value_type(self.ty(inst)?), &name), expr))
+    }
+
+    /// The statements of the instruction `inst`.
+    fn inst(&self, inst: InstId) -> Result<String, BackendError> {
+        Ok(match &self.function.inst(inst).kind {
+            InstKind::Const(_) | InstKind::Marker(_) => String::new(),
+            InstKind::Print(value) => {
+                let v = self.operand(*value)?;
+                match (self.ty(*value)?, self.hosted) {
+                    (FfiType::Int { .. }, true) => format!("    printf(\"%\" PRId64, {});\n", v),
+                    (FfiType::Float { .. }, true) => format!("    tlang_print_float({});\n", v),
+                    (FfiType::Bool, true) => format!("    printf(\"%s\", {} ? \"true\" : \"false\");\n", v),
+                    (FfiType::Str, true) => format!("    printf(\"%s\", {});\n", v),
+                    (FfiType::Int { .. }, false) => format!("    tlang_print_int({});\n", v),
+                    (FfiType::Float { .. }, false) => format!("    tlang_print_float({});\n", v),
+                    (FfiType::Bool, false) => format!("    tlang_print_str({} ? \"true\" : \"false\");\n", v),
+                    (FfiType::Str, false) => format!("    tlang_print_str({});\n", v),
+                }
+            }
+            InstKind::Cast { value, to } => {
+                let v = self.operand(*value)?;
+                let expr = match (self.ty(*value)?, *to) {
+                    (FfiType::Int { .. }, FfiType::Float { .. }) => format!("(double){}", v),
+                    (FfiType::Float { .. }, FfiType::Int { bits, signed }) => {
+                        let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                        format!(
+                            "{v} != {v} ? 0 : {v} <= {} ? {} : {v} >= {} ? {} : (int64_t){v}",
+                            min_f,
+                            int_literal(min),
+                            max_f,
+                            int_literal(max),
+                            v = v
+                        )
+                    }
+                    (FfiType::Int { .. }, FfiType::Int { bits, signed }) => match super::wrap_masks(bits, signed) {
+                        None => v,
+                        Some((mask, 0)) => format!("{} & {}", v, int_literal(mask)),
+                        Some((mask, sign)) => {
+                            format!("(({} & {}) ^ {}) - {}", v, int_literal(mask), int_literal(sign), int_literal(sign))
+                        }
+                    },
+                    (FfiType::Float { .. }, FfiType::Float { .. }) => v,
+                    (from, to) => return Err(BackendError::InvalidIr(format!("cannot cast {:?} to {:?}", from, to))),
+                };
+                self.define(inst, &expr)?
+            }
+            InstKind::CallExtern { name, args, types } => {
+                let args = args
+                    .iter()
+                    .zip(types)
+                    .map(|(&arg, &ty)| {
+                        let v = self.operand(arg)?;
+                        Ok(match ty {
+                            FfiType::Int { .. } | FfiType::Float { .. } => format!("({}){}", c_type(ty)?, v),
+                            FfiType::Bool | FfiType::Str => v,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, BackendError>>()?;
+                let call = format!("{}({})", name, args.join(", "));
+                let result = self.function.ty(inst).filter(|_| self.used.contains(&inst));
+                match result {
+                    None => format!("    (void){};\n", call),
+                    Some(FfiType::Int { .. }) => self.define(inst, &format!("(int64_t){}", call))?,
+                    Some(FfiType::Float { .. }) => self.define(inst, &format!("(double){}", call))?,
+                    Some(FfiType::Bool) => self.define(inst, &call)?,
+                    Some(FfiType::Str) => {
+                        let v = format!("tlV{}", inst.0);
+                        let copy = if self.hosted { format!("strdup({})", v) } else { v.clone() };
+                        format!("{}    {v} = {v} ? {} : \"\";\n", self.define(inst, &call)?, copy, v = v)
+                    }
+                }
+            }
+            InstKind::Call { function, args } => {
+                let args = args.iter().map(|&arg| self.operand(arg)).collect::<Result<Vec<_>, _>>()?;
+                let call = format!("tlF_{}({})", function, args.join(", "));
+                match self.function.ty(inst).filter(|_| self.used.contains(&inst)) {
+                    None => format!("    (void){};\n", call),
+                    Some(_) => self.define(inst, &call)?,
+                }
+            }
+            InstKind::Load(local) => self.define(inst, &format!("tlL{}", local.0))?,
+            InstKind::Store { local, value } => format!("    tlL{} = {};\n", local.0, self.operand(*value)?),
+            InstKind::Arith { op, lhs, rhs } => {
+                let (a, b) = (self.operand(*lhs)?, self.operand(*rhs)?);
+                let expr = match (self.ty(*lhs)?, op) {
+                    // `INT64_MIN / -1` is undefined too; it wraps to `INT64_MIN`.
+                    (FfiType::Int { .. }, ArithOp::Div) => {
+                        format!("{b} == -1 ? (int64_t)(0 - (uint64_t){a}) : {a} / {b}", a = a, b = b)
+                    }
+                    (FfiType::Int { .. }, ArithOp::Rem) => format!("{b} == -1 ? 0 : {a} % {b}", a = a, b = b),
+                    // Signed overflow is undefined in C; unsigned wraps.
+                    (FfiType::Int { .. }, _) => format!("(int64_t)((uint64_t){} {} (uint64_t){})", a, op.symbol(), b),
+                    (_, ArithOp::Rem) => return Err(BackendError::InvalidIr("remainder of floats".to_string())),
+                    _ => format!("{} {} {}", a, op.symbol(), b),
+                };
+                self.define(inst, &expr)?
+            }
+            InstKind::Compare { op, lhs, rhs } => {
+                let expr = format!("{} {} {}", self.operand(*lhs)?, op.symbol(), self.operand(*rhs)?);
+                self.define(inst, &expr)?
+            }
+            InstKind::Not(value) => self.define(inst, &format!("!{}", self.operand(*value)?))?,
+            InstKind::Overflows { op, lhs, rhs, ty } => {
+                let (a, b) = (self.operand(*lhs)?, self.operand(*rhs)?);
+                let int = c_type(*ty)?;
+                let builtin = match op {
+                    ArithOp::Add => "add",
+                    ArithOp::Sub => "sub",
+                    ArithOp::Mul => "mul",
+                    // Only the smallest signed value divided by -1 is out of range.
+                    ArithOp::Div => {
+                        let FfiType::Int { bits, signed: true } = *ty else {
+                            return self.define(inst, "0");
+                        };
+                        let min = plugin_api::int_bounds(bits, true).0;
+                        return self.define(inst, &format!("{} == {} && {} == -1", a, int_literal(min), b));
+                    }
+                    ArithOp::Rem => return self.define(inst, "0"),
+                };
+                let result = format!("tlO{}", inst.0);
+                let check = format!("__builtin_{}_overflow(({int}){}, ({int}){}, &{})", builtin, a, b, result, int = int);
+                format!("    {} {};\n{}", int, result, self.define(inst, &check)?)
+            }
+            InstKind::Trap { condition, message, backtrace, abort } => {
+                let condition = self.operand(*condition)?;
+                let (message, backtrace) = (super::escape_str(message), super::escape_str(backtrace));
+                if self.hosted {
+                    format!("    if ({}) tlang_trap(\"{}\", \"{}\", {});\n", condition, message, backtrace, *abort as i32)
+                } else {
+                    // No stderr to write to, and no `exit`.
+                    let code = if *abort { plugin_api::ABORT_EXIT_CODE } else { plugin_api::PANIC_EXIT_CODE };
+                    let exit = self.exit(&code.to_string());
+                    format!("    if ({}) {{ tlang_print_str(\"{}\\n\"); {} }}\n", condition, message, exit)
+                }
+            }
+            // Lifted TIR has the calls an `Alloca` is emitted as.
+            InstKind::Alloca(_) => return Err(BackendError::InvalidIr("an `Alloca` left unexpanded".to_string())),
+        })
+    }
+
+    /// The statements ending block `id`, adding the blocks they jump to to
+    /// `targets`. Falling through to the next block needs none.
+    fn terminator(
+        &self,
+        id: BlockId,
+        terminator: &Terminator,
+        targets: &mut HashSet<BlockId>,
+    ) -> Result<String, BackendError> {
+        let next = BlockId(id.0 + 1);
+        let mut label = |target: BlockId| {
+            targets.insert(target);
+            format!("tlB{}", target.0)
+        };
+        Ok(match *terminator {
+            Terminator::Switch { value, ref cases, default } => {
+                let value = self.operand(value)?;
+                let mut code = match super::jump_table(cases, default) {
+                    // A table of label addresses, GNU C as the overflow
+                    // builtins are, indexed by the offset from the least case.
+                    Some((least, table)) => {
+                        let labels: Vec<String> =
+                            table.into_iter().map(|target| format!("&&{}", label(target))).collect();
+                        let offset = format!("((uint64_t){} - (uint64_t){})", value, int_literal(least));
+                        format!(
+                            "    {{\n        static void *const tlJ{}[] = {{{}}};\n        \
+                             if ({} < {}) goto *tlJ{}[{}];\n    }}\n",
+                            id.0,
+                            labels.join(", "),
+                            offset,
+                            labels.len(),
+                            id.0,
+                            offset
+                        )
+                    }
+                    None => {
+                        let mut code = format!("    switch ({}) {{\n", value);
+                        for &(n, target) in cases {
+                            code.push_str(&format!("    case {}: goto {};\n", int_literal(n), label(target)));
+                        }
+                        code + "    }\n"
+                    }
+                };
+                if default != next {
+                    code.push_str(&format!("    goto {};\n", label(default)));
+                }
+                code
+            }
+            Terminator::Return if next.index() == self.function.blocks.len() => String::new(),
+            Terminator::Return => match (self.entry, self.function.result) {
+                (true, _) => "    return 0;\n".to_string(),
+                (false, Some(result)) => format!("    return tlL{};\n", result.0),
+                (false, None) => "    return;\n".to_string(),
+            },
+            Terminator::Jump(target) if target == next => String::new(),
+            Terminator::Jump(target) => format!("    goto {};\n", label(target)),
+            Terminator::Branch { condition, then, otherwise } => {
+                let condition = self.operand(condition)?;
+                let mut code = format!("    if ({}) goto {};\n", condition, label(then));
+                if otherwise != next {
+                    code.push_str(&format!("    goto {};\n", label(otherwise)));
+                }
+                code
+            }
+            Terminator::Exit(code) => format!("    {}\n", self.exit(&format!("(int){}", self.operand(code)?))),
+            Terminator::Unreachable => String::new(),
+        })
+    }
+}
+
+/// What a `Trap` whose condition holds calls in a hosted program: report
+/// the panic as `tlang_panic` does and exit with 101, or abort.
+const TRAP: &str = r#"static void tlang_trap(const char *message, const char *backtrace, int aborts) {
+    const char *show = getenv("TLANG_BACKTRACE");
+    if (!aborts) fflush(stdout);
+    fprintf(stderr, "%s\n", message);
+    if (show && strcmp(show, "0") != 0) {
+        fprintf(stderr, "stack backtrace:\n%s\n", backtrace);
+    } else {
+        fputs("note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace\n", stderr);
+    }
+    if (aborts) abort();
+    exit(101);
+}
+
+"#;
+
+/// What a hosted program prints a float with: `%.6f`, with NaN and the
+/// infinities spelled as every backend spells them, where C writes `nan`
+/// or `-nan`.
+const PRINT_FLOAT: &str = r#"static void tlang_print_float(double v) {
+    if (v != v) fputs("NaN", stdout);
+    else if (v > 1.7976931348623157e308) fputs("inf", stdout);
+    else if (v < -1.7976931348623157e308) fputs("-inf", stdout);
+    else printf("%.6f", v);
+}
+
+"#;
+
+/// Compiler invocation (without sources) that cross-compiles for `target`.
+fn cross_cc(target: &TargetInfo) -> String {
+    let mut cmd = format!("clang --target={}", target.triple);
+    for flag in &target.abi_flags {
+        cmd.push(' ');
+        cmd.push_str(flag);
+    }
+    cmd
+}
 
 #[derive(Debug)]
 pub struct CBackend;
 
 impl Backend<CompiledModule> for CBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        self.compile_with_config(module, &BackendConfig::default())
+    }
+
+    fn compile_with_config(
+        &self,
+        module: CompiledModule,
+        config: &BackendConfig,
+    ) -> Result<Self::ModuleIr, BackendError> {
+        // 1. Lift the IR to TIR and resolve the target and options
+        let tir = module.tir()?;
+        let target = super::target::target_info(config)?;
+        let options = COptions::from_config(config)?;
+        let debug = &module.debug_info;
+        let insts = || tir.functions.iter().flat_map(|function| &function.insts);
+        for inst in insts() {
+            if let InstKind::CallExtern { name, .. } = &inst.kind {
+                module.extern_function(name)?;
+            }
+        }
+        let declarations = prototypes(&module.externs)? + &definitions(&module.globals)?;
+        if config.profile == Profile::Embedded {
+            let target = target.as_ref();
+            return embedded::compile(self.name(), &tir, debug, &declarations, target, &options, config);
+        }
 
         // 2. Begin C source
         let mut code = String::new();
-        code.push_str(r#"// Generated by T-Lang C backend
+        code.push_str("// Generated by T-Lang C backend\n");
+        code.push_str(&target_header(target.as_ref()));
+        // `strdup` is POSIX, not ISO C.
+        code.push_str(r#"#define _POSIX_C_SOURCE 200809L
+#include <inttypes.h>
+#include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
 
-int main(void) {
-    long intStack[1024];
-    char *strStack[1024];
-    int intTop = 0, strTop = 0;
 "#);
-
-        // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    intStack[intTop++] = {};\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!(
-                    "    strStack[strTop++] = strdup(\"{}\");\n",
-                    esc
-                ));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    r#"    if (strTop > 0) {
-        char *s = strStack[--strTop];
-        printf("%s", s);
-        free(s);
-    } else if (intTop > 0) {
-        long v = intStack[--intTop];
-        printf("%ld", v);
-    }
-"#,
-                );
-            } else {
-                return Err(BackendError::Generic(format!(
-                    "Unknown IR instr: {}",
-                    instr
-                )));
-            }
+        code.push_str(&declarations);
+        if insts().any(|inst| matches!(inst.kind, InstKind::Trap { .. })) {
+            code.push_str(TRAP);
+        }
+        if tir.functions.iter().any(|function| {
+            function.insts.iter().any(|inst| match inst.kind {
+                InstKind::Print(value) => matches!(function.ty(value), Some(FfiType::Float { .. })),
+                _ => false,
+            })
+        }) {
+            code.push_str(PRINT_FLOAT);
         }
 
-        // 4. Close main
-        code.push_str("    return 0;\n}\n");
+        // 3. Translate the functions, the entry function as `main`
+        code.push_str(&functions(&tir, debug, &options, true)?);
+
+        let artifact = CompiledArtifact::source(self.name(), "main.c", code);
+        let runtime = runtime_flags(&module.externs);
+        Ok(match target {
+            Some(target) => artifact.with_build_command(format!(
+                "{} -std={}{} -o main main.c{}",
+                cross_cc(&target),
+                options.std,
+                debug_flags(debug),
+                runtime
+            )),
+            None => artifact
+                .with_build_command(format!(
+                    "cc -std={}{} -o main main.c{}",
+                    options.std,
+                    debug_flags(debug),
+                    runtime
+                ))
+                .with_build_command("./main"),
+        })
+    }
 
-        Ok(Box::new(code.into_bytes()))
+    fn initialize(&self, config: &BackendConfig) -> Result<(), BackendError> {
+        COptions::from_config(config).map(|_| ())
     }
 
     fn name(&self) -> &'static str {
         "c"
     }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            supports_cross_compilation: true,
+            supports_debug_info: true,
+            ..BackendCapabilities::default()
+        }
+    }
 }
 
 // Register this backend at startup
@@ -87,7 +725,7 @@ static C_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_C_REG: fn() = {
+pub(crate) static FORCE_C_REG: fn() = {
     fn init() {
         Lazy::force(&C_REG);
     }
@@ -1,22 +1,20 @@
 // File: compiler/src/backends/clojure/mod.rs
-//! Clojure codegen backend: reads our IR debug‐text and emits
+//! Clojure codegen backend: decodes our IR and emits
 //! a standalone Clojure script that replays the instructions
 //! on a simple stack.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct ClojureBackend;
 
 impl Backend<CompiledModule> for ClojureBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Clojure script
         let mut code = String::new();
@@ -26,37 +24,89 @@ impl Backend<CompiledModule> for ClojureBackend {
         code.push('\n');
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    (swap! stack conj {})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("    (swap! stack conj \"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "    (let [s (peek @stack)]\n\
-                     \t(swap! stack pop)\n\
-                     \t(print s))\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!(
-                    "Unknown IR instr: {}",
-                    instr
-                )));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    (swap! stack conj {})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    (swap! stack conj {})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    (swap! stack conj {})\n", b));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("    (swap! stack conj \"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    (let [s (peek @stack)]\n\
+                         \t(swap! stack pop)\n\
+                         \t(print s))\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    (print (peek @stack))\n    (swap! stack pop)\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    (print (String/format java.util.Locale/ROOT \"%.6f\" (object-array [(peek @stack)])))\n    (swap! stack pop)\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    (print (peek @stack))\n    (swap! stack pop)\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    (print (peek @stack))\n    (swap! stack pop)\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    (swap! stack #(conj (pop %) (double (peek %))))\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "    (swap! stack #(conj (pop %) (let [f (peek %)] (cond (Double/isNaN f) 0 (<= f {}) {} (>= f {}) {} :else (long f)))))\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    (swap! stack #(conj (pop %) (- (bit-xor (bit-and (peek %) {}) {}) {})))\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("    (flush)\n    (System/exit (peek @stack))\n");
+                }
             }
         }
 
         // 4. End and invoke
         code.push_str("  )\n)\n\n(-main)\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.clj", code)
+            .with_build_command("clojure -M main.clj"))
     }
 
     fn name(&self) -> &'static str {
@@ -72,7 +122,7 @@ static CLOJURE_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_CLOJURE_REG: fn() = {
+pub(crate) static FORCE_CLOJURE_REG: fn() = {
     fn init() {
         Lazy::force(&CLOJURE_REG);
     }
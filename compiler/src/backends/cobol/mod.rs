@@ -1,22 +1,20 @@
 // File: compiler/src/backends/cobol/mod.rs
 //! COBOL codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone COBOL program
-//! that replays the instructions on two simple stacks and prints values.
+//! Decodes our IR and emits a standalone COBOL program
+//! that replays the instructions on three simple stacks (ints, floats, strings) and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct CobolBackend;
 
 impl Backend<CompiledModule> for CobolBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // Begin COBOL program
         let mut code = String::new();
@@ -26,7 +24,12 @@ DATA DIVISION.
 WORKING-STORAGE SECTION.
 01 INT-TOP    PIC 9(4) VALUE ZERO.
 01 INT-STACK.
-   05 INT-ELEM OCCURS 100 TIMES PIC 9(18).
+   05 INT-ELEM OCCURS 100 TIMES PIC S9(18).
+01 INT-OUT    PIC -(18)9.
+01 FLT-TOP    PIC 9(4) VALUE ZERO.
+01 FLT-STACK.
+   05 FLT-ELEM OCCURS 100 TIMES PIC S9(12)V9(6).
+01 FLT-OUT    PIC -(12)9.9(6).
 01 STR-TOP    PIC 9(4) VALUE ZERO.
 01 STR-STACK.
    05 STR-ELEM OCCURS 100 TIMES PIC X(256).
@@ -35,32 +38,43 @@ BEGIN.
 "#);
 
         // Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                // increment INT-TOP and store
-                code.push_str(&format!(
-                    "    ADD 1 TO INT-TOP\n    MOVE {} TO INT-ELEM (INT-TOP)\n",
-                    n
-                ));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\'', "''");
-                code.push_str(
-                    "    ADD 1 TO STR-TOP\n"
-                );
-                code.push_str(&format!(
-                    "    MOVE '{0}' TO STR-ELEM (STR-TOP)\n",
-                    esc
-                ));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    r#"    IF STR-TOP > 0
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    // increment INT-TOP and store
+                    code.push_str(&format!(
+                        "    ADD 1 TO INT-TOP\n    MOVE {} TO INT-ELEM (INT-TOP)\n",
+                        n
+                    ));
+                }
+                Instruction::PushFloat(f) => {
+                    // COBOL has no exponent literals, so spell the value in fixed point.
+                    let v = super::finite_float(f)?;
+                    code.push_str(&format!(
+                        "    ADD 1 TO FLT-TOP\n    MOVE {} TO FLT-ELEM (FLT-TOP)\n",
+                        plugin_api::format_float(v)
+                    ));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!(
+                        "    ADD 1 TO INT-TOP\n    MOVE {} TO INT-ELEM (INT-TOP)\n",
+                        b as i32
+                    ));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = s.replace('\'', "''");
+                    code.push_str(
+                        "    ADD 1 TO STR-TOP\n"
+                    );
+                    code.push_str(&format!(
+                        "    MOVE '{0}' TO STR-ELEM (STR-TOP)\n",
+                        esc
+                    ));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        r#"    IF STR-TOP > 0
         DISPLAY STR-ELEM (STR-TOP) NO ADVANCING
         SUBTRACT 1 FROM STR-TOP
     ELSE
@@ -70,16 +84,110 @@ BEGIN.
         END-IF
     END-IF
 "#,
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str(
+                        r#"    MOVE INT-ELEM (INT-TOP) TO INT-OUT
+    DISPLAY FUNCTION TRIM(INT-OUT) NO ADVANCING
+    SUBTRACT 1 FROM INT-TOP
+"#,
+                    );
+                }
+                Instruction::PrintFloat => {
+                    code.push_str(
+                        r#"    MOVE FLT-ELEM (FLT-TOP) TO FLT-OUT
+    DISPLAY FUNCTION TRIM(FLT-OUT) NO ADVANCING
+    SUBTRACT 1 FROM FLT-TOP
+"#,
+                    );
+                }
+                Instruction::PrintStr => {
+                    code.push_str(
+                        r#"    DISPLAY FUNCTION TRIM(STR-ELEM (STR-TOP) TRAILING) NO ADVANCING
+    SUBTRACT 1 FROM STR-TOP
+"#,
+                    );
+                }
+                Instruction::PrintBool => {
+                    code.push_str(
+                        r#"    IF INT-ELEM (INT-TOP) NOT = 0
+        DISPLAY "true" NO ADVANCING
+    ELSE
+        DISPLAY "false" NO ADVANCING
+    END-IF
+    SUBTRACT 1 FROM INT-TOP
+"#,
+                    );
+                }
+                Instruction::IntToFloat => {
+                    code.push_str(
+                        r#"    ADD 1 TO FLT-TOP
+    MOVE INT-ELEM (INT-TOP) TO FLT-ELEM (FLT-TOP)
+    SUBTRACT 1 FROM INT-TOP
+"#,
+                    );
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // MOVE drops the fraction; bounds beyond the float stack's
+                    // twelve integer digits cannot be reached.
+                    let (min, max) = plugin_api::int_bounds(bits, signed);
+                    code.push_str("    ADD 1 TO INT-TOP\n    EVALUATE TRUE\n");
+                    if max < 1_000_000_000_000 {
+                        code.push_str(&format!(
+                            "        WHEN FLT-ELEM (FLT-TOP) <= {0}\n            MOVE {0} TO INT-ELEM (INT-TOP)\n\
+                             \x20       WHEN FLT-ELEM (FLT-TOP) >= {1}\n            MOVE {1} TO INT-ELEM (INT-TOP)\n",
+                            min, max
+                        ));
+                    }
+                    code.push_str(
+                        r#"        WHEN OTHER
+            MOVE FLT-ELEM (FLT-TOP) TO INT-ELEM (INT-TOP)
+    END-EVALUATE
+    SUBTRACT 1 FROM FLT-TOP
+"#,
+                    );
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    // No bitwise operators: wrap with a modulus instead.
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    COMPUTE INT-ELEM (INT-TOP) =\n        FUNCTION MOD(INT-ELEM (INT-TOP) + {0}, {1}) - {0}\n",
+                            sign,
+                            mask as i128 + 1
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("    MOVE INT-ELEM (INT-TOP) TO RETURN-CODE\n    STOP RUN\n");
+                }
             }
         }
 
         // End program
         code.push_str("    STOP RUN.\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "tlang.cob", code)
+            .with_build_command("cobc -x -o tlang tlang.cob")
+            .with_build_command("./tlang"))
     }
 
     fn name(&self) -> &'static str {
@@ -95,7 +203,7 @@ static COBOL_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_COBOL_REG: fn() = {
+pub(crate) static FORCE_COBOL_REG: fn() = {
     fn init() {
         Lazy::force(&COBOL_REG);
     }
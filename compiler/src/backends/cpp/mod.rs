@@ -0,0 +1,422 @@
+// File: compiler/src/backends/cpp/mod.rs
+//! C++17 codegen backend for T-Lang.
+//! Decodes our IR and emits a standalone C++17 program that replays the
+//! instructions on `std::vector` stacks (ints, floats, bools and
+//! `std::string`s) and prints values.
+//!
+//! Locals are C++ variables `local0`, `local1`, ... declared at the top of
+//! their function, and each `Label` is a `tlL<n>:` label that jumps `goto`.
+//! Every function of the module but the entry is a `static void tlF_<name>()`
+//! on the same stacks, which pops its arguments into its first locals and
+//! leaves its result on them.
+//!
+//! Unlike the C backend nothing is freed by hand: every value is owned by a
+//! standard container, strings by `std::string`s. A struct reaches the
+//! backend as the locals of its fields, as lowering leaves it.
+
+use plugin_api::{
+    register_backend, ArithOp, Backend, CompiledArtifact, CompiledModule, BackendError, FfiType, Instruction,
+};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// Support code emitted at the top of every program.
+const PRELUDE: &str = r#"// Generated by T-Lang C++ backend
+#include <array>
+#include <cmath>
+#include <cstdint>
+#include <cstdio>
+#include <cstdlib>
+#include <functional>
+#include <iomanip>
+#include <iostream>
+#include <limits>
+#include <string>
+#include <tuple>
+#include <utility>
+#include <vector>
+
+namespace tlang {
+
+template <typename T>
+T pop(std::vector<T>& stack) {
+    T value = std::move(stack.back());
+    stack.pop_back();
+    return value;
+}
+
+// Six fractional digits, with NaN and the infinities spelled as every
+// backend spells them, where the stream writes `nan` or `-nan`.
+inline void print_float(double v) {
+    if (std::isnan(v)) {
+        std::cout << "NaN";
+    } else if (std::isinf(v)) {
+        std::cout << (v < 0 ? "-inf" : "inf");
+    } else {
+        std::cout << v;
+    }
+}
+
+} // namespace tlang
+
+"#;
+
+/// What a `Trap` whose condition holds calls: report the panic as
+/// `tlang_panic` does and exit with 101, or abort.
+const TRAP: &str = r#"namespace tlang {
+
+[[noreturn]] inline void trap(const char* message, const char* backtrace, bool aborts) {
+    if (!aborts) {
+        std::cout.flush();
+    }
+    std::cerr << message << '\n';
+    const char* show = std::getenv("TLANG_BACKTRACE");
+    if (show != nullptr && std::string(show) != "0") {
+        std::cerr << "stack backtrace:\n" << backtrace << '\n';
+    } else {
+        std::cerr << "note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace\n";
+    }
+    if (aborts) {
+        std::abort();
+    }
+    std::exit(101);
+}
+
+} // namespace tlang
+
+"#;
+
+/// C++ type of the values carried as `ty`, and the stack they are on.
+fn carried_type(ty: FfiType) -> (&'static str, &'static str) {
+    match ty.carried() {
+        FfiType::Float { .. } => ("double", "fltStack"),
+        FfiType::Bool => ("bool", "boolStack"),
+        FfiType::Str => ("std::string", "strStack"),
+        FfiType::Int { .. } => ("std::int64_t", "intStack"),
+    }
+}
+
+/// C++ type of an integer `Overflows` checks the range of.
+fn int_type(ty: FfiType) -> Result<String, BackendError> {
+    match ty {
+        FfiType::Int { bits: bits @ (8 | 16 | 32 | 64), signed } => {
+            Ok(format!("std::{}int{}_t", if signed { "" } else { "u" }, bits))
+        }
+        other => Err(BackendError::InvalidIr(format!("no C++ integer type for {:?}", other))),
+    }
+}
+
+/// A declaration of every local slot `instrs` use and of the parameters
+/// `params`, zeroed.
+fn local_declarations(params: &[FfiType], instrs: &[Instruction]) -> Result<String, BackendError> {
+    Ok(super::local_slots(params, instrs)?
+        .into_iter()
+        .map(|(slot, ty)| format!("    {} local{}{{}};\n", carried_type(ty).0, slot))
+        .collect())
+}
+
+#[derive(Debug)]
+pub struct CppBackend;
+
+impl Backend<CompiledModule> for CppBackend {
+    type ModuleIr = CompiledArtifact;
+
+    fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
+        // 1. Decode IR
+        let instrs = module.decode()?;
+
+        // 2. Begin C++ source; the stacks are shared by every function
+        let mut code = String::from(PRELUDE);
+        if instrs.iter().any(|instr| matches!(instr, Instruction::Trap { .. })) {
+            code.push_str(TRAP);
+        }
+        code.push_str("static std::vector<std::int64_t> intStack;\n");
+        code.push_str("static std::vector<double> fltStack;\n");
+        code.push_str("static std::vector<bool> boolStack;\n");
+        code.push_str("static std::vector<std::string> strStack;\n\n");
+
+        // 3. Translate the functions the entry calls, declared first so
+        // that they can call each other, with locals of their own, then
+        // the entry as `main`
+        let functions = plugin_api::split_functions(&instrs);
+        let results: HashMap<&str, Option<FfiType>> = functions
+            .iter()
+            .filter_map(|(header, _)| match header {
+                Some(Instruction::Function { name, result, .. }) => Some((name.as_str(), *result)),
+                _ => None,
+            })
+            .collect();
+        for name in results.keys().collect::<std::collections::BTreeSet<_>>() {
+            code.push_str(&format!("static void tlF_{}();\n", name));
+        }
+        if !results.is_empty() {
+            code.push('\n');
+        }
+        let mut main = String::new();
+        for (header, range) in functions {
+            let instrs = &instrs[range];
+            match header {
+                Some(Instruction::Function { name, params, .. }) => {
+                    code.push_str(&format!("static void tlF_{}() {{\n", name));
+                    code.push_str(&local_declarations(params, instrs)?);
+                    for (slot, &ty) in params.iter().enumerate().rev() {
+                        code.push_str(&format!("    local{} = tlang::pop({});\n", slot, carried_type(ty).1));
+                    }
+                    code.push_str(&self.function_body(instrs, false, &results)?);
+                    code.push_str("}\n\n");
+                }
+                _ => {
+                    main.push_str("int main() {\n");
+                    main.push_str(&local_declarations(&[], instrs)?);
+                    main.push_str("    std::cout << std::fixed << std::setprecision(6) << std::boolalpha;\n\n");
+                    main.push_str(&self.function_body(instrs, true, &results)?);
+                }
+            }
+        }
+        code.push_str(&main);
+
+        // 4. Close main; the stacks release their contents at exit
+        code.push_str("    return 0;\n}\n");
+
+        Ok(CompiledArtifact::source(self.name(), "main.cpp", code)
+            .with_build_command("c++ -std=c++17 -o main main.cpp")
+            .with_build_command("./main"))
+    }
+
+    fn name(&self) -> &'static str {
+        "cpp"
+    }
+}
+
+impl CppBackend {
+    /// The C++ statements of one function's `instrs`, `entry` if it is
+    /// `main`. `results` has the result type of each function called.
+    fn function_body(
+        &self,
+        instrs: &[Instruction],
+        entry: bool,
+        results: &HashMap<&str, Option<FfiType>>,
+    ) -> Result<String, BackendError> {
+        let mut code = String::new();
+        for instr in instrs.iter().cloned() {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    // The most negative literal is not expressible in C++.
+                    let lit = if n == i64::MIN {
+                        "std::numeric_limits<std::int64_t>::min()".to_string()
+                    } else {
+                        n.to_string()
+                    };
+                    code.push_str(&format!("    intStack.push_back({});\n", lit));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    fltStack.push_back({});\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    boolStack.push_back({});\n", b));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("    strStack.emplace_back(\"{}\");\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    if (!strStack.empty()) {\n\
+                         \x20       std::cout << tlang::pop(strStack);\n\
+                         \x20   } else if (!intStack.empty()) {\n\
+                         \x20       std::cout << tlang::pop(intStack);\n\
+                         \x20   }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    std::cout << tlang::pop(intStack);\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    tlang::print_float(tlang::pop(fltStack));\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    std::cout << tlang::pop(strStack);\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    std::cout << tlang::pop(boolStack);\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    fltStack.push_back(static_cast<double>(tlang::pop(intStack)));\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // An out-of-range conversion is undefined behaviour in C++.
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    let min = if min == i64::MIN {
+                        "std::numeric_limits<std::int64_t>::min()".to_string()
+                    } else {
+                        min.to_string()
+                    };
+                    code.push_str(&format!(
+                        "    {{\n        double f = tlang::pop(fltStack);\n        intStack.push_back(f != f ? 0 : f <= {} ? {} : f >= {} ? {} : static_cast<std::int64_t>(f));\n    }}\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    intStack.back() = ((intStack.back() & {}) ^ {}) - {};\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { slot, ty } => {
+                    code.push_str(&format!("    local{} = tlang::pop({});\n", slot, carried_type(ty).1));
+                }
+                Instruction::LoadLocal { slot, ty } => {
+                    code.push_str(&format!("    {}.push_back(local{});\n", carried_type(ty).1, slot));
+                }
+                Instruction::Arith { op: ArithOp::Rem, ty: FfiType::Float { .. } } => {
+                    return Err(BackendError::InvalidIr("remainder of floats".to_string()));
+                }
+                Instruction::Arith { op, ty: FfiType::Float { .. } } => {
+                    code.push_str(&format!(
+                        "    {{\n        double b = tlang::pop(fltStack);\n        fltStack.back() = fltStack.back() {} b;\n    }}\n",
+                        op.symbol()
+                    ));
+                }
+                Instruction::Arith { op, .. } => {
+                    // Wrapped to 64 bits, as in `ArithOp::int`: signed overflow
+                    // is undefined behaviour in C++, unsigned wraps around.
+                    let value = match op {
+                        ArithOp::Div => {
+                            "b == 0 ? 0 : b == -1 ? static_cast<std::int64_t>(0 - static_cast<std::uint64_t>(a)) : a / b"
+                                .to_string()
+                        }
+                        ArithOp::Rem => "b == 0 || b == -1 ? 0 : a % b".to_string(),
+                        _ => format!(
+                            "static_cast<std::int64_t>(static_cast<std::uint64_t>(a) {} static_cast<std::uint64_t>(b))",
+                            op.symbol()
+                        ),
+                    };
+                    code.push_str(&format!(
+                        "    {{\n        std::int64_t b = tlang::pop(intStack);\n        std::int64_t a = tlang::pop(intStack);\n        intStack.push_back({});\n    }}\n",
+                        value
+                    ));
+                }
+                Instruction::Compare { op, ty } => {
+                    let (ty, stack) = carried_type(ty);
+                    code.push_str(&format!(
+                        "    {{\n        {ty} b = tlang::pop({s});\n        {ty} a = tlang::pop({s});\n        boolStack.push_back(a {} b);\n    }}\n",
+                        op.symbol(),
+                        ty = ty,
+                        s = stack
+                    ));
+                }
+                Instruction::Not => code.push_str("    boolStack.push_back(!tlang::pop(boolStack));\n"),
+                Instruction::Label(n) => code.push_str(&format!("tlL{}:;\n", n)),
+                Instruction::Jump(n) => code.push_str(&format!("    goto tlL{};\n", n)),
+                Instruction::JumpIfFalse(n) => {
+                    code.push_str(&format!("    if (!tlang::pop(boolStack)) goto tlL{};\n", n));
+                }
+                Instruction::Overflows { op, ty } => {
+                    let int = int_type(ty)?;
+                    let signed = matches!(ty, FfiType::Int { signed: true, .. });
+                    // The exact result is out of range where the builtin's
+                    // result, of the checked type, would not hold it.
+                    let overflows = match op {
+                        ArithOp::Add | ArithOp::Sub | ArithOp::Mul => {
+                            let builtin = match op {
+                                ArithOp::Add => "add",
+                                ArithOp::Sub => "sub",
+                                _ => "mul",
+                            };
+                            format!(
+                                "{int} r;\n        boolStack.push_back(__builtin_{}_overflow(static_cast<{int}>(a), static_cast<{int}>(b), &r));",
+                                builtin,
+                                int = int
+                            )
+                        }
+                        ArithOp::Div if signed => {
+                            format!("boolStack.push_back(b == -1 && a == std::numeric_limits<{}>::min());", int)
+                        }
+                        ArithOp::Div | ArithOp::Rem => "(void)a;\n        (void)b;\n        boolStack.push_back(false);".to_string(),
+                    };
+                    code.push_str(&format!(
+                        "    {{\n        std::int64_t b = tlang::pop(intStack);\n        std::int64_t a = tlang::pop(intStack);\n        {}\n    }}\n",
+                        overflows
+                    ));
+                }
+                Instruction::Trap { message, backtrace, abort } => {
+                    code.push_str(&format!(
+                        "    if (tlang::pop(boolStack)) tlang::trap(\"{}\", \"{}\", {});\n",
+                        super::escape_str(&message),
+                        super::escape_str(&backtrace),
+                        abort
+                    ));
+                }
+                Instruction::Call { name, result, .. } => {
+                    code.push_str(&format!("    tlF_{}();\n", name));
+                    let returns = results.get(name.as_str()).copied().flatten();
+                    if let (Some(ty), None) = (returns, result) {
+                        code.push_str(&format!("    {}.pop_back();\n", carried_type(ty).1));
+                    }
+                }
+                Instruction::Return { .. } => code.push_str("    return;\n"),
+                Instruction::Function { name, .. } => {
+                    return Err(BackendError::InvalidIr(format!("`{}` starts inside a function", name)));
+                }
+                Instruction::Exit if entry => {
+                    code.push_str("    return static_cast<int>(tlang::pop(intStack));\n");
+                }
+                Instruction::Exit => {
+                    // `exit` flushes `std::cout`, as returning from `main` does.
+                    code.push_str("    std::exit(static_cast<int>(tlang::pop(intStack)));\n");
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+            }
+        }
+        Ok(code)
+    }
+}
+
+// Register this backend at startup
+static CPP_REG: Lazy<()> = Lazy::new(|| {
+    register_backend(CppBackend);
+});
+
+#[doc(hidden)]
+#[allow(non_upper_case_globals)]
+#[used]
+pub(crate) static FORCE_CPP_REG: fn() = {
+    fn init() {
+        Lazy::force(&CPP_REG);
+    }
+    init
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lower_program, parse_source};
+    use std::process::Command;
+
+    fn cpp(source: &str) -> String {
+        let program = parse_source(source).unwrap();
+        let artifact = CppBackend.compile(lower_program(&program, source, "main.t").unwrap()).unwrap();
+        String::from_utf8(artifact.data).unwrap()
+    }
+
+    #[test]
+    fn structs_run_as_the_values_of_their_fields() {
+        let code = cpp("struct Point {\n    x: i64,\n    y: i64,\n}\n\nfn sum(p: Point) -> i64 {\n    p.x + p.y\n}\n\nfn main() {\n    let mut p = Point { x: 1, y: 2 };\n    while p.x < 5 {\n        p.x = p.x + 1;\n    }\n    let q = p;\n    p.y = 10;\n    println(sum(q));\n    println(sum(p));\n}\n");
+        let dir = std::env::temp_dir().join(format!("tlang-cpp-structs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.cpp"), code).unwrap();
+        let Ok(build) = Command::new("c++").args(["-std=c++17", "-o", "main", "main.cpp"]).current_dir(&dir).output() else {
+            return;
+        };
+        assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+        let run = Command::new(dir.join("main")).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "7\n15\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,829 @@
+// File: compiler/src/backends/cranelift_jit/mod.rs
+//! Cranelift JIT backend for T-Lang.
+//! Decodes our IR, lowers it to a native function with Cranelift and hands
+//! back a `JitProgram` that runs in-process, without writing any files.
+//!
+//! The three value stacks (ints, floats and strings) are resolved at
+//! compile time; only the prints happen at run time, through the
+//! `tlang_jit_print_*` helpers below. Booleans live on the int stack as
+//! 0/1. Locals are Cranelift variables, one per slot (two for a string: its
+//! pointer and length). Each `Label` starts a Cranelift block, which the
+//! jumps to it branch to; the stacks must be empty there, as the values on
+//! them would otherwise need to be block parameters.
+//!
+//! `CallExtern` calls the C function directly. `tstd`'s functions are
+//! linked into the compiler, so the JIT finds them through `tstd::exports`;
+//! others are looked up in the host process. String literals carry a NUL
+//! terminator for them, and strings they return are measured at run time.
+//! Variadic functions are not supported.
+//!
+//! Every function of the module but the entry is a Cranelift function of
+//! its own, whose parameters are stored in its first locals. It returns its
+//! result, if it has one, then a flag saying whether the program ended in
+//! it and the exit code it ended with, which its callers return in turn.
+//!
+//! The generated function returns the program's exit code: the value of an
+//! `Exit`, which ends it, or 0. Nothing the program does ends the host
+//! process, which may be a watch loop or a REPL running one program after
+//! another. A `Trap` that fires reports its panic through `tlang_jit_trap`
+//! and returns 101, or 134 under the abort strategy. The `tstd` functions
+//! that exit, `tlang_process_exit`, `tlang_panic` and `tlang_abort`, are
+//! not called: `process::exit` returns its code, and a panic is reported
+//! like a `Trap` and returns its code too.
+//!
+//! The backend's IR is an executable program rather than an artifact, so it
+//! is not part of the artifact registry; drivers use it directly.
+
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{types, AbiParam, Block, InstBuilder, Signature, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+use plugin_api::{
+    format_bool, format_float, ArithOp, Backend, BackendCapabilities, BackendError, CompareOp, CompiledModule,
+    FfiType, Instruction,
+};
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+use std::io::Write;
+
+#[derive(Debug)]
+pub struct CraneliftJitBackend;
+
+/// A module compiled to native code, ready to run.
+pub struct JitProgram {
+    module: Option<JITModule>,
+    entry: extern "C" fn() -> i32,
+    /// String literals referenced by pointer from the generated code.
+    _strings: Vec<Box<[u8]>>,
+}
+
+impl JitProgram {
+    /// Execute the program on the current thread, and return its exit code.
+    pub fn run(&self) -> i32 {
+        let code = (self.entry)();
+        let _ = std::io::stdout().flush();
+        code
+    }
+}
+
+impl Drop for JitProgram {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // SAFETY: `entry` cannot be called after `self` is dropped.
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+extern "C" fn tlang_jit_print_int(v: i64) {
+    print!("{}", v);
+}
+
+extern "C" fn tlang_jit_print_float(v: f64) {
+    print!("{}", format_float(v));
+}
+
+extern "C" fn tlang_jit_print_bool(v: i64) {
+    print!("{}", format_bool(v != 0));
+}
+
+extern "C" fn tlang_jit_print_str(ptr: *const u8, len: usize) {
+    // SAFETY: the generated code only passes pointers into `JitProgram::_strings`,
+    // which outlives every call.
+    let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+    print!("{}", String::from_utf8_lossy(bytes));
+}
+
+/// A `Trap` whose condition holds, or a call to `tlang_panic` or
+/// `tlang_abort`: report the panic, after flushing stdout unless it aborts.
+/// The program then returns `PANIC_EXIT_CODE`, or `ABORT_EXIT_CODE`,
+/// leaving the host running.
+extern "C" fn tlang_jit_trap(message: *const c_char, backtrace: *const c_char, aborts: i64) {
+    // SAFETY: the generated code only passes NUL-terminated strings, from
+    // `JitProgram::_strings` or the strings the program made.
+    let (message, backtrace) = unsafe { (CStr::from_ptr(message), CStr::from_ptr(backtrace)) };
+    if aborts == 0 {
+        let _ = std::io::stdout().flush();
+    }
+    plugin_api::report_panic(&message.to_string_lossy(), &backtrace.to_string_lossy());
+}
+
+/// The exit code a panic returns, under the abort strategy if `aborts`.
+fn panic_exit_code(aborts: bool) -> i64 {
+    i64::from(if aborts { plugin_api::ABORT_EXIT_CODE } else { plugin_api::PANIC_EXIT_CODE })
+}
+
+extern "C" fn tlang_jit_str_len(ptr: *const c_char) -> usize {
+    if ptr.is_null() {
+        return 0;
+    }
+    // SAFETY: extern functions return NUL-terminated strings.
+    unsafe { CStr::from_ptr(ptr) }.to_bytes().len()
+}
+
+/// The Cranelift type an integer of `bits` bits is passed as.
+fn int_type(bits: u8) -> types::Type {
+    match bits {
+        8 => types::I8,
+        16 => types::I16,
+        32 => types::I32,
+        _ => types::I64,
+    }
+}
+
+/// How a C function takes or returns a value of type `ty`.
+fn abi_param(ty: FfiType, ptr_ty: types::Type) -> AbiParam {
+    match ty {
+        FfiType::Int { bits: 64, .. } => AbiParam::new(types::I64),
+        FfiType::Int { bits, signed: true } => AbiParam::new(int_type(bits)).sext(),
+        FfiType::Int { bits, signed: false } => AbiParam::new(int_type(bits)).uext(),
+        FfiType::Float { bits: 32 } => AbiParam::new(types::F32),
+        FfiType::Float { .. } => AbiParam::new(types::F64),
+        FfiType::Bool => AbiParam::new(types::I8).uext(),
+        FfiType::Str => AbiParam::new(ptr_ty),
+    }
+}
+
+/// The Cranelift types a value of `ty` is held in on the stacks: a string
+/// is its pointer and length.
+fn value_types(ty: FfiType, ptr_ty: types::Type) -> Vec<types::Type> {
+    match ty {
+        FfiType::Float { .. } => vec![types::F64],
+        FfiType::Str => vec![ptr_ty, ptr_ty],
+        FfiType::Int { .. } | FfiType::Bool => vec![types::I64],
+    }
+}
+
+/// The signature of the function of the module that takes `params` and
+/// returns `result`: after the result, whether the program ended in it, and
+/// its exit code.
+fn signature(jit: &JITModule, params: &[FfiType], result: Option<FfiType>, ptr_ty: types::Type) -> Signature {
+    let mut sig = jit.make_signature();
+    for &ty in params {
+        sig.params.extend(value_types(ty, ptr_ty).into_iter().map(AbiParam::new));
+    }
+    if let Some(ty) = result {
+        sig.returns.extend(value_types(ty, ptr_ty).into_iter().map(AbiParam::new));
+    }
+    sig.returns.extend([AbiParam::new(types::I8), AbiParam::new(types::I32)]);
+    sig
+}
+
+fn jit_error(e: impl std::fmt::Display) -> BackendError {
+    BackendError::Generic(format!("cranelift JIT: {}", e))
+}
+
+fn underflow(instr: &Instruction) -> BackendError {
+    BackendError::InvalidIr(format!("stack underflow at {:?}", instr))
+}
+
+impl Backend<CompiledModule> for CraneliftJitBackend {
+    type ModuleIr = JitProgram;
+
+    fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
+        // 1. Decode IR
+        let instrs = module.decode()?;
+
+        // 2. Set up a JIT module for the host
+        let mut flags = settings::builder();
+        flags.set("use_colocated_libcalls", "false").map_err(jit_error)?;
+        flags.set("is_pic", "false").map_err(jit_error)?;
+        // The module's functions return more values than fit in registers.
+        flags.set("enable_multi_ret_implicit_sret", "true").map_err(jit_error)?;
+        let isa = cranelift_native::builder()
+            .map_err(jit_error)?
+            .finish(settings::Flags::new(flags))
+            .map_err(jit_error)?;
+
+        let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+        builder.symbol("tlang_jit_print_int", tlang_jit_print_int as *const u8);
+        builder.symbol("tlang_jit_print_float", tlang_jit_print_float as *const u8);
+        builder.symbol("tlang_jit_print_bool", tlang_jit_print_bool as *const u8);
+        builder.symbol("tlang_jit_print_str", tlang_jit_print_str as *const u8);
+        builder.symbol("tlang_jit_str_len", tlang_jit_str_len as *const u8);
+        builder.symbol("tlang_jit_trap", tlang_jit_trap as *const u8);
+        builder.symbols(tstd::exports());
+        let mut jit = JITModule::new(builder);
+        let ptr_ty = jit.target_config().pointer_type();
+
+        let mut import = |name: &str, params: &[types::Type]| -> Result<FuncId, BackendError> {
+            let mut sig = jit.make_signature();
+            sig.params.extend(params.iter().map(|&t| AbiParam::new(t)));
+            jit.declare_function(name, Linkage::Import, &sig).map_err(jit_error)
+        };
+        let print_int = import("tlang_jit_print_int", &[types::I64])?;
+        let print_float = import("tlang_jit_print_float", &[types::F64])?;
+        let print_bool = import("tlang_jit_print_bool", &[types::I64])?;
+        let print_str = import("tlang_jit_print_str", &[ptr_ty, ptr_ty])?;
+        let trap = import("tlang_jit_trap", &[ptr_ty, ptr_ty, types::I64])?;
+
+        let mut sig = jit.make_signature();
+        sig.params.push(AbiParam::new(ptr_ty));
+        sig.returns.push(AbiParam::new(ptr_ty));
+        let str_len = jit.declare_function("tlang_jit_str_len", Linkage::Import, &sig).map_err(jit_error)?;
+        let mut externs = HashMap::new();
+        for function in module.externs.iter().filter(|f| !f.variadic) {
+            let mut sig = jit.make_signature();
+            sig.params.extend(function.params.iter().map(|&ty| abi_param(ty, ptr_ty)));
+            sig.returns.extend(function.return_type.map(|ty| abi_param(ty, ptr_ty)));
+            let id = jit.declare_function(&function.name, Linkage::Import, &sig).map_err(jit_error)?;
+            externs.insert(function.name.as_str(), id);
+        }
+
+        // 3. Declare `fn tlang_main() -> i32`, and a function for each of the
+        // others
+        let functions = plugin_api::split_functions(&instrs);
+        let mut main_sig = jit.make_signature();
+        main_sig.returns.push(AbiParam::new(types::I32));
+        let main_id = jit.declare_function("tlang_main", Linkage::Local, &main_sig).map_err(jit_error)?;
+        let mut callees = HashMap::new();
+        for (header, _) in &functions {
+            if let Some(Instruction::Function { name, params, result, .. }) = header {
+                let sig = signature(&jit, params, *result, ptr_ty);
+                let name = name.as_str();
+                let id = jit.declare_function(&format!("tlang_fn_{}", name), Linkage::Local, &sig).map_err(jit_error)?;
+                callees.insert(name, (id, sig, *result));
+            }
+        }
+
+        // 4. Build each function from its instructions
+        let mut ctx = jit.make_context();
+        let mut strings: Vec<Box<[u8]>> = Vec::new();
+        for (header, range) in &functions {
+            let instrs = &instrs[range.clone()];
+            let (id, params, result) = match header {
+                Some(Instruction::Function { name, params, .. }) => {
+                    let (id, ref sig, result) = callees[name.as_str()];
+                    ctx.func.signature = sig.clone();
+                    (id, params.as_slice(), Some(result))
+                }
+                _ => {
+                    ctx.func.signature = main_sig.clone();
+                    (main_id, &[][..], None)
+                }
+            };
+            let mut fctx = FunctionBuilderContext::new();
+            let mut b = FunctionBuilder::new(&mut ctx.func, &mut fctx);
+            let block = b.create_block();
+            b.append_block_params_for_function_params(block);
+            b.switch_to_block(block);
+            b.seal_block(block);
+            // Return `values`, the function's result if it has one: from
+            // `tlang_main` nothing, and from the others the result and
+            // that the program goes on.
+            let returns = |b: &mut FunctionBuilder, values: &[Value]| {
+                if result.is_none() {
+                    let code = b.ins().iconst(types::I32, 0);
+                    b.ins().return_(&[code]);
+                } else {
+                    let mut values = values.to_vec();
+                    values.push(b.ins().iconst(types::I8, 0));
+                    values.push(b.ins().iconst(types::I32, 0));
+                    b.ins().return_(&values);
+                }
+            };
+            // End the program with the exit code `code`, returning it from
+            // `tlang_main` and with the flag from the other functions.
+            let ends = |b: &mut FunctionBuilder, code: Value| match result {
+                None => {
+                    b.ins().return_(&[code]);
+                }
+                Some(result) => {
+                    let mut values = Vec::new();
+                    for ty in result.into_iter().flat_map(|ty| value_types(ty, ptr_ty)) {
+                        values.push(match ty {
+                            types::F64 => b.ins().f64const(0.0),
+                            ty => b.ins().iconst(ty, 0),
+                        });
+                    }
+                    values.push(b.ins().iconst(types::I8, 1));
+                    values.push(code);
+                    b.ins().return_(&values);
+                }
+            };
+            let mut blocks: HashMap<u32, Block> = HashMap::new();
+            let mut label_block = |b: &mut FunctionBuilder, label: u32| *blocks.entry(label).or_insert_with(|| b.create_block());
+            // Whether the block being built has ended, so what follows is
+            // unreachable until the next label.
+            let mut ended = false;
+
+            let print_int = jit.declare_func_in_func(print_int, b.func);
+            let print_float = jit.declare_func_in_func(print_float, b.func);
+            let print_bool = jit.declare_func_in_func(print_bool, b.func);
+            let print_str = jit.declare_func_in_func(print_str, b.func);
+            let str_len = jit.declare_func_in_func(str_len, b.func);
+            let trap = jit.declare_func_in_func(trap, b.func);
+            let externs: HashMap<&str, _> = externs
+                .iter()
+                .map(|(&name, &id)| (name, jit.declare_func_in_func(id, b.func)))
+                .collect();
+            let calls: HashMap<&str, _> = callees
+                .iter()
+                .map(|(&name, &(id, _, result))| (name, (jit.declare_func_in_func(id, b.func), result)))
+                .collect();
+
+            let mut int_stack: Vec<Value> = Vec::new();
+            let mut flt_stack: Vec<Value> = Vec::new();
+            let mut str_stack: Vec<(Value, Value)> = Vec::new();
+            let mut locals: HashMap<u32, (FfiType, Variable, Variable)> = HashMap::new();
+            let mut local = |b: &mut FunctionBuilder, slot: u32, ty: FfiType| {
+                let next = locals.len() as u32 * 2;
+                let &mut (declared, value, len) = locals.entry(slot).or_insert_with(|| {
+                    let (value, len) = (Variable::from_u32(next), Variable::from_u32(next + 1));
+                    let value_ty = match ty {
+                        FfiType::Float { .. } => types::F64,
+                        FfiType::Str => ptr_ty,
+                        _ => types::I64,
+                    };
+                    b.declare_var(value, value_ty);
+                    b.declare_var(len, ptr_ty);
+                    (ty, value, len)
+                });
+                if declared != ty {
+                    return Err(BackendError::InvalidIr(format!(
+                        "local {} holds {:?}, not {:?}",
+                        slot, declared, ty
+                    )));
+                }
+                Ok((value, len))
+            };
+            let args = b.block_params(block).to_vec();
+            let mut args = args.into_iter();
+            for (slot, &ty) in params.iter().enumerate() {
+                let (value, len) = local(&mut b, slot as u32, ty.carried())?;
+                b.def_var(value, args.next().ok_or_else(|| jit_error("missing parameter"))?);
+                if ty == FfiType::Str {
+                    b.def_var(len, args.next().ok_or_else(|| jit_error("missing parameter"))?);
+                }
+            }
+
+            for instr in instrs {
+                // Only the condition of a `JumpIfFalse`, a bool on the int stack.
+                let kept = match instr {
+                    Instruction::Label(_) | Instruction::Jump(_) => Some(0),
+                    Instruction::JumpIfFalse(_) => Some(1),
+                    _ => None,
+                };
+                if kept.is_some_and(|kept| int_stack.len() != kept || !flt_stack.is_empty() || !str_stack.is_empty()) {
+                    return Err(BackendError::InvalidIr(format!("values are left on the stacks at {:?}", instr)));
+                }
+                if ended && !matches!(instr, Instruction::Label(_)) {
+                    // Unreachable code still needs a block to be built in.
+                    let dead = b.create_block();
+                    b.switch_to_block(dead);
+                    ended = false;
+                }
+                match instr {
+                    Instruction::Nop => {}
+                    Instruction::PushInt(n) => {
+                        int_stack.push(b.ins().iconst(types::I64, *n));
+                    }
+                    Instruction::PushFloat(f) => {
+                        let v = super::finite_float(*f)?;
+                        flt_stack.push(b.ins().f64const(v));
+                    }
+                    Instruction::PushBool(v) => {
+                        int_stack.push(b.ins().iconst(types::I64, i64::from(*v)));
+                    }
+                    Instruction::PushStr(s) => {
+                        let bytes: Box<[u8]> = [s.as_bytes(), &[0]].concat().into();
+                        let ptr = b.ins().iconst(ptr_ty, bytes.as_ptr() as i64);
+                        let len = b.ins().iconst(ptr_ty, s.len() as i64);
+                        strings.push(bytes);
+                        str_stack.push((ptr, len));
+                    }
+                    Instruction::CallPrint => {
+                        if let Some((ptr, len)) = str_stack.pop() {
+                            b.ins().call(print_str, &[ptr, len]);
+                        } else if let Some(v) = int_stack.pop() {
+                            b.ins().call(print_int, &[v]);
+                        }
+                    }
+                    Instruction::PrintInt => {
+                        let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        b.ins().call(print_int, &[v]);
+                    }
+                    Instruction::PrintFloat => {
+                        let v = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                        b.ins().call(print_float, &[v]);
+                    }
+                    Instruction::PrintStr => {
+                        let (ptr, len) = str_stack.pop().ok_or_else(|| underflow(instr))?;
+                        b.ins().call(print_str, &[ptr, len]);
+                    }
+                    Instruction::PrintBool => {
+                        let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        b.ins().call(print_bool, &[v]);
+                    }
+                    Instruction::IntToFloat => {
+                        let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        flt_stack.push(b.ins().fcvt_from_sint(types::F64, v));
+                    }
+                    Instruction::FloatToInt { bits, signed } => {
+                        // The saturating conversion maps NaN to 0; narrower
+                        // targets are clamped afterwards.
+                        let v = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let mut n = b.ins().fcvt_to_sint_sat(types::I64, v);
+                        let (min, max) = plugin_api::int_bounds(*bits, *signed);
+                        if min > i64::MIN {
+                            let lo = b.ins().iconst(types::I64, min);
+                            n = b.ins().smax(n, lo);
+                        }
+                        if max < i64::MAX {
+                            let hi = b.ins().iconst(types::I64, max);
+                            n = b.ins().smin(n, hi);
+                        }
+                        int_stack.push(n);
+                    }
+                    Instruction::WrapInt { bits, signed } => {
+                        if let Some((mask, sign)) = super::wrap_masks(*bits, *signed) {
+                            let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                            let v = b.ins().band_imm(v, mask);
+                            let v = b.ins().bxor_imm(v, sign);
+                            let sign = b.ins().iconst(types::I64, sign);
+                            int_stack.push(b.ins().isub(v, sign));
+                        }
+                    }
+                    Instruction::CallExtern { name, args, result } => {
+                        let Some(&callee) = externs.get(name.as_str()) else {
+                            module.extern_function(name)?;
+                            return Err(jit_error(format_args!("cannot call the variadic function `{}`", name)));
+                        };
+                        let mut values = Vec::with_capacity(args.len());
+                        for &ty in args.iter().rev() {
+                            let value = match ty {
+                                FfiType::Float { bits } => {
+                                    let v = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                                    if bits == 32 { b.ins().fdemote(types::F32, v) } else { v }
+                                }
+                                FfiType::Str => str_stack.pop().ok_or_else(|| underflow(instr))?.0,
+                                FfiType::Int { bits, .. } => {
+                                    let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                                    if bits == 64 { v } else { b.ins().ireduce(int_type(bits), v) }
+                                }
+                                FfiType::Bool => {
+                                    let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                                    b.ins().icmp_imm(IntCC::NotEqual, v, 0)
+                                }
+                            };
+                            values.push(value);
+                        }
+                        values.reverse();
+                        // The functions that would end the host end the program.
+                        let ending = match (name.as_str(), values.as_slice()) {
+                            ("tlang_process_exit", &[code]) => Some(code),
+                            (name @ ("tlang_panic" | "tlang_abort"), &[message, backtrace]) => {
+                                let aborts = name == "tlang_abort";
+                                let flag = b.ins().iconst(types::I64, i64::from(aborts));
+                                b.ins().call(trap, &[message, backtrace, flag]);
+                                Some(b.ins().iconst(types::I32, panic_exit_code(aborts)))
+                            }
+                            _ => None,
+                        };
+                        if let Some(code) = ending {
+                            ends(&mut b, code);
+                            int_stack.clear();
+                            flt_stack.clear();
+                            str_stack.clear();
+                            ended = true;
+                            continue;
+                        }
+                        let call = b.ins().call(callee, &values);
+                        if let Some(ty) = *result {
+                            let v = b.inst_results(call)[0];
+                            match ty {
+                                FfiType::Int { bits: 64, .. } => int_stack.push(v),
+                                FfiType::Int { signed: true, .. } => int_stack.push(b.ins().sextend(types::I64, v)),
+                                FfiType::Int { signed: false, .. } | FfiType::Bool => {
+                                    int_stack.push(b.ins().uextend(types::I64, v));
+                                }
+                                FfiType::Float { bits: 32 } => flt_stack.push(b.ins().fpromote(types::F64, v)),
+                                FfiType::Float { .. } => flt_stack.push(v),
+                                FfiType::Str => {
+                                    let measured = b.ins().call(str_len, &[v]);
+                                    let len = b.inst_results(measured)[0];
+                                    str_stack.push((v, len));
+                                }
+                            }
+                        }
+                    }
+                    Instruction::StoreLocal { slot, ty } => {
+                        let (value, len) = local(&mut b, *slot, *ty)?;
+                        match ty {
+                            FfiType::Float { .. } => {
+                                let v = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                                b.def_var(value, v);
+                            }
+                            FfiType::Str => {
+                                let (ptr, n) = str_stack.pop().ok_or_else(|| underflow(instr))?;
+                                b.def_var(value, ptr);
+                                b.def_var(len, n);
+                            }
+                            _ => {
+                                let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                                b.def_var(value, v);
+                            }
+                        }
+                    }
+                    Instruction::LoadLocal { slot, ty } => {
+                        let (value, len) = local(&mut b, *slot, *ty)?;
+                        match ty {
+                            FfiType::Float { .. } => flt_stack.push(b.use_var(value)),
+                            FfiType::Str => {
+                                let ptr = b.use_var(value);
+                                str_stack.push((ptr, b.use_var(len)));
+                            }
+                            _ => int_stack.push(b.use_var(value)),
+                        }
+                    }
+                    Instruction::Exit => {
+                        let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let code = b.ins().ireduce(types::I32, v);
+                        ends(&mut b, code);
+                        int_stack.clear();
+                        flt_stack.clear();
+                        str_stack.clear();
+                        ended = true;
+                    }
+                    Instruction::Arith { op, ty: FfiType::Float { .. } } => {
+                        let rhs = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let lhs = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                        flt_stack.push(match op {
+                            ArithOp::Add => b.ins().fadd(lhs, rhs),
+                            ArithOp::Sub => b.ins().fsub(lhs, rhs),
+                            ArithOp::Mul => b.ins().fmul(lhs, rhs),
+                            ArithOp::Div => b.ins().fdiv(lhs, rhs),
+                            ArithOp::Rem => return Err(BackendError::InvalidIr("remainder of floats".to_string())),
+                        });
+                    }
+                    Instruction::Arith { op: op @ (ArithOp::Div | ArithOp::Rem), .. } => {
+                        // Cranelift traps on dividing by 0, or `i64::MIN` by -1;
+                        // those divide by 1 instead, and then give what
+                        // `ArithOp::int` does.
+                        let rhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let lhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let zero = b.ins().icmp_imm(IntCC::Equal, rhs, 0);
+                        let minus_one = b.ins().icmp_imm(IntCC::Equal, rhs, -1);
+                        let one = b.ins().iconst(types::I64, 1);
+                        let trapping = b.ins().bor(zero, minus_one);
+                        let divisor = b.ins().select(trapping, one, rhs);
+                        int_stack.push(if *op == ArithOp::Rem {
+                            b.ins().srem(lhs, divisor)
+                        } else {
+                            let quotient = b.ins().sdiv(lhs, divisor);
+                            let negated = b.ins().ineg(lhs);
+                            let quotient = b.ins().select(minus_one, negated, quotient);
+                            let nothing = b.ins().iconst(types::I64, 0);
+                            b.ins().select(zero, nothing, quotient)
+                        });
+                    }
+                    Instruction::Arith { op, .. } => {
+                        let rhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let lhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        int_stack.push(match op {
+                            ArithOp::Add => b.ins().iadd(lhs, rhs),
+                            ArithOp::Sub => b.ins().isub(lhs, rhs),
+                            ArithOp::Mul => b.ins().imul(lhs, rhs),
+                            ArithOp::Div | ArithOp::Rem => unreachable!("divisions are handled above"),
+                        });
+                    }
+                    Instruction::Compare { op, ty: FfiType::Float { .. } } => {
+                        let rhs = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let lhs = flt_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let cc = match op {
+                            CompareOp::Eq => FloatCC::Equal,
+                            CompareOp::Ne => FloatCC::NotEqual,
+                            CompareOp::Lt => FloatCC::LessThan,
+                            CompareOp::Le => FloatCC::LessThanOrEqual,
+                            CompareOp::Gt => FloatCC::GreaterThan,
+                            CompareOp::Ge => FloatCC::GreaterThanOrEqual,
+                        };
+                        let holds = b.ins().fcmp(cc, lhs, rhs);
+                        int_stack.push(b.ins().uextend(types::I64, holds));
+                    }
+                    Instruction::Compare { op, .. } => {
+                        let rhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let lhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let cc = match op {
+                            CompareOp::Eq => IntCC::Equal,
+                            CompareOp::Ne => IntCC::NotEqual,
+                            CompareOp::Lt => IntCC::SignedLessThan,
+                            CompareOp::Le => IntCC::SignedLessThanOrEqual,
+                            CompareOp::Gt => IntCC::SignedGreaterThan,
+                            CompareOp::Ge => IntCC::SignedGreaterThanOrEqual,
+                        };
+                        let holds = b.ins().icmp(cc, lhs, rhs);
+                        int_stack.push(b.ins().uextend(types::I64, holds));
+                    }
+                    Instruction::Not => {
+                        let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let not = b.ins().icmp_imm(IntCC::Equal, v, 0);
+                        int_stack.push(b.ins().uextend(types::I64, not));
+                    }
+                    Instruction::Label(label) => {
+                        let target = label_block(&mut b, *label);
+                        if !ended {
+                            b.ins().jump(target, &[]);
+                        }
+                        b.switch_to_block(target);
+                        ended = false;
+                    }
+                    Instruction::Jump(label) => {
+                        let target = label_block(&mut b, *label);
+                        b.ins().jump(target, &[]);
+                        ended = true;
+                    }
+                    Instruction::JumpIfFalse(label) => {
+                        let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let (target, next) = (label_block(&mut b, *label), b.create_block());
+                        b.ins().brif(v, next, &[], target, &[]);
+                        b.switch_to_block(next);
+                    }
+                    Instruction::Overflows { op, ty } => {
+                        let FfiType::Int { bits, signed } = *ty else {
+                            return Err(BackendError::InvalidIr(format!("cannot compile {:?}", instr)));
+                        };
+                        let rhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let lhs = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let overflows = match op {
+                            // Only the smallest signed value divided by -1 is out of range.
+                            ArithOp::Div if signed => {
+                                let (min, _) = plugin_api::int_bounds(bits, true);
+                                let smallest = b.ins().icmp_imm(IntCC::Equal, lhs, min);
+                                let minus_one = b.ins().icmp_imm(IntCC::Equal, rhs, -1);
+                                b.ins().band(smallest, minus_one)
+                            }
+                            ArithOp::Div | ArithOp::Rem => b.ins().iconst(types::I8, 0),
+                            _ => {
+                                // Out of 64 bits, or else of the narrower type.
+                                let (result, wide) = match (op, signed) {
+                                    (ArithOp::Add, true) => b.ins().sadd_overflow(lhs, rhs),
+                                    (ArithOp::Add, false) => b.ins().uadd_overflow(lhs, rhs),
+                                    (ArithOp::Sub, true) => b.ins().ssub_overflow(lhs, rhs),
+                                    (ArithOp::Sub, false) => b.ins().usub_overflow(lhs, rhs),
+                                    (_, true) => b.ins().smul_overflow(lhs, rhs),
+                                    (_, false) => b.ins().umul_overflow(lhs, rhs),
+                                };
+                                if bits >= 64 {
+                                    wide
+                                } else {
+                                    let (min, max) = plugin_api::int_bounds(bits, signed);
+                                    let (below, above) = if signed {
+                                        (IntCC::SignedLessThan, IntCC::SignedGreaterThan)
+                                    } else {
+                                        (IntCC::UnsignedLessThan, IntCC::UnsignedGreaterThan)
+                                    };
+                                    let low = b.ins().icmp_imm(below, result, min);
+                                    let high = b.ins().icmp_imm(above, result, max);
+                                    let narrow = b.ins().bor(low, high);
+                                    b.ins().bor(wide, narrow)
+                                }
+                            }
+                        };
+                        int_stack.push(b.ins().uextend(types::I64, overflows));
+                    }
+                    Instruction::Trap { message, backtrace, abort } => {
+                        let v = int_stack.pop().ok_or_else(|| underflow(instr))?;
+                        let (panics, next) = (b.create_block(), b.create_block());
+                        b.ins().brif(v, panics, &[], next, &[]);
+                        b.switch_to_block(panics);
+                        let mut args = Vec::new();
+                        for text in [message, backtrace] {
+                            let bytes: Box<[u8]> = [text.as_bytes(), &[0]].concat().into();
+                            args.push(b.ins().iconst(ptr_ty, bytes.as_ptr() as i64));
+                            strings.push(bytes);
+                        }
+                        args.push(b.ins().iconst(types::I64, i64::from(*abort)));
+                        b.ins().call(trap, &args);
+                        let code = b.ins().iconst(types::I32, panic_exit_code(*abort));
+                        ends(&mut b, code);
+                        b.switch_to_block(next);
+                    }
+                    Instruction::Call { name, args, result } => {
+                        let &(callee, returned) = calls
+                            .get(name.as_str())
+                            .ok_or_else(|| BackendError::InvalidIr(format!("call of missing function `{}`", name)))?;
+                        let mut values = Vec::new();
+                        for &ty in args.iter().rev() {
+                            match ty {
+                                FfiType::Float { .. } => values.push(flt_stack.pop().ok_or_else(|| underflow(instr))?),
+                                FfiType::Str => {
+                                    let (ptr, len) = str_stack.pop().ok_or_else(|| underflow(instr))?;
+                                    values.extend([len, ptr]);
+                                }
+                                _ => values.push(int_stack.pop().ok_or_else(|| underflow(instr))?),
+                            }
+                        }
+                        values.reverse();
+                        let call = b.ins().call(callee, &values);
+                        let results = b.inst_results(call).to_vec();
+                        let (value, ending) = results.split_at(results.len() - 2);
+                        match (returned, result) {
+                            (Some(FfiType::Float { .. }), Some(_)) => flt_stack.push(value[0]),
+                            (Some(FfiType::Str), Some(_)) => str_stack.push((value[0], value[1])),
+                            (Some(_), Some(_)) => int_stack.push(value[0]),
+                            _ => {}
+                        }
+                        let (ended_in_it, next) = (b.create_block(), b.create_block());
+                        b.ins().brif(ending[0], ended_in_it, &[], next, &[]);
+                        b.switch_to_block(ended_in_it);
+                        ends(&mut b, ending[1]);
+                        b.switch_to_block(next);
+                    }
+                    Instruction::Return { result } => {
+                        let value = match result {
+                            None => Vec::new(),
+                            Some(FfiType::Float { .. }) => vec![flt_stack.pop().ok_or_else(|| underflow(instr))?],
+                            Some(FfiType::Str) => {
+                                let (ptr, len) = str_stack.pop().ok_or_else(|| underflow(instr))?;
+                                vec![ptr, len]
+                            }
+                            Some(_) => vec![int_stack.pop().ok_or_else(|| underflow(instr))?],
+                        };
+                        returns(&mut b, &value);
+                        int_stack.clear();
+                        flt_stack.clear();
+                        str_stack.clear();
+                        ended = true;
+                    }
+                    Instruction::Function { name, .. } => {
+                        return Err(BackendError::InvalidIr(format!("`{}` starts inside a function", name)));
+                    }
+                }
+            }
+
+            if !ended {
+                let mut zeros = Vec::new();
+                for ty in result.flatten().into_iter().flat_map(|ty| value_types(ty, ptr_ty)) {
+                    zeros.push(match ty {
+                        types::F64 => b.ins().f64const(0.0),
+                        ty => b.ins().iconst(ty, 0),
+                    });
+                }
+                returns(&mut b, &zeros);
+            }
+            b.seal_all_blocks();
+            b.finalize();
+            jit.define_function(id, &mut ctx).map_err(jit_error)?;
+            jit.clear_context(&mut ctx);
+        }
+
+        // 5. Link
+        jit.finalize_definitions().map_err(jit_error)?;
+        let code = jit.get_finalized_function(main_id);
+        // SAFETY: `tlang_main` was declared with the default (no-argument)
+        // signature for the host calling convention, returning an `i32`.
+        let entry = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(code) };
+
+        Ok(JitProgram {
+            module: Some(jit),
+            entry,
+            _strings: strings,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "cranelift-jit"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            supports_jit: true,
+            ..BackendCapabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check_program, lower_program, parse_source};
+
+    fn jit(source: &str) -> JitProgram {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        CraneliftJitBackend.compile(lower_program(&program, source, "main.t").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn exits_and_panics_return_to_the_host() {
+        let exits = jit("fn main() {\n    process::exit(3);\n    println(\"after\");\n}\n");
+        assert_eq!(exits.run(), 3);
+        // Running it again shows the first run left this process going.
+        assert_eq!(exits.run(), 3);
+        let panics = jit("fn main() {\n    panic(\"stop\");\n}\n");
+        assert_eq!(panics.run(), plugin_api::PANIC_EXIT_CODE);
+        let traps = jit("fn main() {\n    let mut n = 0;\n    while n < 3 {\n        n += 1;\n    }\n    assert(n == 4);\n}\n");
+        assert_eq!(traps.run(), plugin_api::PANIC_EXIT_CODE);
+    }
+
+    #[test]
+    fn collections_changed_at_run_time_call_tstd() {
+        let source = "fn main() -> i32 {\n    let v = Vec::new();\n    let m = HashMap::new();\n    let mut n = 0;\n    while n < 5 {\n        v.push(n * n);\n        m.insert(n, 1);\n        n += 1;\n    }\n    let mut sum = 0;\n    for x in v {\n        sum += x;\n    }\n    sum + m.get(2) + v.len() as i32\n}\n";
+        assert_eq!(jit(source).run(), 36);
+        let out_of_bounds = "fn main() {\n    let v = Vec::new();\n    let mut n = 0;\n    while n < 2 {\n        v.push(n);\n        n += 1;\n    }\n    v.get(n);\n}\n";
+        assert_eq!(jit(out_of_bounds).run(), plugin_api::PANIC_EXIT_CODE);
+    }
+}
@@ -1,41 +1,39 @@
 // File: compiler/src/backends/css/mod.rs
-//! CSS codegen backend: reads our IR debug-text and emits
+//! CSS codegen backend: decodes our IR and emits
 //! a standalone CSS file with each instruction preserved as a comment.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct CssBackend;
 
 impl Backend<CompiledModule> for CssBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = module.decode()?;
 
         // 2. Begin CSS output
         let mut css = String::new();
         css.push_str("/* T-Lang CSS IR (debug) */\n");
 
         // 3. Embed each instruction as a CSS comment
-        for line in ir.lines() {
-            let inst = line.trim();
-            if inst.is_empty() || inst == "Nop" {
+        for instr in &instrs {
+            if *instr == Instruction::Nop {
                 continue;
             }
+            let inst = format!("{:?}", instr);
             css.push_str("/* ");
-            css.push_str(inst);
+            css.push_str(&inst);
             css.push_str(" */\n");
         }
 
         // 4. Add a no-op valid rule to ensure valid CSS
         css.push_str("\nbody { /* T-Lang IR embedded above */ }\n");
 
-        Ok(Box::new(css.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.css", css))
     }
 
     fn name(&self) -> &'static str {
@@ -51,7 +49,7 @@ static CSS_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_CSS_REG: fn() = {
+pub(crate) static FORCE_CSS_REG: fn() = {
     fn init() {
         Lazy::force(&CSS_REG);
     }
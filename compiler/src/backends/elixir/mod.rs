@@ -1,21 +1,19 @@
 // File: compiler/src/backends/elixir/mod.rs
-//! Elixir codegen backend: reads our IR debug-text and emits a standalone Elixir script
+//! Elixir codegen backend: decodes our IR and emits a standalone Elixir script
 //! that replays the instructions on a simple list‐based stack.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct ElixirBackend;
 
 impl Backend<CompiledModule> for ElixirBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Elixir module
         let mut code = String::new();
@@ -24,39 +22,94 @@ impl Backend<CompiledModule> for ElixirBackend {
         code.push_str("    stack = []\n\n");
 
         // 3. Replay each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    stack = [ {} | stack ]\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!(
-                    "    stack = [ \"{}\" | stack ]\n",
-                    esc
-                ));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "    stack = case stack do\n\
-                     \n      [h | t] -> IO.write(to_string(h)); t\n\
-                     \n      []      -> stack\n\
-                     end\n\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    stack = [ {} | stack ]\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    stack = [ {} | stack ]\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    stack = [ {} | stack ]\n", b));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!(
+                        "    stack = [ \"{}\" | stack ]\n",
+                        esc
+                    ));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    stack = case stack do\n\
+                         \n      [h | t] -> IO.write(to_string(h)); t\n\
+                         \n      []      -> stack\n\
+                         end\n\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    [h | stack] = stack\n    IO.write(Integer.to_string(h))\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    [h | stack] = stack\n    IO.write(:io_lib.format(\"~.6f\", [h]))\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    [h | stack] = stack\n    IO.write(h)\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    [h | stack] = stack\n    IO.write(to_string(h))\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    [h | stack] = stack\n    stack = [ h * 1.0 | stack ]\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // BEAM floats are never NaN.
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "    [h | stack] = stack\n    stack = [ (cond do h <= {} -> {}; h >= {} -> {}; true -> trunc(h) end) | stack ]\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    [h | stack] = stack\n    stack = [ Bitwise.bxor(Bitwise.band(h, {}), {}) - {} | stack ]\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("    [h | stack] = stack\n    System.halt(h)\n");
+                }
             }
         }
 
         // 4. End module and invoke
         code.push_str("  end\nend\n\nTlang.main()\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.exs", code)
+            .with_build_command("elixir main.exs"))
     }
 
     fn name(&self) -> &'static str {
@@ -72,7 +125,7 @@ static ELIXIR_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_ELIXIR_REG: fn() = {
+pub(crate) static FORCE_ELIXIR_REG: fn() = {
     fn init() {
         Lazy::force(&ELIXIR_REG);
     }
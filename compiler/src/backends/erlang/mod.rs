@@ -1,22 +1,20 @@
 // File: compiler/src/backends/erlang/mod.rs
-//! Erlang codegen backend: reads our IR debug‐text and emits
+//! Erlang codegen backend: decodes our IR and emits
 //! a standalone Erlang script that replays the instructions on
 //! a simple list‐based stack and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct ErlangBackend;
 
 impl Backend<CompiledModule> for ErlangBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Erlang script
         let mut code = String::new();
@@ -27,39 +25,109 @@ impl Backend<CompiledModule> for ErlangBackend {
 
         // 3. Replay each IR instruction, generating S1, S2, ...
         let mut idx = 0;
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
+        for instr in instrs {
             let next = idx + 1;
             match instr {
-                _ if instr.starts_with("PushInt(") && instr.ends_with(")") => {
-                    let n = &instr["PushInt(".len()..instr.len() - 1];
-                    code.push_str(&format!("    S{n} = [ {n} | S{idx}],\n", n = n, idx = idx));
+                Instruction::Nop => continue,
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    S{next} = [ {n} | S{idx}],\n", next = next, n = n, idx = idx));
+                }
+                Instruction::PushFloat(f) => {
+                    let lit = super::float_literal(f)?;
+                    code.push_str(&format!("    S{next} = [ {lit} | S{idx}],\n", next = next, lit = lit, idx = idx));
                 }
-                _ if instr.starts_with("PushStr(\"") && instr.ends_with("\")") => {
-                    let raw = &instr["PushStr(\"".len()..instr.len() - 2];
-                    let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    S{next} = [ {b} | S{idx}],\n", next = next, b = b, idx = idx));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
                     code.push_str(&format!(
                         "    S{next} = [ \"{esc}\" | S{idx}],\n",
                         next = next,
                         idx = idx,
                         esc = esc
                     ));
-                    idx = next;
-                    continue; // skip increment below since handled
                 }
-                "CallPrint" => {
+                Instruction::CallPrint => {
                     // Pop head and print with ~p
                     code.push_str(&format!(
-                        "    [H|T] = S{idx}, io:format(\"~p\", [H]),\n    S{next} = T,\n",
+                        "    [H{next}|S{next}] = S{idx}, io:format(\"~p\", [H{next}]),\n",
                         idx = idx,
                         next = next
                     ));
                 }
-                other => {
-                    return Err(BackendError::Generic(format!("Unknown IR instr: {}", other)));
+                Instruction::PrintInt
+                | Instruction::PrintFloat
+                | Instruction::PrintStr
+                | Instruction::PrintBool => {
+                    let fmt = match instr {
+                        Instruction::PrintFloat => "~.6f",
+                        Instruction::PrintStr => "~s",
+                        _ => "~w",
+                    };
+                    code.push_str(&format!(
+                        "    [H{next}|S{next}] = S{idx}, io:format(\"{fmt}\", [H{next}]),\n",
+                        idx = idx,
+                        next = next,
+                        fmt = fmt
+                    ));
+                }
+                Instruction::IntToFloat => {
+                    code.push_str(&format!(
+                        "    [H{next}|T{next}] = S{idx}, S{next} = [float(H{next}) | T{next}],\n",
+                        idx = idx,
+                        next = next
+                    ));
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // BEAM floats are never NaN.
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "    [H{next}|T{next}] = S{idx}, S{next} = [if H{next} =< {min_f} -> {min}; H{next} >= {max_f} -> {max}; true -> trunc(H{next}) end | T{next}],\n",
+                        idx = idx,
+                        next = next,
+                        min_f = min_f,
+                        min = min,
+                        max_f = max_f,
+                        max = max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    let Some((mask, sign)) = super::wrap_masks(bits, signed) else {
+                        continue;
+                    };
+                    code.push_str(&format!(
+                        "    [H{next}|T{next}] = S{idx}, S{next} = [((H{next} band {mask}) bxor {sign}) - {sign} | T{next}],\n",
+                        idx = idx,
+                        next = next,
+                        mask = mask,
+                        sign = sign
+                    ));
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str(&format!(
+                        "    [H{next}|S{next}] = S{idx}, halt(H{next}),\n",
+                        idx = idx,
+                        next = next
+                    ));
                 }
             }
             idx = next;
@@ -68,7 +136,9 @@ impl Backend<CompiledModule> for ErlangBackend {
         // 4. Finish function
         code.push_str("    ok.\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "tlang.erl", code)
+            .with_build_command("erlc tlang.erl")
+            .with_build_command("erl -noshell -s tlang main -s init stop"))
     }
 
     fn name(&self) -> &'static str {
@@ -84,7 +154,7 @@ static ERLANG_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_ERLANG_REG: fn() = {
+pub(crate) static FORCE_ERLANG_REG: fn() = {
     fn init() {
         Lazy::force(&ERLANG_REG);
     }
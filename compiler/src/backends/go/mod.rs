@@ -1,68 +1,261 @@
 // File: compiler/src/backends/go/mod.rs
 //! Go codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Go program
-//! that replays the instructions on two simple stacks and prints values.
+//! Decodes our IR and emits a buildable Go module (`main.go` plus `go.mod`)
+//! that replays the instructions on typed stacks and prints values.
+//! Locals are Go variables `local0`, `local1`, ... declared at the top of
+//! their function, and each `Label` a jump goes to is a `tlL<n>:` label it
+//! jumps to with `goto`. Every function of the module but the entry is a
+//! Go function `tlF_<name>` on the same stacks, which pops its arguments
+//! into its first locals and leaves its result on them.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{
+    register_backend, ArithOp, Backend, CompiledArtifact, CompiledModule, BackendError, CompareOp, FfiType,
+    Instruction,
+};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
+use shared::ast::PrimitiveType;
+use std::collections::{HashMap, HashSet};
+
+/// Module path written to the generated `go.mod`.
+const GO_MODULE: &str = "tlang";
+
+/// Minimum Go release the generated code needs.
+const GO_VERSION: &str = "1.21";
+
+/// Go spelling of a T-Lang primitive type.
+///
+/// Go has no 128-bit integers, so `i128` and `u128` are rejected rather
+/// than narrowed to 64 bits.
+pub fn go_type(ty: PrimitiveType) -> Result<&'static str, BackendError> {
+    Ok(match ty {
+        PrimitiveType::I8 => "int8",
+        PrimitiveType::I16 => "int16",
+        PrimitiveType::I32 => "int32",
+        PrimitiveType::I64 => "int64",
+        PrimitiveType::ISize => "int",
+        PrimitiveType::U8 => "uint8",
+        PrimitiveType::U16 => "uint16",
+        PrimitiveType::U32 => "uint32",
+        PrimitiveType::U64 => "uint64",
+        PrimitiveType::USize => "uint",
+        PrimitiveType::F32 => "float32",
+        PrimitiveType::F64 => "float64",
+        PrimitiveType::Bool => "bool",
+        PrimitiveType::Char => "rune",
+        PrimitiveType::Str => "string",
+        PrimitiveType::Unit => "struct{}",
+        PrimitiveType::I128 | PrimitiveType::U128 => {
+            return Err(BackendError::Generic(format!("Go has no 128-bit integer type for {:?}", ty)));
+        }
+    })
+}
+
+/// Pops the top of a stack.
+const POP: &str = r#"
+func tlPop[T any](stack *[]T) T {
+	s := *stack
+	v := s[len(s)-1]
+	*stack = s[:len(s)-1]
+	return v
+}
+"#;
+
+/// What a `Trap` whose condition holds calls: report the panic as
+/// `tlang_panic` does and exit with 101, or with the 134 of an abort, which
+/// Go cannot raise on every platform.
+const TRAP: &str = r#"
+func tlTrap(message, backtrace string, aborts bool) {
+	fmt.Fprintln(os.Stderr, message)
+	if show, ok := os.LookupEnv("TLANG_BACKTRACE"); ok && show != "0" {
+		fmt.Fprintf(os.Stderr, "stack backtrace:\n%s\n", backtrace)
+	} else {
+		fmt.Fprintln(os.Stderr, "note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace")
+	}
+	if aborts {
+		os.Exit(134)
+	}
+	os.Exit(101)
+}
+"#;
+
+/// What a `PrintFloat` calls: `%.6f`, with NaN and the infinities spelled
+/// as every backend spells them, where Go writes `NaN`, `+Inf` and `-Inf`.
+const PRINT_FLOAT: &str = r#"
+func tlPrintFloat(v float64) {
+	switch {
+	case math.IsNaN(v):
+		fmt.Print("NaN")
+	case math.IsInf(v, 1):
+		fmt.Print("inf")
+	case math.IsInf(v, -1):
+		fmt.Print("-inf")
+	default:
+		fmt.Printf("%.6f", v)
+	}
+}
+"#;
+
+/// What an `Overflows` calls: whether the exact result of `a op b` is out
+/// of the range of a `bits`-bit integer, as `ArithOp::overflows` has it.
+const OVERFLOWS: &str = r#"
+func tlOverflows(op byte, a, b int64, bits uint, signed bool) bool {
+	x, y := big.NewInt(a), big.NewInt(b)
+	if !signed && bits == 64 {
+		x.SetUint64(uint64(a))
+		y.SetUint64(uint64(b))
+	}
+	r := new(big.Int)
+	switch op {
+	case '+':
+		r.Add(x, y)
+	case '-':
+		r.Sub(x, y)
+	case '*':
+		r.Mul(x, y)
+	default:
+		if b == 0 {
+			return false
+		}
+		if op == '/' {
+			r.Quo(x, y)
+		} else {
+			r.Rem(x, y)
+		}
+	}
+	max := new(big.Int).Lsh(big.NewInt(1), bits)
+	min := big.NewInt(0)
+	if signed {
+		max.Rsh(max, 1)
+		min.Neg(max)
+	}
+	max.Sub(max, big.NewInt(1))
+	return r.Cmp(min) < 0 || r.Cmp(max) > 0
+}
+"#;
+
+/// Go type of the values carried as `ty`, and the stack they are on.
+fn carried_type(ty: FfiType) -> (&'static str, &'static str) {
+    match ty.carried() {
+        FfiType::Float { .. } => ("float64", "fltStack"),
+        FfiType::Bool => ("bool", "boolStack"),
+        FfiType::Str => ("string", "strStack"),
+        FfiType::Int { .. } => ("int64", "intStack"),
+    }
+}
+
+/// `a op b` of booleans, which Go only compares for equality; `false` is
+/// less than `true`, as `CompareOp::holds` orders them.
+fn bool_comparison(op: CompareOp) -> &'static str {
+    match op {
+        CompareOp::Eq => "a == b",
+        CompareOp::Ne => "a != b",
+        CompareOp::Lt => "!a && b",
+        CompareOp::Le => "!a || b",
+        CompareOp::Gt => "a && !b",
+        CompareOp::Ge => "a || !b",
+    }
+}
+
+/// The packages `instrs` need imported; Go rejects unused imports.
+fn imports(instrs: &[Instruction]) -> String {
+    let uses = |wanted: fn(&Instruction) -> bool| instrs.iter().any(wanted);
+    let mut packages = vec!["fmt"];
+    if uses(|instr| matches!(instr, Instruction::PrintFloat)) {
+        packages.push("math");
+    }
+    if uses(|instr| matches!(instr, Instruction::Overflows { .. })) {
+        packages.push("math/big");
+    }
+    if uses(|instr| matches!(instr, Instruction::Exit | Instruction::Trap { .. })) {
+        packages.push("os");
+    }
+    let mut code = String::from("import (\n");
+    for package in packages {
+        code.push_str(&format!("\t\"{}\"\n", package));
+    }
+    code.push_str(")\n\n");
+    code
+}
+
+/// Contents of the generated `go.mod`.
+fn go_mod() -> String {
+    format!("module {}\n\ngo {}\n", GO_MODULE, GO_VERSION)
+}
 
 #[derive(Debug)]
 pub struct GoBackend;
 
 impl Backend<CompiledModule> for GoBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = module.decode()?;
 
         // 2. Begin Go source
         let mut code = String::new();
         code.push_str("// Generated by T-Lang Go backend\n");
         code.push_str("package main\n\n");
-        code.push_str("import (\n\t\"fmt\"\n)\n\n");
-        code.push_str("func main() {\n");
-        code.push_str("\tvar intStack []int64\n");
-        code.push_str("\tvar strStack []string\n\n");
-
-        // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
+        code.push_str(&imports(&instrs));
+        for (stack, ty) in [
+            ("intStack", PrimitiveType::I64),
+            ("fltStack", PrimitiveType::F64),
+            ("boolStack", PrimitiveType::Bool),
+            ("strStack", PrimitiveType::Str),
+        ] {
+            code.push_str(&format!("var {} []{}\n", stack, go_type(ty)?));
+        }
+
+        // 3. Translate the entry function as `main`, then the others, which
+        // share its stacks and have locals of their own
+        let functions = plugin_api::split_functions(&instrs);
+        let results: HashMap<&str, Option<FfiType>> = functions
+            .iter()
+            .filter_map(|(header, _)| match header {
+                Some(Instruction::Function { name, result, .. }) => Some((name.as_str(), *result)),
+                _ => None,
+            })
+            .collect();
+        for (header, range) in functions {
+            let instrs = &instrs[range];
+            let params: &[FfiType] = match header {
+                Some(Instruction::Function { name, params, .. }) => {
+                    code.push_str(&format!("\nfunc tlF_{}() {{\n", name));
+                    params
+                }
+                _ => {
+                    code.push_str("\nfunc main() {\n");
+                    &[]
+                }
+            };
+            // Go rejects unused locals, so mark every one as used up front.
+            for (slot, ty) in super::local_slots(params, instrs)? {
+                code.push_str(&format!("\tvar local{} {}\n\t_ = local{}\n", slot, carried_type(ty).0, slot));
             }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("\tintStack = append(intStack, {})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!(
-                    "\tstrStack = append(strStack, \"{}\")\n",
-                    esc
-                ));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "\tif len(strStack) > 0 {\n\
-                     \t\tfmt.Print(strStack[len(strStack)-1])\n\
-                     \t\tstrStack = strStack[:len(strStack)-1]\n\
-                     \t} else if len(intStack) > 0 {\n\
-                     \t\tfmt.Print(intStack[len(intStack)-1])\n\
-                     \t\tintStack = intStack[:len(intStack)-1]\n\
-                     \t}\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+            for (slot, &ty) in params.iter().enumerate().rev() {
+                code.push_str(&format!("\tlocal{} = tlPop(&{})\n", slot, carried_type(ty).1));
             }
+            code.push('\n');
+            code.push_str(&self.function_body(instrs, &results)?);
+            code.push_str("}\n");
         }
 
-        // 4. Close main
-        code.push_str("}\n");
+        // 4. Add the helpers the functions call
+        code.push_str(POP);
+        if instrs.iter().any(|instr| matches!(instr, Instruction::Trap { .. })) {
+            code.push_str(TRAP);
+        }
+        if instrs.iter().any(|instr| matches!(instr, Instruction::PrintFloat)) {
+            code.push_str(PRINT_FLOAT);
+        }
+        if instrs.iter().any(|instr| matches!(instr, Instruction::Overflows { .. })) {
+            code.push_str(OVERFLOWS);
+        }
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.go", code)
+            .with_file("go.mod", go_mod().into_bytes())
+            .with_build_command("go build -o main .")
+            .with_build_command("./main"))
     }
 
     fn name(&self) -> &'static str {
@@ -70,6 +263,194 @@ impl Backend<CompiledModule> for GoBackend {
     }
 }
 
+impl GoBackend {
+    /// The Go statements of one function's `instrs`. `results` has the
+    /// result type of each function called.
+    fn function_body(
+        &self,
+        instrs: &[Instruction],
+        results: &HashMap<&str, Option<FfiType>>,
+    ) -> Result<String, BackendError> {
+        // Go rejects labels nothing jumps to.
+        let targets: HashSet<u32> = instrs
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Jump(n) | Instruction::JumpIfFalse(n) => Some(*n),
+                _ => None,
+            })
+            .collect();
+        let mut code = String::new();
+        for instr in instrs.iter().cloned() {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("\tintStack = append(intStack, {})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("\tfltStack = append(fltStack, {})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("\tboolStack = append(boolStack, {})\n", b));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!(
+                        "\tstrStack = append(strStack, \"{}\")\n",
+                        esc
+                    ));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "\tif len(strStack) > 0 {\n\
+                         \t\tfmt.Print(strStack[len(strStack)-1])\n\
+                         \t\tstrStack = strStack[:len(strStack)-1]\n\
+                         \t} else if len(intStack) > 0 {\n\
+                         \t\tfmt.Print(intStack[len(intStack)-1])\n\
+                         \t\tintStack = intStack[:len(intStack)-1]\n\
+                         \t}\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("\tfmt.Print(intStack[len(intStack)-1])\n\tintStack = intStack[:len(intStack)-1]\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("\ttlPrintFloat(tlPop(&fltStack))\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("\tfmt.Print(strStack[len(strStack)-1])\n\tstrStack = strStack[:len(strStack)-1]\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("\tfmt.Print(boolStack[len(boolStack)-1])\n\tboolStack = boolStack[:len(boolStack)-1]\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("\tfltStack = append(fltStack, float64(intStack[len(intStack)-1]))\n\tintStack = intStack[:len(intStack)-1]\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "\t{{\n\
+                         \t\tf := fltStack[len(fltStack)-1]\n\
+                         \t\tfltStack = fltStack[:len(fltStack)-1]\n\
+                         \t\tvar n int64\n\
+                         \t\tswitch {{\n\
+                         \t\tcase f != f:\n\
+                         \t\tcase f <= {}:\n\
+                         \t\t\tn = {}\n\
+                         \t\tcase f >= {}:\n\
+                         \t\t\tn = {}\n\
+                         \t\tdefault:\n\
+                         \t\t\tn = int64(f)\n\
+                         \t\t}}\n\
+                         \t\tintStack = append(intStack, n)\n\
+                         \t}}\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => match super::wrap_masks(bits, signed) {
+                    None => {}
+                    Some((mask, 0)) => {
+                        code.push_str(&format!("\tintStack[len(intStack)-1] &= {}\n", mask));
+                    }
+                    Some((mask, sign)) => code.push_str(&format!(
+                        "\tintStack[len(intStack)-1] = ((intStack[len(intStack)-1] & {}) ^ {}) - {}\n",
+                        mask, sign, sign
+                    )),
+                },
+                Instruction::StoreLocal { slot, ty } => {
+                    code.push_str(&format!("\tlocal{} = tlPop(&{})\n", slot, carried_type(ty).1));
+                }
+                Instruction::LoadLocal { slot, ty } => {
+                    let stack = carried_type(ty).1;
+                    code.push_str(&format!("\t{s} = append({s}, local{})\n", slot, s = stack));
+                }
+                Instruction::Arith { op: ArithOp::Rem, ty: FfiType::Float { .. } } => {
+                    return Err(BackendError::InvalidIr("remainder of floats".to_string()));
+                }
+                Instruction::Arith { op, ty: FfiType::Float { .. } } => {
+                    code.push_str(&format!(
+                        "\t{{\n\t\tb := tlPop(&fltStack)\n\t\tfltStack[len(fltStack)-1] {}= b\n\t}}\n",
+                        op.symbol()
+                    ));
+                }
+                Instruction::Arith { op, .. } => {
+                    // Go integers wrap around; a divisor of zero gives 0, as
+                    // in `ArithOp::int`, where Go would panic.
+                    let value = match op {
+                        ArithOp::Div | ArithOp::Rem => {
+                            format!("\t\tvar r int64\n\t\tif b != 0 {{\n\t\t\tr = a {} b\n\t\t}}\n", op.symbol())
+                        }
+                        _ => format!("\t\tr := a {} b\n", op.symbol()),
+                    };
+                    code.push_str(&format!(
+                        "\t{{\n\t\tb := tlPop(&intStack)\n\t\ta := tlPop(&intStack)\n{}\t\tintStack = append(intStack, r)\n\t}}\n",
+                        value
+                    ));
+                }
+                Instruction::Compare { op, ty } => {
+                    let stack = carried_type(ty).1;
+                    let holds = match ty {
+                        FfiType::Bool => bool_comparison(op).to_string(),
+                        _ => format!("a {} b", op.symbol()),
+                    };
+                    code.push_str(&format!(
+                        "\t{{\n\t\tb := tlPop(&{s})\n\t\ta := tlPop(&{s})\n\t\tboolStack = append(boolStack, {})\n\t}}\n",
+                        holds,
+                        s = stack
+                    ));
+                }
+                Instruction::Not => code.push_str("\tboolStack = append(boolStack, !tlPop(&boolStack))\n"),
+                Instruction::Label(n) => {
+                    if targets.contains(&n) {
+                        code.push_str(&format!("tlL{}:\n", n));
+                    }
+                }
+                Instruction::Jump(n) => code.push_str(&format!("\tgoto tlL{}\n", n)),
+                Instruction::JumpIfFalse(n) => {
+                    code.push_str(&format!("\tif !tlPop(&boolStack) {{\n\t\tgoto tlL{}\n\t}}\n", n));
+                }
+                Instruction::Overflows { op, ty } => {
+                    let FfiType::Int { bits, signed } = ty else {
+                        return Err(BackendError::InvalidIr(format!("cannot execute {:?}", instr)));
+                    };
+                    code.push_str(&format!(
+                        "\t{{\n\t\tb := tlPop(&intStack)\n\t\ta := tlPop(&intStack)\n\t\tboolStack = append(boolStack, tlOverflows('{}', a, b, {}, {}))\n\t}}\n",
+                        op.symbol(),
+                        bits,
+                        signed
+                    ));
+                }
+                Instruction::Trap { message, backtrace, abort } => {
+                    code.push_str(&format!(
+                        "\tif tlPop(&boolStack) {{\n\t\ttlTrap(\"{}\", \"{}\", {})\n\t}}\n",
+                        super::escape_str(&message),
+                        super::escape_str(&backtrace),
+                        abort
+                    ));
+                }
+                Instruction::Call { name, result, .. } => {
+                    code.push_str(&format!("\ttlF_{}()\n", name));
+                    let returns = results.get(name.as_str()).copied().flatten();
+                    if let (Some(ty), None) = (returns, result) {
+                        code.push_str(&format!("\ttlPop(&{})\n", carried_type(ty).1));
+                    }
+                }
+                Instruction::Return { .. } => code.push_str("\treturn\n"),
+                Instruction::Function { name, .. } => {
+                    return Err(BackendError::InvalidIr(format!("`{}` starts inside a function", name)));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("\tos.Exit(int(intStack[len(intStack)-1]))\n");
+                }
+            }
+        }
+        Ok(code)
+    }
+}
+
 // Register this backend at startup
 static GO_REG: Lazy<()> = Lazy::new(|| {
     register_backend(GoBackend);
@@ -78,9 +459,22 @@ static GO_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_GO_REG: fn() = {
+pub(crate) static FORCE_GO_REG: fn() = {
     fn init() {
         Lazy::force(&GO_REG);
     }
     init
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn go_has_no_128_bit_integers() {
+        assert_eq!(go_type(PrimitiveType::I64).unwrap(), "int64");
+        assert_eq!(go_type(PrimitiveType::U64).unwrap(), "uint64");
+        assert!(go_type(PrimitiveType::I128).is_err());
+        assert!(go_type(PrimitiveType::U128).is_err());
+    }
+}
@@ -1,55 +1,70 @@
 // File: compiler/src/backends/haskell/mod.rs
 //! Haskell codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Haskell program
-//! that replays the instructions on two simple stacks and prints values.
+//! Decodes our IR and emits a standalone Haskell program
+//! that replays the instructions on three simple stacks (ints, floats, strings) and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct HaskellBackend;
 
 impl Backend<CompiledModule> for HaskellBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Haskell source
         let mut code = String::new();
         code.push_str("module Main where\n\n");
-        code.push_str("import Data.List (isPrefixOf)\n\n");
+        code.push_str("import Data.List (isPrefixOf)\n");
+        code.push_str("import Text.Printf (printf)\n\n");
         code.push_str("main :: IO ()\n");
-        code.push_str("main = evaluate [] [] ir\n\n");
+        code.push_str("main = evaluate [] [] [] ir\n\n");
         code.push_str("  where\n");
         code.push_str("    ir :: [String]\n");
         code.push_str("    ir = [\n");
-        for line in ir.lines() {
-            let esc = line.replace('\\', "\\\\").replace('"', "\\\"");
+        // The program interprets the debug spelling of each instruction at run time.
+        for instr in instrs.iter().filter(|i| **i != Instruction::Nop) {
+            let esc = super::escape_str(&format!("{:?}", instr));
             code.push_str(&format!("      \"{}\",\n", esc));
         }
         code.push_str("      ]\n\n");
-        code.push_str("evaluate :: [Integer] -> [String] -> [String] -> IO ()\n");
-        code.push_str("evaluate _ _ [] = return ()\n");
-        code.push_str("evaluate ints strs (instr:rest)\n");
+        code.push_str("evaluate :: [Integer] -> [Double] -> [String] -> [String] -> IO ()\n");
+        code.push_str("evaluate _ _ _ [] = return ()\n");
+        code.push_str("evaluate ints flts strs (instr:rest)\n");
         code.push_str("  | \"PushInt(\" `isPrefixOf` instr =\n");
         code.push_str("      let n = read (takeWhile (/=')') (drop 8 instr)) :: Integer\n");
-        code.push_str("      in evaluate (n:ints) strs rest\n\n");
+        code.push_str("      in evaluate (n:ints) flts strs rest\n\n");
+        code.push_str("  | \"PushFloat(\" `isPrefixOf` instr =\n");
+        code.push_str("      let f = read (takeWhile (/=')') (drop 10 instr)) :: Double\n");
+        code.push_str("      in evaluate ints (f:flts) strs rest\n\n");
+        code.push_str("  | \"PushBool(\" `isPrefixOf` instr =\n");
+        code.push_str("      let b = if takeWhile (/=')') (drop 9 instr) == \"true\" then 1 else 0\n");
+        code.push_str("      in evaluate (b:ints) flts strs rest\n\n");
         code.push_str("  | \"PushStr(\\\"\" `isPrefixOf` instr =\n");
-        code.push_str("      let s = read (drop 8 instr) :: String\n");
-        code.push_str("      in evaluate ints (s:strs) rest\n\n");
+        code.push_str("      let s = read (init (drop 8 instr)) :: String\n");
+        code.push_str("      in evaluate ints flts (s:strs) rest\n\n");
         code.push_str("  | instr == \"CallPrint\" = do\n");
         code.push_str("      case strs of\n");
-        code.push_str("        (s:ss) -> putStr s >> evaluate ints ss rest\n");
+        code.push_str("        (s:ss) -> putStr s >> evaluate ints flts ss rest\n");
         code.push_str("        [] -> case ints of\n");
-        code.push_str("          (i:is) -> putStr (show i) >> evaluate is strs rest\n");
-        code.push_str("          []     -> evaluate ints strs rest\n\n");
-        code.push_str("  | otherwise = evaluate ints strs rest\n");
+        code.push_str("          (i:is) -> putStr (show i) >> evaluate is flts strs rest\n");
+        code.push_str("          []     -> evaluate ints flts strs rest\n\n");
+        code.push_str("  | instr == \"PrintInt\", (i:is) <- ints =\n");
+        code.push_str("      putStr (show i) >> evaluate is flts strs rest\n");
+        code.push_str("  | instr == \"PrintFloat\", (f:fs) <- flts =\n");
+        code.push_str("      printf \"%.6f\" f >> evaluate ints fs strs rest\n");
+        code.push_str("  | instr == \"PrintStr\", (s:ss) <- strs =\n");
+        code.push_str("      putStr s >> evaluate ints flts ss rest\n");
+        code.push_str("  | instr == \"PrintBool\", (b:bs) <- ints =\n");
+        code.push_str("      putStr (if b /= 0 then \"true\" else \"false\") >> evaluate bs flts strs rest\n\n");
+        code.push_str("  | otherwise = evaluate ints flts strs rest\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "Main.hs", code)
+            .with_build_command("runghc Main.hs"))
     }
 
     fn name(&self) -> &'static str {
@@ -65,7 +80,7 @@ static HASKELL_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_HASKELL_REG: fn() = {
+pub(crate) static FORCE_HASKELL_REG: fn() = {
     fn init() {
         Lazy::force(&HASKELL_REG);
     }
@@ -1,22 +1,20 @@
 // File: compiler/src/backends/html/mod.rs
 //! HTML codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone HTML document
+//! Decodes our IR and emits a standalone HTML document
 //! embedding each instruction as an HTML comment and displaying them in a <pre> block.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct HtmlBackend;
 
 impl Backend<CompiledModule> for HtmlBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = module.decode()?;
 
         // 2. Begin HTML document
         let mut html = String::new();
@@ -28,23 +26,22 @@ impl Backend<CompiledModule> for HtmlBackend {
         html.push_str("  <!-- T-Lang IR embedded as comments -->\n");
 
         // 3. Embed each IR instruction as an HTML comment
-        for line in ir.lines() {
-            let inst = line.trim();
-            if inst.is_empty() || inst == "Nop" {
+        for instr in &instrs {
+            if *instr == Instruction::Nop {
                 continue;
             }
+            let inst = format!("{:?}", instr);
             html.push_str("  <!-- ");
-            html.push_str(inst);
+            html.push_str(&inst);
             html.push_str(" -->\n");
         }
 
         // 4. Display IR in a <pre> block for readability
         html.push_str("  <pre>\n");
-        for line in ir.lines() {
-            let inst = line.trim();
-            if !inst.is_empty() {
+        for instr in &instrs {
+            if *instr != Instruction::Nop {
                 html.push_str("    ");
-                html.push_str(inst);
+                html.push_str(&format!("{:?}", instr));
                 html.push('\n');
             }
         }
@@ -52,7 +49,7 @@ impl Backend<CompiledModule> for HtmlBackend {
 
         html.push_str("</body>\n</html>\n");
 
-        Ok(Box::new(html.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "index.html", html))
     }
 
     fn name(&self) -> &'static str {
@@ -68,7 +65,7 @@ static HTML_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_HTML_REG: fn() = {
+pub(crate) static FORCE_HTML_REG: fn() = {
     fn init() {
         Lazy::force(&HTML_REG);
     }
@@ -1,56 +1,111 @@
 // File: compiler/src/backends/java/mod.rs
 //! Java codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Java class
+//! Decodes our IR and emits a standalone Java class
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct JavaBackend;
 
 impl Backend<CompiledModule> for JavaBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Java source
         let mut code = String::new();
         code.push_str("public class TLang {\n");
         code.push_str("    public static void main(String[] args) {\n");
         code.push_str("        java.util.List<Long> intStack = new java.util.ArrayList<>();\n");
+        code.push_str("        java.util.List<Double> fltStack = new java.util.ArrayList<>();\n");
         code.push_str("        java.util.List<String> strStack = new java.util.ArrayList<>();\n\n");
 
         // 3. Replay each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("        intStack.add({}L);\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("        strStack.add(\"{}\");\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "        if (!strStack.isEmpty()) {\n\
-                     \n            String s = strStack.remove(strStack.size()-1);\n\
-                     \n            System.out.print(s);\n\
-                     \n        } else if (!intStack.isEmpty()) {\n\
-                     \n            Long v = intStack.remove(intStack.size()-1);\n\
-                     \n            System.out.print(v);\n\
-                     \n        }\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("        intStack.add({}L);\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("        fltStack.add({});\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("        intStack.add({}L);\n", b as i64));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("        strStack.add(\"{}\");\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "        if (!strStack.isEmpty()) {\n\
+                         \n            String s = strStack.remove(strStack.size()-1);\n\
+                         \n            System.out.print(s);\n\
+                         \n        } else if (!intStack.isEmpty()) {\n\
+                         \n            Long v = intStack.remove(intStack.size()-1);\n\
+                         \n            System.out.print(v);\n\
+                         \n        }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("        System.out.print(intStack.remove(intStack.size()-1));\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("        System.out.print(String.format(java.util.Locale.ROOT, \"%.6f\", fltStack.remove(fltStack.size()-1)));\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("        System.out.print(strStack.remove(strStack.size()-1));\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("        System.out.print(intStack.remove(intStack.size()-1) != 0);\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("        fltStack.add(intStack.remove(intStack.size()-1).doubleValue());\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // `longValue` already truncates, saturates at the long range and maps NaN to 0.
+                    let (min, max) = plugin_api::int_bounds(bits, signed);
+                    code.push_str(&format!(
+                        "        intStack.add(Math.max({}L, Math.min({}L, fltStack.remove(fltStack.size()-1).longValue())));\n",
+                        min, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "        intStack.set(intStack.size()-1, ((intStack.get(intStack.size()-1) & {}L) ^ {}L) - {}L);\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("        System.out.flush();\n");
+                    code.push_str("        System.exit(intStack.remove(intStack.size()-1).intValue());\n");
+                }
             }
         }
 
@@ -58,7 +113,9 @@ impl Backend<CompiledModule> for JavaBackend {
         code.push_str("    }\n");
         code.push_str("}\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "TLang.java", code)
+            .with_build_command("javac TLang.java")
+            .with_build_command("java TLang"))
     }
 
     fn name(&self) -> &'static str {
@@ -74,7 +131,7 @@ static JAVA_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_JAVA_REG: fn() = {
+pub(crate) static FORCE_JAVA_REG: fn() = {
     fn init() {
         Lazy::force(&JAVA_REG);
     }
@@ -1,49 +1,106 @@
 // File: compiler/src/backends/julia/mod.rs
-//! Julia codegen backend: reads our IR debug-text and emits a standalone Julia script
+//! Julia codegen backend: decodes our IR and emits a standalone Julia script
 //! that replays the instructions on a simple stack.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct JuliaBackend;
 
 impl Backend<CompiledModule> for JuliaBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Julia script
         let mut code = String::new();
+        code.push_str("using Printf\n\n");
         code.push_str("function main()\n");
         code.push_str("    stack = Any[]\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    push!(stack, {})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                // escape backslashes and quotes
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("    push!(stack, \"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str("    if !isempty(stack)\n");
-                code.push_str("        v = pop!(stack)\n");
-                code.push_str("        print(v)\n");
-                code.push_str("    end\n");
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    push!(stack, {})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    push!(stack, {})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    push!(stack, {})\n", b));
+                }
+                Instruction::PushStr(s) => {
+                    // escape backslashes and quotes
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("    push!(stack, \"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str("    if !isempty(stack)\n");
+                    code.push_str("        v = pop!(stack)\n");
+                    code.push_str("        print(v)\n");
+                    code.push_str("    end\n");
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    print(pop!(stack))\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    @printf(\"%.6f\", pop!(stack))\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    print(pop!(stack))\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    print(pop!(stack))\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    push!(stack, Float64(pop!(stack)))\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str("    f = pop!(stack)\n");
+                    code.push_str(&format!(
+                        "    push!(stack, isnan(f) ? 0 : f <= {} ? {} : f >= {} ? {} : trunc(Int64, f))\n",
+                        min_f,
+                        super::int_literal(min),
+                        max_f,
+                        max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    push!(stack, xor(pop!(stack) & {}, {}) - {})\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("    exit(pop!(stack))\n");
+                }
             }
         }
 
@@ -51,7 +108,8 @@ impl Backend<CompiledModule> for JuliaBackend {
         code.push_str("end\n\n");
         code.push_str("main()\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.jl", code)
+            .with_build_command("julia main.jl"))
     }
 
     fn name(&self) -> &'static str {
@@ -67,7 +125,7 @@ static JULIA_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_JULIA_REG: fn() = {
+pub(crate) static FORCE_JULIA_REG: fn() = {
     fn init() {
         Lazy::force(&JULIA_REG);
     }
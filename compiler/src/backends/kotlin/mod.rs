@@ -1,61 +1,119 @@
 // File: compiler/src/backends/kotlin/mod.rs
 //! Kotlin codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Kotlin program
+//! Decodes our IR and emits a standalone Kotlin program
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct KotlinBackend;
 
 impl Backend<CompiledModule> for KotlinBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Kotlin source
         let mut code = String::new();
         code.push_str("// Generated by T-Lang Kotlin backend\n");
         code.push_str("fun main() {\n");
         code.push_str("    val intStack = mutableListOf<Long>()\n");
+        code.push_str("    val fltStack = mutableListOf<Double>()\n");
         code.push_str("    val strStack = mutableListOf<String>()\n\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    intStack.add({})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("    strStack.add(\"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "    if (strStack.isNotEmpty()) {\n\
-                     \n        print(strStack.removeAt(strStack.size - 1))\n\
-                     \n    } else if (intStack.isNotEmpty()) {\n\
-                     \n        print(intStack.removeAt(intStack.size - 1))\n\
-                     \n    }\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    intStack.add({})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    fltStack.add({})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    intStack.add({})\n", b as i64));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("    strStack.add(\"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    if (strStack.isNotEmpty()) {\n\
+                         \n        print(strStack.removeAt(strStack.size - 1))\n\
+                         \n    } else if (intStack.isNotEmpty()) {\n\
+                         \n        print(intStack.removeAt(intStack.size - 1))\n\
+                         \n    }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    print(intStack.removeAt(intStack.size - 1))\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    print(String.format(java.util.Locale.ROOT, \"%.6f\", fltStack.removeAt(fltStack.size - 1)))\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    print(strStack.removeAt(strStack.size - 1))\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    print(intStack.removeAt(intStack.size - 1) != 0L)\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    fltStack.add(intStack.removeAt(intStack.size - 1).toDouble())\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // `toLong` already truncates, saturates at the Long range and maps NaN to 0.
+                    let (min, max) = plugin_api::int_bounds(bits, signed);
+                    code.push_str(&format!(
+                        "    intStack.add(fltStack.removeAt(fltStack.size - 1).toLong().coerceIn({}, {}))\n",
+                        super::int_literal(min),
+                        max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    intStack[intStack.size - 1] = ((intStack[intStack.size - 1] and {}) xor {}) - {}\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("    System.out.flush()\n");
+                    code.push_str("    kotlin.system.exitProcess(intStack.removeAt(intStack.size - 1).toInt())\n");
+                }
             }
         }
 
         // 4. Close main
         code.push_str("}\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.kt", code)
+            .with_build_command("kotlinc main.kt -include-runtime -d main.jar")
+            .with_build_command("java -jar main.jar"))
     }
 
     fn name(&self) -> &'static str {
@@ -71,7 +129,7 @@ static KOTLIN_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_KOTLIN_REG: fn() = {
+pub(crate) static FORCE_KOTLIN_REG: fn() = {
     fn init() {
         Lazy::force(&KOTLIN_REG);
     }
@@ -1,52 +1,953 @@
 // File: compiler/src/backends/llvm_backend/mod.rs
 //! LLVM IR backend for T-Lang.
-//! Reads our IR debug-text and emits a minimal LLVM IR module
-//! with each T-Lang IR instruction preserved as an LLVM comment.
+//! Lifts the module's TIR (`CompiledModule::tir`) and emits an LLVM IR
+//! module whose `main` is the entry function: each TIR value becomes an
+//! LLVM value `%v<n>`, integers as `i64`, floats as `double`, booleans as
+//! `i1` and strings as `i8*`, with constants written inline. Each TIR local
+//! is an `alloca` `%l<n>` at the top of `main`, and block `n` is `bb<n>`.
+//! A `Switch` is an LLVM `switch`, or, with cases dense enough for a jump
+//! table, an `indirectbr` through a table of the blocks' addresses.
+//! A `Trap` whose condition holds calls `tlang_trap`, which reports the
+//! panic as the C backend's does. Every other function of the module is a
+//! private function `@tl.<name>`, whose parameters are stored in its first
+//! locals and which returns its result local; an `Exit` in one calls `exit`.
+//!
+//! A target triple in the `BackendConfig` is written to the module header.
+//! When the module carries `DebugInfo`, it gets DWARF metadata (compile
+//! unit, a subprogram per function and one location per line table row) for
+//! the `.t` file, and each instruction the location of the line it is on.
+//! Extern functions are declared with their C signatures, so the module
+//! links against the libraries that define them, and a `CallExtern`
+//! converts its arguments to their C types; a string it returns is copied,
+//! since the function may reuse its buffer. Globals are `internal`
+//! variables, `constant` unless they are mutable; a string global points to
+//! a private array holding its bytes.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{
+    register_backend, Backend, BackendCapabilities, BackendConfig, CompiledArtifact,
+    CompiledModule, BackendError, Constant, DebugInfo, ExternFunction, FfiType, Global,
+};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
+use shared::tir::{ArithOp, BlockId, CompareOp, Function, InstId, InstKind, Module, Terminator};
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+
+/// Metadata id of the first `DILocation`; the ones before are fixed nodes.
+const FIRST_LOCATION: usize = 7;
+
+/// Escape a string for an LLVM metadata string literal.
+fn llvm_str(s: &str) -> String {
+    s.replace('\\', "\\5C").replace('"', "\\22")
+}
+
+/// The LLVM type values of `ty` are passed as, and the extension attribute
+/// the C ABI gives integers narrower than `int`.
+fn llvm_type(ty: FfiType) -> Result<(&'static str, Option<&'static str>), BackendError> {
+    let extension = |signed| Some(if signed { "signext" } else { "zeroext" });
+    Ok(match ty {
+        FfiType::Int { bits: 8, signed } => ("i8", extension(signed)),
+        FfiType::Int { bits: 16, signed } => ("i16", extension(signed)),
+        FfiType::Int { bits: 32, .. } => ("i32", None),
+        FfiType::Int { bits: 64, .. } => ("i64", None),
+        FfiType::Float { bits: 32 } => ("float", None),
+        FfiType::Float { bits: 64 } => ("double", None),
+        FfiType::Bool => ("i1", Some("zeroext")),
+        FfiType::Str => ("i8*", None),
+        other => return Err(BackendError::InvalidIr(format!("no C type for {:?}", other))),
+    })
+}
+
+/// `declare` line for `function`; attributes follow a parameter's type but
+/// precede the return type.
+fn declaration(function: &ExternFunction) -> Result<String, BackendError> {
+    let mut params = Vec::new();
+    for &ty in &function.params {
+        params.push(match llvm_type(ty)? {
+            (ty, Some(attr)) => format!("{} {}", ty, attr),
+            (ty, None) => ty.to_string(),
+        });
+    }
+    if function.variadic {
+        params.push("...".to_string());
+    }
+    let ret = match function.return_type.map(llvm_type).transpose()? {
+        Some((ty, Some(attr))) => format!("{} {}", attr, ty),
+        Some((ty, None)) => ty.to_string(),
+        None => "void".to_string(),
+    };
+    Ok(format!("declare {} @{}({})\n", ret, function.name, params.join(", ")))
+}
+
+/// Definition of `global`, after the array of its bytes if it is a string.
+fn definition(global: &Global) -> Result<String, BackendError> {
+    let (ty, _) = llvm_type(global.ty)?;
+    let kind = if global.mutable { "global" } else { "constant" };
+    let (bytes, value) = match &global.value {
+        Constant::Int(n) => (None, n.to_string()),
+        // Float constants are spelled as the bits of the nearest `double`.
+        Constant::Float(f) if global.ty == (FfiType::Float { bits: 32 }) => {
+            (None, float_bits(*f as f32 as f64))
+        }
+        Constant::Float(f) => (None, float_bits(*f)),
+        Constant::Bool(b) => (None, b.to_string()),
+        Constant::Str(s) => {
+            let array = format!("[{} x i8]", s.len() + 1);
+            let bytes = format!(
+                "@{}.str = private unnamed_addr constant {} c\"{}\\00\"\n",
+                global.name,
+                array,
+                llvm_bytes(s)
+            );
+            let pointer = format!("getelementptr inbounds ({0}, {0}* @{1}.str, i64 0, i64 0)", array, global.name);
+            (Some(bytes), pointer)
+        }
+    };
+    Ok(format!("{}@{} = internal {} {} {}\n", bytes.unwrap_or_default(), global.name, kind, ty, value))
+}
+
+/// Escape `s` for an LLVM `c"..."` array: bytes other than printable ASCII,
+/// `"` and `\` become `\XX`.
+fn llvm_bytes(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            0x20..=0x7e if b != b'"' && b != b'\\' => (b as char).to_string(),
+            _ => format!("\\{:02X}", b),
+        })
+        .collect()
+}
+
+/// DWARF metadata nodes for `debug`, which has a source file and a line
+/// table, and the functions of `tir`.
+///
+/// Row `k` of the line table becomes `!DILocation` number `FIRST_LOCATION + k`,
+/// in the subprogram of the function whose `Marker` it is. `main` is `!4`,
+/// and the functions after the entry follow the locations.
+fn dwarf_metadata(debug: &DebugInfo, file: &str, tir: &Module) -> String {
+    let path = Path::new(file);
+    let filename = path.file_name().map_or(file.into(), |f| f.to_string_lossy());
+    let directory = match path.parent().map(|p| p.to_string_lossy()) {
+        Some(dir) if !dir.is_empty() => dir,
+        _ => ".".into(),
+    };
+    let main_line = debug
+        .functions
+        .iter()
+        .find(|f| f.name == "main")
+        .or(debug.functions.first())
+        .map_or(debug.line_info[0].line, |f| f.line);
+
+    let mut meta = String::from("\n!llvm.dbg.cu = !{!0}\n!llvm.module.flags = !{!2, !3}\n\n");
+    meta.push_str(
+        "!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: \"T-Lang\", \
+         isOptimized: false, runtimeVersion: 0, emissionKind: FullDebug)\n",
+    );
+    meta.push_str(&format!(
+        "!1 = !DIFile(filename: \"{}\", directory: \"{}\")\n",
+        llvm_str(&filename),
+        llvm_str(&directory)
+    ));
+    meta.push_str("!2 = !{i32 7, !\"Dwarf Version\", i32 5}\n");
+    meta.push_str("!3 = !{i32 2, !\"Debug Info Version\", i32 3}\n");
+    meta.push_str(&format!(
+        "!4 = distinct !DISubprogram(name: \"main\", scope: !1, file: !1, line: {0}, type: !5, \
+         scopeLine: {0}, spFlags: DISPFlagDefinition, unit: !0)\n",
+        main_line
+    ));
+    meta.push_str("!5 = !DISubroutineType(types: !6)\n!6 = !{null}\n");
+    let mut scopes = vec![4; debug.line_info.len()];
+    for (i, function) in tir.functions.iter().enumerate().skip(1) {
+        let scope = FIRST_LOCATION + debug.line_info.len() + i - 1;
+        for inst in &function.insts {
+            if let InstKind::Marker(n) = inst.kind
+                && let Some(slot) = scopes.get_mut(n as usize)
+            {
+                *slot = scope;
+            }
+        }
+    }
+    for (k, row) in debug.line_info.iter().enumerate() {
+        meta.push_str(&format!(
+            "!{} = !DILocation(line: {}, column: {}, scope: !{})\n",
+            FIRST_LOCATION + k,
+            row.line,
+            row.column,
+            scopes[k]
+        ));
+    }
+    for (i, function) in tir.functions.iter().enumerate().skip(1) {
+        let line = debug.functions.iter().find(|f| f.name == function.name).map_or(main_line, |f| f.line);
+        meta.push_str(&format!(
+            "!{0} = distinct !DISubprogram(name: \"{1}\", scope: !1, file: !1, line: {2}, type: !5, \
+             scopeLine: {2}, spFlags: DISPFlagDefinition, unit: !0)\n",
+            FIRST_LOCATION + debug.line_info.len() + i - 1,
+            llvm_str(&function.name),
+            line
+        ));
+    }
+    meta
+}
+
+/// The C library functions translated code calls, with their declarations.
+/// Each is declared if something calls it and the program does not declare
+/// it itself.
+const RUNTIME: &[(&str, &str)] = &[
+    ("printf", "declare i32 @printf(i8*, ...)\n"),
+    ("strdup", "declare i8* @strdup(i8*)\n"),
+    ("getenv", "declare i8* @getenv(i8*)\n"),
+    ("strcmp", "declare i32 @strcmp(i8*, i8*)\n"),
+    ("fflush", "declare i32 @fflush(i8*)\n"),
+    ("dprintf", "declare i32 @dprintf(i32, i8*, ...)\n"),
+    ("abort", "declare void @abort()\n"),
+    ("exit", "declare void @exit(i32)\n"),
+];
+
+/// The LLVM type a value of `ty` is held in.
+fn value_type(ty: FfiType) -> &'static str {
+    match ty {
+        FfiType::Int { .. } => "i64",
+        FfiType::Float { .. } => "double",
+        FfiType::Bool => "i1",
+        FfiType::Str => "i8*",
+    }
+}
+
+/// Spell a float constant as the bits of the nearest `double`, the one
+/// spelling LLVM reads for every value.
+fn float_bits(f: f64) -> String {
+    format!("0x{:016X}", f.to_bits())
+}
+
+/// The private arrays holding the strings a module uses, each once.
+#[derive(Default)]
+struct Strings(Vec<String>);
+
+impl Strings {
+    /// An `i8*` constant pointing to the first byte of `s`.
+    fn pointer(&mut self, s: &str) -> String {
+        let i = match self.0.iter().position(|t| t == s) {
+            Some(i) => i,
+            None => {
+                self.0.push(s.to_string());
+                self.0.len() - 1
+            }
+        };
+        format!("getelementptr inbounds ([{0} x i8], [{0} x i8]* @.str.{1}, i64 0, i64 0)", s.len() + 1, i)
+    }
+
+    fn definitions(&self) -> String {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let array = format!("[{} x i8]", s.len() + 1);
+                format!("@.str.{} = private unnamed_addr constant {} c\"{}\\00\"\n", i, array, llvm_bytes(s))
+            })
+            .collect()
+    }
+}
+
+/// Translates a function of the module: the entry function into the body
+/// of `main`, and the others into the bodies of theirs.
+struct Body<'m> {
+    module: &'m CompiledModule,
+    tir: &'m Module,
+    function: &'m Function,
+    /// The debug info to give instructions locations from, if there is a file
+    debug: Option<&'m DebugInfo>,
+    /// Values something reads
+    used: HashSet<InstId>,
+    code: String,
+    /// `, !dbg !<n>` for the source line being translated, once there is one
+    location: String,
+    /// Number of the next `%t<n>`, for the values TIR has no instruction for
+    temps: usize,
+    strings: Strings,
+    /// Names of the `RUNTIME` functions called
+    runtime: BTreeSet<&'static str>,
+    /// Declarations of the intrinsics called
+    intrinsics: BTreeSet<String>,
+    /// Jump tables, as private globals
+    tables: String,
+}
+
+impl<'m> Body<'m> {
+    fn new(module: &'m CompiledModule, tir: &'m Module, debug: Option<&'m DebugInfo>) -> Self {
+        let function = &tir.functions[0];
+        Body {
+            module,
+            tir,
+            function,
+            debug,
+            used: function.used_values(),
+            code: String::new(),
+            location: String::new(),
+            temps: 0,
+            strings: Strings::default(),
+            runtime: BTreeSet::new(),
+            intrinsics: BTreeSet::new(),
+            tables: String::new(),
+        }
+    }
+
+    /// Translate `function` next, keeping the strings, declarations and
+    /// tables gathered so far.
+    fn next(&mut self, function: &'m Function) -> String {
+        self.function = function;
+        self.used = function.used_values();
+        self.location.clear();
+        self.temps = 0;
+        std::mem::take(&mut self.code)
+    }
+
+    /// Whether the function is the entry, which is `main`.
+    fn entry(&self) -> bool {
+        std::ptr::eq(self.function, &self.tir.functions[0])
+    }
+
+    /// The LLVM name of the function translated.
+    fn symbol(&self) -> String {
+        if self.entry() {
+            "@main".to_string()
+        } else {
+            symbol(&self.function.name)
+        }
+    }
+
+    /// The jump table of the `Switch` ending block `id`.
+    fn table(&self, id: BlockId) -> String {
+        if self.entry() {
+            format!("@.table.{}", id.0)
+        } else {
+            format!("@.table.{}.{}", self.function.name, id.0)
+        }
+    }
+
+    fn ty(&self, value: InstId) -> Result<FfiType, BackendError> {
+        self.function
+            .ty(value)
+            .ok_or_else(|| BackendError::InvalidIr(format!("{:?} has no value to read", value)))
+    }
+
+    /// `value` as an LLVM operand: its literal for a constant, else `%v<n>`.
+    fn operand(&mut self, value: InstId) -> String {
+        match &self.function.inst(value).kind {
+            InstKind::Const(Constant::Int(n)) => n.to_string(),
+            InstKind::Const(Constant::Float(f)) => float_bits(*f),
+            InstKind::Const(Constant::Bool(b)) => b.to_string(),
+            InstKind::Const(Constant::Str(s)) => self.strings.pointer(s),
+            _ => format!("%v{}", value.0),
+        }
+    }
+
+    /// `value` with its type, as an argument is written.
+    fn typed(&mut self, value: InstId) -> Result<String, BackendError> {
+        Ok(format!("{} {}", value_type(self.ty(value)?), self.operand(value)))
+    }
+
+    /// Append the instruction `text`, at the current source line.
+    fn line(&mut self, text: &str) {
+        self.code.push_str(&format!("  {}{}\n", text, self.location));
+    }
+
+    /// `%v<n> = expr`.
+    fn define(&mut self, inst: InstId, expr: &str) {
+        self.line(&format!("%v{} = {}", inst.0, expr));
+    }
+
+    /// A new `%t<n>` defined as `expr`.
+    fn temp(&mut self, expr: &str) -> String {
+        let name = format!("%t{}", self.temps);
+        self.temps += 1;
+        self.line(&format!("{} = {}", name, expr));
+        name
+    }
+
+    /// Translate the whole function: the locals, then each block.
+    fn function_body(&mut self) -> Result<(), BackendError> {
+        let live = self.function.live_insts();
+        self.code.push_str("bb0:\n");
+        for (i, &ty) in self.function.locals.iter().enumerate() {
+            let zero = match ty {
+                _ if i < self.function.params => format!("%a{}", i),
+                FfiType::Int { .. } => "0".to_string(),
+                FfiType::Float { .. } => "0.0".to_string(),
+                FfiType::Bool => "false".to_string(),
+                FfiType::Str => self.strings.pointer(""),
+            };
+            let ty = value_type(ty);
+            self.line(&format!("%l{} = alloca {}", i, ty));
+            self.line(&format!("store {0} {1}, {0}* %l{2}", ty, zero, i));
+        }
+        for (id, block) in self.function.iter_blocks() {
+            if id != BlockId::ENTRY {
+                self.code.push_str(&format!("bb{}:\n", id.0));
+            }
+            for &inst in &block.insts {
+                match self.function.inst(inst).kind {
+                    InstKind::Marker(n) => {
+                        let Some(row) = self.debug.and_then(|debug| debug.line_info.get(n as usize)) else {
+                            continue;
+                        };
+                        self.code.push_str(&format!("  ; line {}, column {}\n", row.line, row.column));
+                        self.location = format!(", !dbg !{}", FIRST_LOCATION + n as usize);
+                    }
+                    _ if live.contains(&inst) => self.inst(inst)?,
+                    _ => {}
+                }
+            }
+            self.terminator(id, &block.terminator)?;
+        }
+        Ok(())
+    }
+
+    /// The `printf` format printing the double `v`: `%.6f`, or NaN or an
+    /// infinity spelled as every backend spells them, where C writes `nan`.
+    fn float_format(&mut self, v: &str) -> String {
+        let mut format = self.strings.pointer("%.6f");
+        for (text, test) in [
+            ("-inf", format!("fcmp oeq double {}, {}", v, float_bits(f64::NEG_INFINITY))),
+            ("inf", format!("fcmp oeq double {}, {}", v, float_bits(f64::INFINITY))),
+            ("NaN", format!("fcmp uno double {}, {}", v, v)),
+        ] {
+            let holds = self.temp(&test);
+            let text = self.strings.pointer(text);
+            format = self.temp(&format!("select i1 {}, i8* {}, i8* {}", holds, text, format));
+        }
+        format
+    }
+
+    /// Translate the instruction `inst`.
+    fn inst(&mut self, inst: InstId) -> Result<(), BackendError> {
+        match &self.function.inst(inst).kind {
+            InstKind::Const(_) | InstKind::Marker(_) => {}
+            InstKind::Print(value) => {
+                let v = self.operand(*value);
+                let (format, arg) = match self.ty(*value)? {
+                    FfiType::Int { .. } => (self.strings.pointer("%lld"), format!("i64 {}", v)),
+                    FfiType::Float { .. } => (self.float_format(&v), format!("double {}", v)),
+                    FfiType::Bool => {
+                        let (t, f) = (self.strings.pointer("true"), self.strings.pointer("false"));
+                        let arg = format!("i8* {}", self.temp(&format!("select i1 {}, i8* {}, i8* {}", v, t, f)));
+                        (self.strings.pointer("%s"), arg)
+                    }
+                    FfiType::Str => (self.strings.pointer("%s"), format!("i8* {}", v)),
+                };
+                self.runtime.insert("printf");
+                self.line(&format!("call i32 (i8*, ...) @printf(i8* {}, {})", format, arg));
+            }
+            InstKind::Cast { value, to } => self.cast(inst, *value, *to)?,
+            InstKind::CallExtern { name, args, types } => self.call_extern(inst, name, args, types)?,
+            InstKind::Call { function, args } => {
+                let callee = self
+                    .tir
+                    .function(function)
+                    .ok_or_else(|| BackendError::InvalidIr(format!("no function {}", function)))?;
+                let args = args.iter().map(|&arg| self.typed(arg)).collect::<Result<Vec<_>, _>>()?;
+                let call = format!(
+                    "call {} {}({})",
+                    callee.result_type().map_or("void", value_type),
+                    symbol(function),
+                    args.join(", ")
+                );
+                match self.function.ty(inst).filter(|_| self.used.contains(&inst)) {
+                    Some(_) => self.define(inst, &call),
+                    None => self.line(&call),
+                }
+            }
+            InstKind::Load(local) => {
+                let ty = value_type(self.function.locals[local.index()]);
+                self.define(inst, &format!("load {0}, {0}* %l{1}", ty, local.0));
+            }
+            InstKind::Store { local, value } => {
+                let ty = value_type(self.function.locals[local.index()]);
+                let v = self.operand(*value);
+                self.line(&format!("store {0} {1}, {0}* %l{2}", ty, v, local.0));
+            }
+            InstKind::Arith { op, lhs, rhs } => {
+                let (a, b) = (self.operand(*lhs), self.operand(*rhs));
+                match (self.ty(*lhs)?, op) {
+                    // `sdiv` by 0 is caught by a trap before it; by -1 it
+                    // would overflow for the least value, and wraps instead.
+                    (FfiType::Int { .. }, ArithOp::Div | ArithOp::Rem) => {
+                        let minus_one = self.temp(&format!("icmp eq i64 {}, -1", b));
+                        let divisor = self.temp(&format!("select i1 {}, i64 1, i64 {}", minus_one, b));
+                        let (instruction, wrapped) = match op {
+                            ArithOp::Div => ("sdiv", self.temp(&format!("sub i64 0, {}", a))),
+                            _ => ("srem", "0".to_string()),
+                        };
+                        let result = self.temp(&format!("{} i64 {}, {}", instruction, a, divisor));
+                        self.define(inst, &format!("select i1 {}, i64 {}, i64 {}", minus_one, wrapped, result));
+                    }
+                    (FfiType::Int { .. }, _) => {
+                        let instruction = match op {
+                            ArithOp::Add => "add",
+                            ArithOp::Sub => "sub",
+                            _ => "mul",
+                        };
+                        self.define(inst, &format!("{} i64 {}, {}", instruction, a, b));
+                    }
+                    (FfiType::Float { .. }, ArithOp::Rem) => {
+                        return Err(BackendError::InvalidIr("remainder of floats".to_string()))
+                    }
+                    (FfiType::Float { .. }, _) => {
+                        let instruction = match op {
+                            ArithOp::Add => "fadd",
+                            ArithOp::Sub => "fsub",
+                            ArithOp::Mul => "fmul",
+                            _ => "fdiv",
+                        };
+                        self.define(inst, &format!("{} double {}, {}", instruction, a, b));
+                    }
+                    (ty, _) => return Err(BackendError::InvalidIr(format!("arithmetic on {:?}", ty))),
+                }
+            }
+            InstKind::Compare { op, lhs, rhs } => {
+                let ty = self.ty(*lhs)?;
+                // `!=` holds for NaN, and the other comparisons do not.
+                let condition = match (ty, op) {
+                    (FfiType::Float { .. }, CompareOp::Eq) => "fcmp oeq",
+                    (FfiType::Float { .. }, CompareOp::Ne) => "fcmp une",
+                    (FfiType::Float { .. }, CompareOp::Lt) => "fcmp olt",
+                    (FfiType::Float { .. }, CompareOp::Le) => "fcmp ole",
+                    (FfiType::Float { .. }, CompareOp::Gt) => "fcmp ogt",
+                    (FfiType::Float { .. }, CompareOp::Ge) => "fcmp oge",
+                    (_, CompareOp::Eq) => "icmp eq",
+                    (_, CompareOp::Ne) => "icmp ne",
+                    (FfiType::Int { .. }, CompareOp::Lt) => "icmp slt",
+                    (FfiType::Int { .. }, CompareOp::Le) => "icmp sle",
+                    (FfiType::Int { .. }, CompareOp::Gt) => "icmp sgt",
+                    (FfiType::Int { .. }, CompareOp::Ge) => "icmp sge",
+                    (_, CompareOp::Lt) => "icmp ult",
+                    (_, CompareOp::Le) => "icmp ule",
+                    (_, CompareOp::Gt) => "icmp ugt",
+                    (_, CompareOp::Ge) => "icmp uge",
+                };
+                let (a, b) = (self.operand(*lhs), self.operand(*rhs));
+                self.define(inst, &format!("{} {} {}, {}", condition, value_type(ty), a, b));
+            }
+            InstKind::Not(value) => {
+                let v = self.operand(*value);
+                self.define(inst, &format!("xor i1 {}, true", v));
+            }
+            InstKind::Overflows { op, lhs, rhs, ty } => {
+                let FfiType::Int { bits, signed } = *ty else {
+                    return Err(BackendError::InvalidIr(format!("overflow of {:?}", ty)));
+                };
+                let (a, b) = (self.operand(*lhs), self.operand(*rhs));
+                let operation = match op {
+                    ArithOp::Add => "add",
+                    ArithOp::Sub => "sub",
+                    ArithOp::Mul => "mul",
+                    // Only the smallest signed value divided by -1 is out of range.
+                    ArithOp::Div if signed => {
+                        let min = plugin_api::int_bounds(bits, true).0;
+                        let least = self.temp(&format!("icmp eq i64 {}, {}", a, min));
+                        let minus_one = self.temp(&format!("icmp eq i64 {}, -1", b));
+                        self.define(inst, &format!("and i1 {}, {}", least, minus_one));
+                        return Ok(());
+                    }
+                    ArithOp::Div | ArithOp::Rem => {
+                        self.define(inst, "and i1 false, false");
+                        return Ok(());
+                    }
+                };
+                let int = format!("i{}", bits);
+                let (a, b) = if bits < 64 {
+                    let a = self.temp(&format!("trunc i64 {} to {}", a, int));
+                    (a, self.temp(&format!("trunc i64 {} to {}", b, int)))
+                } else {
+                    (a, b)
+                };
+                let intrinsic = format!("llvm.{}{}.with.overflow.{}", if signed { 's' } else { 'u' }, operation, int);
+                self.intrinsics.insert(format!("declare {{{0}, i1}} @{1}({0}, {0})\n", int, intrinsic));
+                let result = self.temp(&format!("call {{{0}, i1}} @{1}({0} {2}, {0} {3})", int, intrinsic, a, b));
+                self.define(inst, &format!("extractvalue {{{}, i1}} {}, 1", int, result));
+            }
+            InstKind::Trap { condition, message, backtrace, abort } => {
+                let condition = self.operand(*condition);
+                let (message, backtrace) = (self.strings.pointer(message), self.strings.pointer(backtrace));
+                self.line(&format!("br i1 {}, label %trap{n}, label %ok{n}", condition, n = inst.0));
+                self.code.push_str(&format!("trap{}:\n", inst.0));
+                self.line(&format!("call void @tlang_trap(i8* {}, i8* {}, i1 {})", message, backtrace, abort));
+                self.line("unreachable");
+                self.code.push_str(&format!("ok{}:\n", inst.0));
+            }
+            // Lifted TIR has the calls an `Alloca` is emitted as.
+            InstKind::Alloca(_) => return Err(BackendError::InvalidIr("an `Alloca` left unexpanded".to_string())),
+        }
+        Ok(())
+    }
+
+    /// `%v<inst>` as `value` converted to `to`: saturated and NaN as 0 from
+    /// a float to an integer, wrapped from one integer to a narrower one.
+    fn cast(&mut self, inst: InstId, value: InstId, to: FfiType) -> Result<(), BackendError> {
+        let v = self.operand(value);
+        match (self.ty(value)?, to) {
+            (FfiType::Int { .. }, FfiType::Float { .. }) => self.define(inst, &format!("sitofp i64 {} to double", v)),
+            (FfiType::Float { .. }, FfiType::Int { bits, signed }) => {
+                // `fptosi` out of range is poison, which the selects discard.
+                let (min, max) = plugin_api::int_bounds(bits, signed);
+                let truncated = self.temp(&format!("fptosi double {} to i64", v));
+                let high = self.temp(&format!("fcmp oge double {}, {}", v, float_bits(max as f64)));
+                let below = self.temp(&format!("select i1 {}, i64 {}, i64 {}", high, max, truncated));
+                let low = self.temp(&format!("fcmp ole double {}, {}", v, float_bits(min as f64)));
+                let above = self.temp(&format!("select i1 {}, i64 {}, i64 {}", low, min, below));
+                let nan = self.temp(&format!("fcmp uno double {0}, {0}", v));
+                self.define(inst, &format!("select i1 {}, i64 0, i64 {}", nan, above));
+            }
+            (FfiType::Int { .. }, FfiType::Int { bits, signed }) if bits < 64 => {
+                let narrow = self.temp(&format!("trunc i64 {} to i{}", v, bits));
+                let extend = if signed { "sext" } else { "zext" };
+                self.define(inst, &format!("{} i{} {} to i64", extend, bits, narrow));
+            }
+            // Nothing changes; `bitcast` copies the value to `%v<inst>`.
+            (FfiType::Int { .. }, FfiType::Int { .. }) => self.define(inst, &format!("bitcast i64 {} to i64", v)),
+            (FfiType::Float { .. }, FfiType::Float { .. }) => {
+                self.define(inst, &format!("bitcast double {} to double", v))
+            }
+            (from, to) => return Err(BackendError::InvalidIr(format!("cannot cast {:?} to {:?}", from, to))),
+        }
+        Ok(())
+    }
+
+    /// A call of the extern function `name`, converting each argument to
+    /// its C type and promoting those passed to the variable part of a
+    /// variadic function; the result comes back as the type it is held in.
+    fn call_extern(
+        &mut self,
+        inst: InstId,
+        name: &str,
+        args: &[InstId],
+        types: &[FfiType],
+    ) -> Result<(), BackendError> {
+        let function = self.module.extern_function(name)?;
+        let mut passed = Vec::new();
+        for (i, (&arg, &ty)) in args.iter().zip(types).enumerate() {
+            let mut v = self.operand(arg);
+            match ty {
+                FfiType::Int { bits, .. } if bits < 64 => v = self.temp(&format!("trunc i64 {} to i{}", v, bits)),
+                FfiType::Float { bits: 32 } => v = self.temp(&format!("fptrunc double {} to float", v)),
+                _ => {}
+            }
+            let (c_type, attr) = llvm_type(ty)?;
+            if i < function.params.len() {
+                passed.push(match attr {
+                    Some(attr) => format!("{} {} {}", c_type, attr, v),
+                    None => format!("{} {}", c_type, v),
+                });
+                continue;
+            }
+            passed.push(match ty {
+                FfiType::Int { bits, signed } if bits < 32 => {
+                    let extend = if signed { "sext" } else { "zext" };
+                    format!("i32 {}", self.temp(&format!("{} {} {} to i32", extend, c_type, v)))
+                }
+                FfiType::Float { bits: 32 } => format!("double {}", self.temp(&format!("fpext float {} to double", v))),
+                FfiType::Bool => format!("i32 {}", self.temp(&format!("zext i1 {} to i32", v))),
+                _ => format!("{} {}", c_type, v),
+            });
+        }
+        let ret = function.return_type.map(llvm_type).transpose()?.map_or("void", |(ty, _)| ty);
+        let callee = if function.variadic {
+            let mut params =
+                function.params.iter().map(|&ty| llvm_type(ty).map(|(ty, _)| ty)).collect::<Result<Vec<_>, _>>()?;
+            params.push("...");
+            format!("{} ({}) @{}", ret, params.join(", "), name)
+        } else {
+            format!("{} @{}", ret, name)
+        };
+        let call = format!("call {}({})", callee, passed.join(", "));
+        match function.return_type.filter(|_| self.used.contains(&inst)) {
+            None => self.line(&call),
+            Some(FfiType::Int { bits, signed }) if bits < 64 => {
+                let result = self.temp(&call);
+                let extend = if signed { "sext" } else { "zext" };
+                self.define(inst, &format!("{} i{} {} to i64", extend, bits, result));
+            }
+            Some(FfiType::Float { bits: 32 }) => {
+                let result = self.temp(&call);
+                self.define(inst, &format!("fpext float {} to double", result));
+            }
+            Some(FfiType::Str) => {
+                let result = self.temp(&call);
+                let null = self.temp(&format!("icmp eq i8* {}, null", result));
+                let empty = self.strings.pointer("");
+                let string = self.temp(&format!("select i1 {}, i8* {}, i8* {}", null, empty, result));
+                self.runtime.insert("strdup");
+                self.define(inst, &format!("call i8* @strdup(i8* {})", string));
+            }
+            Some(_) => self.define(inst, &call),
+        }
+        Ok(())
+    }
+
+    /// Translate the terminator of block `id`.
+    fn terminator(&mut self, id: BlockId, terminator: &Terminator) -> Result<(), BackendError> {
+        match *terminator {
+            Terminator::Return if self.entry() => self.line("ret i32 0"),
+            Terminator::Return => match self.function.result {
+                Some(result) => {
+                    let ty = value_type(self.function.locals[result.index()]);
+                    let value = self.temp(&format!("load {0}, {0}* %l{1}", ty, result.0));
+                    self.line(&format!("ret {} {}", ty, value));
+                }
+                None => self.line("ret void"),
+            },
+            Terminator::Jump(target) => self.line(&format!("br label %bb{}", target.0)),
+            Terminator::Branch { condition, then, otherwise } => {
+                let condition = self.operand(condition);
+                self.line(&format!("br i1 {}, label %bb{}, label %bb{}", condition, then.0, otherwise.0));
+            }
+            Terminator::Exit(code) => {
+                let code = self.typed(code)?;
+                let code = self.temp(&format!("trunc {} to i32", code));
+                if self.entry() {
+                    self.line(&format!("ret i32 {}", code));
+                } else {
+                    self.runtime.insert("exit");
+                    self.line(&format!("call void @exit(i32 {})", code));
+                    self.line("unreachable");
+                }
+            }
+            Terminator::Unreachable => self.line("unreachable"),
+            Terminator::Switch { value, ref cases, default } => {
+                let value = self.operand(value);
+                match super::jump_table(cases, default) {
+                    // A table of block addresses, indexed by the offset from
+                    // the least case.
+                    Some((least, table)) => {
+                        let array = format!("[{} x i8*]", table.len());
+                        let function = self.symbol();
+                        let addresses: Vec<String> = table
+                            .iter()
+                            .map(|target| format!("i8* blockaddress({}, %bb{})", function, target.0))
+                            .collect();
+                        self.tables.push_str(&format!(
+                            "{} = private unnamed_addr constant {} [{}]\n",
+                            self.table(id),
+                            array,
+                            addresses.join(", ")
+                        ));
+                        let offset = self.temp(&format!("sub i64 {}, {}", value, least));
+                        let inside = self.temp(&format!("icmp ult i64 {}, {}", offset, table.len()));
+                        self.line(&format!("br i1 {}, label %bb{}.table, label %bb{}", inside, id.0, default.0));
+                        self.code.push_str(&format!("bb{}.table:\n", id.0));
+                        let slot = self.temp(&format!(
+                            "getelementptr inbounds {0}, {0}* {1}, i64 0, i64 {2}",
+                            array,
+                            self.table(id),
+                            offset
+                        ));
+                        let address = self.temp(&format!("load i8*, i8** {}", slot));
+                        let mut targets: Vec<BlockId> = Vec::new();
+                        for target in table {
+                            if !targets.contains(&target) {
+                                targets.push(target);
+                            }
+                        }
+                        let labels: Vec<String> =
+                            targets.iter().map(|target| format!("label %bb{}", target.0)).collect();
+                        self.line(&format!("indirectbr i8* {}, [{}]", address, labels.join(", ")));
+                    }
+                    None => {
+                        let mut text = format!("switch i64 {}, label %bb{} [\n", value, default.0);
+                        for &(n, target) in cases {
+                            text.push_str(&format!("    i64 {}, label %bb{}\n", n, target.0));
+                        }
+                        text.push_str("  ]");
+                        self.line(&text);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The LLVM name of the module's function `name`, other than the entry.
+fn symbol(name: &str) -> String {
+    format!("@tl.{}", name)
+}
+
+/// The `define` line opening the module's function `function`, other than
+/// the entry, with its subprogram `scope` if it has one.
+fn definition_line(function: &Function, scope: Option<usize>) -> String {
+    let params: Vec<String> =
+        function.param_types().iter().enumerate().map(|(i, &ty)| format!("{} %a{}", value_type(ty), i)).collect();
+    let dbg = scope.map_or(String::new(), |scope| format!(" !dbg !{}", scope));
+    format!(
+        "\ndefine private {} {}({}){} {{\n",
+        function.result_type().map_or("void", value_type),
+        symbol(&function.name),
+        params.join(", "),
+        dbg
+    )
+}
+
+/// `tlang_trap`, which a `Trap` whose condition holds calls: report the
+/// panic as `tlang_panic` does and exit with 101, or abort.
+fn trap_function(strings: &mut Strings) -> String {
+    let variable = strings.pointer("TLANG_BACKTRACE");
+    let line = strings.pointer("%s\n");
+    let off = strings.pointer("0");
+    let trace = strings.pointer("stack backtrace:\n%s\n");
+    let note =
+        strings.pointer("note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace\n");
+    format!(
+        r#"
+define private void @tlang_trap(i8* %message, i8* %backtrace, i1 %aborts) noreturn {{
+entry:
+  %show = call i8* @getenv(i8* {variable})
+  br i1 %aborts, label %report, label %flush
+flush:
+  call i32 @fflush(i8* null)
+  br label %report
+report:
+  call i32 (i32, i8*, ...) @dprintf(i32 2, i8* {line}, i8* %message)
+  %unset = icmp eq i8* %show, null
+  br i1 %unset, label %note, label %compare
+compare:
+  %same = call i32 @strcmp(i8* %show, i8* {off})
+  %hidden = icmp eq i32 %same, 0
+  br i1 %hidden, label %note, label %trace
+trace:
+  call i32 (i32, i8*, ...) @dprintf(i32 2, i8* {trace}, i8* %backtrace)
+  br label %end
+note:
+  call i32 (i32, i8*, ...) @dprintf(i32 2, i8* {note})
+  br label %end
+end:
+  br i1 %aborts, label %abort, label %panic
+abort:
+  call void @abort()
+  unreachable
+panic:
+  call void @exit(i32 {code})
+  unreachable
+}}
+"#,
+        variable = variable,
+        line = line,
+        off = off,
+        trace = trace,
+        note = note,
+        code = plugin_api::PANIC_EXIT_CODE
+    )
+}
 
 #[derive(Debug)]
 pub struct LlvmBackend;
 
 impl Backend<CompiledModule> for LlvmBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        self.compile_with_config(module, &BackendConfig::default())
+    }
+
+    fn compile_with_config(
+        &self,
+        module: CompiledModule,
+        config: &BackendConfig,
+    ) -> Result<Self::ModuleIr, BackendError> {
+        // 1. Lift the IR to TIR and resolve the target
+        let tir = module.tir()?;
+        let target = super::target::target_info(config)?;
+        let debug = &module.debug_info;
+        let file = debug.source_file.as_deref().filter(|_| !debug.is_empty());
+
+        // 2. Translate the functions, and `tlang_trap` if one traps
+        let mut body = Body::new(&module, &tir, file.map(|_| debug));
+        body.function_body()?;
+        let mut callees = String::new();
+        for (i, function) in tir.functions.iter().enumerate().skip(1) {
+            let main = body.next(function);
+            body.function_body()?;
+            let scope = file.map(|_| FIRST_LOCATION + debug.line_info.len() + i - 1);
+            callees.push_str(&definition_line(function, scope));
+            callees.push_str(&std::mem::replace(&mut body.code, main));
+            callees.push_str("}\n");
+        }
+        let insts = || tir.functions.iter().flat_map(|function| &function.insts);
+        let trap = insts().any(|inst| matches!(inst.kind, InstKind::Trap { .. }));
+        let trap = if trap {
+            body.runtime.extend(["getenv", "fflush", "dprintf", "strcmp", "abort", "exit"]);
+            trap_function(&mut body.strings)
+        } else {
+            String::new()
+        };
 
-        // 2. Begin LLVM IR module
+        // 3. Begin LLVM IR module
         let mut code = String::new();
         code.push_str("; ModuleID = 'tlang'\n");
-        code.push_str("declare i32 @printf(i8*, ...)\n");
-        code.push_str("@.str = private unnamed_addr constant [4 x i8] c\"%s\\0A\\00\", align 1\n\n");
-        code.push_str("define i32 @main() {\n");
-        code.push_str("entry:\n");
-
-        // 3. Embed each T-Lang IR instruction as an LLVM comment
-        for line in ir.lines() {
-            let inst = line.trim();
-            if inst.is_empty() || inst == "Nop" {
-                continue;
+        if let Some(file) = file {
+            code.push_str(&format!("source_filename = \"{}\"\n", llvm_str(file)));
+        }
+        if let Some(target) = &target {
+            code.push_str(&format!("target triple = \"{}\"\n", target.triple));
+        }
+        // The runtime functions called are declared unless the program declares them.
+        for (name, declaration) in RUNTIME {
+            if body.runtime.contains(name) && !module.externs.iter().any(|f| f.name == *name) {
+                code.push_str(declaration);
             }
-            code.push_str("  ; ");
-            code.push_str(inst);
-            code.push('\n');
         }
+        for intrinsic in &body.intrinsics {
+            code.push_str(intrinsic);
+        }
+        for function in &module.externs {
+            code.push_str(&declaration(function)?);
+        }
+        for global in &module.globals {
+            code.push_str(&definition(global)?);
+        }
+        code.push_str(&body.strings.definitions());
+        code.push_str(&body.tables);
 
-        // 4. Return 0
-        code.push_str("  ret i32 0\n");
+        // 4. Define main
+        code.push_str(if file.is_some() {
+            "\ndefine i32 @main() !dbg !4 {\n"
+        } else {
+            "\ndefine i32 @main() {\n"
+        });
+        code.push_str(&body.code);
         code.push_str("}\n");
+        code.push_str(&callees);
+        code.push_str(&trap);
+        if let Some(file) = file {
+            code.push_str(&dwarf_metadata(debug, file, &tir));
+        }
+        let flags = if file.is_some() { " -g" } else { "" };
 
-        Ok(Box::new(code.into_bytes()))
+        let artifact = CompiledArtifact::source(self.name(), "main.ll", code);
+        Ok(match target {
+            Some(target) => {
+                let mut cmd = format!("clang --target={}", target.triple);
+                for flag in &target.abi_flags {
+                    cmd.push(' ');
+                    cmd.push_str(flag);
+                }
+                cmd.push_str(flags);
+                cmd.push_str(" -o main main.ll");
+                artifact.with_build_command(cmd)
+            }
+            None => artifact
+                .with_build_command(format!("clang{} -o main main.ll", flags))
+                .with_build_command("./main"),
+        })
     }
 
     fn name(&self) -> &'static str {
         "llvm"
     }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            supports_cross_compilation: true,
+            supports_debug_info: true,
+            ..BackendCapabilities::default()
+        }
+    }
 }
 
 // Register this backend at startup
@@ -57,7 +958,7 @@ static LLVM_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_LLVM_REG: fn() = {
+pub(crate) static FORCE_LLVM_REG: fn() = {
     fn init() {
         Lazy::force(&LLVM_REG);
     }
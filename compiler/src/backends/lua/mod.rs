@@ -1,56 +1,122 @@
 // File: compiler/src/backends/lua/mod.rs
 //! Lua codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Lua script
+//! Decodes our IR and emits a standalone Lua script
 //! that replays the instructions on two simple stacks and prints values.
 
-use plugin_api::{register_backend, Backend, BackendError, CompiledModule};
-use std::{any::Any, format, str, sync::OnceLock};
+use plugin_api::{register_backend, Backend, BackendError, CompiledArtifact, CompiledModule, Instruction};
+use std::{format, sync::OnceLock};
 
 #[derive(Debug)]
 pub struct LuaBackend;
 
 impl Backend<CompiledModule> for LuaBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Lua script
         let mut code = String::new();
         code.push_str("-- Generated by T-Lang Lua backend\n");
         code.push_str("local intStack = {}\n");
+        code.push_str("local fltStack = {}\n");
         code.push_str("local strStack = {}\n\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" { continue; }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("table.insert(intStack, {})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("table.insert(strStack, \"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "if #strStack > 0 then\n\
-                     \tio.write(table.remove(strStack))\n\
-                     elseif #intStack > 0 then\n\
-                     \tio.write(tostring(table.remove(intStack)))\n\
-                     end\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("table.insert(intStack, {})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("table.insert(fltStack, {})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("table.insert(intStack, {})\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("table.insert(strStack, \"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "if #strStack > 0 then\n\
+                         \tio.write(table.remove(strStack))\n\
+                         elseif #intStack > 0 then\n\
+                         \tio.write(tostring(table.remove(intStack)))\n\
+                         end\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("io.write(tostring(table.remove(intStack)))\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("io.write(string.format(\"%.6f\", table.remove(fltStack)))\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("io.write(table.remove(strStack))\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("io.write(table.remove(intStack) ~= 0 and \"true\" or \"false\")\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("table.insert(fltStack, table.remove(intStack) + 0.0)\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "do\n\
+                         \tlocal f = table.remove(fltStack)\n\
+                         \tif f ~= f then f = 0\n\
+                         \telseif f <= {} then f = {}\n\
+                         \telseif f >= {} then f = {}\n\
+                         \telseif f < 0 then f = math.ceil(f)\n\
+                         \telse f = math.floor(f) end\n\
+                         \ttable.insert(intStack, f)\n\
+                         end\n",
+                        min_f,
+                        super::int_literal(min),
+                        max_f,
+                        max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "table.insert(intStack, ((table.remove(intStack) & {}) ~ {}) - {})\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => code.push_str("os.exit(table.remove(intStack))\n"),
             }
         }
 
         // 4. End of script
-        code.push_str("\n");
-        Ok(Box::new(code.into_bytes()))
+        code.push('\n');
+        Ok(CompiledArtifact::source(self.name(), "main.lua", code)
+            .with_build_command("lua main.lua"))
     }
 
     fn name(&self) -> &'static str {
@@ -64,7 +130,7 @@ static LUA_REG: OnceLock<()> = OnceLock::new();
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_LUA_REG: fn() = {
+pub(crate) static FORCE_LUA_REG: fn() = {
     fn init() {
         LUA_REG.get_or_init(|| register_backend(LuaBackend));
     }
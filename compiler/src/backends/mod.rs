@@ -5,6 +5,8 @@ pub mod asm;
 pub mod c;
 pub mod clojure;
 pub mod cobol;
+pub mod cpp;
+pub mod cranelift_jit;
 pub mod css;
 pub mod elixir;
 pub mod erlang;
@@ -13,6 +15,7 @@ pub mod haskell;
 pub mod html;
 pub mod java;
 pub mod javascript;
+pub mod julia;
 pub mod kotlin;
 pub mod llvm_backend;
 pub mod lua;
@@ -30,3 +33,243 @@ pub mod v;
 pub mod zig;
 pub mod wasm;
 pub mod python;
+pub mod target;
+
+use plugin_api::{BackendError, FfiType, Instruction, Layout, ValueStacks};
+use shared::tir::BlockId;
+use std::collections::BTreeMap;
+
+/// Register every built-in backend with `plugin_api`; calling it again does
+/// nothing.
+///
+/// Each backend registers itself on first use of its `FORCE_*_REG` hook,
+/// and nothing uses those on its own, so drivers call this before
+/// `plugin_api::list_backends`.
+pub fn register_builtin_backends() {
+    for register in [
+        asm::FORCE_ASM_REG,
+        c::FORCE_C_REG,
+        clojure::FORCE_CLOJURE_REG,
+        cobol::FORCE_COBOL_REG,
+        cpp::FORCE_CPP_REG,
+        css::FORCE_CSS_REG,
+        elixir::FORCE_ELIXIR_REG,
+        erlang::FORCE_ERLANG_REG,
+        go::FORCE_GO_REG,
+        haskell::FORCE_HASKELL_REG,
+        html::FORCE_HTML_REG,
+        java::FORCE_JAVA_REG,
+        javascript::FORCE_JS_REG,
+        julia::FORCE_JULIA_REG,
+        kotlin::FORCE_KOTLIN_REG,
+        llvm_backend::FORCE_LLVM_REG,
+        lua::FORCE_LUA_REG,
+        nim::FORCE_NIM_REG,
+        ocaml::FORCE_OCAML_REG,
+        powershell::FORCE_PS_REG,
+        r::FORCE_R_REG,
+        ruby::FORCE_RUBY_REG,
+        rust::FORCE_RUST_REG,
+        scheme::FORCE_SCHEME_REG,
+        shell::FORCE_SHELL_REG,
+        swift::FORCE_SWIFT_REG,
+        typescript::FORCE_TS_REG,
+        v::FORCE_V_REG,
+        zig::FORCE_ZIG_REG,
+        wasm::FORCE_WASM_REG,
+        python::FORCE_PYTHON_REG,
+    ] {
+        register();
+    }
+}
+
+/// Reject NaN and infinities, which no backend can spell as a literal.
+pub(crate) fn finite_float(v: f64) -> Result<f64, BackendError> {
+    if v.is_finite() {
+        Ok(v)
+    } else {
+        Err(BackendError::Generic(format!("Invalid float literal: {}", v)))
+    }
+}
+
+/// Error for a `CallExtern` in a backend whose output cannot call C.
+pub(crate) fn extern_unsupported(backend: &str, name: &str) -> BackendError {
+    BackendError::Generic(format!(
+        "the {} backend cannot call extern function `{}`; use the c, rust or llvm backend",
+        backend, name
+    ))
+}
+
+/// Error for an instruction a backend has no translation for yet.
+pub(crate) fn instruction_unsupported(backend: &str, instr: &Instruction) -> BackendError {
+    BackendError::Generic(format!(
+        "the {} backend cannot translate `{:?}` yet; use the c, cpp, go, rust, python or cranelift-jit backend",
+        backend, instr
+    ))
+}
+
+/// Most instructions `fold_run_time` executes before it takes a program
+/// for one that may not end.
+const MAX_FOLDED_STEPS: usize = 10_000_000;
+
+/// Whether a backend that only replays straight-line code has no
+/// translation for `instr`, which only code decided at run time has.
+fn decided_at_run_time(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::StoreLocal { .. }
+            | Instruction::LoadLocal { .. }
+            | Instruction::Arith { .. }
+            | Instruction::Compare { .. }
+            | Instruction::Not
+            | Instruction::Label(_)
+            | Instruction::Jump(_)
+            | Instruction::JumpIfFalse(_)
+            | Instruction::Overflows { .. }
+            | Instruction::Trap { .. }
+            | Instruction::Function { .. }
+            | Instruction::Call { .. }
+            | Instruction::Return { .. }
+    )
+}
+
+/// `instrs` as a backend that only replays straight-line code can
+/// translate them: unchanged if they are straight-line code, and otherwise
+/// what they print, run here, then the `Exit` of their exit code unless it
+/// is 0.
+///
+/// Code that calls no extern function reads nothing at run time, so it
+/// prints the same every time it runs, as the constants the lowering folds
+/// do. Code that calls one, panics or runs for more than
+/// `MAX_FOLDED_STEPS` instructions is left to the backends that translate
+/// it, with an `instruction_unsupported` error.
+pub(crate) fn fold_run_time(backend: &str, instrs: Vec<Instruction>) -> Result<Vec<Instruction>, BackendError> {
+    let Some(first) = instrs.iter().find(|instr| decided_at_run_time(instr)) else {
+        return Ok(instrs);
+    };
+    let unsupported = || instruction_unsupported(backend, first);
+    let layout = Layout::of(&instrs);
+    let mut stacks = ValueStacks::default();
+    let mut out = String::new();
+    let mut pc = 0;
+    for _ in 0..MAX_FOLDED_STEPS {
+        let Some(instr) = instrs.get(pc) else {
+            return Ok(printing(out, 0));
+        };
+        match instr {
+            Instruction::CallExtern { .. } => return Err(unsupported()),
+            Instruction::Trap { .. } if stacks.bools.last() == Some(&true) => return Err(unsupported()),
+            _ => {}
+        }
+        stacks.execute(instr, &mut out)?;
+        if let Some(code) = stacks.exit_code {
+            return Ok(printing(out, code));
+        }
+        pc = stacks.next_pc(pc, &layout)?;
+    }
+    Err(unsupported())
+}
+
+/// Instructions printing `out` and exiting with `code`.
+fn printing(out: String, code: i32) -> Vec<Instruction> {
+    let mut instrs = Vec::new();
+    if !out.is_empty() {
+        instrs.extend([Instruction::PushStr(out), Instruction::PrintStr]);
+    }
+    if code != 0 {
+        instrs.extend([Instruction::PushInt(code.into()), Instruction::Exit]);
+    }
+    instrs
+}
+
+/// The type carried by every local slot one function's `instrs` use and
+/// its parameters `params` take, by slot.
+pub(crate) fn local_slots(params: &[FfiType], instrs: &[Instruction]) -> Result<BTreeMap<u32, FfiType>, BackendError> {
+    let mut slots: BTreeMap<u32, FfiType> =
+        params.iter().enumerate().map(|(slot, ty)| (slot as u32, ty.carried())).collect();
+    for instr in instrs {
+        if let Instruction::StoreLocal { slot, ty } | Instruction::LoadLocal { slot, ty } = *instr {
+            let ty = ty.carried();
+            if *slots.entry(slot).or_insert(ty) != ty {
+                return Err(BackendError::InvalidIr(format!("local {} is used as two types", slot)));
+            }
+        }
+    }
+    Ok(slots)
+}
+
+/// Spell a `PushFloat` operand as a source literal.
+///
+/// The literal always carries a fractional part (`1.0`, `2.5e-7`, `1.0e20`)
+/// so that languages which reject exponent-only floats such as `1e20`
+/// (Erlang, Elixir) can use it verbatim.
+pub(crate) fn float_literal(v: f64) -> Result<String, BackendError> {
+    let lit = format!("{:?}", finite_float(v)?);
+    match lit.split_once('e') {
+        Some((mantissa, exp)) if !mantissa.contains('.') => Ok(format!("{}.0e{}", mantissa, exp)),
+        _ => Ok(lit),
+    }
+}
+
+/// Escape a `PushStr` operand for a double-quoted, C-style string literal.
+pub(crate) fn escape_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Spell an integer for languages that read `-N` as a negated literal,
+/// where `i64::MIN` would overflow.
+pub(crate) fn int_literal(n: i64) -> String {
+    if n == i64::MIN {
+        format!("({} - 1)", n + 1)
+    } else {
+        n.to_string()
+    }
+}
+
+/// The jump table for a TIR `Switch`, if its cases are dense enough to
+/// want one: at least four, covering at least half the values from the
+/// least to the greatest. It is the least value and the block for each
+/// value from it on, `default` where no case has the value.
+pub(crate) fn jump_table(cases: &[(i64, BlockId)], default: BlockId) -> Option<(i64, Vec<BlockId>)> {
+    let least = cases.iter().map(|&(n, _)| n).min()?;
+    let greatest = cases.iter().map(|&(n, _)| n).max()?;
+    let span = greatest as i128 - least as i128 + 1;
+    if cases.len() < 4 || span > 2 * cases.len() as i128 {
+        return None;
+    }
+    let mut table = vec![default; span as usize];
+    for &(n, target) in cases.iter().rev() {
+        table[(n as i128 - least as i128) as usize] = target;
+    }
+    Some((least, table))
+}
+
+/// Operands for spelling `WrapInt` as `((v & mask) ^ sign) - sign`, where
+/// `sign` is 0 for unsigned targets. `None` when wrapping changes nothing,
+/// as for 64-bit targets.
+pub(crate) fn wrap_masks(bits: u8, signed: bool) -> Option<(i64, i64)> {
+    (bits < 64).then(|| {
+        let mask = (1i64 << bits) - 1;
+        let sign = if signed { 1i64 << (bits - 1) } else { 0 };
+        (mask, sign)
+    })
+}
+
+/// Integer bounds of a `FloatToInt` target and the float literals to
+/// compare against: a float `<=` the first literal becomes `min`, one `>=`
+/// the second becomes `max`, NaN becomes 0 and anything else is truncated.
+pub(crate) fn saturation_bounds(bits: u8, signed: bool) -> Result<(i64, i64, String, String), BackendError> {
+    let (min, max) = plugin_api::int_bounds(bits, signed);
+    Ok((min, max, float_literal(min as f64)?, float_literal(max as f64)?))
+}
@@ -1,60 +1,119 @@
 // File: compiler/src/backends/nim/mod.rs
 //! Nim codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Nim script
+//! Decodes our IR and emits a standalone Nim script
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct NimBackend;
 
 impl Backend<CompiledModule> for NimBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Nim script
         let mut code = String::new();
         code.push_str("# Generated by T-Lang Nim backend\n");
+        code.push_str("import strutils\n\n");
         code.push_str("var intStack: seq[int] = @[]\n");
+        code.push_str("var fltStack: seq[float] = @[]\n");
         code.push_str("var strStack: seq[string] = @[]\n\n");
         code.push_str("proc main() =\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("  intStack.add({})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("  strStack.add(\"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "  if strStack.len > 0:\n\
-           \n    stdout.write(strStack.pop())\n\
-           \n  elif intStack.len > 0:\n\
-           \n    stdout.write($intStack.pop())\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)))
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("  intStack.add({})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("  fltStack.add({})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("  intStack.add({})\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("  strStack.add(\"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "  if strStack.len > 0:\n\
+               \n    stdout.write(strStack.pop())\n\
+               \n  elif intStack.len > 0:\n\
+               \n    stdout.write($intStack.pop())\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("  stdout.write($intStack.pop())\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("  stdout.write(formatFloat(fltStack.pop(), ffDecimal, 6))\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("  stdout.write(strStack.pop())\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("  stdout.write($(intStack.pop() != 0))\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("  fltStack.add(float(intStack.pop()))\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "  block:\n    let f = fltStack.pop()\n    intStack.add(if f != f: 0 elif f <= {}: {} elif f >= {}: {} else: int(f))\n",
+                        min_f,
+                        super::int_literal(min),
+                        max_f,
+                        max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "  intStack[^1] = ((intStack[^1] and {}) xor {}) - {}\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("  flushFile(stdout)\n");
+                    code.push_str("  quit(intStack.pop())\n");
+                }
             }
         }
 
         // 4. Invoke main
         code.push_str("\nmain()\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.nim", code)
+            .with_build_command("nim c -r main.nim"))
     }
 
     fn name(&self) -> &'static str {
@@ -70,7 +129,7 @@ static NIM_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_NIM_REG: fn() = {
+pub(crate) static FORCE_NIM_REG: fn() = {
     fn init() {
         Lazy::force(&NIM_REG);
     }
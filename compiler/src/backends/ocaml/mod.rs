@@ -1,61 +1,123 @@
 // File: compiler/src/backends/ocaml/mod.rs
 //! OCaml codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone OCaml script
+//! Decodes our IR and emits a standalone OCaml script
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct OcamlBackend;
 
 impl Backend<CompiledModule> for OcamlBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin OCaml script
         let mut code = String::new();
         code.push_str("(* Generated by T-Lang OCaml backend *)\n");
         code.push_str("let () =\n");
         code.push_str("  let int_stack = Stack.create () in\n");
+        code.push_str("  let flt_stack = Stack.create () in\n");
         code.push_str("  let str_stack = Stack.create () in\n\n");
 
         // 3. Replay each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("  Stack.push (int_of_string \"{}\") int_stack;\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("  Stack.push \"{}\" str_stack;\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "  if not (Stack.is_empty str_stack) then begin\n\
-                     \n    print_string (Stack.pop str_stack);\n\
-                     \n  end else if not (Stack.is_empty int_stack) then begin\n\
-                     \n    print_int (Stack.pop int_stack);\n\
-                     \n  end;\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("  Stack.push (int_of_string \"{}\") int_stack;\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("  Stack.push ({}) flt_stack;\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("  Stack.push {} int_stack;\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("  Stack.push \"{}\" str_stack;\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "  if not (Stack.is_empty str_stack) then begin\n\
+                         \n    print_string (Stack.pop str_stack);\n\
+                         \n  end else if not (Stack.is_empty int_stack) then begin\n\
+                         \n    print_int (Stack.pop int_stack);\n\
+                         \n  end;\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("  print_int (Stack.pop int_stack);\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("  Printf.printf \"%.6f\" (Stack.pop flt_stack);\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("  print_string (Stack.pop str_stack);\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("  print_string (if Stack.pop int_stack <> 0 then \"true\" else \"false\");\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("  Stack.push (float_of_int (Stack.pop int_stack)) flt_stack;\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // OCaml ints are 63 bits wide, so 64-bit targets saturate at their bounds.
+                    let (min, max, min_f, max_f) = if bits >= 64 {
+                        let min = if signed { "min_int".to_string() } else { "0".to_string() };
+                        let min_f = format!("(float_of_int {})", min);
+                        (min, "max_int".to_string(), min_f, "(float_of_int max_int)".to_string())
+                    } else {
+                        let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                        (format!("({})", min), max.to_string(), format!("({})", min_f), max_f)
+                    };
+                    code.push_str(&format!(
+                        "  (let f = Stack.pop flt_stack in\n   Stack.push (if Float.is_nan f then 0 else if f <= {} then {} else if f >= {} then {} else truncate f) int_stack);\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "  Stack.push ((((Stack.pop int_stack) land {}) lxor {}) - {}) int_stack;\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("  exit (Stack.pop int_stack);\n");
+                }
             }
         }
 
         // 4. End program
         code.push_str("  ();;\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.ml", code)
+            .with_build_command("ocaml main.ml"))
     }
 
     fn name(&self) -> &'static str {
@@ -71,7 +133,7 @@ static OCAML_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_OCAML_REG: fn() = {
+pub(crate) static FORCE_OCAML_REG: fn() = {
     fn init() {
         Lazy::force(&OCAML_REG);
     }
@@ -1,53 +1,55 @@
 // File: compiler/src/backends/powershell/mod.rs
 //! PowerShell codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone PowerShell script
+//! Decodes our IR and emits a standalone PowerShell script
 //! that replays the instructions on two simple stacks and writes output.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct PowershellBackend;
 
 impl Backend<CompiledModule> for PowershellBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin PowerShell script
         let mut code = String::new();
         code.push_str("# Generated by T-Lang PowerShell backend\n");
         code.push_str("$intStack = @()\n");
+        code.push_str("$fltStack = @()\n");
         code.push_str("$strStack = @()\n\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!(
-                    "$intStack += {0}\n",
-                    n
-                ));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('`', "``").replace('"', "`\"");
-                code.push_str(&format!(
-                    "$strStack += \"{}\"\n",
-                    esc
-                ));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    r#"
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!(
+                        "$intStack += {0}\n",
+                        n
+                    ));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("$fltStack += {}\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("$intStack += {}\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = s.replace('`', "``").replace('"', "`\"");
+                    code.push_str(&format!(
+                        "$strStack += \"{}\"\n",
+                        esc
+                    ));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        r#"
 if ($strStack.Count -gt 0) {
     $val = $strStack[-1]
     Write-Host -NoNewline $val
@@ -58,15 +60,68 @@ if ($strStack.Count -gt 0) {
     $intStack = $intStack[0..($intStack.Count - 2)]
 }
 "#,
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("$val = $intStack[-1]\n$intStack = @($intStack | Select-Object -SkipLast 1)\nWrite-Host -NoNewline $val\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("$val = ([double]$fltStack[-1]).ToString(\"F6\", [Globalization.CultureInfo]::InvariantCulture)\n$fltStack = @($fltStack | Select-Object -SkipLast 1)\nWrite-Host -NoNewline $val\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("$val = $strStack[-1]\n$strStack = @($strStack | Select-Object -SkipLast 1)\nWrite-Host -NoNewline $val\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("$val = if ($intStack[-1] -ne 0) { \"true\" } else { \"false\" }\n$intStack = @($intStack | Select-Object -SkipLast 1)\nWrite-Host -NoNewline $val\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("$val = [double]$intStack[-1]\n$intStack = @($intStack | Select-Object -SkipLast 1)\n$fltStack += $val\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str("$f = [double]$fltStack[-1]\n$fltStack = @($fltStack | Select-Object -SkipLast 1)\n");
+                    code.push_str(&format!(
+                        "$val = if ([double]::IsNaN($f)) {{ [long]0 }} elseif ($f -le {}) {{ [long]{} }} elseif ($f -ge {}) {{ [long]{} }} else {{ [long][Math]::Truncate($f) }}\n$intStack += $val\n",
+                        min_f,
+                        super::int_literal(min),
+                        max_f,
+                        max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "$intStack[-1] = (([long]$intStack[-1] -band {}) -bxor {}) - {}\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => code.push_str("exit $intStack[-1]\n"),
             }
         }
 
         // 4. End of script
         code.push('\n');
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.ps1", code)
+            .with_build_command("pwsh main.ps1"))
     }
 
     fn name(&self) -> &'static str {
@@ -82,7 +137,7 @@ static PS_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_PS_REG: fn() = {
+pub(crate) static FORCE_PS_REG: fn() = {
     fn init() {
         Lazy::force(&PS_REG);
     }
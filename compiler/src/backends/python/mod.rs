@@ -1,62 +1,120 @@
 // File: compiler/src/backends/python/mod.rs
 //! Python codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Python script
-//! that replays the instructions on two lists and prints values.
+//! Decodes our IR and emits a standalone Python script
+//! that replays the instructions on three lists (ints, floats, strings) and prints values.
+//! Locals are plain Python variables, `local0`, `local1`, ...
+//! Instructions with labels run in a loop with an `if pc == n:` for the
+//! instructions from each label to the next, in order; a jump sets `pc` to
+//! the label's number and starts the loop over.
+//! Every function of the module but the entry is a Python function
+//! `tl_<name>` that is passed the lists, pops its arguments into locals of
+//! its own and leaves its result on them.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{
+    register_backend, ArithOp, Backend, CompiledArtifact, CompiledModule, BackendError, FfiType, Instruction,
+};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
+use std::collections::HashMap;
+
+/// `q = a / b` of the integers `a` and `b`, rounded toward zero as C and
+/// Rust divide, where `//` rounds down; 0 for a divisor of zero.
+const QUOTIENT: &str = "    q = 0 if b == 0 else abs(a) // abs(b) * (1 if (a < 0) == (b < 0) else -1)\n";
+
+/// What a `Trap` whose condition holds calls: report the panic as
+/// `tlang_panic` does and exit with 101, or abort.
+const TRAP: &str = r#"def tlang_trap(message, backtrace, aborts):
+    import os
+    if not aborts:
+        sys.stdout.flush()
+    sys.stderr.write(message + "\n")
+    show = os.environ.get("TLANG_BACKTRACE")
+    if show is not None and show != "0":
+        sys.stderr.write("stack backtrace:\n" + backtrace + "\n")
+    else:
+        sys.stderr.write("note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace\n")
+    if aborts:
+        os.abort()
+    sys.exit(101)
+
+"#;
+
+/// What a `PrintFloat` calls: `%.6f`, with NaN spelled as every backend
+/// spells it, where Python writes `nan`.
+const PRINT_FLOAT: &str = r#"def tlang_print_float(v):
+    sys.stdout.write("NaN" if v != v else "%.6f" % v)
+
+"#;
+
+/// What a float `Div` calls: `a / b` as IEEE 754 divides, to an infinity
+/// or NaN for a divisor of zero, where Python raises `ZeroDivisionError`.
+const DIVIDE_FLOAT: &str = r#"def tlang_divide(a, b):
+    import math
+    if b != 0.0:
+        return a / b
+    if a != a or a == 0.0:
+        return math.nan
+    return math.copysign(math.inf, a) * math.copysign(1.0, b)
+
+"#;
 
 #[derive(Debug)]
 pub struct PythonBackend;
 
 impl Backend<CompiledModule> for PythonBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = module.decode()?;
 
         // 2. Begin Python script
-        let mut code = String::new();
-        code.push_str("#!/usr/bin/env python3\n");
-        code.push_str("import sys\n\n");
-        code.push_str("def main():\n");
-        code.push_str("    int_stack = []\n");
-        code.push_str("    str_stack = []\n\n");
-
-        // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let inst = line.trim();
-            if inst.is_empty() || inst == "Nop" {
-                continue;
-            }
-            if let Some(n) = inst.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    int_stack.append({})\n", n));
-            } else if let Some(raw) = inst
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("    str_stack.append(\"{}\")\n", esc));
-            } else if inst == "CallPrint" {
-                code.push_str(
-                    "    if str_stack:\n\
-                     \n        sys.stdout.write(str_stack.pop())\n\
-                     \n    elif int_stack:\n\
-                     \n        sys.stdout.write(str(int_stack.pop()))\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", inst)));
+        let mut script = String::new();
+        script.push_str("#!/usr/bin/env python3\n");
+        script.push_str("import sys\n\n");
+        if instrs.iter().any(|instr| matches!(instr, Instruction::Trap { .. })) {
+            script.push_str(TRAP);
+        }
+        if instrs.iter().any(|instr| matches!(instr, Instruction::PrintFloat)) {
+            script.push_str(PRINT_FLOAT);
+        }
+        if instrs.iter().any(|instr| matches!(instr, Instruction::Arith { op: ArithOp::Div, ty: FfiType::Float { .. } })) {
+            script.push_str(DIVIDE_FLOAT);
+        }
+
+        // 3. Translate the entry function, then the others, which share
+        // its lists and have locals of their own
+        let functions = plugin_api::split_functions(&instrs);
+        let results: HashMap<&str, Option<FfiType>> = functions
+            .iter()
+            .filter_map(|(header, _)| match header {
+                Some(Instruction::Function { name, result, .. }) => Some((name.as_str(), *result)),
+                _ => None,
+            })
+            .collect();
+        for (header, range) in functions {
+            match header {
+                Some(Instruction::Function { name, params, .. }) => {
+                    script.push_str(&format!("\ndef tl_{}(int_stack, flt_stack, str_stack):\n", name));
+                    for (slot, &ty) in params.iter().enumerate().rev() {
+                        script.push_str(&format!("    local{} = {}.pop()\n", slot, stack_of(ty)));
+                    }
+                }
+                _ => {
+                    script.push_str("def main():\n");
+                    script.push_str("    int_stack = []\n");
+                    script.push_str("    flt_stack = []\n");
+                    script.push_str("    str_stack = []\n\n");
+                }
             }
+            script.push_str(&self.function_body(&instrs[range], &results)?);
         }
 
         // 4. Invoke main
-        code.push_str("\nif __name__ == \"__main__\":\n");
-        code.push_str("    main()\n");
+        script.push_str("\nif __name__ == \"__main__\":\n");
+        script.push_str("    main()\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.py", script)
+            .with_build_command("python3 main.py"))
     }
 
     fn name(&self) -> &'static str {
@@ -64,6 +122,226 @@ impl Backend<CompiledModule> for PythonBackend {
     }
 }
 
+impl PythonBackend {
+    /// The Python statements of one function's `instrs`, from one label to
+    /// the next. `results` has the result type of each function called.
+    fn function_body(
+        &self,
+        instrs: &[Instruction],
+        results: &HashMap<&str, Option<FfiType>>,
+    ) -> Result<String, BackendError> {
+        let segments_at: HashMap<u32, usize> = instrs
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Label(n) => Some(*n),
+                _ => None,
+            })
+            .zip(1..)
+            .collect();
+        let segment = |label: u32| {
+            segments_at
+                .get(&label)
+                .copied()
+                .ok_or_else(|| BackendError::InvalidIr(format!("jump to missing label {}", label)))
+        };
+        let mut segments = Vec::new();
+        let mut code = String::new();
+        for instr in instrs.iter().cloned() {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    int_stack.append({})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    flt_stack.append({})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    int_stack.append({})\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("    str_stack.append(\"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    if str_stack:\n\
+                         \n        sys.stdout.write(str_stack.pop())\n\
+                         \n    elif int_stack:\n\
+                         \n        sys.stdout.write(str(int_stack.pop()))\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    sys.stdout.write(str(int_stack.pop()))\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    tlang_print_float(flt_stack.pop())\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    sys.stdout.write(str_stack.pop())\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    sys.stdout.write(\"true\" if int_stack.pop() else \"false\")\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    flt_stack.append(float(int_stack.pop()))\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str("    f = flt_stack.pop()\n");
+                    code.push_str(&format!(
+                        "    int_stack.append(0 if f != f else {} if f <= {} else {} if f >= {} else int(f))\n",
+                        min, min_f, max, max_f
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => match super::wrap_masks(bits, signed) {
+                    None => {}
+                    Some((mask, 0)) => code.push_str(&format!("    int_stack[-1] &= {}\n", mask)),
+                    Some((mask, sign)) => code.push_str(&format!(
+                        "    int_stack[-1] = ((int_stack[-1] & {}) ^ {}) - {}\n",
+                        mask, sign, sign
+                    )),
+                },
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::StoreLocal { slot, ty } => {
+                    code.push_str(&format!("    local{} = {}.pop()\n", slot, stack_of(ty)));
+                }
+                Instruction::LoadLocal { slot, ty } => {
+                    code.push_str(&format!("    {}.append(local{})\n", stack_of(ty), slot));
+                }
+                Instruction::Exit => {
+                    code.push_str("    sys.stdout.flush()\n");
+                    code.push_str("    sys.exit(int_stack.pop())\n");
+                }
+                Instruction::Arith { op: ArithOp::Rem, ty: FfiType::Float { .. } } => {
+                    return Err(BackendError::InvalidIr("remainder of floats".to_string()));
+                }
+                Instruction::Arith { op: ArithOp::Div, ty: FfiType::Float { .. } } => {
+                    code.push_str("    b = flt_stack.pop()\n    flt_stack[-1] = tlang_divide(flt_stack[-1], b)\n");
+                }
+                Instruction::Arith { op, ty: FfiType::Float { .. } } => {
+                    code.push_str(&format!("    b = flt_stack.pop()\n    flt_stack[-1] = flt_stack[-1] {} b\n", op.symbol()));
+                }
+                Instruction::Arith { op: op @ (ArithOp::Div | ArithOp::Rem), .. } => {
+                    code.push_str("    b = int_stack.pop()\n    a = int_stack.pop()\n");
+                    code.push_str(QUOTIENT);
+                    code.push_str(match op {
+                        ArithOp::Div => "    int_stack.append((q + 2**63) % 2**64 - 2**63)\n",
+                        _ => "    int_stack.append(a - b * q)\n",
+                    });
+                }
+                Instruction::Arith { op, .. } => {
+                    // Wrapped to 64 bits, as the int stack holds them.
+                    code.push_str(&format!(
+                        "    b = int_stack.pop()\n    int_stack[-1] = (int_stack[-1] {} b + 2**63) % 2**64 - 2**63\n",
+                        op.symbol()
+                    ));
+                }
+                Instruction::Compare { op, ty } => {
+                    code.push_str(&format!(
+                        "    b = {s}.pop()\n    a = {s}.pop()\n    int_stack.append(int(a {} b))\n",
+                        op.symbol(),
+                        s = stack_of(ty)
+                    ));
+                }
+                Instruction::Not => code.push_str("    int_stack[-1] = int(not int_stack[-1])\n"),
+                Instruction::Label(_) => segments.push(std::mem::take(&mut code)),
+                Instruction::Jump(label) => {
+                    code.push_str(&format!("    pc = {}\n    continue\n", segment(label)?));
+                }
+                Instruction::JumpIfFalse(label) => {
+                    code.push_str(&format!(
+                        "    if not int_stack.pop():\n        pc = {}\n        continue\n",
+                        segment(label)?
+                    ));
+                }
+                Instruction::Overflows { op, ty } => {
+                    let FfiType::Int { bits, signed } = ty else {
+                        return Err(BackendError::InvalidIr(format!("cannot execute {:?}", instr)));
+                    };
+                    code.push_str("    b = int_stack.pop()\n    a = int_stack.pop()\n");
+                    if !signed && bits == 64 {
+                        code.push_str("    a, b = a % 2**64, b % 2**64\n");
+                    }
+                    let exact = match op {
+                        ArithOp::Rem => "a - b * q",
+                        ArithOp::Div => "q",
+                        _ => "a {} b",
+                    };
+                    if matches!(op, ArithOp::Div | ArithOp::Rem) {
+                        code.push_str(QUOTIENT);
+                    }
+                    let (min, max): (i128, i128) = match bits.min(64) {
+                        bits if signed => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+                        bits => (0, (1 << bits) - 1),
+                    };
+                    code.push_str(&format!(
+                        "    int_stack.append(int(not {} <= {} <= {}))\n",
+                        min,
+                        exact.replace("{}", op.symbol()),
+                        max
+                    ));
+                }
+                Instruction::Call { name, result, .. } => {
+                    code.push_str(&format!("    tl_{}(int_stack, flt_stack, str_stack)\n", name));
+                    let returns = results.get(name.as_str()).copied().flatten();
+                    if let (Some(ty), None) = (returns, result) {
+                        code.push_str(&format!("    {}.pop()\n", stack_of(ty)));
+                    }
+                }
+                Instruction::Return { .. } => code.push_str("    return\n"),
+                Instruction::Function { name, .. } => {
+                    return Err(BackendError::InvalidIr(format!("`{}` starts inside a function", name)));
+                }
+                Instruction::Trap { message, backtrace, abort } => {
+                    code.push_str(&format!(
+                        "    if int_stack.pop():\n        tlang_trap(\"{}\", \"{}\", {})\n",
+                        super::escape_str(&message),
+                        super::escape_str(&backtrace),
+                        if abort { "True" } else { "False" }
+                    ));
+                }
+            }
+        }
+        if segments.is_empty() {
+            Ok(code)
+        } else {
+            segments.push(code);
+            Ok(segment_loop(&segments))
+        }
+    }
+}
+
+
+/// `segments` run in turn by a loop testing `pc`, indented into it.
+fn segment_loop(segments: &[String]) -> String {
+    let mut code = String::from("    pc = 0\n    while True:\n");
+    for (i, segment) in segments.iter().enumerate() {
+        code.push_str(&format!("        if pc == {}:\n", i));
+        for line in segment.lines() {
+            if !line.is_empty() {
+                code.push_str("        ");
+                code.push_str(line);
+            }
+            code.push('\n');
+        }
+        code.push_str(&format!("            pc = {}\n", i + 1));
+    }
+    code.push_str("        break\n");
+    code
+}
+
+/// The list that holds values of type `ty`.
+fn stack_of(ty: FfiType) -> &'static str {
+    match ty {
+        FfiType::Float { .. } => "flt_stack",
+        FfiType::Str => "str_stack",
+        FfiType::Int { .. } | FfiType::Bool => "int_stack",
+    }
+}
+
 // Register this backend at startup
 static PYTHON_REG: Lazy<()> = Lazy::new(|| {
     register_backend(PythonBackend);
@@ -72,7 +350,7 @@ static PYTHON_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_PYTHON_REG: fn() = {
+pub(crate) static FORCE_PYTHON_REG: fn() = {
     fn init() {
         Lazy::force(&PYTHON_REG);
     }
@@ -1,61 +1,116 @@
 // File: compiler/src/backends/r/mod.rs
 //! R codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone R script
+//! Decodes our IR and emits a standalone R script
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct RBackend;
 
 impl Backend<CompiledModule> for RBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin R script
         let mut code = String::new();
         code.push_str("# Generated by T-Lang R backend\n");
         code.push_str("intStack <- c()\n");
+        code.push_str("fltStack <- c()\n");
         code.push_str("strStack <- c()\n\n");
 
         // 3. Replay each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("intStack <- c(intStack, {})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("strStack <- c(strStack, \"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "if (length(strStack) > 0) {\n\
-                     \tcat(tail(strStack, 1), sep = \"\")\n\
-                     \tstrStack <- head(strStack, -1)\n\
-                     } else if (length(intStack) > 0) {\n\
-                     \tcat(tail(intStack, 1), sep = \"\")\n\
-                     \tintStack <- head(intStack, -1)\n\
-                     }\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("intStack <- c(intStack, {})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("fltStack <- c(fltStack, {})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("intStack <- c(intStack, {})\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("strStack <- c(strStack, \"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "if (length(strStack) > 0) {\n\
+                         \tcat(tail(strStack, 1), sep = \"\")\n\
+                         \tstrStack <- head(strStack, -1)\n\
+                         } else if (length(intStack) > 0) {\n\
+                         \tcat(tail(intStack, 1), sep = \"\")\n\
+                         \tintStack <- head(intStack, -1)\n\
+                         }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("cat(format(tail(intStack, 1), scientific = FALSE), sep = \"\")\nintStack <- head(intStack, -1)\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("cat(sprintf(\"%.6f\", tail(fltStack, 1)), sep = \"\")\nfltStack <- head(fltStack, -1)\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("cat(tail(strStack, 1), sep = \"\")\nstrStack <- head(strStack, -1)\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("cat(if (tail(intStack, 1) != 0) \"true\" else \"false\", sep = \"\")\nintStack <- head(intStack, -1)\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("fltStack <- c(fltStack, tail(intStack, 1))\nintStack <- head(intStack, -1)\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str("f <- tail(fltStack, 1)\nfltStack <- head(fltStack, -1)\n");
+                    code.push_str(&format!(
+                        "intStack <- c(intStack, if (is.nan(f)) 0 else if (f <= {}) {} else if (f >= {}) {} else trunc(f))\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    // R's bitwise functions are 32-bit only; wrap with a modulus.
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "intStack[length(intStack)] <- (tail(intStack, 1) + {0}) %% {1} - {0}\n",
+                            sign,
+                            mask as i128 + 1
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => code.push_str("quit(save = \"no\", status = as.integer(tail(intStack, 1)))\n"),
             }
         }
 
         // 4. End of script
         code.push('\n');
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.R", code)
+            .with_build_command("Rscript main.R"))
     }
 
     fn name(&self) -> &'static str {
@@ -71,7 +126,7 @@ static R_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_R_REG: fn() = {
+pub(crate) static FORCE_R_REG: fn() = {
     fn init() {
         Lazy::force(&R_REG);
     }
@@ -1,60 +1,112 @@
 // File: compiler/src/backends/ruby/mod.rs
 //! Ruby codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Ruby script
+//! Decodes our IR and emits a standalone Ruby script
 //! that replays the instructions on two simple stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct RubyBackend;
 
 impl Backend<CompiledModule> for RubyBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Ruby script
         let mut code = String::new();
         code.push_str("# Generated by T-Lang Ruby backend\n");
         code.push_str("int_stack = []\n");
+        code.push_str("flt_stack = []\n");
         code.push_str("str_stack = []\n\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("int_stack.push({})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("str_stack.push(\"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "if !str_stack.empty?\n\
-                     \tprint str_stack.pop\n\
-                     elsif !int_stack.empty?\n\
-                     \tprint int_stack.pop\n\
-                     end\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("int_stack.push({})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("flt_stack.push({})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("int_stack.push({})\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("str_stack.push(\"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "if !str_stack.empty?\n\
+                         \tprint str_stack.pop\n\
+                         elsif !int_stack.empty?\n\
+                         \tprint int_stack.pop\n\
+                         end\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("print int_stack.pop\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("print format(\"%.6f\", flt_stack.pop)\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("print str_stack.pop\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("print(int_stack.pop != 0 ? \"true\" : \"false\")\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("flt_stack.push(int_stack.pop.to_f)\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str("f = flt_stack.pop\n");
+                    code.push_str(&format!(
+                        "int_stack.push(f.nan? ? 0 : f <= {} ? {} : f >= {} ? {} : f.truncate)\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "int_stack.push(((int_stack.pop & {}) ^ {}) - {})\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => code.push_str("exit int_stack.pop\n"),
             }
         }
 
         // 4. End of script
-        code.push_str("\n");
-        Ok(Box::new(code.into_bytes()))
+        code.push('\n');
+        Ok(CompiledArtifact::source(self.name(), "main.rb", code)
+            .with_build_command("ruby main.rb"))
     }
 
     fn name(&self) -> &'static str {
@@ -70,7 +122,7 @@ static RUBY_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_RUBY_REG: fn() = {
+pub(crate) static FORCE_RUBY_REG: fn() = {
     fn init() {
         Lazy::force(&RUBY_REG);
     }
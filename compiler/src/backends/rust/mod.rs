@@ -1,61 +1,322 @@
 // File: compiler/src/backends/rust/mod.rs
 //! Rust codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Rust program
-//! that replays the instructions on two Vec-based stacks and prints values.
+//! Decodes our IR and emits a Cargo project (`src/main.rs` plus `Cargo.toml`)
+//! whose program replays the instructions on three Vec-based stacks (ints, floats, strings) and prints values.
+//! Extern functions are declared in an `extern "C"` block and called in
+//! `unsafe`, with strings passed as NUL-terminated `CString`s. Globals are
+//! `static`s, `static mut` if they are mutable, with strings as `&str`.
+//! Locals are `let mut` variables declared at the top of `main`.
+//! Instructions with labels run as a loop over a `match` on the number of
+//! the label they continue at, whose arms run the instructions from one
+//! label to the next; a jump sets the number and starts the next round.
+//! Every function of the module but the entry is a Rust function
+//! `tl_<name>` that borrows the stacks of `main`, pops its arguments into
+//! locals of its own and leaves its result on them.
+//!
+//! A program calling `tstd`'s functions, whose names start `tlang_`,
+//! depends on `tstd`, whose sources the artifact carries in `tstd/`.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{
+    register_backend, ArithOp, Backend, CompiledArtifact, CompiledModule, BackendError, Constant,
+    ExternFunction, FfiType, Global, Instruction,
+};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
+use std::collections::HashMap;
+
+/// Package, and so binary, name written to the generated `Cargo.toml`.
+const CARGO_PACKAGE: &str = "main";
+
+/// Rust edition the generated code is written in.
+const RUST_EDITION: &str = "2024";
+
+/// The sources of `tstd`, by their path in the artifact.
+const TSTD_SOURCES: &[(&str, &str)] = &[
+    ("tstd/src/lib.rs", include_str!("../../../../tstd/src/lib.rs")),
+    ("tstd/src/arith.rs", include_str!("../../../../tstd/src/arith.rs")),
+    ("tstd/src/collections.rs", include_str!("../../../../tstd/src/collections.rs")),
+    ("tstd/src/coverage.rs", include_str!("../../../../tstd/src/coverage.rs")),
+    ("tstd/src/env.rs", include_str!("../../../../tstd/src/env.rs")),
+    ("tstd/src/io.rs", include_str!("../../../../tstd/src/io.rs")),
+    ("tstd/src/process.rs", include_str!("../../../../tstd/src/process.rs")),
+    ("tstd/src/random.rs", include_str!("../../../../tstd/src/random.rs")),
+    ("tstd/src/time.rs", include_str!("../../../../tstd/src/time.rs")),
+];
+
+/// Contents of the generated `Cargo.toml`. The program needs no crates but
+/// `tstd` when it calls its functions; the empty `[workspace]` keeps Cargo
+/// from adopting it into a workspace it was written inside of.
+fn cargo_toml(runtime: bool) -> String {
+    let dependencies = if runtime { "tstd = { path = \"tstd\" }\n" } else { "" };
+    format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"{}\"\n\n[dependencies]\n{}\n[workspace]\n",
+        CARGO_PACKAGE, RUST_EDITION, dependencies
+    )
+}
+
+/// Contents of the `Cargo.toml` of the `tstd` the artifact carries.
+fn tstd_cargo_toml() -> String {
+    format!("[package]\nname = \"tstd\"\nversion = \"0.1.0\"\nedition = \"{}\"\n", RUST_EDITION)
+}
+
+/// The Rust type values of `ty` are passed to C as.
+fn rust_type(ty: FfiType) -> Result<String, BackendError> {
+    Ok(match ty {
+        FfiType::Int { bits: bits @ (8 | 16 | 32 | 64), signed } => {
+            format!("{}{}", if signed { 'i' } else { 'u' }, bits)
+        }
+        FfiType::Float { bits: bits @ (32 | 64) } => format!("f{}", bits),
+        FfiType::Bool => "bool".to_string(),
+        FfiType::Str => "*const std::os::raw::c_char".to_string(),
+        other => return Err(BackendError::InvalidIr(format!("no C type for {:?}", other))),
+    })
+}
+
+/// What a `Trap` whose condition holds calls: report the panic as
+/// `tlang_panic` does and exit with 101, or abort.
+const TRAP: &str = r#"fn tlang_trap(message: &str, backtrace: &str, aborts: bool) -> ! {
+    if !aborts {
+        std::io::Write::flush(&mut std::io::stdout()).unwrap();
+    }
+    eprintln!("{}", message);
+    match std::env::var("TLANG_BACKTRACE") {
+        Ok(value) if value != "0" => eprintln!("stack backtrace:\n{}", backtrace),
+        _ => eprintln!("note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace"),
+    }
+    if aborts {
+        std::process::abort();
+    }
+    std::process::exit(101);
+}
+
+"#;
+
+/// The `extern "C"` block declaring `externs`, if there are any.
+fn extern_block(externs: &[ExternFunction]) -> Result<String, BackendError> {
+    if externs.is_empty() {
+        return Ok(String::new());
+    }
+    let mut code = String::from("#[allow(dead_code)]\nunsafe extern \"C\" {\n");
+    for function in externs {
+        let mut params = function
+            .params
+            .iter()
+            .map(|&ty| Ok(format!("_: {}", rust_type(ty)?)))
+            .collect::<Result<Vec<_>, BackendError>>()?;
+        if function.variadic {
+            params.push("...".to_string());
+        }
+        let ret = match function.return_type {
+            Some(ty) => format!(" -> {}", rust_type(ty)?),
+            None => String::new(),
+        };
+        code.push_str(&format!("    fn {}({}){};\n", function.name, params.join(", "), ret));
+    }
+    code.push_str("    #[link_name = \"fflush\"]\n");
+    code.push_str("    fn tl_fflush(stream: *mut std::ffi::c_void) -> i32;\n");
+    code.push_str("}\n\n");
+    Ok(code)
+}
+
+/// The `static` items defining `globals`.
+fn statics(globals: &[Global]) -> Result<String, BackendError> {
+    let mut code = String::new();
+    for global in globals {
+        let ty = match global.ty {
+            FfiType::Str => "&str".to_string(),
+            ty => rust_type(ty)?,
+        };
+        let value = match &global.value {
+            Constant::Int(n) => super::int_literal(*n),
+            Constant::Float(f) => super::float_literal(*f)?,
+            Constant::Bool(b) => b.to_string(),
+            Constant::Str(s) => format!("\"{}\"", super::escape_str(s)),
+        };
+        let keyword = if global.mutable { "static mut" } else { "static" };
+        code.push_str("#[allow(dead_code, non_upper_case_globals)]\n");
+        code.push_str(&format!("{} {}: {} = {};\n", keyword, global.name, ty, value));
+    }
+    if !code.is_empty() {
+        code.push('\n');
+    }
+    Ok(code)
+}
+
+/// Rust for a `CallExtern` of `function`: pop the arguments, call, push the
+/// result. Rust refuses `bool` variadic arguments, so those pass as `i32`.
+///
+/// Rust and C buffer stdout separately, so both are flushed around the call
+/// to keep what each prints in order.
+fn extern_call(
+    function: &ExternFunction,
+    args: &[FfiType],
+    result: Option<FfiType>,
+) -> Result<String, BackendError> {
+    let mut code = String::from("    {\n");
+    for (i, &ty) in args.iter().enumerate().rev() {
+        let value = match ty {
+            FfiType::Int { .. } => format!("int_stack.pop().unwrap() as {}", rust_type(ty)?),
+            FfiType::Float { .. } => format!("flt_stack.pop().unwrap() as {}", rust_type(ty)?),
+            FfiType::Bool if i >= function.params.len() => "int_stack.pop().unwrap() as i32".to_string(),
+            FfiType::Bool => "int_stack.pop().unwrap() != 0".to_string(),
+            FfiType::Str => "std::ffi::CString::new(str_stack.pop().unwrap()).unwrap()".to_string(),
+        };
+        code.push_str(&format!("        let arg{} = {};\n", i, value));
+    }
+    let locals: Vec<String> = args
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| if *ty == FfiType::Str { format!("arg{}.as_ptr()", i) } else { format!("arg{}", i) })
+        .collect();
+    let call = format!("unsafe {{ {}({}) }}", function.name, locals.join(", "));
+    code.push_str("        std::io::Write::flush(&mut std::io::stdout()).unwrap();\n");
+    code.push_str(&match result {
+        None => format!("        {};\n", call),
+        Some(FfiType::Int { .. } | FfiType::Bool) => format!("        int_stack.push({} as i64);\n", call),
+        Some(FfiType::Float { .. }) => format!("        flt_stack.push({} as f64);\n", call),
+        Some(FfiType::Str) => format!(
+            "        let ret = {};\n\
+             \x20       str_stack.push(if ret.is_null() {{\n\
+             \x20           String::new()\n\
+             \x20       }} else {{\n\
+             \x20           unsafe {{ std::ffi::CStr::from_ptr(ret) }}.to_string_lossy().into_owned()\n\
+             \x20       }});\n",
+            call
+        ),
+    });
+    code.push_str("        unsafe { tl_fflush(std::ptr::null_mut()) };\n");
+    code.push_str("    }\n");
+    Ok(code)
+}
+
+/// The stack that holds values of type `ty`.
+fn stack_of(ty: FfiType) -> &'static str {
+    match ty {
+        FfiType::Float { .. } => "flt_stack",
+        FfiType::Str => "str_stack",
+        FfiType::Int { .. } | FfiType::Bool => "int_stack",
+    }
+}
+
+/// The segment each label starts, by its number: segment 0 runs from the
+/// start to the first label, segment `n` from the `n`th label on.
+fn segments_of(instrs: &[Instruction]) -> HashMap<u32, usize> {
+    let labels = instrs.iter().filter_map(|instr| match instr {
+        Instruction::Label(n) => Some(*n),
+        _ => None,
+    });
+    labels.zip(1..).collect()
+}
+
+/// `segments` run in turn by a loop over a `match` on `pc`, indented into it.
+fn segment_loop(segments: &[String]) -> String {
+    let mut code = String::from("    let mut pc = 0;\n    #[allow(unreachable_code)]\n    loop {\n        match pc {\n");
+    for (i, segment) in segments.iter().enumerate() {
+        code.push_str(&format!("            {} => {{\n", i));
+        for line in segment.lines() {
+            if !line.is_empty() {
+                code.push_str("            ");
+                code.push_str(line);
+            }
+            code.push('\n');
+        }
+        code.push_str(&format!("                pc = {};\n            }}\n", i + 1));
+    }
+    code.push_str("            _ => break,\n        }\n    }\n");
+    code
+}
+
+/// A `let mut` for every local slot `instrs` use and for the parameters
+/// `params`, holding the type of its stack. They start out zeroed, which a
+/// first store may overwrite unread.
+fn local_declarations(params: &[FfiType], instrs: &[Instruction]) -> Result<String, BackendError> {
+    let slots = super::local_slots(params, instrs)?;
+    Ok(slots
+        .into_iter()
+        .map(|(slot, ty)| {
+            let (rust_ty, zero) = match ty {
+                FfiType::Float { .. } => ("f64", "0.0"),
+                FfiType::Str => ("String", "String::new()"),
+                FfiType::Int { .. } | FfiType::Bool => ("i64", "0"),
+            };
+            format!("    #[allow(unused_assignments)]\n    let mut local{}: {} = {};\n", slot, rust_ty, zero)
+        })
+        .collect())
+}
 
 #[derive(Debug)]
 pub struct RustBackend;
 
 impl Backend<CompiledModule> for RustBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = module.decode()?;
 
-        // 2. Begin Rust source
-        let mut code = String::new();
-        code.push_str("// Generated by T-Lang Rust backend\n");
-        code.push_str("fn main() {\n");
-        code.push_str("    let mut int_stack: Vec<i64> = Vec::new();\n");
-        code.push_str("    let mut str_stack: Vec<String> = Vec::new();\n\n");
-
-        // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    int_stack.push({});\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("    str_stack.push(String::from(\"{}\"));\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "    if let Some(s) = str_stack.pop() {\n\
-                     \n        print!(\"{}\", s);\n\
-                     \n    } else if let Some(i) = int_stack.pop() {\n\
-                     \n        print!(\"{}\", i);\n\
-                     \n    }\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
-            }
+        // 2. Begin Rust source; `use tstd as _` links the crate whose
+        // functions the extern block declares
+        let runtime = module.externs.iter().any(|function| function.name.starts_with("tlang_"));
+        let mut main = String::new();
+        main.push_str("// Generated by T-Lang Rust backend\n");
+        if runtime {
+            main.push_str("use tstd as _;\n\n");
+        }
+        main.push_str(&extern_block(&module.externs)?);
+        main.push_str(&statics(&module.globals)?);
+        if instrs.iter().any(|instr| matches!(instr, Instruction::Trap { .. })) {
+            main.push_str(TRAP);
         }
 
-        // 4. End of main
-        code.push_str("}\n");
+        // 3. Translate the entry function as `main`, then the others, which
+        // are passed its stacks and have locals of their own
+        let functions = plugin_api::split_functions(&instrs);
+        let results: HashMap<&str, Option<FfiType>> = functions
+            .iter()
+            .filter_map(|(header, _)| match header {
+                Some(Instruction::Function { name, result, .. }) => Some((name.as_str(), *result)),
+                _ => None,
+            })
+            .collect();
+        for (header, range) in functions {
+            let instrs = &instrs[range];
+            match header {
+                Some(Instruction::Function { name, params, .. }) => {
+                    main.push_str("\n#[allow(unused_variables)]\n");
+                    main.push_str(&format!(
+                        "fn tl_{}(\n    int_stack: &mut Vec<i64>,\n    flt_stack: &mut Vec<f64>,\n    \
+                         str_stack: &mut Vec<String>,\n) {{\n",
+                        name
+                    ));
+                    main.push_str(&local_declarations(params, instrs)?);
+                    for (slot, &ty) in params.iter().enumerate().rev() {
+                        main.push_str(&format!("    local{} = {}.pop().unwrap();\n", slot, stack_of(ty)));
+                    }
+                }
+                _ => {
+                    main.push_str("fn main() {\n");
+                    main.push_str("    let mut int_stack: Vec<i64> = Vec::new();\n");
+                    main.push_str("    let mut flt_stack: Vec<f64> = Vec::new();\n");
+                    main.push_str("    let mut str_stack: Vec<String> = Vec::new();\n");
+                    main.push_str(&local_declarations(&[], instrs)?);
+                }
+            }
+            main.push('\n');
+            main.push_str(&self.function_body(&module, instrs, header.is_none(), &results)?);
+            main.push_str("}\n");
+        }
+        let code = main;
 
-        Ok(Box::new(code.into_bytes()))
+        let mut artifact = CompiledArtifact::source(self.name(), "src/main.rs", code)
+            .with_file("Cargo.toml", cargo_toml(runtime).into_bytes());
+        if runtime {
+            artifact = artifact.with_file("tstd/Cargo.toml", tstd_cargo_toml().into_bytes());
+            for (path, source) in TSTD_SOURCES {
+                artifact = artifact.with_file(*path, source.as_bytes().to_vec());
+            }
+        }
+        Ok(artifact
+            .with_build_command("cargo build --release")
+            .with_build_command(format!("./target/release/{}", CARGO_PACKAGE)))
     }
 
     fn name(&self) -> &'static str {
@@ -63,6 +324,198 @@ impl Backend<CompiledModule> for RustBackend {
     }
 }
 
+impl RustBackend {
+    /// The Rust statements of one function's `instrs`, from one label to
+    /// the next, `entry` if the function is `main`, which owns the stacks.
+    /// `results` has the result type of each function called.
+    fn function_body(
+        &self,
+        module: &CompiledModule,
+        instrs: &[Instruction],
+        entry: bool,
+        results: &HashMap<&str, Option<FfiType>>,
+    ) -> Result<String, BackendError> {
+        let segments_at = segments_of(instrs);
+        let segment = |label: u32| {
+            segments_at
+                .get(&label)
+                .copied()
+                .ok_or_else(|| BackendError::InvalidIr(format!("jump to missing label {}", label)))
+        };
+        let mut segments = Vec::new();
+        let mut code = String::new();
+        for instr in instrs.iter().cloned() {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    int_stack.push({});\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    flt_stack.push({});\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    int_stack.push({});\n", b as i64));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("    str_stack.push(String::from(\"{}\"));\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    if let Some(s) = str_stack.pop() {\n\
+                         \n        print!(\"{}\", s);\n\
+                         \n    } else if let Some(i) = int_stack.pop() {\n\
+                         \n        print!(\"{}\", i);\n\
+                         \n    }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    print!(\"{}\", int_stack.pop().unwrap());\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    print!(\"{:.6}\", flt_stack.pop().unwrap());\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    print!(\"{}\", str_stack.pop().unwrap());\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    print!(\"{}\", int_stack.pop().unwrap() != 0);\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    flt_stack.push(int_stack.pop().unwrap() as f64);\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // `as i64` already truncates, saturates and maps NaN to 0.
+                    let (min, max) = plugin_api::int_bounds(bits, signed);
+                    code.push_str(&format!(
+                        "    int_stack.push((flt_stack.pop().unwrap() as i64).clamp({}, {}));\n",
+                        min, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    let v = int_stack.pop().unwrap();\n    int_stack.push(((v & {}) ^ {}) - {});\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::CallExtern { name, args, result } => {
+                    let function = module.extern_function(&name)?;
+                    code.push_str(&extern_call(function, &args, result)?);
+                }
+                Instruction::StoreLocal { slot, ty } => {
+                    code.push_str(&format!("    local{} = {}.pop().unwrap();\n", slot, stack_of(ty)));
+                }
+                Instruction::LoadLocal { slot, ty: FfiType::Str } => {
+                    code.push_str(&format!("    str_stack.push(local{}.clone());\n", slot));
+                }
+                Instruction::LoadLocal { slot, ty } => {
+                    code.push_str(&format!("    {}.push(local{});\n", stack_of(ty), slot));
+                }
+                Instruction::Exit => {
+                    // `exit` runs no destructors, so stdout is flushed first.
+                    code.push_str("    std::io::Write::flush(&mut std::io::stdout()).unwrap();\n");
+                    code.push_str("    std::process::exit(int_stack.pop().unwrap() as i32);\n");
+                }
+                Instruction::Arith { op: ArithOp::Rem, ty: FfiType::Float { .. } } => {
+                    return Err(BackendError::InvalidIr("remainder of floats".to_string()));
+                }
+                Instruction::Arith { op, ty: FfiType::Float { .. } } => {
+                    code.push_str(&format!(
+                        "    let b = flt_stack.pop().unwrap();\n    let a = flt_stack.pop().unwrap();\n    flt_stack.push(a {} b);\n",
+                        op.symbol()
+                    ));
+                }
+                Instruction::Arith { op, .. } => {
+                    let method = match op {
+                        ArithOp::Add => "wrapping_add",
+                        ArithOp::Sub => "wrapping_sub",
+                        ArithOp::Mul => "wrapping_mul",
+                        ArithOp::Div => "wrapping_div",
+                        ArithOp::Rem => "wrapping_rem",
+                    };
+                    // A divisor of zero gives 0, as in `ArithOp::int`.
+                    let value = match op {
+                        ArithOp::Div | ArithOp::Rem => format!("if b == 0 {{ 0 }} else {{ a.{}(b) }}", method),
+                        _ => format!("a.{}(b)", method),
+                    };
+                    code.push_str(&format!(
+                        "    let b = int_stack.pop().unwrap();\n    let a = int_stack.pop().unwrap();\n    int_stack.push({});\n",
+                        value
+                    ));
+                }
+                Instruction::Compare { op, ty } => {
+                    code.push_str(&format!(
+                        "    let b = {s}.pop().unwrap();\n    let a = {s}.pop().unwrap();\n    int_stack.push((a {} b) as i64);\n",
+                        op.symbol(),
+                        s = stack_of(ty)
+                    ));
+                }
+                Instruction::Not => {
+                    code.push_str("    let v = int_stack.pop().unwrap();\n    int_stack.push((v == 0) as i64);\n");
+                }
+                Instruction::Label(_) => segments.push(std::mem::take(&mut code)),
+                Instruction::Jump(label) => {
+                    code.push_str(&format!("    pc = {};\n    continue;\n", segment(label)?));
+                }
+                Instruction::JumpIfFalse(label) => {
+                    code.push_str(&format!(
+                        "    if int_stack.pop().unwrap() == 0 {{\n        pc = {};\n        continue;\n    }}\n",
+                        segment(label)?
+                    ));
+                }
+                Instruction::Overflows { op, ty } => {
+                    let int = rust_type(ty)?;
+                    // Only `Div` by zero fails without being out of range.
+                    let overflows = match op {
+                        ArithOp::Add => format!("(a as {int}).checked_add(b as {int}).is_none()", int = int),
+                        ArithOp::Sub => format!("(a as {int}).checked_sub(b as {int}).is_none()", int = int),
+                        ArithOp::Mul => format!("(a as {int}).checked_mul(b as {int}).is_none()", int = int),
+                        ArithOp::Div => format!("b != 0 && (a as {int}).checked_div(b as {int}).is_none()", int = int),
+                        ArithOp::Rem => "false".to_string(),
+                    };
+                    code.push_str(&format!(
+                        "    let b = int_stack.pop().unwrap();\n    let a = int_stack.pop().unwrap();\n    int_stack.push(({}) as i64);\n",
+                        overflows
+                    ));
+                }
+                Instruction::Call { name, result, .. } => {
+                    let stacks = match entry {
+                        true => "&mut int_stack, &mut flt_stack, &mut str_stack",
+                        false => "int_stack, flt_stack, str_stack",
+                    };
+                    code.push_str(&format!("    tl_{}({});\n", name, stacks));
+                    let returns = results.get(name.as_str()).copied().flatten();
+                    if let (Some(ty), None) = (returns, result) {
+                        code.push_str(&format!("    {}.pop();\n", stack_of(ty)));
+                    }
+                }
+                Instruction::Return { .. } => code.push_str("    return;\n"),
+                Instruction::Function { name, .. } => {
+                    return Err(BackendError::InvalidIr(format!("`{}` starts inside a function", name)));
+                }
+                Instruction::Trap { message, backtrace, abort } => {
+                    code.push_str(&format!(
+                        "    if int_stack.pop().unwrap() != 0 {{\n        tlang_trap(\"{}\", \"{}\", {});\n    }}\n",
+                        super::escape_str(&message),
+                        super::escape_str(&backtrace),
+                        abort
+                    ));
+                }
+            }
+        }
+        if segments.is_empty() {
+            Ok(code)
+        } else {
+            segments.push(code);
+            Ok(segment_loop(&segments))
+        }
+    }
+}
+
+
 // Register this backend at startup
 static RUST_REG: Lazy<()> = Lazy::new(|| {
     register_backend(RustBackend);
@@ -71,7 +524,7 @@ static RUST_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_RUST_REG: fn() = {
+pub(crate) static FORCE_RUST_REG: fn() = {
     fn init() {
         Lazy::force(&RUST_REG);
     }
@@ -1,63 +1,132 @@
 // File: compiler/src/backends/scheme/mod.rs
 //! Scheme codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Scheme script
+//! Decodes our IR and emits a standalone Scheme script
 //! that replays the instructions on a simple list-based stack and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct SchemeBackend;
 
 impl Backend<CompiledModule> for SchemeBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Scheme script
         let mut code = String::new();
         code.push_str(";;; Generated by T-Lang Scheme backend\n");
         code.push_str("(define int-stack '())\n");
+        code.push_str("(define flt-stack '())\n");
         code.push_str("(define str-stack '())\n\n");
+        // R7RS has no printf, so fixed six-digit formatting is spelled out by hand.
+        code.push_str(
+            "(define (tlang-fixed6 x)\n\
+             \x20 (let* ((n (exact (round (* (abs x) 1000000))))\n\
+             \x20        (frac (number->string (remainder n 1000000))))\n\
+             \x20   (string-append (if (< x 0) \"-\" \"\")\n\
+             \x20                  (number->string (quotient n 1000000))\n\
+             \x20                  \".\"\n\
+             \x20                  (make-string (- 6 (string-length frac)) #\\0)\n\
+             \x20                  frac)))\n\n",
+        );
         code.push_str("(define (main)\n");
 
         // 3. Replay each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("  (set! int-stack (cons {} int-stack))\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("  (set! str-stack (cons \"{}\" str-stack))\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "  (if (not (null? str-stack))\n\
-                     \n    (begin\n\
-                     \n      (display (car str-stack))\n\
-                     \n      (set! str-stack (cdr str-stack)))\n\
-                     \n    (when (not (null? int-stack))\n\
-                     \n      (display (car int-stack))\n\
-                     \n      (set! int-stack (cdr int-stack))))\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("  (set! int-stack (cons {} int-stack))\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("  (set! flt-stack (cons {} flt-stack))\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("  (set! int-stack (cons {} int-stack))\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("  (set! str-stack (cons \"{}\" str-stack))\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "  (if (not (null? str-stack))\n\
+                         \n    (begin\n\
+                         \n      (display (car str-stack))\n\
+                         \n      (set! str-stack (cdr str-stack)))\n\
+                         \n    (when (not (null? int-stack))\n\
+                         \n      (display (car int-stack))\n\
+                         \n      (set! int-stack (cdr int-stack))))\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("  (display (car int-stack))\n  (set! int-stack (cdr int-stack))\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("  (display (tlang-fixed6 (car flt-stack)))\n  (set! flt-stack (cdr flt-stack))\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("  (display (car str-stack))\n  (set! str-stack (cdr str-stack))\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("  (display (if (= (car int-stack) 0) \"false\" \"true\"))\n  (set! int-stack (cdr int-stack))\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("  (set! flt-stack (cons (inexact (car int-stack)) flt-stack))\n  (set! int-stack (cdr int-stack))\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "  (let ((f (car flt-stack)))\n\
+                         \x20   (set! flt-stack (cdr flt-stack))\n\
+                         \x20   (set! int-stack (cons (cond ((nan? f) 0) ((<= f {}) {}) ((>= f {}) {}) (else (exact (truncate f)))) int-stack)))\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    // R7RS has no bitwise operators; wrap with a modulus.
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "  (set! int-stack (cons (- (modulo (+ (car int-stack) {0}) {1}) {0}) (cdr int-stack)))\n",
+                            sign,
+                            mask as i128 + 1
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("  (exit (car int-stack))\n");
+                }
             }
         }
 
         // 4. End main and invoke
         code.push_str(")\n\n(main)\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.scm", code)
+            .with_build_command("guile main.scm"))
     }
 
     fn name(&self) -> &'static str {
@@ -73,7 +142,7 @@ static SCHEME_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_SCHEME_REG: fn() = {
+pub(crate) static FORCE_SCHEME_REG: fn() = {
     fn init() {
         Lazy::force(&SCHEME_REG);
     }
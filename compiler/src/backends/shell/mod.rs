@@ -1,51 +1,54 @@
 // File: compiler/src/backends/shell/mod.rs
 //! Shell (Bash) codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Bash script
+//! Decodes our IR and emits a standalone Bash script
 //! that replays the instructions on two arrays and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct ShellBackend;
 
 impl Backend<CompiledModule> for ShellBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // Begin Bash script
         let mut code = String::new();
         code.push_str("#!/usr/bin/env bash\n");
         code.push_str("set -euo pipefail\n\n");
         code.push_str("int_stack=()\n");
+        code.push_str("flt_stack=()\n");
         code.push_str("str_stack=()\n\n");
 
         // Replay each IR instruction
         code.push_str("for instr in \\\n");
-        for line in ir.lines() {
-            let inst = line.trim();
-            if inst.is_empty() || inst == "Nop" {
-                continue;
-            }
-            // escape backslashes and quotes in comment
-            let esc = inst.replace('\\', "\\\\").replace('"', "\\\"");
+        for instr in &instrs {
+            // Strings are embedded unescaped; the bash quoting below protects them.
+            let inst = match instr {
+                Instruction::Nop => continue,
+                Instruction::PushStr(s) => format!("PushStr(\"{}\")", s),
+                other => format!("{:?}", other),
+            };
+            // escape for a double-quoted bash word
+            let esc = inst
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "\\$")
+                .replace('`', "\\`");
             code.push_str(&format!("    \"{}\" \\\n", esc));
         }
         code.push_str("; do\n");
-        code.push_str("  if [[ \"$instr\" == PushInt(*\") ]]; then\n");
+        code.push_str("  if [[ \"$instr\" == \"PushInt(\"*\")\" ]]; then\n");
         code.push_str("    n=${instr#PushInt(}; n=${n%)}\n");
         code.push_str("    int_stack+=(\"$n\")\n");
-        code.push_str("  elif [[ \"$instr\" == PushStr(\\\"*\\\") ]]; then\n");
+        code.push_str("  elif [[ \"$instr\" == 'PushStr(\"'*'\")' ]]; then\n");
         code.push_str("    raw=${instr#PushStr(\\\"}\n");
         code.push_str("    raw=${raw%\\\")}\n");
-        code.push_str("    # escape for bash literal\n");
-        code.push_str("    esc=${raw//\"/\\\\\\\"}\n");
-        code.push_str("    str_stack+=(\"$esc\")\n");
+        code.push_str("    str_stack+=(\"$raw\")\n");
         code.push_str("  elif [[ \"$instr\" == CallPrint ]]; then\n");
         code.push_str("    if (( ${#str_stack[@]} )); then\n");
         code.push_str("      s=${str_stack[-1]}\n");
@@ -56,10 +59,50 @@ impl Backend<CompiledModule> for ShellBackend {
         code.push_str("      echo -n \"$v\"\n");
         code.push_str("      unset 'int_stack[-1]'\n");
         code.push_str("    fi\n");
+        code.push_str("  elif [[ \"$instr\" == \"PushFloat(\"*\")\" ]]; then\n");
+        code.push_str("    f=${instr#PushFloat(}; f=${f%)}\n");
+        code.push_str("    flt_stack+=(\"$f\")\n");
+        code.push_str("  elif [[ \"$instr\" == \"PushBool(\"*\")\" ]]; then\n");
+        code.push_str("    b=${instr#PushBool(}; b=${b%)}\n");
+        code.push_str("    if [[ \"$b\" == true ]]; then int_stack+=(1); else int_stack+=(0); fi\n");
+        code.push_str("  elif [[ \"$instr\" == PrintInt ]]; then\n");
+        code.push_str("    echo -n \"${int_stack[-1]}\"\n");
+        code.push_str("    unset 'int_stack[-1]'\n");
+        code.push_str("  elif [[ \"$instr\" == PrintFloat ]]; then\n");
+        code.push_str("    LC_NUMERIC=C printf '%.6f' \"${flt_stack[-1]}\"\n");
+        code.push_str("    unset 'flt_stack[-1]'\n");
+        code.push_str("  elif [[ \"$instr\" == PrintStr ]]; then\n");
+        code.push_str("    echo -n \"${str_stack[-1]}\"\n");
+        code.push_str("    unset 'str_stack[-1]'\n");
+        code.push_str("  elif [[ \"$instr\" == PrintBool ]]; then\n");
+        code.push_str("    if (( ${int_stack[-1]} )); then echo -n true; else echo -n false; fi\n");
+        code.push_str("    unset 'int_stack[-1]'\n");
+        code.push_str("  elif [[ \"$instr\" == IntToFloat ]]; then\n");
+        code.push_str("    flt_stack+=(\"${int_stack[-1]}\")\n");
+        code.push_str("    unset 'int_stack[-1]'\n");
+        code.push_str("  elif [[ \"$instr\" =~ ^(FloatToInt|WrapInt)\\ \\{\\ bits:\\ ([0-9]+),\\ signed:\\ (true|false)\\ \\}$ ]]; then\n");
+        code.push_str("    bits=${BASH_REMATCH[2]}\n");
+        code.push_str("    if [[ \"${BASH_REMATCH[3]}\" == true ]]; then\n");
+        code.push_str("      lo=$(( -(1 << (bits - 1)) )); hi=$(( (1 << (bits - 1)) - 1 )); sign=$(( 1 << (bits - 1) ))\n");
+        code.push_str("    elif (( bits >= 64 )); then\n");
+        code.push_str("      lo=0; hi=9223372036854775807; sign=0\n");
+        code.push_str("    else\n");
+        code.push_str("      lo=0; hi=$(( (1 << bits) - 1 )); sign=0\n");
+        code.push_str("    fi\n");
+        code.push_str("    if [[ \"${BASH_REMATCH[1]}\" == FloatToInt ]]; then\n");
+        // bash has no floating point; awk truncates and saturates.
+        code.push_str("      n=$(awk -v f=\"${flt_stack[-1]}\" -v lo=\"$lo\" -v hi=\"$hi\" 'BEGIN { if (f != f) print 0; else if (f <= lo + 0) print lo; else if (f >= hi + 0) print hi; else printf \"%d\\n\", int(f) }')\n");
+        code.push_str("      unset 'flt_stack[-1]'\n");
+        code.push_str("      int_stack+=(\"$n\")\n");
+        code.push_str("    elif (( bits < 64 )); then\n");
+        code.push_str("      mask=$(( (1 << bits) - 1 ))\n");
+        code.push_str("      int_stack[-1]=$(( ((int_stack[-1] & mask) ^ sign) - sign ))\n");
+        code.push_str("    fi\n");
         code.push_str("  fi\n");
         code.push_str("done\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.sh", code)
+            .with_build_command("bash main.sh"))
     }
 
     fn name(&self) -> &'static str {
@@ -75,7 +118,7 @@ static SHELL_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_SHELL_REG: fn() = {
+pub(crate) static FORCE_SHELL_REG: fn() = {
     fn init() {
         Lazy::force(&SHELL_REG);
     }
@@ -1,56 +1,113 @@
 // File: compiler/src/backends/swift/mod.rs
 //! Swift codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Swift script
+//! Decodes our IR and emits a standalone Swift script
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct SwiftBackend;
 
 impl Backend<CompiledModule> for SwiftBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Swift source
         let mut code = String::new();
         code.push_str("// Generated by T-Lang Swift backend\n");
         code.push_str("import Foundation\n\n");
         code.push_str("var intStack: [Int] = []\n");
+        code.push_str("var fltStack: [Double] = []\n");
         code.push_str("var strStack: [String] = []\n\n");
         code.push_str("func main() {\n");
 
         // 3. Replay each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    intStack.append({})\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("    strStack.append(\"{}\")\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "    if let s = strStack.popLast() {\n\
-                     \n        print(s, terminator: \"\")\n\
-                     \n    } else if let i = intStack.popLast() {\n\
-                     \n        print(i, terminator: \"\")\n\
-                     \n    }\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    intStack.append({})\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    fltStack.append({})\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    intStack.append({})\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("    strStack.append(\"{}\")\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    if let s = strStack.popLast() {\n\
+                         \n        print(s, terminator: \"\")\n\
+                         \n    } else if let i = intStack.popLast() {\n\
+                         \n        print(i, terminator: \"\")\n\
+                         \n    }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    print(intStack.removeLast(), terminator: \"\")\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    print(String(format: \"%.6f\", fltStack.removeLast()), terminator: \"\")\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    print(strStack.removeLast(), terminator: \"\")\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    print(intStack.removeLast() != 0, terminator: \"\")\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    fltStack.append(Double(intStack.removeLast()))\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // `Int(_:)` traps on NaN and out-of-range values, so check them first.
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "    do {{\n        let f = fltStack.removeLast()\n        intStack.append(f.isNaN ? 0 : f <= {} ? {} : f >= {} ? {} : Int(f))\n    }}\n",
+                        min_f,
+                        super::int_literal(min),
+                        max_f,
+                        max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    intStack[intStack.count - 1] = ((intStack[intStack.count - 1] & {}) ^ {}) - {}\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    code.push_str("    fflush(stdout)\n");
+                    code.push_str("    exit(Int32(truncatingIfNeeded: intStack.removeLast()))\n");
+                }
             }
         }
 
@@ -58,7 +115,8 @@ impl Backend<CompiledModule> for SwiftBackend {
         code.push_str("}\n\n");
         code.push_str("main()\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.swift", code)
+            .with_build_command("swift main.swift"))
     }
 
     fn name(&self) -> &'static str {
@@ -74,7 +132,7 @@ static SWIFT_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_SWIFT_REG: fn() = {
+pub(crate) static FORCE_SWIFT_REG: fn() = {
     fn init() {
         Lazy::force(&SWIFT_REG);
     }
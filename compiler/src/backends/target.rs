@@ -0,0 +1,108 @@
+// File: compiler/src/backends/target.rs
+//! Target-triple handling shared by the native backends (C, LLVM).
+//!
+//! Turns a `BackendConfig::target_triple` into the compiler flags and the
+//! preprocessor check the generated code needs for ARM, RISC-V and x86.
+
+use plugin_api::{BackendConfig, BackendError};
+use std::str::FromStr;
+use target_lexicon::{Architecture, Environment, OperatingSystem, Triple};
+
+/// What a backend needs to know about the machine it generates code for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetInfo {
+    /// Normalized target triple.
+    pub triple: String,
+    /// Architecture name as spelled in the triple (`aarch64`, `riscv64gc`, ...).
+    pub arch: String,
+    /// Pointer width in bits.
+    pub pointer_width: u8,
+    /// C preprocessor condition that holds when compiling for this target.
+    pub c_guard: Option<String>,
+    /// Extra C compiler flags selecting the ABI (float ABI, RISC-V ISA/ABI).
+    pub abi_flags: Vec<String>,
+    /// No operating system (`*-none-*` triples): the program needs its own
+    /// startup code and linker script.
+    pub bare_metal: bool,
+}
+
+/// Resolve the target of `config`, or `None` when compiling for the host.
+pub fn target_info(config: &BackendConfig) -> Result<Option<TargetInfo>, BackendError> {
+    match &config.target_triple {
+        Some(triple) => parse_target(triple).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Parse a target triple such as `armv7-unknown-linux-gnueabihf`.
+pub fn parse_target(triple: &str) -> Result<TargetInfo, BackendError> {
+    let parsed = Triple::from_str(triple)
+        .map_err(|e| BackendError::Generic(format!("Invalid target triple '{}': {}", triple, e)))?;
+    let pointer_width = parsed
+        .pointer_width()
+        .map_err(|_| BackendError::Generic(format!("Unknown pointer width for '{}'", triple)))?
+        .bits();
+    let arch = parsed.architecture.to_string();
+
+    let c_guard = match parsed.architecture {
+        Architecture::X86_64 => Some("defined(__x86_64__)".to_string()),
+        Architecture::X86_32(_) => Some("defined(__i386__)".to_string()),
+        Architecture::Aarch64(_) => Some("defined(__aarch64__)".to_string()),
+        Architecture::Arm(_) => Some("defined(__arm__)".to_string()),
+        Architecture::Riscv32(_) | Architecture::Riscv64(_) => Some(format!(
+            "defined(__riscv) && __riscv_xlen == {}",
+            pointer_width
+        )),
+        _ => None,
+    };
+
+    let mut abi_flags = Vec::new();
+    match parsed.architecture {
+        Architecture::Arm(_) => {
+            let hard_float = matches!(
+                parsed.environment,
+                Environment::Gnueabihf | Environment::Musleabihf | Environment::Eabihf
+            );
+            let float_abi = if hard_float { "hard" } else { "soft" };
+            abi_flags.push(format!("-mfloat-abi={}", float_abi));
+        }
+        Architecture::Riscv32(_) | Architecture::Riscv64(_) => {
+            abi_flags.extend(riscv_flags(&arch, pointer_width));
+        }
+        _ => {}
+    }
+
+    Ok(TargetInfo {
+        triple: parsed.to_string(),
+        arch,
+        pointer_width,
+        c_guard,
+        abi_flags,
+        bare_metal: parsed.operating_system == OperatingSystem::None_,
+    })
+}
+
+/// `-march`/`-mabi` for a RISC-V architecture name like `riscv32imac`.
+fn riscv_flags(arch: &str, pointer_width: u8) -> Vec<String> {
+    let width = pointer_width.to_string();
+    let extensions = arch
+        .strip_prefix("riscv")
+        .and_then(|rest| rest.strip_prefix(width.as_str()))
+        .filter(|ext| !ext.is_empty())
+        .unwrap_or("gc");
+
+    // `g` implies the D (double-precision float) extension.
+    let float_suffix = if extensions.contains('g') || extensions.contains('d') {
+        "d"
+    } else if extensions.contains('f') {
+        "f"
+    } else {
+        ""
+    };
+    let base_abi = if pointer_width == 64 { "lp64" } else { "ilp32" };
+
+    vec![
+        format!("-march=rv{}{}", width, extensions),
+        format!("-mabi={}{}", base_abi, float_suffix),
+    ]
+}
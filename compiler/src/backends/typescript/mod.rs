@@ -1,59 +1,114 @@
 // File: compiler/src/backends/typescript/mod.rs
 //! TypeScript codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone TypeScript script
+//! Decodes our IR and emits a standalone TypeScript script
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct TypescriptBackend;
 
 impl Backend<CompiledModule> for TypescriptBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin TypeScript source
         let mut code = String::new();
         code.push_str("// Generated by T-Lang TypeScript backend\n");
         code.push_str("const intStack: number[] = [];\n");
+        code.push_str("const fltStack: number[] = [];\n");
         code.push_str("const strStack: string[] = [];\n\n");
 
         // 3. Replay each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("intStack.push({});\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("strStack.push(\"{}\");\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "if (strStack.length > 0) {\n\
-                     \tprocess.stdout.write(strStack.pop()!);\n\
-                     } else if (intStack.length > 0) {\n\
-                     \tprocess.stdout.write(intStack.pop()!.toString());\n\
-                     }\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("intStack.push({});\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("fltStack.push({});\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("intStack.push({});\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("strStack.push(\"{}\");\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "if (strStack.length > 0) {\n\
+                         \tprocess.stdout.write(strStack.pop()!);\n\
+                         } else if (intStack.length > 0) {\n\
+                         \tprocess.stdout.write(intStack.pop()!.toString());\n\
+                         }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("process.stdout.write(intStack.pop()!.toString());\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("process.stdout.write(fltStack.pop()!.toFixed(6));\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("process.stdout.write(strStack.pop()!);\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("process.stdout.write(intStack.pop()! !== 0 ? \"true\" : \"false\");\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("fltStack.push(intStack.pop()!);\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "{{ const f = fltStack.pop()!; intStack.push(Number.isNaN(f) ? 0 : f <= {} ? {} : f >= {} ? {} : Math.trunc(f)); }}\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    // Bitwise operators work on 32 bits, so wrap through BigInt.
+                    if bits < 64 {
+                        let wrap = if signed { "asIntN" } else { "asUintN" };
+                        code.push_str(&format!(
+                            "intStack.push(Number(BigInt.{}({}, BigInt(intStack.pop()!))));\n",
+                            wrap, bits
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => code.push_str("process.exit(intStack.pop()!);\n"),
             }
         }
 
         // 4. End of script
-        code.push_str("\n");
-        Ok(Box::new(code.into_bytes()))
+        code.push('\n');
+        Ok(CompiledArtifact::source(self.name(), "main.ts", code)
+            .with_build_command("tsc main.ts")
+            .with_build_command("node main.js"))
     }
 
     fn name(&self) -> &'static str {
@@ -69,7 +124,7 @@ static TS_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_TS_REG: fn() = {
+pub(crate) static FORCE_TS_REG: fn() = {
     fn init() {
         Lazy::force(&TS_REG);
     }
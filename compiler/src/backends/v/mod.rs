@@ -1,62 +1,117 @@
 // File: compiler/src/backends/v/mod.rs
 //! V codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone V program
+//! Decodes our IR and emits a standalone V program
 //! that replays the instructions on two stacks and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct VBackend;
 
 impl Backend<CompiledModule> for VBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin V source
         let mut code = String::new();
         code.push_str("module main\n\n");
         code.push_str("fn main() {\n");
         code.push_str("\tmut int_stack := []int{}\n");
+        code.push_str("\tmut flt_stack := []f64{}\n");
         code.push_str("\tmut str_stack := []string{}\n\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("\tint_stack << {}\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!("\tstr_stack << \"{}\"\n", esc));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    r#"    if str_stack.len > 0 {
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("\tint_stack << {}\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("\tflt_stack << {}\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("\tint_stack << {}\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!("\tstr_stack << \"{}\"\n", esc));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        r#"    if str_stack.len > 0 {
         print(str_stack.pop())
     } else if int_stack.len > 0 {
         print(int_stack.pop().str())
     }
 "#,
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("\tprint(int_stack.pop().str())\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("\tprint('${flt_stack.pop():.6f}')\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("\tprint(str_stack.pop())\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("\tprint((int_stack.pop() != 0).str())\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("\tflt_stack << f64(int_stack.pop())\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "\tint_stack << if flt_stack.last() != flt_stack.last() {{ 0 }} else if flt_stack.last() <= {} {{ {} }} else if flt_stack.last() >= {} {{ {} }} else {{ int(flt_stack.last()) }}\n\tflt_stack.delete_last()\n",
+                        min_f,
+                        super::int_literal(min),
+                        max_f,
+                        max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "\tint_stack[int_stack.len - 1] = ((int_stack[int_stack.len - 1] & {}) ^ {}) - {}\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => code.push_str("\texit(int_stack.pop())\n"),
             }
         }
 
         // 4. End main
         code.push_str("}\n");
 
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.v", code)
+            .with_build_command("v run main.v"))
     }
 
     fn name(&self) -> &'static str {
@@ -72,7 +127,7 @@ static V_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_V_REG: fn() = {
+pub(crate) static FORCE_V_REG: fn() = {
     fn init() {
         Lazy::force(&V_REG);
     }
@@ -1,22 +1,20 @@
 // File: compiler/src/backends/wasm/mod.rs
 //! WASM codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a WebAssembly Text (WAT) module
+//! Decodes our IR and emits a WebAssembly Text (WAT) module
 //! with each IR instruction preserved as a comment.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct WasmBackend;
 
 impl Backend<CompiledModule> for WasmBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = module.decode()?;
 
         // 2. Begin WAT module
         let mut wat = String::new();
@@ -25,13 +23,13 @@ impl Backend<CompiledModule> for WasmBackend {
 
         // 3. Embed each IR instruction as a comment inside the main func
         wat.push_str("  (func $main (result i32)\n");
-        for line in ir.lines() {
-            let inst = line.trim();
-            if inst.is_empty() || inst == "Nop" {
+        for instr in &instrs {
+            if *instr == Instruction::Nop {
                 continue;
             }
+            let inst = format!("{:?}", instr);
             wat.push_str("    ;; ");
-            wat.push_str(inst);
+            wat.push_str(&inst);
             wat.push('\n');
         }
         // 4. Provide a dummy return value so the function is valid
@@ -43,7 +41,8 @@ impl Backend<CompiledModule> for WasmBackend {
         wat.push_str("  (export \"main\" (func $main))\n");
         wat.push_str(")\n");
 
-        Ok(Box::new(wat.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.wat", wat)
+            .with_build_command("wat2wasm main.wat -o main.wasm"))
     }
 
     fn name(&self) -> &'static str {
@@ -59,7 +58,7 @@ static WASM_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_WASM_REG: fn() = {
+pub(crate) static FORCE_WASM_REG: fn() = {
     fn init() {
         Lazy::force(&WASM_REG);
     }
@@ -1,22 +1,20 @@
 // File: compiler/src/backends/zig/mod.rs
 //! Zig codegen backend for T-Lang.
-//! Reads our IR debug-text and emits a standalone Zig program
+//! Decodes our IR and emits a standalone Zig program
 //! that replays the instructions on two arrays and prints values.
 
-use plugin_api::{register_backend, Backend, CompiledModule, BackendError};
+use plugin_api::{register_backend, Backend, CompiledArtifact, CompiledModule, BackendError, Instruction};
 use once_cell::sync::Lazy;
-use std::{any::Any, str};
 
 #[derive(Debug)]
 pub struct ZigBackend;
 
 impl Backend<CompiledModule> for ZigBackend {
-    type ModuleIr = Box<dyn Any + Send + Sync>;
+    type ModuleIr = CompiledArtifact;
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        // 1. Decode IR text
-        let ir = str::from_utf8(&module.bytecode)
-            .map_err(|e| BackendError::Generic(format!("Invalid UTF-8 IR: {}", e)))?;
+        // 1. Decode IR
+        let instrs = super::fold_run_time(self.name(), module.decode()?)?;
 
         // 2. Begin Zig source
         let mut code = String::new();
@@ -25,45 +23,104 @@ impl Backend<CompiledModule> for ZigBackend {
         code.push_str("    var stdout = std.io.getStdOut().writer();\n");
         code.push_str("    var intStack = std.ArrayList(i64).init(std.heap.page_allocator);\n");
         code.push_str("    defer intStack.deinit();\n");
+        code.push_str("    var fltStack = std.ArrayList(f64).init(std.heap.page_allocator);\n");
+        code.push_str("    defer fltStack.deinit();\n");
         code.push_str("    var strStack = std.ArrayList([]const u8).init(std.heap.page_allocator);\n");
         code.push_str("    defer strStack.deinit();\n\n");
 
         // 3. Translate each IR instruction
-        for line in ir.lines() {
-            let instr = line.trim();
-            if instr.is_empty() || instr == "Nop" {
-                continue;
-            }
-            if let Some(n) = instr.strip_prefix("PushInt(").and_then(|s| s.strip_suffix(")")) {
-                code.push_str(&format!("    try intStack.append({});\n", n));
-            } else if let Some(raw) = instr
-                .strip_prefix("PushStr(\"")
-                .and_then(|s| s.strip_suffix("\")"))
-            {
-                let esc = raw.replace('\\', "\\\\").replace('"', "\\\"");
-                code.push_str(&format!(
-                    "    try strStack.append(&\"{}\".{});\n",
-                    esc,
-                    ".*"
-                ));
-            } else if instr == "CallPrint" {
-                code.push_str(
-                    "    if (strStack.len > 0) {\n\
-                     \n        const s = strStack.pop();\n\
-                     \n        _ = try stdout.print(\"{s}\", .{{}});\n\
-                     \n    } else if (intStack.len > 0) {\n\
-                     \n        const i = intStack.pop();\n\
-                     \n        _ = try stdout.print(\"{d}\", .{.d = i});\n\
-                     \n    }\n",
-                );
-            } else {
-                return Err(BackendError::Generic(format!("Unknown IR instr: {}", instr)));
+        for instr in instrs {
+            match instr {
+                Instruction::Nop => {}
+                Instruction::PushInt(n) => {
+                    code.push_str(&format!("    try intStack.append({});\n", n));
+                }
+                Instruction::PushFloat(f) => {
+                    let v = super::float_literal(f)?;
+                    code.push_str(&format!("    try fltStack.append({});\n", v));
+                }
+                Instruction::PushBool(b) => {
+                    code.push_str(&format!("    try intStack.append({});\n", b as i32));
+                }
+                Instruction::PushStr(s) => {
+                    let esc = super::escape_str(&s);
+                    code.push_str(&format!(
+                        "    try strStack.append(&\"{}\".{});\n",
+                        esc,
+                        ".*"
+                    ));
+                }
+                Instruction::CallPrint => {
+                    code.push_str(
+                        "    if (strStack.len > 0) {\n\
+                         \n        const s = strStack.pop();\n\
+                         \n        _ = try stdout.print(\"{s}\", .{{}});\n\
+                         \n    } else if (intStack.len > 0) {\n\
+                         \n        const i = intStack.pop();\n\
+                         \n        _ = try stdout.print(\"{d}\", .{.d = i});\n\
+                         \n    }\n",
+                    );
+                }
+                Instruction::PrintInt => {
+                    code.push_str("    try stdout.print(\"{d}\", .{intStack.pop()});\n");
+                }
+                Instruction::PrintFloat => {
+                    code.push_str("    try stdout.print(\"{d:.6}\", .{fltStack.pop()});\n");
+                }
+                Instruction::PrintStr => {
+                    code.push_str("    try stdout.print(\"{s}\", .{strStack.pop()});\n");
+                }
+                Instruction::PrintBool => {
+                    code.push_str("    try stdout.print(\"{s}\", .{if (intStack.pop() != 0) \"true\" else \"false\"});\n");
+                }
+                Instruction::IntToFloat => {
+                    code.push_str("    try fltStack.append(@floatFromInt(intStack.pop()));\n");
+                }
+                Instruction::FloatToInt { bits, signed } => {
+                    // `@intFromFloat` is illegal behaviour on NaN and out-of-range values.
+                    let (min, max, min_f, max_f) = super::saturation_bounds(bits, signed)?;
+                    code.push_str(&format!(
+                        "    {{\n        const f = fltStack.pop();\n        try intStack.append(if (std.math.isNan(f)) 0 else if (f <= {}) {} else if (f >= {}) {} else @as(i64, @intFromFloat(f)));\n    }}\n",
+                        min_f, min, max_f, max
+                    ));
+                }
+                Instruction::WrapInt { bits, signed } => {
+                    if let Some((mask, sign)) = super::wrap_masks(bits, signed) {
+                        code.push_str(&format!(
+                            "    intStack.items[intStack.items.len - 1] = ((intStack.items[intStack.items.len - 1] & {}) ^ {}) - {};\n",
+                            mask, sign, sign
+                        ));
+                    }
+                }
+                Instruction::StoreLocal { .. }
+                | Instruction::LoadLocal { .. }
+                | Instruction::Arith { .. }
+                | Instruction::Compare { .. }
+                | Instruction::Not
+                | Instruction::Label(_)
+                | Instruction::Jump(_)
+                | Instruction::JumpIfFalse(_)
+                | Instruction::Overflows { .. }
+                | Instruction::Trap { .. }
+                | Instruction::Function { .. }
+                | Instruction::Call { .. }
+                | Instruction::Return { .. } => {
+                    return Err(super::instruction_unsupported(self.name(), &instr));
+                }
+                Instruction::CallExtern { name, .. } => {
+                    return Err(super::extern_unsupported(self.name(), &name));
+                }
+                Instruction::Exit => {
+                    // Exit statuses are a byte, as the code is on POSIX.
+                    code.push_str("    std.process.exit(@truncate(@as(u64, @bitCast(intStack.pop()))));\n");
+                }
             }
         }
 
         // 4. End main
         code.push_str("}\n");
-        Ok(Box::new(code.into_bytes()))
+        Ok(CompiledArtifact::source(self.name(), "main.zig", code)
+            .with_build_command("zig run main.zig"))
     }
 
     fn name(&self) -> &'static str {
@@ -79,7 +136,7 @@ static ZIG_REG: Lazy<()> = Lazy::new(|| {
 #[doc(hidden)]
 #[allow(non_upper_case_globals)]
 #[used]
-static FORCE_ZIG_REG: fn() = {
+pub(crate) static FORCE_ZIG_REG: fn() = {
     fn init() {
         Lazy::force(&ZIG_REG);
     }
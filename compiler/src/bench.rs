@@ -0,0 +1,241 @@
+// compiler/src/bench.rs
+//! Benchmarks, for `tlang bench`: top-level functions marked `#[bench]`,
+//! which take no arguments and return nothing.
+//!
+//! A benchmark is lowered as the entry point in place of `main` and run a
+//! number of times after a few untimed warm-up runs. What is reported is
+//! the mean, median and standard deviation of the time one run took. The
+//! statistics of a whole run can be saved as a `Baseline`, and a later run
+//! compared with it: a mean that moved by less than the noise threshold
+//! counts as unchanged.
+
+use crate::lint::name_span;
+use crate::safety::report::Location;
+use serde::{Deserialize, Serialize};
+use shared::ast::PrimitiveType;
+use shared::source::line_col_from_offset;
+use shared::{ItemKind, Program, Result, TlError, TypeKind};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Instant;
+
+/// The attribute that marks a benchmark.
+pub const BENCH_ATTRIBUTE: &str = "bench";
+
+/// A function marked `#[bench]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Benchmark {
+    pub name: String,
+    /// Where its name is written
+    pub location: Location,
+}
+
+/// The benchmarks of `program`, in source order.
+///
+/// Fails on a `#[bench]` that marks anything but a function, or a function
+/// with parameters or a result.
+pub fn benchmarks(program: &Program, source: &str) -> Result<Vec<Benchmark>> {
+    let mut benchmarks = Vec::new();
+    for item in &program.items {
+        let Some(attribute) = item.attribute(BENCH_ATTRIBUTE) else { continue };
+        let invalid = |message: &str| TlError::type_error(source.to_string(), attribute.span, message.to_string());
+        let ItemKind::Function { name, params, return_type, .. } = &item.kind else {
+            return Err(invalid("`#[bench]` only marks functions"));
+        };
+        let returns_unit = return_type
+            .as_ref()
+            .is_none_or(|ty| matches!(ty.kind, TypeKind::Primitive(PrimitiveType::Unit)));
+        if !params.is_empty() || !returns_unit {
+            return Err(invalid("a `#[bench]` function takes no arguments and returns nothing"));
+        }
+        let (line, column) = line_col_from_offset(source, name_span(source, item, name).offset());
+        benchmarks.push(Benchmark { name: name.clone(), location: Location { line, column } });
+    }
+    Ok(benchmarks)
+}
+
+/// How long the runs of a benchmark took, in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    /// How many runs were timed
+    pub iterations: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// The sample standard deviation
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    /// The statistics of `samples`, the time each run took; all 0 when
+    /// there are none.
+    pub fn of(samples: &[f64]) -> Self {
+        let n = samples.len();
+        if n == 0 {
+            return Stats { iterations: 0, mean: 0.0, median: 0.0, stddev: 0.0, min: 0.0, max: 0.0 };
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n.is_multiple_of(2) { (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0 } else { sorted[n / 2] };
+        let variance = match n {
+            1 => 0.0,
+            _ => sorted.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (n - 1) as f64,
+        };
+        Stats { iterations: n, mean, median, stddev: variance.sqrt(), min: sorted[0], max: sorted[n - 1] }
+    }
+}
+
+/// Run `run` `warmup` times, then time it `iterations` times, at least
+/// once.
+pub fn measure(warmup: usize, iterations: usize, mut run: impl FnMut()) -> Stats {
+    for _ in 0..warmup {
+        run();
+    }
+    let samples: Vec<f64> = (0..iterations.max(1))
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed().as_secs_f64() * 1e9
+        })
+        .collect();
+    Stats::of(&samples)
+}
+
+/// The statistics of a run of benchmarks, by name, for later runs to be
+/// compared with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub benchmarks: BTreeMap<String, Stats>,
+}
+
+impl Baseline {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a baseline is always serializable")
+    }
+
+    /// Write the baseline to `path` as JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_json())
+            .map_err(|error| TlError::io(format!("Failed to write baseline {}", path.display()), Some(error)))
+    }
+
+    /// Read a baseline written by `write`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| TlError::io(format!("Failed to read baseline {}", path.display()), Some(error)))?;
+        serde_json::from_str(&text)
+            .map_err(|error| TlError::io(format!("Malformed baseline {}: {}", path.display(), error), None))
+    }
+}
+
+/// Which way a benchmark moved from its baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Faster,
+    Slower,
+    Unchanged,
+}
+
+/// How the mean of a benchmark compares with its baseline's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    /// The change of the mean as a fraction of the baseline's: `0.1` is
+    /// 10% slower
+    pub change: f64,
+    pub verdict: Verdict,
+}
+
+impl Comparison {
+    /// Compare `current` with `baseline`, counting a change of at most
+    /// `noise`, a fraction of the baseline's mean, as none.
+    pub fn new(baseline: &Stats, current: &Stats, noise: f64) -> Self {
+        let change = if baseline.mean > 0.0 { (current.mean - baseline.mean) / baseline.mean } else { 0.0 };
+        let verdict = if change.abs() <= noise {
+            Verdict::Unchanged
+        } else if change < 0.0 {
+            Verdict::Faster
+        } else {
+            Verdict::Slower
+        };
+        Comparison { change, verdict }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self.verdict {
+            Verdict::Faster => "faster",
+            Verdict::Slower => "slower",
+            Verdict::Unchanged => "no change",
+        };
+        write!(f, "{:+.1}% ({})", self.change * 100.0, verdict)
+    }
+}
+
+/// `nanos` in the largest unit it is at least one of, to three decimals:
+/// `1.250 µs`.
+pub fn format_duration(nanos: f64) -> String {
+    let (value, unit) = match nanos {
+        n if n >= 1e9 => (n / 1e9, "s"),
+        n if n >= 1e6 => (n / 1e6, "ms"),
+        n if n >= 1e3 => (n / 1e3, "µs"),
+        n => (n, "ns"),
+    };
+    format!("{:.3} {}", value, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check_program, lower_function, parse_source, LoweringOptions};
+
+    fn checked(source: &str) -> Program {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        program
+    }
+
+    #[test]
+    fn benchmarks_are_marked_functions_lowered_in_place_of_main() {
+        let source = "fn main() {\n    println(\"main\");\n}\n\n#[bench]\nfn bench_sum() {\n    \
+                      for i in 0..3 {\n        print(i);\n    }\n}\n";
+        let program = checked(source);
+        let found = benchmarks(&program, source).unwrap();
+        assert_eq!(found, [Benchmark { name: "bench_sum".to_string(), location: Location { line: 6, column: 4 } }]);
+
+        let module = lower_function(&program, source, "bench.t", "bench_sum", LoweringOptions::default()).unwrap();
+        assert_eq!(plugin_api::interpret(&module).unwrap(), "012");
+
+        let invalid = [
+            "#[bench]\nfn takes(n: i32) {}\n",
+            "#[bench]\nfn gives() -> i32 { 1 }\n",
+            "#[bench]\nstruct S { n: i32 }\n",
+        ];
+        for invalid in invalid {
+            let program = parse_source(invalid).unwrap();
+            assert!(benchmarks(&program, invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn runs_are_summarized_and_compared_with_a_baseline() {
+        let stats = Stats::of(&[4.0, 1.0, 10.0, 2.0, 3.0]);
+        assert_eq!((stats.iterations, stats.mean, stats.median, stats.min, stats.max), (5, 4.0, 3.0, 1.0, 10.0));
+        assert!((stats.stddev - 12.5f64.sqrt()).abs() < 1e-9);
+        assert_eq!(Stats::of(&[1.0, 2.0]).median, 1.5);
+        assert_eq!(measure(2, 0, || {}).iterations, 1);
+
+        let slower = Stats { mean: 5.0, ..stats };
+        assert_eq!(Comparison::new(&stats, &slower, 0.05).to_string(), "+25.0% (slower)");
+        assert_eq!(Comparison::new(&slower, &stats, 0.05).verdict, Verdict::Faster);
+        assert_eq!(Comparison::new(&stats, &Stats { mean: 4.1, ..stats }, 0.05).verdict, Verdict::Unchanged);
+
+        let baseline = Baseline { benchmarks: BTreeMap::from([("bench_sum".to_string(), stats)]) };
+        assert_eq!(serde_json::from_str::<Baseline>(&baseline.to_json()).unwrap(), baseline);
+        assert_eq!(format_duration(1250.0), "1.250 µs");
+        assert_eq!(format_duration(12.0), "12.000 ns");
+    }
+}
@@ -0,0 +1,225 @@
+// compiler/src/callgraph.rs
+//! The calls between the functions of a program.
+//!
+//! The graph has an edge for every call from one function defined in the
+//! program to another, at the span of the call. Its strongly connected
+//! components are the recursion cycles: a function in one may call itself
+//! again before returning. The safety analysis bounds stack depth with it,
+//! and the checker finds the self calls of `#[tailcall]` functions.
+
+use miette::SourceSpan;
+use shared::{Expr, ExprKind, ItemKind, Program, StmtKind};
+use std::collections::{BTreeMap, HashMap};
+
+/// A call from one function of the program to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call<'a> {
+    pub callee: &'a str,
+    pub span: SourceSpan,
+}
+
+/// The calls between a program's functions.
+#[derive(Debug, Default)]
+pub struct CallGraph<'a> {
+    /// Each function with a body and the calls it makes to functions of the
+    /// program, in source order
+    calls: BTreeMap<&'a str, Vec<Call<'a>>>,
+    /// Where each function is defined
+    definitions: BTreeMap<&'a str, SourceSpan>,
+}
+
+impl<'a> CallGraph<'a> {
+    /// The call graph of `program`.
+    pub fn new(program: &'a Program) -> Self {
+        let mut graph = CallGraph::default();
+        for item in &program.items {
+            if let ItemKind::Function { name, body: Some(_), .. } = &item.kind {
+                graph.definitions.insert(name, item.span);
+            }
+        }
+        for item in &program.items {
+            if let ItemKind::Function { name, body: Some(body), .. } = &item.kind {
+                let mut calls = Vec::new();
+                graph.collect_calls(body, &mut calls);
+                graph.calls.insert(name, calls);
+            }
+        }
+        graph
+    }
+
+    /// The functions of the program, by name.
+    pub fn functions(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.calls.keys().copied()
+    }
+
+    /// The calls `function` makes to other functions of the program.
+    pub fn calls(&self, function: &str) -> &[Call<'a>] {
+        self.calls.get(function).map_or(&[], Vec::as_slice)
+    }
+
+    /// Where `function` is defined.
+    pub fn definition(&self, function: &str) -> Option<SourceSpan> {
+        self.definitions.get(function).copied()
+    }
+
+    fn collect_calls(&self, expr: &'a Expr, calls: &mut Vec<Call<'a>>) {
+        let mut visit = |expr: &'a Expr| self.collect_calls(expr, calls);
+        match &expr.kind {
+            ExprKind::Call { callee, args, .. } => {
+                if let ExprKind::Variable { path } = &callee.kind
+                    && let [name] = path.as_slice()
+                    && let Some((callee, _)) = self.definitions.get_key_value(name.as_str())
+                {
+                    calls.push(Call { callee, span: expr.span });
+                } else {
+                    self.collect_calls(callee, calls);
+                }
+                args.iter().for_each(|arg| self.collect_calls(arg, calls));
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                visit(receiver);
+                args.iter().for_each(visit);
+            }
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner }
+            | ExprKind::Loop { body: inner, .. }
+            | ExprKind::Closure { body: inner, .. }
+            | ExprKind::Async { body: inner, .. }
+            | ExprKind::Spawn { body: inner } => visit(inner),
+            ExprKind::Index { object: left, index: right }
+            | ExprKind::Binary { left, right, .. }
+            | ExprKind::Assign { target: left, value: right, .. }
+            | ExprKind::While { condition: left, body: right, .. }
+            | ExprKind::For { iterable: left, body: right, .. } => {
+                visit(left);
+                visit(right);
+            }
+            ExprKind::Range { start, end, .. } => start.iter().chain(end).for_each(|bound| visit(bound)),
+            ExprKind::If { condition, then_branch, else_branch } => {
+                visit(condition);
+                visit(then_branch);
+                else_branch.iter().for_each(|branch| visit(branch));
+            }
+            ExprKind::Match { expr: scrutinee, arms } => {
+                visit(scrutinee);
+                for arm in arms {
+                    arm.guard.iter().for_each(&mut visit);
+                    visit(&arm.body);
+                }
+            }
+            ExprKind::Block(block) => {
+                for stmt in &block.statements {
+                    match &stmt.kind {
+                        StmtKind::Expr(expr)
+                        | StmtKind::Let { initializer: Some(expr), .. }
+                        | StmtKind::Defer { body: expr } => visit(expr),
+                        StmtKind::Let { initializer: None, .. } | StmtKind::Item(_) | StmtKind::Macro { .. } => {}
+                    }
+                }
+                block.expr.iter().for_each(|expr| visit(expr));
+            }
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => value.iter().for_each(|value| visit(value)),
+            ExprKind::Tuple(elements) => elements.iter().for_each(visit),
+            ExprKind::Array { elements, repeat } => {
+                elements.iter().for_each(&mut visit);
+                repeat.iter().for_each(|count| visit(count));
+            }
+            ExprKind::Struct { fields, base, .. } => {
+                fields.iter().filter_map(|field| field.value.as_ref()).for_each(&mut visit);
+                base.iter().for_each(|base| visit(base));
+            }
+            ExprKind::Variable { .. }
+            | ExprKind::Literal(_)
+            | ExprKind::Continue { .. }
+            | ExprKind::Macro { .. }
+            | ExprKind::Error => {}
+        }
+    }
+
+    /// The strongly connected components that recurse: those of more than
+    /// one function, and single functions that call themselves. Each lists
+    /// its functions in order of definition.
+    pub fn recursion_cycles(&self) -> Vec<Vec<&'a str>> {
+        let mut tarjan = Tarjan { graph: self, index: HashMap::new(), low: HashMap::new(), stack: Vec::new(), components: Vec::new() };
+        for function in self.functions() {
+            if !tarjan.index.contains_key(function) {
+                tarjan.visit(function);
+            }
+        }
+        let mut cycles: Vec<Vec<&str>> = tarjan
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.calls(component[0]).iter().any(|call| call.callee == component[0])
+            })
+            .collect();
+        let offset = |function: &str| self.definition(function).map_or(0, |span| span.offset());
+        for cycle in &mut cycles {
+            cycle.sort_by_key(|function| offset(function));
+        }
+        cycles.sort_by_key(|cycle| offset(cycle[0]));
+        cycles
+    }
+}
+
+/// Tarjan's algorithm for strongly connected components.
+struct Tarjan<'g, 'a> {
+    graph: &'g CallGraph<'a>,
+    index: HashMap<&'a str, usize>,
+    low: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Tarjan<'_, 'a> {
+    fn visit(&mut self, function: &'a str) {
+        let index = self.index.len();
+        self.index.insert(function, index);
+        self.low.insert(function, index);
+        self.stack.push(function);
+
+        for call in self.graph.calls(function) {
+            if !self.index.contains_key(call.callee) {
+                self.visit(call.callee);
+                let low = self.low[function].min(self.low[call.callee]);
+                self.low.insert(function, low);
+            } else if self.stack.contains(&call.callee) {
+                let low = self.low[function].min(self.index[call.callee]);
+                self.low.insert(function, low);
+            }
+        }
+
+        if self.low[function] == index {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                component.push(member);
+                if member == function {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+
+    #[test]
+    fn calls_to_functions_outside_the_program_are_not_edges() {
+        let source = "fn main() {\n    print(\"hi\");\n    helper();\n}\nfn helper() {}";
+        let program = parse_source(source).unwrap();
+        let graph = CallGraph::new(&program);
+        let callees: Vec<&str> = graph.calls("main").iter().map(|call| call.callee).collect();
+        assert_eq!(callees, ["helper"]);
+        assert!(graph.recursion_cycles().is_empty());
+    }
+}
@@ -1,154 +1,158 @@
-//! Lower the AST into a simple IR (`Value`) along with source‐spans.
+//! `CodeGenerator` is the compiler's last phase: it lowers a checked
+//! program, optimizes it and hands it to the registered backend named by
+//! the target, which yields `GeneratedCode`.
 
-use miette::SourceSpan;
-use errors::{TlError, ErrorCode};
-use shared::ast::{Expr, ExprKind, Literal, Pattern, Span as AstSpan, Stmt};
+use errors::TlError;
+use plugin_api::{
+    find_backend, run_optimizers, ArtifactFormat, BackendConfig, BackendError, CompiledArtifact, DebugInfo,
+};
+use shared::ast::Program;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
 
-/// Our IR: a flat sequence of instructions/values.
-#[derive(Debug, Clone)]
-pub enum Value {
-    LiteralNumber(f64, SourceSpan),
-    LiteralBool(bool, SourceSpan),
-    LiteralString(String, SourceSpan),
-    GetVar(String, SourceSpan),
-    SetVar(String, Box<Value>, SourceSpan),
-    Block(Vec<Value>, SourceSpan),
-    Number(f64, SourceSpan),   // alias if you prefer the shorter name
-    Bool(bool, SourceSpan),
-    String(String, SourceSpan),
-    List(Vec<Value>, SourceSpan),
-    Add(Box<Value>, Box<Value>, SourceSpan),
-    Sub(Box<Value>, Box<Value>, SourceSpan),
-    Mul(Box<Value>, Box<Value>, SourceSpan),
-    Div(Box<Value>, Box<Value>, SourceSpan),
-    Less(Box<Value>, Box<Value>, SourceSpan),
-    Greater(Box<Value>, Box<Value>, SourceSpan),
-    LessEqual(Box<Value>, Box<Value>, SourceSpan),
-    GreaterEqual(Box<Value>, Box<Value>, SourceSpan),
-    EqualEqual(Box<Value>, Box<Value>, SourceSpan),
-    NotEqual(Box<Value>, Box<Value>, SourceSpan),
-    And(Box<Value>, Box<Value>, SourceSpan),
-    Or(Box<Value>, Box<Value>, SourceSpan),
-    Not(Box<Value>, SourceSpan),
-    Function { name: String, params: Vec<String>, body: Box<Value>, span: SourceSpan },
-    Call(String, Vec<Value>, SourceSpan),
-    If { cond: Box<Value>, then_branch: Box<Value>, else_branch: Option<Box<Value>>, span: SourceSpan },
-    Return(Box<Value>, SourceSpan),
-    /// …and so on for each operator your eval() matches…
-    /// Preserve explicit grouping parentheses
-    Group(Box<Value>, SourceSpan),
-    // … you can add more IR ops here (binary, call, etc.) …
+use crate::backends::register_builtin_backends;
+use crate::ir::lower_program;
+use crate::optimizer::register_builtin_optimizers;
+
+/// Name debug info gives the source, which the compiler is handed as text.
+const SOURCE_FILE: &str = "<input>";
+
+/// The code a backend generated: a main file, the files written next to it
+/// and the commands that build and run them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedCode {
+    /// Name of the backend that generated the code
+    pub target: String,
+    /// How `source` should be treated
+    pub format: ArtifactFormat,
+    /// File name the main file is written as
+    pub filename: String,
+    /// Contents of the main file: source text, or object code for binary formats
+    pub source: Vec<u8>,
+    /// Further `(file name, contents)` pairs, such as package manifests
+    pub additional_files: Vec<(String, Vec<u8>)>,
+    /// Shell commands that build and run the code, in order, from the
+    /// directory it was written to
+    pub build_commands: Vec<String>,
 }
-impl Value {
-    pub fn span(&self) -> SourceSpan {
-        match self {
-            Value::Number(_, s)
-            | Value::Bool(_, s)
-            | Value::String(_, s)
-            | Value::List(_, s)
-            | Value::Add(_, _, s)
-            // …repeat for every variant that carries a `SourceSpan`…
-            | Value::Block(_, s)
-            | Value::Group(_, s)
-            | Value::GetVar(_, s)
-            | Value::SetVar(_, _, s)
-            => *s,
-            // Should never happen as all variants contain a SourceSpan
-            _ => SourceSpan::from((0, 0))
+
+impl GeneratedCode {
+    /// The main file's contents.
+    pub fn bytes(&self) -> &[u8] {
+        &self.source
+    }
+
+    /// The main file's contents as text, unless they are binary.
+    pub fn text(&self) -> Option<&str> {
+        match self.format {
+            ArtifactFormat::Binary => None,
+            ArtifactFormat::Source | ArtifactFormat::Text => std::str::from_utf8(&self.source).ok(),
         }
     }
-}
 
-pub fn generate(stmts: &[Stmt]) -> Result<Vec<Value>, TlError> {
-    let mut result = Vec::new();
-    for stmt in stmts {
-        match stmt {
-            Stmt::Local { pat, init, span, .. } => {
-                if let Some(init_expr) = init {
-                    let v = gen_expr(init_expr)?;
-                    result.push(Value::SetVar(
-                        extract_ident(pat)?,
-                        Box::new(v),
-                        span_to_sourcespan(*span),
-                    ));
-                }
-            }
-            Stmt::Expr { expr, .. } | Stmt::Semi { expr, .. } => {
-                let v = gen_expr(expr)?;
-                result.push(v);
+    /// Every file as `(file name, contents)`: the main file first, then the
+    /// additional files in order.
+    pub fn files(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        std::iter::once((self.filename.as_str(), self.bytes())).chain(
+            self.additional_files
+                .iter()
+                .map(|(name, data)| (name.as_str(), data.as_slice())),
+        )
+    }
+
+    /// Write every file into `dir`, creating it and any subdirectories.
+    pub fn write_to(&self, dir: &Path) -> std::io::Result<()> {
+        for (name, data) in self.files() {
+            let path = dir.join(name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
             }
-            _ => {}
+            fs::write(path, data)?;
         }
+        Ok(())
     }
-    Ok(result)
 }
 
-fn gen_expr(expr: &Expr) -> Result<Value, TlError> {
-    match &expr.kind {
-        ExprKind::Literal(Literal::Float(n), span) => {
-            Ok(Value::LiteralNumber(*n, span_to_sourcespan(*span)))
-        }
-        ExprKind::Literal(Literal::Boolean(b), span) => {
-            Ok(Value::LiteralBool(*b, span_to_sourcespan(*span)))
-        }
-        ExprKind::Literal(Literal::String(s), span) => {
-            Ok(Value::LiteralString(s.clone(), span_to_sourcespan(*span)))
-        }
-
-        ExprKind::Variable(path, span) => {
-            let name = path.join("::");
-            Ok(Value::GetVar(name, span_to_sourcespan(*span)))
+impl From<CompiledArtifact> for GeneratedCode {
+    fn from(artifact: CompiledArtifact) -> Self {
+        GeneratedCode {
+            target: artifact.target,
+            format: artifact.format,
+            filename: artifact.suggested_filename,
+            source: artifact.data,
+            additional_files: artifact.extra_files,
+            build_commands: artifact.build_commands,
         }
+    }
+}
 
-        ExprKind::Grouping(inner, span) => {
-            // generate inner IR, then wrap in a Group to preserve the span
-            let inner_ir = gen_expr(inner)?;
-            Ok(Value::Group(Box::new(inner_ir), span_to_sourcespan(*span)))
-        }
+/// Generates code for one target through the backend registry.
+#[derive(Debug, Clone)]
+pub struct CodeGenerator {
+    /// Name of the backend to generate code with
+    target: String,
+    /// Level the registered optimizers are enabled for
+    optimization_level: u8,
+    /// Optimizers turned on or off by name, whatever the level
+    optimizers: HashMap<String, bool>,
+    /// Settings handed to the backend
+    config: BackendConfig,
+    /// Whether the backend gets the program's debug info
+    debug_info: bool,
+}
 
-        ExprKind::BlockExpr(block) => {
-            let inner = generate(&block.stmts)?;
-            Ok(Value::Block(inner, span_to_sourcespan(block.span)))
+impl CodeGenerator {
+    /// A generator for the backend named `target`, with default backend settings.
+    pub fn new(target: String, optimization_level: u8) -> Self {
+        Self {
+            target,
+            optimization_level,
+            optimizers: HashMap::new(),
+            config: BackendConfig::default(),
+            debug_info: true,
         }
+    }
 
-        other => Err(TlError::new(
-            "codegen",
-            "",
-            span_to_sourcespan(expr_span(expr)),
-            ErrorCode::Unsupported,
-            format!("unhandled expr in codegen: {:?}", other),
-        )),
+    /// Hand the backend `config` instead of the defaults.
+    pub fn with_config(mut self, config: BackendConfig) -> Self {
+        self.config = config;
+        self
     }
-}
 
-/// Convert our AST `Span` into a `SourceSpan`.
-fn span_to_sourcespan(span: AstSpan) -> SourceSpan {
-    SourceSpan::from((span.start, span.len))
-}
+    /// Turn the optimizers in `optimizers` on (`true`) or off, overriding
+    /// the optimization level.
+    pub fn with_optimizers(mut self, optimizers: HashMap<String, bool>) -> Self {
+        self.optimizers = optimizers;
+        self
+    }
 
-/// Extract an identifier name out of a single‐binding pattern.
-fn extract_ident(pat: &Pattern) -> Result<String, TlError> {
-    if let Pattern::Identifier { name, .. } = pat {
-        Ok(name.clone())
-    } else {
-        Err(TlError::new(
-            "codegen",
-            "",
-            SourceSpan::from((0, 0)),
-            ErrorCode::Unsupported,
-            "expected identifier in codegen".to_string(),
-        ))
+    /// Whether to hand the backend the program's debug info; on by default.
+    pub fn with_debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = debug_info;
+        self
     }
-}
 
-/// Give us easy access to an expr’s AST span.
-fn expr_span(expr: &Expr) -> AstSpan {
-    use ExprKind::*;
-    match &expr.kind {
-        Literal(_, span)
-        | Variable(_, span)
-        | Grouping(_, span) => *span,
-        BlockExpr(block) => block.span,
-        _ => AstSpan { start: 0, len: 0 }, // fallback for unhandled kinds
+    /// Lower `program`, whose text is `source`, run the optimizers enabled
+    /// at the optimization level or by name over it, and compile it with the target
+    /// backend. Returns the code with what the optimizers counted.
+    ///
+    /// The checker has accepted `program` by now, so a program that cannot
+    /// be lowered or compiled is a compiler bug.
+    pub fn generate(&self, program: &Program, source: &str) -> Result<(GeneratedCode, BTreeMap<String, u64>), TlError> {
+        register_builtin_backends();
+        register_builtin_optimizers();
+        let backend = find_backend(&self.target)
+            .ok_or_else(|| TlError::internal(format!("No backend named '{}'", self.target)))?;
+        let failed = |e: BackendError| TlError::internal(format!("{} backend: {}", self.target, e));
+
+        let mut module = lower_program(program, source, SOURCE_FILE).map_err(failed)?;
+        if !self.debug_info {
+            module.debug_info = DebugInfo::default();
+        }
+        let module = run_optimizers(module, self.optimization_level, &self.optimizers).map_err(failed)?;
+        let optimizations = module.optimizations.clone();
+        let artifact = backend.compile_erased_with(module, &self.config).map_err(failed)?;
+        Ok((artifact.into(), optimizations))
     }
 }
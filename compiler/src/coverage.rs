@@ -0,0 +1,245 @@
+// compiler/src/coverage.rs
+//! Line coverage of programs lowered with `LoweringOptions::coverage`, for
+//! `--coverage` and `tlang cov report`.
+//!
+//! An instrumented module counts every run of each of its statements: the
+//! statement's first instructions call `tstd`'s `tlang_coverage_hit` with
+//! the number of its counter, and `tstd` writes the counts out when the
+//! program exits, one per line, counter 0 first. The module's
+//! `CoverageMap` says which line each counter is on, and which lines start
+//! a statement at all, so that code that never ran, such as a function
+//! `main` does not call or what follows a panic, is reported with a count
+//! of 0 rather than left out.
+
+use miette::SourceSpan;
+use plugin_api::CompiledModule;
+use serde::{Deserialize, Serialize};
+use shared::ast::stmt::ImplItem;
+use shared::ast::Block;
+use shared::source::line_col_from_offset;
+use shared::{Expr, ExprKind, Item, ItemKind, Program, Result, StmtKind, TlError};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The `tstd` function instrumented code calls with the number of a counter.
+pub const COUNTER_FUNCTION: &str = "tlang_coverage_hit";
+
+/// Where the counters of an instrumented module are in its source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageMap {
+    /// Path of the `.t` file the module was compiled from
+    pub source_file: Option<String>,
+    /// The line each counter is on, by its number
+    pub counters: Vec<u32>,
+    /// Every line a statement starts on, in order
+    pub lines: Vec<u32>,
+}
+
+impl CoverageMap {
+    /// The map of `module`, lowered for coverage from `program`, whose text
+    /// is `source`.
+    pub fn new(module: &CompiledModule, program: &Program, source: &str) -> Self {
+        let counters: Vec<u32> = module.debug_info.counters.iter().map(|row| row.line).collect();
+        let mut statements = Statements { source, lines: counters.clone() };
+        statements.items(&program.items);
+        let mut lines = statements.lines;
+        lines.sort_unstable();
+        lines.dedup();
+        CoverageMap { source_file: module.debug_info.source_file.clone(), counters, lines }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a coverage map is always serializable")
+    }
+
+    /// Write the map to `path` as JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json())
+            .map_err(|error| TlError::io(format!("Failed to write coverage map {}", path.display()), Some(error)))
+    }
+
+    /// Read a map written by `write`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| TlError::io(format!("Failed to read coverage map {}", path.display()), Some(error)))?;
+        serde_json::from_str(&text)
+            .map_err(|error| TlError::io(format!("Malformed coverage map {}: {}", path.display(), error), None))
+    }
+
+    /// How often each line ran, as `counts` has it, one count per counter:
+    /// every line of `lines`, with the sum of the counts of its counters.
+    /// Counters past the end of `counts` never ran.
+    ///
+    /// Fails if there are more counts than counters, as when they were
+    /// counted by another build of the program.
+    pub fn line_counts(&self, counts: &[u64]) -> Result<BTreeMap<u32, u64>> {
+        if counts.len() > self.counters.len() {
+            return Err(TlError::io(
+                format!(
+                    "{} counts for {} counters; were they counted by another build of the program?",
+                    counts.len(),
+                    self.counters.len()
+                ),
+                None,
+            ));
+        }
+        let mut lines: BTreeMap<u32, u64> = self.lines.iter().map(|&line| (line, 0)).collect();
+        for (line, count) in self.counters.iter().zip(counts) {
+            *lines.entry(*line).or_default() += count;
+        }
+        Ok(lines)
+    }
+}
+
+/// The counts of a counts file, one per line, counter 0 first.
+pub fn parse_counts(text: &str) -> Result<Vec<u64>> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| {
+            line.trim()
+                .parse()
+                .map_err(|_| TlError::io(format!("line {} of the counts is not a count: `{}`", index + 1, line), None))
+        })
+        .collect()
+}
+
+/// Read the counts a program wrote to `path`.
+pub fn read_counts(path: &Path) -> Result<Vec<u64>> {
+    let text = fs::read_to_string(path)
+        .map_err(|error| TlError::io(format!("Failed to read coverage counts {}", path.display()), Some(error)))?;
+    parse_counts(&text)
+}
+
+/// Collects the lines the statements of function bodies start on.
+struct Statements<'a> {
+    source: &'a str,
+    lines: Vec<u32>,
+}
+
+impl Statements<'_> {
+    fn items(&mut self, items: &[Item]) {
+        for item in items {
+            match &item.kind {
+                ItemKind::Function { body: Some(body), .. } => self.expr(body),
+                ItemKind::Impl { items: impl_items, .. } => {
+                    for impl_item in impl_items {
+                        if let ImplItem::Function { body, .. } = impl_item {
+                            self.expr(body);
+                        }
+                    }
+                }
+                ItemKind::Module { items, .. } => self.items(items),
+                _ => {}
+            }
+        }
+    }
+
+    fn line(&mut self, span: SourceSpan) {
+        let (line, _) = line_col_from_offset(self.source, span.offset());
+        self.lines.push(line as u32);
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.statements {
+            self.line(stmt.span);
+            match &stmt.kind {
+                StmtKind::Let { initializer: Some(expr), .. }
+                | StmtKind::Expr(expr)
+                | StmtKind::Defer { body: expr } => self.expr(expr),
+                StmtKind::Let { .. } | StmtKind::Item(_) | StmtKind::Macro { .. } => {}
+            }
+        }
+        if let Some(expr) = &block.expr {
+            // Some expressions are not told where they are.
+            if !expr.span.is_empty() {
+                self.line(expr.span);
+            }
+            self.expr(expr);
+        }
+    }
+
+    /// Look for blocks in `expr`.
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If { then_branch, else_branch, .. } => {
+                self.expr(then_branch);
+                else_branch.iter().for_each(|branch| self.expr(branch));
+            }
+            ExprKind::Match { arms, .. } => arms.iter().for_each(|arm| self.expr(&arm.body)),
+            ExprKind::While { body, .. }
+            | ExprKind::For { body, .. }
+            | ExprKind::Loop { body, .. }
+            | ExprKind::Closure { body, .. }
+            | ExprKind::Async { body, .. }
+            | ExprKind::Spawn { body }
+            | ExprKind::Unsafe { body } => self.expr(body),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check_program, lower_program_with_options, parse_source, LoweringOptions};
+    use plugin_api::Instruction;
+
+    const SOURCE: &str = "fn main() {\n    for i in 0..3 {\n        println(i);\n    }\n    println(\"done\");\n}\n\n\
+                          fn never() {\n    println(\"never\");\n}\n";
+
+    fn instrumented(source: &str) -> (CompiledModule, CoverageMap) {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        let options = LoweringOptions { coverage: true, ..LoweringOptions::default() };
+        let module = lower_program_with_options(&program, source, "main.t", options).unwrap();
+        let map = CoverageMap::new(&module, &program, source);
+        (module, map)
+    }
+
+    #[test]
+    fn every_statement_run_is_counted() {
+        let (module, map) = instrumented(SOURCE);
+        // The loop, its body once per iteration, and the last `println`.
+        assert_eq!(map.counters, [2, 3, 3, 3, 5]);
+        assert_eq!(map.lines, [2, 3, 5, 9]);
+        let hits: Vec<i64> = module
+            .instructions
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [Instruction::PushInt(counter), Instruction::CallExtern { name, .. }] if name == COUNTER_FUNCTION => {
+                    Some(*counter)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(hits, [0, 1, 2, 3, 4]);
+        assert!(module.externs.iter().any(|function| function.name == COUNTER_FUNCTION));
+
+        let lines = map.line_counts(&[1, 1, 1, 1, 1]).unwrap();
+        assert_eq!(lines.into_iter().collect::<Vec<_>>(), [(2, 1), (3, 3), (5, 1), (9, 0)]);
+        let map: CoverageMap = serde_json::from_str(&map.to_json()).unwrap();
+        assert_eq!(map.source_file.as_deref(), Some("main.t"));
+    }
+
+    #[test]
+    fn counts_must_fit_the_map() {
+        assert_eq!(parse_counts("3\n0\n1\n").unwrap(), [3, 0, 1]);
+        assert!(parse_counts("3\nmany\n").is_err());
+
+        let (module, map) = instrumented(SOURCE);
+        assert!(map.line_counts(&[1; 6]).is_err());
+        // A panic in the loop: nothing after it ran.
+        assert_eq!(map.line_counts(&[1, 1]).unwrap()[&5], 0);
+        let uninstrumented = lower_program_with_options(
+            &parse_source(SOURCE).unwrap(),
+            SOURCE,
+            "main.t",
+            LoweringOptions::default(),
+        )
+        .unwrap();
+        assert!(uninstrumented.debug_info.counters.is_empty());
+        assert!(uninstrumented.instructions.len() < module.instructions.len());
+    }
+}
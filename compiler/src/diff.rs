@@ -0,0 +1,286 @@
+// compiler/src/diff.rs
+//! Structural diffs of syntax trees and lowered modules, for
+//! `tlang internal diff-ir`.
+//!
+//! Both sides are compared as the data they serialize to, with every `span`
+//! left out, so code that only moved or was reformatted is no different.
+//! Where the two differ a change is reported at the path leading there,
+//! such as `items[0].kind.Function.name`: field names, the variants of
+//! enums, and indices into sequences. Sequences are aligned on what they
+//! have in common, so an inserted statement or instruction is one change
+//! rather than a change of everything after it. Of a module, its TIR is
+//! compared, but not its debug info, and every `file:line:col` of the
+//! source file in it, as panic messages and backtraces have, is replaced
+//! by `<location>`.
+
+use plugin_api::{BackendError, CompiledModule};
+use serde::Serialize;
+use serde_json::Value;
+use shared::Program;
+use std::fmt;
+
+/// What a location in the source file is replaced by in a module's TIR.
+const LOCATION: &str = "<location>";
+
+/// How long a value is shown in a change before it is cut short.
+const SHOWN: usize = 100;
+
+/// One difference between the old and the new version.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// `value` is only in the new version, at `path` there
+    Added { path: String, value: Value },
+    /// `value` is only in the old version, at `path` there
+    Removed { path: String, value: Value },
+    /// What is at `path` in the old version changed from `old` to `new`
+    Changed { path: String, old: Value, new: Value },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {}: {}", path, shown(value)),
+            Change::Removed { path, value } => write!(f, "- {}: {}", path, shown(value)),
+            Change::Changed { path, old, new } => write!(f, "~ {}: {} -> {}", path, shown(old), shown(new)),
+        }
+    }
+}
+
+/// `value` as compact JSON, cut short past `SHOWN` characters.
+fn shown(value: &Value) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(SHOWN) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// The differences between two syntax trees.
+pub fn diff_programs(old: &Program, new: &Program) -> Vec<Change> {
+    diff_data(old, new)
+}
+
+/// The differences between the TIR of two lowered modules.
+///
+/// # Errors
+/// Returns an error if either module's code cannot be lifted to TIR.
+pub fn diff_modules(old: &CompiledModule, new: &CompiledModule) -> Result<Vec<Change>, BackendError> {
+    let value = |module: &CompiledModule| -> Result<Value, BackendError> {
+        let mut value = serde_json::to_value(module.tir()?).expect("modules are always serializable");
+        if let Some(file) = module.debug_info.source_file.as_deref().filter(|file| !file.is_empty()) {
+            strip_locations(&mut value, file);
+        }
+        Ok(value)
+    };
+    let mut changes = Vec::new();
+    diff(&value(old)?, &value(new)?, &mut String::new(), &mut changes);
+    Ok(changes)
+}
+
+fn diff_data<T: Serialize>(old: &T, new: &T) -> Vec<Change> {
+    let value = |data: &T| {
+        let mut value = serde_json::to_value(data).expect("syntax trees and modules are always serializable");
+        strip_spans(&mut value);
+        value
+    };
+    let mut changes = Vec::new();
+    diff(&value(old), &value(new), &mut String::new(), &mut changes);
+    changes
+}
+
+/// Take every `span` out of `value`.
+fn strip_spans(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.remove("span");
+            fields.values_mut().for_each(strip_spans);
+        }
+        Value::Array(elements) => elements.iter_mut().for_each(strip_spans),
+        _ => {}
+    }
+}
+
+/// Replace every `file:line:col` in the strings of `value` with `LOCATION`.
+fn strip_locations(value: &mut Value, file: &str) {
+    match value {
+        Value::String(text) => {
+            let mut stripped = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find(file) {
+                stripped.push_str(&rest[..start]);
+                let after = &rest[start + file.len()..];
+                match location_len(after) {
+                    Some(len) => {
+                        stripped.push_str(LOCATION);
+                        rest = &after[len..];
+                    }
+                    None => {
+                        stripped.push_str(file);
+                        rest = after;
+                    }
+                }
+            }
+            stripped.push_str(rest);
+            *text = stripped;
+        }
+        Value::Object(fields) => fields.values_mut().for_each(|field| strip_locations(field, file)),
+        Value::Array(elements) => elements.iter_mut().for_each(|element| strip_locations(element, file)),
+        _ => {}
+    }
+}
+
+/// How long the `:line:col` `text` starts with is, if it starts with one.
+fn location_len(text: &str) -> Option<usize> {
+    let mut len = 0;
+    for _ in 0..2 {
+        let digits = text[len..].strip_prefix(':')?.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        len += 1 + digits;
+    }
+    Some(len)
+}
+
+/// Record the changes from `old` to `new`, both at `path`.
+fn diff(old: &Value, new: &Value, path: &mut String, changes: &mut Vec<Change>) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        // The same fields, or the same variant of an enum.
+        (Value::Object(old_fields), Value::Object(new_fields))
+            if old_fields.len() == new_fields.len() && old_fields.keys().all(|key| new_fields.contains_key(key)) =>
+        {
+            for (key, old_field) in old_fields {
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                diff(old_field, &new_fields[key], path, changes);
+                path.truncate(len);
+            }
+        }
+        (Value::Array(old_elements), Value::Array(new_elements)) => {
+            diff_sequences(old_elements, new_elements, path, changes)
+        }
+        _ => changes.push(Change::Changed { path: display(path), old: old.clone(), new: new.clone() }),
+    }
+}
+
+/// Record the changes between two sequences at `path`. Their longest
+/// common subsequence is unchanged; between its elements, what the two
+/// have in the same place is diffed, and the rest was removed or added.
+fn diff_sequences(old: &[Value], new: &[Value], path: &mut String, changes: &mut Vec<Change>) {
+    // `common[i][j]`: how long the longest common subsequence of
+    // `old[i..]` and `new[j..]` is.
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] =
+                if old[i] == new[j] { common[i + 1][j + 1] + 1 } else { common[i + 1][j].max(common[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let (mut old_gap, mut new_gap) = (0, 0);
+    loop {
+        let matched = i < old.len() && j < new.len() && old[i] == new[j];
+        if matched || (i == old.len() && j == new.len()) {
+            gap(old, new, (old_gap, i), (new_gap, j), path, changes);
+            if !matched {
+                break;
+            }
+            i += 1;
+            j += 1;
+            (old_gap, new_gap) = (i, j);
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+}
+
+/// Record the changes between `old[old_start..old_end]` and
+/// `new[new_start..new_end]`, which have nothing in common.
+fn gap(
+    old: &[Value],
+    new: &[Value],
+    (old_start, old_end): (usize, usize),
+    (new_start, new_end): (usize, usize),
+    path: &mut String,
+    changes: &mut Vec<Change>,
+) {
+    let paired = (old_end - old_start).min(new_end - new_start);
+    let len = path.len();
+    for k in 0..paired {
+        path.push_str(&format!("[{}]", old_start + k));
+        diff(&old[old_start + k], &new[new_start + k], path, changes);
+        path.truncate(len);
+    }
+    for (index, value) in old.iter().enumerate().take(old_end).skip(old_start + paired) {
+        changes.push(Change::Removed { path: format!("{}[{}]", path, index), value: value.clone() });
+    }
+    for (index, value) in new.iter().enumerate().take(new_end).skip(new_start + paired) {
+        changes.push(Change::Added { path: format!("{}[{}]", path, index), value: value.clone() });
+    }
+}
+
+/// `path`, or `.` for the whole value.
+fn display(path: &str) -> String {
+    if path.is_empty() { ".".to_string() } else { path.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+
+    fn changes(old: &str, new: &str) -> Vec<String> {
+        let (old, new) = (parse_source(old).unwrap(), parse_source(new).unwrap());
+        diff_programs(&old, &new).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn spans_are_ignored() {
+        assert!(changes("fn main() { print(1); }", "\n\nfn   main() {\n    print(1);\n}\n").is_empty());
+    }
+
+    #[test]
+    fn changes_are_reported_where_they_are() {
+        let changed = changes("fn main() { print(1); }", "fn main() { print(2); }");
+        assert_eq!(changed.len(), 1);
+        assert!(changed[0].starts_with("~ items[0].kind.Function.body."), "{}", changed[0]);
+        assert!(changed[0].ends_with(": 1 -> 2"), "{}", changed[0]);
+    }
+
+    fn lower(source: &str, file: &str) -> CompiledModule {
+        let mut program = parse_source(source).unwrap();
+        crate::check_program(&mut program, source.to_string()).unwrap();
+        crate::lower_program(&program, source, file).unwrap()
+    }
+
+    fn module_changes(old: &CompiledModule, new: &CompiledModule) -> Vec<String> {
+        diff_modules(old, new).unwrap().iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn locations_in_the_tir_are_ignored() {
+        let source = "fn main() {\n    let mut n = 0;\n    while n < 3 {\n        n += 1;\n    }\n    assert(n == 3);\n}\n";
+        let old = lower(source, "old.t");
+        assert!(module_changes(&old, &lower(source, "new.t")).is_empty());
+        assert!(module_changes(&old, &lower(&format!("\n{}", source), "old.t")).is_empty());
+    }
+
+    #[test]
+    fn added_instructions_are_reported_in_the_tir() {
+        let old = lower("fn main() {\n    println(1);\n}\n", "main.t");
+        let new = lower("fn main() {\n    println(1);\n    println(2);\n}\n", "main.t");
+        let changed = module_changes(&old, &new);
+        assert!(changed.iter().all(|change| change.starts_with("+ functions[0].")), "{:?}", changed);
+        let constant = r#"{"kind":{"Const":{"Int":2}},"ty":{"Int":{"bits":64,"signed":true}}}"#;
+        assert!(changed.iter().any(|change| change.ends_with(constant)), "{:?}", changed);
+    }
+}
@@ -0,0 +1,1006 @@
+// File: compiler/src/expand.rs
+//! Expansion of `macro_rules!` macros, the first pass after parsing.
+//!
+//! The grammar keeps a definition's rules and an invocation's arguments as
+//! token text. `expand_macros` matches each invocation against the rules of
+//! its macro in order and replaces it with the first match's body, the
+//! fragments bound by `$name:kind` substituted in, parsed as a block
+//! expression. Macros invoked in an expansion are expanded before it is
+//! parsed, nested at most `MACRO_RECURSION_LIMIT` deep.
+//!
+//! Hygiene is kept simple: names a body binds with `let` or `for` are renamed
+//! apart from everything the caller wrote, so a macro's locals neither capture
+//! the caller's variables nor leak out, and an expression fragment is
+//! substituted as a parenthesised whole.
+
+use crate::parser::parse_expansion;
+use errors::suggest::{did_you_mean, similar_names};
+use errors::TlError;
+use miette::SourceSpan;
+use shared::ast::stmt::{ImplItem, MacroArg, MacroRule};
+use shared::ast::Block;
+use shared::{Expr, ExprKind, Item, ItemKind, Program, Stmt, StmtKind, TokenStream, TokenType};
+use std::collections::{HashMap, HashSet};
+
+/// How deeply macro invocations may nest inside expansions.
+pub const MACRO_RECURSION_LIMIT: usize = 64;
+
+/// Most tokens one invocation written in the source may expand to.
+const MAX_EXPANSION_TOKENS: usize = 1 << 16;
+
+/// Expand every macro invocation in `program`.
+///
+/// Returns the errors found; an invocation that fails to expand becomes an
+/// `ExprKind::Error`, so later passes can still look at the rest.
+pub fn expand_macros(program: &mut Program, source: &str) -> Vec<TlError> {
+    let mut expander = Expander::new(source);
+    expander.define(&program.items);
+    for item in &mut program.items {
+        expander.item(item);
+    }
+    expander.errors
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delimiter {
+    Paren,
+    Bracket,
+    Brace,
+}
+
+impl Delimiter {
+    fn opened_by(token: &TokenType) -> Option<Self> {
+        match token {
+            TokenType::LParen => Some(Self::Paren),
+            TokenType::LBracket => Some(Self::Bracket),
+            TokenType::LBrace => Some(Self::Brace),
+            _ => None,
+        }
+    }
+
+    fn closed_by(token: &TokenType) -> Option<Self> {
+        match token {
+            TokenType::RParen => Some(Self::Paren),
+            TokenType::RBracket => Some(Self::Bracket),
+            TokenType::RBrace => Some(Self::Brace),
+            _ => None,
+        }
+    }
+
+    fn open(self) -> TokenType {
+        match self {
+            Self::Paren => TokenType::LParen,
+            Self::Bracket => TokenType::LBracket,
+            Self::Brace => TokenType::LBrace,
+        }
+    }
+
+    fn close(self) -> TokenType {
+        match self {
+            Self::Paren => TokenType::RParen,
+            Self::Bracket => TokenType::RBracket,
+            Self::Brace => TokenType::RBrace,
+        }
+    }
+}
+
+/// A token, or a delimited group of token trees.
+#[derive(Debug, Clone, PartialEq)]
+enum TokenTree {
+    Token(TokenType),
+    Group(Delimiter, Vec<TokenTree>),
+}
+
+/// Lex token text kept by the grammar back into tokens.
+fn lex(texts: &[String]) -> Result<Vec<TokenType>, TlError> {
+    let mut tokens = Vec::with_capacity(texts.len());
+    // Each text on its own, so a `///` comment cannot swallow what follows.
+    for text in texts {
+        let stream = TokenStream::lex(text)?;
+        let lexed = stream.tokens().iter().map(|token| &token.token_type);
+        tokens.extend(lexed.filter(|token| **token != TokenType::Eof).cloned());
+    }
+    Ok(tokens)
+}
+
+/// Group `tokens` into token trees; the grammar has already balanced the
+/// delimiters.
+fn token_trees(tokens: Vec<TokenType>) -> Vec<TokenTree> {
+    let mut stack: Vec<(Delimiter, Vec<TokenTree>)> = Vec::new();
+    let mut trees = Vec::new();
+    for token in tokens {
+        if let Some(delimiter) = Delimiter::opened_by(&token) {
+            stack.push((delimiter, std::mem::take(&mut trees)));
+        } else if Delimiter::closed_by(&token).is_some()
+            && let Some((delimiter, outer)) = stack.pop()
+        {
+            let group = TokenTree::Group(delimiter, std::mem::replace(&mut trees, outer));
+            trees.push(group);
+        } else {
+            trees.push(TokenTree::Token(token));
+        }
+    }
+    trees
+}
+
+fn flatten(trees: Vec<TokenTree>, out: &mut Vec<TokenType>) {
+    for tree in trees {
+        match tree {
+            TokenTree::Token(token) => out.push(token),
+            TokenTree::Group(delimiter, inner) => {
+                out.push(delimiter.open());
+                flatten(inner, out);
+                out.push(delimiter.close());
+            }
+        }
+    }
+}
+
+/// What a `$name:kind` fragment matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fragment {
+    /// An expression: token trees up to a `,`, `;` or `=>`.
+    Expr,
+    /// A type: as an expression, but also ending at `=` or `|`.
+    Ty,
+    /// A pattern, ending where a type does.
+    Pat,
+    /// One identifier.
+    Ident,
+    /// One literal, possibly negated.
+    Literal,
+    /// One `{ ... }` group.
+    Block,
+    /// Any one token tree.
+    Tt,
+}
+
+impl Fragment {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "expr" => Self::Expr,
+            "ty" => Self::Ty,
+            "pat" => Self::Pat,
+            "ident" => Self::Ident,
+            "literal" => Self::Literal,
+            "block" => Self::Block,
+            "tt" => Self::Tt,
+            _ => return None,
+        })
+    }
+
+    /// Whether a fragment of this kind ends before `token`.
+    fn ends_at(self, token: &TokenType) -> bool {
+        match token {
+            TokenType::Comma | TokenType::Semicolon | TokenType::FatArrow => true,
+            TokenType::Eq | TokenType::Or => matches!(self, Self::Ty | Self::Pat),
+            _ => false,
+        }
+    }
+}
+
+/// How often a `$( ... )` repetition repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kleene {
+    /// `*`
+    Any,
+    /// `+`
+    AtLeastOne,
+    /// `?`
+    AtMostOne,
+}
+
+#[derive(Debug, Clone)]
+struct Repetition<T> {
+    parts: Vec<T>,
+    separator: Option<TokenType>,
+    kleene: Kleene,
+}
+
+/// A piece of a rule's pattern.
+#[derive(Debug, Clone)]
+enum Matcher {
+    Token(TokenType),
+    Group(Delimiter, Vec<Matcher>),
+    Fragment(String, Fragment),
+    Repeat(Repetition<Matcher>),
+}
+
+/// A piece of a rule's body.
+#[derive(Debug, Clone)]
+enum Transcriber {
+    Token(TokenType),
+    Group(Delimiter, Vec<Transcriber>),
+    Var(String),
+    Repeat(Repetition<Transcriber>),
+}
+
+/// A rule ready to match.
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Vec<Matcher>,
+    body: Vec<Transcriber>,
+    /// Names the body binds itself, renamed in every expansion.
+    locals: HashSet<String>,
+}
+
+/// The separator and operator after a `$( ... )` group at `trees[0..]`, and
+/// how many trees they take.
+fn repetition_op(trees: &[TokenTree]) -> Result<(Option<TokenType>, Kleene, usize), String> {
+    let kleene = |tree: Option<&TokenTree>| match tree {
+        Some(TokenTree::Token(TokenType::Star)) => Some(Kleene::Any),
+        Some(TokenTree::Token(TokenType::Plus)) => Some(Kleene::AtLeastOne),
+        Some(TokenTree::Token(TokenType::Question)) => Some(Kleene::AtMostOne),
+        _ => None,
+    };
+    if let Some(op) = kleene(trees.first()) {
+        return Ok((None, op, 1));
+    }
+    match (trees.first(), kleene(trees.get(1))) {
+        (Some(TokenTree::Token(separator)), Some(op)) => Ok((Some(separator.clone()), op, 2)),
+        _ => Err("Expected `*`, `+` or `?` after `$( ... )`".to_string()),
+    }
+}
+
+fn matchers(trees: &[TokenTree]) -> Result<Vec<Matcher>, String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < trees.len() {
+        match (&trees[i], trees.get(i + 1)) {
+            (TokenTree::Token(TokenType::Dollar), Some(TokenTree::Token(TokenType::Identifier(name)))) => {
+                let kind = match (trees.get(i + 2), trees.get(i + 3)) {
+                    (
+                        Some(TokenTree::Token(TokenType::Colon)),
+                        Some(TokenTree::Token(TokenType::Identifier(kind))),
+                    ) => Fragment::from_name(kind).ok_or_else(|| {
+                        format!(
+                            "Unknown fragment kind `{}`, expected one of `expr`, `ty`, `pat`, `ident`, `literal`, `block`, `tt`",
+                            kind
+                        )
+                    })?,
+                    _ => return Err(format!("`${}` needs a fragment kind, e.g. `${}:expr`", name, name)),
+                };
+                out.push(Matcher::Fragment(name.clone(), kind));
+                i += 4;
+            }
+            (TokenTree::Token(TokenType::Dollar), Some(TokenTree::Group(Delimiter::Paren, inner))) => {
+                let (separator, kleene, taken) = repetition_op(&trees[i + 2..])?;
+                out.push(Matcher::Repeat(Repetition { parts: matchers(inner)?, separator, kleene }));
+                i += 2 + taken;
+            }
+            (TokenTree::Token(TokenType::Dollar), _) => {
+                return Err("Expected a `$name:kind` fragment or `$( ... )` after `$`".to_string());
+            }
+            (TokenTree::Group(delimiter, inner), _) => {
+                out.push(Matcher::Group(*delimiter, matchers(inner)?));
+                i += 1;
+            }
+            (TokenTree::Token(token), _) => {
+                out.push(Matcher::Token(token.clone()));
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn transcribers(trees: &[TokenTree]) -> Result<Vec<Transcriber>, String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < trees.len() {
+        match (&trees[i], trees.get(i + 1)) {
+            (TokenTree::Token(TokenType::Dollar), Some(TokenTree::Token(TokenType::Identifier(name)))) => {
+                out.push(Transcriber::Var(name.clone()));
+                i += 2;
+            }
+            (TokenTree::Token(TokenType::Dollar), Some(TokenTree::Group(Delimiter::Paren, inner))) => {
+                let (separator, kleene, taken) = repetition_op(&trees[i + 2..])?;
+                out.push(Transcriber::Repeat(Repetition { parts: transcribers(inner)?, separator, kleene }));
+                i += 2 + taken;
+            }
+            (TokenTree::Token(TokenType::Dollar), _) => {
+                return Err("Expected a `$name` or `$( ... )` after `$`".to_string());
+            }
+            (TokenTree::Group(delimiter, inner), _) => {
+                out.push(Transcriber::Group(*delimiter, transcribers(inner)?));
+                i += 1;
+            }
+            (TokenTree::Token(token), _) => {
+                out.push(Transcriber::Token(token.clone()));
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Names the body itself binds: those right after `let`, `let mut` or `for`.
+fn body_locals(parts: &[Transcriber], locals: &mut HashSet<String>) {
+    for (i, part) in parts.iter().enumerate() {
+        match part {
+            Transcriber::Token(TokenType::Let | TokenType::For) => {
+                let mut next = parts[i + 1..].iter();
+                let mut name = next.next();
+                if matches!(name, Some(Transcriber::Token(TokenType::Mut))) {
+                    name = next.next();
+                }
+                if let Some(Transcriber::Token(TokenType::Identifier(name))) = name
+                    && name != "_"
+                {
+                    locals.insert(name.clone());
+                }
+            }
+            Transcriber::Group(_, inner) => body_locals(inner, locals),
+            Transcriber::Repeat(repetition) => body_locals(&repetition.parts, locals),
+            _ => {}
+        }
+    }
+}
+
+impl Rule {
+    fn compile(rule: &MacroRule) -> Result<Self, String> {
+        let pattern = matchers(&token_trees(lex(&rule.pattern).map_err(|e| e.to_string())?))?;
+        let body = transcribers(&token_trees(lex(&rule.body).map_err(|e| e.to_string())?))?;
+        let mut locals = HashSet::new();
+        body_locals(&body, &mut locals);
+        Ok(Self { pattern, body, locals })
+    }
+}
+
+/// What a fragment matched: its tokens, or one binding per repetition.
+#[derive(Debug, Clone)]
+enum Binding {
+    One(Fragment, Vec<TokenTree>),
+    Many(Vec<Binding>),
+}
+
+type Bindings = HashMap<String, Binding>;
+
+/// Match `pattern` against the whole of `input`.
+fn match_all(pattern: &[Matcher], input: &[TokenTree], bindings: &mut Bindings) -> bool {
+    let mut at = 0;
+    match_seq(pattern, input, &mut at, None, bindings) && at == input.len()
+}
+
+/// Match `pattern` against `input` from `at`, moving `at` past what matched.
+/// `follow` is the token the pattern's caller expects next, if known.
+fn match_seq(
+    pattern: &[Matcher],
+    input: &[TokenTree],
+    at: &mut usize,
+    follow: Option<&TokenType>,
+    bindings: &mut Bindings,
+) -> bool {
+    for (i, matcher) in pattern.iter().enumerate() {
+        let next = match pattern.get(i + 1) {
+            Some(Matcher::Token(token)) => Some(token),
+            Some(_) => None,
+            None => follow,
+        };
+        let matched = match matcher {
+            Matcher::Token(token) => {
+                let found = input.get(*at) == Some(&TokenTree::Token(token.clone()));
+                *at += usize::from(found);
+                found
+            }
+            Matcher::Group(delimiter, parts) => match input.get(*at) {
+                Some(TokenTree::Group(found, inner)) if found == delimiter => {
+                    *at += 1;
+                    match_all(parts, inner, bindings)
+                }
+                _ => false,
+            },
+            Matcher::Fragment(name, kind) => match fragment(*kind, &input[*at..], next) {
+                Some(taken) => {
+                    bindings.insert(name.clone(), Binding::One(*kind, input[*at..*at + taken].to_vec()));
+                    *at += taken;
+                    true
+                }
+                None => false,
+            },
+            Matcher::Repeat(repetition) => match_repeat(repetition, input, at, next, bindings),
+        };
+        if !matched {
+            return false;
+        }
+    }
+    true
+}
+
+fn match_repeat(
+    repetition: &Repetition<Matcher>,
+    input: &[TokenTree],
+    at: &mut usize,
+    follow: Option<&TokenType>,
+    bindings: &mut Bindings,
+) -> bool {
+    let inner_follow = repetition.separator.as_ref().or(follow);
+    let mut rounds: Vec<Bindings> = Vec::new();
+    loop {
+        let mut next = *at;
+        if !rounds.is_empty()
+            && let Some(separator) = &repetition.separator
+        {
+            if input.get(next) != Some(&TokenTree::Token(separator.clone())) {
+                break;
+            }
+            next += 1;
+        }
+        let mut round = Bindings::new();
+        // A round that matches nothing would match forever.
+        if !match_seq(&repetition.parts, input, &mut next, inner_follow, &mut round) || next == *at {
+            break;
+        }
+        *at = next;
+        rounds.push(round);
+        if repetition.kleene == Kleene::AtMostOne {
+            break;
+        }
+    }
+    if repetition.kleene == Kleene::AtLeastOne && rounds.is_empty() {
+        return false;
+    }
+    let mut names = Vec::new();
+    fragment_names(&repetition.parts, &mut names);
+    for name in names {
+        let each = rounds.iter_mut().filter_map(|round| round.remove(&name)).collect();
+        bindings.insert(name, Binding::Many(each));
+    }
+    true
+}
+
+fn fragment_names(pattern: &[Matcher], names: &mut Vec<String>) {
+    for matcher in pattern {
+        match matcher {
+            Matcher::Fragment(name, _) => names.push(name.clone()),
+            Matcher::Group(_, parts) => fragment_names(parts, names),
+            Matcher::Repeat(repetition) => fragment_names(&repetition.parts, names),
+            Matcher::Token(_) => {}
+        }
+    }
+}
+
+/// How many of `input`'s leading trees a `kind` fragment takes, if it matches.
+fn fragment(kind: Fragment, input: &[TokenTree], follow: Option<&TokenType>) -> Option<usize> {
+    use TokenType::*;
+    let literal = |tree: Option<&TokenTree>| {
+        matches!(
+            tree,
+            Some(TokenTree::Token(
+                Integer(_) | Float(_) | TypedInteger(..) | TypedFloat(..) | String(_) | Char(_) | True | False
+            ))
+        )
+    };
+    match kind {
+        Fragment::Ident => matches!(input.first(), Some(TokenTree::Token(Identifier(_)))).then_some(1),
+        Fragment::Literal if literal(input.first()) => Some(1),
+        Fragment::Literal => {
+            (input.first() == Some(&TokenTree::Token(Minus)) && literal(input.get(1))).then_some(2)
+        }
+        Fragment::Block => matches!(input.first(), Some(TokenTree::Group(Delimiter::Brace, _))).then_some(1),
+        Fragment::Tt => (!input.is_empty()).then_some(1),
+        Fragment::Expr | Fragment::Ty | Fragment::Pat => {
+            let taken = input
+                .iter()
+                .take_while(|tree| match tree {
+                    TokenTree::Token(token) => !kind.ends_at(token) && Some(token) != follow,
+                    TokenTree::Group(..) => true,
+                })
+                .count();
+            (taken > 0).then_some(taken)
+        }
+    }
+}
+
+/// Why an expansion failed, and the macros being expanded at the time.
+struct Failure {
+    message: String,
+    backtrace: Vec<String>,
+}
+
+struct Expander<'a> {
+    source: &'a str,
+    /// Rules of each macro, in the order they are tried.
+    macros: HashMap<String, Vec<Rule>>,
+    /// Macros being expanded, outermost first.
+    stack: Vec<String>,
+    /// Expansions so far; numbers the renamed locals of each.
+    expansions: usize,
+    errors: Vec<TlError>,
+}
+
+impl<'a> Expander<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            macros: HashMap::new(),
+            stack: Vec::new(),
+            expansions: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, span: SourceSpan, message: impl Into<String>, backtrace: Vec<String>) {
+        self.errors.push(TlError::macro_error(self.source, span, message, backtrace));
+    }
+
+    fn fail(&self, message: impl Into<String>) -> Failure {
+        Failure {
+            message: message.into(),
+            backtrace: self.stack.iter().rev().cloned().collect(),
+        }
+    }
+
+    /// Record the macros defined in `items` and the modules among them.
+    fn define(&mut self, items: &[Item]) {
+        for item in items {
+            match &item.kind {
+                ItemKind::Macro { name, rules } => {
+                    if self.macros.contains_key(name) {
+                        self.error(item.span, format!("Macro `{}!` is defined more than once", name), Vec::new());
+                        continue;
+                    }
+                    let mut compiled = Vec::new();
+                    for rule in rules {
+                        match Rule::compile(rule) {
+                            Ok(rule) => compiled.push(rule),
+                            Err(message) => self.error(rule.span, message, Vec::new()),
+                        }
+                    }
+                    self.macros.insert(name.clone(), compiled);
+                }
+                ItemKind::Module { items, .. } => self.define(items),
+                _ => {}
+            }
+        }
+    }
+
+    fn item(&mut self, item: &mut Item) {
+        match &mut item.kind {
+            ItemKind::Function { body: Some(body), .. } => self.expr(body),
+            ItemKind::Const { value, .. } | ItemKind::Static { value, .. } => self.expr(value),
+            ItemKind::Impl { items, .. } => {
+                for impl_item in items {
+                    if let ImplItem::Function { body, .. } = impl_item {
+                        self.expr(body);
+                    }
+                }
+            }
+            ItemKind::Module { items, .. } => items.iter_mut().for_each(|item| self.item(item)),
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        for stmt in &mut block.statements {
+            self.stmt(stmt);
+        }
+        if let Some(expr) = &mut block.expr {
+            self.expr(expr);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match &mut stmt.kind {
+            StmtKind::Let { initializer, .. } => initializer.iter_mut().for_each(|e| self.expr(e)),
+            StmtKind::Expr(expr) | StmtKind::Defer { body: expr } => self.expr(expr),
+            StmtKind::Item(item) => self.item(item),
+            StmtKind::Macro { path, args } => {
+                let expr = self.invocation(&path.join("::"), args, stmt.span);
+                stmt.kind = StmtKind::Expr(expr);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match &mut expr.kind {
+            ExprKind::Macro { path, args } => {
+                *expr = self.invocation(&path.join("::"), args, expr.span);
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    arm.guard.iter_mut().for_each(|e| self.expr(e));
+                    self.expr(&mut arm.body);
+                }
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter_mut().for_each(|a| self.expr(a));
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.expr(receiver);
+                args.iter_mut().for_each(|a| self.expr(a));
+            }
+            ExprKind::Binary { left: a, right: b, .. }
+            | ExprKind::Index { object: a, index: b }
+            | ExprKind::Assign { target: a, value: b, .. }
+            | ExprKind::While { condition: a, body: b, .. }
+            | ExprKind::For { iterable: a, body: b, .. } => {
+                self.expr(a);
+                self.expr(b);
+            }
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                else_branch.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::Range { start, end, .. } => {
+                start.iter_mut().chain(end.iter_mut()).for_each(|e| self.expr(e));
+            }
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => {
+                value.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::Tuple(elems) => elems.iter_mut().for_each(|e| self.expr(e)),
+            ExprKind::Array { elements, repeat } => {
+                elements.iter_mut().for_each(|e| self.expr(e));
+                repeat.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::Struct { fields, base, .. } => {
+                fields.iter_mut().filter_map(|f| f.value.as_mut()).for_each(|e| self.expr(e));
+                base.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Loop { body: inner, .. }
+            | ExprKind::Closure { body: inner, .. }
+            | ExprKind::Async { body: inner, .. }
+            | ExprKind::Spawn { body: inner }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner } => self.expr(inner),
+            ExprKind::Literal(_)
+            | ExprKind::Variable { .. }
+            | ExprKind::Continue { .. }
+            | ExprKind::Error => {}
+        }
+    }
+
+    /// The expansion of the invocation of `name` at `span`, written in the
+    /// source, or an `Error` expression once the failure is reported.
+    fn invocation(&mut self, name: &str, args: &[MacroArg], span: SourceSpan) -> Expr {
+        let texts: Vec<String> = args.iter().flat_map(|arg| arg.tokens.iter().cloned()).collect();
+        let tokens = match lex(&texts) {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                self.errors.push(err);
+                return Expr::new(ExprKind::Error, span);
+            }
+        };
+        self.stack.clear();
+        let result = self.expand(name, token_trees(tokens)).and_then(|trees| {
+            let mut tokens = vec![TokenType::LBrace];
+            flatten(trees, &mut tokens);
+            tokens.push(TokenType::RBrace);
+            if tokens.len() > MAX_EXPANSION_TOKENS {
+                return Err(self.fail(format!(
+                    "`{}!` expands to more than {} tokens",
+                    name, MAX_EXPANSION_TOKENS
+                )));
+            }
+            parse_expansion(self.source, tokens, span).map_err(|err| Failure {
+                message: match err {
+                    TlError::Parser { message, .. } => message,
+                    other => other.to_string(),
+                },
+                backtrace: vec![name.to_string()],
+            })
+        });
+        match result {
+            Ok(expr) => Expr { span, ..expr },
+            Err(Failure { message, backtrace }) => {
+                self.error(span, message, backtrace);
+                Expr::new(ExprKind::Error, span)
+            }
+        }
+    }
+
+    /// Expand `name!` applied to `args`, and every invocation in the result.
+    fn expand(&mut self, name: &str, args: Vec<TokenTree>) -> Result<Vec<TokenTree>, Failure> {
+        if self.stack.len() >= MACRO_RECURSION_LIMIT {
+            return Err(self.fail(format!(
+                "Recursion limit of {} reached while expanding `{}!`",
+                MACRO_RECURSION_LIMIT, name
+            )));
+        }
+        let Some(rules) = self.macros.get(name) else {
+            let names: Vec<String> = similar_names(name, self.macros.keys().map(String::as_str))
+                .into_iter()
+                .map(|name| format!("{}!", name))
+                .collect();
+            let mut message = format!("Cannot find macro `{}!`", name);
+            if let Some(help) = did_you_mean(&names) {
+                message = format!("{}; {}", message, help);
+            }
+            return Err(self.fail(message));
+        };
+        let mut bindings = Bindings::new();
+        let Some(rule) = rules.iter().find(|rule| {
+            bindings.clear();
+            match_all(&rule.pattern, &args, &mut bindings)
+        }) else {
+            return Err(self.fail(format!("No rule of `{}!` matches these arguments", name)));
+        };
+        let rule = rule.clone();
+
+        self.expansions += 1;
+        let mut out = Vec::new();
+        self.stack.push(name.to_string());
+        transcribe(&rule.body, &bindings, &rule.locals, self.expansions, &mut out)
+            .map_err(|message| self.fail(message))?;
+        let out = self.expand_nested(out)?;
+        self.stack.pop();
+        Ok(out)
+    }
+
+    /// Replace every `name!(...)` in `trees` with its expansion, as a block.
+    fn expand_nested(&mut self, trees: Vec<TokenTree>) -> Result<Vec<TokenTree>, Failure> {
+        let mut out = Vec::with_capacity(trees.len());
+        let mut trees = trees.into_iter().peekable();
+        while let Some(tree) = trees.next() {
+            match tree {
+                TokenTree::Token(TokenType::Identifier(name))
+                    if trees.peek() == Some(&TokenTree::Token(TokenType::Bang)) =>
+                {
+                    trees.next();
+                    let Some(TokenTree::Group(_, args)) = trees.next() else {
+                        return Err(self.fail(format!("Expected arguments after `{}!`", name)));
+                    };
+                    let expansion = self.expand(&name, args)?;
+                    out.push(TokenTree::Group(Delimiter::Brace, expansion));
+                }
+                TokenTree::Group(delimiter, inner) => {
+                    out.push(TokenTree::Group(delimiter, self.expand_nested(inner)?));
+                }
+                token => out.push(token),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Write out `body` with the fragments in `bindings` substituted and the
+/// body's own `locals` renamed for expansion number `expansion`.
+fn transcribe(
+    body: &[Transcriber],
+    bindings: &Bindings,
+    locals: &HashSet<String>,
+    expansion: usize,
+    out: &mut Vec<TokenTree>,
+) -> Result<(), String> {
+    for part in body {
+        match part {
+            // `#` cannot appear in a name written in the source.
+            Transcriber::Token(TokenType::Identifier(name)) if locals.contains(name) => {
+                out.push(TokenTree::Token(TokenType::Identifier(format!("{}#{}", name, expansion))));
+            }
+            Transcriber::Token(token) => out.push(TokenTree::Token(token.clone())),
+            Transcriber::Group(delimiter, inner) => {
+                let mut group = Vec::new();
+                transcribe(inner, bindings, locals, expansion, &mut group)?;
+                out.push(TokenTree::Group(*delimiter, group));
+            }
+            Transcriber::Var(name) => match bindings.get(name) {
+                Some(Binding::One(Fragment::Expr, trees)) if trees.len() > 1 => {
+                    out.push(TokenTree::Group(Delimiter::Paren, trees.clone()));
+                }
+                Some(Binding::One(_, trees)) => out.extend(trees.iter().cloned()),
+                Some(Binding::Many(_)) => {
+                    return Err(format!("`${}` repeats, so it must be used inside `$( ... )`", name));
+                }
+                None => return Err(format!("No fragment named `${}` in the pattern", name)),
+            },
+            Transcriber::Repeat(repetition) => {
+                let mut names = Vec::new();
+                repeated_vars(&repetition.parts, bindings, &mut names);
+                let mut rounds = None;
+                for name in &names {
+                    let Some(Binding::Many(each)) = bindings.get(name) else {
+                        continue;
+                    };
+                    match rounds {
+                        Some((count, first)) if count != each.len() => {
+                            return Err(format!(
+                                "`${}` repeats {} times but `${}` {} times",
+                                first,
+                                count,
+                                name,
+                                each.len()
+                            ));
+                        }
+                        _ => rounds = Some((each.len(), name)),
+                    }
+                }
+                let Some((count, _)) = rounds else {
+                    return Err("`$( ... )` must use a fragment that repeats".to_string());
+                };
+                for round in 0..count {
+                    if round > 0
+                        && let Some(separator) = &repetition.separator
+                    {
+                        out.push(TokenTree::Token(separator.clone()));
+                    }
+                    let mut inner = bindings.clone();
+                    for name in &names {
+                        if let Some(Binding::Many(each)) = bindings.get(name) {
+                            inner.insert(name.clone(), each[round].clone());
+                        }
+                    }
+                    transcribe(&repetition.parts, &inner, locals, expansion, out)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Variables used in `body` that are bound to repetitions.
+fn repeated_vars(body: &[Transcriber], bindings: &Bindings, names: &mut Vec<String>) {
+    for part in body {
+        match part {
+            Transcriber::Var(name)
+                if matches!(bindings.get(name), Some(Binding::Many(_))) && !names.contains(name) =>
+            {
+                names.push(name.clone());
+            }
+            Transcriber::Group(_, inner) => repeated_vars(inner, bindings, names),
+            Transcriber::Repeat(repetition) => repeated_vars(&repetition.parts, bindings, names),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    /// Parse and expand `source`, returning the program and the errors.
+    fn expand(source: &str) -> (Program, Vec<TlError>) {
+        let (mut program, errors) = Parser::new(source.to_string()).parse_with_recovery();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let errors = expand_macros(&mut program, source);
+        (program, errors)
+    }
+
+    /// The body of the program's last function.
+    fn main_body(program: &Program) -> &Block {
+        let Some(ItemKind::Function { body: Some(body), .. }) = program.items.last().map(|i| &i.kind) else {
+            panic!("expected a function last");
+        };
+        let ExprKind::Block(block) = &body.kind else {
+            panic!("expected a block body");
+        };
+        block
+    }
+
+    fn messages(errors: &[TlError]) -> Vec<String> {
+        errors.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn invocation_becomes_the_matching_rule_body() {
+        let source = "macro_rules! square { ($x:expr) => { $x * $x }; }\n\
+                      fn main() { let n = square!(1 + 2); }";
+        let (program, errors) = expand(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let StmtKind::Let { initializer: Some(init), .. } = &main_body(&program).statements[0].kind else {
+            panic!("expected a let");
+        };
+        let ExprKind::Block(block) = &init.kind else {
+            panic!("expected the expansion to be a block, got {:?}", init.kind);
+        };
+        let Some(ExprKind::Binary { left, op: shared::BinaryOp::Mul, .. }) = block.expr.as_ref().map(|e| &e.kind) else {
+            panic!("expected `$x * $x`");
+        };
+        // The fragment keeps its grouping.
+        assert!(matches!(left.kind, ExprKind::Binary { op: shared::BinaryOp::Add, .. }));
+        let at = source.find("square!(").unwrap();
+        assert_eq!(init.span.offset(), at);
+    }
+
+    #[test]
+    fn rules_are_tried_in_order_with_repetitions() {
+        let source = "macro_rules! sum {\n\
+                          () => { 0 };\n\
+                          ($first:expr $(, $rest:expr)*) => { $first $(+ $rest)* };\n\
+                      }\n\
+                      fn main() { sum!(); sum!(1, 2, 3); }";
+        let (program, errors) = expand(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let statements = &main_body(&program).statements;
+        assert_eq!(statements.len(), 2);
+        let StmtKind::Expr(expr) = &statements[1].kind else {
+            panic!("expected an expression statement");
+        };
+        let ExprKind::Block(block) = &expr.kind else {
+            panic!("expected a block");
+        };
+        let Some(ExprKind::Binary { left, .. }) = block.expr.as_ref().map(|e| &e.kind) else {
+            panic!("expected `1 + 2 + 3`");
+        };
+        assert!(matches!(left.kind, ExprKind::Binary { .. }));
+    }
+
+    #[test]
+    fn body_locals_do_not_capture_caller_names() {
+        let source = "macro_rules! swap { ($a:ident, $b:ident) => { let tmp = $a; $a = $b; $b = tmp; }; }\n\
+                      fn main() { let mut tmp = 1; let mut y = 2; swap!(tmp, y); }";
+        let (program, errors) = expand(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let StmtKind::Expr(expr) = &main_body(&program).statements[2].kind else {
+            panic!("expected the invocation");
+        };
+        let ExprKind::Block(block) = &expr.kind else {
+            panic!("expected a block");
+        };
+        let StmtKind::Let { pattern, initializer: Some(init), .. } = &block.statements[0].kind else {
+            panic!("expected `let tmp = $a`");
+        };
+        // The macro's `tmp` is renamed; the caller's, passed as `$a`, is not.
+        assert!(matches!(&pattern.kind, shared::PatternKind::Ident(name) if name.starts_with("tmp#")));
+        assert!(matches!(&init.kind, ExprKind::Variable { path } if path == &["tmp"]));
+    }
+
+    #[test]
+    fn nested_invocations_are_expanded() {
+        let source = "macro_rules! double { ($x:expr) => { $x + $x }; }\n\
+                      macro_rules! quad { ($x:expr) => { double!(double!($x)) }; }\n\
+                      fn main() { let n = quad!(1); }";
+        let (_, errors) = expand(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn failures_name_the_macro_and_its_callers() {
+        let source = "macro_rules! one { ($x:literal) => { $x }; }\n\
+                      macro_rules! outer { ($x:ident) => { one!($x) }; }\n\
+                      fn main() { one!(1, 2); outer!(a); onee!(1); }";
+        let (program, errors) = expand(source);
+        assert_eq!(
+            messages(&errors),
+            [
+                "Macro error: No rule of `one!` matches these arguments",
+                "Macro error: No rule of `one!` matches these arguments\n  in the expansion of `outer!`",
+                "Macro error: Cannot find macro `onee!`; did you mean `one!`?",
+            ]
+        );
+        assert!(errors.iter().all(|err| err.code() == "E0008"));
+        let at = source.find("outer!").unwrap();
+        assert_eq!(errors[1].span().map(|span| span.offset()), Some(at));
+        // Failed invocations are left as error nodes.
+        let StmtKind::Expr(expr) = &main_body(&program).statements[0].kind else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(expr.kind, ExprKind::Error);
+    }
+
+    #[test]
+    fn runaway_recursion_stops_at_the_limit() {
+        let source = "macro_rules! forever { () => { forever!() }; }\n\
+                      fn main() { forever!(); }";
+        let (_, errors) = expand(source);
+        let [TlError::Macro { message, backtrace, .. }] = &errors[..] else {
+            panic!("expected one macro error, got {:?}", errors);
+        };
+        assert!(message.starts_with("Recursion limit of 64"), "{}", message);
+        assert_eq!(backtrace.len(), MACRO_RECURSION_LIMIT);
+        assert!(errors[0].to_string().ends_with("in the expansion of `forever!` (64 times)"));
+    }
+
+    #[test]
+    fn bad_definitions_and_expansions_are_reported() {
+        let source = "macro_rules! bad { ($x) => { $x }; ($x:expr) => { $y }; }\n\
+                      macro_rules! broken { () => { 1 + }; }\n\
+                      fn main() { bad!(1); broken!(); }";
+        let (_, errors) = expand(source);
+        let messages = messages(&errors);
+        assert_eq!(messages[0], "Macro error: `$x` needs a fragment kind, e.g. `$x:expr`");
+        assert_eq!(messages[1], "Macro error: No fragment named `$y` in the pattern\n  in the expansion of `bad!`");
+        assert!(messages[2].contains("in the expansion of `broken!`"), "{}", messages[2]);
+    }
+}
@@ -0,0 +1,309 @@
+// File: compiler/src/format.rs
+//! Source formatter for T-Lang.
+//!
+//! Formatting works on the token stream rather than the AST so comments and
+//! the author's line structure survive. Each line is re-indented by the
+//! brackets still open at its start (one level per line that opened any),
+//! spacing between tokens is normalised, runs of blank lines collapse to one,
+//! and a line longer than `max_width` has its first bracketed list split one
+//! element per line. Comments and lines inside multi-line tokens are kept
+//! verbatim.
+
+use shared::{Result, TokenStream, TokenType};
+use std::ops::Range;
+
+/// Layout settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Spaces per indentation level.
+    pub indent_width: usize,
+    /// Lines longer than this are split where possible.
+    pub max_width: usize,
+    /// Indent with tabs instead of spaces.
+    pub hard_tabs: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent_width: 4, max_width: 100, hard_tabs: false }
+    }
+}
+
+/// Format a whole source file.
+pub fn format_source(source: &str, options: &FormatOptions) -> Result<String> {
+    let lines = source.lines().count();
+    let formatted = format_lines(source, options, 0..lines)?;
+    Ok(formatted.trim_end_matches('\n').to_string() + "\n")
+}
+
+/// Format the source lines in `lines` (0-based, end exclusive), using the
+/// rest of the file for indentation context. Every returned line ends in
+/// `\n`; the result replaces exactly those lines.
+pub fn format_lines(source: &str, options: &FormatOptions, lines: Range<usize>) -> Result<String> {
+    let layout = Layout::new(source)?;
+    Ok(layout.render(options, lines))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Token(TokenType),
+    Comment,
+}
+
+/// A token or comment, with its byte range in the source.
+#[derive(Debug)]
+struct Element {
+    piece: Piece,
+    start: usize,
+    end: usize,
+}
+
+impl Element {
+    fn is_open(&self) -> bool {
+        matches!(self.piece, Piece::Token(TokenType::LParen | TokenType::LBracket | TokenType::LBrace))
+    }
+
+    fn is_close(&self) -> bool {
+        matches!(self.piece, Piece::Token(TokenType::RParen | TokenType::RBracket | TokenType::RBrace))
+    }
+}
+
+/// Whether `a` and `b`, adjacent on one line, are separated by a space.
+/// Ambiguous operators (`<`, `*`, `&`, `-`, ...) keep the source's choice.
+fn space_between(a: &Piece, b: &Piece, had_space: bool) -> bool {
+    use TokenType::*;
+    let (a, b) = match (a, b) {
+        (Piece::Token(a), Piece::Token(b)) => (a, b),
+        _ => return true,
+    };
+    let spaced_op = |t: &TokenType| {
+        matches!(
+            t,
+            Eq | EqEq | Ne | Le | Ge | AndAnd | OrOr | PlusEq | MinusEq | StarEq | SlashEq
+                | PercentEq | CaretEq | AndEq | OrEq | ShlEq | ShrEq | Arrow | FatArrow
+        )
+    };
+    match (a, b) {
+        (LBrace, RBrace) | (LParen, RParen) | (LBracket, RBracket) => false,
+        (_, Comma | Semicolon | RParen | RBracket | Dot | Question | ColonColon | Colon) => false,
+        (LParen | LBracket | Dot | ColonColon | Bang, _) | (Pound, LBracket) => false,
+        (Comma | Semicolon | Colon, _) => true,
+        (LBrace, _) | (_, LBrace | RBrace) => true,
+        (a, _) if spaced_op(a) => true,
+        (_, b) if spaced_op(b) => true,
+        // Calls, indexing and macro invocations.
+        (Identifier(_) | RParen | RBracket | SelfValue | SelfType, LParen | LBracket | Bang) => {
+            false
+        }
+        _ => had_space,
+    }
+}
+
+struct Layout<'a> {
+    source: &'a str,
+    /// Byte offset at which each line starts.
+    line_starts: Vec<usize>,
+    elements: Vec<Element>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(source: &'a str) -> Result<Self> {
+        let mut elements = Vec::new();
+        let mut stream = TokenStream::lex(source)?;
+        loop {
+            for comment in stream.leading_trivia().iter().filter(|trivia| trivia.is_comment()) {
+                let start = comment.span.offset();
+                elements.push(Element { piece: Piece::Comment, start, end: start + comment.text.trim_end().len() });
+            }
+            let token = stream.advance();
+            if token.token_type == TokenType::Eof {
+                break;
+            }
+            let start = token.span.offset();
+            let end = start + token.span.len();
+            let element = match token.token_type.clone() {
+                // Doc comments are laid out like any other comment.
+                TokenType::DocComment(_) => {
+                    Element { piece: Piece::Comment, start, end: start + source[start..end].trim_end().len() }
+                }
+                token_type => Element { piece: Piece::Token(token_type), start, end },
+            };
+            elements.push(element);
+        }
+
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Ok(Self { source, line_starts, elements })
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    fn indent(options: &FormatOptions, level: usize) -> String {
+        if options.hard_tabs {
+            "\t".repeat(level)
+        } else {
+            " ".repeat(level * options.indent_width)
+        }
+    }
+
+    /// Join elements of one line with normalised spacing.
+    fn join(&self, elements: &[&Element]) -> String {
+        let mut out = String::new();
+        for (i, el) in elements.iter().enumerate() {
+            if i > 0 {
+                let prev = elements[i - 1];
+                let had_space = prev.end < el.start;
+                if space_between(&prev.piece, &el.piece, had_space) {
+                    out.push(' ');
+                }
+            }
+            out.push_str(&self.source[el.start..el.end]);
+        }
+        out
+    }
+
+    /// Lines for one source line at `level`, split if it is too long.
+    fn render_line(&self, options: &FormatOptions, level: usize, elements: &[&Element]) -> Vec<String> {
+        let indent = Self::indent(options, level);
+        let line = format!("{}{}", indent, self.join(elements));
+        if line.chars().count() <= options.max_width {
+            return vec![line];
+        }
+
+        // The first bracket closed on this line with a top-level comma inside.
+        for (open, el) in elements.iter().enumerate() {
+            if !matches!(el.piece, Piece::Token(TokenType::LParen | TokenType::LBracket)) {
+                continue;
+            }
+            let mut depth = 0;
+            let mut commas = Vec::new();
+            let mut close = None;
+            for (i, inner) in elements.iter().enumerate().skip(open) {
+                if inner.is_open() {
+                    depth += 1;
+                } else if inner.is_close() {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(i);
+                        break;
+                    }
+                } else if depth == 1 && inner.piece == Piece::Token(TokenType::Comma) {
+                    commas.push(i);
+                }
+            }
+            let Some(close) = close else {
+                break;
+            };
+            if commas.is_empty() {
+                continue;
+            }
+
+            let inner_indent = Self::indent(options, level + 1);
+            let mut lines = vec![format!("{}{}", indent, self.join(&elements[..=open]))];
+            let mut start = open + 1;
+            for end in commas.into_iter().chain(std::iter::once(close)) {
+                if start < end {
+                    lines.push(format!("{}{},", inner_indent, self.join(&elements[start..end])));
+                }
+                start = end + 1;
+            }
+            lines.push(format!("{}{}", indent, self.join(&elements[close..])));
+            return lines;
+        }
+        vec![line]
+    }
+
+    fn render(&self, options: &FormatOptions, range: Range<usize>) -> String {
+        let line_count = self.line_starts.len();
+        let mut on_line: Vec<Vec<&Element>> = vec![Vec::new(); line_count];
+        let mut verbatim = vec![false; line_count];
+        for el in &self.elements {
+            let first = self.line_of(el.start);
+            on_line[first].push(el);
+            let last = self.line_of(el.end.saturating_sub(1).max(el.start));
+            for covered in &mut verbatim[first + 1..=last] {
+                *covered = true;
+            }
+        }
+
+        let mut out = String::new();
+        // Lines that opened brackets still unclosed.
+        let mut open: Vec<usize> = Vec::new();
+        let mut prev_blank = true;
+        let mut prev_opens = false;
+        for (line, elements) in on_line.iter().enumerate() {
+            let emit = range.contains(&line);
+            let leading_close = elements.iter().take_while(|el| el.is_close()).count();
+            for _ in 0..leading_close {
+                open.pop();
+            }
+            let mut level = open.clone();
+            level.dedup();
+
+            let text = if verbatim[line] {
+                let end = self.line_starts.get(line + 1).map_or(self.source.len(), |&e| e - 1);
+                Some(vec![self.source[self.line_starts[line]..end].trim_end().to_string()])
+            } else if elements.is_empty() {
+                // Keep single blank lines, not after an opening bracket or
+                // before a closing one.
+                let next_closes = on_line[line + 1..]
+                    .iter()
+                    .find(|els| !els.is_empty())
+                    .is_none_or(|els| els[0].is_close());
+                (!prev_blank && !prev_opens && !next_closes).then(|| vec![String::new()])
+            } else {
+                Some(self.render_line(options, level.len(), elements))
+            };
+
+            for el in &elements[leading_close..] {
+                if el.is_open() {
+                    open.push(line);
+                } else if el.is_close() {
+                    open.pop();
+                }
+            }
+
+            if let Some(text) = text {
+                prev_blank = elements.is_empty() && !verbatim[line];
+                prev_opens = elements.last().is_some_and(|el| el.is_open());
+                if emit {
+                    for l in text {
+                        out.push_str(&l);
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reindents_and_normalises_spacing() {
+        let source = "fn main(){\n\n\nlet x:i64=1 ;   // one\n  if x==1 {\nprint(x) ;\n}\n\n\n}\n";
+        let expected = "fn main() {\n    let x: i64 = 1; // one\n    if x == 1 {\n        print(x);\n    }\n}\n";
+        assert_eq!(format_source(source, &FormatOptions::default()).unwrap(), expected);
+    }
+
+    #[test]
+    fn splits_long_argument_lists() {
+        let options = FormatOptions { max_width: 20, ..FormatOptions::default() };
+        let source = "fn main() {\n    call(alpha, beta, gamma);\n}\n";
+        let expected = "fn main() {\n    call(\n        alpha,\n        beta,\n        gamma,\n    );\n}\n";
+        assert_eq!(format_source(source, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn formats_a_line_range_in_context() {
+        let source = "fn main() {\nlet a = 1;\nlet b = 2;\n}\n";
+        let formatted = format_lines(source, &FormatOptions::default(), 2..3).unwrap();
+        assert_eq!(formatted, "    let b = 2;\n");
+    }
+}
@@ -0,0 +1,467 @@
+// compiler/src/graph.rs
+//! The call graph and module dependency graph of a program, for
+//! `tlang graph`.
+//!
+//! Both are read off the syntax tree, so a program can be drawn before it
+//! type checks. A function is named by its path from the root of the
+//! program, `m::f` for one declared in `mod m`, and a method after its type,
+//! `Point::new`. A call is an edge when the function it names is in the
+//! program: the first segment of its path is looked up among the items of
+//! the calling module, then its `use` declarations, and otherwise from the
+//! root. A method call is an edge when the checker recorded the type of its
+//! receiver.
+//!
+//! A module depends on another when it imports from it or calls one of its
+//! functions. The root is `crate`; a `use` of something outside the program
+//! makes its first segment, such as `std`, an external module.
+//!
+//! Either graph can be written as Graphviz DOT or as JSON.
+
+use crate::lint::name_span;
+use crate::resolve::type_name;
+use crate::safety::report::Location;
+use miette::SourceSpan;
+use serde::Serialize;
+use shared::ast::Block;
+use shared::ast::stmt::ImplItem;
+use shared::source::line_col_from_offset;
+use shared::{Expr, ExprKind, Item, ItemKind, Program, StmtKind, TypeKind};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The name of the program's root module.
+const ROOT: &str = "crate";
+
+/// A directed graph, its nodes and edges in a stable order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// A function or module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Node {
+    pub name: String,
+    /// Where it is declared; `None` for the root and external modules
+    pub location: Option<Location>,
+    /// Whether it is outside the program
+    pub external: bool,
+}
+
+/// Calls or dependencies from one node to another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    /// How many calls or `use` declarations the edge stands for
+    pub count: usize,
+}
+
+/// The two graphs of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramGraphs {
+    pub calls: Graph,
+    pub modules: Graph,
+}
+
+impl ProgramGraphs {
+    /// The call and module dependency graphs of `program`.
+    pub fn new(program: &Program, source: &str) -> Self {
+        let mut index = Index::default();
+        index.module(&program.items, &[], None, source);
+
+        let mut calls = Edges::new();
+        let mut modules = Edges::new();
+        for function in &index.functions {
+            let mut walk = Calls { index: &index, module: &function.module, callees: Vec::new() };
+            walk.expr(function.body);
+            for callee in walk.callees {
+                let module = &index.functions[index.by_name[&callee]].module;
+                if *module != function.module {
+                    modules.add(module_name(&function.module), module_name(module));
+                }
+                calls.add(function.name.clone(), callee);
+            }
+        }
+
+        let mut nodes: Vec<Node> = index.modules.values().map(|module| module.node.clone()).collect();
+        for (module, imports) in index.modules.iter().map(|(path, module)| (path, &module.imports)) {
+            for target in imports.values() {
+                let dependency = index.module_of(target);
+                if dependency != module_name(module) {
+                    if nodes.iter().all(|node| node.name != dependency) {
+                        nodes.push(Node { name: dependency.clone(), location: None, external: true });
+                    }
+                    modules.add(module_name(module), dependency);
+                }
+            }
+        }
+        let functions = index.functions.iter().map(|function| Node {
+            name: function.name.clone(),
+            location: Some(function.location),
+            external: false,
+        });
+
+        ProgramGraphs {
+            calls: Graph { nodes: functions.collect(), edges: calls.finish() },
+            modules: Graph { nodes, edges: modules.finish() },
+        }
+    }
+}
+
+impl Graph {
+    /// The graph in Graphviz DOT, as the digraph `name`. External nodes are
+    /// dashed, and an edge standing for more than one call is labelled with
+    /// its count.
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut dot = format!("digraph {} {{\n", name);
+        for node in &self.nodes {
+            let style = if node.external { " [style=dashed]" } else { "" };
+            dot.push_str(&format!("    {}{};\n", quote(&node.name), style));
+        }
+        for edge in &self.edges {
+            let label = if edge.count > 1 { format!(" [label=\"{}\"]", edge.count) } else { String::new() };
+            dot.push_str(&format!("    {} -> {}{};\n", quote(&edge.from), quote(&edge.to), label));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a graph is always serializable")
+    }
+}
+
+/// `name` as a DOT identifier.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The name of the module at `path`.
+fn module_name(path: &[String]) -> String {
+    if path.is_empty() { ROOT.to_string() } else { path.join("::") }
+}
+
+/// Counts the edges between nodes, in the order they are first met.
+struct Edges {
+    counts: BTreeMap<(String, String), usize>,
+    order: Vec<(String, String)>,
+}
+
+impl Edges {
+    fn new() -> Self {
+        Edges { counts: BTreeMap::new(), order: Vec::new() }
+    }
+
+    fn add(&mut self, from: String, to: String) {
+        let count = self.counts.entry((from.clone(), to.clone())).or_insert(0);
+        if *count == 0 {
+            self.order.push((from, to));
+        }
+        *count += 1;
+    }
+
+    fn finish(self) -> Vec<Edge> {
+        let counts = self.counts;
+        self.order.into_iter().map(|key| Edge { count: counts[&key], from: key.0, to: key.1 }).collect()
+    }
+}
+
+/// A function of the program with a body.
+struct Function<'a> {
+    name: String,
+    /// The path of the module declaring it
+    module: Vec<String>,
+    location: Location,
+    body: &'a Expr,
+}
+
+/// What a module declares.
+struct Module {
+    node: Node,
+    /// The names of its items
+    items: HashSet<String>,
+    /// What each of its `use` declarations brings in, by name, as a path
+    /// from the root
+    imports: BTreeMap<String, Vec<String>>,
+}
+
+/// The modules and functions of a program, to look paths up in.
+#[derive(Default)]
+struct Index<'a> {
+    modules: BTreeMap<Vec<String>, Module>,
+    functions: Vec<Function<'a>>,
+    by_name: HashMap<String, usize>,
+}
+
+impl<'a> Index<'a> {
+    /// Record the module at `path`, declared by `item` unless it is the root,
+    /// and the modules and functions in `items`.
+    fn module(&mut self, items: &'a [Item], path: &[String], item: Option<&Item>, source: &str) {
+        let name = path.last().map_or("", String::as_str);
+        let location = item.map(|item| location(source, name_span(source, item, name)));
+        let mut module = Module {
+            node: Node { name: module_name(path), location, external: false },
+            items: HashSet::new(),
+            imports: BTreeMap::new(),
+        };
+        let mut uses = Vec::new();
+        for item in items {
+            match &item.kind {
+                ItemKind::Function { name, .. }
+                | ItemKind::Struct { name, .. }
+                | ItemKind::Enum { name, .. }
+                | ItemKind::Trait { name, .. }
+                | ItemKind::TypeAlias { name, .. }
+                | ItemKind::Const { name, .. }
+                | ItemKind::Static { name, .. }
+                | ItemKind::Module { name, .. } => {
+                    module.items.insert(name.clone());
+                }
+                ItemKind::Use { path: used, alias, glob: false } => uses.push((used, alias)),
+                _ => {}
+            }
+        }
+        for (used, alias) in uses {
+            if let Some(name) = alias.as_ref().or(used.last()) {
+                let target = self.absolute(&module, path, used);
+                module.imports.insert(name.clone(), target);
+            }
+        }
+        self.modules.insert(path.to_vec(), module);
+
+        for item in items {
+            match &item.kind {
+                ItemKind::Function { name, body: Some(body), .. } => {
+                    self.function(path, name, &qualified(path, name), item, body, source);
+                }
+                ItemKind::Impl { self_ty, items: impl_items, .. } => {
+                    for impl_item in impl_items {
+                        if let ImplItem::Function { name, body, .. } = impl_item {
+                            let full_name = qualified(path, &format!("{}::{}", type_name(self_ty), name));
+                            self.function(path, name, &full_name, item, body, source);
+                        }
+                    }
+                }
+                ItemKind::Module { name, items, .. } => {
+                    let mut inner = path.to_vec();
+                    inner.push(name.clone());
+                    self.module(items, &inner, Some(item), source);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn function(&mut self, module: &[String], name: &str, full_name: &str, item: &Item, body: &'a Expr, source: &str) {
+        self.by_name.insert(full_name.to_string(), self.functions.len());
+        self.functions.push(Function {
+            name: full_name.to_string(),
+            module: module.to_vec(),
+            location: location(source, name_span(source, item, name)),
+            body,
+        });
+    }
+
+    /// `path`, written in the module at `module`, as a path from the root.
+    fn resolve(&self, module: &[String], path: &[String]) -> Vec<String> {
+        match self.modules.get(module) {
+            Some(declared) => self.absolute(declared, module, path),
+            None => path.to_vec(),
+        }
+    }
+
+    /// `path` as a path from the root, written in `declared`, the module at
+    /// `module`.
+    fn absolute(&self, declared: &Module, module: &[String], path: &[String]) -> Vec<String> {
+        let Some((first, rest)) = path.split_first() else { return Vec::new() };
+        let mut absolute = if declared.items.contains(first) {
+            let mut absolute = module.to_vec();
+            absolute.push(first.clone());
+            absolute
+        } else if let Some(target) = declared.imports.get(first) {
+            target.clone()
+        } else {
+            vec![first.clone()]
+        };
+        absolute.extend(rest.iter().cloned());
+        absolute
+    }
+
+    /// The name of the module `path` leads into: the deepest module of the
+    /// program it starts with, or else its first segment, outside it.
+    fn module_of(&self, path: &[String]) -> String {
+        let inside = (0..=path.len()).rev().find(|&len| {
+            let root = || path.first().is_some_and(|first| self.modules[&path[..0]].items.contains(first));
+            self.modules.contains_key(&path[..len]) && (len > 0 || root())
+        });
+        match inside {
+            Some(len) => module_name(&path[..len]),
+            None => path.first().cloned().unwrap_or_else(|| ROOT.to_string()),
+        }
+    }
+}
+
+/// `name` declared in the module at `module`.
+fn qualified(module: &[String], name: &str) -> String {
+    if module.is_empty() { name.to_string() } else { format!("{}::{}", module.join("::"), name) }
+}
+
+fn location(source: &str, span: SourceSpan) -> Location {
+    let (line, column) = line_col_from_offset(source, span.offset());
+    Location { line, column }
+}
+
+/// Collects the functions of the program one body calls, in source order.
+struct Calls<'i, 'a> {
+    index: &'i Index<'a>,
+    /// The module of the function
+    module: &'i [String],
+    callees: Vec<String>,
+}
+
+impl Calls<'_, '_> {
+    /// Record a call to `path`, if it names a function of the program.
+    fn call(&mut self, path: &[String]) {
+        let name = self.index.resolve(self.module, path).join("::");
+        if self.index.by_name.contains_key(&name) {
+            self.callees.push(name);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Let { initializer: Some(expr), .. }
+                | StmtKind::Expr(expr)
+                | StmtKind::Defer { body: expr } => self.expr(expr),
+                StmtKind::Let { .. } | StmtKind::Item(_) | StmtKind::Macro { .. } => {}
+            }
+        }
+        if let Some(expr) = &block.expr {
+            self.expr(expr);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Call { callee, args, .. } => {
+                match &callee.kind {
+                    ExprKind::Variable { path } => self.call(path),
+                    _ => self.expr(callee),
+                }
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::MethodCall { receiver, method, args } => {
+                self.expr(receiver);
+                if let Some(ty) = &receiver.ty {
+                    let ty = match &ty.kind {
+                        TypeKind::Reference { target, .. } => target,
+                        _ => ty,
+                    };
+                    let path: Vec<String> = type_name(ty).split("::").map(str::to_string).collect();
+                    self.call(&[path, vec![method.clone()]].concat());
+                }
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                else_branch.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    arm.guard.iter().for_each(|e| self.expr(e));
+                    self.expr(&arm.body);
+                }
+            }
+            ExprKind::Binary { left: a, right: b, .. }
+            | ExprKind::Index { object: a, index: b }
+            | ExprKind::Assign { target: a, value: b, .. }
+            | ExprKind::While { condition: a, body: b, .. }
+            | ExprKind::For { iterable: a, body: b, .. } => {
+                self.expr(a);
+                self.expr(b);
+            }
+            ExprKind::Range { start, end, .. } => start.iter().chain(end).for_each(|e| self.expr(e)),
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => value.iter().for_each(|e| self.expr(e)),
+            ExprKind::Tuple(elems) => elems.iter().for_each(|e| self.expr(e)),
+            ExprKind::Array { elements, repeat } => {
+                elements.iter().for_each(|e| self.expr(e));
+                repeat.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::Struct { fields, base, .. } => {
+                fields.iter().filter_map(|f| f.value.as_ref()).for_each(|e| self.expr(e));
+                base.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Loop { body: inner, .. }
+            | ExprKind::Closure { body: inner, .. }
+            | ExprKind::Async { body: inner, .. }
+            | ExprKind::Spawn { body: inner }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner } => self.expr(inner),
+            ExprKind::Literal(_)
+            | ExprKind::Variable { .. }
+            | ExprKind::Continue { .. }
+            | ExprKind::Macro { .. }
+            | ExprKind::Error => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+
+    fn graphs(source: &str) -> ProgramGraphs {
+        ProgramGraphs::new(&parse_source(source).unwrap(), source)
+    }
+
+    fn edges(graph: &Graph) -> Vec<(&str, &str, usize)> {
+        graph.edges.iter().map(|edge| (edge.from.as_str(), edge.to.as_str(), edge.count)).collect()
+    }
+
+    #[test]
+    fn calls_are_resolved_through_modules_and_imports() {
+        let source = "use std::fs;\nuse shapes::area;\n\nmod shapes {\n    fn area(w: i32) -> i32 { double(w) }\n    \
+                      fn double(x: i32) -> i32 { x * 2 }\n}\n\nfn main() {\n    area(1);\n    shapes::area(2);\n    \
+                      print(3);\n}\n";
+        let graphs = graphs(source);
+
+        let functions: Vec<&str> = graphs.calls.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(functions, ["shapes::area", "shapes::double", "main"]);
+        assert_eq!(graphs.calls.nodes[0].location, Some(Location { line: 5, column: 8 }));
+        assert_eq!(edges(&graphs.calls), [("shapes::area", "shapes::double", 1), ("main", "shapes::area", 2)]);
+
+        let modules: Vec<(&str, bool)> =
+            graphs.modules.nodes.iter().map(|node| (node.name.as_str(), node.external)).collect();
+        assert_eq!(modules, [("crate", false), ("shapes", false), ("std", true)]);
+        assert_eq!(edges(&graphs.modules), [("crate", "shapes", 3), ("crate", "std", 1)]);
+    }
+
+    #[test]
+    fn graphs_are_written_as_dot_and_json() {
+        let source = "fn main() {\n    helper();\n    helper();\n}\n\nfn helper() {}\n";
+        let graphs = graphs(source);
+
+        assert_eq!(
+            graphs.calls.to_dot("calls"),
+            "digraph calls {\n    \"main\";\n    \"helper\";\n    \"main\" -> \"helper\" [label=\"2\"];\n}\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&graphs.calls.to_json()).unwrap();
+        assert_eq!(json["edges"][0]["to"], "helper");
+        assert_eq!(json["nodes"][1]["location"]["line"], 6);
+        let json: serde_json::Value = serde_json::from_str(&graphs.modules.to_json()).unwrap();
+        assert_eq!(json["nodes"][0]["name"], "crate");
+    }
+}
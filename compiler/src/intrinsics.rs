@@ -0,0 +1,198 @@
+// compiler/src/intrinsics.rs
+//! The `io`, `env`, `time`, `random` and `process` functions of the
+//! standard library.
+//!
+//! Programs call them by path, as `io::read_file("notes.txt")`. The type
+//! checker knows their signatures, and the lowering turns each call into a
+//! `CallExtern` of the C function `tstd` exports for it, so every backend
+//! that calls extern functions calls these too; the program is linked
+//! against `tstd`'s static library.
+//!
+//! Most functions need something from the host: its files, console,
+//! environment, clock or processes. `Capabilities` says which of them a
+//! compilation may use. Hosted programs may use all of them; embedded
+//! programs have none to use, and a call to any of these functions fails
+//! type checking. `random` needs nothing: it is a seeded generator whose
+//! numbers are the same on every backend.
+
+use plugin_api::{ExternFunction, Profile};
+use shared::ast::PrimitiveType::{self, Bool, I32, I64, Str, Unit};
+use shared::Type;
+use miette::SourceSpan;
+use crate::ir::ffi_type;
+
+/// What an intrinsic needs from the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Reading and writing files
+    Files,
+    /// Standard input and output
+    Console,
+    /// Command-line arguments and environment variables
+    Environment,
+    /// A monotonic clock, and sleeping
+    Clock,
+    /// Exiting, and starting other programs
+    Processes,
+}
+
+impl Capability {
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Files => "file access",
+            Capability::Console => "console access",
+            Capability::Environment => "environment access",
+            Capability::Clock => "a clock",
+            Capability::Processes => "process control",
+        }
+    }
+}
+
+/// The capabilities a compilation may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub files: bool,
+    pub console: bool,
+    pub environment: bool,
+    pub clock: bool,
+    pub processes: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities { files: true, console: true, environment: true, clock: true, processes: true }
+    }
+}
+
+impl Capabilities {
+    /// No capabilities at all.
+    pub fn none() -> Self {
+        Capabilities { files: false, console: false, environment: false, clock: false, processes: false }
+    }
+
+    /// The capabilities programs built for `profile` have.
+    pub fn for_profile(profile: Profile) -> Self {
+        match profile {
+            Profile::Hosted => Capabilities::default(),
+            Profile::Embedded => Capabilities::none(),
+        }
+    }
+
+    /// Whether an intrinsic needing `capability` may be called; one that
+    /// needs none always may.
+    pub fn allows(&self, capability: Option<Capability>) -> bool {
+        match capability {
+            None => true,
+            Some(Capability::Files) => self.files,
+            Some(Capability::Console) => self.console,
+            Some(Capability::Environment) => self.environment,
+            Some(Capability::Clock) => self.clock,
+            Some(Capability::Processes) => self.processes,
+        }
+    }
+}
+
+/// A standard library function implemented by a C function of `tstd`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intrinsic {
+    /// The module it is in, as `io`
+    pub module: &'static str,
+    pub name: &'static str,
+    pub params: &'static [PrimitiveType],
+    pub return_type: PrimitiveType,
+    /// The C function it calls
+    pub symbol: &'static str,
+    pub capability: Option<Capability>,
+}
+
+/// Every intrinsic. Strings they cannot produce, such as the text of a file
+/// that does not exist or an unset variable, are empty.
+///
+/// `time::now_ms` counts milliseconds on a monotonic clock from the first
+/// call, which returns 0. `random::next` returns a non-negative `i64` from
+/// a SplitMix64 generator seeded with 0 until `random::seed` reseeds it;
+/// `random::below(n)` is `next() % n`, and 0 for `n <= 0`.
+/// `process::spawn` runs a shell command, waits for it and returns its exit
+/// code, or -1 if it could not run; `process::exit` flushes stdout first.
+pub const INTRINSICS: &[Intrinsic] = &[
+    intrinsic("io", "read_file", &[Str], Str, "tlang_io_read_file", Some(Capability::Files)),
+    intrinsic("io", "write_file", &[Str, Str], Bool, "tlang_io_write_file", Some(Capability::Files)),
+    intrinsic("io", "read_line", &[], Str, "tlang_io_read_line", Some(Capability::Console)),
+    intrinsic("io", "write", &[Str], Unit, "tlang_io_write", Some(Capability::Console)),
+    intrinsic("env", "arg_count", &[], I32, "tlang_env_arg_count", Some(Capability::Environment)),
+    intrinsic("env", "arg", &[I32], Str, "tlang_env_arg", Some(Capability::Environment)),
+    intrinsic("env", "var", &[Str], Str, "tlang_env_var", Some(Capability::Environment)),
+    intrinsic("time", "now_ms", &[], I64, "tlang_time_now_ms", Some(Capability::Clock)),
+    intrinsic("time", "sleep_ms", &[I64], Unit, "tlang_time_sleep_ms", Some(Capability::Clock)),
+    intrinsic("random", "seed", &[I64], Unit, "tlang_random_seed", None),
+    intrinsic("random", "next", &[], I64, "tlang_random_next", None),
+    intrinsic("random", "below", &[I64], I64, "tlang_random_below", None),
+    intrinsic("process", "exit", &[I32], Unit, "tlang_process_exit", Some(Capability::Processes)),
+    intrinsic("process", "spawn", &[Str], I32, "tlang_process_spawn", Some(Capability::Processes)),
+];
+
+const fn intrinsic(
+    module: &'static str,
+    name: &'static str,
+    params: &'static [PrimitiveType],
+    return_type: PrimitiveType,
+    symbol: &'static str,
+    capability: Option<Capability>,
+) -> Intrinsic {
+    Intrinsic { module, name, params, return_type, symbol, capability }
+}
+
+/// The intrinsic `path` names, as `["io", "read_file"]`.
+pub fn intrinsic_named(path: &[String]) -> Option<&'static Intrinsic> {
+    match path {
+        [module, name] => INTRINSICS.iter().find(|f| f.module == *module && f.name == *name),
+        _ => None,
+    }
+}
+
+impl Intrinsic {
+    /// `io::read_file`
+    pub fn path(&self) -> String {
+        format!("{}::{}", self.module, self.name)
+    }
+
+    /// The C function it calls, as an extern function of the module.
+    pub fn extern_function(&self) -> ExternFunction {
+        let span = SourceSpan::new(0.into(), 0);
+        let ffi = |prim: PrimitiveType| ffi_type(&Type::primitive(prim, span));
+        ExternFunction {
+            name: self.symbol.to_string(),
+            params: self.params.iter().map(|prim| ffi(prim.clone()).expect("intrinsics take C types")).collect(),
+            return_type: ffi(self.return_type.clone()),
+            variadic: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_api::FfiType;
+
+    #[test]
+    fn intrinsics_call_c_functions_of_tstd() {
+        let path = ["io".to_string(), "write_file".to_string()];
+        let write_file = intrinsic_named(&path).unwrap();
+        assert_eq!(write_file.path(), "io::write_file");
+        let function = write_file.extern_function();
+        assert_eq!(function.name, "tlang_io_write_file");
+        assert_eq!(function.params, [FfiType::Str, FfiType::Str]);
+        assert_eq!(function.return_type, Some(FfiType::Bool));
+        assert_eq!(intrinsic_named(&path[1..]), None);
+    }
+
+    #[test]
+    fn embedded_programs_have_no_capabilities() {
+        let embedded = Capabilities::for_profile(Profile::Embedded);
+        let hosted = Capabilities::for_profile(Profile::Hosted);
+        for f in INTRINSICS {
+            assert_eq!(embedded.allows(f.capability), f.module == "random", "{}", f.path());
+            assert!(hosted.allows(f.capability));
+        }
+    }
+}
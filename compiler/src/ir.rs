@@ -1,60 +1,3690 @@
 // File: compiler/src/ir.rs
-// IR lowering from AST `Program` into plugin_api::CompiledModule and Instruction.
+//! IR lowering from the AST `Program` into `plugin_api::CompiledModule`.
+//!
+//! The body of `fn main`, or of another entry point such as a benchmark, is
+//! lowered to a TIR function (`shared::tir`), followed by the program's
+//! functions it calls, which `CompiledModule::from_tir` then emits as stack
+//! instructions:
+//! `print`/`println` calls whose arguments
+//! are literals, or `let` bindings of literals, become constants and prints,
+//! with `as` casts of those arguments lowered to conversions.
+//! `for` loops over constant ranges of at most `MAX_UNROLLED_ITERATIONS`
+//! and with no `break` or `continue` are unrolled; `while`, `loop` and the
+//! other `for` loops over ranges become blocks that branch at run time, and
+//! so does an `if` whose condition is only known then. A `match` on an
+//! integer or an enum variant only known then ends its block in a `Switch`,
+//! a case for each literal or variant an arm names; one on a value known
+//! here lowers the arm it takes. A binding such code
+//! assigns to is moved into a TIR local rather than folded, and what is
+//! computed from it is lowered to instructions: comparisons, `!`, `&&` and
+//! `||`, and arithmetic. Integer division and remainder are guarded by a
+//! `Trap` that panics on a divisor of zero, or a quotient out of range;
+//! under overflow checks so are `+`, `-`, `*` and negation, which otherwise
+//! wrap around. A `spawn` block runs its
+//! body to completion where it is spawned, which is one of the schedules a
+//! real thread allows, so `join` has nothing left to wait for. Alongside
+//! the instructions the lowering fills the module's `DebugInfo` with a line
+//! table and the program's variables and functions, so backends can map
+//! generated code back to the `.t` source.
+//!
+//! Lowered for coverage, every statement starts a row of the line table,
+//! which begins by counting that it ran: it passes the number of its counter
+//! to `tstd`'s `tlang_coverage_hit`, and the debug info records which row
+//! each counter is at. An unrolled loop thus counts its body once per
+//! iteration.
+//!
+//! Functions of `extern` blocks become the module's `ExternFunction`s, and
+//! calls to them `CallExtern`s, whether as a statement, a `let` initializer
+//! or a print argument, and in the `unsafe` block calling them needs. So do
+//! calls to the standard library's intrinsics, such as `io::read_file`,
+//! whose C functions join the module's externs when the program calls them.
+//!
+//! Of the prelude, `format` is folded into the string it makes, and so are
+//! the arguments of `eprint` and `eprintln`, which are written to stderr by
+//! `tstd`'s `tlang_eprint`. `assert` of a constant holds or not here:
+//! one that holds lowers to nothing and one that fails to a `panic`, which
+//! calls `tlang_panic` (`tlang_abort` under the abort strategy) with the
+//! message, where the panic is and a backtrace. So do the other operations
+//! that panic at run time, such as an index out of bounds or a division by
+//! zero; with their values known here, the panic is lowered in their place
+//! and nothing after it in `main` is. An `assert` of a condition only known
+//! at run time is a `Trap` on where it does not hold, and a panic in code
+//! decided at run time a `Trap` that always fires, which the interpreter and
+//! the backends running such code carry out without `tstd`.
+//!
+//! A `defer` block is lowered where its enclosing block ends, after the
+//! block's value and, in a `main` returning `i32`, before the exit, the
+//! last deferred first. It sees the bindings as they were where it was
+//! deferred. A `break` or `continue` runs the deferred blocks of the blocks
+//! it leaves before it jumps, a `return` those of every block it is in
+//! before the program ends, and a panic ends the program without running
+//! any.
+//!
+//! A call of one of the program's functions is a `Call`, and the function,
+//! lowered once the entry point is, a TIR function whose first locals are
+//! its parameters, bound as values only known at run time. The value its
+//! body ends in is returned as a `return` of it is: stored in the
+//! function's result local before its deferred blocks run. It starts with
+//! the statics that are not `mut` as the only constants, and a panic in it
+//! is a `Trap`, as in code decided at run time. Its `#[inline]` or
+//! `#[inline(never)]` attribute tells `compiler::optimizer::inline` what to
+//! do with its calls.
+//!
+//! A `static` becomes one of the module's `Global`s, its initializer folded
+//! to a constant, which backends define as a global variable. Code reading
+//! it reads that constant; assigning to a `static mut` changes what later
+//! code reads, not the global, which keeps its initial value.
+//!
+//! Type aliases are expanded before lowering, and a newtype, a tuple
+//! struct with one field, is lowered as that field: `Meters(1.5)` is the
+//! constant `1.5`, and `let Meters(m) = d;` binds `m` to `d`'s value.
+//!
+//! A struct with named fields, and no generics, is lowered field by field:
+//! `let p = Point { x: 1, y: 2 };` binds `p.x` and `p.y` as two `let`s
+//! would, a field of another such struct in turn as its fields (`p.a.b`).
+//! `p.x` reads and assigns one of them, `let q = p;` copies each, and a
+//! parameter of the struct is a parameter for each field. A function
+//! cannot return one.
+//!
+//! A reference is lowered as what it refers to: `&x` and `*r` are the
+//! values of `x` and `r`, and a `&` parameter or return type is its
+//! referent's. `let r = &mut x;` binds `r` to the local `x` is moved into,
+//! so assigning to `*r` or `r.f` assigns to `x`, and a call of a function
+//! taking a `&mut` is lowered in place, its body in the caller with the
+//! `&mut` parameters bound so; such a function cannot call itself.
+//!
+//! The built-in collections `Vec`, `HashMap` and `HashSet` are evaluated
+//! here like the `let` constants: a binding of `Vec::new()` starts an empty
+//! collection, method calls on it with constant arguments update it or
+//! return a constant, and a `for` loop over a `Vec` or `HashSet` is unrolled
+//! over its items. Every backend thus prints what the methods return without
+//! a collection at run time; a lookup that finds nothing panics. A
+//! collection of integers that a loop or `if` decided at run time changes,
+//! or that a method call reads with a value only known then, is instead
+//! made at run time, item by item, by `tstd`'s `tlang_vec_*`,
+//! `tlang_map_*` and `tlang_set_*` functions, which hold it behind an
+//! integer handle in a local. Its method calls become `CallExtern`s of
+//! them, guarded by `Trap`s where they panic, and a `for` loop over it
+//! counts through its items at run time.
 
-use plugin_api::{CompiledModule, Instruction};
-use crate::parser::Program;
-use crate::ast::{Stmt, Expr, ExprKind, Literal};
+use miette::SourceSpan;
+use plugin_api::{
+    format_bool, format_float, BackendError, CompiledModule, Constant, DebugInfo, ExternFunction, FfiType,
+    FunctionInfo, Global, LineInfo, PanicStrategy, Profile, VariableInfo,
+};
+use crate::coverage::COUNTER_FUNCTION;
+use crate::intrinsics::intrinsic_named;
+use crate::resolve::{sub_exprs, type_name};
+use crate::types::{aliases, utils};
+use shared::ast::expr::MatchArm;
+use shared::ast::stmt::{AttributeArg, ExternItem, StructFields};
+use shared::ast::{Block, PrimitiveType};
+use shared::source::LineIndex;
+use shared::tir::{self, ArithOp, BlockId, CompareOp, InstId, InstKind, LocalId, Terminator};
+use shared::{
+    BinaryOp, Expr, ExprKind, Item, ItemKind, Literal, Pattern, PatternKind, Program, Stmt, StmtKind, Type,
+    TypeKind, UnaryOp,
+};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 
-/// Lower a full program into a CompiledModule (instructions-only here).
-pub fn lower_program(prog: &Program) -> CompiledModule {
-    let mut instrs = Vec::new();
-    // Walk through each top-level statement
-    for stmt in &prog.statements {
-        lower_stmt(stmt, &mut instrs);
+/// Entry point whose body is lowered to instructions, unless another is
+/// asked for.
+const ENTRY_POINT: &str = "main";
+
+/// Most iterations a `for` loop over a constant range is unrolled to;
+/// longer ones loop at run time.
+const MAX_UNROLLED_ITERATIONS: i128 = 4096;
+
+/// How a program is lowered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoweringOptions {
+    /// Whether `+`, `-` and `*` that overflow their integer type panic, as
+    /// in a debug build, rather than wrap around.
+    pub overflow_checks: bool,
+    /// What a panic does to the program
+    pub panic: PanicStrategy,
+    /// Whether each row of the line table counts how often it runs, for
+    /// `crate::coverage`
+    pub coverage: bool,
+}
+
+impl Default for LoweringOptions {
+    fn default() -> Self {
+        LoweringOptions { overflow_checks: true, panic: PanicStrategy::Unwind, coverage: false }
+    }
+}
+
+impl LoweringOptions {
+    /// The options of a compilation at `debug_level`: overflow checks at
+    /// any level above 0.
+    pub fn for_debug_level(debug_level: u8) -> Self {
+        LoweringOptions { overflow_checks: debug_level > 0, ..LoweringOptions::default() }
+    }
+
+    /// These options with the panic strategy of `profile`.
+    pub fn with_profile(self, profile: Profile) -> Self {
+        LoweringOptions { panic: PanicStrategy::for_profile(profile), ..self }
+    }
+}
+
+/// Lower a full program into a `CompiledModule` with debug info pointing at
+/// `file`, the path `source` was read from.
+pub fn lower_program(
+    program: &Program,
+    source: &str,
+    file: &str,
+) -> Result<CompiledModule, BackendError> {
+    lower_program_with_options(program, source, file, LoweringOptions::default())
+}
+
+/// `lower_program` with `options`.
+pub fn lower_program_with_options(
+    program: &Program,
+    source: &str,
+    file: &str,
+    options: LoweringOptions,
+) -> Result<CompiledModule, BackendError> {
+    lower_function(program, source, file, ENTRY_POINT, options)
+}
+
+/// `lower_program_with_options` with the function `entry` in place of
+/// `main`, as the body the instructions run.
+pub fn lower_function(
+    program: &Program,
+    source: &str,
+    file: &str,
+    entry: &str,
+    options: LoweringOptions,
+) -> Result<CompiledModule, BackendError> {
+    let (module, debug) = lower_module(program, source, file, entry, options)?;
+    CompiledModule::from_tir(&module, debug)
+}
+
+/// The TIR `lower_program_with_options` emits as instructions, its values
+/// of the types the program gives them rather than those they are carried
+/// as, which is what `CompiledModule::tir` lifts back.
+pub fn lower_program_tir(
+    program: &Program,
+    source: &str,
+    file: &str,
+    options: LoweringOptions,
+) -> Result<tir::Module, BackendError> {
+    lower_module(program, source, file, ENTRY_POINT, options).map(|(module, _)| module)
+}
+
+/// The TIR of `program`, with `entry` as its entry point, and its debug
+/// info.
+fn lower_module(
+    program: &Program,
+    source: &str,
+    file: &str,
+    entry: &str,
+    options: LoweringOptions,
+) -> Result<(tir::Module, DebugInfo), BackendError> {
+    // A program lowered without type checking may still name its aliases.
+    let expanded;
+    let program = if program.items.iter().any(|item| matches!(item.kind, ItemKind::TypeAlias { .. })) {
+        let mut copy = program.clone();
+        aliases::expand_aliases(&mut copy, source).map_err(|err| BackendError::InvalidIr(err.to_string()))?;
+        expanded = copy;
+        &expanded
+    } else {
+        program
+    };
+    let mut lowering = Lowering {
+        options,
+        entry,
+        source,
+        lines: LineIndex::new(source),
+        function: tir::Function::new(entry),
+        block: BlockId::ENTRY,
+        row_start: None,
+        debug: DebugInfo {
+            source_file: Some(file.to_string()),
+            ..DebugInfo::default()
+        },
+        constants: HashMap::new(),
+        variables: HashMap::new(),
+        values: HashMap::new(),
+        shadowed: Vec::new(),
+        loops: Vec::new(),
+        inlined: Vec::new(),
+        fixed_collections: 0,
+        decided_at_run_time: false,
+        collections: vec![HashMap::new()],
+        deferred: Vec::new(),
+        deferring: false,
+        exits: false,
+        externs: Vec::new(),
+        globals: Vec::new(),
+        newtypes: newtypes(program),
+        structs: structs(program),
+        variants: variants(program),
+        unreachable: false,
+        items: functions(program),
+        statics: HashMap::new(),
+        called: Vec::new(),
+        lowered: Vec::new(),
+    };
+    // Extern functions and statics may be used before their items.
+    for item in &program.items {
+        lowering.declare_externs(item).map_err(Stop::into_error)?;
+        lowering.declare_static(item).map_err(Stop::into_error)?;
+    }
+    for item in &program.items {
+        lowering.lower_item(item).map_err(Stop::into_error)?;
+    }
+    // The functions the entry point calls, then those they call.
+    let mut next = 0;
+    while let Some(name) = lowering.called.get(next).cloned() {
+        let item = lowering.items[name.as_str()];
+        lowering.lower_callee(item).map_err(Stop::into_error)?;
+        next += 1;
+    }
+
+    let Lowering { mut lowered, function, debug, externs, globals, .. } = lowering;
+    lowered.push(function);
+    Ok((tir::Module { functions: lowered, externs, globals }, debug))
+}
+
+/// The functions of `program` with a body, by name.
+fn functions(program: &Program) -> HashMap<&str, &Item> {
+    program
+        .items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            ItemKind::Function { name, body: Some(_), .. } => Some((name.as_str(), item)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// What the `#[inline]` attribute of `item` asks for: `#[inline(never)]`
+/// that calls to it are never inlined, `#[inline]` that they always are.
+fn inline_of(item: &Item) -> tir::Inline {
+    match item.attribute("inline") {
+        Some(attribute) if attribute.args.contains(&AttributeArg::Ident("never".to_string())) => tir::Inline::Never,
+        Some(_) => tir::Inline::Always,
+        None => tir::Inline::Auto,
+    }
+}
+
+/// `body`, of a function returning a value, with the value it ends in
+/// returned: `{ ...; e }` becomes `{ ...; return e }`, through the blocks,
+/// the branches of an `if` and the arms of a `match` it ends in.
+fn return_tail(body: &mut Expr) {
+    match &mut body.kind {
+        ExprKind::Block(block) => {
+            if let Some(tail) = &mut block.expr {
+                return_tail(tail);
+            }
+        }
+        ExprKind::If { then_branch, else_branch: Some(else_branch), .. } => {
+            return_tail(then_branch);
+            return_tail(else_branch);
+        }
+        ExprKind::Match { arms, .. } => arms.iter_mut().for_each(|arm| return_tail(&mut arm.body)),
+        ExprKind::Unsafe { body } => return_tail(body),
+        ExprKind::Return { .. } | ExprKind::Loop { .. } => {}
+        _ => {
+            let value = body.clone();
+            body.kind = ExprKind::Return { value: Some(Box::new(value)) };
+        }
+    }
+}
+
+/// The field type of each newtype of `program`: a tuple struct with one
+/// field, whose values are lowered as the values of that field.
+fn newtypes(program: &Program) -> HashMap<String, Type> {
+    program
+        .items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            ItemKind::Struct { name, fields: StructFields::Unnamed(fields), .. } if fields.len() == 1 => {
+                Some((name.clone(), fields[0].clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The fields of each struct of `program` with named fields and no generic
+/// parameters, in the order declared, whose values are lowered field by
+/// field.
+fn structs(program: &Program) -> HashMap<String, Vec<(String, Type)>> {
+    program
+        .items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            ItemKind::Struct { name, generics, fields: StructFields::Named(fields) } if generics.is_empty() => {
+                Some((name.clone(), fields.iter().map(|field| (field.name.clone(), field.ty.clone())).collect()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The value of each variant without fields of each enum of `program`, by
+/// the enum's name and the variant's: its place among the enum's variants,
+/// counting from 0.
+fn variants(program: &Program) -> HashMap<(String, String), i128> {
+    let mut values = HashMap::new();
+    for item in &program.items {
+        if let ItemKind::Enum { name, variants, .. } = &item.kind {
+            for (n, variant) in variants.iter().enumerate() {
+                if matches!(variant.fields, StructFields::Unit) {
+                    values.insert((name.clone(), variant.name.clone()), n as i128);
+                }
+            }
+        }
+    }
+    values
+}
+
+/// Whether a `main` returning `ty` returns the program's exit code.
+fn returns_exit_code(ty: &Type) -> bool {
+    matches!(ty.kind, TypeKind::Primitive(PrimitiveType::I32))
+}
+
+/// The C type a value of `ty` crosses an extern function boundary as, if it
+/// can: fixed-width integers, floats, `bool`, and `str` as a C string.
+pub fn ffi_type(ty: &Type) -> Option<FfiType> {
+    let prim = match &ty.kind {
+        TypeKind::Primitive(prim) => prim,
+        TypeKind::Reference { target, .. } => match &target.kind {
+            TypeKind::Primitive(PrimitiveType::Str) => return Some(FfiType::Str),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(match prim {
+        PrimitiveType::Bool => FfiType::Bool,
+        PrimitiveType::Str => FfiType::Str,
+        PrimitiveType::F32 => FfiType::Float { bits: 32 },
+        PrimitiveType::F64 => FfiType::Float { bits: 64 },
+        // `isize`/`usize` have no fixed width and `i128`/`u128` no C type.
+        PrimitiveType::ISize | PrimitiveType::USize | PrimitiveType::I128 | PrimitiveType::U128 => {
+            return None;
+        }
+        _ if utils::is_integer_type(ty) => FfiType::Int {
+            bits: utils::type_size_bits(ty)? as u8,
+            signed: utils::is_signed_type(ty),
+        },
+        _ => return None,
+    })
+}
+
+/// Type a literal initializer gives an unannotated binding.
+fn literal_type(lit: &Literal) -> String {
+    match lit {
+        Literal::TypedInteger(_, ty) | Literal::TypedFloat(_, ty) => {
+            type_name(&Type::primitive(ty.clone(), SourceSpan::new(0.into(), 0)))
+        }
+        Literal::Integer(_) => "i64".to_string(),
+        Literal::Float(_) => "f64".to_string(),
+        Literal::String(_) => "str".to_string(),
+        Literal::Char(_) => "char".to_string(),
+        Literal::Bool(_) => "bool".to_string(),
+        Literal::Unit => "()".to_string(),
+    }
+}
+
+/// Whether two constants of the same type are the same value.
+fn same_value(a: &Literal, b: &Literal) -> bool {
+    literal_value(a) == literal_value(b)
+}
+
+/// The value of a built-in collection bound by `main`.
+#[derive(Debug, Clone)]
+enum Collection {
+    Vec(Vec<Literal>),
+    /// Entries in the order their keys were first inserted
+    Map(Vec<(Literal, Literal)>),
+    /// Items in the order they were first inserted
+    Set(Vec<Literal>),
+    /// What a `checked_` method of an integer returned
+    Option(Option<Literal>),
+    /// A collection of integers changed at run time, held by a local with
+    /// its handle in `tstd`, whose functions for it start `tlang_vec_`,
+    /// `tlang_map_` or `tlang_set_` after its kind
+    Runtime(&'static str),
+}
+
+impl Collection {
+    /// The empty collection `expr` creates, if it is `Vec::new()`,
+    /// `HashMap::new()` or `HashSet::new()`.
+    fn created_by(expr: &Expr) -> Option<Self> {
+        let ExprKind::Call { callee, args, .. } = &expr.kind else {
+            return None;
+        };
+        let ExprKind::Variable { path } = &callee.kind else {
+            return None;
+        };
+        match (path.as_slice(), args.is_empty()) {
+            ([ty, new], true) if new == "new" => match ty.as_str() {
+                "Vec" => Some(Collection::Vec(Vec::new())),
+                "HashMap" => Some(Collection::Map(Vec::new())),
+                "HashSet" => Some(Collection::Set(Vec::new())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Call `method` with `args`, returning its result, or why there is none.
+    fn call(&mut self, method: &str, args: &[Literal]) -> Result<Literal, Failure> {
+        let len = match self {
+            Collection::Vec(items) | Collection::Set(items) => items.len(),
+            Collection::Map(entries) => entries.len(),
+            Collection::Option(value) => usize::from(value.is_some()),
+            Collection::Runtime(_) => return Err(Failure::Unsupported(format!("method `{}` here", method))),
+        };
+        let index = |lit: &Literal| match lit {
+            Literal::Integer(n) | Literal::TypedInteger(n, _) => usize::try_from(*n).ok(),
+            _ => None,
+        };
+        let position = |items: &[Literal], item: &Literal| items.iter().position(|x| same_value(x, item));
+        let result = match (self, method, args) {
+            (_, "len", []) => Literal::Integer(len as i128),
+            (_, "is_empty", []) => Literal::Bool(len == 0),
+            (Collection::Vec(items), "push", [item]) => {
+                items.push(item.clone());
+                Literal::Unit
+            }
+            (Collection::Vec(items), "pop", []) => items.pop().ok_or(Failure::panic("`pop` from an empty Vec"))?,
+            (Collection::Vec(items), "get", [i]) => {
+                let len = items.len();
+                index(i).and_then(|i| items.get(i)).cloned().ok_or_else(|| out_of_bounds(len, i))?
+            }
+            (Collection::Vec(items), "set", [i, item]) => {
+                let len = items.len();
+                let slot = index(i).and_then(|i| items.get_mut(i)).ok_or_else(|| out_of_bounds(len, i))?;
+                *slot = item.clone();
+                Literal::Unit
+            }
+            (Collection::Vec(items) | Collection::Set(items), "contains", [item]) => {
+                Literal::Bool(position(items, item).is_some())
+            }
+            (Collection::Set(items), "insert", [item]) => {
+                if position(items, item).is_none() {
+                    items.push(item.clone());
+                }
+                Literal::Unit
+            }
+            (Collection::Set(items), "remove", [item]) => {
+                if let Some(i) = position(items, item) {
+                    items.remove(i);
+                }
+                Literal::Unit
+            }
+            (Collection::Map(entries), "insert", [key, value]) => {
+                match entries.iter_mut().find(|(k, _)| same_value(k, key)) {
+                    Some(entry) => entry.1 = value.clone(),
+                    None => entries.push((key.clone(), value.clone())),
+                }
+                Literal::Unit
+            }
+            (Collection::Map(entries), "get", [key]) => entries
+                .iter()
+                .find(|(k, _)| same_value(k, key))
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| Failure::Panic(format!("key {} not found in the HashMap", literal_value(key))))?,
+            (Collection::Map(entries), "remove", [key]) => {
+                entries.retain(|(k, _)| !same_value(k, key));
+                Literal::Unit
+            }
+            (Collection::Map(entries), "contains_key", [key]) => {
+                Literal::Bool(entries.iter().any(|(k, _)| same_value(k, key)))
+            }
+            (Collection::Option(value), "is_some", []) => Literal::Bool(value.is_some()),
+            (Collection::Option(value), "is_none", []) => Literal::Bool(value.is_none()),
+            (Collection::Option(value), "unwrap", []) => {
+                value.clone().ok_or(Failure::panic("called `Option::unwrap()` on a `None` value"))?
+            }
+            (Collection::Option(value), "unwrap_or", [default]) => value.clone().unwrap_or_else(|| default.clone()),
+            (Collection::Option(value), "expect", [message]) => {
+                value.clone().ok_or_else(|| Failure::Panic(literal_value(message)))?
+            }
+            _ => return Err(Failure::Unsupported(format!("method `{}`", method))),
+        };
+        Ok(result)
+    }
+}
+
+/// Why a method call on a constant collection has no result.
+#[derive(Debug)]
+enum Failure {
+    /// The lowering cannot make the call, as described
+    Unsupported(String),
+    /// The call panics with this message
+    Panic(String),
+}
+
+impl Failure {
+    fn panic(message: &str) -> Self {
+        Failure::Panic(message.to_string())
+    }
+}
+
+fn out_of_bounds(len: usize, index: &Literal) -> Failure {
+    Failure::Panic(format!("index out of bounds: the len is {} but the index is {}", len, literal_value(index)))
+}
+
+/// What integer arithmetic does with a result its type cannot hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overflow {
+    /// Give no result
+    Checked,
+    /// Wrap around, keeping the low bits
+    Wrapping,
+    /// Stop at the type's smallest or largest value
+    Saturating,
+}
+
+/// The arithmetic an integer method does, like `checked_add`, and how it
+/// overflows.
+fn integer_method(method: &str) -> Option<(&str, Overflow)> {
+    let (overflow, op) = method.split_once('_')?;
+    let overflow = match overflow {
+        "checked" => Overflow::Checked,
+        "wrapping" => Overflow::Wrapping,
+        "saturating" => Overflow::Saturating,
+        _ => return None,
+    };
+    matches!(op, "add" | "sub" | "mul").then_some((op, overflow))
+}
+
+/// `a op b`, where `op` is `add`, `sub`, `mul`, `div` or `rem`, in the
+/// integer type `ty`; `None` if it overflows and `overflow` is `Checked`.
+/// Values are `i128`s, so `u128` stops at `i128::MAX`. `b` is not 0 for
+/// `div` and `rem`.
+fn integer_arithmetic(op: &str, a: i128, b: i128, ty: &PrimitiveType, overflow: Overflow) -> Option<i128> {
+    let ty = Type::primitive(ty.clone(), SourceSpan::new(0.into(), 0));
+    let bits = utils::type_size_bits(&ty).unwrap_or(32).min(128);
+    let (min, max) = match (bits, utils::is_signed_type(&ty)) {
+        (128, true) => (i128::MIN, i128::MAX),
+        (128, false) => (0, i128::MAX),
+        (bits, true) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+        (bits, false) => (0, (1 << bits) - 1),
+    };
+    let exact = match op {
+        "add" => a.checked_add(b),
+        "sub" => a.checked_sub(b),
+        "div" => a.checked_div(b),
+        "rem" => a.checked_rem(b),
+        _ => a.checked_mul(b),
+    };
+    match (exact, overflow) {
+        (Some(n), _) if (min..=max).contains(&n) => Some(n),
+        (_, Overflow::Checked) => None,
+        (Some(n), Overflow::Saturating) => Some(n.clamp(min, max)),
+        (None, Overflow::Saturating) => {
+            // Past what `i128` holds, which way decides the bound.
+            let up = match op {
+                "add" => b > 0,
+                "sub" => b < 0,
+                _ => (a < 0) == (b < 0),
+            };
+            Some(if up { max } else { min })
+        }
+        (_, Overflow::Wrapping) => {
+            let wrapped = match op {
+                "add" => a.wrapping_add(b),
+                "sub" => a.wrapping_sub(b),
+                "div" => a.wrapping_div(b),
+                "rem" => a.wrapping_rem(b),
+                _ => a.wrapping_mul(b),
+            };
+            if bits == 128 {
+                return Some(wrapped);
+            }
+            Some(wrapped.wrapping_sub(min).rem_euclid(1 << bits) + min)
+        }
     }
-    // bytecode Vec<u8> can be filled by a serializer later; empty for now
-    CompiledModule::new(Vec::new(), instrs)
 }
 
-/// Lower a single statement into instructions.
-fn lower_stmt(stmt: &Stmt, instrs: &mut Vec<Instruction>) {
-    match stmt {
-        Stmt::Expr { expr, .. } | Stmt::Semi { expr, .. } => {
-            lower_expr(expr, instrs);
+/// The comparison `op` makes, if it is one.
+fn compare_op(op: &BinaryOp) -> Option<CompareOp> {
+    Some(match op {
+        BinaryOp::Eq => CompareOp::Eq,
+        BinaryOp::Ne => CompareOp::Ne,
+        BinaryOp::Lt => CompareOp::Lt,
+        BinaryOp::Le => CompareOp::Le,
+        BinaryOp::Gt => CompareOp::Gt,
+        BinaryOp::Ge => CompareOp::Ge,
+        _ => return None,
+    })
+}
+
+/// Whether `a op b` holds of two constants; `None` if they do not compare.
+fn compare_literals(op: CompareOp, a: &Literal, b: &Literal) -> Option<bool> {
+    Some(match (a, b) {
+        (Literal::Integer(a) | Literal::TypedInteger(a, _), Literal::Integer(b) | Literal::TypedInteger(b, _)) => {
+            op.holds(a, b)
         }
-        // Other Stmt variants (Local, Item, etc.) currently no-op
-        _ => {}
+        (Literal::Float(a) | Literal::TypedFloat(a, _), Literal::Float(b) | Literal::TypedFloat(b, _)) => {
+            op.holds(a, b)
+        }
+        (Literal::Bool(a), Literal::Bool(b)) => op.holds(a, b),
+        (Literal::Char(a), Literal::Char(b)) => op.holds(a, b),
+        (Literal::String(a), Literal::String(b)) => op.holds(a, b),
+        _ => return None,
+    })
+}
+
+/// `expr` and every expression inside it.
+fn exprs_in(expr: &Expr) -> Vec<&Expr> {
+    let mut exprs = vec![expr];
+    let mut next = 0;
+    while let Some(&expr) = exprs.get(next) {
+        exprs.extend(sub_exprs(expr));
+        next += 1;
+    }
+    exprs
+}
+
+/// The names of the variables and fields `expr` assigns to or borrows as
+/// `&mut`, and of the collections it changes.
+fn assigned_names(expr: &Expr) -> HashSet<String> {
+    exprs_in(expr)
+        .into_iter()
+        .filter_map(|expr| match &expr.kind {
+            ExprKind::Assign { target, .. } | ExprKind::Reference { expr: target, mutable: true } => Some(target),
+            ExprKind::MethodCall { receiver, method, .. } if mutates(method) => Some(receiver),
+            _ => None,
+        })
+        .filter_map(|target| place_name(target))
+        .collect()
+}
+
+/// The name the place `expr` is bound under: a variable's own, or `p.x`
+/// for the field `x` of the struct in `p`, each of whose fields is bound on
+/// its own. A reference is bound to what it refers to, so `*r` is `r`.
+fn place_name(expr: &Expr) -> Option<String> {
+    match &expr.kind {
+        ExprKind::Variable { path } if path.len() == 1 => Some(path[0].clone()),
+        ExprKind::FieldAccess { object, field } => Some(format!("{}.{}", place_name(object)?, field)),
+        ExprKind::Dereference { expr } => place_name(expr),
+        _ => None,
     }
 }
 
-/// Lower an expression into instructions.
-fn lower_expr(expr: &Expr, instrs: &mut Vec<Instruction>) {
+/// The expression `expr` is the value of, if it is an `unsafe` block of
+/// that expression alone: `unsafe { abs(x) }`.
+fn unsafe_value(expr: &Expr) -> Option<&Expr> {
     match &expr.kind {
-        ExprKind::Literal(lit, _) => match lit {
-            Literal::Integer(i) => {
-                // safely convert i128 to i64, skipping out-of-range
-                if let Ok(v) = i64::try_from(*i) {
-                    instrs.push(Instruction::PushInt(v));
+        ExprKind::Unsafe { body } => match &body.kind {
+            ExprKind::Block(Block { statements, expr: Some(value), .. }) if statements.is_empty() => Some(value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `expr` has a `break` or `continue` in it.
+fn breaks(expr: &Expr) -> bool {
+    exprs_in(expr).iter().any(|expr| matches!(expr.kind, ExprKind::Break { .. } | ExprKind::Continue { .. }))
+}
+
+/// Whether `method` of a collection changes it.
+fn mutates(method: &str) -> bool {
+    matches!(method, "push" | "pop" | "set" | "insert" | "remove")
+}
+
+/// The function `callee` names, if it is a plain name such as `print`.
+fn callee_name(callee: &Expr) -> Option<&str> {
+    match &callee.kind {
+        ExprKind::Variable { path } if path.len() == 1 => Some(&path[0]),
+        _ => None,
+    }
+}
+
+/// A literal as the generated program prints it.
+fn literal_value(lit: &Literal) -> String {
+    match lit {
+        Literal::Integer(n) | Literal::TypedInteger(n, _) => n.to_string(),
+        Literal::Float(f) | Literal::TypedFloat(f, _) => format_float(*f),
+        Literal::String(s) => s.clone(),
+        Literal::Char(c) => c.to_string(),
+        Literal::Bool(b) => format_bool(*b).to_string(),
+        Literal::Unit => "()".to_string(),
+    }
+}
+
+/// What a name is bound to.
+#[derive(Debug, Clone)]
+enum Binding {
+    /// Nothing the lowering can read, or nothing at all
+    Unbound,
+    /// A value known here
+    Constant(Literal),
+    /// A local holding a value only known at run time
+    Variable(LocalId, FfiType),
+    /// A value of the struct of this name, its fields bound as `name.field`
+    Struct(String),
+}
+
+/// A deferred block, with the bindings it sees.
+#[derive(Debug, Clone)]
+struct Deferred {
+    body: Expr,
+    constants: HashMap<String, Literal>,
+    variables: HashMap<String, (LocalId, FfiType)>,
+    values: HashMap<String, String>,
+}
+
+/// A call lowered in place, in its caller, for the `return`s in the body of
+/// the function called.
+#[derive(Debug)]
+struct Inlined {
+    /// The function called
+    function: String,
+    /// Where a `return` goes, once the deferred blocks of the function ran
+    exit: BlockId,
+    /// The local a `return` stores the value it returns in
+    result: Option<LocalId>,
+    /// Whether a `return` was lowered, so code after the call can run
+    returned: bool,
+}
+
+/// A loop being lowered, for the `break`s and `continue`s in it.
+#[derive(Debug)]
+struct Loop {
+    /// Where `continue` goes
+    next: BlockId,
+    /// Where `break` goes, once the loop is left
+    exit: BlockId,
+    /// How many lists of `Lowering::deferred` are outside the loop
+    deferred: usize,
+    /// Whether a `break` was lowered, so code after the loop can run
+    broken: bool,
+}
+
+struct Lowering<'a> {
+    options: LoweringOptions,
+    /// The function whose body is lowered
+    entry: &'a str,
+    source: &'a str,
+    /// Where the lines of `source` start
+    lines: LineIndex<'a>,
+    /// The entry point as lowered so far
+    function: tir::Function,
+    /// The block of `function` instructions are added to
+    block: BlockId,
+    /// How many instructions `function` had when the last line table row
+    /// started, each row being a `Marker` numbered after it, and the block
+    /// it started in.
+    row_start: Option<(usize, BlockId)>,
+    debug: DebugInfo,
+    /// Literal values of the statics and the `let` bindings seen so far.
+    constants: HashMap<String, Literal>,
+    /// Locals of the bindings whose values are only known at run time, such
+    /// as those a loop assigns to; no name is here and in `constants`.
+    variables: HashMap<String, (LocalId, FfiType)>,
+    /// The struct of each binding of a struct value, whose fields are bound
+    /// in `constants` and `variables`.
+    values: HashMap<String, String>,
+    /// What the names bound in each scope being lowered were bound to
+    /// before, one map per scope from the outermost in, restored when it ends.
+    shadowed: Vec<HashMap<String, Binding>>,
+    /// The loops being lowered, from the outermost in.
+    loops: Vec<Loop>,
+    /// The calls of functions taking a `&mut` being lowered in place, from
+    /// the outermost in.
+    inlined: Vec<Inlined>,
+    /// How many scopes of `collections` are outside the innermost loop or
+    /// `if` lowered to run at run time, which cannot change their
+    /// collections since they are evaluated here.
+    fixed_collections: usize,
+    /// Whether the code being lowered runs as often as a condition only
+    /// known at run time says.
+    decided_at_run_time: bool,
+    /// Current values of the `let` bindings of collections seen so far,
+    /// one map per scope from the outermost in; `None` where a binding of
+    /// something else shadows one.
+    collections: Vec<HashMap<String, Option<Collection>>>,
+    /// Deferred blocks of the blocks being lowered, one list per block from
+    /// the outermost in.
+    deferred: Vec<Vec<Deferred>>,
+    /// Whether a deferred block is being lowered.
+    deferring: bool,
+    /// Whether the entry point returns the program's exit code.
+    exits: bool,
+    /// Functions of the program's `extern` blocks.
+    externs: Vec<ExternFunction>,
+    /// The program's statics.
+    globals: Vec<Global>,
+    /// The field type of each newtype.
+    newtypes: HashMap<String, Type>,
+    /// The fields of each struct lowered field by field.
+    structs: HashMap<String, Vec<(String, Type)>>,
+    /// The value of each variant without fields, by enum and variant.
+    variants: HashMap<(String, String), i128>,
+    /// Whether the code being lowered never runs: it follows a panic, a
+    /// `break`, a `continue` or a `return`.
+    unreachable: bool,
+    /// The program's functions with a body, by name.
+    items: HashMap<&'a str, &'a Item>,
+    /// Values of the statics that are not `mut`, which every function reads.
+    statics: HashMap<String, Literal>,
+    /// The functions the code lowered so far calls, in the order first
+    /// called, each lowered once the entry point is.
+    called: Vec<String>,
+    /// The functions lowered before `function`, the entry point first.
+    lowered: Vec<tir::Function>,
+}
+
+/// Why an expression was not lowered.
+#[derive(Debug)]
+enum Stop {
+    /// The lowering cannot express it.
+    Error(BackendError),
+    /// It panics with `message` every time it runs.
+    Panic { message: String, span: SourceSpan },
+}
+
+impl Stop {
+    fn into_error(self) -> BackendError {
+        match self {
+            Stop::Error(err) => err,
+            // Statements catch the panics of their expressions.
+            Stop::Panic { message, .. } => BackendError::InvalidIr(format!("uncaught panic: {}", message)),
+        }
+    }
+}
+
+impl<'a> Lowering<'a> {
+    /// 1‑based line and column of the start of `span`.
+    fn position(&self, span: SourceSpan) -> (u32, u32) {
+        let (line, column) = self.lines.offset_to_line_col(span.offset());
+        (line as u32, column as u32)
+    }
+
+    /// Add an instruction of `kind` to the block being lowered into.
+    fn push(&mut self, kind: InstKind, ty: Option<FfiType>) -> InstId {
+        self.function.push(self.block, kind, ty)
+    }
+
+    /// Add the constant `value`, returning it and its type.
+    fn constant(&mut self, value: Constant) -> (InstId, FfiType) {
+        let ty = value.ty();
+        (self.push(InstKind::Const(value), Some(ty)), ty)
+    }
+
+    /// Start a line table row for the instructions lowered from here on.
+    /// Until `from_tir` places it, a row's `instruction` is its number.
+    fn mark(&mut self, span: SourceSpan) {
+        let (line, column) = self.position(span);
+        let emitted = self.function.insts.len();
+        match self.debug.line_info.last_mut() {
+            // Nothing was lowered for the previous row; reuse it.
+            Some(row) if self.row_start == Some((emitted, self.block)) => {
+                row.line = line;
+                row.column = column;
+            }
+            // A counted row counts one statement, even among others on its line.
+            Some(row) if row.line == line && !self.options.coverage && self.row_start.is_some() => {}
+            _ => {
+                let n = self.debug.line_info.len();
+                let row = LineInfo { instruction: n, line, column };
+                self.debug.line_info.push(row.clone());
+                self.push(InstKind::Marker(n as u32), None);
+                self.row_start = Some((self.function.insts.len(), self.block));
+                if self.options.coverage {
+                    self.count(row);
+                }
+            }
+        }
+    }
+
+    /// Count the runs of the line table row `row`, which starts here, with
+    /// the next coverage counter.
+    fn count(&mut self, row: LineInfo) {
+        let counter = self.debug.counters.len();
+        let args = vec![FfiType::Int { bits: 32, signed: true }];
+        self.declare_extern(&ExternFunction {
+            name: COUNTER_FUNCTION.to_string(),
+            params: args.clone(),
+            return_type: None,
+            variadic: false,
+        });
+        let (counter, _) = self.constant(Constant::Int(counter as i64));
+        self.push(InstKind::CallExtern { name: COUNTER_FUNCTION.to_string(), args: vec![counter], types: args }, None);
+        self.debug.counters.push(row);
+    }
+
+    fn error(&self, span: SourceSpan, what: &str) -> Stop {
+        let (line, column) = self.position(span);
+        Stop::Error(BackendError::InvalidIr(format!("{}:{}: cannot lower {}", line, column, what)))
+    }
+
+    /// What `failure` of a method call at `span` stops with.
+    fn failed(&self, span: SourceSpan, failure: Failure) -> Stop {
+        match failure {
+            Failure::Unsupported(what) => self.error(span, &what),
+            Failure::Panic(message) => Stop::Panic { message, span },
+        }
+    }
+
+    /// Record the functions of an `extern` block.
+    fn declare_externs(&mut self, item: &Item) -> Result<(), Stop> {
+        let ItemKind::Extern { items, .. } = &item.kind else {
+            return Ok(());
+        };
+        for extern_item in items {
+            let ExternItem::Function { name, params, return_type, variadic } = extern_item else {
+                continue;
+            };
+            let ffi = |ty: &Type| {
+                ffi_type(ty).ok_or_else(|| self.error(item.span, "extern function signature"))
+            };
+            let params = params.iter().map(|param| ffi(&param.ty)).collect::<Result<_, _>>()?;
+            let return_type = match return_type {
+                Some(Type { kind: TypeKind::Primitive(PrimitiveType::Unit), .. }) | None => None,
+                Some(ty) => Some(ffi(ty)?),
+            };
+            self.externs.push(ExternFunction {
+                name: name.clone(),
+                params,
+                return_type,
+                variadic: *variadic,
+            });
+        }
+        Ok(())
+    }
+
+    /// Record a `static` as a global, with its initializer folded to the
+    /// constant it starts with.
+    fn declare_static(&mut self, item: &Item) -> Result<(), Stop> {
+        let ItemKind::Static { name, ty, value, mutable } = &item.kind else {
+            return Ok(());
+        };
+        let ty = self.representation(ty);
+        let ffi = ffi_type(ty).ok_or_else(|| self.error(item.span, "static of this type"))?;
+        let literal = match (self.literal(value)?, &ty.kind) {
+            // `static X: u8 = 200;` keeps its type for the arithmetic on `X`.
+            (Literal::Integer(n), TypeKind::Primitive(prim)) if utils::is_integer_type(ty) => {
+                Literal::TypedInteger(n, prim.clone())
+            }
+            (literal, _) => literal,
+        };
+        let constant = match &literal {
+            Literal::Integer(n) | Literal::TypedInteger(n, _) => i64::try_from(*n).ok().map(Constant::Int),
+            Literal::Float(f) | Literal::TypedFloat(f, _) => Some(Constant::Float(*f)),
+            Literal::Bool(b) => Some(Constant::Bool(*b)),
+            Literal::String(s) => Some(Constant::Str(s.clone())),
+            _ => None,
+        };
+        let value = constant.ok_or_else(|| self.error(value.span, "static initializer"))?;
+        self.globals.push(Global { name: name.clone(), ty: ffi, value, mutable: *mutable });
+        if !mutable {
+            self.statics.insert(name.clone(), literal.clone());
+        }
+        self.constants.insert(name.clone(), literal);
+        Ok(())
+    }
+
+    /// The type values of `ty` are lowered as: the field type of a newtype,
+    /// the type a reference refers to, else `ty` itself.
+    fn representation<'t>(&'t self, mut ty: &'t Type) -> &'t Type {
+        loop {
+            ty = match &ty.kind {
+                TypeKind::Named { path, generics }
+                    if let ([name], []) = (path.as_slice(), generics.as_slice())
+                        && let Some(field) = self.newtypes.get(name) =>
+                {
+                    field
+                }
+                TypeKind::Reference { target, .. } => target,
+                _ => return ty,
+            };
+        }
+    }
+
+    /// Whether `callee` names a newtype, so calling it makes one.
+    fn is_newtype(&self, callee: &Expr) -> bool {
+        callee_name(callee).is_some_and(|name| self.newtypes.contains_key(name))
+    }
+
+    /// The pattern `pattern` binds the value to: the field pattern of a
+    /// newtype's, since a newtype is its field.
+    fn newtype_field<'p>(&self, mut pattern: &'p Pattern) -> &'p Pattern {
+        while let PatternKind::Enum { path, variant, fields } = &pattern.kind
+            && let ([], [field]) = (path.as_slice(), fields.as_slice())
+            && self.newtypes.contains_key(variant)
+        {
+            pattern = field;
+        }
+        pattern
+    }
+
+    /// The extern function `callee` names, if it names one: a function of
+    /// an `extern` block, or the C function of an `io` or `env` intrinsic.
+    fn extern_callee(&self, callee: &Expr) -> Option<ExternFunction> {
+        match &callee.kind {
+            ExprKind::Variable { path } if path.len() == 1 => {
+                self.externs.iter().find(|function| function.name == path[0]).cloned()
+            }
+            ExprKind::Variable { path } => intrinsic_named(path).map(|f| f.extern_function()),
+            _ => None,
+        }
+    }
+
+    /// Lower `args` and call `function` with them, returning the call.
+    /// Arguments past the fixed parameters pass with C's default promotions.
+    fn lower_extern_call(
+        &mut self,
+        function: &ExternFunction,
+        args: &[Expr],
+        span: SourceSpan,
+    ) -> Result<InstId, Stop> {
+        if args.len() < function.params.len() || (!function.variadic && args.len() > function.params.len()) {
+            return Err(self.error(span, "extern call with this many arguments"));
+        }
+        let mut values = Vec::with_capacity(args.len());
+        let mut types = Vec::with_capacity(args.len());
+        for (i, arg) in args.iter().enumerate() {
+            let ty = match function.params.get(i) {
+                Some(&ty) => ty,
+                None => self.arg_type(arg)?.promoted(),
+            };
+            let (value, lowered) = self.lower_value(arg)?;
+            if lowered.carried() != ty.carried() {
+                return Err(self.error(arg.span, "extern call argument of this type"));
+            }
+            values.push(value);
+            types.push(ty);
+        }
+        self.declare_extern(function);
+        let kind = InstKind::CallExtern { name: function.name.clone(), args: values, types };
+        Ok(self.push(kind, function.return_type))
+    }
+
+    /// The program's function `callee` names, if it names one a binding
+    /// does not shadow.
+    fn user_callee(&self, callee: &Expr) -> Option<&'a Item> {
+        let name = callee_name(callee).filter(|name| !self.variables.contains_key(*name))?;
+        self.items.get(name).copied()
+    }
+
+    /// The types of the parameters of the program's function `item`, a
+    /// struct's fields each a parameter, and of the value it returns if it
+    /// returns one.
+    fn signature(&self, item: &Item) -> Result<(Vec<FfiType>, Option<FfiType>), Stop> {
+        let ItemKind::Function { generics, params, return_type, async_, .. } = &item.kind else {
+            return Err(self.error(item.span, "call of something other than a function"));
+        };
+        if !generics.is_empty() || *async_ {
+            return Err(self.error(item.span, "call of a generic or `async` function"));
+        }
+        let ffi = |ty: &Type| {
+            ffi_type(self.representation(ty)).ok_or_else(|| self.error(item.span, "function signature"))
+        };
+        let mut types = Vec::with_capacity(params.len());
+        for param in params {
+            match self.struct_name(&param.ty) {
+                Some(layout) => {
+                    for (_, ty) in self.leaves(&layout) {
+                        types.push(ffi(&ty)?);
+                    }
+                }
+                None => types.push(ffi(&param.ty)?),
+            }
+        }
+        let result = match return_type {
+            Some(Type { kind: TypeKind::Primitive(PrimitiveType::Unit), .. }) | None => None,
+            Some(ty) => Some(ffi(ty)?),
+        };
+        Ok((types, result))
+    }
+
+    /// Lower `args` and call the program's function `item` with them,
+    /// returning the value it returns and its type, if it returns one.
+    /// The function is lowered once the entry point is, unless it takes a
+    /// `&mut`; see `inline_call`.
+    fn lower_call(
+        &mut self,
+        item: &'a Item,
+        args: &[Expr],
+        span: SourceSpan,
+    ) -> Result<Option<(InstId, FfiType)>, Stop> {
+        let ItemKind::Function { name, params, .. } = &item.kind else {
+            return Err(self.error(span, "call of something other than a function"));
+        };
+        if name == self.entry {
+            return Err(self.error(span, "call of the entry point"));
+        }
+        let (types, result) = self.signature(item)?;
+        if args.len() != params.len() {
+            return Err(self.error(span, "call with this many arguments"));
+        }
+        if params.iter().any(|param| matches!(param.ty.kind, TypeKind::Reference { mutable: true, .. })) {
+            return self.inline_call(item, args, span);
+        }
+        let mut values = Vec::with_capacity(types.len());
+        for (arg, param) in args.iter().zip(params) {
+            match self.struct_name(&param.ty) {
+                Some(layout) => {
+                    for (_, binding) in self.struct_value(arg, &layout)? {
+                        values.push((self.binding_value(binding, arg.span)?, arg.span));
+                    }
+                }
+                None => values.push((self.lower_value(arg)?, arg.span)),
+            }
+        }
+        let mut args = Vec::with_capacity(values.len());
+        for (((value, lowered), span), ty) in values.into_iter().zip(types) {
+            if lowered.carried() != ty.carried() {
+                return Err(self.error(span, "argument of this type"));
+            }
+            args.push(value);
+        }
+        if !self.called.contains(name) {
+            self.called.push(name.clone());
+        }
+        let call = self.push(InstKind::Call { function: name.clone(), args }, result);
+        Ok(result.map(|ty| (call, ty)))
+    }
+
+    /// Lower the body of the program's function `item`, which takes a
+    /// `&mut`, in place of a call of it with `args`, returning the value it
+    /// returns and its type, if it returns one. A `&mut` parameter is bound
+    /// to the place its argument borrows, so what the body assigns through
+    /// it the caller reads after, and the others to their arguments' values.
+    /// The body starts with only the statics bound, as in `lower_callee`.
+    fn inline_call(
+        &mut self,
+        item: &'a Item,
+        args: &[Expr],
+        span: SourceSpan,
+    ) -> Result<Option<(InstId, FfiType)>, Stop> {
+        let ItemKind::Function { name, params, body: Some(body), .. } = &item.kind else {
+            return Err(self.error(span, "call of a function without a body"));
+        };
+        if self.inlined.iter().any(|inlined| inlined.function == *name) {
+            return Err(self.error(span, "recursive call of a function taking a `&mut`"));
+        }
+        let (_, result) = self.signature(item)?;
+        let mut bindings = Vec::new();
+        for (arg, param) in args.iter().zip(params) {
+            let PatternKind::Ident(param_name) = &self.newtype_field(&param.pattern).kind else {
+                return Err(self.error(param.span, "parameter pattern"));
+            };
+            if let Some(place) = self.borrowed_mutably(arg) {
+                bindings.extend(self.alias(param_name, place)?);
+            } else if let Some(layout) = self.struct_name(&param.ty) {
+                bindings.push((param_name.clone(), Binding::Struct(layout.clone())));
+                for (leaf, binding) in self.struct_value(arg, &layout)? {
+                    bindings.push((format!("{}.{}", param_name, leaf), binding));
+                }
+            } else {
+                bindings.push((param_name.clone(), self.value_binding(arg, &param.ty)?));
+            }
+        }
+        let exit = self.function.add_block();
+        let result = result.map(|ty| (self.function.add_local(ty), ty));
+
+        let constants = std::mem::replace(&mut self.constants, self.statics.clone());
+        let variables = std::mem::take(&mut self.variables);
+        let values = std::mem::take(&mut self.values);
+        let shadowed = std::mem::take(&mut self.shadowed);
+        let loops = std::mem::take(&mut self.loops);
+        let collections = std::mem::replace(&mut self.collections, vec![HashMap::new()]);
+        let fixed_collections = std::mem::replace(&mut self.fixed_collections, 0);
+        let deferred = std::mem::take(&mut self.deferred);
+        let deferring = std::mem::replace(&mut self.deferring, false);
+        let exits = std::mem::replace(&mut self.exits, false);
+        for (name, binding) in bindings {
+            self.rebind(&name, binding);
+        }
+        let function = name.clone();
+        self.inlined.push(Inlined { function, exit, result: result.map(|(local, _)| local), returned: false });
+
+        let mut body = body.clone();
+        if result.is_some() {
+            return_tail(&mut body);
+        }
+        let lowered = self.lower_expr(&body);
+        let mut lowered = self.or_panic(lowered);
+        if lowered.is_ok() && !self.unreachable {
+            if result.is_some() {
+                lowered = Err(self.error(body.span, "a function that can end without returning a value"));
+            } else if let Some(inlined) = self.inlined.last_mut() {
+                inlined.returned = true;
+                self.function.terminate(self.block, Terminator::Jump(exit));
+            }
+        }
+        let returned = self.inlined.pop().is_some_and(|inlined| inlined.returned);
+
+        self.constants = constants;
+        self.variables = variables;
+        self.values = values;
+        self.shadowed = shadowed;
+        self.loops = loops;
+        self.collections = collections;
+        self.fixed_collections = fixed_collections;
+        self.deferred = deferred;
+        self.deferring = deferring;
+        self.exits = exits;
+        lowered?;
+        self.block = exit;
+        self.unreachable = !returned;
+        Ok(result.map(|(local, ty)| (self.push(InstKind::Load(local), Some(ty)), ty)))
+    }
+
+    /// Lower the body of the program's function `item` as a function of
+    /// the module, its parameters the first locals, where a `Call` passes
+    /// its arguments. It starts with only the statics bound, and runs as
+    /// often as it is called.
+    fn lower_callee(&mut self, item: &'a Item) -> Result<(), Stop> {
+        let ItemKind::Function { name, params, body: Some(body), .. } = &item.kind else {
+            return Ok(());
+        };
+        let (types, result) = self.signature(item)?;
+        let mut function = tir::Function::new(name.clone());
+        let mut variables = HashMap::new();
+        let mut values = HashMap::new();
+        let mut types = types.into_iter();
+        for param in params {
+            let PatternKind::Ident(param_name) = &self.newtype_field(&param.pattern).kind else {
+                return Err(self.error(param.span, "parameter pattern"));
+            };
+            let names = match self.struct_name(&param.ty) {
+                Some(layout) => {
+                    let leaves = self.leaves(&layout);
+                    values.insert(param_name.clone(), layout);
+                    leaves.into_iter().map(|(leaf, _)| format!("{}.{}", param_name, leaf)).collect()
+                }
+                None => vec![param_name.clone()],
+            };
+            for name in names {
+                let ty = types.next().expect("a type for each parameter");
+                variables.insert(name, (function.add_local(ty), ty));
+                function.params += 1;
+            }
+        }
+        function.result = result.map(|ty| function.add_local(ty));
+        function.inline = inline_of(item);
+        let caller = std::mem::replace(&mut self.function, function);
+        self.lowered.push(caller);
+        self.block = BlockId::ENTRY;
+        self.row_start = None;
+        self.constants = self.statics.clone();
+        self.variables = variables;
+        self.values = values;
+        self.shadowed.clear();
+        self.loops.clear();
+        self.fixed_collections = 0;
+        self.decided_at_run_time = true;
+        self.collections = vec![HashMap::new()];
+        self.deferred.clear();
+        self.deferring = false;
+        self.exits = false;
+        self.unreachable = false;
+
+        let mut body = body.clone();
+        if result.is_some() {
+            return_tail(&mut body);
+        }
+        let lowered = self.lower_expr(&body);
+        self.or_panic(lowered)?;
+        if !self.unreachable {
+            if result.is_some() {
+                return Err(self.error(body.span, "a function that can end without returning a value"));
+            }
+            self.function.terminate(self.block, Terminator::Return);
+        }
+        Ok(())
+    }
+
+    /// Make `function` one of the module's externs, if it is not yet.
+    fn declare_extern(&mut self, function: &ExternFunction) {
+        if !self.externs.iter().any(|f| f.name == function.name) {
+            self.externs.push(function.clone());
+        }
+    }
+
+    /// Push `texts` and pass them to the `tstd` function `name`, such as
+    /// `tlang_eprint`.
+    fn call_runtime(&mut self, name: &str, texts: Vec<String>) {
+        let params = vec![FfiType::Str; texts.len()];
+        self.declare_extern(&ExternFunction {
+            name: name.to_string(),
+            params: params.clone(),
+            return_type: None,
+            variadic: false,
+        });
+        let args = texts.into_iter().map(|text| self.constant(Constant::Str(text)).0).collect();
+        self.push(InstKind::CallExtern { name: name.to_string(), args, types: params }, None);
+    }
+
+    /// The type a variadic argument has, before promotion.
+    fn arg_type(&self, arg: &Expr) -> Result<FfiType, Stop> {
+        if let Some(ty) = arg.ty.as_ref().and_then(ffi_type) {
+            return Ok(ty);
+        }
+        let ty = match &arg.kind {
+            ExprKind::Cast { target_type, .. } => ffi_type(target_type),
+            ExprKind::Call { callee, .. } => self.extern_callee(callee).and_then(|f| f.return_type),
+            _ => match self.literal(arg)? {
+                // Unsuffixed literals default as in type checking.
+                Literal::Integer(_) => Some(FfiType::Int { bits: 32, signed: true }),
+                Literal::Float(_) => Some(FfiType::Float { bits: 64 }),
+                Literal::TypedInteger(_, prim) | Literal::TypedFloat(_, prim) => {
+                    ffi_type(&Type::primitive(prim, arg.span))
                 }
+                Literal::String(_) => Some(FfiType::Str),
+                Literal::Bool(_) => Some(FfiType::Bool),
+                Literal::Char(_) | Literal::Unit => None,
+            },
+        };
+        ty.ok_or_else(|| self.error(arg.span, "variadic argument of this type"))
+    }
+
+    fn lower_item(&mut self, item: &Item) -> Result<(), Stop> {
+        let ItemKind::Function { name, params, return_type, body, .. } = &item.kind else {
+            return Ok(());
+        };
+        let (line, column) = self.position(item.span);
+        for param in params {
+            if let PatternKind::Ident(param_name) = &param.pattern.kind {
+                let (line, column) = self.position(param.span);
+                self.debug.variables.push(VariableInfo {
+                    name: param_name.clone(),
+                    ty: type_name(&param.ty),
+                    const_value: None,
+                    line,
+                    column,
+                });
             }
-            Literal::String(s) => {
-                instrs.push(Instruction::PushStr(s.clone()));
+        }
+
+        match body {
+            Some(body) if name == self.entry && return_type.as_ref().is_some_and(returns_exit_code) => {
+                self.exits = true;
+                self.lower_exiting(body)?;
             }
+            Some(body) if name == self.entry => self.lower_expr(body)?,
             _ => {}
-        },
-        ExprKind::Call { func, args, .. } => {
-            lower_expr(func, instrs);
-            for arg in args {
-                lower_expr(arg, instrs);
+        }
+        self.debug.functions.push(FunctionInfo {
+            name: name.clone(),
+            line,
+            column,
+            // Placed by `from_tir`, with the function's instructions.
+            instructions: 0..0,
+        });
+        Ok(())
+    }
+
+    /// Lower the statements of `block` up to the first that panics, breaks,
+    /// continues or returns; what follows it never runs. Its bindings end with it.
+    fn lower_block(&mut self, block: &Block) -> Result<(), Stop> {
+        self.in_scope(|lowering| {
+            lowering.deferred.push(Vec::new());
+            lowering.lower_statements(block)?;
+            if let Some(expr) = &block.expr
+                && !lowering.unreachable
+            {
+                lowering.mark(expr.span);
+                let lowered = lowering.lower_expr(expr);
+                lowering.or_panic(lowered)?;
             }
-            instrs.push(Instruction::CallFunction);
+            lowering.lower_deferred()
+        })
+    }
+
+    /// Lower the deferred blocks of the innermost block, the last deferred
+    /// first, unless it panicked.
+    fn lower_deferred(&mut self) -> Result<(), Stop> {
+        for deferred in self.deferred.pop().unwrap_or_default().into_iter().rev() {
+            self.lower_deferred_block(deferred)?;
+        }
+        Ok(())
+    }
+
+    /// Lower `deferred` with the bindings it sees, if it can run.
+    fn lower_deferred_block(&mut self, deferred: Deferred) -> Result<(), Stop> {
+        if self.unreachable {
+            return Ok(());
+        }
+        let constants = std::mem::replace(&mut self.constants, deferred.constants);
+        let variables = std::mem::replace(&mut self.variables, deferred.variables);
+        let values = std::mem::replace(&mut self.values, deferred.values);
+        self.mark(deferred.body.span);
+        let deferring = std::mem::replace(&mut self.deferring, true);
+        let lowered = self.lower_expr(&deferred.body);
+        self.deferring = deferring;
+        self.constants = constants;
+        self.variables = variables;
+        self.values = values;
+        self.or_panic(lowered)
+    }
+
+    fn lower_statements(&mut self, block: &Block) -> Result<(), Stop> {
+        for stmt in &block.statements {
+            if self.unreachable {
+                return Ok(());
+            }
+            let lowered = self.lower_stmt(stmt);
+            self.or_panic(lowered)?;
+        }
+        Ok(())
+    }
+
+    /// Lower the body of a `main` returning `i32`: the program exits with
+    /// the value of its block, unless it panics first.
+    fn lower_exiting(&mut self, body: &Expr) -> Result<(), Stop> {
+        let ExprKind::Block(block) = &body.kind else {
+            return Err(self.error(body.span, "this body of `main`"));
+        };
+        self.deferred.push(Vec::new());
+        self.lower_statements(block)?;
+        if self.unreachable {
+            return Ok(());
         }
-        // Nested grouping, binary, etc.
-        ExprKind::Binary { left, op, right, .. } => {
-            lower_expr(left, instrs);
-            lower_expr(right, instrs);
-            // You can push other op-specific instructions here
+        let Some(expr) = &block.expr else {
+            return Err(self.error(body.span, "a `main` without a final exit code"));
+        };
+        self.mark(expr.span);
+        let lowered = self.lower_exit(expr);
+        self.or_panic(lowered)
+    }
+
+    /// Lower the exit code `expr` and exit with it once the deferred blocks
+    /// of `main` have run.
+    fn lower_exit(&mut self, expr: &Expr) -> Result<(), Stop> {
+        let (code, ty) = self.lower_value(expr)?;
+        if !matches!(ty, FfiType::Int { .. }) {
+            return Err(self.error(expr.span, "this exit code"));
         }
-        _ => {}
+        self.lower_deferred()?;
+        if !self.unreachable {
+            self.function.terminate(self.block, Terminator::Exit(code));
+        }
+        Ok(())
+    }
+
+    /// `lowered`, or the panic it stopped with lowered in its place.
+    fn or_panic(&mut self, lowered: Result<(), Stop>) -> Result<(), Stop> {
+        match lowered {
+            Err(Stop::Panic { message, span }) => {
+                self.lower_panic(&message, span);
+                Ok(())
+            }
+            lowered => lowered,
+        }
+    }
+
+    fn lower_stmt(&mut self, stmt: &Stmt) -> Result<(), Stop> {
+        self.mark(stmt.span);
+        match &stmt.kind {
+            StmtKind::Expr(expr) => self.lower_expr(expr),
+            StmtKind::Let { pattern, ty, initializer, .. } => {
+                let pattern = self.newtype_field(pattern);
+                let PatternKind::Ident(name) = &pattern.kind else {
+                    return Ok(());
+                };
+                // `let r = &mut x;` binds `r` to the local `x` is bound to, so
+                // assigning to `*r` assigns to `x`.
+                if let Some(init) = initializer
+                    && let Some(place) = self.borrowed_mutably(init)
+                {
+                    for (name, binding) in self.alias(name, place)? {
+                        self.bind(&name, binding);
+                    }
+                    let (line, column) = self.position(pattern.span);
+                    let ty = ty.as_ref().map_or_else(|| "_".to_string(), type_name);
+                    self.debug.variables.push(VariableInfo { name: name.clone(), ty, const_value: None, line, column });
+                    if let Some(scope) = self.collections.last_mut() {
+                        scope.insert(name.clone(), None);
+                    }
+                    return Ok(());
+                }
+                if let Some(init) = initializer
+                    && let Some(layout) =
+                        ty.as_ref().and_then(|ty| self.struct_name(ty)).or_else(|| self.struct_of(init))
+                {
+                    let fields = self.struct_value(init, &layout)?;
+                    self.bind_struct(name, fields, &layout, pattern.span);
+                    return Ok(());
+                }
+                let collection = match initializer {
+                    Some(init) => Collection::created_by(init).map_or_else(|| self.option_of(init), |c| Ok(Some(c)))?,
+                    None => None,
+                };
+                // A value only known at run time is kept in a local.
+                let runtime = match initializer {
+                    Some(init)
+                        if collection.is_none()
+                            && !matches!(init.kind, ExprKind::Spawn { .. })
+                            && (unsafe_value(init).is_some() || !matches!(init.kind, ExprKind::Unsafe { .. }))
+                            && self.is_runtime(init) =>
+                    {
+                        let (value, value_ty) = self.lower_value(init)?;
+                        let declared = ty.as_ref().and_then(|ty| ffi_type(self.representation(ty)));
+                        let ty = declared.filter(|ty| ty.carried() == value_ty.carried()).unwrap_or(value_ty);
+                        let local = self.function.add_local(ty);
+                        self.push(InstKind::Store { local, value }, None);
+                        Some((local, ty))
+                    }
+                    _ => None,
+                };
+                let value = match initializer {
+                    _ if collection.is_some() || runtime.is_some() => None,
+                    Some(Expr { kind: ExprKind::Literal(lit), .. }) => Some(lit.clone()),
+                    Some(init @ Expr { kind: ExprKind::Binary { .. } | ExprKind::Unary { .. } | ExprKind::Cast { .. }, .. }) => {
+                        Some(self.literal(init)?)
+                    }
+                    Some(init @ Expr { kind: ExprKind::Match { .. }, .. }) => Some(self.literal(init)?),
+                    Some(Expr { kind: ExprKind::MethodCall { receiver, method, args }, span, .. }) => {
+                        Some(self.lower_method_call(receiver, method, args, *span)?)
+                    }
+                    Some(Expr { kind: ExprKind::Spawn { body }, .. }) => {
+                        self.lower_spawn(body)?;
+                        None
+                    }
+                    Some(Expr { kind: ExprKind::Unsafe { body }, .. }) => {
+                        self.lower_expr(body)?;
+                        None
+                    }
+                    Some(init @ Expr { kind: ExprKind::Call { callee, .. }, .. }) if callee_name(callee) == Some("format") => {
+                        Some(self.literal(init)?)
+                    }
+                    // A copy of a constant, or a newtype wrapping one, is that constant.
+                    Some(
+                        init @ Expr {
+                            kind:
+                                ExprKind::Variable { .. }
+                                | ExprKind::FieldAccess { .. }
+                                | ExprKind::Reference { .. }
+                                | ExprKind::Dereference { .. },
+                            ..
+                        },
+                    ) => self.literal(init).ok(),
+                    Some(init @ Expr { kind: ExprKind::Call { callee, .. }, .. }) if self.is_newtype(callee) => {
+                        self.literal(init).ok()
+                    }
+                    // The call still happens; its result is not a constant.
+                    Some(init @ Expr { kind: ExprKind::Call { callee, args, .. }, .. }) => {
+                        if let Some(function) = self.extern_callee(callee) {
+                            self.lower_extern_call(&function, args, init.span)?;
+                        }
+                        None
+                    }
+                    _ => None,
+                };
+                // `let x: u8 = 200;` keeps its type for the arithmetic on `x`.
+                let value = match (value, ty.as_ref().map(|ty| self.representation(ty))) {
+                    (Some(Literal::Integer(n)), Some(ty @ Type { kind: TypeKind::Primitive(prim), .. }))
+                        if utils::is_integer_type(ty) =>
+                    {
+                        Some(Literal::TypedInteger(n, prim.clone()))
+                    }
+                    (value, _) => value,
+                };
+                let ty = match (ty, &value) {
+                    (Some(ty), _) => type_name(ty),
+                    (None, Some(lit)) => literal_type(lit),
+                    (None, None) => "_".to_string(),
+                };
+                let (line, column) = self.position(pattern.span);
+                self.debug.variables.push(VariableInfo {
+                    name: name.clone(),
+                    ty,
+                    const_value: value.as_ref().map(literal_value),
+                    line,
+                    column,
+                });
+                let binding = match (value, runtime) {
+                    (Some(lit), _) => Binding::Constant(lit),
+                    (None, Some((local, ty))) => Binding::Variable(local, ty),
+                    (None, None) => Binding::Unbound,
+                };
+                self.bind(name, binding);
+                if let Some(scope) = self.collections.last_mut() {
+                    scope.insert(name.clone(), collection);
+                }
+                Ok(())
+            }
+            StmtKind::Defer { body } => {
+                // The values of the variables it reads, and of the fields of
+                // the structs, are copied, as later code may assign to them.
+                let mut variables = HashMap::new();
+                for place in exprs_in(body).into_iter().filter_map(place_name) {
+                    let fields = format!("{}.", place);
+                    let mut read: Vec<(String, (LocalId, FfiType))> = self
+                        .variables
+                        .iter()
+                        .filter(|(name, _)| **name == place || name.starts_with(&fields))
+                        .map(|(name, &local)| (name.clone(), local))
+                        .collect();
+                    read.sort_by(|a, b| a.0.cmp(&b.0));
+                    for (name, (local, ty)) in read {
+                        if let Entry::Vacant(entry) = variables.entry(name) {
+                            let value = self.push(InstKind::Load(local), Some(ty));
+                            let copy = self.function.add_local(ty);
+                            self.push(InstKind::Store { local: copy, value }, None);
+                            entry.insert((copy, ty));
+                        }
+                    }
+                }
+                let values = self.values.clone();
+                let deferred = Deferred { body: body.clone(), constants: self.constants.clone(), variables, values };
+                if let Some(scope) = self.deferred.last_mut() {
+                    scope.push(deferred);
+                }
+                Ok(())
+            }
+            StmtKind::Item(_) | StmtKind::Macro { .. } => Ok(()),
+        }
+    }
+
+    fn lower_expr(&mut self, expr: &Expr) -> Result<(), Stop> {
+        match &expr.kind {
+            ExprKind::Block(block) => self.lower_block(block),
+            ExprKind::Call { callee, args, .. } => {
+                if let Some(function) = self.extern_callee(callee) {
+                    return self.lower_extern_call(&function, args, expr.span).map(drop);
+                }
+                if let Some(item) = self.user_callee(callee) {
+                    return self.lower_call(item, args, expr.span).map(drop);
+                }
+                let newline = match callee_name(callee) {
+                    Some("print") => false,
+                    Some("println") => true,
+                    Some(name @ ("eprint" | "eprintln")) => {
+                        let mut text = String::new();
+                        for arg in args {
+                            text.push_str(&literal_value(&self.literal(arg)?));
+                        }
+                        if name == "eprintln" {
+                            text.push('\n');
+                        }
+                        self.call_runtime("tlang_eprint", vec![text]);
+                        return Ok(());
+                    }
+                    Some("assert") => return self.lower_assert(args, expr.span),
+                    Some("panic") => {
+                        let message = match args.as_slice() {
+                            [message] => literal_value(&self.literal(message)?),
+                            _ => return Err(self.error(expr.span, "panic with this many arguments")),
+                        };
+                        self.lower_panic(&message, expr.span);
+                        return Ok(());
+                    }
+                    // The thread already ran when it was spawned.
+                    Some("join") => return Ok(()),
+                    _ => return Err(self.error(expr.span, "call")),
+                };
+                for arg in args {
+                    self.lower_print(arg)?;
+                }
+                if newline {
+                    let (newline, _) = self.constant(Constant::Str("\n".to_string()));
+                    self.push(InstKind::Print(newline), None);
+                }
+                Ok(())
+            }
+            ExprKind::MethodCall { .. } if self.is_runtime(expr) => self.lower_collection_call(expr).map(drop),
+            ExprKind::MethodCall { receiver, method, args } => {
+                self.lower_method_call(receiver, method, args, expr.span).map(drop)
+            }
+            ExprKind::For { pattern, iterable, body, label: None } => self.lower_for(pattern, iterable, body),
+            ExprKind::While { condition, body, label: None } => self.lower_while(Some(condition), body, expr.span),
+            ExprKind::Loop { body, label: None } => self.lower_while(None, body, expr.span),
+            ExprKind::Break { label: None, value: None } => self.lower_break(true, expr.span),
+            ExprKind::Continue { label: None } => self.lower_break(false, expr.span),
+            ExprKind::Return { value } => self.lower_return(value.as_deref(), expr.span),
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.lower_if(condition, then_branch, else_branch.as_deref(), expr.span)
+            }
+            ExprKind::Match { expr: scrutinee, arms } => self.lower_match(scrutinee, arms, expr.span),
+            ExprKind::Assign { target, op, value } => self.lower_assign(expr, target, op.as_ref(), value),
+            ExprKind::Spawn { body } => self.lower_spawn(body),
+            ExprKind::Unsafe { body } => self.lower_expr(body),
+            ExprKind::Literal(Literal::Unit) => Ok(()),
+            _ => Err(self.error(expr.span, "expression")),
+        }
+    }
+
+    /// Lower a `for` loop over a range, or over the items of a `Vec` or
+    /// `HashSet`. A loop over the items of a constant collection, or over a
+    /// constant range of at most `MAX_UNROLLED_ITERATIONS`, with no `break`
+    /// or `continue` is unrolled, lowering the body once per item with the
+    /// loop variable bound to that item; any other loop runs at run time.
+    fn lower_for(&mut self, pattern: &Pattern, iterable: &Expr, body: &Expr) -> Result<(), Stop> {
+        let pattern = self.newtype_field(pattern);
+        let name = match &pattern.kind {
+            PatternKind::Ident(name) => Some(name.as_str()),
+            _ => None,
+        };
+        let (items, ty) = match &iterable.kind {
+            ExprKind::Range { start: Some(start), end: Some(end), inclusive } => {
+                let bound = |expr: &Expr| match self.literal(expr) {
+                    Ok(Literal::Integer(n) | Literal::TypedInteger(n, _)) => Some(n),
+                    _ => None,
+                };
+                let unrolled = match (bound(start), bound(end)) {
+                    (Some(first), Some(last)) if !breaks(body) => {
+                        let last = if *inclusive { last + 1 } else { last };
+                        (last - first <= MAX_UNROLLED_ITERATIONS).then_some((first, last))
+                    }
+                    _ => None,
+                };
+                let Some((first, last)) = unrolled else {
+                    return self.lower_range_loop(name, pattern.span, start, end, *inclusive, body);
+                };
+                let items: Vec<Literal> = (first..last).map(Literal::Integer).collect();
+                (items, literal_type(&Literal::Integer(first)))
+            }
+            ExprKind::Variable { path } if path.len() == 1 => match self.collection(&path[0]) {
+                Some(Collection::Vec(items) | Collection::Set(items)) if !breaks(body) => {
+                    let ty = items.first().map_or_else(|| "_".to_string(), literal_type);
+                    (items.clone(), ty)
+                }
+                Some(Collection::Vec(_) | Collection::Set(_) | Collection::Runtime("vec" | "set")) => {
+                    let item_ty = self.item_type(iterable, 0);
+                    return self.lower_collection_loop(name, pattern.span, &path[0], item_ty, body);
+                }
+                _ => return Err(self.error(iterable.span, "loop over anything but a range, Vec or HashSet")),
+            },
+            _ => return Err(self.error(iterable.span, "loop over anything but a range, Vec or HashSet")),
+        };
+        self.declare_loop_variable(name, pattern.span, ty);
+
+        for item in items {
+            if self.unreachable {
+                break;
+            }
+            self.in_scope(|lowering| {
+                if let Some(name) = name {
+                    lowering.bind(name, Binding::Constant(item));
+                }
+                lowering.lower_expr(body)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Record the variable `name` of a `for` loop, if it binds one.
+    fn declare_loop_variable(&mut self, name: Option<&str>, span: SourceSpan, ty: String) {
+        if let Some(name) = name {
+            let (line, column) = self.position(span);
+            self.debug.variables.push(VariableInfo { name: name.to_string(), ty, const_value: None, line, column });
+        }
+    }
+
+    /// Lower a `for` loop over the range from `start` to `end` that counts
+    /// at run time: the bounds are evaluated once, then the body runs with
+    /// the loop variable bound to a local counting from `start`, as long as
+    /// it is below `end`, or not above it if the range is `inclusive`.
+    fn lower_range_loop(
+        &mut self,
+        name: Option<&str>,
+        span: SourceSpan,
+        start: &Expr,
+        end: &Expr,
+        inclusive: bool,
+        body: &Expr,
+    ) -> Result<(), Stop> {
+        self.materialize(assigned_names(body), span)?;
+        let (first, first_ty) = self.lower_value(start)?;
+        let (last, last_ty) = self.lower_value(end)?;
+        if !matches!((first_ty, last_ty), (FfiType::Int { .. }, FfiType::Int { .. })) {
+            return Err(self.error(span, "loop over a range of anything but integers"));
+        }
+        let ty = if self.is_runtime(start) || !self.is_runtime(end) { first_ty } else { last_ty };
+        self.declare_loop_variable(name, span, "_".to_string());
+        let op = if inclusive { CompareOp::Le } else { CompareOp::Lt };
+        self.lower_counting_loop(first, last, ty, op, |lowering, counter| {
+            if let Some(name) = name {
+                lowering.bind(name, Binding::Variable(counter, ty));
+            }
+            lowering.lower_expr(body)
+        })
+    }
+
+    /// Lower a `for` loop over the items of the `Vec` or `HashSet` bound to
+    /// `collection` at run time: its length is read once, then the body
+    /// runs with the loop variable bound to a local holding each item in
+    /// turn, of type `item_ty`.
+    fn lower_collection_loop(
+        &mut self,
+        name: Option<&str>,
+        span: SourceSpan,
+        collection: &str,
+        item_ty: FfiType,
+        body: &Expr,
+    ) -> Result<(), Stop> {
+        if !matches!(item_ty, FfiType::Int { .. }) {
+            return Err(self.error(span, "a collection of anything but integers at run time"));
+        }
+        self.materialize(assigned_names(body), span)?;
+        let (handle, kind) = self.runtime_collection(collection, span)?;
+        let get = if kind == "vec" { "get" } else { "item" };
+        let items = self.function.add_local(FfiType::INT);
+        self.push(InstKind::Store { local: items, value: handle }, None);
+        let len = self.call_collection(kind, "len", vec![handle], Some(FfiType::INT));
+        let (first, _) = self.constant(Constant::Int(0));
+        self.declare_loop_variable(name, span, "_".to_string());
+        self.lower_counting_loop(first, len, FfiType::INT, CompareOp::Lt, |lowering, counter| {
+            let handle = lowering.push(InstKind::Load(items), Some(FfiType::INT));
+            let index = lowering.push(InstKind::Load(counter), Some(FfiType::INT));
+            let item = lowering.call_collection(kind, get, vec![handle, index], Some(FfiType::INT));
+            let (item, ty) = lowering.narrow(item, item_ty);
+            let local = lowering.function.add_local(ty);
+            lowering.push(InstKind::Store { local, value: item }, None);
+            if let Some(name) = name {
+                lowering.bind(name, Binding::Variable(local, ty));
+            }
+            lowering.lower_expr(body)
+        })
+    }
+
+    /// Lower a loop that counts at run time in a local of type `ty`, from
+    /// `first` for as long as the count is `op` `last`, with `lower`
+    /// lowering the body given that local.
+    fn lower_counting_loop(
+        &mut self,
+        first: InstId,
+        last: InstId,
+        ty: FfiType,
+        op: CompareOp,
+        lower: impl FnOnce(&mut Self, LocalId) -> Result<(), Stop>,
+    ) -> Result<(), Stop> {
+        let counter = self.function.add_local(ty);
+        let bound = self.function.add_local(ty);
+        self.push(InstKind::Store { local: counter, value: first }, None);
+        self.push(InstKind::Store { local: bound, value: last }, None);
+
+        let header = self.function.add_block();
+        self.function.terminate(self.block, Terminator::Jump(header));
+        self.block = header;
+        let lhs = self.push(InstKind::Load(counter), Some(ty));
+        let rhs = self.push(InstKind::Load(bound), Some(ty));
+        let condition = self.push(InstKind::Compare { op, lhs, rhs }, Some(FfiType::Bool));
+        let (then, latch, exit) = (self.function.add_block(), self.function.add_block(), self.function.add_block());
+        self.function.terminate(header, Terminator::Branch { condition, then, otherwise: exit });
+        self.block = then;
+        self.lower_loop_body(latch, exit, |lowering| lower(lowering, counter))?;
+
+        self.block = latch;
+        let count = self.push(InstKind::Load(counter), Some(ty));
+        let (one, _) = self.constant(Constant::Int(1));
+        let next = self.push(InstKind::Arith { op: ArithOp::Add, lhs: count, rhs: one }, Some(ty.carried()));
+        self.push(InstKind::Store { local: counter, value: next }, None);
+        self.function.terminate(latch, Terminator::Jump(header));
+        self.block = exit;
+        self.unreachable = false;
+        Ok(())
+    }
+
+    /// Lower a `while` loop, or a `loop` if there is no `condition`, which
+    /// runs at run time. `while true` is lowered as a `loop`, and
+    /// `while false` to nothing.
+    fn lower_while(&mut self, condition: Option<&Expr>, body: &Expr, span: SourceSpan) -> Result<(), Stop> {
+        let mut assigned = assigned_names(body);
+        assigned.extend(condition.map(assigned_names).unwrap_or_default());
+        self.materialize(assigned, span)?;
+        let condition = match condition {
+            Some(condition) if !self.is_runtime(condition) => match self.literal(condition) {
+                Ok(Literal::Bool(false)) => return Ok(()),
+                Ok(Literal::Bool(true)) => None,
+                _ => Some(condition),
+            },
+            condition => condition,
+        };
+
+        let header = self.function.add_block();
+        self.function.terminate(self.block, Terminator::Jump(header));
+        self.block = header;
+        let exit = match condition {
+            Some(condition) => {
+                let (condition, _) = self.lower_value(condition)?;
+                let (then, exit) = (self.function.add_block(), self.function.add_block());
+                self.function.terminate(self.block, Terminator::Branch { condition, then, otherwise: exit });
+                self.block = then;
+                exit
+            }
+            None => self.function.add_block(),
+        };
+        let broken = self.lower_loop_body(header, exit, |lowering| lowering.lower_expr(body))?;
+        self.block = exit;
+        // A `loop` is only left by a `break`.
+        self.unreachable = condition.is_none() && !broken;
+        Ok(())
+    }
+
+    /// Lower the body of a loop that runs at run time into the block being
+    /// lowered into, then go on at `next`; a `continue` goes there too, and
+    /// a `break` to `exit`. Returns whether there is a `break`.
+    fn lower_loop_body(
+        &mut self,
+        next: BlockId,
+        exit: BlockId,
+        lower: impl FnOnce(&mut Self) -> Result<(), Stop>,
+    ) -> Result<bool, Stop> {
+        self.loops.push(Loop { next, exit, deferred: self.deferred.len(), broken: false });
+        let lowered = self.at_run_time(|lowering| lowering.in_scope(lower));
+        let broken = self.loops.pop().is_some_and(|lp| lp.broken);
+        lowered?;
+        if !self.unreachable {
+            self.function.terminate(self.block, Terminator::Jump(next));
+        }
+        Ok(broken)
+    }
+
+    /// Lower `break`, or `continue` if not `breaking`, of the innermost
+    /// loop: run the deferred blocks of the blocks it leaves, the innermost
+    /// first, then jump out of the loop or on to its next iteration.
+    fn lower_break(&mut self, breaking: bool, span: SourceSpan) -> Result<(), Stop> {
+        let Some(innermost) = self.loops.last_mut() else {
+            return Err(self.error(span, if breaking { "`break` outside of a loop" } else { "`continue` outside of a loop" }));
+        };
+        innermost.broken |= breaking;
+        let target = if breaking { innermost.exit } else { innermost.next };
+        let deferred: Vec<Deferred> = self.deferred[innermost.deferred..].iter().flatten().cloned().collect();
+        for deferred in deferred.into_iter().rev() {
+            self.lower_deferred_block(deferred)?;
+        }
+        if !self.unreachable {
+            self.function.terminate(self.block, Terminator::Jump(target));
+            self.unreachable = true;
+        }
+        Ok(())
+    }
+
+    /// Lower a `return` from a function the entry point calls, with the
+    /// value `value` it returns stored in its result local, or from the
+    /// entry point, with the exit code `value` in a `main` returning `i32`:
+    /// run the deferred blocks of every block it leaves, the innermost
+    /// first, then go back to the caller or end the program.
+    fn lower_return(&mut self, value: Option<&Expr>, span: SourceSpan) -> Result<(), Stop> {
+        if self.deferring {
+            return Err(self.error(span, "`return` in a `defer` block"));
+        }
+        let result = self.inlined.last().map_or(self.function.result, |inlined| inlined.result);
+        let code = match (value, result) {
+            (Some(value), Some(result)) => {
+                let (returned, ty) = self.lower_value(value)?;
+                if ty.carried() != self.function.locals[result.index()].carried() {
+                    return Err(self.error(value.span, "return value of this type"));
+                }
+                self.push(InstKind::Store { local: result, value: returned }, None);
+                None
+            }
+            (Some(value), None) if self.exits => {
+                let (code, ty) = self.lower_value(value)?;
+                if !matches!(ty, FfiType::Int { .. }) {
+                    return Err(self.error(value.span, "this exit code"));
+                }
+                Some(code)
+            }
+            (None, None) if !self.exits => None,
+            _ => return Err(self.error(span, "this `return`")),
+        };
+        let deferred: Vec<Deferred> = self.deferred.iter().flatten().cloned().collect();
+        for deferred in deferred.into_iter().rev() {
+            self.lower_deferred_block(deferred)?;
+        }
+        if !self.unreachable {
+            // A `return` of a call lowered in place goes on after the call.
+            let terminator = match self.inlined.last_mut() {
+                Some(inlined) => {
+                    inlined.returned = true;
+                    Terminator::Jump(inlined.exit)
+                }
+                None => code.map_or(Terminator::Return, Terminator::Exit),
+            };
+            self.function.terminate(self.block, terminator);
+            self.unreachable = true;
+        }
+        Ok(())
+    }
+
+    /// Lower an `if` used as a statement: only the branch a constant
+    /// condition picks, or else both, branching on the condition at run
+    /// time.
+    fn lower_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Expr,
+        else_branch: Option<&Expr>,
+        span: SourceSpan,
+    ) -> Result<(), Stop> {
+        if !self.is_runtime(condition)
+            && let Ok(Literal::Bool(holds)) = self.literal(condition)
+        {
+            return match (holds, else_branch) {
+                (true, _) => self.lower_expr(then_branch),
+                (false, Some(else_branch)) => self.lower_expr(else_branch),
+                (false, None) => Ok(()),
+            };
+        }
+        let mut assigned = assigned_names(then_branch);
+        assigned.extend(else_branch.map(assigned_names).unwrap_or_default());
+        self.materialize(assigned, span)?;
+        let (condition, _) = self.lower_value(condition)?;
+        let then = self.function.add_block();
+        let otherwise = else_branch.map(|_| self.function.add_block());
+        let join = self.function.add_block();
+        self.function.terminate(self.block, Terminator::Branch { condition, then, otherwise: otherwise.unwrap_or(join) });
+
+        let mut ends = true;
+        for (block, branch) in [(Some(then), Some(then_branch)), (otherwise, else_branch)] {
+            let (Some(block), Some(branch)) = (block, branch) else {
+                ends = false;
+                continue;
+            };
+            self.block = block;
+            self.unreachable = false;
+            self.at_run_time(|lowering| lowering.lower_expr(branch))?;
+            if !self.unreachable {
+                self.function.terminate(self.block, Terminator::Jump(join));
+            }
+            ends &= self.unreachable;
+        }
+        self.block = join;
+        self.unreachable = ends;
+        Ok(())
+    }
+
+    /// Lower a `match` as a statement. With the value matched known here,
+    /// only the first arm matching it is lowered. Otherwise the value, an
+    /// integer or a variant without fields, ends its block in a `Switch`:
+    /// each value an arm names before one that matches any is a case going
+    /// to that arm, unless an arm before names it, and that arm, binding
+    /// the value or `_`, is the default.
+    fn lower_match(&mut self, scrutinee: &Expr, arms: &[MatchArm], span: SourceSpan) -> Result<(), Stop> {
+        if !self.is_runtime(scrutinee) {
+            let value = self.literal(scrutinee)?;
+            let Some(arm) = self.matching_arm(&value, arms)? else {
+                return Ok(());
+            };
+            return self.in_scope(|lowering| {
+                if let PatternKind::Ident(name) = &arm.pattern.kind {
+                    lowering.bind(name, Binding::Constant(value));
+                }
+                lowering.lower_expr(&arm.body)
+            });
+        }
+        let mut assigned = HashSet::new();
+        for arm in arms {
+            assigned.extend(assigned_names(&arm.body));
+        }
+        self.materialize(assigned, span)?;
+        let (value, ty) = self.lower_value(scrutinee)?;
+        if !matches!(ty, FfiType::Int { .. }) {
+            return Err(self.error(scrutinee.span, "`match` on a value of this type only known at run time"));
+        }
+        let join = self.function.add_block();
+        let mut cases: Vec<(i64, BlockId)> = Vec::new();
+        let mut lowered = Vec::new();
+        let mut default = join;
+        for arm in arms {
+            if let Some(guard) = &arm.guard {
+                return Err(self.error(guard.span, "`match` arm guard on a value only known at run time"));
+            }
+            let Some(values) = self.case_values(&arm.pattern)? else {
+                default = self.function.add_block();
+                lowered.push((default, arm));
+                break;
+            };
+            let mut new = Vec::new();
+            for value in values {
+                let Constant::Int(n) = self.constant_of(&value, arm.pattern.span)? else {
+                    return Err(self.error(arm.pattern.span, "`match` pattern of this type"));
+                };
+                if cases.iter().all(|&(m, _)| m != n) && !new.contains(&n) {
+                    new.push(n);
+                }
+            }
+            // An arm earlier arms leave nothing to match never runs.
+            if !new.is_empty() {
+                let block = self.function.add_block();
+                cases.extend(new.into_iter().map(|n| (n, block)));
+                lowered.push((block, arm));
+            }
+        }
+        self.function.terminate(self.block, Terminator::Switch { value, cases, default });
+
+        let mut ends = default != join;
+        for (block, arm) in lowered {
+            self.block = block;
+            self.unreachable = false;
+            self.at_run_time(|lowering| {
+                lowering.in_scope(|lowering| {
+                    if let PatternKind::Ident(name) = &arm.pattern.kind {
+                        let local = lowering.function.add_local(ty);
+                        lowering.push(InstKind::Store { local, value }, None);
+                        lowering.bind(name, Binding::Variable(local, ty));
+                    }
+                    lowering.lower_expr(&arm.body)
+                })
+            })?;
+            if !self.unreachable {
+                self.function.terminate(self.block, Terminator::Jump(join));
+            }
+            ends &= self.unreachable;
+        }
+        self.block = join;
+        self.unreachable = ends;
+        Ok(())
+    }
+
+    /// The first of `arms` whose pattern matches `value`.
+    fn matching_arm<'m>(&self, value: &Literal, arms: &'m [MatchArm]) -> Result<Option<&'m MatchArm>, Stop> {
+        for arm in arms {
+            if let Some(guard) = &arm.guard {
+                return Err(self.error(guard.span, "`match` arm guard"));
+            }
+            match self.case_values(&arm.pattern)? {
+                Some(values) if !values.iter().any(|case| same_value(case, value)) => {}
+                _ => return Ok(Some(arm)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// The values `pattern` matches, a literal or a variant without fields
+    /// each, or `None` if it matches any: `_` or a name binding the value.
+    fn case_values(&self, pattern: &Pattern) -> Result<Option<Vec<Literal>>, Stop> {
+        match &pattern.kind {
+            PatternKind::Wild | PatternKind::Ident(_) => Ok(None),
+            PatternKind::Literal(literal) => Ok(Some(vec![literal.clone()])),
+            PatternKind::Enum { path, variant, fields } if fields.is_empty() && !path.is_empty() => {
+                let mut path = path.clone();
+                path.push(variant.clone());
+                match self.variant(&path) {
+                    Some(n) => Ok(Some(vec![Literal::Integer(n)])),
+                    None => Err(self.error(pattern.span, "`match` pattern naming no variant without fields")),
+                }
+            }
+            PatternKind::Or(alternatives) => {
+                let mut values = Vec::new();
+                for alternative in alternatives {
+                    match self.case_values(alternative)? {
+                        Some(more) => values.extend(more),
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(values))
+            }
+            _ => Err(self.error(pattern.span, "this `match` pattern")),
+        }
+    }
+
+    /// The value of the variant `path` names, `Enum::Variant`, if it is
+    /// one without fields.
+    fn variant(&self, path: &[String]) -> Option<i128> {
+        match path {
+            [name, variant] => self.variants.get(&(name.clone(), variant.clone())).copied(),
+            _ => None,
+        }
+    }
+
+    /// Assign `value`, or `target op value` for a compound assignment, to
+    /// the variable `target`: a constant stays one while what is assigned
+    /// is known here.
+    fn lower_assign(&mut self, expr: &Expr, target: &Expr, op: Option<&BinaryOp>, value: &Expr) -> Result<(), Stop> {
+        let Some(name) = place_name(target) else {
+            return Err(self.error(target.span, "assignment to anything but a variable or field"));
+        };
+        let name = &name;
+        if op.is_none()
+            && let Some(layout) = self.struct_of(target).or_else(|| self.struct_of(value))
+        {
+            // The new values are all read before any field changes.
+            for (field, binding) in self.struct_value(value, &layout)? {
+                self.assign_binding(&format!("{}.{}", name, field), binding, target.span)?;
+            }
+            return Ok(());
+        }
+        // `x += 1` is `x = x + 1`.
+        let combined;
+        let value = match op {
+            Some(op) => {
+                let kind = ExprKind::Binary { left: Box::new(target.clone()), op: op.clone(), right: Box::new(value.clone()) };
+                combined = Expr { kind, ty: target.ty.clone(), span: expr.span };
+                &combined
+            }
+            None => value,
+        };
+        self.forget_value(name);
+        match self.binding(name) {
+            Binding::Constant(old) if !self.is_runtime(value) => {
+                // The variable keeps its type, as `let x: u8 = 200;` does.
+                let new = match (self.literal(value)?, old) {
+                    (Literal::Integer(n), Literal::TypedInteger(_, ty)) => Literal::TypedInteger(n, ty),
+                    (Literal::Float(f), Literal::TypedFloat(_, ty)) => Literal::TypedFloat(f, ty),
+                    (new, _) => new,
+                };
+                self.rebind(name, Binding::Constant(new));
+            }
+            Binding::Constant(old) => {
+                let ty = self.local_type(&old).ok_or_else(|| self.error(target.span, "assignment of this type"))?;
+                let (value, _) = self.lower_value(value)?;
+                let local = self.function.add_local(ty);
+                self.push(InstKind::Store { local, value }, None);
+                self.rebind(name, Binding::Variable(local, ty));
+            }
+            Binding::Variable(local, _) => {
+                let (value, _) = self.lower_value(value)?;
+                self.push(InstKind::Store { local, value }, None);
+            }
+            Binding::Unbound | Binding::Struct(_) => return Err(self.error(target.span, "assignment to this variable")),
+        }
+        Ok(())
+    }
+
+    /// Lower code that runs at run time as often as a condition says. It
+    /// cannot change the collections bound outside it.
+    fn at_run_time(&mut self, lower: impl FnOnce(&mut Self) -> Result<(), Stop>) -> Result<(), Stop> {
+        let fixed = std::mem::replace(&mut self.fixed_collections, self.collections.len());
+        let decided = std::mem::replace(&mut self.decided_at_run_time, true);
+        let result = lower(self);
+        self.fixed_collections = fixed;
+        self.decided_at_run_time = decided;
+        result
+    }
+
+    /// Move each of `names` that is bound to a constant into a local, as
+    /// code assigning to it may run any number of times, and make each
+    /// constant collection of them at run time, as code changing it may.
+    fn materialize(&mut self, names: HashSet<String>, span: SourceSpan) -> Result<(), Stop> {
+        // Assigning to a struct assigns to each of its fields.
+        let mut names: Vec<String> = names
+            .into_iter()
+            .flat_map(|name| {
+                let fields = format!("{}.", name);
+                let mut within: Vec<String> =
+                    self.constants.keys().filter(|key| key.starts_with(&fields)).cloned().collect();
+                within.push(name);
+                within
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            if let Some(Collection::Vec(_) | Collection::Map(_) | Collection::Set(_)) = self.collection(&name) {
+                self.runtime_collection(&name, span)?;
+                continue;
+            }
+            let Some(literal) = self.constants.get(&name).cloned() else {
+                continue;
+            };
+            let ty = self.local_type(&literal).ok_or_else(|| self.error(span, "assignment of this type in a loop or `if`"))?;
+            let (value, _) = self.lower_constant(&literal, span)?;
+            let local = self.function.add_local(ty);
+            self.push(InstKind::Store { local, value }, None);
+            self.forget_value(&name);
+            self.rebind(&name, Binding::Variable(local, ty));
+        }
+        Ok(())
+    }
+
+    /// The type of a local holding `literal`: an unsuffixed integer is an
+    /// `i32`, as in type checking, and one of no fixed width 64 bits.
+    fn local_type(&self, literal: &Literal) -> Option<FfiType> {
+        match literal {
+            Literal::Integer(_) => Some(FfiType::Int { bits: 32, signed: true }),
+            Literal::TypedInteger(_, prim) => {
+                let ty = Type::primitive(prim.clone(), SourceSpan::new(0.into(), 0));
+                Some(ffi_type(&ty).unwrap_or(FfiType::Int { bits: 64, signed: utils::is_signed_type(&ty) }))
+            }
+            Literal::Float(_) | Literal::TypedFloat(..) => Some(FfiType::FLOAT),
+            Literal::Bool(_) => Some(FfiType::Bool),
+            Literal::String(_) | Literal::Char(_) => Some(FfiType::Str),
+            Literal::Unit => None,
+        }
+    }
+
+    /// The debug info of the variable `name` no longer knows its value.
+    fn forget_value(&mut self, name: &str) {
+        if let Some(variable) = self.debug.variables.iter_mut().rev().find(|v| v.name == name) {
+            variable.const_value = None;
+        }
+    }
+
+    /// What `name` is bound to.
+    fn binding(&self, name: &str) -> Binding {
+        if let Some(literal) = self.constants.get(name) {
+            return Binding::Constant(literal.clone());
+        }
+        if let Some(layout) = self.values.get(name) {
+            return Binding::Struct(layout.clone());
+        }
+        match self.variables.get(name) {
+            Some(&(local, ty)) => Binding::Variable(local, ty),
+            None => Binding::Unbound,
+        }
+    }
+
+    /// Bind `name` to `binding` in the innermost scope; what it was bound
+    /// to before comes back when the scope ends.
+    fn bind(&mut self, name: &str, binding: Binding) {
+        let previous = self.binding(name);
+        if let Some(scope) = self.shadowed.last_mut() {
+            scope.entry(name.to_string()).or_insert(previous);
+        }
+        self.rebind(name, binding);
+    }
+
+    /// Make `name` stand for `binding` in whichever scope bound it.
+    fn rebind(&mut self, name: &str, binding: Binding) {
+        self.constants.remove(name);
+        self.variables.remove(name);
+        self.values.remove(name);
+        match binding {
+            Binding::Constant(literal) => {
+                self.constants.insert(name.to_string(), literal);
+            }
+            Binding::Variable(local, ty) => {
+                self.variables.insert(name.to_string(), (local, ty));
+            }
+            Binding::Struct(layout) => {
+                self.values.insert(name.to_string(), layout);
+            }
+            Binding::Unbound => {}
+        }
+    }
+
+    /// The struct values of `ty` are, if they are lowered field by field.
+    fn struct_name(&self, ty: &Type) -> Option<String> {
+        match &self.representation(ty).kind {
+            TypeKind::Named { path, generics } if generics.is_empty() && path.len() == 1 => {
+                self.structs.contains_key(&path[0]).then(|| path[0].clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// The struct `expr` makes or holds a value of, if its values are
+    /// lowered field by field.
+    fn struct_of(&self, expr: &Expr) -> Option<String> {
+        match &expr.kind {
+            ExprKind::Struct { path, .. } if path.len() == 1 && self.structs.contains_key(&path[0]) => {
+                Some(path[0].clone())
+            }
+            ExprKind::Variable { path } if path.len() == 1 && self.values.contains_key(&path[0]) => {
+                Some(self.values[&path[0]].clone())
+            }
+            ExprKind::Reference { expr: referent, .. } | ExprKind::Dereference { expr: referent } => {
+                self.struct_of(referent)
+            }
+            ExprKind::FieldAccess { object, field } if let Some(outer) = self.struct_of(object) => {
+                let (_, ty) = self.structs[&outer].iter().find(|(name, _)| name == field)?;
+                self.struct_name(ty)
+            }
+            _ => expr.ty.as_ref().and_then(|ty| self.struct_name(ty)),
+        }
+    }
+
+    /// The places in a value of the struct `name` that hold one value
+    /// each, with their types: its fields, and the fields of a field that is
+    /// itself such a struct, as `inner.x`. A struct holding itself holds it
+    /// as a field of its own, which cannot be lowered.
+    fn leaves(&self, name: &str) -> Vec<(String, Type)> {
+        fn collect(
+            lowering: &Lowering,
+            name: &str,
+            prefix: &str,
+            within: &mut Vec<String>,
+            leaves: &mut Vec<(String, Type)>,
+        ) {
+            within.push(name.to_string());
+            for (field, ty) in &lowering.structs[name] {
+                match lowering.struct_name(ty).filter(|inner| !within.contains(inner)) {
+                    Some(inner) => collect(lowering, &inner, &format!("{}{}.", prefix, field), within, leaves),
+                    None => leaves.push((format!("{}{}", prefix, field), ty.clone())),
+                }
+            }
+            within.pop();
+        }
+        let mut leaves = Vec::new();
+        collect(self, name, "", &mut Vec::new(), &mut leaves);
+        leaves
+    }
+
+    /// What each of `leaves` of `expr`, a value of the struct `name`, is
+    /// bound to: the values of a struct literal's fields, or copies of those
+    /// of the struct in a place.
+    fn struct_value(&mut self, expr: &Expr, name: &str) -> Result<Vec<(String, Binding)>, Stop> {
+        if let ExprKind::Reference { expr: referent, .. } = &expr.kind {
+            return self.struct_value(referent, name);
+        }
+        if let Some(place) = place_name(expr) {
+            let leaves = self.leaves(name);
+            let copies = leaves.into_iter().map(|(leaf, _)| {
+                let copy = self.copy_binding(&format!("{}.{}", place, leaf));
+                (leaf, copy)
+            });
+            return Ok(copies.collect());
+        }
+        let ExprKind::Struct { fields: inits, base: None, .. } = &expr.kind else {
+            return Err(self.error(expr.span, "struct value of this kind"));
+        };
+        let mut values = Vec::new();
+        for (field, ty) in self.structs[name].clone() {
+            let Some(init) = inits.iter().find(|init| init.name == field) else {
+                return Err(self.error(expr.span, "struct literal without every field"));
+            };
+            // `Point { x, y }` is `Point { x: x, y: y }`.
+            let shorthand;
+            let value = match &init.value {
+                Some(value) => value,
+                None => {
+                    shorthand = Expr::new(ExprKind::Variable { path: vec![field.clone()] }, init.span);
+                    &shorthand
+                }
+            };
+            match self.struct_name(&ty).filter(|inner| inner != name) {
+                Some(inner) => {
+                    for (leaf, binding) in self.struct_value(value, &inner)? {
+                        values.push((format!("{}.{}", field, leaf), binding));
+                    }
+                }
+                None => values.push((field, self.value_binding(value, &ty)?)),
+            }
+        }
+        Ok(values)
+    }
+
+    /// Bind the struct of a `let` of the struct `layout` at `span`, each of
+    /// `fields` under `name.field`.
+    fn bind_struct(&mut self, name: &str, fields: Vec<(String, Binding)>, layout: &str, span: SourceSpan) {
+        let (line, column) = self.position(span);
+        let variable = VariableInfo { name: name.to_string(), ty: layout.to_string(), const_value: None, line, column };
+        self.debug.variables.push(variable);
+        self.bind(name, Binding::Struct(layout.to_string()));
+        for (field, binding) in fields {
+            self.bind(&format!("{}.{}", name, field), binding);
+        }
+        if let Some(scope) = self.collections.last_mut() {
+            scope.insert(name.to_string(), None);
+        }
+    }
+
+    /// A binding to the value of `expr`, of type `ty`: the constant if it
+    /// is known here, of `ty` if it is an integer, else a new local holding
+    /// it.
+    fn value_binding(&mut self, expr: &Expr, ty: &Type) -> Result<Binding, Stop> {
+        let ty = self.representation(ty).clone();
+        if !self.is_runtime(expr)
+            && let Ok(literal) = self.literal(expr)
+        {
+            return Ok(Binding::Constant(match (literal, &ty.kind) {
+                (Literal::Integer(n), TypeKind::Primitive(prim)) if utils::is_integer_type(&ty) => {
+                    Literal::TypedInteger(n, prim.clone())
+                }
+                (literal, _) => literal,
+            }));
+        }
+        let (value, value_ty) = self.lower_value(expr)?;
+        let ty = ffi_type(&ty).filter(|ty| ty.carried() == value_ty.carried()).unwrap_or(value_ty);
+        let local = self.function.add_local(ty);
+        self.push(InstKind::Store { local, value }, None);
+        Ok(Binding::Variable(local, ty))
+    }
+
+    /// A copy of what `name` is bound to: the same constant, or a new local
+    /// holding what its local holds now.
+    fn copy_binding(&mut self, name: &str) -> Binding {
+        match self.binding(name) {
+            Binding::Variable(local, ty) => {
+                let value = self.push(InstKind::Load(local), Some(ty));
+                let copy = self.function.add_local(ty);
+                self.push(InstKind::Store { local: copy, value }, None);
+                Binding::Variable(copy, ty)
+            }
+            binding => binding,
+        }
+    }
+
+    /// Make the place `name` hold what `binding` is bound to, as assigning
+    /// to it does.
+    fn assign_binding(&mut self, name: &str, binding: Binding, span: SourceSpan) -> Result<(), Stop> {
+        self.forget_value(name);
+        match (self.binding(name), binding) {
+            (Binding::Unbound | Binding::Struct(_), _) | (_, Binding::Unbound | Binding::Struct(_)) => {
+                return Err(self.error(span, "assignment to this field"));
+            }
+            // The field keeps its type, as `let x: u8 = 200;` does.
+            (Binding::Constant(old), Binding::Constant(new)) => {
+                let new = match (new, old) {
+                    (Literal::Integer(n), Literal::TypedInteger(_, ty)) => Literal::TypedInteger(n, ty),
+                    (Literal::Float(f), Literal::TypedFloat(_, ty)) => Literal::TypedFloat(f, ty),
+                    (new, _) => new,
+                };
+                self.rebind(name, Binding::Constant(new));
+            }
+            (Binding::Constant(old), Binding::Variable(from, ty)) => {
+                let ty = self.local_type(&old).filter(|old| old.carried() == ty.carried()).unwrap_or(ty);
+                let value = self.push(InstKind::Load(from), Some(ty));
+                let local = self.function.add_local(ty);
+                self.push(InstKind::Store { local, value }, None);
+                self.rebind(name, Binding::Variable(local, ty));
+            }
+            (Binding::Variable(local, _), new) => {
+                let (value, _) = self.binding_value(new, span)?;
+                self.push(InstKind::Store { local, value }, None);
+            }
+        }
+        Ok(())
+    }
+
+    /// The place `expr` borrows as `&mut`: that of `&mut x`, or of a `&mut`
+    /// it moves, such as `r`.
+    fn borrowed_mutably<'e>(&self, expr: &'e Expr) -> Option<&'e Expr> {
+        let place = match (&expr.kind, expr.ty.as_ref().map(|ty| &ty.kind)) {
+            (ExprKind::Reference { expr: place, mutable: true }, _) => place,
+            (_, Some(TypeKind::Reference { mutable: true, .. })) => expr,
+            _ => return None,
+        };
+        place_name(place).is_some().then_some(place)
+    }
+
+    /// What to bind `name` to for it to refer to `place`: what `place` is
+    /// bound to, and for a struct the same for each field, once each
+    /// constant of them is moved into a local both names then share.
+    fn alias(&mut self, name: &str, place: &Expr) -> Result<Vec<(String, Binding)>, Stop> {
+        let place_name = place_name(place).expect("a place");
+        if self.collection(&place_name).is_some() {
+            return Err(self.error(place.span, "`&mut` of a collection"));
+        }
+        self.materialize(HashSet::from([place_name.clone()]), place.span)?;
+        let Some(layout) = self.struct_of(place) else {
+            return match self.binding(&place_name) {
+                Binding::Variable(local, ty) => Ok(vec![(name.to_string(), Binding::Variable(local, ty))]),
+                _ => Err(self.error(place.span, "`&mut` of this")),
+            };
+        };
+        let mut bindings = vec![(name.to_string(), Binding::Struct(layout.clone()))];
+        for (leaf, _) in self.leaves(&layout) {
+            bindings.push((format!("{}.{}", name, leaf), self.binding(&format!("{}.{}", place_name, leaf))));
+        }
+        Ok(bindings)
+    }
+
+    /// The value `binding`, bound to a constant or a local, holds.
+    fn binding_value(&mut self, binding: Binding, span: SourceSpan) -> Result<(InstId, FfiType), Stop> {
+        match binding {
+            Binding::Constant(literal) => self.lower_constant(&literal, span),
+            Binding::Variable(local, ty) => Ok((self.push(InstKind::Load(local), Some(ty)), ty)),
+            Binding::Unbound | Binding::Struct(_) => Err(self.error(span, "value of this field")),
+        }
+    }
+
+    /// Lower the body of a `spawn` block in place. Its bindings are the
+    /// thread's own and end with it.
+    fn lower_spawn(&mut self, body: &Expr) -> Result<(), Stop> {
+        self.in_scope(|lowering| lowering.lower_expr(body))
+    }
+
+    /// Lower a scope whose bindings end with it. What it assigns to the
+    /// bindings of the enclosing scopes, and does to their collections,
+    /// they keep.
+    fn in_scope(&mut self, lower: impl FnOnce(&mut Self) -> Result<(), Stop>) -> Result<(), Stop> {
+        self.shadowed.push(HashMap::new());
+        self.collections.push(HashMap::new());
+        let result = lower(self);
+        self.collections.pop();
+        for (name, binding) in self.shadowed.pop().unwrap_or_default() {
+            self.rebind(&name, binding);
+        }
+        result
+    }
+
+    /// The collection `name` is bound to, if it is one.
+    fn collection(&mut self, name: &str) -> Option<&mut Collection> {
+        self.collections.iter_mut().rev().find_map(|scope| scope.get_mut(name)).and_then(Option::as_mut)
+    }
+
+    /// Call `method` on the collection `receiver` names, returning the
+    /// constant it returns.
+    fn lower_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+        span: SourceSpan,
+    ) -> Result<Literal, Stop> {
+        let args = args.iter().map(|arg| self.literal(arg)).collect::<Result<Vec<_>, _>>()?;
+        if let Some((op, overflow)) = integer_method(method) {
+            if overflow == Overflow::Checked {
+                return Err(self.error(span, "an Option used as a value"));
+            }
+            let (a, ty) = self.integer(receiver)?;
+            let [Literal::Integer(b) | Literal::TypedInteger(b, _)] = args[..] else {
+                return Err(self.error(span, "integer method argument"));
+            };
+            let n = integer_arithmetic(op, a, b, &ty, overflow).expect("only checked arithmetic fails");
+            return Ok(Literal::TypedInteger(n, ty));
+        }
+        // `x.checked_add(1).unwrap_or(0)`
+        if let Some(mut option) = self.option_of(receiver)? {
+            let result = option.call(method, &args);
+            return result.map_err(|failure| self.failed(span, failure));
+        }
+        let fixed = self.fixed_collections;
+        let collection = match &receiver.kind {
+            ExprKind::Variable { path } if path.len() == 1 => {
+                let scope = self.collections.iter().rposition(|scope| scope.contains_key(&path[0]));
+                if scope.is_some_and(|scope| scope < fixed) && mutates(method) {
+                    return Err(self.error(span, "change to a collection in a loop or `if` decided at run time"));
+                }
+                self.collection(&path[0])
+            }
+            _ => None,
+        };
+        let Some(collection) = collection else {
+            return Err(self.error(receiver.span, "method call on anything but a collection"));
+        };
+        let result = collection.call(method, &args);
+        result.map_err(|failure| self.failed(span, failure))
+    }
+
+    /// Lower the method call `expr` on a collection at run time, with what
+    /// it reads known only then: a call of the `tstd` function for it,
+    /// after `Trap`s where it panics. Returns its value, unless it returns
+    /// nothing.
+    fn lower_collection_call(&mut self, expr: &Expr) -> Result<Option<(InstId, FfiType)>, Stop> {
+        let ExprKind::MethodCall { receiver, method, args } = &expr.kind else {
+            return Err(self.error(expr.span, "method call"));
+        };
+        let name = match &receiver.kind {
+            ExprKind::Variable { path } if path.len() == 1 => path[0].as_str(),
+            _ => return Err(self.error(receiver.span, "method call on anything but a collection")),
+        };
+        let scope = self.collections.iter().rposition(|scope| scope.contains_key(name));
+        if scope.is_some_and(|scope| scope < self.fixed_collections)
+            && mutates(method)
+            && !matches!(self.collection(name), Some(Collection::Runtime(_)))
+        {
+            return Err(self.error(expr.span, "change to a collection in a loop or `if` decided at run time"));
+        }
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            let (value, ty) = self.lower_value(arg)?;
+            if !matches!(ty, FfiType::Int { .. }) {
+                return Err(self.error(arg.span, "a collection of anything but integers at run time"));
+            }
+            values.push(value);
+        }
+        let (handle, kind) = self.runtime_collection(name, receiver.span)?;
+        let ty = self.runtime_type(expr.ty.as_ref());
+        let call = |lowering: &mut Self, method, returns| {
+            let mut args = vec![handle];
+            args.extend(&values);
+            lowering.call_collection(kind, method, args, returns)
+        };
+        let result = match (kind, method.as_str(), values.as_slice()) {
+            (_, "len", []) => Some((call(self, "len", Some(FfiType::INT)), ty)),
+            (_, "is_empty", []) => Some((self.is_empty(kind, handle), FfiType::Bool)),
+            ("vec", "pop", []) => {
+                let empty = self.is_empty(kind, handle);
+                self.lower_trap(empty, "`pop` from an empty Vec", expr.span);
+                let item = call(self, "pop", Some(FfiType::INT));
+                Some(self.narrow(item, ty))
+            }
+            ("vec", "get" | "set", [index, ..]) => {
+                let len = self.call_collection(kind, "len", vec![handle], Some(FfiType::INT));
+                let (zero, _) = self.constant(Constant::Int(0));
+                for (op, bound) in [(CompareOp::Lt, zero), (CompareOp::Ge, len)] {
+                    let outside = self.push(InstKind::Compare { op, lhs: *index, rhs: bound }, Some(FfiType::Bool));
+                    self.lower_trap(outside, "index out of bounds", expr.span);
+                }
+                if method == "set" {
+                    call(self, "set", None);
+                    None
+                } else {
+                    let item = call(self, "get", Some(FfiType::INT));
+                    Some(self.narrow(item, ty))
+                }
+            }
+            ("map", "get", [key]) => {
+                let args = vec![handle, *key];
+                let found = self.call_collection(kind, "contains_key", args, Some(FfiType::Bool));
+                let missing = self.push(InstKind::Not(found), Some(FfiType::Bool));
+                self.lower_trap(missing, "key not found in the HashMap", expr.span);
+                let value = call(self, "get", Some(FfiType::INT));
+                Some(self.narrow(value, ty))
+            }
+            ("vec" | "set", "contains", [_]) | ("map", "contains_key", [_]) => {
+                Some((call(self, method, Some(FfiType::Bool)), FfiType::Bool))
+            }
+            ("vec", "push", [_])
+            | ("set", "insert" | "remove", [_])
+            | ("map", "remove", [_])
+            | ("map", "insert", [_, _]) => {
+                call(self, method, None);
+                None
+            }
+            _ => return Err(self.error(expr.span, &format!("method `{}` at run time", method))),
+        };
+        Ok(result)
+    }
+
+    /// The handle of the collection bound to `name` at run time, and its
+    /// kind. A constant collection is made there, item by item, and `name`
+    /// is bound to it from then on, unless it is bound outside code decided
+    /// at run time, which cannot change it.
+    fn runtime_collection(&mut self, name: &str, span: SourceSpan) -> Result<(InstId, &'static str), Stop> {
+        let (kind, entries): (_, Vec<Vec<Literal>>) = match self.collection(name).cloned() {
+            Some(Collection::Runtime(kind)) => {
+                let (local, ty) = self.variables[name];
+                return Ok((self.push(InstKind::Load(local), Some(ty)), kind));
+            }
+            Some(Collection::Vec(items)) => ("vec", items.into_iter().map(|item| vec![item]).collect()),
+            Some(Collection::Set(items)) => ("set", items.into_iter().map(|item| vec![item]).collect()),
+            Some(Collection::Map(entries)) => ("map", entries.into_iter().map(|(k, v)| vec![k, v]).collect()),
+            _ => return Err(self.error(span, "method call on anything but a collection")),
+        };
+        let handle = self.call_collection(kind, "new", Vec::new(), Some(FfiType::INT));
+        let add = if kind == "vec" { "push" } else { "insert" };
+        for entry in entries {
+            let mut args = vec![handle];
+            for literal in &entry {
+                let constant @ Constant::Int(_) = self.constant_of(literal, span)? else {
+                    return Err(self.error(span, "a collection of anything but integers at run time"));
+                };
+                args.push(self.constant(constant).0);
+            }
+            self.call_collection(kind, add, args, None);
+        }
+        let scope = self.collections.iter().rposition(|scope| scope.contains_key(name));
+        if let Some(scope) = scope.filter(|&scope| scope >= self.fixed_collections) {
+            self.collections[scope].insert(name.to_string(), Some(Collection::Runtime(kind)));
+            let local = self.function.add_local(FfiType::INT);
+            self.push(InstKind::Store { local, value: handle }, None);
+            self.rebind(name, Binding::Variable(local, FfiType::INT));
+        }
+        Ok((handle, kind))
+    }
+
+    /// Call `tstd`'s `method` function for collections of `kind` with the
+    /// `i64`s `args`, the first a handle.
+    fn call_collection(&mut self, kind: &str, method: &str, args: Vec<InstId>, returns: Option<FfiType>) -> InstId {
+        let name = format!("tlang_{}_{}", kind, method);
+        let params = vec![FfiType::INT; args.len()];
+        self.declare_extern(&ExternFunction {
+            name: name.clone(),
+            params: params.clone(),
+            return_type: returns,
+            variadic: false,
+        });
+        self.push(InstKind::CallExtern { name, args, types: params }, returns)
+    }
+
+    /// Whether the collection of `kind` with the handle `handle` is empty.
+    fn is_empty(&mut self, kind: &str, handle: InstId) -> InstId {
+        let len = self.call_collection(kind, "len", vec![handle], Some(FfiType::INT));
+        let (zero, _) = self.constant(Constant::Int(0));
+        self.push(InstKind::Compare { op: CompareOp::Eq, lhs: len, rhs: zero }, Some(FfiType::Bool))
+    }
+
+    /// The type the `i`th type parameter of the collection `expr` is
+    /// lowered as at run time, such as the items of a `Vec`.
+    fn item_type(&self, expr: &Expr, i: usize) -> FfiType {
+        let generics = match expr.ty.as_ref().map(|ty| &self.representation(ty).kind) {
+            Some(TypeKind::Named { generics, .. }) => generics.get(i),
+            _ => None,
+        };
+        self.runtime_type(generics)
+    }
+
+    /// The type a value of `ty` is lowered as at run time: an integer of
+    /// no fixed width as 64 bits, and one of a type not known as an `i64`.
+    fn runtime_type(&self, ty: Option<&Type>) -> FfiType {
+        match ty.map(|ty| self.representation(ty)) {
+            Some(ty) if utils::is_integer_type(ty) => {
+                ffi_type(ty).unwrap_or(FfiType::Int { bits: 64, signed: utils::is_signed_type(ty) })
+            }
+            Some(ty) => ffi_type(ty).unwrap_or(FfiType::INT),
+            None => FfiType::INT,
+        }
+    }
+
+    /// The `Option` `expr` returns, if it is a call of a `checked_` method.
+    fn option_of(&self, expr: &Expr) -> Result<Option<Collection>, Stop> {
+        let ExprKind::MethodCall { receiver, method, args } = &expr.kind else {
+            return Ok(None);
+        };
+        let Some((op, Overflow::Checked)) = integer_method(method) else {
+            return Ok(None);
+        };
+        let (a, ty) = self.integer(receiver)?;
+        let b = match args.as_slice() {
+            [arg] => self.integer(arg)?.0,
+            _ => return Err(self.error(expr.span, "integer method with this many arguments")),
+        };
+        let n = integer_arithmetic(op, a, b, &ty, Overflow::Checked);
+        Ok(Some(Collection::Option(n.map(|n| Literal::TypedInteger(n, ty)))))
+    }
+
+    /// The constant integer `expr` denotes, and its type: as type checking
+    /// found it, as its literal says, or `i32` if neither tells.
+    fn integer(&self, expr: &Expr) -> Result<(i128, PrimitiveType), Stop> {
+        let (n, literal_ty) = match self.literal(expr)? {
+            Literal::Integer(n) => (n, None),
+            Literal::TypedInteger(n, ty) => (n, Some(ty)),
+            _ => return Err(self.error(expr.span, "arithmetic on something other than integers")),
+        };
+        let checked_ty = match &expr.ty {
+            Some(ty @ Type { kind: TypeKind::Primitive(prim), .. }) if utils::is_integer_type(ty) => Some(prim.clone()),
+            _ => None,
+        };
+        Ok((n, checked_ty.or(literal_ty).unwrap_or(PrimitiveType::I32)))
+    }
+
+    /// `left op right` of constant integers. Under overflow checks, a result
+    /// its type cannot hold panics; otherwise it wraps around. Division and
+    /// remainder by zero, or overflowing, always panic.
+    fn fold_arithmetic(&self, expr: &Expr, op: &BinaryOp, left: &Expr, right: &Expr) -> Result<Literal, Stop> {
+        if let (Literal::Float(a) | Literal::TypedFloat(a, _), Literal::Float(b) | Literal::TypedFloat(b, _)) =
+            (self.literal(left)?, self.literal(right)?)
+        {
+            return Ok(self.fold_float_arithmetic(expr, op, a, b));
+        }
+        let (a, left_ty) = self.integer(left)?;
+        let (b, right_ty) = self.integer(right)?;
+        // An unsuffixed operand takes the other's type.
+        let ty = match (&expr.ty, left_ty == PrimitiveType::I32) {
+            (Some(Type { kind: TypeKind::Primitive(prim), .. }), _) => prim.clone(),
+            (_, true) => right_ty,
+            (_, false) => left_ty,
+        };
+        let (name, verb) = match op {
+            BinaryOp::Add => ("add", "add"),
+            BinaryOp::Sub => ("sub", "subtract"),
+            BinaryOp::Div => ("div", "divide"),
+            BinaryOp::Mod => ("rem", "calculate the remainder"),
+            _ => ("mul", "multiply"),
+        };
+        let panic = |message: String| Err(Stop::Panic { message, span: expr.span });
+        if b == 0 && matches!(op, BinaryOp::Div | BinaryOp::Mod) {
+            let zero = if name == "div" { "by zero" } else { "with a divisor of zero" };
+            return panic(format!("attempt to {} {}", verb, zero));
+        }
+        let checked = self.options.overflow_checks || matches!(op, BinaryOp::Div | BinaryOp::Mod);
+        let overflow = if checked { Overflow::Checked } else { Overflow::Wrapping };
+        match integer_arithmetic(name, a, b, &ty, overflow) {
+            Some(n) => Ok(Literal::TypedInteger(n, ty)),
+            None => panic(format!("attempt to {} with overflow", verb)),
+        }
+    }
+
+    /// `a op b` of constant floats, in IEEE arithmetic on the `f64`s floats
+    /// are carried as: dividing by zero gives an infinity or NaN.
+    fn fold_float_arithmetic(&self, expr: &Expr, op: &BinaryOp, a: f64, b: f64) -> Literal {
+        let f = match op {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Mod => a % b,
+            _ => a * b,
+        };
+        match &expr.ty {
+            Some(ty @ Type { kind: TypeKind::Primitive(prim), .. }) if utils::is_float_type(ty) => {
+                Literal::TypedFloat(f, prim.clone())
+            }
+            _ => Literal::Float(f),
+        }
+    }
+
+    /// `assert(condition)`: of a constant condition, nothing if it holds
+    /// and a panic if not; of one only known at run time, a `Trap` on where
+    /// it does not hold.
+    fn lower_assert(&mut self, args: &[Expr], span: SourceSpan) -> Result<(), Stop> {
+        let [condition] = args else {
+            return Err(self.error(span, "assert with this many arguments"));
+        };
+        let text = self.source.get(condition.span.offset()..condition.span.offset() + condition.span.len());
+        let message = format!("assertion failed: {}", text.unwrap_or("false"));
+        if self.is_runtime(condition) {
+            let (holds, _) = self.lower_value(condition)?;
+            let fails = self.push(InstKind::Not(holds), Some(FfiType::Bool));
+            self.lower_trap(fails, &message, span);
+            return Ok(());
+        }
+        match self.literal(condition) {
+            Ok(Literal::Bool(true)) => Ok(()),
+            Ok(Literal::Bool(false)) => {
+                self.lower_panic(&message, span);
+                Ok(())
+            }
+            _ => Err(self.error(condition.span, "assertion of this condition")),
+        }
+    }
+
+    /// What a panic with `message` at `span` reports: the message with
+    /// where it is in the source, and the backtrace of the function it is
+    /// in to there.
+    fn panic_report(&self, message: &str, span: SourceSpan) -> (String, String) {
+        let (line, column) = self.position(span);
+        let file = self.debug.source_file.as_deref().unwrap_or("<unknown>");
+        let location = format!("{}:{}:{}", file, line, column);
+        let text = format!("panicked at {}:\n{}", location, message);
+        let backtrace = format!("   0: {}\n             at {}", self.function.name, location);
+        (text, backtrace)
+    }
+
+    /// Trap with `message`, reported as a panic at `span`, if the boolean
+    /// `condition` is true at run time.
+    fn lower_trap(&mut self, condition: InstId, message: &str, span: SourceSpan) {
+        let (message, backtrace) = self.panic_report(message, span);
+        let abort = self.options.panic == PanicStrategy::Abort;
+        self.push(InstKind::Trap { condition, message, backtrace, abort }, None);
+    }
+
+    /// Trap at `span` if `lhs op rhs` of integers of type `ty` is run
+    /// time arithmetic that panics: division by zero, and a result out of
+    /// range of a division or, under overflow checks, of the others.
+    fn lower_arithmetic_guards(&mut self, op: ArithOp, lhs: InstId, rhs: InstId, ty: FfiType, span: SourceSpan) {
+        let FfiType::Int { signed, .. } = ty else {
+            return;
+        };
+        let verb = match op {
+            ArithOp::Add => "add",
+            ArithOp::Sub => "subtract",
+            ArithOp::Mul => "multiply",
+            ArithOp::Div => "divide",
+            ArithOp::Rem => "calculate the remainder",
+        };
+        if matches!(op, ArithOp::Div | ArithOp::Rem) {
+            let (zero, _) = self.constant(Constant::Int(0));
+            let by_zero = self.push(InstKind::Compare { op: CompareOp::Eq, lhs: rhs, rhs: zero }, Some(FfiType::Bool));
+            let zero = if op == ArithOp::Div { "by zero" } else { "with a divisor of zero" };
+            self.lower_trap(by_zero, &format!("attempt to {} {}", verb, zero), span);
+        }
+        // A remainder is always in range; so is an unsigned quotient.
+        let checked = match op {
+            ArithOp::Div => signed,
+            ArithOp::Rem => false,
+            _ => self.options.overflow_checks,
+        };
+        if checked {
+            let overflows = self.push(InstKind::Overflows { op, lhs, rhs, ty }, Some(FfiType::Bool));
+            self.lower_trap(overflows, &format!("attempt to {} with overflow", verb), span);
+        }
+    }
+
+    /// Panic with `message`: call `tlang_panic`, or `tlang_abort` under
+    /// the abort strategy, with the message, where in the source `span` is
+    /// and the backtrace of `main` to there. Code decided at run time traps
+    /// instead, which every backend that runs it carries out. Nothing after
+    /// it is lowered.
+    fn lower_panic(&mut self, message: &str, span: SourceSpan) {
+        if self.decided_at_run_time {
+            let (always, _) = self.constant(Constant::Bool(true));
+            self.lower_trap(always, message, span);
+            self.function.terminate(self.block, Terminator::Unreachable);
+            self.unreachable = true;
+            return;
+        }
+        let (text, backtrace) = self.panic_report(message, span);
+        let runtime = match self.options.panic {
+            PanicStrategy::Unwind => "tlang_panic",
+            PanicStrategy::Abort => "tlang_abort",
+        };
+        self.call_runtime(runtime, vec![text, backtrace]);
+        self.function.terminate(self.block, Terminator::Unreachable);
+        self.unreachable = true;
+    }
+
+    /// Lower `arg` and print it.
+    fn lower_print(&mut self, arg: &Expr) -> Result<(), Stop> {
+        let (value, _) = self.lower_value(arg)?;
+        self.push(InstKind::Print(value), None);
+        Ok(())
+    }
+
+    /// The literal `expr` denotes: a literal or a `let`-bound constant.
+    fn literal(&self, expr: &Expr) -> Result<Literal, Stop> {
+        match &expr.kind {
+            ExprKind::Literal(lit) => Ok(lit.clone()),
+            ExprKind::Variable { path } if path.len() == 1 => match self.constants.get(&path[0]) {
+                Some(lit) => Ok(lit.clone()),
+                None => Err(self.error(expr.span, "non-constant print argument")),
+            },
+            ExprKind::FieldAccess { .. } => match place_name(expr).and_then(|name| self.constants.get(&name)) {
+                Some(lit) => Ok(lit.clone()),
+                None => Err(self.error(expr.span, "non-constant print argument")),
+            },
+            ExprKind::Reference { expr: referent, .. } | ExprKind::Dereference { expr: referent } => {
+                self.literal(referent)
+            }
+            ExprKind::Variable { path } if self.variant(path).is_some() => {
+                Ok(Literal::Integer(self.variant(path).expect("a variant")))
+            }
+            ExprKind::Match { expr: scrutinee, arms } => {
+                let value = self.literal(scrutinee)?;
+                match self.matching_arm(&value, arms)? {
+                    Some(arm) if matches!(arm.pattern.kind, PatternKind::Ident(_)) => {
+                        Err(self.error(arm.span, "value of a `match` arm binding a name"))
+                    }
+                    Some(arm) => self.literal(&arm.body),
+                    None => Err(self.error(expr.span, "value of a `match` no arm of which matches")),
+                }
+            }
+            ExprKind::Call { callee, args, .. } if callee_name(callee) == Some("format") => {
+                self.format(args, expr.span).map(Literal::String)
+            }
+            ExprKind::Call { callee, args, .. } if self.is_newtype(callee) && args.len() == 1 => {
+                self.literal(&args[0])
+            }
+            ExprKind::Binary {
+                left,
+                op: op @ (BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod),
+                right,
+            } => {
+                self.fold_arithmetic(expr, op, left, right)
+            }
+            ExprKind::Binary { left, op, right } if compare_op(op).is_some() => {
+                let (a, b) = (self.literal(left)?, self.literal(right)?);
+                let op = compare_op(op).expect("a comparison");
+                let holds = compare_literals(op, &a, &b).ok_or_else(|| self.error(expr.span, "comparison of these values"))?;
+                Ok(Literal::Bool(holds))
+            }
+            // `&&` and `||` only look at the right side if the left does not decide.
+            ExprKind::Binary { left, op: op @ (BinaryOp::And | BinaryOp::Or), right } => match self.literal(left)? {
+                Literal::Bool(decided) if decided == (*op == BinaryOp::Or) => Ok(Literal::Bool(decided)),
+                Literal::Bool(_) => match self.literal(right)? {
+                    Literal::Bool(b) => Ok(Literal::Bool(b)),
+                    _ => Err(self.error(right.span, "logic on something other than booleans")),
+                },
+                _ => Err(self.error(left.span, "logic on something other than booleans")),
+            },
+            ExprKind::Unary { op: UnaryOp::Not, expr: operand } => match self.literal(operand)? {
+                Literal::Bool(b) => Ok(Literal::Bool(!b)),
+                _ => Err(self.error(expr.span, "`!` of something other than a boolean")),
+            },
+            ExprKind::Unary { op: UnaryOp::Neg, expr: operand } => match self.literal(operand)? {
+                Literal::Integer(n) => Ok(Literal::Integer(-n)),
+                Literal::TypedInteger(n, ty) => Ok(Literal::TypedInteger(-n, ty)),
+                Literal::Float(f) => Ok(Literal::Float(-f)),
+                Literal::TypedFloat(f, ty) => Ok(Literal::TypedFloat(-f, ty)),
+                _ => Err(self.error(expr.span, "negation of something other than a number")),
+            },
+            ExprKind::Cast { expr: inner, target_type } => self.fold_cast(expr.span, inner, target_type),
+            _ => Err(self.error(expr.span, "print argument")),
+        }
+    }
+
+    /// `inner as target` of a constant `inner`, converted as it would be at
+    /// run time: integers wrap to the target's width, floats saturate to it,
+    /// and `bool` and `char` are their numeric values.
+    fn fold_cast(&self, span: SourceSpan, inner: &Expr, target: &Type) -> Result<Literal, Stop> {
+        let TypeKind::Primitive(prim) = &target.kind else {
+            return Err(self.error(span, "cast"));
+        };
+        let value = self.literal(inner)?;
+        if *prim == PrimitiveType::Char {
+            return match value {
+                Literal::Char(c) => Ok(Literal::Char(c)),
+                Literal::Integer(n) | Literal::TypedInteger(n, _) => {
+                    let byte = u8::try_from(n).map_err(|_| self.error(span, "cast"))?;
+                    Ok(Literal::Char(char::from(byte)))
+                }
+                _ => Err(self.error(span, "cast")),
+            };
+        }
+        // An integer, or the float to convert.
+        let number = match value {
+            Literal::Integer(n) | Literal::TypedInteger(n, _) => Ok(n),
+            Literal::Bool(b) => Ok(i128::from(b)),
+            Literal::Char(c) => Ok(i128::from(u32::from(c))),
+            Literal::Float(f) | Literal::TypedFloat(f, _) => Err(f),
+            _ => return Err(self.error(span, "cast")),
+        };
+        if utils::is_float_type(target) {
+            let f = match number {
+                Ok(n) => n as f64,
+                Err(f) => f,
+            };
+            return Ok(Literal::TypedFloat(f, prim.clone()));
+        }
+        if !utils::is_integer_type(target) {
+            return Err(self.error(span, "cast"));
+        }
+        let n = match number {
+            Ok(n) => integer_arithmetic("add", n, 0, prim, Overflow::Wrapping),
+            // `as i128` saturates, and makes NaN zero.
+            Err(f) => integer_arithmetic("add", f as i128, 0, prim, Overflow::Saturating),
+        };
+        n.map(|n| Literal::TypedInteger(n, prim.clone())).ok_or_else(|| self.error(span, "cast"))
+    }
+
+    /// `format(template, args...)`: the template with each `{}` replaced by
+    /// the next argument as it would print, and `{{` and `}}` by braces.
+    fn format(&self, args: &[Expr], span: SourceSpan) -> Result<String, Stop> {
+        let Some((template, args)) = args.split_first() else {
+            return Err(self.error(span, "format without a template"));
+        };
+        let Literal::String(template) = self.literal(template)? else {
+            return Err(self.error(template.span, "format template that is not a string"));
+        };
+        let mut args = args.iter();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    text.push(c);
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    let arg = args.next().ok_or_else(|| self.error(span, "format with fewer arguments than `{}`s"))?;
+                    text.push_str(&literal_value(&self.literal(arg)?));
+                }
+                _ => text.push(c),
+            }
+        }
+        if let Some(arg) = args.next() {
+            return Err(self.error(arg.span, "format with more arguments than `{}`s"));
+        }
+        Ok(text)
+    }
+
+    /// Lower the value of `expr`, returning it and its type.
+    fn lower_value(&mut self, expr: &Expr) -> Result<(InstId, FfiType), Stop> {
+        if let ExprKind::Cast { expr: inner, target_type } = &expr.kind {
+            return self.lower_cast(expr.span, inner, target_type);
+        }
+        if let Some(value) = unsafe_value(expr) {
+            return self.lower_value(value);
+        }
+        if let ExprKind::Call { callee, args, .. } = &expr.kind
+            && let Some(function) = self.extern_callee(callee)
+        {
+            let Some(result) = function.return_type else {
+                return Err(self.error(expr.span, "value of a function returning nothing"));
+            };
+            let call = self.lower_extern_call(&function, args, expr.span)?;
+            return Ok((call, result));
+        }
+        if let ExprKind::Call { callee, args, .. } = &expr.kind
+            && let Some(item) = self.user_callee(callee)
+        {
+            return match self.lower_call(item, args, expr.span)? {
+                Some(value) => Ok(value),
+                None => Err(self.error(expr.span, "value of a function returning nothing")),
+            };
+        }
+        if self.is_runtime(expr) {
+            return self.lower_runtime(expr);
+        }
+        let value = match &expr.kind {
+            ExprKind::MethodCall { receiver, method, args } => self.lower_method_call(receiver, method, args, expr.span)?,
+            _ => self.literal(expr)?,
+        };
+        self.lower_constant(&value, expr.span)
+    }
+
+    /// Lower the constant `literal`, the value of the expression at `span`,
+    /// an integer of the type the literal has. NaN and the infinities, which
+    /// backends cannot spell as literals, are computed by dividing by zero.
+    fn lower_constant(&mut self, literal: &Literal, span: SourceSpan) -> Result<(InstId, FfiType), Stop> {
+        match self.constant_of(literal, span)? {
+            Constant::Float(f) if !f.is_finite() => {
+                let numerator = if f.is_nan() { 0.0 } else { f.signum() };
+                let (lhs, ty) = self.constant(Constant::Float(numerator));
+                let (rhs, _) = self.constant(Constant::Float(0.0));
+                Ok((self.push(InstKind::Arith { op: ArithOp::Div, lhs, rhs }, Some(ty)), ty))
+            }
+            constant @ Constant::Int(_) => {
+                let ty = self.local_type(literal).unwrap_or(FfiType::INT);
+                Ok((self.push(InstKind::Const(constant), Some(ty)), ty))
+            }
+            constant => Ok(self.constant(constant)),
+        }
+    }
+
+    /// The constant of `literal`, the value of the expression at `span`.
+    fn constant_of(&self, literal: &Literal, span: SourceSpan) -> Result<Constant, Stop> {
+        Ok(match literal.clone() {
+            Literal::Integer(n) | Literal::TypedInteger(n, _) => {
+                Constant::Int(i64::try_from(n).map_err(|_| self.error(span, "integer literal"))?)
+            }
+            Literal::Float(f) => Constant::Float(f),
+            // Floats are carried as `f64`; an `f32` literal is rounded to `f32` first.
+            Literal::TypedFloat(f, PrimitiveType::F32) => Constant::Float(f64::from(f as f32)),
+            Literal::TypedFloat(f, _) => Constant::Float(f),
+            Literal::String(s) => Constant::Str(s),
+            Literal::Char(c) => Constant::Str(c.to_string()),
+            Literal::Bool(b) => Constant::Bool(b),
+            Literal::Unit => Constant::Str("()".to_string()),
+        })
+    }
+
+    /// Whether `expr` reads a binding whose value is only known at run time,
+    /// or calls one of the program's functions, an intrinsic or an extern
+    /// function for its result.
+    fn is_runtime(&self, expr: &Expr) -> bool {
+        exprs_in(expr).iter().any(|expr| match &expr.kind {
+            ExprKind::Variable { path } => path.len() == 1 && self.variables.contains_key(&path[0]),
+            ExprKind::FieldAccess { .. } => place_name(expr).is_some_and(|name| self.variables.contains_key(&name)),
+            ExprKind::Call { callee, .. } => {
+                self.user_callee(callee).is_some()
+                    || self.extern_callee(callee).is_some_and(|function| function.return_type.is_some())
+            }
+            _ => false,
+        })
+    }
+
+    /// Lower the value of `expr`, which reads bindings only known at run
+    /// time, with the guards of its arithmetic.
+    fn lower_runtime(&mut self, expr: &Expr) -> Result<(InstId, FfiType), Stop> {
+        match &expr.kind {
+            ExprKind::Variable { path } => {
+                let (local, ty) = self.variables[&path[0]];
+                Ok((self.push(InstKind::Load(local), Some(ty)), ty))
+            }
+            ExprKind::FieldAccess { .. } => {
+                let (local, ty) = self.variables[&place_name(expr).expect("a field of a struct")];
+                Ok((self.push(InstKind::Load(local), Some(ty)), ty))
+            }
+            ExprKind::Reference { expr: referent, .. } | ExprKind::Dereference { expr: referent } => {
+                self.lower_value(referent)
+            }
+            ExprKind::Binary { left, op, right } => {
+                if let Some(op) = compare_op(op) {
+                    let (lhs, left_ty) = self.lower_value(left)?;
+                    let (rhs, right_ty) = self.lower_value(right)?;
+                    if left_ty.carried() != right_ty.carried() || left_ty == FfiType::Str {
+                        return Err(self.error(expr.span, "comparison of these values"));
+                    }
+                    let ty = FfiType::Bool;
+                    return Ok((self.push(InstKind::Compare { op, lhs, rhs }, Some(ty)), ty));
+                }
+                let op = match op {
+                    BinaryOp::And | BinaryOp::Or => return self.lower_logic(op, left, right),
+                    BinaryOp::Add => ArithOp::Add,
+                    BinaryOp::Sub => ArithOp::Sub,
+                    BinaryOp::Mul => ArithOp::Mul,
+                    BinaryOp::Div => ArithOp::Div,
+                    BinaryOp::Mod => ArithOp::Rem,
+                    _ => return Err(self.error(expr.span, "this operator on values only known at run time")),
+                };
+                let (lhs, left_ty) = self.lower_value(left)?;
+                let (rhs, right_ty) = self.lower_value(right)?;
+                if left_ty.carried() != right_ty.carried() || !matches!(left_ty, FfiType::Int { .. } | FfiType::Float { .. }) {
+                    return Err(self.error(expr.span, "arithmetic on these values"));
+                }
+                if op == ArithOp::Rem && left_ty.carried() == FfiType::FLOAT {
+                    return Err(self.error(expr.span, "remainder of floats"));
+                }
+                // As type checking found it, else as the operand read at run time.
+                let operand_ty = if self.is_runtime(left) { left_ty } else { right_ty };
+                let ty = expr.ty.as_ref().and_then(|ty| ffi_type(self.representation(ty))).unwrap_or(operand_ty);
+                self.lower_arithmetic_guards(op, lhs, rhs, ty, expr.span);
+                let value = self.push(InstKind::Arith { op, lhs, rhs }, Some(ty.carried()));
+                Ok(self.narrow(value, ty))
+            }
+            ExprKind::Unary { op: UnaryOp::Neg, expr: operand } => {
+                let (value, ty) = self.lower_value(operand)?;
+                let (kind, ty) = match ty {
+                    // `-x` is `0 - x`, and `-1.0 * x` for a float, which keeps `-0.0`.
+                    FfiType::Int { .. } => {
+                        let (zero, _) = self.constant(Constant::Int(0));
+                        if self.options.overflow_checks {
+                            let overflows = InstKind::Overflows { op: ArithOp::Sub, lhs: zero, rhs: value, ty };
+                            let overflows = self.push(overflows, Some(FfiType::Bool));
+                            self.lower_trap(overflows, "attempt to negate with overflow", expr.span);
+                        }
+                        (InstKind::Arith { op: ArithOp::Sub, lhs: zero, rhs: value }, ty)
+                    }
+                    FfiType::Float { .. } => {
+                        let (minus_one, _) = self.constant(Constant::Float(-1.0));
+                        (InstKind::Arith { op: ArithOp::Mul, lhs: minus_one, rhs: value }, ty)
+                    }
+                    _ => return Err(self.error(expr.span, "negation of something other than a number")),
+                };
+                let value = self.push(kind, Some(ty.carried()));
+                Ok(self.narrow(value, ty))
+            }
+            ExprKind::Unary { op: UnaryOp::Not, expr: operand } => match self.lower_value(operand)? {
+                (value, FfiType::Bool) => Ok((self.push(InstKind::Not(value), Some(FfiType::Bool)), FfiType::Bool)),
+                _ => Err(self.error(expr.span, "`!` of something other than a boolean")),
+            },
+            ExprKind::Call { callee, args, .. } if self.is_newtype(callee) && args.len() == 1 => self.lower_value(&args[0]),
+            ExprKind::MethodCall { .. } => match self.lower_collection_call(expr)? {
+                Some(value) => Ok(value),
+                None => Err(self.error(expr.span, "value of a method returning nothing")),
+            },
+            _ => Err(self.error(expr.span, "this expression of values only known at run time")),
+        }
+    }
+
+    /// `value`, of an integer type `ty` narrower than 64 bits, wrapped to
+    /// `ty`'s width.
+    fn narrow(&mut self, value: InstId, ty: FfiType) -> (InstId, FfiType) {
+        match ty {
+            FfiType::Int { bits, .. } if bits < 64 => (self.push(InstKind::Cast { value, to: ty }, Some(ty)), ty),
+            _ => (value, ty),
+        }
+    }
+
+    /// `left && right` or `left || right` at run time: `right` is only
+    /// evaluated if `left` does not decide.
+    fn lower_logic(&mut self, op: &BinaryOp, left: &Expr, right: &Expr) -> Result<(InstId, FfiType), Stop> {
+        let (decider, FfiType::Bool) = self.lower_value(left)? else {
+            return Err(self.error(left.span, "logic on something other than booleans"));
+        };
+        let result = self.function.add_local(FfiType::Bool);
+        self.push(InstKind::Store { local: result, value: decider }, None);
+        let (rest, join) = (self.function.add_block(), self.function.add_block());
+        let (then, otherwise) = if *op == BinaryOp::And { (rest, join) } else { (join, rest) };
+        self.function.terminate(self.block, Terminator::Branch { condition: decider, then, otherwise });
+        self.block = rest;
+        let (value, FfiType::Bool) = self.lower_value(right)? else {
+            return Err(self.error(right.span, "logic on something other than booleans"));
+        };
+        self.push(InstKind::Store { local: result, value }, None);
+        self.function.terminate(self.block, Terminator::Jump(join));
+        self.block = join;
+        Ok((self.push(InstKind::Load(result), Some(FfiType::Bool)), FfiType::Bool))
+    }
+
+    /// Lower `inner as target`. Numeric casts convert on the int and float
+    /// stacks; casts from `bool` and `char`, and `u8 as char`, are folded
+    /// here since backends store those values differently.
+    fn lower_cast(
+        &mut self,
+        span: SourceSpan,
+        inner: &Expr,
+        target: &Type,
+    ) -> Result<(InstId, FfiType), Stop> {
+        let TypeKind::Primitive(prim) = &target.kind else {
+            return Err(self.error(span, "cast"));
+        };
+        let folded = match self.literal(inner) {
+            Ok(Literal::Bool(b)) => Some(i64::from(b)),
+            Ok(Literal::Char(c)) => Some(i64::from(u32::from(c))),
+            _ => None,
+        };
+        if *prim == PrimitiveType::Char {
+            return match self.literal(inner)? {
+                Literal::Char(c) => Ok(self.constant(Constant::Str(c.to_string()))),
+                Literal::Integer(n) | Literal::TypedInteger(n, _) => {
+                    let byte = u8::try_from(n).map_err(|_| self.error(span, "cast"))?;
+                    Ok(self.constant(Constant::Str(char::from(byte).to_string())))
+                }
+                _ => Err(self.error(span, "cast")),
+            };
+        }
+
+        let (value, from) = match folded {
+            Some(n) => self.constant(Constant::Int(n)),
+            None => self.lower_value(inner)?,
+        };
+        if utils::is_float_type(target) {
+            let to = FfiType::FLOAT;
+            return match from {
+                FfiType::Int { .. } => Ok((self.push(InstKind::Cast { value, to }, Some(to)), to)),
+                // Floats are carried as `f64`, so float casts change nothing.
+                FfiType::Float { .. } => Ok((value, from)),
+                _ => Err(self.error(span, "cast")),
+            };
+        }
+        let bits = match utils::type_size_bits(target) {
+            Some(bits) if utils::is_integer_type(target) && bits <= 64 => bits as u8,
+            _ => return Err(self.error(span, "cast")),
+        };
+        let to = FfiType::Int { bits, signed: utils::is_signed_type(target) };
+        match from {
+            FfiType::Int { .. } if bits == 64 => Ok((value, to)),
+            FfiType::Int { .. } | FfiType::Float { .. } => Ok((self.push(InstKind::Cast { value, to }, Some(to)), to)),
+            _ => Err(self.error(span, "cast")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check_program, parse_source};
+    use plugin_api::Instruction;
+
+    fn lower(source: &str, options: LoweringOptions) -> CompiledModule {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        lower_program_with_options(&program, source, "main.t", options).unwrap()
+    }
+
+    #[test]
+    fn panics_are_located_and_end_the_program() {
+        let source = "fn main() {\n    let v = Vec::new();\n    v.push(1);\n    println(v.get(3));\n    println(\"unreachable\");\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let Some((Instruction::PushStr(message), rest)) = module.instructions.split_first() else {
+            panic!("{:?}", module.instructions);
+        };
+        assert_eq!(message, "panicked at main.t:4:13:\nindex out of bounds: the len is 1 but the index is 3");
+        assert_eq!(rest[0], Instruction::PushStr("   0: main\n             at main.t:4:13".to_string()));
+        assert!(matches!(&rest[1], Instruction::CallExtern { name, .. } if name == "tlang_panic"));
+        assert_eq!(rest.len(), 2);
+
+        let embedded = LoweringOptions::default().with_profile(Profile::Embedded);
+        let module = lower("fn main() {\n    let zero = 0;\n    println(1 / zero);\n}\n", embedded);
+        assert!(matches!(&module.instructions[0], Instruction::PushStr(m) if m.ends_with("attempt to divide by zero")));
+        assert_eq!(module.externs[0].name, "tlang_abort");
+
+        // Columns count characters, past any that take several bytes.
+        let module = lower("fn main() {\n    let zero = 0; print(\"é\"); println(1 / zero);\n}\n", embedded);
+        assert!(matches!(&module.instructions[2], Instruction::PushStr(m) if m.starts_with("panicked at main.t:2:39:")));
+    }
+
+    #[test]
+    fn overflow_wraps_without_checks() {
+        let source = "fn main() {\n    let x: u8 = 200;\n    println(x + 100);\n}\n";
+        let unchecked = LoweringOptions { overflow_checks: false, ..LoweringOptions::default() };
+        assert_eq!(lower(source, unchecked).instructions[0], Instruction::PushInt(44));
+        let checked = lower(source, LoweringOptions::default());
+        assert!(matches!(&checked.instructions[0], Instruction::PushStr(m) if m.ends_with("attempt to add with overflow")));
+    }
+
+    #[test]
+    fn main_returning_i32_exits_with_its_value() {
+        let source = "fn main() -> i32 {\n    println(\"done\");\n    2 + 1\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        assert_eq!(module.instructions[4..], [Instruction::PushInt(3), Instruction::Exit]);
+        let unit = lower("fn main() {\n    println(3);\n}\n", LoweringOptions::default());
+        assert!(!unit.instructions.contains(&Instruction::Exit));
+    }
+
+    #[test]
+    fn deferred_blocks_run_last_first_at_the_end_of_their_block() {
+        let source = "fn main() -> i32 {\n    let x = 1;\n    defer { print(x); }\n    let x = 2;\n    defer { print(x); }\n    print(0);\n    7\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        use Instruction::{Exit, PrintInt, PushInt};
+        assert_eq!(
+            module.instructions,
+            [PushInt(0), PrintInt, PushInt(7), PushInt(2), PrintInt, PushInt(1), PrintInt, Exit]
+        );
+    }
+
+    #[test]
+    fn returns_run_the_deferred_blocks_they_leave() {
+        let source = "fn main() -> i32 {\n    defer { println(\"outer\"); }\n    let mut n = 0;\n    loop {\n        defer { print(n); }\n        n += 1;\n        if n == 3 {\n            return n;\n        };\n    }\n    0\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        assert_eq!(plugin_api::interpret_with_exit_code(&module).unwrap(), ("012outer\n".to_string(), 3));
+
+        let source = "fn main() {\n    defer { println(\"done\"); }\n    if true {\n        return;\n    };\n    println(\"unreachable\");\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        assert_eq!(plugin_api::interpret_with_exit_code(&module).unwrap(), ("done\n".to_string(), 0));
+    }
+
+    #[test]
+    fn extern_calls_in_unsafe_blocks_are_lowered() {
+        let source = "extern \"C\" { fn abs(n: i32) -> i32; }\nfn main() {\n    unsafe { abs(-1); };\n    let n = unsafe { abs(-2) };\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let calls = module.instructions.iter().filter(|instr| matches!(instr, Instruction::CallExtern { name, .. } if name == "abs"));
+        assert_eq!(calls.count(), 2);
+    }
+
+    #[test]
+    fn bound_intrinsic_results_are_read_at_run_time() {
+        let source = "fn main() {\n    let s = io::read_file(\"x\");\n    io::write(s);\n    let n = env::arg_count();\n    process::exit(n);\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let loads = module.instructions.iter().filter(|instr| matches!(instr, Instruction::LoadLocal { .. }));
+        assert_eq!(loads.count(), 2);
+    }
+
+    #[test]
+    fn bound_extern_results_are_read_at_run_time() {
+        let source = "extern \"C\" { fn abs(n: i32) -> i32; }\nfn main() {\n    let r = unsafe { abs(-3) };\n    println(r);\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        assert!(module.instructions.contains(&Instruction::LoadLocal { slot: 0, ty: FfiType::INT }));
+        assert_eq!(module.instructions.last(), Some(&Instruction::PrintStr));
+    }
+
+    #[test]
+    fn statics_become_globals_with_folded_initializers() {
+        let source = "static BASE: i32 = 40;\nstatic mut TOTAL: i32 = BASE + 2;\nfn main() {\n    unsafe { println(TOTAL); };\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let int = FfiType::Int { bits: 32, signed: true };
+        assert_eq!(
+            module.globals,
+            [
+                Global { name: "BASE".to_string(), ty: int, value: Constant::Int(40), mutable: false },
+                Global { name: "TOTAL".to_string(), ty: int, value: Constant::Int(42), mutable: true },
+            ]
+        );
+        assert_eq!(module.instructions[..2], [Instruction::PushInt(42), Instruction::PrintInt]);
+    }
+
+    #[test]
+    fn loops_only_known_at_run_time_jump() {
+        let source = "fn main() {\n    let mut n = 0;\n    let mut sum = 0;\n    while n < 4 {\n        n += 1;\n        if n == 2 {\n            continue;\n        };\n        sum = sum + n;\n    }\n    for i in 0..n {\n        if i > 1 {\n            break;\n        };\n        print(i);\n    }\n    println(\" \", sum, n == 4);\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        assert!(module.instructions.contains(&Instruction::JumpIfFalse(3)));
+        assert_eq!(plugin_api::interpret(&module).unwrap(), "01 8true\n");
+
+        // Short constant loops are still unrolled; long ones count at run time.
+        let short = lower("fn main() {\n    for i in 0..3 {\n        print(i);\n    }\n}\n", LoweringOptions::default());
+        assert!(!short.instructions.iter().any(|instr| matches!(instr, Instruction::Label(_))));
+        let long = lower("fn main() {\n    let mut n = 0;\n    for i in 0..10000 {\n        n = i;\n    }\n    print(n);\n}\n", LoweringOptions::default());
+        assert_eq!(plugin_api::interpret(&long).unwrap(), "9999");
+    }
+
+    #[test]
+    fn panics_only_known_at_run_time_trap() {
+        let source = "fn main() {\n    let mut n = 1;\n    while n < 3 {\n        n += 1;\n    }\n    assert(n == 3);\n    print(n);\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let trap = module.instructions.iter().rposition(|instr| matches!(instr, Instruction::Trap { .. })).unwrap();
+        assert_eq!(module.instructions[trap - 1], Instruction::Not);
+        assert!(matches!(&module.instructions[trap], Instruction::Trap { message, .. } if message.ends_with("assertion failed: n == 3")));
+        assert_eq!(plugin_api::interpret(&module).unwrap(), "3");
+        let failing = lower(&source.replace("n == 3", "n == 4"), LoweringOptions::default());
+        assert_eq!(plugin_api::interpret_with_exit_code(&failing).unwrap(), (String::new(), plugin_api::PANIC_EXIT_CODE));
+
+        // A `panic` in an `if` decided at run time traps as it is reached.
+        let source = "fn main() {\n    let mut n = 0;\n    while n < 5 {\n        if n == 3 {\n            panic(\"three\");\n        };\n        print(n);\n        n += 1;\n    }\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        assert!(module.externs.is_empty());
+        assert_eq!(plugin_api::interpret_with_exit_code(&module).unwrap(), ("012".to_string(), plugin_api::PANIC_EXIT_CODE));
+    }
+
+    #[test]
+    fn arithmetic_at_run_time_traps_where_it_panics() {
+        let source = "fn main() {\n    let mut n: u8 = 250;\n    while n < 252 {\n        n += 1;\n    }\n    print(n / 2, n % 5);\n    println(n + 4);\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let traps: Vec<&str> = module
+            .instructions
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Trap { message, .. } => Some(message.as_str()),
+                _ => None,
+            })
+            .collect();
+        // `n += 1` and `n + 4` may overflow; an unsigned quotient cannot.
+        assert_eq!(traps.len(), 4, "{:?}", traps);
+        assert!(traps[0].ends_with("main.t:4:9:\nattempt to add with overflow"), "{}", traps[0]);
+        assert!(traps[1].ends_with("main.t:6:11:\nattempt to divide by zero"), "{}", traps[1]);
+        assert!(traps[2].ends_with("attempt to calculate the remainder with a divisor of zero"), "{}", traps[2]);
+        assert_eq!(plugin_api::interpret_with_exit_code(&module).unwrap(), ("1262".to_string(), plugin_api::PANIC_EXIT_CODE));
+
+        let wrapping = lower(source, LoweringOptions { overflow_checks: false, ..LoweringOptions::default() });
+        assert_eq!(plugin_api::interpret_with_exit_code(&wrapping).unwrap(), ("12620\n".to_string(), 0));
+        let by_zero = source.replace("n % 5", "n % (n - 252)");
+        let module = lower(&by_zero, LoweringOptions::default());
+        assert_eq!(plugin_api::interpret_with_exit_code(&module).unwrap(), ("126".to_string(), plugin_api::PANIC_EXIT_CODE));
+    }
+
+    #[test]
+    fn collections_changed_at_run_time_are_held_by_tstd() {
+        let source = "fn main() {\n    let v = Vec::new();\n    v.push(4);\n    let mut n = 0;\n    while n < 3 {\n        v.push(n);\n        n += 1;\n    }\n    println(v.get(1));\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let externs: Vec<&str> = module.externs.iter().map(|function| function.name.as_str()).collect();
+        assert_eq!(externs, ["tlang_vec_new", "tlang_vec_push", "tlang_vec_len", "tlang_vec_get"]);
+        let bounds = |inst: &&Instruction| {
+            matches!(inst, Instruction::Trap { message, .. } if message.ends_with("index out of bounds"))
+        };
+        assert_eq!(module.instructions.iter().filter(bounds).count(), 2, "{:?}", module.instructions);
+
+        let strings = "fn main() {\n    let v = Vec::new();\n    v.push(\"a\");\n    let mut n = 0;\n    while n < 3 {\n        v.push(\"b\");\n        n += 1;\n    }\n}\n";
+        let mut program = parse_source(strings).unwrap();
+        check_program(&mut program, strings.to_string()).unwrap();
+        let err = lower_program(&program, strings, "main.t").unwrap_err();
+        assert!(err.to_string().contains("cannot lower a collection of anything but integers at run time"), "{}", err);
+    }
+
+    #[test]
+    fn newtypes_and_aliases_lower_as_what_they_wrap() {
+        let source = "type Count = u8;\nstruct Meters(f64);\nstatic LIMIT: Count = 200;\nfn main() {\n    let Meters(m) = Meters(1.5);\n    println(m, LIMIT + 100);\n}\n";
+        let module = lower(source, LoweringOptions { overflow_checks: false, ..LoweringOptions::default() });
+        assert_eq!(module.globals[0].ty, FfiType::Int { bits: 8, signed: false });
+        assert_eq!(
+            module.instructions[..4],
+            [Instruction::PushFloat(1.5), Instruction::PrintFloat, Instruction::PushInt(44), Instruction::PrintInt]
+        );
+    }
+
+    #[test]
+    fn structs_lower_field_by_field() {
+        let source = "struct Point {\n    x: i64,\n    y: u8,\n}\n\nstruct Line {\n    from: Point,\n    to: Point,\n}\n\nfn length(line: Line) -> i64 {\n    line.to.x - line.from.x\n}\n\nfn main() {\n    let mut line = Line { from: Point { x: 1, y: 2 }, to: Point { x: 4, y: 254 } };\n    let y = line.to.y;\n    println(y + 5);\n    let p = line.from;\n    defer { println(p.x); }\n    let mut i = 0;\n    while i < 3 {\n        line.to.x = line.to.x + 1;\n        line.from = line.to;\n        i += 1;\n    }\n    println(length(line), line.from.x, p.x);\n}\n";
+        let module = lower(source, LoweringOptions { overflow_checks: false, ..LoweringOptions::default() });
+        // `u8` fields wrap as `u8` variables do.
+        assert_eq!(plugin_api::interpret(&module).unwrap(), "3\n071\n1\n");
+    }
+
+    #[test]
+    fn float_arithmetic_is_folded() {
+        let source = "fn main() {\n    let b = 0.5 + 0.25;\n    let a = 2.5;\n    println(b, a * 2.0, 1.0 / 0.0);\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        assert_eq!(
+            module.instructions[..4],
+            [Instruction::PushFloat(0.75), Instruction::PrintFloat, Instruction::PushFloat(5.0), Instruction::PrintFloat]
+        );
+        // An infinity is no literal, so it is divided out at run time.
+        assert_eq!(plugin_api::interpret(&module).unwrap(), "0.7500005.000000inf\n");
+    }
+
+    #[test]
+    fn matches_at_run_time_switch() {
+        let source = "enum Dir { North, East, South, West }\nfn main() {\n    let mut n = 0;\n    let mut d = Dir::North;\n    while n < 6 {\n        match n {\n            0 => print(\"a\"),\n            1 | 3 => print(\"b\"),\n            3 => print(\"never\"),\n            k => print(k),\n        };\n        match d {\n            Dir::North => { d = Dir::West; }\n            Dir::West => { d = Dir::East; }\n            _ => print(\"!\"),\n        };\n        n += 1;\n    }\n    match Dir::South { Dir::South => println(\" south\"), _ => println(\" other\") };\n}\n";
+        let module = lower(source, LoweringOptions::default());
+        let tir = module.tir().unwrap();
+        let switches: Vec<&Terminator> = tir.functions[0]
+            .blocks
+            .iter()
+            .map(|block| &block.terminator)
+            .filter(|terminator| matches!(terminator, Terminator::Switch { .. }))
+            .collect();
+        let Terminator::Switch { cases, .. } = switches[0] else { unreachable!() };
+        // The second `3` is left to the arm before it.
+        assert_eq!(cases.iter().map(|&(n, _)| n).collect::<Vec<_>>(), [0, 1, 3]);
+        let Terminator::Switch { cases, .. } = switches[1] else { unreachable!() };
+        assert_eq!(cases.iter().map(|&(n, _)| n).collect::<Vec<_>>(), [0, 3]);
+        assert_eq!(plugin_api::interpret(&module).unwrap(), "ab2!b!4!5! south\n");
+
+        let guarded = "fn main() {\n    let mut n = 0;\n    while n < 2 {\n        match n {\n            k if k > 0 => print(k),\n            _ => {}\n        };\n        n += 1;\n    }\n}\n";
+        let mut program = parse_source(guarded).unwrap();
+        check_program(&mut program, guarded.to_string()).unwrap();
+        let err = lower_program(&program, guarded, "main.t").unwrap_err();
+        assert!(err.to_string().contains("cannot lower `match` arm guard on a value only known"), "{}", err);
     }
 }
@@ -6,20 +6,59 @@
 //! Designed for safety-critical systems with comprehensive error handling and analysis.
 
 use shared::{Program, Result, TlError};
-use errors::TlError as CompilerError;
+use errors::ErrorCollector;
+pub use errors::Severity;
 use miette::SourceSpan;
+use plugin_api::{BackendCapabilities, BackendConfig};
+pub use plugin_api::{ArtifactFormat, PanicStrategy, Profile};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
 
 pub mod parser;
+pub mod expand;
 pub mod types;
 pub mod safety;
 pub mod codegen;
+pub mod link;
+pub mod format;
+pub mod ir;
+pub mod intrinsics;
+pub mod resolve;
+pub mod lint;
+pub mod metrics;
+pub mod graph;
+pub mod callgraph;
+pub mod diff;
+pub mod coverage;
+pub mod bench;
 pub mod backends;
+pub mod optimizer;
+pub mod ast_transform;
+pub mod stats;
+pub mod query;
 
 // Re-export key types for convenience
 pub use parser::{Parser, parse_source, parse_expression};
+pub use expand::expand_macros;
 pub use types::{check_program, check_expression, TypeChecker};
-pub use safety::{analyze_safety, SafetyAnalyzer, SafetyViolation, SafetySeverity};
+pub use safety::{analyze_safety, SafetyAnalyzer, SafetyConfig, SafetyReport, SafetyViolation, SafetySeverity};
 pub use codegen::{CodeGenerator, GeneratedCode};
+pub use link::{link, LinkError, LinkOptions, LinkOutput};
+pub use format::{format_lines, format_source, FormatOptions};
+pub use ir::{lower_function, lower_program, lower_program_tir, lower_program_with_options, LoweringOptions};
+pub use intrinsics::{Capabilities, Capability};
+pub use resolve::{Symbol, SymbolKind, SymbolTable};
+pub use lint::lint_program;
+pub use metrics::{FunctionMetrics, MetricsReport, Thresholds};
+pub use graph::{Graph, ProgramGraphs};
+pub use callgraph::CallGraph;
+pub use diff::{diff_modules, diff_programs, Change};
+pub use coverage::CoverageMap;
+pub use bench::{Baseline, Benchmark, Stats};
+pub use ast_transform::{AstTransform, TransformConfig, register_transform, list_transforms};
+pub use stats::{CompilationStats, MemoryStats, PassStats};
+pub use query::{CheckedItem, Database, FileId, ItemKey};
 
 /// Main compiler pipeline that processes T-Lang source code.
 pub struct Compiler {
@@ -29,6 +68,8 @@ pub struct Compiler {
     options: CompilerOptions,
     /// Collected warnings and errors
     diagnostics: Vec<CompilerDiagnostic>,
+    /// Errors of the current compilation, up to `max_errors`
+    errors: ErrorCollector,
 }
 
 /// Compiler configuration options.
@@ -38,6 +79,9 @@ pub struct CompilerOptions {
     pub target: String,
     /// Optimization level (0 = none, 3 = maximum)
     pub optimization_level: u8,
+    /// Optimizers turned on (`true`) or off by name, whatever the
+    /// optimization level, as `--enable-opt` / `--disable-opt` do
+    pub optimizers: HashMap<String, bool>,
     /// Enable safety analysis
     pub safety_analysis: bool,
     /// Enable strict mode (treat warnings as errors)
@@ -46,8 +90,30 @@ pub struct CompilerOptions {
     pub max_errors: usize,
     /// Output directory for generated files
     pub output_dir: String,
-    /// Debug information level
+    /// Debug information level: 0 for none, 1 for what the backend supports,
+    /// 2 and up to ask for it, warning when the backend cannot emit it
     pub debug_level: u8,
+    /// Kind of artifact wanted (`None` = whichever the backend produces)
+    pub output_format: Option<ArtifactFormat>,
+    /// Per-transform configuration, keyed by `AstTransform::name`
+    pub transforms: HashMap<String, TransformConfig>,
+    /// Target triple for cross-compiling backends (`None` = host)
+    pub target_triple: Option<String>,
+    /// Backend-specific `key=value` settings, e.g. `c_std=c11`
+    pub backend_options: BTreeMap<String, String>,
+    /// Hosted or embedded (static memory, no heap) code generation
+    pub profile: Profile,
+    /// Stack budget in bytes, checked by safety analysis and reserved by
+    /// bare-metal linker scripts (`None` = defaults)
+    pub stack_size: Option<usize>,
+    /// Deepest call chain safety analysis accepts, in frames; overrides
+    /// the depth derived from `stack_size`
+    pub max_call_depth: Option<usize>,
+    /// Which safety lints are reported, and how severely
+    pub safety: SafetyConfig,
+    /// Where to write a report of the safety analysis, as HTML if the path
+    /// ends in `.html` and JSON otherwise (`None` = no report)
+    pub safety_report: Option<PathBuf>,
 }
 
 /// Compilation result containing generated code and diagnostics.
@@ -59,13 +125,15 @@ pub struct CompilationResult {
     pub diagnostics: Vec<CompilerDiagnostic>,
     /// Whether compilation succeeded
     pub success: bool,
+    /// Time and memory each pass took
+    pub stats: CompilationStats,
 }
 
 /// Compiler diagnostic (error, warning, or info message).
 #[derive(Debug, Clone)]
 pub struct CompilerDiagnostic {
     /// Severity level
-    pub level: DiagnosticLevel,
+    pub level: Severity,
     /// Human-readable message
     pub message: String,
     /// Source location where the diagnostic occurred
@@ -74,15 +142,8 @@ pub struct CompilerDiagnostic {
     pub code: Option<String>,
     /// Suggested fix if available
     pub suggestion: Option<String>,
-}
-
-/// Diagnostic severity levels.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum DiagnosticLevel {
-    Info,
-    Warning,
-    Error,
-    Fatal,
+    /// Related locations, each with what happened there
+    pub notes: Vec<(String, SourceSpan)>,
 }
 
 impl Default for CompilerOptions {
@@ -90,22 +151,70 @@ impl Default for CompilerOptions {
         Self {
             target: "rust".to_string(),
             optimization_level: 1,
+            optimizers: HashMap::new(),
             safety_analysis: true,
             strict_mode: false,
             max_errors: 100,
             output_dir: "target".to_string(),
             debug_level: 1,
+            output_format: None,
+            transforms: HashMap::new(),
+            target_triple: None,
+            backend_options: BTreeMap::new(),
+            profile: Profile::Hosted,
+            stack_size: None,
+            max_call_depth: None,
+            safety: SafetyConfig::default(),
+            safety_report: None,
+        }
+    }
+}
+
+impl CompilerOptions {
+    /// Settings handed to backends for this compilation.
+    pub fn backend_config(&self) -> BackendConfig {
+        BackendConfig {
+            target_triple: self.target_triple.clone(),
+            profile: self.profile,
+            stack_size: self.stack_size,
+            options: self.backend_options.clone(),
         }
     }
+
+    /// Check that `target` names a registered backend, and that it accepts
+    /// the backend options, so a typo fails before anything is compiled;
+    /// an unknown target's error lists the targets there are.
+    pub fn validate_target(&self) -> std::result::Result<(), CompilerDiagnostic> {
+        backends::register_builtin_backends();
+        if let Some(backend) = plugin_api::find_backend(&self.target) {
+            return backend
+                .initialize(&self.backend_config())
+                .map_err(|e| CompilerDiagnostic::error(format!("Invalid backend options: {}", e), None));
+        }
+        let mut targets: Vec<&str> = plugin_api::list_backends().iter().map(|b| b.name()).collect();
+        targets.sort_unstable();
+        let message = format!(
+            "Unknown target `{}`; available targets: {}",
+            self.target,
+            targets.join(", ")
+        );
+        let close = errors::suggest::similar_names(&self.target, targets);
+        Err(CompilerDiagnostic {
+            suggestion: errors::suggest::did_you_mean(&close),
+            ..CompilerDiagnostic::error(message, None)
+        })
+    }
 }
 
 impl Compiler {
     /// Create a new compiler instance.
     pub fn new(source: String, options: CompilerOptions) -> Self {
+        let errors = ErrorCollector::with_limit(options.max_errors);
         Self {
             source,
             options,
             diagnostics: Vec::new(),
+            errors,
         }
     }
 
@@ -116,179 +225,251 @@ impl Compiler {
 
     /// Compile the source code through the complete pipeline.
     pub fn compile(&mut self) -> CompilationResult {
+        let mut stats = CompilationStats::default();
+        let mut result = self.run_phases(&mut stats);
+        result.stats = stats;
+        result
+    }
+
+    /// Run each phase in turn, recording what it cost in `stats`.
+    fn run_phases(&mut self, stats: &mut CompilationStats) -> CompilationResult {
         // Clear previous diagnostics
         self.diagnostics.clear();
+        self.errors = ErrorCollector::with_limit(self.options.max_errors);
+
+        // Phase 0: A target there is a backend for
+        if let Err(diagnostic) = self.options.validate_target() {
+            self.diagnostics.push(diagnostic);
+            return self.create_failed_result();
+        }
 
-        // Phase 1: Parsing
-        let mut program = match self.parse_phase() {
+        // Phase 1: Parsing, reporting every syntax error
+        let mut program = match stats.measure("parse", || self.parse_phase()) {
             Ok(program) => program,
-            Err(error) => {
-                self.add_error_diagnostic(error);
+            Err(errors) => {
+                self.errors.extend(errors);
                 return self.create_failed_result();
             }
         };
 
-        // Phase 2: Type checking
-        if let Err(error) = self.type_check_phase(&mut program) {
-            self.add_error_diagnostic(error);
-            if self.options.strict_mode {
+        // Phase 1b: Macro expansion, before anything looks up names
+        let errors = stats.measure("expand macros", || expand_macros(&mut program, &self.source));
+        if !errors.is_empty() {
+            self.errors.extend(errors);
+            return self.create_failed_result();
+        }
+
+        // Phase 1c: AST transforms
+        program = match self.transform_phase(program, stats) {
+            Ok(program) => program,
+            Err(error) => {
+                let _ = self.report(error);
                 return self.create_failed_result();
             }
+        };
+
+        // Phase 2: Type checking
+        if let Err(error) = stats.measure("type check", || self.type_check_phase(&mut program))
+            && (self.report(error).is_break() || self.options.strict_mode)
+        {
+            return self.create_failed_result();
         }
 
+        // Phase 2b: Lints for unused code
+        stats.measure("lint", || self.lint_phase(&program));
+
         // Phase 3: Safety analysis
-        if self.options.safety_analysis {
-            if let Err(error) = self.safety_analysis_phase(&program) {
-                self.add_error_diagnostic(error);
-                if self.options.strict_mode {
-                    return self.create_failed_result();
-                }
-            }
+        if self.options.safety_analysis
+            && let Err(error) = stats.measure("safety analysis", || self.safety_analysis_phase(&program))
+            && (self.report(error).is_break() || self.options.strict_mode)
+        {
+            return self.create_failed_result();
         }
 
         // Phase 4: Code generation
-        let generated_code = match self.codegen_phase(&program) {
-            Ok(code) => Some(code),
+        let generated_code = match stats.measure("codegen", || self.codegen_phase(&program)) {
+            Ok((code, optimizations)) => {
+                stats.optimizations = optimizations;
+                Some(code)
+            }
             Err(error) => {
-                self.add_error_diagnostic(error);
+                let _ = self.report(error);
                 return self.create_failed_result();
             }
         };
 
         // Return successful result
+        self.flush_errors();
         CompilationResult {
             code: generated_code,
             diagnostics: self.diagnostics.clone(),
             success: !self.has_errors(),
+            stats: CompilationStats::default(),
         }
     }
 
-    /// Parse the source code into an AST.
-    fn parse_phase(&mut self) -> Result<Program> {
-        let parser = Parser::new(self.source.clone());
-        parser.parse()
+    /// Parse the source code into an AST, or return every syntax error.
+    fn parse_phase(&mut self) -> std::result::Result<Program, Vec<TlError>> {
+        let (program, errors) = Parser::new(self.source.clone()).parse_with_recovery();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Run all registered AST transforms in their declared order, each
+    /// measured as a pass of its own.
+    fn transform_phase(&mut self, program: Program, stats: &mut CompilationStats) -> Result<Program> {
+        let transforms = list_transforms();
+        let default_config = TransformConfig::default();
+        let mut program = program;
+        for transform in ast_transform::order_transforms(&transforms)? {
+            let config = self.options.transforms.get(transform.name()).unwrap_or(&default_config);
+            if config.enabled {
+                let name = format!("transform {}", transform.name());
+                program = stats.measure(name, || transform.transform(program, config))?;
+            }
+        }
+        Ok(program)
     }
 
     /// Perform type checking and inference.
     fn type_check_phase(&mut self, program: &mut Program) -> Result<()> {
-        let mut type_checker = TypeChecker::new(self.source.clone());
-        type_checker.check_program(program)
+        let mut type_checker = TypeChecker::new(self.source.clone())
+            .with_capabilities(Capabilities::for_profile(self.options.profile));
+        let result = type_checker.check_program(program);
+        for warning in type_checker.warnings() {
+            self.diagnostics.push(
+                CompilerDiagnostic::warning(warning.message.clone(), Some(warning.span))
+                    .with_code("W0001".to_string()),
+            );
+        }
+        result
     }
 
-    /// Perform safety analysis.
-    fn safety_analysis_phase(&mut self, program: &Program) -> Result<()> {
-        let violations = analyze_safety(program, self.source.clone())?;
-
-        // Convert safety violations to diagnostics
-        for violation in violations {
-            let diagnostic = CompilerDiagnostic {
-                level: match violation.severity() {
-                    SafetySeverity::Info => DiagnosticLevel::Info,
-                    SafetySeverity::Warning => DiagnosticLevel::Warning,
-                    SafetySeverity::Error => DiagnosticLevel::Error,
-                    SafetySeverity::Critical => DiagnosticLevel::Fatal,
-                },
-                message: violation.description(),
-                span: Some(self.get_violation_span(&violation)),
-                code: Some(self.get_violation_code(&violation)),
-                suggestion: None,
-            };
-
-            self.diagnostics.push(diagnostic);
+    /// Warn about unused locals, imports and functions.
+    fn lint_phase(&mut self, program: &Program) {
+        for (level, warning) in lint_program(program, &self.source).take_diagnostics() {
+            self.diagnostics.push(CompilerDiagnostic {
+                level,
+                message: warning.to_string(),
+                span: warning.span(),
+                code: Some(warning.code().to_string()),
+                suggestion: warning.fix().map(|fix| fix.title.clone()),
+                notes: Vec::new(),
+            });
         }
+    }
 
+    /// Perform safety analysis.
+    fn safety_analysis_phase(&mut self, program: &Program) -> Result<()> {
+        let diagnostics = safety_diagnostics(program, &self.source, &self.options)?;
+        self.diagnostics.extend(diagnostics);
         Ok(())
     }
 
-    /// Generate code for the target backend.
-    fn codegen_phase(&mut self, program: &Program) -> Result<GeneratedCode> {
-        let mut generator = CodeGenerator::new(
-            self.options.target.clone(),
-            self.options.optimization_level,
-        );
-
-        generator.generate(program)
+    /// Generate code for the target backend, within what it supports,
+    /// with what the optimizers counted.
+    fn codegen_phase(&mut self, program: &Program) -> Result<(GeneratedCode, BTreeMap<String, u64>)> {
+        let capabilities = plugin_api::find_backend(&self.options.target)
+            .map_or_else(BackendCapabilities::default, |backend| backend.capabilities());
+        let (optimization_level, debug_info) = self.fit_to_backend(&capabilities);
+        let generator = CodeGenerator::new(self.options.target.clone(), optimization_level)
+            .with_config(self.options.backend_config())
+            .with_optimizers(self.options.optimizers.clone())
+            .with_debug_info(debug_info);
+
+        let (code, optimizations) = generator.generate(program, &self.source)?;
+        if !capabilities.output_formats.contains(&code.format) {
+            return Err(TlError::internal(format!(
+                "The {} backend produced {:?} output, which it does not declare",
+                code.target, code.format
+            )));
+        }
+        Ok((code, optimizations))
     }
 
-    // Helper methods
+    /// The optimization level and whether to emit debug info, lowered to
+    /// what a backend with `capabilities` supports, with a warning for each
+    /// option it cannot honour. An output format it does not produce is
+    /// warned about too; it produces its usual one instead.
+    fn fit_to_backend(&mut self, capabilities: &BackendCapabilities) -> (u8, bool) {
+        let target = &self.options.target;
+        let mut warnings = Vec::new();
+
+        let wanted = self.options.optimization_level;
+        let optimization_level = wanted.min(capabilities.max_optimization_level);
+        if optimization_level < wanted {
+            warnings.push(format!(
+                "The {} backend optimizes up to level {}; compiling at that level instead of {}",
+                target, optimization_level, wanted
+            ));
+        }
 
-    fn add_error_diagnostic(&mut self, error: TlError) {
-        let diagnostic = CompilerDiagnostic {
-            level: DiagnosticLevel::Error,
-            message: error.to_string(),
-            span: self.extract_span_from_error(&error),
-            code: Some(self.extract_code_from_error(&error)),
-            suggestion: None,
-        };
+        let debug_info = self.options.debug_level > 0 && capabilities.supports_debug_info;
+        if self.options.debug_level > 1 && !capabilities.supports_debug_info {
+            warnings.push(format!("The {} backend cannot emit debug info; compiling without it", target));
+        }
 
-        self.diagnostics.push(diagnostic);
-    }
+        if let Some(wanted) = self.options.output_format
+            && !capabilities.output_formats.contains(&wanted)
+        {
+            let usual = capabilities.output_formats.first().map_or("no".to_string(), |f| format!("{:?}", f));
+            warnings.push(format!(
+                "The {} backend cannot produce {:?} output; producing {} output instead",
+                target, wanted, usual
+            ));
+        }
 
-    fn create_failed_result(&self) -> CompilationResult {
-        CompilationResult {
-            code: None,
-            diagnostics: self.diagnostics.clone(),
-            success: false,
+        for warning in warnings {
+            self.diagnostics.push(CompilerDiagnostic::warning(warning, None).with_code("W0002".to_string()));
         }
+        (optimization_level, debug_info)
     }
 
-    fn has_errors(&self) -> bool {
-        self.diagnostics.iter().any(|d| {
-            matches!(d.level, DiagnosticLevel::Error | DiagnosticLevel::Fatal)
-        })
-    }
+    // Helper methods
 
-    fn extract_span_from_error(&self, error: &TlError) -> Option<SourceSpan> {
-        match error {
-            TlError::Lexer { span, .. } => Some(*span),
-            TlError::Parser { span, .. } => Some(*span),
-            TlError::Type { span, .. } => Some(*span),
-            TlError::Safety { span, .. } => Some(*span),
-            TlError::Runtime { span, .. } => Some(*span),
-            _ => None,
-        }
+    /// Record an error; `Break` once `max_errors` have been reported.
+    fn report(&mut self, error: TlError) -> ControlFlow<()> {
+        self.errors.add(error, Severity::Error)
     }
 
-    fn extract_code_from_error(&self, error: &TlError) -> String {
-        match error {
-            TlError::Lexer { .. } => "E0001".to_string(),
-            TlError::Parser { .. } => "E0002".to_string(),
-            TlError::Type { .. } => "E0003".to_string(),
-            TlError::Safety { .. } => "E0004".to_string(),
-            TlError::Runtime { .. } => "E0005".to_string(),
-            TlError::Io { .. } => "E0006".to_string(),
-            TlError::Internal { .. } => "E0999".to_string(),
+    /// Turn the collected errors into diagnostics, followed by notes on
+    /// what was left out.
+    fn flush_errors(&mut self) {
+        let stopped = self.errors.is_aborted().then(|| self.errors.error_count());
+        for error in self.errors.take_errors() {
+            self.diagnostics.push(CompilerDiagnostic {
+                level: Severity::Error,
+                message: error.to_string(),
+                span: error.span(),
+                code: Some(error.code().to_string()),
+                suggestion: errors::suggest::did_you_mean(error.suggestions()),
+                notes: Vec::new(),
+            });
+        }
+        if let Some(summary) = self.errors.summary() {
+            self.diagnostics.push(CompilerDiagnostic::info(summary, None));
+        }
+        if let Some(count) = stopped {
+            let message = format!("Compilation stopped after {} errors", count);
+            self.diagnostics.push(CompilerDiagnostic::info(message, None));
         }
     }
 
-    fn get_violation_span(&self, violation: &SafetyViolation) -> SourceSpan {
-        match violation {
-            SafetyViolation::UninitializedVariable { span, .. } => *span,
-            SafetyViolation::UseAfterMove { span, .. } => *span,
-            SafetyViolation::MemoryLeak { allocation_site, .. } => *allocation_site,
-            SafetyViolation::ResourceLeak { acquisition_site, .. } => *acquisition_site,
-            SafetyViolation::NullPointerDereference { span, .. } => *span,
-            SafetyViolation::BufferOverflow { span, .. } => *span,
-            SafetyViolation::StackOverflow { span, .. } => *span,
-            SafetyViolation::UnsafeOperation { span, .. } => *span,
-            SafetyViolation::DataRace { span, .. } => *span,
-            SafetyViolation::RealtimeViolation { span, .. } => *span,
+    fn create_failed_result(&mut self) -> CompilationResult {
+        self.flush_errors();
+        CompilationResult {
+            code: None,
+            diagnostics: self.diagnostics.clone(),
+            success: false,
+            stats: CompilationStats::default(),
         }
     }
 
-    fn get_violation_code(&self, violation: &SafetyViolation) -> String {
-        match violation {
-            SafetyViolation::UninitializedVariable { .. } => "S0001".to_string(),
-            SafetyViolation::UseAfterMove { .. } => "S0002".to_string(),
-            SafetyViolation::MemoryLeak { .. } => "S0003".to_string(),
-            SafetyViolation::ResourceLeak { .. } => "S0004".to_string(),
-            SafetyViolation::NullPointerDereference { .. } => "S0005".to_string(),
-            SafetyViolation::BufferOverflow { .. } => "S0006".to_string(),
-            SafetyViolation::StackOverflow { .. } => "S0007".to_string(),
-            SafetyViolation::UnsafeOperation { .. } => "S0008".to_string(),
-            SafetyViolation::DataRace { .. } => "S0009".to_string(),
-            SafetyViolation::RealtimeViolation { .. } => "S0010".to_string(),
-        }
+    fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.level.is_error())
     }
 }
 
@@ -296,33 +477,36 @@ impl CompilerDiagnostic {
     /// Create a new error diagnostic.
     pub fn error(message: String, span: Option<SourceSpan>) -> Self {
         Self {
-            level: DiagnosticLevel::Error,
+            level: Severity::Error,
             message,
             span,
             code: None,
             suggestion: None,
+            notes: Vec::new(),
         }
     }
 
     /// Create a new warning diagnostic.
     pub fn warning(message: String, span: Option<SourceSpan>) -> Self {
         Self {
-            level: DiagnosticLevel::Warning,
+            level: Severity::Warning,
             message,
             span,
             code: None,
             suggestion: None,
+            notes: Vec::new(),
         }
     }
 
     /// Create a new info diagnostic.
     pub fn info(message: String, span: Option<SourceSpan>) -> Self {
         Self {
-            level: DiagnosticLevel::Info,
+            level: Severity::Info,
             message,
             span,
             code: None,
             suggestion: None,
+            notes: Vec::new(),
         }
     }
 
@@ -337,6 +521,12 @@ impl CompilerDiagnostic {
         self.code = Some(code);
         self
     }
+
+    /// Point at a related location.
+    pub fn with_note(mut self, message: String, span: SourceSpan) -> Self {
+        self.notes.push((message, span));
+        self
+    }
 }
 
 /// Convenience function to compile source code with default options.
@@ -345,11 +535,81 @@ pub fn compile_source(source: String) -> CompilationResult {
     compiler.compile()
 }
 
+/// Parse, expand, check and lower `source`, read from `file`, to the module
+/// backends and optimizers take, failing on the safety violations that are
+/// errors at the usual lint levels.
+pub fn compile_module(source: &str, file: &str) -> Result<plugin_api::CompiledModule> {
+    compile_module_with(source, file, &CompilerOptions::default()).map(|(module, _)| module)
+}
+
+/// `compile_module`, with the safety analyses `options` configure run on
+/// the checked program. A violation reported as an error fails it; the
+/// other diagnostics come back with the module.
+pub fn compile_module_with(
+    source: &str,
+    file: &str,
+    options: &CompilerOptions,
+) -> Result<(plugin_api::CompiledModule, Vec<CompilerDiagnostic>)> {
+    let mut program = parse_source(source)?;
+    if let Some(error) = expand_macros(&mut program, source).into_iter().next() {
+        return Err(error);
+    }
+    check_program(&mut program, source.to_string())?;
+    let diagnostics = safety_diagnostics(&program, source, options)?;
+    if let Some(error) = diagnostics.iter().find(|diagnostic| diagnostic.level.is_error()) {
+        let span = error.span.unwrap_or_else(|| SourceSpan::new(0.into(), 0));
+        return Err(TlError::safety(source, span, error.message.clone()));
+    }
+    let module = lower_program(&program, source, file).map_err(|e| TlError::internal(e.to_string()))?;
+    Ok((module, diagnostics))
+}
+
+/// Run the safety analyses `options` configure on the checked `program`,
+/// write the safety report it asks for, and return the violations as
+/// diagnostics at the levels of `options.safety`.
+pub fn safety_diagnostics(program: &Program, source: &str, options: &CompilerOptions) -> Result<Vec<CompilerDiagnostic>> {
+    let mut analyzer = SafetyAnalyzer::new(source.to_string())
+        .with_heap_allowed(options.profile != Profile::Embedded);
+    if let Some(bytes) = options.stack_size {
+        analyzer = analyzer.with_stack_size(bytes);
+    }
+    if let Some(depth) = options.max_call_depth {
+        analyzer = analyzer.with_max_call_depth(depth);
+    }
+    let violations = analyzer.analyze_program(program)?;
+    if let Some(path) = &options.safety_report {
+        SafetyReport::new(program, source, &violations, &options.safety)?.write(path)?;
+    }
+
+    // Convert safety violations to diagnostics
+    let mut diagnostics = Vec::new();
+    for violation in violations {
+        let Some(severity) = options.safety.severity(&violation) else {
+            continue;
+        };
+        let diagnostic = CompilerDiagnostic {
+            level: match severity {
+                SafetySeverity::Info => Severity::Info,
+                SafetySeverity::Warning => Severity::Warning,
+                SafetySeverity::Error => Severity::Error,
+                SafetySeverity::Critical => Severity::Fatal,
+            },
+            message: violation.description(),
+            span: Some(violation.span()),
+            code: Some(violation.code().to_string()),
+            suggestion: None,
+            notes: violation.notes(),
+        };
+
+        diagnostics.push(diagnostic);
+    }
+
+    Ok(diagnostics)
+}
+
 /// Convenience function to compile source code with specific target.
 pub fn compile_to_target(source: String, target: String) -> CompilationResult {
-    let mut options = CompilerOptions::default();
-    options.target = target;
-
+    let options = CompilerOptions { target, ..CompilerOptions::default() };
     let mut compiler = Compiler::new(source, options);
     compiler.compile()
 }
@@ -377,7 +637,7 @@ mod tests {
         }
 
         // At minimum, should not have fatal errors
-        assert!(!result.diagnostics.iter().any(|d| d.level == DiagnosticLevel::Fatal));
+        assert!(!result.diagnostics.iter().any(|d| d.level == Severity::Fatal));
     }
 
     #[test]
@@ -392,10 +652,191 @@ mod tests {
 
         // Should have type error
         assert!(result.diagnostics.iter().any(|d| {
-            d.level == DiagnosticLevel::Error && d.message.contains("type")
+            d.level == Severity::Error && d.message.contains("type")
+        }));
+    }
+
+    #[test]
+    fn test_compilation_records_each_phase() {
+        let result = compile_source("fn main() { print(\"hi\"); }".to_string());
+        let passes: Vec<&str> = result.stats.passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(passes, ["parse", "expand macros", "type check", "lint", "safety analysis", "codegen"]);
+
+        let failed = compile_source("fn main() { let = ; }".to_string());
+        assert_eq!(failed.stats.passes.len(), 1);
+    }
+
+    #[test]
+    fn test_codegen_generates_the_target_backends_files() {
+        let result = compile_to_target("fn main() { print(\"hi\"); }".to_string(), "rust".to_string());
+
+        let code = result.code.expect("rust code");
+        assert_eq!(code.target, "rust");
+        let files: Vec<&str> = code.files().map(|(name, _)| name).collect();
+        assert_eq!(files, ["src/main.rs", "Cargo.toml"]);
+        assert!(code.text().unwrap().contains("String::from(\"hi\")"));
+        assert_eq!(code.build_commands, ["cargo build --release", "./target/release/main"]);
+    }
+
+    #[test]
+    fn test_unknown_targets_fail_before_parsing() {
+        let result = compile_to_target("fn main() {".to_string(), "rsut".to_string());
+
+        assert!(!result.success);
+        assert!(result.stats.passes.is_empty());
+        let [diagnostic] = &result.diagnostics[..] else {
+            panic!("expected one diagnostic, got {:?}", result.diagnostics);
+        };
+        assert!(diagnostic.message.starts_with("Unknown target `rsut`; available targets: "));
+        assert!(diagnostic.message.contains("c, clojure"));
+        assert_eq!(diagnostic.suggestion.as_deref(), Some("did you mean `rust`?"));
+    }
+
+    #[test]
+    fn test_backend_options_reach_the_backend() {
+        let source = "fn main() { print(\"hi\"); }".to_string();
+        let mut options = CompilerOptions { target: "c".to_string(), ..CompilerOptions::default() };
+        options.backend_options.insert("c_std".to_string(), "c11".to_string());
+
+        let code = Compiler::new(source.clone(), options.clone()).compile().code.expect("c code");
+        assert_eq!(code.build_commands[0], "cc -std=c11 -g -o main main.c");
+
+        options.backend_options.insert("c_std".to_string(), "c89".to_string());
+        let rejected = Compiler::new(source, options).compile();
+        assert!(!rejected.success);
+        assert!(rejected.diagnostics[0].message.starts_with("Invalid backend options: "));
+    }
+
+    #[test]
+    fn test_optimizer_overrides_reach_the_optimizers() {
+        use plugin_api::{BackendError, CompiledModule, Optimizer};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        struct CountRuns;
+        impl Optimizer for CountRuns {
+            fn name(&self) -> &'static str {
+                "test-count-runs"
+            }
+            fn min_opt_level(&self) -> u8 {
+                u8::MAX
+            }
+            fn optimize(&self, module: CompiledModule) -> std::result::Result<CompiledModule, BackendError> {
+                RUNS.fetch_add(1, Ordering::SeqCst);
+                Ok(module)
+            }
+        }
+        plugin_api::register_optimizer(CountRuns);
+
+        let source = "fn main() { print(\"hi\"); }".to_string();
+        let mut options = CompilerOptions { optimization_level: 3, ..CompilerOptions::default() };
+        assert!(Compiler::new(source.clone(), options.clone()).compile().success);
+        assert_eq!(RUNS.load(Ordering::SeqCst), 0);
+
+        options.optimization_level = 0;
+        options.optimizers.insert("test-count-runs".to_string(), true);
+        assert!(Compiler::new(source, options).compile().success);
+        assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_inlined_call_sites_are_in_the_stats() {
+        let source = "fn twice(n: i64) -> i64 { n * 2 }\nfn main() { println(twice(2)); println(twice(3)); }".to_string();
+        let options = CompilerOptions { target: "c".to_string(), ..CompilerOptions::default() };
+
+        let result = Compiler::new(source, options).compile();
+
+        assert!(result.success);
+        assert_eq!(result.stats.optimizations["inlined call sites"], 2);
+    }
+
+    #[test]
+    fn test_promoted_allocations_are_in_the_stats() {
+        let source = "#[inline(never)]\nfn sum(n: i64) -> i64 {\n    let v = Vec::new();\n    v.push(n);\n    v.push(n);\n    v.get(0) + v.get(1)\n}\nfn main() { println(sum(4)); }".to_string();
+        let options = CompilerOptions { target: "c".to_string(), ..CompilerOptions::default() };
+
+        let result = Compiler::new(source, options).compile();
+
+        assert!(result.success);
+        assert_eq!(result.stats.optimizations["promoted allocations"], 1);
+    }
+
+    #[test]
+    fn test_options_beyond_the_backend_fall_back_with_warnings() {
+        let source = "fn main() { print(\"hi\"); }".to_string();
+        let options = CompilerOptions {
+            optimization_level: 3,
+            debug_level: 2,
+            output_format: Some(ArtifactFormat::Binary),
+            ..CompilerOptions::default()
+        };
+
+        let result = Compiler::new(source, options).compile();
+
+        assert!(result.success);
+        assert_eq!(result.code.expect("rust code").format, ArtifactFormat::Source);
+        let warnings: Vec<&str> = result
+            .diagnostics
+            .iter()
+            .filter(|d| d.code.as_deref() == Some("W0002"))
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "The rust backend cannot emit debug info; compiling without it",
+                "The rust backend cannot produce Binary output; producing Source output instead",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors_stop_at_max_errors() {
+        let source = "fn main() { let a = ; let b = ; let c = ; }".to_string();
+        let options = CompilerOptions { max_errors: 2, ..CompilerOptions::default() };
+
+        let result = Compiler::new(source, options).compile();
+
+        assert!(!result.success);
+        let errors = result.diagnostics.iter().filter(|d| d.level == Severity::Error).count();
+        assert_eq!(errors, 2);
+        assert!(result.diagnostics.iter().any(|d| {
+            d.level == Severity::Info && d.message == "Compilation stopped after 2 errors"
         }));
     }
 
+    #[test]
+    fn test_macro_errors_fail_compilation() {
+        let source = "fn main() { let x = missing!(1); }".to_string();
+
+        let result = Compiler::with_defaults(source).compile();
+
+        assert!(!result.success);
+        assert!(result.diagnostics.iter().any(|d| d.code.as_deref() == Some("E0008")));
+    }
+
+    #[test]
+    fn test_unused_code_is_warned_about() {
+        let source = "fn main() { let x = 1; }\nfn helper() { }".to_string();
+
+        let result = Compiler::with_defaults(source).compile();
+
+        assert!(result.success);
+        let unused: Vec<(&str, Option<&str>)> = result
+            .diagnostics
+            .iter()
+            .filter(|d| d.code.as_deref() == Some("W0003"))
+            .map(|d| (d.message.as_str(), d.suggestion.as_deref()))
+            .collect();
+        assert_eq!(
+            unused,
+            [
+                ("Unused variable `x`", Some("Rename it to `_x`")),
+                ("Function `helper` is never used", Some("Remove `helper`")),
+            ]
+        );
+    }
+
     #[test]
     fn test_compile_with_safety_violation() {
         let source = r#"
@@ -405,8 +846,7 @@ mod tests {
             }
         "#.to_string();
 
-        let mut options = CompilerOptions::default();
-        options.safety_analysis = true;
+        let options = CompilerOptions { safety_analysis: true, ..CompilerOptions::default() };
 
         let mut compiler = Compiler::new(source, options);
         let result = compiler.compile();
@@ -416,4 +856,84 @@ mod tests {
             d.message.contains("uninitialized")
         }));
     }
+
+    #[test]
+    fn test_leaks_are_reported_where_the_resource_was_acquired() {
+        let source = "fn main() { let f = open(\"log\"); let p = malloc(8); free(p); }".to_string();
+        let start = source.find("open").unwrap();
+
+        let result = Compiler::with_defaults(source).compile();
+
+        let leaks: Vec<_> = result.diagnostics.iter().filter(|d| d.code.as_deref() == Some("S0004")).collect();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].level, Severity::Warning);
+        assert_eq!(leaks[0].span, Some(SourceSpan::new(start.into(), "open(\"log\")".len())));
+        assert!(!result.diagnostics.iter().any(|d| d.code.as_deref() == Some("S0003")));
+    }
+
+    #[test]
+    fn test_use_after_move_points_at_the_move() {
+        let source = "fn main() { let a = \"text\"; let b = a; print(a); }".to_string();
+        let moved = source.find("= a").unwrap() + 2;
+        let used = source.rfind('a').unwrap();
+
+        let result = Compiler::with_defaults(source).compile();
+
+        let uses: Vec<_> = result.diagnostics.iter().filter(|d| d.code.as_deref() == Some("S0002")).collect();
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0].span, Some(SourceSpan::new(used.into(), 1)));
+        assert_eq!(uses[0].notes, vec![("value moved here".to_string(), SourceSpan::new(moved.into(), 1))]);
+    }
+
+    #[test]
+    fn test_safety_lint_levels_change_what_fails_compilation() {
+        let source = "fn main() { let f = open(\"log\"); }".to_string();
+        let leaks = |result: &CompilationResult| {
+            result.diagnostics.iter().filter(|d| d.code.as_deref() == Some("S0004")).map(|d| d.level).collect::<Vec<_>>()
+        };
+
+        let standard = Compiler::with_defaults(source.clone()).compile();
+        assert_eq!(leaks(&standard), vec![Severity::Warning]);
+
+        let safety = SafetyConfig::for_profile(safety::SafetyProfile::SafetyCritical);
+        let options = CompilerOptions { safety: safety.clone(), ..CompilerOptions::default() };
+        let critical = Compiler::new(source.clone(), options).compile();
+        assert!(!critical.success);
+        assert_eq!(leaks(&critical), vec![Severity::Error]);
+
+        let mut allowed = safety;
+        allowed.set("resource_leak", safety::LintLevel::Allow);
+        let options = CompilerOptions { safety: allowed, ..CompilerOptions::default() };
+        assert!(leaks(&Compiler::new(source, options).compile()).is_empty());
+    }
+
+    #[test]
+    fn test_safety_report_is_written_as_html_or_json() {
+        let source = "fn main() { let f = open(\"log\"); }".to_string();
+        let dir = std::env::temp_dir().join(format!("tlang-safety-report-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in ["report.json", "report.html"] {
+            let path = dir.join(name);
+            let options = CompilerOptions { safety_report: Some(path.clone()), ..CompilerOptions::default() };
+            Compiler::new(source.clone(), options).compile();
+            let report = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(name.ends_with(".html"), report.starts_with("<!DOCTYPE html>"));
+            assert!(report.contains("S0004"));
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_call_chains_deeper_than_max_call_depth_fail() {
+        let source = "fn main() { a(); } fn a() { b(); } fn b() {}".to_string();
+        let options = CompilerOptions { max_call_depth: Some(2), ..CompilerOptions::default() };
+
+        let result = Compiler::new(source, options).compile();
+
+        assert!(!result.success);
+        assert!(result.diagnostics.iter().any(|d| {
+            d.level == Severity::Fatal && d.code.as_deref() == Some("S0007")
+        }));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,268 @@
+//! Link step: turn the C, assembly or LLVM IR a backend generated into an
+//! executable in one go, by invoking a C compiler driver such as `cc` or
+//! `clang`, which compiles the sources and runs the system linker. The
+//! driver can also stop short of linking, and compile the main file alone
+//! to assembly or an object file.
+//!
+//! The driver is `LinkOptions::linker`, else `$CC`, else `clang` for LLVM
+//! IR, which only clang reads, and on Windows, and `cc` otherwise. Extra
+//! flags, such as `-fuse-ld=lld` or `-lm`, follow the inputs. The
+//! executable gets the platform's suffix, `.exe` on Windows.
+//!
+//! An executable is linked against `tstd`'s static library, whose functions
+//! the intrinsics, panics and collections held at run time call, with the
+//! system libraries Rust's standard library needs. `runtime_library` finds
+//! it: `$TLANG_TSTD_LIB`, else the one Cargo built beside the running
+//! compiler. Code with a linker script is freestanding and links without it.
+
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use thiserror::Error;
+
+use crate::codegen::GeneratedCode;
+
+/// Extensions of the files a C compiler driver takes as inputs.
+const LINKABLE: &[&str] = &["c", "s", "S", "ll", "bc", "o", "obj"];
+
+/// File name of `tstd`'s static library.
+const RUNTIME_LIBRARY: &str = if cfg!(windows) { "tstd.lib" } else { "libtstd.a" };
+
+/// System libraries a Rust static library is linked with, as
+/// `rustc --print native-static-libs` lists them.
+const NATIVE_LIBRARIES: &[&str] = if cfg!(target_os = "macos") {
+    &["-lSystem", "-lc", "-lm"]
+} else if cfg!(windows) {
+    &["-lkernel32", "-ladvapi32", "-lntdll", "-luserenv", "-lws2_32"]
+} else {
+    &["-lgcc_s", "-lutil", "-lrt", "-lpthread", "-lm", "-ldl", "-lc"]
+};
+
+/// How to link generated code.
+#[derive(Debug, Clone, Default)]
+pub struct LinkOptions {
+    /// Compiler driver to invoke (`None` = `$CC`, or a default for the inputs)
+    pub linker: Option<String>,
+    /// Extra arguments, passed after the inputs
+    pub flags: Vec<String>,
+}
+
+/// What the driver makes of generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkOutput {
+    /// Assembly for the main file (`-S`)
+    Assembly,
+    /// An object file for the main file (`-c`)
+    Object,
+    /// An executable, from every linkable file
+    Executable,
+}
+
+impl LinkOutput {
+    /// The driver flag that stops it at this output, if any.
+    fn flag(self) -> Option<&'static str> {
+        match self {
+            LinkOutput::Assembly => Some("-S"),
+            LinkOutput::Object => Some("-c"),
+            LinkOutput::Executable => None,
+        }
+    }
+}
+
+/// Why generated code could not be linked.
+#[derive(Debug, Error)]
+pub enum LinkError {
+    #[error("the {0} backend generates nothing a linker takes")]
+    NotLinkable(String),
+    #[error("cannot write the generated code to {dir:?}: {source}")]
+    Write { dir: PathBuf, source: io::Error },
+    #[error("cannot run {linker}: {source}")]
+    NotFound { linker: String, source: io::Error },
+    #[error("{linker} failed ({status}):\n{stderr}")]
+    Failed { linker: String, status: ExitStatus, stderr: String },
+}
+
+/// The files of `code` a C compiler driver compiles and links, in order.
+pub fn link_inputs(code: &GeneratedCode) -> Vec<&str> {
+    code.files()
+        .map(|(name, _)| name)
+        .filter(|name| {
+            let extension = Path::new(name).extension().and_then(OsStr::to_str);
+            extension.is_some_and(|extension| LINKABLE.contains(&extension))
+        })
+        .collect()
+}
+
+/// `path` with the platform's executable suffix, unless it has an extension.
+pub fn executable_path(path: &Path) -> PathBuf {
+    if path.extension().is_some() || env::consts::EXE_SUFFIX.is_empty() {
+        path.to_path_buf()
+    } else {
+        path.with_extension(env::consts::EXE_EXTENSION)
+    }
+}
+
+/// Write `code` into `dir` and link it into the executable `output`,
+/// returning the executable's path.
+pub fn link(code: &GeneratedCode, dir: &Path, output: &Path, options: &LinkOptions) -> Result<PathBuf, LinkError> {
+    build(code, dir, output, LinkOutput::Executable, options)
+}
+
+/// Write `code` into `dir` and have the driver make `kind` of it at
+/// `output`, returning the path of what it wrote.
+pub fn build(
+    code: &GeneratedCode,
+    dir: &Path,
+    output: &Path,
+    kind: LinkOutput,
+    options: &LinkOptions,
+) -> Result<PathBuf, LinkError> {
+    let mut inputs = link_inputs(code);
+    // `-S` and `-c` take one input when there is an `-o`.
+    if kind != LinkOutput::Executable {
+        inputs.retain(|name| *name == code.filename);
+    }
+    if inputs.is_empty() {
+        return Err(LinkError::NotLinkable(code.target.clone()));
+    }
+    code.write_to(dir)
+        .map_err(|source| LinkError::Write { dir: dir.to_path_buf(), source })?;
+
+    let linker = options
+        .linker
+        .clone()
+        .or_else(|| env::var("CC").ok().filter(|cc| !cc.is_empty()))
+        .unwrap_or_else(|| default_linker(&inputs).to_string());
+    let written = match kind {
+        LinkOutput::Executable => executable_path(output),
+        _ => output.to_path_buf(),
+    };
+    // The linker runs in `dir`, so the output path must not be relative to here.
+    let absolute = env::current_dir()
+        .map(|cwd| cwd.join(&written))
+        .unwrap_or_else(|_| written.clone());
+
+    let freestanding = code.files().any(|(name, _)| name.ends_with(".ld"));
+    let runtime = match kind {
+        LinkOutput::Executable if !freestanding => runtime_library(),
+        _ => None,
+    };
+    let native = if runtime.is_some() { NATIVE_LIBRARIES } else { &[] };
+
+    let result = Command::new(&linker)
+        .current_dir(dir)
+        .args(kind.flag())
+        .args(&inputs)
+        .arg("-o")
+        .arg(&absolute)
+        .args(&runtime)
+        .args(native)
+        .args(&options.flags)
+        .output()
+        .map_err(|source| LinkError::NotFound { linker: linker.clone(), source })?;
+    if !result.status.success() {
+        return Err(LinkError::Failed {
+            linker,
+            status: result.status,
+            stderr: String::from_utf8_lossy(&result.stderr).into_owned(),
+        });
+    }
+    Ok(written)
+}
+
+/// `tstd`'s static library: `$TLANG_TSTD_LIB`, else the one in the Cargo
+/// target directory of the running executable, or of the test in its
+/// `deps`, where a build of `tstd` as a dependency leaves it with a hash
+/// in its name. The newest is taken.
+pub fn runtime_library() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("TLANG_TSTD_LIB").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let exe = env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    let profile = if dir.ends_with("deps") { dir.parent()? } else { dir };
+    let (stem, extension) = RUNTIME_LIBRARY.rsplit_once('.')?;
+    // `libtstd.a`, or `libtstd-<hash>.a`
+    let is_runtime = |name: &str| {
+        let hash = name.strip_prefix(stem).and_then(|rest| rest.strip_suffix(extension));
+        name == RUNTIME_LIBRARY || hash.is_some_and(|hash| hash.starts_with('-') && hash.ends_with('.'))
+    };
+    [profile.to_path_buf(), profile.join("deps")]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_str().is_some_and(is_runtime))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// The driver that reads every one of `inputs`.
+fn default_linker(inputs: &[&str]) -> &'static str {
+    let llvm_ir = inputs.iter().any(|name| name.ends_with(".ll") || name.ends_with(".bc"));
+    if llvm_ir || cfg!(windows) { "clang" } else { "cc" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_api::ArtifactFormat;
+
+    fn generated(filename: &str, additional: &[&str]) -> GeneratedCode {
+        GeneratedCode {
+            target: "test".to_string(),
+            format: ArtifactFormat::Source,
+            filename: filename.to_string(),
+            source: Vec::new(),
+            additional_files: additional.iter().map(|name| (name.to_string(), Vec::new())).collect(),
+            build_commands: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn only_sources_and_objects_are_linked() {
+        let code = generated("main.c", &["startup.c", "link.ld", "Cargo.toml"]);
+        assert_eq!(link_inputs(&code), ["main.c", "startup.c"]);
+        assert!(link_inputs(&generated("main.py", &[])).is_empty());
+    }
+
+    #[test]
+    fn llvm_ir_is_linked_with_clang() {
+        assert_eq!(default_linker(&["main.ll"]), "clang");
+        assert_eq!(default_linker(&["main.c"]), if cfg!(windows) { "clang" } else { "cc" });
+    }
+
+    #[test]
+    fn the_runtime_library_is_found_beside_the_build() {
+        let runtime = runtime_library().expect("tstd is built before the compiler's tests");
+        let name = runtime.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("libtstd") || name.starts_with("tstd"), "{}", name);
+    }
+
+    // `true` stands in for the driver, accepting any arguments.
+    #[cfg(unix)]
+    #[test]
+    fn assembly_and_objects_are_built_from_the_main_file_alone() {
+        let code = generated("startup.c", &["main.c"]);
+        let dir = env::temp_dir().join(format!("tlang-link-{}", std::process::id()));
+        let options = LinkOptions { linker: Some("true".to_string()), flags: Vec::new() };
+        for kind in [LinkOutput::Assembly, LinkOutput::Object] {
+            let written = build(&code, &dir, Path::new("main.o"), kind, &options).unwrap();
+            assert_eq!(written, Path::new("main.o"));
+        }
+        let err = build(&generated("Cargo.toml", &["main.c"]), &dir, Path::new("main.s"), LinkOutput::Assembly, &options);
+        assert!(matches!(err, Err(LinkError::NotLinkable(_))));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn code_without_linkable_files_is_refused() {
+        let code = generated("main.py", &[]);
+        let dir = env::temp_dir();
+        let err = link(&code, &dir, Path::new("main"), &LinkOptions::default()).unwrap_err();
+        assert!(matches!(err, LinkError::NotLinkable(target) if target == "test"));
+    }
+}
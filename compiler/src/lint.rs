@@ -0,0 +1,545 @@
+// compiler/src/lint.rs
+//! Warnings about code that is never used.
+//!
+//! Once names are resolved, three kinds of leftovers are reported: local
+//! variables and parameters that are never read, `use` declarations whose
+//! name nothing refers to, and private functions that nothing reachable
+//! from `main`, a public function or a `#[bench]` benchmark calls. A name starting with `_` is
+//! never reported, so `let _guard = ...;` keeps a value without a warning.
+//!
+//! Each warning carries a fix: a local is renamed to start with `_`, and an
+//! import or a function is removed along with the lines it takes up.
+
+use crate::bench::BENCH_ATTRIBUTE;
+use errors::{ErrorCollector, Fix, Severity};
+use miette::SourceSpan;
+use shared::ast::stmt::{ExternItem, FnParam, ImplItem, MacroArg, StructFields, TraitItem};
+use shared::ast::Block;
+use shared::{
+    Expr, ExprKind, Item, ItemKind, Pattern, PatternKind, Program, StmtKind, TlError, Type, TypeKind, Visibility,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The lint warnings for `program`, in source order, each at
+/// `Severity::Warning`.
+pub fn lint_program(program: &Program, source: &str) -> ErrorCollector {
+    let mut lints = Lints {
+        source,
+        warnings: Vec::new(),
+        roots: HashSet::new(),
+        calls: HashMap::new(),
+        functions: Vec::new(),
+        imports: Vec::new(),
+    };
+    for item in &program.items {
+        lints.item(item);
+    }
+    lints.unused_imports();
+    lints.dead_functions();
+
+    let mut warnings = lints.warnings;
+    warnings.sort_by_key(|warning| warning.span().map_or(0, |span| span.offset()));
+    let mut collector = ErrorCollector::new();
+    for warning in warnings {
+        let _ = collector.add(warning, Severity::Warning);
+    }
+    collector
+}
+
+struct Lints<'a> {
+    source: &'a str,
+    warnings: Vec<TlError>,
+    /// Names referred to from code that is kept whatever it calls: `main`,
+    /// public functions, benchmarks, methods, and the types and
+    /// initializers of other items
+    roots: HashSet<String>,
+    /// The names each private function refers to
+    calls: HashMap<String, HashSet<String>>,
+    /// The private functions, which are dead unless reached from a root
+    functions: Vec<(&'a Item, &'a str)>,
+    /// Private `use` declarations and the names they import
+    imports: Vec<(&'a Item, &'a str)>,
+}
+
+impl<'a> Lints<'a> {
+    fn item(&mut self, item: &'a Item) {
+        let mut body = Body::new(self.source);
+        match &item.kind {
+            ItemKind::Function { name, params, return_type, body: Some(function_body), .. } => {
+                body.function(params, return_type.as_ref(), function_body);
+                let names = self.finish(body);
+                if name == "main" || item.vis != Visibility::Private || item.attribute(BENCH_ATTRIBUTE).is_some() {
+                    self.roots.extend(names);
+                } else {
+                    self.calls.entry(name.clone()).or_default().extend(names);
+                    self.functions.push((item, name.as_str()));
+                }
+                return;
+            }
+            ItemKind::Struct { fields, .. } => body.fields(fields),
+            ItemKind::Enum { variants, .. } => {
+                for variant in variants {
+                    body.fields(&variant.fields);
+                    if let Some(discriminant) = &variant.discriminant {
+                        body.expr(discriminant);
+                    }
+                }
+            }
+            ItemKind::TypeAlias { ty, .. } => body.ty(ty),
+            ItemKind::Const { ty, value, .. } | ItemKind::Static { ty, value, .. } => {
+                body.ty(ty);
+                body.expr(value);
+            }
+            ItemKind::Trait { supertraits, items, .. } => {
+                supertraits.iter().for_each(|ty| body.ty(ty));
+                for trait_item in items {
+                    match trait_item {
+                        TraitItem::Function { params, return_type, body: Some(default), .. } => {
+                            body.function(params, return_type.as_ref(), default);
+                        }
+                        TraitItem::Const { ty, value, .. } => {
+                            body.ty(ty);
+                            value.iter().for_each(|value| body.expr(value));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            ItemKind::Impl { trait_, self_ty, items, .. } => {
+                trait_.iter().chain([self_ty]).for_each(|ty| body.ty(ty));
+                for impl_item in items {
+                    match impl_item {
+                        ImplItem::Function { params, return_type, body: method, .. } => {
+                            body.function(params, return_type.as_ref(), method);
+                        }
+                        ImplItem::Type { ty, .. } => body.ty(ty),
+                        ImplItem::Const { ty, value, .. } => {
+                            body.ty(ty);
+                            body.expr(value);
+                        }
+                    }
+                }
+            }
+            ItemKind::Extern { items, .. } => {
+                for extern_item in items {
+                    match extern_item {
+                        ExternItem::Function { params, return_type, .. } => {
+                            params.iter().for_each(|param| body.ty(&param.ty));
+                            return_type.iter().for_each(|ty| body.ty(ty));
+                        }
+                        ExternItem::Static { ty, .. } => body.ty(ty),
+                    }
+                }
+            }
+            ItemKind::Use { path, alias, glob: false } if item.vis == Visibility::Private => {
+                if let Some(name) = alias.as_ref().or(path.last()) {
+                    self.imports.push((item, name.as_str()));
+                }
+            }
+            ItemKind::Module { items, .. } => items.iter().for_each(|item| self.item(item)),
+            _ => {}
+        }
+        let names = self.finish(body);
+        self.roots.extend(names);
+    }
+
+    /// Take the warnings of `body` and lint the items declared in it,
+    /// returning the names it refers to.
+    fn finish(&mut self, body: Body<'a>) -> HashSet<String> {
+        self.warnings.extend(body.warnings);
+        for item in body.items {
+            self.item(item);
+        }
+        body.names
+    }
+
+    fn unused_imports(&mut self) {
+        let used: HashSet<&str> =
+            self.roots.iter().chain(self.calls.values().flatten()).map(String::as_str).collect();
+        for &(item, name) in &self.imports {
+            if name.starts_with('_') || used.contains(name) {
+                continue;
+            }
+            let fix = Fix {
+                title: "Remove the unused import".to_string(),
+                span: removal(self.source, item),
+                replacement: String::new(),
+            };
+            let warning = TlError::lint(self.source.to_string(), item.span, format!("Unused import `{}`", name));
+            self.warnings.push(warning.with_fix(fix));
+        }
+    }
+
+    /// Warn about the private functions not reachable from any root.
+    fn dead_functions(&mut self) {
+        let mut reached: HashSet<&str> = HashSet::new();
+        let mut pending: Vec<&str> = self.roots.iter().map(String::as_str).collect();
+        while let Some(name) = pending.pop() {
+            if let Some(names) = self.calls.get(name)
+                && reached.insert(name)
+            {
+                pending.extend(names.iter().map(String::as_str));
+            }
+        }
+
+        for &(item, name) in &self.functions {
+            if name.starts_with('_') || reached.contains(name) {
+                continue;
+            }
+            let fix = Fix {
+                title: format!("Remove `{}`", name),
+                span: removal(self.source, item),
+                replacement: String::new(),
+            };
+            let message = format!("Function `{}` is never used", name);
+            let warning = TlError::lint(self.source.to_string(), name_span(self.source, item, name), message);
+            self.warnings.push(warning.with_fix(fix));
+        }
+    }
+}
+
+/// A variable or parameter in scope.
+struct Local {
+    name: String,
+    /// "variable" or "parameter"
+    what: &'static str,
+    span: SourceSpan,
+    /// Bound by a struct pattern's `name` shorthand, which renaming has to
+    /// spell out as `name: _name`
+    shorthand: bool,
+    used: bool,
+}
+
+/// Follows the names used in one function body, or in the types and
+/// initializers of an item.
+struct Body<'a> {
+    source: &'a str,
+    scopes: Vec<Vec<Local>>,
+    /// Names referred to that are not locals: the first and last segments of
+    /// each path
+    names: HashSet<String>,
+    /// Items declared inside the body, linted after it
+    items: Vec<&'a Item>,
+    warnings: Vec<TlError>,
+}
+
+impl<'a> Body<'a> {
+    fn new(source: &'a str) -> Self {
+        Body { source, scopes: Vec::new(), names: HashSet::new(), items: Vec::new(), warnings: Vec::new() }
+    }
+
+    fn function(&mut self, params: &'a [FnParam], return_type: Option<&'a Type>, body: &'a Expr) {
+        self.scopes.push(Vec::new());
+        for param in params {
+            self.ty(&param.ty);
+            self.bind(&param.pattern, "parameter");
+        }
+        return_type.iter().for_each(|ty| self.ty(ty));
+        self.expr(body);
+        self.pop();
+    }
+
+    /// Leave the innermost scope, warning about the locals never used in it.
+    fn pop(&mut self) {
+        for local in self.scopes.pop().unwrap_or_default() {
+            if local.used || local.name.starts_with('_') {
+                continue;
+            }
+            let replacement = match local.shorthand {
+                true => format!("{}: _{}", local.name, local.name),
+                false => format!("_{}", local.name),
+            };
+            let fix = Fix { title: format!("Rename it to `_{}`", local.name), span: local.span, replacement };
+            let message = format!("Unused {} `{}`", local.what, local.name);
+            self.warnings.push(TlError::lint(self.source.to_string(), local.span, message).with_fix(fix));
+        }
+    }
+
+    fn declare(&mut self, name: &str, what: &'static str, span: SourceSpan, shorthand: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Local { name: name.to_string(), what, span, shorthand, used: false });
+        }
+    }
+
+    /// Mark the innermost local `name` used, if there is one.
+    fn use_local(&mut self, name: &str) -> bool {
+        let local = self.scopes.iter_mut().rev().find_map(|scope| scope.iter_mut().rev().find(|l| l.name == name));
+        match local {
+            Some(local) => {
+                local.used = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record a use of the name `path`: a local, or something else.
+    fn refer(&mut self, path: &[String]) {
+        if let [name] = path
+            && self.use_local(name)
+        {
+            return;
+        }
+        self.names.extend(path.first().into_iter().chain(path.last()).cloned());
+    }
+
+    fn bind(&mut self, pattern: &'a Pattern, what: &'static str) {
+        match &pattern.kind {
+            PatternKind::Ident(name) => self.declare(name, what, pattern.span, false),
+            PatternKind::Tuple(patterns) | PatternKind::Slice(patterns) => {
+                patterns.iter().for_each(|p| self.bind(p, what));
+            }
+            PatternKind::Enum { path, variant, fields } => {
+                self.names.extend(path.first().cloned());
+                self.names.insert(variant.clone());
+                fields.iter().for_each(|p| self.bind(p, what));
+            }
+            PatternKind::Struct { path, fields } => {
+                self.refer(path);
+                for field in fields {
+                    match &field.pattern {
+                        Some(p) => self.bind(p, what),
+                        None => self.declare(&field.name, what, field.span, true),
+                    }
+                }
+            }
+            // Every alternative binds the same names.
+            PatternKind::Or(patterns) => patterns.iter().take(1).for_each(|p| self.bind(p, what)),
+            PatternKind::Guard { pattern, condition } => {
+                self.bind(pattern, what);
+                self.expr(condition);
+            }
+            PatternKind::Wild | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+        }
+    }
+
+    fn fields(&mut self, fields: &'a StructFields) {
+        match fields {
+            StructFields::Named(fields) => fields.iter().for_each(|field| self.ty(&field.ty)),
+            StructFields::Unnamed(types) => types.iter().for_each(|ty| self.ty(ty)),
+            StructFields::Unit => {}
+        }
+    }
+
+    fn ty(&mut self, ty: &'a Type) {
+        match &ty.kind {
+            TypeKind::Named { path, generics } => {
+                self.names.extend(path.first().into_iter().chain(path.last()).cloned());
+                generics.iter().for_each(|ty| self.ty(ty));
+            }
+            TypeKind::Array { element, .. } | TypeKind::Slice { element } => self.ty(element),
+            TypeKind::Reference { target, .. } | TypeKind::Pointer { target, .. } => self.ty(target),
+            TypeKind::Function { params, return_type, .. } => {
+                params.iter().for_each(|ty| self.ty(ty));
+                self.ty(return_type);
+            }
+            TypeKind::Tuple(elems) => elems.iter().for_each(|ty| self.ty(ty)),
+            _ => {}
+        }
+    }
+
+    /// A macro not expanded: any of its tokens may be a name it uses.
+    fn macro_call(&mut self, path: &[String], args: &[MacroArg]) {
+        self.names.extend(path.first().cloned());
+        for token in args.iter().flat_map(|arg| &arg.tokens) {
+            if token.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                self.refer(std::slice::from_ref(token));
+            }
+        }
+    }
+
+    fn block(&mut self, block: &'a Block) {
+        self.scopes.push(Vec::new());
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Let { pattern, ty, initializer, .. } => {
+                    ty.iter().for_each(|ty| self.ty(ty));
+                    initializer.iter().for_each(|e| self.expr(e));
+                    self.bind(pattern, "variable");
+                }
+                StmtKind::Expr(expr) | StmtKind::Defer { body: expr } => self.expr(expr),
+                StmtKind::Item(item) => self.items.push(item),
+                StmtKind::Macro { path, args } => self.macro_call(path, args),
+            }
+        }
+        if let Some(expr) = &block.expr {
+            self.expr(expr);
+        }
+        self.pop();
+    }
+
+    fn expr(&mut self, expr: &'a Expr) {
+        match &expr.kind {
+            ExprKind::Variable { path } => self.refer(path),
+            ExprKind::Macro { path, args } => self.macro_call(path, args),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Cast { expr: inner, target_type } => {
+                self.ty(target_type);
+                self.expr(inner);
+            }
+            ExprKind::Closure { params, return_type, body, .. } => {
+                self.scopes.push(Vec::new());
+                for param in params {
+                    param.ty.iter().for_each(|ty| self.ty(ty));
+                    self.bind(&param.pattern, "parameter");
+                }
+                return_type.iter().for_each(|ty| self.ty(ty));
+                self.expr(body);
+                self.pop();
+            }
+            ExprKind::For { pattern, iterable, body, .. } => {
+                self.expr(iterable);
+                self.scopes.push(Vec::new());
+                self.bind(pattern, "variable");
+                self.expr(body);
+                self.pop();
+            }
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    self.bind(&arm.pattern, "variable");
+                    arm.guard.iter().for_each(|e| self.expr(e));
+                    self.expr(&arm.body);
+                    self.pop();
+                }
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.expr(receiver);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::Binary { left: a, right: b, .. }
+            | ExprKind::Index { object: a, index: b }
+            | ExprKind::Assign { target: a, value: b, .. }
+            | ExprKind::While { condition: a, body: b, .. } => {
+                self.expr(a);
+                self.expr(b);
+            }
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                else_branch.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::Range { start, end, .. } => start.iter().chain(end).for_each(|e| self.expr(e)),
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => value.iter().for_each(|e| self.expr(e)),
+            ExprKind::Tuple(elems) => elems.iter().for_each(|e| self.expr(e)),
+            ExprKind::Array { elements, repeat } => {
+                elements.iter().for_each(|e| self.expr(e));
+                repeat.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::Struct { path, fields, base } => {
+                self.refer(path);
+                for field in fields {
+                    match &field.value {
+                        Some(value) => self.expr(value),
+                        None => self.refer(std::slice::from_ref(&field.name)),
+                    }
+                }
+                base.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Loop { body: inner, .. }
+            | ExprKind::Async { body: inner, .. }
+            | ExprKind::Spawn { body: inner }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner } => self.expr(inner),
+            ExprKind::Literal(_) | ExprKind::Continue { .. } | ExprKind::Error => {}
+        }
+    }
+}
+
+/// Where the name of the function `name` is written in `item`, or the whole
+/// item if it cannot be found. A method is looked for in its `impl`.
+pub(crate) fn name_span(source: &str, item: &Item, name: &str) -> SourceSpan {
+    let start = item.span.offset();
+    let text = source.get(start..start + item.span.len()).unwrap_or_default();
+    match text.find(&format!("fn {}", name)) {
+        Some(at) => SourceSpan::new((start + at + 3).into(), name.len()),
+        None => item.span,
+    }
+}
+
+/// The source to delete to remove `item`: the item with its attributes and,
+/// when nothing else is on them, the lines it takes up.
+fn removal(source: &str, item: &Item) -> SourceSpan {
+    let attrs = item.attrs.iter().map(|attr| attr.span.offset());
+    let mut start = attrs.chain([item.span.offset()]).min().unwrap_or_default();
+    let mut end = item.span.offset() + item.span.len();
+    let line_start = source[..start].rfind('\n').map_or(0, |at| at + 1);
+    let rest = &source[end..];
+    let line_end = rest.find('\n').map_or(rest.len(), |at| at + 1);
+    if source[line_start..start].trim().is_empty() && rest[..line_end].trim().is_empty() {
+        start = line_start;
+        end += line_end;
+    }
+    SourceSpan::new(start.into(), end - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+
+    /// The messages of the warnings for `source`, and the source with every
+    /// fix applied.
+    fn lint(source: &str) -> (Vec<String>, String) {
+        let program = parse_source(source).unwrap();
+        let collector = lint_program(&program, source);
+        let warnings: Vec<&TlError> = collector.diagnostics().iter().map(|(_, warning)| warning).collect();
+        let mut fixed = source.to_string();
+        for fix in warnings.iter().rev().filter_map(|warning| warning.fix()) {
+            let start = fix.span.offset();
+            fixed.replace_range(start..start + fix.span.len(), &fix.replacement);
+        }
+        (warnings.iter().map(|warning| warning.to_string()).collect(), fixed)
+    }
+
+    #[test]
+    fn unused_locals_are_renamed() {
+        let source = "fn main() {\n    let x = 1;\n    let y = 2;\n    let _z = 3;\n    print(add(y, 4));\n}\n\n\
+                      fn add(a: i32, b: i32) -> i32 { a }\n";
+        let (warnings, fixed) = lint(source);
+        assert_eq!(warnings, ["Unused variable `x`", "Unused parameter `b`"]);
+        assert!(fixed.contains("let _x = 1;") && fixed.contains("_b: i32"), "{}", fixed);
+        assert!(lint(&fixed).0.is_empty());
+    }
+
+    #[test]
+    fn shadowed_and_nested_locals_are_told_apart() {
+        let source = "fn main() {\n    let n = 1;\n    let n = n + 1;\n    for i in 0..n { let m = 2; }\n}\n";
+        let (warnings, _) = lint(source);
+        assert_eq!(warnings, ["Unused variable `i`", "Unused variable `m`"]);
+    }
+
+    #[test]
+    fn unused_imports_are_removed() {
+        let source = "use std::io;\nuse std::fs;\n\nfn main() {\n    fs::read(\"a\");\n}\n";
+        let (warnings, fixed) = lint(source);
+        assert_eq!(warnings, ["Unused import `io`"]);
+        assert_eq!(fixed, "use std::fs;\n\nfn main() {\n    fs::read(\"a\");\n}\n");
+    }
+
+    #[test]
+    fn functions_unreachable_from_main_or_public_ones_are_dead() {
+        let source = "fn main() { used(); }\n\
+                      fn used() { }\n\
+                      pub fn api() -> i32 { helper() }\n\
+                      fn helper() -> i32 { 1 }\n\
+                      /// Calls itself, but nothing calls it.\n\
+                      fn lonely() { lonely(); }\n\
+                      fn _kept() { }\n\
+                      #[bench]\n\
+                      fn bench_used() { used(); }\n";
+        let (warnings, fixed) = lint(source);
+        assert_eq!(warnings, ["Function `lonely` is never used"]);
+        assert!(!fixed.contains("lonely") && fixed.contains("fn helper() -> i32 { 1 }\nfn _kept"), "{}", fixed);
+    }
+}
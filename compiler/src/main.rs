@@ -3,16 +3,36 @@
 //!
 //! Usage:
 //!     cargo run --bin compiler -- <input_file.t> [--out-dir <directory>]
+//!         [-O <level>] [--enable-opt <name>] [--disable-opt <name>]
+//!         [--target-triple <triple>] [--profile hosted|embedded|safety-critical]
+//!         [--backend-opt <key>=<value>]...
+//!         [--stack-size <bytes>] [--max-call-depth <frames>]
+//!         [--allow <lint>] [--warn <lint>] [--deny <lint>] [--safety-report <path>]
+//!         [--time-passes] [--memory-report] [--stats-format table|json]
+//!     cargo run --bin compiler -- --list-optimizers [-O <level>]
 //!
-//! Reads `<input_file.t>`, compiles to `CompiledModule` (stub), then for each
-//! registered backend (via plugin_api), calls `backend.compile(...)` and writes
-//! the resulting IR blob to `<out-dir>/<backend_name>.bin`.
+//! Reads `<input_file.t>`, compiles it to a `CompiledModule`, running the
+//! safety analyses on the way, whose warnings it prints, whose errors fail
+//! it and whose `--safety-report` it writes, then runs the registered
+//! optimizers enabled for the optimization level, then for each
+//! registered backend (via plugin_api), calls `backend.compile_erased(...)`,
+//! writes the resulting artifact to `<out-dir>/<target>/<suggested_filename>`
+//! and prints the commands that build and run it. `--time-passes` and
+//! `--memory-report` print what each of those steps cost afterwards, and
+//! what the optimizers counted, such as the call sites they inlined.
+//!
+//! Backend options come from the `[backend-options]` table of a
+//! `tlang.toml` next to the input file, overridden by `--backend-opt`.
 
 use anyhow::{bail, Context, Result};
-use plugin_api::{CompiledModule, list_backends};
-use shared::fs::read_to_string;
+use compiler::safety::{lint_named, LintLevel, SafetyConfig, SafetyProfile};
+use compiler::{CompilationStats, CompilerOptions, Profile};
+use plugin_api::{CompiledArtifact, list_backends, list_optimizers, optimizer_enabled};
+use serde::Deserialize;
+use shared::source::diagnostic;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
-use std::fs;
+use std::fs::{self, read_to_string};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
@@ -20,17 +40,50 @@ use std::path::{Path, PathBuf};
 struct Config {
     input_path: PathBuf,
     out_dir: PathBuf,
+    opt_level: u8,
+    /// Explicit optimizer overrides from `--enable-opt` / `--disable-opt`.
+    optimizers: HashMap<String, bool>,
+    list_optimizers: bool,
+    /// Cross-compilation target; only backends that support it run.
+    target_triple: Option<String>,
+    profile: Profile,
+    stack_size: Option<usize>,
+    max_call_depth: Option<usize>,
+    /// `key=value` settings handed to every backend.
+    backend_options: BTreeMap<String, String>,
+    /// Safety lint levels: the profile's, then `--allow` / `--warn` / `--deny`.
+    safety: SafetyConfig,
+    /// Where to write the safety report (`.html` for HTML, JSON otherwise).
+    safety_report: Option<PathBuf>,
+    /// Print the time each step took.
+    time_passes: bool,
+    /// Print the memory each step used.
+    memory_report: bool,
+    /// Print those as JSON instead of a table.
+    stats_json: bool,
 }
 
 impl Config {
     fn parse_args() -> Result<Self> {
         let mut args = env::args().skip(1); // skip binary name
 
-        // Expect at least one argument: the input .t file.
-        let input = args.next().context("Expected path to <input_file.t>")?;
+        let mut input = None;
         let mut out_dir = PathBuf::from("out"); // default “out” directory
+        let mut opt_level = 1;
+        let mut optimizers = HashMap::new();
+        let mut list_optimizers = false;
+        let mut target_triple = None;
+        let mut profile = Profile::Hosted;
+        let mut stack_size = None;
+        let mut max_call_depth = None;
+        let mut cli_backend_options = Vec::new();
+        let mut safety_profile = SafetyProfile::Standard;
+        let mut lint_levels = Vec::new();
+        let mut safety_report = None;
+        let mut time_passes = false;
+        let mut memory_report = false;
+        let mut stats_json = false;
 
-        // Optional: allow “--out-dir <dir>”
         while let Some(arg) = args.next() {
             match arg.as_str() {
                 "--out-dir" => {
@@ -40,34 +93,234 @@ impl Config {
                         bail!("--out-dir requires a directory path");
                     }
                 }
+                "-O" | "--opt-level" => {
+                    let level = args.next().context("--opt-level requires a level (0-3)")?;
+                    opt_level = match level.parse::<u8>() {
+                        Ok(level) if level <= 3 => level,
+                        _ => bail!("Invalid optimization level: {}", level),
+                    };
+                }
+                "--enable-opt" | "--disable-opt" => {
+                    let name = args
+                        .next()
+                        .with_context(|| format!("{} requires an optimizer name", arg))?;
+                    optimizers.insert(name, arg == "--enable-opt");
+                }
+                "--list-optimizers" => list_optimizers = true,
+                "--target-triple" => {
+                    let triple = args.next().context("--target-triple requires a triple")?;
+                    target_triple = Some(triple);
+                }
+                "--profile" => {
+                    let name = args
+                        .next()
+                        .context("--profile requires hosted, embedded or safety-critical")?;
+                    match name.as_str() {
+                        "hosted" => profile = Profile::Hosted,
+                        "embedded" => profile = Profile::Embedded,
+                        // Orthogonal to where the program runs
+                        "safety-critical" => safety_profile = SafetyProfile::SafetyCritical,
+                        _ => bail!("Unknown profile: {}", name),
+                    }
+                }
+                "--stack-size" => {
+                    let bytes = args.next().context("--stack-size requires a size in bytes")?;
+                    let bytes = bytes
+                        .parse::<usize>()
+                        .with_context(|| format!("Invalid stack size: {}", bytes))?;
+                    stack_size = Some(bytes);
+                }
+                "--max-call-depth" => {
+                    let depth = args.next().context("--max-call-depth requires a number of frames")?;
+                    let depth = depth
+                        .parse::<usize>()
+                        .with_context(|| format!("Invalid call depth: {}", depth))?;
+                    max_call_depth = Some(depth);
+                }
+                "--backend-opt" => {
+                    let option = args.next().context("--backend-opt requires key=value")?;
+                    let Some((key, value)) = option.split_once('=') else {
+                        bail!("Invalid backend option, expected key=value: {}", option);
+                    };
+                    cli_backend_options.push((key.to_string(), value.to_string()));
+                }
+                "--allow" | "--warn" | "--deny" => {
+                    let name = args.next().with_context(|| format!("{} requires a lint name", arg))?;
+                    let lint = lint_named(&name).with_context(|| format!("Unknown safety lint: {}", name))?;
+                    let level = match arg.as_str() {
+                        "--allow" => LintLevel::Allow,
+                        "--warn" => LintLevel::Warn,
+                        _ => LintLevel::Deny,
+                    };
+                    lint_levels.push((lint, level));
+                }
+                "--safety-report" => {
+                    let path = args.next().context("--safety-report requires a file path")?;
+                    safety_report = Some(PathBuf::from(path));
+                }
+                "--time-passes" => time_passes = true,
+                "--memory-report" => memory_report = true,
+                "--stats-format" => {
+                    let format = args.next().context("--stats-format requires table or json")?;
+                    stats_json = match format.as_str() {
+                        "table" => false,
+                        "json" => true,
+                        _ => bail!("Unknown stats format: {}", format),
+                    };
+                }
+                unknown if unknown.starts_with('-') => {
+                    bail!("Unrecognized argument: {}", unknown);
+                }
+                path if input.is_none() => input = Some(PathBuf::from(path)),
                 unknown => {
                     bail!("Unrecognized argument: {}", unknown);
                 }
             }
         }
 
+        // The input file is only optional when just listing optimizers.
+        let input_path = match input {
+            Some(path) => path,
+            None if list_optimizers => PathBuf::new(),
+            None => bail!("Expected path to <input_file.t>"),
+        };
+
+        let mut backend_options = manifest_backend_options(&input_path)?;
+        backend_options.extend(cli_backend_options);
+
+        let mut safety = SafetyConfig::for_profile(safety_profile);
+        for (lint, level) in lint_levels {
+            safety.set(lint, level);
+        }
+
         Ok(Config {
-            input_path: PathBuf::from(input),
+            input_path,
             out_dir,
+            opt_level,
+            optimizers,
+            list_optimizers,
+            target_triple,
+            profile,
+            stack_size,
+            max_call_depth,
+            backend_options,
+            safety,
+            safety_report,
+            time_passes,
+            memory_report,
+            stats_json,
         })
     }
 }
 
+/// The part of a `tlang.toml` the compiler reads.
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default, rename = "backend-options")]
+    backend_options: BTreeMap<String, toml::Value>,
+}
+
+/// The `[backend-options]` of the `tlang.toml` beside `input`, if there is
+/// one. Values other than strings are passed on as TOML writes them.
+fn manifest_backend_options(input: &Path) -> Result<BTreeMap<String, String>> {
+    let path = input.parent().unwrap_or_else(|| Path::new(".")).join("tlang.toml");
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let text = read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    let manifest: Manifest = toml::from_str(&text).with_context(|| format!("Invalid manifest {:?}", path))?;
+    Ok(manifest
+        .backend_options
+        .into_iter()
+        .map(|(key, value)| match value {
+            toml::Value::String(text) => (key, text),
+            other => (key, other.to_string()),
+        })
+        .collect())
+}
+
+/// Reject `--enable-opt` / `--disable-opt` names that no optimizer registered.
+fn check_optimizer_names(cfg: &Config) -> Result<()> {
+    let known = list_optimizers();
+    for name in cfg.optimizers.keys() {
+        if !known.iter().any(|o| o.name() == name) {
+            bail!("Unknown optimizer: {}", name);
+        }
+    }
+    Ok(())
+}
+
+/// Print every registered optimizer and whether it runs with this configuration.
+fn print_optimizers(cfg: &Config) -> Result<()> {
+    for optimizer in list_optimizers() {
+        let state = if optimizer_enabled(optimizer, cfg.opt_level, &cfg.optimizers) {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        println!(
+            "{:<24} {:<8} (default from -O{})",
+            optimizer.name(),
+            state,
+            optimizer.min_opt_level()
+        );
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let cfg = Config::parse_args()?;
+    compiler::optimizer::register_builtin_optimizers();
+    check_optimizer_names(&cfg)?;
+    if cfg.list_optimizers {
+        return print_optimizers(&cfg);
+    }
 
     // 1. Read source
     let source = read_to_string(&cfg.input_path)
         .with_context(|| format!("Failed to read source file: {:?}", cfg.input_path))?;
 
-    // 2. Compile to bytecode (stub)
-    let module: CompiledModule = compiler::compile_source(&source)?;
+    let mut stats = CompilationStats::default();
+    let options = CompilerOptions {
+        target_triple: cfg.target_triple.clone(),
+        profile: cfg.profile,
+        stack_size: cfg.stack_size,
+        max_call_depth: cfg.max_call_depth,
+        backend_options: cfg.backend_options.clone(),
+        safety: cfg.safety.clone(),
+        safety_report: cfg.safety_report.clone(),
+        ..CompilerOptions::default()
+    };
+
+    // 2. Compile to bytecode, with the safety analyses and their report
+    let file = cfg.input_path.to_string_lossy();
+    let compiled = stats.measure("compile", || compiler::compile_module_with(&source, &file, &options));
+    let (module, diagnostics) = compiled.unwrap_or_else(|err| {
+        eprintln!("{}", diagnostic(&file, &source, &err));
+        std::process::exit(1)
+    });
+    for diagnostic in &diagnostics {
+        let code = diagnostic.code.as_deref().unwrap_or("safety");
+        eprintln!("{}[{}]: {}", format!("{:?}", diagnostic.level).to_lowercase(), code, diagnostic.message);
+    }
     let bc_len = module.bytecode.len();
     println!(
         "Compiled '{:?}' → {} bytes of bytecode.",
         cfg.input_path, bc_len
     );
 
+    // 2b. Run optimizer plugins, in registration order
+    let mut module = module;
+    for optimizer in list_optimizers() {
+        if optimizer_enabled(optimizer, cfg.opt_level, &cfg.optimizers) {
+            let name = format!("optimize {}", optimizer.name());
+            module = stats
+                .measure(name, || optimizer.optimize_at_level(module, cfg.opt_level))
+                .context("Optimizer failed")?;
+        }
+    }
+    stats.optimizations = module.optimizations.clone();
+
     // 3. Ensure output directory exists
     if !cfg.out_dir.exists() {
         fs::create_dir_all(&cfg.out_dir)
@@ -75,32 +328,81 @@ fn main() -> Result<()> {
     }
 
     // 4. Dispatch to each registered backend
+    let backend_config = options.backend_config();
+    compiler::backends::register_builtin_backends();
     for backend in list_backends() {
-        let ir = backend
-            .compile(module.clone())
-            .unwrap_or_else(|e| {
-                panic!("Backend '{}' failed to compile: {}", backend.name(), e);
-            });
-
-        // ir is `Box<dyn Any + Send + Sync>` –– assume each backend boxed a Vec<u8>.
-        let bytes: &Vec<u8> = ir
-            .downcast_ref::<Vec<u8>>()
-            .unwrap_or_else(|| panic!("Backend '{}' returned non-Vec<u8> IR", backend.name()));
-
-        let out_path = cfg.out_dir.join(format!("{}.bin", backend.name()));
-        let mut file = fs::File::create(&out_path)
-            .with_context(|| format!("Failed to create backend output file {:?}", out_path))?;
-        file.write_all(bytes)
-            .with_context(|| format!("Failed to write IR to {:?}", out_path))?;
+        if backend_config.target_triple.is_some()
+            && !backend.capabilities().supports_cross_compilation
+        {
+            println!("{} backend skipped: no cross-compilation support", backend.name());
+            continue;
+        }
+        backend
+            .initialize(&backend_config)
+            .with_context(|| format!("Backend '{}' rejected its options", backend.name()))?;
+        let artifact = stats
+            .measure(format!("backend {}", backend.name()), || {
+                backend.compile_erased_with(module.clone(), &backend_config)
+            })
+            .with_context(|| format!("Backend '{}' failed to compile", backend.name()))?;
+        let out_path = write_artifact(&cfg.out_dir, &artifact)?;
 
         println!(
-            "{} backend produced output ({} bytes) → {:?}",
-            backend.name(),
-            bytes.len(),
+            "{} backend produced {:?} output ({} bytes) → {:?}",
+            artifact.target,
+            artifact.format,
+            artifact.data.len(),
             out_path,
         );
+        print_build_instructions(&artifact, &out_path);
+    }
+
+    if cfg.time_passes || cfg.memory_report {
+        if cfg.stats_json {
+            println!("{}", stats.to_json());
+        } else {
+            print!("{}", stats.table(cfg.time_passes, cfg.memory_report));
+        }
     }
 
     Ok(())
 }
 
+/// Write `artifact` (and its extra files) to `<out_dir>/<target>/`.
+///
+/// Each target gets its own directory so that artifacts sharing a file name
+/// (`main.c`, `main.rs`, ...) and their build outputs do not collide.
+fn write_artifact(out_dir: &Path, artifact: &CompiledArtifact) -> Result<PathBuf> {
+    let target_dir = out_dir.join(&artifact.target);
+    fs::create_dir_all(&target_dir)
+        .with_context(|| format!("Failed to create output directory {:?}", target_dir))?;
+
+    for (filename, data) in artifact.files() {
+        write_file(&target_dir.join(filename), data)?;
+    }
+    Ok(target_dir.join(&artifact.suggested_filename))
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create output directory {:?}", parent))?;
+    }
+    let mut file = fs::File::create(path)
+        .with_context(|| format!("Failed to create backend output file {:?}", path))?;
+    file.write_all(data)
+        .with_context(|| format!("Failed to write artifact to {:?}", path))
+}
+
+/// Print the commands needed to build and run a written artifact.
+fn print_build_instructions(artifact: &CompiledArtifact, out_path: &Path) {
+    if artifact.build_commands.is_empty() {
+        return;
+    }
+    let dir = out_path.parent().unwrap_or_else(|| Path::new("."));
+    println!("    to build and run:");
+    println!("        cd {}", dir.display());
+    for command in &artifact.build_commands {
+        println!("        {}", command);
+    }
+}
@@ -0,0 +1,335 @@
+// compiler/src/metrics.rs
+//! Size and complexity metrics of functions, for `tlang lint --metrics`.
+//!
+//! Three numbers are measured for every function and method with a body:
+//!
+//! - its cyclomatic complexity, one more than the number of decisions it
+//!   makes: each `if`, `while` and `for`, each `match` arm after the first
+//!   and each arm guard, each `&&` and `||`, and each `?`;
+//! - its nesting depth, how many of those constructs and closures are open
+//!   at the deepest point of its body, with an `else if` as deep as its `if`;
+//! - its lines of code, the lines it spans that hold more than space and
+//!   comments.
+//!
+//! A function over one of the `Thresholds` is warned about, and the whole
+//! report can be written as JSON for dashboards to track.
+
+use crate::lint::name_span;
+use crate::resolve::type_name;
+use crate::safety::report::Location;
+use errors::{ErrorCollector, Severity};
+use miette::SourceSpan;
+use serde::Serialize;
+use shared::ast::stmt::ImplItem;
+use shared::ast::Block;
+use shared::source::line_col_from_offset;
+use shared::{BinaryOp, Expr, ExprKind, Item, ItemKind, Program, Result, StmtKind, TlError};
+use std::path::Path;
+
+/// The most each metric may be before a function is warned about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Thresholds {
+    pub complexity: usize,
+    pub nesting: usize,
+    pub lines: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds { complexity: 10, nesting: 4, lines: 50 }
+    }
+}
+
+/// What was measured of one function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FunctionMetrics {
+    /// The function's name; a method's is `Type::method`
+    pub name: String,
+    pub location: Location,
+    pub complexity: usize,
+    pub nesting: usize,
+    pub lines: usize,
+    /// Where the function's name is written
+    #[serde(skip)]
+    pub span: SourceSpan,
+}
+
+/// The metrics of every function of a program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetricsReport {
+    pub thresholds: Thresholds,
+    /// How many functions are over at least one threshold
+    pub over: usize,
+    pub functions: Vec<FunctionMetrics>,
+}
+
+impl MetricsReport {
+    /// Measure the functions of `program`, judging them by `thresholds`.
+    pub fn new(program: &Program, source: &str, thresholds: Thresholds) -> Self {
+        let mut functions = Vec::new();
+        collect(&program.items, "", source, &mut functions);
+        let mut report = MetricsReport { thresholds, over: 0, functions };
+        report.over = report.functions.iter().filter(|function| !report.excesses(function).is_empty()).count();
+        report
+    }
+
+    /// How `function` exceeds the thresholds, one phrase per metric.
+    fn excesses(&self, function: &FunctionMetrics) -> Vec<String> {
+        let limits = self.thresholds;
+        let mut excesses = Vec::new();
+        if function.complexity > limits.complexity {
+            excesses.push(format!("a cyclomatic complexity of {} (limit {})", function.complexity, limits.complexity));
+        }
+        if function.nesting > limits.nesting {
+            excesses.push(format!("nesting {} deep (limit {})", function.nesting, limits.nesting));
+        }
+        if function.lines > limits.lines {
+            excesses.push(format!("{} lines of code (limit {})", function.lines, limits.lines));
+        }
+        excesses
+    }
+
+    /// A warning for each function over a threshold, at `Severity::Warning`.
+    pub fn warnings(&self, source: &str) -> ErrorCollector {
+        let mut collector = ErrorCollector::new();
+        for function in &self.functions {
+            let excesses = self.excesses(function);
+            if excesses.is_empty() {
+                continue;
+            }
+            let message = format!("Function `{}` has {}", function.name, excesses.join(", "));
+            let _ = collector.add(TlError::complexity(source.to_string(), function.span, message), Severity::Warning);
+        }
+        collector
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a metrics report is always serializable")
+    }
+
+    /// Write the report to `path` as JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_json()).map_err(|error| {
+            TlError::io(format!("Failed to write metrics report {}", path.display()), Some(error))
+        })
+    }
+}
+
+/// Measure the functions among `items`, whose names start with `prefix`.
+fn collect(items: &[Item], prefix: &str, source: &str, functions: &mut Vec<FunctionMetrics>) {
+    for item in items {
+        match &item.kind {
+            ItemKind::Function { name, body: Some(body), .. } => {
+                functions.push(measure(&format!("{}{}", prefix, name), name, item, body, source));
+            }
+            ItemKind::Impl { self_ty, items: impl_items, .. } => {
+                for impl_item in impl_items {
+                    if let ImplItem::Function { name, body, .. } = impl_item {
+                        let full_name = format!("{}{}::{}", prefix, type_name(self_ty), name);
+                        functions.push(measure(&full_name, name, item, body, source));
+                    }
+                }
+            }
+            ItemKind::Module { name, items, .. } => collect(items, &format!("{}{}::", prefix, name), source, functions),
+            _ => {}
+        }
+    }
+}
+
+/// The metrics of the function `name` declared by `item`, whose body is
+/// `body`.
+fn measure(full_name: &str, name: &str, item: &Item, body: &Expr, source: &str) -> FunctionMetrics {
+    let span = name_span(source, item, name);
+    let (line, column) = line_col_from_offset(source, span.offset());
+    let mut decisions = Decisions { complexity: 1, depth: 0, nesting: 0 };
+    decisions.expr(body);
+    let start = item.span.offset();
+    let text = source.get(start..start + item.span.len()).unwrap_or_default();
+    FunctionMetrics {
+        name: full_name.to_string(),
+        location: Location { line, column },
+        complexity: decisions.complexity,
+        nesting: decisions.nesting,
+        lines: text.lines().filter(|line| is_code(line)).count(),
+        span,
+    }
+}
+
+/// Whether `line` holds more than space and comments.
+fn is_code(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with("//") && !line.starts_with("/*")
+}
+
+/// Counts the decisions of a body and how deeply they nest.
+struct Decisions {
+    complexity: usize,
+    /// How many constructs the walk is inside
+    depth: usize,
+    /// The deepest `depth` reached
+    nesting: usize,
+}
+
+impl Decisions {
+    /// Walk `expr` one construct deeper.
+    fn nested(&mut self, expr: &Expr) {
+        self.depth += 1;
+        self.nesting = self.nesting.max(self.depth);
+        self.expr(expr);
+        self.depth -= 1;
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Let { initializer: Some(expr), .. }
+                | StmtKind::Expr(expr)
+                | StmtKind::Defer { body: expr } => self.expr(expr),
+                StmtKind::Let { .. } | StmtKind::Item(_) | StmtKind::Macro { .. } => {}
+            }
+        }
+        if let Some(expr) = &block.expr {
+            self.expr(expr);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.complexity += 1;
+                self.expr(condition);
+                self.nested(then_branch);
+                match else_branch.as_deref() {
+                    // `else if` continues the chain rather than nesting in it.
+                    Some(branch @ Expr { kind: ExprKind::If { .. }, .. }) => self.expr(branch),
+                    Some(branch) => self.nested(branch),
+                    None => {}
+                }
+            }
+            ExprKind::While { condition, body, .. } => {
+                self.complexity += 1;
+                self.expr(condition);
+                self.nested(body);
+            }
+            ExprKind::For { iterable, body, .. } => {
+                self.complexity += 1;
+                self.expr(iterable);
+                self.nested(body);
+            }
+            ExprKind::Loop { body, .. } => self.nested(body),
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.complexity += arms.len().saturating_sub(1);
+                self.expr(scrutinee);
+                self.depth += 1;
+                self.nesting = self.nesting.max(self.depth);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.complexity += 1;
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                }
+                self.depth -= 1;
+            }
+            ExprKind::Closure { body, .. } => self.nested(body),
+            ExprKind::Binary { op, left, right } => {
+                if matches!(op, BinaryOp::And | BinaryOp::Or) {
+                    self.complexity += 1;
+                }
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Try { expr: inner } => {
+                self.complexity += 1;
+                self.expr(inner);
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.expr(receiver);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::Index { object: a, index: b } | ExprKind::Assign { target: a, value: b, .. } => {
+                self.expr(a);
+                self.expr(b);
+            }
+            ExprKind::Range { start, end, .. } => start.iter().chain(end).for_each(|e| self.expr(e)),
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => value.iter().for_each(|e| self.expr(e)),
+            ExprKind::Tuple(elems) => elems.iter().for_each(|e| self.expr(e)),
+            ExprKind::Array { elements, repeat } => {
+                elements.iter().for_each(|e| self.expr(e));
+                repeat.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::Struct { fields, base, .. } => {
+                fields.iter().filter_map(|f| f.value.as_ref()).for_each(|e| self.expr(e));
+                base.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Async { body: inner, .. }
+            | ExprKind::Spawn { body: inner }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Unsafe { body: inner }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner } => self.expr(inner),
+            ExprKind::Literal(_)
+            | ExprKind::Variable { .. }
+            | ExprKind::Continue { .. }
+            | ExprKind::Macro { .. }
+            | ExprKind::Error => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+
+    fn report(source: &str, thresholds: Thresholds) -> MetricsReport {
+        MetricsReport::new(&parse_source(source).unwrap(), source, thresholds)
+    }
+
+    #[test]
+    fn decisions_nesting_and_lines_are_counted() {
+        let source = "fn main() {\n    // Counts down.\n    let n = 3;\n\n    for i in 0..n {\n        \
+                      if i == 2 && n > 1 {\n            print(i);\n        \
+                      } else if i == 1 {\n            print(0);\n        }\n    }\n}\n\n\
+                      fn id(x: i32) -> i32 { x }\n";
+        let report = report(source, Thresholds::default());
+
+        let measured: Vec<_> = report
+            .functions
+            .iter()
+            .map(|function| (function.name.as_str(), function.complexity, function.nesting, function.lines))
+            .collect();
+        // `for`, `if`, `&&` and `else if`; the `else if` is no deeper.
+        assert_eq!(measured, [("main", 5, 2, 10), ("id", 1, 0, 1)]);
+        assert_eq!(report.functions[1].location, Location { line: 14, column: 4 });
+        assert_eq!(report.over, 0);
+        assert!(report.warnings(source).is_empty());
+    }
+
+    #[test]
+    fn functions_over_a_threshold_are_warned_about() {
+        let source =
+            "fn main() {\n    for i in 0..3 {\n        if i > 1 {\n            print(i);\n        }\n    }\n}\n";
+        let report = report(source, Thresholds { complexity: 2, nesting: 1, lines: 50 });
+
+        assert_eq!(report.over, 1);
+        let warnings: Vec<String> =
+            report.warnings(source).diagnostics().iter().map(|(_, warning)| warning.to_string()).collect();
+        assert_eq!(
+            warnings,
+            ["Function `main` has a cyclomatic complexity of 3 (limit 2), nesting 2 deep (limit 1)"]
+        );
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["functions"][0]["complexity"], 3);
+        assert_eq!(json["thresholds"]["nesting"], 1);
+    }
+}
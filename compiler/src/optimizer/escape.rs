@@ -0,0 +1,229 @@
+//! Escape analysis (`escape`), from `-O1`: a collection nothing outside
+//! its function can see is made by an `Alloca`, which the function frees
+//! when it returns, rather than left in `tstd`'s table for good.
+//!
+//! A collection is made by a call of its constructor, such as
+//! `tlang_vec_new`, which gives back its handle. The handle escapes if a
+//! value holding it, the call's or that of a load of a local a store of one
+//! put it in, is read by anything but such a store or as the first argument
+//! of a `tstd` function for its kind, such as `tlang_vec_push`: stored in
+//! the result local, passed to a `Call`, pushed into another collection,
+//! printed, compared or read by a terminator. A call in a loop is left as
+//! it is, since each iteration makes a collection the next one may still
+//! hold. The module counts the sites promoted as `promoted allocations`.
+
+use plugin_api::{BackendError, CompiledModule, ExternFunction, Optimizer};
+use shared::tir::cfg::Cfg;
+use shared::tir::{self, Allocation, FfiType, Function, InstId, InstKind, LocalId, FREE_COLLECTION};
+use std::collections::{HashMap, HashSet};
+
+/// What the module counts the allocations made `Alloca`s as.
+pub const PROMOTED_ALLOCATIONS: &str = "promoted allocations";
+
+/// Makes the collections that do not escape their function `Alloca`s.
+pub struct EscapeAnalysis;
+
+impl Optimizer for EscapeAnalysis {
+    fn name(&self) -> &'static str {
+        "escape"
+    }
+
+    fn optimize(&self, module: CompiledModule) -> Result<CompiledModule, BackendError> {
+        let mut tir = module.tir()?;
+        let promoted = promote_allocations(&mut tir);
+        if promoted == 0 {
+            return Ok(module);
+        }
+        let mut module = module.with_tir(&tir)?;
+        module.count(PROMOTED_ALLOCATIONS, promoted);
+        Ok(module)
+    }
+}
+
+/// Make each call of a collection's constructor in `module` whose
+/// collection does not escape the function an `Alloca`, declaring
+/// `FREE_COLLECTION` if there is one. Returns how many were.
+pub fn promote_allocations(module: &mut tir::Module) -> u64 {
+    let promoted: u64 = module.functions.iter_mut().map(promote_in).sum();
+    if promoted > 0 && module.extern_function(FREE_COLLECTION).is_none() {
+        module.externs.push(ExternFunction {
+            name: FREE_COLLECTION.to_string(),
+            params: vec![FfiType::INT],
+            return_type: None,
+            variadic: false,
+        });
+    }
+    promoted
+}
+
+/// Promote the allocations of `function` that do not escape it, outside
+/// its loops. Returns how many were.
+fn promote_in(function: &mut Function) -> u64 {
+    let loops = Cfg::new(function).loops();
+    let sites: Vec<(InstId, Allocation)> = function
+        .iter_blocks()
+        .filter(|&(id, _)| !loops.iter().any(|l| l.contains(id)))
+        .flat_map(|(_, block)| block.insts.iter().copied())
+        .filter_map(|inst| match &function.inst(inst).kind {
+            InstKind::CallExtern { name, args, .. } if args.is_empty() && function.inst(inst).ty.is_some() => {
+                Allocation::ALL.into_iter().find(|allocation| *name == allocation.constructor()).map(|a| (inst, a))
+            }
+            _ => None,
+        })
+        .collect();
+    if sites.is_empty() {
+        return 0;
+    }
+    let uses = Uses::of(function);
+    let mut promoted = 0;
+    for (site, allocation) in sites {
+        if !uses.escapes(function, site, allocation) {
+            function.insts[site.index()].kind = InstKind::Alloca(allocation);
+            promoted += 1;
+        }
+    }
+    promoted
+}
+
+/// Who reads what in a function.
+struct Uses {
+    /// The instructions reading each value
+    readers: HashMap<InstId, Vec<InstId>>,
+    /// The loads of each local
+    loads: HashMap<LocalId, Vec<InstId>>,
+    /// The values terminators read
+    terminators: HashSet<InstId>,
+}
+
+impl Uses {
+    fn of(function: &Function) -> Self {
+        let mut uses = Uses { readers: HashMap::new(), loads: HashMap::new(), terminators: HashSet::new() };
+        for (_, block) in function.iter_blocks() {
+            for &inst in &block.insts {
+                let kind = &function.inst(inst).kind;
+                if let InstKind::Load(local) = kind {
+                    uses.loads.entry(*local).or_default().push(inst);
+                }
+                for value in kind.operands() {
+                    uses.readers.entry(value).or_default().push(inst);
+                }
+            }
+            uses.terminators.extend(block.terminator.operands());
+        }
+        uses
+    }
+
+    /// Whether the handle of the collection of `allocation` that `site`
+    /// makes can be seen outside `function`.
+    fn escapes(&self, function: &Function, site: InstId, allocation: Allocation) -> bool {
+        let mut holding = vec![site];
+        let mut seen = HashSet::from([site]);
+        let mut locals = HashSet::new();
+        while let Some(value) = holding.pop() {
+            if self.terminators.contains(&value) {
+                return true;
+            }
+            for &reader in self.readers.get(&value).into_iter().flatten() {
+                match &function.inst(reader).kind {
+                    InstKind::Store { local, .. } if Some(*local) != function.result => {
+                        if locals.insert(*local) {
+                            let loads = self.loads.get(local).into_iter().flatten();
+                            holding.extend(loads.filter(|&&load| seen.insert(load)));
+                        }
+                    }
+                    InstKind::CallExtern { name, args, .. }
+                        if allocation.is_method(name) && args[0] == value && !args[1..].contains(&value) => {}
+                    _ => return true,
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{lower_program_with_options, LoweringOptions};
+    use crate::optimizer::register_builtin_optimizers;
+    use crate::{check_program, parse_source};
+
+    fn lower(source: &str) -> CompiledModule {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        lower_program_with_options(&program, source, "main.t", LoweringOptions::default()).unwrap()
+    }
+
+    /// How many `CallExtern`s of `name` each function of `module` has.
+    fn calls(module: &tir::Module, name: &str) -> Vec<usize> {
+        let calls = |function: &Function| {
+            let calls_name = |inst: &InstId| {
+                matches!(&function.inst(*inst).kind, InstKind::CallExtern { name: called, .. } if called == name)
+            };
+            function.blocks.iter().flat_map(|block| &block.insts).filter(|inst| calls_name(inst)).count()
+        };
+        module.functions.iter().map(calls).collect()
+    }
+
+    const COLLECTIONS: &str = "fn squares(n: i64) -> i64 {\n    let v = Vec::new();\n    let mut i = 0;\n    while i < n {\n        v.push(i * i);\n        i += 1;\n    }\n    if n > 2 {\n        return v.get(2);\n    };\n    v.len() as i64\n}\n\nfn distinct(a: i64, b: i64) -> i64 {\n    let s = HashSet::new();\n    s.insert(a);\n    s.insert(b);\n    s.len() as i64\n}\n\nfn main() {\n    println(squares(2), \" \", squares(5), \" \", distinct(3, 3));\n}\n";
+
+    #[test]
+    fn collections_that_stay_in_their_function_are_promoted() {
+        let mut tir = lower(COLLECTIONS).tir().unwrap();
+        assert_eq!(promote_allocations(&mut tir), 2);
+        let promoted: Vec<Vec<Allocation>> = tir
+            .functions
+            .iter()
+            .map(|function| {
+                let allocas = function.insts.iter().filter_map(|inst| match inst.kind {
+                    InstKind::Alloca(allocation) => Some(allocation),
+                    _ => None,
+                });
+                allocas.collect()
+            })
+            .collect();
+        assert_eq!(promoted, [vec![], vec![Allocation::Vec], vec![Allocation::Set]]);
+        assert_eq!(tir::verify::verify(&tir), Ok(()));
+
+        // Emitted, each Alloca is a call of its constructor again, and the
+        // function frees it where it returns.
+        let module = CompiledModule::from_tir(&tir, Default::default()).unwrap().tir().unwrap();
+        assert_eq!(calls(&module, "tlang_vec_new"), [0, 1, 0]);
+        assert_eq!(calls(&module, FREE_COLLECTION), [0, 1, 1]);
+    }
+
+    #[test]
+    fn handles_read_elsewhere_escape() {
+        // Only %2, which %0 is pushed into, stays in `make`: %0 is in
+        // another collection, %5 is returned, %7 printed and %9 pushed
+        // into itself.
+        let text = "extern fn tlang_vec_new() -> i64\nextern fn tlang_vec_push(i64, i64)\n\nfn main {\nbb0:\n    %0: i64 = call @make()\n    return\n}\n\nfn make() -> l0 {\n    local l0: i64\n    local l1: i64\nbb0:\n    %0: i64 = call tlang_vec_new()\n    %1 = store l1, %0\n    %2: i64 = call tlang_vec_new()\n    %3: i64 = load l1\n    %4 = call tlang_vec_push(%2: i64, %3: i64)\n    %5: i64 = call tlang_vec_new()\n    %6 = store l0, %5\n    %7: i64 = call tlang_vec_new()\n    %8 = print %7\n    %9: i64 = call tlang_vec_new()\n    %10 = call tlang_vec_push(%9: i64, %9: i64)\n    return\n}\n";
+        let mut tir: tir::Module = text.parse().unwrap();
+        assert_eq!(promote_allocations(&mut tir), 1);
+        let make = &tir.functions[1];
+        let allocas: Vec<InstId> = (0..make.insts.len() as u32)
+            .map(InstId)
+            .filter(|&inst| matches!(make.inst(inst).kind, InstKind::Alloca(_)))
+            .collect();
+        assert_eq!(allocas, [InstId(2)]);
+        assert!(tir.extern_function(FREE_COLLECTION).is_some());
+    }
+
+    #[test]
+    fn allocations_in_loops_stay() {
+        let source = "fn count(n: i64) -> i64 {\n    let mut total: i64 = 0;\n    let mut i = 0;\n    while i < n {\n        let v = Vec::new();\n        v.push(i);\n        total += v.len() as i64;\n        i += 1;\n    }\n    total\n}\n\nfn main() {\n    println(count(3));\n}\n";
+        let optimized = EscapeAnalysis.optimize(lower(source)).unwrap();
+        assert!(optimized.optimizations.is_empty());
+    }
+
+    #[test]
+    fn escape_runs_from_o1_and_counts_the_sites() {
+        register_builtin_optimizers();
+        let overrides = HashMap::new();
+        assert!(!plugin_api::optimizer_enabled(&EscapeAnalysis, 0, &overrides));
+        assert!(plugin_api::optimizer_enabled(&EscapeAnalysis, 1, &overrides));
+        let optimized = EscapeAnalysis.optimize(lower(COLLECTIONS)).unwrap();
+        assert_eq!(optimized.optimizations[PROMOTED_ALLOCATIONS], 2);
+        assert!(optimized.externs.iter().any(|function| function.name == FREE_COLLECTION));
+    }
+}
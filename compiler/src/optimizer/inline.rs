@@ -0,0 +1,306 @@
+//! Inlining (`inline`), from `-O1`: a `Call` of one of the module's
+//! functions is replaced by a copy of the function's body.
+//!
+//! The copy has locals of its own, the arguments stored in those of the
+//! parameters, and each `Return` in it jumps on to what followed the call,
+//! which loads the value returned from the copy's result local. A function
+//! marked `#[inline]` is inlined wherever it is called, one marked
+//! `#[inline(never)]` nowhere, and any other where it has at most
+//! `size_limit` instructions, more the higher the optimization level.
+//! A function that calls itself, directly or through others, is never
+//! inlined, which is what keeps inlining from going on forever. Functions
+//! are inlined into before the functions calling them, so a caller gets
+//! the copy with their own calls inlined, and those no call is left to are
+//! dropped. The module counts the call sites inlined as `inlined call
+//! sites`.
+
+use plugin_api::{BackendError, CompiledModule, Optimizer};
+use shared::tir::{self, BlockId, Function, Inst, InstId, InstKind, LocalId, Terminator};
+use std::collections::{HashMap, HashSet};
+
+/// What the module counts the inlined call sites as.
+pub const INLINED_CALL_SITES: &str = "inlined call sites";
+
+/// Replaces calls of small functions, and of those marked `#[inline]`,
+/// with their bodies.
+pub struct Inliner;
+
+impl Optimizer for Inliner {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn optimize(&self, module: CompiledModule) -> Result<CompiledModule, BackendError> {
+        self.optimize_at_level(module, self.min_opt_level())
+    }
+
+    fn optimize_at_level(&self, module: CompiledModule, opt_level: u8) -> Result<CompiledModule, BackendError> {
+        let mut tir = module.tir()?;
+        let inlined = inline_calls(&mut tir, size_limit(opt_level));
+        if inlined == 0 {
+            return Ok(module);
+        }
+        let mut module = module.with_tir(&tir)?;
+        module.count(INLINED_CALL_SITES, inlined);
+        Ok(module)
+    }
+}
+
+/// Most instructions a function without an `#[inline]` attribute may have
+/// to be inlined at `opt_level`.
+pub fn size_limit(opt_level: u8) -> usize {
+    match opt_level {
+        0 | 1 => 16,
+        2 => 48,
+        _ => 160,
+    }
+}
+
+/// Inline the calls of `module` that call a function `#[inline]` asks to,
+/// or one of at most `limit` instructions that it leaves to the optimizer,
+/// unless the function calls itself, then drop the functions nothing calls
+/// any more. Returns how many call sites were inlined.
+pub fn inline_calls(module: &mut tir::Module, limit: usize) -> u64 {
+    let recursive = recursive_functions(module);
+    let inlinable: HashSet<String> = module
+        .functions
+        .iter()
+        .skip(1)
+        .filter(|function| !recursive.contains(&function.name))
+        .filter(|function| match function.inline {
+            tir::Inline::Always => true,
+            tir::Inline::Never => false,
+            tir::Inline::Auto => size(function) <= limit,
+        })
+        .map(|function| function.name.clone())
+        .collect();
+    let mut inlined = 0;
+    for caller in callees_first(module) {
+        while let Some((block, at, name)) = next_call(&module.functions[caller], &inlinable) {
+            let callee = module.function(&name).expect("a call names a function of the module").clone();
+            inline_call(&mut module.functions[caller], block, at, &callee);
+            inlined += 1;
+        }
+    }
+    if inlined > 0 {
+        drop_uncalled(module);
+    }
+    inlined
+}
+
+/// How many instructions `function`'s blocks hold, but for `Marker`s,
+/// which emit nothing.
+fn size(function: &Function) -> usize {
+    placed(function).filter(|&inst| !matches!(function.inst(inst).kind, InstKind::Marker(_))).count()
+}
+
+/// The instructions the blocks of `function` hold, in layout order.
+fn placed(function: &Function) -> impl Iterator<Item = InstId> + '_ {
+    function.blocks.iter().flat_map(|block| block.insts.iter().copied())
+}
+
+/// The names of the functions `function` calls, in the order it calls them.
+fn callees(function: &Function) -> Vec<&str> {
+    placed(function)
+        .filter_map(|inst| match &function.inst(inst).kind {
+            InstKind::Call { function, .. } => Some(function.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The functions of `module` that can call themselves, directly or
+/// through others.
+fn recursive_functions(module: &tir::Module) -> HashSet<String> {
+    let calls: HashMap<&str, Vec<&str>> =
+        module.functions.iter().map(|function| (function.name.as_str(), callees(function))).collect();
+    let mut recursive = HashSet::new();
+    for function in &module.functions {
+        let mut seen = HashSet::new();
+        let mut work = calls[function.name.as_str()].clone();
+        while let Some(name) = work.pop() {
+            if name == function.name {
+                recursive.insert(function.name.clone());
+                break;
+            }
+            if seen.insert(name) {
+                work.extend(calls.get(name).into_iter().flatten().copied());
+            }
+        }
+    }
+    recursive
+}
+
+/// Indices of the functions of `module` with those each calls before it,
+/// where they do not call it back.
+fn callees_first(module: &tir::Module) -> Vec<usize> {
+    fn visit(module: &tir::Module, i: usize, seen: &mut [bool], order: &mut Vec<usize>) {
+        if std::mem::replace(&mut seen[i], true) {
+            return;
+        }
+        for name in callees(&module.functions[i]) {
+            if let Some(callee) = module.functions.iter().position(|function| function.name == name) {
+                visit(module, callee, seen, order);
+            }
+        }
+        order.push(i);
+    }
+    let mut seen = vec![false; module.functions.len()];
+    let mut order = Vec::with_capacity(module.functions.len());
+    for i in 0..module.functions.len() {
+        visit(module, i, &mut seen, &mut order);
+    }
+    order
+}
+
+/// The first call in `function` of one of `inlinable`, other than itself:
+/// its block, its position in the block and the function it calls.
+fn next_call(function: &Function, inlinable: &HashSet<String>) -> Option<(BlockId, usize, String)> {
+    function.iter_blocks().find_map(|(id, block)| {
+        block.insts.iter().enumerate().find_map(|(at, &inst)| match &function.inst(inst).kind {
+            InstKind::Call { function: name, .. } if *name != function.name && inlinable.contains(name) => {
+                Some((id, at, name.clone()))
+            }
+            _ => None,
+        })
+    })
+}
+
+/// Replace the call at position `at` of `block` in `caller` by a copy of
+/// `callee`'s body.
+fn inline_call(caller: &mut Function, block: BlockId, at: usize, callee: &Function) {
+    let call = caller.block(block).insts[at];
+    let InstKind::Call { args, .. } = caller.inst(call).kind.clone() else {
+        return;
+    };
+    let returned = caller.inst(call).ty;
+
+    // What follows the call moves to a block of its own, the copy's Returns
+    // jump to.
+    let rest = caller.add_block();
+    let after = caller.blocks[block.index()].insts.split_off(at + 1);
+    caller.blocks[block.index()].insts.pop();
+    caller.blocks[rest.index()].insts = after;
+    let terminator = std::mem::replace(&mut caller.blocks[block.index()].terminator, Terminator::Unreachable);
+    caller.terminate(rest, terminator);
+
+    let locals: Vec<LocalId> = callee.locals.iter().map(|&ty| caller.add_local(ty)).collect();
+    for (&local, value) in locals.iter().zip(args) {
+        caller.push(block, InstKind::Store { local, value }, None);
+    }
+    let blocks: Vec<BlockId> = callee.blocks.iter().map(|_| caller.add_block()).collect();
+    caller.terminate(block, Terminator::Jump(blocks[0]));
+
+    // A value may be read in a block laid out before the one defining it,
+    // so the operands are mapped once every instruction is copied.
+    let mut values = HashMap::new();
+    for (id, callee_block) in callee.iter_blocks() {
+        for &inst in &callee_block.insts {
+            let Inst { kind, ty } = callee.inst(inst).clone();
+            values.insert(inst, caller.push(blocks[id.index()], kind, ty));
+        }
+    }
+    for &copy in values.values() {
+        let kind = &mut caller.insts[copy.index()].kind;
+        kind.map_operands(|value| values[&value]);
+        match kind {
+            InstKind::Load(local) | InstKind::Store { local, .. } => *local = locals[local.index()],
+            _ => {}
+        }
+    }
+    for (id, callee_block) in callee.iter_blocks() {
+        let mut terminator = callee_block.terminator.clone();
+        terminator.map_operands(|value| values[&value]);
+        terminator.map_successors(|target| blocks[target.index()]);
+        if terminator == Terminator::Return {
+            terminator = Terminator::Jump(rest);
+        }
+        caller.terminate(blocks[id.index()], terminator);
+    }
+
+    // What read the call's value reads the copy's result local.
+    if let (Some(ty), Some(result)) = (returned, callee.result) {
+        let load = InstId(caller.insts.len() as u32);
+        caller.insts.push(Inst { kind: InstKind::Load(locals[result.index()]), ty: Some(ty) });
+        caller.blocks[rest.index()].insts.insert(0, load);
+        let read = |value: InstId| if value == call { load } else { value };
+        for inst in &mut caller.insts {
+            inst.kind.map_operands(read);
+        }
+        for block in &mut caller.blocks {
+            block.terminator.map_operands(read);
+        }
+    }
+    caller.compact();
+}
+
+/// Drop the functions of `module` that neither the entry point nor what it
+/// calls calls.
+fn drop_uncalled(module: &mut tir::Module) {
+    let Some(entry) = module.functions.first() else {
+        return;
+    };
+    let mut called = HashSet::from([entry.name.clone()]);
+    let mut work = vec![entry];
+    while let Some(function) = work.pop() {
+        for name in callees(function) {
+            if called.insert(name.to_string())
+                && let Some(callee) = module.function(name)
+            {
+                work.push(callee);
+            }
+        }
+    }
+    module.functions.retain(|function| called.contains(&function.name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{lower_program_with_options, LoweringOptions};
+    use crate::optimizer::register_builtin_optimizers;
+    use crate::{check_program, parse_source};
+
+    fn lower(source: &str) -> CompiledModule {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        lower_program_with_options(&program, source, "main.t", LoweringOptions::default()).unwrap()
+    }
+
+    fn functions(module: &CompiledModule) -> Vec<String> {
+        module.tir().unwrap().functions.into_iter().map(|function| function.name).collect()
+    }
+
+    const CALLS: &str = "fn double(n: i64) -> i64 {\n    n * 2\n}\n\n#[inline(never)]\nfn show(n: i64) {\n    println(n);\n}\n\nfn fact(n: i64) -> i64 {\n    if n < 2 {\n        1\n    } else {\n        n * fact(n - 1)\n    }\n}\n\nfn main() {\n    let mut i = 0;\n    while i < 3 {\n        show(double(i));\n        i += 1;\n    }\n    println(fact(5));\n}\n";
+
+    #[test]
+    fn small_callees_are_inlined_and_dropped() {
+        let module = lower(CALLS);
+        assert_eq!(functions(&module), ["main", "double", "show", "fact"]);
+
+        let optimized = Inliner.optimize(module).unwrap();
+        // `show` is marked never to be, and `fact` calls itself.
+        assert_eq!(functions(&optimized), ["main", "show", "fact"]);
+        assert_eq!(optimized.optimizations[INLINED_CALL_SITES], 1);
+        assert_eq!(plugin_api::interpret(&optimized).unwrap(), "0\n2\n4\n120\n");
+    }
+
+    #[test]
+    fn the_limit_grows_with_the_level() {
+        let source = "fn mix(a: i64, b: i64) -> i64 {\n    let c = a * 3 + b;\n    let d = c * c - a;\n    d / 2 + c\n}\n\nfn main() {\n    let x = 4;\n    println(mix(x, 5), \" \", mix(1, x));\n}\n";
+        let at_o1 = Inliner.optimize_at_level(lower(source), 1).unwrap();
+        assert!(at_o1.optimizations.is_empty());
+        let at_o3 = Inliner.optimize_at_level(lower(source), 3).unwrap();
+        assert_eq!(at_o3.optimizations[INLINED_CALL_SITES], 2);
+        assert_eq!(functions(&at_o3), ["main"]);
+        assert_eq!(plugin_api::interpret(&at_o3).unwrap(), "159 31\n");
+    }
+
+    #[test]
+    fn inline_runs_from_o1() {
+        register_builtin_optimizers();
+        let overrides = HashMap::new();
+        assert!(!plugin_api::optimizer_enabled(&Inliner, 0, &overrides));
+        assert!(plugin_api::optimizer_enabled(&Inliner, 1, &overrides));
+    }
+}
@@ -0,0 +1,496 @@
+//! Loop optimizations, from `-O2`: loop-invariant code motion (`licm`) and
+//! strength reduction of induction-variable multiplies (`strength-reduce`).
+//!
+//! Both find natural loops with `tir::cfg::Cfg::loops`, innermost first,
+//! and put what runs once before a loop in its preheader: the block control
+//! enters the loop from, made for it if the loop is entered from more than
+//! one place or its predecessor goes elsewhere too. Both first forward each
+//! block's stores to its loads (`forward_stores`), since TIR lifted from
+//! stack code keeps every operand in a local of its own, which hides what
+//! the loop computes.
+
+use super::rewrite_functions;
+use plugin_api::{BackendError, CompiledModule, Optimizer};
+use shared::tir::cfg::{Cfg, Loop};
+use shared::tir::{ArithOp, BlockId, Constant, FfiType, Function, Inst, InstId, InstKind, LocalId, Terminator};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+/// Moves what a loop computes the same on every iteration to its
+/// preheader.
+pub struct Licm;
+
+impl Optimizer for Licm {
+    fn name(&self) -> &'static str {
+        "licm"
+    }
+
+    fn min_opt_level(&self) -> u8 {
+        2
+    }
+
+    fn optimize(&self, module: CompiledModule) -> Result<CompiledModule, BackendError> {
+        rewrite_functions(module, |function| forward_stores(function) | hoist_invariants(function))
+    }
+}
+
+/// Replaces multiplying a loop's induction variable by a constant with a
+/// local that grows by the product of the constant and each step.
+pub struct StrengthReduction;
+
+impl Optimizer for StrengthReduction {
+    fn name(&self) -> &'static str {
+        "strength-reduce"
+    }
+
+    fn min_opt_level(&self) -> u8 {
+        2
+    }
+
+    fn optimize(&self, module: CompiledModule) -> Result<CompiledModule, BackendError> {
+        rewrite_functions(module, |function| forward_stores(function) | reduce_multiplies(function))
+    }
+}
+
+/// Within each block, read a local's value where the block stored or
+/// loaded it last, rather than loading it again, then drop the stores to
+/// locals nothing loads any more, but for the result local a `Return`
+/// reads. Returns whether anything changed.
+pub fn forward_stores(function: &mut Function) -> bool {
+    let mut forwarded: HashMap<InstId, InstId> = HashMap::new();
+    let resolve = |forwarded: &HashMap<InstId, InstId>, mut value: InstId| {
+        while let Some(&to) = forwarded.get(&value) {
+            value = to;
+        }
+        value
+    };
+    for block in &function.blocks {
+        let mut known: HashMap<LocalId, InstId> = HashMap::new();
+        for &inst in &block.insts {
+            match function.insts[inst.index()].kind {
+                InstKind::Store { local, value } => {
+                    known.insert(local, resolve(&forwarded, value));
+                }
+                InstKind::Load(local) => match known.get(&local) {
+                    Some(&value) if function.ty(value) == function.insts[inst.index()].ty => {
+                        forwarded.insert(inst, value);
+                    }
+                    _ => {
+                        known.insert(local, inst);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    let mut changed = !forwarded.is_empty();
+    for inst in &mut function.insts {
+        inst.kind.map_operands(|value| resolve(&forwarded, value));
+    }
+    for block in &mut function.blocks {
+        block.terminator.map_operands(|value| resolve(&forwarded, value));
+        block.insts.retain(|inst| !forwarded.contains_key(inst));
+    }
+
+    let loaded: HashSet<LocalId> = placed(function)
+        .filter_map(|inst| match function.inst(inst).kind {
+            InstKind::Load(local) => Some(local),
+            _ => None,
+        })
+        .chain(function.result)
+        .collect();
+    for block in &mut function.blocks {
+        let before = block.insts.len();
+        block.insts.retain(|&inst| {
+            !matches!(function.insts[inst.index()].kind, InstKind::Store { local, .. } if !loaded.contains(&local))
+        });
+        changed |= block.insts.len() != before;
+    }
+    changed
+}
+
+/// Hoist the instructions of each loop that compute the same value on
+/// every iteration, and cannot fail or be seen, to its preheader. Returns
+/// whether anything moved.
+///
+/// Constants stay where they are, as a constant costs no more than the
+/// slot a hoisted one would be read from; a hoisted instruction reads a
+/// copy made in the preheader.
+pub fn hoist_invariants(function: &mut Function) -> bool {
+    let mut changed = false;
+    // A hoist can add a block, so the loops are found again after it.
+    'search: loop {
+        let cfg = Cfg::new(function);
+        for lp in cfg.loops() {
+            let hoisted = invariants(function, &cfg, &lp);
+            if hoisted.iter().all(|&inst| matches!(function.inst(inst).kind, InstKind::Const(_))) {
+                continue;
+            }
+            let preheader = preheader(function, &cfg, &lp);
+            let hoisted: HashSet<InstId> =
+                hoisted.into_iter().filter(|&inst| !matches!(function.inst(inst).kind, InstKind::Const(_))).collect();
+            let in_loop: HashSet<InstId> =
+                lp.blocks.iter().flat_map(|&block| function.block(block).insts.clone()).collect();
+            let mut copies: HashMap<InstId, InstId> = HashMap::new();
+            for &block in &lp.blocks {
+                let insts = std::mem::take(&mut function.blocks[block.index()].insts);
+                for &inst in insts.iter().filter(|inst| hoisted.contains(inst)) {
+                    let mut kind = function.inst(inst).kind.clone();
+                    let mut constants = Vec::new();
+                    kind.map_operands(|value| match function.inst(value).kind {
+                        InstKind::Const(_) if in_loop.contains(&value) => {
+                            constants.push(value);
+                            value
+                        }
+                        _ => value,
+                    });
+                    for value in constants {
+                        if let Entry::Vacant(entry) = copies.entry(value) {
+                            let Inst { kind, ty } = function.inst(value).clone();
+                            entry.insert(function.push(preheader, kind, ty));
+                        }
+                    }
+                    kind.map_operands(|value| copies.get(&value).copied().unwrap_or(value));
+                    function.insts[inst.index()].kind = kind;
+                    function.blocks[preheader.index()].insts.push(inst);
+                }
+                function.blocks[block.index()].insts.extend(insts.into_iter().filter(|inst| !hoisted.contains(inst)));
+            }
+            changed = true;
+            continue 'search;
+        }
+        return changed;
+    }
+}
+
+/// The instructions of `lp` that can run once before it instead, in an
+/// order that defines each value before it is read.
+fn invariants(function: &Function, cfg: &Cfg, lp: &Loop) -> Vec<InstId> {
+    let block_of = blocks_of(function);
+    let stored = stored_locals(function, &lp.blocks);
+    let mut hoisted = Vec::new();
+    let mut invariant = HashSet::new();
+    for &block in &lp.blocks {
+        for &inst in &function.block(block).insts {
+            let kind = &function.inst(inst).kind;
+            let outside = kind.operands().iter().all(|value| {
+                invariant.contains(value) || block_of.get(value).is_some_and(|block| !lp.contains(*block))
+            });
+            let pure = match *kind {
+                InstKind::Const(_) | InstKind::Compare { .. } | InstKind::Not(_) | InstKind::Overflows { .. } => true,
+                // C leaves converting an out-of-range float undefined; keep
+                // the conversion where it is checked.
+                InstKind::Cast { value, .. } => !matches!(function.ty(value), Some(FfiType::Float { .. })),
+                // Dividing by zero is only ever defined because a trap
+                // before it stops the program.
+                InstKind::Arith { op: ArithOp::Div | ArithOp::Rem, rhs, .. } => {
+                    matches!(function.inst(rhs).kind, InstKind::Const(Constant::Int(n)) if n != 0)
+                        || matches!(function.inst(rhs).kind, InstKind::Const(Constant::Float(_)))
+                }
+                InstKind::Arith { .. } => true,
+                InstKind::Load(local) => !stored.contains(&local) && stored_before(function, cfg, lp, local),
+                _ => false,
+            };
+            if pure && outside {
+                invariant.insert(inst);
+                hoisted.push(inst);
+            }
+        }
+    }
+    hoisted
+}
+
+/// Rewrite `counter * k`, for a constant `k` and a local `counter` that
+/// `lp` only ever adds a constant to or takes one from, to read a local
+/// that is `counter * k` throughout the loop and moves in steps of `k`
+/// times the counter's. Returns whether anything was rewritten.
+pub fn reduce_multiplies(function: &mut Function) -> bool {
+    let mut changed = false;
+    'search: loop {
+        let cfg = Cfg::new(function);
+        for lp in cfg.loops() {
+            let steps = induction_variables(function, &lp);
+            // Each counter and factor, and the multiplies of the one by the other.
+            let mut products: Vec<((LocalId, i64), Vec<InstId>)> = Vec::new();
+            for &block in &lp.blocks {
+                for (inst, counter, factor) in multiplies(function, block) {
+                    if steps.contains_key(&counter) && stored_before(function, &cfg, &lp, counter) {
+                        match products.iter_mut().find(|(key, _)| *key == (counter, factor)) {
+                            Some((_, insts)) => insts.push(inst),
+                            None => products.push(((counter, factor), vec![inst])),
+                        }
+                    }
+                }
+            }
+            if products.is_empty() {
+                continue;
+            }
+
+            let preheader = preheader(function, &cfg, &lp);
+            for ((counter, factor), multiplies) in products {
+                let product = function.add_local(FfiType::INT);
+                let value = function.push(preheader, InstKind::Load(counter), Some(FfiType::INT));
+                let k = function.push(preheader, InstKind::Const(Constant::Int(factor)), Some(FfiType::INT));
+                let start = InstKind::Arith { op: ArithOp::Mul, lhs: value, rhs: k };
+                let start = function.push(preheader, start, Some(FfiType::INT));
+                function.push(preheader, InstKind::Store { local: product, value: start }, None);
+                // After each step of the counter, the product takes its own.
+                for &(store, op, step) in &steps[&counter] {
+                    let by = step.wrapping_mul(factor);
+                    let old = add_after(function, store, InstKind::Load(product), Some(FfiType::INT));
+                    let by = add_after(function, old, InstKind::Const(Constant::Int(by)), Some(FfiType::INT));
+                    let new = add_after(function, by, InstKind::Arith { op, lhs: old, rhs: by }, Some(FfiType::INT));
+                    add_after(function, new, InstKind::Store { local: product, value: new }, None);
+                }
+                for inst in multiplies {
+                    function.insts[inst.index()] = Inst { kind: InstKind::Load(product), ty: Some(FfiType::INT) };
+                }
+            }
+            changed = true;
+            continue 'search;
+        }
+        return changed;
+    }
+}
+
+/// The locals `lp` changes only by adding or subtracting a constant, each
+/// with the stores that do, how, and by how much.
+fn induction_variables(function: &Function, lp: &Loop) -> HashMap<LocalId, Vec<(InstId, ArithOp, i64)>> {
+    let mut steps: HashMap<LocalId, Vec<(InstId, ArithOp, i64)>> = HashMap::new();
+    let mut other = HashSet::new();
+    for &block in &lp.blocks {
+        let insts = &function.block(block).insts;
+        for (i, &inst) in insts.iter().enumerate() {
+            let InstKind::Store { local, value } = function.inst(inst).kind else {
+                continue;
+            };
+            match step(function, &insts[..i], local, value) {
+                Some((op, by)) if function.locals[local.index()] == FfiType::INT => {
+                    steps.entry(local).or_default().push((inst, op, by))
+                }
+                _ => {
+                    other.insert(local);
+                }
+            }
+        }
+    }
+    steps.retain(|local, _| !other.contains(local));
+    steps
+}
+
+/// How storing `value` to `local` after `before` in its block changes it:
+/// by adding or subtracting the returned constant to the value it had,
+/// loaded in the block with no store to it since.
+///
+/// A step of a narrower integer is cast back to its width, which changes
+/// nothing once a trap before it has stopped the program if it overflows.
+fn step(function: &Function, before: &[InstId], local: LocalId, value: InstId) -> Option<(ArithOp, i64)> {
+    let mut sum = value;
+    let mut checked = None;
+    if let InstKind::Cast { value, to: ty @ FfiType::Int { .. } } = function.inst(value).kind {
+        sum = value;
+        checked = Some(ty);
+    }
+    let InstKind::Arith { op: op @ (ArithOp::Add | ArithOp::Sub), lhs, rhs } = function.inst(sum).kind else {
+        return None;
+    };
+    let InstKind::Const(Constant::Int(by)) = function.inst(rhs).kind else {
+        return None;
+    };
+    let loaded = before.iter().rposition(|&inst| inst == lhs)?;
+    if !matches!(function.inst(lhs).kind, InstKind::Load(l) if l == local)
+        || before[loaded..].iter().any(|&inst| stores_to(function, inst, local))
+    {
+        return None;
+    }
+    if let Some(ty) = checked {
+        let overflows = |inst: &InstId| {
+            matches!(function.inst(*inst).kind,
+                InstKind::Overflows { op: o, lhs: l, rhs: r, ty: t } if (o, l, r, t) == (op, lhs, rhs, ty))
+        };
+        let trapped = before.iter().any(|&inst| {
+            matches!(function.inst(inst).kind, InstKind::Trap { condition, abort: _, .. } if overflows(&condition))
+        });
+        if !trapped {
+            return None;
+        }
+    }
+    Some((op, by))
+}
+
+/// The multiplies of `block` of a local, loaded in the block with no store
+/// to it since, by a constant: each with the local and the constant.
+fn multiplies(function: &Function, block: BlockId) -> Vec<(InstId, LocalId, i64)> {
+    let insts = &function.block(block).insts;
+    let mut found = Vec::new();
+    // The local each load of the block read, while nothing stored to it.
+    let mut current: HashMap<InstId, LocalId> = HashMap::new();
+    for &inst in insts {
+        match function.inst(inst).kind {
+            InstKind::Load(local) if function.locals[local.index()] == FfiType::INT => {
+                current.insert(inst, local);
+            }
+            InstKind::Store { local, .. } => current.retain(|_, loaded| *loaded != local),
+            InstKind::Arith { op: ArithOp::Mul, lhs, rhs } => {
+                let constant = |value: InstId| match function.inst(value).kind {
+                    InstKind::Const(Constant::Int(n)) => Some(n),
+                    _ => None,
+                };
+                let operands = [(lhs, rhs), (rhs, lhs)];
+                if let Some((local, k)) =
+                    operands.into_iter().find_map(|(counter, k)| Some((*current.get(&counter)?, constant(k)?)))
+                {
+                    found.push((inst, local, k));
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// The block control enters `lp` from: its one predecessor outside it, if
+/// that goes nowhere else, or else a new block that the predecessors
+/// outside go to instead of the header and that goes to the header.
+fn preheader(function: &mut Function, cfg: &Cfg, lp: &Loop) -> BlockId {
+    let outside: Vec<BlockId> = cfg.predecessors(lp.header).iter().copied().filter(|&p| !lp.contains(p)).collect();
+    if let [only] = outside[..]
+        && function.block(only).terminator == Terminator::Jump(lp.header)
+    {
+        return only;
+    }
+    let preheader = function.add_block();
+    function.terminate(preheader, Terminator::Jump(lp.header));
+    for block in outside {
+        let retarget = |target| if target == lp.header { preheader } else { target };
+        function.blocks[block.index()].terminator.map_successors(retarget);
+    }
+    preheader
+}
+
+/// Whether something outside `lp` stores to `local` before control can
+/// enter it, so a load before it reads a value.
+fn stored_before(function: &Function, cfg: &Cfg, lp: &Loop, local: LocalId) -> bool {
+    function.iter_blocks().any(|(id, block)| {
+        !lp.contains(id)
+            && cfg.dominates(id, lp.header)
+            && block.insts.iter().any(|&inst| stores_to(function, inst, local))
+    })
+}
+
+/// Whether `inst` stores to `local`.
+fn stores_to(function: &Function, inst: InstId, local: LocalId) -> bool {
+    matches!(function.inst(inst).kind, InstKind::Store { local: stored, .. } if stored == local)
+}
+
+/// The locals something in `blocks` stores to.
+fn stored_locals(function: &Function, blocks: &[BlockId]) -> HashSet<LocalId> {
+    let mut stored = HashSet::new();
+    for &block in blocks {
+        for &inst in &function.block(block).insts {
+            if let InstKind::Store { local, .. } = function.inst(inst).kind {
+                stored.insert(local);
+            }
+        }
+    }
+    stored
+}
+
+/// The block each instruction of a block is in.
+fn blocks_of(function: &Function) -> HashMap<InstId, BlockId> {
+    function.iter_blocks().flat_map(|(id, block)| block.insts.iter().map(move |&inst| (inst, id))).collect()
+}
+
+/// The instructions in a block.
+fn placed(function: &Function) -> impl Iterator<Item = InstId> + '_ {
+    function.blocks.iter().flat_map(|block| block.insts.iter().copied())
+}
+
+/// Add an instruction of `kind` right after `after`, in its block.
+fn add_after(function: &mut Function, after: InstId, kind: InstKind, ty: Option<FfiType>) -> InstId {
+    let id = InstId(function.insts.len() as u32);
+    function.insts.push(Inst { kind, ty });
+    for block in &mut function.blocks {
+        if let Some(i) = block.insts.iter().position(|&inst| inst == after) {
+            block.insts.insert(i + 1, id);
+            break;
+        }
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{lower_program_with_options, LoweringOptions};
+    use crate::optimizer::register_builtin_optimizers;
+    use crate::{check_program, parse_source};
+
+    fn lower(source: &str) -> CompiledModule {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        lower_program_with_options(&program, source, "main.t", LoweringOptions::default()).unwrap()
+    }
+
+    /// The multiplies of `module`'s entry function, by whether a loop
+    /// holds them, innermost first.
+    fn multiplies_in_loops(module: &CompiledModule) -> (usize, Vec<usize>) {
+        let function = module.tir().unwrap().functions.remove(0);
+        let loops = Cfg::new(&function).loops();
+        let mut outside = 0;
+        let mut inside = vec![0; loops.len()];
+        for (id, block) in function.iter_blocks() {
+            for &inst in &block.insts {
+                if matches!(function.inst(inst).kind, InstKind::Arith { op: ArithOp::Mul, .. }) {
+                    match loops.iter().position(|lp| lp.contains(id)) {
+                        Some(i) => inside[i] += 1,
+                        None => outside += 1,
+                    }
+                }
+            }
+        }
+        (outside, inside)
+    }
+
+    #[test]
+    fn invariants_of_an_inner_loop_move_to_its_preheader() {
+        let source = "fn main() {\n    let mut total = 0;\n    let mut j = 0;\n    while j < 4 {\n        let mut i = 0;\n        while i < 5 {\n            total = total + j * 7 + i;\n            i = i + 1;\n        }\n        j = j + 1;\n    }\n    println(total);\n}\n";
+        let module = lower(source);
+        assert_eq!(multiplies_in_loops(&module), (0, vec![1, 0]));
+
+        let optimized = Licm.optimize(module).unwrap();
+        // `j * 7` runs once per iteration of the outer loop, not the inner.
+        assert_eq!(multiplies_in_loops(&optimized), (0, vec![0, 1]));
+        assert_eq!(plugin_api::interpret(&optimized).unwrap(), "250\n");
+    }
+
+    #[test]
+    fn multiplies_of_a_counter_become_additions() {
+        let source = "fn main() {\n    let mut total = 0;\n    let mut i = 0;\n    while i < 20 {\n        total = total + i * 8;\n        i = i + 2;\n    }\n    println(total);\n}\n";
+        let module = lower(source);
+        assert_eq!(multiplies_in_loops(&module), (0, vec![1]));
+
+        let optimized = StrengthReduction.optimize(module).unwrap();
+        // What is left is the product the loop starts from.
+        assert_eq!(multiplies_in_loops(&optimized), (1, vec![0]));
+        assert_eq!(plugin_api::interpret(&optimized).unwrap(), "720\n");
+    }
+
+    #[test]
+    fn what_a_function_returns_is_kept() {
+        let source = "fn sum(n: i64) -> i64 {\n    let mut total = 0;\n    let mut i = 0;\n    while i < n {\n        total = total + i * 3;\n        i = i + 1;\n    }\n    total\n}\n\nfn main() {\n    println(sum(4));\n}\n";
+        let optimized = StrengthReduction.optimize(Licm.optimize(lower(source)).unwrap()).unwrap();
+        assert_eq!(plugin_api::interpret(&optimized).unwrap(), "18\n");
+    }
+
+    #[test]
+    fn loop_passes_run_from_o2() {
+        register_builtin_optimizers();
+        register_builtin_optimizers();
+        let names: Vec<&str> = plugin_api::list_optimizers().iter().map(|o| o.name()).collect();
+        assert_eq!(names.iter().filter(|&&name| name == "licm").count(), 1);
+        let overrides = HashMap::new();
+        assert!(!plugin_api::optimizer_enabled(&Licm, 1, &overrides));
+        assert!(plugin_api::optimizer_enabled(&StrengthReduction, 2, &overrides));
+    }
+}
@@ -0,0 +1,47 @@
+//! The optimizers built into the compiler.
+//!
+//! Each is a `plugin_api::Optimizer`, registered by
+//! `register_builtin_optimizers` so it runs where the optimizers plugins
+//! register do, when the optimization level or `--enable-opt` asks for it.
+//! They work on the TIR a module lifts to (`CompiledModule::tir`), one
+//! function at a time, and leave a module they change nothing in as it was.
+
+pub mod escape;
+pub mod inline;
+pub mod loops;
+pub mod tailcall;
+
+use plugin_api::{register_optimizer, BackendError, CompiledModule};
+use shared::tir;
+use std::sync::Once;
+
+/// Register the built-in optimizers, in the order they run: `tailcall`,
+/// `escape`, `inline`, `licm`, then `strength-reduce`. Tail calls become
+/// loops first, so escape analysis sees the loops they make, and a function
+/// that only called itself can be inlined. Escape analysis comes next, so
+/// a collection a callee frees is freed where it is inlined too. Calling
+/// this again registers nothing.
+pub fn register_builtin_optimizers() {
+    static REGISTERED: Once = Once::new();
+    REGISTERED.call_once(|| {
+        register_optimizer(tailcall::TailCalls);
+        register_optimizer(escape::EscapeAnalysis);
+        register_optimizer(inline::Inliner);
+        register_optimizer(loops::Licm);
+        register_optimizer(loops::StrengthReduction);
+    });
+}
+
+/// `module` with `pass` run over each function of its TIR; `pass` says
+/// whether it changed the function.
+fn rewrite_functions(
+    module: CompiledModule,
+    mut pass: impl FnMut(&mut tir::Function) -> bool,
+) -> Result<CompiledModule, BackendError> {
+    let mut tir = module.tir()?;
+    let mut changed = false;
+    for function in &mut tir.functions {
+        changed |= pass(function);
+    }
+    if changed { module.with_tir(&tir) } else { Ok(module) }
+}
@@ -0,0 +1,179 @@
+//! Tail call elimination (`tailcall`), at every level: a call a function
+//! makes to itself whose result is the function's result, with nothing
+//! left to do after it, becomes a jump back to the function's start with
+//! the arguments in its parameters. The recursion then runs in constant
+//! stack space, as `#[tailcall]`, which the checker holds functions to,
+//! promises.
+//!
+//! The function's code moves out of its entry block into a loop header the
+//! calls jump to. A function with an `Alloca` is left as it is, since a
+//! call made in a loop would free its collection once for every iteration.
+//! The module counts the calls replaced as `looped tail calls`.
+
+use plugin_api::{BackendError, CompiledModule, Optimizer};
+use shared::tir::{self, BlockId, Function, InstKind, LocalId, Terminator};
+
+/// What the module counts the calls replaced by jumps as.
+pub const LOOPED_TAIL_CALLS: &str = "looped tail calls";
+
+/// Replaces the self tail calls of each function with loops.
+pub struct TailCalls;
+
+impl Optimizer for TailCalls {
+    fn name(&self) -> &'static str {
+        "tailcall"
+    }
+
+    fn min_opt_level(&self) -> u8 {
+        0
+    }
+
+    fn optimize(&self, module: CompiledModule) -> Result<CompiledModule, BackendError> {
+        let mut tir = module.tir()?;
+        let looped = loop_tail_calls(&mut tir);
+        if looped == 0 {
+            return Ok(module);
+        }
+        let mut module = module.with_tir(&tir)?;
+        module.count(LOOPED_TAIL_CALLS, looped);
+        Ok(module)
+    }
+}
+
+/// Replace the self tail calls of every function of `module` with jumps.
+/// Returns how many were.
+pub fn loop_tail_calls(module: &mut tir::Module) -> u64 {
+    module.functions.iter_mut().map(loop_in).sum()
+}
+
+/// Replace the self tail calls of `function` with jumps back to its start.
+/// Returns how many were.
+fn loop_in(function: &mut Function) -> u64 {
+    if function.insts.iter().any(|inst| matches!(inst.kind, InstKind::Alloca(_))) {
+        return 0;
+    }
+    if !function.iter_blocks().any(|(id, _)| tail_call(function, id).is_some()) {
+        return 0;
+    }
+
+    // The entry only jumps to the header, which has its code.
+    let head = function.add_block();
+    for block in &mut function.blocks {
+        block.terminator.map_successors(|target| if target == BlockId::ENTRY { head } else { target });
+    }
+    function.blocks.swap(BlockId::ENTRY.index(), head.index());
+    function.terminate(BlockId::ENTRY, Terminator::Jump(head));
+
+    let mut looped = 0;
+    for i in 1..function.blocks.len() {
+        let id = BlockId(i as u32);
+        let Some(at) = tail_call(function, id) else { continue };
+        let call = function.block(id).insts[at];
+        let InstKind::Call { args, .. } = function.inst(call).kind.clone() else {
+            unreachable!("a tail call is a call")
+        };
+        function.blocks[i].insts.truncate(at);
+        for (param, value) in args.into_iter().enumerate() {
+            let store = InstKind::Store { local: LocalId(param as u32), value };
+            function.push(id, store, None);
+        }
+        function.terminate(id, Terminator::Jump(head));
+        looped += 1;
+    }
+    function.compact();
+    looped
+}
+
+/// Where in `block` a call of `function` to itself is, if its result is
+/// the function's: it is the block's last instruction but for the store of
+/// its value in the result local, and the block then returns.
+fn tail_call(function: &Function, block: BlockId) -> Option<usize> {
+    let insts = &function.block(block).insts;
+    let at = match function.result {
+        Some(result) => {
+            let [.., call, store] = insts.as_slice() else { return None };
+            match function.inst(*store).kind {
+                InstKind::Store { local, value } if local == result && value == *call => insts.len() - 2,
+                _ => return None,
+            }
+        }
+        None => insts.len().checked_sub(1)?,
+    };
+    let calls_itself = match &function.inst(insts[at]).kind {
+        InstKind::Call { function: callee, args } => *callee == function.name && args.len() == function.params,
+        _ => false,
+    };
+    (calls_itself && returns(function, &function.block(block).terminator)).then_some(at)
+}
+
+/// Whether `terminator` returns, or jumps to a block that does nothing but
+/// mark rows of the line table and return.
+fn returns(function: &Function, terminator: &Terminator) -> bool {
+    match terminator {
+        Terminator::Return => true,
+        Terminator::Jump(target) => {
+            let target = function.block(*target);
+            target.terminator == Terminator::Return
+                && target.insts.iter().all(|inst| matches!(function.inst(*inst).kind, InstKind::Marker(_)))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::cranelift_jit::CraneliftJitBackend;
+    use crate::{check_program, lower_program, parse_source};
+    use plugin_api::Backend;
+
+    const COUNT: &str = "#[tailcall]\nfn count(n: i64, acc: i64) -> i64 {\n    if n == 0 {\n        acc\n    } else {\n        count(n - 1, acc + 2)\n    }\n}\n\nfn main() -> i32 {\n    (count(1000000, 0) - 1999993) as i32\n}\n";
+
+    fn lower(source: &str) -> CompiledModule {
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        lower_program(&program, source, "main.t").unwrap()
+    }
+
+    /// The functions `function` of `module` calls.
+    fn callees(module: &tir::Module, function: &str) -> Vec<String> {
+        let function = module.function(function).unwrap();
+        let calls = function.blocks.iter().flat_map(|block| &block.insts);
+        calls
+            .filter_map(|inst| match &function.inst(*inst).kind {
+                InstKind::Call { function, .. } => Some(function.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn self_tail_calls_become_loops() {
+        let mut tir = lower(COUNT).tir().unwrap();
+        assert_eq!(callees(&tir, "count"), ["count"]);
+        assert_eq!(loop_tail_calls(&mut tir), 1);
+        assert!(callees(&tir, "count").is_empty());
+        assert_eq!(tir::verify::verify(&tir), Ok(()));
+
+        // A call whose result is used is not a tail call.
+        let source = "fn sum(n: i64) -> i64 {\n    if n == 0 {\n        0\n    } else {\n        n + sum(n - 1)\n    }\n}\n\nfn main() {\n    println(sum(4));\n}\n";
+        let mut tir = lower(source).tir().unwrap();
+        assert_eq!(loop_tail_calls(&mut tir), 0);
+        assert_eq!(callees(&tir, "sum"), ["sum"]);
+    }
+
+    #[test]
+    fn deep_tail_recursion_runs_in_constant_stack() {
+        let module = TailCalls.optimize(lower(COUNT)).unwrap();
+        assert_eq!(module.optimizations[LOOPED_TAIL_CALLS], 1);
+        assert_eq!(plugin_api::interpret_with_exit_code(&module).unwrap().1, 7);
+        // A million frames would not fit in this thread's stack.
+        let exit_code = std::thread::Builder::new()
+            .stack_size(1 << 20)
+            .spawn(move || CraneliftJitBackend.compile(module).unwrap().run())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(exit_code, 7);
+    }
+}
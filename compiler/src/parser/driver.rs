@@ -0,0 +1,629 @@
+// File: compiler/src/parser/driver.rs
+
+//! Front end of the LALRPOP grammar in `grammar.lalrpop`.
+//!
+//! The shared tokenizer feeds the generated parser a token at a time, as it
+//! asks for them, and every lexical or syntax error comes back as a
+//! `TlError` pointing into the source. The
+//! compiler stops at the first error; the IDE takes the best-effort program
+//! from [`Parser::parse_with_recovery`], where the pieces that failed to parse
+//! are `ItemKind::Error` and `ExprKind::Error` nodes.
+
+use errors::{ErrorCollector, Result, TlError};
+use lalrpop_util::{lalrpop_mod, ErrorRecovery, ParseError};
+use miette::SourceSpan;
+use shared::{Expr, Program, Token, TokenType, Tokenizer, MAX_RECURSION_DEPTH};
+use std::iter::Peekable;
+
+lalrpop_mod!(
+    #[allow(clippy::all, unused)]
+    grammar,
+    "/src/grammar.rs"
+);
+
+/// A token as the generated parser sees it: start offset, token, end offset.
+type Triple = (usize, TokenType, usize);
+
+/// Parses a whole T-Lang source file.
+#[derive(Debug, Clone)]
+pub struct Parser {
+    source: String,
+}
+
+impl Parser {
+    pub fn new(source: String) -> Self {
+        Self { source }
+    }
+
+    /// Parse the source, failing on the first lexical or syntax error.
+    pub fn parse(&self) -> Result<Program> {
+        let (program, errors) = self.parse_with_recovery();
+        match errors.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(program),
+        }
+    }
+
+    /// Parse the source, carrying on past errors.
+    ///
+    /// Always produces a program, together with the errors found, in source
+    /// order and without those that repeat or fall inside an earlier one.
+    /// Lexing stops at the first lexical error, and parsing at input nested
+    /// too deeply to analyse, so the program then covers only the source
+    /// before it.
+    pub fn parse_with_recovery(&self) -> (Program, Vec<TlError>) {
+        let mut cut = None;
+        let mut recovered = Vec::new();
+        let result = grammar::ProgramParser::new().parse(
+            &self.source,
+            &mut recovered,
+            Input::new(&self.source, Tokenizer::new(&self.source), &mut cut),
+        );
+
+        let truncated = cut.is_some();
+        let mut found: Vec<TlError> = recovered
+            .into_iter()
+            .map(|recovery: ErrorRecovery<usize, TokenType, String>| recovery.error)
+            .chain(result.as_ref().err().cloned())
+            // Past the cut the parser only saw the input run out.
+            .filter(|err| !(truncated && matches!(err, ParseError::UnrecognizedEof { .. })))
+            .map(|err| parse_error(&self.source, err))
+            .chain(cut)
+            .collect();
+        let program = result.unwrap_or_else(|_| Program {
+            items: Vec::new(),
+            span: SourceSpan::new(0.into(), self.source.len()),
+        });
+        found.sort_by_key(|err| err.span().map_or(0, |span| span.offset()));
+        // Resynchronising can trip over the same tokens more than once.
+        let mut errors = ErrorCollector::new();
+        errors.extend(found);
+        (program, errors.into_errors())
+    }
+}
+
+/// Parse a complete source file.
+pub fn parse_source(source: &str) -> Result<Program> {
+    Parser::new(source.to_string()).parse()
+}
+
+/// Parse a single expression, such as a REPL line.
+pub fn parse_expression(source: &str) -> Result<Expr> {
+    expression(source, Tokenizer::new(source))
+}
+
+/// Parse a macro invocation's expansion at `span`, a block expression.
+///
+/// The tokens have no source text of their own, so each is placed, empty,
+/// at the start of the invocation, and everything parsed from them points
+/// there.
+pub(crate) fn parse_expansion(source: &str, tokens: Vec<TokenType>, span: SourceSpan) -> Result<Expr> {
+    let at = SourceSpan::new(span.offset().into(), 0);
+    let tokens = tokens.into_iter().map(|token_type| Ok(Token::new(token_type, String::new(), at)));
+    expression(source, tokens)
+}
+
+/// Parse `tokens` of `source` as one expression.
+fn expression(source: &str, tokens: impl Iterator<Item = Result<Token>>) -> Result<Expr> {
+    let mut cut = None;
+    let mut recovered = Vec::new();
+    let parsed = grammar::ExpressionParser::new().parse(source, &mut recovered, Input::new(source, tokens, &mut cut));
+    if let Some(err) = cut {
+        return Err(err);
+    }
+    let expr = parsed.map_err(|err| parse_error(source, err))?;
+    match recovered.into_iter().next() {
+        Some(recovery) => Err(parse_error(source, recovery.error)),
+        None => Ok(expr),
+    }
+}
+
+/// Tokens as the generated parser reads them, lexed as it asks for them.
+///
+/// The input ends at `Eof`, or early where lexing fails or the source nests
+/// too deeply, with the error left in `cut`.
+struct Input<'a, I: Iterator<Item = Result<Token>>> {
+    source: &'a str,
+    tokens: Peekable<I>,
+    nesting: Nesting,
+    cut: &'a mut Option<TlError>,
+}
+
+impl<'a, I: Iterator<Item = Result<Token>>> Input<'a, I> {
+    fn new(source: &'a str, tokens: I, cut: &'a mut Option<TlError>) -> Self {
+        Input { source, tokens: tokens.peekable(), nesting: Nesting::default(), cut }
+    }
+}
+
+impl<I: Iterator<Item = Result<Token>>> Iterator for Input<'_, I> {
+    type Item = std::result::Result<Triple, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cut.is_some() {
+            return None;
+        }
+        let token = match self.tokens.next()? {
+            Ok(token) if token.token_type == TokenType::Eof => return None,
+            Ok(token) => token,
+            Err(err) => {
+                *self.cut = Some(err.with_source_code(self.source));
+                return None;
+            }
+        };
+        let next = match self.tokens.peek() {
+            Some(Ok(next)) => Some(&next.token_type),
+            _ => None,
+        };
+        if self.nesting.enter(&token.token_type, next) > MAX_RECURSION_DEPTH {
+            let message = format!("Expression nests too deeply (more than {} levels)", MAX_RECURSION_DEPTH);
+            *self.cut = Some(TlError::parser(self.source, token.span, message));
+            return None;
+        }
+        let start = token.span.offset();
+        Some(Ok((start, token.token_type, start + token.span.len())))
+    }
+}
+
+/// How deeply the tokens read so far nest.
+///
+/// Later passes walk the AST recursively, so input nested deeper than
+/// `MAX_RECURSION_DEPTH` is refused up front rather than overflowing the
+/// stack. Brackets nest, and so does each link of a chain: `1 + 2 + 3` and
+/// `f()()()` fold into trees as deep as they are long. A chain ends at a
+/// separator, or at a block that nothing continues.
+struct Nesting {
+    /// Chain links since the chain began, per open bracket
+    links: Vec<usize>,
+    depth: usize,
+}
+
+impl Default for Nesting {
+    fn default() -> Self {
+        Nesting { links: vec![0], depth: 0 }
+    }
+}
+
+impl Nesting {
+    /// The depth after `token`, which `next` follows.
+    fn enter(&mut self, token: &TokenType, next: Option<&TokenType>) -> usize {
+        let continued = next.is_some_and(|next| {
+            is_chain_link(next) || matches!(next, TokenType::LParen | TokenType::LBracket)
+        });
+        match token {
+            TokenType::LParen | TokenType::LBracket | TokenType::LBrace => {
+                self.links.push(0);
+                self.depth += 1;
+            }
+            TokenType::RParen | TokenType::RBracket | TokenType::RBrace if self.links.len() > 1 => {
+                self.depth -= 1 + self.links.pop().unwrap_or(0);
+                let ends_chain = *token == TokenType::RBrace && !continued;
+                if let Some(count) = self.links.last_mut() {
+                    if ends_chain {
+                        self.depth -= *count;
+                        *count = 0;
+                    } else if *token != TokenType::RBrace {
+                        // A call or index wraps what it follows.
+                        *count += 1;
+                        self.depth += 1;
+                    }
+                }
+            }
+            TokenType::Semicolon | TokenType::Comma => {
+                if let Some(count) = self.links.last_mut() {
+                    self.depth -= *count;
+                    *count = 0;
+                }
+            }
+            kind if is_chain_link(kind) => {
+                if let Some(count) = self.links.last_mut() {
+                    *count += 1;
+                    self.depth += 1;
+                }
+            }
+            _ => {}
+        }
+        self.depth
+    }
+}
+
+/// Tokens that wrap what came before (or follows) them in another node.
+fn is_chain_link(kind: &TokenType) -> bool {
+    use TokenType::*;
+    matches!(
+        kind,
+        Plus | Minus | Star | Slash | Percent | Caret | Bang | Tilde | And | Or | Shl | Shr
+            | Eq | EqEq | Ne | Lt | Le | Gt | Ge | AndAnd | OrOr
+            | PlusEq | MinusEq | StarEq | SlashEq | PercentEq | CaretEq | AndEq | OrEq
+            | ShlEq | ShrEq | As | Dot | DotDot | DotDotEq | Question | Else
+    )
+}
+
+/// Turn a parser error into a `TlError` at the offending token.
+fn parse_error(source: &str, err: ParseError<usize, TokenType, String>) -> TlError {
+    let (span, message) = match err {
+        ParseError::InvalidToken { location } => ((location, 0), "Invalid token".to_string()),
+        ParseError::UnrecognizedEof { location, expected } => (
+            (location, 0),
+            format!("Unexpected end of input{}", expected_list(&expected)),
+        ),
+        ParseError::UnrecognizedToken { token: (start, token, end), expected } => (
+            (start, end - start),
+            format!("Unexpected `{}`{}", token, expected_list(&expected)),
+        ),
+        ParseError::ExtraToken { token: (start, token, end) } => {
+            ((start, end - start), format!("Unexpected `{}` after the end of input", token))
+        }
+        ParseError::User { error } => ((0, 0), error),
+    };
+    TlError::parser(source, span, message)
+}
+
+/// ", expected one of `a`, `b`" for the terminals LALRPOP says could follow.
+fn expected_list(expected: &[String]) -> String {
+    if expected.is_empty() {
+        return String::new();
+    }
+    let names: Vec<String> = expected
+        .iter()
+        .map(|name| format!("`{}`", name.trim_matches('"')))
+        .collect();
+    match names.as_slice() {
+        [only] => format!(", expected {}", only),
+        _ => format!(", expected one of {}", names.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::ast::stmt::{ExternItem, StructFields};
+    use shared::{Expr, ExprKind, ItemKind, Literal, PatternKind, StmtKind};
+
+    fn kinds(program: &Program) -> Vec<&ItemKind> {
+        program.items.iter().map(|item| &item.kind).collect()
+    }
+
+    #[test]
+    fn parses_valid_source_without_errors() {
+        let (program, errors) = Parser::new("fn main() { let x: i32 = 1; }".into()).parse_with_recovery();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(matches!(kinds(&program)[..], [ItemKind::Function { .. }]));
+    }
+
+    #[test]
+    fn broken_item_becomes_error_node() {
+        let source = "fn a() {}\nstruct 1\nfn b() {}";
+        let (program, errors) = Parser::new(source.into()).parse_with_recovery();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            kinds(&program)[..],
+            [ItemKind::Function { .. }, ItemKind::Error, ItemKind::Function { .. }]
+        ));
+        assert!(Parser::new(source.into()).parse().is_err());
+    }
+
+    #[test]
+    fn broken_expression_keeps_surrounding_function() {
+        let source = "fn main() { let x = ; let y = 2; }";
+        let (program, errors) = Parser::new(source.into()).parse_with_recovery();
+        assert_eq!(errors.len(), 1);
+        let [ItemKind::Function { body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected one function, got {:?}", program.items);
+        };
+        let ExprKind::Block(block) = &body.kind else {
+            panic!("expected a block body");
+        };
+        assert_eq!(block.statements.len(), 2);
+        match &errors[0] {
+            TlError::Parser { span, .. } => assert_eq!(span.offset(), source.find(';').unwrap()),
+            other => panic!("expected a parser error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lexical_error_keeps_the_items_before_it() {
+        let source = "fn a() {}\nfn b() { \"unterminated }";
+        let (program, errors) = Parser::new(source.into()).parse_with_recovery();
+        assert!(matches!(errors[..], [TlError::Lexer { .. }]), "{:?}", errors);
+        assert!(matches!(kinds(&program)[0], ItemKind::Function { name, .. } if name == "a"));
+    }
+
+    #[test]
+    fn parse_reports_lexical_errors() {
+        let source = "fn main() { let s = \"open; }";
+        match Parser::new(source.into()).parse() {
+            // The tokenizer leaves the source out; the parser attaches it.
+            Err(TlError::Lexer { src, .. }) => assert_eq!(src, source),
+            other => panic!("expected a lexical error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_refused_not_overflowed() {
+        for source in [
+            format!("const X: i32 = {}1;", "-".repeat(100_000)),
+            format!("const X: i32 = 1{};", " + 1".repeat(100_000)),
+            format!("fn f() {{ {}1{} }}", "{".repeat(100_000), "}".repeat(100_000)),
+        ] {
+            match Parser::new(source).parse() {
+                Err(TlError::Parser { message, .. }) => assert!(message.contains("too deeply")),
+                other => panic!("expected a nesting error, got {:?}", other.map(|_| ())),
+            }
+        }
+        let shallow = format!("const X: i32 = 1{};", " + 1".repeat(100));
+        assert!(Parser::new(shallow).parse().is_ok());
+    }
+
+    #[test]
+    fn parses_macro_definitions_and_invocations() {
+        let source = "macro_rules! twice { ($e:expr) => { $e; $e }; }\nfn main() { twice!(f(1)); }";
+        let program = parse_source(source).unwrap();
+        let [ItemKind::Macro { name, rules }, ItemKind::Function { body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected a macro and a function, got {:?}", program.items);
+        };
+        assert_eq!(name, "twice");
+        assert_eq!(rules[0].pattern, ["$", "e", ":", "expr"]);
+        assert_eq!(rules[0].body, ["$", "e", ";", "$", "e"]);
+        let ExprKind::Block(block) = &body.kind else {
+            panic!("expected a block body");
+        };
+        let shared::StmtKind::Expr(call) = &block.statements[0].kind else {
+            panic!("expected an expression statement");
+        };
+        let ExprKind::Macro { path, args } = &call.kind else {
+            panic!("expected a macro invocation, got {:?}", call.kind);
+        };
+        assert_eq!(path, &["twice"]);
+        assert_eq!(args[0].tokens, ["f", "(", "1", ")"]);
+    }
+
+    #[test]
+    fn parses_extern_blocks() {
+        let source = "extern \"C\" { fn abs(n: i32) -> i32; fn printf(format: str, ...) -> i32; static errno: i32; }";
+        let program = parse_source(source).unwrap();
+        let [ItemKind::Extern { abi, items }] = kinds(&program)[..] else {
+            panic!("expected an extern block, got {:?}", program.items);
+        };
+        assert_eq!(abi.as_deref(), Some("C"));
+        let [
+            ExternItem::Function { name: abs, params, variadic: false, .. },
+            ExternItem::Function { name: printf, variadic: true, .. },
+            ExternItem::Static { name: errno, mutable: false, .. },
+        ] = &items[..]
+        else {
+            panic!("expected two functions and a static, got {:?}", items);
+        };
+        assert_eq!((abs.as_str(), printf.as_str(), errno.as_str()), ("abs", "printf", "errno"));
+        assert_eq!(params.len(), 1);
+    }
+
+    #[test]
+    fn parses_primitive_types_and_wildcards() {
+        let program = parse_source("fn f(_: u8) -> bool { true }").unwrap();
+        let [ItemKind::Function { return_type: Some(ty), .. }] = kinds(&program)[..] else {
+            panic!("expected one function");
+        };
+        assert_eq!(ty.kind, shared::TypeKind::Primitive(shared::ast::PrimitiveType::Bool));
+    }
+
+    #[test]
+    fn parses_spawn_blocks_and_generic_types() {
+        let program = parse_source("fn f(m: Mutex<i32>) { let t = spawn { g(m); }; }").unwrap();
+        let [ItemKind::Function { params, body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected one function");
+        };
+        let shared::TypeKind::Named { path, generics } = &params[0].ty.kind else {
+            panic!("expected a named type, got {:?}", params[0].ty);
+        };
+        assert_eq!((path.as_slice(), generics.len()), (&["Mutex".to_string()][..], 1));
+        let shared::ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let shared::StmtKind::Let { initializer: Some(init), .. } = &block.statements[0].kind else {
+            panic!("expected a let");
+        };
+        assert!(matches!(init.kind, shared::ExprKind::Spawn { .. }), "{:?}", init.kind);
+    }
+
+    #[test]
+    fn parses_references_dereferences_and_fields() {
+        let program = parse_source("fn f(p: &mut Point) -> i32 { *(&mut p.pos).x + p.len() }").unwrap();
+        let [ItemKind::Function { params, body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected one function");
+        };
+        assert!(matches!(params[0].ty.kind, shared::TypeKind::Reference { mutable: true, .. }));
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let Some(ExprKind::Binary { left, right, .. }) = block.expr.as_ref().map(|e| &e.kind) else {
+            panic!("expected a sum, got {:?}", block.expr);
+        };
+        let ExprKind::Dereference { expr: field } = &left.kind else { panic!("expected `*`, got {:?}", left.kind) };
+        let ExprKind::FieldAccess { object, field } = &field.kind else { panic!("expected `.x`, got {:?}", field.kind) };
+        assert_eq!(field, "x");
+        assert!(matches!(object.kind, ExprKind::Reference { mutable: true, .. }), "{:?}", object.kind);
+        assert!(matches!(&right.kind, ExprKind::MethodCall { method, .. } if method == "len"), "{:?}", right.kind);
+    }
+
+    #[test]
+    fn parses_unsafe_blocks_and_raw_pointer_types() {
+        let program = parse_source("fn f(p: *mut i32) -> *const i32 { unsafe { *p = 1; } }").unwrap();
+        let [ItemKind::Function { params, return_type: Some(return_type), body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected one function");
+        };
+        assert!(matches!(params[0].ty.kind, shared::TypeKind::Pointer { mutable: true, .. }));
+        assert!(matches!(return_type.kind, shared::TypeKind::Pointer { mutable: false, .. }));
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let Some(ExprKind::Unsafe { body }) = block.expr.as_ref().map(|e| &e.kind) else {
+            panic!("expected an unsafe block, got {:?}", block.expr);
+        };
+        assert!(matches!(body.kind, ExprKind::Block(_)), "{:?}", body.kind);
+    }
+
+    #[test]
+    fn parses_tuple_structs_and_their_patterns() {
+        let program = parse_source("struct Meters(f64);\nfn main() { let Meters(m) = Meters(1.5); }").unwrap();
+        let [ItemKind::Struct { fields, .. }, ItemKind::Function { body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected a struct and a function");
+        };
+        assert!(matches!(fields, StructFields::Unnamed(types) if types.len() == 1), "{:?}", fields);
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let StmtKind::Let { pattern, .. } = &block.statements[0].kind else { panic!("expected a let") };
+        let PatternKind::Enum { path, variant, fields } = &pattern.kind else {
+            panic!("expected a tuple struct pattern, got {:?}", pattern.kind);
+        };
+        assert!(path.is_empty() && variant == "Meters" && fields.len() == 1);
+    }
+
+    #[test]
+    fn parses_struct_literals_outside_conditions() {
+        let source = "fn f(b: Point) { let p = Point { x: 1, y, }; let q = geo::Point { x: 2, ..b }; if p.x == 1 { g(Unit {}); }; }";
+        let program = parse_source(source).unwrap();
+        let [ItemKind::Function { body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected one function");
+        };
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let literals: Vec<&ExprKind> = block.statements[..2]
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StmtKind::Let { initializer: Some(init), .. } => &init.kind,
+                other => panic!("expected a let, got {:?}", other),
+            })
+            .collect();
+        let ExprKind::Struct { path, fields, base: None } = literals[0] else { panic!("expected a struct literal") };
+        assert_eq!(path, &["Point"]);
+        let names: Vec<(&str, bool)> = fields.iter().map(|f| (f.name.as_str(), f.value.is_some())).collect();
+        assert_eq!(names, [("x", true), ("y", false)]);
+        let ExprKind::Struct { path, fields, base: Some(base) } = literals[1] else { panic!("expected a base") };
+        assert!(path == &["geo", "Point"] && fields.len() == 1);
+        assert!(matches!(&base.kind, ExprKind::Variable { path } if path == &["b"]));
+        // `p.x == 1 {` is the condition and the block, not a literal.
+        let StmtKind::Expr(Expr { kind: ExprKind::If { then_branch, .. }, .. }) = &block.statements[2].kind else {
+            panic!("expected an if");
+        };
+        assert!(matches!(then_branch.kind, ExprKind::Block(_)));
+
+        let errors = parse_source("fn f() { if p == Point { x: 1 } { } }");
+        assert!(errors.is_err(), "a struct literal in a condition needs parentheses");
+    }
+
+    #[test]
+    fn parses_match_arms_and_their_patterns() {
+        let source = "fn f(c: Color) -> i32 { match c { Color::Red => 1, Color::Green | Color::Blue => { 2 } n if g(n) => 3, _ => 4 } }";
+        let program = parse_source(source).unwrap();
+        let [ItemKind::Function { body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected one function");
+        };
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let Some(ExprKind::Match { expr, arms }) = block.expr.as_ref().map(|e| &e.kind) else {
+            panic!("expected a match, got {:?}", block.expr);
+        };
+        assert!(matches!(&expr.kind, ExprKind::Variable { path } if path == &["c"]), "{:?}", expr.kind);
+        let patterns: Vec<&PatternKind> = arms.iter().map(|arm| &arm.pattern.kind).collect();
+        let PatternKind::Enum { path, variant, fields } = patterns[0] else { panic!("expected a variant pattern") };
+        assert!(path == &["Color"] && variant == "Red" && fields.is_empty());
+        assert!(matches!(patterns[1], PatternKind::Or(alternatives) if alternatives.len() == 2), "{:?}", patterns[1]);
+        assert!(matches!(patterns[2], PatternKind::Ident(n) if n == "n") && arms[2].guard.is_some());
+        assert!(matches!(patterns[3], PatternKind::Wild) && arms[3].guard.is_none());
+        assert!(matches!(arms[1].body.kind, ExprKind::Block(_)));
+
+        let program = parse_source("fn f(x: i32) { match x { 0 => g(), 7 => {} }; }").unwrap();
+        let [ItemKind::Function { body: Some(body), .. }] = kinds(&program)[..] else {
+            panic!("expected one function");
+        };
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let StmtKind::Expr(Expr { kind: ExprKind::Match { arms, .. }, .. }) = &block.statements[0].kind else {
+            panic!("expected a match statement");
+        };
+        assert!(matches!(arms[1].pattern.kind, PatternKind::Literal(Literal::Integer(7))));
+    }
+
+    #[test]
+    fn nodes_span_all_of_their_source() {
+        let source = "fn f(a: i32) -> bool {\n    let x = a as i64 * 2;\n    \
+                      if x < 10 || a == 1 { true } else { false }\n}";
+        let text = |span: SourceSpan| &source[span.offset()..span.offset() + span.len()];
+        let program = parse_source(source).unwrap();
+        let [ItemKind::Function { params, return_type: Some(return_type), body: Some(body), .. }] = kinds(&program)[..]
+        else {
+            panic!("expected one function");
+        };
+        assert_eq!((text(params[0].span), text(params[0].ty.span)), ("a: i32", "i32"));
+        assert_eq!(text(return_type.span), "bool");
+        assert_eq!(text(body.span), &source[source.find('{').unwrap()..]);
+
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let StmtKind::Let { initializer: Some(init), .. } = &block.statements[0].kind else { panic!("expected a let") };
+        assert_eq!(text(init.span), "a as i64 * 2");
+        let Some(tail) = &block.expr else { panic!("expected a final expression") };
+        assert_eq!(text(tail.span), "if x < 10 || a == 1 { true } else { false }");
+        let ExprKind::If { condition, then_branch, .. } = &tail.kind else { panic!("expected an if") };
+        assert_eq!((text(condition.span), text(then_branch.span)), ("x < 10 || a == 1", "{ true }"));
+        let ExprKind::Block(then_block) = &then_branch.kind else { panic!("expected a block") };
+        assert_eq!(then_block.expr.as_ref().map(|expr| text(expr.span)), Some("true"));
+    }
+
+    /// The statements of the body of the function `item`.
+    fn statements(item: &ItemKind) -> &[shared::Stmt] {
+        let ItemKind::Function { body: Some(body), .. } = item else {
+            panic!("expected a function, got {:?}", item);
+        };
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        &block.statements
+    }
+
+    #[test]
+    fn parses_module_declarations() {
+        let program = parse_source("mod A; mod B;").unwrap();
+        let [ItemKind::Module { name: a, items, inline: false }, ItemKind::Module { name: b, .. }] = kinds(&program)[..]
+        else {
+            panic!("expected two modules, got {:?}", program.items);
+        };
+        assert_eq!((a.as_str(), b.as_str()), ("A", "B"));
+        assert!(items.is_empty());
+
+        let program = parse_source("mod M { fn f() {} }").unwrap();
+        let [ItemKind::Module { name, items, inline: true }] = kinds(&program)[..] else {
+            panic!("expected an inline module, got {:?}", program.items);
+        };
+        assert_eq!((name.as_str(), items.len()), ("M", 1));
+        // Modules are declared with `mod`, as in Rust; `module` is not a keyword.
+        assert!(parse_source("module M;").is_err());
+        assert!(parse_source("mod Bad fn;").is_err());
+    }
+
+    #[test]
+    fn parses_let_and_return_statements() {
+        let program = parse_source("mod Test { fn f() -> i32 { let x: i32 = 42; return x; } }").unwrap();
+        let [ItemKind::Module { items, .. }] = kinds(&program)[..] else { panic!("expected a module") };
+        let [let_x, ret] = statements(&items[0].kind) else { panic!("expected two statements") };
+        assert!(matches!(&let_x.kind, StmtKind::Let { ty: Some(_), initializer: Some(_), .. }));
+        let StmtKind::Expr(ret) = &ret.kind else { panic!("expected an expression statement") };
+        assert!(matches!(&ret.kind, ExprKind::Return { value: Some(_) }));
+    }
+
+    #[test]
+    fn parses_if_else_and_while() {
+        // `if` is an expression, so as a statement it takes a `;`; `while` does not.
+        let program = parse_source("fn loopit() { if true { } else { }; while false { } }").unwrap();
+        let exprs: Vec<_> = statements(kinds(&program)[0])
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StmtKind::Expr(expr) => &expr.kind,
+                other => panic!("expected an expression statement, got {:?}", other),
+            })
+            .collect();
+        assert!(matches!(exprs[..], [ExprKind::If { else_branch: Some(_), .. }, ExprKind::While { .. }]), "{:?}", exprs);
+    }
+
+    #[test]
+    fn parses_binary_unary_and_call_expressions() {
+        let program = parse_source("fn e() { 1 + 2 * (3 - 4); !false; foo(5, \"bar\"); }").unwrap();
+        let [sum, not, call] = statements(kinds(&program)[0]) else { panic!("expected three statements") };
+        let expr = |stmt: &shared::Stmt| match &stmt.kind {
+            StmtKind::Expr(expr) => expr.kind.clone(),
+            other => panic!("expected an expression statement, got {:?}", other),
+        };
+        assert!(matches!(expr(sum), ExprKind::Binary { op: shared::BinaryOp::Add, .. }));
+        assert!(matches!(expr(not), ExprKind::Unary { op: shared::UnaryOp::Not, .. }));
+        let ExprKind::Call { callee, args, .. } = expr(call) else { panic!("expected a call") };
+        assert!(matches!(&callee.kind, ExprKind::Variable { path } if path == &["foo"]));
+        assert!(matches!(&args[..], [_, Expr { kind: ExprKind::Literal(Literal::String(s)), .. }] if s == "bar"));
+    }
+}
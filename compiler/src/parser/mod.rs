@@ -2,15 +2,10 @@
 
 //! Main entry point for the T‑Lang parser.
 //!
-//! This module organizes the submodules responsible for parsing different
-//! parts of the language: modules, declarations, statements, expressions,
-//! patterns, and types.
+//! `driver` runs the LALRPOP grammar and builds the `shared::ast` tree,
+//! the one AST every later stage works on.
 
-mod modules;
-mod declarations;
-mod statements;
-mod expressions;
-mod patterns;
-mod types;
+mod driver;
 
-pub use modules::parse_module;
+pub use driver::{Parser, parse_source, parse_expression};
+pub(crate) use driver::parse_expansion;
@@ -0,0 +1,481 @@
+// compiler/src/query.rs
+//! One engine for on-demand analyses, shared by the LSP and incremental
+//! builds.
+//!
+//! A `Database` holds the text of every file it has been given and answers
+//! queries about them: the parsed program, its names, the type-checked
+//! program, its lint warnings, its safety violations and its lowered
+//! module. Each answer is computed the first time it is asked for and kept
+//! until the file's text changes, so asking again is free and an edit
+//! recomputes only the file it touched.
+//!
+//! Answers are kept per file, and the type-checked top-level items of a
+//! file by name as well, which `type_check` puts together: an edit to a
+//! function re-parses the file it is in, but re-checks only that function,
+//! and the items after it if it changed length. An item is checked against
+//! the declarations of the others, so an edit to another's signature
+//! re-checks it too.
+//!
+//! A top-level item marked `#[cfg(name, ...)]` is part of the program only
+//! while every feature it names is on, as `set_features` turns them on.
+
+use crate::ir::{lower_program_with_options, LoweringOptions};
+use crate::safety::{SafetyAnalyzer, SafetyViolation};
+use crate::{expand_macros, lint_program, Parser, SymbolTable, TypeChecker};
+use errors::ErrorCollector;
+use plugin_api::{BackendError, CompiledModule};
+use shared::ast::stmt::AttributeArg;
+use shared::{Item, ItemKind, Program, TlError};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// A file known to a `Database`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+/// A file's program with its macros expanded, and the errors met on the way.
+#[derive(Debug)]
+pub struct Parsed {
+    /// Best-effort when parsing fails; parts that did not parse are `Error`
+    /// nodes.
+    pub program: Program,
+    pub errors: Vec<TlError>,
+}
+
+/// A file's program after type checking, with types filled in as far as
+/// checking got.
+#[derive(Debug)]
+pub struct Checked {
+    pub program: Program,
+    /// The first type error, if any
+    pub error: Option<TlError>,
+}
+
+/// A top-level item of a file after type checking on its own, against the
+/// declarations of the file's other items.
+#[derive(Debug)]
+pub struct CheckedItem {
+    pub item: Item,
+    /// The first type error, if any
+    pub error: Option<TlError>,
+}
+
+/// A top-level item of a file, by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemKey {
+    pub file: FileId,
+    pub name: String,
+}
+
+/// What the safety analyses found in a file's type-checked program, before
+/// any lint levels are applied.
+pub type Safety = std::result::Result<Vec<SafetyViolation>, TlError>;
+
+/// What lowering a file's type-checked program produced.
+pub type Lowered = std::result::Result<CompiledModule, BackendError>;
+
+#[derive(Debug)]
+struct SourceFile {
+    path: String,
+    text: String,
+    /// Revision at which `text` last changed
+    changed_at: u64,
+}
+
+/// An answer and the revision of the file it was computed from, or for an
+/// item, the fingerprint of what it was computed from.
+#[derive(Debug)]
+struct Memo<T> {
+    revision: u64,
+    value: Arc<T>,
+}
+
+/// Files and the memoized answers to queries about them.
+#[derive(Debug, Default)]
+pub struct Database {
+    files: Vec<SourceFile>,
+    ids: HashMap<String, FileId>,
+    /// Bumped by every change to any file
+    revision: u64,
+    parsed: HashMap<FileId, Memo<Parsed>>,
+    symbols: HashMap<FileId, Memo<SymbolTable>>,
+    checked: HashMap<FileId, Memo<Checked>>,
+    checked_items: HashMap<ItemKey, Memo<CheckedItem>>,
+    lints: HashMap<FileId, Memo<ErrorCollector>>,
+    safety: HashMap<FileId, Memo<Safety>>,
+    lowered: HashMap<FileId, Memo<Lowered>>,
+    /// How every file is lowered
+    lowering: LoweringOptions,
+    /// The features `#[cfg]` items may name
+    features: BTreeSet<String>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Database::default()
+    }
+
+    /// A database that lowers files with `options`.
+    pub fn with_lowering_options(options: LoweringOptions) -> Self {
+        Database { lowering: options, ..Database::default() }
+    }
+
+    /// The file at `path`, empty until its text is set.
+    pub fn file(&mut self, path: &str) -> FileId {
+        if let Some(&id) = self.ids.get(path) {
+            return id;
+        }
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile {
+            path: path.to_string(),
+            text: String::new(),
+            changed_at: self.revision,
+        });
+        self.ids.insert(path.to_string(), id);
+        id
+    }
+
+    /// Set the text of `file`. Answers about it are recomputed when next
+    /// asked for, unless the text is unchanged.
+    pub fn set_text(&mut self, file: FileId, text: &str) {
+        let source = &mut self.files[file.0];
+        if source.text == text {
+            return;
+        }
+        self.revision += 1;
+        source.text = text.to_string();
+        source.changed_at = self.revision;
+    }
+
+    /// Turn on exactly `features`. Every file is recomputed when next asked
+    /// about, unless they were already the ones on.
+    pub fn set_features(&mut self, features: BTreeSet<String>) {
+        if self.features == features {
+            return;
+        }
+        self.revision += 1;
+        self.features = features;
+        for source in &mut self.files {
+            source.changed_at = self.revision;
+        }
+    }
+
+    pub fn path(&self, file: FileId) -> &str {
+        &self.files[file.0].path
+    }
+
+    pub fn text(&self, file: FileId) -> &str {
+        &self.files[file.0].text
+    }
+
+    /// The program of `file`, with its macros expanded.
+    pub fn parse(&mut self, file: FileId) -> Arc<Parsed> {
+        let source = &self.files[file.0];
+        if let Some(parsed) = fresh(&self.parsed, &file, source.changed_at) {
+            return parsed;
+        }
+        let (mut program, mut errors) = Parser::new(source.text.clone()).parse_with_recovery();
+        program.items.retain(|item| {
+            let mut names = item.attrs.iter().filter(|attr| attr.path == ["cfg"]).flat_map(|attr| &attr.args);
+            names.all(|name| matches!(name, AttributeArg::Ident(name) if self.features.contains(name)))
+        });
+        errors.extend(expand_macros(&mut program, &source.text));
+        memoize(&mut self.parsed, file, source.changed_at, Parsed { program, errors })
+    }
+
+    /// The names `file` declares.
+    pub fn symbols(&mut self, file: FileId) -> Arc<SymbolTable> {
+        let revision = self.files[file.0].changed_at;
+        if let Some(symbols) = fresh(&self.symbols, &file, revision) {
+            return symbols;
+        }
+        let table = SymbolTable::build(&self.parse(file).program);
+        memoize(&mut self.symbols, file, revision, table)
+    }
+
+    /// The program of `file` after type checking, each item as
+    /// `check_item` checks it, its error the first item's there is. A
+    /// program that failed to parse is checked too; its type errors are
+    /// mostly fallout of the syntax errors.
+    pub fn type_check(&mut self, file: FileId) -> Arc<Checked> {
+        let revision = self.files[file.0].changed_at;
+        if let Some(checked) = fresh(&self.checked, &file, revision) {
+            return checked;
+        }
+        let parsed = self.parse(file);
+        let mut items = Vec::with_capacity(parsed.program.items.len());
+        let mut error = None;
+        for index in 0..parsed.program.items.len() {
+            let checked = self.check_item_at(file, &parsed, index);
+            if error.is_none() {
+                error = checked.error.clone();
+            }
+            items.push(checked.item.clone());
+        }
+        let program = Program { items, span: parsed.program.span };
+        memoize(&mut self.checked, file, revision, Checked { program, error })
+    }
+
+    /// The item of `file` called `name` after type checking, if the file
+    /// has one. It is checked again only once its text, where it starts or
+    /// the declarations of the other items change.
+    pub fn check_item(&mut self, file: FileId, name: &str) -> Option<Arc<CheckedItem>> {
+        let parsed = self.parse(file);
+        let index = parsed.program.items.iter().position(|item| item_name(item) == Some(name))?;
+        Some(self.check_item_at(file, &parsed, index))
+    }
+
+    /// The item at `index` of `parsed`, the program of `file`, after type
+    /// checking, kept by its name, or by its place if it has none.
+    fn check_item_at(&mut self, file: FileId, parsed: &Parsed, index: usize) -> Arc<CheckedItem> {
+        let items = &parsed.program.items;
+        let text = self.text(file);
+        let fingerprint = fingerprint(text, items, index);
+        let name = item_name(&items[index]).map_or_else(|| format!("#{}", index), str::to_string);
+        let key = ItemKey { file, name };
+        if let Some(checked) = fresh(&self.checked_items, &key, fingerprint) {
+            return checked;
+        }
+        let items = items.iter().enumerate().map(|(i, item)| if i == index { item.clone() } else { declaration(item) });
+        let mut program = Program { items: items.collect(), span: parsed.program.span };
+        let mut checker = TypeChecker::new(text.to_string());
+        let error = checker.check_program(&mut program).err();
+        let item = program.items.swap_remove(index);
+        memoize(&mut self.checked_items, key, fingerprint, CheckedItem { item, error })
+    }
+
+    /// The warnings about unused code in `file`'s type-checked program.
+    pub fn lint(&mut self, file: FileId) -> Arc<ErrorCollector> {
+        let revision = self.files[file.0].changed_at;
+        if let Some(lints) = fresh(&self.lints, &file, revision) {
+            return lints;
+        }
+        let checked = self.type_check(file);
+        let lints = lint_program(&checked.program, self.text(file));
+        memoize(&mut self.lints, file, revision, lints)
+    }
+
+    /// The safety violations in `file`'s type-checked program, found as a
+    /// hosted program's are.
+    pub fn safety(&mut self, file: FileId) -> Arc<Safety> {
+        let revision = self.files[file.0].changed_at;
+        if let Some(safety) = fresh(&self.safety, &file, revision) {
+            return safety;
+        }
+        let checked = self.type_check(file);
+        let violations = SafetyAnalyzer::new(self.text(file).to_string()).analyze_program(&checked.program);
+        memoize(&mut self.safety, file, revision, violations)
+    }
+
+    /// The module `file` lowers to. Whether it parsed and type checked is
+    /// for the caller to ask first.
+    pub fn lower(&mut self, file: FileId) -> Arc<Lowered> {
+        let revision = self.files[file.0].changed_at;
+        if let Some(lowered) = fresh(&self.lowered, &file, revision) {
+            return lowered;
+        }
+        let checked = self.type_check(file);
+        let source = &self.files[file.0];
+        let module = lower_program_with_options(&checked.program, &source.text, &source.path, self.lowering);
+        memoize(&mut self.lowered, file, revision, module)
+    }
+}
+
+/// The answer memoized for `key`, unless what it was computed from has
+/// changed since.
+fn fresh<K: Eq + Hash, T>(memos: &HashMap<K, Memo<T>>, key: &K, revision: u64) -> Option<Arc<T>> {
+    memos.get(key).filter(|memo| memo.revision == revision).map(|memo| Arc::clone(&memo.value))
+}
+
+fn memoize<K: Eq + Hash, T>(memos: &mut HashMap<K, Memo<T>>, key: K, revision: u64, value: T) -> Arc<T> {
+    let value = Arc::new(value);
+    memos.insert(key, Memo { revision, value: Arc::clone(&value) });
+    value
+}
+
+/// The name a top-level item is known by, if it has one.
+fn item_name(item: &Item) -> Option<&str> {
+    match &item.kind {
+        ItemKind::Function { name, .. }
+        | ItemKind::Struct { name, .. }
+        | ItemKind::Enum { name, .. }
+        | ItemKind::Union { name, .. }
+        | ItemKind::Trait { name, .. }
+        | ItemKind::TypeAlias { name, .. }
+        | ItemKind::Const { name, .. }
+        | ItemKind::Static { name, .. }
+        | ItemKind::Module { name, .. }
+        | ItemKind::Macro { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// What an item is checked against of another: all of it but a function's
+/// body.
+fn declaration(item: &Item) -> Item {
+    let mut item = item.clone();
+    if let ItemKind::Function { body, .. } = &mut item.kind {
+        *body = None;
+    }
+    item
+}
+
+/// A hash of what checking `items[index]` depends on: its text and where it
+/// starts, and the declarations of the other items.
+fn fingerprint(text: &str, items: &[Item], index: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    items[index].span.offset().hash(&mut hasher);
+    for (i, item) in items.iter().enumerate() {
+        let start = item.span.offset();
+        let end = match &item.kind {
+            ItemKind::Function { body: Some(body), .. } if i != index => body.span.offset(),
+            _ => start + item.span.len(),
+        };
+        text.get(start..end).unwrap_or_default().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_are_kept_until_the_file_changes() {
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        db.set_text(file, "fn main() { let x = 1; }");
+
+        let parsed = db.parse(file);
+        assert!(parsed.errors.is_empty());
+        assert!(Arc::ptr_eq(&parsed, &db.parse(file)));
+        let checked = db.type_check(file);
+        assert!(checked.error.is_none());
+        let lints = db.lint(file);
+        assert_eq!(lints.len(), 1);
+
+        // Setting the same text keeps every answer
+        db.set_text(file, "fn main() { let x = 1; }");
+        assert!(Arc::ptr_eq(&parsed, &db.parse(file)));
+        assert!(Arc::ptr_eq(&checked, &db.type_check(file)));
+        assert!(Arc::ptr_eq(&lints, &db.lint(file)));
+
+        db.set_text(file, "fn main() { let x: bool = 1; }");
+        assert!(!Arc::ptr_eq(&parsed, &db.parse(file)));
+        assert!(db.type_check(file).error.is_some());
+    }
+
+    #[test]
+    fn an_edit_recomputes_only_its_own_file() {
+        let mut db = Database::new();
+        let main = db.file("main.t");
+        let util = db.file("util.t");
+        db.set_text(main, "fn main() { }");
+        db.set_text(util, "fn helper() { }\nstruct Point { x: i32 }");
+
+        let util_symbols = db.symbols(util);
+        assert_eq!(util_symbols.symbols().len(), 2);
+        db.set_text(main, "fn main() { helper(); }");
+        assert!(Arc::ptr_eq(&util_symbols, &db.symbols(util)));
+        assert_eq!(db.file("util.t"), util);
+        assert_eq!(db.path(util), "util.t");
+    }
+
+    #[test]
+    fn safety_violations_are_answers_too() {
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        db.set_text(file, "fn main() {\n    let a = \"text\";\n    let b = a;\n    println(a);\n}\n");
+        let safety = db.safety(file);
+        let lints: Vec<&str> = safety.as_ref().as_ref().unwrap().iter().map(|violation| violation.lint()).collect();
+        assert_eq!(lints, ["use_after_move"]);
+        assert!(Arc::ptr_eq(&safety, &db.safety(file)));
+    }
+
+    #[test]
+    fn syntax_errors_are_answers_too() {
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        db.set_text(file, "fn main() { let = ; }");
+        assert!(!db.parse(file).errors.is_empty());
+
+        db.set_text(file, "fn main() { print(1); }");
+        assert!(db.parse(file).errors.is_empty());
+        assert!(db.lower(file).is_ok());
+    }
+
+    #[test]
+    fn cfg_items_are_there_while_their_features_are_on() {
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        db.set_text(file, "#[cfg(simd)]\nfn wide() { }\n#[cfg(simd, avx)]\nfn wider() { }\nfn main() { }");
+        let functions = |db: &mut Database| -> Vec<String> {
+            let symbols = db.symbols(file);
+            symbols.symbols().iter().filter(|symbol| symbol.name != "main").map(|symbol| symbol.name.clone()).collect()
+        };
+        assert!(functions(&mut db).is_empty());
+        db.set_features(BTreeSet::from(["simd".to_string()]));
+        assert_eq!(functions(&mut db), ["wide"]);
+
+        // The same features keep every answer
+        let parsed = db.parse(file);
+        db.set_features(BTreeSet::from(["simd".to_string()]));
+        assert!(Arc::ptr_eq(&parsed, &db.parse(file)));
+        db.set_features(BTreeSet::from(["avx".to_string(), "simd".to_string()]));
+        assert_eq!(functions(&mut db), ["wide", "wider"]);
+    }
+
+    #[test]
+    fn an_edit_rechecks_only_its_own_item() {
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        let source = |n: i64| {
+            format!("fn helper(x: i64) -> i64 {{\n    x + 1\n}}\n\nfn main() {{\n    println(helper({}));\n}}\n", n)
+        };
+        db.set_text(file, &source(1));
+        let helper = db.check_item(file, "helper").unwrap();
+        let main = db.check_item(file, "main").unwrap();
+        assert!(helper.error.is_none() && main.error.is_none());
+        assert!(db.check_item(file, "missing").is_none());
+
+        // Editing `main` keeps `helper`, which comes before it.
+        db.set_text(file, &source(2));
+        assert!(Arc::ptr_eq(&helper, &db.check_item(file, "helper").unwrap()));
+        assert!(!Arc::ptr_eq(&main, &db.check_item(file, "main").unwrap()));
+
+        // Editing `helper`'s body keeps `main`, which only sees its signature.
+        db.set_text(file, &source(2).replace("x + 1", "x - 1"));
+        let main = db.check_item(file, "main").unwrap();
+        assert!(!Arc::ptr_eq(&helper, &db.check_item(file, "helper").unwrap()));
+        db.set_text(file, &source(2).replace("x + 1", "x * 1"));
+        assert!(Arc::ptr_eq(&main, &db.check_item(file, "main").unwrap()));
+
+        // Changing its signature re-checks `main` against it.
+        db.set_text(file, &source(2).replace("x: i64", "x: bool"));
+        assert!(db.check_item(file, "main").unwrap().error.is_some());
+    }
+
+    #[test]
+    fn type_check_rechecks_only_the_edited_items() {
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        let source = |n: i64| {
+            format!("fn helper(x: i64) -> i64 {{\n    x + 1\n}}\n\nfn main() {{\n    println(helper({}));\n}}\n", n)
+        };
+        db.set_text(file, &source(1));
+        assert!(db.type_check(file).error.is_none());
+        let helper = db.check_item(file, "helper").unwrap();
+
+        db.set_text(file, &source(2));
+        assert!(db.type_check(file).error.is_none());
+        assert!(Arc::ptr_eq(&helper, &db.check_item(file, "helper").unwrap()));
+
+        db.set_text(file, &source(2).replace("helper(2)", "helper(true)"));
+        let checked = db.type_check(file);
+        assert!(checked.error.is_some());
+        assert!(Arc::ptr_eq(&helper, &db.check_item(file, "helper").unwrap()));
+        assert_eq!(checked.program.items.len(), 2);
+    }
+}
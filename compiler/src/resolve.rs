@@ -0,0 +1,600 @@
+// File: compiler/src/resolve.rs
+//! Name resolution tables for T-Lang programs.
+//!
+//! `SymbolTable::build` walks a program once and records every declaration
+//! with the byte range of source in which it is visible: items anywhere in
+//! their file, module or block, parameters inside their function body, and
+//! `let`, `for`, `match` and closure bindings from the point of declaration
+//! to the end of the construct that owns them. Fields and methods are
+//! recorded per type name. Tools query the table by offset instead of
+//! re-scanning the source text.
+
+use miette::SourceSpan;
+use shared::ast::stmt::{ExternItem, FnParam, ImplItem, StructField, StructFields};
+use shared::ast::{Block, PrimitiveType};
+use shared::{
+    Expr, ExprKind, Item, ItemKind, Pattern, PatternKind, Program, Stmt, StmtKind, Type, TypeKind,
+};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// What a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Struct,
+    Enum,
+    Union,
+    Trait,
+    TypeAlias,
+    Const,
+    Static,
+    Module,
+    Macro,
+    Parameter,
+    Variable,
+}
+
+/// A declared name.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Span of the declaration.
+    pub span: SourceSpan,
+    /// Byte range of source in which the name is visible.
+    pub scope: Range<usize>,
+    /// Declared type (a function type for functions), if known.
+    pub ty: Option<Type>,
+    /// Doc comment of an item.
+    pub docs: Option<String>,
+}
+
+/// A field or method of a named type.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    /// Field type, or the method's function type.
+    pub ty: Option<Type>,
+    pub is_method: bool,
+}
+
+/// All declarations of a program, queryable by position.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    members: HashMap<String, Vec<Member>>,
+}
+
+fn range(span: SourceSpan) -> Range<usize> {
+    span.offset()..span.offset() + span.len()
+}
+
+/// Spelling of a type in hover text and debug info, close to how it is
+/// written in source.
+pub fn type_name(ty: &Type) -> String {
+    match &ty.kind {
+        TypeKind::Primitive(p) => match format!("{:?}", p).to_lowercase().as_str() {
+            "unit" => "()".to_string(),
+            name => name.to_string(),
+        },
+        TypeKind::Named { path, generics } if generics.is_empty() => path.join("::"),
+        TypeKind::Named { path, generics } => {
+            let args: Vec<String> = generics.iter().map(type_name).collect();
+            format!("{}<{}>", path.join("::"), args.join(", "))
+        }
+        TypeKind::Reference { target, mutable, .. } => {
+            format!("&{}{}", if *mutable { "mut " } else { "" }, type_name(target))
+        }
+        TypeKind::Pointer { target, mutable } => {
+            format!("*{} {}", if *mutable { "mut" } else { "const" }, type_name(target))
+        }
+        TypeKind::Slice { element } | TypeKind::Array { element, .. } => {
+            format!("[{}]", type_name(element))
+        }
+        TypeKind::Tuple(elems) => {
+            let elems: Vec<String> = elems.iter().map(type_name).collect();
+            format!("({})", elems.join(", "))
+        }
+        TypeKind::Function { params, return_type, .. } => {
+            let params: Vec<String> = params.iter().map(type_name).collect();
+            format!("fn({}) -> {}", params.join(", "), type_name(return_type))
+        }
+        _ => "_".to_string(),
+    }
+}
+
+/// Function type of a signature; a missing return type is `()`.
+fn signature(params: &[FnParam], return_type: Option<&Type>, span: SourceSpan) -> Type {
+    let params = params.iter().map(|p| p.ty.clone()).collect();
+    let return_type = return_type
+        .cloned()
+        .unwrap_or_else(|| Type::primitive(PrimitiveType::Unit, span));
+    Type::function(params, return_type, shared::SafetyLevel::Safe)
+}
+
+fn field_members(fields: &[StructField]) -> Vec<Member> {
+    fields
+        .iter()
+        .map(|f| Member { name: f.name.clone(), ty: Some(f.ty.clone()), is_method: false })
+        .collect()
+}
+
+impl SymbolTable {
+    /// Build the table for a whole program.
+    pub fn build(program: &Program) -> Self {
+        let mut table = SymbolTable::default();
+        for item in &program.items {
+            table.item(item, 0..usize::MAX);
+        }
+        table
+    }
+
+    /// Every declaration, in source order of traversal.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Names visible at `offset`; an inner declaration hides outer ones.
+    pub fn visible_at(&self, offset: usize) -> Vec<&Symbol> {
+        let mut visible: HashMap<&str, &Symbol> = HashMap::new();
+        for symbol in self.symbols.iter().filter(|s| s.scope.contains(&offset)) {
+            match visible.get(symbol.name.as_str()) {
+                Some(seen) if seen.scope.start > symbol.scope.start => {}
+                _ => {
+                    visible.insert(&symbol.name, symbol);
+                }
+            }
+        }
+        let mut visible: Vec<&Symbol> = visible.into_values().collect();
+        visible.sort_by(|a, b| a.name.cmp(&b.name));
+        visible
+    }
+
+    /// The declaration `name` refers to at `offset`.
+    pub fn resolve(&self, name: &str, offset: usize) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .filter(|s| s.name == name && s.scope.contains(&offset))
+            .max_by_key(|s| s.scope.start)
+    }
+
+    /// Fields and methods of the type named `type_name`.
+    pub fn members(&self, type_name: &str) -> &[Member] {
+        self.members.get(type_name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Fields and methods reachable with `.` on a value of type `ty`.
+    pub fn members_of_type(&self, ty: &Type) -> &[Member] {
+        match &ty.kind {
+            TypeKind::Named { path, .. } => {
+                path.last().map_or(&[], |name| self.members(name))
+            }
+            TypeKind::Reference { target, .. } | TypeKind::Pointer { target, .. } => {
+                self.members_of_type(target)
+            }
+            _ => &[],
+        }
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, span: SourceSpan, scope: Range<usize>, ty: Option<Type>) {
+        self.symbols.push(Symbol { name: name.to_string(), kind, span, scope, ty, docs: None });
+    }
+
+    /// Declare `item`, visible in `scope`, and everything inside it.
+    fn item(&mut self, item: &Item, scope: Range<usize>) {
+        let span = item.span;
+        let first = self.symbols.len();
+        match &item.kind {
+            ItemKind::Function { name, params, return_type, body, .. } => {
+                let ty = signature(params, return_type.as_ref(), span);
+                self.declare(name, SymbolKind::Function, span, scope, Some(ty));
+                if let Some(body) = body {
+                    self.params(params, range(body.span));
+                    self.expr(body);
+                }
+            }
+            ItemKind::Struct { name, fields, .. } => {
+                self.declare(name, SymbolKind::Struct, span, scope, None);
+                if let StructFields::Named(fields) = fields {
+                    let members = field_members(fields);
+                    self.members.entry(name.clone()).or_default().extend(members);
+                }
+            }
+            ItemKind::Union { name, fields, .. } => {
+                self.declare(name, SymbolKind::Union, span, scope, None);
+                let members = field_members(fields);
+                self.members.entry(name.clone()).or_default().extend(members);
+            }
+            ItemKind::Enum { name, .. } => self.declare(name, SymbolKind::Enum, span, scope, None),
+            ItemKind::Trait { name, .. } => self.declare(name, SymbolKind::Trait, span, scope, None),
+            ItemKind::TypeAlias { name, ty, .. } => {
+                self.declare(name, SymbolKind::TypeAlias, span, scope, Some(ty.clone()))
+            }
+            ItemKind::Const { name, ty, value } => {
+                self.declare(name, SymbolKind::Const, span, scope, Some(ty.clone()));
+                self.expr(value);
+            }
+            ItemKind::Static { name, ty, value, .. } => {
+                self.declare(name, SymbolKind::Static, span, scope, Some(ty.clone()));
+                self.expr(value);
+            }
+            ItemKind::Module { name, items, .. } => {
+                self.declare(name, SymbolKind::Module, span, scope, None);
+                for inner in items {
+                    self.item(inner, range(span));
+                }
+            }
+            ItemKind::Macro { name, .. } => self.declare(name, SymbolKind::Macro, span, scope, None),
+            ItemKind::Impl { self_ty, items, .. } => {
+                let type_name = match &self_ty.kind {
+                    TypeKind::Named { path, .. } => path.last().cloned(),
+                    _ => None,
+                };
+                for impl_item in items {
+                    let ImplItem::Function { name, params, return_type, body, .. } = impl_item else {
+                        continue;
+                    };
+                    if let Some(type_name) = &type_name {
+                        let ty = signature(params, return_type.as_ref(), span);
+                        self.members.entry(type_name.clone()).or_default().push(Member {
+                            name: name.clone(),
+                            ty: Some(ty),
+                            is_method: true,
+                        });
+                    }
+                    self.params(params, range(body.span));
+                    self.expr(body);
+                }
+            }
+            ItemKind::Extern { items, .. } => {
+                for extern_item in items {
+                    match extern_item {
+                        ExternItem::Function { name, params, return_type, .. } => {
+                            let ty = signature(params, return_type.as_ref(), span);
+                            self.declare(name, SymbolKind::Function, span, scope.clone(), Some(ty));
+                        }
+                        ExternItem::Static { name, ty, .. } => {
+                            self.declare(name, SymbolKind::Static, span, scope.clone(), Some(ty.clone()));
+                        }
+                    }
+                }
+            }
+            ItemKind::Use { .. } | ItemKind::Error => {}
+        }
+        // An item declares its own name before anything inside it.
+        if let Some(symbol) = self.symbols.get_mut(first)
+            && symbol.span == span
+        {
+            symbol.docs = item.docs();
+        }
+    }
+
+    fn params(&mut self, params: &[FnParam], scope: Range<usize>) {
+        for param in params {
+            self.pattern(&param.pattern, SymbolKind::Parameter, scope.clone(), Some(&param.ty));
+        }
+    }
+
+    /// Bind the names of `pattern`; only a plain identifier gets `ty`.
+    fn pattern(&mut self, pattern: &Pattern, kind: SymbolKind, scope: Range<usize>, ty: Option<&Type>) {
+        match &pattern.kind {
+            PatternKind::Ident(name) => self.declare(name, kind, pattern.span, scope, ty.cloned()),
+            PatternKind::Tuple(pats) | PatternKind::Slice(pats) | PatternKind::Or(pats) => {
+                for p in pats {
+                    self.pattern(p, kind, scope.clone(), None);
+                }
+            }
+            PatternKind::Enum { fields, .. } => {
+                for p in fields {
+                    self.pattern(p, kind, scope.clone(), None);
+                }
+            }
+            PatternKind::Struct { fields, .. } => {
+                for field in fields {
+                    match &field.pattern {
+                        Some(p) => self.pattern(p, kind, scope.clone(), None),
+                        None => self.declare(&field.name, kind, field.span, scope.clone(), None),
+                    }
+                }
+            }
+            PatternKind::Guard { pattern, condition } => {
+                self.pattern(pattern, kind, scope, ty);
+                self.expr(condition);
+            }
+            PatternKind::Wild | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        let end = range(block.span).end;
+        for stmt in &block.statements {
+            self.stmt(stmt, end, range(block.span));
+        }
+        if let Some(expr) = &block.expr {
+            self.expr(expr);
+        }
+    }
+
+    /// A statement of a block whose source ends at `end`.
+    fn stmt(&mut self, stmt: &Stmt, end: usize, block: Range<usize>) {
+        match &stmt.kind {
+            StmtKind::Let { pattern, ty, initializer, .. } => {
+                if let Some(init) = initializer {
+                    self.expr(init);
+                }
+                let ty = ty.as_ref().or(initializer.as_ref().and_then(|e| e.ty.as_ref()));
+                let scope = range(stmt.span).end..end;
+                self.pattern(pattern, SymbolKind::Variable, scope, ty);
+            }
+            StmtKind::Expr(expr) | StmtKind::Defer { body: expr } => self.expr(expr),
+            // Items in a block are visible in the whole block.
+            StmtKind::Item(item) => self.item(item, block),
+            StmtKind::Macro { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::For { pattern, iterable, body, .. } => {
+                self.expr(iterable);
+                self.pattern(pattern, SymbolKind::Variable, range(body.span), None);
+                self.expr(body);
+            }
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    self.pattern(&arm.pattern, SymbolKind::Variable, range(arm.span), None);
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                }
+            }
+            ExprKind::Closure { params, body, .. } => {
+                for param in params {
+                    let scope = range(body.span);
+                    self.pattern(&param.pattern, SymbolKind::Parameter, scope, param.ty.as_ref());
+                }
+                self.expr(body);
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.expr(receiver);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Index { object, index } => {
+                self.expr(object);
+                self.expr(index);
+            }
+            ExprKind::Assign { target, value, .. } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                if let Some(e) = else_branch {
+                    self.expr(e);
+                }
+            }
+            ExprKind::While { condition, body, .. } => {
+                self.expr(condition);
+                self.expr(body);
+            }
+            ExprKind::Range { start, end, .. } => {
+                start.iter().chain(end.iter()).for_each(|e| self.expr(e));
+            }
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => {
+                value.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::Tuple(elems) => elems.iter().for_each(|e| self.expr(e)),
+            ExprKind::Array { elements, repeat } => {
+                elements.iter().for_each(|e| self.expr(e));
+                repeat.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::Struct { fields, base, .. } => {
+                fields.iter().filter_map(|f| f.value.as_ref()).for_each(|e| self.expr(e));
+                base.iter().for_each(|e| self.expr(e));
+            }
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Loop { body: inner, .. }
+            | ExprKind::Async { body: inner, .. }
+            | ExprKind::Spawn { body: inner }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner } => self.expr(inner),
+            ExprKind::Literal(_)
+            | ExprKind::Variable { .. }
+            | ExprKind::Continue { .. }
+            | ExprKind::Macro { .. }
+            | ExprKind::Error => {}
+        }
+    }
+}
+
+/// Expressions directly inside `expr`; for a block, those of its statements.
+pub(crate) fn sub_exprs(expr: &Expr) -> Vec<&Expr> {
+    fn block_exprs(block: &Block) -> Vec<&Expr> {
+        let mut out = Vec::new();
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Expr(e) | StmtKind::Defer { body: e } => out.push(e),
+                StmtKind::Let { initializer: Some(e), .. } => out.push(e),
+                _ => {}
+            }
+        }
+        out.extend(block.expr.as_deref());
+        out
+    }
+    match &expr.kind {
+        ExprKind::Block(block) => block_exprs(block),
+        ExprKind::Call { callee, args, .. } => std::iter::once(&**callee).chain(args).collect(),
+        ExprKind::MethodCall { receiver, args, .. } => {
+            std::iter::once(&**receiver).chain(args).collect()
+        }
+        ExprKind::Binary { left, right, .. } => vec![left, right],
+        ExprKind::Index { object, index } => vec![object, index],
+        ExprKind::Assign { target, value, .. } => vec![target, value],
+        ExprKind::If { condition, then_branch, else_branch } => {
+            let mut out: Vec<&Expr> = vec![condition, then_branch];
+            out.extend(else_branch.as_deref());
+            out
+        }
+        ExprKind::While { condition, body, .. } => vec![condition, body],
+        ExprKind::For { iterable, body, .. } => vec![iterable, body],
+        ExprKind::Match { expr, arms } => {
+            let mut out: Vec<&Expr> = vec![expr];
+            for arm in arms {
+                out.extend(arm.guard.as_ref());
+                out.push(&arm.body);
+            }
+            out
+        }
+        ExprKind::Range { start, end, .. } => start.iter().chain(end).map(|e| &**e).collect(),
+        ExprKind::Break { value, .. } | ExprKind::Return { value } => {
+            value.iter().map(|e| &**e).collect()
+        }
+        ExprKind::Tuple(elems) => elems.iter().collect(),
+        ExprKind::Array { elements, repeat } => {
+            elements.iter().chain(repeat.iter().map(|e| &**e)).collect()
+        }
+        ExprKind::Struct { fields, base, .. } => fields
+            .iter()
+            .filter_map(|f| f.value.as_ref())
+            .chain(base.iter().map(|e| &**e))
+            .collect(),
+        ExprKind::Closure { body, .. } => vec![body],
+        ExprKind::FieldAccess { object: inner, .. }
+        | ExprKind::Unary { expr: inner, .. }
+        | ExprKind::Loop { body: inner, .. }
+        | ExprKind::Async { body: inner, .. }
+        | ExprKind::Spawn { body: inner }
+        | ExprKind::Await { expr: inner }
+        | ExprKind::Try { expr: inner }
+        | ExprKind::Unsafe { body: inner }
+        | ExprKind::Cast { expr: inner, .. }
+        | ExprKind::Reference { expr: inner, .. }
+        | ExprKind::Dereference { expr: inner } => vec![inner],
+        ExprKind::Literal(_)
+        | ExprKind::Variable { .. }
+        | ExprKind::Continue { .. }
+        | ExprKind::Macro { .. }
+        | ExprKind::Error => Vec::new(),
+    }
+}
+
+/// Bodies and initializers of an item that contain expressions.
+fn item_exprs(item: &Item) -> Vec<&Expr> {
+    match &item.kind {
+        ItemKind::Function { body, .. } => body.iter().collect(),
+        ItemKind::Const { value, .. } | ItemKind::Static { value, .. } => vec![value],
+        ItemKind::Impl { items, .. } => items
+            .iter()
+            .filter_map(|i| match i {
+                ImplItem::Function { body, .. } => Some(body),
+                _ => None,
+            })
+            .collect(),
+        ItemKind::Module { items, .. } => items.iter().flat_map(item_exprs).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The innermost expression whose span contains `offset`.
+pub fn expr_at(program: &Program, offset: usize) -> Option<&Expr> {
+    let contains = |e: &&Expr| range(e.span).contains(&offset);
+    let mut found = program.items.iter().flat_map(item_exprs).find(contains)?;
+    while let Some(inner) = sub_exprs(found).into_iter().find(contains) {
+        found = inner;
+    }
+    Some(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(offset: usize, len: usize) -> SourceSpan {
+        SourceSpan::new(offset.into(), len)
+    }
+
+    fn ident(name: &str, offset: usize) -> Pattern {
+        Pattern { kind: PatternKind::Ident(name.to_string()), span: span(offset, name.len()) }
+    }
+
+    #[test]
+    fn let_bindings_are_visible_after_their_statement() {
+        // fn main() { let a = 1; let b = 2; }
+        let let_stmt = |name: &str, offset: usize| Stmt {
+            kind: StmtKind::Let {
+                pattern: ident(name, offset + 4),
+                ty: Some(Type::primitive(PrimitiveType::I64, span(0, 0))),
+                initializer: None,
+                mutable: false,
+            },
+            span: span(offset, 11),
+        };
+        let body = Expr::new(
+            ExprKind::Block(Block {
+                statements: vec![let_stmt("a", 12), let_stmt("b", 24)],
+                expr: None,
+                span: span(10, 27),
+            }),
+            span(10, 27),
+        );
+        let program = Program {
+            items: vec![Item {
+                kind: ItemKind::Function {
+                    name: "main".to_string(),
+                    generics: vec![],
+                    params: vec![],
+                    return_type: None,
+                    body: Some(body),
+                    safety: shared::SafetyLevel::Safe,
+                    async_: false,
+                    const_: false,
+                },
+                attrs: vec![],
+                vis: shared::Visibility::Private,
+                span: span(0, 37),
+            }],
+            span: span(0, 37),
+        };
+
+        let table = SymbolTable::build(&program);
+        let names = |offset| -> Vec<String> {
+            table.visible_at(offset).iter().map(|s| s.name.clone()).collect()
+        };
+        assert_eq!(names(20), ["main"]);
+        assert_eq!(names(30), ["a", "main"]);
+        assert_eq!(names(36), ["a", "b", "main"]);
+        assert_eq!(table.resolve("a", 30).map(|s| s.kind), Some(SymbolKind::Variable));
+        assert!(table.resolve("b", 30).is_none());
+    }
+
+    #[test]
+    fn items_carry_their_doc_comments() {
+        // /// A point.
+        // struct P {}
+        let item = Item::new(
+            ItemKind::Struct { name: "P".to_string(), generics: vec![], fields: StructFields::Named(vec![]) },
+            span(14, 11),
+        )
+        .with_attrs(vec![shared::ast::Attribute::doc("A point.".to_string(), span(0, 13))]);
+        let table = SymbolTable::build(&Program { items: vec![item], span: span(0, 25) });
+        assert_eq!(table.symbols()[0].docs.as_deref(), Some("A point."));
+    }
+}
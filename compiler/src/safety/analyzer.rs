@@ -4,57 +4,121 @@
 //! Performs comprehensive safety checks including:
 //! - Memory safety analysis
 //! - Resource leak detection
-//! - Timing analysis for real-time systems
+//! - Timing analysis for real-time systems, in `realtime`
 //! - Null pointer analysis
 //! - Buffer overflow detection
-
+//! - Data races between spawned threads and the code that spawned them
+
+use super::stack::find_stack_overflows;
+use super::config::{allowed_lints, lint_code};
+use super::init::find_uninitialized;
+use super::realtime::check_realtime;
+use super::resources::{bound_names, find_leaks, place_root};
+use crate::types::utils::is_copy_type;
+use shared::ast::stmt::FnParam;
+use shared::ast::types::PrimitiveType;
 use shared::{
-    Program, Item, ItemKind, Stmt, StmtKind, Expr, ExprKind, Type, TypeKind,
-    SafetyLevel, Result, TlError
+    Program, Item, ItemKind, Stmt, StmtKind, Expr, ExprKind, BinaryOp, Type, TypeKind, PatternKind, Literal,
+    SafetyLevel, Result
 };
 use miette::SourceSpan;
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 
+/// Prelude functions that only read their arguments, so passing a value to
+/// one does not move it.
+const BORROWING_PRELUDE: &[&str] = &["print", "println", "eprint", "eprintln", "format", "assert"];
+
 /// Safety analysis context and results.
 pub struct SafetyAnalyzer {
     /// Source code for error reporting
     source: String,
     /// Currently active variables and their safety status
     variables: HashMap<String, VariableSafety>,
-    /// Memory allocations that need to be freed
-    pending_allocations: HashSet<AllocationId>,
-    /// Resource acquisitions that need to be released
-    pending_resources: HashSet<ResourceId>,
     /// Safety violations found during analysis
     violations: Vec<SafetyViolation>,
-    /// Function call stack for recursion detection
-    call_stack: Vec<String>,
-    /// Maximum allowed call depth for real-time systems
+    /// Maximum allowed call depth, in frames
     max_call_depth: usize,
+    /// Whether heap-allocating builtins may be called (false for embedded)
+    heap_allowed: bool,
+    /// Threads spawned by the code being analyzed that may still be running
+    threads: Vec<RunningThread>,
+    /// The `spawn` bodies being analyzed, innermost last
+    spawns: Vec<SpawnFrame>,
+    /// Counter giving each declaration and thread its own id
+    next_id: u64,
+    /// The program's `static mut`s, which only `unsafe` code may use
+    mutable_statics: HashSet<String>,
+}
+
+/// One read or write of a variable.
+#[derive(Debug, Clone)]
+struct Access {
+    name: String,
+    declaration: u64,
+    span: SourceSpan,
+    write: bool,
+}
+
+impl Access {
+    /// Whether `self` and `other` race when they may happen at once.
+    fn conflicts_with(&self, other: &Access) -> bool {
+        self.declaration == other.declaration && (self.write || other.write)
+    }
 }
 
+/// A spawned thread that has not been joined.
+#[derive(Debug, Clone)]
+struct RunningThread {
+    id: u64,
+    /// The `spawn` expression that started it
+    spawned_at: SourceSpan,
+    /// The variable, by name and declaration, holding the handle `join` takes
+    handle: Option<(String, u64)>,
+    /// Its accesses of variables it shares with the code that spawned it
+    accesses: Vec<Access>,
+}
+
+/// A `spawn` body being analyzed.
+struct SpawnFrame {
+    /// The declarations visible where the thread was spawned
+    outer: HashSet<u64>,
+    /// Accesses of those so far
+    accesses: Vec<Access>,
+}
+
+/// Estimated stack frame size, used to turn a stack budget into a call depth.
+pub const ESTIMATED_FRAME_BYTES: usize = 256;
+
+/// Builtins that allocate on the heap and are rejected by the embedded profile.
+pub const HEAP_BUILTINS: &[&str] = &[
+    "malloc", "alloc", "allocate", "Box::new", "Vec::new", "Vec::with_capacity",
+    "String::new", "String::from", "format", "vec",
+];
+
 /// Safety information about a variable.
 #[derive(Debug, Clone)]
 pub struct VariableSafety {
-    /// Whether the variable has been initialized
-    pub initialized: bool,
-    /// Whether the variable has been moved (no longer accessible)
-    pub moved: bool,
-    /// Whether the variable is borrowed
-    pub borrowed: bool,
+    /// Where the variable was last moved out, unless it was assigned since
+    pub moved: Option<SourceSpan>,
     /// Lifetime information
     pub lifetime: Option<String>,
     /// Safety level required to access this variable
     pub safety_level: SafetyLevel,
+    /// Type of the variable, when known; only values of known non-`Copy`
+    /// types are moved
+    pub ty: Option<Type>,
+    /// Which declaration this is, telling apart variables of the same name
+    pub declaration: u64,
 }
 
 /// Unique identifier for memory allocations.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-pub struct AllocationId(u64);
+pub struct AllocationId(pub(crate) u64);
 
 /// Unique identifier for system resources.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-pub struct ResourceId(u64);
+pub struct ResourceId(pub(crate) u64);
 
 /// Types of safety violations.
 #[derive(Debug, Clone)]
@@ -92,11 +156,19 @@ pub enum SafetyViolation {
         buffer_size: Option<u64>,
         access_index: String,
     },
-    /// Stack overflow risk from deep recursion
+    /// Stack overflow risk from recursion or a deep call chain
     StackOverflow {
         span: SourceSpan,
-        call_depth: usize,
+        /// Frames on the deepest chain; `None` for recursion, which has no
+        /// bound
+        call_depth: Option<usize>,
+        /// Frames the stack allows
+        max_depth: usize,
+        /// The function the chain or cycle starts from
         function_name: String,
+        /// The functions called along the chain or round the cycle, starting
+        /// with `function_name`
+        call_chain: Vec<String>,
     },
     /// Unsafe operation in safe context
     UnsafeOperation {
@@ -112,38 +184,99 @@ pub enum SafetyViolation {
     },
     /// Real-time constraint violation
     RealtimeViolation {
+        span: SourceSpan,
+        /// The `#[realtime]` or `#[wcet]` function
+        function: String,
+        issue: RealtimeIssue,
+        /// The call in that function leading to the issue, when it is in
+        /// a function it calls
+        called_at: Option<SourceSpan>,
+    },
+    /// Heap allocation where only static memory is allowed
+    HeapAllocation {
         span: SourceSpan,
         function: String,
-        max_time: u64,
-        estimated_time: u64,
     },
 }
 
+/// Why a real-time function may not finish in time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RealtimeIssue {
+    /// A call that allocates on the heap
+    Allocation { call: String },
+    /// A loop whose number of iterations is not known at compile time
+    UnboundedLoop,
+    /// A call that may wait for another thread, a lock or I/O
+    BlockingCall { call: String },
+    /// A call back into a function that is still running
+    Recursion { callee: String },
+    /// The estimated worst case, in cycles, exceeds the `#[wcet]` bound
+    Overrun { max_time: u64, estimated_time: u64 },
+}
+
 impl SafetyAnalyzer {
     /// Create a new safety analyzer.
     pub fn new(source: String) -> Self {
         Self {
             source,
             variables: HashMap::new(),
-            pending_allocations: HashSet::new(),
-            pending_resources: HashSet::new(),
             violations: Vec::new(),
-            call_stack: Vec::new(),
             max_call_depth: 256, // Default stack limit for safety-critical systems
+            heap_allowed: true,
+            threads: Vec::new(),
+            spawns: Vec::new(),
+            next_id: 0,
+            mutable_statics: HashSet::new(),
         }
     }
 
+    /// Limit call depth to what fits in `bytes` of stack.
+    pub fn with_stack_size(mut self, bytes: usize) -> Self {
+        self.max_call_depth = (bytes / ESTIMATED_FRAME_BYTES).max(1);
+        self
+    }
+
+    /// Limit call depth to `depth` frames.
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = depth.max(1);
+        self
+    }
+
+    /// Allow or reject calls to heap-allocating builtins.
+    pub fn with_heap_allowed(mut self, allowed: bool) -> Self {
+        self.heap_allowed = allowed;
+        self
+    }
+
     /// Analyze a complete program for safety violations.
     pub fn analyze_program(&mut self, program: &Program) -> Result<Vec<SafetyViolation>> {
         self.violations.clear();
+        self.mutable_statics = program
+            .items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Static { name, mutable: true, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
 
         // Analyze each top-level item
         for item in &program.items {
             self.analyze_item(item)?;
         }
-
-        // Check for resource leaks at program end
-        self.check_resource_leaks();
+        self.violations.extend(find_stack_overflows(program, self.max_call_depth));
+        let realtime = check_realtime(program, &self.source)?;
+        self.violations.extend(realtime);
+
+        let allowed = allowed_lints(program, &self.source)?;
+        self.violations.retain(|violation| {
+            let span = violation.span();
+            !allowed.iter().any(|(item, lint)| {
+                *lint == violation.lint()
+                    && item.offset() <= span.offset()
+                    && span.offset() + span.len() <= item.offset() + item.len()
+            })
+        });
 
         Ok(self.violations.clone())
     }
@@ -151,8 +284,8 @@ impl SafetyAnalyzer {
     /// Analyze a top-level item.
     fn analyze_item(&mut self, item: &Item) -> Result<()> {
         match &item.kind {
-            ItemKind::Function { name, body, safety, .. } => {
-                self.analyze_function(name, body.as_ref(), *safety, item.span)?;
+            ItemKind::Function { params, body, safety, .. } => {
+                self.analyze_function(params, body.as_ref(), *safety)?;
             }
 
             ItemKind::Static { value, .. } => {
@@ -172,30 +305,27 @@ impl SafetyAnalyzer {
     }
 
     /// Analyze a function for safety violations.
-    fn analyze_function(&mut self, name: &str, body: Option<&Expr>,
-                        safety_level: SafetyLevel, span: SourceSpan) -> Result<()> {
-        // Check for stack overflow risk
-        if self.call_stack.len() >= self.max_call_depth {
-            self.violations.push(SafetyViolation::StackOverflow {
-                span,
-                call_depth: self.call_stack.len(),
-                function_name: name.to_string(),
-            });
-            return Ok(()); // Don't continue analysis to prevent infinite recursion
-        }
-
+    fn analyze_function(&mut self, params: &[FnParam], body: Option<&Expr>,
+                        safety_level: SafetyLevel) -> Result<()> {
         // Enter function scope
-        self.call_stack.push(name.to_string());
         let prev_variables = self.variables.clone();
+        let prev_threads = std::mem::take(&mut self.threads);
+        for param in params {
+            if let PatternKind::Ident(name) = &param.pattern.kind {
+                self.declare(name, Some(param.ty.clone()), safety_level);
+            }
+        }
 
         // Analyze function body if present
         if let Some(body_expr) = body {
             self.analyze_expr_in_context(body_expr, safety_level)?;
+            self.violations.extend(find_uninitialized(params, body_expr));
+            self.violations.extend(find_leaks(params, body_expr));
         }
 
         // Exit function scope
         self.variables = prev_variables;
-        self.call_stack.pop();
+        self.threads = prev_threads;
 
         Ok(())
     }
@@ -210,6 +340,7 @@ impl SafetyAnalyzer {
         match &expr.kind {
             ExprKind::Variable { path } => {
                 self.check_variable_access(path, expr.span)?;
+                self.check_mutable_static(expr, context_safety);
             }
 
             ExprKind::Call { callee, args, safety } => {
@@ -222,19 +353,33 @@ impl SafetyAnalyzer {
                     });
                 }
 
-                // Analyze callee and arguments
-                self.analyze_expr_in_context(callee, *safety)?;
+                // Analyze callee and arguments; the prelude's printing
+                // functions only read theirs
+                self.analyze_expr_in_context(callee, context_safety)?;
+                let borrows = matches!(&callee.kind, ExprKind::Variable { path } if path.len() == 1 && BORROWING_PRELUDE.contains(&path[0].as_str()));
                 for arg in args {
-                    self.analyze_expr_in_context(arg, *safety)?;
+                    self.analyze_expr_in_context(arg, context_safety)?;
+                    if !borrows {
+                        self.move_out(arg, arg.span);
+                    }
                 }
 
                 // Check for specific unsafe operations
-                self.check_unsafe_call(callee, args, expr.span)?;
+                self.check_unsafe_call(callee, args, expr.span, context_safety)?;
+
+                if let (ExprKind::Variable { path }, [handle]) = (&callee.kind, args.as_slice())
+                    && *path == ["join"]
+                {
+                    self.join_thread(handle);
+                }
             }
 
-            ExprKind::Binary { left, right, .. } => {
+            ExprKind::Binary { left, op, right } => {
                 self.analyze_expr_in_context(left, context_safety)?;
                 self.analyze_expr_in_context(right, context_safety)?;
+                if matches!(op, BinaryOp::Add | BinaryOp::Sub) && is_pointer(left) {
+                    self.require_unsafe("pointer arithmetic", expr.span, context_safety);
+                }
             }
 
             ExprKind::Unary { expr: inner, .. } => {
@@ -242,31 +387,148 @@ impl SafetyAnalyzer {
             }
 
             ExprKind::Assign { target, value, .. } => {
-                self.analyze_assignment(target, value, expr.span)?;
+                self.analyze_assignment(target, value, context_safety)?;
+            }
+
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.analyze_expr_in_context(receiver, context_safety)?;
+                for arg in args {
+                    self.analyze_expr_in_context(arg, context_safety)?;
+                    self.move_out(arg, arg.span);
+                }
+            }
+
+            ExprKind::FieldAccess { object, .. } => {
+                self.analyze_expr_in_context(object, context_safety)?;
+            }
+
+            // The values of the fields move into the struct; `Point { x }`
+            // moves the variable `x`.
+            ExprKind::Struct { fields, base, .. } => {
+                for field in fields {
+                    let shorthand;
+                    let value = match &field.value {
+                        Some(value) => value,
+                        None => {
+                            shorthand = Expr::new(ExprKind::Variable { path: vec![field.name.clone()] }, field.span);
+                            &shorthand
+                        }
+                    };
+                    self.analyze_expr_in_context(value, context_safety)?;
+                    self.move_out(value, value.span);
+                }
+                if let Some(base) = base {
+                    self.analyze_expr_in_context(base, context_safety)?;
+                }
             }
 
             ExprKind::If { condition, then_branch, else_branch } => {
                 self.analyze_expr_in_context(condition, context_safety)?;
+                let before = self.variables.clone();
+                let threads_before = self.threads.clone();
                 self.analyze_expr_in_context(then_branch, context_safety)?;
+                let after_then = std::mem::replace(&mut self.variables, before);
+                let threads_after_then = std::mem::replace(&mut self.threads, threads_before);
                 if let Some(else_expr) = else_branch {
                     self.analyze_expr_in_context(else_expr, context_safety)?;
                 }
+                self.join_variable_states(&after_then);
+                self.join_threads(threads_after_then);
+            }
+
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.analyze_expr_in_context(scrutinee, context_safety)?;
+                let before = self.variables.clone();
+                let threads_before = self.threads.clone();
+                let mut joined: Option<HashMap<String, VariableSafety>> = None;
+                let mut running = Vec::new();
+                for arm in arms {
+                    self.variables = before.clone();
+                    self.threads = threads_before.clone();
+                    // Binding part of the scrutinee by value moves it into the arm
+                    let mut names = Vec::new();
+                    bound_names(&arm.pattern, &mut names);
+                    if !names.is_empty() {
+                        self.move_out(scrutinee, arm.pattern.span);
+                    }
+                    let ty = match &arm.pattern.kind {
+                        PatternKind::Ident(_) => self.value_type(scrutinee),
+                        _ => None,
+                    };
+                    for name in &names {
+                        self.declare(name, ty.clone(), context_safety);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.analyze_expr_in_context(guard, context_safety)?;
+                    }
+                    self.analyze_expr_in_context(&arm.body, context_safety)?;
+                    self.end_scope(&before, &names);
+                    if let Some(other_arms) = &joined {
+                        self.join_variable_states(other_arms);
+                    }
+                    joined = Some(self.variables.clone());
+                    running.extend(std::mem::take(&mut self.threads));
+                }
+                if joined.is_none() {
+                    self.variables = before;
+                }
+                self.threads = threads_before;
+                self.join_threads(running);
+            }
+
+            ExprKind::For { pattern, iterable, body, .. } => {
+                self.analyze_expr_in_context(iterable, context_safety)?;
+                self.move_out(iterable, iterable.span);
+                let before = self.variables.clone();
+                let mut names = Vec::new();
+                bound_names(pattern, &mut names);
+                for name in &names {
+                    self.declare(name, None, context_safety);
+                }
+                self.analyze_loop_body(body, context_safety)?;
+                self.end_scope(&before, &names);
+            }
+
+            ExprKind::While { condition, body, .. } => {
+                self.analyze_expr_in_context(condition, context_safety)?;
+                self.analyze_loop_body(body, context_safety)?;
+            }
+
+            ExprKind::Loop { body, .. } => {
+                self.analyze_loop_body(body, context_safety)?;
             }
 
             ExprKind::Block(block) => {
                 // Enter block scope
                 let prev_variables = self.variables.clone();
+                let mut declared = Vec::new();
+                let mut deferred = Vec::new();
 
                 for stmt in &block.statements {
+                    match &stmt.kind {
+                        StmtKind::Let { pattern, .. } => bound_names(pattern, &mut declared),
+                        // Deferred blocks run after the rest of the block, on
+                        // the variables in scope where they were deferred
+                        StmtKind::Defer { body } => {
+                            deferred.push((body, self.variables.clone()));
+                            continue;
+                        }
+                        _ => {}
+                    }
                     self.analyze_stmt_in_context(stmt, context_safety)?;
                 }
 
                 if let Some(block_expr) = &block.expr {
                     self.analyze_expr_in_context(block_expr, context_safety)?;
                 }
+                for (body, scope) in deferred.into_iter().rev() {
+                    let after = std::mem::replace(&mut self.variables, scope);
+                    self.analyze_expr_in_context(body, context_safety)?;
+                    self.variables = after;
+                }
 
                 // Exit block scope, but preserve variable state changes
-                self.merge_variable_states(prev_variables);
+                self.end_scope(&prev_variables, &declared);
             }
 
             ExprKind::Index { object, index } => {
@@ -277,12 +539,14 @@ impl SafetyAnalyzer {
 
             ExprKind::Dereference { expr: target } => {
                 self.analyze_expr_in_context(target, context_safety)?;
+                if is_pointer(target) {
+                    self.require_unsafe("dereference of raw pointer", expr.span, context_safety);
+                }
                 self.check_null_dereference(target, expr.span)?;
             }
 
             ExprKind::Reference { expr: target, .. } => {
                 self.analyze_expr_in_context(target, context_safety)?;
-                self.check_borrow_rules(target, expr.span)?;
             }
 
             ExprKind::Unsafe { body } => {
@@ -290,6 +554,10 @@ impl SafetyAnalyzer {
                 self.analyze_expr_in_context(body, SafetyLevel::Unsafe)?;
             }
 
+            ExprKind::Spawn { body } => {
+                self.analyze_spawn(body, expr.span, context_safety)?;
+            }
+
             _ => {
                 // Handle other expression types as needed
             }
@@ -305,20 +573,22 @@ impl SafetyAnalyzer {
                 self.analyze_expr_in_context(expr, context_safety)?;
             }
 
-            StmtKind::Let { pattern, initializer, .. } => {
-                if let shared::PatternKind::Ident(name) = &pattern.kind {
-                    let var_safety = VariableSafety {
-                        initialized: initializer.is_some(),
-                        moved: false,
-                        borrowed: false,
-                        lifetime: None,
-                        safety_level: context_safety,
-                    };
-
-                    self.variables.insert(name.clone(), var_safety);
+            StmtKind::Let { pattern, ty, initializer, .. } => {
+                // The initializer is evaluated before the new binding exists,
+                // so `let s = s;` reads and moves the outer `s`
+                if let Some(init_expr) = initializer {
+                    self.analyze_expr_in_context(init_expr, context_safety)?;
+                    self.move_out(init_expr, init_expr.span);
+                }
 
-                    if let Some(init_expr) = initializer {
-                        self.analyze_expr_in_context(init_expr, context_safety)?;
+                if let PatternKind::Ident(name) = &pattern.kind {
+                    let ty = ty.clone().or_else(|| initializer.as_ref().and_then(|init| self.value_type(init)));
+                    let declaration = self.declare(name, ty, context_safety);
+                    // `let h = spawn { .. }` names the thread for `join(h)`
+                    if let Some(Expr { kind: ExprKind::Spawn { .. }, .. }) = initializer
+                        && let Some(thread) = self.threads.last_mut()
+                    {
+                        thread.handle = Some((name.clone(), declaration));
                     }
                 }
             }
@@ -335,76 +605,79 @@ impl SafetyAnalyzer {
         if path.len() == 1 {
             let name = &path[0];
             if let Some(var_safety) = self.variables.get(name) {
-                if !var_safety.initialized {
-                    self.violations.push(SafetyViolation::UninitializedVariable {
-                        name: name.clone(),
-                        span,
-                    });
-                }
-
-                if var_safety.moved {
+                // Reads before assignment are found by `init::find_uninitialized`
+                if let Some(move_location) = var_safety.moved {
                     self.violations.push(SafetyViolation::UseAfterMove {
                         name: name.clone(),
                         span,
-                        move_location: span, // TODO: Track actual move location
+                        move_location,
                     });
                 }
             }
+            self.access(name, span, false);
         }
 
         Ok(())
     }
 
-    fn check_unsafe_call(&mut self, callee: &Expr, args: &[Expr], span: SourceSpan) -> Result<()> {
+    fn check_unsafe_call(&mut self, callee: &Expr, _args: &[Expr], span: SourceSpan,
+                         context_safety: SafetyLevel) -> Result<()> {
+        // Static-memory profiles reject heap allocation outright
+        if !self.heap_allowed
+            && let ExprKind::Variable { path } = &callee.kind {
+                let func_name = path.join("::");
+                if HEAP_BUILTINS.contains(&func_name.as_str()) {
+                    self.violations.push(SafetyViolation::HeapAllocation {
+                        span,
+                        function: func_name,
+                    });
+                }
+            }
+
         // Check for known unsafe functions
-        if let ExprKind::Variable { path } = &callee.kind {
-            if path.len() == 1 {
+        if let ExprKind::Variable { path } = &callee.kind
+            && path.len() == 1 {
                 let func_name = &path[0];
+                // Allocations and releases are followed by `resources::find_leaks`
                 match func_name.as_str() {
-                    "malloc" | "alloc" | "allocate" => {
-                        // Memory allocation - track for leak detection
-                        let alloc_id = AllocationId(self.pending_allocations.len() as u64);
-                        self.pending_allocations.insert(alloc_id);
-                    }
-
-                    "free" | "dealloc" | "deallocate" => {
-                        // Memory deallocation - remove from pending
-                        // TODO: Match with specific allocation
-                    }
-
                     "unsafe_ptr_read" | "unsafe_ptr_write" => {
-                        self.violations.push(SafetyViolation::UnsafeOperation {
-                            span,
-                            operation: format!("call to {}", func_name),
-                            required_safety: SafetyLevel::Unsafe,
-                        });
+                        self.require_unsafe(&format!("call to {}", func_name), span, context_safety);
                     }
 
                     _ => {}
                 }
             }
-        }
 
         Ok(())
     }
 
+    /// Report a use of a `static mut` that no variable hides outside `unsafe`.
+    fn check_mutable_static(&mut self, expr: &Expr, context_safety: SafetyLevel) {
+        if let ExprKind::Variable { path } = &expr.kind
+            && let [name] = path.as_slice()
+            && self.mutable_statics.contains(name)
+            && !self.variables.contains_key(name)
+        {
+            self.require_unsafe("use of mutable static", expr.span, context_safety);
+        }
+    }
+
     fn check_buffer_access(&mut self, buffer: &Expr, index: &Expr, span: SourceSpan) -> Result<()> {
         // Static analysis for buffer bounds checking
         // This is a simplified version - a full implementation would need more sophisticated analysis
 
-        if let Some(buffer_type) = &buffer.ty {
-            if let TypeKind::Array { size, .. } = &buffer_type.kind {
+        if let Some(buffer_type) = &buffer.ty
+            && let TypeKind::Array { size, .. } = &buffer_type.kind {
                 // Try to determine if index is within bounds
-                if let ExprKind::Literal(shared::Literal::Integer(idx)) = &index.kind {
-                    if let shared::types::ArraySize::Literal(size_val) = size {
-                        if (*idx as u64) >= *size_val {
+                if let ExprKind::Literal(shared::Literal::Integer(idx) | shared::Literal::TypedInteger(idx, _)) = &index.kind {
+                    if let shared::ast::types::ArraySize::Literal(size_val) = size
+                        && (*idx as u64) >= *size_val {
                             self.violations.push(SafetyViolation::BufferOverflow {
                                 span,
                                 buffer_size: Some(*size_val),
                                 access_index: idx.to_string(),
                             });
                         }
-                    }
                 } else {
                     // Dynamic index - potential overflow
                     self.violations.push(SafetyViolation::BufferOverflow {
@@ -414,7 +687,6 @@ impl SafetyAnalyzer {
                     });
                 }
             }
-        }
 
         Ok(())
     }
@@ -423,98 +695,260 @@ impl SafetyAnalyzer {
         // Check for potential null pointer dereference
         // This would need flow analysis to be fully effective
 
-        if let Some(target_type) = &target.ty {
-            if let TypeKind::Pointer { .. } = &target_type.kind {
+        if let Some(target_type) = &target.ty
+            && let TypeKind::Pointer { .. } = &target_type.kind {
                 // Pointer dereference - could be null
                 self.violations.push(SafetyViolation::NullPointerDereference {
                     span,
                     expression: format!("{:?}", target.kind),
                 });
             }
-        }
 
         Ok(())
     }
 
-    fn check_borrow_rules(&mut self, target: &Expr, span: SourceSpan) -> Result<()> {
-        // Check Rust-style borrowing rules
-        if let ExprKind::Variable { path } = &target.kind {
-            if path.len() == 1 {
+    fn analyze_assignment(&mut self, target: &Expr, value: &Expr, context_safety: SafetyLevel) -> Result<()> {
+        // Analyze the value being assigned
+        self.analyze_expr_in_context(value, context_safety)?;
+        if let ExprKind::Dereference { expr: pointer } = &target.kind
+            && is_pointer(pointer)
+        {
+            self.require_unsafe("dereference of raw pointer", target.span, context_safety);
+        }
+        self.check_mutable_static(target, context_safety);
+        self.move_out(value, value.span);
+
+        if let Some((name, _)) = place_root(target) {
+            self.access(name, target.span, true);
+        }
+
+        // Update variable state for assignment target
+        if let ExprKind::Variable { path } = &target.kind
+            && path.len() == 1 {
                 let name = &path[0];
                 if let Some(var_safety) = self.variables.get_mut(name) {
-                    if var_safety.borrowed {
-                        // Multiple borrows - potential data race
-                        self.violations.push(SafetyViolation::DataRace {
-                            span,
-                            variable: name.clone(),
-                            conflicting_access: span,
-                        });
-                    } else {
-                        var_safety.borrowed = true;
-                    }
+                    var_safety.moved = None; // Assignment reinitializes
                 }
             }
-        }
 
         Ok(())
     }
 
-    fn analyze_assignment(&mut self, target: &Expr, value: &Expr, span: SourceSpan) -> Result<()> {
-        // Analyze the value being assigned
-        self.analyze_expr(value)?;
+    // Helper methods
 
-        // Update variable state for assignment target
-        if let ExprKind::Variable { path } = &target.kind {
-            if path.len() == 1 {
-                let name = &path[0];
-                if let Some(var_safety) = self.variables.get_mut(name) {
-                    var_safety.initialized = true;
-                    var_safety.moved = false; // Assignment reinitializes
-                }
+    /// Report `operation` unless `context_safety` allows unsafe operations.
+    fn require_unsafe(&mut self, operation: &str, span: SourceSpan, context_safety: SafetyLevel) {
+        if !self.is_safety_compatible(SafetyLevel::Unsafe, context_safety) {
+            self.violations.push(SafetyViolation::UnsafeOperation {
+                span,
+                operation: operation.to_string(),
+                required_safety: SafetyLevel::Unsafe,
+            });
+        }
+    }
+
+    fn is_safety_compatible(&self, required: SafetyLevel, context: SafetyLevel) -> bool {
+        matches!(
+            (required, context),
+            (SafetyLevel::Safe, _)
+                | (SafetyLevel::Unsafe, SafetyLevel::Unsafe)
+                | (SafetyLevel::Critical, SafetyLevel::Critical)
+        )
+    }
+
+    /// Bring a variable into scope, shadowing any other of the same name,
+    /// and return its declaration id.
+    fn declare(&mut self, name: &str, ty: Option<Type>, safety_level: SafetyLevel) -> u64 {
+        let declaration = self.fresh_id();
+        self.variables.insert(name.to_string(), VariableSafety {
+            moved: None,
+            lifetime: None,
+            safety_level,
+            ty,
+            declaration,
+        });
+        declaration
+    }
+
+    fn fresh_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Record a read or write of the variable `name`, reporting a race with
+    /// any running thread that accesses it too, unless both only read.
+    fn access(&mut self, name: &str, span: SourceSpan, write: bool) {
+        let Some(declaration) = self.variables.get(name).map(|var| var.declaration) else { return };
+        let access = Access { name: name.to_string(), declaration, span, write };
+        self.check_race(&access);
+        if let Some(frame) = self.spawns.last_mut()
+            && frame.outer.contains(&declaration)
+        {
+            frame.accesses.push(access);
+        }
+    }
+
+    fn check_race(&mut self, access: &Access) {
+        let conflict = self
+            .threads
+            .iter()
+            .flat_map(|thread| &thread.accesses)
+            .find(|other| access.conflicts_with(other));
+        if let Some(other) = conflict {
+            self.violations.push(SafetyViolation::DataRace {
+                span: access.span,
+                variable: access.name.clone(),
+                conflicting_access: other.span,
+            });
+        }
+    }
+
+    /// Analyze a `spawn` body, which runs alongside everything after it
+    /// until it is joined. Its accesses of the variables it shares with the
+    /// spawning code are checked against the threads already running, and
+    /// later accesses are checked against it in turn.
+    fn analyze_spawn(&mut self, body: &Expr, span: SourceSpan, context_safety: SafetyLevel) -> Result<()> {
+        let outer_threads = std::mem::take(&mut self.threads);
+        self.spawns.push(SpawnFrame {
+            outer: self.variables.values().map(|var| var.declaration).collect(),
+            accesses: Vec::new(),
+        });
+        let result = self.analyze_expr_in_context(body, context_safety);
+        let mut frame = self.spawns.pop().expect("spawn frame pushed above");
+        // Threads the body spawned and did not join outlive it
+        let inner = std::mem::replace(&mut self.threads, outer_threads);
+        for access in inner.into_iter().flat_map(|thread| thread.accesses) {
+            if frame.outer.contains(&access.declaration) {
+                frame.accesses.push(access);
             }
         }
+        result?;
 
+        for access in &frame.accesses {
+            self.check_race(access);
+        }
+        if let Some(enclosing) = self.spawns.last_mut() {
+            enclosing.accesses.extend(
+                frame.accesses.iter().filter(|access| enclosing.outer.contains(&access.declaration)).cloned(),
+            );
+        }
+        let id = self.fresh_id();
+        self.threads.push(RunningThread { id, spawned_at: span, handle: None, accesses: frame.accesses });
         Ok(())
     }
 
-    // Helper methods
+    /// `join(handle)`: the thread `handle` names has finished.
+    fn join_thread(&mut self, handle: &Expr) {
+        let ExprKind::Variable { path } = &handle.kind else { return };
+        let [name] = path.as_slice() else { return };
+        let Some(declaration) = self.variables.get(name).map(|var| var.declaration) else { return };
+        let joined = Some((name.clone(), declaration));
+        self.threads.retain(|thread| thread.handle != joined);
+    }
 
-    fn is_safety_compatible(&self, required: SafetyLevel, context: SafetyLevel) -> bool {
-        match (required, context) {
-            (SafetyLevel::Safe, _) => true,
-            (SafetyLevel::Unsafe, SafetyLevel::Unsafe) => true,
-            (SafetyLevel::Critical, SafetyLevel::Critical) => true,
-            _ => false,
+    /// Add the threads running at the end of another path to the same
+    /// point to those running now.
+    fn join_threads(&mut self, other: Vec<RunningThread>) {
+        for thread in other {
+            if !self.threads.iter().any(|running| running.id == thread.id) {
+                self.threads.push(thread);
+            }
         }
     }
 
-    fn merge_variable_states(&mut self, prev_variables: HashMap<String, VariableSafety>) {
-        // Merge variable states from block scope back to parent scope
-        // Keep initialization and move status, but reset borrowing
-        for (name, mut new_state) in self.variables.clone() {
-            if let Some(prev_state) = prev_variables.get(&name) {
-                new_state.borrowed = prev_state.borrowed;
-                self.variables.insert(name, new_state);
+    /// The type of `expr`: as the type checker recorded it, or for a
+    /// variable as it was declared, for a string literal `str` and for a
+    /// struct literal its struct.
+    fn value_type(&self, expr: &Expr) -> Option<Type> {
+        if let Some(ty) = &expr.ty {
+            return Some(ty.clone());
+        }
+        match &expr.kind {
+            ExprKind::Variable { path } if path.len() == 1 => {
+                self.variables.get(&path[0]).and_then(|var| var.ty.clone())
             }
+            ExprKind::Literal(Literal::String(_)) => Some(Type::primitive(PrimitiveType::Str, expr.span)),
+            ExprKind::Struct { path, .. } => {
+                Some(Type::new(TypeKind::Named { path: path.clone(), generics: Vec::new() }, expr.span))
+            }
+            _ => None,
         }
     }
 
-    fn check_resource_leaks(&mut self) {
-        // Check for any remaining allocations or resources at program end
-        for &alloc_id in &self.pending_allocations {
-            self.violations.push(SafetyViolation::MemoryLeak {
-                allocation_id: alloc_id,
-                allocation_site: SourceSpan::new(0.into(), 0), // TODO: Track actual site
-            });
+    /// Record that the value of `expr` moves at `site`, if `expr` names a
+    /// variable whose type is known not to be `Copy`.
+    fn move_out(&mut self, expr: &Expr, site: SourceSpan) {
+        let ExprKind::Variable { path } = &expr.kind else { return };
+        if path.len() != 1 {
+            return;
+        }
+        let moves = self
+            .value_type(expr)
+            .is_some_and(|ty| !matches!(ty.kind, TypeKind::Unknown(_)) && !is_copy_type(&ty));
+        if let (true, Some(var_safety)) = (moves, self.variables.get_mut(&path[0])) {
+            var_safety.moved = Some(site);
+        }
+    }
+
+    /// Analyze a loop body, which may run any number of times. A variable
+    /// from before the loop that the body moves and does not reassign is
+    /// used after the move on the next iteration, and a thread the body
+    /// spawns and does not join still runs when the next iteration spawns
+    /// another.
+    fn analyze_loop_body(&mut self, body: &Expr, context_safety: SafetyLevel) -> Result<()> {
+        let before = self.variables.clone();
+        let threads_before: HashSet<u64> = self.threads.iter().map(|thread| thread.id).collect();
+        self.analyze_expr_in_context(body, context_safety)?;
+
+        for thread in self.threads.iter().filter(|thread| !threads_before.contains(&thread.id)) {
+            for access in thread.accesses.iter().filter(|access| access.write) {
+                self.violations.push(SafetyViolation::DataRace {
+                    span: access.span,
+                    variable: access.name.clone(),
+                    conflicting_access: thread.spawned_at,
+                });
+            }
         }
 
-        for &resource_id in &self.pending_resources {
-            self.violations.push(SafetyViolation::ResourceLeak {
-                resource_id,
-                resource_type: "unknown".to_string(), // TODO: Track resource type
-                acquisition_site: SourceSpan::new(0.into(), 0),
+        let mut carried: Vec<_> = self
+            .variables
+            .iter()
+            .filter(|(name, _)| before.get(*name).is_some_and(|prev| prev.moved.is_none()))
+            .filter_map(|(name, state)| state.moved.map(|site| (site, name.clone())))
+            .collect();
+        carried.sort_by_key(|(site, _)| site.offset());
+        for (site, name) in carried {
+            self.violations.push(SafetyViolation::UseAfterMove {
+                name,
+                span: site,
+                move_location: site,
             });
         }
+
+        // The body may not run at all
+        self.join_variable_states(&before);
+        Ok(())
+    }
+
+    /// Give up the variables `names` declared in a scope that is ending:
+    /// those that shadowed another get the outer state back, the rest go.
+    fn end_scope(&mut self, outer: &HashMap<String, VariableSafety>, names: &[String]) {
+        for name in names {
+            match outer.get(name) {
+                Some(state) => self.variables.insert(name.clone(), state.clone()),
+                None => self.variables.remove(name),
+            };
+        }
+    }
+
+    /// Join the current variable states with those at the end of another
+    /// path to the same point: a variable is moved if it is on either.
+    fn join_variable_states(&mut self, other: &HashMap<String, VariableSafety>) {
+        for (name, state) in self.variables.iter_mut() {
+            if let Some(other_state) = other.get(name) {
+                state.moved = state.moved.or(other_state.moved);
+            }
+        }
     }
 }
 
@@ -528,10 +962,72 @@ impl SafetyViolation {
             SafetyViolation::ResourceLeak { .. } => SafetySeverity::Warning,
             SafetyViolation::NullPointerDereference { .. } => SafetySeverity::Critical,
             SafetyViolation::BufferOverflow { .. } => SafetySeverity::Critical,
+            // Recursion may well be bounded by a base case the analysis cannot see
+            SafetyViolation::StackOverflow { call_depth: None, .. } => SafetySeverity::Warning,
             SafetyViolation::StackOverflow { .. } => SafetySeverity::Critical,
             SafetyViolation::UnsafeOperation { .. } => SafetySeverity::Error,
             SafetyViolation::DataRace { .. } => SafetySeverity::Critical,
             SafetyViolation::RealtimeViolation { .. } => SafetySeverity::Error,
+            SafetyViolation::HeapAllocation { .. } => SafetySeverity::Error,
+        }
+    }
+
+    /// The name of the lint reporting this kind of violation.
+    pub fn lint(&self) -> &'static str {
+        match self {
+            SafetyViolation::UninitializedVariable { .. } => "uninitialized_variable",
+            SafetyViolation::UseAfterMove { .. } => "use_after_move",
+            SafetyViolation::MemoryLeak { .. } => "memory_leak",
+            SafetyViolation::ResourceLeak { .. } => "resource_leak",
+            SafetyViolation::NullPointerDereference { .. } => "null_pointer_dereference",
+            SafetyViolation::BufferOverflow { .. } => "buffer_overflow",
+            SafetyViolation::StackOverflow { .. } => "stack_overflow",
+            SafetyViolation::UnsafeOperation { .. } => "unsafe_operation",
+            SafetyViolation::DataRace { .. } => "data_race",
+            SafetyViolation::RealtimeViolation { .. } => "realtime_violation",
+            SafetyViolation::HeapAllocation { .. } => "heap_allocation",
+        }
+    }
+
+    /// The diagnostic code of this kind of violation.
+    pub fn code(&self) -> &'static str {
+        lint_code(self.lint()).expect("every violation's lint has a code")
+    }
+
+    /// Where the violation is reported.
+    pub fn span(&self) -> SourceSpan {
+        match self {
+            SafetyViolation::MemoryLeak { allocation_site: span, .. }
+            | SafetyViolation::ResourceLeak { acquisition_site: span, .. }
+            | SafetyViolation::UninitializedVariable { span, .. }
+            | SafetyViolation::UseAfterMove { span, .. }
+            | SafetyViolation::NullPointerDereference { span, .. }
+            | SafetyViolation::BufferOverflow { span, .. }
+            | SafetyViolation::StackOverflow { span, .. }
+            | SafetyViolation::UnsafeOperation { span, .. }
+            | SafetyViolation::DataRace { span, .. }
+            | SafetyViolation::RealtimeViolation { span, .. }
+            | SafetyViolation::HeapAllocation { span, .. } => *span,
+        }
+    }
+
+    /// Related locations to point at besides the violation itself.
+    pub fn notes(&self) -> Vec<(String, SourceSpan)> {
+        match self {
+            // Only the same code on a later iteration can use a value where it moved
+            SafetyViolation::UseAfterMove { span, move_location, .. } if move_location == span => {
+                vec![("value moved here, in the previous iteration of the loop".to_string(), *move_location)]
+            }
+            SafetyViolation::UseAfterMove { move_location, .. } => {
+                vec![("value moved here".to_string(), *move_location)]
+            }
+            SafetyViolation::DataRace { span, conflicting_access, .. } if conflicting_access != span => {
+                vec![("conflicting access".to_string(), *conflicting_access)]
+            }
+            SafetyViolation::RealtimeViolation { function, called_at: Some(call), .. } => {
+                vec![(format!("reached from `{}` through this call", function), *call)]
+            }
+            _ => Vec::new(),
         }
     }
 
@@ -556,8 +1052,12 @@ impl SafetyViolation {
             SafetyViolation::BufferOverflow { .. } => {
                 "Potential buffer overflow".to_string()
             }
-            SafetyViolation::StackOverflow { function_name, call_depth, .. } => {
-                format!("Stack overflow risk in function '{}' at depth {}", function_name, call_depth)
+            SafetyViolation::StackOverflow { call_depth: None, function_name, call_chain, .. } => {
+                format!("Possible unbounded recursion in '{}': {}", function_name, call_chain.join(" -> "))
+            }
+            SafetyViolation::StackOverflow { call_depth: Some(depth), max_depth, call_chain, .. } => {
+                format!("Stack overflow risk: the call chain {} is {} frames deep, but the stack allows {}",
+                        call_chain.join(" -> "), depth, max_depth)
             }
             SafetyViolation::UnsafeOperation { operation, .. } => {
                 format!("Unsafe operation '{}' used in safe context", operation)
@@ -565,16 +1065,34 @@ impl SafetyViolation {
             SafetyViolation::DataRace { variable, .. } => {
                 format!("Potential data race on variable '{}'", variable)
             }
-            SafetyViolation::RealtimeViolation { function, max_time, estimated_time, .. } => {
-                format!("Real-time constraint violation in '{}': {}ms > {}ms",
-                        function, estimated_time, max_time)
+            SafetyViolation::RealtimeViolation { function, issue, .. } => match issue {
+                RealtimeIssue::Allocation { call } => {
+                    format!("Real-time function '{}' allocates on the heap via '{}'", function, call)
+                }
+                RealtimeIssue::UnboundedLoop => {
+                    format!("Real-time function '{}' contains a loop without a compile-time bound", function)
+                }
+                RealtimeIssue::BlockingCall { call } => {
+                    format!("Real-time function '{}' makes the blocking call '{}'", function, call)
+                }
+                RealtimeIssue::Recursion { callee } => {
+                    format!("Real-time function '{}' recurses through '{}'", function, callee)
+                }
+                RealtimeIssue::Overrun { max_time, estimated_time } => {
+                    format!("Real-time constraint violation in '{}': an estimated {} cycles exceeds the bound of {}",
+                            function, estimated_time, max_time)
+                }
+            },
+            SafetyViolation::HeapAllocation { function, .. } => {
+                format!("Heap allocation via '{}' is not allowed in the embedded profile", function)
             }
         }
     }
 }
 
 /// Severity levels for safety violations.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SafetySeverity {
     Info,
     Warning,
@@ -586,4 +1104,233 @@ pub enum SafetySeverity {
 pub fn analyze_safety(program: &Program, source: String) -> Result<Vec<SafetyViolation>> {
     let mut analyzer = SafetyAnalyzer::new(source);
     analyzer.analyze_program(program)
-}
\ No newline at end of file
+}
+
+/// Whether `expr` was typed as a raw pointer.
+fn is_pointer(expr: &Expr) -> bool {
+    matches!(expr.ty.as_ref().map(|ty| &ty.kind), Some(TypeKind::Pointer { .. }))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check_program, parse_source};
+    use shared::ast::expr::MatchArm;
+    use shared::Pattern;
+
+    /// Each use of a moved value in `source`, as the line of the use and
+    /// the line of the move.
+    fn uses_after_move(source: &str) -> Vec<(usize, usize)> {
+        let program = parse_source(source).unwrap();
+        moves_in(source, &program)
+    }
+
+    fn moves_in(source: &str, program: &Program) -> Vec<(usize, usize)> {
+        let line = |span: SourceSpan| source[..span.offset()].matches('\n').count() + 1;
+        analyze_safety(program, source.to_string())
+            .unwrap()
+            .into_iter()
+            .filter_map(|violation| match violation {
+                SafetyViolation::UseAfterMove { span, move_location, .. } => Some((line(span), line(move_location))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn assigning_a_string_moves_it() {
+        let source = "fn f() {\n    let a = \"text\";\n    let b = a;\n    g(a);\n}";
+        assert_eq!(uses_after_move(source), vec![(4, 3)]);
+    }
+
+    #[test]
+    fn printing_a_value_does_not_move_it() {
+        let source = "fn f(s: String) {\n    println(s);\n    print(format(\"{}\", s));\n    g(s);\n}";
+        assert!(uses_after_move(source).is_empty());
+    }
+
+    #[test]
+    fn a_deferred_block_reads_the_variables_it_was_deferred_with() {
+        let source = "fn f() {\n    let name = \"outer\";\n    defer { g(name); }\n    let name = \"shadowed\";\n    defer { g(name); }\n}";
+        assert!(uses_after_move(source).is_empty());
+    }
+
+    #[test]
+    fn passing_a_non_copy_argument_moves_it() {
+        let source = "fn f(s: String, n: i32) {\n    g(s, n);\n    g(s, n);\n}";
+        assert_eq!(uses_after_move(source), vec![(3, 2)]);
+    }
+
+    #[test]
+    fn copy_values_are_not_moved() {
+        let source = "fn f(n: i32, c: char) {\n    let m = n;\n    g(n, c);\n    g(c, n);\n}";
+        assert!(uses_after_move(source).is_empty());
+    }
+
+    #[test]
+    fn a_move_on_either_branch_counts() {
+        let source = "fn f(s: String, ready: bool) {\n    if ready {\n        g(s);\n    } else {\n        h();\n    };\n    g(s);\n}";
+        assert_eq!(uses_after_move(source), vec![(7, 3)]);
+    }
+
+    #[test]
+    fn assignment_gives_a_moved_variable_a_new_value() {
+        let source = "fn f(s: String) {\n    let mut t = s;\n    g(t);\n    t = \"again\";\n    g(t);\n}";
+        assert!(uses_after_move(source).is_empty());
+    }
+
+    #[test]
+    fn a_move_inside_a_loop_is_a_use_on_the_next_iteration() {
+        let source = "fn f(s: String) {\n    for i in 0..3 {\n        g(s);\n    }\n}";
+        assert_eq!(uses_after_move(source), vec![(3, 3)]);
+    }
+
+    #[test]
+    fn shadowing_in_a_block_does_not_move_the_outer_variable() {
+        let source = "fn f(s: String) {\n    {\n        let s = \"inner\";\n        g(s);\n    };\n    g(s);\n}";
+        assert!(uses_after_move(source).is_empty());
+    }
+
+    #[test]
+    fn struct_values_move() {
+        let point = "struct Point { x: i32, y: i32 }\n";
+        // Assigning a struct moves it, and its fields are then gone too.
+        let source = format!("{}fn f() {{\n    let p = Point {{ x: 1, y: 2 }};\n    let q = p;\n    g(p.x);\n}}", point);
+        assert_eq!(uses_after_move(&source), vec![(5, 4)]);
+        // So does passing one to a function.
+        let source = format!("{}fn f(p: Point) {{\n    g(p);\n    h(p);\n}}", point);
+        assert_eq!(uses_after_move(&source), vec![(4, 3)]);
+        // A struct literal moves the values of its fields, `name` written
+        // alone as well.
+        let source = "struct Named { name: String }\nfn f(name: String) {\n    let a = Named { name };\n    g(name);\n}";
+        assert_eq!(uses_after_move(source), vec![(4, 3)]);
+        // Checked, the values keep the types the checker gave them.
+        let source = format!("{}fn f() {{\n    let p = Point {{ x: 1, y: 2 }};\n    take(p);\n    take(p);\n}}\nfn take(p: Point) {{ }}\nfn main() {{ }}", point);
+        let mut program = parse_source(&source).unwrap();
+        check_program(&mut program, source.clone()).unwrap();
+        assert_eq!(moves_in(&source, &program), vec![(5, 4)]);
+    }
+
+    #[test]
+    fn match_arms_that_bind_move_the_scrutinee() {
+        // `match s { t => g(t), _ => h() }; g(s);` in `fn f(s: String)`
+        let source = "fn f(s: String) {\n    g(s);\n}";
+        let mut program = parse_source(source).unwrap();
+        let at = |text: &str| SourceSpan::new(source.find(text).unwrap().into(), text.len());
+        let variable = |name: &str| Expr::new(ExprKind::Variable { path: vec![name.to_string()] }, at(name));
+        let call = |args: Vec<Expr>| Expr::new(ExprKind::Call { callee: Box::new(variable("g")), args, safety: SafetyLevel::Safe }, at("g(s)"));
+        let arm = |kind: PatternKind, body: Expr| MatchArm {
+            pattern: Pattern { kind, span: at("s: String") },
+            guard: None,
+            body,
+            span: at("s: String"),
+        };
+        let matched = Expr::new(
+            ExprKind::Match {
+                expr: Box::new(variable("s")),
+                arms: vec![arm(PatternKind::Ident("t".to_string()), call(vec![variable("t")])), arm(PatternKind::Wild, call(Vec::new()))],
+            },
+            at("g(s)"),
+        );
+        let ItemKind::Function { body: Some(body), .. } = &mut program.items[0].kind else { panic!() };
+        let ExprKind::Block(block) = &mut body.kind else { panic!() };
+        block.statements.insert(0, Stmt { kind: StmtKind::Expr(matched), span: at("g(s)") });
+
+        assert_eq!(moves_in(source, &program), vec![(2, 1)]);
+    }
+
+    /// Each data race in `source`, as the line of the access and the line
+    /// of the access it conflicts with.
+    fn races(source: &str) -> Vec<(usize, usize)> {
+        let line = |span: SourceSpan| source[..span.offset()].matches('\n').count() + 1;
+        let program = parse_source(source).unwrap();
+        analyze_safety(&program, source.to_string())
+            .unwrap()
+            .into_iter()
+            .filter_map(|violation| match violation {
+                SafetyViolation::DataRace { span, conflicting_access, .. } => Some((line(span), line(conflicting_access))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reading_a_variable_a_running_thread_writes_races() {
+        let source = "fn f() {\n    let mut count = 0;\n    let worker = spawn {\n        count = count + 1;\n    };\n    g(count);\n    join(worker);\n    g(count);\n}";
+        assert_eq!(races(source), vec![(6, 4)]);
+    }
+
+    #[test]
+    fn two_threads_writing_one_variable_race() {
+        let source = "fn f() {\n    let mut count = 0;\n    let a = spawn {\n        count = 1;\n    };\n    let b = spawn {\n        count = 2;\n    };\n    join(a);\n    join(b);\n}";
+        assert_eq!(races(source), vec![(7, 4)]);
+    }
+
+    #[test]
+    fn threads_that_only_read_do_not_race() {
+        let source = "fn f(limit: i32) {\n    let a = spawn {\n        g(limit);\n    };\n    g(limit);\n    join(a);\n}";
+        assert!(races(source).is_empty());
+    }
+
+    #[test]
+    fn a_thread_spawned_in_a_loop_races_with_the_next_one() {
+        let source = "fn f() {\n    let mut total = 0;\n    for i in 0..4 {\n        spawn {\n            total = total + i;\n        };\n    }\n}";
+        assert_eq!(races(source), vec![(5, 4)]);
+    }
+
+    #[test]
+    fn borrowing_a_variable_twice_is_no_race() {
+        let source = "fn f() {\n    let p = 1;\n    let r = &p;\n    let q = &p;\n    g(r);\n    g(q);\n}";
+        assert!(races(source).is_empty());
+    }
+
+    #[test]
+    fn sharing_through_a_mutex_is_synchronized() {
+        let source = "fn f() {\n    let total = mutex(0);\n    let worker = spawn {\n        let guard = lock(total);\n        store(guard, load(guard) + 1);\n        unlock(guard);\n    };\n    let guard = lock(total);\n    g(load(guard));\n    unlock(guard);\n    join(worker);\n}";
+        let program = parse_source(source).unwrap();
+        let violations = analyze_safety(&program, source.to_string()).unwrap();
+        assert!(violations.is_empty(), "{:?}", violations);
+    }
+
+    /// The lines of the unsafe operations `program` makes outside `unsafe`.
+    fn unsafe_operations(source: &str, program: &Program) -> Vec<usize> {
+        let line = |span: SourceSpan| source[..span.offset()].matches('\n').count() + 1;
+        analyze_safety(program, source.to_string())
+            .unwrap()
+            .into_iter()
+            .filter_map(|violation| match violation {
+                SafetyViolation::UnsafeOperation { span, .. } => Some(line(span)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unsafe_operations_are_only_allowed_in_unsafe_blocks() {
+        let source = "fn f(p: i32) {\n    unsafe_ptr_read(p);\n    unsafe {\n        g(unsafe_ptr_read(p));\n    }\n}";
+        assert_eq!(unsafe_operations(source, &parse_source(source).unwrap()), vec![2]);
+
+        let source = "fn main() {\n    let mut x = 1;\n    let p = &mut x as *mut i32;\n    unsafe {\n        *(p + 0) = *p + 1;\n    }\n}";
+        let mut program = parse_source(source).unwrap();
+        check_program(&mut program, source.to_string()).unwrap();
+        assert!(unsafe_operations(source, &program).is_empty());
+
+        let source = "static mut COUNT: i32 = 0;\nfn f() {\n    COUNT = 1;\n    unsafe {\n        COUNT = COUNT + 1;\n    };\n    let COUNT = 2;\n    g(COUNT);\n}";
+        assert_eq!(unsafe_operations(source, &parse_source(source).unwrap()), vec![3]);
+    }
+
+    #[test]
+    fn allow_attributes_silence_their_lint_within_the_item() {
+        let source = "#[allow(safety::resource_leak, dead_code)]\nfn quiet() {\n    let f = open(\"log\");\n}\nfn loud() {\n    let f = open(\"log\");\n}";
+        let program = parse_source(source).unwrap();
+        let violations = analyze_safety(&program, source.to_string()).unwrap();
+        let leaks: Vec<_> = violations.iter().map(|violation| violation.span().offset()).collect();
+        assert_eq!(leaks, vec![source.rfind("open").unwrap()]);
+    }
+
+    #[test]
+    fn allowing_an_unknown_safety_lint_is_an_error() {
+        let source = "#[allow(safety::leaks)]\nfn f() {}";
+        let program = parse_source(source).unwrap();
+        assert!(analyze_safety(&program, source.to_string()).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+// compiler/src/safety/config.rs
+//! Which safety lints are reported, and how severely.
+//!
+//! Each kind of `SafetyViolation` is a lint, named in snake case after the
+//! variant: `memory_leak`, `data_race` and so on. A `SafetyConfig` turns
+//! lints off or changes their severity for a whole compilation; an item's
+//! `#[allow(safety::<lint>)]` attribute turns one off within that item.
+
+use super::analyzer::{SafetySeverity, SafetyViolation};
+use miette::SourceSpan;
+use shared::ast::stmt::AttributeArg;
+use shared::{Program, Result, TlError};
+use std::collections::BTreeMap;
+
+/// Every safety lint, with the diagnostic code it reports under.
+pub const LINTS: &[(&str, &str)] = &[
+    ("uninitialized_variable", "S0001"),
+    ("use_after_move", "S0002"),
+    ("memory_leak", "S0003"),
+    ("resource_leak", "S0004"),
+    ("null_pointer_dereference", "S0005"),
+    ("buffer_overflow", "S0006"),
+    ("stack_overflow", "S0007"),
+    ("unsafe_operation", "S0008"),
+    ("data_race", "S0009"),
+    ("realtime_violation", "S0010"),
+    ("heap_allocation", "S0011"),
+];
+
+/// The lint called `name`, with or without the `safety::` prefix.
+pub fn lint_named(name: &str) -> Option<&'static str> {
+    let name = name.strip_prefix("safety::").unwrap_or(name);
+    LINTS.iter().map(|(lint, _)| *lint).find(|lint| *lint == name)
+}
+
+/// The diagnostic code `lint` reports under.
+pub fn lint_code(lint: &str) -> Option<&'static str> {
+    LINTS.iter().find(|(name, _)| *name == lint).map(|(_, code)| *code)
+}
+
+/// The lints each item of `program` allows, with the item's span.
+pub fn allowed_lints(program: &Program, source: &str) -> Result<Vec<(SourceSpan, &'static str)>> {
+    let mut allowed = Vec::new();
+    for item in &program.items {
+        for attr in item.attrs.iter().filter(|attr| attr.path == ["allow"]) {
+            for arg in &attr.args {
+                let AttributeArg::Ident(name) = arg else { continue };
+                // Other tools' lints are theirs to check
+                if !name.starts_with("safety::") {
+                    continue;
+                }
+                let lint = lint_named(name).ok_or_else(|| {
+                    TlError::safety(source.to_string(), attr.span, format!("Unknown safety lint `{}`", name))
+                })?;
+                allowed.push((item.span, lint));
+            }
+        }
+    }
+    Ok(allowed)
+}
+
+/// How a lint's violations are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Not at all
+    Allow,
+    /// As warnings, whatever their usual severity
+    Warn,
+    /// As errors at least, failing compilation
+    Deny,
+}
+
+/// Sets of lint levels chosen together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SafetyProfile {
+    /// Every lint at its usual severity.
+    #[default]
+    Standard,
+    /// For code that must not fail: leaks and recursion are errors too.
+    SafetyCritical,
+}
+
+/// The lint levels of a compilation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SafetyConfig {
+    /// Levels that differ from the usual severity, by lint
+    levels: BTreeMap<&'static str, LintLevel>,
+}
+
+impl SafetyConfig {
+    /// The lint levels of `profile`.
+    pub fn for_profile(profile: SafetyProfile) -> Self {
+        let mut config = SafetyConfig::default();
+        if profile == SafetyProfile::SafetyCritical {
+            for lint in ["memory_leak", "resource_leak", "stack_overflow"] {
+                config.set(lint, LintLevel::Deny);
+            }
+        }
+        config
+    }
+
+    /// Report `lint` at `level`.
+    pub fn set(&mut self, lint: &'static str, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+
+    /// The level `lint` was set to, if any.
+    pub fn level(&self, lint: &str) -> Option<LintLevel> {
+        self.levels.get(lint).copied()
+    }
+
+    /// The severity to report `violation` at, or `None` if its lint is
+    /// allowed.
+    pub fn severity(&self, violation: &SafetyViolation) -> Option<SafetySeverity> {
+        let usual = violation.severity();
+        match self.level(violation.lint()) {
+            None => Some(usual),
+            Some(LintLevel::Allow) => None,
+            Some(LintLevel::Warn) => Some(SafetySeverity::Warning),
+            Some(LintLevel::Deny) => Some(usual.max(SafetySeverity::Error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::safety::analyzer::ResourceId;
+    use miette::SourceSpan;
+
+    fn leak() -> SafetyViolation {
+        SafetyViolation::ResourceLeak {
+            resource_id: ResourceId(0),
+            resource_type: "file".to_string(),
+            acquisition_site: SourceSpan::new(0.into(), 4),
+        }
+    }
+
+    #[test]
+    fn every_violation_is_a_lint() {
+        assert_eq!(lint_named("safety::resource_leak"), Some(leak().lint()));
+        assert_eq!(lint_named("data_race"), Some("data_race"));
+        assert_eq!(lint_named("safety::leaks"), None);
+        assert_eq!(lint_code("resource_leak"), Some("S0004"));
+    }
+
+    #[test]
+    fn lint_levels_override_the_usual_severity() {
+        let mut config = SafetyConfig::default();
+        assert_eq!(config.severity(&leak()), Some(SafetySeverity::Warning));
+        config.set("resource_leak", LintLevel::Deny);
+        assert_eq!(config.severity(&leak()), Some(SafetySeverity::Error));
+        config.set("resource_leak", LintLevel::Allow);
+        assert_eq!(config.severity(&leak()), None);
+    }
+
+    #[test]
+    fn the_safety_critical_profile_denies_leaks() {
+        let config = SafetyConfig::for_profile(SafetyProfile::SafetyCritical);
+        assert_eq!(config.severity(&leak()), Some(SafetySeverity::Error));
+        assert_eq!(SafetyConfig::for_profile(SafetyProfile::Standard), SafetyConfig::default());
+    }
+}
@@ -0,0 +1,393 @@
+// compiler/src/safety/init.rs
+//! Definite-initialization analysis.
+//!
+//! A variable declared without a value, as in `let x: i32;`, may only be
+//! read once every path to the read has assigned it. The function body is
+//! followed in evaluation order like in `resources`: the two sides of an
+//! `if` and the arms of a `match` separately, joined afterwards, so a
+//! variable is initialized after them only if it is on every side that
+//! carries on. A path ending in `return`, `break` or `continue` carries on
+//! only to where it jumps, and a `while` or `for` body may not run at all.
+
+use super::analyzer::SafetyViolation;
+use super::resources::bound_names;
+use miette::SourceSpan;
+use shared::ast::stmt::FnParam;
+use shared::ast::Block;
+use shared::{Expr, ExprKind, Pattern, StmtKind};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Whether each local variable is assigned on every path reaching a point.
+type State = BTreeMap<String, bool>;
+
+/// The state on the paths reaching a point; `None` after `return`, `break`
+/// or `continue`, when none do.
+type Flow = Option<State>;
+
+/// Narrow `flow` to what holds on it and on `other` alike.
+fn join(flow: &mut Flow, other: Flow) {
+    match (flow.as_mut(), other) {
+        (Some(state), Some(other)) => {
+            for (name, initialized) in state.iter_mut() {
+                *initialized &= other.get(name).copied().unwrap_or(true);
+            }
+        }
+        (None, other) => *flow = other,
+        (Some(_), None) => {}
+    }
+}
+
+/// The paths leaving a loop being followed.
+struct LoopFrame {
+    label: Option<String>,
+    breaks: Flow,
+}
+
+/// The reads of a function's variables before they are assigned on some
+/// path, in source order.
+pub fn find_uninitialized(params: &[FnParam], body: &Expr) -> Vec<SafetyViolation> {
+    let mut analysis = Analysis::default();
+    let mut flow = Some(State::new());
+    for param in params {
+        Analysis::bind(&mut flow, &param.pattern, true);
+    }
+    analysis.eval(body, &mut flow);
+
+    analysis
+        .reported
+        .into_iter()
+        .map(|((offset, len), name)| SafetyViolation::UninitializedVariable {
+            name,
+            span: SourceSpan::new(offset.into(), len),
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Analysis {
+    loops: Vec<LoopFrame>,
+    /// The reads found so far, by span, so a read is reported once.
+    reported: BTreeSet<((usize, usize), String)>,
+}
+
+impl Analysis {
+    /// Bind the names in `pattern`, returning what they were before for
+    /// `unbind`.
+    fn bind(flow: &mut Flow, pattern: &Pattern, initialized: bool) -> Vec<(String, Option<bool>)> {
+        let Some(state) = flow else { return Vec::new() };
+        let mut names = Vec::new();
+        bound_names(pattern, &mut names);
+        names
+            .into_iter()
+            .map(|name| {
+                let previous = state.insert(name.clone(), initialized);
+                (name, previous)
+            })
+            .collect()
+    }
+
+    /// Restore the bindings `bind` replaced, as their scope ends.
+    fn unbind(flow: &mut Flow, saved: Vec<(String, Option<bool>)>) {
+        let Some(state) = flow else { return };
+        for (name, previous) in saved.into_iter().rev() {
+            match previous {
+                Some(initialized) => state.insert(name, initialized),
+                None => state.remove(&name),
+            };
+        }
+    }
+
+    fn read(&mut self, name: &str, span: SourceSpan, flow: &Flow) {
+        if let Some(false) = flow.as_ref().and_then(|state| state.get(name)) {
+            self.reported.insert(((span.offset(), span.len()), name.to_string()));
+        }
+    }
+
+    fn frame(&mut self, label: &Option<String>) -> Option<&mut LoopFrame> {
+        match label {
+            Some(label) => self.loops.iter_mut().rev().find(|frame| frame.label.as_ref() == Some(label)),
+            None => self.loops.last_mut(),
+        }
+    }
+
+    /// Follow `expr` on `flow`.
+    fn eval(&mut self, expr: &Expr, flow: &mut Flow) {
+        if flow.is_none() {
+            return;
+        }
+        match &expr.kind {
+            ExprKind::Variable { path } if path.len() == 1 => self.read(&path[0], expr.span, flow),
+
+            ExprKind::Call { callee, args, .. } => {
+                self.eval(callee, flow);
+                for arg in args {
+                    self.eval(arg, flow);
+                }
+            }
+
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.eval(receiver, flow);
+                for arg in args {
+                    self.eval(arg, flow);
+                }
+            }
+
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner } => self.eval(inner, flow),
+
+            ExprKind::Index { object: left, index: right } | ExprKind::Binary { left, right, .. } => {
+                self.eval(left, flow);
+                self.eval(right, flow);
+            }
+
+            ExprKind::Range { start, end, .. } => {
+                for bound in start.iter().chain(end) {
+                    self.eval(bound, flow);
+                }
+            }
+
+            ExprKind::Assign { target, op, value } => {
+                self.eval(value, flow);
+                match &target.kind {
+                    ExprKind::Variable { path } if path.len() == 1 && op.is_none() => {
+                        if let Some(initialized) = flow.as_mut().and_then(|state| state.get_mut(&path[0])) {
+                            *initialized = true;
+                        }
+                    }
+                    // `x += 1` and `x.field = 1` need `x` to have a value already.
+                    _ => self.eval(target, flow),
+                }
+            }
+
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.eval(condition, flow);
+                let mut otherwise = flow.clone();
+                self.eval(then_branch, flow);
+                if let Some(else_branch) = else_branch {
+                    self.eval(else_branch, &mut otherwise);
+                }
+                join(flow, otherwise);
+            }
+
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.eval(scrutinee, flow);
+                let mut after = None;
+                for arm in arms {
+                    let mut arm_flow = flow.clone();
+                    let saved = Self::bind(&mut arm_flow, &arm.pattern, true);
+                    if let Some(guard) = &arm.guard {
+                        self.eval(guard, &mut arm_flow);
+                    }
+                    self.eval(&arm.body, &mut arm_flow);
+                    Self::unbind(&mut arm_flow, saved);
+                    join(&mut after, arm_flow);
+                }
+                if !arms.is_empty() {
+                    *flow = after;
+                }
+            }
+
+            ExprKind::Block(block) => self.eval_block(block, flow),
+
+            ExprKind::Loop { body, label } => self.eval_loop(label, None, None, body, flow),
+
+            ExprKind::While { condition, body, label } => {
+                self.eval_loop(label, Some(condition), None, body, flow)
+            }
+
+            ExprKind::For { pattern, iterable, body, label } => {
+                self.eval(iterable, flow);
+                self.eval_loop(label, None, Some(pattern), body, flow)
+            }
+
+            ExprKind::Break { label, value } => {
+                if let Some(value) = value {
+                    self.eval(value, flow);
+                }
+                let state = flow.take();
+                if let Some(frame) = self.frame(label) {
+                    join(&mut frame.breaks, state);
+                }
+            }
+
+            ExprKind::Continue { .. } => *flow = None,
+
+            ExprKind::Return { value } => {
+                if let Some(value) = value {
+                    self.eval(value, flow);
+                }
+                *flow = None;
+            }
+
+            ExprKind::Tuple(elements) | ExprKind::Array { elements, .. } => {
+                for element in elements {
+                    self.eval(element, flow);
+                }
+                if let ExprKind::Array { repeat: Some(count), .. } = &expr.kind {
+                    self.eval(count, flow);
+                }
+            }
+
+            ExprKind::Struct { fields, base, .. } => {
+                for field in fields {
+                    match &field.value {
+                        Some(value) => self.eval(value, flow),
+                        None => self.read(&field.name, expr.span, flow),
+                    }
+                }
+                if let Some(base) = base {
+                    self.eval(base, flow);
+                }
+            }
+
+            ExprKind::Closure { params, body, .. } => {
+                // The closure may run any number of times, so what it assigns
+                // stays unassigned outside it.
+                let mut inner = flow.clone();
+                for param in params {
+                    Self::bind(&mut inner, &param.pattern, true);
+                }
+                let outer_loops = std::mem::take(&mut self.loops);
+                self.eval(body, &mut inner);
+                self.loops = outer_loops;
+            }
+
+            ExprKind::Async { body, .. } | ExprKind::Spawn { body } => {
+                let mut inner = flow.clone();
+                let outer_loops = std::mem::take(&mut self.loops);
+                self.eval(body, &mut inner);
+                self.loops = outer_loops;
+            }
+
+            ExprKind::Variable { .. } | ExprKind::Literal(_) | ExprKind::Macro { .. } | ExprKind::Error => {}
+        }
+    }
+
+    fn eval_block(&mut self, block: &Block, flow: &mut Flow) {
+        let mut saved = Vec::new();
+        let mut deferred = Vec::new();
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Let { pattern, initializer, .. } => {
+                    if let Some(initializer) = initializer {
+                        self.eval(initializer, flow);
+                    }
+                    saved.extend(Self::bind(flow, pattern, initializer.is_some()));
+                }
+                StmtKind::Expr(expr) => self.eval(expr, flow),
+                StmtKind::Defer { body } => deferred.push(body),
+                StmtKind::Item(_) | StmtKind::Macro { .. } => {}
+            }
+        }
+        if let Some(expr) = &block.expr {
+            self.eval(expr, flow);
+        }
+        // Deferred blocks run last, seeing everything the block assigned.
+        for body in deferred.into_iter().rev() {
+            self.eval(body, flow);
+        }
+        Self::unbind(flow, saved);
+    }
+
+    /// Follow a loop. A pass over the body only ever assigns variables from
+    /// before the loop, so the state at its head is the state on entry and
+    /// one pass suffices. `while` and `for` loops, unlike `loop`, can end
+    /// without `break`, before the body has run.
+    fn eval_loop(
+        &mut self,
+        label: &Option<String>,
+        condition: Option<&Expr>,
+        binding: Option<&Pattern>,
+        body: &Expr,
+        flow: &mut Flow,
+    ) {
+        if let Some(condition) = condition {
+            self.eval(condition, flow);
+        }
+        let finished = if condition.is_some() || binding.is_some() { flow.clone() } else { None };
+
+        self.loops.push(LoopFrame { label: label.clone(), breaks: None });
+        let mut pass = flow.clone();
+        let saved = match binding {
+            Some(pattern) => Self::bind(&mut pass, pattern, true),
+            None => Vec::new(),
+        };
+        self.eval(body, &mut pass);
+        Self::unbind(&mut pass, saved);
+        let frame = self.loops.pop().expect("pushed above");
+
+        let mut exit = finished;
+        join(&mut exit, frame.breaks);
+        *flow = exit;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+    use shared::ItemKind;
+
+    /// The source line of each read of an unassigned variable in the
+    /// function `f` in `source`.
+    fn uninitialized_reads(source: &str) -> Vec<usize> {
+        let program = parse_source(source).unwrap();
+        let (params, body) = program
+            .items
+            .iter()
+            .find_map(|item| match &item.kind {
+                ItemKind::Function { name, params, body: Some(body), .. } if name == "f" => Some((params, body)),
+                _ => None,
+            })
+            .unwrap();
+        find_uninitialized(params, body)
+            .into_iter()
+            .map(|violation| match violation {
+                SafetyViolation::UninitializedVariable { span, .. } => {
+                    source[..span.offset()].matches('\n').count() + 1
+                }
+                other => panic!("not an uninitialized read: {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_after_assignment_are_fine() {
+        assert!(uninitialized_reads("fn f() { let x: i32; x = 1; g(x); }").is_empty());
+        assert!(uninitialized_reads("fn f(n: i32) { let x = n; g(x); }").is_empty());
+    }
+
+    #[test]
+    fn reads_before_any_assignment_are_reported() {
+        let source = "fn f() {\n    let x: i32;\n    g(x);\n    x = 1;\n    g(x);\n}";
+        assert_eq!(uninitialized_reads(source), [3]);
+        // Adding to `x` reads it first.
+        assert_eq!(uninitialized_reads("fn f() {\n    let x: i32;\n    x += 1;\n}"), [3]);
+    }
+
+    #[test]
+    fn a_variable_must_be_assigned_on_both_branches() {
+        let source = "fn f(c: bool) {\n    let x: i32;\n    if c {\n        x = 1;\n    };\n    g(x);\n}";
+        assert_eq!(uninitialized_reads(source), [6]);
+        let source = "fn f(c: bool) {\n    let x: i32;\n    if c {\n        x = 1;\n    } else {\n        x = 2;\n    };\n    g(x);\n}";
+        assert!(uninitialized_reads(source).is_empty());
+    }
+
+    #[test]
+    fn a_loop_body_may_not_run() {
+        let source = "fn f(n: i32) {\n    let x: i32;\n    for i in 0..n {\n        x = i;\n    }\n    g(x);\n}";
+        assert_eq!(uninitialized_reads(source), [6]);
+    }
+
+    #[test]
+    fn a_shadowing_binding_has_its_own_state() {
+        let source = "fn f() {\n    let x: i32;\n    {\n        let x = 1;\n        g(x);\n    };\n    g(x);\n}";
+        assert_eq!(uninitialized_reads(source), [7]);
+    }
+}
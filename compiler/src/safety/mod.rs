@@ -0,0 +1,26 @@
+// compiler/src/safety/mod.rs
+//! Safety analysis for T-Lang.
+//!
+//! `analyzer` walks the program and collects safety violations; the
+//! flow-sensitive analyses it runs on each function live in `init`, for
+//! reads of unassigned variables, and `resources`, for leaks. Across
+//! functions, `stack` finds recursion and deep call chains, and
+//! `realtime` checks the functions with time constraints. `config` says
+//! which of the violations found are reported, and how severely, and
+//! `report` writes them up for audits.
+
+pub mod analyzer;
+pub mod config;
+pub mod init;
+pub mod realtime;
+pub mod report;
+pub mod resources;
+pub mod stack;
+
+pub use analyzer::{analyze_safety, RealtimeIssue, SafetyAnalyzer, SafetyViolation, SafetySeverity};
+pub use config::{lint_named, LintLevel, SafetyConfig, SafetyProfile, LINTS};
+pub use init::find_uninitialized;
+pub use realtime::check_realtime;
+pub use report::SafetyReport;
+pub use resources::{find_leaks, ResourceApi, RESOURCE_APIS};
+pub use stack::find_stack_overflows;
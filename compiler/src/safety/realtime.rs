@@ -0,0 +1,400 @@
+// compiler/src/safety/realtime.rs
+//! Real-time constraint checking.
+//!
+//! A function marked `#[realtime]` must finish in a time known before it
+//! runs: it may not allocate on the heap, loop without a bound known at
+//! compile time, make calls that wait on another thread or on I/O, or
+//! recurse, and neither may the functions it calls. `#[wcet(N)]` marks a
+//! function real-time as well and bounds its worst-case execution time at
+//! `N` cycles.
+//!
+//! The estimate is a cost model, not a measurement: every operation costs
+//! `OP_CYCLES`, every call `CALL_CYCLES` plus the callee's own worst case,
+//! an `if` or `match` its most expensive branch, and a loop over a constant
+//! range its body once per iteration. Calls to functions without a body in
+//! the program cost only the call.
+
+use super::analyzer::{RealtimeIssue, SafetyViolation, HEAP_BUILTINS};
+use miette::SourceSpan;
+use shared::ast::stmt::AttributeArg;
+use shared::ast::types::ArraySize;
+use shared::{Expr, ExprKind, Item, ItemKind, Literal, Program, Result, StmtKind, TlError, TypeKind};
+use std::collections::{HashMap, HashSet};
+
+/// Estimated cycles of one operation.
+pub const OP_CYCLES: u64 = 1;
+
+/// Estimated cycles of a call, besides the work the callee does.
+pub const CALL_CYCLES: u64 = 10;
+
+/// Calls that may wait for another thread, a lock or I/O.
+pub const BLOCKING_CALLS: &[&str] = &["lock", "acquire", "join", "recv", "sleep", "wait", "read_line", "accept"];
+
+/// A worst-case estimate in cycles; `None` when there is no bound.
+type Cost = Option<u64>;
+
+fn add(a: Cost, b: Cost) -> Cost {
+    Some(a?.saturating_add(b?))
+}
+
+fn max(a: Cost, b: Cost) -> Cost {
+    Some(a?.max(b?))
+}
+
+/// The time constraint an item's attributes put on it: `None` if it has
+/// none, `Some(None)` for `#[realtime]` and `Some(Some(n))` for `#[wcet(n)]`.
+fn constraint(item: &Item, source: &str) -> Result<Option<Option<u64>>> {
+    if let Some(wcet) = item.attribute("wcet") {
+        let cycles = match wcet.args.as_slice() {
+            [AttributeArg::Literal(Literal::Integer(n) | Literal::TypedInteger(n, _))] => u64::try_from(*n).ok(),
+            _ => None,
+        };
+        return match cycles {
+            Some(cycles) => Ok(Some(Some(cycles))),
+            None => Err(TlError::safety(
+                source.to_string(),
+                wcet.span,
+                format!("`#[wcet]` takes the bound in cycles, at most {}, as in `#[wcet(500)]`", u64::MAX),
+            )),
+        };
+    }
+    Ok(item.attribute("realtime").map(|_| None))
+}
+
+/// The violations of the time constraints on the program's functions.
+pub fn check_realtime(program: &Program, source: &str) -> Result<Vec<SafetyViolation>> {
+    let functions: HashMap<&str, &Expr> = program
+        .items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            ItemKind::Function { name, body: Some(body), .. } => Some((name.as_str(), body)),
+            _ => None,
+        })
+        .collect();
+
+    let mut violations = Vec::new();
+    for item in &program.items {
+        let ItemKind::Function { name, body: Some(body), .. } = &item.kind else { continue };
+        let Some(bound) = constraint(item, source)? else { continue };
+
+        let mut checker = Checker {
+            function: name,
+            functions: &functions,
+            stack: vec![name.as_str()],
+            called_at: None,
+            reported: HashSet::new(),
+            violations: Vec::new(),
+        };
+        let cost = checker.cost(body);
+        violations.append(&mut checker.violations);
+
+        // An unbounded estimate is already explained by the issues above.
+        if let (Some(max_time), Some(estimated_time)) = (bound, cost)
+            && estimated_time > max_time
+        {
+            violations.push(SafetyViolation::RealtimeViolation {
+                span: item.span,
+                function: name.clone(),
+                issue: RealtimeIssue::Overrun { max_time, estimated_time },
+                called_at: None,
+            });
+        }
+    }
+    Ok(violations)
+}
+
+/// Follows one real-time function and everything it calls.
+struct Checker<'a> {
+    function: &'a str,
+    functions: &'a HashMap<&'a str, &'a Expr>,
+    /// The functions being followed, outermost first
+    stack: Vec<&'a str>,
+    /// The call in the real-time function itself being followed
+    called_at: Option<SourceSpan>,
+    /// Spans already reported, so code reached twice is reported once
+    reported: HashSet<(usize, usize)>,
+    violations: Vec<SafetyViolation>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, span: SourceSpan, issue: RealtimeIssue) {
+        if self.reported.insert((span.offset(), span.len())) {
+            self.violations.push(SafetyViolation::RealtimeViolation {
+                span,
+                function: self.function.to_string(),
+                issue,
+                called_at: self.called_at,
+            });
+        }
+    }
+
+    /// The sum of the costs of `exprs`.
+    fn costs(&mut self, exprs: impl IntoIterator<Item = &'a Expr>) -> Cost {
+        // Every expression is costed, for its violations, even past one
+        // without a bound.
+        let mut total = Some(0);
+        for expr in exprs {
+            total = add(total, self.cost(expr));
+        }
+        total
+    }
+
+    /// The worst-case cycles of evaluating `expr`, reporting what keeps it
+    /// from having a bound.
+    fn cost(&mut self, expr: &'a Expr) -> Cost {
+        let inner = match &expr.kind {
+            ExprKind::Call { callee, args, .. } => {
+                let args = self.costs(args);
+                add(args, self.call(callee, expr.span))
+            }
+
+            ExprKind::MethodCall { receiver, args, .. } => {
+                let receiver = self.cost(receiver);
+                add(receiver, add(self.costs(args), Some(CALL_CYCLES)))
+            }
+
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner } => self.cost(inner),
+
+            ExprKind::Index { object: left, index: right }
+            | ExprKind::Binary { left, right, .. }
+            | ExprKind::Assign { target: left, value: right, .. } => {
+                let left = self.cost(left);
+                add(left, self.cost(right))
+            }
+
+            ExprKind::Range { start, end, .. } => self.costs(start.iter().chain(end).map(|bound| &**bound)),
+
+            ExprKind::If { condition, then_branch, else_branch } => {
+                let condition = self.cost(condition);
+                let then_branch = self.cost(then_branch);
+                let else_branch = match else_branch {
+                    Some(else_branch) => self.cost(else_branch),
+                    None => Some(0),
+                };
+                add(condition, max(then_branch, else_branch))
+            }
+
+            ExprKind::Match { expr: scrutinee, arms } => {
+                let scrutinee = self.cost(scrutinee);
+                let mut worst = Some(0);
+                for arm in arms {
+                    let guard = match &arm.guard {
+                        Some(guard) => self.cost(guard),
+                        None => Some(0),
+                    };
+                    let body = self.cost(&arm.body);
+                    worst = max(worst, add(guard, body));
+                }
+                add(scrutinee, worst)
+            }
+
+            ExprKind::Block(block) => {
+                let mut total = Some(0);
+                for stmt in &block.statements {
+                    let cost = match &stmt.kind {
+                        StmtKind::Expr(expr)
+                        | StmtKind::Let { initializer: Some(expr), .. }
+                        | StmtKind::Defer { body: expr } => self.cost(expr),
+                        StmtKind::Let { initializer: None, .. } | StmtKind::Item(_) | StmtKind::Macro { .. } => {
+                            Some(0)
+                        }
+                    };
+                    total = add(total, cost);
+                }
+                match &block.expr {
+                    Some(expr) => add(total, self.cost(expr)),
+                    None => total,
+                }
+            }
+
+            ExprKind::Loop { body, .. } => {
+                self.report(expr.span, RealtimeIssue::UnboundedLoop);
+                self.cost(body);
+                None
+            }
+
+            ExprKind::While { condition, body, .. } => {
+                self.report(expr.span, RealtimeIssue::UnboundedLoop);
+                self.cost(condition);
+                self.cost(body);
+                None
+            }
+
+            ExprKind::For { iterable, body, .. } => {
+                let iterations = iterations(iterable);
+                if iterations.is_none() {
+                    self.report(expr.span, RealtimeIssue::UnboundedLoop);
+                }
+                let iterable = self.cost(iterable);
+                let per_iteration = add(self.cost(body), Some(OP_CYCLES));
+                add(iterable, per_iteration.zip(iterations).map(|(cost, n)| cost.saturating_mul(n)))
+            }
+
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => match value {
+                Some(value) => self.cost(value),
+                None => Some(0),
+            },
+
+            ExprKind::Tuple(elements) | ExprKind::Array { elements, repeat: None } => self.costs(elements),
+
+            ExprKind::Array { elements, repeat: Some(count) } => {
+                let count = self.cost(count);
+                add(count, self.costs(elements))
+            }
+
+            ExprKind::Struct { fields, base, .. } => {
+                let fields = self.costs(fields.iter().filter_map(|field| field.value.as_ref()));
+                add(fields, self.costs(base.iter().map(|base| &**base)))
+            }
+
+            // Starting a thread allocates its stack.
+            ExprKind::Spawn { .. } => {
+                self.report(expr.span, RealtimeIssue::Allocation { call: "spawn".to_string() });
+                None
+            }
+
+            // The body runs later, when it is called or awaited.
+            ExprKind::Closure { .. } | ExprKind::Async { .. } => Some(0),
+
+            ExprKind::Variable { .. }
+            | ExprKind::Literal(_)
+            | ExprKind::Continue { .. }
+            | ExprKind::Macro { .. }
+            | ExprKind::Error => Some(0),
+        };
+        add(inner, Some(OP_CYCLES))
+    }
+
+    /// The cost of calling `callee`, following it into its body when the
+    /// program defines it.
+    fn call(&mut self, callee: &'a Expr, span: SourceSpan) -> Cost {
+        let ExprKind::Variable { path } = &callee.kind else {
+            let callee = self.cost(callee);
+            return add(callee, Some(CALL_CYCLES));
+        };
+        let name = path.join("::");
+        if HEAP_BUILTINS.contains(&name.as_str()) {
+            self.report(span, RealtimeIssue::Allocation { call: name });
+            return None;
+        }
+        if BLOCKING_CALLS.contains(&name.as_str()) {
+            self.report(span, RealtimeIssue::BlockingCall { call: name });
+            return None;
+        }
+        let Some((&callee, &body)) = self.functions.get_key_value(name.as_str()) else {
+            return Some(CALL_CYCLES);
+        };
+        if self.stack.contains(&callee) {
+            self.report(span, RealtimeIssue::Recursion { callee: name });
+            return None;
+        }
+
+        let outermost = self.stack.len() == 1;
+        if outermost {
+            self.called_at = Some(span);
+        }
+        self.stack.push(callee);
+        let cost = self.cost(body);
+        self.stack.pop();
+        if outermost {
+            self.called_at = None;
+        }
+        add(cost, Some(CALL_CYCLES))
+    }
+}
+
+/// How many times a `for` loop over `iterable` runs, if that is known at
+/// compile time: for a range with literal bounds or an array of known size.
+fn iterations(iterable: &Expr) -> Option<u64> {
+    let literal = |bound: &Option<Box<Expr>>| match bound.as_deref().map(|bound| &bound.kind) {
+        Some(ExprKind::Literal(Literal::Integer(n) | Literal::TypedInteger(n, _))) => Some(*n),
+        _ => None,
+    };
+    match &iterable.kind {
+        ExprKind::Range { start, end, inclusive } => {
+            let (start, end) = (literal(start)?, literal(end)?);
+            let end = if *inclusive { end + 1 } else { end };
+            Some(end.saturating_sub(start).max(0) as u64)
+        }
+        _ => match iterable.ty.as_ref().map(|ty| &ty.kind) {
+            Some(TypeKind::Array { size: ArraySize::Literal(size), .. }) => Some(*size),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+
+    /// Each real-time issue in `source`, with its line and the line of the
+    /// call that reaches it, if any.
+    fn issues(source: &str) -> Vec<(usize, RealtimeIssue, Option<usize>)> {
+        let line = |span: SourceSpan| source[..span.offset()].matches('\n').count() + 1;
+        let program = parse_source(source).unwrap();
+        check_realtime(&program, source)
+            .unwrap()
+            .into_iter()
+            .filter_map(|violation| match violation {
+                SafetyViolation::RealtimeViolation { span, issue, called_at, .. } => {
+                    Some((line(span), issue, called_at.map(line)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn allocation_unbounded_loops_and_blocking_calls_are_rejected() {
+        let source = "#[realtime]\nfn tick(n: i32) {\n    let buffer = malloc(64);\n    for i in 0..n {\n        g(i);\n    }\n    lock(m);\n}";
+        assert_eq!(issues(source), vec![
+            (3, RealtimeIssue::Allocation { call: "malloc".to_string() }, None),
+            (4, RealtimeIssue::UnboundedLoop, None),
+            (7, RealtimeIssue::BlockingCall { call: "lock".to_string() }, None),
+        ]);
+    }
+
+    #[test]
+    fn functions_without_a_constraint_are_not_checked() {
+        let source = "fn tick(n: i32) {\n    let buffer = malloc(64);\n    lock(m);\n}";
+        assert!(issues(source).is_empty());
+    }
+
+    #[test]
+    fn issues_in_called_functions_point_at_the_call() {
+        let source = "fn helper() {\n    let b = malloc(8);\n}\n#[realtime]\nfn tick() {\n    helper();\n    helper();\n}";
+        assert_eq!(issues(source), vec![(2, RealtimeIssue::Allocation { call: "malloc".to_string() }, Some(6))]);
+    }
+
+    #[test]
+    fn recursion_has_no_bound() {
+        let source = "#[realtime]\nfn f(n: i32) {\n    g(n);\n    f(n);\n}";
+        assert_eq!(issues(source), vec![(4, RealtimeIssue::Recursion { callee: "f".to_string() }, None)]);
+    }
+
+    #[test]
+    fn wcet_bounds_the_estimated_cycles() {
+        let body = "fn f() {\n    for i in 0..10 {\n        g(i);\n    }\n}";
+        let overrun = issues(&format!("#[wcet(100)]\n{}", body));
+        let [(2, RealtimeIssue::Overrun { max_time: 100, estimated_time }, None)] = overrun[..] else {
+            panic!("expected an overrun, got {:?}", overrun);
+        };
+        // Ten iterations of a call
+        assert!(estimated_time > 10 * CALL_CYCLES, "{}", estimated_time);
+        assert!(issues(&format!("#[wcet({})]\n{}", estimated_time, body)).is_empty());
+
+        for bound in ["fast", "18446744073709551616"] {
+            let source = format!("#[wcet({})]\nfn f() {{}}", bound);
+            let err = check_realtime(&parse_source(&source).unwrap(), &source).unwrap_err();
+            assert!(err.to_string().contains("takes the bound in cycles"), "{}", err);
+        }
+    }
+}
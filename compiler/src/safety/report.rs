@@ -0,0 +1,365 @@
+// compiler/src/safety/report.rs
+//! Safety reports for certification audits.
+//!
+//! A report lists every rule checked with the level it ran at, every
+//! violation found with its location and severity, what each function
+//! contributed, and the `#[allow(safety::...)]` attributes that silenced a
+//! rule. It is written as JSON, or as a standalone HTML page when the path
+//! ends in `.html`.
+
+use super::analyzer::{SafetySeverity, SafetyViolation};
+use super::config::{allowed_lints, LintLevel, SafetyConfig, LINTS};
+use miette::SourceSpan;
+use serde::Serialize;
+use shared::source::line_col_from_offset;
+use shared::{ItemKind, Program, Result, TlError};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Everything safety analysis checked and found in one program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SafetyReport {
+    /// Whether nothing was found severe enough to fail compilation
+    pub passed: bool,
+    /// Violations found, by severity
+    pub summary: Summary,
+    pub rules: Vec<RuleReport>,
+    pub functions: Vec<FunctionReport>,
+    pub violations: Vec<ViolationReport>,
+    pub suppressions: Vec<Suppression>,
+}
+
+/// How many violations of each severity were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub critical: usize,
+    pub error: usize,
+    pub warning: usize,
+    pub info: usize,
+}
+
+/// One rule and how it was applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleReport {
+    /// The lint name, as in `#[allow(safety::memory_leak)]`
+    pub lint: &'static str,
+    pub code: &'static str,
+    pub title: &'static str,
+    /// `default`, or the level `SafetyConfig` set: `allow`, `warn` or `deny`
+    pub level: &'static str,
+    /// Violations reported under the rule
+    pub violations: usize,
+}
+
+/// What one function contributed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FunctionReport {
+    pub name: String,
+    pub location: Location,
+    pub violations: usize,
+    /// The most severe violation in the function, if any
+    pub worst: Option<SafetySeverity>,
+}
+
+/// One violation reported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ViolationReport {
+    pub rule: &'static str,
+    pub code: &'static str,
+    pub severity: SafetySeverity,
+    pub message: String,
+    /// The function the violation is in, if any
+    pub function: Option<String>,
+    pub location: Location,
+    pub notes: Vec<Note>,
+}
+
+/// A related location of a violation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Note {
+    pub message: String,
+    pub location: Location,
+}
+
+/// A rule an item's `#[allow]` attribute turned off within it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Suppression {
+    pub rule: &'static str,
+    pub function: Option<String>,
+    pub location: Location,
+}
+
+/// A one-based line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SafetyReport {
+    /// The report on `violations`, found in `program`, reported at the
+    /// levels of `config`.
+    pub fn new(program: &Program, source: &str, violations: &[SafetyViolation], config: &SafetyConfig) -> Result<Self> {
+        let location = |span: SourceSpan| {
+            let (line, column) = line_col_from_offset(source, span.offset());
+            Location { line, column }
+        };
+        let functions: Vec<(&str, SourceSpan)> = program
+            .items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Function { name, body: Some(_), .. } => Some((name.as_str(), item.span)),
+                _ => None,
+            })
+            .collect();
+        let function_at = |span: SourceSpan| {
+            functions
+                .iter()
+                .find(|(_, item)| item.offset() <= span.offset() && span.offset() < item.offset() + item.len())
+                .map(|(name, _)| name.to_string())
+        };
+
+        let mut summary = Summary::default();
+        let mut reported = Vec::new();
+        for violation in violations {
+            let Some(severity) = config.severity(violation) else {
+                continue;
+            };
+            *match severity {
+                SafetySeverity::Critical => &mut summary.critical,
+                SafetySeverity::Error => &mut summary.error,
+                SafetySeverity::Warning => &mut summary.warning,
+                SafetySeverity::Info => &mut summary.info,
+            } += 1;
+            reported.push(ViolationReport {
+                rule: violation.lint(),
+                code: violation.code(),
+                severity,
+                message: violation.description(),
+                function: function_at(violation.span()),
+                location: location(violation.span()),
+                notes: violation
+                    .notes()
+                    .into_iter()
+                    .map(|(message, span)| Note { message, location: location(span) })
+                    .collect(),
+            });
+        }
+        reported.sort_by_key(|violation| (violation.location.line, violation.location.column));
+
+        let rules = LINTS
+            .iter()
+            .map(|&(lint, code)| RuleReport {
+                lint,
+                code,
+                title: errors::explain::explain(code).map_or("", |explanation| explanation.title),
+                level: match config.level(lint) {
+                    None => "default",
+                    Some(LintLevel::Allow) => "allow",
+                    Some(LintLevel::Warn) => "warn",
+                    Some(LintLevel::Deny) => "deny",
+                },
+                violations: reported.iter().filter(|violation| violation.rule == lint).count(),
+            })
+            .collect();
+
+        let function_reports = functions
+            .iter()
+            .map(|(name, span)| {
+                let found = || reported.iter().filter(|violation| violation.function.as_deref() == Some(*name));
+                FunctionReport {
+                    name: name.to_string(),
+                    location: location(*span),
+                    violations: found().count(),
+                    worst: found().map(|violation| violation.severity).max(),
+                }
+            })
+            .collect();
+
+        let suppressions = allowed_lints(program, source)?
+            .into_iter()
+            .map(|(span, rule)| Suppression { rule, function: function_at(span), location: location(span) })
+            .collect();
+
+        Ok(SafetyReport {
+            passed: summary.critical == 0 && summary.error == 0,
+            summary,
+            rules,
+            functions: function_reports,
+            violations: reported,
+            suppressions,
+        })
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a safety report is always serializable")
+    }
+
+    /// The report as a standalone HTML page.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Safety report</title>\n<style>\n\
+             body { font-family: sans-serif; margin: 2em; }\n\
+             table { border-collapse: collapse; margin-bottom: 2em; }\n\
+             th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }\n\
+             .passed { color: #070; } .failed { color: #b00; }\n\
+             </style>\n</head>\n<body>\n<h1>Safety report</h1>\n",
+        );
+        let Summary { critical, error, warning, info } = self.summary;
+        let (class, verdict) = if self.passed { ("passed", "Passed") } else { ("failed", "Failed") };
+        let _ = writeln!(
+            html,
+            "<p class=\"{}\">{}: {} critical, {} errors, {} warnings, {} notes</p>",
+            class, verdict, critical, error, warning, info
+        );
+
+        html.push_str("<h2>Rules checked</h2>\n<table>\n<tr><th>Code</th><th>Lint</th><th>Rule</th><th>Level</th><th>Violations</th></tr>\n");
+        for rule in &self.rules {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>safety::{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                rule.code,
+                rule.lint,
+                escape(rule.title),
+                rule.level,
+                rule.violations
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Functions</h2>\n<table>\n<tr><th>Function</th><th>Line</th><th>Violations</th><th>Worst</th></tr>\n");
+        for function in &self.functions {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&function.name),
+                function.location.line,
+                function.violations,
+                function.worst.map_or("", severity_name)
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Violations</h2>\n");
+        if self.violations.is_empty() {
+            html.push_str("<p>None.</p>\n");
+        } else {
+            html.push_str("<table>\n<tr><th>Location</th><th>Code</th><th>Severity</th><th>Function</th><th>Message</th></tr>\n");
+            for violation in &self.violations {
+                let mut message = escape(&violation.message);
+                for note in &violation.notes {
+                    let _ = write!(message, "<br>{}:{}: {}", note.location.line, note.location.column, escape(&note.message));
+                }
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}:{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    violation.location.line,
+                    violation.location.column,
+                    violation.code,
+                    severity_name(violation.severity),
+                    escape(violation.function.as_deref().unwrap_or("")),
+                    message
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.suppressions.is_empty() {
+            html.push_str("<h2>Suppressions</h2>\n<table>\n<tr><th>Location</th><th>Lint</th><th>Function</th></tr>\n");
+            for suppression in &self.suppressions {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}:{}</td><td>safety::{}</td><td>{}</td></tr>",
+                    suppression.location.line,
+                    suppression.location.column,
+                    suppression.rule,
+                    escape(suppression.function.as_deref().unwrap_or(""))
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Write the report to `path`: HTML if it ends in `.html` or `.htm`,
+    /// JSON otherwise.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let html = path.extension().is_some_and(|extension| extension == "html" || extension == "htm");
+        let contents = if html { self.to_html() } else { self.to_json() };
+        std::fs::write(path, contents).map_err(|error| {
+            TlError::io(format!("Failed to write safety report {}", path.display()), Some(error))
+        })
+    }
+}
+
+fn severity_name(severity: SafetySeverity) -> &'static str {
+    match severity {
+        SafetySeverity::Critical => "critical",
+        SafetySeverity::Error => "error",
+        SafetySeverity::Warning => "warning",
+        SafetySeverity::Info => "info",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+    use crate::safety::analyze_safety;
+
+    fn report(source: &str, config: &SafetyConfig) -> SafetyReport {
+        let program = parse_source(source).unwrap();
+        let violations = analyze_safety(&program, source.to_string()).unwrap();
+        SafetyReport::new(&program, source, &violations, config).unwrap()
+    }
+
+    #[test]
+    fn violations_are_summarized_per_rule_and_function() {
+        let source = "fn main() {\n    helper();\n}\nfn helper() {\n    let f = open(\"log\");\n}";
+        let report = report(source, &SafetyConfig::default());
+
+        assert!(report.passed);
+        assert_eq!(report.summary, Summary { warning: 1, ..Summary::default() });
+        assert_eq!(report.rules.len(), LINTS.len());
+        let leaks = report.rules.iter().find(|rule| rule.lint == "resource_leak").unwrap();
+        assert_eq!((leaks.code, leaks.level, leaks.violations), ("S0004", "default", 1));
+        assert_eq!(report.violations[0].function.as_deref(), Some("helper"));
+        assert_eq!(report.violations[0].location, Location { line: 5, column: 13 });
+        let worst: Vec<_> = report.functions.iter().map(|function| (function.name.as_str(), function.worst)).collect();
+        assert_eq!(worst, [("main", None), ("helper", Some(SafetySeverity::Warning))]);
+    }
+
+    #[test]
+    fn the_report_follows_the_configured_levels() {
+        let source = "fn main() {\n    let f = open(\"log\");\n}";
+        let config = SafetyConfig::for_profile(crate::safety::SafetyProfile::SafetyCritical);
+        let report = report(source, &config);
+
+        assert!(!report.passed);
+        assert_eq!(report.violations[0].severity, SafetySeverity::Error);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["violations"][0]["severity"], "error");
+        assert_eq!(json["rules"][3]["level"], "deny");
+    }
+
+    #[test]
+    fn suppressions_are_listed_and_html_is_escaped() {
+        let source = "#[allow(safety::resource_leak)]\nfn quiet() {\n    let f = open(\"log\");\n}";
+        let report = report(source, &SafetyConfig::default());
+
+        assert!(report.violations.is_empty());
+        assert_eq!(report.suppressions.len(), 1);
+        assert_eq!(report.suppressions[0].function.as_deref(), Some("quiet"));
+        let html = report.to_html();
+        assert!(html.contains("<h2>Suppressions</h2>"));
+        assert!(html.contains("safety::resource_leak"));
+        assert_eq!(escape("a < b & \"c\""), "a &lt; b &amp; &quot;c&quot;");
+    }
+}
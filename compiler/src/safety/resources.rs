@@ -0,0 +1,741 @@
+// compiler/src/safety/resources.rs
+//! Data-flow analysis of resource handles.
+//!
+//! A function body is followed in evaluation order, tracking which
+//! resources may still be open and which variables may hold each of them.
+//! The two sides of an `if` and the arms of a `match` are followed
+//! separately and joined afterwards; a loop body is followed again until
+//! the state stops changing. A resource that may still be open on a path
+//! out of the function, by falling off its end, `return` or `?`, is a leak,
+//! reported once at the call that acquired it.
+//!
+//! The `defer` blocks of a block are followed wherever a path leaves it:
+//! at its end, and at a `return`, `?`, `break` or `continue` out of it. A
+//! release deferred right after the acquisition thus covers every path.
+//!
+//! A handle stops being the function's concern once it is released,
+//! returned, stored outside the function's locals or moved into a call:
+//! whoever receives it then owns it. Passing `&handle` only lends it, as
+//! does a call that only works through it, like `load(guard)`.
+
+use super::analyzer::{AllocationId, ResourceId, SafetyViolation};
+use miette::SourceSpan;
+use shared::ast::stmt::FnParam;
+use shared::ast::Block;
+use shared::{Expr, ExprKind, Pattern, PatternKind, StmtKind};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A kind of resource: the calls that acquire one and those that release it.
+#[derive(Debug)]
+pub struct ResourceApi {
+    /// What is acquired; leaks of `memory` are reported as memory leaks.
+    pub kind: &'static str,
+    pub acquire: &'static [&'static str],
+    pub release: &'static [&'static str],
+    /// Calls that work through a handle without taking it, like reading the
+    /// value a lock guards.
+    pub uses: &'static [&'static str],
+}
+
+/// The resources tracked. Besides these, `drop(handle)` releases any kind.
+pub static RESOURCE_APIS: &[ResourceApi] = &[
+    ResourceApi {
+        kind: "memory",
+        acquire: &["malloc", "calloc", "alloc", "allocate"],
+        release: &["free", "dealloc", "deallocate"],
+        uses: &[],
+    },
+    ResourceApi {
+        kind: "file",
+        acquire: &["open", "fopen", "File::open", "File::create"],
+        release: &["close", "fclose"],
+        uses: &[],
+    },
+    ResourceApi {
+        kind: "socket",
+        acquire: &["socket", "TcpStream::connect", "TcpListener::bind"],
+        release: &["close", "shutdown"],
+        uses: &[],
+    },
+    ResourceApi {
+        kind: "lock",
+        acquire: &["lock", "acquire"],
+        release: &["unlock", "release"],
+        uses: &["load", "store"],
+    },
+];
+
+/// Resources, by their index in `Analysis::resources`.
+type Handles = BTreeSet<usize>;
+
+/// What is known at one point of a path.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct State {
+    /// Resources each local variable may hold.
+    holds: BTreeMap<String, Handles>,
+    /// Resources that may still be open.
+    open: Handles,
+}
+
+impl State {
+    /// Widen to what holds on this path or on `other`.
+    fn join(&mut self, other: &State) {
+        for (name, handles) in &other.holds {
+            self.holds.entry(name.clone()).or_default().extend(handles);
+        }
+        self.open.extend(&other.open);
+    }
+}
+
+/// The state on the paths reaching a point; `None` after `return`, `break`
+/// or `continue`, when none do.
+type Flow = Option<State>;
+
+fn join(flow: &mut Flow, other: Flow) {
+    match (flow.as_mut(), other) {
+        (Some(state), Some(other)) => state.join(&other),
+        (None, other) => *flow = other,
+        (Some(_), None) => {}
+    }
+}
+
+/// Where a resource was acquired.
+struct Resource {
+    kind: &'static str,
+    site: SourceSpan,
+}
+
+/// The paths leaving a loop being followed.
+struct LoopFrame {
+    label: Option<String>,
+    breaks: Flow,
+    continues: Flow,
+    /// Resources the `break` values may hold.
+    values: Handles,
+    /// Blocks with deferred blocks outside the loop, which `break` and
+    /// `continue` do not leave.
+    scopes: usize,
+}
+
+/// Names `pattern` binds.
+pub(super) fn bound_names(pattern: &Pattern, names: &mut Vec<String>) {
+    match &pattern.kind {
+        PatternKind::Ident(name) => names.push(name.clone()),
+        PatternKind::Tuple(patterns)
+        | PatternKind::Slice(patterns)
+        | PatternKind::Or(patterns)
+        | PatternKind::Enum { fields: patterns, .. } => {
+            for pattern in patterns {
+                bound_names(pattern, names);
+            }
+        }
+        PatternKind::Struct { fields, .. } => {
+            for field in fields {
+                match &field.pattern {
+                    Some(pattern) => bound_names(pattern, names),
+                    None => names.push(field.name.clone()),
+                }
+            }
+        }
+        PatternKind::Guard { pattern, .. } => bound_names(pattern, names),
+        PatternKind::Wild | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+    }
+}
+
+/// The local variable a place expression such as `s.buf[i]` stores into,
+/// and whether the place is the whole variable.
+pub(super) fn place_root(place: &Expr) -> Option<(&str, bool)> {
+    match &place.kind {
+        ExprKind::Variable { path } if path.len() == 1 => Some((&path[0], true)),
+        ExprKind::FieldAccess { object, .. } | ExprKind::Index { object, .. } => {
+            place_root(object).map(|(name, _)| (name, false))
+        }
+        _ => None,
+    }
+}
+
+/// The memory and resource leaks of a function, in acquisition order.
+pub fn find_leaks(params: &[FnParam], body: &Expr) -> Vec<SafetyViolation> {
+    let mut analysis = Analysis::default();
+    let mut flow = Some(State::default());
+    for param in params {
+        Analysis::bind(&mut flow, &param.pattern, &Handles::new());
+    }
+    let result = analysis.eval(body, &mut flow);
+    analysis.leave(&flow, &result);
+
+    analysis
+        .leaked
+        .iter()
+        .map(|&id| {
+            let resource = &analysis.resources[id];
+            if resource.kind == "memory" {
+                SafetyViolation::MemoryLeak {
+                    allocation_id: AllocationId(id as u64),
+                    allocation_site: resource.site,
+                }
+            } else {
+                SafetyViolation::ResourceLeak {
+                    resource_id: ResourceId(id as u64),
+                    resource_type: resource.kind.to_string(),
+                    acquisition_site: resource.site,
+                }
+            }
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Analysis {
+    resources: Vec<Resource>,
+    /// The resource acquired at each site, so each pass over a loop body
+    /// acquires the same one.
+    sites: HashMap<(usize, usize), usize>,
+    loops: Vec<LoopFrame>,
+    /// Deferred blocks of the blocks being followed, from the outermost in.
+    deferred: Vec<Vec<Expr>>,
+    /// Depth of closures being followed; their `return`s only leave them.
+    closures: usize,
+    /// Resources held by the variables read so far, for closure captures.
+    touched: Handles,
+    leaked: Handles,
+}
+
+impl Analysis {
+    /// A path leaves the function returning a value that holds `result`.
+    fn leave(&mut self, flow: &Flow, result: &Handles) {
+        if self.closures > 0 {
+            return;
+        }
+        if let Some(state) = flow {
+            self.leaked.extend(state.open.difference(result));
+        }
+    }
+
+    /// `handles` are now someone else's to release.
+    fn give_away(flow: &mut Flow, handles: &Handles) {
+        if let Some(state) = flow {
+            state.open.retain(|id| !handles.contains(id));
+        }
+    }
+
+    /// `function` releases those of `handles` it knows how to.
+    fn release(&self, flow: &mut Flow, handles: &Handles, function: &str) {
+        let Some(state) = flow else { return };
+        state.open.retain(|&id| {
+            let kind = self.resources[id].kind;
+            let releases = function == "drop"
+                || RESOURCE_APIS
+                    .iter()
+                    .any(|api| api.kind == kind && api.release.contains(&function));
+            !(handles.contains(&id) && releases)
+        });
+    }
+
+    fn acquire(&mut self, kind: &'static str, site: SourceSpan, flow: &mut Flow) -> Handles {
+        let resources = &mut self.resources;
+        let id = *self.sites.entry((site.offset(), site.len())).or_insert_with(|| {
+            resources.push(Resource { kind, site });
+            resources.len() - 1
+        });
+        if let Some(state) = flow {
+            state.open.insert(id);
+        }
+        Handles::from([id])
+    }
+
+    /// Bind the names in `pattern` to `handles`, returning what they were
+    /// bound to before for `unbind`.
+    fn bind(flow: &mut Flow, pattern: &Pattern, handles: &Handles) -> Vec<(String, Option<Handles>)> {
+        let Some(state) = flow else { return Vec::new() };
+        let mut names = Vec::new();
+        bound_names(pattern, &mut names);
+        names
+            .into_iter()
+            .map(|name| {
+                let previous = state.holds.insert(name.clone(), handles.clone());
+                (name, previous)
+            })
+            .collect()
+    }
+
+    /// Restore the bindings `bind` replaced, as their scope ends.
+    fn unbind(flow: &mut Flow, saved: Vec<(String, Option<Handles>)>) {
+        let Some(state) = flow else { return };
+        for (name, previous) in saved.into_iter().rev() {
+            match previous {
+                Some(handles) => state.holds.insert(name, handles),
+                None => state.holds.remove(&name),
+            };
+        }
+    }
+
+    fn read(&mut self, name: &str, flow: &Flow) -> Handles {
+        let handles = flow
+            .as_ref()
+            .and_then(|state| state.holds.get(name))
+            .cloned()
+            .unwrap_or_default();
+        self.touched.extend(&handles);
+        handles
+    }
+
+    /// Follow the deferred blocks a path runs as it leaves the blocks from
+    /// the `depth`th in, the innermost and the last deferred first.
+    fn exit_scopes(&mut self, depth: usize, flow: &mut Flow) {
+        // A `return` in a deferred block leaves the blocks it runs for too.
+        let pending = std::mem::take(&mut self.deferred);
+        for body in pending.iter().skip(depth).rev().flat_map(|scope| scope.iter().rev()) {
+            self.eval(body, flow);
+        }
+        self.deferred = pending;
+    }
+
+    fn frame(&mut self, label: &Option<String>) -> Option<&mut LoopFrame> {
+        match label {
+            Some(label) => self.loops.iter_mut().rev().find(|frame| frame.label.as_ref() == Some(label)),
+            None => self.loops.last_mut(),
+        }
+    }
+
+    /// Follow `expr` on `flow`, returning the resources its value may hold.
+    fn eval(&mut self, expr: &Expr, flow: &mut Flow) -> Handles {
+        if flow.is_none() {
+            return Handles::new();
+        }
+        match &expr.kind {
+            ExprKind::Variable { path } if path.len() == 1 => self.read(&path[0], flow),
+
+            ExprKind::Call { callee, args, .. } => self.eval_call(callee, args, expr.span, flow),
+
+            ExprKind::MethodCall { receiver, method, args } => {
+                let receiver = self.eval(receiver, flow);
+                for arg in args {
+                    let moved = self.eval(arg, flow);
+                    Self::give_away(flow, &moved);
+                }
+                self.release(flow, &receiver, method);
+                Handles::new()
+            }
+
+            ExprKind::FieldAccess { object, .. }
+            | ExprKind::Cast { expr: object, .. }
+            | ExprKind::Dereference { expr: object }
+            | ExprKind::Await { expr: object }
+            | ExprKind::Unsafe { body: object } => self.eval(object, flow),
+
+            ExprKind::Index { object, index } => {
+                let handles = self.eval(object, flow);
+                self.eval(index, flow);
+                handles
+            }
+
+            ExprKind::Reference { expr: inner, .. } => {
+                // Lending a handle leaves it where it is.
+                self.eval(inner, flow);
+                Handles::new()
+            }
+
+            ExprKind::Binary { left, right, .. } => {
+                self.eval(left, flow);
+                self.eval(right, flow);
+                Handles::new()
+            }
+
+            ExprKind::Unary { expr: inner, .. } => {
+                self.eval(inner, flow);
+                Handles::new()
+            }
+
+            ExprKind::Range { start, end, .. } => {
+                for bound in start.iter().chain(end) {
+                    self.eval(bound, flow);
+                }
+                Handles::new()
+            }
+
+            ExprKind::Assign { target, op, value } => {
+                self.eval_assign(target, op.is_some(), value, flow);
+                Handles::new()
+            }
+
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.eval(condition, flow);
+                let mut otherwise = flow.clone();
+                let mut handles = self.eval(then_branch, flow);
+                if let Some(else_branch) = else_branch {
+                    handles.extend(self.eval(else_branch, &mut otherwise));
+                }
+                join(flow, otherwise);
+                handles
+            }
+
+            ExprKind::Match { expr: scrutinee, arms } => {
+                let scrutinee = self.eval(scrutinee, flow);
+                let mut handles = Handles::new();
+                let mut after = None;
+                for arm in arms {
+                    let mut arm_flow = flow.clone();
+                    let saved = Self::bind(&mut arm_flow, &arm.pattern, &scrutinee);
+                    if let Some(guard) = &arm.guard {
+                        self.eval(guard, &mut arm_flow);
+                    }
+                    handles.extend(self.eval(&arm.body, &mut arm_flow));
+                    Self::unbind(&mut arm_flow, saved);
+                    join(&mut after, arm_flow);
+                }
+                if !arms.is_empty() {
+                    *flow = after;
+                }
+                handles
+            }
+
+            ExprKind::Block(block) => self.eval_block(block, flow),
+
+            ExprKind::Loop { body, label } => self.eval_loop(label, None, None, body, flow),
+
+            ExprKind::While { condition, body, label } => {
+                self.eval_loop(label, Some(condition), None, body, flow)
+            }
+
+            ExprKind::For { pattern, iterable, body, label } => {
+                let items = self.eval(iterable, flow);
+                self.eval_loop(label, None, Some((pattern, items)), body, flow)
+            }
+
+            ExprKind::Break { label, value } => {
+                let handles = match value {
+                    Some(value) => self.eval(value, flow),
+                    None => Handles::new(),
+                };
+                if let Some(depth) = self.frame(label).map(|frame| frame.scopes) {
+                    self.exit_scopes(depth, flow);
+                }
+                let state = flow.take();
+                if let Some(frame) = self.frame(label) {
+                    join(&mut frame.breaks, state);
+                    frame.values.extend(handles);
+                }
+                Handles::new()
+            }
+
+            ExprKind::Continue { label } => {
+                if let Some(depth) = self.frame(label).map(|frame| frame.scopes) {
+                    self.exit_scopes(depth, flow);
+                }
+                let state = flow.take();
+                if let Some(frame) = self.frame(label) {
+                    join(&mut frame.continues, state);
+                }
+                Handles::new()
+            }
+
+            ExprKind::Return { value } => {
+                let result = match value {
+                    Some(value) => self.eval(value, flow),
+                    None => Handles::new(),
+                };
+                self.exit_scopes(0, flow);
+                self.leave(flow, &result);
+                *flow = None;
+                Handles::new()
+            }
+
+            ExprKind::Try { expr: inner } => {
+                let handles = self.eval(inner, flow);
+                // On failure `?` returns, leaving everything else still open.
+                let mut failed = flow.clone();
+                self.exit_scopes(0, &mut failed);
+                self.leave(&failed, &handles);
+                handles
+            }
+
+            ExprKind::Tuple(elements) | ExprKind::Array { elements, .. } => {
+                let mut handles = Handles::new();
+                for element in elements {
+                    handles.extend(self.eval(element, flow));
+                }
+                if let ExprKind::Array { repeat: Some(count), .. } = &expr.kind {
+                    self.eval(count, flow);
+                }
+                handles
+            }
+
+            ExprKind::Struct { fields, base, .. } => {
+                let mut handles = Handles::new();
+                for field in fields {
+                    handles.extend(match &field.value {
+                        Some(value) => self.eval(value, flow),
+                        None => self.read(&field.name, flow),
+                    });
+                }
+                if let Some(base) = base {
+                    handles.extend(self.eval(base, flow));
+                }
+                handles
+            }
+
+            ExprKind::Closure { params, body, .. } => {
+                self.eval_closure(params.iter().map(|param| &param.pattern), body, flow)
+            }
+
+            ExprKind::Async { body, .. } | ExprKind::Spawn { body } => {
+                self.eval_closure(std::iter::empty(), body, flow)
+            }
+
+            ExprKind::Variable { .. } | ExprKind::Literal(_) | ExprKind::Macro { .. } | ExprKind::Error => {
+                Handles::new()
+            }
+        }
+    }
+
+    fn eval_call(&mut self, callee: &Expr, args: &[Expr], span: SourceSpan, flow: &mut Flow) -> Handles {
+        // Calling a closure hands over the handles it captured.
+        let captured = self.eval(callee, flow);
+        Self::give_away(flow, &captured);
+
+        let mut moved = Handles::new();
+        for arg in args {
+            moved.extend(self.eval(arg, flow));
+        }
+        let name = match &callee.kind {
+            ExprKind::Variable { path } => path.join("::"),
+            _ => String::new(),
+        };
+        if name == "drop" || RESOURCE_APIS.iter().any(|api| api.release.contains(&name.as_str())) {
+            self.release(flow, &moved, &name);
+        } else if !RESOURCE_APIS.iter().any(|api| api.uses.contains(&name.as_str())) {
+            Self::give_away(flow, &moved);
+        }
+
+        match RESOURCE_APIS.iter().find(|api| api.acquire.contains(&name.as_str())) {
+            Some(api) => self.acquire(api.kind, span, flow),
+            None => Handles::new(),
+        }
+    }
+
+    /// `target = value`, or `target op= value` when `compound`.
+    fn eval_assign(&mut self, target: &Expr, compound: bool, value: &Expr, flow: &mut Flow) {
+        let handles = self.eval(value, flow);
+        if compound || !matches!(target.kind, ExprKind::Variable { .. }) {
+            self.eval(target, flow);
+        }
+        let Some(state) = flow else { return };
+        match place_root(target) {
+            Some((name, whole)) if state.holds.contains_key(name) => {
+                let held = state.holds.entry(name.to_string()).or_default();
+                if whole && !compound {
+                    // Whatever the variable held before stays open, unheld.
+                    *held = handles;
+                } else {
+                    held.extend(handles);
+                }
+            }
+            // Stored in a global or through a pointer: it outlives the function.
+            _ => state.open.retain(|id| !handles.contains(id)),
+        }
+    }
+
+    fn eval_block(&mut self, block: &Block, flow: &mut Flow) -> Handles {
+        let mut saved = Vec::new();
+        self.deferred.push(Vec::new());
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Let { pattern, initializer, .. } => {
+                    let handles = match initializer {
+                        Some(initializer) => self.eval(initializer, flow),
+                        None => Handles::new(),
+                    };
+                    saved.extend(Self::bind(flow, pattern, &handles));
+                }
+                StmtKind::Expr(expr) => {
+                    self.eval(expr, flow);
+                }
+                // Followed wherever a path leaves the block.
+                StmtKind::Defer { body } => {
+                    if let Some(scope) = self.deferred.last_mut()
+                        && flow.is_some()
+                    {
+                        scope.push(body.clone());
+                    }
+                }
+                StmtKind::Item(_) | StmtKind::Macro { .. } => {}
+            }
+        }
+        let handles = match &block.expr {
+            Some(expr) => self.eval(expr, flow),
+            None => Handles::new(),
+        };
+        self.exit_scopes(self.deferred.len() - 1, flow);
+        self.deferred.pop();
+        Self::unbind(flow, saved);
+        handles
+    }
+
+    /// Follow a loop until another pass over its body changes nothing.
+    /// `while` loops check `condition` before each pass, `for` loops bind
+    /// `binding` to the items; both, unlike `loop`, can end without `break`.
+    fn eval_loop(
+        &mut self,
+        label: &Option<String>,
+        condition: Option<&Expr>,
+        binding: Option<(&Pattern, Handles)>,
+        body: &Expr,
+        flow: &mut Flow,
+    ) -> Handles {
+        let Some(mut head) = flow.take() else { return Handles::new() };
+        loop {
+            self.loops.push(LoopFrame {
+                label: label.clone(),
+                breaks: None,
+                continues: None,
+                values: Handles::new(),
+                scopes: self.deferred.len(),
+            });
+            let mut pass = Some(head.clone());
+            if let Some(condition) = condition {
+                self.eval(condition, &mut pass);
+            }
+            let finished = if condition.is_some() || binding.is_some() { pass.clone() } else { None };
+            let saved = match &binding {
+                Some((pattern, items)) => Self::bind(&mut pass, pattern, items),
+                None => Vec::new(),
+            };
+            self.eval(body, &mut pass);
+            Self::unbind(&mut pass, saved);
+            let frame = self.loops.pop().expect("pushed above");
+
+            let mut next = head.clone();
+            for state in pass.iter().chain(&frame.continues) {
+                next.join(state);
+            }
+            if next == head {
+                let mut exit = finished;
+                join(&mut exit, frame.breaks);
+                *flow = exit;
+                return frame.values;
+            }
+            head = next;
+        }
+    }
+
+    /// A closure captures the handles of the variables its body reads.
+    fn eval_closure<'p>(
+        &mut self,
+        params: impl Iterator<Item = &'p Pattern>,
+        body: &Expr,
+        flow: &mut Flow,
+    ) -> Handles {
+        let outer_touched = std::mem::take(&mut self.touched);
+        let outer_loops = std::mem::take(&mut self.loops);
+        let outer_deferred = std::mem::take(&mut self.deferred);
+        self.closures += 1;
+
+        let mut inner = flow.clone();
+        for pattern in params {
+            Self::bind(&mut inner, pattern, &Handles::new());
+        }
+        self.eval(body, &mut inner);
+
+        self.closures -= 1;
+        self.loops = outer_loops;
+        self.deferred = outer_deferred;
+        let captured = std::mem::replace(&mut self.touched, outer_touched);
+        self.touched.extend(&captured);
+        captured
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+    use shared::ItemKind;
+
+    /// The kinds of resource leaked by the function `f` in `source`, with
+    /// the source text of each acquisition.
+    fn leaks(source: &str) -> Vec<(String, String)> {
+        let program = parse_source(source).unwrap();
+        let (params, body) = program
+            .items
+            .iter()
+            .find_map(|item| match &item.kind {
+                ItemKind::Function { name, params, body: Some(body), .. } if name == "f" => Some((params, body)),
+                _ => None,
+            })
+            .unwrap();
+        find_leaks(params, body)
+            .into_iter()
+            .map(|violation| {
+                let (kind, site) = match violation {
+                    SafetyViolation::MemoryLeak { allocation_site, .. } => ("memory".to_string(), allocation_site),
+                    SafetyViolation::ResourceLeak { resource_type, acquisition_site, .. } => {
+                        (resource_type, acquisition_site)
+                    }
+                    other => panic!("not a leak: {:?}", other),
+                };
+                (kind, source[site.offset()..site.offset() + site.len()].to_string())
+            })
+            .collect()
+    }
+
+    fn leak(kind: &str, site: &str) -> (String, String) {
+        (kind.to_string(), site.to_string())
+    }
+
+    #[test]
+    fn released_resources_do_not_leak() {
+        assert!(leaks("fn f() { let p = malloc(8); free(p); }").is_empty());
+        assert!(leaks("fn f() { let h = open(\"a\"); let g = h; close(g); }").is_empty());
+        assert!(leaks("fn f() { let p = malloc(8) as i64; drop(p); }").is_empty());
+    }
+
+    #[test]
+    fn leaks_are_reported_at_the_acquisition() {
+        assert_eq!(leaks("fn f() { let p = malloc(8); }"), [leak("memory", "malloc(8)")]);
+        assert_eq!(leaks("fn f() { open(\"a\"); }"), [leak("file", "open(\"a\")")]);
+        // Releasing a resource of another kind does not count.
+        assert_eq!(leaks("fn f() { let h = open(\"a\"); free(h); }"), [leak("file", "open(\"a\")")]);
+    }
+
+    #[test]
+    fn a_release_on_one_branch_only_leaks_on_the_other() {
+        let source = "fn f(c: bool) { let h = open(\"a\"); if c { close(h); } }";
+        assert_eq!(leaks(source), [leak("file", "open(\"a\")")]);
+        let source = "fn f(c: bool) { let h = open(\"a\"); if c { close(h); } else { drop(h); } }";
+        assert!(leaks(source).is_empty());
+    }
+
+    #[test]
+    fn returned_and_moved_handles_are_not_leaks() {
+        assert!(leaks("fn f() -> i32 { let h = open(\"a\"); h }").is_empty());
+        assert!(leaks("fn f(c: bool) -> i32 { let h = open(\"a\"); if c { h } else { close(h); 0 } }").is_empty());
+        assert!(leaks("fn f() { let h = open(\"a\"); keep(h); }").is_empty());
+    }
+
+    #[test]
+    fn overwriting_a_handle_leaks_the_old_one() {
+        let source = "fn f() { let mut h = open(\"a\"); h = open(\"b\"); close(h); }";
+        assert_eq!(leaks(source), [leak("file", "open(\"a\")")]);
+        let source = "fn f() { let h = open(\"a\"); { let h = open(\"b\"); close(h); }; close(h); }";
+        assert!(leaks(source).is_empty());
+    }
+
+    #[test]
+    fn loops_reach_a_fixpoint() {
+        let source = "fn f(n: i32) { for i in 0..n { let p = malloc(8); } }";
+        assert_eq!(leaks(source), [leak("memory", "malloc(8)")]);
+        let source = "fn f(n: i32) { let mut p = malloc(8); for i in 0..n { free(p); p = malloc(8); } free(p); }";
+        assert!(leaks(source).is_empty());
+    }
+
+    #[test]
+    fn deferred_releases_count_on_every_path_out_of_their_block() {
+        assert!(leaks("fn f() { let h = open(\"a\"); defer { close(h); } h.flush(); }").is_empty());
+        let source = "fn f(n: i32) { for i in 0..n { let p = malloc(8); defer { free(p); } } }";
+        assert!(leaks(source).is_empty());
+        // Deferred in a block the handle outlives.
+        let source = "fn f(c: bool) { let h = open(\"a\"); if c { defer { close(h); } } }";
+        assert_eq!(leaks(source), [leak("file", "open(\"a\")")]);
+    }
+}
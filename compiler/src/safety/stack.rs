@@ -0,0 +1,185 @@
+// compiler/src/safety/stack.rs
+//! Stack depth analysis.
+//!
+//! The recursion cycles of the program's call graph are reported, since
+//! nothing bounds their stack depth. Outside them the graph is acyclic, and
+//! the deepest chain of frames from each function nobody calls, such as
+//! `main`, is checked against the allowed depth.
+
+use super::analyzer::SafetyViolation;
+use crate::callgraph::{Call, CallGraph};
+use shared::Program;
+use std::collections::{BTreeSet, HashMap};
+
+/// The stack overflow risks of `program`: every recursion cycle, and every
+/// chain of calls from a function nobody calls that is more than
+/// `max_depth` frames deep.
+pub fn find_stack_overflows(program: &Program, max_depth: usize) -> Vec<SafetyViolation> {
+    let graph = CallGraph::new(program);
+    let mut violations = Vec::new();
+
+    let cycles = graph.recursion_cycles();
+    let mut cycle_of = HashMap::new();
+    for (id, cycle) in cycles.iter().enumerate() {
+        for function in cycle {
+            cycle_of.insert(*function, id);
+        }
+        // From the cycle's first function round to the call back into it
+        let path = path_within(&graph, cycle, cycle[0]);
+        let (closing, _) = path.last().copied().expect("a cycle has a call");
+        let mut call_chain = vec![cycle[0].to_string()];
+        call_chain.extend(path.iter().map(|(call, _)| call.callee.to_string()));
+        violations.push(SafetyViolation::StackOverflow {
+            span: closing.span,
+            call_depth: None,
+            max_depth,
+            function_name: cycle[0].to_string(),
+            call_chain,
+        });
+    }
+
+    // Deepest chains, counting one pass through each cycle.
+    let mut depths = Depths { graph: &graph, cycle_of: &cycle_of, deepest: HashMap::new() };
+    let called: BTreeSet<&str> = graph
+        .functions()
+        .flat_map(|function| graph.calls(function).iter().filter(move |call| call.callee != function))
+        .map(|call| call.callee)
+        .collect();
+    for root in graph.functions().filter(|function| !called.contains(function)) {
+        let depth = depths.depth(root);
+        if depth <= max_depth {
+            continue;
+        }
+        let mut call_chain = vec![root.to_string()];
+        let mut overflowing = None;
+        let mut function = root;
+        while let Some((_, Some(call))) = depths.deepest.get(function).copied() {
+            call_chain.push(call.callee.to_string());
+            // The call pushing the frame one past the limit
+            if call_chain.len() == max_depth + 1 {
+                overflowing = Some(call.span);
+            }
+            function = call.callee;
+        }
+        violations.push(SafetyViolation::StackOverflow {
+            span: overflowing.or(graph.definition(root)).unwrap_or(program.span),
+            call_depth: Some(depth),
+            max_depth,
+            function_name: root.to_string(),
+            call_chain,
+        });
+    }
+    violations
+}
+
+/// The calls leading from `from` back to `to` without leaving `cycle`,
+/// each with the function making it.
+fn path_within<'a>(graph: &CallGraph<'a>, cycle: &[&'a str], to: &'a str) -> Vec<(Call<'a>, &'a str)> {
+    // Breadth-first from `to`, so the path is a shortest one.
+    let mut reached: HashMap<&str, (Call<'a>, &'a str)> = HashMap::new();
+    let mut queue = std::collections::VecDeque::from([to]);
+    while let Some(function) = queue.pop_front() {
+        for call in graph.calls(function) {
+            if !cycle.contains(&call.callee) || reached.contains_key(call.callee) {
+                continue;
+            }
+            reached.insert(call.callee, (*call, function));
+            if call.callee == to {
+                queue.clear();
+                break;
+            }
+            queue.push_back(call.callee);
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut function = to;
+    while let Some(&(call, caller)) = reached.get(function) {
+        path.push((call, caller));
+        if caller == to {
+            break;
+        }
+        function = caller;
+    }
+    path.reverse();
+    path
+}
+
+/// The depth of the deepest call chain from each function.
+struct Depths<'g, 'a> {
+    graph: &'g CallGraph<'a>,
+    cycle_of: &'g HashMap<&'a str, usize>,
+    /// For each function done, its depth and the call starting its deepest
+    /// chain
+    deepest: HashMap<&'a str, (usize, Option<Call<'a>>)>,
+}
+
+impl<'a> Depths<'_, 'a> {
+    fn depth(&mut self, function: &'a str) -> usize {
+        if let Some((depth, _)) = self.deepest.get(function) {
+            return *depth;
+        }
+        // Calls within the function's own cycle go round it once; they are
+        // reported as recursion.
+        self.deepest.insert(function, (1, None));
+        let cycle = self.cycle_of.get(function);
+        let mut best = (1, None);
+        for call in self.graph.calls(function) {
+            if cycle.is_some() && self.cycle_of.get(call.callee) == cycle {
+                continue;
+            }
+            let depth = 1 + self.depth(call.callee);
+            if depth > best.0 {
+                best = (depth, Some(*call));
+            }
+        }
+        self.deepest.insert(function, best);
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_source;
+    use miette::SourceSpan;
+
+    /// Each stack overflow risk in `source`: the line it is reported at,
+    /// the depth found and the chain of calls.
+    fn overflows(source: &str, max_depth: usize) -> Vec<(usize, Option<usize>, Vec<String>)> {
+        let line = |span: SourceSpan| source[..span.offset()].matches('\n').count() + 1;
+        find_stack_overflows(&parse_source(source).unwrap(), max_depth)
+            .into_iter()
+            .filter_map(|violation| match violation {
+                SafetyViolation::StackOverflow { span, call_depth, call_chain, .. } => {
+                    Some((line(span), call_depth, call_chain))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn chain(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn direct_recursion_is_reported_at_the_recursive_call() {
+        let source = "fn main() {\n    count(3);\n}\nfn count(n: i32) {\n    g(n);\n    count(n);\n}";
+        assert_eq!(overflows(source, 256), vec![(6, None, chain(&["count", "count"]))]);
+    }
+
+    #[test]
+    fn mutual_recursion_is_one_cycle() {
+        let source = "fn even(n: i32) {\n    odd(n);\n}\nfn odd(n: i32) {\n    even(n);\n}\nfn main() {\n    even(4);\n}";
+        assert_eq!(overflows(source, 256), vec![(5, None, chain(&["even", "odd", "even"]))]);
+    }
+
+    #[test]
+    fn call_chains_deeper_than_the_limit_are_reported() {
+        let source = "fn main() {\n    a();\n}\nfn a() {\n    b();\n}\nfn b() {\n    c();\n}\nfn c() {}";
+        assert!(overflows(source, 4).is_empty());
+        // The call to `c` needs the fourth frame.
+        assert_eq!(overflows(source, 3), vec![(8, Some(4), chain(&["main", "a", "b", "c"]))]);
+    }
+}
@@ -0,0 +1,182 @@
+// compiler/src/stats.rs
+//! Time and memory spent in each pass of a compilation, and what the
+//! optimizers did.
+//!
+//! Memory is the resident set size of the process as the operating system
+//! reports it, read before and after each pass. It reads as zero where the
+//! operating system does not report it; only Linux does so far.
+
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Resident memory around one pass, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryStats {
+    pub resident_before: u64,
+    pub resident_after: u64,
+}
+
+impl MemoryStats {
+    /// How much the pass grew resident memory; negative if it shrank.
+    pub fn growth(&self) -> i64 {
+        self.resident_after as i64 - self.resident_before as i64
+    }
+}
+
+/// What one pass cost.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PassStats {
+    pub name: String,
+    #[serde(rename = "time_us", serialize_with = "micros")]
+    pub time: Duration,
+    pub memory: MemoryStats,
+}
+
+/// What each pass of a compilation cost, in the order they ran.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CompilationStats {
+    pub passes: Vec<PassStats>,
+    /// How often the optimizers did each thing they count, such as
+    /// `inlined call sites` (see `plugin_api::CompiledModule::optimizations`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub optimizations: BTreeMap<String, u64>,
+}
+
+impl CompilationStats {
+    /// Run `pass`, recording its cost under `name`.
+    pub fn measure<T>(&mut self, name: impl Into<String>, pass: impl FnOnce() -> T) -> T {
+        let resident_before = resident_memory();
+        let start = Instant::now();
+        let result = pass();
+        let time = start.elapsed();
+        self.passes.push(PassStats {
+            name: name.into(),
+            time,
+            memory: MemoryStats { resident_before, resident_after: resident_memory() },
+        });
+        result
+    }
+
+    /// Time spent in all the passes.
+    pub fn total_time(&self) -> Duration {
+        self.passes.iter().map(|pass| pass.time).sum()
+    }
+
+    /// The stats as a table with a row per pass and a total, showing the
+    /// time taken, memory use or both, then a row per thing the optimizers
+    /// counted.
+    pub fn table(&self, time: bool, memory: bool) -> String {
+        let names = self.passes.iter().map(|pass| &pass.name).chain(self.optimizations.keys());
+        let width = names.map(String::len).chain([5]).max().unwrap_or(5);
+        let mut table = format!("{:<width$}", "pass");
+        if time {
+            table.push_str("        time");
+        }
+        if memory {
+            table.push_str("    resident      growth");
+        }
+        table.push('\n');
+
+        let mut row = |name: &str, duration: Duration, memory_stats: Option<MemoryStats>| {
+            let _ = write!(table, "{:<width$}", name);
+            if time {
+                let _ = write!(table, " {:>8.3} ms", duration.as_secs_f64() * 1000.0);
+            }
+            if memory && let Some(stats) = memory_stats {
+                let _ = write!(table, " {:>7.1} MiB {:>+7.1} MiB", mebibytes(stats.resident_after as i64), mebibytes(stats.growth()));
+            }
+            table.push('\n');
+        };
+        for pass in &self.passes {
+            row(&pass.name, pass.time, Some(pass.memory));
+        }
+        row("total", self.total_time(), None);
+        for (what, n) in &self.optimizations {
+            let _ = writeln!(table, "{:<width$} {:>11}", what, n);
+        }
+        table
+    }
+
+    /// The stats as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("compilation stats are always serializable")
+    }
+}
+
+fn micros<S: Serializer>(time: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(time.as_micros())
+}
+
+fn mebibytes(bytes: i64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// The resident set size of this process in bytes, or 0 if unknown.
+pub fn resident_memory() -> u64 {
+    // `VmRSS:    12345 kB`
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+            line.split_whitespace().nth(1)?.parse::<u64>().ok()
+        })
+        .map_or(0, |kib| kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_are_recorded_in_order() {
+        let mut stats = CompilationStats::default();
+        let parsed = stats.measure("parse", || 1 + 1);
+        stats.measure("codegen", || std::thread::sleep(Duration::from_millis(2)));
+
+        assert_eq!(parsed, 2);
+        let names: Vec<&str> = stats.passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(names, ["parse", "codegen"]);
+        assert!(stats.passes[1].time >= Duration::from_millis(2));
+        assert_eq!(stats.total_time(), stats.passes[0].time + stats.passes[1].time);
+    }
+
+    #[test]
+    fn stats_print_as_a_table_or_json() {
+        let pass = |name: &str, millis| PassStats {
+            name: name.to_string(),
+            time: Duration::from_millis(millis),
+            memory: MemoryStats { resident_before: 1 << 20, resident_after: 3 << 20 },
+        };
+        let stats = CompilationStats { passes: vec![pass("parse", 2), pass("type check", 5)], ..Default::default() };
+
+        let table = stats.table(true, true);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "pass              time    resident      growth");
+        assert_eq!(lines[1], "parse         2.000 ms     3.0 MiB    +2.0 MiB");
+        assert_eq!(lines[3], "total         7.000 ms");
+        assert_eq!(stats.table(true, false).lines().nth(2), Some("type check    5.000 ms"));
+
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["passes"][1]["name"], "type check");
+        assert_eq!(json["passes"][1]["time_us"], 5000);
+        assert_eq!(json["passes"][1]["memory"]["resident_after"], 3 << 20);
+        assert!(json.get("optimizations").is_none());
+    }
+
+    #[test]
+    fn what_the_optimizers_counted_follows_the_total() {
+        let mut stats = CompilationStats::default();
+        stats.measure("optimize inline", || ());
+        stats.optimizations.insert("inlined call sites".to_string(), 3);
+
+        let table = stats.table(false, false);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "pass              ");
+        assert_eq!(lines[2], "total             ");
+        assert_eq!(lines[3], "inlined call sites           3");
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["optimizations"]["inlined call sites"], 3);
+    }
+}
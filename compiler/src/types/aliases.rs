@@ -0,0 +1,283 @@
+// compiler/src/types/aliases.rs
+//! Expansion of type aliases, before type checking.
+//!
+//! `type Name = Type;` makes `Name` another name for `Type`, not a new
+//! type: every written type naming an alias is replaced by what the alias
+//! stands for, followed through aliases of aliases. The checker and the
+//! lowering then only see the types aliases name, so a `Km` is an `f64`
+//! wherever one is expected. An alias defined in terms of itself, directly
+//! or through other aliases, names no type and is rejected.
+
+use shared::ast::stmt::{ExternItem, ImplItem, StructFields};
+use shared::ast::Block;
+use shared::{Expr, ExprKind, Item, ItemKind, Program, Result, Stmt, StmtKind, TlError, Type, TypeKind};
+use std::collections::HashMap;
+
+/// Replace every alias written in `program` by the type it names.
+pub fn expand_aliases(program: &mut Program, source: &str) -> Result<()> {
+    let mut declared = HashMap::new();
+    collect(&program.items, &mut declared);
+    if declared.is_empty() {
+        return Ok(());
+    }
+
+    let mut aliases = Aliases { source, declared, expanded: HashMap::new() };
+    let mut names: Vec<String> = aliases.declared.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        aliases.resolve(&name, &mut Vec::new())?;
+    }
+    for item in &mut program.items {
+        aliases.item(item);
+    }
+    Ok(())
+}
+
+/// Record the aliases declared in `items`, with their items' spans.
+fn collect(items: &[Item], declared: &mut HashMap<String, (Type, miette::SourceSpan)>) {
+    for item in items {
+        match &item.kind {
+            ItemKind::TypeAlias { name, ty, .. } => {
+                declared.insert(name.clone(), (ty.clone(), item.span));
+            }
+            ItemKind::Module { items, .. } => collect(items, declared),
+            _ => {}
+        }
+    }
+}
+
+/// The name a written type refers to, if it may be an alias.
+fn alias_name(ty: &Type) -> Option<&str> {
+    match &ty.kind {
+        TypeKind::Named { path, generics } if path.len() == 1 && generics.is_empty() => Some(&path[0]),
+        _ => None,
+    }
+}
+
+struct Aliases<'a> {
+    source: &'a str,
+    /// Each alias's target as written, and where it is declared.
+    declared: HashMap<String, (Type, miette::SourceSpan)>,
+    /// The targets resolved so far, with no aliases left in them.
+    expanded: HashMap<String, Type>,
+}
+
+impl Aliases<'_> {
+    /// The type the alias `name` stands for. `resolving` holds the aliases
+    /// whose targets are being resolved, the outermost first.
+    fn resolve(&mut self, name: &str, resolving: &mut Vec<String>) -> Result<Type> {
+        if let Some(ty) = self.expanded.get(name) {
+            return Ok(ty.clone());
+        }
+        if let Some(start) = resolving.iter().position(|alias| alias == name) {
+            let mut cycle = resolving[start..].to_vec();
+            cycle.push(name.to_string());
+            let span = self.declared[&cycle[0]].1;
+            return Err(TlError::type_error(
+                self.source.to_string(),
+                span,
+                format!("Type alias `{}` is defined in terms of itself: {}", cycle[0], cycle.join(" = ")),
+            ));
+        }
+        let target = self.declared[name].0.clone();
+        resolving.push(name.to_string());
+        let ty = self.resolve_type(&target, resolving);
+        resolving.pop();
+        let ty = ty?;
+        self.expanded.insert(name.to_string(), ty.clone());
+        Ok(ty)
+    }
+
+    /// `ty` with the aliases in it resolved.
+    fn resolve_type(&mut self, ty: &Type, resolving: &mut Vec<String>) -> Result<Type> {
+        if let Some(name) = alias_name(ty)
+            && self.declared.contains_key(name)
+        {
+            let target = self.resolve(name, resolving)?;
+            return Ok(Type::new(target.kind, ty.span));
+        }
+        let mut resolve = |t: &Type| self.resolve_type(t, resolving).map(Box::new);
+        let kind = match &ty.kind {
+            TypeKind::Array { element, size } => TypeKind::Array { element: resolve(element)?, size: size.clone() },
+            TypeKind::Slice { element } => TypeKind::Slice { element: resolve(element)? },
+            TypeKind::Reference { target, lifetime, mutable } => {
+                TypeKind::Reference { target: resolve(target)?, lifetime: lifetime.clone(), mutable: *mutable }
+            }
+            TypeKind::Pointer { target, mutable } => TypeKind::Pointer { target: resolve(target)?, mutable: *mutable },
+            TypeKind::Function { params, return_type, safety } => TypeKind::Function {
+                params: params.iter().map(|p| resolve(p).map(|p| *p)).collect::<Result<_>>()?,
+                return_type: resolve(return_type)?,
+                safety: *safety,
+            },
+            TypeKind::Tuple(elems) => {
+                TypeKind::Tuple(elems.iter().map(|e| resolve(e).map(|e| *e)).collect::<Result<_>>()?)
+            }
+            TypeKind::Named { path, generics } => TypeKind::Named {
+                path: path.clone(),
+                generics: generics.iter().map(|g| resolve(g).map(|g| *g)).collect::<Result<_>>()?,
+            },
+            kind => kind.clone(),
+        };
+        Ok(Type::new(kind, ty.span))
+    }
+
+    /// Expand the aliases in `ty`, all of which are resolved.
+    fn expand(&mut self, ty: &mut Type) {
+        // Resolving cannot fail any more, nor meet a cycle.
+        if let Ok(expanded) = self.resolve_type(ty, &mut Vec::new()) {
+            *ty = expanded;
+        }
+    }
+
+    fn item(&mut self, item: &mut Item) {
+        match &mut item.kind {
+            ItemKind::Function { params, return_type, body, .. } => {
+                params.iter_mut().for_each(|param| self.expand(&mut param.ty));
+                return_type.iter_mut().for_each(|ty| self.expand(ty));
+                body.iter_mut().for_each(|body| self.expr(body));
+            }
+            ItemKind::Struct { fields, .. } => match fields {
+                StructFields::Named(fields) => fields.iter_mut().for_each(|field| self.expand(&mut field.ty)),
+                StructFields::Unnamed(types) => types.iter_mut().for_each(|ty| self.expand(ty)),
+                StructFields::Unit => {}
+            },
+            ItemKind::Enum { variants, .. } => {
+                for variant in variants {
+                    match &mut variant.fields {
+                        StructFields::Named(fields) => fields.iter_mut().for_each(|field| self.expand(&mut field.ty)),
+                        StructFields::Unnamed(types) => types.iter_mut().for_each(|ty| self.expand(ty)),
+                        StructFields::Unit => {}
+                    }
+                }
+            }
+            ItemKind::TypeAlias { ty, .. } => self.expand(ty),
+            ItemKind::Const { ty, value, .. } | ItemKind::Static { ty, value, .. } => {
+                self.expand(ty);
+                self.expr(value);
+            }
+            ItemKind::Extern { items, .. } => {
+                for extern_item in items {
+                    match extern_item {
+                        ExternItem::Function { params, return_type, .. } => {
+                            params.iter_mut().for_each(|param| self.expand(&mut param.ty));
+                            return_type.iter_mut().for_each(|ty| self.expand(ty));
+                        }
+                        ExternItem::Static { ty, .. } => self.expand(ty),
+                    }
+                }
+            }
+            ItemKind::Impl { items, .. } => {
+                for impl_item in items {
+                    match impl_item {
+                        ImplItem::Function { params, return_type, body, .. } => {
+                            params.iter_mut().for_each(|param| self.expand(&mut param.ty));
+                            return_type.iter_mut().for_each(|ty| self.expand(ty));
+                            self.expr(body);
+                        }
+                        ImplItem::Type { ty, .. } => self.expand(ty),
+                        ImplItem::Const { ty, value, .. } => {
+                            self.expand(ty);
+                            self.expr(value);
+                        }
+                    }
+                }
+            }
+            ItemKind::Module { items, .. } => items.iter_mut().for_each(|item| self.item(item)),
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        for stmt in &mut block.statements {
+            self.stmt(stmt);
+        }
+        if let Some(expr) = &mut block.expr {
+            self.expr(expr);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match &mut stmt.kind {
+            StmtKind::Let { ty, initializer, .. } => {
+                ty.iter_mut().for_each(|ty| self.expand(ty));
+                initializer.iter_mut().for_each(|e| self.expr(e));
+            }
+            StmtKind::Expr(expr) | StmtKind::Defer { body: expr } => self.expr(expr),
+            StmtKind::Item(item) => self.item(item),
+            StmtKind::Macro { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match &mut expr.kind {
+            ExprKind::Cast { expr: inner, target_type } => {
+                self.expand(target_type);
+                self.expr(inner);
+            }
+            ExprKind::Closure { params, return_type, body, .. } => {
+                params.iter_mut().filter_map(|param| param.ty.as_mut()).for_each(|ty| self.expand(ty));
+                return_type.iter_mut().for_each(|ty| self.expand(ty));
+                self.expr(body);
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    arm.guard.iter_mut().for_each(|e| self.expr(e));
+                    self.expr(&mut arm.body);
+                }
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.expr(callee);
+                args.iter_mut().for_each(|a| self.expr(a));
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.expr(receiver);
+                args.iter_mut().for_each(|a| self.expr(a));
+            }
+            ExprKind::Binary { left: a, right: b, .. }
+            | ExprKind::Index { object: a, index: b }
+            | ExprKind::Assign { target: a, value: b, .. }
+            | ExprKind::While { condition: a, body: b, .. }
+            | ExprKind::For { iterable: a, body: b, .. } => {
+                self.expr(a);
+                self.expr(b);
+            }
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.expr(condition);
+                self.expr(then_branch);
+                else_branch.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::Range { start, end, .. } => {
+                start.iter_mut().chain(end.iter_mut()).for_each(|e| self.expr(e));
+            }
+            ExprKind::Break { value, .. } | ExprKind::Return { value } => {
+                value.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::Tuple(elems) => elems.iter_mut().for_each(|e| self.expr(e)),
+            ExprKind::Array { elements, repeat } => {
+                elements.iter_mut().for_each(|e| self.expr(e));
+                repeat.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::Struct { fields, base, .. } => {
+                fields.iter_mut().filter_map(|f| f.value.as_mut()).for_each(|e| self.expr(e));
+                base.iter_mut().for_each(|e| self.expr(e));
+            }
+            ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Loop { body: inner, .. }
+            | ExprKind::Async { body: inner, .. }
+            | ExprKind::Spawn { body: inner }
+            | ExprKind::Await { expr: inner }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Unsafe { body: inner }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner } => self.expr(inner),
+            ExprKind::Literal(_)
+            | ExprKind::Variable { .. }
+            | ExprKind::Continue { .. }
+            | ExprKind::Macro { .. }
+            | ExprKind::Error => {}
+        }
+    }
+}
@@ -2,13 +2,39 @@
 //! Type checker for T-Lang.
 //! Performs type inference and checking with safety analysis for critical systems.
 
+use shared::ast::PrimitiveType;
 use shared::{
     Program, Item, ItemKind, Stmt, StmtKind, Expr, ExprKind, Type, TypeKind,
-    PrimitiveType, BinaryOp, UnaryOp, Literal, Pattern, PatternKind,
+    BinaryOp, UnaryOp, Literal, PatternKind,
     Result, TlError
 };
+use super::utils;
+use super::coercion::{CastKind, CoercionKind, CoercionRules};
+use crate::intrinsics::{intrinsic_named, Capabilities, INTRINSICS};
+use crate::ir::ffi_type;
+use crate::resolve::type_name;
+use shared::ast::expr::{FieldInit, MatchArm};
+use shared::ast::stmt::{ExternItem, FnParam, StructFields};
+use errors::suggest::similar_names;
 use miette::SourceSpan;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// The built-in collections, with the names of their type parameters.
+pub const COLLECTIONS: &[(&str, &[&str])] = &[("Vec", &["T"]), ("HashMap", &["K", "V"]), ("HashSet", &["T"])];
+
+/// What the `checked_` methods of integers return, with its type parameter.
+pub const OPTION: (&str, &[&str]) = ("Option", &["T"]);
+
+/// The key of the methods every integer type has, in terms of `Self`.
+const INTEGER_METHODS: &str = "{integer}";
+
+/// The variadic functions of the prelude, which print their arguments.
+const PRINTING: &[&str] = &["print", "println", "eprint", "eprintln", "format"];
+
+/// The mutex and channel functions, which the lowering cannot run: it runs
+/// a `spawn` body to completion where it is spawned, and a thread waiting
+/// on a lock or a channel could not.
+const UNLOWERED_SYNCHRONIZATION: &[&str] = &["mutex", "lock", "load", "store", "unlock", "channel", "send", "recv"];
 
 /// Type checking context with symbol tables and inference state.
 pub struct TypeChecker {
@@ -16,28 +42,84 @@ pub struct TypeChecker {
     variables: HashMap<String, Type>,
     /// Function signatures
     functions: HashMap<String, FunctionSignature>,
+    /// Method signatures by type name, in terms of the type's parameters
+    methods: HashMap<String, HashMap<String, FunctionSignature>>,
     /// Type definitions (structs, enums, aliases)
     types: HashMap<String, TypeDefinition>,
     /// Type inference variable counter
     next_type_var: u32,
-    /// Active type constraints for inference
-    constraints: Vec<TypeConstraint>,
+    /// Solutions for type inference variables
+    substitutions: HashMap<u32, Type>,
+    /// Inference variables standing for a literal's type
+    literal_vars: HashMap<u32, LiteralVar>,
+    /// Integer literals and their type variables, range-checked once solved
+    int_literals: Vec<(i128, u32, SourceSpan)>,
+    /// Float literals and their type variables, checked to fit once solved
+    float_literals: Vec<(f64, u32, SourceSpan)>,
+    /// Variables of `let` bindings with neither annotation nor initializer
+    unannotated: Vec<(u32, SourceSpan)>,
+    /// Types of the program's statics, and whether each is a `static mut`;
+    /// a variable of the same name hides one
+    statics: HashMap<String, (Type, bool)>,
+    /// Variables of the current scope declared `let mut`
+    mutable: HashSet<String>,
+    /// Variables of the enclosing scopes, and which of them are mutable,
+    /// restored by `pop_scope`
+    scopes: Vec<(HashMap<String, Type>, HashSet<String>)>,
+    /// How many `unsafe` blocks the expression being checked is in
+    unsafe_depth: usize,
+    /// How many loops of the current function or thread the expression
+    /// being checked is in
+    loop_depth: usize,
+    /// What the function being checked returns, which `return` values must
+    /// have; `None` outside of a function or in a thread it spawns
+    returns: Option<Type>,
+    /// How many `defer` blocks the expression being checked is in
+    defer_depth: usize,
+    /// `as` casts (operand type, target type), checked once solved
+    casts: Vec<(Type, Type, SourceSpan)>,
+    /// Implicit coercion and cast rules
+    coercion: CoercionRules,
+    /// Problems found that do not fail type checking
+    warnings: Vec<TypeWarning>,
+    /// What the intrinsics, such as `io::read_file`, may be called for
+    capabilities: Capabilities,
     /// Source code for error reporting
     source: String,
 }
 
+/// A diagnostic that does not stop compilation, such as a lossy cast.
+#[derive(Debug, Clone)]
+pub struct TypeWarning {
+    pub span: SourceSpan,
+    pub message: String,
+}
+
+/// What an unsolved literal variable may become, and its default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LiteralVar {
+    /// Any integer type; `i32` if nothing else is required.
+    Integer,
+    /// Any float type; `f64` if nothing else is required.
+    Float,
+}
+
 /// Function signature information.
 #[derive(Debug, Clone)]
 pub struct FunctionSignature {
     pub params: Vec<Type>,
     pub return_type: Type,
     pub safety_level: shared::SafetyLevel,
+    /// Whether further arguments may follow `params`, as for C's `printf`.
+    pub variadic: bool,
 }
 
 /// Type definition for user-defined types.
 #[derive(Debug, Clone)]
 pub enum TypeDefinition {
     Struct { fields: HashMap<String, Type> },
+    /// `struct Name(A, B);`, whose fields are numbered
+    TupleStruct { fields: Vec<Type> },
     Enum { variants: HashMap<String, Vec<Type>> },
     Alias { target: Type },
 }
@@ -57,18 +139,48 @@ impl TypeChecker {
         let mut checker = Self {
             variables: HashMap::new(),
             functions: HashMap::new(),
+            methods: HashMap::new(),
             types: HashMap::new(),
             next_type_var: 0,
-            constraints: Vec::new(),
+            substitutions: HashMap::new(),
+            literal_vars: HashMap::new(),
+            int_literals: Vec::new(),
+            float_literals: Vec::new(),
+            unannotated: Vec::new(),
+            statics: HashMap::new(),
+            mutable: HashSet::new(),
+            scopes: Vec::new(),
+            unsafe_depth: 0,
+            loop_depth: 0,
+            returns: None,
+            defer_depth: 0,
+            casts: Vec::new(),
+            coercion: CoercionRules::new(source.clone()),
+            warnings: Vec::new(),
+            capabilities: Capabilities::default(),
             source,
         };
 
         checker.add_builtin_functions();
+        checker.add_builtin_collections();
         checker
     }
 
+    /// Allow only the intrinsics `capabilities` allows.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Warnings found so far, in the order they were found.
+    pub fn warnings(&self) -> &[TypeWarning] {
+        &self.warnings
+    }
+
     /// Type check a complete program.
     pub fn check_program(&mut self, program: &mut Program) -> Result<()> {
+        super::aliases::expand_aliases(program, &self.source)?;
+
         // First pass: collect type definitions and function signatures
         for item in &program.items {
             self.collect_item_signature(item)?;
@@ -81,8 +193,18 @@ impl TypeChecker {
 
         // Solve type inference constraints
         self.solve_constraints()?;
+        for item in &mut program.items {
+            match &mut item.kind {
+                ItemKind::Function { body: Some(body), .. } => self.apply_solutions(body),
+                ItemKind::Const { value, .. } | ItemKind::Static { value, .. } => {
+                    self.apply_solutions(value)
+                }
+                _ => {}
+            }
+        }
 
-        Ok(())
+        super::tailcall::check_tailcalls(program, &self.source)?;
+        super::lifetimes::check_lifetimes(program, &self.source)
     }
 
     /// Collect type information from items without checking bodies.
@@ -101,12 +223,38 @@ impl TypeChecker {
                     params: param_types?,
                     return_type: ret_type,
                     safety_level: shared::SafetyLevel::Safe,
+                    variadic: false,
                 });
             }
 
+            ItemKind::Static { name, ty, mutable, .. } => {
+                self.statics.insert(name.clone(), (ty.clone(), *mutable));
+            }
+
+            ItemKind::Extern { items, .. } => {
+                for extern_item in items {
+                    match extern_item {
+                        ExternItem::Function { name, params, return_type, variadic } => {
+                            let ret_type = return_type.clone().unwrap_or_else(|| {
+                                Type::new(TypeKind::Primitive(PrimitiveType::Unit), item.span)
+                            });
+                            self.functions.insert(name.clone(), FunctionSignature {
+                                params: params.iter().map(|p| p.ty.clone()).collect(),
+                                return_type: ret_type,
+                                safety_level: shared::SafetyLevel::Unsafe,
+                                variadic: *variadic,
+                            });
+                        }
+                        ExternItem::Static { name, ty, .. } => {
+                            self.variables.insert(name.clone(), ty.clone());
+                        }
+                    }
+                }
+            }
+
             ItemKind::Struct { name, fields, .. } => {
                 match fields {
-                    shared::stmt::StructFields::Named(field_list) => {
+                    StructFields::Named(field_list) => {
                         let field_map: HashMap<String, Type> = field_list.iter()
                             .map(|f| (f.name.clone(), f.ty.clone()))
                             .collect();
@@ -115,10 +263,40 @@ impl TypeChecker {
                             fields: field_map,
                         });
                     }
+                    // The name is also the function constructing one.
+                    StructFields::Unnamed(field_types) => {
+                        self.types.insert(name.clone(), TypeDefinition::TupleStruct {
+                            fields: field_types.clone(),
+                        });
+                        self.functions.insert(name.clone(), FunctionSignature {
+                            params: field_types.clone(),
+                            return_type: Type::new(
+                                TypeKind::Named { path: vec![name.clone()], generics: vec![] },
+                                item.span,
+                            ),
+                            safety_level: shared::SafetyLevel::Safe,
+                            variadic: false,
+                        });
+                    }
                     _ => {} // Handle other field types as needed
                 }
             }
 
+            // A variant without fields is a value of the enum, `Color::Red`.
+            ItemKind::Enum { name, variants, .. } => {
+                let variants = variants.iter()
+                    .map(|variant| {
+                        let fields = match &variant.fields {
+                            StructFields::Named(fields) => fields.iter().map(|f| f.ty.clone()).collect(),
+                            StructFields::Unnamed(types) => types.clone(),
+                            StructFields::Unit => Vec::new(),
+                        };
+                        (variant.name.clone(), fields)
+                    })
+                    .collect();
+                self.types.insert(name.clone(), TypeDefinition::Enum { variants });
+            }
+
             ItemKind::TypeAlias { name, ty, .. } => {
                 self.types.insert(name.clone(), TypeDefinition::Alias {
                     target: ty.clone(),
@@ -134,24 +312,38 @@ impl TypeChecker {
     /// Type check a top-level item.
     fn check_item(&mut self, item: &mut Item) -> Result<()> {
         match &mut item.kind {
-            ItemKind::Function { params, body, return_type, .. } => {
+            ItemKind::Function { name, params, body, return_type, .. } => {
+                if name == "main" {
+                    self.check_main_signature(params, return_type.as_ref())?;
+                }
+                if let Some(ty @ Type { kind: TypeKind::Reference { mutable: true, .. }, .. }) = return_type {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        ty.span,
+                        format!("Returning a `&mut` from `{}` is not supported yet: a call's value is copied out of \
+                                 the function, so assigning through it would not reach what it borrows", name),
+                    ));
+                }
+
                 // Enter function scope
                 self.push_scope();
 
                 // Add parameters to scope
                 for param in params {
-                    if let PatternKind::Ident(name) = &param.pattern.kind {
-                        self.variables.insert(name.clone(), param.ty.clone());
-                    }
+                    self.bind_pattern(&param.pattern, &param.ty, false)?;
                 }
 
                 // Type check body
                 if let Some(body_expr) = body {
-                    let body_type = self.check_expr(body_expr)?;
+                    let unit = Type::new(TypeKind::Primitive(PrimitiveType::Unit), item.span);
+                    self.returns = Some(return_type.clone().unwrap_or(unit));
+                    let body_type = self.check_expr(body_expr);
+                    self.returns = None;
+                    let body_type = body_type?;
 
                     // Check return type compatibility
                     if let Some(expected_return) = return_type {
-                        self.require_compatible(&body_type, expected_return, body_expr.span,
+                        self.require_coercible(&body_type, expected_return, body_expr.span,
                                                 "Function body type doesn't match return type")?;
                     }
                 }
@@ -161,26 +353,94 @@ impl TypeChecker {
 
             ItemKind::Const { ty, value, .. } => {
                 let value_type = self.check_expr(value)?;
-                self.require_compatible(&value_type, ty, value.span,
+                self.require_coercible(&value_type, ty, value.span,
                                         "Constant value type doesn't match declared type")?;
             }
 
             ItemKind::Static { ty, value, .. } => {
                 let value_type = self.check_expr(value)?;
-                self.require_compatible(&value_type, ty, value.span,
+                self.require_coercible(&value_type, ty, value.span,
                                         "Static value type doesn't match declared type")?;
             }
 
+            ItemKind::Extern { abi, items } => self.check_extern_block(abi.as_deref(), items, item.span)?,
+
             _ => {} // Other items don't need body checking
         }
 
         Ok(())
     }
 
+    /// Check that `main` takes nothing and returns nothing or its exit code.
+    fn check_main_signature(&self, params: &[FnParam], return_type: Option<&Type>) -> Result<()> {
+        if let Some(param) = params.first() {
+            return Err(TlError::type_error(
+                self.source.clone(),
+                param.span,
+                "Function main takes no parameters; env::arg reads the command line".to_string(),
+            ));
+        }
+        match return_type.map(|ty| (ty, &ty.kind)) {
+            None | Some((_, TypeKind::Primitive(PrimitiveType::Unit | PrimitiveType::I32))) => Ok(()),
+            Some((ty, _)) => Err(TlError::type_error(
+                self.source.clone(),
+                ty.span,
+                format!("Function main must return () or i32, its exit code, not {}", type_name(ty)),
+            )),
+        }
+    }
+
+    /// Check that an `extern` block uses the C ABI and that every type in it
+    /// can cross that boundary.
+    fn check_extern_block(&self, abi: Option<&str>, items: &[ExternItem], span: SourceSpan) -> Result<()> {
+        if let Some(abi) = abi.filter(|abi| *abi != "C") {
+            return Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("Unsupported ABI \"{}\"; extern blocks use \"C\"", abi),
+            ));
+        }
+        let require_ffi = |ty: &Type| {
+            if ffi_type(ty).is_some() {
+                Ok(())
+            } else {
+                Err(TlError::type_error(
+                    self.source.clone(),
+                    ty.span,
+                    format!("Type {} cannot be passed to or from C", type_name(ty)),
+                ))
+            }
+        };
+        for extern_item in items {
+            match extern_item {
+                ExternItem::Function { name, params, return_type, variadic } => {
+                    if *variadic && params.is_empty() {
+                        return Err(TlError::type_error(
+                            self.source.clone(),
+                            span,
+                            format!("Variadic extern function {} needs a parameter before `...`", name),
+                        ));
+                    }
+                    for param in params {
+                        require_ffi(&param.ty)?;
+                    }
+                    match return_type {
+                        Some(ty) if !matches!(ty.kind, TypeKind::Primitive(PrimitiveType::Unit)) => {
+                            require_ffi(ty)?
+                        }
+                        _ => {}
+                    }
+                }
+                ExternItem::Static { ty, .. } => require_ffi(ty)?,
+            }
+        }
+        Ok(())
+    }
+
     /// Type check an expression and return its type.
     pub fn check_expr(&mut self, expr: &mut Expr) -> Result<Type> {
         let expr_type = match &mut expr.kind {
-            ExprKind::Literal(literal) => self.check_literal(literal),
+            ExprKind::Literal(literal) => self.check_literal(literal, expr.span),
 
             ExprKind::Variable { path } => self.check_variable(path, expr.span),
 
@@ -192,14 +452,20 @@ impl TypeChecker {
                 self.check_unary_expr(op, inner, expr.span)
             }
 
-            ExprKind::Call { callee, args, .. } => {
-                self.check_call_expr(callee, args, expr.span)
+            ExprKind::Call { callee, args, safety } => {
+                self.check_call_expr(callee, args, safety, expr.span)
+            }
+
+            ExprKind::MethodCall { receiver, method, args } => {
+                self.check_method_call(receiver, method, args, expr.span)
             }
 
             ExprKind::If { condition, then_branch, else_branch } => {
                 self.check_if_expr(condition, then_branch, else_branch, expr.span)
             }
 
+            ExprKind::Match { expr: scrutinee, arms } => self.check_match_expr(scrutinee, arms, expr.span),
+
             ExprKind::Block(block) => {
                 self.check_block_expr(block)
             }
@@ -208,6 +474,66 @@ impl TypeChecker {
                 self.check_assign_expr(target, value, expr.span)
             }
 
+            ExprKind::Cast { expr: inner, target_type } => {
+                self.check_cast_expr(inner, target_type, expr.span)
+            }
+
+            ExprKind::Range { start, end, .. } => {
+                self.check_range_expr(start, end, expr.span)
+            }
+
+            ExprKind::For { pattern, iterable, body, .. } => {
+                self.check_for_expr(pattern, iterable, body, expr.span)
+            }
+
+            ExprKind::While { condition, body, .. } => {
+                let cond_type = self.check_expr(condition)?;
+                self.require_boolean(&cond_type, condition.span)?;
+                self.check_loop_body(body, expr.span, "A `while` loop body must not produce a value")
+            }
+
+            ExprKind::Loop { body, .. } => {
+                self.check_loop_body(body, expr.span, "A `loop` body must not produce a value")
+            }
+
+            ExprKind::Break { .. } | ExprKind::Continue { .. } => {
+                if self.loop_depth == 0 {
+                    let keyword = if matches!(expr.kind, ExprKind::Break { .. }) { "break" } else { "continue" };
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        expr.span,
+                        format!("`{}` outside of a loop", keyword),
+                    ));
+                }
+                Ok(Type::new(TypeKind::Primitive(PrimitiveType::Unit), expr.span))
+            }
+
+            ExprKind::Return { value } => self.check_return_expr(value.as_deref_mut(), expr.span),
+
+            ExprKind::Try { expr: inner } => self.check_try_expr(inner, expr.span),
+
+            ExprKind::Spawn { body } => self.check_spawn_expr(body, expr.span),
+
+            ExprKind::Unsafe { body } => {
+                self.unsafe_depth += 1;
+                let body_type = self.check_expr(body);
+                self.unsafe_depth -= 1;
+                body_type
+            }
+
+            ExprKind::Reference { expr: inner, mutable } => {
+                self.check_reference_expr(inner, *mutable, expr.span)
+            }
+
+            ExprKind::Dereference { expr: inner } => self.check_deref_expr(inner, expr.span),
+
+            ExprKind::FieldAccess { object, field } => self.check_field_access(object, field, expr.span),
+
+            ExprKind::Struct { path, fields, base } => self.check_struct_expr(path, fields, base.as_deref_mut(), expr.span),
+
+            // The parser already reported it; leave its type open.
+            ExprKind::Error => Ok(self.fresh_var(expr.span)),
+
             _ => {
                 return Err(TlError::type_error(
                     self.source.clone(),
@@ -224,10 +550,40 @@ impl TypeChecker {
     }
 
     /// Type check a literal.
-    fn check_literal(&self, literal: &Literal) -> Result<Type> {
+    ///
+    /// Numeric literals get an inference variable, so their type can come
+    /// from how they are used.
+    fn check_literal(&mut self, literal: &Literal, span: SourceSpan) -> Result<Type> {
         let type_kind = match literal {
-            Literal::Integer(_) => TypeKind::Primitive(PrimitiveType::I32), // Default to i32
-            Literal::Float(_) => TypeKind::Primitive(PrimitiveType::F64),   // Default to f64
+            Literal::Integer(value) => {
+                let ty = self.fresh_literal_var(LiteralVar::Integer, span);
+                if let TypeKind::Unknown(var) = ty.kind {
+                    self.int_literals.push((*value, var, span));
+                }
+                return Ok(ty);
+            }
+            Literal::Float(value) => {
+                let ty = self.fresh_literal_var(LiteralVar::Float, span);
+                if let TypeKind::Unknown(var) = ty.kind {
+                    self.float_literals.push((*value, var, span));
+                }
+                return Ok(ty);
+            }
+            // A suffix fixes the type, which the range checks still apply to.
+            Literal::TypedInteger(value, ty) => {
+                if let TypeKind::Unknown(var) = self.fresh_var(span).kind {
+                    self.substitutions.insert(var, Type::primitive(ty.clone(), span));
+                    self.int_literals.push((*value, var, span));
+                }
+                TypeKind::Primitive(ty.clone())
+            }
+            Literal::TypedFloat(value, ty) => {
+                if let TypeKind::Unknown(var) = self.fresh_var(span).kind {
+                    self.substitutions.insert(var, Type::primitive(ty.clone(), span));
+                    self.float_literals.push((*value, var, span));
+                }
+                TypeKind::Primitive(ty.clone())
+            }
             Literal::String(_) => TypeKind::Primitive(PrimitiveType::Str),
             Literal::Char(_) => TypeKind::Primitive(PrimitiveType::Char),
             Literal::Bool(_) => TypeKind::Primitive(PrimitiveType::Bool),
@@ -243,13 +599,24 @@ impl TypeChecker {
             let name = &path[0];
             if let Some(var_type) = self.variables.get(name) {
                 Ok(var_type.clone())
+            } else if let Some((static_type, mutable)) = self.statics.get(name) {
+                if *mutable {
+                    self.require_unsafe(span, &format!("Use of the mutable static `{}`", name))?;
+                }
+                Ok(static_type.clone())
             } else {
+                let names = self.variables.keys().chain(self.statics.keys());
                 Err(TlError::type_error(
                     self.source.clone(),
                     span,
                     format!("Undefined variable: {}", name),
-                ))
+                ).with_suggestions(similar_names(name, names.map(String::as_str))))
             }
+        } else if let [name, variant] = path
+            && let Some(TypeDefinition::Enum { variants }) = self.types.get(name)
+            && variants.get(variant).is_some_and(Vec::is_empty)
+        {
+            Ok(Type::new(TypeKind::Named { path: vec![name.clone()], generics: vec![] }, span))
         } else {
             Err(TlError::type_error(
                 self.source.clone(),
@@ -264,6 +631,15 @@ impl TypeChecker {
         let left_type = self.check_expr(left)?;
         let right_type = self.check_expr(right)?;
 
+        // `p + n` moves a raw pointer `n` elements along.
+        if matches!(op, BinaryOp::Add | BinaryOp::Sub)
+            && let TypeKind::Pointer { .. } = self.shallow_resolve(&left_type).kind
+        {
+            self.require_unsafe(span, "Pointer arithmetic")?;
+            self.require_integer(&right_type, right.span)?;
+            return Ok(left_type);
+        }
+
         match op {
             BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
                 // Arithmetic operations require numeric types
@@ -305,11 +681,17 @@ impl TypeChecker {
     }
 
     /// Type check a unary expression.
-    fn check_unary_expr(&mut self, op: &UnaryOp, expr: &mut Expr, span: SourceSpan) -> Result<Type> {
+    fn check_unary_expr(&mut self, op: &UnaryOp, expr: &mut Expr, _span: SourceSpan) -> Result<Type> {
         let expr_type = self.check_expr(expr)?;
 
         match op {
             UnaryOp::Neg => {
+                // `-128` is one literal as far as range checks go.
+                if let ExprKind::Literal(Literal::Integer(_) | Literal::TypedInteger(..)) = expr.kind
+                    && let Some(literal) = self.int_literals.last_mut()
+                {
+                    literal.0 = -literal.0;
+                }
                 self.require_numeric(&expr_type, expr.span)?;
                 Ok(expr_type)
             }
@@ -327,14 +709,40 @@ impl TypeChecker {
     }
 
     /// Type check a function call.
-    fn check_call_expr(&mut self, callee: &mut Expr, args: &mut [Expr], span: SourceSpan) -> Result<Type> {
+    fn check_call_expr(&mut self, callee: &mut Expr, args: &mut [Expr], safety: &mut shared::SafetyLevel,
+                       span: SourceSpan) -> Result<Type> {
         // For now, assume callee is a simple function name
         if let ExprKind::Variable { path } = &callee.kind {
-            if path.len() == 1 {
-                let func_name = &path[0];
+            let intrinsic = intrinsic_named(path);
+            if path.len() == 1 || intrinsic.is_some() {
+                if let Some(intrinsic) = intrinsic
+                    && let Some(capability) = intrinsic.capability
+                    && !self.capabilities.allows(Some(capability))
+                {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        span,
+                        format!("{} needs {}, which this profile does not allow",
+                                intrinsic.path(), capability.name()),
+                    ));
+                }
+                let func_name = &path.join("::");
                 if let Some(signature) = self.functions.get(func_name).cloned() {
+                    if signature.safety_level == shared::SafetyLevel::Unsafe {
+                        self.require_unsafe(span, &format!("Calling the extern function `{}`", func_name))?;
+                    }
+                    *safety = signature.safety_level;
+                    let signature = self.instantiate(&signature, span);
                     // Check argument count
-                    if args.len() != signature.params.len() {
+                    if signature.variadic && args.len() < signature.params.len() {
+                        return Err(TlError::type_error(
+                            self.source.clone(),
+                            span,
+                            format!("Function {} expects at least {} arguments, got {}",
+                                    func_name, signature.params.len(), args.len()),
+                        ));
+                    }
+                    if !signature.variadic && args.len() != signature.params.len() {
                         return Err(TlError::type_error(
                             self.source.clone(),
                             span,
@@ -344,20 +752,56 @@ impl TypeChecker {
                     }
 
                     // Check argument types
-                    for (i, (arg, expected_type)) in args.iter_mut().zip(signature.params.iter()).enumerate() {
+                    let (fixed, rest) = args.split_at_mut(signature.params.len());
+                    for (i, (arg, expected_type)) in fixed.iter_mut().zip(signature.params.iter()).enumerate() {
                         let arg_type = self.check_expr(arg)?;
-                        self.require_compatible(&arg_type, expected_type, arg.span,
+                        self.require_coercible(&arg_type, expected_type, arg.span,
                                                 &format!("Argument {} has wrong type", i + 1))?;
                     }
 
+                    // Variadic arguments go to C as they are, except those
+                    // of the prelude, which are only printed.
+                    let to_c = !PRINTING.contains(&func_name.as_str());
+                    for arg in rest {
+                        let arg_type = self.check_expr(arg)?;
+                        let arg_type = self.shallow_resolve(&arg_type);
+                        if to_c && !matches!(arg_type.kind, TypeKind::Unknown(_)) && ffi_type(&arg_type).is_none() {
+                            return Err(TlError::type_error(
+                                self.source.clone(),
+                                arg.span,
+                                format!("Type {} cannot be passed to C", type_name(&arg_type)),
+                            ));
+                        }
+                    }
+
                     Ok(signature.return_type)
+                } else if UNLOWERED_SYNCHRONIZATION.contains(&func_name.as_str()) {
+                    Err(TlError::type_error(
+                        self.source.clone(),
+                        callee.span,
+                        format!("`{}` is not supported yet: a spawned thread runs where it is spawned, so threads \
+                                 cannot share a mutex or a channel", func_name),
+                    ))
                 } else {
                     Err(TlError::type_error(
                         self.source.clone(),
-                        span,
+                        callee.span,
                         format!("Undefined function: {}", func_name),
-                    ))
+                    ).with_suggestions(similar_names(func_name, self.functions.keys().map(String::as_str))))
+                }
+            } else if let [type_name, function] = path.as_slice()
+                && function == "new"
+                && let Some((name, params)) = COLLECTIONS.iter().find(|(name, _)| name == type_name)
+            {
+                if !args.is_empty() {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        span,
+                        format!("Function {}::new expects 0 arguments, got {}", name, args.len()),
+                    ));
                 }
+                let generics = params.iter().map(|_| self.fresh_var(span)).collect();
+                Ok(Type::new(TypeKind::Named { path: vec![name.to_string()], generics }, span))
             } else {
                 Err(TlError::type_error(
                     self.source.clone(),
@@ -374,6 +818,182 @@ impl TypeChecker {
         }
     }
 
+    /// Type check a method call. Only the built-in collections have methods;
+    /// a method's signature is instantiated with the receiver's type
+    /// arguments.
+    fn check_method_call(&mut self, receiver: &mut Expr, method: &str, args: &mut [Expr], span: SourceSpan) -> Result<Type> {
+        let receiver_type = self.check_expr(receiver)?;
+        let resolved = self.shallow_resolve(&receiver_type);
+        let generic = match &resolved.kind {
+            TypeKind::Named { path, generics } if path.len() == 1 => COLLECTIONS
+                .iter()
+                .chain([&OPTION])
+                .find(|(name, _)| *name == path[0])
+                .map(|(name, params)| (*name, *params, generics)),
+            _ => None,
+        };
+        let (type_name, mut vars): (&str, HashMap<String, Type>) = match generic {
+            Some((type_name, params, generics)) => {
+                if generics.len() != params.len() {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        receiver.span,
+                        format!("{} takes {} type arguments, got {}", type_name, params.len(), generics.len()),
+                    ));
+                }
+                (type_name, params.iter().map(|param| param.to_string()).zip(generics.iter().cloned()).collect())
+            }
+            // Integer methods take and return the receiver's own type
+            None if self.is_integer(&resolved) => {
+                (INTEGER_METHODS, HashMap::from([("Self".to_string(), receiver_type.clone())]))
+            }
+            None => {
+                return Err(TlError::type_error(
+                    self.source.clone(),
+                    receiver.span,
+                    format!("`{}` has no methods", self.describe(&receiver_type)),
+                ));
+            }
+        };
+        let methods = &self.methods[type_name];
+        let Some(signature) = methods.get(method).cloned() else {
+            return Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("No method `{}` on `{}`", method, self.describe(&receiver_type)),
+            ).with_suggestions(similar_names(method, methods.keys().map(String::as_str))));
+        };
+
+        if args.len() != signature.params.len() {
+            return Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("Method {} expects {} arguments, got {}", method, signature.params.len(), args.len()),
+            ));
+        }
+        for (i, (arg, param)) in args.iter_mut().zip(&signature.params).enumerate() {
+            let expected_type = self.instantiate_type(param, &mut vars, span);
+            let arg_type = self.check_expr(arg)?;
+            self.require_coercible(&arg_type, &expected_type, arg.span, &format!("Argument {} has wrong type", i + 1))?;
+        }
+        Ok(self.instantiate_type(&signature.return_type, &mut vars, span))
+    }
+
+    /// Whether `ty` is an integer type or an integer literal whose type is
+    /// not known yet.
+    fn is_integer(&self, ty: &Type) -> bool {
+        match ty.kind {
+            TypeKind::Unknown(var) => self.literal_vars.get(&var) == Some(&LiteralVar::Integer),
+            _ => utils::is_integer_type(ty),
+        }
+    }
+
+    /// `signature` with each of its generic parameters replaced by a new
+    /// inference variable, so every call solves them afresh.
+    fn instantiate(&mut self, signature: &FunctionSignature, span: SourceSpan) -> FunctionSignature {
+        let mut vars = HashMap::new();
+        FunctionSignature {
+            params: signature.params.iter().map(|p| self.instantiate_type(p, &mut vars, span)).collect(),
+            return_type: self.instantiate_type(&signature.return_type, &mut vars, span),
+            ..signature.clone()
+        }
+    }
+
+    fn instantiate_type(&mut self, ty: &Type, vars: &mut HashMap<String, Type>, span: SourceSpan) -> Type {
+        let kind = match &ty.kind {
+            TypeKind::Generic { name, .. } => {
+                if !vars.contains_key(name) {
+                    let var = self.fresh_var(span);
+                    vars.insert(name.clone(), var);
+                }
+                return vars[name].clone();
+            }
+            TypeKind::Array { element, size } => TypeKind::Array {
+                element: Box::new(self.instantiate_type(element, vars, span)),
+                size: size.clone(),
+            },
+            TypeKind::Slice { element } => TypeKind::Slice { element: Box::new(self.instantiate_type(element, vars, span)) },
+            TypeKind::Reference { target, lifetime, mutable } => TypeKind::Reference {
+                target: Box::new(self.instantiate_type(target, vars, span)),
+                lifetime: lifetime.clone(),
+                mutable: *mutable,
+            },
+            TypeKind::Pointer { target, mutable } => TypeKind::Pointer {
+                target: Box::new(self.instantiate_type(target, vars, span)),
+                mutable: *mutable,
+            },
+            TypeKind::Function { params, return_type, safety } => TypeKind::Function {
+                params: params.iter().map(|p| self.instantiate_type(p, vars, span)).collect(),
+                return_type: Box::new(self.instantiate_type(return_type, vars, span)),
+                safety: *safety,
+            },
+            TypeKind::Tuple(elems) => {
+                TypeKind::Tuple(elems.iter().map(|e| self.instantiate_type(e, vars, span)).collect())
+            }
+            TypeKind::Named { path, generics } => TypeKind::Named {
+                path: path.clone(),
+                generics: generics.iter().map(|g| self.instantiate_type(g, vars, span)).collect(),
+            },
+            kind => kind.clone(),
+        };
+        Type::new(kind, ty.span)
+    }
+
+    /// Type check a `spawn` block. The body sees the enclosing variables,
+    /// and the spawn is a `Thread<T>` for the body's type `T`.
+    fn check_spawn_expr(&mut self, body: &mut Expr, span: SourceSpan) -> Result<Type> {
+        // A thread cannot `break` out of the loop it was spawned in, nor
+        // return from the function that spawned it.
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let returns = self.returns.take();
+        self.push_scope();
+        let body_type = self.check_expr(body);
+        self.pop_scope();
+        self.loop_depth = loop_depth;
+        self.returns = returns;
+        Ok(Type::new(TypeKind::Named { path: vec!["Thread".to_string()], generics: vec![body_type?] }, span))
+    }
+
+    /// Type check a `return`, whose value, `()` if it has none, must be
+    /// what the function returns. Control never goes on past it, so it
+    /// takes whatever type its place needs.
+    fn check_return_expr(&mut self, value: Option<&mut Expr>, span: SourceSpan) -> Result<Type> {
+        let Some(expected) = self.returns.clone() else {
+            return Err(TlError::type_error(self.source.clone(), span, "`return` outside of a function".to_string()));
+        };
+        if self.defer_depth > 0 {
+            return Err(TlError::type_error(self.source.clone(), span, "`return` in a `defer` block".to_string()));
+        }
+        let value_type = match value {
+            Some(value) => self.check_expr(value)?,
+            None => Type::new(TypeKind::Primitive(PrimitiveType::Unit), span),
+        };
+        self.require_coercible(&value_type, &expected, span, "Returned value type doesn't match return type")?;
+        Ok(self.fresh_var(span))
+    }
+
+    /// Type check `expr?`: an `Option<T>` in a function returning an
+    /// `Option` is the `T` it holds, and returns `None` if it holds nothing.
+    fn check_try_expr(&mut self, inner: &mut Expr, span: SourceSpan) -> Result<Type> {
+        let inner_type = self.check_expr(inner)?;
+        let returns_option = self.returns.as_ref().is_some_and(|returns| {
+            matches!(self.shallow_resolve(returns).kind, TypeKind::Named { path, .. } if path == [OPTION.0])
+        });
+        match self.shallow_resolve(&inner_type).kind {
+            TypeKind::Named { path, generics } if path == [OPTION.0] && generics.len() == 1 && returns_option => {
+                Ok(generics[0].clone())
+            }
+            _ => Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!(
+                    "`?` needs an `Option` in a function returning an `Option`, found `{}`",
+                    self.describe(&inner_type)
+                ),
+            )),
+        }
+    }
+
     /// Type check an if expression.
     fn check_if_expr(&mut self, condition: &mut Expr, then_branch: &mut Expr,
                      else_branch: &mut Option<Box<Expr>>, span: SourceSpan) -> Result<Type> {
@@ -396,8 +1016,85 @@ impl TypeChecker {
         }
     }
 
+    /// Type check a `match`: each arm's pattern against the value matched,
+    /// its guard as a condition, and its body, in the scope of what the
+    /// pattern binds. The arms must have compatible types, which is the
+    /// type of the `match`; one without arms has none.
+    fn check_match_expr(&mut self, scrutinee: &mut Expr, arms: &mut [MatchArm], span: SourceSpan) -> Result<Type> {
+        let scrutinee_type = self.check_expr(scrutinee)?;
+        let mut match_type = None;
+        for arm in arms {
+            self.push_scope();
+            let arm_type = self.check_arm_pattern(&arm.pattern, &scrutinee_type).and_then(|()| {
+                if let Some(guard) = &mut arm.guard {
+                    let guard_type = self.check_expr(guard)?;
+                    self.require_boolean(&guard_type, guard.span)?;
+                }
+                self.check_expr(&mut arm.body)
+            });
+            self.pop_scope();
+            let arm_type = arm_type?;
+            match &match_type {
+                Some(ty) => {
+                    self.require_compatible(&arm_type, ty, arm.body.span, "Match arms must have compatible types")?
+                }
+                None => match_type = Some(arm_type),
+            }
+        }
+        Ok(match_type.unwrap_or_else(|| Type::new(TypeKind::Primitive(PrimitiveType::Unit), span)))
+    }
+
+    /// Check that `pattern` of a `match` arm can match a value of type `ty`,
+    /// binding the names in it: a literal, `Color::Red` or
+    /// `Shape::Circle(r)` of an enum, or either of `a | b`, besides what a
+    /// `let` can take apart.
+    fn check_arm_pattern(&mut self, pattern: &shared::Pattern, ty: &Type) -> Result<()> {
+        match &pattern.kind {
+            PatternKind::Literal(literal) => {
+                let literal_type = self.check_literal(literal, pattern.span)?;
+                self.require_compatible(&literal_type, ty, pattern.span, "Pattern doesn't match the value's type")
+            }
+            PatternKind::Or(alternatives) => {
+                alternatives.iter().try_for_each(|alternative| self.check_arm_pattern(alternative, ty))
+            }
+            PatternKind::Enum { path, variant, fields } if !path.is_empty() => {
+                let name = path.join("::");
+                let Some(TypeDefinition::Enum { variants }) = self.types.get(&name) else {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        pattern.span,
+                        format!("`{}` is not an enum", name),
+                    ));
+                };
+                let Some(field_types) = variants.get(variant).cloned() else {
+                    let names = variants.keys().map(String::as_str);
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        pattern.span,
+                        format!("Enum {} has no variant {}", name, variant),
+                    ).with_suggestions(similar_names(variant, names)));
+                };
+                let enum_type = Type::new(TypeKind::Named { path: path.clone(), generics: vec![] }, pattern.span);
+                self.require_compatible(ty, &enum_type, pattern.span, "Pattern doesn't match the value's type")?;
+                if fields.len() != field_types.len() {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        pattern.span,
+                        format!("Variant {}::{} has {} fields, but the pattern has {}",
+                                name, variant, field_types.len(), fields.len()),
+                    ));
+                }
+                for (field, field_type) in fields.iter().zip(&field_types) {
+                    self.check_arm_pattern(field, field_type)?;
+                }
+                Ok(())
+            }
+            _ => self.bind_pattern(pattern, ty, false),
+        }
+    }
+
     /// Type check a block expression.
-    fn check_block_expr(&mut self, block: &mut shared::Block) -> Result<Type> {
+    fn check_block_expr(&mut self, block: &mut shared::ast::Block) -> Result<Type> {
         self.push_scope();
 
         // Check all statements
@@ -405,9 +1102,14 @@ impl TypeChecker {
             self.check_stmt(stmt)?;
         }
 
-        // Check final expression
+        // Check final expression; a block ending in `return ...;` has no
+        // value control reaches.
         let block_type = if let Some(expr) = &mut block.expr {
             self.check_expr(expr)?
+        } else if let Some(StmtKind::Expr(Expr { kind: ExprKind::Return { .. }, .. })) =
+            block.statements.last().map(|stmt| &stmt.kind)
+        {
+            self.fresh_var(block.span)
         } else {
             Type::new(TypeKind::Primitive(PrimitiveType::Unit), block.span)
         };
@@ -420,26 +1122,296 @@ impl TypeChecker {
     fn check_assign_expr(&mut self, target: &mut Expr, value: &mut Expr, span: SourceSpan) -> Result<Type> {
         let value_type = self.check_expr(value)?;
 
-        // For now, only support simple variable assignment
-        if let ExprKind::Variable { path } = &target.kind {
-            if path.len() == 1 {
+        match &target.kind {
+            ExprKind::Variable { path } if path.len() == 1 => {
                 let var_name = &path[0];
-                if let Some(target_type) = self.variables.get(var_name) {
-                    self.require_compatible(&value_type, target_type, span,
+                if let Some(target_type) = self.variables.get(var_name).cloned() {
+                    self.require_coercible(&value_type, &target_type, span,
                                             "Assignment value type doesn't match variable type")?;
+                } else if self.statics.contains_key(var_name) {
+                    let target_type = self.check_expr(target)?;
+                    self.require_mutable_place(target, |place| format!("assign to `{}`", place))?;
+                    self.require_coercible(&value_type, &target_type, span,
+                                            "Assignment value type doesn't match the type of the static")?;
                 } else {
                     return Err(TlError::type_error(
                         self.source.clone(),
-                        span,
+                        target.span,
                         format!("Undefined variable in assignment: {}", var_name),
-                    ));
+                    ).with_suggestions(similar_names(var_name, self.variables.keys().map(String::as_str))));
                 }
             }
+            // Through a reference, or into a field.
+            ExprKind::Dereference { .. } | ExprKind::FieldAccess { .. } => {
+                let target_type = self.check_expr(target)?;
+                self.require_mutable_place(target, |place| format!("assign to `{}`", place))?;
+                self.require_coercible(&value_type, &target_type, span,
+                                        "Assignment value type doesn't match the type of the place")?;
+            }
+            _ => {}
         }
 
         Ok(Type::new(TypeKind::Primitive(PrimitiveType::Unit), span))
     }
 
+    /// Type check `&place` or `&mut place`, a reference to the place's type.
+    fn check_reference_expr(&mut self, inner: &mut Expr, mutable: bool, span: SourceSpan) -> Result<Type> {
+        let target = self.check_expr(inner)?;
+        if mutable {
+            self.require_mutable_place(inner, |place| format!("borrow `{}` as mutable", place))?;
+        }
+        Ok(Type::new(TypeKind::Reference { target: Box::new(target), lifetime: None, mutable }, span))
+    }
+
+    /// Type check `*expr`, the value a reference or pointer refers to.
+    fn check_deref_expr(&mut self, inner: &mut Expr, span: SourceSpan) -> Result<Type> {
+        let inner_type = self.check_expr(inner)?;
+        match self.shallow_resolve(&inner_type).kind {
+            TypeKind::Reference { target, .. } => Ok(*target),
+            TypeKind::Pointer { target, .. } => {
+                self.require_unsafe(span, "Dereferencing a raw pointer")?;
+                Ok(*target)
+            }
+            _ => Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("Cannot dereference a value of type `{}`", self.describe(&inner_type)),
+            )),
+        }
+    }
+
+    /// Type check `object.field`. References are followed to what they
+    /// refer to, so `p.x` of a `p: &Point` is the `x` of that `Point`.
+    fn check_field_access(&mut self, object: &mut Expr, field: &str, span: SourceSpan) -> Result<Type> {
+        let object_type = self.check_expr(object)?;
+        let mut object_type = self.shallow_resolve(&object_type);
+        while let TypeKind::Reference { target, .. } = object_type.kind {
+            object_type = self.shallow_resolve(&target);
+        }
+        let fields = match &object_type.kind {
+            TypeKind::Named { path, .. } => match path.last().and_then(|name| self.types.get(name)) {
+                Some(TypeDefinition::Struct { fields }) => Some(fields),
+                _ => None,
+            },
+            _ => None,
+        };
+        match fields.map(|fields| (fields.get(field), fields)) {
+            Some((Some(field_type), _)) => Ok(field_type.clone()),
+            Some((None, fields)) => Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("No field `{}` on type `{}`", field, self.describe(&object_type)),
+            ).with_suggestions(similar_names(field, fields.keys().map(String::as_str)))),
+            None => Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("Type `{}` has no fields", self.describe(&object_type)),
+            )),
+        }
+    }
+
+    /// Type check a struct literal `Name { field: value, .. }`. Each field
+    /// of the struct is given once, unless `..base` gives the rest; a field
+    /// written alone, as in `Point { x }`, takes the variable of its name.
+    fn check_struct_expr(&mut self, path: &[String], fields: &mut [FieldInit], base: Option<&mut Expr>,
+                         span: SourceSpan) -> Result<Type> {
+        let name = path.join("::");
+        let struct_type = Type::new(TypeKind::Named { path: path.to_vec(), generics: vec![] }, span);
+        let Some(TypeDefinition::Struct { fields: declared }) = path.last().and_then(|last| self.types.get(last)).cloned()
+        else {
+            return Err(TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("`{}` is not a struct with named fields", name),
+            ));
+        };
+        let mut given = HashSet::new();
+        for field in fields.iter_mut() {
+            let Some(field_type) = declared.get(&field.name) else {
+                return Err(TlError::type_error(
+                    self.source.clone(),
+                    field.span,
+                    format!("No field `{}` on type `{}`", field.name, name),
+                ).with_suggestions(similar_names(&field.name, declared.keys().map(String::as_str))));
+            };
+            if !given.insert(field.name.clone()) {
+                return Err(TlError::type_error(
+                    self.source.clone(),
+                    field.span,
+                    format!("Field `{}` is given more than once", field.name),
+                ));
+            }
+            let value_type = match &mut field.value {
+                Some(value) => self.check_expr(value)?,
+                None => self.check_variable(std::slice::from_ref(&field.name), field.span)?,
+            };
+            self.require_coercible(&value_type, field_type, field.span,
+                                   &format!("Field `{}` has wrong type", field.name))?;
+        }
+        match base {
+            Some(base) => {
+                let base_type = self.check_expr(base)?;
+                self.require_coercible(&base_type, &struct_type, base.span, "The base of a struct literal has wrong type")?;
+            }
+            None => {
+                let mut missing: Vec<&str> = declared.keys().map(String::as_str).filter(|f| !given.contains(*f)).collect();
+                missing.sort_unstable();
+                if !missing.is_empty() {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        span,
+                        format!("Missing fields of `{}`: {}", name, missing.join(", ")),
+                    ));
+                }
+            }
+        }
+        Ok(struct_type)
+    }
+
+    /// Check that `place` may be changed: it is a variable declared
+    /// `let mut`, or is reached through a `&mut`.
+    /// `action` says what was attempted with the place's text.
+    fn require_mutable_place(&self, place: &Expr, action: impl Fn(&str) -> String) -> Result<()> {
+        match self.immutability(place) {
+            Some(reason) => Err(TlError::type_error(
+                self.source.clone(),
+                place.span,
+                format!("Cannot {}: {}", action(self.text(place.span)), reason),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Why `place` may not be changed, if it may not.
+    fn immutability(&self, place: &Expr) -> Option<String> {
+        let shared = |reference: &Expr| {
+            let pointer = matches!(reference.ty.as_ref().map(|ty| self.shallow_resolve(ty).kind),
+                                   Some(TypeKind::Pointer { .. }));
+            let (kind, mutable) = if pointer { ("a `*const` pointer", "`*mut`") } else { ("a `&` reference", "`&mut`") };
+            format!("`{}` is {}, not {}", self.text(reference.span), kind, mutable)
+        };
+        match &place.kind {
+            ExprKind::Variable { path } if path.len() == 1 && !self.mutable.contains(&path[0]) => {
+                match self.statics.get(&path[0]) {
+                    Some((_, true)) if !self.variables.contains_key(&path[0]) => None,
+                    Some(_) if !self.variables.contains_key(&path[0]) => {
+                        Some(format!("`{}` is a `static`, not `static mut`", path[0]))
+                    }
+                    _ => Some(format!("`{}` is not declared `let mut`", path[0])),
+                }
+            }
+            ExprKind::Dereference { expr: reference } => match self.reference_mutability(reference) {
+                Some(false) => Some(shared(reference)),
+                _ => None,
+            },
+            ExprKind::FieldAccess { object, .. } => match self.reference_mutability(object) {
+                Some(mutable) => (!mutable).then(|| shared(object)),
+                None => self.immutability(object),
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether `expr`, if it is a reference or pointer, may change what it
+    /// refers to.
+    fn reference_mutability(&self, expr: &Expr) -> Option<bool> {
+        match self.shallow_resolve(expr.ty.as_ref()?).kind {
+            TypeKind::Reference { mutable, .. } | TypeKind::Pointer { mutable, .. } => Some(mutable),
+            _ => None,
+        }
+    }
+
+    /// The source text at `span`.
+    fn text(&self, span: SourceSpan) -> &str {
+        self.source.get(span.offset()..span.offset() + span.len()).unwrap_or("_")
+    }
+
+    /// Type check a range. Both bounds must be integers of one type `T`,
+    /// and the range is a `Range<T>`.
+    fn check_range_expr(&mut self, start: &mut Option<Box<Expr>>, end: &mut Option<Box<Expr>>,
+                        span: SourceSpan) -> Result<Type> {
+        let element = self.fresh_var(span);
+        for bound in [start, end].into_iter().flatten() {
+            let bound_type = self.check_expr(bound)?;
+            self.require_integer(&bound_type, bound.span)?;
+            self.require_compatible(&bound_type, &element, bound.span,
+                                    "Range bounds must have the same type")?;
+        }
+        Ok(Type::new(TypeKind::Named { path: vec!["Range".to_string()], generics: vec![element] }, span))
+    }
+
+    /// The type of the items a `for` loop over a value of type `ty` binds.
+    ///
+    /// This is the iterator protocol: a `Range<T>`, `Vec<T>` or `HashSet<T>`
+    /// yields `T`, and an array or slice, or a reference to one, yields its
+    /// elements.
+    fn iterator_item(&self, ty: &Type) -> Option<Type> {
+        let ty = self.shallow_resolve(ty);
+        match ty.kind {
+            TypeKind::Named { path, mut generics }
+                if (path == ["Range"] || path == ["Vec"] || path == ["HashSet"]) && generics.len() == 1 => generics.pop(),
+            TypeKind::Array { element, .. } | TypeKind::Slice { element } => Some(*element),
+            TypeKind::Reference { target, .. } => self.iterator_item(&target),
+            _ => None,
+        }
+    }
+
+    /// Type check a `for` loop. The pattern is bound to the iterable's item
+    /// type inside the body, and the loop itself is `()`.
+    fn check_for_expr(&mut self, pattern: &shared::Pattern, iterable: &mut Expr, body: &mut Expr,
+                      span: SourceSpan) -> Result<Type> {
+        let iterable_type = self.check_expr(iterable)?;
+        let Some(item_type) = self.iterator_item(&iterable_type) else {
+            return Err(TlError::type_error(
+                self.source.clone(),
+                iterable.span,
+                format!("`{}` is not iterable: expected a range, array or slice", self.describe(&iterable_type)),
+            ));
+        };
+
+        self.push_scope();
+        self.bind_pattern(pattern, &item_type, false)?;
+        self.loop_depth += 1;
+        let body_type = self.check_expr(body);
+        self.loop_depth -= 1;
+        self.pop_scope();
+
+        let unit = Type::new(TypeKind::Primitive(PrimitiveType::Unit), span);
+        self.require_compatible(&body_type?, &unit, body.span, "A `for` loop body must not produce a value")?;
+        Ok(unit)
+    }
+
+    /// Type check the body of a `while` or `loop`, which is `()` like the
+    /// loop itself.
+    fn check_loop_body(&mut self, body: &mut Expr, span: SourceSpan, message: &str) -> Result<Type> {
+        self.push_scope();
+        self.loop_depth += 1;
+        let body_type = self.check_expr(body);
+        self.loop_depth -= 1;
+        self.pop_scope();
+
+        let unit = Type::new(TypeKind::Primitive(PrimitiveType::Unit), span);
+        self.require_compatible(&body_type?, &unit, body.span, message)?;
+        Ok(unit)
+    }
+
+    /// Type check an `as` cast. Whether the cast is allowed is decided in
+    /// `solve_constraints`, once the operand's type is known.
+    fn check_cast_expr(&mut self, expr: &mut Expr, target_type: &Type, span: SourceSpan) -> Result<Type> {
+        let expr_type = self.check_expr(expr)?;
+
+        // An unsuffixed literal takes the target type when it can, so
+        // `300 as u8` is reported as out of range rather than truncated.
+        if let TypeKind::Unknown(var) = self.shallow_resolve(&expr_type).kind
+            && self.literal_vars.contains_key(&var)
+        {
+            self.unify(&expr_type, target_type);
+        }
+
+        self.casts.push((expr_type, target_type.clone(), span));
+        Ok(target_type.clone())
+    }
+
     /// Type check a statement.
     fn check_stmt(&mut self, stmt: &mut Stmt) -> Result<()> {
         match &mut stmt.kind {
@@ -447,29 +1419,34 @@ impl TypeChecker {
                 self.check_expr(expr)?;
             }
 
-            StmtKind::Let { pattern, ty, initializer, .. } => {
-                if let PatternKind::Ident(name) = &pattern.kind {
-                    let var_type = if let Some(declared_type) = ty {
-                        declared_type.clone()
-                    } else if let Some(init_expr) = initializer {
-                        self.check_expr(init_expr)?
-                    } else {
-                        return Err(TlError::type_error(
-                            self.source.clone(),
-                            stmt.span,
-                            "Let binding must have either type annotation or initializer".to_string(),
-                        ));
-                    };
+            StmtKind::Defer { body } => {
+                self.defer_depth += 1;
+                let checked = self.check_expr(body);
+                self.defer_depth -= 1;
+                checked?;
+            }
 
-                    // If both type and initializer are present, check compatibility
-                    if let (Some(declared_type), Some(init_expr)) = (ty, initializer) {
+            StmtKind::Let { pattern, ty, initializer, mutable } => {
+                let var_type = match (ty.as_ref(), initializer.as_mut()) {
+                    (Some(declared_type), Some(init_expr)) => {
                         let init_type = self.check_expr(init_expr)?;
-                        self.require_compatible(&init_type, declared_type, init_expr.span,
+                        self.require_coercible(&init_type, declared_type, init_expr.span,
                                                 "Initializer type doesn't match declared type")?;
+                        declared_type.clone()
+                    }
+                    (Some(declared_type), None) => declared_type.clone(),
+                    (None, Some(init_expr)) => self.check_expr(init_expr)?,
+                    // Solved by later assignments.
+                    (None, None) => {
+                        let var_type = self.fresh_var(pattern.span);
+                        if let TypeKind::Unknown(var) = var_type.kind {
+                            self.unannotated.push((var, stmt.span));
+                        }
+                        var_type
                     }
+                };
 
-                    self.variables.insert(name.clone(), var_type);
-                }
+                self.bind_pattern(pattern, &var_type, *mutable)?;
             }
 
             _ => {
@@ -484,96 +1461,558 @@ impl TypeChecker {
         Ok(())
     }
 
+    /// Bind the names in `pattern` to the parts of a value of type `ty`
+    /// they match; `Name(a, b)` takes apart a tuple struct.
+    fn bind_pattern(&mut self, pattern: &shared::Pattern, ty: &Type, mutable: bool) -> Result<()> {
+        match &pattern.kind {
+            PatternKind::Ident(name) => {
+                self.variables.insert(name.clone(), ty.clone());
+                if mutable {
+                    self.mutable.insert(name.clone());
+                } else {
+                    self.mutable.remove(name);
+                }
+                Ok(())
+            }
+            PatternKind::Wild => Ok(()),
+            PatternKind::Enum { path, variant, fields } if path.is_empty() => {
+                let Some(TypeDefinition::TupleStruct { fields: field_types }) = self.types.get(variant).cloned() else {
+                    let tuple_structs = self.types.iter()
+                        .filter(|(_, definition)| matches!(definition, TypeDefinition::TupleStruct { .. }))
+                        .map(|(name, _)| name.as_str());
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        pattern.span,
+                        format!("`{}` is not a tuple struct", variant),
+                    ).with_suggestions(similar_names(variant, tuple_structs)));
+                };
+                let struct_type = Type::new(TypeKind::Named { path: vec![variant.clone()], generics: vec![] }, pattern.span);
+                self.require_compatible(ty, &struct_type, pattern.span, "Pattern doesn't match the value's type")?;
+                if fields.len() != field_types.len() {
+                    return Err(TlError::type_error(
+                        self.source.clone(),
+                        pattern.span,
+                        format!("Tuple struct {} has {} fields, but the pattern has {}",
+                                variant, field_types.len(), fields.len()),
+                    ));
+                }
+                for (field, field_type) in fields.iter().zip(&field_types) {
+                    self.bind_pattern(field, field_type, mutable)?;
+                }
+                Ok(())
+            }
+            _ => Err(TlError::type_error(
+                self.source.clone(),
+                pattern.span,
+                "Only names, `_` and tuple structs can be bound here".to_string(),
+            )),
+        }
+    }
+
     // Type checking helper methods
 
     fn require_compatible(&mut self, actual: &Type, expected: &Type, span: SourceSpan, message: &str) -> Result<()> {
-        if !self.types_compatible(actual, expected) {
+        if self.unify(actual, expected) {
+            Ok(())
+        } else {
             Err(TlError::type_error(
                 self.source.clone(),
                 span,
-                format!("{}: expected {:?}, found {:?}", message, expected.kind, actual.kind),
+                format!("{}: expected `{}`, found `{}`", message, self.describe(expected), self.describe(actual)),
             ))
-        } else {
-            Ok(())
         }
     }
 
+    /// Like `require_compatible`, but also accepts the implicit numeric
+    /// coercions that keep every value, such as `i32` to `i64`.
+    fn require_coercible(&mut self, actual: &Type, expected: &Type, span: SourceSpan, message: &str) -> Result<()> {
+        let (from, to) = (self.resolve_type(actual), self.resolve_type(expected));
+        // A `&mut T` does everything a `&T` does.
+        if let (TypeKind::Reference { target: from, mutable: true, .. },
+                TypeKind::Reference { target: to, mutable: false, .. }) = (&from.kind, &to.kind)
+        {
+            return self.require_compatible(from, to, span, message);
+        }
+        if let (TypeKind::Primitive(_), TypeKind::Primitive(_)) = (&from.kind, &to.kind)
+            && self.coercion.try_coerce(&from, &to, span)
+                .is_ok_and(|c| c.kind == CoercionKind::Numeric && c.is_safe)
+        {
+            return Ok(());
+        }
+        self.require_compatible(actual, expected, span, message)
+    }
+
+    /// Fail unless in an `unsafe` block, since `operation` is unsafe.
+    fn require_unsafe(&self, span: SourceSpan, operation: &str) -> Result<()> {
+        if self.unsafe_depth > 0 {
+            return Ok(());
+        }
+        Err(TlError::type_error(
+            self.source.clone(),
+            span,
+            format!("{} is unsafe and needs an `unsafe` block", operation),
+        ))
+    }
+
     fn require_numeric(&self, ty: &Type, span: SourceSpan) -> Result<()> {
-        match &ty.kind {
-            TypeKind::Primitive(prim) => match prim {
-                PrimitiveType::I8 | PrimitiveType::I16 | PrimitiveType::I32 | PrimitiveType::I64 | PrimitiveType::I128 | PrimitiveType::ISize |
-                PrimitiveType::U8 | PrimitiveType::U16 | PrimitiveType::U32 | PrimitiveType::U64 | PrimitiveType::U128 | PrimitiveType::USize |
-                PrimitiveType::F32 | PrimitiveType::F64 => Ok(()),
-                _ => Err(TlError::type_error(
-                    self.source.clone(),
-                    span,
-                    format!("Expected numeric type, found {:?}", ty.kind),
-                )),
-            },
-            _ => Err(TlError::type_error(
-                self.source.clone(),
-                span,
-                format!("Expected numeric type, found {:?}", ty.kind),
-            )),
+        let ty = self.shallow_resolve(ty);
+        // An unsolved variable may still become a number.
+        if matches!(ty.kind, TypeKind::Unknown(_)) || utils::is_numeric_type(&ty) {
+            return Ok(());
         }
+        Err(TlError::type_error(
+            self.source.clone(),
+            span,
+            format!("Expected numeric type, found `{}`", self.describe(&ty)),
+        ))
     }
 
-    fn require_boolean(&self, ty: &Type, span: SourceSpan) -> Result<()> {
-        match &ty.kind {
-            TypeKind::Primitive(PrimitiveType::Bool) => Ok(()),
-            _ => Err(TlError::type_error(
-                self.source.clone(),
-                span,
-                format!("Expected boolean type, found {:?}", ty.kind),
-            )),
+    fn require_boolean(&mut self, ty: &Type, span: SourceSpan) -> Result<()> {
+        let bool_type = Type::new(TypeKind::Primitive(PrimitiveType::Bool), span);
+        if self.unify(ty, &bool_type) {
+            return Ok(());
         }
+        Err(TlError::type_error(
+            self.source.clone(),
+            span,
+            format!("Expected boolean type, found `{}`", self.describe(ty)),
+        ))
     }
 
     fn require_integer(&self, ty: &Type, span: SourceSpan) -> Result<()> {
-        match &ty.kind {
-            TypeKind::Primitive(prim) => match prim {
-                PrimitiveType::I8 | PrimitiveType::I16 | PrimitiveType::I32 | PrimitiveType::I64 | PrimitiveType::I128 | PrimitiveType::ISize |
-                PrimitiveType::U8 | PrimitiveType::U16 | PrimitiveType::U32 | PrimitiveType::U64 | PrimitiveType::U128 | PrimitiveType::USize => Ok(()),
-                _ => Err(TlError::type_error(
-                    self.source.clone(),
-                    span,
-                    format!("Expected integer type, found {:?}", ty.kind),
-                )),
+        let ty = self.shallow_resolve(ty);
+        let possible = match ty.kind {
+            TypeKind::Unknown(var) => self.literal_vars.get(&var) != Some(&LiteralVar::Float),
+            _ => utils::is_integer_type(&ty),
+        };
+        if possible {
+            return Ok(());
+        }
+        Err(TlError::type_error(
+            self.source.clone(),
+            span,
+            format!("Expected integer type, found `{}`", self.describe(&ty)),
+        ))
+    }
+
+    /// A new inference variable.
+    fn fresh_var(&mut self, span: SourceSpan) -> Type {
+        let var = self.next_type_var;
+        self.next_type_var += 1;
+        Type::new(TypeKind::Unknown(var), span)
+    }
+
+    /// A new inference variable for the type of a numeric literal.
+    fn fresh_literal_var(&mut self, kind: LiteralVar, span: SourceSpan) -> Type {
+        let ty = self.fresh_var(span);
+        if let TypeKind::Unknown(var) = ty.kind {
+            self.literal_vars.insert(var, kind);
+        }
+        ty
+    }
+
+    /// Follow solved variables to a concrete type or an unsolved variable.
+    fn shallow_resolve(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let TypeKind::Unknown(var) = ty.kind {
+            match self.substitutions.get(&var) {
+                Some(solution) => ty = solution.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// `ty` with every solved variable replaced by its solution.
+    pub fn resolve_type(&self, ty: &Type) -> Type {
+        let ty = self.shallow_resolve(ty);
+        let resolve = |t: &Type| Box::new(self.resolve_type(t));
+        let kind = match ty.kind {
+            TypeKind::Array { element, size } => TypeKind::Array { element: resolve(&element), size },
+            TypeKind::Slice { element } => TypeKind::Slice { element: resolve(&element) },
+            TypeKind::Reference { target, lifetime, mutable } => {
+                TypeKind::Reference { target: resolve(&target), lifetime, mutable }
+            }
+            TypeKind::Pointer { target, mutable } => TypeKind::Pointer { target: resolve(&target), mutable },
+            TypeKind::Function { params, return_type, safety } => TypeKind::Function {
+                params: params.iter().map(|p| self.resolve_type(p)).collect(),
+                return_type: resolve(&return_type),
+                safety,
             },
-            _ => Err(TlError::type_error(
-                self.source.clone(),
-                span,
-                format!("Expected integer type, found {:?}", ty.kind),
-            )),
+            TypeKind::Tuple(elems) => TypeKind::Tuple(elems.iter().map(|e| self.resolve_type(e)).collect()),
+            TypeKind::Named { path, generics } => TypeKind::Named {
+                path,
+                generics: generics.iter().map(|g| self.resolve_type(g)).collect(),
+            },
+            kind => kind,
+        };
+        Type::new(kind, ty.span)
+    }
+
+    /// How a type reads in diagnostics; unsolved literal variables show
+    /// what they may still become.
+    fn describe(&self, ty: &Type) -> String {
+        let ty = self.resolve_type(ty);
+        match ty.kind {
+            TypeKind::Unknown(var) => match self.literal_vars.get(&var) {
+                Some(LiteralVar::Integer) => "{integer}".to_string(),
+                Some(LiteralVar::Float) => "{float}".to_string(),
+                None => "_".to_string(),
+            },
+            _ => type_name(&ty),
         }
     }
 
-    fn types_compatible(&self, a: &Type, b: &Type) -> bool {
-        // For now, require exact type equality
-        // TODO: Implement proper type compatibility rules (subtyping, coercion, etc.)
-        a.kind == b.kind
+    /// Whether `ty` contains the variable `var`.
+    fn occurs(&self, var: u32, ty: &Type) -> bool {
+        let ty = self.shallow_resolve(ty);
+        match &ty.kind {
+            TypeKind::Unknown(other) => *other == var,
+            TypeKind::Array { element, .. } | TypeKind::Slice { element } => self.occurs(var, element),
+            TypeKind::Reference { target, .. } | TypeKind::Pointer { target, .. } => self.occurs(var, target),
+            TypeKind::Function { params, return_type, .. } => {
+                params.iter().any(|p| self.occurs(var, p)) || self.occurs(var, return_type)
+            }
+            TypeKind::Tuple(elems) => elems.iter().any(|e| self.occurs(var, e)),
+            TypeKind::Named { generics, .. } => generics.iter().any(|g| self.occurs(var, g)),
+            _ => false,
+        }
+    }
+
+    /// Solve `var` as `ty`, if a literal variable allows it.
+    fn bind(&mut self, var: u32, ty: &Type) -> bool {
+        let allowed = match self.literal_vars.get(&var) {
+            Some(LiteralVar::Integer) => utils::is_integer_type(ty),
+            Some(LiteralVar::Float) => utils::is_float_type(ty),
+            None => true,
+        };
+        if !allowed || self.occurs(var, ty) {
+            return false;
+        }
+        self.substitutions.insert(var, ty.clone());
+        true
+    }
+
+    /// Make `a` and `b` the same type, solving variables on either side.
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.shallow_resolve(a), self.shallow_resolve(b));
+        match (&a.kind, &b.kind) {
+            (TypeKind::Unknown(x), TypeKind::Unknown(y)) if x == y => true,
+            (TypeKind::Unknown(x), TypeKind::Unknown(y)) => {
+                match (self.literal_vars.get(x).copied(), self.literal_vars.get(y).copied()) {
+                    (Some(xk), Some(yk)) if xk != yk => return false,
+                    // The surviving variable keeps the literal's restriction.
+                    (Some(xk), None) => {
+                        self.literal_vars.insert(*y, xk);
+                    }
+                    _ => {}
+                }
+                self.substitutions.insert(*x, b.clone());
+                true
+            }
+            (TypeKind::Unknown(var), _) => self.bind(*var, &b),
+            (_, TypeKind::Unknown(var)) => self.bind(*var, &a),
+            (TypeKind::Array { element: ae, size: asz }, TypeKind::Array { element: be, size: bsz }) => {
+                asz == bsz && self.unify(ae, be)
+            }
+            (TypeKind::Slice { element: ae }, TypeKind::Slice { element: be }) => self.unify(ae, be),
+            (TypeKind::Reference { target: at, mutable: am, .. },
+                TypeKind::Reference { target: bt, mutable: bm, .. })
+            | (TypeKind::Pointer { target: at, mutable: am },
+                TypeKind::Pointer { target: bt, mutable: bm }) => am == bm && self.unify(at, bt),
+            (TypeKind::Function { params: ap, return_type: ar, .. },
+                TypeKind::Function { params: bp, return_type: br, .. }) => {
+                ap.len() == bp.len()
+                    && ap.iter().zip(bp).all(|(x, y)| self.unify(x, y))
+                    && self.unify(ar, br)
+            }
+            (TypeKind::Tuple(ae), TypeKind::Tuple(be)) => {
+                ae.len() == be.len() && ae.iter().zip(be).all(|(x, y)| self.unify(x, y))
+            }
+            (TypeKind::Named { path: ap, generics: ag }, TypeKind::Named { path: bp, generics: bg }) => {
+                ap == bp && ag.len() == bg.len() && ag.iter().zip(bg).all(|(x, y)| self.unify(x, y))
+            }
+            _ => a.kind == b.kind,
+        }
     }
 
     fn push_scope(&mut self) {
-        // TODO: Implement proper scope stack
-        // For now, we'll use a simple approach
+        self.scopes.push((self.variables.clone(), self.mutable.clone()));
     }
 
     fn pop_scope(&mut self) {
-        // TODO: Implement proper scope stack
+        if let Some((variables, mutable)) = self.scopes.pop() {
+            self.variables = variables;
+            self.mutable = mutable;
+        }
     }
 
-    fn solve_constraints(&mut self) -> Result<()> {
-        // TODO: Implement constraint solver for type inference
+    /// Default the literal variables nothing constrained (integers to `i32`,
+    /// floats to `f64`), reject bindings whose type is still unknown, and
+    /// check every integer literal fits the type it ended up with.
+    pub fn solve_constraints(&mut self) -> Result<()> {
+        let mut literal_vars: Vec<(u32, LiteralVar)> =
+            self.literal_vars.iter().map(|(var, kind)| (*var, *kind)).collect();
+        literal_vars.sort();
+        for (var, kind) in literal_vars {
+            let span = SourceSpan::new(0.into(), 0);
+            if let TypeKind::Unknown(root) = self.shallow_resolve(&Type::new(TypeKind::Unknown(var), span)).kind {
+                let default = match kind {
+                    LiteralVar::Integer => PrimitiveType::I32,
+                    LiteralVar::Float => PrimitiveType::F64,
+                };
+                self.substitutions.insert(root, Type::primitive(default, span));
+            }
+        }
+
+        for &(var, span) in &self.unannotated {
+            if let TypeKind::Unknown(_) = self.shallow_resolve(&Type::new(TypeKind::Unknown(var), span)).kind {
+                return Err(TlError::type_error(
+                    self.source.clone(),
+                    span,
+                    "Type annotations needed: the type of this binding cannot be inferred".to_string(),
+                ));
+            }
+        }
+
+        for &(value, var, span) in &self.int_literals {
+            let ty = self.resolve_type(&Type::new(TypeKind::Unknown(var), span));
+            let (Some(bits), signed) = (utils::type_size_bits(&ty), utils::is_signed_type(&ty)) else {
+                continue;
+            };
+            let (min, max) = match (signed, bits) {
+                (true, 128) => (i128::MIN, i128::MAX),
+                (true, bits) => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+                (false, 128) => (0, i128::MAX),
+                (false, bits) => (0, (1i128 << bits) - 1),
+            };
+            if value < min || value > max {
+                return Err(TlError::type_error(
+                    self.source.clone(),
+                    span,
+                    format!("Integer literal {} is out of range for `{}` ({}..={})", value, type_name(&ty), min, max),
+                ));
+            }
+        }
+
+        for &(value, var, span) in &self.float_literals {
+            let ty = self.resolve_type(&Type::new(TypeKind::Unknown(var), span));
+            if matches!(ty.kind, TypeKind::Primitive(PrimitiveType::F32)) && (value as f32).is_infinite() {
+                return Err(TlError::type_error(
+                    self.source.clone(),
+                    span,
+                    format!("Float literal {} is out of range for `f32`", value),
+                ));
+            }
+        }
+
+        for (from, to, span) in std::mem::take(&mut self.casts) {
+            let (from, to) = (self.resolve_type(&from), self.resolve_type(&to));
+            if let CastKind::Lossy(message) = self.coercion.check_cast(&from, &to, span)? {
+                self.warnings.push(TypeWarning { span, message });
+            }
+        }
         Ok(())
     }
 
+    /// Replace inference variables in the types recorded on `expr` and the
+    /// expressions inside it with their solutions.
+    pub fn apply_solutions(&self, expr: &mut Expr) {
+        if let Some(ty) = &expr.ty {
+            expr.ty = Some(self.resolve_type(ty));
+        }
+        match &mut expr.kind {
+            ExprKind::Binary { left, right, .. } | ExprKind::Assign { target: left, value: right, .. } => {
+                self.apply_solutions(left);
+                self.apply_solutions(right);
+            }
+            ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Reference { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner }
+            | ExprKind::FieldAccess { object: inner, .. }
+            | ExprKind::Try { expr: inner }
+            | ExprKind::Return { value: Some(inner) } => self.apply_solutions(inner),
+            ExprKind::Range { start, end, .. } => {
+                for bound in [start, end].into_iter().flatten() {
+                    self.apply_solutions(bound);
+                }
+            }
+            ExprKind::For { iterable, body, .. } => {
+                self.apply_solutions(iterable);
+                self.apply_solutions(body);
+            }
+            ExprKind::While { condition, body, .. } => {
+                self.apply_solutions(condition);
+                self.apply_solutions(body);
+            }
+            ExprKind::Loop { body, .. } => self.apply_solutions(body),
+            ExprKind::Spawn { body } | ExprKind::Unsafe { body } => self.apply_solutions(body),
+            ExprKind::Struct { fields, base, .. } => {
+                for value in fields.iter_mut().filter_map(|field| field.value.as_mut()) {
+                    self.apply_solutions(value);
+                }
+                if let Some(base) = base {
+                    self.apply_solutions(base);
+                }
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.apply_solutions(callee);
+                args.iter_mut().for_each(|arg| self.apply_solutions(arg));
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.apply_solutions(receiver);
+                args.iter_mut().for_each(|arg| self.apply_solutions(arg));
+            }
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.apply_solutions(condition);
+                self.apply_solutions(then_branch);
+                if let Some(else_expr) = else_branch {
+                    self.apply_solutions(else_expr);
+                }
+            }
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.apply_solutions(scrutinee);
+                for arm in arms {
+                    if let Some(guard) = &mut arm.guard {
+                        self.apply_solutions(guard);
+                    }
+                    self.apply_solutions(&mut arm.body);
+                }
+            }
+            ExprKind::Block(block) => {
+                for stmt in &mut block.statements {
+                    match &mut stmt.kind {
+                        StmtKind::Expr(e) | StmtKind::Let { initializer: Some(e), .. } | StmtKind::Defer { body: e } => {
+                            self.apply_solutions(e)
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(e) = &mut block.expr {
+                    self.apply_solutions(e);
+                }
+            }
+            _ => {}
+        }
+    }
+
     fn add_builtin_functions(&mut self) {
-        // Add built-in functions like print
-        self.functions.insert("print".to_string(), FunctionSignature {
-            params: vec![Type::new(TypeKind::Primitive(PrimitiveType::Str), SourceSpan::new(0.into(), 0))],
-            return_type: Type::new(TypeKind::Primitive(PrimitiveType::Unit), SourceSpan::new(0.into(), 0)),
+        let span = SourceSpan::new(0.into(), 0);
+        let t = Type::new(TypeKind::Generic { name: "T".to_string(), bounds: Vec::new() }, span);
+        let unit = Type::new(TypeKind::Primitive(PrimitiveType::Unit), span);
+        let str_type = Type::new(TypeKind::Primitive(PrimitiveType::Str), span);
+        let bool_type = Type::new(TypeKind::Primitive(PrimitiveType::Bool), span);
+
+        // The prelude, in scope in every program: printing any number of
+        // values of any types to stdout or stderr, `assert`, `panic`, and
+        // `format`, whose arguments fill the template's `{}`s in order.
+        let prelude = [
+            ("print", vec![], unit.clone(), true),
+            ("println", vec![], unit.clone(), true),
+            ("eprint", vec![], unit.clone(), true),
+            ("eprintln", vec![], unit.clone(), true),
+            ("assert", vec![bool_type], unit.clone(), false),
+            ("panic", vec![str_type.clone()], unit.clone(), false),
+            ("format", vec![str_type.clone()], str_type, true),
+        ];
+        for (name, params, return_type, variadic) in prelude {
+            self.functions.insert(name.to_string(), FunctionSignature {
+                params,
+                return_type,
+                safety_level: shared::SafetyLevel::Safe,
+                variadic,
+            });
+        }
+
+        // `join` waits for a thread's result
+        let thread = Type::new(TypeKind::Named { path: vec!["Thread".to_string()], generics: vec![t.clone()] }, span);
+        self.functions.insert("join".to_string(), FunctionSignature {
+            params: vec![thread],
+            return_type: t,
             safety_level: shared::SafetyLevel::Safe,
+            variadic: false,
         });
+
+        // `io::read_file` and the other calls out to the host
+        for intrinsic in INTRINSICS {
+            self.functions.insert(intrinsic.path(), FunctionSignature {
+                params: intrinsic.params.iter().map(|prim| Type::primitive(prim.clone(), span)).collect(),
+                return_type: Type::primitive(intrinsic.return_type.clone(), span),
+                safety_level: shared::SafetyLevel::Safe,
+                variadic: false,
+            });
+        }
+    }
+
+    /// The methods of `Vec<T>`, `HashMap<K, V>` and `HashSet<T>`, of
+    /// `Option<T>`, and of every integer type. Lookups that find nothing,
+    /// such as `get` past the end or `unwrap` of `None`, fail when the
+    /// program is lowered.
+    fn add_builtin_collections(&mut self) {
+        let span = SourceSpan::new(0.into(), 0);
+        let param = |name: &str| Type::new(TypeKind::Generic { name: name.to_string(), bounds: Vec::new() }, span);
+        let (t, k, v, own) = (param("T"), param("K"), param("V"), param("Self"));
+        let option = |ty: &Type| Type::new(TypeKind::Named { path: vec![OPTION.0.to_string()], generics: vec![ty.clone()] }, span);
+        let primitive = |prim| Type::new(TypeKind::Primitive(prim), span);
+        let (unit, boolean, usize) = (primitive(PrimitiveType::Unit), primitive(PrimitiveType::Bool), primitive(PrimitiveType::USize));
+        let collections = [
+            ("Vec", vec![
+                ("push", vec![t.clone()], unit.clone()),
+                ("pop", vec![], t.clone()),
+                ("get", vec![usize.clone()], t.clone()),
+                ("set", vec![usize.clone(), t.clone()], unit.clone()),
+                ("contains", vec![t.clone()], boolean.clone()),
+                ("len", vec![], usize.clone()),
+                ("is_empty", vec![], boolean.clone()),
+            ]),
+            ("HashMap", vec![
+                ("insert", vec![k.clone(), v.clone()], unit.clone()),
+                ("get", vec![k.clone()], v.clone()),
+                ("remove", vec![k.clone()], unit.clone()),
+                ("contains_key", vec![k.clone()], boolean.clone()),
+                ("len", vec![], usize.clone()),
+                ("is_empty", vec![], boolean.clone()),
+            ]),
+            ("HashSet", vec![
+                ("insert", vec![t.clone()], unit.clone()),
+                ("remove", vec![t.clone()], unit.clone()),
+                ("contains", vec![t.clone()], boolean.clone()),
+                ("len", vec![], usize.clone()),
+                ("is_empty", vec![], boolean.clone()),
+            ]),
+            (OPTION.0, vec![
+                ("is_some", vec![], boolean.clone()),
+                ("is_none", vec![], boolean.clone()),
+                ("unwrap", vec![], t.clone()),
+                ("unwrap_or", vec![t.clone()], t.clone()),
+                ("expect", vec![primitive(PrimitiveType::Str)], t.clone()),
+            ]),
+            // Arithmetic that cannot overflow: `checked_` returns `None`
+            // where the result does not fit, `wrapping_` wraps around and
+            // `saturating_` stops at the type's bounds.
+            (INTEGER_METHODS, vec![
+                ("checked_add", vec![own.clone()], option(&own)),
+                ("checked_sub", vec![own.clone()], option(&own)),
+                ("checked_mul", vec![own.clone()], option(&own)),
+                ("wrapping_add", vec![own.clone()], own.clone()),
+                ("wrapping_sub", vec![own.clone()], own.clone()),
+                ("wrapping_mul", vec![own.clone()], own.clone()),
+                ("saturating_add", vec![own.clone()], own.clone()),
+                ("saturating_sub", vec![own.clone()], own.clone()),
+                ("saturating_mul", vec![own.clone()], own.clone()),
+            ]),
+        ];
+        for (type_name, methods) in collections {
+            let methods = methods.into_iter().map(|(name, params, return_type)| {
+                (name.to_string(), FunctionSignature {
+                    params,
+                    return_type,
+                    safety_level: shared::SafetyLevel::Safe,
+                    variadic: false,
+                })
+            });
+            self.methods.insert(type_name.to_string(), methods.collect());
+        }
     }
 }
\ No newline at end of file
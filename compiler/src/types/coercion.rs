@@ -4,7 +4,10 @@
 //! Handles automatic type conversions and subtyping relationships.
 //! Designed for safety-critical systems with explicit coercion rules.
 
-use shared::{Type, TypeKind, PrimitiveType, Result, TlError};
+use shared::ast::PrimitiveType;
+use shared::{Type, TypeKind, Result, TlError};
+use super::utils;
+use crate::resolve::type_name;
 use miette::SourceSpan;
 
 /// Type coercion engine for automatic type conversions.
@@ -36,6 +39,15 @@ pub enum CoercionKind {
     Custom(String),
 }
 
+/// How an allowed explicit `as` cast treats its operand.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CastKind {
+    /// Every value of the source type survives the cast.
+    Lossless,
+    /// Some values change; the message says how.
+    Lossy(String),
+}
+
 /// Result of a coercion attempt.
 #[derive(Debug, Clone)]
 pub struct CoercionResult {
@@ -132,6 +144,75 @@ impl CoercionRules {
         ))
     }
 
+    /// Check an explicit `from as to` cast.
+    ///
+    /// Casts are allowed between numeric types, from `bool` and `char` to
+    /// integers, and from `u8` to `char`. Anything else is an error.
+    pub fn check_cast(&self, from: &Type, to: &Type, span: SourceSpan) -> Result<CastKind> {
+        use PrimitiveType::*;
+
+        if self.types_identical(from, to) {
+            return Ok(CastKind::Lossless);
+        }
+        let invalid = || {
+            TlError::type_error(
+                self.source.clone(),
+                span,
+                format!("Cannot cast `{}` as `{}`", type_name(from), type_name(to)),
+            )
+        };
+        // Taking a raw pointer is safe; dereferencing it is not.
+        if let TypeKind::Pointer { target: to_target, .. } = &to.kind {
+            let pointer = match &from.kind {
+                TypeKind::Reference { .. } => self.try_reference_coercion(from, to)?.is_some(),
+                TypeKind::Pointer { target, .. } => self.types_identical(target, to_target),
+                _ => false,
+            };
+            return if pointer { Ok(CastKind::Lossless) } else { Err(invalid()) };
+        }
+        let (TypeKind::Primitive(from_prim), TypeKind::Primitive(to_prim)) = (&from.kind, &to.kind) else {
+            return Err(invalid());
+        };
+
+        let bits = |ty: &Type| utils::type_size_bits(ty).unwrap_or(0);
+        let (from_bits, to_bits) = (bits(from), bits(to));
+        let (int, float) = (utils::is_integer_type, utils::is_float_type);
+        let loss = if int(from) && int(to) {
+            match (utils::is_signed_type(from), utils::is_signed_type(to)) {
+                _ if to_bits < from_bits => Some("may truncate the value"),
+                (true, false) => Some("may change the sign of the value"),
+                (false, true) if to_bits == from_bits => Some("may change the sign of the value"),
+                _ => None,
+            }
+        } else if int(from) && float(to) {
+            // Magnitudes wider than the mantissa are rounded.
+            let mantissa = if *to_prim == F32 { 24 } else { 53 };
+            let magnitude = from_bits - utils::is_signed_type(from) as u32;
+            (magnitude > mantissa).then_some("may round the value")
+        } else if float(from) && int(to) {
+            Some("truncates toward zero and saturates at the bounds of the target")
+        } else if float(from) && float(to) {
+            (to_bits < from_bits).then_some("may lose precision")
+        } else {
+            match (from_prim, to_prim) {
+                (Bool, _) if int(to) => None,
+                (Char, _) if int(to) => (to_bits < 32).then_some("may truncate the value"),
+                (U8, Char) => None,
+                _ => return Err(invalid()),
+            }
+        };
+
+        Ok(match loss {
+            None => CastKind::Lossless,
+            Some(loss) => CastKind::Lossy(format!(
+                "Cast from `{}` to `{}` {}",
+                type_name(from),
+                type_name(to),
+                loss,
+            )),
+        })
+    }
+
     /// Find the best common type for a set of types.
     pub fn find_common_type(&self, types: &[Type], span: SourceSpan) -> Result<Type> {
         if types.is_empty() {
@@ -168,8 +249,8 @@ impl CoercionRules {
         match (&from.kind, &to.kind) {
             // &mut T to &T (mutable reference to immutable reference)
             (TypeKind::Reference { target: from_target, mutable: true, .. },
-                TypeKind::Reference { target: to_target, mutable: false, .. }) => {
-                if self.types_identical(from_target, to_target) {
+                TypeKind::Reference { target: to_target, mutable: false, .. })
+                if self.types_identical(from_target, to_target) => {
                     return Ok(Some(CoercionResult {
                         kind: CoercionKind::Subtyping,
                         target_type: to.clone(),
@@ -177,7 +258,6 @@ impl CoercionRules {
                         cost: CoercionCost::Free,
                     }));
                 }
-            }
 
             // TODO: Add other subtyping rules (trait objects, lifetimes, etc.)
             _ => {}
@@ -187,9 +267,9 @@ impl CoercionRules {
     }
 
     /// Try numeric coercion.
-    fn try_numeric_coercion(&self, from: &Type, to: &Type, span: SourceSpan) -> Result<Option<CoercionResult>> {
-        if let (TypeKind::Primitive(from_prim), TypeKind::Primitive(to_prim)) = (&from.kind, &to.kind) {
-            if let Some((kind, cost, is_safe)) = self.numeric_conversion_info(from_prim, to_prim) {
+    fn try_numeric_coercion(&self, from: &Type, to: &Type, _span: SourceSpan) -> Result<Option<CoercionResult>> {
+        if let (TypeKind::Primitive(from_prim), TypeKind::Primitive(to_prim)) = (&from.kind, &to.kind)
+            && let Some((kind, cost, is_safe)) = self.numeric_conversion_info(from_prim, to_prim) {
                 return Ok(Some(CoercionResult {
                     kind,
                     target_type: to.clone(),
@@ -197,7 +277,6 @@ impl CoercionRules {
                     cost,
                 }));
             }
-        }
 
         Ok(None)
     }
@@ -254,8 +333,8 @@ impl CoercionRules {
         match (&from.kind, &to.kind) {
             // &T to *const T
             (TypeKind::Reference { target: from_target, mutable: false, .. },
-                TypeKind::Pointer { target: to_target, mutable: false }) => {
-                if self.types_identical(from_target, to_target) {
+                TypeKind::Pointer { target: to_target, mutable: false })
+                if self.types_identical(from_target, to_target) => {
                     return Ok(Some(CoercionResult {
                         kind: CoercionKind::Reference,
                         target_type: to.clone(),
@@ -263,12 +342,11 @@ impl CoercionRules {
                         cost: CoercionCost::High,
                     }));
                 }
-            }
 
             // &mut T to *mut T
             (TypeKind::Reference { target: from_target, mutable: true, .. },
-                TypeKind::Pointer { target: to_target, mutable: true }) => {
-                if self.types_identical(from_target, to_target) {
+                TypeKind::Pointer { target: to_target, mutable: true })
+                if self.types_identical(from_target, to_target) => {
                     return Ok(Some(CoercionResult {
                         kind: CoercionKind::Reference,
                         target_type: to.clone(),
@@ -276,12 +354,11 @@ impl CoercionRules {
                         cost: CoercionCost::High,
                     }));
                 }
-            }
 
             // &mut T to *const T
             (TypeKind::Reference { target: from_target, mutable: true, .. },
-                TypeKind::Pointer { target: to_target, mutable: false }) => {
-                if self.types_identical(from_target, to_target) {
+                TypeKind::Pointer { target: to_target, mutable: false })
+                if self.types_identical(from_target, to_target) => {
                     return Ok(Some(CoercionResult {
                         kind: CoercionKind::Reference,
                         target_type: to.clone(),
@@ -289,7 +366,6 @@ impl CoercionRules {
                         cost: CoercionCost::High,
                     }));
                 }
-            }
 
             _ => {}
         }
@@ -302,8 +378,8 @@ impl CoercionRules {
         match (&from.kind, &to.kind) {
             // [T; N] to [T]
             (TypeKind::Array { element: from_elem, .. },
-                TypeKind::Slice { element: to_elem }) => {
-                if self.types_identical(from_elem, to_elem) {
+                TypeKind::Slice { element: to_elem })
+                if self.types_identical(from_elem, to_elem) => {
                     return Ok(Some(CoercionResult {
                         kind: CoercionKind::ArrayToSlice,
                         target_type: to.clone(),
@@ -311,7 +387,6 @@ impl CoercionRules {
                         cost: CoercionCost::Free,
                     }));
                 }
-            }
 
             _ => {}
         }
@@ -324,10 +399,10 @@ impl CoercionRules {
         match (&from.kind, &to.kind) {
             // Function types with identical signatures
             (TypeKind::Function { params: from_params, return_type: from_ret, .. },
-                TypeKind::Function { params: to_params, return_type: to_ret, .. }) => {
+                TypeKind::Function { params: to_params, return_type: to_ret, .. })
                 if from_params.len() == to_params.len() &&
                     self.types_identical(from_ret, to_ret) &&
-                    from_params.iter().zip(to_params.iter()).all(|(a, b)| self.types_identical(a, b)) {
+                    from_params.iter().zip(to_params.iter()).all(|(a, b)| self.types_identical(a, b)) => {
                     return Ok(Some(CoercionResult {
                         kind: CoercionKind::FunctionItem,
                         target_type: to.clone(),
@@ -335,7 +410,6 @@ impl CoercionRules {
                         cost: CoercionCost::Free,
                     }));
                 }
-            }
 
             _ => {}
         }
@@ -371,15 +445,14 @@ impl CoercionRules {
 
             // References with different mutability - prefer immutable
             (TypeKind::Reference { target: target_a, mutable: mut_a, lifetime: life_a },
-                TypeKind::Reference { target: target_b, mutable: mut_b, lifetime: life_b }) => {
-                if self.types_identical(target_a, target_b) && life_a == life_b {
+                TypeKind::Reference { target: target_b, mutable: mut_b, lifetime: life_b })
+                if self.types_identical(target_a, target_b) && life_a == life_b => {
                     return Ok(Type::new(TypeKind::Reference {
                         target: target_a.clone(),
                         mutable: *mut_a && *mut_b, // Both must be mutable for result to be mutable
                         lifetime: life_a.clone(),
                     }, span));
                 }
-            }
 
             _ => {}
         }
@@ -398,7 +471,7 @@ impl CoercionRules {
 
         // If types are the same, return that type
         if a == b {
-            return Some(*a);
+            return Some(a.clone());
         }
 
         // Define a hierarchy for numeric types
@@ -407,53 +480,50 @@ impl CoercionRules {
         let float_hierarchy = [F32, F64];
 
         // Find positions in hierarchies
-        let a_int_pos = int_hierarchy.iter().position(|&t| t == *a);
-        let b_int_pos = int_hierarchy.iter().position(|&t| t == *b);
-        let a_uint_pos = uint_hierarchy.iter().position(|&t| t == *a);
-        let b_uint_pos = uint_hierarchy.iter().position(|&t| t == *b);
-        let a_float_pos = float_hierarchy.iter().position(|&t| t == *a);
-        let b_float_pos = float_hierarchy.iter().position(|&t| t == *b);
+        let a_int_pos = int_hierarchy.iter().position(|t| t == a);
+        let b_int_pos = int_hierarchy.iter().position(|t| t == b);
+        let a_uint_pos = uint_hierarchy.iter().position(|t| t == a);
+        let b_uint_pos = uint_hierarchy.iter().position(|t| t == b);
+        let a_float_pos = float_hierarchy.iter().position(|t| t == a);
+        let b_float_pos = float_hierarchy.iter().position(|t| t == b);
 
         // Both are signed integers
         if let (Some(pos_a), Some(pos_b)) = (a_int_pos, b_int_pos) {
-            return Some(int_hierarchy[pos_a.max(pos_b)]);
+            return Some(int_hierarchy[pos_a.max(pos_b)].clone());
         }
 
         // Both are unsigned integers
         if let (Some(pos_a), Some(pos_b)) = (a_uint_pos, b_uint_pos) {
-            return Some(uint_hierarchy[pos_a.max(pos_b)]);
+            return Some(uint_hierarchy[pos_a.max(pos_b)].clone());
         }
 
         // Both are floats
         if let (Some(pos_a), Some(pos_b)) = (a_float_pos, b_float_pos) {
-            return Some(float_hierarchy[pos_a.max(pos_b)]);
+            return Some(float_hierarchy[pos_a.max(pos_b)].clone());
         }
 
         // Mixed integer types - prefer signed if possible
         if a_int_pos.is_some() && b_uint_pos.is_some() {
             // Try to find a signed type that can represent the unsigned type
-            if let Some(uint_pos) = b_uint_pos {
-                if uint_pos < int_hierarchy.len() - 1 {
-                    return Some(int_hierarchy[uint_pos + 1]);
+            if let Some(uint_pos) = b_uint_pos
+                && uint_pos < int_hierarchy.len() - 1 {
+                    return Some(int_hierarchy[uint_pos + 1].clone());
                 }
-            }
         }
 
-        if a_uint_pos.is_some() && b_int_pos.is_some() {
-            if let Some(uint_pos) = a_uint_pos {
-                if uint_pos < int_hierarchy.len() - 1 {
-                    return Some(int_hierarchy[uint_pos + 1]);
+        if a_uint_pos.is_some() && b_int_pos.is_some()
+            && let Some(uint_pos) = a_uint_pos
+                && uint_pos < int_hierarchy.len() - 1 {
+                    return Some(int_hierarchy[uint_pos + 1].clone());
                 }
-            }
-        }
 
         // Integer to float - use appropriate float type
         if (a_int_pos.is_some() || a_uint_pos.is_some()) && b_float_pos.is_some() {
-            return Some(*b);
+            return Some(b.clone());
         }
 
         if (b_int_pos.is_some() || b_uint_pos.is_some()) && a_float_pos.is_some() {
-            return Some(*a);
+            return Some(a.clone());
         }
 
         // No common type found
@@ -478,7 +548,6 @@ impl CoercionResult {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use shared::types::*;
 
     fn i32_type() -> Type {
         Type::new(TypeKind::Primitive(PrimitiveType::I32), SourceSpan::new(0.into(), 0))
@@ -536,4 +605,26 @@ mod tests {
         // f64 to i32 should not work (lossy)
         assert!(!rules.can_coerce(&f64_t, &i32_t));
     }
+
+    #[test]
+    fn test_cast_rules() {
+        let rules = CoercionRules::new("test".to_string());
+        let span = SourceSpan::new(0.into(), 0);
+        let prim = |p| Type::new(TypeKind::Primitive(p), span);
+
+        assert_eq!(rules.check_cast(&i32_type(), &i64_type(), span).unwrap(), CastKind::Lossless);
+        assert_eq!(rules.check_cast(&i32_type(), &f64_type(), span).unwrap(), CastKind::Lossless);
+        assert_eq!(rules.check_cast(&prim(PrimitiveType::Bool), &i32_type(), span).unwrap(), CastKind::Lossless);
+        assert_eq!(rules.check_cast(&prim(PrimitiveType::U8), &prim(PrimitiveType::Char), span).unwrap(), CastKind::Lossless);
+
+        let CastKind::Lossy(message) = rules.check_cast(&i64_type(), &prim(PrimitiveType::U8), span).unwrap() else {
+            panic!("i64 as u8 should be lossy");
+        };
+        assert_eq!(message, "Cast from `i64` to `u8` may truncate the value");
+        assert!(matches!(rules.check_cast(&i32_type(), &prim(PrimitiveType::U32), span), Ok(CastKind::Lossy(_))));
+        assert!(matches!(rules.check_cast(&f64_type(), &i32_type(), span), Ok(CastKind::Lossy(_))));
+
+        assert!(rules.check_cast(&prim(PrimitiveType::Str), &i32_type(), span).is_err());
+        assert!(rules.check_cast(&i32_type(), &prim(PrimitiveType::Char), span).is_err());
+    }
 }
\ No newline at end of file
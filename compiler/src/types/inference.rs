@@ -4,9 +4,11 @@
 //! Implements Hindley-Milner style type inference with extensions for safety analysis.
 //! Designed to handle complex type relationships while maintaining safety guarantees.
 
-use shared::{Type, TypeKind, PrimitiveType, Result, TlError};
+use shared::ast::PrimitiveType;
+use shared::{Type, TypeKind, Result, TlError};
+use errors::suggest::similar_names;
 use miette::SourceSpan;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 /// Type inference context and engine.
 pub struct TypeInferer {
@@ -198,6 +200,9 @@ impl TypeInferer {
                 let var = self.fresh_var();
                 return Ok(self.var_type(var, span));
             }
+            shared::Literal::TypedInteger(_, ty) | shared::Literal::TypedFloat(_, ty) => {
+                TypeKind::Primitive(ty.clone())
+            }
             shared::Literal::String(_) => TypeKind::Primitive(PrimitiveType::Str),
             shared::Literal::Char(_) => TypeKind::Primitive(PrimitiveType::Char),
             shared::Literal::Bool(_) => TypeKind::Primitive(PrimitiveType::Bool),
@@ -218,7 +223,7 @@ impl TypeInferer {
                     self.source.clone(),
                     span,
                     format!("Undefined variable: {}", name),
-                ))
+                ).with_suggestions(similar_names(name, context.variables.keys().map(String::as_str))))
             }
         } else {
             // Module path - not yet implemented
@@ -241,7 +246,7 @@ impl TypeInferer {
                     left_type.clone(),
                     right_type.clone(),
                     span,
-                    ConstraintReason::BinaryOperation(*op),
+                    ConstraintReason::BinaryOperation(op.clone()),
                 );
 
                 // Result type is the same as operand types
@@ -254,7 +259,7 @@ impl TypeInferer {
                     left_type,
                     right_type,
                     span,
-                    ConstraintReason::BinaryOperation(*op),
+                    ConstraintReason::BinaryOperation(op.clone()),
                 );
 
                 Ok(Type::new(TypeKind::Primitive(PrimitiveType::Bool), span))
@@ -266,7 +271,7 @@ impl TypeInferer {
                     left_type,
                     right_type,
                     span,
-                    ConstraintReason::BinaryOperation(*op),
+                    ConstraintReason::BinaryOperation(op.clone()),
                 );
 
                 Ok(Type::new(TypeKind::Primitive(PrimitiveType::Bool), span))
@@ -280,14 +285,14 @@ impl TypeInferer {
                     left_type,
                     bool_type.clone(),
                     span,
-                    ConstraintReason::BinaryOperation(*op),
+                    ConstraintReason::BinaryOperation(op.clone()),
                 );
 
                 self.add_constraint(
                     right_type,
                     bool_type.clone(),
                     span,
-                    ConstraintReason::BinaryOperation(*op),
+                    ConstraintReason::BinaryOperation(op.clone()),
                 );
 
                 Ok(bool_type)
@@ -320,7 +325,7 @@ impl TypeInferer {
                     expr_type,
                     bool_type.clone(),
                     span,
-                    ConstraintReason::UnaryOperation(*op),
+                    ConstraintReason::UnaryOperation(op.clone()),
                 );
 
                 Ok(bool_type)
@@ -406,7 +411,7 @@ impl TypeInferer {
     }
 
     /// Infer the type of a block expression.
-    fn infer_block(&mut self, block: &mut shared::Block, context: &mut InferenceContext) -> Result<Type> {
+    fn infer_block(&mut self, block: &mut shared::ast::Block, context: &mut InferenceContext) -> Result<Type> {
         // Create new scope
         let saved_vars = context.variables.clone();
 
@@ -456,7 +461,7 @@ impl TypeInferer {
             Ok(Type::new(
                 TypeKind::Array {
                     element: Box::new(elem_type),
-                    size: shared::types::ArraySize::Inferred,
+                    size: shared::ast::types::ArraySize::Inferred,
                 },
                 span,
             ))
@@ -470,7 +475,7 @@ impl TypeInferer {
                 return Ok(Type::new(
                     TypeKind::Array {
                         element: Box::new(elem_type),
-                        size: shared::types::ArraySize::Literal(0),
+                        size: shared::ast::types::ArraySize::Literal(0),
                     },
                     span,
                 ));
@@ -493,7 +498,7 @@ impl TypeInferer {
             Ok(Type::new(
                 TypeKind::Array {
                     element: Box::new(first_type),
-                    size: shared::types::ArraySize::Literal(elements.len() as u64),
+                    size: shared::ast::types::ArraySize::Literal(elements.len() as u64),
                 },
                 span,
             ))
@@ -513,7 +518,7 @@ impl TypeInferer {
     /// Infer types for a statement.
     fn infer_stmt(&mut self, stmt: &mut shared::Stmt, context: &mut InferenceContext) -> Result<()> {
         match &mut stmt.kind {
-            shared::StmtKind::Expr(expr) => {
+            shared::StmtKind::Expr(expr) | shared::StmtKind::Defer { body: expr } => {
                 self.infer_expr(expr, context)?;
             }
 
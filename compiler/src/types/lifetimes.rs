@@ -0,0 +1,293 @@
+// compiler/src/types/lifetimes.rs
+//! Scope-based checking of references.
+//!
+//! A reference borrows the variable its place starts from, and must not
+//! outlive it. A variable lives until the end of the block declaring it,
+//! and a parameter until its function returns. So a reference to a variable
+//! may not be the value of the block declaring it, nor be stored in a
+//! variable declared outside that block, and a function may not return a
+//! reference to one of its locals or by-value parameters. A reference
+//! reached through a reference parameter borrows from the caller and may
+//! be returned. A call returning a reference borrows from its arguments, as
+//! long as the shortest-lived of them.
+
+use miette::SourceSpan;
+use shared::ast::Block;
+use shared::{Expr, ExprKind, ItemKind, PatternKind, Program, Result, StmtKind, TlError, TypeKind};
+use std::collections::HashMap;
+
+/// Check the references of every function of `program`.
+pub fn check_lifetimes(program: &Program, source: &str) -> Result<()> {
+    for item in &program.items {
+        let ItemKind::Function { name, params, body: Some(body), .. } = &item.kind else { continue };
+        let mut borrows = Borrows { source, function: name, scopes: vec![HashMap::new()] };
+        for param in params {
+            if let PatternKind::Ident(param_name) = &param.pattern.kind {
+                borrows.declare(param_name, None);
+            }
+        }
+        if let Some(loan) = borrows.value(body)? {
+            return Err(borrows.returned(&loan));
+        }
+    }
+    Ok(())
+}
+
+/// A borrow of the variable `name`, declared `depth` blocks in.
+#[derive(Debug, Clone)]
+struct Loan {
+    name: String,
+    depth: usize,
+    span: SourceSpan,
+}
+
+/// A variable in scope: how many blocks in it was declared, and what its
+/// value borrows, if it is a reference to a variable of the function.
+struct Variable {
+    depth: usize,
+    loan: Option<Loan>,
+}
+
+/// Follows what the values of one function borrow.
+struct Borrows<'a> {
+    source: &'a str,
+    function: &'a str,
+    /// The variables of the blocks being followed; the parameters first.
+    scopes: Vec<HashMap<String, Variable>>,
+}
+
+impl Borrows<'_> {
+    fn declare(&mut self, name: &str, loan: Option<Loan>) {
+        let depth = self.scopes.len() - 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Variable { depth, loan });
+        }
+    }
+
+    fn variable(&mut self, name: &str) -> Option<&mut Variable> {
+        self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name))
+    }
+
+    /// Check `expr`, returning what its value borrows.
+    fn value(&mut self, expr: &Expr) -> Result<Option<Loan>> {
+        let loan = match &expr.kind {
+            ExprKind::Variable { path } if path.len() == 1 => {
+                self.variable(&path[0]).and_then(|variable| variable.loan.clone())
+            }
+            ExprKind::Reference { expr: place, .. } => self.place(place)?,
+            ExprKind::Block(block) => self.block(block)?,
+            ExprKind::If { condition, then_branch, else_branch } => {
+                self.value(condition)?;
+                let then_loan = self.value(then_branch)?;
+                let else_loan = match else_branch {
+                    Some(branch) => self.value(branch)?,
+                    None => None,
+                };
+                shortest(then_loan, else_loan)
+            }
+            ExprKind::Match { expr: scrutinee, arms } => {
+                self.value(scrutinee)?;
+                let mut loan = None;
+                for arm in arms {
+                    let arm_loan = self.value(&arm.body)?;
+                    loan = shortest(loan, arm_loan);
+                }
+                loan
+            }
+            ExprKind::Call { callee, args, .. } => {
+                self.value(callee)?;
+                let mut loan = None;
+                for arg in args {
+                    let arg_loan = self.value(arg)?;
+                    loan = shortest(loan, arg_loan);
+                }
+                loan.filter(|_| is_reference(expr))
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                // The method borrows its receiver.
+                let mut loan = self.place(receiver)?;
+                for arg in args {
+                    let arg_loan = self.value(arg)?;
+                    loan = shortest(loan, arg_loan);
+                }
+                loan.filter(|_| is_reference(expr))
+            }
+            ExprKind::Assign { target, value, .. } => {
+                let loan = self.value(value)?;
+                match &target.kind {
+                    ExprKind::Variable { path } if path.len() == 1 => {
+                        let depth = self.variable(&path[0]).map(|variable| variable.depth);
+                        if let (Some(depth), Some(loan)) = (depth, &loan)
+                            && loan.depth > depth
+                        {
+                            return Err(self.dropped(loan));
+                        }
+                        if let Some(variable) = self.variable(&path[0]) {
+                            variable.loan = loan;
+                        }
+                    }
+                    _ => {
+                        self.place(target)?;
+                    }
+                }
+                None
+            }
+            ExprKind::Return { value } => {
+                if let Some(value) = value
+                    && let Some(loan) = self.value(value)?
+                {
+                    return Err(self.returned(&loan));
+                }
+                None
+            }
+            ExprKind::For { pattern, iterable, body, .. } => {
+                self.value(iterable)?;
+                self.scopes.push(HashMap::new());
+                if let PatternKind::Ident(name) = &pattern.kind {
+                    self.declare(name, None);
+                }
+                let body = self.value(body);
+                self.scopes.pop();
+                body?;
+                None
+            }
+            ExprKind::While { condition, body, .. } => {
+                self.value(condition)?;
+                self.value(body)?;
+                None
+            }
+            ExprKind::Loop { body, .. } => {
+                self.value(body)?;
+                None
+            }
+            ExprKind::Binary { left, right, .. } => {
+                self.value(left)?;
+                self.value(right)?;
+                None
+            }
+            ExprKind::Index { object, index } => {
+                self.value(object)?;
+                self.value(index)?;
+                None
+            }
+            // Reading through a reference copies the value out.
+            ExprKind::Unary { expr: inner, .. }
+            | ExprKind::Cast { expr: inner, .. }
+            | ExprKind::Dereference { expr: inner }
+            | ExprKind::FieldAccess { object: inner, .. } => {
+                self.value(inner)?;
+                None
+            }
+            ExprKind::Unsafe { body } | ExprKind::Spawn { body } => self.value(body)?,
+            ExprKind::Tuple(elements) | ExprKind::Array { elements, .. } => {
+                let mut loan = None;
+                for element in elements {
+                    let element_loan = self.value(element)?;
+                    loan = shortest(loan, element_loan);
+                }
+                loan
+            }
+            _ => None,
+        };
+        Ok(loan)
+    }
+
+    /// Check the place `expr` and return what a reference to it borrows:
+    /// the variable it starts from, or what the reference it goes through
+    /// borrows.
+    fn place(&mut self, expr: &Expr) -> Result<Option<Loan>> {
+        match &expr.kind {
+            ExprKind::Variable { path } if path.len() == 1 && !is_reference(expr) => {
+                let name = &path[0];
+                Ok(self.variable(name).map(|variable| Loan { name: name.clone(), depth: variable.depth, span: expr.span }))
+            }
+            ExprKind::FieldAccess { object, .. } | ExprKind::Index { object, .. } if !is_reference(object) => {
+                if let ExprKind::Index { index, .. } = &expr.kind {
+                    self.value(index)?;
+                }
+                self.place(object)
+            }
+            ExprKind::FieldAccess { object: reference, .. } | ExprKind::Dereference { expr: reference } => {
+                self.value(reference)
+            }
+            _ => self.value(expr),
+        }
+    }
+
+    /// Check `block`; its value may not borrow the variables it declares.
+    fn block(&mut self, block: &Block) -> Result<Option<Loan>> {
+        self.scopes.push(HashMap::new());
+        let loan = self.block_value(block);
+        self.scopes.pop();
+        let loan = loan?;
+        match loan {
+            Some(loan) if loan.depth == self.scopes.len() => match self.scopes.len() {
+                // The body of the function.
+                1 => Err(self.returned(&loan)),
+                _ => Err(self.dropped(&loan)),
+            },
+            loan => Ok(loan),
+        }
+    }
+
+    fn block_value(&mut self, block: &Block) -> Result<Option<Loan>> {
+        for stmt in &block.statements {
+            match &stmt.kind {
+                StmtKind::Let { pattern, initializer, .. } => {
+                    let loan = match initializer {
+                        Some(initializer) => self.value(initializer)?,
+                        None => None,
+                    };
+                    if let PatternKind::Ident(name) = &pattern.kind {
+                        self.declare(name, loan);
+                    }
+                }
+                StmtKind::Expr(expr) | StmtKind::Defer { body: expr } => {
+                    self.value(expr)?;
+                }
+                StmtKind::Item(_) | StmtKind::Macro { .. } => {}
+            }
+        }
+        match &block.expr {
+            Some(expr) => self.value(expr),
+            None => Ok(None),
+        }
+    }
+
+    /// `loan` is returned from the function.
+    fn returned(&self, loan: &Loan) -> TlError {
+        TlError::type_error(
+            self.source.to_string(),
+            loan.span,
+            format!(
+                "Cannot return a reference to `{}`, which is dropped when {} returns",
+                loan.name, self.function
+            ),
+        )
+    }
+
+    /// `loan` outlives the block declaring its variable.
+    fn dropped(&self, loan: &Loan) -> TlError {
+        TlError::type_error(
+            self.source.to_string(),
+            loan.span,
+            format!(
+                "`{}` does not live long enough: it is dropped at the end of its block while still borrowed",
+                loan.name
+            ),
+        )
+    }
+}
+
+/// Whether `expr` was typed as a reference.
+fn is_reference(expr: &Expr) -> bool {
+    matches!(expr.ty.as_ref().map(|ty| &ty.kind), Some(TypeKind::Reference { .. }))
+}
+
+/// The loan of the two that ends first: of the variable declared deepest.
+fn shortest(a: Option<Loan>, b: Option<Loan>) -> Option<Loan> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.depth > a.depth { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
@@ -7,13 +7,16 @@
 pub mod checker;
 pub mod inference;
 pub mod coercion;
-mod coercion;
+pub mod lifetimes;
+pub mod tailcall;
+pub mod aliases;
 
 pub use checker::{TypeChecker, FunctionSignature, TypeDefinition, TypeConstraint};
 pub use inference::{TypeInferer, InferenceContext, TypeVariable};
-pub use coercion::{CoercionRules, CoercionKind};
+pub use coercion::{CoercionRules, CoercionKind, CastKind};
 
-use shared::{Type, TypeKind, PrimitiveType, Result, TlError};
+use shared::ast::PrimitiveType;
+use shared::{Type, TypeKind, Result};
 use miette::SourceSpan;
 
 /// Type checking entry point for programs.
@@ -25,13 +28,20 @@ pub fn check_program(program: &mut shared::Program, source: String) -> Result<()
 /// Type check a single expression.
 pub fn check_expression(expr: &mut shared::Expr, source: String) -> Result<Type> {
     let mut checker = TypeChecker::new(source);
-    checker.check_expr(expr)
+    let ty = checker.check_expr(expr)?;
+    checker.solve_constraints()?;
+    checker.apply_solutions(expr);
+    Ok(checker.resolve_type(&ty))
 }
 
 /// Utility functions for working with types.
 pub mod utils {
     use super::*;
 
+    /// The built-in types whose values are handles to state shared between
+    /// threads: copying one shares the state rather than duplicating it.
+    pub const SHARED_HANDLE_TYPES: &[&str] = &["Mutex", "Guard", "Channel"];
+
     /// Check if a type is numeric.
     pub fn is_numeric_type(ty: &Type) -> bool {
         match &ty.kind {
@@ -100,6 +110,33 @@ pub mod utils {
         }
     }
 
+    /// Check if values of a type are copied rather than moved when used.
+    ///
+    /// Primitives other than `str`, shared references, pointers and
+    /// functions are `Copy`, as are arrays and tuples of `Copy` types and the
+    /// shared handles. Other named and generic types are moved; an
+    /// unresolved type is treated as moved, so callers that must not guess
+    /// should check for `Unknown` first.
+    pub fn is_copy_type(ty: &Type) -> bool {
+        match &ty.kind {
+            TypeKind::Primitive(prim) => *prim != PrimitiveType::Str,
+            TypeKind::Reference { mutable, .. } => !mutable,
+            TypeKind::Pointer { .. } | TypeKind::Function { .. } | TypeKind::Never => true,
+            TypeKind::Array { element, .. } => is_copy_type(element),
+            TypeKind::Tuple(elements) => elements.iter().all(is_copy_type),
+            TypeKind::Named { path, .. } => is_shared_handle(path),
+            TypeKind::Slice { .. }
+            | TypeKind::Generic { .. }
+            | TypeKind::Associated { .. }
+            | TypeKind::Unknown(_) => false,
+        }
+    }
+
+    /// Check if a named type's path is one of `SHARED_HANDLE_TYPES`.
+    pub fn is_shared_handle(path: &[String]) -> bool {
+        matches!(path, [name] if SHARED_HANDLE_TYPES.contains(&name.as_str()))
+    }
+
     /// Check if two types are structurally equivalent.
     pub fn types_equivalent(a: &Type, b: &Type) -> bool {
         match (&a.kind, &b.kind) {
@@ -240,4 +277,703 @@ mod tests {
         let expr_type = result.unwrap();
         assert!(is_integer_type(&expr_type));
     }
-}
\ No newline at end of file
+
+    fn block_of_lets(lets: Vec<(&str, Option<Type>, Expr)>) -> Expr {
+        let span = SourceSpan::new(0.into(), 0);
+        let statements = lets
+            .into_iter()
+            .map(|(name, ty, init)| shared::Stmt {
+                kind: shared::StmtKind::Let {
+                    pattern: shared::Pattern { kind: shared::PatternKind::Ident(name.to_string()), span },
+                    ty,
+                    initializer: Some(init),
+                    mutable: false,
+                },
+                span,
+            })
+            .collect();
+        Expr::new(ExprKind::Block(shared::ast::Block { statements, expr: None, span }), span)
+    }
+
+    fn int(value: i128) -> Expr {
+        literal(Literal::Integer(value))
+    }
+
+    fn literal(literal: Literal) -> Expr {
+        Expr::new(ExprKind::Literal(literal), SourceSpan::new(0.into(), 0))
+    }
+
+    fn var(name: &str) -> Expr {
+        Expr::new(ExprKind::Variable { path: vec![name.to_string()] }, SourceSpan::new(0.into(), 0))
+    }
+
+    fn u8_type() -> Type {
+        Type::primitive(PrimitiveType::U8, SourceSpan::new(0.into(), 0))
+    }
+
+    fn i64_type() -> Type {
+        Type::primitive(PrimitiveType::I64, SourceSpan::new(0.into(), 0))
+    }
+
+    fn cast(expr: Expr, target_type: Type) -> Expr {
+        Expr::new(ExprKind::Cast { expr: Box::new(expr), target_type }, SourceSpan::new(0.into(), 0))
+    }
+
+    fn initializer_type(block: &Expr, index: usize) -> Option<Type> {
+        let ExprKind::Block(block) = &block.kind else { return None };
+        match &block.statements[index].kind {
+            shared::StmtKind::Let { initializer: Some(init), .. } => init.ty.clone(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_literal_type_inferred_from_later_use() {
+        // let x = 5; let y: u8 = x;
+        let mut block = block_of_lets(vec![("x", None, int(5)), ("y", Some(u8_type()), var("x"))]);
+        assert!(check_expression(&mut block, String::new()).is_ok());
+        assert_eq!(initializer_type(&block, 0), Some(u8_type()));
+    }
+
+    #[test]
+    fn test_unconstrained_literal_defaults_to_i32() {
+        let mut block = block_of_lets(vec![("x", None, int(5))]);
+        assert!(check_expression(&mut block, String::new()).is_ok());
+        assert_eq!(initializer_type(&block, 0), Some(i32_type()));
+    }
+
+    #[test]
+    fn test_literal_out_of_range_for_inferred_type() {
+        let mut block = block_of_lets(vec![("x", None, int(300)), ("y", Some(u8_type()), var("x"))]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("out of range for `u8`"), "{}", err);
+    }
+
+    #[test]
+    fn test_suffix_fixes_literal_type() {
+        let mut block = block_of_lets(vec![("x", None, literal(Literal::TypedInteger(7, PrimitiveType::U8)))]);
+        assert!(check_expression(&mut block, String::new()).is_ok());
+        assert_eq!(initializer_type(&block, 0), Some(u8_type()));
+
+        let mut block = block_of_lets(vec![("x", None, literal(Literal::TypedInteger(256, PrimitiveType::U8)))]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("out of range for `u8`"), "{}", err);
+    }
+
+    #[test]
+    fn test_float_literal_out_of_range_for_f32() {
+        let mut block = block_of_lets(vec![("x", None, literal(Literal::TypedFloat(1e39, PrimitiveType::F32)))]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("out of range for `f32`"), "{}", err);
+    }
+
+    #[test]
+    fn test_mismatch_reports_expected_and_found() {
+        let mut block = block_of_lets(vec![("b", Some(bool_type()), int(1))]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("expected `bool`, found `{integer}`"), "{}", err);
+    }
+
+    #[test]
+    fn test_lossy_cast_warns() {
+        // let x: i64 = 5; let y = x as u8;
+        let mut block = block_of_lets(vec![("x", Some(i64_type()), int(5)), ("y", None, cast(var("x"), u8_type()))]);
+        let mut checker = TypeChecker::new(String::new());
+        checker.check_expr(&mut block).unwrap();
+        checker.solve_constraints().unwrap();
+        let warnings = checker.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Cast from `i64` to `u8` may truncate the value");
+    }
+
+    #[test]
+    fn test_cast_literal_takes_target_type() {
+        let mut expr = cast(int(300), u8_type());
+        let err = check_expression(&mut expr, String::new()).unwrap_err();
+        assert!(err.to_string().contains("out of range for `u8`"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_cast_is_an_error() {
+        let mut expr = cast(Expr::new(ExprKind::Literal(Literal::Bool(true)), SourceSpan::new(0.into(), 0)), f64_type());
+        let err = check_expression(&mut expr, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Cannot cast `bool` as `f64`"), "{}", err);
+    }
+
+    #[test]
+    fn test_implicit_widening_coercion() {
+        // let x: i32 = 5; let y: i64 = x;
+        let mut block = block_of_lets(vec![("x", Some(i32_type()), int(5)), ("y", Some(i64_type()), var("x"))]);
+        assert!(check_expression(&mut block, String::new()).is_ok());
+
+        // Narrowing is never implicit.
+        let mut block = block_of_lets(vec![("x", Some(i64_type()), int(5)), ("y", Some(i32_type()), var("x"))]);
+        assert!(check_expression(&mut block, String::new()).is_err());
+    }
+
+    #[test]
+    fn test_undefined_variable_suggests_similar_names() {
+        // let count = 1; let total = 2; let y = coutn;
+        let mut block = block_of_lets(vec![("count", None, int(1)), ("total", None, int(2)), ("y", None, var("coutn"))]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Undefined variable: coutn"), "{}", err);
+        assert_eq!(err.suggestions(), ["count"]);
+    }
+
+    fn for_loop(name: &str, iterable: Expr, body: Expr) -> Expr {
+        let span = SourceSpan::new(0.into(), 0);
+        Expr::new(ExprKind::For {
+            pattern: shared::Pattern { kind: shared::PatternKind::Ident(name.to_string()), span },
+            iterable: Box::new(iterable),
+            body: Box::new(body),
+            label: None,
+        }, span)
+    }
+
+    fn range(start: Expr, end: Expr) -> Expr {
+        Expr::new(ExprKind::Range {
+            start: Some(Box::new(start)),
+            end: Some(Box::new(end)),
+            inclusive: false,
+        }, SourceSpan::new(0.into(), 0))
+    }
+
+    #[test]
+    fn test_for_loop_binds_range_items() {
+        // for i in 0..10 { let b: u8 = i; }
+        let body = block_of_lets(vec![("b", Some(u8_type()), var("i"))]);
+        let mut expr = for_loop("i", range(int(0), int(10)), body);
+        assert_eq!(check_expression(&mut expr, String::new()).unwrap(), unit_type());
+
+        let ExprKind::For { iterable, .. } = &expr.kind else { unreachable!() };
+        let ExprKind::Range { start: Some(start), .. } = &iterable.kind else { unreachable!() };
+        assert_eq!(start.ty, Some(u8_type()));
+    }
+
+    #[test]
+    fn test_for_loop_requires_an_iterable() {
+        let mut expr = for_loop("i", int(3), block_of_lets(vec![]));
+        let err = check_expression(&mut expr, String::new()).unwrap_err();
+        assert!(err.to_string().contains("`{integer}` is not iterable"), "{}", err);
+    }
+
+    #[test]
+    fn test_break_needs_an_enclosing_loop() {
+        let span = SourceSpan::new(0.into(), 0);
+        let brk = || Expr::new(ExprKind::Break { label: None, value: None }, span);
+        let body = Expr::new(ExprKind::Block(shared::ast::Block { statements: vec![], expr: Some(Box::new(brk())), span }), span);
+        let mut expr = Expr::new(ExprKind::While {
+            condition: Box::new(literal(Literal::Bool(true))),
+            body: Box::new(body),
+            label: None,
+        }, span);
+        assert_eq!(check_expression(&mut expr, String::new()).unwrap(), unit_type());
+
+        let err = check_expression(&mut brk(), String::new()).unwrap_err();
+        assert!(err.to_string().contains("`break` outside of a loop"), "{}", err);
+        let ExprKind::While { condition, .. } = &mut expr.kind else { unreachable!() };
+        **condition = int(1);
+        assert!(check_expression(&mut expr, String::new()).is_err());
+    }
+
+    fn call(name: &str, args: Vec<Expr>) -> Expr {
+        Expr::new(ExprKind::Call {
+            callee: Box::new(var(name)),
+            args,
+            safety: shared::SafetyLevel::Safe,
+        }, SourceSpan::new(0.into(), 0))
+    }
+
+    fn named(name: &str, generic: Type) -> Type {
+        Type::new(TypeKind::Named { path: vec![name.to_string()], generics: vec![generic] }, SourceSpan::new(0.into(), 0))
+    }
+
+    #[test]
+    fn test_join_returns_the_spawned_body_type() {
+        // let h = spawn { 5 }; let x: u8 = join(h);
+        let body = Expr::new(ExprKind::Block(shared::ast::Block {
+            statements: Vec::new(),
+            expr: Some(Box::new(int(5))),
+            span: SourceSpan::new(0.into(), 0),
+        }), SourceSpan::new(0.into(), 0));
+        let spawn = Expr::new(ExprKind::Spawn { body: Box::new(body) }, SourceSpan::new(0.into(), 0));
+        let mut block = block_of_lets(vec![("h", None, spawn), ("x", Some(u8_type()), call("join", vec![var("h")]))]);
+        check_expression(&mut block, String::new()).unwrap();
+        assert_eq!(initializer_type(&block, 0), Some(named("Thread", u8_type())));
+    }
+
+    #[test]
+    fn test_generic_builtins_are_instantiated_per_call() {
+        // let h = spawn { 5 }; let g = spawn { true }; let x: u8 = join(h); let y = join(g);
+        let spawn = |value: Expr| {
+            let body = Expr::new(ExprKind::Block(shared::ast::Block {
+                statements: Vec::new(),
+                expr: Some(Box::new(value)),
+                span: SourceSpan::new(0.into(), 0),
+            }), SourceSpan::new(0.into(), 0));
+            Expr::new(ExprKind::Spawn { body: Box::new(body) }, SourceSpan::new(0.into(), 0))
+        };
+        let mut block = block_of_lets(vec![
+            ("h", None, spawn(int(5))),
+            ("g", None, spawn(literal(Literal::Bool(true)))),
+            ("x", Some(u8_type()), call("join", vec![var("h")])),
+            ("y", None, call("join", vec![var("g")])),
+        ]);
+        check_expression(&mut block, String::new()).unwrap();
+        assert_eq!(initializer_type(&block, 0), Some(named("Thread", u8_type())));
+        let bool_type = Type::primitive(PrimitiveType::Bool, SourceSpan::new(0.into(), 0));
+        assert_eq!(initializer_type(&block, 3), Some(bool_type));
+    }
+
+    #[test]
+    fn test_mutexes_and_channels_are_not_supported() {
+        let mut block = block_of_lets(vec![("c", None, call("channel", vec![]))]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("`channel` is not supported yet"), "{}", err);
+    }
+
+    fn new_collection(name: &str) -> Expr {
+        let path = vec![name.to_string(), "new".to_string()];
+        let callee = Expr::new(ExprKind::Variable { path }, SourceSpan::new(0.into(), 0));
+        Expr::new(ExprKind::Call {
+            callee: Box::new(callee),
+            args: Vec::new(),
+            safety: shared::SafetyLevel::Safe,
+        }, SourceSpan::new(0.into(), 0))
+    }
+
+    fn method_call(receiver: &str, method: &str, args: Vec<Expr>) -> Expr {
+        Expr::new(ExprKind::MethodCall {
+            receiver: Box::new(var(receiver)),
+            method: method.to_string(),
+            args,
+        }, SourceSpan::new(0.into(), 0))
+    }
+
+    #[test]
+    fn test_collection_methods_use_the_receivers_type_arguments() {
+        // let v = Vec::new(); let p = v.push(1); let x: u8 = v.get(0);
+        let mut block = block_of_lets(vec![
+            ("v", None, new_collection("Vec")),
+            ("p", None, method_call("v", "push", vec![int(1)])),
+            ("x", Some(u8_type()), method_call("v", "get", vec![int(0)])),
+        ]);
+        check_expression(&mut block, String::new()).unwrap();
+        assert_eq!(initializer_type(&block, 0), Some(named("Vec", u8_type())));
+
+        // let m = HashMap::new(); let a = m.insert(1, true); let b = m.get(true);
+        let mut block = block_of_lets(vec![
+            ("m", None, new_collection("HashMap")),
+            ("a", None, method_call("m", "insert", vec![int(1), literal(Literal::Bool(true))])),
+            ("b", None, method_call("m", "get", vec![literal(Literal::Bool(true))])),
+        ]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Argument 1 has wrong type"), "{}", err);
+    }
+
+    #[test]
+    fn test_unknown_methods_suggest_similar_names() {
+        // let s = HashSet::new(); let n = s.is_empt();
+        let mut block = block_of_lets(vec![
+            ("s", None, new_collection("HashSet")),
+            ("n", None, method_call("s", "is_empt", vec![])),
+        ]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("No method `is_empt` on `HashSet<_>`"), "{}", err);
+        assert_eq!(err.suggestions(), ["is_empty"]);
+
+        // let x = true; let n = x.len();
+        let mut block = block_of_lets(vec![
+            ("x", None, literal(Literal::Bool(true))),
+            ("n", None, method_call("x", "len", vec![])),
+        ]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("`bool` has no methods"), "{}", err);
+
+        // let x = 1; let n = x.len();
+        let mut block = block_of_lets(vec![("x", None, int(1)), ("n", None, method_call("x", "len", vec![]))]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("No method `len` on `{integer}`"), "{}", err);
+    }
+
+    #[test]
+    fn test_checked_arithmetic_returns_an_option_of_the_receivers_type() {
+        // let x: u8 = 200; let sum = x.checked_add(100); let y: u8 = sum.unwrap_or(0);
+        let mut block = block_of_lets(vec![
+            ("x", Some(u8_type()), int(200)),
+            ("sum", None, method_call("x", "checked_add", vec![int(100)])),
+            ("y", Some(u8_type()), method_call("sum", "unwrap_or", vec![int(0)])),
+            ("z", None, method_call("x", "saturating_mul", vec![int(2)])),
+        ]);
+        check_expression(&mut block, String::new()).unwrap();
+        assert_eq!(initializer_type(&block, 1), Some(named("Option", u8_type())));
+        assert_eq!(initializer_type(&block, 3), Some(u8_type()));
+
+        // let x: u8 = 1; let y = x.wrapping_sub(true);
+        let mut block = block_of_lets(vec![
+            ("x", Some(u8_type()), int(1)),
+            ("y", None, method_call("x", "wrapping_sub", vec![literal(Literal::Bool(true))])),
+        ]);
+        let err = check_expression(&mut block, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Argument 1 has wrong type"), "{}", err);
+    }
+
+    fn path_call(path: &[&str], args: Vec<Expr>) -> Expr {
+        let path = path.iter().map(|segment| segment.to_string()).collect();
+        let callee = Expr::new(ExprKind::Variable { path }, SourceSpan::new(0.into(), 0));
+        Expr::new(ExprKind::Call {
+            callee: Box::new(callee),
+            args,
+            safety: shared::SafetyLevel::Safe,
+        }, SourceSpan::new(0.into(), 0))
+    }
+
+    #[test]
+    fn test_prelude_functions_take_any_printable_values() {
+        let mut block = block_of_lets(vec![
+            ("a", None, call("println", vec![literal(Literal::String("n = ".to_string())), int(1)])),
+            ("b", None, call("eprint", vec![literal(Literal::Bool(true))])),
+            ("s", None, call("format", vec![literal(Literal::String("{} {}".to_string())), int(1), literal(Literal::Char('c'))])),
+        ]);
+        check_expression(&mut block, String::new()).unwrap();
+        assert_eq!(initializer_type(&block, 2), Some(string_type()));
+
+        let mut assertion = call("assert", vec![int(1)]);
+        let err = check_expression(&mut assertion, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Argument 1 has wrong type"), "{}", err);
+        let mut format = call("format", vec![]);
+        let err = check_expression(&mut format, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Function format expects at least 1 arguments, got 0"), "{}", err);
+    }
+
+    #[test]
+    fn test_io_and_env_functions_are_typed() {
+        let path = literal(Literal::String("notes.txt".to_string()));
+        let mut read = path_call(&["io", "read_file"], vec![path.clone()]);
+        assert_eq!(check_expression(&mut read, String::new()).unwrap(), string_type());
+        let mut write = path_call(&["io", "write_file"], vec![path.clone(), int(1)]);
+        let err = check_expression(&mut write, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Argument 2 has wrong type"), "{}", err);
+        let mut arg = path_call(&["env", "arg"], vec![]);
+        let err = check_expression(&mut arg, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Function env::arg expects 1 arguments, got 0"), "{}", err);
+    }
+
+    #[test]
+    fn test_embedded_programs_cannot_call_io_functions() {
+        let mut checker = TypeChecker::new(String::new()).with_capabilities(crate::Capabilities::none());
+        let mut var = path_call(&["env", "var"], vec![literal(Literal::String("HOME".to_string()))]);
+        let err = checker.check_expr(&mut var).unwrap_err();
+        assert!(err.to_string().contains("env::var needs environment access, which this profile does not allow"), "{}", err);
+    }
+
+    fn extern_block(abi: &str, items: Vec<shared::ast::stmt::ExternItem>) -> shared::Item {
+        shared::Item::new(shared::ItemKind::Extern { abi: Some(abi.to_string()), items }, SourceSpan::new(0.into(), 0))
+    }
+
+    fn printf(variadic: bool) -> shared::ast::stmt::ExternItem {
+        let span = SourceSpan::new(0.into(), 0);
+        shared::ast::stmt::ExternItem::Function {
+            name: "printf".to_string(),
+            params: vec![shared::ast::stmt::FnParam {
+                pattern: shared::Pattern { kind: shared::PatternKind::Ident("format".to_string()), span },
+                ty: Type::primitive(PrimitiveType::Str, span),
+                default: None,
+                attrs: Vec::new(),
+                span,
+            }],
+            return_type: Some(Type::primitive(PrimitiveType::I32, span)),
+            variadic,
+        }
+    }
+
+    fn main_calling_printf(args: Vec<Expr>) -> shared::Item {
+        let span = SourceSpan::new(0.into(), 0);
+        let call = Expr::new(ExprKind::Call {
+            callee: Box::new(var("printf")),
+            args,
+            safety: shared::SafetyLevel::Safe,
+        }, span);
+        let call = Expr::new(ExprKind::Unsafe { body: Box::new(call) }, span);
+        shared::Item::new(shared::ItemKind::Function {
+            name: "main".to_string(),
+            generics: Vec::new(),
+            params: Vec::new(),
+            return_type: None,
+            body: Some(block_of_lets(vec![("n", Some(Type::primitive(PrimitiveType::I32, span)), call)])),
+            safety: shared::SafetyLevel::Safe,
+            async_: false,
+            const_: false,
+        }, span)
+    }
+
+    fn program(items: Vec<shared::Item>) -> shared::Program {
+        shared::Program { items, span: SourceSpan::new(0.into(), 0) }
+    }
+
+    #[test]
+    fn test_variadic_extern_functions_take_extra_arguments() {
+        let format = literal(Literal::String("%d %f\\n".to_string()));
+        let mut ok = program(vec![
+            extern_block("C", vec![printf(true)]),
+            main_calling_printf(vec![format, int(1), literal(Literal::Float(2.5))]),
+        ]);
+        assert!(check_program(&mut ok, String::new()).is_ok());
+
+        let mut missing = program(vec![extern_block("C", vec![printf(true)]), main_calling_printf(vec![])]);
+        let err = check_program(&mut missing, String::new()).unwrap_err();
+        assert!(err.to_string().contains("expects at least 1 arguments"), "{}", err);
+    }
+
+    #[test]
+    fn test_extern_blocks_only_use_the_c_abi_and_c_types() {
+        let mut abi = program(vec![extern_block("stdcall", vec![printf(false)])]);
+        let err = check_program(&mut abi, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Unsupported ABI \"stdcall\""), "{}", err);
+
+        let span = SourceSpan::new(0.into(), 0);
+        let errno = shared::ast::stmt::ExternItem::Static {
+            name: "errno".to_string(),
+            ty: Type::primitive(PrimitiveType::Char, span),
+            mutable: true,
+        };
+        let mut char_static = program(vec![extern_block("C", vec![errno])]);
+        let err = check_program(&mut char_static, String::new()).unwrap_err();
+        assert!(err.to_string().contains("Type char cannot be passed to or from C"), "{}", err);
+    }
+
+    #[test]
+    fn test_main_returns_nothing_or_an_exit_code() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        assert!(check("fn main() -> i32 { 0 }").is_ok());
+        let err = check("fn main() -> bool { true }").unwrap_err();
+        assert!(err.to_string().contains("Function main must return () or i32, its exit code, not bool"), "{}", err);
+        let err = check("fn main(n: i32) { }").unwrap_err();
+        assert!(err.to_string().contains("Function main takes no parameters"), "{}", err);
+    }
+
+    #[test]
+    fn test_return_values_have_the_function_return_type() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        assert!(check("fn main() -> i32 { if true { return 1; }; return 0; }").is_ok());
+        assert!(check("fn main() { let mut n = 0; loop { n += 1; if n > 3 { return; }; } }").is_ok());
+        assert!(check("fn next(n: u8) -> Option<u8> { let m = n.checked_add(1)?; m.checked_add(1) }\nfn main() { }").is_ok());
+
+        let err = check("fn main() -> i32 { return true; }").unwrap_err();
+        assert!(err.to_string().contains("Returned value type doesn't match return type"), "{}", err);
+        let err = check("fn main() { defer { return; } }").unwrap_err();
+        assert!(err.to_string().contains("`return` in a `defer` block"), "{}", err);
+        let err = check("fn main() { let t = spawn { return; }; }").unwrap_err();
+        assert!(err.to_string().contains("`return` outside of a function"), "{}", err);
+        let err = check("fn main() { let n: u8 = 1; let m = n.checked_add(1)?; }").unwrap_err();
+        assert!(err.to_string().contains("`?` needs an `Option` in a function returning an `Option`"), "{}", err);
+    }
+
+    #[test]
+    fn test_references_follow_mutability_and_deref_to_fields() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let point = "struct Point { x: i32, y: i32 }\nfn main() { }\n";
+        assert!(check(&format!("{}fn get(p: &Point) -> i32 {{ p.x + (*p).y }}", point)).is_ok());
+        assert!(check(&format!("{}fn set(p: &mut Point) {{ p.x = 3; }}", point)).is_ok());
+        assert!(check("fn main() { let mut x = 1; let r = &mut x; *r = 2; let s: &i32 = r; println(*s); }").is_ok());
+
+        let err = check("fn main() { let x = 1; let r = &mut x; }").unwrap_err();
+        assert!(err.to_string().contains("Cannot borrow `x` as mutable: `x` is not declared `let mut`"), "{}", err);
+        let err = check(&format!("{}fn set(p: &Point) {{ p.x = 3; }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Cannot assign to `p.x`: `p` is a `&` reference, not `&mut`"), "{}", err);
+        let err = check(&format!("{}fn get(p: &Point) -> i32 {{ p.z }}", point)).unwrap_err();
+        assert!(err.to_string().contains("No field `z` on type `Point`"), "{}", err);
+        let err = check("fn main() { let x = 1; println(*x); }").unwrap_err();
+        assert!(err.to_string().contains("Cannot dereference a value of type"), "{}", err);
+    }
+
+    #[test]
+    fn test_references_to_local_structs_deref_to_fields() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let point = "struct Point { x: i32, y: i32 }\n";
+        assert!(check(&format!("{}fn main() {{ let s = Point {{ x: 1, y: 2 }}; let r = &s; println(r.x + (*r).y); }}", point)).is_ok());
+        assert!(check(&format!("{}fn main() {{ let mut s = Point {{ x: 1, y: 2 }}; let r = &mut s; r.x = 3; println(s.x); }}", point)).is_ok());
+        // A reference to a reference derefs as far as it takes.
+        assert!(check(&format!("{}fn main() {{ let s = Point {{ x: 1, y: 2 }}; let r = &s; let rr = &r; println(rr.y); }}", point)).is_ok());
+
+        // The field read through the reference has the field's type.
+        let source = format!("{}fn main() {{ let s = Point {{ x: 1, y: 2 }}; let r = &s; let n = r.x; }}", point);
+        let mut program = crate::parse_source(&source).unwrap();
+        check_program(&mut program, source).unwrap();
+        let shared::ItemKind::Function { body: Some(body), .. } = &program.items[1].kind else { panic!("expected main") };
+        let ExprKind::Block(block) = &body.kind else { panic!("expected a block body") };
+        let shared::StmtKind::Let { initializer: Some(field), .. } = &block.statements[2].kind else { panic!("expected a let") };
+        assert!(matches!(&field.ty, Some(ty) if ty.kind == TypeKind::Primitive(PrimitiveType::I32)), "{:?}", field.ty);
+
+        let err = check(&format!("{}fn main() {{ let s = Point {{ x: 1, y: 2 }}; let r = &s; r.x = 3; }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Cannot assign to `r.x`: `r` is a `&` reference, not `&mut`"), "{}", err);
+        let err = check(&format!("{}fn main() {{ let s = Point {{ x: 1, y: 2 }}; let r = &s; println(r.z); }}", point)).unwrap_err();
+        assert!(err.to_string().contains("No field `z` on type `Point`"), "{}", err);
+        let err = check(&format!("{}fn f() -> &i32 {{ let s = Point {{ x: 1, y: 2 }}; let r = &s; &r.x }}\nfn main() {{ }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Cannot return a reference to `s`"), "{}", err);
+        let err = check(&format!("{}fn x(p: &mut Point) -> &mut i32 {{ &mut p.x }}\nfn main() {{ }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Returning a `&mut` from `x` is not supported yet"), "{}", err);
+    }
+
+    #[test]
+    fn test_struct_literals_give_every_field() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let point = "struct Point { x: i32, y: i32 }\n";
+        assert!(check(&format!("{}fn main() {{ let y = 2; let p = Point {{ x: 1, y }}; println(p.x + p.y); }}", point)).is_ok());
+        assert!(check(&format!("{}fn main() {{ let p = Point {{ x: 1, y: 2 }}; let q = Point {{ y: 3, ..p }}; println(q.x); }}", point)).is_ok());
+        // A condition takes a struct literal in parentheses only.
+        assert!(check(&format!("{}fn main() {{ if (Point {{ x: 1, y: 2 }}).x == 1 {{ println(1); }}; }}", point)).is_ok());
+
+        let err = check(&format!("{}fn main() {{ let p = Point {{ x: 1 }}; }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Missing fields of `Point`: y"), "{}", err);
+        let err = check(&format!("{}fn main() {{ let p = Point {{ x: 1, y: 2, z: 3 }}; }}", point)).unwrap_err();
+        assert!(err.to_string().contains("No field `z` on type `Point`"), "{}", err);
+        let err = check(&format!("{}fn main() {{ let p = Point {{ x: 1, x: 2, y: 3 }}; }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Field `x` is given more than once"), "{}", err);
+        let err = check(&format!("{}fn main() {{ let p = Point {{ x: true, y: 2 }}; }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Field `x` has wrong type"), "{}", err);
+        let err = check("fn main() { let p = Nowhere { x: 1 }; }").unwrap_err();
+        assert!(err.to_string().contains("`Nowhere` is not a struct with named fields"), "{}", err);
+    }
+
+    #[test]
+    fn test_raw_pointers_and_extern_calls_need_unsafe() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let abs = "extern \"C\" { fn abs(n: i32) -> i32; }\n";
+        assert!(check(&format!("{}fn main() {{ let n = unsafe {{ abs(-1) }}; }}", abs)).is_ok());
+        assert!(check("fn main() { let mut x = 1; let p = &mut x as *mut i32; unsafe { *p = *(p + 0) + 1; } }").is_ok());
+
+        let err = check(&format!("{}fn main() {{ let n = abs(-1); }}", abs)).unwrap_err();
+        assert!(err.to_string().contains("Calling the extern function `abs` is unsafe and needs an `unsafe` block"), "{}", err);
+        let err = check("fn main() { let x = 1; let p = &x as *const i32; println(*p); }").unwrap_err();
+        assert!(err.to_string().contains("Dereferencing a raw pointer is unsafe"), "{}", err);
+        let err = check("fn main() { let x = 1; let p = &x as *const i32; let q = p + 1; }").unwrap_err();
+        assert!(err.to_string().contains("Pointer arithmetic is unsafe"), "{}", err);
+        let err = check("fn main() { let x = 1; let p = &x as *const i32; unsafe { *p = 2; } }").unwrap_err();
+        assert!(err.to_string().contains("`p` is a `*const` pointer, not `*mut`"), "{}", err);
+    }
+
+    #[test]
+    fn test_mutable_statics_need_unsafe() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let statics = "static LIMIT: i32 = 10;\nstatic mut COUNT: i32 = 0;\n";
+        assert!(check(&format!("{}fn main() {{ println(LIMIT); unsafe {{ COUNT = COUNT + LIMIT; }}; }}", statics)).is_ok());
+        assert!(check(&format!("{}fn main() {{ let COUNT = 1; println(COUNT); }}", statics)).is_ok());
+
+        let err = check(&format!("{}fn main() {{ println(COUNT); }}", statics)).unwrap_err();
+        assert!(err.to_string().contains("Use of the mutable static `COUNT` is unsafe and needs an `unsafe` block"), "{}", err);
+        let err = check(&format!("{}fn main() {{ LIMIT = 3; }}", statics)).unwrap_err();
+        assert!(err.to_string().contains("Cannot assign to `LIMIT`: `LIMIT` is a `static`, not `static mut`"), "{}", err);
+    }
+
+    #[test]
+    fn test_type_aliases_and_newtypes() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let aliases = "type Km = f64;\ntype Distance = Km;\n";
+        assert!(check(&format!("{}fn half(d: Distance) -> f64 {{ d / 2.0 }}\nfn main() {{ let k: Km = 3.0; println(half(k)); }}", aliases)).is_ok());
+        let err = check(&format!("{}fn main() {{ let k: Km = true; }}", aliases)).unwrap_err();
+        assert!(err.to_string().contains("expected `f64`, found `bool`"), "{}", err);
+        let err = check("type A = B;\ntype B = &A;\nfn main() { }").unwrap_err();
+        assert!(err.to_string().contains("Type alias `A` is defined in terms of itself: A = B = A"), "{}", err);
+
+        // A newtype is a type of its own, taken apart by a pattern.
+        let newtypes = "struct Meters(f64);\nstruct Seconds(f64);\n";
+        assert!(check(&format!("{}fn main() {{ let d = Meters(1.5); let Meters(m) = d; println(m + 1.0); }}", newtypes)).is_ok());
+        let err = check(&format!("{}fn main() {{ let d: Meters = Seconds(1.5); }}", newtypes)).unwrap_err();
+        assert!(err.to_string().contains("expected `Meters`, found `Seconds`"), "{}", err);
+        let err = check(&format!("{}fn main() {{ let Meters(m) = Seconds(1.5); }}", newtypes)).unwrap_err();
+        assert!(err.to_string().contains("Pattern doesn't match the value's type: expected `Meters`, found `Seconds`"), "{}", err);
+        let err = check(&format!("{}fn main() {{ let Meters(a, b) = Meters(1.5); }}", newtypes)).unwrap_err();
+        assert!(err.to_string().contains("Tuple struct Meters has 1 fields, but the pattern has 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_match_arms_check_against_the_matched_value() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let color = "enum Color { Red, Green, Blue }\n";
+        assert!(check(&format!("{}fn main() {{ let c = Color::Green; let n = match c {{ Color::Red => 1, Color::Green | Color::Blue => 2 }}; println(n); }}", color)).is_ok());
+        assert!(check("fn main() { let x = 3; match x { 0 => println(\"zero\"), n if n > 2 => println(n), _ => {} }; }").is_ok());
+
+        let err = check("fn main() { let x = 3; match x { true => 1, _ => 2 }; }").unwrap_err();
+        assert!(err.to_string().contains("Pattern doesn't match the value's type"), "{}", err);
+        let err = check("fn main() { let x = 3; match x { 0 => 1, _ => true }; }").unwrap_err();
+        assert!(err.to_string().contains("Match arms must have compatible types"), "{}", err);
+        let err = check("fn main() { let x = 3; match x { n if n => 1, _ => 2 }; }").unwrap_err();
+        assert!(err.to_string().contains("bool"), "{}", err);
+        let err = check(&format!("{}fn main() {{ match Color::Red {{ Color::Pink => 1, _ => 2 }}; }}", color)).unwrap_err();
+        assert!(err.to_string().contains("Enum Color has no variant Pink"), "{}", err);
+        // What an arm binds is in scope in that arm only.
+        let err = check("fn main() { let x = 3; match x { n => 1, _ => n }; }").unwrap_err();
+        assert!(err.to_string().contains("Undefined variable: n"), "{}", err);
+    }
+
+    #[test]
+    fn test_references_do_not_outlive_what_they_borrow() {
+        let check = |source: &str| {
+            let mut program = crate::parse_source(source).unwrap();
+            check_program(&mut program, source.to_string())
+        };
+        let point = "struct Point { x: i32 }\nfn main() { }\n";
+        assert!(check(&format!("{}fn get(p: &Point) -> &i32 {{ &p.x }}", point)).is_ok());
+        assert!(check("fn first(a: &i32, b: &i32) -> &i32 { let r = a; r }\nfn main() { }").is_ok());
+
+        let err = check(&format!("{}fn get(p: Point) -> &i32 {{ &p.x }}", point)).unwrap_err();
+        assert!(err.to_string().contains("Cannot return a reference to `p`, which is dropped when get returns"), "{}", err);
+        let err = check("fn f() -> &i32 { let x = 1; let r = &x; r }\nfn main() { }").unwrap_err();
+        assert!(err.to_string().contains("Cannot return a reference to `x`"), "{}", err);
+        // A call returning a reference borrows from its arguments.
+        let err = check("fn id(a: &i32) -> &i32 { a }\nfn f() -> &i32 { let x = 1; id(&x) }\nfn main() { }").unwrap_err();
+        assert!(err.to_string().contains("Cannot return a reference to `x`"), "{}", err);
+        let err = check("fn main() { let mut r = &0; { let x = 1; r = &x; }; println(*r); }").unwrap_err();
+        assert!(err.to_string().contains("`x` does not live long enough"), "{}", err);
+    }
+
+    #[test]
+    fn test_tailcall_functions_only_call_themselves_in_tail_position() {
+        let check = |function: &str| {
+            let source = format!("{}\nfn main() {{ }}", function);
+            let mut program = crate::parse_source(&source).unwrap();
+            check_program(&mut program, source.clone())
+        };
+        assert!(check("#[tailcall] fn count(n: i32, acc: i32) -> i32 { if n == 0 { acc } else { count(n - 1, acc + 1) } }").is_ok());
+        assert!(check("#[tailcall] fn down(n: i32) -> i32 { if n > 0 { let m = n - 1; down(m) } else { n } }").is_ok());
+
+        let err = check("#[tailcall] fn sum(n: i32) -> i32 { if n == 0 { 0 } else { n + sum(n - 1) } }").unwrap_err();
+        assert!(err.to_string().contains("Call to sum is not a tail call"), "{}", err);
+        // The argument's call is not in tail position; the outer one is.
+        let err = check("#[tailcall] fn twice(n: i32) -> i32 { if n == 0 { 0 } else { twice(twice(n - 1)) } }").unwrap_err();
+        assert!(err.to_string().contains("Call to twice is not a tail call"), "{}", err);
+        // The deferred block runs after the call returns.
+        let err = check("#[tailcall] fn spin(n: i32) -> i32 { defer { print(n); } spin(n) }").unwrap_err();
+        assert!(err.to_string().contains("Call to spin is not a tail call"), "{}", err);
+        let err = check("#[tailcall] fn id(n: i32) -> i32 { n }").unwrap_err();
+        assert!(err.to_string().contains("Function id is marked #[tailcall] but never calls itself"), "{}", err);
+    }
+}
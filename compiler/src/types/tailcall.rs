@@ -0,0 +1,132 @@
+// compiler/src/types/tailcall.rs
+//! Checking of `#[tailcall]` functions.
+//!
+//! A function marked `#[tailcall]` promises that every call it makes to
+//! itself is a tail call: the call's result is the function's result, as
+//! the value of its body or of a `return`, with nothing left to do after
+//! it. Such calls can reuse the caller's frame, so the recursion runs in
+//! constant stack space, like a loop: the `tailcall` optimizer makes them
+//! jumps.
+
+use crate::callgraph::CallGraph;
+use miette::SourceSpan;
+use shared::ast::Block;
+use shared::{Expr, ExprKind, ItemKind, Program, Result, StmtKind, TlError};
+use std::collections::HashSet;
+
+/// Check every `#[tailcall]` function of `program`.
+pub fn check_tailcalls(program: &Program, source: &str) -> Result<()> {
+    let graph = CallGraph::new(program);
+    for item in &program.items {
+        let ItemKind::Function { name, body: Some(body), .. } = &item.kind else { continue };
+        if let Some(attribute) = item.attribute("tailcall") {
+            check_function(name, body, attribute.span, &graph, source)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that `name`, marked `#[tailcall]` at `attribute`, calls itself
+/// and only in tail position.
+fn check_function(name: &str, body: &Expr, attribute: SourceSpan, graph: &CallGraph, source: &str) -> Result<()> {
+    let self_calls: Vec<SourceSpan> =
+        graph.calls(name).iter().filter(|call| call.callee == name).map(|call| call.span).collect();
+    if self_calls.is_empty() {
+        return Err(TlError::type_error(
+            source.to_string(),
+            attribute,
+            format!("Function {} is marked #[tailcall] but never calls itself", name),
+        ));
+    }
+
+    let mut tail_calls = TailCalls { function: name, spans: HashSet::new(), deferring: 0 };
+    tail_calls.tail(body);
+    match self_calls.into_iter().find(|span| !tail_calls.spans.contains(&(span.offset(), span.len()))) {
+        Some(span) => Err(TlError::type_error(
+            source.to_string(),
+            span,
+            format!(
+                "Call to {} is not a tail call, so #[tailcall] cannot reuse the frame; \
+                 return its result directly instead of using it",
+                name
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Collects the spans of the calls to `function` in tail position.
+struct TailCalls<'a> {
+    function: &'a str,
+    spans: HashSet<(usize, usize)>,
+    /// Blocks with a `defer` the walk is in; their deferred blocks run
+    /// after any call in them, so none of those is a tail call
+    deferring: usize,
+}
+
+impl TailCalls<'_> {
+    /// `expr`'s value is the function's result.
+    fn tail(&mut self, expr: &Expr) {
+        if self.deferring > 0 {
+            return self.discarded(expr);
+        }
+        match &expr.kind {
+            ExprKind::Call { callee, .. } => {
+                if let ExprKind::Variable { path } = &callee.kind
+                    && path.len() == 1
+                    && path[0] == self.function
+                {
+                    self.spans.insert((expr.span.offset(), expr.span.len()));
+                }
+            }
+            ExprKind::Block(block) => self.block(block, true),
+            ExprKind::If { then_branch, else_branch, .. } => {
+                self.tail(then_branch);
+                if let Some(branch) = else_branch {
+                    self.tail(branch);
+                }
+            }
+            ExprKind::Match { arms, .. } => arms.iter().for_each(|arm| self.tail(&arm.body)),
+            ExprKind::Unsafe { body } => self.tail(body),
+            _ => self.discarded(expr),
+        }
+    }
+
+    /// `expr`'s value is not the function's result, but a `return` in it
+    /// ends the function.
+    fn discarded(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Return { value: Some(value) } => self.tail(value),
+            ExprKind::Block(block) => self.block(block, false),
+            ExprKind::If { then_branch, else_branch, .. } => {
+                self.discarded(then_branch);
+                if let Some(branch) = else_branch {
+                    self.discarded(branch);
+                }
+            }
+            ExprKind::Match { arms, .. } => arms.iter().for_each(|arm| self.discarded(&arm.body)),
+            ExprKind::Unsafe { body }
+            | ExprKind::Loop { body, .. }
+            | ExprKind::While { body, .. }
+            | ExprKind::For { body, .. } => self.discarded(body),
+            _ => {}
+        }
+    }
+
+    /// `block`, whose value is the function's result if `tail`.
+    fn block(&mut self, block: &Block, tail: bool) {
+        let deferring = block.statements.iter().any(|stmt| matches!(stmt.kind, StmtKind::Defer { .. }));
+        self.deferring += usize::from(deferring);
+        for stmt in &block.statements {
+            if let StmtKind::Expr(expr) | StmtKind::Let { initializer: Some(expr), .. } = &stmt.kind {
+                self.discarded(expr);
+            }
+        }
+        match &block.expr {
+            Some(expr) if tail => self.tail(expr),
+            Some(expr) => self.discarded(expr),
+            None => {}
+        }
+        self.deferring -= usize::from(deferring);
+    }
+}
@@ -0,0 +1,40 @@
+//! The `compiler` binary runs the safety analyses its flags configure.
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn safety_report_is_written_for_the_chosen_profile() {
+    let dir = std::env::temp_dir().join(format!("compiler-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("count.t");
+    let program = "fn count(n: i32) -> i32 {\n    if n == 0 {\n        return 0;\n    };\n    count(n - 1)\n}\nfn main() {\n    println(count(3));\n}\n";
+    fs::write(&input, program).unwrap();
+    let report = dir.join("report.json");
+    let compile = |extra: &[&str]| {
+        let _ = fs::remove_file(&report);
+        Command::new(env!("CARGO_BIN_EXE_compiler"))
+            .arg(&input)
+            .arg("--out-dir")
+            .arg(dir.join("out"))
+            .arg("--safety-report")
+            .arg(&report)
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+
+    // Recursion is a warning usually, and an error for safety-critical code
+    let output = compile(&[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("warning[S0007]"));
+    assert!(fs::read_to_string(&report).unwrap().contains("\"passed\": true"));
+
+    let output = compile(&["--profile", "safety-critical"]);
+    assert!(!output.status.success());
+    assert!(fs::read_to_string(&report).unwrap().contains("\"passed\": false"));
+
+    let output = compile(&["--profile", "safety-critical", "--allow", "stack_overflow"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let _ = fs::remove_dir_all(&dir);
+}
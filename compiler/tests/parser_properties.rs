@@ -0,0 +1,132 @@
+// File: compiler/tests/parser_properties.rs
+
+//! Property tests for the front end: `tokenize` and `Parser::parse` must
+//! return (an error, at worst) for any input, never panic or spin.
+//!
+//! Inputs come from a seeded generator so failures reproduce; the fuzz
+//! targets in `compiler/fuzz` explore the same properties without a budget.
+
+use compiler::Parser;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Cases per property.
+const CASES: usize = 2_000;
+
+/// Longest a single case may take before it counts as a hang.
+const DEADLINE: Duration = Duration::from_secs(10);
+
+/// Fragments that steer random input toward interesting token sequences.
+const FRAGMENTS: &[&str] = &[
+    "fn", "let", "mut", "if", "else", "for", "in", "while", "return", "struct", "enum",
+    "impl", "mod", "use", "pub", "const", "static", "type", "as", "true", "false", "main",
+    "x", "_", "i32", "u8", "f64", "bool", "str", "(", ")", "{", "}", "[", "]", ",", ";",
+    ":", "::", ".", "..", "..=", "->", "=>", "=", "==", "+", "-", "*", "/", "%", "!", "&",
+    "&&", "||", "<", ">", "<=", ">=", "+=", "0", "42", "0x", "0xFF", "0b102", "1_000u8",
+    "1e", "2.5f32", "9999999999999999999999999999999999999999", "\"", "\"s\"", "\"\\",
+    "\"\\u{", "\"\\x80\"", "'", "'a'", "'\\n'", "r#\"", "r\"raw\"", "///", "/// doc\n",
+    "//", "/*", "*/", "\n", " ", "\t", "\u{feff}", "é", "\0",
+];
+
+/// xorshift64*: small, seedable and good enough to pick fragments.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Arbitrary bytes, read the way a file with invalid UTF-8 would be.
+fn random_bytes(rng: &mut Rng) -> String {
+    let len = rng.below(256);
+    let bytes: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// A soup of tokens and token fragments.
+fn random_fragments(rng: &mut Rng) -> String {
+    let len = rng.below(64);
+    (0..len).map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())]).collect::<Vec<_>>().join(" ")
+}
+
+/// Run `property` on `CASES` generated inputs, each under `DEADLINE`.
+fn check(seed: u64, generate: fn(&mut Rng) -> String, property: fn(String)) {
+    let mut rng = Rng(seed);
+    for case in 0..CASES {
+        let input = generate(&mut rng);
+        let (done, wait) = mpsc::channel();
+        let worker = {
+            let input = input.clone();
+            thread::spawn(move || {
+                property(input);
+                let _ = done.send(());
+            })
+        };
+        match wait.recv_timeout(DEADLINE) {
+            Ok(()) => {}
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                panic!("case {} (seed {}) did not finish: {:?}", case, seed, input)
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                let _ = worker.join();
+                panic!("case {} (seed {}) panicked: {:?}", case, seed, input)
+            }
+        }
+    }
+}
+
+fn tokenize(input: String) {
+    let _ = shared::tokenize(input);
+}
+
+fn parse(input: String) {
+    let parser = Parser::new(input);
+    let _ = parser.parse();
+    let _ = parser.parse_with_recovery();
+}
+
+#[test]
+fn tokenize_never_panics_on_bytes() {
+    check(0x7E57_0001, random_bytes, tokenize);
+}
+
+#[test]
+fn tokenize_never_panics_on_fragments() {
+    check(0x7E57_0002, random_fragments, tokenize);
+}
+
+#[test]
+fn parse_never_panics_on_bytes() {
+    check(0x7E57_0003, random_bytes, parse);
+}
+
+#[test]
+fn parse_never_panics_on_fragments() {
+    check(0x7E57_0004, random_fragments, parse);
+}
+
+#[test]
+fn parse_survives_deep_nesting() {
+    let n = 100_000;
+    for source in [
+        format!("const X: i32 = {}1;", "-".repeat(n)),
+        format!("const X: i32 = 1{};", "+1".repeat(n)),
+        format!("const X: i32 = f{};", "()".repeat(n)),
+        format!("fn f() {{ {}1{} }}", "(".repeat(n), ")".repeat(n)),
+        format!("fn f() {{ {}1{} }}", "{".repeat(n), "}".repeat(n)),
+        format!("fn f() {{ {}1; }}", "x = ".repeat(n)),
+        format!("fn f() {{ if a {{}}{} }}", " else if a {}".repeat(n)),
+        "fn f() {".repeat(n),
+    ] {
+        parse(source);
+    }
+}
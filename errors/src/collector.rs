@@ -0,0 +1,234 @@
+// errors/src/collector.rs
+//! Gathers the diagnostics of a pass that carries on past errors.
+//!
+//! One mistake tends to set off others: a parser resynchronising after a
+//! bad token may trip over the same input again, and everything inside a
+//! construct that did not parse is suspect. The collector keeps only the
+//! first error for each piece of source and counts the rest, so a report
+//! leads with the cause and ends with "N similar errors omitted".
+//!
+//! It also tells the pass when to give up: after a `Fatal` diagnostic, or
+//! once it holds as many errors as its limit allows.
+
+use crate::{Severity, TlError};
+use std::ops::{ControlFlow, Range};
+
+/// Diagnostics of one pass, minus duplicates and cascades.
+#[derive(Debug, Default)]
+pub struct ErrorCollector {
+    /// Diagnostics kept, in the order they were added.
+    diagnostics: Vec<(Severity, TlError)>,
+    /// Source covered by the errors kept; later diagnostics inside it are fallout.
+    claimed: Vec<Range<usize>>,
+    /// Diagnostics dropped as duplicates or cascades.
+    omitted: usize,
+    /// Most errors kept before the pass is asked to stop (`None` = no limit).
+    limit: Option<usize>,
+    /// Whether the pass has been asked to stop; later diagnostics are dropped.
+    aborted: bool,
+}
+
+impl ErrorCollector {
+    /// A collector without an error limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A collector that asks the pass to stop at its `limit`th error (at
+    /// least the first).
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            limit: Some(limit.max(1)),
+            ..Self::default()
+        }
+    }
+
+    /// Record `error` at `severity`, unless it repeats one already kept or
+    /// points into the source of an error kept.
+    ///
+    /// Returns `Break` when the pass should stop: `error` is `Fatal`, or the
+    /// error limit is reached. Anything added after that is dropped.
+    pub fn add(&mut self, error: TlError, severity: Severity) -> ControlFlow<()> {
+        if self.aborted {
+            return ControlFlow::Break(());
+        }
+        let duplicate = self.diagnostics.iter().any(|(_, kept)| same_error(kept, &error));
+        let range = error.span().map(|span| span.offset()..span.offset() + span.len());
+        let cascade = range
+            .as_ref()
+            .is_some_and(|range| self.claimed.iter().any(|claimed| inside(range, claimed)));
+        if duplicate || cascade {
+            self.omitted += 1;
+            return ControlFlow::Continue(());
+        }
+
+        // A warning is no reason to distrust what follows it.
+        if severity.is_error() {
+            self.claimed.extend(range);
+        }
+        self.diagnostics.push((severity, error));
+        let limit_reached = self.limit.is_some_and(|limit| self.error_count() >= limit);
+        if severity == Severity::Fatal || limit_reached {
+            self.aborted = true;
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Every diagnostic kept, in the order added.
+    pub fn diagnostics(&self) -> &[(Severity, TlError)] {
+        &self.diagnostics
+    }
+
+    /// The errors kept, without warnings and lesser diagnostics.
+    pub fn errors(&self) -> impl Iterator<Item = &TlError> {
+        self.diagnostics
+            .iter()
+            .filter(|(severity, _)| severity.is_error())
+            .map(|(_, error)| error)
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors().count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Whether the pass was asked to stop, by a `Fatal` diagnostic or the limit.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// How many diagnostics were dropped as duplicates or cascades.
+    pub fn omitted(&self) -> usize {
+        self.omitted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    /// "N similar errors omitted", when any were.
+    pub fn summary(&self) -> Option<String> {
+        match self.omitted {
+            0 => None,
+            1 => Some("1 similar error omitted".to_string()),
+            n => Some(format!("{} similar errors omitted", n)),
+        }
+    }
+
+    /// Remove and return the errors, leaving the lesser diagnostics.
+    pub fn take_errors(&mut self) -> Vec<TlError> {
+        let (errors, rest) = std::mem::take(&mut self.diagnostics)
+            .into_iter()
+            .partition(|(severity, _)| severity.is_error());
+        self.diagnostics = rest;
+        errors.into_iter().map(|(_, error)| error).collect()
+    }
+
+    /// Remove and return every diagnostic, in the order added.
+    pub fn take_diagnostics(&mut self) -> Vec<(Severity, TlError)> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// The errors kept, without warnings and lesser diagnostics.
+    pub fn into_errors(mut self) -> Vec<TlError> {
+        self.take_errors()
+    }
+}
+
+impl Extend<TlError> for ErrorCollector {
+    /// Add each error at `Severity::Error`, stopping if asked to.
+    fn extend<I: IntoIterator<Item = TlError>>(&mut self, errors: I) {
+        for error in errors {
+            if self.add(error, Severity::Error).is_break() {
+                break;
+            }
+        }
+    }
+}
+
+/// Whether `a` and `b` report the same problem at the same place.
+fn same_error(a: &TlError, b: &TlError) -> bool {
+    a.code() == b.code() && a.span() == b.span() && a.to_string() == b.to_string()
+}
+
+/// Whether `range` lies within `claimed`. Empty ranges, as at the end of
+/// input, still have a position, which is compared instead.
+fn inside(range: &Range<usize>, claimed: &Range<usize>) -> bool {
+    if claimed.is_empty() || range.is_empty() {
+        return claimed.start <= range.start && range.start <= claimed.end;
+    }
+    claimed.start <= range.start && range.end <= claimed.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(offset: usize, len: usize, message: &str) -> TlError {
+        TlError::parser("", (offset, len), message)
+    }
+
+    #[test]
+    fn duplicates_are_omitted() {
+        let mut collector = ErrorCollector::new();
+        let _ = collector.add(parser(4, 1, "Unexpected `;`"), Severity::Error);
+        let _ = collector.add(parser(4, 1, "Unexpected `;`"), Severity::Error);
+        assert_eq!(collector.len(), 1);
+        assert_eq!(collector.summary().as_deref(), Some("1 similar error omitted"));
+    }
+
+    #[test]
+    fn errors_inside_a_reported_span_are_cascades() {
+        let mut collector = ErrorCollector::new();
+        collector.extend([
+            parser(10, 20, "Unexpected `struct`"),
+            TlError::type_error("", (12, 3), "Undefined variable: x"),
+            parser(15, 0, "Unexpected end of input"),
+            parser(40, 1, "Unexpected `}`"),
+        ]);
+        assert_eq!(collector.len(), 2);
+        assert_eq!(collector.omitted(), 2);
+        assert_eq!(collector.summary().as_deref(), Some("2 similar errors omitted"));
+    }
+
+    #[test]
+    fn errors_without_a_span_are_only_deduplicated() {
+        let mut collector = ErrorCollector::new();
+        collector.extend([TlError::io("cannot read a.t", None), TlError::io("cannot read b.t", None)]);
+        assert_eq!(collector.len(), 2);
+        assert_eq!(collector.summary(), None);
+    }
+
+    #[test]
+    fn warnings_neither_count_toward_the_limit_nor_hide_errors() {
+        let mut collector = ErrorCollector::with_limit(2);
+        let lossy = TlError::type_error("", (0, 10), "Cast may truncate");
+        assert!(collector.add(lossy, Severity::Warning).is_continue());
+        assert!(collector.add(parser(3, 1, "Unexpected `;`"), Severity::Error).is_continue());
+        assert!(collector.add(parser(20, 1, "Unexpected `}`"), Severity::Error).is_break());
+        assert!(collector.is_aborted());
+        assert!(collector.add(parser(30, 1, "Unexpected `)`"), Severity::Error).is_break());
+        assert_eq!(collector.error_count(), 2);
+
+        assert_eq!(collector.take_errors().len(), 2);
+        assert_eq!(collector.diagnostics().len(), 1);
+        assert!(!collector.has_errors());
+    }
+
+    #[test]
+    fn fatal_errors_stop_the_pass() {
+        let mut collector = ErrorCollector::new();
+        assert!(collector.add(TlError::internal("unreachable"), Severity::Fatal).is_break());
+        assert!(collector.add(parser(0, 1, "Unexpected `;`"), Severity::Error).is_break());
+        assert_eq!(collector.take_diagnostics().len(), 1);
+        assert!(collector.is_empty());
+    }
+}
@@ -0,0 +1,587 @@
+// errors/src/explain.rs
+//! Long-form explanations of diagnostic codes, shown by `tlang explain <code>`.
+//!
+//! Every code a diagnostic can carry has an entry here: the `E` codes of
+//! `TlError`, the `S` codes of safety violations and the `W` codes of
+//! warnings. Codes are stable; once released, a code is never reused for a
+//! different problem.
+
+use std::fmt;
+
+/// The explanation of one diagnostic code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Explanation {
+    /// The code, e.g. `E0003`.
+    pub code: &'static str,
+    /// One-line summary of the problem.
+    pub title: &'static str,
+    /// What triggers the diagnostic, an example that does, and how to fix it.
+    pub text: &'static str,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}\n\n{}", self.code, self.title, self.text.trim())
+    }
+}
+
+/// Look up the explanation of `code`, ignoring case and surrounding space.
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    let code = code.trim();
+    EXPLANATIONS.iter().find(|e| e.code.eq_ignore_ascii_case(code))
+}
+
+/// Every explained code, in order.
+pub fn codes() -> impl Iterator<Item = &'static str> {
+    EXPLANATIONS.iter().map(|e| e.code)
+}
+
+/// All explanations, sorted by code.
+pub static EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        code: "E0001",
+        title: "invalid token",
+        text: r#"
+The lexer found characters that do not form any T-Lang token, such as a
+stray symbol or a string literal that is never closed.
+
+Erroneous example:
+
+    fn main() {
+        println("hello);
+    }
+
+The string starting at `"hello` runs to the end of the file. Close it:
+
+    fn main() {
+        println("hello");
+    }
+"#,
+    },
+    Explanation {
+        code: "E0002",
+        title: "syntax error",
+        text: r#"
+The parser expected a different token here: a missing `;`, `)` or `}`, or
+a keyword in a place the grammar does not allow it.
+
+Erroneous example:
+
+    fn main() {
+        let x = 1
+        println(x);
+    }
+
+Every statement ends with `;`:
+
+    fn main() {
+        let x = 1;
+        println(x);
+    }
+"#,
+    },
+    Explanation {
+        code: "E0003",
+        title: "type error",
+        text: r#"
+An expression has a type that does not fit where it is used: a value of
+the wrong type, an operator applied to operands it does not accept, or a
+name that is not defined.
+
+Erroneous example:
+
+    fn main() {
+        let x: i32 = "five";
+    }
+
+Give the binding a value of its declared type, or change the type:
+
+    fn main() {
+        let x: i32 = 5;
+    }
+
+T-Lang never converts between numeric types implicitly; use `as` to
+convert, e.g. `(x as f64) / 2.0`.
+
+For a name that is not defined, the help lists declared names spelled
+similarly, e.g. "did you mean `count`?" for `coutn`.
+"#,
+    },
+    Explanation {
+        code: "E0004",
+        title: "safety violation",
+        text: r#"
+The program does something that may be memory- or thread-unsafe outside
+an `unsafe` block. The specific kind of violation is reported with its own
+`S` code; run `tlang explain` on that code for details.
+
+Erroneous example:
+
+    fn main() {
+        let p = 0 as *const i32;
+        println(*p);
+    }
+
+Dereferencing a raw pointer is unsafe. Avoid it, or state that the
+invariants hold by wrapping the operation:
+
+    fn main() {
+        let p = 0 as *const i32;
+        unsafe { println(*p); }
+    }
+"#,
+    },
+    Explanation {
+        code: "E0005",
+        title: "runtime error",
+        text: r#"
+The program reached an invalid state while running: a division by zero,
+an index out of bounds or an arithmetic overflow in a checked context.
+
+Erroneous example:
+
+    fn main() {
+        let zero = 0;
+        println(10 / zero);
+    }
+
+Check the divisor before dividing, or restructure the computation so it
+cannot be zero.
+"#,
+    },
+    Explanation {
+        code: "E0006",
+        title: "I/O error",
+        text: r#"
+Reading a source file or writing an output failed: the file does not
+exist, a directory is not writable, or the disk is full.
+
+Erroneous example:
+
+    tlang run does-not-exist.t
+
+Check the path and its permissions. The message names the file and the
+operating system's reason.
+"#,
+    },
+    Explanation {
+        code: "E0007",
+        title: "invalid escape sequence",
+        text: r#"
+A string or character literal contains a backslash followed by something
+that is not a valid escape.
+
+Erroneous example:
+
+    fn main() {
+        println("C:\temp\new\dir");
+    }
+
+Here `\d` is not an escape. The valid escapes are `\n`, `\r`, `\t`, `\\`,
+`\0`, `\'`, `\"`, `\x7F` and `\u{10FFFF}`. Escape the backslash, or use a
+raw string, which needs no escapes:
+
+    fn main() {
+        println(r"C:\temp\new\dir");
+    }
+"#,
+    },
+    Explanation {
+        code: "E0008",
+        title: "macro expansion error",
+        text: r#"
+A `macro_rules!` macro could not be expanded: no macro of that name is
+defined, none of its rules matches the invocation's tokens, its expansion
+does not parse, or it keeps invoking macros past the recursion limit of 64.
+
+Erroneous example:
+
+    macro_rules! square {
+        ($x:expr) => { $x * $x };
+    }
+
+    fn main() {
+        println(square!(1, 2));
+    }
+
+The only rule of `square!` takes one expression. Pass one:
+
+    fn main() {
+        println(square!(1 + 2));
+    }
+
+An expression argument is substituted as a whole, as if in parentheses, so
+this prints 9. Bindings a macro's body introduces with `let` or `for` are
+renamed apart from the caller's names, and each expansion is a block, so
+nothing it binds outlives it.
+
+When the failing invocation came from another macro's expansion, the
+message lists the expansions that led to it, innermost first.
+"#,
+    },
+    Explanation {
+        code: "E0999",
+        title: "internal compiler error",
+        text: r#"
+The compiler reached a state it should never reach. This is a bug in the
+compiler, not in your program.
+
+Please report it with the smallest program that triggers it, the full
+message, and the location it names.
+"#,
+    },
+    Explanation {
+        code: "S0001",
+        title: "use of an uninitialized variable",
+        text: r#"
+A variable is read before any value has been assigned to it on some path.
+
+Erroneous example:
+
+    fn main() {
+        let x: i32;
+        println(x);
+    }
+
+Initialize the variable where it is declared, or on every path before it
+is read:
+
+    fn main() {
+        let x: i32 = 0;
+        println(x);
+    }
+"#,
+    },
+    Explanation {
+        code: "S0002",
+        title: "use of a moved value",
+        text: r#"
+A value is used after ownership of it was moved elsewhere. The note points
+at the move.
+
+Erroneous example:
+
+    fn main() {
+        let a = "text";
+        let b = a;
+        println(a);
+    }
+
+Assigning a value, passing it to a function and binding it in a `match`
+arm all move it. Values of `Copy` types, such as numbers, `bool`, `char`
+and shared references, are copied instead and stay usable. A move inside
+a loop is reported too, since the next iteration uses the moved value.
+
+Use the new owner, or copy the value explicitly before moving it.
+"#,
+    },
+    Explanation {
+        code: "S0003",
+        title: "memory leak",
+        text: r#"
+An allocation is never freed on some path through the function.
+
+Erroneous example:
+
+    fn main() {
+        let buffer = malloc(64);
+    }
+
+Free the allocation before every return, or return it or pass it to a
+function, which then owns it.
+"#,
+    },
+    Explanation {
+        code: "S0004",
+        title: "resource leak",
+        text: r#"
+A resource such as a file or a lock is acquired and never released on
+some path through the function.
+
+Erroneous example:
+
+    fn save(ready: bool) {
+        let file = open("log.txt");
+        if ready {
+            close(file);
+        }
+    }
+
+The file stays open when `ready` is false. Release it on every path, or
+return it or pass it to a function, which then owns it:
+
+    fn save(ready: bool) {
+        let file = open("log.txt");
+        if ready {
+            close(file);
+        } else {
+            drop(file);
+        }
+    }
+"#,
+    },
+    Explanation {
+        code: "S0005",
+        title: "possible null pointer dereference",
+        text: r#"
+A pointer that may be null is dereferenced without a check.
+
+Erroneous example:
+
+    fn main() {
+        let p = 0 as *const i32;
+        unsafe { println(*p); }
+    }
+
+Compare the pointer with null before dereferencing it, or use a reference,
+which is never null.
+"#,
+    },
+    Explanation {
+        code: "S0006",
+        title: "possible buffer overflow",
+        text: r#"
+An index may lie outside the bounds of the array or buffer it indexes.
+
+Erroneous example:
+
+    fn main() {
+        let a = [1, 2, 3];
+        println(a[3]);
+    }
+
+Indexes run from 0 to the length minus one. Check the index against the
+length first, or iterate over the elements instead.
+"#,
+    },
+    Explanation {
+        code: "S0007",
+        title: "possible stack overflow",
+        text: r#"
+A call chain may nest deeper than the configured stack allows.
+
+The chains are followed from every function that nothing else calls, such
+as `main`, and each call counts one frame. The limit is 256 frames, or what
+fits in `--stack-size` bytes at 256 bytes a frame; a chain deeper than that
+is an error, reported at the call that goes past it.
+
+Recursion has no depth the compiler can bound, so each cycle of functions
+calling each other is reported once, at the call that closes it, as a
+warning:
+
+    fn even(n: i32) {
+        odd(n - 1);
+    }
+
+    fn odd(n: i32) {
+        even(n - 1);
+    }
+
+Make sure the recursion has a base case that is always reached, or rewrite
+it as a loop.
+"#,
+    },
+    Explanation {
+        code: "S0008",
+        title: "unsafe operation in safe code",
+        text: r#"
+An operation that requires a lower safety level than the surrounding code
+allows, such as a raw pointer dereference or a call to an `unsafe` function.
+
+Wrap the operation in an `unsafe` block once you have checked the
+invariants it relies on, or use a safe alternative.
+"#,
+    },
+    Explanation {
+        code: "S0009",
+        title: "possible data race",
+        text: r#"
+A variable is accessed from two threads at once and at least one access
+is a write, with no synchronization between them. The note points at the
+conflicting access.
+
+Erroneous example:
+
+    fn main() {
+        let mut count = 0;
+        let worker = spawn {
+            count = count + 1;
+        };
+        println(count);
+        join(worker);
+    }
+
+The spawned thread may still be writing `count` when `main` reads it.
+Join the thread before the read:
+
+    fn main() {
+        let mut count = 0;
+        let worker = spawn {
+            count = count + 1;
+        };
+        join(worker);
+        println(count);
+    }
+
+A thread spawned in a loop and not joined in the same iteration races
+with the one the next iteration spawns.
+"#,
+    },
+    Explanation {
+        code: "S0010",
+        title: "real-time constraint violated",
+        text: r#"
+A function marked `#[realtime]` or `#[wcet(N)]` may not finish in time.
+Such a function, and every function it calls, may not allocate on the
+heap, loop without a bound known at compile time, make calls that block
+on a lock, another thread or I/O, or recurse. Under `#[wcet(N)]` its
+estimated worst case must also stay within `N` cycles.
+
+Erroneous example:
+
+    #[realtime]
+    fn tick(samples: i32) {
+        for i in 0..samples {
+            process(i);
+        }
+    }
+
+The number of iterations depends on `samples`. Loop over a constant
+range instead, and do any allocation or blocking work before the
+real-time path starts:
+
+    #[realtime]
+    fn tick() {
+        for i in 0..64 {
+            process(i);
+        }
+    }
+
+When the issue lies in a called function, the note points at the call.
+For an estimate over the bound, reduce the work done on that path or
+relax the bound if it was too strict.
+"#,
+    },
+    Explanation {
+        code: "S0011",
+        title: "heap allocation in static-memory code",
+        text: r#"
+A function that may only use static memory, as under the embedded profile,
+allocates on the heap.
+
+Use fixed-size arrays or statically allocated buffers instead, or build
+with a profile that permits allocation.
+"#,
+    },
+    Explanation {
+        code: "W0001",
+        title: "lossy cast",
+        text: r#"
+An `as` cast may lose information: it truncates, wraps, saturates or
+rounds the value.
+
+Example:
+
+    fn main() {
+        println(300 as u8);
+    }
+
+This prints 44, because 300 wraps around modulo 256. If that is intended
+nothing needs to change; otherwise cast to a type wide enough for every
+value, or check the range first.
+"#,
+    },
+    Explanation {
+        code: "W0002",
+        title: "option the backend does not support",
+        text: r#"
+The target backend cannot do what a compiler option asks for, so the
+option was scaled back: the optimization level was lowered to the highest
+the backend supports, debug info was left out, or the backend produced
+the kind of output it always does.
+
+`tlang targets` lists what each backend supports. Pick a backend that
+supports the option, or drop the option to silence the warning.
+"#,
+    },
+    Explanation {
+        code: "W0003",
+        title: "unused code",
+        text: r#"
+A local variable or parameter is never read, a `use` declaration imports
+a name nothing refers to, or a private function is never called from
+`main` or any public function.
+
+Example:
+
+    use std::io;
+
+    fn helper() -> i32 { 1 }
+
+    fn main() {
+        let count = 2;
+    }
+
+Here `io`, `helper` and `count` are all unused. Remove what is left over,
+or, for a variable that is needed but not read, start its name with an
+underscore: `let _count = 2;`. A name starting with an underscore is never
+reported.
+"#,
+    },
+    Explanation {
+        code: "W0004",
+        title: "function over a metrics threshold",
+        text: r#"
+`tlang lint --metrics` measured a function past one of its thresholds:
+
+- cyclomatic complexity, one more than the decisions the function makes:
+  each `if`, `while`, `for`, `&&`, `||` and `?`, and each `match` arm
+  after the first (default limit 10, `--max-complexity`);
+- nesting depth, how many of those are open at its deepest point
+  (default limit 4, `--max-nesting`);
+- lines of code, not counting blank lines and comments (default limit 50,
+  `--max-lines`).
+
+Long, deeply nested functions with many paths are hard to read and to
+test. Move parts of the function into functions of their own, or return
+early instead of nesting.
+"#,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_sorted_and_unique() {
+        let codes: Vec<_> = codes().collect();
+        assert!(codes.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", codes);
+    }
+
+    #[test]
+    fn lookup_ignores_case_and_space() {
+        assert_eq!(explain(" e0003 ").map(|e| e.code), Some("E0003"));
+        assert!(explain("E9999").is_none());
+    }
+
+    #[test]
+    fn every_error_code_is_explained() {
+        let errors = [
+            crate::TlError::lexer("", (0, 0), ""),
+            crate::TlError::invalid_escape("", (0, 0), ""),
+            crate::TlError::parser("", (0, 0), ""),
+            crate::TlError::type_error("", (0, 0), ""),
+            crate::TlError::safety("", (0, 0), ""),
+            crate::TlError::runtime("", (0, 0), ""),
+            crate::TlError::macro_error("", (0, 0), "", Vec::new()),
+            crate::TlError::lint("", (0, 0), ""),
+            crate::TlError::complexity("", (0, 0), ""),
+            crate::TlError::io("", None),
+            crate::TlError::internal(""),
+        ];
+        for error in &errors {
+            assert!(explain(error.code()).is_some(), "{} has no explanation", error.code());
+        }
+    }
+}
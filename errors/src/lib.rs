@@ -1,6 +1,16 @@
 // errors/src/lib.rs
 //! Unified error handling for T-Lang compiler and runtime.
 //! Provides structured, user-friendly diagnostics with source spans.
+//!
+//! Each diagnostic carries a stable code, explained at length by
+//! [`explain::explain`].
+
+pub mod collector;
+pub mod explain;
+pub mod suggest;
+
+pub use collector::ErrorCollector;
+pub use explain::{explain, Explanation};
 
 use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
@@ -10,7 +20,7 @@ use thiserror::Error;
 pub enum TlError {
     #[error("Lexical error: {message}")]
     #[diagnostic(
-        code(t::lexer),
+        code(E0001),
         help("Check for invalid characters or malformed tokens")
     )]
     Lexer {
@@ -21,9 +31,22 @@ pub enum TlError {
         message: String,
     },
 
+    #[error("Lexical error: {message}")]
+    #[diagnostic(
+        code(E0007),
+        help("Valid escapes are \\n, \\r, \\t, \\\\, \\0, \\', \\\", \\x7F and \\u{{10FFFF}}; a raw string r\"...\" needs none")
+    )]
+    InvalidEscape {
+        #[source_code]
+        src: String,
+        #[label("invalid escape")]
+        span: SourceSpan,
+        message: String,
+    },
+
     #[error("Parse error: {message}")]
     #[diagnostic(
-        code(t::parser),
+        code(E0002),
         help("Check syntax against T-Lang grammar specification")
     )]
     Parser {
@@ -35,21 +58,22 @@ pub enum TlError {
     },
 
     #[error("Type error: {message}")]
-    #[diagnostic(
-        code(t::types),
-        help("Ensure all expressions have compatible types")
-    )]
+    #[diagnostic(code(E0003))]
     Type {
         #[source_code]
         src: String,
         #[label("type mismatch")]
         span: SourceSpan,
         message: String,
+        /// Declared names close to an undefined one, nearest first.
+        suggestions: Vec<String>,
+        #[help]
+        help: Option<String>,
     },
 
     #[error("Safety violation: {message}")]
     #[diagnostic(
-        code(t::safety),
+        code(E0004),
         help("Use explicit unsafe blocks for potentially dangerous operations")
     )]
     Safety {
@@ -62,7 +86,7 @@ pub enum TlError {
 
     #[error("Runtime error: {message}")]
     #[diagnostic(
-        code(t::runtime),
+        code(E0005),
         help("Check for logic errors or invalid runtime state")
     )]
     Runtime {
@@ -74,16 +98,57 @@ pub enum TlError {
     },
 
     #[error("I/O error: {message}")]
-    #[diagnostic(code(t::io))]
+    #[diagnostic(code(E0006))]
     Io {
         message: String,
         #[diagnostic(skip)]
-        source: Option<std::io::Error>,
+        source: Option<std::sync::Arc<std::io::Error>>,
+    },
+
+    #[error("Macro error: {message}{}", expansion_trace(.backtrace))]
+    #[diagnostic(code(E0008))]
+    Macro {
+        #[source_code]
+        src: String,
+        #[label("in this macro invocation")]
+        span: SourceSpan,
+        message: String,
+        /// Macros being expanded when the error arose, innermost first;
+        /// empty when the invocation at `span` itself could not be expanded.
+        backtrace: Vec<String>,
+    },
+
+    #[error("{message}")]
+    #[diagnostic(code(W0003), severity(Warning))]
+    Lint {
+        #[source_code]
+        src: String,
+        #[label("never used")]
+        span: SourceSpan,
+        message: String,
+        #[help]
+        help: Option<String>,
+        /// The edit that silences the warning, if there is one.
+        fix: Option<Fix>,
+    },
+
+    #[error("{message}")]
+    #[diagnostic(
+        code(W0004),
+        severity(Warning),
+        help("Split the function into smaller ones, or raise the threshold if it is clearer as it is")
+    )]
+    Complexity {
+        #[source_code]
+        src: String,
+        #[label("in this function")]
+        span: SourceSpan,
+        message: String,
     },
 
     #[error("Internal compiler error: {message}")]
     #[diagnostic(
-        code(t::internal),
+        code(E0999),
         help("This is a bug in the T-Lang compiler. Please report it.")
     )]
     Internal {
@@ -92,19 +157,73 @@ pub enum TlError {
     },
 }
 
-/// Error severity levels for diagnostics.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An edit that resolves a diagnostic: `replacement` in place of the source
+/// at `span`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    /// What the edit does, e.g. "Remove the unused import"
+    pub title: String,
+    pub span: SourceSpan,
+    pub replacement: String,
+}
+
+/// How serious a diagnostic is, least to most; every crate reports with
+/// these levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Severity {
-    Error,
-    Warning,
-    Info,
     Hint,
+    Info,
+    Warning,
+    /// Compilation fails, but the pass may carry on to find more errors.
+    Error,
+    /// Compilation fails and the pass stops here.
+    Fatal,
+}
+
+impl Severity {
+    /// Whether a diagnostic at this level fails compilation.
+    pub fn is_error(self) -> bool {
+        self >= Severity::Error
+    }
 }
 
 /// Result type for T-Lang operations.
 pub type Result<T> = std::result::Result<T, TlError>;
 
 impl TlError {
+    /// The stable diagnostic code of this error, e.g. `E0003`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TlError::Lexer { .. } => "E0001",
+            TlError::Parser { .. } => "E0002",
+            TlError::Type { .. } => "E0003",
+            TlError::Safety { .. } => "E0004",
+            TlError::Runtime { .. } => "E0005",
+            TlError::Io { .. } => "E0006",
+            TlError::InvalidEscape { .. } => "E0007",
+            TlError::Macro { .. } => "E0008",
+            TlError::Lint { .. } => "W0003",
+            TlError::Complexity { .. } => "W0004",
+            TlError::Internal { .. } => "E0999",
+        }
+    }
+
+    /// Where in the source the error points, if anywhere.
+    pub fn span(&self) -> Option<SourceSpan> {
+        match self {
+            TlError::Lexer { span, .. }
+            | TlError::InvalidEscape { span, .. }
+            | TlError::Parser { span, .. }
+            | TlError::Type { span, .. }
+            | TlError::Safety { span, .. }
+            | TlError::Runtime { span, .. }
+            | TlError::Macro { span, .. }
+            | TlError::Lint { span, .. }
+            | TlError::Complexity { span, .. } => Some(*span),
+            TlError::Io { .. } | TlError::Internal { .. } => None,
+        }
+    }
+
     /// Create a lexer error with source context.
     pub fn lexer(src: impl Into<String>, span: impl Into<SourceSpan>, message: impl Into<String>) -> Self {
         Self::Lexer {
@@ -114,6 +233,15 @@ impl TlError {
         }
     }
 
+    /// Create an invalid escape sequence error; `span` covers the whole escape.
+    pub fn invalid_escape(src: impl Into<String>, span: impl Into<SourceSpan>, message: impl Into<String>) -> Self {
+        Self::InvalidEscape {
+            src: src.into(),
+            span: span.into(),
+            message: message.into(),
+        }
+    }
+
     /// Create a parser error with source context.
     pub fn parser(src: impl Into<String>, span: impl Into<SourceSpan>, message: impl Into<String>) -> Self {
         Self::Parser {
@@ -129,6 +257,67 @@ impl TlError {
             src: src.into(),
             span: span.into(),
             message: message.into(),
+            suggestions: Vec::new(),
+            help: Some("Ensure all expressions have compatible types".to_string()),
+        }
+    }
+
+    /// Offer `names` as what an undefined name may have meant. Only type
+    /// errors carry suggestions; other errors are returned unchanged.
+    pub fn with_suggestions(mut self, names: Vec<String>) -> Self {
+        if let Self::Type { suggestions, help, .. } = &mut self
+            && let Some(did_you_mean) = suggest::did_you_mean(&names)
+        {
+            *help = Some(did_you_mean);
+            *suggestions = names;
+        }
+        self
+    }
+
+    /// Names suggested in place of an undefined one, nearest first.
+    pub fn suggestions(&self) -> &[String] {
+        match self {
+            Self::Type { suggestions, .. } => suggestions,
+            _ => &[],
+        }
+    }
+
+    /// Create a lint warning about source that is never used.
+    pub fn lint(src: impl Into<String>, span: impl Into<SourceSpan>, message: impl Into<String>) -> Self {
+        Self::Lint {
+            src: src.into(),
+            span: span.into(),
+            message: message.into(),
+            help: None,
+            fix: None,
+        }
+    }
+
+    /// Offer `fix` for a lint warning, its title as the help. Other errors
+    /// are returned unchanged.
+    pub fn with_fix(mut self, new_fix: Fix) -> Self {
+        if let Self::Lint { help, fix, .. } = &mut self {
+            *help = Some(new_fix.title.clone());
+            *fix = Some(new_fix);
+        }
+        self
+    }
+
+    /// The edit that resolves this diagnostic, if there is one.
+    pub fn fix(&self) -> Option<&Fix> {
+        match self {
+            Self::Lint { fix, .. } => fix.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Create a warning about a function over a size or complexity
+    /// threshold.
+    pub fn complexity(src: impl Into<String>, span: impl Into<SourceSpan>, message: impl Into<String>) -> Self {
+        Self::Complexity {
+            src: src.into(),
+            span: span.into(),
+            message: message.into(),
         }
     }
 
@@ -150,11 +339,45 @@ impl TlError {
         }
     }
 
+    /// Create a macro expansion error at the invocation written in the
+    /// source; `backtrace` names the macros being expanded, innermost first.
+    pub fn macro_error(
+        src: impl Into<String>,
+        span: impl Into<SourceSpan>,
+        message: impl Into<String>,
+        backtrace: Vec<String>,
+    ) -> Self {
+        Self::Macro {
+            src: src.into(),
+            span: span.into(),
+            message: message.into(),
+            backtrace,
+        }
+    }
+
+    /// This error shown against `source`, for one made where the source
+    /// was not at hand, as the tokenizer makes them.
+    pub fn with_source_code(mut self, source: &str) -> Self {
+        match &mut self {
+            TlError::Lexer { src, .. }
+            | TlError::InvalidEscape { src, .. }
+            | TlError::Parser { src, .. }
+            | TlError::Type { src, .. }
+            | TlError::Safety { src, .. }
+            | TlError::Runtime { src, .. }
+            | TlError::Macro { src, .. }
+            | TlError::Lint { src, .. }
+            | TlError::Complexity { src, .. } => *src = source.to_string(),
+            TlError::Io { .. } | TlError::Internal { .. } => {}
+        }
+        self
+    }
+
     /// Create an I/O error.
     pub fn io(message: impl Into<String>, source: Option<std::io::Error>) -> Self {
         Self::Io {
             message: message.into(),
-            source,
+            source: source.map(std::sync::Arc::new),
         }
     }
 
@@ -165,4 +388,22 @@ impl TlError {
             location: std::panic::Location::caller().to_string(),
         }
     }
-}
\ No newline at end of file
+}
+
+/// "in the expansion of `m!`" lines for a macro backtrace, innermost first,
+/// with recursive runs of one macro shown once.
+fn expansion_trace(backtrace: &[String]) -> String {
+    let mut trace = String::new();
+    let mut frames = backtrace.iter().peekable();
+    while let Some(name) = frames.next() {
+        let mut times = 1;
+        while frames.next_if_eq(&name).is_some() {
+            times += 1;
+        }
+        trace.push_str(&format!("\n  in the expansion of `{}!`", name));
+        if times > 1 {
+            trace.push_str(&format!(" ({} times)", times));
+        }
+    }
+    trace
+}
@@ -0,0 +1,96 @@
+// errors/src/suggest.rs
+//! "Did you mean" suggestions for misspelled names.
+
+/// Most suggestions offered for one name.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Levenshtein distance between `a` and `b`: the fewest single-character
+/// insertions, deletions and substitutions that turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The candidates close enough to `name` to be what was meant, nearest
+/// first and alphabetically among equals.
+///
+/// A candidate qualifies when it differs from `name` only in case, or when
+/// at most one edit per three characters of `name`, rounded up, turns one
+/// into the other.
+pub fn similar_names<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let limit = name.chars().count().div_ceil(3).max(1);
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter_map(|candidate| {
+            let distance = if candidate.eq_ignore_ascii_case(name) {
+                0
+            } else {
+                edit_distance(name, candidate)
+            };
+            (distance <= limit).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort_unstable();
+    close.dedup();
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| candidate.to_string()).collect()
+}
+
+/// Help text offering `names`, or `None` when there are none.
+pub fn did_you_mean(names: &[String]) -> Option<String> {
+    match names {
+        [] => None,
+        [name] => Some(format!("did you mean `{}`?", name)),
+        names => {
+            let quoted: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+            Some(format!("did you mean one of {}?", quoted.join(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("count", "count"), 0);
+        assert_eq!(edit_distance("coutn", "count"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn only_close_names_are_suggested_nearest_first() {
+        let names = ["counter", "count", "amount", "Count", "total"];
+        assert_eq!(similar_names("cont", names), ["count", "Count"]);
+        assert_eq!(similar_names("coutn", names), ["count"]);
+        assert_eq!(similar_names("COUNT", names), ["Count", "count"]);
+        assert_eq!(similar_names("countr", names), ["count", "counter", "Count"]);
+        assert!(similar_names("x", ["y_axis", "width"]).is_empty());
+    }
+
+    #[test]
+    fn help_lists_the_suggestions() {
+        assert_eq!(did_you_mean(&[]), None);
+        assert_eq!(did_you_mean(&["count".into()]).as_deref(), Some("did you mean `count`?"));
+        assert_eq!(
+            did_you_mean(&["a".into(), "b".into()]).as_deref(),
+            Some("did you mean one of `a`, `b`?")
+        );
+    }
+}
@@ -1,19 +1,23 @@
 /* ===================== example_backend/src/lib.rs ======================== */
-use plugin_api::{register_backend, Backend, BackendError, CompiledModule};
+use plugin_api::{register_backend, Backend, BackendError, CompiledArtifact, CompiledModule};
 
 /// A toy backend that simply echoes the module byte‑code back out.
 pub struct EchoBackend;
 
 impl Backend<CompiledModule> for EchoBackend {
-    type ModuleIr = Box<dyn std::any::Any + Send + Sync>; // final artefact as trait object
+    type ModuleIr = CompiledArtifact; // the untouched byte‑code, as a binary artefact
 
     fn compile(&self, module: CompiledModule) -> Result<Self::ModuleIr, BackendError> {
-        Ok(Box::new(module.bytecode.into_boxed_slice()))
+        Ok(CompiledArtifact::new(self.name(), module.bytecode))
     }
 
     fn name(&self) -> &'static str {
         "echo"
     }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
 }
 
 // Register on library load.
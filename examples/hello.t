@@ -1,2 +1,4 @@
-# examples/hello.t
-print("Hello, T-Lang!\n");
+// examples/hello.t
+fn main() {
+    print("Hello, T-Lang!\n");
+}
@@ -0,0 +1,18 @@
+// Casts bound by `let` and inside arithmetic, folded and at run time.
+fn main() {
+    let y = 5 as i64;
+    println(y);
+    let a = 3;
+    let f = a as f64;
+    println(f);
+    let w = 200 as i32 + 100;
+    println(w);
+    println(250 as u8 + 5);
+    let mut n = 0;
+    while n < 2 {
+        n = n + 1;
+    }
+    let m = n as f64;
+    println(m);
+    println(n as i64 * 1000);
+}
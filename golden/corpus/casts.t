@@ -0,0 +1,11 @@
+// Numeric casts: wrapping, saturating and int/float conversion.
+fn main() {
+    println(300 as u8);
+    println(200 as i8);
+    println(3.9 as i32);
+    println(1.0e20 as i64);
+    println(7 as f64);
+    println(true as i32);
+    println('A' as u32);
+    println(65 as char);
+}
@@ -0,0 +1,16 @@
+// Division, remainder and negation of values only known at run time, and
+// the panic of an addition that overflows its type.
+fn main() {
+    let n: i32 = -7;
+    let mut d = 0;
+    while d < 2 {
+        d += 1;
+    }
+    println(n / d, " ", n % d, " ", -(n * d), " ", 7 / d);
+    let mut m: u8 = 200;
+    while m < 250 {
+        m = m + 25;
+    }
+    println("m ", m);
+    println(m + 6);
+}
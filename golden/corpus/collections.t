@@ -0,0 +1,25 @@
+// The built-in collections, which the lowering evaluates like constants.
+fn main() {
+    let v = Vec::new();
+    for i in 1..=3 {
+        v.push(i);
+    }
+    v.set(0, 10);
+    println("len ", v.len(), ", first ", v.get(0));
+    let last = v.pop();
+    println("popped ", last, ", has 2: ", v.contains(2));
+    for x in v {
+        println("item ", x);
+    }
+
+    let ages: HashMap<str, i64> = HashMap::new();
+    ages.insert("ada", 36);
+    ages.insert("ada", 37);
+    println("ada ", ages.get("ada"), ", bob: ", ages.contains_key("bob"));
+
+    let seen = HashSet::new();
+    seen.insert("a");
+    seen.insert("a");
+    seen.insert("b");
+    println("distinct ", seen.len());
+}
@@ -0,0 +1,14 @@
+// `defer` blocks run as their block ends, the last deferred first, and
+// before `main` exits with its value.
+fn main() -> i32 {
+    let name = "outer";
+    defer { println("closed ", name); }
+    for i in 0..2 {
+        defer { println("end of step ", i); }
+        println("step ", i);
+    }
+    let name = "shadowed";
+    defer { println("then ", name); }
+    println("exiting with 5");
+    5
+}
@@ -0,0 +1,14 @@
+// A `return` from loops and `if`s decided at run time runs the deferred
+// blocks of every block it leaves, the innermost first, then exits.
+fn main() -> i32 {
+    defer { println("outer"); }
+    let mut n = 0;
+    while n < 10 {
+        defer { print(n); }
+        n += 1;
+        if n == 3 {
+            return n;
+        };
+    }
+    0
+}
@@ -0,0 +1,5 @@
+// `main` returning `i32`: the program exits with its value.
+fn main() -> i32 {
+    println("exiting with 3");
+    3
+}
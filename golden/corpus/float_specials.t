@@ -0,0 +1,12 @@
+// NaN and the infinities print the same on every backend.
+fn main() {
+    let zero = 0.0;
+    println(1.0 / zero);
+    println(-1.0 / zero);
+    println(zero / zero);
+    let mut n = 0.0;
+    while n < 1.0 {
+        n = n + 0.5;
+    }
+    println(n / zero, -n / zero, (n - n) / zero);
+}
@@ -0,0 +1,46 @@
+// Functions called from `main`, with parameters, results, recursion, early
+// returns and values only known at run time.
+fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+fn fib(n: i64) -> i64 {
+    if n < 2 {
+        n
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+#[inline(never)]
+fn greet(times: i32) {
+    defer { println("done"); }
+    let mut i = 0;
+    while i < times {
+        println("hello ", i);
+        i += 1;
+    }
+}
+
+#[inline]
+fn sign(n: i32) -> i32 {
+    if n < 0 {
+        return -1;
+    };
+    match n {
+        0 => 0,
+        _ => 1,
+    }
+}
+
+fn main() {
+    println(add(2, 3));
+    let x = fib(10);
+    println(x);
+    greet(2);
+    let mut total = 0;
+    for i in 0..5 {
+        total += add(i, sign(i - 2));
+    }
+    println(total);
+}
@@ -0,0 +1,13 @@
+// Every kind of literal, printed directly and through `let` bindings.
+fn main() {
+    let answer = 42;
+    let ratio = 2.5;
+    let ok = true;
+    let min = -9223372036854775807i64 - 1;
+    println(answer);
+    println(min);
+    println(ratio, " ", 1.0e20);
+    println(ok, " ", false);
+    println('x');
+    println("tab\there \"quoted\" back\\slash");
+}
@@ -0,0 +1,10 @@
+// `for` loops over constant ranges, which the lowering unrolls.
+fn main() {
+    for i in 0..3 {
+        println("step ", i);
+    }
+    for n in 1..=2 {
+        let label = "n=";
+        println(label, n);
+    }
+}
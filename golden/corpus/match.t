@@ -0,0 +1,48 @@
+// `match`es on integers and on enum variants only known at run time, which
+// end their blocks in a `Switch`: dense cases make a jump table in C.
+enum Suit { Clubs, Diamonds, Hearts, Spades }
+
+fn main() {
+    let mut i = 0;
+    while i < 8 {
+        match i {
+            0 => println("zero"),
+            1 | 2 => println("one or two"),
+            3 => {
+                println("three");
+            }
+            4 => println("four"),
+            5 => println("five"),
+            n => println("many: ", n),
+        };
+        i += 1;
+    }
+
+    let mut suit = Suit::Clubs;
+    let mut red = 0;
+    for k in 0..i {
+        match suit {
+            Suit::Diamonds | Suit::Hearts => {
+                red += 1;
+            }
+            _ => {}
+        };
+        match k % 3 {
+            0 => {
+                suit = Suit::Hearts;
+            }
+            1 => {
+                suit = Suit::Spades;
+            }
+            _ => {
+                suit = Suit::Diamonds;
+            }
+        };
+    }
+    println("red suits: ", red);
+
+    match Suit::Spades {
+        Suit::Spades => println("spades"),
+        _ => println("not spades"),
+    };
+}
@@ -0,0 +1,17 @@
+// A type alias is another name for its type; a newtype wraps one field
+// and is lowered as that field.
+type Km = f64;
+type Distance = Km;
+struct Meters(f64);
+struct Count(i32);
+
+static MARATHON: Distance = 42.195;
+
+fn main() {
+    let Meters(m) = Meters(1500.0);
+    println(m);
+    let hops = Count(3);
+    let Count(n) = hops;
+    println(n * 2);
+    println(MARATHON);
+}
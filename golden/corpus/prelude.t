@@ -0,0 +1,11 @@
+// The prelude: printing any values, `format` and `assert`, folded by the
+// lowering.
+fn main() {
+    let apples = 3;
+    let line = format("{} apples cost {} each", apples, 0.5);
+    println(line);
+    println(format("{{braces}} and {}", true));
+    print("no newline, ", 'c', "\n");
+    assert(true);
+    println();
+}
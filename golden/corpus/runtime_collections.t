@@ -0,0 +1,12 @@
+// Collections changed in loops decided at run time, which `tstd` holds, so
+// the program calls its functions.
+fn main() {
+    let v = Vec::new();
+    v.push(4);
+    let mut n = 0;
+    while n < 3 {
+        v.push(n * 10);
+        n += 1;
+    }
+    println("len ", v.len(), ", second ", v.get(1), ", last ", v.get(3));
+}
@@ -0,0 +1,15 @@
+// Statics are defined as globals, their initializers folded at compile
+// time; a `static mut` is only used in `unsafe`.
+static GREETING: str = "hello";
+static LIMIT: i32 = 40;
+static RATIO: f64 = 0.5;
+static VERBOSE: bool = true;
+static mut COUNT: i32 = LIMIT + 2;
+
+fn main() {
+    println(GREETING, " from a static");
+    println(LIMIT * 2);
+    println(RATIO);
+    println(VERBOSE);
+    unsafe { println(COUNT); };
+}
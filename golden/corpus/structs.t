@@ -0,0 +1,20 @@
+// A struct with named fields is lowered field by field, so it runs on
+// every backend as the values of its fields do.
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+fn sum(p: Point) -> i64 {
+    p.x + p.y
+}
+
+fn main() {
+    let mut p = Point { x: 1, y: 2 };
+    while p.x < 5 {
+        p.x = p.x + 1;
+    }
+    let q = p;
+    p.y = 10;
+    println(sum(q), " ", sum(p));
+}
@@ -0,0 +1,10 @@
+// A spawned thread, which the lowering runs where it is spawned, and its
+// join, which then has nothing to wait for.
+fn main() {
+    let worker = spawn {
+        let greeting = "from the thread";
+        println(greeting);
+    };
+    join(worker);
+    println("after join");
+}
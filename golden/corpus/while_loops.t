@@ -0,0 +1,36 @@
+// `while`, `loop` and `for` loops whose iterations are only known at run
+// time, with `break` and `continue`, and `if`s on values they assign.
+fn main() -> i32 {
+    let mut total = 0;
+    let mut i = 0;
+    while i < 6 {
+        i += 1;
+        if i == 2 || i == 4 {
+            continue;
+        };
+        total += i;
+    }
+    println("total without 2 and 4 ", total);
+
+    let mut n: u8 = 250;
+    loop {
+        n = n + 2;
+        if n > 253 {
+            break;
+        };
+    }
+    println("stopped at ", n);
+
+    for k in 0..i {
+        defer { println("end of ", k); }
+        if k == 2 {
+            break;
+        } else {
+            println("k ", k);
+        };
+    }
+
+    let big = i > 5 && total != 0;
+    println(big, " ", !big);
+    total - 4
+}
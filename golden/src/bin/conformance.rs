@@ -0,0 +1,44 @@
+//! File: golden/src/bin/conformance.rs
+//! Build and run every corpus program on the backends and compare what they
+//! print with the reference interpreter.
+//!
+//! Usage:
+//!     cargo run -p golden --bin conformance -- [--backend <name>]... [--work-dir <dir>]
+//!         [<program>...]
+//!
+//! Backends default to `c`, `cpp`, `go`, `python` and `rust`. Exits with status 1 if any
+//! backend's program printed something else or failed to build or run.
+
+use anyhow::{bail, Context, Result};
+use golden::conformance::{self, Options};
+use golden::Layout;
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+fn parse_args() -> Result<Options> {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => {
+                let name = args.next().context("--backend requires a backend name")?;
+                options.backends.push(name);
+            }
+            "--work-dir" => {
+                let dir = args.next().context("--work-dir requires a directory path")?;
+                options.work_dir = PathBuf::from(dir);
+            }
+            unknown if unknown.starts_with('-') => bail!("Unrecognized argument: {}", unknown),
+            program => options.programs.push(program.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> Result<ExitCode> {
+    let options = parse_args()?;
+    let report = conformance::run(&Layout::checked_in(), &options)?;
+    println!("{}", report);
+    Ok(if report.is_success() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
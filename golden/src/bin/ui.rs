@@ -0,0 +1,33 @@
+//! File: golden/src/bin/ui.rs
+//! Compile every program in `ui/` and compare the diagnostics with the
+//! `//~ ERROR` and `//~ WARNING` annotations in it.
+//!
+//! Usage:
+//!     cargo run -p golden --bin ui -- [<program>...]
+//!
+//! Exits with status 1 if any program reported a diagnostic it does not
+//! annotate, or missed one it does.
+
+use anyhow::{bail, Result};
+use golden::ui::{self, Options};
+use golden::Layout;
+use std::env;
+use std::process::ExitCode;
+
+fn parse_args() -> Result<Options> {
+    let mut options = Options::default();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            unknown if unknown.starts_with('-') => bail!("Unrecognized argument: {}", unknown),
+            program => options.programs.push(program.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> Result<ExitCode> {
+    let options = parse_args()?;
+    let report = ui::run(&Layout::checked_in(), &options)?;
+    println!("{}", report);
+    Ok(if report.is_success() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
@@ -0,0 +1,345 @@
+// File: golden/src/conformance.rs
+
+//! End-to-end conformance: every corpus program must print the same on each
+//! backend as on the reference interpreter, `plugin_api::interpret`.
+//!
+//! A backend's artifact is written to `<work dir>/<program>/<backend>/` and
+//! its build commands run there in order; the output of the last one, which
+//! runs the program, is compared with the interpreter's, and it must exit
+//! with the interpreter's exit code, as the build commands exit with 0. A
+//! backend whose
+//! toolchain is not installed is skipped rather than failed.
+//!
+//! A program calling `tstd`'s functions, which the interpreter cannot, has
+//! what it prints in `<program>.stdout` beside it, and exits with 0. The
+//! backends that cannot call C functions are skipped on it, and `tstd`'s
+//! static library is put beside an artifact whose build commands link it.
+
+use crate::{lowered_programs, select, Layout};
+use anyhow::{anyhow, Context, Result};
+use plugin_api::{CompiledModule, ErasedBackend};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Backends executed when none are named.
+pub const DEFAULT_BACKENDS: &[&str] = &["c", "cpp", "go", "python", "rust"];
+
+/// Extension of the output of a program the interpreter cannot run.
+const EXPECTED_EXTENSION: &str = "stdout";
+
+/// File name by which build commands link `tstd`'s static library.
+const RUNTIME_LIBRARY: &str = "libtstd.a";
+
+/// Longest a single build or run command may take.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Exit status of `sh` when it cannot find the command.
+const COMMAND_NOT_FOUND: i32 = 127;
+
+/// What to execute.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Backends to execute, by name; `DEFAULT_BACKENDS` when empty.
+    pub backends: Vec<String>,
+    /// Programs to execute, by file stem; all of them when empty.
+    pub programs: Vec<String>,
+    /// Scratch directory the artifacts are built in.
+    pub work_dir: PathBuf,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            backends: Vec::new(),
+            programs: Vec::new(),
+            work_dir: std::env::temp_dir().join("tlang-conformance"),
+        }
+    }
+}
+
+/// How one backend's program compares with the interpreter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// It printed exactly what the interpreter did.
+    Agrees,
+    /// It ran, but printed something else.
+    Diverges { actual: String },
+    /// The backend rejected the program, or the build or run failed.
+    Failed(String),
+    /// The toolchain the artifact needs is not installed.
+    Skipped(String),
+}
+
+/// The execution of one program on one backend.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub program: String,
+    pub backend: String,
+    /// What the interpreter printed.
+    pub expected: String,
+    pub verdict: Verdict,
+}
+
+/// Result of a conformance run.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub executions: Vec<Execution>,
+}
+
+impl Report {
+    /// Executions that diverged from the interpreter or failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Execution> {
+        self.executions
+            .iter()
+            .filter(|e| matches!(e.verdict, Verdict::Diverges { .. } | Verdict::Failed(_)))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut agreed = 0;
+        for execution in &self.executions {
+            let name = format!("{}/{}", execution.program, execution.backend);
+            match &execution.verdict {
+                Verdict::Agrees => agreed += 1,
+                Verdict::Diverges { actual } => {
+                    writeln!(f, "DIVERGES {}", name)?;
+                    writeln!(f, "    interpreter: {:?}", execution.expected)?;
+                    writeln!(f, "    {:<11}: {:?}", execution.backend, actual)?;
+                }
+                Verdict::Failed(reason) => {
+                    writeln!(f, "FAILED   {}", name)?;
+                    for line in reason.lines() {
+                        writeln!(f, "    {}", line)?;
+                    }
+                }
+                Verdict::Skipped(reason) => writeln!(f, "skipped  {}: {}", name, reason)?,
+            }
+        }
+        let count = |wanted: fn(&Verdict) -> bool| {
+            self.executions.iter().filter(|e| wanted(&e.verdict)).count()
+        };
+        write!(
+            f,
+            "{} agree, {} diverge, {} failed, {} skipped",
+            agreed,
+            count(|v| matches!(v, Verdict::Diverges { .. })),
+            count(|v| matches!(v, Verdict::Failed(_))),
+            count(|v| matches!(v, Verdict::Skipped(_))),
+        )
+    }
+}
+
+/// Build and run the corpus of `layout` on each backend in `options`.
+///
+/// Fails only when the run itself cannot proceed: an unknown program or
+/// backend name, a program the interpreter cannot run, or an I/O error.
+pub fn run(layout: &Layout, options: &Options) -> Result<Report> {
+    let wanted: Vec<String> = if options.backends.is_empty() {
+        DEFAULT_BACKENDS.iter().map(|name| name.to_string()).collect()
+    } else {
+        options.backends.clone()
+    };
+    let backends = select("backend", crate::backends(), &wanted, |backend| backend.name())?;
+
+    let mut report = Report::default();
+    for (program, module) in lowered_programs(layout, &options.programs)? {
+        let (expected, exit_code) = match plugin_api::interpret_with_exit_code(&module) {
+            Ok(ran) => ran,
+            Err(err) => {
+                let path = layout.corpus.join(format!("{}.{}", program, EXPECTED_EXTENSION));
+                let expected = fs::read_to_string(&path)
+                    .map_err(|_| anyhow!(err))
+                    .with_context(|| format!("the interpreter cannot run {}", program))?;
+                (expected, 0)
+            }
+        };
+        for backend in &backends {
+            let dir = options.work_dir.join(&program).join(backend.name());
+            let verdict = match execute(*backend, &module, &dir, exit_code)? {
+                Ok(actual) if actual == expected => Verdict::Agrees,
+                Ok(actual) => Verdict::Diverges { actual },
+                Err(verdict) => verdict,
+            };
+            report.executions.push(Execution {
+                program: program.clone(),
+                backend: backend.name().to_string(),
+                expected: expected.clone(),
+                verdict,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Compile `module` with `backend` into `dir`, run its build commands and
+/// return what the last one printed, exiting with `exit_code`, or why there
+/// is nothing to compare.
+fn execute(
+    backend: &dyn ErasedBackend,
+    module: &CompiledModule,
+    dir: &Path,
+    exit_code: i32,
+) -> Result<std::result::Result<String, Verdict>> {
+    let artifact = match backend.compile_erased(module.clone()) {
+        Ok(artifact) => artifact,
+        Err(err) if err.to_string().contains("cannot call extern function") => {
+            return Ok(Err(Verdict::Skipped("the backend cannot call C functions".to_string())));
+        }
+        Err(err) => return Ok(Err(Verdict::Failed(err.to_string()))),
+    };
+    let Some((run, build)) = artifact.build_commands.split_last() else {
+        return Ok(Err(Verdict::Skipped("the artifact has no run command".to_string())));
+    };
+
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    for (name, data) in artifact.files() {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("cannot create {}", parent.display()))?;
+        }
+        fs::write(&path, data).with_context(|| format!("cannot write {}", path.display()))?;
+    }
+    if artifact.build_commands.iter().any(|command| command.contains(RUNTIME_LIBRARY)) {
+        let Some(runtime) = compiler::link::runtime_library() else {
+            return Ok(Err(Verdict::Skipped("tstd's static library is not built".to_string())));
+        };
+        let path = dir.join(RUNTIME_LIBRARY);
+        fs::copy(&runtime, &path).with_context(|| format!("cannot copy {} to {}", runtime.display(), path.display()))?;
+    }
+
+    for command in build {
+        if let Err(verdict) = shell(dir, command, 0)? {
+            return Ok(Err(verdict));
+        }
+    }
+    shell(dir, run, exit_code)
+}
+
+/// Run `command` with `sh` in `dir` and return its stdout, or the verdict
+/// when it could not run or did not exit with `exit_code`.
+fn shell(dir: &Path, command: &str, exit_code: i32) -> Result<std::result::Result<String, Verdict>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("cannot start sh")?;
+
+    // Drain both pipes while waiting, so a chatty command cannot block on them.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > COMMAND_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            let reason = format!("`{}` did not finish within {:?}", command, COMMAND_TIMEOUT);
+            return Ok(Err(Verdict::Failed(reason)));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    match status.code() {
+        Some(code) if code == exit_code => Ok(Ok(stdout)),
+        Some(COMMAND_NOT_FOUND) => {
+            let tool = command.split_whitespace().next().unwrap_or(command);
+            Ok(Err(Verdict::Skipped(format!("`{}` is not installed", tool))))
+        }
+        _ if exit_code != 0 => Ok(Err(Verdict::Failed(format!(
+            "`{}` failed ({}, expected exit status: {})\n{}",
+            command,
+            status,
+            exit_code,
+            stderr.trim_end()
+        )))),
+        _ => Ok(Err(Verdict::Failed(format!("`{}` failed ({})\n{}", command, status, stderr.trim_end())))),
+    }
+}
+
+/// Read `pipe` to the end on another thread.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("conformance-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn shell_returns_stdout_or_a_verdict() {
+        let dir = scratch("shell");
+        assert_eq!(shell(&dir, "printf 'a\\nb'", 0).unwrap(), Ok("a\nb".to_string()));
+        assert!(matches!(
+            shell(&dir, "no-such-tool-anywhere --version", 0).unwrap(),
+            Err(Verdict::Skipped(reason)) if reason.contains("no-such-tool-anywhere")
+        ));
+        assert!(matches!(
+            shell(&dir, "echo broken >&2; exit 3", 0).unwrap(),
+            Err(Verdict::Failed(reason)) if reason.contains("broken")
+        ));
+        assert_eq!(shell(&dir, "echo done; exit 3", 3).unwrap(), Ok("done\n".to_string()));
+        assert!(matches!(
+            shell(&dir, "exit 0", 3).unwrap(),
+            Err(Verdict::Failed(reason)) if reason.contains("expected exit status: 3")
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn divergence_is_reported_with_both_outputs() {
+        let report = Report {
+            executions: vec![Execution {
+                program: "hello".to_string(),
+                backend: "c".to_string(),
+                expected: "hi\n".to_string(),
+                verdict: Verdict::Diverges { actual: "ho\n".to_string() },
+            }],
+        };
+        assert!(!report.is_success());
+        assert_eq!(
+            report.to_string(),
+            "DIVERGES hello/c\n    interpreter: \"hi\\n\"\n    c          : \"ho\\n\"\n\
+             0 agree, 1 diverge, 0 failed, 0 skipped"
+        );
+    }
+
+    #[test]
+    fn unknown_backends_are_errors() {
+        let options = Options { backends: vec!["no-such-backend".into()], ..Options::default() };
+        let err = run(&Layout::checked_in(), &options).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown backend: no-such-backend");
+    }
+}
@@ -0,0 +1,468 @@
+// File: golden/src/lib.rs
+
+//! Golden tests for the codegen backends.
+//!
+//! Every program in `corpus/` is parsed, lowered and compiled by each
+//! registered backend, and the artifact, rendered as text by [`render`], is
+//! compared with `snapshots/<program>/<backend>.snap`. Blessing writes the
+//! current output over the snapshots instead, so a codegen change shows up
+//! as a diff to checked-in files.
+//!
+//! [`conformance`] builds and runs the same programs, checking that each
+//! backend's program prints what the reference interpreter does.
+//!
+//! [`ui`] compiles the programs in `ui/`, checking that the diagnostics
+//! each produces are the ones its `//~` comments expect.
+
+use anyhow::{anyhow, bail, Context, Result};
+use plugin_api::{ArtifactFormat, CompiledArtifact, CompiledModule, ErasedBackend};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod conformance;
+pub mod ui;
+
+/// Extension of corpus programs.
+const PROGRAM_EXTENSION: &str = "t";
+
+/// Extension of snapshot files.
+const SNAPSHOT_EXTENSION: &str = "snap";
+
+/// Bytes per line of a hex dump.
+const HEX_LINE_BYTES: usize = 32;
+
+/// Most lines of each side shown for a changed snapshot.
+const DIFF_LINES: usize = 20;
+
+/// Where the programs and their snapshots live.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Directory of `.t` programs.
+    pub corpus: PathBuf,
+    /// Directory of per-program snapshot directories.
+    pub snapshots: PathBuf,
+    /// Directory of `.t` programs with annotated diagnostics.
+    pub ui: PathBuf,
+}
+
+impl Layout {
+    /// `corpus/`, `snapshots/` and `ui/` under `root`.
+    pub fn at(root: &Path) -> Self {
+        Layout {
+            corpus: root.join("corpus"),
+            snapshots: root.join("snapshots"),
+            ui: root.join("ui"),
+        }
+    }
+
+    /// The checked-in corpus of this crate.
+    pub fn checked_in() -> Self {
+        Self::at(Path::new(env!("CARGO_MANIFEST_DIR")))
+    }
+
+    fn snapshot(&self, program: &str, backend: &str) -> PathBuf {
+        self.snapshots
+            .join(program)
+            .join(format!("{}.{}", backend, SNAPSHOT_EXTENSION))
+    }
+}
+
+/// What to compare.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Overwrite the snapshots with the current output.
+    pub bless: bool,
+    /// Backends to run, by name; all of them when empty.
+    pub backends: Vec<String>,
+    /// Programs to compile, by file stem; all of them when empty.
+    pub programs: Vec<String>,
+}
+
+/// How one snapshot compares with the current output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The output matches the snapshot.
+    Unchanged,
+    /// There is no snapshot yet.
+    New,
+    /// The output differs from the snapshot, which held `expected`.
+    Changed { expected: String },
+    /// Nothing produces the snapshot any more.
+    Obsolete,
+}
+
+/// The comparison for one program on one backend.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub program: String,
+    pub backend: String,
+    pub outcome: Outcome,
+    /// The current output; empty for an obsolete snapshot.
+    pub actual: String,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        self.outcome == Outcome::Unchanged
+    }
+}
+
+/// Result of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+    /// The snapshots were overwritten, so differences are not failures.
+    pub blessed: bool,
+}
+
+impl Report {
+    /// The checks whose snapshot is out of date.
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !check.passed())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.blessed || self.failures().next().is_none()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in self.failures() {
+            let state = match (&check.outcome, self.blessed) {
+                (Outcome::Obsolete, true) => "removed",
+                (_, true) => "blessed",
+                (Outcome::New, false) => "NEW",
+                (Outcome::Changed { .. }, false) => "CHANGED",
+                (Outcome::Obsolete, false) => "OBSOLETE",
+                (Outcome::Unchanged, false) => unreachable!("failures() skips passed checks"),
+            };
+            writeln!(f, "{:<8} {}/{}", state, check.program, check.backend)?;
+            if let (Outcome::Changed { expected }, false) = (&check.outcome, self.blessed) {
+                write!(f, "{}", diff(expected, &check.actual))?;
+            }
+        }
+        let count = |wanted: fn(&Outcome) -> bool| {
+            self.checks.iter().filter(|check| wanted(&check.outcome)).count()
+        };
+        write!(
+            f,
+            "{} unchanged, {} changed, {} new, {} obsolete",
+            count(|o| *o == Outcome::Unchanged),
+            count(|o| matches!(o, Outcome::Changed { .. })),
+            count(|o| *o == Outcome::New),
+            count(|o| *o == Outcome::Obsolete),
+        )?;
+        if !self.is_success() {
+            write!(f, "\nrun `cargo run -p golden -- --bless` to accept the current output")?;
+        }
+        Ok(())
+    }
+}
+
+/// The backends under test, sorted by name: every registered backend that
+/// emits an artifact rather than running the program in‑process.
+pub fn backends() -> Vec<&'static dyn ErasedBackend> {
+    compiler::backends::register_builtin_backends();
+    let mut backends: Vec<_> = plugin_api::list_backends()
+        .into_iter()
+        .filter(|backend| !backend.capabilities().supports_jit)
+        .collect();
+    backends.sort_by_key(|backend| backend.name());
+    backends
+}
+
+/// Parse and lower `source`, which debug info attributes to `file`.
+pub fn lower(source: &str, file: &str) -> Result<CompiledModule> {
+    let program = compiler::parse_source(source).map_err(|e| anyhow!("{}: {}", file, e))?;
+    compiler::lower_program(&program, source, file).with_context(|| format!("cannot lower {}", file))
+}
+
+/// The snapshot text of an artifact: its format and build commands, then
+/// each file. Binary contents are written as hex.
+pub fn render(artifact: &CompiledArtifact) -> String {
+    let mut out = format!("format: {:?}\n", artifact.format);
+    for command in &artifact.build_commands {
+        out.push_str(&format!("build: {}\n", command));
+    }
+    for (index, (name, data)) in artifact.files().enumerate() {
+        out.push_str(&format!("\n==== {}\n", name));
+        let binary = index == 0 && artifact.format == ArtifactFormat::Binary;
+        match std::str::from_utf8(data) {
+            Ok(text) if !binary => {
+                out.push_str(text);
+                if !text.is_empty() && !text.ends_with('\n') {
+                    out.push_str("\n(no newline at end of file)\n");
+                }
+            }
+            _ => {
+                for line in data.chunks(HEX_LINE_BYTES) {
+                    let hex: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    out.push_str(&hex.join(" "));
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    out
+}
+
+/// The current snapshot text of `program` on `backend`; a backend that
+/// rejects the program snapshots its error.
+pub fn snapshot(backend: &dyn ErasedBackend, module: &CompiledModule) -> String {
+    match backend.compile_erased(module.clone()) {
+        Ok(artifact) => render(&artifact),
+        Err(err) => format!("error: {}\n", err),
+    }
+}
+
+/// Compare (or, with `options.bless`, overwrite) the snapshots in `layout`.
+///
+/// Fails only when the run itself cannot proceed: an unknown program or
+/// backend name, a program that does not compile, or an I/O error.
+pub fn run(layout: &Layout, options: &Options) -> Result<Report> {
+    let programs = lowered_programs(layout, &options.programs)?;
+    let backends = select("backend", backends(), &options.backends, |backend| backend.name())?;
+
+    let mut report = Report { checks: Vec::new(), blessed: options.bless };
+    for (program, module) in &programs {
+        for backend in &backends {
+            let actual = snapshot(*backend, module);
+            let path = layout.snapshot(program, backend.name());
+            let outcome = match fs::read_to_string(&path) {
+                Ok(expected) if expected == actual => Outcome::Unchanged,
+                Ok(expected) => Outcome::Changed { expected },
+                Err(_) => Outcome::New,
+            };
+            if options.bless && outcome != Outcome::Unchanged {
+                write_snapshot(&path, &actual)?;
+            }
+            report.checks.push(Check {
+                program: program.clone(),
+                backend: backend.name().to_string(),
+                outcome,
+                actual,
+            });
+        }
+    }
+
+    // Only a full run knows which snapshots nothing produces.
+    if options.programs.is_empty() && options.backends.is_empty() {
+        let produced: BTreeSet<PathBuf> = report
+            .checks
+            .iter()
+            .map(|check| layout.snapshot(&check.program, &check.backend))
+            .collect();
+        for (program, backend, path) in snapshots(&layout.snapshots)? {
+            if produced.contains(&path) {
+                continue;
+            }
+            if options.bless {
+                remove_snapshot(&path)?;
+            }
+            report.checks.push(Check {
+                program,
+                backend,
+                outcome: Outcome::Obsolete,
+                actual: String::new(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// The corpus programs named in `wanted` (all when empty), lowered, as
+/// `(name, module)` sorted by name.
+pub(crate) fn lowered_programs(layout: &Layout, wanted: &[String]) -> Result<Vec<(String, CompiledModule)>> {
+    let programs = select("program", programs(&layout.corpus)?, wanted, |(name, _)| name.as_str())?;
+    programs
+        .into_iter()
+        .map(|(program, path)| {
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("cannot read {}", path.display()))?;
+            let module = lower(&source, &format!("corpus/{}.{}", program, PROGRAM_EXTENSION))?;
+            Ok((program, module))
+        })
+        .collect()
+}
+
+/// The programs in `dir` as `(file stem, path)`, sorted by name.
+pub(crate) fn programs(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(PROGRAM_EXTENSION) {
+            continue;
+        }
+        if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+            programs.push((stem.to_string(), path.clone()));
+        }
+    }
+    programs.sort();
+    Ok(programs)
+}
+
+/// Every snapshot under `dir` as `(program, backend, path)`.
+fn snapshots(dir: &Path) -> Result<Vec<(String, String, PathBuf)>> {
+    let mut snapshots = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(snapshots);
+    };
+    for entry in entries {
+        let program_dir = entry?.path();
+        let Some(program) = program_dir.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !program_dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&program_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SNAPSHOT_EXTENSION) {
+                continue;
+            }
+            if let Some(backend) = path.file_stem().and_then(|stem| stem.to_str()) {
+                snapshots.push((program.to_string(), backend.to_string(), path.clone()));
+            }
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// The items named in `wanted`, or all of them when it is empty.
+pub(crate) fn select<T>(kind: &str, items: Vec<T>, wanted: &[String], name: impl Fn(&T) -> &str) -> Result<Vec<T>> {
+    if let Some(unknown) = wanted.iter().find(|w| !items.iter().any(|item| name(item) == *w)) {
+        bail!("Unknown {}: {}", kind, unknown);
+    }
+    Ok(items
+        .into_iter()
+        .filter(|item| wanted.is_empty() || wanted.iter().any(|w| w == name(item)))
+        .collect())
+}
+
+fn write_snapshot(path: &Path, text: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    }
+    fs::write(path, text).with_context(|| format!("cannot write {}", path.display()))
+}
+
+/// Delete a snapshot, and its program's directory once that is empty.
+fn remove_snapshot(path: &Path) -> Result<()> {
+    fs::remove_file(path).with_context(|| format!("cannot remove {}", path.display()))?;
+    if let Some(dir) = path.parent() {
+        // Fails, harmlessly, while other snapshots remain.
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(())
+}
+
+/// The lines that differ between `expected` and `actual`, once their common
+/// beginning and end are cut off.
+fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut out = format!("    @@ line {} @@\n", prefix + 1);
+    for (sign, lines) in [('-', &old[prefix..old.len() - suffix]), ('+', &new[prefix..new.len() - suffix])] {
+        for line in lines.iter().take(DIFF_LINES) {
+            out.push_str(&format!("    {} {}\n", sign, line));
+        }
+        if lines.len() > DIFF_LINES {
+            out.push_str(&format!("    {} ... {} more lines\n", sign, lines.len() - DIFF_LINES));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory with one corpus program.
+    fn scratch(name: &str) -> Layout {
+        let root = std::env::temp_dir().join(format!("golden-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let layout = Layout::at(&root);
+        fs::create_dir_all(&layout.corpus).unwrap();
+        fs::write(layout.corpus.join("hello.t"), "fn main() { println(\"hi\"); }\n").unwrap();
+        layout
+    }
+
+    #[test]
+    fn renders_text_and_binary_files() {
+        let artifact = CompiledArtifact::new("bin", vec![0, 1, 255])
+            .with_file("notes.txt", b"line".to_vec())
+            .with_build_command("run it");
+        assert_eq!(
+            render(&artifact),
+            "format: Binary\nbuild: run it\n\n==== bin.bin\n00 01 ff\n\n\
+             ==== notes.txt\nline\n(no newline at end of file)\n"
+        );
+    }
+
+    #[test]
+    fn diff_shows_only_the_changed_lines() {
+        let shown = diff("a\nb\nc\nd\n", "a\nB\nc\nd\n");
+        assert_eq!(shown, "    @@ line 2 @@\n    - b\n    + B\n");
+    }
+
+    #[test]
+    fn bless_then_check_round_trips() {
+        let layout = scratch("round-trip");
+        let first = run(&layout, &Options::default()).unwrap();
+        assert!(!first.is_success());
+        assert!(first.checks.iter().all(|check| check.outcome == Outcome::New));
+
+        let blessed = run(&layout, &Options { bless: true, ..Options::default() }).unwrap();
+        assert!(blessed.is_success());
+        let checked = run(&layout, &Options::default()).unwrap();
+        assert!(checked.is_success(), "{}", checked);
+        assert_eq!(checked.checks.len(), backends().len());
+        let _ = fs::remove_dir_all(layout.corpus.parent().unwrap());
+    }
+
+    #[test]
+    fn reports_changed_and_obsolete_snapshots() {
+        let layout = scratch("stale");
+        run(&layout, &Options { bless: true, ..Options::default() }).unwrap();
+        let backend = backends()[0].name();
+        fs::write(layout.snapshot("hello", backend), "format: Source\n").unwrap();
+        write_snapshot(&layout.snapshot("gone", backend), "").unwrap();
+
+        let report = run(&layout, &Options::default()).unwrap();
+        let outcome = |program: &str| {
+            report
+                .checks
+                .iter()
+                .find(|check| check.program == program && check.backend == backend)
+                .map(|check| check.outcome.clone())
+        };
+        assert!(matches!(outcome("hello"), Some(Outcome::Changed { .. })));
+        assert_eq!(outcome("gone"), Some(Outcome::Obsolete));
+
+        run(&layout, &Options { bless: true, ..Options::default() }).unwrap();
+        assert!(!layout.snapshots.join("gone").exists());
+        assert!(run(&layout, &Options::default()).unwrap().is_success());
+        let _ = fs::remove_dir_all(layout.corpus.parent().unwrap());
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let layout = scratch("unknown");
+        let options = Options { backends: vec!["no-such-backend".into()], ..Options::default() };
+        let err = run(&layout, &options).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown backend: no-such-backend");
+        let _ = fs::remove_dir_all(layout.corpus.parent().unwrap());
+    }
+}
@@ -0,0 +1,38 @@
+//! File: golden/src/main.rs
+//! Compare every backend's output for the corpus with the snapshots.
+//!
+//! Usage:
+//!     cargo run -p golden -- [--bless] [--backend <name>]... [<program>...]
+//!
+//! Without `--bless`, prints each snapshot that is out of date with a diff
+//! and exits with status 1 if there are any. With it, overwrites those
+//! snapshots and deletes the ones nothing produces any more.
+
+use anyhow::{bail, Context, Result};
+use golden::{Layout, Options};
+use std::env;
+use std::process::ExitCode;
+
+fn parse_args() -> Result<Options> {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bless" => options.bless = true,
+            "--backend" => {
+                let name = args.next().context("--backend requires a backend name")?;
+                options.backends.push(name);
+            }
+            unknown if unknown.starts_with('-') => bail!("Unrecognized argument: {}", unknown),
+            program => options.programs.push(program.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> Result<ExitCode> {
+    let options = parse_args()?;
+    let report = golden::run(&Layout::checked_in(), &options)?;
+    println!("{}", report);
+    Ok(if report.is_success() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
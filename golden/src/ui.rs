@@ -0,0 +1,265 @@
+// File: golden/src/ui.rs
+
+//! Diagnostic tests, like rustc's UI tests: every program in `ui/` is
+//! compiled, and the diagnostics it produces must be the ones its comments
+//! announce.
+//!
+//! An annotation is a comment starting with `//~`, then `ERROR` or
+//! `WARNING` and text the diagnostic's message must contain:
+//!
+//! ```text
+//! let flag: i32 = true; //~ ERROR Type mismatch
+//! ```
+//!
+//! It expects the diagnostic on its own line. Each `^` after the `~` moves
+//! that one line up, and `//~|` expects it on the same line as the
+//! annotation before. Every diagnostic must be annotated, and every
+//! annotation met by a diagnostic.
+//!
+//! The diagnostics are what the compiler reports on its way to lowering:
+//! every syntax error, or else the first type error or the lint warnings,
+//! and why the program could not be lowered. A diagnostic with no place in
+//! the source, as lowering's are, is on line 1.
+
+use crate::{programs, select, Layout};
+use anyhow::{bail, Context, Result};
+use compiler::{Database, Severity};
+use std::fmt;
+use std::fs;
+
+/// What an annotation or diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Error => "ERROR",
+            Kind::Warning => "WARNING",
+        })
+    }
+}
+
+/// A diagnostic a program expects, or one the compiler reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The one-based line it is on
+    pub line: usize,
+    pub kind: Kind,
+    /// For an annotation, text the message must contain
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.line, self.kind, self.message)
+    }
+}
+
+/// The diagnostics the `//~` comments of `source` expect.
+///
+/// Fails on an annotation that names no kind or points before the first
+/// line, and on a `//~|` with no annotation before it.
+pub fn annotations(source: &str) -> Result<Vec<Diagnostic>> {
+    let mut expected: Vec<Diagnostic> = Vec::new();
+    for (index, text) in source.lines().enumerate() {
+        let Some(start) = text.find("//~") else { continue };
+        let rest = &text[start + 3..];
+        let line = index + 1;
+        let (line, rest) = if let Some(rest) = rest.strip_prefix('|') {
+            let previous = expected.last().with_context(|| format!("line {}: `//~|` follows no annotation", line))?;
+            (previous.line, rest)
+        } else {
+            let carets = rest.len() - rest.trim_start_matches('^').len();
+            if carets >= line {
+                bail!("line {}: the annotation points before the first line", line);
+            }
+            (line - carets, &rest[carets..])
+        };
+        let rest = rest.trim();
+        let (kind, message) = match rest.split_once(char::is_whitespace).unwrap_or((rest, "")) {
+            ("ERROR", message) => (Kind::Error, message),
+            ("WARNING", message) => (Kind::Warning, message),
+            _ => bail!("line {}: an annotation must start with ERROR or WARNING", line),
+        };
+        expected.push(Diagnostic { line, kind, message: message.trim().to_string() });
+    }
+    Ok(expected)
+}
+
+/// The diagnostics compiling `source` reports, in the order reported.
+pub fn diagnostics(source: &str, file: &str) -> Vec<Diagnostic> {
+    let mut db = Database::new();
+    let id = db.file(file);
+    db.set_text(id, source);
+    // Where a diagnostic starts, and its message.
+    let diagnostic = |kind, offset: Option<usize>, message: String| Diagnostic {
+        line: offset.map_or(1, |offset| source[..offset.min(source.len())].matches('\n').count() + 1),
+        kind,
+        message: message.lines().next().unwrap_or_default().to_string(),
+    };
+
+    let parsed = db.parse(id);
+    if !parsed.errors.is_empty() {
+        return parsed
+            .errors
+            .iter()
+            .map(|err| diagnostic(Kind::Error, err.span().map(|span| span.offset()), err.to_string()))
+            .collect();
+    }
+    if let Some(err) = &db.type_check(id).error {
+        return vec![diagnostic(Kind::Error, err.span().map(|span| span.offset()), err.to_string())];
+    }
+    let lints = db.lint(id);
+    let mut diagnostics: Vec<Diagnostic> = lints
+        .diagnostics()
+        .iter()
+        .map(|(severity, lint)| {
+            let kind = if *severity == Severity::Warning { Kind::Warning } else { Kind::Error };
+            diagnostic(kind, lint.span().map(|span| span.offset()), lint.to_string())
+        })
+        .collect();
+    if let Err(err) = &*db.lower(id) {
+        diagnostics.push(diagnostic(Kind::Error, None, err.to_string()));
+    }
+    diagnostics
+}
+
+/// How the diagnostics of one program compare with its annotations.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub program: String,
+    /// Annotations no diagnostic met
+    pub missing: Vec<Diagnostic>,
+    /// Diagnostics no annotation announced
+    pub unexpected: Vec<Diagnostic>,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// Compare the diagnostics of `program`, whose text is `source`, with its
+/// annotations. Each diagnostic meets the first annotation left on its
+/// line, of its kind, whose text its message contains.
+pub fn check(program: &str, source: &str, file: &str) -> Result<Check> {
+    let mut missing = annotations(source).with_context(|| format!("bad annotation in {}", file))?;
+    let mut unexpected = Vec::new();
+    for diagnostic in diagnostics(source, file) {
+        let met = missing.iter().position(|annotation| {
+            annotation.line == diagnostic.line
+                && annotation.kind == diagnostic.kind
+                && diagnostic.message.contains(&annotation.message)
+        });
+        match met {
+            Some(index) => {
+                missing.remove(index);
+            }
+            None => unexpected.push(diagnostic),
+        }
+    }
+    Ok(Check { program: program.to_string(), missing, unexpected })
+}
+
+/// What to check.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Programs to check, by file stem; all of them when empty.
+    pub programs: Vec<String>,
+}
+
+/// Result of a run.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Programs whose diagnostics differ from their annotations.
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !check.passed())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in self.failures() {
+            writeln!(f, "FAILED   {}", check.program)?;
+            for annotation in &check.missing {
+                writeln!(f, "    expected   {}", annotation)?;
+            }
+            for diagnostic in &check.unexpected {
+                writeln!(f, "    unexpected {}", diagnostic)?;
+            }
+        }
+        let failed = self.failures().count();
+        write!(f, "{} passed, {} failed", self.checks.len() - failed, failed)
+    }
+}
+
+/// Check the programs in `layout.ui` that `options` select.
+///
+/// Fails only when the run itself cannot proceed: an unknown program name,
+/// a malformed annotation, or an I/O error.
+pub fn run(layout: &Layout, options: &Options) -> Result<Report> {
+    let programs = select("program", programs(&layout.ui)?, &options.programs, |(name, _)| name.as_str())?;
+    let mut report = Report::default();
+    for (program, path) in programs {
+        let source = fs::read_to_string(&path).with_context(|| format!("cannot read {}", path.display()))?;
+        report.checks.push(check(&program, &source, &format!("ui/{}.t", program))?);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(line: usize, kind: Kind, message: &str) -> Diagnostic {
+        Diagnostic { line, kind, message: message.to_string() }
+    }
+
+    #[test]
+    fn annotations_point_at_their_line_or_above() {
+        let source = "fn main() {\n    let x = 1; //~ WARNING Unused variable\n    //~^ ERROR one\n    \
+                      //~^^ ERROR two\n    //~| WARNING three\n}\n";
+        assert_eq!(
+            annotations(source).unwrap(),
+            [
+                diagnostic(2, Kind::Warning, "Unused variable"),
+                diagnostic(2, Kind::Error, "one"),
+                diagnostic(2, Kind::Error, "two"),
+                diagnostic(2, Kind::Warning, "three"),
+            ]
+        );
+        assert!(annotations("//~^ ERROR above").is_err());
+        assert!(annotations("//~| ERROR nothing before").is_err());
+        assert!(annotations("fn main() {} //~ NOTE unknown").is_err());
+    }
+
+    #[test]
+    fn diagnostics_are_matched_with_annotations() {
+        let source = "fn main() {\n    let x = 1; //~ WARNING Unused variable `x`\n}\n";
+        assert!(check("unused", source, "unused.t").unwrap().passed());
+
+        let source = "fn main() {\n    let x = 1;\n} //~ ERROR never reported\n";
+        let check = check("wrong", source, "wrong.t").unwrap();
+        assert_eq!(check.missing, [diagnostic(3, Kind::Error, "never reported")]);
+        assert_eq!(check.unexpected, [diagnostic(2, Kind::Warning, "Unused variable `x`")]);
+        let report = Report { checks: vec![check] };
+        assert_eq!(
+            report.to_string(),
+            "FAILED   wrong\n    expected   3: ERROR never reported\n    \
+             unexpected 2: WARNING Unused variable `x`\n0 passed, 1 failed"
+        );
+    }
+}
@@ -0,0 +1,19 @@
+// File: golden/tests/conformance.rs
+
+//! Every corpus program must print the same on the C, C++, Go, Python and Rust
+//! backends as on the interpreter. Backends without a toolchain are skipped.
+
+use golden::conformance::{self, Options};
+use golden::Layout;
+
+#[test]
+fn backends_agree_with_the_interpreter() {
+    let options = Options {
+        work_dir: std::env::temp_dir().join(format!("tlang-conformance-{}", std::process::id())),
+        ..Options::default()
+    };
+    let report = conformance::run(&Layout::checked_in(), &options).unwrap();
+    println!("{}", report);
+    let _ = std::fs::remove_dir_all(&options.work_dir);
+    assert!(report.is_success(), "{}", report);
+}
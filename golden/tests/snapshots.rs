@@ -0,0 +1,11 @@
+// File: golden/tests/snapshots.rs
+
+//! The checked-in snapshots must match what the backends generate today.
+
+use golden::{Layout, Options};
+
+#[test]
+fn backends_match_checked_in_snapshots() {
+    let report = golden::run(&Layout::checked_in(), &Options::default()).unwrap();
+    assert!(report.is_success(), "{}", report);
+}
@@ -0,0 +1,12 @@
+// File: golden/tests/ui.rs
+
+//! Every program in `ui/` must report exactly the diagnostics it annotates.
+
+use golden::ui::{self, Options};
+use golden::Layout;
+
+#[test]
+fn diagnostics_match_annotations() {
+    let report = ui::run(&Layout::checked_in(), &Options::default()).unwrap();
+    assert!(report.is_success(), "{}", report);
+}
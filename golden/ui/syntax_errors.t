@@ -0,0 +1,6 @@
+// Every syntax error is reported, not just the first.
+fn main() {
+    let = 1; //~ ERROR Unexpected `'='`
+    print(2);
+    let x 3; //~ ERROR Unexpected `3`
+}
@@ -0,0 +1,6 @@
+// A value of the wrong type for its annotation.
+fn main() {
+    let count: i32 = 1;
+    let flag: bool = count; //~ ERROR expected `bool`, found `i32`
+    print(flag);
+}
@@ -0,0 +1,5 @@
+// A name nothing declares; the annotation points up at it.
+fn main() {
+    print(count);
+    //~^ ERROR Undefined variable: count
+}
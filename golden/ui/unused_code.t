@@ -0,0 +1,13 @@
+// Warnings about code nothing uses; names starting with `_` are exempt.
+use std::fs; //~ WARNING Unused import `fs`
+
+fn main() {
+    let total = 1; //~ WARNING Unused variable `total`
+    let _ignored = 2;
+    print(3);
+}
+
+fn helper(x: i32) -> i32 { //~ WARNING Function `helper` is never used
+    //~| WARNING Unused parameter `x`
+    4
+}
@@ -0,0 +1,102 @@
+//! The standard library functions `interpret` calls a module's `CallExtern`s
+//! of, as the JIT links them: `env`, `io`, `process`, `random` and `time`,
+//! run by `tstd`'s own code rather than its C exports. What they print to
+//! stdout goes where the program's `print`s do. Other extern functions,
+//! such as the collections' and those a program declares, need native
+//! code to call.
+
+use crate::{report_panic, BackendError, ABORT_EXIT_CODE, PANIC_EXIT_CODE};
+use shared::tir::Constant;
+use std::time::Duration;
+
+/// What calling an extern function did.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Called {
+    /// It returned, with its result if it has one.
+    Returned(Option<Constant>),
+    /// It ended the program with this exit code.
+    Exited(i32),
+}
+
+/// Call the extern function `name` with `args`, appending what it prints
+/// to `out`.
+///
+/// # Errors
+/// Fails for an extern function that is not one of the intrinsics, or is
+/// given arguments of the wrong types.
+pub fn call(name: &str, args: &[Constant], out: &mut String) -> Result<Called, BackendError> {
+    let returned = |value: Constant| Ok(Called::Returned(Some(value)));
+    let str = |value: String| returned(Constant::Str(value));
+    match (name, args) {
+        ("tlang_env_arg_count", []) => returned(Constant::Int(tstd::env::args().len() as i64)),
+        ("tlang_env_arg", [Constant::Int(index)]) => {
+            let arg = usize::try_from(*index).ok().and_then(|i| tstd::env::args().into_iter().nth(i));
+            str(arg.unwrap_or_default())
+        }
+        ("tlang_env_var", [Constant::Str(name)]) => str(tstd::env::var(name).unwrap_or_default()),
+        ("tlang_io_read_file", [Constant::Str(path)]) => str(tstd::io::read_file(path).unwrap_or_default()),
+        ("tlang_io_write_file", [Constant::Str(path), Constant::Str(contents)]) => {
+            returned(Constant::Bool(tstd::io::write_file(path, contents).is_ok()))
+        }
+        ("tlang_io_read_line", []) => {
+            let mut line = String::new();
+            let _ = std::io::stdin().read_line(&mut line);
+            str(line.trim_end_matches(['\n', '\r']).to_string())
+        }
+        ("tlang_io_write", [Constant::Str(text)]) => {
+            out.push_str(text);
+            Ok(Called::Returned(None))
+        }
+        ("tlang_eprint", [Constant::Str(text)]) => {
+            eprint!("{}", text);
+            Ok(Called::Returned(None))
+        }
+        ("tlang_process_exit", [Constant::Int(code)]) => Ok(Called::Exited(*code as i32)),
+        ("tlang_process_spawn", [Constant::Str(command)]) => {
+            returned(Constant::Int(i64::from(tstd::process::run(command).unwrap_or(-1))))
+        }
+        ("tlang_panic" | "tlang_abort", [Constant::Str(message), Constant::Str(backtrace)]) => {
+            report_panic(message, backtrace);
+            Ok(Called::Exited(if name == "tlang_abort" { ABORT_EXIT_CODE } else { PANIC_EXIT_CODE }))
+        }
+        ("tlang_random_seed", [Constant::Int(seed)]) => {
+            tstd::random::seed(*seed as u64);
+            Ok(Called::Returned(None))
+        }
+        ("tlang_random_next", []) => returned(Constant::Int(tstd::random::tlang_random_next())),
+        ("tlang_random_below", [Constant::Int(n)]) => returned(Constant::Int(tstd::random::tlang_random_below(*n))),
+        ("tlang_time_now_ms", []) => returned(Constant::Int(tstd::time::elapsed().as_millis() as i64)),
+        ("tlang_time_sleep_ms", [Constant::Int(ms)]) => {
+            tstd::time::sleep(Duration::from_millis((*ms).max(0) as u64));
+            Ok(Called::Returned(None))
+        }
+        _ => Err(BackendError::Generic(format!(
+            "cannot call extern function `{}` without compiling to native code",
+            name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intrinsics_run_without_native_code() {
+        let mut out = String::new();
+        let path = std::env::temp_dir().join(format!("tlang-intrinsics-{}", std::process::id()));
+        let path = Constant::Str(path.display().to_string());
+        let written = call("tlang_io_write_file", &[path.clone(), Constant::Str("text".into())], &mut out);
+        assert_eq!(written.unwrap(), Called::Returned(Some(Constant::Bool(true))));
+        let read = call("tlang_io_read_file", std::slice::from_ref(&path), &mut out);
+        assert_eq!(read.unwrap(), Called::Returned(Some(Constant::Str("text".into()))));
+        if let Constant::Str(path) = path {
+            let _ = std::fs::remove_file(path);
+        }
+
+        assert_eq!(call("tlang_io_write", &[Constant::Str("hi".into())], &mut out).unwrap(), Called::Returned(None));
+        assert_eq!(out, "hi");
+        assert_eq!(call("tlang_process_exit", &[Constant::Int(3)], &mut out).unwrap(), Called::Exited(3));
+        assert!(call("tlang_vec_new", &[], &mut out).unwrap_err().to_string().contains("native code"));
+    }
+}
@@ -2,24 +2,585 @@
 //!
 //! This crate exposes:
 //! - `CompiledModule`: holds raw bytecode and structured instructions.
-//! - `Instruction`: an enum of bytecode operations.
-//! - `Backend` trait: for pluggable codegen backends.
-//! - Registration functions to register and list backends.
+//! - `Instruction`: an enum of bytecode operations, with `float_to_int` /
+//!   `wrap_int` defining what the cast instructions compute and `interpret`
+//!   what a whole module prints and exits with, run as the register
+//!   bytecode of `RegisterProgram`.
+//! - `DebugInfo`: line table, variables and functions mapping IR back to source.
+//! - `ExternFunction` / `FfiType`: C functions a module calls and the types
+//!   their arguments cross the boundary as, defined with the rest of the
+//!   TIR in `shared::tir`.
+//! - `emit_module` / `lift_module`: a TIR module as the stack instructions
+//!   of a `CompiledModule` and back, which `CompiledModule::from_tir` and
+//!   `CompiledModule::tir` use, and `emit_function` / `lift_function` for
+//!   one function.
+//! - `encode_instructions` / `decode_instructions`: the versioned binary IR format.
+//! - `Backend` trait: for pluggable codegen backends, with typed IR,
+//!   `BackendCapabilities` and per‑compilation `BackendConfig`, and the
+//!   `Profile` and `PanicStrategy` programs are built with.
+//! - `ErasedBackend` / `CompiledArtifact`: the type‑erased view stored in the registry,
+//!   plus what the driver needs to write and build the output.
+//! - Registration functions to register, list and find backends.
+//! - `Optimizer` trait and registry: passes run on the `CompiledModule` before backends.
 
-use once_cell::sync::Lazy;
-use std::{any::Any, sync::Mutex};
+pub mod intrinsics;
+mod register;
+mod stack;
+
+pub use register::RegisterProgram;
+pub use shared::tir::{ArithOp, CompareOp, Constant, ExternFunction, FfiType, Global, Inline};
+pub use stack::{emit_function, emit_module, lift_function, lift_module, split_functions, Emitted};
+
+use serde::{Deserialize, Serialize};
+use shared::tir;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use thiserror::Error;
 
 /// Instructions that the front‑end emits as IR (a.k.a. "bytecode").
-#[derive(Debug, Clone, PartialEq)]
+///
+/// New variants must only ever be appended: the binary encoding identifies
+/// variants by position, so reordering requires bumping `IR_VERSION`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Instruction {
+    /// Do nothing.
+    Nop,
     /// Push a UTF‑8 string literal.
     PushStr(String),
     /// Call the standard print function.
     CallPrint,
     /// Push a 64‑bit integer literal.
     PushInt(i64),
-    // TODO: add other instructions as needed, e.g. CallAdd, etc.
+    /// Push a 64‑bit floating point literal.
+    PushFloat(f64),
+    /// Push a boolean literal.
+    PushBool(bool),
+    /// Pop an integer and print it in decimal.
+    PrintInt,
+    /// Pop a float and print it with six fractional digits (C's `%.6f`).
+    PrintFloat,
+    /// Pop a string and print it verbatim.
+    PrintStr,
+    /// Pop a boolean and print it as `true` or `false`.
+    PrintBool,
+    /// Pop an integer and push it as the nearest float.
+    IntToFloat,
+    /// Pop a float and push it as an integer of `bits` bits (see `float_to_int`).
+    FloatToInt { bits: u8, signed: bool },
+    /// Pop an integer and push it wrapped to `bits` bits (see `wrap_int`).
+    WrapInt { bits: u8, signed: bool },
+    /// Pop one value per entry of `args`, the last argument on top, and call
+    /// the extern function `name` with them. Its result is pushed if
+    /// `result` is `Some`, and discarded otherwise.
+    CallExtern { name: String, args: Vec<FfiType>, result: Option<FfiType> },
+    /// Pop an integer and end the program with it as the exit code, after
+    /// writing out everything printed so far. Falling off the end of the
+    /// instructions exits with 0.
+    Exit,
+    /// Pop a value of type `ty` into the local variable `slot`.
+    StoreLocal { slot: u32, ty: FfiType },
+    /// Push the value last stored in the local variable `slot`, of type `ty`.
+    LoadLocal { slot: u32, ty: FfiType },
+    /// Pop two values of type `ty`, integers or floats, and push `a op b`,
+    /// `b` being the one that was on top (see `ArithOp`).
+    Arith { op: ArithOp, ty: FfiType },
+    /// Pop two values of type `ty`, integers, floats or booleans, and push
+    /// whether `a op b` holds, `b` being the one that was on top.
+    Compare { op: CompareOp, ty: FfiType },
+    /// Pop a boolean and push its negation.
+    Not,
+    /// Where the jumps to label `n` continue. Does nothing.
+    Label(u32),
+    /// Continue at the `Label` of that number.
+    Jump(u32),
+    /// Pop a boolean and continue at the `Label` of that number if it is
+    /// false, else at the next instruction.
+    JumpIfFalse(u32),
+    /// Pop two integers of type `ty` and push whether `a op b` overflows it
+    /// (see `ArithOp::overflows`), `b` being the one that was on top.
+    Overflows { op: ArithOp, ty: FfiType },
+    /// Pop a boolean and, if it is true, panic as `tlang_panic` does with
+    /// `message` and `backtrace`, or abort without flushing stdout under
+    /// `abort` (see `report_panic`).
+    Trap { message: String, backtrace: String, abort: bool },
+    /// Start the code of the function `name`, which runs from here to its
+    /// `Return` with the arguments of a `Call` in its first locals, of types
+    /// `params`, and returns a value of type `result` if that is `Some`.
+    /// The entry point's code comes before every function's; executing this
+    /// ends it, as falling off the end of the instructions does.
+    Function { name: String, params: Vec<FfiType>, result: Option<FfiType>, inline: Inline },
+    /// Pop one value per entry of `args`, the last argument on top, and run
+    /// the function `name` with them, on locals of its own. Its result is
+    /// pushed if `result` is `Some`, and discarded otherwise.
+    Call { name: String, args: Vec<FfiType>, result: Option<FfiType> },
+    /// Pop the result of the function being run, of type `result` if it
+    /// has one, and continue after the `Call` that ran it, with the
+    /// caller's locals.
+    Return { result: Option<FfiType> },
+}
+
+/// Number of fractional digits every backend uses for `PrintFloat`.
+pub const FLOAT_PRINT_PRECISION: usize = 6;
+
+/// Canonical text of a float as printed by `PrintFloat`.
+///
+/// Backends that resolve output at compile time use this directly; backends
+/// that print at run time must produce the same text.
+pub fn format_float(value: f64) -> String {
+    format!("{:.*}", FLOAT_PRINT_PRECISION, value)
+}
+
+/// Canonical text of a boolean as printed by `PrintBool`.
+pub fn format_bool(value: bool) -> &'static str {
+    if value { "true" } else { "false" }
+}
+
+/// Smallest and largest value of a `bits`‑bit integer that the `i64` int
+/// stack can hold; unsigned 64‑bit values stop at `i64::MAX`.
+pub fn int_bounds(bits: u8, signed: bool) -> (i64, i64) {
+    match (bits.min(64), signed) {
+        (64, true) => (i64::MIN, i64::MAX),
+        (64, false) => (0, i64::MAX),
+        (bits, true) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+        (bits, false) => (0, (1 << bits) - 1),
+    }
+}
+
+/// Result of `FloatToInt`: truncated toward zero and saturated at
+/// `int_bounds`, with NaN as 0.
+pub fn float_to_int(value: f64, bits: u8, signed: bool) -> i64 {
+    let (min, max) = int_bounds(bits, signed);
+    if value.is_nan() {
+        0
+    } else {
+        (value as i64).clamp(min, max)
+    }
+}
+
+/// Result of `WrapInt`: the low `bits` bits of `value`, sign‑extended when
+/// `signed`. 64‑bit targets leave the value unchanged.
+pub fn wrap_int(value: i64, bits: u8, signed: bool) -> i64 {
+    if bits >= 64 {
+        return value;
+    }
+    let shift = 64 - u32::from(bits);
+    if signed {
+        (value << shift) >> shift
+    } else {
+        ((value as u64) << shift >> shift) as i64
+    }
+}
+
+/// Exit code of a program that panics, as of a Rust panic.
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+/// Exit code shells report for a program that aborts, by `SIGABRT`.
+pub const ABORT_EXIT_CODE: i32 = 134;
+
+/// Write a panic's `message` to stderr, and its `backtrace` if the
+/// `TLANG_BACKTRACE` variable is set to anything but `0`, as `tlang_panic`
+/// does.
+pub fn report_panic(message: &str, backtrace: &str) {
+    eprintln!("{}", message);
+    match std::env::var("TLANG_BACKTRACE") {
+        Ok(value) if value != "0" => eprintln!("stack backtrace:\n{}", backtrace),
+        _ => eprintln!("note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace"),
+    }
+}
+
+/// A function being run, as the `Call` running it left its caller.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    /// The function's name.
+    pub function: String,
+    /// The locals of the caller.
+    pub locals: BTreeMap<u32, Constant>,
+    /// Where the caller continues, once `next_pc` has placed the call.
+    pub resume: usize,
+    /// Whether the caller takes the result.
+    pub keeps_result: bool,
+}
+
+/// The value stacks the IR runs on, one per kind of value.
+///
+/// Executing instructions here defines what a program prints; backends must
+/// print the same.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValueStacks {
+    pub ints: Vec<i64>,
+    pub floats: Vec<f64>,
+    pub bools: Vec<bool>,
+    pub strs: Vec<String>,
+    /// Values of the local variables stored so far, by slot.
+    pub locals: BTreeMap<u32, Constant>,
+    /// The code of the executed `Exit`; nothing runs after it.
+    pub exit_code: Option<i32>,
+    /// The label the last executed instruction jumped to, until `next_pc`
+    /// takes it.
+    pub jump: Option<u32>,
+    /// The functions being run, the innermost last.
+    pub frames: Vec<Frame>,
+    /// Where the last executed instruction continues, as a `Call` or
+    /// `Return` does, until `next_pc` takes it.
+    pub goes_to: Option<Goto>,
+}
+
+/// Where a `Call` or `Return` continues.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Goto {
+    /// The start of the function of that name
+    Function(String),
+    /// That instruction
+    Instruction(usize),
+}
+
+impl ValueStacks {
+    /// Execute one instruction, appending anything it prints to `out`.
+    pub fn execute(&mut self, instr: &Instruction, out: &mut String) -> Result<(), BackendError> {
+        let underflow = || BackendError::InvalidIr(format!("stack underflow at {:?}", instr));
+        match instr {
+            Instruction::Nop => {}
+            Instruction::PushInt(n) => self.ints.push(*n),
+            Instruction::PushFloat(f) => self.floats.push(*f),
+            Instruction::PushBool(b) => self.bools.push(*b),
+            Instruction::PushStr(s) => self.strs.push(s.clone()),
+            Instruction::CallPrint => {
+                if let Some(s) = self.strs.pop() {
+                    out.push_str(&s);
+                } else if let Some(n) = self.ints.pop() {
+                    out.push_str(&n.to_string());
+                }
+            }
+            Instruction::PrintInt => {
+                out.push_str(&self.ints.pop().ok_or_else(underflow)?.to_string())
+            }
+            Instruction::PrintFloat => {
+                out.push_str(&format_float(self.floats.pop().ok_or_else(underflow)?))
+            }
+            Instruction::PrintStr => out.push_str(&self.strs.pop().ok_or_else(underflow)?),
+            Instruction::PrintBool => {
+                out.push_str(format_bool(self.bools.pop().ok_or_else(underflow)?))
+            }
+            Instruction::IntToFloat => {
+                let n = self.ints.pop().ok_or_else(underflow)?;
+                self.floats.push(n as f64);
+            }
+            Instruction::FloatToInt { bits, signed } => {
+                let f = self.floats.pop().ok_or_else(underflow)?;
+                self.ints.push(float_to_int(f, *bits, *signed));
+            }
+            Instruction::WrapInt { bits, signed } => {
+                let n = self.ints.pop().ok_or_else(underflow)?;
+                self.ints.push(wrap_int(n, *bits, *signed));
+            }
+            Instruction::CallExtern { name, args, result } => {
+                let mut values = Vec::with_capacity(args.len());
+                for &ty in args.iter().rev() {
+                    values.push(self.pop(ty).ok_or_else(underflow)?);
+                }
+                values.reverse();
+                match intrinsics::call(name, &values, out)? {
+                    intrinsics::Called::Exited(code) => self.exit_code = Some(code),
+                    intrinsics::Called::Returned(Some(value)) if result.is_some() => self.push(value),
+                    intrinsics::Called::Returned(_) => {}
+                }
+            }
+            Instruction::Exit => {
+                let code = self.ints.pop().ok_or_else(underflow)?;
+                self.exit_code = Some(code as i32);
+            }
+            Instruction::StoreLocal { slot, ty } => {
+                let value = self.pop(*ty).ok_or_else(underflow)?;
+                self.locals.insert(*slot, value);
+            }
+            Instruction::LoadLocal { slot, ty } => match (ty, self.locals.get(slot)) {
+                (FfiType::Int { .. }, Some(Constant::Int(n))) => self.ints.push(*n),
+                (FfiType::Float { .. }, Some(Constant::Float(f))) => self.floats.push(*f),
+                (FfiType::Bool, Some(Constant::Bool(b))) => self.bools.push(*b),
+                (FfiType::Str, Some(Constant::Str(s))) => self.strs.push(s.clone()),
+                _ => return Err(BackendError::InvalidIr(format!("local {} read before a {:?} is stored", slot, ty))),
+            },
+            Instruction::Arith { op, ty: FfiType::Int { .. } } => {
+                let b = self.ints.pop().ok_or_else(underflow)?;
+                let a = self.ints.pop().ok_or_else(underflow)?;
+                self.ints.push(op.int(a, b));
+            }
+            Instruction::Arith { op: ArithOp::Rem, ty: FfiType::Float { .. } } => {
+                return Err(BackendError::InvalidIr(format!("cannot execute {:?}", instr)));
+            }
+            Instruction::Arith { op, ty: FfiType::Float { .. } } => {
+                let b = self.floats.pop().ok_or_else(underflow)?;
+                let a = self.floats.pop().ok_or_else(underflow)?;
+                self.floats.push(op.float(a, b));
+            }
+            Instruction::Compare { op, ty } => {
+                let holds = match ty {
+                    FfiType::Int { .. } => {
+                        let b = self.ints.pop().ok_or_else(underflow)?;
+                        op.holds(self.ints.pop().ok_or_else(underflow)?, b)
+                    }
+                    FfiType::Float { .. } => {
+                        let b = self.floats.pop().ok_or_else(underflow)?;
+                        op.holds(self.floats.pop().ok_or_else(underflow)?, b)
+                    }
+                    FfiType::Bool => {
+                        let b = self.bools.pop().ok_or_else(underflow)?;
+                        op.holds(self.bools.pop().ok_or_else(underflow)?, b)
+                    }
+                    FfiType::Str => return Err(BackendError::InvalidIr(format!("cannot execute {:?}", instr))),
+                };
+                self.bools.push(holds);
+            }
+            Instruction::Arith { .. } => return Err(BackendError::InvalidIr(format!("cannot execute {:?}", instr))),
+            Instruction::Not => {
+                let b = self.bools.pop().ok_or_else(underflow)?;
+                self.bools.push(!b);
+            }
+            Instruction::Label(_) => {}
+            Instruction::Jump(label) => self.jump = Some(*label),
+            Instruction::JumpIfFalse(label) => {
+                if !self.bools.pop().ok_or_else(underflow)? {
+                    self.jump = Some(*label);
+                }
+            }
+            Instruction::Overflows { op, ty } => {
+                let b = self.ints.pop().ok_or_else(underflow)?;
+                let a = self.ints.pop().ok_or_else(underflow)?;
+                self.bools.push(op.overflows(a, b, *ty));
+            }
+            Instruction::Trap { message, backtrace, abort } => {
+                if self.bools.pop().ok_or_else(underflow)? {
+                    report_panic(message, backtrace);
+                    self.exit_code = Some(if *abort { ABORT_EXIT_CODE } else { PANIC_EXIT_CODE });
+                }
+            }
+            Instruction::Function { name, .. } => {
+                if !self.frames.is_empty() {
+                    return Err(BackendError::InvalidIr(format!("the function before `{}` does not return", name)));
+                }
+                self.exit_code = Some(0);
+            }
+            Instruction::Call { name, args, result } => {
+                let mut locals = BTreeMap::new();
+                for (slot, ty) in args.iter().enumerate().rev() {
+                    locals.insert(slot as u32, self.pop(*ty).ok_or_else(underflow)?);
+                }
+                let locals = std::mem::replace(&mut self.locals, locals);
+                let keeps_result = result.is_some();
+                self.frames.push(Frame { function: name.clone(), locals, resume: 0, keeps_result });
+                self.goes_to = Some(Goto::Function(name.clone()));
+            }
+            Instruction::Return { result } => {
+                let frame = self.frames.pop().ok_or_else(|| {
+                    BackendError::InvalidIr("`Return` outside of a function".to_string())
+                })?;
+                if let Some(ty) = result
+                    && !frame.keeps_result
+                {
+                    self.pop(*ty).ok_or_else(underflow)?;
+                }
+                self.locals = frame.locals;
+                self.goes_to = Some(Goto::Instruction(frame.resume));
+            }
+        }
+        Ok(())
+    }
+
+    /// Pop a value of type `ty`.
+    fn pop(&mut self, ty: FfiType) -> Option<Constant> {
+        match ty {
+            FfiType::Int { .. } => self.ints.pop().map(Constant::Int),
+            FfiType::Float { .. } => self.floats.pop().map(Constant::Float),
+            FfiType::Bool => self.bools.pop().map(Constant::Bool),
+            FfiType::Str => self.strs.pop().map(Constant::Str),
+        }
+    }
+
+    /// Push `value` on its stack.
+    fn push(&mut self, value: Constant) {
+        match value {
+            Constant::Int(n) => self.ints.push(n),
+            Constant::Float(f) => self.floats.push(f),
+            Constant::Bool(b) => self.bools.push(b),
+            Constant::Str(s) => self.strs.push(s),
+        }
+    }
+
+    /// The instruction to execute after the one at `pc`: the one at the
+    /// label it jumped to, the first of the function it called, the one
+    /// after the call it returned to, or the next. `layout` is where
+    /// `Layout::of` finds the labels and functions.
+    pub fn next_pc(&mut self, pc: usize, layout: &Layout) -> Result<usize, BackendError> {
+        if let Some(label) = self.jump.take() {
+            return layout
+                .labels
+                .get(&label)
+                .copied()
+                .ok_or_else(|| BackendError::InvalidIr(format!("jump to label {}, which is not there", label)));
+        }
+        match self.goes_to.take() {
+            Some(Goto::Function(name)) => {
+                let start = layout.functions.get(&name).copied().ok_or_else(|| {
+                    BackendError::InvalidIr(format!("call to function `{}`, which is not there", name))
+                })?;
+                if let Some(frame) = self.frames.last_mut() {
+                    frame.resume = pc + 1;
+                }
+                Ok(start + 1)
+            }
+            Some(Goto::Instruction(resume)) => Ok(resume),
+            None => Ok(pc + 1),
+        }
+    }
+}
+
+/// Where the labels and functions of some instructions are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    /// Where each `Label` is, by its number.
+    pub labels: HashMap<u32, usize>,
+    /// Where the `Function` starting each function is, by its name.
+    pub functions: HashMap<String, usize>,
+}
+
+impl Layout {
+    /// The layout of `instructions`.
+    pub fn of(instructions: &[Instruction]) -> Self {
+        let mut layout = Layout::default();
+        for (i, instr) in instructions.iter().enumerate() {
+            match instr {
+                Instruction::Label(n) => {
+                    layout.labels.insert(*n, i);
+                }
+                Instruction::Function { name, .. } => {
+                    layout.functions.insert(name.clone(), i);
+                }
+                _ => {}
+            }
+        }
+        layout
+    }
+}
+
+/// Run `module` to completion and return everything it prints.
+pub fn interpret(module: &CompiledModule) -> Result<String, BackendError> {
+    interpret_with_exit_code(module).map(|(out, _)| out)
+}
+
+/// Run `module` to completion and return everything it prints and the code
+/// it exits with, as register bytecode compiled from its TIR.
+pub fn interpret_with_exit_code(module: &CompiledModule) -> Result<(String, i32), BackendError> {
+    RegisterProgram::compile(&module.tir()?)?.run()
+}
+
+/// What `interpret_with_exit_code` returns, found by stepping `ValueStacks`
+/// through `module`'s instructions one by one, as the debugger does.
+pub fn step_with_exit_code(module: &CompiledModule) -> Result<(String, i32), BackendError> {
+    let instrs = module.decode()?;
+    let layout = Layout::of(&instrs);
+    let mut stacks = ValueStacks::default();
+    let mut out = String::new();
+    let mut pc = 0;
+    while let Some(instr) = instrs.get(pc) {
+        stacks.execute(instr, &mut out)?;
+        if let Some(code) = stacks.exit_code {
+            return Ok((out, code));
+        }
+        pc = stacks.next_pc(pc, &layout)?;
+    }
+    Ok((out, 0))
+}
+
+/// Magic bytes at the start of every encoded IR blob.
+pub const IR_MAGIC: [u8; 4] = *b"TLIR";
+
+/// Version of the binary IR encoding produced by `encode_instructions`.
+pub const IR_VERSION: u16 = 2;
+
+/// Encode instructions as `IR_MAGIC`, a little‑endian `IR_VERSION`, then the
+/// bincode‑serialized instruction list.
+pub fn encode_instructions(instructions: &[Instruction]) -> Result<Vec<u8>, BackendError> {
+    let mut bytes = Vec::with_capacity(IR_MAGIC.len() + 2);
+    bytes.extend_from_slice(&IR_MAGIC);
+    bytes.extend_from_slice(&IR_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, instructions)
+        .map_err(|e| BackendError::InvalidIr(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Decode a blob produced by `encode_instructions`, checking magic and version.
+pub fn decode_instructions(bytes: &[u8]) -> Result<Vec<Instruction>, BackendError> {
+    let header_len = IR_MAGIC.len() + 2;
+    if bytes.len() < header_len || bytes[..IR_MAGIC.len()] != IR_MAGIC {
+        return Err(BackendError::InvalidIr("missing IR header".to_string()));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != IR_VERSION {
+        return Err(BackendError::UnsupportedIrVersion {
+            found: version,
+            expected: IR_VERSION,
+        });
+    }
+    bincode::deserialize(&bytes[header_len..]).map_err(|e| BackendError::InvalidIr(e.to_string()))
+}
+
+/// Source position of the instructions starting at `instruction`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineInfo {
+    /// Index of the first instruction generated for this line.
+    pub instruction: usize,
+    /// 1‑based line in the original `.t` source.
+    pub line: u32,
+    /// 1‑based column in the original `.t` source.
+    pub column: u32,
+}
+
+/// A variable declared in the original source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariableInfo {
+    pub name: String,
+    /// Type as written in the source, or inferred from the initializer.
+    pub ty: String,
+    /// Value of a binding folded at compile time, as it would print.
+    pub const_value: Option<String>,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A function declared in the original source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub line: u32,
+    pub column: u32,
+    /// Instructions generated for the body; empty if it was not lowered.
+    pub instructions: std::ops::Range<usize>,
+}
+
+/// Mapping from IR back to the original source, for debuggers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DebugInfo {
+    /// Path of the `.t` file the module was compiled from.
+    pub source_file: Option<String>,
+    /// Line table, ordered by instruction index.
+    pub line_info: Vec<LineInfo>,
+    pub variables: Vec<VariableInfo>,
+    pub functions: Vec<FunctionInfo>,
+    /// Where each coverage counter is, by its number; empty unless the
+    /// module was instrumented for coverage.
+    pub counters: Vec<LineInfo>,
+}
+
+impl DebugInfo {
+    /// Whether there is anything for a backend to emit.
+    pub fn is_empty(&self) -> bool {
+        self.source_file.is_none() || self.line_info.is_empty()
+    }
+
+    /// The line table row that starts at `instruction`, if any.
+    pub fn row_starting_at(&self, instruction: usize) -> Option<&LineInfo> {
+        self.line_info.iter().find(|row| row.instruction == instruction)
+    }
 }
 
 /// A compiled module, pairing raw bytes with a sequence of high‑level instructions.
@@ -29,18 +590,153 @@ pub struct CompiledModule {
     pub bytecode: Vec<u8>,
     /// High‑level decoded instructions.
     pub instructions: Vec<Instruction>,
+    /// Source mapping filled in by codegen; empty when unavailable.
+    pub debug_info: DebugInfo,
+    /// Extern functions the module declares, which backends emit prototypes
+    /// for; every `CallExtern` names one of them.
+    pub externs: Vec<ExternFunction>,
+    /// Global variables the module defines, which backends emit with their
+    /// initial values.
+    pub globals: Vec<Global>,
+    /// How often the optimizers that ran over the module did each thing
+    /// they count, such as `inlined call sites`, for a compilation's stats.
+    pub optimizations: BTreeMap<String, u64>,
 }
 
 impl CompiledModule {
     /// Create a new `CompiledModule` from raw bytes and decoded instructions.
     pub fn new(bytecode: Vec<u8>, instructions: Vec<Instruction>) -> Self {
-        CompiledModule { bytecode, instructions }
+        CompiledModule {
+            bytecode,
+            instructions,
+            debug_info: DebugInfo::default(),
+            externs: Vec::new(),
+            globals: Vec::new(),
+            optimizations: BTreeMap::new(),
+        }
+    }
+
+    /// Create a `CompiledModule` whose bytecode is the encoded form of `instructions`.
+    pub fn from_instructions(instructions: Vec<Instruction>) -> Result<Self, BackendError> {
+        let bytecode = encode_instructions(&instructions)?;
+        Ok(CompiledModule::new(bytecode, instructions))
+    }
+
+    /// A module running the first function of `tir`, its entry point, and
+    /// the others as it calls them, with the TIR's externs and globals and
+    /// `debug_info`.
+    ///
+    /// Row `n` of the line table starts where the TIR has `Marker(n)`, and
+    /// the coverage counters of a row move with it. A function of the debug
+    /// info has the instructions of the TIR function of its name, and one
+    /// the TIR does not have none, where those before it end.
+    ///
+    /// Debug builds check the TIR with `tir::verify::verify` first, so TIR
+    /// that lowering or an optimizer got wrong fails here.
+    pub fn from_tir(tir: &tir::Module, mut debug_info: DebugInfo) -> Result<Self, BackendError> {
+        if tir.functions.is_empty() {
+            return Err(BackendError::InvalidIr("a module without functions has no entry point".to_string()));
+        }
+        if cfg!(debug_assertions) {
+            tir::verify::verify(tir).map_err(BackendError::InvalidIr)?;
+        }
+        let emitted = emit_module(tir)?;
+        let len = emitted.instructions.len();
+        let old: Vec<LineInfo> = debug_info.line_info.clone();
+        for (n, row) in debug_info.line_info.iter_mut().enumerate() {
+            row.instruction = emitted.marks.get(&(n as u32)).copied().unwrap_or(len);
+        }
+        for counter in &mut debug_info.counters {
+            if let Some(n) = old.iter().position(|row| row == counter) {
+                counter.instruction = debug_info.line_info[n].instruction;
+            }
+        }
+        // Blocks need not be laid out in the order their rows started.
+        debug_info.line_info.sort_by_key(|row| row.instruction);
+        let mut end = 0;
+        for function in &mut debug_info.functions {
+            let lowered = tir.functions.iter().position(|f| f.name == function.name);
+            function.instructions = lowered.map_or(end..end, |i| emitted.functions[i].clone());
+            end = function.instructions.end;
+        }
+        Ok(CompiledModule::from_instructions(emitted.instructions)?
+            .with_debug_info(debug_info)
+            .with_externs(tir.externs.clone())
+            .with_globals(tir.globals.clone()))
+    }
+
+    /// The module as TIR, lifted from its bytecode: the entry point, named
+    /// after the function whose instructions the debug info says start the
+    /// module (or `main`), then the functions it calls, with `Marker(n)`
+    /// where row `n` of the line table starts.
+    pub fn tir(&self) -> Result<tir::Module, BackendError> {
+        let name = self
+            .debug_info
+            .functions
+            .iter()
+            .find(|f| f.instructions.start == 0 && !f.instructions.is_empty())
+            .map_or("main", |f| f.name.as_str());
+        let rows: Vec<usize> = self.debug_info.line_info.iter().map(|row| row.instruction).collect();
+        Ok(tir::Module {
+            functions: lift_module(name, &self.decode()?, &rows)?,
+            externs: self.externs.clone(),
+            globals: self.globals.clone(),
+        })
+    }
+
+    /// This module with its code replaced by `tir`'s, as an optimizer
+    /// working on the module's TIR makes it; see `from_tir`. What the
+    /// optimizers counted so far stays.
+    pub fn with_tir(self, tir: &tir::Module) -> Result<Self, BackendError> {
+        let mut module = CompiledModule::from_tir(tir, self.debug_info)?;
+        module.optimizations = self.optimizations;
+        Ok(module)
+    }
+
+    /// Count that an optimizer did `what` `n` more times.
+    pub fn count(&mut self, what: &str, n: u64) {
+        *self.optimizations.entry(what.to_string()).or_default() += n;
+    }
+
+    /// Attach debug information.
+    pub fn with_debug_info(mut self, debug_info: DebugInfo) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    /// Attach extern function declarations.
+    pub fn with_externs(mut self, externs: Vec<ExternFunction>) -> Self {
+        self.externs = externs;
+        self
+    }
+
+    /// Attach global variable definitions.
+    pub fn with_globals(mut self, globals: Vec<Global>) -> Self {
+        self.globals = globals;
+        self
+    }
+
+    /// The declaration of the extern function `name`, which a `CallExtern`
+    /// calls.
+    pub fn extern_function(&self, name: &str) -> Result<&ExternFunction, BackendError> {
+        self.externs
+            .iter()
+            .find(|f| f.name == name)
+            .ok_or_else(|| BackendError::InvalidIr(format!("call to undeclared extern function `{}`", name)))
     }
 
     /// Access the sequence of instructions.
     pub fn instructions(&self) -> &[Instruction] {
         &self.instructions
     }
+
+    /// Decode `bytecode` back into instructions.
+    ///
+    /// Backends call this rather than trusting `instructions`, so that a
+    /// module loaded from disk is handled exactly like a freshly built one.
+    pub fn decode(&self) -> Result<Vec<Instruction>, BackendError> {
+        decode_instructions(&self.bytecode)
+    }
 }
 
 /// Errors returned by backends.
@@ -48,35 +744,749 @@ impl CompiledModule {
 pub enum BackendError {
     #[error("generic backend error: {0}")]
     Generic(String),
+    #[error("invalid IR: {0}")]
+    InvalidIr(String),
+    #[error("unsupported IR version {found} (expected {expected})")]
+    UnsupportedIrVersion { found: u16, expected: u16 },
+}
+
+/// How the bytes of a `CompiledArtifact` should be treated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArtifactFormat {
+    /// Source code in the target language, to be built or run by its toolchain.
+    Source,
+    /// Other human‑readable output (listings, reports, ...).
+    Text,
+    /// Opaque binary output (object code, bytecode, ...).
+    Binary,
+}
+
+/// The final output of a backend, independent of the backend's internal IR type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledArtifact {
+    /// Name of the backend that produced the artifact.
+    pub target: String,
+    /// How `data` should be interpreted.
+    pub format: ArtifactFormat,
+    /// Raw artifact contents (source text, object code, ...).
+    pub data: Vec<u8>,
+    /// File name the driver should write `data` to. This and the names of
+    /// `extra_files` are relative paths, and may name subdirectories.
+    pub suggested_filename: String,
+    /// Further `(file name, contents)` pairs written next to the main file,
+    /// such as package manifests.
+    pub extra_files: Vec<(String, Vec<u8>)>,
+    /// Shell commands that build and run the artifact, in order, from the
+    /// directory it was written to.
+    pub build_commands: Vec<String>,
+}
+
+impl CompiledArtifact {
+    /// Create a binary artifact for `target` holding `data`, named `<target>.bin`.
+    pub fn new(target: impl Into<String>, data: Vec<u8>) -> Self {
+        let target = target.into();
+        CompiledArtifact {
+            suggested_filename: format!("{}.bin", target),
+            target,
+            format: ArtifactFormat::Binary,
+            data,
+            extra_files: Vec::new(),
+            build_commands: Vec::new(),
+        }
+    }
+
+    /// Create a source artifact for `target`, to be written as `filename`.
+    pub fn source(target: impl Into<String>, filename: impl Into<String>, code: String) -> Self {
+        CompiledArtifact {
+            target: target.into(),
+            format: ArtifactFormat::Source,
+            data: code.into_bytes(),
+            suggested_filename: filename.into(),
+            extra_files: Vec::new(),
+            build_commands: Vec::new(),
+        }
+    }
+
+    /// Add a file written next to the main file.
+    pub fn with_file(mut self, filename: impl Into<String>, data: Vec<u8>) -> Self {
+        self.extra_files.push((filename.into(), data));
+        self
+    }
+
+    /// Append a build/run command.
+    pub fn with_build_command(mut self, command: impl Into<String>) -> Self {
+        self.build_commands.push(command.into());
+        self
+    }
+
+    /// Every file the artifact consists of as `(file name, contents)`: the
+    /// main file first, then the extra files in order.
+    pub fn files(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        std::iter::once((self.suggested_filename.as_str(), self.data.as_slice())).chain(
+            self.extra_files
+                .iter()
+                .map(|(name, data)| (name.as_str(), data.as_slice())),
+        )
+    }
+}
+
+/// Conversion from a backend's IR into the artifact handed back to the driver.
+pub trait IntoArtifact {
+    /// Wrap `self` as the artifact produced by the backend named `target`.
+    fn into_artifact(self, target: &str) -> CompiledArtifact;
+}
+
+impl IntoArtifact for CompiledArtifact {
+    fn into_artifact(self, _target: &str) -> CompiledArtifact {
+        self
+    }
+}
+
+impl IntoArtifact for Vec<u8> {
+    fn into_artifact(self, target: &str) -> CompiledArtifact {
+        CompiledArtifact::new(target, self)
+    }
+}
+
+impl IntoArtifact for String {
+    fn into_artifact(self, target: &str) -> CompiledArtifact {
+        CompiledArtifact {
+            format: ArtifactFormat::Text,
+            suggested_filename: format!("{}.txt", target),
+            ..CompiledArtifact::new(target, self.into_bytes())
+        }
+    }
+}
+
+/// What a backend can do beyond producing an artifact. The compiler fits
+/// its options to these before codegen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackendCapabilities {
+    /// The backend executes modules in‑process instead of emitting files.
+    pub supports_jit: bool,
+    /// The backend honours `BackendConfig::target_triple`.
+    pub supports_cross_compilation: bool,
+    /// Highest optimization level the backend's output is right at; 3 by default.
+    pub max_optimization_level: u8,
+    /// The backend turns a module's `DebugInfo` into debug info of its output.
+    pub supports_debug_info: bool,
+    /// Formats of the artifacts the backend produces, the usual one first;
+    /// source only by default.
+    pub output_formats: &'static [ArtifactFormat],
+}
+
+impl Default for BackendCapabilities {
+    fn default() -> Self {
+        BackendCapabilities {
+            supports_jit: false,
+            supports_cross_compilation: false,
+            max_optimization_level: 3,
+            supports_debug_info: false,
+            output_formats: &[ArtifactFormat::Source],
+        }
+    }
+}
+
+/// Kind of environment the generated program runs in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// A normal operating system with a heap and a C library.
+    #[default]
+    Hosted,
+    /// Microcontrollers and other `no_std` targets: no heap, static memory only.
+    Embedded,
+}
+
+/// What a panicking program does once it has reported the panic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicStrategy {
+    /// Unwind: flush what the program printed and exit with status 101.
+    #[default]
+    Unwind,
+    /// Abort the process on the spot, flushing nothing.
+    Abort,
+}
+
+impl PanicStrategy {
+    /// The strategy of programs built for `profile`: hosted programs
+    /// unwind, embedded ones have nowhere to return to and abort.
+    pub fn for_profile(profile: Profile) -> Self {
+        match profile {
+            Profile::Hosted => PanicStrategy::Unwind,
+            Profile::Embedded => PanicStrategy::Abort,
+        }
+    }
+}
+
+/// Per‑compilation settings handed to backends.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackendConfig {
+    /// Target triple to generate code for, e.g. `aarch64-unknown-linux-gnu`;
+    /// `None` means the host.
+    pub target_triple: Option<String>,
+    /// Environment the program runs in.
+    pub profile: Profile,
+    /// Stack size in bytes reserved for the program on bare‑metal targets;
+    /// `None` lets the backend choose.
+    pub stack_size: Option<usize>,
+    /// Backend‑specific `key=value` settings, such as the C backend's
+    /// `c_std`. Every backend gets them all and reads the keys it knows.
+    pub options: BTreeMap<String, String>,
+}
+
+impl BackendConfig {
+    /// The value of backend option `key`, if it was given.
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str)
+    }
+
+    /// Backend option `key` as `true` or `false`; `default` when it was not given.
+    pub fn bool_option(&self, key: &str, default: bool) -> Result<bool, BackendError> {
+        match self.option(key) {
+            None => Ok(default),
+            Some("true") => Ok(true),
+            Some("false") => Ok(false),
+            Some(other) => Err(BackendError::Generic(format!(
+                "backend option {} must be true or false, not {}",
+                key, other
+            ))),
+        }
+    }
 }
 
 /// The pluggable backend interface: transform a `CompiledModule` into some IR.
 pub trait Backend<M>: Send + Sync {
     /// The backend‑specific IR type.
-    type ModuleIr: Any + Send + Sync;
+    type ModuleIr;
+    /// Check `config`, in particular its backend options, before any module
+    /// is compiled with it. Accepts everything by default.
+    fn initialize(&self, config: &BackendConfig) -> Result<(), BackendError> {
+        let _ = config;
+        Ok(())
+    }
     /// Compile or transform the given module, returning backend IR or an error.
     fn compile(&self, module: M) -> Result<Self::ModuleIr, BackendError>;
+    /// Compile with explicit settings. Backends that ignore `config` can rely
+    /// on the default, which calls `compile`.
+    fn compile_with_config(
+        &self,
+        module: M,
+        config: &BackendConfig,
+    ) -> Result<Self::ModuleIr, BackendError> {
+        let _ = config;
+        self.compile(module)
+    }
     /// A human‑readable backend name.
     fn name(&self) -> &'static str;
+    /// Version of the backend; by default the plugin API's, which the
+    /// built‑in backends are released with.
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+    /// Features this backend supports; none by default.
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::default()
+    }
+}
+
+/// Object‑safe view of a backend, used by the registry.
+///
+/// Implemented automatically for every `Backend<CompiledModule>` whose IR
+/// implements `IntoArtifact`, so backends keep their own typed IR.
+pub trait ErasedBackend: Send + Sync {
+    /// A human‑readable backend name.
+    fn name(&self) -> &'static str;
+    /// Version of the backend.
+    fn version(&self) -> &'static str;
+    /// Features this backend supports.
+    fn capabilities(&self) -> BackendCapabilities;
+    /// Check `config` before compiling with it.
+    fn initialize(&self, config: &BackendConfig) -> Result<(), BackendError>;
+    /// Compile the module and convert the backend IR into an artifact.
+    fn compile_erased(&self, module: CompiledModule) -> Result<CompiledArtifact, BackendError>;
+    /// Like `compile_erased`, with explicit settings.
+    fn compile_erased_with(
+        &self,
+        module: CompiledModule,
+        config: &BackendConfig,
+    ) -> Result<CompiledArtifact, BackendError>;
+}
+
+impl<B> ErasedBackend for B
+where
+    B: Backend<CompiledModule>,
+    B::ModuleIr: IntoArtifact,
+{
+    fn name(&self) -> &'static str {
+        Backend::name(self)
+    }
+
+    fn version(&self) -> &'static str {
+        Backend::version(self)
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        Backend::capabilities(self)
+    }
+
+    fn initialize(&self, config: &BackendConfig) -> Result<(), BackendError> {
+        Backend::initialize(self, config)
+    }
+
+    fn compile_erased(&self, module: CompiledModule) -> Result<CompiledArtifact, BackendError> {
+        let ir = self.compile(module)?;
+        Ok(ir.into_artifact(Backend::name(self)))
+    }
+
+    fn compile_erased_with(
+        &self,
+        module: CompiledModule,
+        config: &BackendConfig,
+    ) -> Result<CompiledArtifact, BackendError> {
+        let ir = self.compile_with_config(module, config)?;
+        Ok(ir.into_artifact(Backend::name(self)))
+    }
 }
 
 /// Registry of all available backends.
-static BACKENDS: Lazy<Mutex<Vec<&'static dyn Backend<CompiledModule, ModuleIr = Box<dyn Any + Send + Sync>>>>> =
-    Lazy::new(|| Mutex::new(Vec::new()));
+static BACKENDS: Mutex<Vec<&'static dyn ErasedBackend>> = Mutex::new(Vec::new());
+
+/// Lock the registry; a panic while registering cannot leave the list half‑written.
+fn backends() -> MutexGuard<'static, Vec<&'static dyn ErasedBackend>> {
+    BACKENDS.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
 /// Register a new backend at startup.
 pub fn register_backend<B>(backend: B)
 where
-    B: Backend<CompiledModule, ModuleIr = Box<dyn Any + Send + Sync>> + 'static,
+    B: ErasedBackend + 'static,
 {
     // Leak the backend so it lives for the program duration.
-    let boxed: Box<dyn Backend<CompiledModule, ModuleIr = Box<dyn Any + Send + Sync>>> = Box::new(backend);
-    let static_ref: &'static dyn Backend<CompiledModule, ModuleIr = Box<dyn Any + Send + Sync>> = Box::leak(boxed);
-    BACKENDS.lock().unwrap().push(static_ref);
+    let static_ref: &'static dyn ErasedBackend = Box::leak(Box::new(backend));
+    backends().push(static_ref);
 }
 
 /// List all registered backends.
-pub fn list_backends(
-) -> Vec<&'static dyn Backend<CompiledModule, ModuleIr = Box<dyn Any + Send + Sync>>> {
-    BACKENDS.lock().unwrap().clone()
+pub fn list_backends() -> Vec<&'static dyn ErasedBackend> {
+    backends().clone()
+}
+
+/// Look up a registered backend by name.
+pub fn find_backend(name: &str) -> Option<&'static dyn ErasedBackend> {
+    backends().iter().copied().find(|b| b.name() == name)
+}
+
+/// An optimization pass over the IR, run after codegen and before backends.
+pub trait Optimizer: Send + Sync {
+    /// Unique name, used on the command line to enable or disable the pass.
+    fn name(&self) -> &'static str;
+
+    /// Lowest optimization level at which the pass runs by default.
+    fn min_opt_level(&self) -> u8 {
+        1
+    }
+
+    /// Rewrite the module.
+    fn optimize(&self, module: CompiledModule) -> Result<CompiledModule, BackendError>;
+
+    /// Rewrite the module as the optimization level `opt_level` asks, for a
+    /// pass that does more at higher levels; `optimize` by default.
+    fn optimize_at_level(&self, module: CompiledModule, opt_level: u8) -> Result<CompiledModule, BackendError> {
+        let _ = opt_level;
+        self.optimize(module)
+    }
+}
+
+/// Registry of all available optimizers, in registration (= execution) order.
+static OPTIMIZERS: Mutex<Vec<&'static dyn Optimizer>> = Mutex::new(Vec::new());
+
+fn optimizers() -> MutexGuard<'static, Vec<&'static dyn Optimizer>> {
+    OPTIMIZERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Register a new optimizer at startup.
+pub fn register_optimizer<O>(optimizer: O)
+where
+    O: Optimizer + 'static,
+{
+    let static_ref: &'static dyn Optimizer = Box::leak(Box::new(optimizer));
+    optimizers().push(static_ref);
+}
+
+/// List all registered optimizers.
+pub fn list_optimizers() -> Vec<&'static dyn Optimizer> {
+    optimizers().clone()
+}
+
+/// Whether `optimizer` runs at `opt_level`, honouring explicit per-name
+/// overrides (`true` = force on, `false` = force off).
+pub fn optimizer_enabled(
+    optimizer: &dyn Optimizer,
+    opt_level: u8,
+    overrides: &HashMap<String, bool>,
+) -> bool {
+    overrides
+        .get(optimizer.name())
+        .copied()
+        .unwrap_or(opt_level >= optimizer.min_opt_level())
+}
+
+/// Run every enabled registered optimizer over `module`, in registration order.
+pub fn run_optimizers(
+    module: CompiledModule,
+    opt_level: u8,
+    overrides: &HashMap<String, bool>,
+) -> Result<CompiledModule, BackendError> {
+    run_optimizer_list(&list_optimizers(), module, opt_level, overrides)
+}
+
+fn run_optimizer_list(
+    passes: &[&dyn Optimizer],
+    module: CompiledModule,
+    opt_level: u8,
+    overrides: &HashMap<String, bool>,
+) -> Result<CompiledModule, BackendError> {
+    let mut module = module;
+    for pass in passes {
+        if optimizer_enabled(*pass, opt_level, overrides) {
+            module = pass.optimize_at_level(module, opt_level)?;
+        }
+    }
+    Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instructions_round_trip_through_bytecode() {
+        let instrs = vec![
+            Instruction::PushStr("hi\n".to_string()),
+            Instruction::PrintStr,
+            Instruction::PushFloat(2.5),
+            Instruction::PrintFloat,
+            Instruction::PushBool(true),
+            Instruction::PrintBool,
+        ];
+        let module = CompiledModule::from_instructions(instrs.clone()).unwrap();
+        assert_eq!(&module.bytecode[..4], &IR_MAGIC);
+        assert_eq!(module.decode().unwrap(), instrs);
+    }
+
+    #[test]
+    fn casts_wrap_and_saturate() {
+        assert_eq!(wrap_int(300, 8, false), 44);
+        assert_eq!(wrap_int(200, 8, true), -56);
+        assert_eq!(wrap_int(-1, 16, false), 65535);
+        assert_eq!(wrap_int(i64::MIN, 64, true), i64::MIN);
+        assert_eq!(float_to_int(-3.9, 32, true), -3);
+        assert_eq!(float_to_int(300.0, 8, false), 255);
+        assert_eq!(float_to_int(-1.0, 8, false), 0);
+        assert_eq!(float_to_int(f64::NAN, 64, true), 0);
+        assert_eq!(float_to_int(1e300, 64, true), i64::MAX);
+    }
+
+    #[test]
+    fn interpret_prints_what_the_instructions_print() {
+        let module = CompiledModule::from_instructions(vec![
+            Instruction::PushInt(300),
+            Instruction::WrapInt { bits: 8, signed: false },
+            Instruction::PrintInt,
+            Instruction::PushStr(" ".to_string()),
+            Instruction::CallPrint,
+            Instruction::PushInt(7),
+            Instruction::IntToFloat,
+            Instruction::PrintFloat,
+            Instruction::PushBool(false),
+            Instruction::PrintBool,
+        ])
+        .unwrap();
+        assert_eq!(interpret(&module).unwrap(), "44 7.000000false");
+
+        let underflow = CompiledModule::from_instructions(vec![Instruction::PrintStr]).unwrap();
+        assert!(matches!(interpret(&underflow), Err(BackendError::InvalidIr(_))));
+    }
+
+    #[test]
+    fn exit_ends_the_program_with_its_code() {
+        let module = CompiledModule::from_instructions(vec![
+            Instruction::PushStr("bye".to_string()),
+            Instruction::PrintStr,
+            Instruction::PushInt(3),
+            Instruction::Exit,
+            Instruction::PushStr("never".to_string()),
+            Instruction::PrintStr,
+        ])
+        .unwrap();
+        assert_eq!(interpret_with_exit_code(&module).unwrap(), ("bye".to_string(), 3));
+
+        let falls_off = CompiledModule::from_instructions(vec![Instruction::Nop]).unwrap();
+        assert_eq!(interpret_with_exit_code(&falls_off).unwrap(), (String::new(), 0));
+    }
+
+    struct Upper;
+
+    impl Backend<CompiledModule> for Upper {
+        type ModuleIr = String;
+
+        fn compile(&self, module: CompiledModule) -> Result<String, BackendError> {
+            let mut out = String::new();
+            for instr in module.decode()? {
+                if let Instruction::PushStr(s) = instr {
+                    out.push_str(&s.to_uppercase());
+                }
+            }
+            Ok(out)
+        }
+
+        fn name(&self) -> &'static str {
+            "test-upper"
+        }
+    }
+
+    #[test]
+    fn typed_backends_register_and_compile_erased() {
+        register_backend(Upper);
+        let backend = find_backend("test-upper").unwrap();
+        let module = CompiledModule::from_instructions(vec![Instruction::PushStr("hi".into())]).unwrap();
+        let artifact = backend.compile_erased(module).unwrap();
+        assert_eq!(artifact.target, "test-upper");
+        assert_eq!(artifact.format, ArtifactFormat::Text);
+        assert_eq!(artifact.data, b"HI".to_vec());
+        assert_eq!(artifact.suggested_filename, "test-upper.txt");
+        assert_eq!(backend.version(), env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn backend_options_read_as_text_or_booleans() {
+        let mut config = BackendConfig::default();
+        config.options.insert("c_std".to_string(), "c11".to_string());
+        config.options.insert("emit_line_directives".to_string(), "no".to_string());
+        assert_eq!(config.option("c_std"), Some("c11"));
+        assert_eq!(config.option("missing"), None);
+        assert!(config.bool_option("missing", true).unwrap());
+        assert!(config.bool_option("emit_line_directives", true).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_other_versions() {
+        let mut bytes = encode_instructions(&[Instruction::Nop]).unwrap();
+        bytes[4] = 0xff;
+        assert!(matches!(
+            decode_instructions(&bytes),
+            Err(BackendError::UnsupportedIrVersion { expected: IR_VERSION, .. })
+        ));
+        assert!(decode_instructions(b"PushInt(1)").is_err());
+    }
+
+    #[test]
+    fn debug_info_line_table_lookup() {
+        let module = CompiledModule::from_instructions(vec![Instruction::Nop; 3]).unwrap();
+        assert!(module.debug_info.is_empty());
+
+        let module = module.with_debug_info(DebugInfo {
+            source_file: Some("main.t".to_string()),
+            line_info: vec![
+                LineInfo { instruction: 0, line: 2, column: 5 },
+                LineInfo { instruction: 2, line: 3, column: 5 },
+            ],
+            ..DebugInfo::default()
+        });
+        assert!(!module.debug_info.is_empty());
+        assert_eq!(module.debug_info.row_starting_at(2).map(|r| r.line), Some(3));
+        assert!(module.debug_info.row_starting_at(1).is_none());
+    }
+
+    #[test]
+    fn modules_lift_to_tir_and_back_with_their_line_table() {
+        let module = CompiledModule::from_instructions(vec![
+            Instruction::PushInt(1),
+            Instruction::PrintInt,
+            Instruction::PushInt(2),
+            Instruction::PrintInt,
+        ])
+        .unwrap();
+        let row = LineInfo { instruction: 2, line: 3, column: 5 };
+        let module = module.with_debug_info(DebugInfo {
+            source_file: Some("main.t".to_string()),
+            line_info: vec![LineInfo { instruction: 0, line: 2, column: 5 }, row.clone()],
+            functions: vec![FunctionInfo { name: "run".to_string(), line: 1, column: 1, instructions: 0..4 }],
+            counters: vec![row],
+            ..DebugInfo::default()
+        });
+        let mut tir = module.tir().unwrap();
+        assert_eq!(tir.functions[0].name, "run");
+
+        // Drop the first print; the second row and its counter move up.
+        let function = &mut tir.functions[0];
+        function.blocks[0].insts.retain(|&inst| inst.index() > 2 || inst.index() == 0);
+        let moved = module.with_tir(&tir).unwrap();
+        assert_eq!(moved.decode().unwrap(), [Instruction::PushInt(2), Instruction::PrintInt]);
+        assert_eq!(moved.debug_info.line_info[1].instruction, 0);
+        assert_eq!(moved.debug_info.counters[0].instruction, 0);
+        assert_eq!(moved.debug_info.functions[0].instructions, 0..2);
+    }
+
+    #[test]
+    fn locals_hold_what_was_last_stored() {
+        let int = FfiType::INT;
+        let module = CompiledModule::from_instructions(vec![
+            Instruction::PushInt(4),
+            Instruction::StoreLocal { slot: 0, ty: int },
+            Instruction::LoadLocal { slot: 0, ty: int },
+            Instruction::LoadLocal { slot: 0, ty: int },
+            Instruction::PrintInt,
+            Instruction::PrintInt,
+        ])
+        .unwrap();
+        assert_eq!(interpret(&module).unwrap(), "44");
+        let unset = CompiledModule::from_instructions(vec![Instruction::LoadLocal { slot: 1, ty: int }]).unwrap();
+        assert!(matches!(interpret(&unset), Err(BackendError::InvalidIr(_))));
+    }
+
+    #[test]
+    fn jumps_go_to_their_labels() {
+        let int = FfiType::INT;
+        use Instruction::*;
+        // for (i = 3; i > 0; i -= 1) print(i)
+        let module = CompiledModule::from_instructions(vec![
+            PushInt(3),
+            StoreLocal { slot: 0, ty: int },
+            Label(0),
+            LoadLocal { slot: 0, ty: int },
+            PushInt(0),
+            Compare { op: CompareOp::Gt, ty: int },
+            JumpIfFalse(1),
+            LoadLocal { slot: 0, ty: int },
+            PrintInt,
+            LoadLocal { slot: 0, ty: int },
+            PushInt(1),
+            Arith { op: ArithOp::Sub, ty: int },
+            StoreLocal { slot: 0, ty: int },
+            Jump(0),
+            Label(1),
+        ])
+        .unwrap();
+        assert_eq!(interpret(&module).unwrap(), "321");
+        assert_eq!(step_with_exit_code(&module).unwrap(), ("321".to_string(), 0));
+        let missing = CompiledModule::from_instructions(vec![Jump(7)]).unwrap();
+        assert!(matches!(interpret(&missing), Err(BackendError::InvalidIr(_))));
+    }
+
+    #[test]
+    fn traps_panic_only_when_their_condition_holds() {
+        let i8 = FfiType::Int { bits: 8, signed: true };
+        use Instruction::*;
+        let trap = |abort| Trap { message: "attempt to add with overflow".to_string(), backtrace: String::new(), abort };
+        let module = CompiledModule::from_instructions(vec![
+            PushInt(100),
+            PushInt(27),
+            Overflows { op: ArithOp::Add, ty: i8 },
+            trap(false),
+            PushStr("fits ".to_string()),
+            PrintStr,
+            PushInt(100),
+            PushInt(28),
+            Overflows { op: ArithOp::Add, ty: i8 },
+            trap(false),
+            PushStr("never printed".to_string()),
+            PrintStr,
+        ])
+        .unwrap();
+        assert_eq!(interpret_with_exit_code(&module).unwrap(), ("fits ".to_string(), PANIC_EXIT_CODE));
+        let aborts = CompiledModule::from_instructions(vec![PushBool(true), trap(true)]).unwrap();
+        assert_eq!(interpret_with_exit_code(&aborts).unwrap().1, ABORT_EXIT_CODE);
+    }
+
+    #[test]
+    fn extern_calls_need_a_declaration_and_native_code() {
+        let call = Instruction::CallExtern {
+            name: "abs".to_string(),
+            args: vec![FfiType::Int { bits: 32, signed: true }],
+            result: Some(FfiType::Int { bits: 32, signed: true }),
+        };
+        let module = CompiledModule::from_instructions(vec![Instruction::PushInt(-3), call]).unwrap();
+        assert!(matches!(module.extern_function("abs"), Err(BackendError::InvalidIr(_))));
+        assert!(matches!(interpret(&module), Err(BackendError::Generic(_))));
+
+        let module = module.with_externs(vec![ExternFunction {
+            name: "abs".to_string(),
+            params: vec![FfiType::Int { bits: 32, signed: true }],
+            return_type: Some(FfiType::Int { bits: 32, signed: true }),
+            variadic: false,
+        }]);
+        assert_eq!(module.extern_function("abs").unwrap().params.len(), 1);
+        assert_eq!(FfiType::Int { bits: 8, signed: false }.promoted(), FfiType::Int { bits: 32, signed: true });
+        assert_eq!(FfiType::Float { bits: 32 }.promoted(), FfiType::Float { bits: 64 });
+    }
+
+    #[test]
+    fn interpreters_call_the_standard_library() {
+        use Instruction::{PrintStr, PushInt, PushStr};
+        let extern_call = |name: &str, args: Vec<FfiType>, result: Option<FfiType>| Instruction::CallExtern {
+            name: name.to_string(),
+            args,
+            result,
+        };
+        let module = CompiledModule::from_instructions(vec![
+            PushStr("PATH".to_string()),
+            extern_call("tlang_env_var", vec![FfiType::Str], Some(FfiType::Str)),
+            PrintStr,
+            PushStr("!".to_string()),
+            extern_call("tlang_io_write", vec![FfiType::Str], None),
+            PushInt(4),
+            extern_call("tlang_process_exit", vec![FfiType::Int { bits: 32, signed: true }], None),
+            PushStr("unreachable".to_string()),
+            PrintStr,
+        ])
+        .unwrap();
+        let expected = (format!("{}!", std::env::var("PATH").unwrap_or_default()), 4);
+        assert_eq!(interpret_with_exit_code(&module).unwrap(), expected);
+        assert_eq!(step_with_exit_code(&module).unwrap(), expected);
+    }
+
+    struct DropNops;
+
+    impl Optimizer for DropNops {
+        fn name(&self) -> &'static str {
+            "drop-nops"
+        }
+
+        fn min_opt_level(&self) -> u8 {
+            2
+        }
+
+        fn optimize(&self, module: CompiledModule) -> Result<CompiledModule, BackendError> {
+            let instrs = module
+                .decode()?
+                .into_iter()
+                .filter(|i| *i != Instruction::Nop)
+                .collect();
+            CompiledModule::from_instructions(instrs)
+        }
+    }
+
+    #[test]
+    fn optimizers_follow_level_and_overrides() {
+        let module =
+            CompiledModule::from_instructions(vec![Instruction::Nop, Instruction::CallPrint])
+                .unwrap();
+        let passes: [&dyn Optimizer; 1] = [&DropNops];
+        let mut overrides = HashMap::new();
+
+        let kept = run_optimizer_list(&passes, module.clone(), 1, &overrides).unwrap();
+        assert_eq!(kept.decode().unwrap().len(), 2);
+
+        let dropped = run_optimizer_list(&passes, module.clone(), 2, &overrides).unwrap();
+        assert_eq!(dropped.decode().unwrap(), vec![Instruction::CallPrint]);
+
+        overrides.insert("drop-nops".to_string(), false);
+        let disabled = run_optimizer_list(&passes, module.clone(), 3, &overrides).unwrap();
+        assert_eq!(disabled.decode().unwrap().len(), 2);
+
+        overrides.insert("drop-nops".to_string(), true);
+        let forced = run_optimizer_list(&passes, module, 0, &overrides).unwrap();
+        assert_eq!(forced.decode().unwrap(), vec![Instruction::CallPrint]);
+    }
 }
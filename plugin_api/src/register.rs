@@ -0,0 +1,1128 @@
+//! Register bytecode, which `interpret` runs a module's TIR as.
+//!
+//! `RegisterProgram::compile` gives each function of a module a chunk of
+//! bytes: an opcode of `op`, then its operands, each a little-endian `u32`
+//! register, jump target or index, and for a few opcodes a byte or two
+//! more. A function's frame is one `i64` register per local, then one per
+//! distinct constant it uses, then one per value it computes; floats are
+//! held as their bits, booleans as 0 or 1 and strings as an index into the
+//! program's strings. The constants' registers are the function's constant
+//! pool: the chunk keeps a frame with them filled in, which a `Call` copies
+//! to the top of the register file, so entering a function runs no code.
+//!
+//! Most of what stack code spells as loads and stores runs as nothing. A
+//! `Load` read only in its block, before the local is stored again, reads
+//! the local's register; a value stored in a local by the instruction
+//! right after the one computing it is computed into the local's register.
+//! A `Load` control may reach before the local is stored, even one whose
+//! value nothing reads, checks a flag register the stores set, and fails
+//! as `ValueStacks` does.
+//!
+//! `RegisterProgram::run` is one `match` on the opcode byte in a loop,
+//! which compiles to a jump table, as computed gotos would. The register
+//! file and the stack of callers are one `Vec` each, with room for deep
+//! calls made up front.
+
+use crate::intrinsics::{self, Called};
+use crate::{float_to_int, format_bool, format_float, report_panic, wrap_int, BackendError};
+use crate::{ABORT_EXIT_CODE, PANIC_EXIT_CODE};
+use shared::tir::{
+    self, ArithOp, BlockId, CompareOp, Constant, FfiType, Function, InstId, InstKind, LocalId, Terminator,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// The opcodes, each with the operands that follow it.
+mod op {
+    /// `dst src`: copy the register.
+    pub const MOVE: u8 = 0;
+    /// `dst a b`: the integer `a + b`, wrapping around at 64 bits; the
+    /// other integer opcodes up to `IREM` compute as `ArithOp::int` does.
+    pub const IADD: u8 = 1;
+    pub const ISUB: u8 = 2;
+    pub const IMUL: u8 = 3;
+    pub const IDIV: u8 = 4;
+    pub const IREM: u8 = 5;
+    /// `dst a b`: the float `a + b`, and so on up to `FREM`.
+    pub const FADD: u8 = 6;
+    pub const FSUB: u8 = 7;
+    pub const FMUL: u8 = 8;
+    pub const FDIV: u8 = 9;
+    pub const FREM: u8 = 10;
+    /// `dst a b`: whether the integers, or booleans, `a == b`, and so on
+    /// up to `IGE`.
+    pub const IEQ: u8 = 11;
+    pub const INE: u8 = 12;
+    pub const ILT: u8 = 13;
+    pub const ILE: u8 = 14;
+    pub const IGT: u8 = 15;
+    pub const IGE: u8 = 16;
+    /// `dst a b`: whether the floats `a == b`, and so on up to `FGE`.
+    pub const FEQ: u8 = 17;
+    pub const FNE: u8 = 18;
+    pub const FLT: u8 = 19;
+    pub const FLE: u8 = 20;
+    pub const FGT: u8 = 21;
+    pub const FGE: u8 = 22;
+    /// `dst src`: the boolean negated.
+    pub const NOT: u8 = 23;
+    /// `dst a b`, then the bytes `op bits signed`: whether `a op b`
+    /// overflows the integer type.
+    pub const OVERFLOWS: u8 = 24;
+    /// `dst src`: the integer as a float.
+    pub const INT_TO_FLOAT: u8 = 25;
+    /// `dst src`, then the bytes `bits signed`: see `float_to_int`.
+    pub const FLOAT_TO_INT: u8 = 26;
+    /// `dst src`, then the bytes `bits signed`: see `wrap_int`.
+    pub const WRAP_INT: u8 = 27;
+    /// `src`: print the value as its type's stack instruction does.
+    pub const PRINT_INT: u8 = 28;
+    pub const PRINT_FLOAT: u8 = 29;
+    pub const PRINT_BOOL: u8 = 30;
+    pub const PRINT_STR: u8 = 31;
+    /// `target`: continue at the byte.
+    pub const JUMP: u8 = 32;
+    /// `condition target`: continue at `target` if the boolean is true.
+    pub const JUMP_IF: u8 = 33;
+    /// `condition target`: continue at `target` if the boolean is false.
+    pub const JUMP_IF_NOT: u8 = 34;
+    /// `condition then otherwise`
+    pub const BRANCH: u8 = 35;
+    /// `value table`: continue where the chunk's switch table sends the
+    /// integer.
+    pub const SWITCH: u8 = 36;
+    /// `condition trap`: if the boolean is true, panic with the program's
+    /// trap.
+    pub const TRAP: u8 = 37;
+    /// `code`: end the program.
+    pub const EXIT: u8 = 38;
+    /// `function dst count`, then `count` registers: run the function with
+    /// the registers in its parameters, and its result in `dst`, unless
+    /// that is `NONE`.
+    pub const CALL: u8 = 39;
+    /// Give the result local back to the caller.
+    pub const RETURN: u8 = 40;
+    /// `extern dst`, then a register per argument: call the extern
+    /// function as `intrinsics::call` does, with its result in `dst` if it
+    /// has one.
+    pub const EXTERN: u8 = 41;
+    /// `flag`: set the flag register of a local to say it is stored.
+    pub const SET: u8 = 42;
+    /// `flag failure`: fail with the program's failure unless the flag
+    /// register is set.
+    pub const CHECK: u8 = 43;
+    /// `failure`: fail with the program's failure.
+    pub const FAIL: u8 = 44;
+    /// `a b target`, then the byte `op`: continue at `target` unless the
+    /// integers, or booleans, `a op b`, as a `Branch` on a `Compare` does.
+    pub const JUMP_UNLESS: u8 = 45;
+    /// `a b trap`, then the bytes `op bits signed`: panic with the
+    /// program's trap if `a op b` overflows the integer type, as a `Trap`
+    /// on `Overflows` does.
+    pub const TRAP_OVERFLOWS: u8 = 46;
+}
+
+/// The register operand of a `CALL` whose result nobody takes.
+const NONE: u32 = u32::MAX;
+
+/// How many registers, and callers, the program starts with room for.
+const REGISTERS: usize = 1 << 12;
+const CALLERS: usize = 1 << 8;
+
+/// A module as register bytecode, ready to `run`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegisterProgram {
+    /// One per function of the module, the entry point first.
+    chunks: Vec<Chunk>,
+    /// The strings of the constant pools, which string registers index.
+    strs: Vec<String>,
+    /// Panics `TRAP` may start.
+    traps: Vec<TrapMessage>,
+    /// Messages of the invalid IR `CHECK` and `FAIL` fail with.
+    failures: Vec<String>,
+    /// The extern functions `EXTERN` calls.
+    externs: Vec<ExternCall>,
+}
+
+/// An extern function `EXTERN` calls, with the types of its arguments and
+/// of the result it takes.
+#[derive(Debug, Clone, PartialEq)]
+struct ExternCall {
+    name: String,
+    args: Vec<FfiType>,
+    result: Option<FfiType>,
+}
+
+/// The bytecode of one function.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Chunk {
+    code: Vec<u8>,
+    /// The registers a call starts with: its constants filled in, zeros
+    /// elsewhere.
+    frame: Vec<i64>,
+    /// The register `RETURN` gives back, if the function returns a value.
+    result: Option<u32>,
+    /// The cases of its `SWITCH`es, sorted, and where to go by default.
+    switches: Vec<(Vec<(i64, u32)>, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TrapMessage {
+    message: String,
+    backtrace: String,
+    abort: bool,
+}
+
+/// A caller, as a `CALL` left it.
+struct Caller {
+    chunk: usize,
+    resume: usize,
+    base: usize,
+    dst: u32,
+}
+
+impl RegisterProgram {
+    /// The bytecode of `module`.
+    pub fn compile(module: &tir::Module) -> Result<Self, BackendError> {
+        let functions: HashMap<&str, u32> =
+            module.functions.iter().enumerate().map(|(i, function)| (function.name.as_str(), i as u32)).collect();
+        let mut program = RegisterProgram::default();
+        // Strings by their index in `strs`.
+        let mut strs = HashMap::new();
+        for function in &module.functions {
+            let chunk = ChunkCompiler::new(&mut program, &mut strs, &functions, function).compile()?;
+            program.chunks.push(chunk);
+        }
+        Ok(program)
+    }
+
+    /// How many bytes of code the functions compiled to, in order.
+    pub fn code_sizes(&self) -> Vec<usize> {
+        self.chunks.iter().map(|chunk| chunk.code.len()).collect()
+    }
+
+    /// Run the program to completion and return everything it prints and
+    /// the code it exits with.
+    pub fn run(&self) -> Result<(String, i32), BackendError> {
+        let mut out = String::new();
+        // Strings the program made as it ran, which the string registers
+        // past `strs` index.
+        let mut made: Vec<String> = Vec::new();
+        let Some(entry) = self.chunks.first() else {
+            return Ok((out, 0));
+        };
+        let mut registers: Vec<i64> = Vec::with_capacity(REGISTERS.max(entry.frame.len()));
+        registers.extend_from_slice(&entry.frame);
+        let mut callers: Vec<Caller> = Vec::with_capacity(CALLERS);
+        let mut current = 0;
+        let mut chunk = entry;
+        let mut code: &[u8] = &chunk.code;
+        let mut base = 0;
+        let mut pc = 0;
+
+        macro_rules! operand {
+            ($n:expr) => {
+                read(code, pc + 1 + 4 * $n)
+            };
+        }
+        macro_rules! reg {
+            ($n:expr) => {
+                registers[base + operand!($n)]
+            };
+        }
+        macro_rules! byte {
+            ($n:expr) => {
+                code[pc + $n]
+            };
+        }
+        macro_rules! binary {
+            (|$a:ident, $b:ident| $value:expr) => {{
+                let ($a, $b) = (reg!(1), reg!(2));
+                reg!(0) = $value;
+                pc += 13;
+            }};
+        }
+        macro_rules! float {
+            (|$a:ident, $b:ident| $value:expr) => {
+                binary!(|a, b| {
+                    let ($a, $b) = (f64::from_bits(a as u64), f64::from_bits(b as u64));
+                    ($value).to_bits() as i64
+                })
+            };
+        }
+        macro_rules! compare {
+            ($op:ident) => {
+                binary!(|a, b| i64::from(CompareOp::$op.holds(a, b)))
+            };
+            ($op:ident, float) => {
+                binary!(|a, b| i64::from(CompareOp::$op.holds(f64::from_bits(a as u64), f64::from_bits(b as u64))))
+            };
+        }
+
+        loop {
+            match code[pc] {
+                op::MOVE => {
+                    reg!(0) = reg!(1);
+                    pc += 9;
+                }
+                op::IADD => binary!(|a, b| a.wrapping_add(b)),
+                op::ISUB => binary!(|a, b| a.wrapping_sub(b)),
+                op::IMUL => binary!(|a, b| a.wrapping_mul(b)),
+                op::IDIV => binary!(|a, b| ArithOp::Div.int(a, b)),
+                op::IREM => binary!(|a, b| ArithOp::Rem.int(a, b)),
+                op::FADD => float!(|a, b| a + b),
+                op::FSUB => float!(|a, b| a - b),
+                op::FMUL => float!(|a, b| a * b),
+                op::FDIV => float!(|a, b| a / b),
+                op::FREM => float!(|a, b| a % b),
+                op::IEQ => compare!(Eq),
+                op::INE => compare!(Ne),
+                op::ILT => compare!(Lt),
+                op::ILE => compare!(Le),
+                op::IGT => compare!(Gt),
+                op::IGE => compare!(Ge),
+                op::FEQ => compare!(Eq, float),
+                op::FNE => compare!(Ne, float),
+                op::FLT => compare!(Lt, float),
+                op::FLE => compare!(Le, float),
+                op::FGT => compare!(Gt, float),
+                op::FGE => compare!(Ge, float),
+                op::NOT => {
+                    reg!(0) = i64::from(reg!(1) == 0);
+                    pc += 9;
+                }
+                op::OVERFLOWS => {
+                    let ty = FfiType::Int { bits: byte!(14), signed: byte!(15) != 0 };
+                    reg!(0) = i64::from(ARITH_OPS[usize::from(byte!(13))].overflows(reg!(1), reg!(2), ty));
+                    pc += 16;
+                }
+                op::INT_TO_FLOAT => {
+                    reg!(0) = (reg!(1) as f64).to_bits() as i64;
+                    pc += 9;
+                }
+                op::FLOAT_TO_INT => {
+                    reg!(0) = float_to_int(f64::from_bits(reg!(1) as u64), byte!(9), byte!(10) != 0);
+                    pc += 11;
+                }
+                op::WRAP_INT => {
+                    reg!(0) = wrap_int(reg!(1), byte!(9), byte!(10) != 0);
+                    pc += 11;
+                }
+                op::PRINT_INT => {
+                    let _ = write!(out, "{}", reg!(0));
+                    pc += 5;
+                }
+                op::PRINT_FLOAT => {
+                    out.push_str(&format_float(f64::from_bits(reg!(0) as u64)));
+                    pc += 5;
+                }
+                op::PRINT_BOOL => {
+                    out.push_str(format_bool(reg!(0) != 0));
+                    pc += 5;
+                }
+                op::PRINT_STR => {
+                    out.push_str(self.string(reg!(0), &made));
+                    pc += 5;
+                }
+                op::JUMP => pc = operand!(0),
+                op::JUMP_IF => pc = if reg!(0) != 0 { operand!(1) } else { pc + 9 },
+                op::JUMP_IF_NOT => pc = if reg!(0) == 0 { operand!(1) } else { pc + 9 },
+                op::BRANCH => pc = if reg!(0) != 0 { operand!(1) } else { operand!(2) },
+                op::SWITCH => {
+                    let (cases, default) = &chunk.switches[operand!(1)];
+                    let value = reg!(0);
+                    pc = match cases.binary_search_by_key(&value, |&(case, _)| case) {
+                        Ok(i) => cases[i].1 as usize,
+                        Err(_) => *default as usize,
+                    };
+                }
+                op::TRAP => {
+                    if reg!(0) != 0 {
+                        let trap = &self.traps[operand!(1)];
+                        report_panic(&trap.message, &trap.backtrace);
+                        return Ok((out, if trap.abort { ABORT_EXIT_CODE } else { PANIC_EXIT_CODE }));
+                    }
+                    pc += 9;
+                }
+                op::EXIT => return Ok((out, reg!(0) as i32)),
+                op::CALL => {
+                    let callee = operand!(0);
+                    let count = operand!(2);
+                    let top = registers.len();
+                    registers.extend_from_slice(&self.chunks[callee].frame);
+                    for i in 0..count {
+                        registers[top + i] = reg!(3 + i);
+                    }
+                    let dst = operand!(1) as u32;
+                    callers.push(Caller { chunk: current, resume: pc + 13 + 4 * count, base, dst });
+                    current = callee;
+                    chunk = &self.chunks[current];
+                    code = &chunk.code;
+                    base = top;
+                    pc = 0;
+                }
+                op::RETURN => {
+                    let result = chunk.result.map(|result| registers[base + result as usize]);
+                    registers.truncate(base);
+                    let Some(caller) = callers.pop() else {
+                        return Ok((out, 0));
+                    };
+                    current = caller.chunk;
+                    chunk = &self.chunks[current];
+                    code = &chunk.code;
+                    base = caller.base;
+                    pc = caller.resume;
+                    if let Some(result) = result
+                        && caller.dst != NONE
+                    {
+                        registers[base + caller.dst as usize] = result;
+                    }
+                }
+                op::EXTERN => {
+                    let call = &self.externs[operand!(0)];
+                    let args: Vec<Constant> =
+                        call.args.iter().enumerate().map(|(i, &ty)| self.value(ty, reg!(2 + i), &made)).collect();
+                    match intrinsics::call(&call.name, &args, &mut out)? {
+                        Called::Exited(code) => return Ok((out, code)),
+                        Called::Returned(Some(value)) if call.result.is_some() => {
+                            reg!(1) = self.register(value, &mut made);
+                        }
+                        Called::Returned(_) => {}
+                    }
+                    pc += 9 + 4 * call.args.len();
+                }
+                op::SET => {
+                    reg!(0) = 1;
+                    pc += 5;
+                }
+                op::CHECK => {
+                    if reg!(0) == 0 {
+                        return Err(BackendError::InvalidIr(self.failures[operand!(1)].clone()));
+                    }
+                    pc += 9;
+                }
+                op::FAIL => return Err(BackendError::InvalidIr(self.failures[operand!(0)].clone())),
+                op::TRAP_OVERFLOWS => {
+                    let ty = FfiType::Int { bits: byte!(14), signed: byte!(15) != 0 };
+                    if ARITH_OPS[usize::from(byte!(13))].overflows(reg!(0), reg!(1), ty) {
+                        let trap = &self.traps[operand!(2)];
+                        report_panic(&trap.message, &trap.backtrace);
+                        return Ok((out, if trap.abort { ABORT_EXIT_CODE } else { PANIC_EXIT_CODE }));
+                    }
+                    pc += 16;
+                }
+                op::JUMP_UNLESS => {
+                    let holds = COMPARE_OPS[usize::from(byte!(13))].holds(reg!(0), reg!(1));
+                    pc = if holds { pc + 14 } else { operand!(2) };
+                }
+                other => return Err(BackendError::InvalidIr(format!("no opcode {}", other))),
+            }
+        }
+    }
+
+    /// The string a string register holds, `made` being the strings the
+    /// program made so far.
+    fn string<'a>(&'a self, register: i64, made: &'a [String]) -> &'a str {
+        let index = register as usize;
+        match self.strs.get(index) {
+            Some(s) => s,
+            None => &made[index - self.strs.len()],
+        }
+    }
+
+    /// The value of type `ty` a register holds.
+    fn value(&self, ty: FfiType, register: i64, made: &[String]) -> Constant {
+        match ty {
+            FfiType::Int { .. } => Constant::Int(register),
+            FfiType::Float { .. } => Constant::Float(f64::from_bits(register as u64)),
+            FfiType::Bool => Constant::Bool(register != 0),
+            FfiType::Str => Constant::Str(self.string(register, made).to_string()),
+        }
+    }
+
+    /// `value` as a register holds it, a string as one of `made`.
+    fn register(&self, value: Constant, made: &mut Vec<String>) -> i64 {
+        match value {
+            Constant::Int(n) => n,
+            Constant::Float(f) => f.to_bits() as i64,
+            Constant::Bool(b) => i64::from(b),
+            Constant::Str(s) => {
+                made.push(s);
+                (self.strs.len() + made.len() - 1) as i64
+            }
+        }
+    }
+}
+
+/// The operators `OVERFLOWS` numbers, in order.
+const ARITH_OPS: [ArithOp; 5] = [ArithOp::Add, ArithOp::Sub, ArithOp::Mul, ArithOp::Div, ArithOp::Rem];
+
+/// The comparisons `JUMP_UNLESS` numbers, in the order `IEQ` on has them.
+const COMPARE_OPS: [CompareOp; 6] =
+    [CompareOp::Eq, CompareOp::Ne, CompareOp::Lt, CompareOp::Le, CompareOp::Gt, CompareOp::Ge];
+
+/// The `u32` operand at byte `at`, as an index.
+#[inline(always)]
+fn read(code: &[u8], at: usize) -> usize {
+    u32::from_le_bytes(code[at..at + 4].try_into().expect("an operand is four bytes")) as usize
+}
+
+/// Compiles one function to a `Chunk`.
+struct ChunkCompiler<'a> {
+    program: &'a mut RegisterProgram,
+    strs: &'a mut HashMap<String, usize>,
+    functions: &'a HashMap<&'a str, u32>,
+    function: &'a Function,
+    /// The instructions of each block that emit something, in order.
+    placed: Vec<Vec<InstId>>,
+    /// The register of each value, `NONE` for one without.
+    registers: Vec<u32>,
+    /// The registers of the constants, by kind and bits.
+    constants: HashMap<(u8, i64), u32>,
+    /// The flag register of each local a load may read unset.
+    flags: Vec<Option<u32>>,
+    /// The locals each block is entered with stored, `None` for a block
+    /// control never reaches.
+    stored: Vec<Option<Vec<bool>>>,
+    /// The values computed into the local whose store comes right after.
+    forwarded: HashMap<InstId, u32>,
+    /// The loads whose value is the one stored before them in the block.
+    forwarded_loads: HashSet<InstId>,
+    /// Whether anything reads each local's register.
+    read_locals: Vec<bool>,
+    /// The constants' registers.
+    pool: HashSet<u32>,
+    /// The comparisons the `Branch` of their block jumps on, emitted with it.
+    fused: HashSet<InstId>,
+    frame: Vec<i64>,
+    code: Vec<u8>,
+    /// Where each block's code starts, and the operands to patch with that.
+    starts: Vec<u32>,
+    patches: Vec<(usize, BlockId)>,
+    switches: Vec<(Vec<(i64, BlockId)>, BlockId)>,
+}
+
+impl<'a> ChunkCompiler<'a> {
+    fn new(
+        program: &'a mut RegisterProgram,
+        strs: &'a mut HashMap<String, usize>,
+        functions: &'a HashMap<&'a str, u32>,
+        function: &'a Function,
+    ) -> Self {
+        let live = function.live_insts();
+        let placed = function
+            .blocks
+            .iter()
+            .map(|block| {
+                // A load nothing reads may still fail.
+                let emits = |inst: &&InstId| match function.inst(**inst).kind {
+                    InstKind::Marker(_) => false,
+                    InstKind::Load(_) => true,
+                    _ => live.contains(inst),
+                };
+                block.insts.iter().filter(emits).copied().collect()
+            })
+            .collect();
+        ChunkCompiler {
+            program,
+            strs,
+            functions,
+            function,
+            placed,
+            registers: vec![NONE; function.insts.len()],
+            constants: HashMap::new(),
+            flags: vec![None; function.locals.len()],
+            stored: Vec::new(),
+            forwarded: HashMap::new(),
+            forwarded_loads: HashSet::new(),
+            read_locals: Vec::new(),
+            pool: HashSet::new(),
+            fused: HashSet::new(),
+            frame: vec![0; function.locals.len()],
+            code: Vec::new(),
+            starts: Vec::new(),
+            patches: Vec::new(),
+            switches: Vec::new(),
+        }
+    }
+
+    fn compile(mut self) -> Result<Chunk, BackendError> {
+        self.find_stored_locals();
+        self.allocate_registers();
+        for block in 0..self.function.blocks.len() {
+            self.starts.push(self.code.len() as u32);
+            self.emit_block(block)?;
+        }
+        for (at, block) in std::mem::take(&mut self.patches) {
+            let start = self.starts[block.index()];
+            self.code[at..at + 4].copy_from_slice(&start.to_le_bytes());
+        }
+        let switches = std::mem::take(&mut self.switches)
+            .into_iter()
+            .map(|(cases, default)| {
+                let mut table: Vec<(i64, u32)> = Vec::with_capacity(cases.len());
+                for (case, block) in cases {
+                    // The first case of a value is the one taken.
+                    if !table.iter().any(|&(seen, _)| seen == case) {
+                        table.push((case, self.starts[block.index()]));
+                    }
+                }
+                table.sort_unstable_by_key(|&(case, _)| case);
+                (table, self.starts[default.index()])
+            })
+            .collect();
+        Ok(Chunk { code: self.code, frame: self.frame, result: self.function.result.map(|local| local.0), switches })
+    }
+
+    /// Find the locals stored on every path to each block, from the
+    /// parameters on, and give a flag register to each a load may read
+    /// before.
+    fn find_stored_locals(&mut self) {
+        let function = self.function;
+        let n = function.locals.len();
+        let mut stored: Vec<Option<Vec<bool>>> = vec![None; function.blocks.len()];
+        if function.blocks.is_empty() {
+            return;
+        }
+        stored[0] = Some((0..n).map(|local| local < function.params).collect());
+        let mut work = vec![BlockId::ENTRY];
+        while let Some(block) = work.pop() {
+            let mut out = stored[block.index()].clone().expect("a block is worked on once reached");
+            for &inst in &self.placed[block.index()] {
+                if let InstKind::Store { local, .. } = function.inst(inst).kind {
+                    out[local.index()] = true;
+                }
+            }
+            for next in function.block(block).terminator.successors() {
+                let changed = match &mut stored[next.index()] {
+                    Some(entry) => {
+                        let mut changed = false;
+                        for (local, stored) in entry.iter_mut().enumerate() {
+                            if *stored && !out[local] {
+                                *stored = false;
+                                changed = true;
+                            }
+                        }
+                        changed
+                    }
+                    unreached => {
+                        *unreached = Some(out.clone());
+                        true
+                    }
+                };
+                if changed {
+                    work.push(next);
+                }
+            }
+        }
+        for (block, entry) in stored.iter().enumerate() {
+            let Some(entry) = entry else { continue };
+            let mut entry = entry.clone();
+            for &inst in &self.placed[block] {
+                match function.inst(inst).kind {
+                    InstKind::Store { local, .. } => entry[local.index()] = true,
+                    InstKind::Load(local) if !entry[local.index()] && self.flags[local.index()].is_none() => {
+                        self.flags[local.index()] = Some(self.frame.len() as u32);
+                        self.frame.push(0);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.stored = stored;
+    }
+
+    /// Give each value a register: its constant's, its local's, or one of
+    /// its own.
+    fn allocate_registers(&mut self) {
+        let function = self.function;
+        let mut readers: Vec<Vec<(usize, usize)>> = vec![Vec::new(); function.insts.len()];
+        for (block, insts) in self.placed.iter().enumerate() {
+            for (at, &inst) in insts.iter().enumerate() {
+                for value in function.inst(inst).kind.operands() {
+                    readers[value.index()].push((block, at));
+                }
+            }
+            for value in function.blocks[block].terminator.operands() {
+                readers[value.index()].push((block, insts.len()));
+            }
+        }
+        for block in 0..self.placed.len() {
+            for at in 0..self.placed[block].len() {
+                let inst = self.placed[block][at];
+                if let InstKind::Store { local, value } = function.inst(inst).kind
+                    && at > 0
+                    && self.placed[block][at - 1] == value
+                    && readers[value.index()] == [(block, at)]
+                    && computes(&function.inst(value).kind)
+                {
+                    self.forwarded.insert(value, local.0);
+                }
+            }
+        }
+        let locals = function.locals.len() as u32;
+        for block in 0..self.placed.len() {
+            // The registers of the values last stored in locals in the
+            // block, where no other instruction writes them.
+            let mut stored: HashMap<LocalId, u32> = HashMap::new();
+            for at in 0..self.placed[block].len() {
+                let inst = self.placed[block][at];
+                let register = match &function.inst(inst).kind {
+                    InstKind::Const(constant) => self.constant(constant),
+                    InstKind::Store { local, value } => {
+                        // What was stored from the local is in its register
+                        // no more.
+                        stored.retain(|_, register| *register != local.0);
+                        match self.reg(*value) {
+                            NONE => stored.remove(local),
+                            register => stored.insert(*local, register),
+                        };
+                        NONE
+                    }
+                    InstKind::Load(local) => {
+                        // Read in the block only, and before a local's
+                        // register is stored to again, the value may be read
+                        // from that register: the last stored to the local,
+                        // or the local's own.
+                        let uses = &readers[inst.index()];
+                        let in_block = uses.iter().all(|&(used, used_at)| used == block && used_at > at);
+                        let end = uses.iter().map(|&(_, used_at)| used_at).max().unwrap_or(at + 1);
+                        let end = end.min(self.placed[block].len());
+                        let holds = |register: u32| {
+                            let stores = |between: &InstId| match function.inst(*between).kind {
+                                InstKind::Store { local: written, .. } => written.0 == register,
+                                _ => false,
+                            };
+                            register >= locals || in_block && !self.placed[block][at + 1..end].iter().any(stores)
+                        };
+                        match stored.get(local) {
+                            Some(&register) if holds(register) => {
+                                self.forwarded_loads.insert(inst);
+                                register
+                            }
+                            _ if holds(local.0) => local.0,
+                            _ => self.fresh(),
+                        }
+                    }
+                    _ if function.inst(inst).ty.is_none() => NONE,
+                    _ => match (self.fold(inst), self.forwarded.get(&inst)) {
+                        (Some(constant), _) => constant,
+                        (None, Some(&local)) => local,
+                        (None, None) => self.fresh(),
+                    },
+                };
+                self.registers[inst.index()] = register;
+            }
+            for at in 1..self.placed[block].len() {
+                if let InstKind::Trap { condition, .. } = function.inst(self.placed[block][at]).kind
+                    && self.placed[block][at - 1] == condition
+                    && let InstKind::Overflows { ty: FfiType::Int { .. }, .. } = function.inst(condition).kind
+                    && readers[condition.index()] == [(block, at)]
+                    && !self.pool.contains(&self.reg(condition))
+                {
+                    self.fused.insert(condition);
+                }
+            }
+            if let Terminator::Branch { condition, .. } = function.blocks[block].terminator
+                && let InstKind::Compare { lhs, .. } = function.inst(condition).kind
+                && matches!(self.ty(lhs), Some(FfiType::Int { .. } | FfiType::Bool))
+                && readers[condition.index()] == [(block, self.placed[block].len())]
+                && self.placed[block].contains(&condition)
+                && !self.pool.contains(&self.reg(condition))
+            {
+                self.fused.insert(condition);
+            }
+        }
+        // What a `RETURN` or a load left reads.
+        self.read_locals = vec![false; function.locals.len()];
+        if let Some(result) = function.result {
+            self.read_locals[result.index()] = true;
+        }
+        for inst in self.placed.iter().flatten() {
+            if let InstKind::Load(local) = function.inst(*inst).kind {
+                let read = if self.forwarded_loads.contains(inst) { self.reg(*inst) } else { local.0 };
+                if read < locals {
+                    self.read_locals[read as usize] = true;
+                }
+            }
+        }
+    }
+
+    /// The register of the constant `inst` computes, if what it reads are
+    /// constants.
+    fn fold(&mut self, inst: InstId) -> Option<u32> {
+        let known = |value: InstId| self.known(value);
+        let float = |bits: i64| f64::from_bits(bits as u64);
+        let constant = match &self.function.inst(inst).kind {
+            InstKind::Arith { op, lhs, rhs } => match self.ty(*lhs)? {
+                FfiType::Int { .. } => Constant::Int(op.int(known(*lhs)?, known(*rhs)?)),
+                FfiType::Float { .. } => Constant::Float(op.float(float(known(*lhs)?), float(known(*rhs)?))),
+                _ => return None,
+            },
+            InstKind::Compare { op, lhs, rhs } => Constant::Bool(match self.ty(*lhs)? {
+                FfiType::Int { .. } | FfiType::Bool => op.holds(known(*lhs)?, known(*rhs)?),
+                FfiType::Float { .. } => op.holds(float(known(*lhs)?), float(known(*rhs)?)),
+                FfiType::Str => return None,
+            }),
+            InstKind::Not(value) => Constant::Bool(known(*value)? == 0),
+            InstKind::Overflows { op, lhs, rhs, ty } => Constant::Bool(op.overflows(known(*lhs)?, known(*rhs)?, *ty)),
+            InstKind::Cast { value, to } => match (self.ty(*value)?, *to) {
+                (FfiType::Int { .. }, FfiType::Float { .. }) => Constant::Float(known(*value)? as f64),
+                (FfiType::Float { .. }, FfiType::Int { bits, signed }) => {
+                    Constant::Int(float_to_int(float(known(*value)?), bits, signed))
+                }
+                (FfiType::Int { .. }, FfiType::Int { bits, signed }) => {
+                    Constant::Int(wrap_int(known(*value)?, bits, signed))
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(self.constant(&constant))
+    }
+
+    /// Where control going to `block` ends up once it has gone through the
+    /// blocks that only jump on.
+    fn destination(&self, mut block: BlockId) -> BlockId {
+        for _ in 0..self.placed.len() {
+            match self.function.block(block).terminator {
+                Terminator::Jump(target) if self.placed[block.index()].is_empty() => block = target,
+                _ => break,
+            }
+        }
+        block
+    }
+
+    fn fresh(&mut self) -> u32 {
+        self.frame.push(0);
+        (self.frame.len() - 1) as u32
+    }
+
+    /// The register holding `constant`, in the constant pool.
+    fn constant(&mut self, constant: &Constant) -> u32 {
+        let key = match constant {
+            Constant::Int(n) => (0, *n),
+            Constant::Float(f) => (1, f.to_bits() as i64),
+            Constant::Bool(b) => (2, i64::from(*b)),
+            Constant::Str(s) => {
+                let next = self.program.strs.len();
+                let index = *self.strs.entry(s.clone()).or_insert(next);
+                if index == next {
+                    self.program.strs.push(s.clone());
+                }
+                (3, index as i64)
+            }
+        };
+        if let Some(&register) = self.constants.get(&key) {
+            return register;
+        }
+        let register = self.fresh();
+        self.frame[register as usize] = key.1;
+        self.constants.insert(key, register);
+        self.pool.insert(register);
+        register
+    }
+
+    fn reg(&self, value: InstId) -> u32 {
+        self.registers[value.index()]
+    }
+
+    /// The value of `value`, if it is a constant.
+    fn known(&self, value: InstId) -> Option<i64> {
+        let register = self.reg(value);
+        self.pool.contains(&register).then(|| self.frame[register as usize])
+    }
+
+    fn ty(&self, value: InstId) -> Option<FfiType> {
+        self.function.inst(value).ty
+    }
+
+    fn emit(&mut self, op: u8, operands: &[u32]) {
+        self.code.push(op);
+        for operand in operands {
+            self.code.extend_from_slice(&operand.to_le_bytes());
+        }
+    }
+
+    /// Emit a jump whose last operand is where `target` starts.
+    fn emit_jump(&mut self, op: u8, operands: &[u32], target: BlockId) {
+        self.emit(op, operands);
+        self.patches.push((self.code.len(), target));
+        self.code.extend_from_slice(&0u32.to_le_bytes());
+    }
+
+    fn fail(&mut self, message: String) {
+        self.program.failures.push(message);
+        self.emit(op::FAIL, &[(self.program.failures.len() - 1) as u32]);
+    }
+
+    fn emit_block(&mut self, block: usize) -> Result<(), BackendError> {
+        let function = self.function;
+        let mut stored = self.stored[block].clone();
+        for at in 0..self.placed[block].len() {
+            let inst = self.placed[block][at];
+            let dst = self.reg(inst);
+            let kind = &function.inst(inst).kind;
+            // Folded to a constant, or emitted with its `Branch`.
+            if computes(kind) && !matches!(kind, InstKind::Call { .. }) && self.pool.contains(&dst)
+                || self.fused.contains(&inst)
+            {
+                continue;
+            }
+            match kind {
+                InstKind::Const(_) | InstKind::Marker(_) => {}
+                InstKind::Print(value) => {
+                    let op = match self.ty(*value) {
+                        Some(FfiType::Int { .. }) => op::PRINT_INT,
+                        Some(FfiType::Float { .. }) => op::PRINT_FLOAT,
+                        Some(FfiType::Bool) => op::PRINT_BOOL,
+                        Some(FfiType::Str) => op::PRINT_STR,
+                        None => return Err(BackendError::InvalidIr(format!("{:?} prints no value", inst))),
+                    };
+                    self.emit(op, &[self.reg(*value)]);
+                }
+                InstKind::Cast { value, to } => {
+                    let src = self.reg(*value);
+                    match (self.ty(*value), *to) {
+                        (Some(FfiType::Int { .. }), FfiType::Float { .. }) => self.emit(op::INT_TO_FLOAT, &[dst, src]),
+                        (Some(FfiType::Float { .. }), FfiType::Int { bits, signed }) => {
+                            self.emit(op::FLOAT_TO_INT, &[dst, src]);
+                            self.code.extend([bits, u8::from(signed)]);
+                        }
+                        (Some(FfiType::Int { .. }), FfiType::Int { bits, signed }) if bits < 64 => {
+                            self.emit(op::WRAP_INT, &[dst, src]);
+                            self.code.extend([bits, u8::from(signed)]);
+                        }
+                        _ if dst != src => self.emit(op::MOVE, &[dst, src]),
+                        _ => {}
+                    }
+                }
+                InstKind::CallExtern { name, args, types } => {
+                    let call = ExternCall { name: name.clone(), args: types.clone(), result: self.ty(inst) };
+                    self.program.externs.push(call);
+                    let mut operands = vec![(self.program.externs.len() - 1) as u32, dst];
+                    operands.extend(args.iter().map(|&arg| self.reg(arg)));
+                    self.emit(op::EXTERN, &operands);
+                }
+                InstKind::Call { function: name, args } => {
+                    let Some(&callee) = self.functions.get(name.as_str()) else {
+                        return Err(BackendError::InvalidIr(format!("call of missing function `{}`", name)));
+                    };
+                    let mut operands = vec![callee, dst, args.len() as u32];
+                    operands.extend(args.iter().map(|&arg| self.reg(arg)));
+                    self.emit(op::CALL, &operands);
+                }
+                InstKind::Load(local) => {
+                    if let Some(stored) = &stored
+                        && !stored[local.index()]
+                        && let Some(flag) = self.flags[local.index()]
+                    {
+                        let ty = function.locals[local.index()];
+                        self.program.failures.push(format!("local {} read before a {:?} is stored", local.0, ty));
+                        self.emit(op::CHECK, &[flag, (self.program.failures.len() - 1) as u32]);
+                    }
+                    if dst != local.0 && !self.forwarded_loads.contains(&inst) {
+                        self.emit(op::MOVE, &[dst, local.0]);
+                    }
+                }
+                // A store nothing loads is dropped.
+                InstKind::Store { local, .. } if !self.read_locals[local.index()] => {}
+                InstKind::Store { local, value } => {
+                    if self.reg(*value) != local.0 {
+                        self.emit(op::MOVE, &[local.0, self.reg(*value)]);
+                    }
+                    if let Some(flag) = self.flags[local.index()] {
+                        self.emit(op::SET, &[flag]);
+                    }
+                    if let Some(stored) = &mut stored {
+                        stored[local.index()] = true;
+                    }
+                }
+                InstKind::Alloca(_) => return Err(BackendError::InvalidIr("an `Alloca` left unexpanded".to_string())),
+                InstKind::Arith { op, lhs, rhs } => {
+                    let base = match self.ty(*lhs) {
+                        Some(FfiType::Int { .. }) => op::IADD,
+                        Some(FfiType::Float { .. }) => op::FADD,
+                        ty => {
+                            self.fail(format!("cannot execute {:?} of {:?}", op, ty));
+                            continue;
+                        }
+                    };
+                    self.emit(base + arith_index(*op), &[dst, self.reg(*lhs), self.reg(*rhs)]);
+                }
+                InstKind::Compare { op, lhs, rhs } => {
+                    let base = match self.ty(*lhs) {
+                        Some(FfiType::Int { .. } | FfiType::Bool) => op::IEQ,
+                        Some(FfiType::Float { .. }) => op::FEQ,
+                        ty => {
+                            self.fail(format!("cannot execute {:?} of {:?}", op, ty));
+                            continue;
+                        }
+                    };
+                    self.emit(base + compare_index(*op), &[dst, self.reg(*lhs), self.reg(*rhs)]);
+                }
+                InstKind::Not(value) => self.emit(op::NOT, &[dst, self.reg(*value)]),
+                InstKind::Overflows { op, lhs, rhs, ty: FfiType::Int { bits, signed } } => {
+                    self.emit(op::OVERFLOWS, &[dst, self.reg(*lhs), self.reg(*rhs)]);
+                    self.code.extend([arith_index(*op), *bits, u8::from(*signed)]);
+                }
+                // Nothing overflows a type that is not an integer.
+                InstKind::Overflows { .. } => {
+                    let never = self.constant(&Constant::Bool(false));
+                    self.emit(op::MOVE, &[dst, never]);
+                }
+                InstKind::Trap { condition, .. } if self.known(*condition) == Some(0) => {}
+                InstKind::Trap { condition, message, backtrace, abort } => {
+                    let trap = TrapMessage { message: message.clone(), backtrace: backtrace.clone(), abort: *abort };
+                    self.program.traps.push(trap);
+                    let trap = (self.program.traps.len() - 1) as u32;
+                    match function.inst(*condition).kind {
+                        InstKind::Overflows { op, lhs, rhs, ty: FfiType::Int { bits, signed } }
+                            if self.fused.contains(condition) =>
+                        {
+                            self.emit(op::TRAP_OVERFLOWS, &[self.reg(lhs), self.reg(rhs), trap]);
+                            self.code.extend([arith_index(op), bits, u8::from(signed)]);
+                        }
+                        _ => self.emit(op::TRAP, &[self.reg(*condition), trap]),
+                    }
+                }
+            }
+        }
+        let next = BlockId(block as u32 + 1);
+        let mut terminator = function.blocks[block].terminator.clone();
+        terminator.map_successors(|target| self.destination(target));
+        if let Terminator::Branch { condition, then, otherwise } = terminator
+            && let Some(holds) = self.known(condition)
+        {
+            terminator = Terminator::Jump(if holds != 0 { then } else { otherwise });
+        }
+        match &terminator {
+            Terminator::Return => self.emit(op::RETURN, &[]),
+            Terminator::Jump(target) if *target == next => {}
+            Terminator::Jump(target) => self.emit_jump(op::JUMP, &[], *target),
+            Terminator::Branch { condition, then, otherwise } if self.fused.contains(condition) => {
+                let InstKind::Compare { op, lhs, rhs } = function.inst(*condition).kind else {
+                    unreachable!("only comparisons are fused");
+                };
+                // Falling into `otherwise`, jump to `then` unless the
+                // comparison does not hold.
+                let (op, target, rest) =
+                    if *otherwise == next { (negated(op), *then, None) } else { (op, *otherwise, Some(*then)) };
+                self.emit_jump(op::JUMP_UNLESS, &[self.reg(lhs), self.reg(rhs)], target);
+                self.code.push(compare_index(op));
+                if let Some(then) = rest.filter(|&then| then != next) {
+                    self.emit_jump(op::JUMP, &[], then);
+                }
+            }
+            Terminator::Branch { condition, then, otherwise } => {
+                let condition = self.reg(*condition);
+                if *then == next {
+                    self.emit_jump(op::JUMP_IF_NOT, &[condition], *otherwise);
+                } else if *otherwise == next {
+                    self.emit_jump(op::JUMP_IF, &[condition], *then);
+                } else {
+                    self.emit(op::BRANCH, &[condition]);
+                    for target in [*then, *otherwise] {
+                        self.patches.push((self.code.len(), target));
+                        self.code.extend_from_slice(&0u32.to_le_bytes());
+                    }
+                }
+            }
+            Terminator::Exit(code) => self.emit(op::EXIT, &[self.reg(*code)]),
+            Terminator::Switch { value, cases, default } => {
+                self.switches.push((cases.clone(), *default));
+                self.emit(op::SWITCH, &[self.reg(*value), (self.switches.len() - 1) as u32]);
+            }
+            Terminator::Unreachable => self.fail(format!("reached the end of unreachable block bb{}", block)),
+        }
+        Ok(())
+    }
+}
+
+/// Whether the instruction computes its value into a register of its
+/// choosing, which may as well be a local's.
+fn computes(kind: &InstKind) -> bool {
+    matches!(
+        kind,
+        InstKind::Arith { .. }
+            | InstKind::Compare { .. }
+            | InstKind::Not(_)
+            | InstKind::Overflows { .. }
+            | InstKind::Cast { .. }
+            | InstKind::Call { .. }
+    )
+}
+
+/// How far past `IADD` or `FADD` the opcode of `op` is.
+fn arith_index(op: ArithOp) -> u8 {
+    ARITH_OPS.iter().position(|&known| known == op).expect("every operator is numbered") as u8
+}
+
+/// How far past `IEQ` or `FEQ` the opcode of `op` is.
+fn compare_index(op: CompareOp) -> u8 {
+    COMPARE_OPS.iter().position(|&known| known == op).expect("every comparison is numbered") as u8
+}
+
+/// The comparison holding where `op` does not, of integers.
+fn negated(op: CompareOp) -> CompareOp {
+    match op {
+        CompareOp::Eq => CompareOp::Ne,
+        CompareOp::Ne => CompareOp::Eq,
+        CompareOp::Lt => CompareOp::Ge,
+        CompareOp::Le => CompareOp::Gt,
+        CompareOp::Gt => CompareOp::Le,
+        CompareOp::Ge => CompareOp::Lt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str) -> Result<(String, i32), BackendError> {
+        RegisterProgram::compile(&text.parse().unwrap())?.run()
+    }
+
+    #[test]
+    fn loads_and_stores_run_as_register_reads() {
+        // l1 = 0 + 1 + 2 + 3 + 4
+        let text = "fn main {\n    local l0: i64\n    local l1: i64\nbb0:\n    %0: i64 = const 0\n    %1 = store l0, %0\n    %2 = store l1, %0\n    jump bb1\nbb1:\n    %3: i64 = load l0\n    %4: i64 = const 5\n    %5: bool = lt %3, %4\n    branch %5, bb2, bb3\nbb2:\n    %6: i64 = load l1\n    %7: i64 = load l0\n    %8: i64 = add %6, %7\n    %9 = store l1, %8\n    %10: i64 = const 1\n    %11: i64 = add %7, %10\n    %12 = store l0, %11\n    jump bb1\nbb3:\n    %13: i64 = load l1\n    %14 = print %13\n    return\n}\n";
+        let program = RegisterProgram::compile(&text.parse().unwrap()).unwrap();
+        assert_eq!(program.run().unwrap(), ("10".to_string(), 0));
+        // Two moves, then a compare-and-jump, two adds into the locals and
+        // a jump back, a print and a return: no instruction for the loads
+        // and stores in the loop.
+        assert_eq!(program.code_sizes(), [9 + 9 + 14 + 13 + 13 + 5 + 5 + 1]);
+    }
+
+    #[test]
+    fn calls_run_on_frames_of_their_own() {
+        let text = "fn main {\nbb0:\n    %0: i64 = const 10\n    %1: i64 = call @fact(%0)\n    %2 = print %1\n    %3: str = const \" \"\n    %4 = print %3\n    %5 = print %0\n    return\n}\n\nfn fact(l0) -> l1 {\n    local l0: i64\n    local l1: i64\nbb0:\n    %0: i64 = load l0\n    %1: i64 = const 2\n    %2: bool = lt %0, %1\n    branch %2, bb1, bb2\nbb1:\n    %3: i64 = const 1\n    %4 = store l1, %3\n    return\nbb2:\n    %5: i64 = load l0\n    %6: i64 = const 1\n    %7: i64 = sub %5, %6\n    %8: i64 = call @fact(%7)\n    %9: i64 = mul %5, %8\n    %10 = store l1, %9\n    return\n}\n";
+        assert_eq!(run(text).unwrap(), ("3628800 10".to_string(), 0));
+    }
+
+    #[test]
+    fn locals_read_before_a_store_fail_where_they_are() {
+        let module = |n: i64| {
+            format!("fn main {{\nbb0:\n    %0: i64 = const {}\n    %1: i64 = call @pick(%0)\n    %2 = print %1\n    return\n}}\n\nfn pick(l0) -> l1 {{\n    local l0: i64\n    local l1: i64\n    local l2: i64\nbb0:\n    %0: i64 = load l0\n    %1: i64 = const 0\n    %2: bool = gt %0, %1\n    branch %2, bb1, bb2\nbb1:\n    %3 = store l2, %0\n    jump bb2\nbb2:\n    %4: i64 = load l2\n    %5 = store l1, %4\n    return\n}}\n", n)
+        };
+        assert_eq!(run(&module(4)).unwrap(), ("4".to_string(), 0));
+        match run(&module(0)) {
+            Err(BackendError::InvalidIr(message)) => assert!(message.contains("local 2 read before"), "{}", message),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn switches_traps_and_exits() {
+        // The first case of 7 is taken.
+        let text = "fn main {\nbb0:\n    %0: i64 = const 7\n    switch %0, bb3 [7: bb1, 7: bb2]\nbb1:\n    %1: str = const \"a\"\n    %2 = print %1\n    %3: i64 = const 3\n    exit %3\nbb2:\n    return\nbb3:\n    return\n}\n";
+        assert_eq!(run(text).unwrap(), ("a".to_string(), 3));
+
+        // The overflow check and its trap run as one instruction.
+        let text = "fn main {\nbb0:\n    %0: i64 = const 100\n    %1: i64 = const 27\n    %2: i64 = call @add(%0, %1)\n    %3 = print %2\n    %4: i64 = const 28\n    %5: i64 = call @add(%0, %4)\n    %6 = print %5\n    return\n}\n\nfn add(l0, l1) -> l2 {\n    local l0: i64\n    local l1: i64\n    local l2: i64\nbb0:\n    %0: i64 = load l0\n    %1: i64 = load l1\n    %2: bool = overflows add %0, %1 as i8\n    %3 = trap %2, \"attempt to add with overflow\", \"\"\n    %4: i64 = add %0, %1\n    %5 = store l2, %4\n    return\n}\n";
+        let program = RegisterProgram::compile(&text.parse().unwrap()).unwrap();
+        assert_eq!(program.run().unwrap(), ("127".to_string(), PANIC_EXIT_CODE));
+        assert_eq!(program.code_sizes()[1], 16 + 13 + 1);
+    }
+}
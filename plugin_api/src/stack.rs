@@ -0,0 +1,1014 @@
+//! A TIR function as stack instructions, and stack instructions as TIR.
+//!
+//! Emitting keeps a value on the stack of its kind from where it is defined
+//! to its one use, as long as that use is in the same block and finds it
+//! on top. Any other value is stored in a local slot where it is defined
+//! and loaded where it is used; the function's own locals take the first
+//! slots. Code shaped like a tree of expressions thus emits the pushes and
+//! pops it would be written with by hand. The stacks are empty between
+//! blocks, and a value nothing reads is not computed, unless computing it
+//! has an effect. A function with `Alloca`s is emitted as
+//! `Function::expand_allocas` has it, with calls making and freeing the
+//! collections, which is how it lifts back.
+//!
+//! Blocks are emitted in layout order. One that control reaches other than
+//! by falling into it from the block before starts with a `Label` numbered
+//! as the block; a `Branch` jumps to its `otherwise` block if the condition
+//! is false, a `Switch` tests its cases one after another, and a `Return`
+//! before the last block jumps to a label after all of them.
+//!
+//! A module is its entry point's instructions, then each other function's
+//! after a `Function` header, ending in the `Return` of its result local.
+//! Their labels are numbered past those of the functions before, so every
+//! label of the module is a different one.
+//!
+//! Lifting runs the instructions on stacks of values, as `ValueStacks`
+//! runs them on stacks of constants, and makes the instruction computing
+//! each value. A `Label`, and the instruction after a jump, `Exit` or
+//! `Return`, starts a block. Tests of one value against case after case, as
+//! a `Switch` is emitted, are lifted back to a `Switch`.
+
+use crate::{BackendError, Instruction};
+use shared::tir::cfg::Cfg;
+use shared::tir::{
+    Block, BlockId, CompareOp, Constant, FfiType, Function, InstId, InstKind, LocalId, Module, Terminator,
+};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Stack instructions emitted for a TIR function, or module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Emitted {
+    pub instructions: Vec<Instruction>,
+    /// Where the instructions after each `Marker` start, by its number.
+    pub marks: HashMap<u32, usize>,
+    /// The instructions of each function of a module, by its index; empty
+    /// for a function emitted on its own.
+    pub functions: Vec<Range<usize>>,
+}
+
+/// Emit `module` as stack instructions.
+pub fn emit_module(module: &Module) -> Result<Emitted, BackendError> {
+    let mut out = Emitted::default();
+    let mut first_label = 0;
+    for (i, function) in module.functions.iter().enumerate() {
+        let emitted = emit_function(function)?;
+        let result = function.result_type().map(FfiType::carried);
+        if i > 0 {
+            out.instructions.push(Instruction::Function {
+                name: function.name.clone(),
+                params: function.param_types().to_vec(),
+                result: function.result_type(),
+                inline: function.inline,
+            });
+        }
+        let start = out.instructions.len();
+        out.marks.extend(emitted.marks.iter().map(|(&n, &at)| (n, start + at)));
+        out.instructions.extend(emitted.instructions.into_iter().map(|instruction| match instruction {
+            Instruction::Label(n) => Instruction::Label(first_label + n),
+            Instruction::Jump(n) => Instruction::Jump(first_label + n),
+            Instruction::JumpIfFalse(n) => Instruction::JumpIfFalse(first_label + n),
+            instruction => instruction,
+        }));
+        if i > 0 {
+            if let (Some(local), Some(ty)) = (function.result, result) {
+                out.instructions.push(Instruction::LoadLocal { slot: local.0, ty });
+            }
+            out.instructions.push(Instruction::Return { result });
+        }
+        out.functions.push(start..out.instructions.len());
+        first_label += function.blocks.len() as u32 + 1;
+    }
+    Ok(out)
+}
+
+/// Which stack a value of `ty` is kept on while emitting. Booleans count as
+/// on the integers' stack, where most backends keep them, so that keeping
+/// that stack in order keeps the interpreter's two in order as well.
+fn stack_of(ty: FfiType) -> usize {
+    match ty {
+        FfiType::Int { .. } | FfiType::Bool => 0,
+        FfiType::Float { .. } => 1,
+        FfiType::Str => 2,
+    }
+}
+
+/// Emit `function` as stack instructions.
+pub fn emit_function(function: &Function) -> Result<Emitted, BackendError> {
+    let expanded = function.expand_allocas();
+    let function = expanded.as_ref().unwrap_or(function);
+    let live = function.live_insts();
+    let mut defined_in = HashMap::new();
+    for (id, block) in function.iter_blocks() {
+        for &inst in &block.insts {
+            defined_in.insert(inst, id);
+        }
+    }
+    // The block of each use of each value.
+    let mut uses: HashMap<InstId, Vec<BlockId>> = HashMap::new();
+    for (id, block) in function.iter_blocks() {
+        let operands = block.insts.iter().filter(|inst| live.contains(inst)).flat_map(|&inst| {
+            function.inst(inst).kind.operands()
+        });
+        for operand in operands.chain(block.terminator.operands()) {
+            uses.entry(operand).or_default().push(id);
+        }
+    }
+    let mut spilled: HashSet<InstId> = uses
+        .iter()
+        .filter(|(value, blocks)| blocks.len() != 1 || defined_in.get(value) != Some(&blocks[0]))
+        .map(|(&value, _)| value)
+        .collect();
+    loop {
+        let mut emitter = Emitter::new(function, &live, &uses, &spilled);
+        match emitter.emit() {
+            Ok(()) => return Ok(emitter.out),
+            Err(Retry::Spill(values)) => spilled.extend(values),
+            Err(Retry::Fail(err)) => return Err(err),
+        }
+    }
+}
+
+/// Why an attempt at emitting stopped.
+enum Retry {
+    /// These values cannot stay on the stack; store them in slots.
+    Spill(Vec<InstId>),
+    /// The function cannot be emitted.
+    Fail(BackendError),
+}
+
+impl From<BackendError> for Retry {
+    fn from(err: BackendError) -> Self {
+        Retry::Fail(err)
+    }
+}
+
+struct Emitter<'f> {
+    function: &'f Function,
+    live: &'f HashSet<InstId>,
+    uses: &'f HashMap<InstId, Vec<BlockId>>,
+    spilled: &'f HashSet<InstId>,
+    /// Slot of each spilled value, after those of the locals.
+    slots: HashMap<InstId, u32>,
+    /// Values on each stack, by `stack_of`.
+    stacks: [Vec<InstId>; 3],
+    out: Emitted,
+}
+
+impl<'f> Emitter<'f> {
+    fn new(
+        function: &'f Function,
+        live: &'f HashSet<InstId>,
+        uses: &'f HashMap<InstId, Vec<BlockId>>,
+        spilled: &'f HashSet<InstId>,
+    ) -> Self {
+        let mut order: Vec<InstId> = spilled.iter().copied().collect();
+        order.sort();
+        let first = function.locals.len() as u32;
+        let slots = order.into_iter().zip(first..).collect();
+        Emitter { function, live, uses, spilled, slots, stacks: Default::default(), out: Emitted::default() }
+    }
+
+    fn ty(&self, value: InstId) -> Result<FfiType, BackendError> {
+        self.function.ty(value).ok_or_else(|| {
+            BackendError::InvalidIr(format!("{:?} of `{}` has no value to read", value, self.function.name))
+        })
+    }
+
+    fn push(&mut self, instruction: Instruction) {
+        self.out.instructions.push(instruction);
+    }
+
+    fn emit(&mut self) -> Result<(), Retry> {
+        let end = self.function.blocks.len() as u32;
+        let labelled = self.jump_targets();
+        for (id, block) in self.function.iter_blocks() {
+            if labelled.contains(&id.0) {
+                self.push(Instruction::Label(id.0));
+            }
+            let reloaded = self.reloaded(block);
+            for &inst in &block.insts {
+                if self.live.contains(&inst) && reloaded.is_none_or(|(load, _)| load != inst) {
+                    self.emit_inst(inst)?;
+                }
+            }
+            let next = id.0 + 1;
+            if let Terminator::Switch { value, ref cases, default } = block.terminator {
+                self.emit_switch(value, reloaded.map(|(_, local)| local), cases, default, next)?;
+            } else {
+                self.take(&block.terminator.operands())?;
+            }
+            match block.terminator {
+                Terminator::Exit(_) => self.push(Instruction::Exit),
+                Terminator::Unreachable | Terminator::Switch { .. } => {}
+                Terminator::Return if next == end => {}
+                Terminator::Return => self.push(Instruction::Jump(end)),
+                Terminator::Jump(target) if target.0 == next => {}
+                Terminator::Jump(target) => self.push(Instruction::Jump(target.0)),
+                Terminator::Branch { then, otherwise, .. } => {
+                    self.push(Instruction::JumpIfFalse(otherwise.0));
+                    if then.0 != next {
+                        self.push(Instruction::Jump(then.0));
+                    }
+                }
+            }
+            let left: Vec<InstId> = self.stacks.iter_mut().flat_map(std::mem::take).collect();
+            if !left.is_empty() {
+                return Err(Retry::Spill(left));
+            }
+        }
+        if labelled.contains(&end) {
+            self.push(Instruction::Label(end));
+        }
+        Ok(())
+    }
+
+    /// The numbers of the blocks jumps go to, and of the end of the
+    /// function if a `Return` jumps there.
+    fn jump_targets(&self) -> HashSet<u32> {
+        let end = self.function.blocks.len() as u32;
+        let mut targets = HashSet::new();
+        for (id, block) in self.function.iter_blocks() {
+            let next = id.0 + 1;
+            match block.terminator {
+                Terminator::Return if next != end => {
+                    targets.insert(end);
+                }
+                Terminator::Jump(target) if target.0 != next => {
+                    targets.insert(target.0);
+                }
+                Terminator::Branch { then, otherwise, .. } => {
+                    targets.insert(otherwise.0);
+                    if then.0 != next {
+                        targets.insert(then.0);
+                    }
+                }
+                Terminator::Switch { ref cases, default, .. } => {
+                    targets.extend(cases.iter().map(|(_, target)| target.0));
+                    if default.0 != next {
+                        targets.insert(default.0);
+                    }
+                }
+                _ => {}
+            }
+        }
+        targets
+    }
+
+    /// The `Load` a `Switch` ending `block` switches on, and its local, if
+    /// nothing else reads it and nothing stores to the local after it: each
+    /// test of the switch loads the local again instead.
+    fn reloaded(&self, block: &Block) -> Option<(InstId, LocalId)> {
+        let Terminator::Switch { value, .. } = block.terminator else {
+            return None;
+        };
+        let InstKind::Load(local) = self.function.inst(value).kind else {
+            return None;
+        };
+        let at = block.insts.iter().position(|&inst| inst == value)?;
+        let stored = block.insts[at..].iter().any(|&inst| match self.function.inst(inst).kind {
+            InstKind::Store { local: stored, .. } => stored == local,
+            _ => false,
+        });
+        (self.uses[&value].len() == 1 && !stored).then_some((value, local))
+    }
+
+    /// A `Switch` as one test per case, in order, then a jump to `default`:
+    /// whether `value` is other than the case, jumping to the case's block
+    /// if not. Each test ends a block, so the tests after the first read
+    /// `value` from its slot, or from `local` if it is `reloaded` from one.
+    fn emit_switch(
+        &mut self,
+        value: InstId,
+        local: Option<LocalId>,
+        cases: &[(i64, BlockId)],
+        default: BlockId,
+        next: u32,
+    ) -> Result<(), Retry> {
+        if cases.len() > 1 && local.is_none() && !self.spilled.contains(&value) {
+            return Err(Retry::Spill(vec![value]));
+        }
+        let ty = self.ty(value)?.carried();
+        for &(n, target) in cases {
+            match local {
+                Some(local) => self.push(Instruction::LoadLocal { slot: local.0, ty: self.local_ty(local)? }),
+                None => self.take(&[value])?,
+            }
+            self.push(Instruction::PushInt(n));
+            self.push(Instruction::Compare { op: CompareOp::Ne, ty });
+            self.push(Instruction::JumpIfFalse(target.0));
+        }
+        if default.0 != next {
+            self.push(Instruction::Jump(default.0));
+        }
+        Ok(())
+    }
+
+    fn emit_inst(&mut self, inst: InstId) -> Result<(), Retry> {
+        let kind = &self.function.inst(inst).kind;
+        self.take(&kind.operands())?;
+        let used = self.uses.contains_key(&inst);
+        let instruction = match kind {
+            InstKind::Const(Constant::Int(n)) => Instruction::PushInt(*n),
+            InstKind::Const(Constant::Float(f)) => Instruction::PushFloat(*f),
+            InstKind::Const(Constant::Bool(b)) => Instruction::PushBool(*b),
+            InstKind::Const(Constant::Str(s)) => Instruction::PushStr(s.clone()),
+            InstKind::Print(value) => match self.ty(*value)? {
+                FfiType::Int { .. } => Instruction::PrintInt,
+                FfiType::Float { .. } => Instruction::PrintFloat,
+                FfiType::Bool => Instruction::PrintBool,
+                FfiType::Str => Instruction::PrintStr,
+            },
+            InstKind::Cast { value, to } => match (self.ty(*value)?, *to) {
+                (FfiType::Int { .. }, FfiType::Float { .. }) => Instruction::IntToFloat,
+                (FfiType::Float { .. }, FfiType::Int { bits, signed }) => Instruction::FloatToInt { bits, signed },
+                (FfiType::Int { .. }, FfiType::Int { bits, signed }) => Instruction::WrapInt { bits, signed },
+                (FfiType::Float { .. }, FfiType::Float { .. }) => Instruction::Nop,
+                (from, to) => {
+                    let message = format!("cannot cast {:?} to {:?} in `{}`", from, to, self.function.name);
+                    return Err(Retry::Fail(BackendError::InvalidIr(message)));
+                }
+            },
+            InstKind::CallExtern { name, types, .. } => Instruction::CallExtern {
+                name: name.clone(),
+                args: types.clone(),
+                result: self.function.ty(inst).filter(|_| used),
+            },
+            InstKind::Call { function, args } => Instruction::Call {
+                name: function.clone(),
+                args: args.iter().map(|&arg| self.ty(arg).map(FfiType::carried)).collect::<Result<_, _>>()?,
+                result: self.function.ty(inst).filter(|_| used).map(FfiType::carried),
+            },
+            InstKind::Arith { op, lhs, .. } => Instruction::Arith { op: *op, ty: self.ty(*lhs)?.carried() },
+            InstKind::Compare { op, lhs, .. } => Instruction::Compare { op: *op, ty: self.ty(*lhs)?.carried() },
+            InstKind::Not(_) => Instruction::Not,
+            InstKind::Overflows { op, ty, .. } => Instruction::Overflows { op: *op, ty: *ty },
+            InstKind::Trap { message, backtrace, abort, .. } => {
+                Instruction::Trap { message: message.clone(), backtrace: backtrace.clone(), abort: *abort }
+            }
+            InstKind::Load(local) => Instruction::LoadLocal { slot: local.0, ty: self.local_ty(*local)? },
+            InstKind::Store { local, .. } => Instruction::StoreLocal { slot: local.0, ty: self.local_ty(*local)? },
+            InstKind::Marker(n) => {
+                self.out.marks.insert(*n, self.out.instructions.len());
+                return Ok(());
+            }
+            InstKind::Alloca(_) => {
+                let message = format!("an `Alloca` of `{}` is left after expanding them", self.function.name);
+                return Err(Retry::Fail(BackendError::InvalidIr(message)));
+            }
+        };
+        if instruction != Instruction::Nop {
+            self.push(instruction);
+        }
+        if let Some(ty) = self.function.ty(inst).filter(|_| used) {
+            match self.slots.get(&inst) {
+                Some(&slot) => self.push(Instruction::StoreLocal { slot, ty: ty.carried() }),
+                None => self.stacks[stack_of(ty)].push(inst),
+            }
+        }
+        Ok(())
+    }
+
+    fn local_ty(&self, local: LocalId) -> Result<FfiType, BackendError> {
+        match self.function.locals.get(local.index()) {
+            Some(ty) => Ok(ty.carried()),
+            None => Err(BackendError::InvalidIr(format!("`{}` has no local {}", self.function.name, local.0))),
+        }
+    }
+
+    /// Pop `operands` off the stacks, loading the spilled ones from their
+    /// slots, or say which cannot be where they need to be.
+    fn take(&mut self, operands: &[InstId]) -> Result<(), Retry> {
+        for stack in 0..self.stacks.len() {
+            let mut on_this = Vec::new();
+            for &operand in operands {
+                if stack_of(self.ty(operand)?) == stack {
+                    on_this.push(operand);
+                }
+            }
+            // Loads push above what is on the stack, so the values on the
+            // stack must come first and be on top, in order.
+            let kept = on_this.iter().take_while(|value| !self.spilled.contains(value)).count();
+            let late: Vec<InstId> = on_this[kept..].iter().copied().filter(|v| !self.spilled.contains(v)).collect();
+            if !late.is_empty() {
+                return Err(Retry::Spill(late));
+            }
+            let values = &mut self.stacks[stack];
+            if values.len() < kept || values[values.len() - kept..] != on_this[..kept] {
+                return Err(Retry::Spill(on_this[..kept].to_vec()));
+            }
+            values.truncate(values.len() - kept);
+        }
+        for operand in operands {
+            if let Some(&slot) = self.slots.get(operand) {
+                let ty = self.ty(*operand)?.carried();
+                self.push(Instruction::LoadLocal { slot, ty });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lift `instructions` to a TIR function called `name`, with `Marker(n)`
+/// where the instruction `rows[n]` starts, as for the rows of a line table.
+pub fn lift_function(name: &str, instructions: &[Instruction], rows: &[usize]) -> Result<Function, BackendError> {
+    lift(Function::new(name), instructions, rows)
+}
+
+/// The code of each function of a module's `instructions`: the entry
+/// point's, which has no header, then each `Function` header with the
+/// instructions after it, up to the next header.
+pub fn split_functions(instructions: &[Instruction]) -> Vec<(Option<&Instruction>, Range<usize>)> {
+    let mut headers: Vec<usize> = (0..instructions.len())
+        .filter(|&i| matches!(instructions[i], Instruction::Function { .. }))
+        .collect();
+    headers.push(instructions.len());
+    let mut start: usize = 0;
+    let mut functions = Vec::with_capacity(headers.len());
+    for end in headers {
+        functions.push((start.checked_sub(1).map(|header| &instructions[header]), start..end));
+        start = end + 1;
+    }
+    functions
+}
+
+/// Lift the instructions of a module to its functions, the entry point
+/// first, called `entry`. Row `n` of `rows` is where the instruction
+/// `rows[n]` starts, in the first function whose instructions reach it.
+pub fn lift_module(entry: &str, instructions: &[Instruction], rows: &[usize]) -> Result<Vec<Function>, BackendError> {
+    let segments = split_functions(instructions);
+    let mut functions = Vec::with_capacity(segments.len());
+    let mut placed = vec![false; rows.len()];
+    for (header, Range { start, end }) in segments {
+        let function = match header {
+            Some(Instruction::Function { name, params, inline, .. }) => {
+                let mut function = Function::new(name.clone());
+                for &ty in params {
+                    function.add_local(ty.carried());
+                }
+                (function.params, function.inline) = (params.len(), *inline);
+                function
+            }
+            _ => Function::new(entry),
+        };
+        let rows: Vec<usize> = rows
+            .iter()
+            .zip(&mut placed)
+            .map(|(&row, placed)| match row.checked_sub(start) {
+                Some(at) if !*placed && row <= end => {
+                    *placed = true;
+                    at
+                }
+                _ => usize::MAX,
+            })
+            .collect();
+        functions.push(lift(function, &instructions[start..end], &rows)?);
+    }
+    Ok(functions)
+}
+
+/// Lift `instructions` into `function`, which has only its parameters.
+fn lift(function: Function, instructions: &[Instruction], rows: &[usize]) -> Result<Function, BackendError> {
+    let (labels, blocks) = blocks_of(instructions);
+    let typed = (0..function.params as u32).collect();
+    let stacks = Default::default();
+    let mut lifter = Lifter { function, block: BlockId::ENTRY, started: false, labels, typed, stacks };
+    for _ in 1..blocks {
+        lifter.function.add_block();
+    }
+    for (i, instruction) in instructions.iter().enumerate() {
+        lifter.mark(rows, i);
+        lifter.lift(instruction)?;
+    }
+    lifter.mark(rows, instructions.len());
+    let mut function = lifter.function;
+    // Nothing follows the `Return` a function ends with.
+    let last = BlockId(function.blocks.len() as u32 - 1);
+    if matches!(instructions.last(), Some(Instruction::Return { .. })) && function.block(last).insts.is_empty() {
+        function.terminate(last, Terminator::Unreachable);
+    }
+    form_switches(&mut function);
+    function.compact();
+    Ok(function)
+}
+
+/// The test of a `Switch` case that `block` ends in, as `emit_switch`
+/// emits it: `(local, load, case, target, next)` for a `Branch` on
+/// whether the value it loads from `local` is other than `case`, going to
+/// `target` if not, else to `next`. Any block but the first of a switch
+/// holds nothing else.
+fn case_test(function: &Function, block: BlockId, first: bool) -> Option<(LocalId, InstId, i64, BlockId, BlockId)> {
+    let block = function.block(block);
+    let Terminator::Branch { condition, then, otherwise } = block.terminator else {
+        return None;
+    };
+    let [.., load, case, test] = block.insts[..] else {
+        return None;
+    };
+    if (!first && block.insts.len() != 3) || test != condition {
+        return None;
+    }
+    match (&function.inst(load).kind, &function.inst(case).kind, &function.inst(test).kind) {
+        (&InstKind::Load(local), &InstKind::Const(Constant::Int(n)), &InstKind::Compare { op: CompareOp::Ne, lhs, rhs })
+            if (lhs, rhs) == (load, case) =>
+        {
+            Some((local, load, n, otherwise, then))
+        }
+        _ => None,
+    }
+}
+
+/// Make each run of two or more case tests of one local, each block after
+/// the first entered only from the one before, a `Switch` again, ending
+/// the first block; the others are left empty and unreachable. Returns
+/// whether there was one.
+fn form_switches(function: &mut Function) -> bool {
+    let cfg = Cfg::new(function);
+    let mut formed = false;
+    for first in 0..function.blocks.len() as u32 {
+        let first = BlockId(first);
+        let Some((local, value, n, target, mut next)) = case_test(function, first, true) else {
+            continue;
+        };
+        let (mut cases, mut chain, mut last) = (vec![(n, target)], Vec::new(), first);
+        while cfg.predecessors(next) == [last]
+            && let Some((same, _, n, target, after)) = case_test(function, next, false)
+            && same == local
+        {
+            // A case already tested is never reached again.
+            if cases.iter().all(|&(m, _)| m != n) {
+                cases.push((n, target));
+            }
+            chain.push(next);
+            (last, next) = (next, after);
+        }
+        if chain.is_empty() {
+            continue;
+        }
+        // The jump to the default, after the tests.
+        let block = function.block(next);
+        let default = match block.terminator {
+            Terminator::Jump(target) if block.insts.is_empty() && cfg.predecessors(next) == [last] => {
+                chain.push(next);
+                target
+            }
+            _ => next,
+        };
+        for block in chain {
+            function.blocks[block.index()] = Default::default();
+            function.terminate(block, Terminator::Unreachable);
+        }
+        let insts = &mut function.blocks[first.index()].insts;
+        insts.truncate(insts.len() - 2);
+        function.terminate(first, Terminator::Switch { value, cases, default });
+        formed = true;
+    }
+    formed
+}
+
+/// The block each `Label` of `instructions` starts, and how many blocks
+/// they make. A label right after another, or after a jump or `Exit`,
+/// starts the same block as it.
+fn blocks_of(instructions: &[Instruction]) -> (HashMap<u32, BlockId>, usize) {
+    let mut labels = HashMap::new();
+    let (mut block, mut started) = (0, false);
+    for instruction in instructions {
+        match instruction {
+            Instruction::Label(n) => {
+                if started {
+                    block += 1;
+                    started = false;
+                }
+                labels.insert(*n, BlockId(block));
+            }
+            Instruction::Jump(_) | Instruction::JumpIfFalse(_) | Instruction::Exit | Instruction::Return { .. } => {
+                block += 1;
+                started = false;
+            }
+            Instruction::Nop => {}
+            _ => started = true,
+        }
+    }
+    (labels, block as usize + 1)
+}
+
+struct Lifter {
+    function: Function,
+    /// The block instructions are lifted into
+    block: BlockId,
+    /// Whether an instruction was lifted into `block`, so a `Label` starts
+    /// the next
+    started: bool,
+    /// The block each label starts
+    labels: HashMap<u32, BlockId>,
+    /// Slots whose type a `StoreLocal` or `LoadLocal` has given
+    typed: HashSet<u32>,
+    /// Values on the int, float, bool and string stacks
+    stacks: [Vec<InstId>; 4],
+}
+
+impl Lifter {
+    fn mark(&mut self, rows: &[usize], instruction: usize) {
+        for (n, _) in rows.iter().enumerate().filter(|&(_, &row)| row == instruction) {
+            self.function.push(self.block, InstKind::Marker(n as u32), None);
+        }
+    }
+
+    fn stack(ty: FfiType) -> usize {
+        match ty {
+            FfiType::Int { .. } => 0,
+            FfiType::Float { .. } => 1,
+            FfiType::Bool => 2,
+            FfiType::Str => 3,
+        }
+    }
+
+    fn pop(&mut self, ty: FfiType, instruction: &Instruction) -> Result<InstId, BackendError> {
+        self.stacks[Self::stack(ty)]
+            .pop()
+            .ok_or_else(|| BackendError::InvalidIr(format!("stack underflow at {:?}", instruction)))
+    }
+
+    /// Add the instruction `kind` to the block, pushing its value if it has one.
+    fn add(&mut self, kind: InstKind, ty: Option<FfiType>) -> InstId {
+        let inst = self.function.push(self.block, kind, ty);
+        if let Some(ty) = ty {
+            self.stacks[Self::stack(ty)].push(inst);
+        }
+        inst
+    }
+
+    /// The local variable `slot` names, holding values of type `ty`.
+    fn local(&mut self, slot: u32, ty: FfiType) -> Result<LocalId, BackendError> {
+        let locals = &mut self.function.locals;
+        if locals.len() <= slot as usize {
+            locals.resize(slot as usize + 1, ty);
+        }
+        // Slots need not be used in order.
+        if self.typed.insert(slot) {
+            locals[slot as usize] = ty;
+        } else if locals[slot as usize] != ty {
+            return Err(BackendError::InvalidIr(format!("local {} holds both {:?} and {:?}", slot, locals[slot as usize], ty)));
+        }
+        Ok(LocalId(slot))
+    }
+
+    /// The block label `n` starts.
+    fn label(&self, n: u32) -> Result<BlockId, BackendError> {
+        self.labels.get(&n).copied().ok_or_else(|| BackendError::InvalidIr(format!("jump to missing label {}", n)))
+    }
+
+    /// End the block with `terminator`, continuing in the next, which
+    /// starts with nothing on the stacks.
+    fn end_block(&mut self, terminator: Terminator, instruction: &Instruction) -> Result<(), BackendError> {
+        if self.stacks.iter().any(|stack| !stack.is_empty()) {
+            return Err(BackendError::InvalidIr(format!("values are left on the stacks at {:?}", instruction)));
+        }
+        self.function.terminate(self.block, terminator);
+        self.block = BlockId(self.block.0 + 1);
+        self.started = false;
+        Ok(())
+    }
+
+    fn lift(&mut self, instruction: &Instruction) -> Result<(), BackendError> {
+        if !matches!(instruction, Instruction::Nop | Instruction::Label(_)) {
+            self.started = true;
+        }
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::Label(n) => {
+                let target = self.label(*n)?;
+                if self.started {
+                    self.end_block(Terminator::Jump(target), instruction)?;
+                }
+            }
+            Instruction::Jump(n) => {
+                let target = self.label(*n)?;
+                self.end_block(Terminator::Jump(target), instruction)?;
+            }
+            Instruction::JumpIfFalse(n) => {
+                let otherwise = self.label(*n)?;
+                let condition = self.pop(FfiType::Bool, instruction)?;
+                let then = BlockId(self.block.0 + 1);
+                self.end_block(Terminator::Branch { condition, then, otherwise }, instruction)?;
+            }
+            Instruction::Arith { op, ty } => {
+                let rhs = self.pop(*ty, instruction)?;
+                let lhs = self.pop(*ty, instruction)?;
+                self.add(InstKind::Arith { op: *op, lhs, rhs }, Some(ty.carried()));
+            }
+            Instruction::Compare { op, ty } => {
+                let rhs = self.pop(*ty, instruction)?;
+                let lhs = self.pop(*ty, instruction)?;
+                self.add(InstKind::Compare { op: *op, lhs, rhs }, Some(FfiType::Bool));
+            }
+            Instruction::Not => {
+                let value = self.pop(FfiType::Bool, instruction)?;
+                self.add(InstKind::Not(value), Some(FfiType::Bool));
+            }
+            Instruction::Overflows { op, ty } => {
+                let rhs = self.pop(FfiType::INT, instruction)?;
+                let lhs = self.pop(FfiType::INT, instruction)?;
+                self.add(InstKind::Overflows { op: *op, lhs, rhs, ty: *ty }, Some(FfiType::Bool));
+            }
+            Instruction::Trap { message, backtrace, abort } => {
+                let condition = self.pop(FfiType::Bool, instruction)?;
+                let (message, backtrace) = (message.clone(), backtrace.clone());
+                self.add(InstKind::Trap { condition, message, backtrace, abort: *abort }, None);
+            }
+            Instruction::PushInt(n) => {
+                self.add(InstKind::Const(Constant::Int(*n)), Some(FfiType::INT));
+            }
+            Instruction::PushFloat(f) => {
+                self.add(InstKind::Const(Constant::Float(*f)), Some(FfiType::FLOAT));
+            }
+            Instruction::PushBool(b) => {
+                self.add(InstKind::Const(Constant::Bool(*b)), Some(FfiType::Bool));
+            }
+            Instruction::PushStr(s) => {
+                self.add(InstKind::Const(Constant::Str(s.clone())), Some(FfiType::Str));
+            }
+            Instruction::CallPrint => {
+                let value = match self.stacks[3].pop() {
+                    Some(s) => Some(s),
+                    None => self.stacks[0].pop(),
+                };
+                if let Some(value) = value {
+                    self.add(InstKind::Print(value), None);
+                }
+            }
+            Instruction::PrintInt => {
+                let value = self.pop(FfiType::INT, instruction)?;
+                self.add(InstKind::Print(value), None);
+            }
+            Instruction::PrintFloat => {
+                let value = self.pop(FfiType::FLOAT, instruction)?;
+                self.add(InstKind::Print(value), None);
+            }
+            Instruction::PrintBool => {
+                let value = self.pop(FfiType::Bool, instruction)?;
+                self.add(InstKind::Print(value), None);
+            }
+            Instruction::PrintStr => {
+                let value = self.pop(FfiType::Str, instruction)?;
+                self.add(InstKind::Print(value), None);
+            }
+            Instruction::IntToFloat => {
+                let value = self.pop(FfiType::INT, instruction)?;
+                self.add(InstKind::Cast { value, to: FfiType::FLOAT }, Some(FfiType::FLOAT));
+            }
+            Instruction::FloatToInt { bits, signed } => {
+                let value = self.pop(FfiType::FLOAT, instruction)?;
+                let to = FfiType::Int { bits: *bits, signed: *signed };
+                self.add(InstKind::Cast { value, to }, Some(FfiType::INT));
+            }
+            Instruction::WrapInt { bits, signed } => {
+                let value = self.pop(FfiType::INT, instruction)?;
+                let to = FfiType::Int { bits: *bits, signed: *signed };
+                self.add(InstKind::Cast { value, to }, Some(FfiType::INT));
+            }
+            Instruction::CallExtern { name, args, result } => {
+                let mut values = Vec::with_capacity(args.len());
+                for &ty in args.iter().rev() {
+                    values.push(self.pop(ty, instruction)?);
+                }
+                values.reverse();
+                let kind = InstKind::CallExtern { name: name.clone(), args: values, types: args.clone() };
+                self.add(kind, *result);
+            }
+            Instruction::Call { name, args, result } => {
+                let mut values = Vec::with_capacity(args.len());
+                for &ty in args.iter().rev() {
+                    values.push(self.pop(ty, instruction)?);
+                }
+                values.reverse();
+                self.add(InstKind::Call { function: name.clone(), args: values }, *result);
+            }
+            Instruction::Return { result } => {
+                if let Some(ty) = result {
+                    let value = self.pop(*ty, instruction)?;
+                    let InstKind::Load(local) = self.function.inst(value).kind else {
+                        return Err(BackendError::InvalidIr(format!("{:?} of a value not in a local", instruction)));
+                    };
+                    if self.function.result.is_some_and(|result| result != local) {
+                        return Err(BackendError::InvalidIr(format!("{:?} of two locals", instruction)));
+                    }
+                    self.function.result = Some(local);
+                    // The local is what is returned; the load is left to `compact`.
+                    let insts = &mut self.function.blocks[self.block.index()].insts;
+                    if insts.last() == Some(&value) {
+                        insts.pop();
+                    }
+                }
+                self.end_block(Terminator::Return, instruction)?;
+            }
+            Instruction::Function { name, .. } => {
+                return Err(BackendError::InvalidIr(format!("the function before `{}` does not return", name)));
+            }
+            Instruction::Exit => {
+                let code = self.pop(FfiType::INT, instruction)?;
+                // What is left on the stacks is never read.
+                self.stacks = Default::default();
+                self.end_block(Terminator::Exit(code), instruction)?;
+            }
+            Instruction::StoreLocal { slot, ty } => {
+                let local = self.local(*slot, *ty)?;
+                let value = self.pop(*ty, instruction)?;
+                self.add(InstKind::Store { local, value }, None);
+            }
+            Instruction::LoadLocal { slot, ty } => {
+                let local = self.local(*slot, *ty)?;
+                self.add(InstKind::Load(local), Some(*ty));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::tir::{self, ArithOp, CompareOp};
+
+    fn int(function: &mut Function, n: i64) -> InstId {
+        function.push(BlockId::ENTRY, InstKind::Const(Constant::Int(n)), Some(FfiType::INT))
+    }
+
+    #[test]
+    fn values_used_once_in_order_stay_on_the_stack() {
+        let mut function = tir::Function::new("main");
+        let seven = int(&mut function, 7);
+        let two = int(&mut function, 2);
+        function.push(BlockId::ENTRY, InstKind::Print(two), None);
+        function.push(BlockId::ENTRY, InstKind::Marker(0), None);
+        let f = function.push(BlockId::ENTRY, InstKind::Cast { value: seven, to: FfiType::FLOAT }, Some(FfiType::FLOAT));
+        function.push(BlockId::ENTRY, InstKind::Print(f), None);
+        // Never read, so never computed.
+        int(&mut function, 9);
+
+        let emitted = emit_function(&function).unwrap();
+        use Instruction::*;
+        assert_eq!(emitted.instructions, [PushInt(7), PushInt(2), PrintInt, IntToFloat, PrintFloat]);
+        assert_eq!(emitted.marks[&0], 3);
+    }
+
+    #[test]
+    fn values_out_of_order_or_read_twice_go_through_slots() {
+        let mut function = tir::Function::new("main");
+        let total = function.add_local(FfiType::INT);
+        let one = int(&mut function, 1);
+        let two = int(&mut function, 2);
+        function.push(BlockId::ENTRY, InstKind::Print(one), None);
+        function.push(BlockId::ENTRY, InstKind::Store { local: total, value: two }, None);
+        let loaded = function.push(BlockId::ENTRY, InstKind::Load(total), Some(FfiType::INT));
+        function.push(BlockId::ENTRY, InstKind::Print(loaded), None);
+        function.terminate(BlockId::ENTRY, Terminator::Exit(loaded));
+
+        let emitted = emit_function(&function).unwrap();
+        use Instruction::*;
+        let int = FfiType::INT;
+        assert_eq!(
+            emitted.instructions,
+            [
+                PushInt(1),
+                StoreLocal { slot: 1, ty: int },
+                PushInt(2),
+                LoadLocal { slot: 1, ty: int },
+                PrintInt,
+                StoreLocal { slot: 0, ty: int },
+                LoadLocal { slot: 0, ty: int },
+                StoreLocal { slot: 2, ty: int },
+                LoadLocal { slot: 2, ty: int },
+                PrintInt,
+                LoadLocal { slot: 2, ty: int },
+                Exit,
+            ]
+        );
+    }
+
+    #[test]
+    fn lifting_and_emitting_give_back_the_instructions() {
+        use Instruction::*;
+        let instructions = vec![
+            PushStr("n = ".to_string()),
+            PrintStr,
+            PushInt(300),
+            WrapInt { bits: 8, signed: false },
+            PushBool(true),
+            CallExtern {
+                name: "f".to_string(),
+                args: vec![FfiType::INT, FfiType::Bool],
+                result: Some(FfiType::Float { bits: 32 }),
+            },
+            PrintFloat,
+            PushInt(0),
+            Exit,
+        ];
+        let function = lift_function("main", &instructions, &[2]).unwrap();
+        assert_eq!(function.block(BlockId::ENTRY).insts.len(), 9);
+        assert_eq!(function.inst(InstId(2)).kind, InstKind::Marker(0));
+        let emitted = emit_function(&function).unwrap();
+        assert_eq!(emitted.instructions, instructions);
+        assert_eq!(emitted.marks[&0], 2);
+
+        assert!(lift_function("main", &[PrintStr], &[]).is_err());
+    }
+
+    #[test]
+    fn traps_read_the_guard_before_them() {
+        use Instruction::*;
+        let instructions = vec![
+            PushInt(7),
+            PushInt(0),
+            Overflows { op: ArithOp::Div, ty: FfiType::Int { bits: 32, signed: true } },
+            Trap { message: "attempt to divide with overflow".to_string(), backtrace: String::new(), abort: false },
+        ];
+        let function = lift_function("main", &instructions, &[]).unwrap();
+        let trap = &function.inst(InstId(3)).kind;
+        assert!(matches!(trap, InstKind::Trap { condition: InstId(2), abort: false, .. }));
+        assert!(trap.has_effects());
+        assert_eq!(emit_function(&function).unwrap().instructions, instructions);
+    }
+
+    #[test]
+    fn branches_become_labels_and_jumps() {
+        // while n < 3 { n = n + 1 }
+        let mut function = tir::Function::new("main");
+        let n = function.add_local(FfiType::INT);
+        let zero = int(&mut function, 0);
+        function.push(BlockId::ENTRY, InstKind::Store { local: n, value: zero }, None);
+        let (header, body, exit) = (function.add_block(), function.add_block(), function.add_block());
+        function.terminate(BlockId::ENTRY, Terminator::Jump(header));
+        let lhs = function.push(header, InstKind::Load(n), Some(FfiType::INT));
+        let rhs = function.push(header, InstKind::Const(Constant::Int(3)), Some(FfiType::INT));
+        let condition = function.push(header, InstKind::Compare { op: CompareOp::Lt, lhs, rhs }, Some(FfiType::Bool));
+        function.terminate(header, Terminator::Branch { condition, then: body, otherwise: exit });
+        let lhs = function.push(body, InstKind::Load(n), Some(FfiType::INT));
+        let rhs = function.push(body, InstKind::Const(Constant::Int(1)), Some(FfiType::INT));
+        let sum = function.push(body, InstKind::Arith { op: ArithOp::Add, lhs, rhs }, Some(FfiType::INT));
+        function.push(body, InstKind::Store { local: n, value: sum }, None);
+        function.terminate(body, Terminator::Jump(header));
+
+        let emitted = emit_function(&function).unwrap();
+        use Instruction::*;
+        let int = FfiType::INT;
+        let instructions = [
+            PushInt(0),
+            StoreLocal { slot: 0, ty: int },
+            Label(1),
+            LoadLocal { slot: 0, ty: int },
+            PushInt(3),
+            Compare { op: CompareOp::Lt, ty: int },
+            JumpIfFalse(3),
+            LoadLocal { slot: 0, ty: int },
+            PushInt(1),
+            Arith { op: ArithOp::Add, ty: int },
+            StoreLocal { slot: 0, ty: int },
+            Jump(1),
+            Label(3),
+        ];
+        assert_eq!(emitted.instructions, instructions);
+
+        let lifted = lift_function("main", &instructions, &[]).unwrap();
+        assert_eq!(lifted.blocks.len(), 4);
+        assert_eq!(lifted.block(BlockId(1)).terminator, Terminator::Branch { condition: InstId(4), then: body, otherwise: exit });
+        assert_eq!(emit_function(&lifted).unwrap().instructions, instructions);
+    }
+
+    #[test]
+    fn switches_become_case_tests_and_back() {
+        // match n { 1 | 5 => print("a"), 2 => print("b"), _ => {} }
+        let mut function = tir::Function::new("main");
+        let n = function.add_local(FfiType::INT);
+        let two = int(&mut function, 2);
+        function.push(BlockId::ENTRY, InstKind::Store { local: n, value: two }, None);
+        let value = function.push(BlockId::ENTRY, InstKind::Load(n), Some(FfiType::INT));
+        let (a, b, join) = (function.add_block(), function.add_block(), function.add_block());
+        let cases = vec![(1, a), (2, b), (5, a)];
+        function.terminate(BlockId::ENTRY, Terminator::Switch { value, cases, default: join });
+        for (block, text) in [(a, "a"), (b, "b")] {
+            let text = function.push(block, InstKind::Const(Constant::Str(text.to_string())), Some(FfiType::Str));
+            function.push(block, InstKind::Print(text), None);
+            function.terminate(block, Terminator::Jump(join));
+        }
+
+        let emitted = emit_function(&function).unwrap();
+        use Instruction::*;
+        let int = FfiType::INT;
+        let test = |n, target| {
+            [LoadLocal { slot: 0, ty: int }, PushInt(n), Compare { op: CompareOp::Ne, ty: int }, JumpIfFalse(target)]
+        };
+        let mut instructions = vec![PushInt(2), StoreLocal { slot: 0, ty: int }];
+        instructions.extend([test(1, 1), test(2, 2), test(5, 1)].concat());
+        instructions.extend([Jump(3), Label(1), PushStr("a".to_string()), PrintStr, Jump(3)]);
+        instructions.extend([Label(2), PushStr("b".to_string()), PrintStr, Label(3)]);
+        assert_eq!(emitted.instructions, instructions);
+
+        // The tests lift to a block each, which come back together.
+        let lifted = lift_function("main", &instructions, &[]).unwrap();
+        assert_eq!(lifted.blocks.len(), 4);
+        assert_eq!(lifted.block(BlockId::ENTRY).terminator, function.block(BlockId::ENTRY).terminator);
+        assert_eq!(lifted.insts.len(), function.insts.len());
+        assert_eq!(emit_function(&lifted).unwrap().instructions, instructions);
+    }
+}
@@ -1,66 +1,123 @@
-//! scaffold/src/ast.rs - Minimal AST for parsing `fn main() -> i32 { return 42; }`
-//!
-//! FROZEN: Once this works, never change the structure - only add fields.
-
-#[derive(Debug, Clone)]
-pub struct Program {
-    pub functions: Vec<Function>,
-}
-
-#[derive(Debug, Clone)]
-pub struct Function {
-    pub name: String,
-    pub params: Vec<Parameter>,
-    pub return_type: Option<Type>,
-    pub body: Block,
-}
-
-#[derive(Debug, Clone)]
-pub struct Parameter {
-    pub name: String,
-    pub param_type: Type,
-}
-
-#[derive(Debug, Clone)]
-pub struct Type {
-    pub name: String,  // Just store type names as strings for now
-}
-
-#[derive(Debug, Clone)]
-pub struct Block {
-    pub statements: Vec<Statement>,
-}
-
-#[derive(Debug, Clone)]
-pub enum Statement {
-    Return(Expression),
-}
-
-#[derive(Debug, Clone)]
-pub enum Expression {
-    Literal(Literal),
-}
-
-#[derive(Debug, Clone)]
-pub enum Literal {
-    Integer(i64),
-}
-
-impl Program {
-    pub fn new() -> Self {
-        Self {
-            functions: Vec::new(),
-        }
-    }
-}
-
-impl Function {
-    pub fn new(name: String) -> Self {
-        Self {
-            name,
-            params: Vec::new(),
-            return_type: None,
-            body: Block { statements: Vec::new() },
-        }
-    }
+//! scaffold/src/ast.rs - Minimal AST for functions, structs and `impl` blocks
+//!
+//! FROZEN: Once this works, never change the structure - only add fields.
+//!
+//! Everything derives `Serialize` for `tlang ast --format json|sexpr`.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub structs: Vec<Struct>,
+    pub impls: Vec<Impl>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    pub body: Block,
+    /// Methods take `self` (by value) before `params`.
+    pub has_self: bool,
+}
+
+/// `struct Name { field: Type, ... }`
+#[derive(Debug, Clone, Serialize)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Parameter>,
+}
+
+/// `impl TypeName { fn method(self, ...) ... }`
+#[derive(Debug, Clone, Serialize)]
+pub struct Impl {
+    pub type_name: String,
+    pub methods: Vec<Function>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Parameter {
+    pub name: String,
+    pub param_type: Type,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Type {
+    pub name: String,  // Just store type names as strings for now
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum Statement {
+    Return(Expression),
+    Let { name: String, value: Expression },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum Expression {
+    Literal(Literal),
+    /// A parameter, `let` binding or `self`.
+    Variable(String),
+    Binary { op: BinaryOp, left: Box<Expression>, right: Box<Expression> },
+    /// `function` is a free function or `Type::name`.
+    Call { function: String, args: Vec<Expression> },
+    MethodCall { receiver: Box<Expression>, method: String, args: Vec<Expression> },
+    StructLiteral { name: String, fields: Vec<(String, Expression)> },
+    Field { object: Box<Expression>, field: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum Literal {
+    Integer(i64),
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self {
+            functions: Vec::new(),
+            structs: Vec::new(),
+            impls: Vec::new(),
+        }
+    }
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Function {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            params: Vec::new(),
+            return_type: None,
+            body: Block { statements: Vec::new() },
+            has_self: false,
+        }
+    }
 }
\ No newline at end of file
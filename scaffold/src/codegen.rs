@@ -1,164 +1,283 @@
-//! scaffold/src/codegen.rs - Code generator that outputs valid Rust code
-//!
-//! This generates Rust code from our validated AST.
-
-use crate::ast::*;
-
-#[derive(Debug)]
-pub enum CodegenError {
-    UnsupportedConstruct(String),
-    InvalidLiteral(String),
-}
-
-impl std::fmt::Display for CodegenError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            CodegenError::UnsupportedConstruct(msg) => {
-                write!(f, "Unsupported construct: {msg}")
-            }
-            CodegenError::InvalidLiteral(msg) => {
-                write!(f, "Invalid literal: {msg}")
-            }
-        }
-    }
-}
-
-impl std::error::Error for CodegenError {}
-
-pub struct CodeGenerator {
-    output: String,
-}
-
-impl CodeGenerator {
-    pub fn new() -> Self {
-        Self {
-            output: String::new(),
-        }
-    }
-
-    /// Generate Rust code from a program
-    pub fn generate_program(&mut self, program: &Program) -> Result<String, CodegenError> {
-        self.output.clear();
-
-        // Generate all functions
-        for function in &program.functions {
-            self.generate_function(function)?;
-        }
-
-        Ok(self.output.clone())
-    }
-
-    /// Generate Rust code for a function
-    fn generate_function(&mut self, function: &Function) -> Result<(), CodegenError> {
-        // Special handling for main function to make it compatible with Rust
-        if function.name == "main" {
-            // Generate a wrapper main function
-            self.output.push_str("fn main() {\n");
-            self.output.push_str("    let exit_code = tlang_main();\n");
-            self.output.push_str("    std::process::exit(exit_code);\n");
-            self.output.push_str("}\n\n");
-
-            // Generate the actual T-Lang main function with a different name
-            self.output.push_str("fn tlang_main()");
-
-            // Generate return type
-            if let Some(return_type) = &function.return_type {
-                self.output.push_str(" -> ");
-                self.generate_type(return_type)?;
-            }
-
-            self.output.push(' ');
-
-            // Generate function body
-            self.generate_block(&function.body)?;
-        } else {
-            // Generate function signature normally for non-main functions
-            self.output.push_str("fn ");
-            self.output.push_str(&function.name);
-            self.output.push('(');
-
-            // Generate parameters
-            for (i, param) in function.params.iter().enumerate() {
-                if i > 0 {
-                    self.output.push_str(", ");
-                }
-                self.output.push_str(&param.name);
-                self.output.push_str(": ");
-                self.generate_type(&param.param_type)?;
-            }
-
-            self.output.push(')');
-
-            // Generate return type
-            if let Some(return_type) = &function.return_type {
-                self.output.push_str(" -> ");
-                self.generate_type(return_type)?;
-            }
-
-            self.output.push(' ');
-
-            // Generate function body
-            self.generate_block(&function.body)?;
-        }
-
-        self.output.push('\n');
-
-        Ok(())
-    }
-
-    /// Generate Rust code for a type
-    fn generate_type(&mut self, type_ref: &Type) -> Result<(), CodegenError> {
-        // For now, just output the type name directly
-        // This works for primitive types like i32
-        self.output.push_str(&type_ref.name);
-        Ok(())
-    }
-
-    /// Generate Rust code for a block
-    fn generate_block(&mut self, block: &Block) -> Result<(), CodegenError> {
-        self.output.push('{');
-
-        for statement in &block.statements {
-            self.output.push('\n');
-            self.output.push_str("    "); // Indent
-            self.generate_statement(statement)?;
-        }
-
-        self.output.push('\n');
-        self.output.push('}');
-
-        Ok(())
-    }
-
-    /// Generate Rust code for a statement
-    fn generate_statement(&mut self, statement: &Statement) -> Result<(), CodegenError> {
-        match statement {
-            Statement::Return(expr) => {
-                self.output.push_str("return ");
-                self.generate_expression(expr)?;
-                self.output.push(';');
-            }
-        }
-        Ok(())
-    }
-
-    /// Generate Rust code for an expression
-    fn generate_expression(&mut self, expr: &Expression) -> Result<(), CodegenError> {
-        match expr {
-            Expression::Literal(literal) => {
-                self.generate_literal(literal)?;
-            }
-        }
-        Ok(())
-    }
-
-    /// Generate Rust code for a literal
-    fn generate_literal(&mut self, literal: &Literal) -> Result<(), CodegenError> {
-        match literal {
-            Literal::Integer(value) => {
-                self.output.push_str(&value.to_string());
-            }
-        }
-        Ok(())
-    }
-}
\ No newline at end of file
+//! scaffold/src/codegen.rs - Code generator that outputs valid Rust code
+//!
+//! This generates Rust code from our validated AST. Structs, `impl` blocks
+//! and method calls map one-to-one onto their Rust counterparts.
+
+use crate::ast::*;
+
+#[derive(Debug)]
+pub enum CodegenError {
+    UnsupportedConstruct(String),
+    InvalidLiteral(String),
+}
+
+impl std::fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodegenError::UnsupportedConstruct(msg) => {
+                write!(f, "Unsupported construct: {msg}")
+            }
+            CodegenError::InvalidLiteral(msg) => {
+                write!(f, "Invalid literal: {msg}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+pub struct CodeGenerator {
+    output: String,
+}
+
+impl CodeGenerator {
+    pub fn new() -> Self {
+        Self {
+            output: String::new(),
+        }
+    }
+
+    /// Generate Rust code from a program
+    pub fn generate_program(&mut self, program: &Program) -> Result<String, CodegenError> {
+        self.output.clear();
+
+        // Scaffold values are plain data, so structs are `Copy` and methods
+        // can take `self` by value without moving the receiver.
+        for item in &program.structs {
+            self.generate_struct(item)?;
+        }
+
+        for item in &program.impls {
+            self.output.push_str("impl ");
+            self.output.push_str(&item.type_name);
+            self.output.push_str(" {\n");
+            for method in &item.methods {
+                self.output.push_str("    ");
+                self.generate_signature(&method.name, method)?;
+                self.generate_block(&method.body, 1)?;
+                self.output.push('\n');
+            }
+            self.output.push_str("}\n\n");
+        }
+
+        // Generate all functions
+        for function in &program.functions {
+            self.generate_function(function)?;
+        }
+
+        Ok(self.output.clone())
+    }
+
+    /// Generate a Rust struct definition
+    fn generate_struct(&mut self, item: &Struct) -> Result<(), CodegenError> {
+        self.output.push_str("#[derive(Debug, Clone, Copy)]\n");
+        self.output.push_str("struct ");
+        self.output.push_str(&item.name);
+        self.output.push_str(" {\n");
+        for field in &item.fields {
+            self.output.push_str("    ");
+            self.output.push_str(&field.name);
+            self.output.push_str(": ");
+            self.generate_type(&field.param_type)?;
+            self.output.push_str(",\n");
+        }
+        self.output.push_str("}\n\n");
+        Ok(())
+    }
+
+    /// Generate Rust code for a function
+    fn generate_function(&mut self, function: &Function) -> Result<(), CodegenError> {
+        // Special handling for main function to make it compatible with Rust
+        if function.name == "main" {
+            // Generate a wrapper main function, exiting with the code
+            // `tlang_main` returns
+            self.output.push_str("fn main() {\n");
+            if function.return_type.is_some() {
+                self.output.push_str("    let exit_code = tlang_main();\n");
+                self.output.push_str("    std::process::exit(exit_code);\n");
+            } else {
+                self.output.push_str("    tlang_main();\n");
+            }
+            self.output.push_str("}\n\n");
+
+            // Generate the actual T-Lang main function with a different name
+            self.generate_signature("tlang_main", function)?;
+        } else {
+            // Generate function signature normally for non-main functions
+            self.generate_signature(&function.name, function)?;
+        }
+
+        // Generate function body
+        self.generate_block(&function.body, 0)?;
+        self.output.push('\n');
+
+        Ok(())
+    }
+
+    /// Generate `fn name(params) -> Type ` for a function or method
+    fn generate_signature(&mut self, name: &str, function: &Function) -> Result<(), CodegenError> {
+        self.output.push_str("fn ");
+        self.output.push_str(name);
+        self.output.push('(');
+
+        if function.has_self {
+            self.output.push_str("self");
+        }
+
+        // Generate parameters
+        for (i, param) in function.params.iter().enumerate() {
+            if i > 0 || function.has_self {
+                self.output.push_str(", ");
+            }
+            self.output.push_str(&param.name);
+            self.output.push_str(": ");
+            self.generate_type(&param.param_type)?;
+        }
+
+        self.output.push(')');
+
+        // Generate return type
+        if let Some(return_type) = &function.return_type {
+            self.output.push_str(" -> ");
+            self.generate_type(return_type)?;
+        }
+
+        self.output.push(' ');
+        Ok(())
+    }
+
+    /// Generate Rust code for a type
+    fn generate_type(&mut self, type_ref: &Type) -> Result<(), CodegenError> {
+        // Primitive and struct names are spelled the same in Rust
+        self.output.push_str(&type_ref.name);
+        Ok(())
+    }
+
+    /// Generate Rust code for a block nested `depth` levels deep
+    fn generate_block(&mut self, block: &Block, depth: usize) -> Result<(), CodegenError> {
+        let indent = "    ".repeat(depth);
+        self.output.push('{');
+
+        for statement in &block.statements {
+            self.output.push('\n');
+            self.output.push_str(&indent);
+            self.output.push_str("    "); // Indent
+            self.generate_statement(statement)?;
+        }
+
+        self.output.push('\n');
+        self.output.push_str(&indent);
+        self.output.push('}');
+
+        Ok(())
+    }
+
+    /// Generate Rust code for a statement
+    fn generate_statement(&mut self, statement: &Statement) -> Result<(), CodegenError> {
+        match statement {
+            Statement::Return(expr) => {
+                self.output.push_str("return ");
+                self.generate_expression(expr)?;
+                self.output.push(';');
+            }
+            Statement::Let { name, value } => {
+                self.output.push_str("let ");
+                self.output.push_str(name);
+                self.output.push_str(" = ");
+                self.generate_expression(value)?;
+                self.output.push(';');
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate a comma-separated argument list in parentheses
+    fn generate_arguments(&mut self, args: &[Expression]) -> Result<(), CodegenError> {
+        self.output.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.generate_expression(arg)?;
+        }
+        self.output.push(')');
+        Ok(())
+    }
+
+    /// Generate an operand of a binary operation, parenthesising nested
+    /// operations rather than tracking precedence
+    fn generate_operand(&mut self, expr: &Expression) -> Result<(), CodegenError> {
+        if matches!(expr, Expression::Binary { .. }) {
+            self.output.push('(');
+            self.generate_expression(expr)?;
+            self.output.push(')');
+            Ok(())
+        } else {
+            self.generate_expression(expr)
+        }
+    }
+
+    /// Generate Rust code for an expression
+    fn generate_expression(&mut self, expr: &Expression) -> Result<(), CodegenError> {
+        match expr {
+            Expression::Literal(literal) => {
+                self.generate_literal(literal)?;
+            }
+            Expression::Variable(name) => {
+                self.output.push_str(name);
+            }
+            Expression::Binary { op, left, right } => {
+                self.generate_operand(left)?;
+                self.output.push(' ');
+                self.output.push_str(op.symbol());
+                self.output.push(' ');
+                self.generate_operand(right)?;
+            }
+            Expression::Call { function, args } => {
+                self.output.push_str(function);
+                self.generate_arguments(args)?;
+            }
+            Expression::MethodCall { receiver, method, args } => {
+                self.generate_expression(receiver)?;
+                self.output.push('.');
+                self.output.push_str(method);
+                self.generate_arguments(args)?;
+            }
+            Expression::StructLiteral { name, fields } => {
+                self.output.push_str(name);
+                self.output.push_str(" { ");
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.output.push_str(field);
+                    self.output.push_str(": ");
+                    self.generate_expression(value)?;
+                }
+                self.output.push_str(" }");
+            }
+            Expression::Field { object, field } => {
+                self.generate_expression(object)?;
+                self.output.push('.');
+                self.output.push_str(field);
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate Rust code for a literal
+    fn generate_literal(&mut self, literal: &Literal) -> Result<(), CodegenError> {
+        match literal {
+            Literal::Integer(value) => {
+                self.output.push_str(&value.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,151 @@
+//! scaffold/src/commands.rs - The scaffold CLI's commands
+//!
+//! Each command reports its progress on stdout and returns its failure as a
+//! `TlError`, leaving the caller to render it and pick an exit code, so the
+//! commands can run inside another program too.
+
+use std::fs;
+use std::path::Path;
+
+use crate::ast;
+use crate::codegen;
+use crate::compile::{self, CompileOptions};
+use crate::dump::{self, DumpFormat};
+use crate::error_bridge::{
+    convert_codegen_error, convert_compile_error, convert_dump_error, convert_parse_error,
+    convert_type_error, ScaffoldResult,
+};
+use crate::parser;
+use crate::typechecker;
+use errors::TlError;
+
+pub const VERSION: &str = "0.1.0-scaffold";
+
+/// Compile T-Lang source to executable
+pub fn command_compile(filename: &str, options: &CompileOptions) -> ScaffoldResult<()> {
+    println!("🔨 T-Lang Compiler {} - Compiling {}", VERSION, filename);
+
+    let (_source, program) = parse_and_check(filename)?;
+
+    println!("🔍 Generating code...");
+    let rust_code = generate_code(&program)?;
+
+    println!("🔍 Compiling to executable...");
+    let base_name = get_base_name(filename);
+    let compiler = compile::Compiler::with_options(options.clone());
+    let executable_path = compiler
+        .compile_rust_code(&rust_code, &base_name)
+        .map_err(convert_compile_error)?;
+    println!("✅ Compilation successful!");
+    println!("📦 Created: {}", executable_path);
+    Ok(())
+}
+
+/// Compile and run T-Lang source, returning the program's exit code
+pub fn command_run(filename: &str, options: &CompileOptions) -> ScaffoldResult<i32> {
+    println!("🚀 T-Lang Compiler {} - Running {}", VERSION, filename);
+
+    let (_source, program) = parse_and_check(filename)?;
+
+    println!("🔍 Generating code...");
+    let rust_code = generate_code(&program)?;
+
+    println!("🔍 Compiling and running...");
+    let base_name = get_base_name(filename);
+    let compiler = compile::Compiler::with_options(options.clone());
+    let (_executable_path, exit_code) = compiler
+        .compile_and_run(&rust_code, &base_name)
+        .map_err(convert_compile_error)?;
+    println!("✅ Execution completed!");
+    println!("📊 Exit code: {}", exit_code);
+    Ok(exit_code)
+}
+
+/// Check syntax and types only
+pub fn command_check(filename: &str) -> ScaffoldResult<()> {
+    println!("🔍 T-Lang Compiler {} - Checking {}", VERSION, filename);
+
+    parse_and_check(filename)?;
+
+    println!("✅ Check passed! No errors found.");
+    Ok(())
+}
+
+/// Show parsed AST
+///
+/// `json` and `sexpr` print the dump alone, so tools can read stdout as is.
+pub fn command_ast(filename: &str, format: DumpFormat) -> ScaffoldResult<()> {
+    if format == DumpFormat::Debug {
+        println!("🔍 T-Lang Compiler {} - AST for {}", VERSION, filename);
+    }
+
+    let source = read_source_file(filename)?;
+    let program = parse_source(&source)?;
+
+    let text = dump::dump(&program, format).map_err(convert_dump_error)?;
+    if format == DumpFormat::Debug {
+        println!("\n📋 Abstract Syntax Tree:");
+    }
+    println!("{}", text);
+    Ok(())
+}
+
+/// Helper: Read and validate source file
+fn read_source_file(filename: &str) -> ScaffoldResult<String> {
+    if !Path::new(filename).exists() {
+        return Err(TlError::io(format!("File not found: {}", filename), None));
+    }
+
+    let content = fs::read_to_string(filename)
+        .map_err(|e| TlError::io(format!("Error reading file '{}'", filename), Some(e)))?;
+    if content.trim().is_empty() {
+        return Err(TlError::io(format!("File is empty: {}", filename), None));
+    }
+    Ok(content)
+}
+
+/// Helper: Parse source into AST
+fn parse_source(source: &str) -> ScaffoldResult<ast::Program> {
+    let mut parser = parser::Parser::new(source);
+    parser.parse().map_err(|e| convert_parse_error(e, source))
+}
+
+/// Helper: Type check AST
+fn type_check_program(program: &ast::Program, source: &str) -> ScaffoldResult<()> {
+    let typechecker = typechecker::TypeChecker::new();
+    typechecker
+        .check_program(program)
+        .map_err(|e| convert_type_error(&e.to_string(), source))
+}
+
+/// Helper: Generate code from AST
+fn generate_code(program: &ast::Program) -> ScaffoldResult<String> {
+    let mut codegen = codegen::CodeGenerator::new();
+    codegen.generate_program(program).map_err(convert_codegen_error)
+}
+
+/// Helper: Parse and type-check (common pattern)
+fn parse_and_check(filename: &str) -> ScaffoldResult<(String, ast::Program)> {
+    let source = read_source_file(filename)?;
+
+    println!("🔍 Parsing...");
+    let program = parse_source(&source)?;
+
+    println!("✅ Parsing successful!");
+
+    println!("🔍 Type checking...");
+    type_check_program(&program, &source)?;
+
+    println!("✅ Type checking passed!");
+
+    Ok((source, program))
+}
+
+/// Helper: Extract base name from filename
+fn get_base_name(filename: &str) -> String {
+    Path::new(filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output")
+        .to_string()
+}
@@ -1,16 +1,24 @@
 //! scaffold/src/compile.rs - Calls rustc to build executable
 //!
 //! This module handles compilation of generated Rust code to executable.
+//! `CompileOptions` pick the output directory, release mode, target and
+//! extra rustc flags; an executable built from unchanged code with
+//! unchanged flags is reused rather than rebuilt.
 
+use std::collections::hash_map::DefaultHasher;
 use std::fs;
-use std::path::Path;
-use std::process::Command;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub enum CompileError {
     WriteError(std::io::Error),
     CompileError(String),
     RustcNotFound,
+    TimedOut(Duration),
 }
 
 impl std::fmt::Display for CompileError {
@@ -19,57 +27,97 @@ impl std::fmt::Display for CompileError {
             CompileError::WriteError(e) => write!(f, "Failed to write Rust file: {e}"),
             CompileError::CompileError(msg) => write!(f, "Compilation failed: {msg}"),
             CompileError::RustcNotFound => write!(f, "rustc not found - please install Rust"),
+            CompileError::TimedOut(timeout) => write!(f, "Program did not finish within {:?}", timeout),
         }
     }
 }
 
 impl std::error::Error for CompileError {}
 
+/// How generated Rust is built.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOptions {
+    /// Where the generated source and the executable are written
+    pub output_dir: PathBuf,
+    /// Optimize as `cargo build --release` does, without debug assertions
+    /// or overflow checks
+    pub release: bool,
+    /// Target triple to build for; the host when `None`
+    pub target: Option<String>,
+    /// Passed to rustc after the flags above, so they can override them
+    pub rustc_flags: Vec<String>,
+    /// Skip rustc when the executable was built from the same code with
+    /// the same flags
+    pub cache: bool,
+    /// Longest `compile_and_run` lets the program run; no limit when `None`
+    pub timeout: Option<Duration>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("target/scaffold_temp"),
+            release: false,
+            target: None,
+            rustc_flags: Vec::new(),
+            cache: true,
+            timeout: None,
+        }
+    }
+}
+
 pub struct Compiler {
-    temp_dir: String,
+    options: CompileOptions,
 }
 
 impl Compiler {
     pub fn new() -> Self {
-        Self {
-            temp_dir: "target/scaffold_temp".to_string(),
-        }
+        Self::with_options(CompileOptions::default())
+    }
+
+    pub fn with_options(options: CompileOptions) -> Self {
+        Self { options }
     }
 
     /// Compile Rust source code to executable
+    ///
+    /// `<output_name>.rs` and the executable go in the output directory,
+    /// next to a `<output_name>.fingerprint` of the code and flags they were
+    /// built with, which the cache compares against.
     pub fn compile_rust_code(&self, rust_code: &str, output_name: &str) -> Result<String, CompileError> {
-        // Create temp directory if it doesn't exist
-        std::fs::create_dir_all(&self.temp_dir)
-            .map_err(CompileError::WriteError)?;
+        let dir = &self.options.output_dir;
+        fs::create_dir_all(dir).map_err(CompileError::WriteError)?;
+
+        let output_path = dir.join(self.executable_name(output_name));
+        let fingerprint_file = dir.join(format!("{}.fingerprint", output_name));
+        let args = self.rustc_args();
+        let fingerprint = fingerprint(rust_code, &args);
+        if self.options.cache
+            && output_path.exists()
+            && fs::read_to_string(&fingerprint_file).is_ok_and(|old| old == fingerprint)
+        {
+            return Ok(output_path.display().to_string());
+        }
 
-        // Write Rust code to temporary file
-        let rust_file = format!("{}/main.rs", self.temp_dir);
+        // Write Rust code to the output directory
+        let rust_file = dir.join(format!("{}.rs", output_name));
         fs::write(&rust_file, rust_code)
             .map_err(CompileError::WriteError)?;
 
-        // Determine output executable name (with .exe on Windows)
-        let exe_name = if cfg!(windows) {
-            format!("{}.exe", output_name)
-        } else {
-            output_name.to_string()
-        };
-
-        let output_path = format!("{}/{}", self.temp_dir, exe_name);
-
         // Call rustc to compile
         let output = Command::new("rustc")
             .arg(&rust_file)
             .arg("-o")
             .arg(&output_path)
-            .arg("--edition")
-            .arg("2021")
+            .args(&args)
             .output();
 
         match output {
             Ok(result) => {
                 if result.status.success() {
                     // Compilation successful
-                    Ok(output_path)
+                    fs::write(&fingerprint_file, fingerprint).map_err(CompileError::WriteError)?;
+                    Ok(output_path.display().to_string())
                 } else {
                     // Compilation failed
                     let stderr = String::from_utf8_lossy(&result.stderr);
@@ -86,6 +134,32 @@ impl Compiler {
         }
     }
 
+    /// Flags for rustc, after the input and output paths
+    fn rustc_args(&self) -> Vec<String> {
+        let mut args = vec!["--edition".to_string(), "2021".to_string()];
+        if self.options.release {
+            args.extend(["-C".to_string(), "opt-level=3".to_string()]);
+        }
+        if let Some(target) = &self.options.target {
+            args.extend(["--target".to_string(), target.clone()]);
+        }
+        args.extend(self.options.rustc_flags.iter().cloned());
+        args
+    }
+
+    /// Executable file name (with .exe for Windows targets)
+    fn executable_name(&self, output_name: &str) -> String {
+        let windows = match &self.options.target {
+            Some(target) => target.contains("windows"),
+            None => cfg!(windows),
+        };
+        if windows {
+            format!("{}.exe", output_name)
+        } else {
+            output_name.to_string()
+        }
+    }
+
     /// Run the compiled executable and return its output
     pub fn run_executable(&self, exe_path: &str) -> Result<String, CompileError> {
         let output = Command::new(exe_path)
@@ -98,27 +172,75 @@ impl Compiler {
     }
 
     /// Full compilation pipeline: T-Lang source -> executable -> run
-    pub fn compile_and_run(&self, rust_code: &str, output_name: &str) -> Result<(String, String, i32), CompileError> {
+    ///
+    /// The program shares this process's stdin, stdout and stderr, so its
+    /// output appears as it is written and it can read input. It is killed
+    /// once it outlives the timeout.
+    pub fn compile_and_run(&self, rust_code: &str, output_name: &str) -> Result<(String, i32), CompileError> {
         // Compile the Rust code
         let exe_path = self.compile_rust_code(rust_code, output_name)?;
 
-        // Run the executable and capture both output and exit code
-        let output_result = Command::new(&exe_path)
-            .output()
+        let mut child = Command::new(&exe_path)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn()
             .map_err(|e| CompileError::CompileError(format!("Failed to run executable: {}", e)))?;
+        let status = match self.options.timeout {
+            Some(timeout) => wait_timeout(&mut child, timeout)?,
+            None => child.wait().map_err(wait_failed)?,
+        };
 
-        let stdout = String::from_utf8_lossy(&output_result.stdout).to_string();
-        let exit_code = output_result.status.code().unwrap_or(-1);
-
-        Ok((exe_path, stdout, exit_code))
+        Ok((exe_path, status.code().unwrap_or(-1)))
     }
 
     /// Clean up temporary files
     pub fn cleanup(&self) -> Result<(), CompileError> {
-        if Path::new(&self.temp_dir).exists() {
-            std::fs::remove_dir_all(&self.temp_dir)
+        if Path::new(&self.options.output_dir).exists() {
+            std::fs::remove_dir_all(&self.options.output_dir)
                 .map_err(CompileError::WriteError)?;
         }
         Ok(())
     }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wait for `child`, killing it if it runs longer than `timeout`.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<ExitStatus, CompileError> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().map_err(wait_failed)? {
+            return Ok(status);
+        }
+        if started.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CompileError::TimedOut(timeout));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn wait_failed(e: std::io::Error) -> CompileError {
+    CompileError::CompileError(format!("Failed to wait for executable: {}", e))
+}
+
+/// What an executable was built from: the code, the rustc flags and the
+/// rustc used. The hash only has to match builds by the same scaffold.
+fn fingerprint(rust_code: &str, args: &[String]) -> String {
+    let rustc = Command::new("rustc")
+        .arg("--version")
+        .output()
+        .map(|output| output.stdout)
+        .unwrap_or_default();
+    let mut hasher = DefaultHasher::new();
+    rust_code.hash(&mut hasher);
+    args.hash(&mut hasher);
+    rustc.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
 }
\ No newline at end of file
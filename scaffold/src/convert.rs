@@ -0,0 +1,184 @@
+//! scaffold/src/convert.rs - Conversion of scaffold programs to the compiler's AST
+//!
+//! `to_shared` turns a parsed scaffold `Program` into a `shared::ast::Program`,
+//! the tree the main compiler's type checker, safety analyzer and backends
+//! work on. The scaffold AST keeps no source positions, so every span is the
+//! empty one at offset 0, as the grammar gives nodes it does not place. Items
+//! come out structs first, then `impl` blocks, then functions.
+//!
+//! A function body's final `return value;` becomes the block's value, as it
+//! is written in the main language; an earlier one stays a `return`. Methods
+//! taking `self` get a first parameter `self` of the `impl`'s type.
+
+use crate::ast::*;
+use miette::SourceSpan;
+use shared::ast::expr::FieldInit;
+use shared::ast::stmt::{FnParam, ImplItem, StructField, StructFields};
+use shared::ast::{self as tl, ExprKind, ItemKind, PatternKind, PrimitiveType, SafetyLevel, StmtKind, TypeKind};
+
+/// `program` as the main compiler's AST.
+pub fn to_shared(program: &Program) -> tl::Program {
+    let structs = program.structs.iter().map(convert_struct);
+    let impls = program.impls.iter().map(convert_impl);
+    let functions = program.functions.iter().map(|function| {
+        let body = Some(convert_body(&function.body));
+        let kind = ItemKind::Function {
+            name: function.name.clone(),
+            generics: Vec::new(),
+            params: function.params.iter().map(convert_param).collect(),
+            return_type: function.return_type.as_ref().map(convert_type),
+            body,
+            safety: SafetyLevel::Safe,
+            async_: false,
+            const_: false,
+        };
+        tl::Item::new(kind, no_span())
+    });
+    tl::Program { items: structs.chain(impls).chain(functions).collect(), span: no_span() }
+}
+
+fn no_span() -> SourceSpan {
+    SourceSpan::new(0.into(), 0)
+}
+
+fn convert_struct(strukt: &Struct) -> tl::Item {
+    let fields = strukt
+        .fields
+        .iter()
+        .map(|field| StructField {
+            name: field.name.clone(),
+            ty: convert_type(&field.param_type),
+            vis: tl::Visibility::Private,
+            attrs: Vec::new(),
+            span: no_span(),
+        })
+        .collect();
+    let fields = StructFields::Named(fields);
+    tl::Item::new(ItemKind::Struct { name: strukt.name.clone(), generics: Vec::new(), fields }, no_span())
+}
+
+fn convert_impl(imp: &Impl) -> tl::Item {
+    let self_ty = named_type(&imp.type_name);
+    let items = imp
+        .methods
+        .iter()
+        .map(|method| {
+            let mut params = Vec::new();
+            if method.has_self {
+                params.push(FnParam {
+                    pattern: ident_pattern("self"),
+                    ty: self_ty.clone(),
+                    default: None,
+                    attrs: Vec::new(),
+                    span: no_span(),
+                });
+            }
+            params.extend(method.params.iter().map(convert_param));
+            ImplItem::Function {
+                name: method.name.clone(),
+                generics: Vec::new(),
+                params,
+                return_type: method.return_type.as_ref().map(convert_type),
+                body: convert_body(&method.body),
+                safety: SafetyLevel::Safe,
+                vis: tl::Visibility::Private,
+            }
+        })
+        .collect();
+    let kind = ItemKind::Impl { generics: Vec::new(), trait_: None, self_ty, items, safety: SafetyLevel::Safe };
+    tl::Item::new(kind, no_span())
+}
+
+fn convert_param(param: &Parameter) -> FnParam {
+    FnParam {
+        pattern: ident_pattern(&param.name),
+        ty: convert_type(&param.param_type),
+        default: None,
+        attrs: Vec::new(),
+        span: no_span(),
+    }
+}
+
+fn ident_pattern(name: &str) -> tl::Pattern {
+    tl::Pattern { kind: PatternKind::Ident(name.to_string()), span: no_span() }
+}
+
+/// Primitive type names become primitives, as the grammar tells them apart.
+fn convert_type(ty: &Type) -> tl::Type {
+    match PrimitiveType::from_name(&ty.name) {
+        Some(prim) => tl::Type::primitive(prim, no_span()),
+        None => named_type(&ty.name),
+    }
+}
+
+fn named_type(name: &str) -> tl::Type {
+    tl::Type::new(TypeKind::Named { path: vec![name.to_string()], generics: Vec::new() }, no_span())
+}
+
+fn convert_body(body: &Block) -> tl::Expr {
+    let mut statements: Vec<&Statement> = body.statements.iter().collect();
+    let value = match statements.last() {
+        Some(Statement::Return(expr)) => {
+            statements.pop();
+            Some(Box::new(convert_expr(expr)))
+        }
+        _ => None,
+    };
+    let statements = statements
+        .into_iter()
+        .map(|statement| match statement {
+            Statement::Let { name, value } => {
+                let (pattern, initializer) = (ident_pattern(name), Some(convert_expr(value)));
+                tl::Stmt::new(StmtKind::Let { pattern, ty: None, initializer, mutable: false }, no_span())
+            }
+            Statement::Return(expr) => tl::Stmt::expr(tl::Expr::new(
+                ExprKind::Return { value: Some(Box::new(convert_expr(expr))) },
+                no_span(),
+            )),
+        })
+        .collect();
+    tl::Expr::new(ExprKind::Block(tl::Block { statements, expr: value, span: no_span() }), no_span())
+}
+
+fn convert_expr(expr: &Expression) -> tl::Expr {
+    let kind = match expr {
+        Expression::Literal(Literal::Integer(value)) => ExprKind::Literal(tl::Literal::Integer(i128::from(*value))),
+        Expression::Variable(name) => ExprKind::Variable { path: vec![name.clone()] },
+        Expression::Binary { op, left, right } => ExprKind::Binary {
+            left: Box::new(convert_expr(left)),
+            op: match op {
+                BinaryOp::Add => tl::BinaryOp::Add,
+                BinaryOp::Sub => tl::BinaryOp::Sub,
+                BinaryOp::Mul => tl::BinaryOp::Mul,
+            },
+            right: Box::new(convert_expr(right)),
+        },
+        Expression::Call { function, args } => {
+            let path = function.split("::").map(str::to_string).collect();
+            ExprKind::Call {
+                callee: Box::new(tl::Expr::new(ExprKind::Variable { path }, no_span())),
+                args: args.iter().map(convert_expr).collect(),
+                safety: SafetyLevel::Safe,
+            }
+        }
+        Expression::MethodCall { receiver, method, args } => ExprKind::MethodCall {
+            receiver: Box::new(convert_expr(receiver)),
+            method: method.clone(),
+            args: args.iter().map(convert_expr).collect(),
+        },
+        Expression::StructLiteral { name, fields } => ExprKind::Struct {
+            path: vec![name.clone()],
+            fields: fields
+                .iter()
+                .map(|(field, value)| {
+                    FieldInit { name: field.clone(), value: Some(convert_expr(value)), span: no_span() }
+                })
+                .collect(),
+            base: None,
+        },
+        Expression::Field { object, field } => {
+            ExprKind::FieldAccess { object: Box::new(convert_expr(object)), field: field.clone() }
+        }
+    };
+    tl::Expr::new(kind, no_span())
+}
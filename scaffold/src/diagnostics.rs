@@ -1,3 +1,5 @@
+//! scaffold/src/diagnostics.rs - Errors of a scaffold run, rendered by miette
+
 use errors::TlError;
 
 pub struct ScaffoldDiagnostics {
@@ -16,10 +18,31 @@ impl ScaffoldDiagnostics {
     pub fn has_errors(&self) -> bool {
         !self.errors.is_empty()
     }
+
+    /// Each error as miette renders it, with the source it points into.
+    pub fn render(&self) -> Vec<String> {
+        let handler = miette::GraphicalReportHandler::new();
+        self.errors
+            .iter()
+            .map(|error| {
+                let mut out = String::new();
+                // Writing into a `String` cannot fail.
+                let _ = handler.render_report(&mut out, error);
+                out
+            })
+            .collect()
+    }
+
+    /// Write every error to stderr.
+    pub fn emit(&self) {
+        for report in self.render() {
+            eprint!("{}", report);
+        }
+    }
 }
 
 impl Default for ScaffoldDiagnostics {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,6 @@
+//! scaffold/src/dump.rs - Stable text dumps of the AST
+//!
+//! The formats are `shared::dump`'s, the same `tlang compile --emit ast`
+//! prints.
+
+pub use shared::dump::{dump, DumpError, DumpFormat};
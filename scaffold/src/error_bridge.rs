@@ -1,38 +1,51 @@
+//! scaffold/src/error_bridge.rs - Scaffold errors as workspace `TlError`s
+//!
+//! Every failure of a scaffold command ends up here, so the CLI reports them
+//! the way the rest of the workspace does. The scaffold parser and type
+//! checker do not track positions, so their errors point at the start of
+//! the source, or at its end for an unexpected end of file.
+
+use crate::codegen::CodegenError;
+use crate::compile::CompileError;
+use crate::dump::DumpError;
 use crate::parser::ParseError as ScaffoldParseError;
 use errors::TlError;
 
 pub fn convert_parse_error(err: ScaffoldParseError, source: &str) -> TlError {
     match err {
         ScaffoldParseError::UnexpectedToken(token) => {
-            TlError::parse_error(
-                source.to_string(),
-                errors::SourceSpan { start: errors::SourcePos(0), len: 1 },
-                format!("Unexpected token: {}", token)
-            )
+            TlError::parser(source, (0, 0), format!("Unexpected token: {}", token))
         }
         ScaffoldParseError::UnexpectedEof => {
-            TlError::parse_error(
-                source.to_string(),
-                errors::SourceSpan { start: errors::SourcePos(0), len: 1 },
-                "Unexpected end of file".to_string()
-            )
+            TlError::parser(source, (source.len(), 0), "Unexpected end of file")
         }
         ScaffoldParseError::InvalidSyntax(msg) => {
-            TlError::parse_error(
-                source.to_string(),
-                errors::SourceSpan { start: errors::SourcePos(0), len: 1 },
-                format!("Invalid syntax: {}", msg)
-            )
+            TlError::parser(source, (0, 0), format!("Invalid syntax: {}", msg))
         }
     }
 }
 
 pub fn convert_type_error(message: &str, source: &str) -> TlError {
-    TlError::type_error(
-        source.to_string(),
-        errors::SourceSpan { start: errors::SourcePos(0), len: 1 },
-        message.to_string()
-    )
+    TlError::type_error(source, (0, 0), message)
+}
+
+/// The checker accepted the program, so code it cannot generate is a bug.
+pub fn convert_codegen_error(err: CodegenError) -> TlError {
+    TlError::internal(err.to_string())
+}
+
+/// rustc rejecting the generated code is a bug; not finding rustc, failing
+/// to write its input, or a program that runs too long, is not.
+pub fn convert_compile_error(err: CompileError) -> TlError {
+    match err {
+        CompileError::WriteError(e) => TlError::io("Failed to write Rust file", Some(e)),
+        CompileError::RustcNotFound | CompileError::TimedOut(_) => TlError::io(err.to_string(), None),
+        CompileError::CompileError(_) => TlError::internal(err.to_string()),
+    }
+}
+
+pub fn convert_dump_error(err: DumpError) -> TlError {
+    TlError::internal(err.to_string())
 }
 
-pub type ScaffoldResult<T> = std::result::Result<T, TlError>;
\ No newline at end of file
+pub type ScaffoldResult<T> = std::result::Result<T, TlError>;
@@ -9,6 +9,9 @@ pub mod parser;
 pub mod typechecker;
 pub mod codegen;
 pub mod compile;
+pub mod dump;
+pub mod commands;
+pub mod convert;
 
 // NEW: Phase 2 Week 1 Day 1 additions
 pub mod error_bridge;
@@ -19,8 +22,11 @@ pub use ast::*;
 pub use parser::{Parser, ParseError};
 pub use typechecker::{TypeChecker, TypeError};
 pub use codegen::{CodeGenerator, CodegenError};
-pub use compile::{Compiler, CompileError};
+pub use compile::{Compiler, CompileError, CompileOptions};
+pub use dump::{dump, DumpError, DumpFormat};
+pub use convert::to_shared;
 
 // Re-export new error system types
 pub use error_bridge::{ScaffoldResult, convert_parse_error, convert_type_error};
+pub use commands::{command_ast, command_check, command_compile, command_run};
 pub use diagnostics::ScaffoldDiagnostics;
\ No newline at end of file
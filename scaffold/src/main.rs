@@ -4,28 +4,27 @@
 //! - tlang compile <file>     : Compile T-Lang source to executable
 //! - tlang run <file>         : Compile and run T-Lang source
 //! - tlang check <file>       : Parse and type-check only
-//! - tlang ast <file>         : Show parsed AST
+//! - tlang ast <file> [--format json|sexpr|debug] : Show parsed AST
 //! - tlang --help             : Show help
 //! - tlang --version          : Show version
-
-mod ast;
-mod parser;
-mod typechecker;
-mod codegen;
-mod compile;
-
-// NEW: Phase 2 Week 1 Day 1 additions
-mod error_bridge;
-mod diagnostics;
+//!
+//! A failed command is reported as a miette diagnostic and exits with 1;
+//! `run` exits with the program's exit code.
 
 use std::env;
-use std::fs;
+use std::path::PathBuf;
 use std::process;
-use std::path::Path;
+use std::time::Duration;
 
-// NEW: Import error bridge types (for Day 2 use)
+use scaffold::commands::{command_ast, command_check, command_compile, command_run, VERSION};
+use scaffold::compile::CompileOptions;
+use scaffold::diagnostics::ScaffoldDiagnostics;
+use scaffold::dump::DumpFormat;
+use scaffold::error_bridge::ScaffoldResult;
 
-const VERSION: &str = "0.1.0-scaffold";
+/// Options `compile` and `run` take
+const COMPILE_OPTIONS: &str =
+    "[--out-dir <dir>] [--release] [--target <triple>] [--rustc-flag <flag>]... [--no-cache] [--timeout <seconds>]";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -37,32 +36,39 @@ fn main() {
 
     match args[1].as_str() {
         "compile" => {
-            if args.len() != 3 {
-                eprintln!("Usage: {} compile <input.t>", args[0]);
+            if args.len() < 3 {
+                eprintln!("Usage: {} compile <input.t> {}", args[0], COMPILE_OPTIONS);
                 process::exit(1);
             }
-            command_compile(&args[2]);
+            let options = parse_compile_options(&args[3..]);
+            exit_on_error(command_compile(&args[2], &options));
         }
         "run" => {
-            if args.len() != 3 {
-                eprintln!("Usage: {} run <input.t>", args[0]);
+            if args.len() < 3 {
+                eprintln!("Usage: {} run <input.t> {}", args[0], COMPILE_OPTIONS);
                 process::exit(1);
             }
-            command_run(&args[2]);
+            let options = parse_compile_options(&args[3..]);
+            process::exit(exit_on_error(command_run(&args[2], &options)));
         }
         "check" => {
             if args.len() != 3 {
                 eprintln!("Usage: {} check <input.t>", args[0]);
                 process::exit(1);
             }
-            command_check(&args[2]);
+            exit_on_error(command_check(&args[2]));
         }
         "ast" => {
-            if args.len() != 3 {
-                eprintln!("Usage: {} ast <input.t>", args[0]);
-                process::exit(1);
-            }
-            command_ast(&args[2]);
+            let (filename, format) = match args.len() {
+                3 => (&args[2], DumpFormat::Debug),
+                5 if args[3] == "--format" => (&args[2], parse_format(&args[4])),
+                5 if args[2] == "--format" => (&args[4], parse_format(&args[3])),
+                _ => {
+                    eprintln!("Usage: {} ast <input.t> [--format json|sexpr|debug]", args[0]);
+                    process::exit(1);
+                }
+            };
+            exit_on_error(command_ast(filename, format));
         }
         "--help" | "-h" | "help" => {
             print_help(&args[0]);
@@ -89,162 +95,83 @@ fn print_help(program_name: &str) {
     println!("    compile <file>    Compile T-Lang source to executable");
     println!("    run <file>        Compile and run T-Lang source");
     println!("    check <file>      Parse and type-check only");
-    println!("    ast <file>        Show parsed AST");
+    println!("    ast <file>        Show parsed AST (--format json|sexpr|debug)");
     println!("    help              Show this help message");
     println!("    version           Show version information");
     println!();
+    println!("COMPILE AND RUN OPTIONS:");
+    println!("    --out-dir <dir>       Write the executable to <dir> (default target/scaffold_temp)");
+    println!("    --release             Build with optimizations");
+    println!("    --target <triple>     Build for another target");
+    println!("    --rustc-flag <flag>   Pass <flag> to rustc; may be repeated");
+    println!("    --no-cache            Rebuild even if the code has not changed");
+    println!("    --timeout <seconds>   Kill a program run for longer (run only)");
+    println!();
     println!("EXAMPLES:");
     println!("    {} compile hello.t", program_name);
     println!("    {} run hello.t", program_name);
+    println!("    {} compile hello.t --release --out-dir build", program_name);
     println!("    {} check hello.t", program_name);
     println!();
 }
 
-/// Compile T-Lang source to executable
-fn command_compile(filename: &str) {
-    println!("🔨 T-Lang Compiler {} - Compiling {}", VERSION, filename);
-
-    let (source, program) = parse_and_check(filename);
-
-    println!("🔍 Generating code...");
-    let rust_code = generate_code(&program);
-
-    println!("🔍 Compiling to executable...");
-    let base_name = get_base_name(filename);
-    let compiler = compile::Compiler::new();
-    match compiler.compile_rust_code(&rust_code, &base_name) {
-        Ok(executable_path) => {
-            println!("✅ Compilation successful!");
-            println!("📦 Created: {}", executable_path);
-        }
-        Err(e) => {
-            eprintln!("❌ Compilation failed: {}", e);
-            process::exit(1);
-        }
-    }
-}
-
-/// Compile and run T-Lang source
-fn command_run(filename: &str) {
-    println!("🚀 T-Lang Compiler {} - Running {}", VERSION, filename);
-
-    let (source, program) = parse_and_check(filename);
-
-    println!("🔍 Generating code...");
-    let rust_code = generate_code(&program);
-
-    println!("🔍 Compiling and running...");
-    let base_name = get_base_name(filename);
-    let compiler = compile::Compiler::new();
-    match compiler.compile_and_run(&rust_code, &base_name) {
-        Ok((executable_path, output, exit_code)) => {
-            println!("✅ Execution completed!");
-            println!("📊 Exit code: {}", exit_code);
-        }
-        Err(e) => {
-            eprintln!("❌ Execution failed: {}", e);
-            process::exit(1);
-        }
-    }
-}
-
-/// Check syntax and types only
-fn command_check(filename: &str) {
-    println!("🔍 T-Lang Compiler {} - Checking {}", VERSION, filename);
-
-    let (_source, _program) = parse_and_check(filename);
-
-    println!("✅ Check passed! No errors found.");
-}
-
-/// Show parsed AST
-fn command_ast(filename: &str) {
-    println!("🔍 T-Lang Compiler {} - AST for {}", VERSION, filename);
-
-    let source = read_source_file(filename);
-    let program = parse_source(&source, filename);
-
-    println!("\n📋 Abstract Syntax Tree:");
-    println!("{:#?}", program);
-}
-
-/// Helper: Read and validate source file
-fn read_source_file(filename: &str) -> String {
-    if !Path::new(filename).exists() {
-        eprintln!("❌ File not found: {}", filename);
-        process::exit(1);
-    }
-
-    match fs::read_to_string(filename) {
-        Ok(content) => {
-            if content.trim().is_empty() {
-                eprintln!("❌ File is empty: {}", filename);
+/// Helper: Parse the options after `compile <file>` or `run <file>`
+fn parse_compile_options(args: &[String]) -> CompileOptions {
+    let mut options = CompileOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| match args.next() {
+            Some(value) => value.clone(),
+            None => {
+                eprintln!("❌ {} requires a value", name);
+                process::exit(1);
+            }
+        };
+        match arg.as_str() {
+            "--out-dir" => options.output_dir = PathBuf::from(value("--out-dir")),
+            "--release" => options.release = true,
+            "--target" => options.target = Some(value("--target")),
+            "--rustc-flag" => options.rustc_flags.push(value("--rustc-flag")),
+            "--no-cache" => options.cache = false,
+            "--timeout" => {
+                let seconds = value("--timeout");
+                match seconds.parse::<f64>().ok().and_then(|s| Duration::try_from_secs_f64(s).ok()) {
+                    Some(timeout) => options.timeout = Some(timeout),
+                    None => {
+                        eprintln!("❌ --timeout takes a number of seconds, not '{}'", seconds);
+                        process::exit(1);
+                    }
+                }
+            }
+            unknown => {
+                eprintln!("❌ Unknown option: {} (expected {})", unknown, COMPILE_OPTIONS);
                 process::exit(1);
             }
-            content
-        }
-        Err(e) => {
-            eprintln!("❌ Error reading file '{}': {}", filename, e);
-            process::exit(1);
         }
     }
+    options
 }
 
-/// Helper: Parse source into AST
-fn parse_source(source: &str, filename: &str) -> ast::Program {
-    let mut parser = parser::Parser::new(source);
-    match parser.parse() {
-        Ok(program) => program,
+/// Helper: Parse the value of `--format`
+fn parse_format(name: &str) -> DumpFormat {
+    match name.parse() {
+        Ok(format) => format,
         Err(e) => {
-            eprintln!("❌ Parse error in '{}': {}", filename, e);
+            eprintln!("❌ {}", e);
             process::exit(1);
         }
     }
 }
 
-/// Helper: Type check AST
-fn type_check_program(program: &ast::Program, filename: &str) {
-    let typechecker = typechecker::TypeChecker::new();
-    if let Err(e) = typechecker.check_program(program) {
-        eprintln!("❌ Type error in '{}': {}", filename, e);
-        process::exit(1);
-    }
-}
-
-/// Helper: Generate code from AST
-fn generate_code(program: &ast::Program) -> String {
-    let mut codegen = codegen::CodeGenerator::new();
-    match codegen.generate_program(program) {
-        Ok(code) => code,
-        Err(e) => {
-            eprintln!("❌ Code generation error: {}", e);
+/// Helper: Render a command's error, then exit with 1
+fn exit_on_error<T>(result: ScaffoldResult<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => {
+            let mut diagnostics = ScaffoldDiagnostics::new();
+            diagnostics.add_error(error);
+            diagnostics.emit();
             process::exit(1);
         }
     }
 }
-
-/// Helper: Parse and type-check (common pattern)
-fn parse_and_check(filename: &str) -> (String, ast::Program) {
-    let source = read_source_file(filename);
-
-    println!("🔍 Parsing...");
-    let program = parse_source(&source, filename);
-
-    println!("✅ Parsing successful!");
-
-    println!("🔍 Type checking...");
-    type_check_program(&program, filename);
-
-    println!("✅ Type checking passed!");
-
-    (source, program)
-}
-
-/// Helper: Extract base name from filename
-fn get_base_name(filename: &str) -> String {
-    Path::new(filename)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("output")
-        .to_string()
-}
\ No newline at end of file
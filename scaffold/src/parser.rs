@@ -1,152 +1,372 @@
-//! scaffold/src/parser.rs - Hardcoded parser for `fn main() -> i32 { return 42; }`
-//!
-//! This is intentionally hardcoded and brittle - it only needs to parse ONE program.
-
-use crate::ast::*;
-
-#[derive(Debug)]
-pub enum ParseError {
-    UnexpectedToken(String),
-    UnexpectedEof,
-    InvalidSyntax(String),
-}
-
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ParseError::UnexpectedToken(token) => write!(f, "Unexpected token: {token}"),
-            ParseError::UnexpectedEof => write!(f, "Unexpected end of file"),
-            ParseError::InvalidSyntax(msg) => write!(f, "Invalid syntax: {msg}"),
-        }
-    }
-}
-
-impl std::error::Error for ParseError {}
-
-pub struct Parser {
-    tokens: Vec<String>,
-    pos: usize,
-}
-
-impl Parser {
-    pub fn new(input: &str) -> Self {
-        // Strip BOM if present
-        let input = input.strip_prefix('\u{FEFF}').unwrap_or(input);
-
-        // Extremely basic tokenization - just split on whitespace and common delimiters
-        let mut tokens = Vec::new();
-        let mut current_token = String::new();
-
-        for ch in input.chars() {
-            match ch {
-                ' ' | '\t' | '\n' | '\r' => {
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
-                    }
-                }
-                '(' | ')' | '{' | '}' | ';' => {
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
-                    }
-                    tokens.push(ch.to_string());
-                }
-                '-' => {
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
-                    }
-                    // Look ahead for ">"
-                    tokens.push(ch.to_string());
-                }
-                '>' => {
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
-                    }
-                    tokens.push(ch.to_string());
-                }
-                _ => current_token.push(ch),
-            }
-        }
-
-        if !current_token.is_empty() {
-            tokens.push(current_token);
-        }
-
-        Self { tokens, pos: 0 }
-    }
-
-    pub fn parse(&mut self) -> Result<Program, ParseError> {
-        let mut program = Program::new();
-
-        // Parse exactly: fn main() -> i32 { return 42; }
-        self.expect_token("fn")?;
-        let name = self.expect_identifier()?;
-        self.expect_token("(")?;
-        self.expect_token(")")?;
-        self.expect_token("-")?;
-        self.expect_token(">")?;
-        let return_type = self.expect_identifier()?;
-        self.expect_token("{")?;
-        self.expect_token("return")?;
-        let value = self.expect_number()?;
-        self.expect_token(";")?;
-        self.expect_token("}")?;
-
-        // Build the AST
-        let literal = Literal::Integer(value);
-        let expr = Expression::Literal(literal);
-        let stmt = Statement::Return(expr);
-        let block = Block {
-            statements: vec![stmt],
-        };
-
-        let mut function = Function::new(name);
-        function.return_type = Some(Type { name: return_type });
-        function.body = block;
-
-        program.functions.push(function);
-
-        Ok(program)
-    }
-
-    fn current_token(&self) -> Option<&String> {
-        self.tokens.get(self.pos)
-    }
-
-    fn advance(&mut self) -> Option<String> {
-        let token = self.current_token().cloned();
-        self.pos += 1;
-        token
-    }
-
-    fn expect_token(&mut self, expected: &str) -> Result<(), ParseError> {
-        match self.advance() {
-            Some(token) if token == expected => Ok(()),
-            Some(token) => Err(ParseError::UnexpectedToken(token)),
-            None => Err(ParseError::UnexpectedEof),
-        }
-    }
-
-    fn expect_identifier(&mut self) -> Result<String, ParseError> {
-        match self.advance() {
-            Some(token) if token.chars().all(|c| c.is_alphanumeric() || c == '_') => {
-                Ok(token)
-            }
-            Some(token) => Err(ParseError::UnexpectedToken(token)),
-            None => Err(ParseError::UnexpectedEof),
-        }
-    }
-
-    fn expect_number(&mut self) -> Result<i64, ParseError> {
-        match self.advance() {
-            Some(token) => {
-                token.parse::<i64>()
-                    .map_err(|_| ParseError::InvalidSyntax(format!("Expected number, got {token}")))
-            }
-            None => Err(ParseError::UnexpectedEof),
-        }
-    }
+//! scaffold/src/parser.rs - Recursive-descent parser for the scaffold subset
+//!
+//! A program is a sequence of `fn`, `struct` and `impl` items. Bodies hold
+//! `let` and `return` statements over integer arithmetic, calls, method
+//! calls, struct literals and field access. Still deliberately small.
+
+use crate::ast::*;
+
+#[derive(Debug)]
+pub enum ParseError {
+    UnexpectedToken(String),
+    UnexpectedEof,
+    InvalidSyntax(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedToken(token) => write!(f, "Unexpected token: {token}"),
+            ParseError::UnexpectedEof => write!(f, "Unexpected end of file"),
+            ParseError::InvalidSyntax(msg) => write!(f, "Invalid syntax: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Self {
+        // Strip BOM if present
+        let input = input.strip_prefix('\u{FEFF}').unwrap_or(input);
+
+        // Extremely basic tokenization - just split on whitespace and common delimiters
+        let mut tokens = Vec::new();
+        let mut current_token = String::new();
+
+        for ch in input.chars() {
+            match ch {
+                ' ' | '\t' | '\n' | '\r' => {
+                    if !current_token.is_empty() {
+                        tokens.push(current_token.clone());
+                        current_token.clear();
+                    }
+                }
+                '(' | ')' | '{' | '}' | ';' | ',' | ':' | '.' | '+' | '*' | '=' => {
+                    if !current_token.is_empty() {
+                        tokens.push(current_token.clone());
+                        current_token.clear();
+                    }
+                    tokens.push(ch.to_string());
+                }
+                '-' => {
+                    if !current_token.is_empty() {
+                        tokens.push(current_token.clone());
+                        current_token.clear();
+                    }
+                    // Look ahead for ">"
+                    tokens.push(ch.to_string());
+                }
+                '>' => {
+                    if !current_token.is_empty() {
+                        tokens.push(current_token.clone());
+                        current_token.clear();
+                    }
+                    tokens.push(ch.to_string());
+                }
+                _ => current_token.push(ch),
+            }
+        }
+
+        if !current_token.is_empty() {
+            tokens.push(current_token);
+        }
+
+        Self { tokens, pos: 0 }
+    }
+
+    pub fn parse(&mut self) -> Result<Program, ParseError> {
+        let mut program = Program::new();
+
+        while let Some(token) = self.current_token() {
+            match token.as_str() {
+                "fn" => {
+                    let function = self.parse_function(false)?;
+                    program.functions.push(function);
+                }
+                "struct" => {
+                    let item = self.parse_struct()?;
+                    program.structs.push(item);
+                }
+                "impl" => {
+                    let item = self.parse_impl()?;
+                    program.impls.push(item);
+                }
+                _ => return Err(ParseError::UnexpectedToken(token.clone())),
+            }
+        }
+
+        Ok(program)
+    }
+
+    /// fn name(params) -> Type { ... }
+    fn parse_function(&mut self, in_impl: bool) -> Result<Function, ParseError> {
+        self.expect_token("fn")?;
+        let name = self.expect_identifier()?;
+        let mut function = Function::new(name);
+
+        self.expect_token("(")?;
+        if in_impl && self.check("self") {
+            self.advance();
+            function.has_self = true;
+            if !self.check(")") {
+                self.expect_token(",")?;
+            }
+        }
+        while !self.check(")") {
+            let name = self.expect_identifier()?;
+            if name == "self" {
+                return Err(ParseError::InvalidSyntax(
+                    "`self` must be the first parameter of a method".to_string(),
+                ));
+            }
+            self.expect_token(":")?;
+            let param_type = self.parse_type()?;
+            function.params.push(Parameter { name, param_type });
+            if !self.check(")") {
+                self.expect_token(",")?;
+            }
+        }
+        self.expect_token(")")?;
+
+        if self.check("-") {
+            self.expect_token("-")?;
+            self.expect_token(">")?;
+            function.return_type = Some(self.parse_type()?);
+        }
+
+        function.body = self.parse_block()?;
+        Ok(function)
+    }
+
+    /// struct Name { field: Type, ... }
+    fn parse_struct(&mut self) -> Result<Struct, ParseError> {
+        self.expect_token("struct")?;
+        let name = self.expect_identifier()?;
+        let mut fields = Vec::new();
+
+        self.expect_token("{")?;
+        while !self.check("}") {
+            let name = self.expect_identifier()?;
+            self.expect_token(":")?;
+            let param_type = self.parse_type()?;
+            fields.push(Parameter { name, param_type });
+            if !self.check("}") {
+                self.expect_token(",")?;
+            }
+        }
+        self.expect_token("}")?;
+
+        Ok(Struct { name, fields })
+    }
+
+    /// impl TypeName { fn ... }
+    fn parse_impl(&mut self) -> Result<Impl, ParseError> {
+        self.expect_token("impl")?;
+        let type_name = self.expect_identifier()?;
+        let mut methods = Vec::new();
+
+        self.expect_token("{")?;
+        while !self.check("}") {
+            methods.push(self.parse_function(true)?);
+        }
+        self.expect_token("}")?;
+
+        Ok(Impl { type_name, methods })
+    }
+
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        let name = self.expect_identifier()?;
+        Ok(Type { name })
+    }
+
+    fn parse_block(&mut self) -> Result<Block, ParseError> {
+        let mut statements = Vec::new();
+
+        self.expect_token("{")?;
+        while !self.check("}") {
+            statements.push(self.parse_statement()?);
+        }
+        self.expect_token("}")?;
+
+        Ok(Block { statements })
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        let statement = match self.current_token().map(String::as_str) {
+            Some("return") => {
+                self.advance();
+                Statement::Return(self.parse_expression()?)
+            }
+            Some("let") => {
+                self.advance();
+                let name = self.expect_identifier()?;
+                self.expect_token("=")?;
+                let value = self.parse_expression()?;
+                Statement::Let { name, value }
+            }
+            Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            None => return Err(ParseError::UnexpectedEof),
+        };
+        self.expect_token(";")?;
+        Ok(statement)
+    }
+
+    /// Sums, with `*` binding tighter than `+` and `-`.
+    fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_term()?;
+        loop {
+            let op = match self.current_token().map(String::as_str) {
+                Some("+") => BinaryOp::Add,
+                Some("-") => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_term()?;
+            left = Expression::Binary { op, left: Box::new(left), right: Box::new(right) };
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_postfix()?;
+        while self.check("*") {
+            self.advance();
+            let right = self.parse_postfix()?;
+            left = Expression::Binary { op: BinaryOp::Mul, left: Box::new(left), right: Box::new(right) };
+        }
+        Ok(left)
+    }
+
+    /// A primary expression followed by `.field` and `.method(args)`.
+    fn parse_postfix(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.parse_primary()?;
+        while self.check(".") {
+            self.advance();
+            let name = self.expect_identifier()?;
+            expr = if self.check("(") {
+                let args = self.parse_arguments()?;
+                Expression::MethodCall { receiver: Box::new(expr), method: name, args }
+            } else {
+                Expression::Field { object: Box::new(expr), field: name }
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+        match self.current_token().map(String::as_str) {
+            Some("-") => {
+                self.advance();
+                let value = self.expect_number()?;
+                Ok(Expression::Literal(Literal::Integer(-value)))
+            }
+            Some("(") => {
+                self.advance();
+                let expr = self.parse_expression()?;
+                self.expect_token(")")?;
+                Ok(expr)
+            }
+            Some(token) if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                let value = self.expect_number()?;
+                Ok(Expression::Literal(Literal::Integer(value)))
+            }
+            Some(_) => {
+                let mut name = self.expect_identifier()?;
+                if self.check(":") {
+                    // Type::function
+                    self.expect_token(":")?;
+                    self.expect_token(":")?;
+                    name = format!("{}::{}", name, self.expect_identifier()?);
+                    let args = self.parse_arguments()?;
+                    return Ok(Expression::Call { function: name, args });
+                }
+                if self.check("(") {
+                    let args = self.parse_arguments()?;
+                    Ok(Expression::Call { function: name, args })
+                } else if self.check("{") {
+                    self.parse_struct_literal(name)
+                } else {
+                    Ok(Expression::Variable(name))
+                }
+            }
+            None => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    /// (expr, ...)
+    fn parse_arguments(&mut self) -> Result<Vec<Expression>, ParseError> {
+        let mut args = Vec::new();
+        self.expect_token("(")?;
+        while !self.check(")") {
+            args.push(self.parse_expression()?);
+            if !self.check(")") {
+                self.expect_token(",")?;
+            }
+        }
+        self.expect_token(")")?;
+        Ok(args)
+    }
+
+    /// Name { field: expr, ... }
+    fn parse_struct_literal(&mut self, name: String) -> Result<Expression, ParseError> {
+        let mut fields = Vec::new();
+        self.expect_token("{")?;
+        while !self.check("}") {
+            let field = self.expect_identifier()?;
+            self.expect_token(":")?;
+            fields.push((field, self.parse_expression()?));
+            if !self.check("}") {
+                self.expect_token(",")?;
+            }
+        }
+        self.expect_token("}")?;
+        Ok(Expression::StructLiteral { name, fields })
+    }
+
+    fn check(&self, expected: &str) -> bool {
+        self.current_token().is_some_and(|token| token == expected)
+    }
+
+    fn current_token(&self) -> Option<&String> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<String> {
+        let token = self.current_token().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect_token(&mut self, expected: &str) -> Result<(), ParseError> {
+        match self.advance() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ParseError::UnexpectedToken(token)),
+            None => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        match self.advance() {
+            Some(token) if token.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+                Ok(token)
+            }
+            Some(token) => Err(ParseError::UnexpectedToken(token)),
+            None => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    fn expect_number(&mut self) -> Result<i64, ParseError> {
+        match self.advance() {
+            Some(token) => {
+                token.parse::<i64>()
+                    .map_err(|_| ParseError::InvalidSyntax(format!("Expected number, got {token}")))
+            }
+            None => Err(ParseError::UnexpectedEof),
+        }
+    }
 }
\ No newline at end of file
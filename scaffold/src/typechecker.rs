@@ -1,117 +1,396 @@
-//! scaffold/src/typechecker.rs - Trivial type checker for `fn main() -> i32 { return 42; }`
-//!
-//! This is intentionally hardcoded - it only validates the exact program structure we support.
-
-use crate::ast::*;
-
-#[derive(Debug)]
-pub enum TypeError {
-    WrongFunctionName(String),
-    WrongReturnType(String),
-    WrongStatementType,
-    WrongExpressionType,
-    WrongLiteralType,
-}
-
-impl std::fmt::Display for TypeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TypeError::WrongFunctionName(name) => {
-                write!(f, "Expected function 'main', found '{name}'")
-            }
-            TypeError::WrongReturnType(type_name) => {
-                write!(f, "Expected return type 'i32', found '{type_name}'")
-            }
-            TypeError::WrongStatementType => {
-                write!(f, "Expected return statement")
-            }
-            TypeError::WrongExpressionType => {
-                write!(f, "Expected literal expression")
-            }
-            TypeError::WrongLiteralType => {
-                write!(f, "Expected integer literal")
-            }
-        }
-    }
-}
-
-impl std::error::Error for TypeError {}
-
-pub struct TypeChecker;
-
-impl TypeChecker {
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// Type check a program - validates exactly our expected structure
-    pub fn check_program(&self, program: &Program) -> Result<(), TypeError> {
-        // Must have exactly one function
-        if program.functions.len() != 1 {
-            return Err(TypeError::WrongFunctionName("expected exactly one function".to_string()));
-        }
-
-        let function = &program.functions[0];
-        self.check_function(function)
-    }
-
-    /// Type check a function - must be main() -> i32
-    fn check_function(&self, function: &Function) -> Result<(), TypeError> {
-        // Must be named "main"
-        if function.name != "main" {
-            return Err(TypeError::WrongFunctionName(function.name.clone()));
-        }
-
-        // Must have no parameters (already guaranteed by parser structure)
-        if !function.params.is_empty() {
-            return Err(TypeError::WrongFunctionName("main function cannot have parameters".to_string()));
-        }
-
-        // Must return i32
-        match &function.return_type {
-            Some(return_type) => {
-                if return_type.name != "i32" {
-                    return Err(TypeError::WrongReturnType(return_type.name.clone()));
-                }
-            }
-            None => {
-                return Err(TypeError::WrongReturnType("missing return type".to_string()));
-            }
-        }
-
-        // Check the function body
-        self.check_block(&function.body)
-    }
-
-    /// Type check a block - must contain exactly one return statement
-    fn check_block(&self, block: &Block) -> Result<(), TypeError> {
-        // Must have exactly one statement
-        if block.statements.len() != 1 {
-            return Err(TypeError::WrongStatementType);
-        }
-
-        let statement = &block.statements[0];
-        self.check_statement(statement)
-    }
-
-    /// Type check a statement - must be return with integer literal
-    fn check_statement(&self, statement: &Statement) -> Result<(), TypeError> {
-        match statement {
-            Statement::Return(expr) => self.check_expression_is_i32(expr),
-        }
-    }
-
-    /// Type check an expression - must be integer literal
-    fn check_expression_is_i32(&self, expr: &Expression) -> Result<(), TypeError> {
-        match expr {
-            Expression::Literal(literal) => self.check_literal_is_i32(literal),
-        }
-    }
-
-    /// Type check a literal - must be integer
-    fn check_literal_is_i32(&self, literal: &Literal) -> Result<(), TypeError> {
-        match literal {
-            Literal::Integer(_) => Ok(()), // Any integer is valid i32 for now
-        }
-    }
-}
\ No newline at end of file
+//! scaffold/src/typechecker.rs - Type checker for the scaffold subset
+//!
+//! Types are names: `i32`, `i64` or a declared struct. Integer literals take
+//! whichever integer type they meet. Methods see `self` as a value of their
+//! `impl` type, and `main` returns nothing or its exit code, an `i32`.
+
+use crate::ast::*;
+use std::collections::HashMap;
+
+/// Type of an integer literal before it meets a concrete integer type.
+const INTEGER: &str = "{integer}";
+
+/// Type of a function without a return type.
+const UNIT: &str = "()";
+
+#[derive(Debug)]
+pub enum TypeError {
+    WrongFunctionName(String),
+    WrongReturnType(String),
+    WrongStatementType,
+    WrongExpressionType,
+    WrongLiteralType,
+    MissingMain,
+    Duplicate(String),
+    UnknownType(String),
+    UnknownVariable(String),
+    SelfOutsideMethod,
+    UnknownFunction(String),
+    UnknownMethod { type_name: String, method: String },
+    NotAMethod { type_name: String, method: String },
+    UnknownField { type_name: String, field: String },
+    MissingField { type_name: String, field: String },
+    ArgumentCount { name: String, expected: usize, found: usize },
+    Mismatch { expected: String, found: String },
+}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeError::WrongFunctionName(name) => {
+                write!(f, "Expected function 'main', found '{name}'")
+            }
+            TypeError::WrongReturnType(type_name) => {
+                write!(f, "Expected return type '()' or 'i32', found '{type_name}'")
+            }
+            TypeError::WrongStatementType => {
+                write!(f, "Expected return statement")
+            }
+            TypeError::WrongExpressionType => {
+                write!(f, "Expected literal expression")
+            }
+            TypeError::WrongLiteralType => {
+                write!(f, "Expected integer literal")
+            }
+            TypeError::MissingMain => {
+                write!(f, "Program has no 'main' function")
+            }
+            TypeError::Duplicate(name) => {
+                write!(f, "'{name}' is defined more than once")
+            }
+            TypeError::UnknownType(name) => {
+                write!(f, "Unknown type '{name}'")
+            }
+            TypeError::UnknownVariable(name) => {
+                write!(f, "Unknown variable '{name}'")
+            }
+            TypeError::SelfOutsideMethod => {
+                write!(f, "'self' is only available in methods that take 'self'")
+            }
+            TypeError::UnknownFunction(name) => {
+                write!(f, "Unknown function '{name}'")
+            }
+            TypeError::UnknownMethod { type_name, method } => {
+                write!(f, "Type '{type_name}' has no method '{method}'")
+            }
+            TypeError::NotAMethod { type_name, method } => {
+                write!(f, "'{type_name}::{method}' does not take 'self'; call it as '{type_name}::{method}(...)'")
+            }
+            TypeError::UnknownField { type_name, field } => {
+                write!(f, "Type '{type_name}' has no field '{field}'")
+            }
+            TypeError::MissingField { type_name, field } => {
+                write!(f, "Missing field '{field}' in '{type_name}' literal")
+            }
+            TypeError::ArgumentCount { name, expected, found } => {
+                write!(f, "'{name}' takes {expected} argument(s), found {found}")
+            }
+            TypeError::Mismatch { expected, found } => {
+                write!(f, "Expected type '{expected}', found '{found}'")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+pub struct TypeChecker;
+
+/// Declarations visible to every function body.
+struct Declarations<'a> {
+    structs: HashMap<&'a str, &'a Struct>,
+    functions: HashMap<&'a str, &'a Function>,
+    /// Methods and associated functions, keyed by type then name.
+    methods: HashMap<(&'a str, &'a str), &'a Function>,
+}
+
+impl<'a> Declarations<'a> {
+    fn collect(program: &'a Program) -> Result<Self, TypeError> {
+        let mut decls = Self {
+            structs: HashMap::new(),
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+        };
+        for item in &program.structs {
+            if decls.structs.insert(&item.name, item).is_some() {
+                return Err(TypeError::Duplicate(item.name.clone()));
+            }
+        }
+        for function in &program.functions {
+            if decls.functions.insert(&function.name, function).is_some() {
+                return Err(TypeError::Duplicate(function.name.clone()));
+            }
+        }
+        for item in &program.impls {
+            if !decls.structs.contains_key(item.type_name.as_str()) {
+                return Err(TypeError::UnknownType(item.type_name.clone()));
+            }
+            for method in &item.methods {
+                let key = (item.type_name.as_str(), method.name.as_str());
+                if decls.methods.insert(key, method).is_some() {
+                    return Err(TypeError::Duplicate(format!("{}::{}", item.type_name, method.name)));
+                }
+            }
+        }
+        Ok(decls)
+    }
+
+    fn check_type(&self, type_ref: &Type) -> Result<(), TypeError> {
+        match type_ref.name.as_str() {
+            "i32" | "i64" => Ok(()),
+            name if self.structs.contains_key(name) => Ok(()),
+            name => Err(TypeError::UnknownType(name.to_string())),
+        }
+    }
+}
+
+fn is_integer(ty: &str) -> bool {
+    matches!(ty, "i32" | "i64" | INTEGER)
+}
+
+/// The type a value of `found` has where `expected` is required.
+fn unify(expected: &str, found: &str) -> Result<String, TypeError> {
+    match (expected, found) {
+        _ if expected == found => Ok(expected.to_string()),
+        (INTEGER, ty) | (ty, INTEGER) if is_integer(ty) => Ok(ty.to_string()),
+        _ => Err(TypeError::Mismatch { expected: expected.to_string(), found: found.to_string() }),
+    }
+}
+
+/// Variables of the function being checked.
+type Locals = HashMap<String, String>;
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Type check a program: every function and method body, plus `main`'s signature
+    pub fn check_program(&self, program: &Program) -> Result<(), TypeError> {
+        let decls = Declarations::collect(program)?;
+
+        for item in &program.structs {
+            for field in &item.fields {
+                decls.check_type(&field.param_type)?;
+            }
+        }
+
+        let main = decls.functions.get("main").ok_or(TypeError::MissingMain)?;
+        self.check_main(main)?;
+
+        for function in &program.functions {
+            self.check_function(&decls, function, None)?;
+        }
+        for item in &program.impls {
+            for method in &item.methods {
+                self.check_function(&decls, method, Some(&item.type_name))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `main` must be main() -> i32
+    fn check_main(&self, function: &Function) -> Result<(), TypeError> {
+        if !function.params.is_empty() {
+            return Err(TypeError::WrongFunctionName("main function cannot have parameters".to_string()));
+        }
+
+        // `main` returns nothing, and exits with 0, or its exit code
+        match &function.return_type {
+            Some(return_type) if return_type.name == "i32" => Ok(()),
+            Some(return_type) => Err(TypeError::WrongReturnType(return_type.name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Type check a function; `self_type` is the `impl` type for methods
+    fn check_function(
+        &self,
+        decls: &Declarations,
+        function: &Function,
+        self_type: Option<&str>,
+    ) -> Result<(), TypeError> {
+        let mut locals = Locals::new();
+        if let (true, Some(self_type)) = (function.has_self, self_type) {
+            locals.insert("self".to_string(), self_type.to_string());
+        }
+        for param in &function.params {
+            decls.check_type(&param.param_type)?;
+            locals.insert(param.name.clone(), param.param_type.name.clone());
+        }
+
+        let return_type = match &function.return_type {
+            Some(return_type) => {
+                decls.check_type(return_type)?;
+                return_type.name.as_str()
+            }
+            None => UNIT,
+        };
+
+        // A function with a result must end by returning it
+        let ends_in_return = matches!(function.body.statements.last(), Some(Statement::Return(_)));
+        if return_type != UNIT && !ends_in_return {
+            return Err(TypeError::WrongStatementType);
+        }
+
+        self.check_block(decls, &function.body, &mut locals, return_type)
+    }
+
+    fn check_block(
+        &self,
+        decls: &Declarations,
+        block: &Block,
+        locals: &mut Locals,
+        return_type: &str,
+    ) -> Result<(), TypeError> {
+        for statement in &block.statements {
+            self.check_statement(decls, statement, locals, return_type)?;
+        }
+        Ok(())
+    }
+
+    fn check_statement(
+        &self,
+        decls: &Declarations,
+        statement: &Statement,
+        locals: &mut Locals,
+        return_type: &str,
+    ) -> Result<(), TypeError> {
+        match statement {
+            Statement::Return(expr) => {
+                let found = self.check_expression(decls, expr, locals)?;
+                unify(return_type, &found)?;
+            }
+            Statement::Let { name, value } => {
+                let mut ty = self.check_expression(decls, value, locals)?;
+                if ty == INTEGER {
+                    ty = "i32".to_string();
+                }
+                locals.insert(name.clone(), ty);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check arguments against parameter types
+    fn check_arguments(
+        &self,
+        decls: &Declarations,
+        name: &str,
+        params: &[Parameter],
+        args: &[Expression],
+        locals: &Locals,
+    ) -> Result<(), TypeError> {
+        if params.len() != args.len() {
+            return Err(TypeError::ArgumentCount {
+                name: name.to_string(),
+                expected: params.len(),
+                found: args.len(),
+            });
+        }
+        for (param, arg) in params.iter().zip(args) {
+            let found = self.check_expression(decls, arg, locals)?;
+            unify(&param.param_type.name, &found)?;
+        }
+        Ok(())
+    }
+
+    fn result_type(function: &Function) -> String {
+        function.return_type.as_ref().map_or(UNIT.to_string(), |ty| ty.name.clone())
+    }
+
+    /// Type of an expression
+    fn check_expression(
+        &self,
+        decls: &Declarations,
+        expr: &Expression,
+        locals: &Locals,
+    ) -> Result<String, TypeError> {
+        match expr {
+            Expression::Literal(Literal::Integer(_)) => Ok(INTEGER.to_string()),
+            Expression::Variable(name) => match locals.get(name) {
+                Some(ty) => Ok(ty.clone()),
+                None if name == "self" => Err(TypeError::SelfOutsideMethod),
+                None => Err(TypeError::UnknownVariable(name.clone())),
+            },
+            Expression::Binary { left, right, .. } => {
+                let left = self.check_expression(decls, left, locals)?;
+                let right = self.check_expression(decls, right, locals)?;
+                if !is_integer(&left) {
+                    return Err(TypeError::Mismatch { expected: "i32".to_string(), found: left });
+                }
+                unify(&left, &right)
+            }
+            Expression::Call { function, args } => {
+                let callee = match function.split_once("::") {
+                    Some((type_name, name)) => {
+                        let callee = decls.methods.get(&(type_name, name)).ok_or_else(|| {
+                            TypeError::UnknownMethod { type_name: type_name.to_string(), method: name.to_string() }
+                        })?;
+                        if callee.has_self {
+                            // `Type::method(value, ...)` passes `self` explicitly
+                            let (receiver, rest) = args.split_first().ok_or_else(|| {
+                                TypeError::ArgumentCount {
+                                    name: function.clone(),
+                                    expected: callee.params.len() + 1,
+                                    found: 0,
+                                }
+                            })?;
+                            let found = self.check_expression(decls, receiver, locals)?;
+                            unify(type_name, &found)?;
+                            self.check_arguments(decls, function, &callee.params, rest, locals)?;
+                            return Ok(Self::result_type(callee));
+                        }
+                        callee
+                    }
+                    None => decls
+                        .functions
+                        .get(function.as_str())
+                        .ok_or_else(|| TypeError::UnknownFunction(function.clone()))?,
+                };
+                self.check_arguments(decls, function, &callee.params, args, locals)?;
+                Ok(Self::result_type(callee))
+            }
+            Expression::MethodCall { receiver, method, args } => {
+                let type_name = self.check_expression(decls, receiver, locals)?;
+                let callee = decls.methods.get(&(type_name.as_str(), method.as_str())).ok_or_else(|| {
+                    TypeError::UnknownMethod { type_name: type_name.clone(), method: method.clone() }
+                })?;
+                if !callee.has_self {
+                    return Err(TypeError::NotAMethod { type_name, method: method.clone() });
+                }
+                self.check_arguments(decls, method, &callee.params, args, locals)?;
+                Ok(Self::result_type(callee))
+            }
+            Expression::StructLiteral { name, fields } => {
+                let item = decls.structs.get(name.as_str()).ok_or_else(|| TypeError::UnknownType(name.clone()))?;
+                for (field, value) in fields {
+                    let declared = item.fields.iter().find(|f| &f.name == field).ok_or_else(|| {
+                        TypeError::UnknownField { type_name: name.clone(), field: field.clone() }
+                    })?;
+                    let found = self.check_expression(decls, value, locals)?;
+                    unify(&declared.param_type.name, &found)?;
+                }
+                if let Some(missing) = item.fields.iter().find(|f| !fields.iter().any(|(n, _)| n == &f.name)) {
+                    return Err(TypeError::MissingField { type_name: name.clone(), field: missing.name.clone() });
+                }
+                Ok(name.clone())
+            }
+            Expression::Field { object, field } => {
+                let type_name = self.check_expression(decls, object, locals)?;
+                decls
+                    .structs
+                    .get(type_name.as_str())
+                    .and_then(|item| item.fields.iter().find(|f| &f.name == field))
+                    .map(|f| f.param_type.name.clone())
+                    .ok_or(TypeError::UnknownField { type_name, field: field.clone() })
+            }
+        }
+    }
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,55 @@
+//! scaffold/tests/convert.rs
+//!
+//! Scaffold programs converted with `to_shared` must be the tree the main
+//! parser builds for the same program, so that the main compiler's passes
+//! take them as they are.
+
+use scaffold::{to_shared, Parser};
+use shared::ast::stmt::ImplItem;
+use shared::{ItemKind, PatternKind, TypeKind};
+
+fn convert(source: &str) -> shared::Program {
+    to_shared(&Parser::new(source).parse().unwrap())
+}
+
+#[test]
+fn converted_programs_match_the_main_parser_and_run() {
+    let scaffold = "struct Pair {\n    a: i32,\n    b: i32,\n}\n\nfn add(a: i32, b: i32) -> i32 {\n    \
+                    let sum = a + b;\n    return sum * 2;\n}\n\n\
+                    fn main() -> i32 {\n    let n = 20 + 1;\n    return n * 2;\n}";
+    let main = "struct Pair {\n    a: i32,\n    b: i32,\n}\n\nfn add(a: i32, b: i32) -> i32 {\n    \
+                let sum = a + b;\n    sum * 2\n}\n\nfn main() -> i32 {\n    let n = 20 + 1;\n    n * 2\n}";
+    let mut program = convert(scaffold);
+    assert!(compiler::diff_programs(&compiler::parse_source(main).unwrap(), &program).is_empty());
+
+    compiler::check_program(&mut program, main.to_string()).unwrap();
+    assert!(compiler::analyze_safety(&program, main.to_string()).unwrap().is_empty());
+    let module = compiler::lower_program(&program, main, "main.t").unwrap();
+    assert_eq!(plugin_api::interpret_with_exit_code(&module).unwrap(), (String::new(), 42));
+}
+
+#[test]
+fn methods_take_self_as_their_first_parameter() {
+    let program = convert(
+        "struct Point {\n    x: i32,\n}\n\nimpl Point {\n    fn get(self) -> i32 {\n        return self.x;\n    \
+         }\n}\n\n\
+         fn main() -> i32 {\n    let p = Point { x: 3 };\n    return p.get();\n}",
+    );
+    let kinds: Vec<&str> = program
+        .items
+        .iter()
+        .map(|item| match &item.kind {
+            ItemKind::Struct { .. } => "struct",
+            ItemKind::Impl { .. } => "impl",
+            ItemKind::Function { .. } => "fn",
+            _ => "other",
+        })
+        .collect();
+    assert_eq!(kinds, ["struct", "impl", "fn"]);
+
+    let ItemKind::Impl { items, .. } = &program.items[1].kind else { unreachable!() };
+    let ImplItem::Function { params, .. } = &items[0] else { unreachable!() };
+    assert_eq!(params.len(), 1);
+    assert!(matches!(&params[0].pattern.kind, PatternKind::Ident(name) if name == "self"));
+    assert!(matches!(&params[0].ty.kind, TypeKind::Named { path, .. } if path == &["Point"]));
+}
@@ -1,5 +0,0 @@
-// TEST PROGRAM 1: scaffold/tests/test1_hello.t
-    // Basic hello world - return 42
-fn main() -> i32 {
-    return 42;
-}
\ No newline at end of file
@@ -1,5 +0,0 @@
-// TEST PROGRAM 2: scaffold/tests/test2_zero.t
-    // Return zero (success exit code)
-fn main() -> i32 {
-    return 0;
-}
@@ -1,5 +0,0 @@
-// TEST PROGRAM 3: scaffold/tests/test3_negative.t
-    // Return negative number
-    fn main() -> i32 {
-    return -1;
-}
\ No newline at end of file
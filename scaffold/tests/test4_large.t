@@ -1,5 +0,0 @@
-// TEST PROGRAM 4: scaffold/tests/test4_large.t
-    // Return large number
-    fn main() -> i32 {
-    return 1000;
-}
\ No newline at end of file
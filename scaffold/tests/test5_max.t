@@ -1,5 +0,0 @@
-// TEST PROGRAM 5: scaffold/tests/test5_max.t
-    // Return max value for demonstration
-    fn main() -> i32 {
-    return 2147483647;
-}
\ No newline at end of file
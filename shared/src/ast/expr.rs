@@ -2,7 +2,7 @@
 //! Expression AST nodes for T-Lang.
 //! Comprehensive expression system supporting all programming paradigms.
 
-use super::types::{Type, SafetyLevel};
+use super::types::{Type, PrimitiveType, SafetyLevel};
 use miette::SourceSpan;
 use serde::{Deserialize, Serialize};
 
@@ -161,6 +161,12 @@ pub enum ExprKind {
         body: Box<Expr>,
     },
 
+    /// Thread spawn: spawn { ... }, running the body on a new thread; its
+    /// value is a `Thread<T>` handle that `join` waits on for the result
+    Spawn {
+        body: Box<Expr>,
+    },
+
     /// Await: expr.await
     Await {
         expr: Box<Expr>,
@@ -192,6 +198,15 @@ pub enum ExprKind {
     Dereference {
         expr: Box<Expr>,
     },
+
+    /// Macro invocation: name!(tokens); expanded away before name resolution
+    Macro {
+        path: Vec<String>,
+        args: Vec<super::stmt::MacroArg>,
+    },
+
+    /// Source that failed to parse; the parser reports why and carries on.
+    Error,
 }
 
 /// Literal values.
@@ -199,6 +214,9 @@ pub enum ExprKind {
 pub enum Literal {
     Integer(i128),
     Float(f64),
+    /// Suffixed number, `42u8` or `3.0f32`: its type is the suffix.
+    TypedInteger(i128, PrimitiveType),
+    TypedFloat(f64, PrimitiveType),
     String(String),
     Char(char),
     Bool(bool),
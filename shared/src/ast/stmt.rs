@@ -31,6 +31,12 @@ pub enum StmtKind {
         mutable: bool,
     },
 
+    /// Deferred block: defer { ... }, run when the enclosing block is
+    /// left, after everything else in it; several run last first
+    Defer {
+        body: Expr,
+    },
+
     /// Item definition (function, struct, enum, etc.)
     Item(Item),
 
@@ -151,6 +157,9 @@ pub enum ItemKind {
         name: String,
         rules: Vec<MacroRule>,
     },
+
+    /// Item that failed to parse; the parser reports why and carries on.
+    Error,
 }
 
 /// Struct field definitions.
@@ -288,13 +297,17 @@ pub enum AttributeArg {
 /// Macro-related types.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MacroArg {
-    pub tokens: Vec<String>, // Simplified for now
+    /// Source text of each token between the invocation's delimiters.
+    pub tokens: Vec<String>,
     pub span: SourceSpan,
 }
 
+/// One `(pattern) => { body }` rule of a `macro_rules!` definition.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MacroRule {
-    pub pattern: Vec<String>, // Simplified for now
+    /// Source text of each token between the pattern's delimiters.
+    pub pattern: Vec<String>,
+    /// Source text of each token between the body's delimiters.
     pub body: Vec<String>,
     pub span: SourceSpan,
 }
@@ -329,4 +342,34 @@ impl Item {
         self.attrs = attrs;
         self
     }
+
+    /// The item's attribute whose path is the single segment `name`.
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attrs.iter().find(|attr| attr.path == [name])
+    }
+
+    /// Text of the item's doc comments, one line per `///` comment.
+    pub fn docs(&self) -> Option<String> {
+        let lines: Vec<&str> = self.attrs.iter().filter_map(Attribute::doc_text).collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+impl Attribute {
+    /// The `doc` attribute a `///` comment stands for.
+    pub fn doc(text: String, span: SourceSpan) -> Self {
+        Self {
+            path: vec!["doc".to_string()],
+            args: vec![AttributeArg::Literal(super::expr::Literal::String(text))],
+            span,
+        }
+    }
+
+    /// Text of a `doc` attribute.
+    pub fn doc_text(&self) -> Option<&str> {
+        match self.args.as_slice() {
+            [AttributeArg::Literal(super::expr::Literal::String(text))] if self.path == ["doc"] => Some(text),
+            _ => None,
+        }
+    }
 }
\ No newline at end of file
@@ -96,6 +96,32 @@ pub enum PrimitiveType {
     Unit, // ()
 }
 
+impl PrimitiveType {
+    /// The primitive a type name such as `i32` or `str` denotes, if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "i8" => Self::I8,
+            "i16" => Self::I16,
+            "i32" => Self::I32,
+            "i64" => Self::I64,
+            "i128" => Self::I128,
+            "isize" => Self::ISize,
+            "u8" => Self::U8,
+            "u16" => Self::U16,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "u128" => Self::U128,
+            "usize" => Self::USize,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            "bool" => Self::Bool,
+            "char" => Self::Char,
+            "str" => Self::Str,
+            _ => return None,
+        })
+    }
+}
+
 /// Array size specification.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ArraySize {
@@ -117,7 +143,7 @@ pub struct Lifetime {
 }
 
 /// Safety levels for functions and operations.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SafetyLevel {
     /// Memory safe, no undefined behavior possible
     Safe,
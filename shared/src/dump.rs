@@ -0,0 +1,430 @@
+//! Stable text dumps of the AST and the TIR.
+//!
+//! `{:#?}` output changes whenever std's `Debug` formatting does, so tools and
+//! golden tests read these instead. Both `json` and `sexpr` come from the
+//! types' `Serialize` impls: fields appear in declaration order and nothing
+//! depends on addresses or hashing, so the same program always dumps the same.
+
+use serde::Serialize;
+use serde::ser::{self, Impossible};
+use std::fmt;
+use std::str::FromStr;
+
+/// Output format of `tlang compile --emit ast|tir --format`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DumpFormat {
+    /// Pretty-printed JSON.
+    Json,
+    /// S-expressions: `(Struct (field value) ...)`, one node per line once
+    /// a node no longer fits on one.
+    Sexpr,
+    /// Rust's `{:#?}`; for people, not tools.
+    Debug,
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(DumpFormat::Json),
+            "sexpr" => Ok(DumpFormat::Sexpr),
+            "debug" => Ok(DumpFormat::Debug),
+            _ => Err(format!("Unknown format '{}' (expected json, sexpr or debug)", s)),
+        }
+    }
+}
+
+/// Render `value` in `format`.
+pub fn dump<T: Serialize + fmt::Debug>(value: &T, format: DumpFormat) -> Result<String, DumpError> {
+    match format {
+        DumpFormat::Json => serde_json::to_string_pretty(value).map_err(|e| DumpError(e.to_string())),
+        DumpFormat::Sexpr => {
+            let mut out = String::new();
+            value.serialize(SexprSerializer)?.write(&mut out, 0);
+            Ok(out)
+        }
+        DumpFormat::Debug => Ok(format!("{:#?}", value)),
+    }
+}
+
+/// Why a value could not be dumped.
+#[derive(Debug)]
+pub struct DumpError(String);
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot dump: {}", self.0)
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+impl ser::Error for DumpError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DumpError(msg.to_string())
+    }
+}
+
+/// Widest a list may be to stay on one line.
+const LINE_WIDTH: usize = 80;
+
+/// An S-expression: an atom or a parenthesised list.
+enum Sexpr {
+    Atom(String),
+    List(Vec<Sexpr>),
+}
+
+impl Sexpr {
+    /// Width on one line.
+    fn width(&self) -> usize {
+        match self {
+            Sexpr::Atom(atom) => atom.len(),
+            Sexpr::List(items) => items.iter().map(|i| i.width() + 1).sum::<usize>() + 1,
+        }
+    }
+
+    /// On one line if it fits, else the head, then each item indented below.
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            Sexpr::Atom(atom) => out.push_str(atom),
+            Sexpr::List(items) if indent + self.width() <= LINE_WIDTH => {
+                out.push('(');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+                    item.write(out, indent);
+                }
+                out.push(')');
+            }
+            Sexpr::List(items) => {
+                out.push('(');
+                for (i, item) in items.iter().enumerate() {
+                    if i == 0 {
+                        item.write(out, indent + 1);
+                    } else {
+                        out.push('\n');
+                        out.push_str(&" ".repeat(indent + 2));
+                        item.write(out, indent + 2);
+                    }
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn atom(text: impl Into<String>) -> Sexpr {
+    Sexpr::Atom(text.into())
+}
+
+/// `(head items...)`
+fn tagged(head: &str, items: Vec<Sexpr>) -> Sexpr {
+    let mut list = vec![atom(head)];
+    list.extend(items);
+    Sexpr::List(list)
+}
+
+/// Builds an `Sexpr` tree: structs and variants are headed by their name,
+/// fields are `(name value)`, `None` is `nil` and `Some(x)` is just `x`.
+struct SexprSerializer;
+
+impl ser::Serializer for SexprSerializer {
+    type Ok = Sexpr;
+    type Error = DumpError;
+    type SerializeSeq = Items;
+    type SerializeTuple = Items;
+    type SerializeTupleStruct = Items;
+    type SerializeTupleVariant = Items;
+    type SerializeMap = Impossible<Sexpr, DumpError>;
+    type SerializeStruct = Items;
+    type SerializeStructVariant = Items;
+
+    fn serialize_bool(self, v: bool) -> Result<Sexpr, DumpError> {
+        Ok(atom(v.to_string()))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Sexpr, DumpError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Sexpr, DumpError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Sexpr, DumpError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Sexpr, DumpError> {
+        Ok(atom(v.to_string()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Sexpr, DumpError> {
+        Ok(atom(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Sexpr, DumpError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Sexpr, DumpError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Sexpr, DumpError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Sexpr, DumpError> {
+        Ok(atom(v.to_string()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Sexpr, DumpError> {
+        Ok(atom(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Sexpr, DumpError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Sexpr, DumpError> {
+        Ok(atom(format!("{:?}", v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Sexpr, DumpError> {
+        Ok(atom(format!("{:?}", v)))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Sexpr, DumpError> {
+        Ok(atom(format!("{:?}", v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Sexpr, DumpError> {
+        Ok(Sexpr::List(v.iter().map(|b| atom(b.to_string())).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Sexpr, DumpError> {
+        Ok(atom("nil"))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Sexpr, DumpError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Sexpr, DumpError> {
+        Ok(Sexpr::List(Vec::new()))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Sexpr, DumpError> {
+        Ok(atom(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Sexpr, DumpError> {
+        Ok(atom(variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Sexpr, DumpError> {
+        Ok(tagged(name, vec![value.serialize(self)?]))
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Sexpr, DumpError> {
+        Ok(tagged(variant, vec![value.serialize(self)?]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Items, DumpError> {
+        Ok(Items::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Items, DumpError> {
+        Ok(Items::new(None, len))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Items, DumpError> {
+        Ok(Items::new(Some(name), len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Items, DumpError> {
+        Ok(Items::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, DumpError> {
+        Err(DumpError("maps have no stable order".to_string()))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Items, DumpError> {
+        Ok(Items::new(Some(name), len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Items, DumpError> {
+        Ok(Items::new(Some(variant), len))
+    }
+}
+
+/// The items of a list under construction, after an optional head.
+struct Items(Vec<Sexpr>);
+
+impl Items {
+    fn new(head: Option<&str>, len: usize) -> Self {
+        let mut items = Vec::with_capacity(len + 1);
+        items.extend(head.map(atom));
+        Items(items)
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.0.push(value.serialize(SexprSerializer)?);
+        Ok(())
+    }
+
+    fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), DumpError> {
+        self.0.push(tagged(key, vec![value.serialize(SexprSerializer)?]));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexpr, DumpError> {
+        Ok(Sexpr::List(self.0))
+    }
+}
+
+impl ser::SerializeSeq for Items {
+    type Ok = Sexpr;
+    type Error = DumpError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexpr, DumpError> {
+        Items::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Items {
+    type Ok = Sexpr;
+    type Error = DumpError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexpr, DumpError> {
+        Items::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Items {
+    type Ok = Sexpr;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexpr, DumpError> {
+        Items::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Items {
+    type Ok = Sexpr;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), DumpError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexpr, DumpError> {
+        Items::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Items {
+    type Ok = Sexpr;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), DumpError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Sexpr, DumpError> {
+        Items::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Items {
+    type Ok = Sexpr;
+    type Error = DumpError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), DumpError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Sexpr, DumpError> {
+        Items::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tir::{BlockId, Constant, FfiType, Function, InstKind, Module, Terminator};
+
+    fn exit_with_one() -> Module {
+        let mut main = Function::new("main");
+        let one = main.push(BlockId::ENTRY, InstKind::Const(Constant::Int(1)), Some(FfiType::INT));
+        main.terminate(BlockId::ENTRY, Terminator::Exit(one));
+        Module { functions: vec![main], ..Module::default() }
+    }
+
+    #[test]
+    fn dumps_are_stable_and_json_reads_back() {
+        let module = exit_with_one();
+        let json = dump(&module, DumpFormat::Json).unwrap();
+        assert_eq!(serde_json::from_str::<Module>(&json).unwrap(), module);
+
+        let sexpr = dump(&module, DumpFormat::Sexpr).unwrap();
+        assert_eq!(sexpr, dump(&exit_with_one(), DumpFormat::Sexpr).unwrap());
+        assert!(sexpr.starts_with("(Module\n  (functions\n    ((Function\n"), "{}", sexpr);
+        assert!(sexpr.contains("(kind (Const (Int 1)))"), "{}", sexpr);
+        assert!(sexpr.contains("(terminator (Exit (InstId 0)))"), "{}", sexpr);
+        // Literals are i128s.
+        assert_eq!(dump(&i128::MIN, DumpFormat::Sexpr).unwrap(), i128::MIN.to_string());
+        assert_eq!("yaml".parse::<DumpFormat>().unwrap_err(), "Unknown format 'yaml' (expected json, sexpr or debug)");
+    }
+}
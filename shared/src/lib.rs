@@ -10,16 +10,19 @@
 //! - Error-safe with no panics or unwraps
 
 pub mod ast;
+pub mod dump;
+pub mod mir;
+pub mod tir;
 pub mod token;
 pub mod tokenizer;
-mod ast;
+
 // Re-export commonly used types at the crate root
 pub use ast::{
     Program, Module, Item, ItemKind, Stmt, StmtKind, Expr, ExprKind,
     Type, TypeKind, Pattern, PatternKind, Literal, BinaryOp, UnaryOp,
     Visibility, SafetyLevel, Span
 };
-pub use token::{Token, TokenType};
+pub use token::{Token, TokenStream, TokenType};
 pub use tokenizer::{tokenize, Tokenizer};
 
 // Re-export error handling
@@ -44,31 +47,111 @@ pub fn is_tlang_file(extension: &str) -> bool {
 
 /// Utility functions for working with source code.
 pub mod source {
-    use crate::Span;
+    use crate::{Span, TlError};
+    use errors::suggest::did_you_mean;
 
     /// Calculate line and column numbers from a byte offset.
     pub fn line_col_from_offset(source: &str, offset: usize) -> (usize, usize) {
-        let mut line = 1;
-        let mut col = 1;
+        LineIndex::new(source).offset_to_line_col(offset)
+    }
 
-        for (i, ch) in source.char_indices() {
-            if i >= offset {
-                break;
-            }
+    /// Where each line of a text starts, for turning byte offsets into
+    /// lines and columns and back without rescanning the text each time.
+    ///
+    /// Lines and columns come in two flavours: 1-based, with columns counted
+    /// in characters, as diagnostics and debug info print them, and 0-based,
+    /// with columns counted in UTF-16 code units, as LSP positions are.
+    /// Offsets past the end of a line or the text are clamped to it.
+    #[derive(Debug, Clone)]
+    pub struct LineIndex<'a> {
+        text: &'a str,
+        /// The offset of the first byte of each line
+        starts: Vec<usize>,
+    }
+
+    impl<'a> LineIndex<'a> {
+        pub fn new(text: &'a str) -> Self {
+            let starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+            LineIndex { text, starts }
+        }
+
+        pub fn line_count(&self) -> usize {
+            self.starts.len()
+        }
+
+        /// The 0-based line `offset` is on.
+        fn line_of(&self, offset: usize) -> usize {
+            self.starts.partition_point(|&start| start <= offset) - 1
+        }
+
+        /// The text of the 0-based `line`, without its newline.
+        fn line_text(&self, line: usize) -> &'a str {
+            let start = self.starts[line];
+            let end = self.starts.get(line + 1).map_or(self.text.len(), |next| next - 1);
+            &self.text[start..end]
+        }
+
+        /// The characters of `line` before `offset`.
+        fn before(&self, line: usize, offset: usize) -> impl Iterator<Item = char> + 'a {
+            let start = self.starts[line];
+            self.line_text(line).char_indices().take_while(move |&(i, _)| start + i < offset).map(|(_, ch)| ch)
+        }
 
-            if ch == '\n' {
-                line += 1;
-                col = 1;
-            } else {
-                col += 1;
+        /// The 1-based line and character column of `offset`.
+        pub fn offset_to_line_col(&self, offset: usize) -> (usize, usize) {
+            let line = self.line_of(offset.min(self.text.len()));
+            (line + 1, self.before(line, offset).count() + 1)
+        }
+
+        /// The offset of the 1-based `line` and character `column`.
+        pub fn line_col_to_offset(&self, line: usize, column: usize) -> usize {
+            self.offset_within(line.saturating_sub(1), column.saturating_sub(1), |_| 1)
+        }
+
+        /// The 0-based line and UTF-16 column of `offset`.
+        pub fn offset_to_utf16(&self, offset: usize) -> (u32, u32) {
+            let line = self.line_of(offset.min(self.text.len()));
+            let column: usize = self.before(line, offset).map(char::len_utf16).sum();
+            (line as u32, column as u32)
+        }
+
+        /// The offset of the 0-based `line` and UTF-16 `column`.
+        pub fn utf16_to_offset(&self, line: u32, column: u32) -> usize {
+            self.offset_within(line as usize, column as usize, char::len_utf16)
+        }
+
+        /// The offset of the first character of `line` at or past `column`,
+        /// counting each character as `width` says.
+        fn offset_within(&self, line: usize, column: usize, width: impl Fn(char) -> usize) -> usize {
+            let Some(&start) = self.starts.get(line) else { return self.text.len() };
+            let mut seen = 0;
+            for (i, ch) in self.line_text(line).char_indices() {
+                if seen >= column {
+                    return start + i;
+                }
+                seen += width(ch);
             }
+            start + self.line_text(line).len()
         }
+    }
 
-        (line, col)
+    /// `err`, made from `source`, the text of the file at `path`, as the one
+    /// line `path:line:col: error[code]: message` editors and tools read,
+    /// and a `= help:` line after it when names are suggested in place of
+    /// an undefined one.
+    pub fn diagnostic(path: &str, source: &str, err: &TlError) -> String {
+        let (line, col) = err.span().map_or((1, 1), |span| line_col_from_offset(source, span.offset()));
+        let message = err.to_string();
+        let message = message.lines().next().unwrap_or_default();
+        let mut text = format!("{}:{}:{}: error[{}]: {}", path, line, col, err.code(), message);
+        if let Some(help) = did_you_mean(err.suggestions()) {
+            text.push_str(&format!("\n  = help: {}", help));
+        }
+        text
     }
 
     /// Get the text content of a span from source code.
-    pub fn span_text<'a>(source: &'a str, span: Span) -> &'a str {
+    pub fn span_text(source: &str, span: Span) -> &str {
         let start = span.start.min(source.len());
         let end = span.end.min(source.len());
         &source[start..end]
@@ -111,9 +194,9 @@ pub mod names {
     pub fn is_keyword(name: &str) -> bool {
         matches!(
             name,
-            "as" | "async" | "await" | "break" | "const" | "continue" | "else" | "enum" |
-            "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" |
-            "mod" | "move" | "mut" | "pub" | "ref" | "return" | "self" | "Self" |
+            "as" | "async" | "await" | "break" | "const" | "continue" | "defer" | "else" | "enum" |
+            "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" |
+            "mod" | "move" | "mut" | "pub" | "ref" | "return" | "self" | "Self" | "spawn" |
             "static" | "struct" | "super" | "trait" | "true" | "type" | "union" |
             "unsafe" | "use" | "where" | "while"
         )
@@ -221,4 +304,21 @@ mod tests {
         assert_eq!(source::line_col_from_offset(source, 6), (2, 1));
         assert_eq!(source::line_col_from_offset(source, 12), (3, 1));
     }
+
+    #[test]
+    fn test_line_index_round_trips() {
+        let text = "let é = 1;\n\n𝄞 x\n";
+        let index = source::LineIndex::new(text);
+        assert_eq!(index.line_count(), 4);
+        let x = text.find('x').unwrap();
+        assert_eq!(index.offset_to_line_col(x), (3, 3));
+        assert_eq!(index.line_col_to_offset(3, 3), x);
+        assert_eq!(index.offset_to_utf16(x), (2, 3));
+        assert_eq!(index.utf16_to_offset(2, 3), x);
+        assert_eq!(index.offset_to_line_col(text.find('=').unwrap()), (1, 7));
+        // Past the end of a line, or of the text, is its end.
+        assert_eq!(index.utf16_to_offset(0, 99), text.find('\n').unwrap());
+        assert_eq!(index.line_col_to_offset(9, 1), text.len());
+        assert_eq!(index.offset_to_line_col(text.len() + 5), (4, 1));
+    }
 }
\ No newline at end of file
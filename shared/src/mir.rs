@@ -0,0 +1,317 @@
+//! T‑Lang Mid‑IR: a TIR function as the control-flow graph analyses and
+//! optimizations work on, in SSA style.
+//!
+//! Like TIR, a graph keeps its blocks in a `Vec` indexed by `MirBlockId`,
+//! the entry block first, and its temporaries in a `Vec` indexed by
+//! `Temp`.
+//!
+//! `MirGraph::from_tir` promotes the function's locals to temps, as
+//! mem2reg does: a `Phi` for a local goes at each block of the iterated
+//! dominance frontier of the blocks that store to it, and a walk of the
+//! dominator tree then renames each `Load` to the temp the local last held,
+//! so neither `Load` nor `Store` is left. Every other instruction becomes
+//! an `Assign` whose temp is numbered as that instruction's value. A local
+//! read before anything is stored to it reads zero, as a slot of the
+//! stack instructions does.
+
+use crate::tir::cfg::Cfg;
+use crate::tir::{self, BlockId, Constant, FfiType, InstId, InstKind, LocalId};
+use serde::{Deserialize, Serialize};
+
+/// Index of a block in its graph's `blocks`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MirBlockId(pub u32);
+
+impl MirBlockId {
+    /// The block a graph starts at.
+    pub const ENTRY: MirBlockId = MirBlockId(0);
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Index of a temporary in its graph's `temps`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Temp(pub u32);
+
+impl Temp {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A function's control-flow graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MirGraph {
+    pub name: String,
+    /// Blocks by `MirBlockId`, in the TIR function's layout order.
+    pub blocks: Vec<MirBlock>,
+    /// Types of the temporaries by `Temp`; `None` for one that names an
+    /// instruction without a value.
+    pub temps: Vec<Option<FfiType>>,
+    /// Types of the TIR function's locals, by `LocalId`.
+    pub locals: Vec<FfiType>,
+}
+
+/// A basic block: its instructions, phis first, then the terminator.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MirBlock {
+    pub instrs: Vec<MirInstr>,
+    pub terminator: MirTerminator,
+}
+
+/// An instruction of a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MirInstr {
+    /// `temp` is the result of `op`, whose operands name temps by number.
+    Assign { temp: Temp, op: InstKind },
+    /// `temp` is the value `local` has on entry to the block: the operand
+    /// of the predecessor control came from. There is one operand for each
+    /// predecessor control can reach, in layout order.
+    Phi { temp: Temp, local: LocalId, operands: Vec<(MirBlockId, Temp)> },
+}
+
+/// How a block ends.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum MirTerminator {
+    /// Return from the function.
+    #[default]
+    Return,
+    /// Continue at the block.
+    Jump(MirBlockId),
+    /// Continue at `then` if `condition` is true, else at `otherwise`.
+    CondJump { condition: Temp, then: MirBlockId, otherwise: MirBlockId },
+    /// End the program with the exit code `code`.
+    Exit(Temp),
+    /// Continue at the block of the case `value` is, else at `default`.
+    Switch { value: Temp, cases: Vec<(i64, MirBlockId)>, default: MirBlockId },
+    /// Never reached.
+    Unreachable,
+}
+
+impl MirTerminator {
+    /// The blocks control may go to next.
+    pub fn successors(&self) -> Vec<MirBlockId> {
+        match self {
+            MirTerminator::Jump(target) => vec![*target],
+            MirTerminator::CondJump { then, otherwise, .. } => vec![*then, *otherwise],
+            MirTerminator::Switch { cases, default, .. } => {
+                cases.iter().map(|&(_, target)| target).chain([*default]).collect()
+            }
+            MirTerminator::Return | MirTerminator::Exit(_) | MirTerminator::Unreachable => Vec::new(),
+        }
+    }
+}
+
+impl MirGraph {
+    /// The SSA graph of `function`. Blocks control cannot reach from the
+    /// entry are kept, so ids stay those of TIR, but emptied, ending in
+    /// `Unreachable`.
+    pub fn from_tir(function: &tir::Function) -> Self {
+        let n = function.blocks.len();
+        let mut graph = MirGraph {
+            name: function.name.clone(),
+            blocks: vec![MirBlock { instrs: Vec::new(), terminator: MirTerminator::Unreachable }; n],
+            temps: function.insts.iter().map(|inst| inst.ty).collect(),
+            locals: function.locals.clone(),
+        };
+        let cfg = Cfg::new(function);
+
+        // The phis of each block, by local, to be renamed.
+        let frontiers = cfg.frontiers();
+        let mut phis: Vec<Vec<(LocalId, Temp)>> = vec![Vec::new(); n];
+        for (local, &ty) in function.locals.iter().enumerate() {
+            let local = LocalId(local as u32);
+            let mut work: Vec<BlockId> = function
+                .iter_blocks()
+                .filter(|&(id, block)| {
+                    cfg.is_reachable(id)
+                        && block.insts.iter().any(|&inst| {
+                            matches!(function.inst(inst).kind, InstKind::Store { local: stored, .. } if stored == local)
+                        })
+                })
+                .map(|(id, _)| id)
+                .collect();
+            let mut placed = vec![false; n];
+            while let Some(block) = work.pop() {
+                for &join in &frontiers[block.index()] {
+                    if !placed[join.index()] {
+                        placed[join.index()] = true;
+                        phis[join.index()].push((local, graph.add_temp(Some(ty))));
+                        work.push(join);
+                    }
+                }
+            }
+        }
+        for (block, phis) in graph.blocks.iter_mut().zip(&phis) {
+            block.instrs.extend(phis.iter().map(|&(local, temp)| MirInstr::Phi { temp, local, operands: Vec::new() }));
+        }
+
+        // What each value is renamed to; only those of loads change.
+        let mut renamed: Vec<Temp> = (0..function.insts.len() as u32).map(Temp).collect();
+        let mut zeros: Vec<Option<Temp>> = vec![None; function.locals.len()];
+        let mut zero_instrs = Vec::new();
+        let mut zero = |graph: &mut MirGraph, local: LocalId| {
+            *zeros[local.index()].get_or_insert_with(|| {
+                let ty = graph.locals[local.index()];
+                let temp = graph.add_temp(Some(ty));
+                let value = match ty {
+                    FfiType::Int { .. } => Constant::Int(0),
+                    FfiType::Float { .. } => Constant::Float(0.0),
+                    FfiType::Bool => Constant::Bool(false),
+                    FfiType::Str => Constant::Str(String::new()),
+                };
+                zero_instrs.push(MirInstr::Assign { temp, op: InstKind::Const(value) });
+                temp
+            })
+        };
+
+        // Each block starts from the temps the locals held at the end of
+        // its immediate dominator.
+        let children = cfg.dominator_tree();
+        let mut stack = vec![(BlockId::ENTRY, vec![None; function.locals.len()])];
+        while let Some((id, mut current)) = stack.pop() {
+            for &(local, temp) in &phis[id.index()] {
+                current[local.index()] = Some(temp);
+            }
+            let block = function.block(id);
+            for &inst in &block.insts {
+                let mut op = function.inst(inst).kind.clone();
+                match op {
+                    InstKind::Load(local) => {
+                        renamed[inst.index()] = match current[local.index()] {
+                            Some(temp) => temp,
+                            None => zero(&mut graph, local),
+                        };
+                    }
+                    InstKind::Store { local, value } => current[local.index()] = Some(renamed[value.index()]),
+                    _ => {
+                        op.map_operands(|value| InstId(renamed[value.index()].0));
+                        graph.blocks[id.index()].instrs.push(MirInstr::Assign { temp: Temp(inst.0), op });
+                    }
+                }
+            }
+            let temp = |value: InstId| renamed[value.index()];
+            graph.blocks[id.index()].terminator = match &block.terminator {
+                tir::Terminator::Return => MirTerminator::Return,
+                tir::Terminator::Jump(target) => MirTerminator::Jump(MirBlockId(target.0)),
+                tir::Terminator::Branch { condition, then, otherwise } => MirTerminator::CondJump {
+                    condition: temp(*condition),
+                    then: MirBlockId(then.0),
+                    otherwise: MirBlockId(otherwise.0),
+                },
+                tir::Terminator::Exit(code) => MirTerminator::Exit(temp(*code)),
+                tir::Terminator::Switch { value, cases, default } => MirTerminator::Switch {
+                    value: temp(*value),
+                    cases: cases.iter().map(|&(n, target)| (n, MirBlockId(target.0))).collect(),
+                    default: MirBlockId(default.0),
+                },
+                tir::Terminator::Unreachable => MirTerminator::Unreachable,
+            };
+
+            let mut successors = block.terminator.successors();
+            successors.sort();
+            successors.dedup();
+            for successor in successors {
+                for (i, &(local, _)) in phis[successor.index()].iter().enumerate() {
+                    let value = match current[local.index()] {
+                        Some(temp) => temp,
+                        None => zero(&mut graph, local),
+                    };
+                    if let MirInstr::Phi { operands, .. } = &mut graph.blocks[successor.index()].instrs[i] {
+                        operands.push((MirBlockId(id.0), value));
+                    }
+                }
+            }
+            stack.extend(children[id.index()].iter().rev().map(|&child| (child, current.clone())));
+        }
+        // The entry has no phis, and dominates every read of a zero.
+        if n > 0 {
+            graph.blocks[0].instrs.splice(0..0, zero_instrs);
+        }
+        for instr in graph.blocks.iter_mut().flat_map(|block| &mut block.instrs) {
+            if let MirInstr::Phi { operands, .. } = instr {
+                operands.sort_by_key(|&(block, _)| block);
+            }
+        }
+        graph
+    }
+
+    /// Add a temporary of type `ty`.
+    pub fn add_temp(&mut self, ty: Option<FfiType>) -> Temp {
+        self.temps.push(ty);
+        Temp(self.temps.len() as u32 - 1)
+    }
+
+    pub fn block(&self, id: MirBlockId) -> &MirBlock {
+        &self.blocks[id.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tir::{Function, Terminator};
+
+    #[test]
+    fn locals_become_temps_joined_by_phis() {
+        let mut function = Function::new("main");
+        let x = function.add_local(FfiType::INT);
+        let (then, otherwise, join) = (function.add_block(), function.add_block(), function.add_block());
+        let condition = function.push(BlockId::ENTRY, InstKind::Const(Constant::Bool(true)), Some(FfiType::Bool));
+        function.terminate(BlockId::ENTRY, Terminator::Branch { condition, then, otherwise });
+        for (block, n) in [(then, 1), (otherwise, 2)] {
+            let value = function.push(block, InstKind::Const(Constant::Int(n)), Some(FfiType::INT));
+            function.push(block, InstKind::Store { local: x, value }, None);
+            function.terminate(block, Terminator::Jump(join));
+        }
+        let read = function.push(join, InstKind::Load(x), Some(FfiType::INT));
+        function.push(join, InstKind::Print(read), None);
+        function.terminate(join, Terminator::Exit(read));
+
+        let graph = MirGraph::from_tir(&function);
+        assert_eq!(graph.blocks.len(), 4);
+        assert_eq!(
+            graph.block(MirBlockId::ENTRY).terminator,
+            MirTerminator::CondJump { condition: Temp(0), then: MirBlockId(1), otherwise: MirBlockId(2) }
+        );
+        // The stores are gone; each side only defines its constant.
+        assert!(matches!(graph.block(MirBlockId(1)).instrs[..], [MirInstr::Assign { temp: Temp(1), .. }]));
+        // Seven instructions make temps 0 to 6; the phi gets the next, and
+        // the load is renamed to it.
+        let operands = vec![(MirBlockId(1), Temp(1)), (MirBlockId(2), Temp(3))];
+        let phi = MirInstr::Phi { temp: Temp(7), local: x, operands };
+        assert_eq!(
+            graph.block(MirBlockId(3)).instrs,
+            [phi, MirInstr::Assign { temp: Temp(6), op: InstKind::Print(InstId(7)) }]
+        );
+        assert_eq!(graph.block(MirBlockId(3)).terminator, MirTerminator::Exit(Temp(7)));
+        assert_eq!(graph.temps[7], Some(FfiType::INT));
+    }
+
+    #[test]
+    fn loops_get_a_phi_at_the_header_and_reads_before_stores_read_zero() {
+        // let mut i; loop { print(i); i = i + 1; }, with `i` never set before the loop.
+        let mut function = Function::new("main");
+        let i = function.add_local(FfiType::INT);
+        let header = function.add_block();
+        function.terminate(BlockId::ENTRY, Terminator::Jump(header));
+        let read = function.push(header, InstKind::Load(i), Some(FfiType::INT));
+        function.push(header, InstKind::Print(read), None);
+        let one = function.push(header, InstKind::Const(Constant::Int(1)), Some(FfiType::INT));
+        let add = InstKind::Arith { op: tir::ArithOp::Add, lhs: read, rhs: one };
+        let next = function.push(header, add, Some(FfiType::INT));
+        function.push(header, InstKind::Store { local: i, value: next }, None);
+        function.terminate(header, Terminator::Jump(header));
+
+        let graph = MirGraph::from_tir(&function);
+        // The phi is temp 5; the zero, made while renaming, temp 6.
+        let zero = MirInstr::Assign { temp: Temp(6), op: InstKind::Const(Constant::Int(0)) };
+        assert_eq!(graph.block(MirBlockId::ENTRY).instrs, [zero]);
+        let operands = vec![(MirBlockId::ENTRY, Temp(6)), (MirBlockId(1), Temp(3))];
+        assert_eq!(graph.block(MirBlockId(1)).instrs[0], MirInstr::Phi { temp: Temp(5), local: i, operands });
+        let add = InstKind::Arith { op: tir::ArithOp::Add, lhs: InstId(5), rhs: InstId(2) };
+        assert_eq!(graph.block(MirBlockId(1)).instrs[3], MirInstr::Assign { temp: Temp(3), op: add });
+    }
+}
@@ -0,0 +1,217 @@
+//! The control-flow graph of a TIR function, and what passes read off it:
+//! predecessors, the dominator tree, dominance frontiers and natural loops.
+//!
+//! Dominators are found with the iterative algorithm of Cooper, Harvey and
+//! Kennedy over the blocks in reverse postorder. Blocks control cannot
+//! reach from the entry have no dominator and dominate nothing.
+
+use super::{BlockId, Function};
+
+/// A natural loop: the blocks from which control can reach a back edge
+/// into `header` without going through it, and `header` itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: BlockId,
+    /// The loop's blocks in reverse postorder, so `header` first.
+    pub blocks: Vec<BlockId>,
+}
+
+impl Loop {
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.blocks.contains(&block)
+    }
+}
+
+/// Edges and dominators of a function's blocks, as they were when it was
+/// built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    /// Predecessors of each block, by index, each once.
+    predecessors: Vec<Vec<BlockId>>,
+    /// The reachable blocks in reverse postorder, the entry first.
+    order: Vec<BlockId>,
+    /// Immediate dominator of each reachable block but the entry.
+    idom: Vec<Option<BlockId>>,
+}
+
+impl Cfg {
+    pub fn new(function: &Function) -> Self {
+        let n = function.blocks.len();
+        let mut predecessors = vec![Vec::new(); n];
+        for (id, block) in function.iter_blocks() {
+            for successor in block.terminator.successors() {
+                if !predecessors[successor.index()].contains(&id) {
+                    predecessors[successor.index()].push(id);
+                }
+            }
+        }
+
+        // Postorder, without recursion: a block is done once its
+        // successors are.
+        let mut visited = vec![false; n];
+        let mut postorder = Vec::with_capacity(n);
+        let mut stack = vec![(BlockId::ENTRY, 0)];
+        visited[0] = n > 0;
+        while let Some((block, next)) = stack.pop() {
+            let successors = function.block(block).terminator.successors();
+            match successors.get(next) {
+                Some(&successor) => {
+                    stack.push((block, next + 1));
+                    if !visited[successor.index()] {
+                        visited[successor.index()] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => postorder.push(block),
+            }
+        }
+        let order: Vec<BlockId> = postorder.into_iter().rev().collect();
+
+        let mut rank = vec![usize::MAX; n];
+        for (i, block) in order.iter().enumerate() {
+            rank[block.index()] = i;
+        }
+        let mut idom: Vec<Option<BlockId>> = vec![None; n];
+        if n > 0 {
+            idom[0] = Some(BlockId::ENTRY);
+        }
+        let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+            while a != b {
+                while rank[a.index()] > rank[b.index()] {
+                    a = idom[a.index()].expect("processed blocks have dominators");
+                }
+                while rank[b.index()] > rank[a.index()] {
+                    b = idom[b.index()].expect("processed blocks have dominators");
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut processed = predecessors[block.index()].iter().filter(|p| idom[p.index()].is_some());
+                let Some(&first) = processed.next() else {
+                    continue;
+                };
+                let new = processed.fold(first, |dom, &p| intersect(&idom, p, dom));
+                if idom[block.index()] != Some(new) {
+                    idom[block.index()] = Some(new);
+                    changed = true;
+                }
+            }
+        }
+        // The entry has no immediate dominator; it was its own to seed the search.
+        if n > 0 {
+            idom[0] = None;
+        }
+        Cfg { predecessors, order, idom }
+    }
+
+    /// The blocks that can go to `block` next.
+    pub fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        &self.predecessors[block.index()]
+    }
+
+    /// The blocks reachable from the entry, in reverse postorder: each
+    /// before its successors, but along back edges.
+    pub fn reverse_postorder(&self) -> &[BlockId] {
+        &self.order
+    }
+
+    /// Whether control can reach `block` from the entry.
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        block == BlockId::ENTRY || self.idom[block.index()].is_some()
+    }
+
+    /// The immediate dominator of `block`, if it is reachable and not the entry.
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block.index()]
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`; a
+    /// reachable block dominates itself.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        if !self.is_reachable(a) || !self.is_reachable(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom(b) {
+                Some(dom) => b = dom,
+                None => return false,
+            }
+        }
+    }
+
+    /// The blocks each reachable block immediately dominates, by index, in
+    /// reverse postorder.
+    pub fn dominator_tree(&self) -> Vec<Vec<BlockId>> {
+        let mut children = vec![Vec::new(); self.idom.len()];
+        for &block in &self.order {
+            if let Some(dom) = self.idom(block) {
+                children[dom.index()].push(block);
+            }
+        }
+        children
+    }
+
+    /// The dominance frontier of each block, by index: the blocks where
+    /// its dominance ends, each a join one of whose predecessors it
+    /// dominates but which it does not strictly dominate.
+    pub fn frontiers(&self) -> Vec<Vec<BlockId>> {
+        let mut frontiers: Vec<Vec<BlockId>> = vec![Vec::new(); self.idom.len()];
+        for &block in &self.order {
+            let predecessors = self.predecessors(block);
+            if predecessors.len() < 2 {
+                continue;
+            }
+            for &p in predecessors.iter().filter(|&&p| self.is_reachable(p)) {
+                let mut runner = p;
+                while Some(runner) != self.idom(block) {
+                    if !frontiers[runner.index()].contains(&block) {
+                        frontiers[runner.index()].push(block);
+                    }
+                    match self.idom(runner) {
+                        Some(dom) => runner = dom,
+                        None => break,
+                    }
+                }
+            }
+        }
+        frontiers
+    }
+
+    /// The natural loops, one per header, innermost first: a loop nested in
+    /// another comes before it. A back edge is one into a block that
+    /// dominates where it comes from.
+    pub fn loops(&self) -> Vec<Loop> {
+        let mut rank = vec![usize::MAX; self.idom.len()];
+        for (i, block) in self.order.iter().enumerate() {
+            rank[block.index()] = i;
+        }
+        let mut loops = Vec::new();
+        for &header in &self.order {
+            let latches: Vec<BlockId> =
+                self.predecessors(header).iter().copied().filter(|&p| self.dominates(header, p)).collect();
+            if latches.is_empty() {
+                continue;
+            }
+            let mut blocks = vec![header];
+            let mut work = latches;
+            while let Some(block) = work.pop() {
+                if !blocks.contains(&block) {
+                    blocks.push(block);
+                    work.extend(self.predecessors(block).iter().filter(|&&p| self.is_reachable(p)));
+                }
+            }
+            blocks.sort_by_key(|block| rank[block.index()]);
+            loops.push(Loop { header, blocks });
+        }
+        // A loop holds every loop nested in it, and more.
+        loops.sort_by_key(|lp| lp.blocks.len());
+        loops
+    }
+}
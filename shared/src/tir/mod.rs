@@ -1,7 +1,786 @@
-//! T‑Lang Intermediate Representation (TIR)
+//! T‑Lang Intermediate Representation (TIR).
+//!
+//! The one IR between lowering and the backends. A `Module` holds the
+//! functions of a program and the extern functions and globals they use.
+//! A `Function` is a graph of basic blocks, each a list of instructions
+//! ending in a `Terminator`. Blocks and instructions live in `Vec` arenas of
+//! their function and refer to each other by index (`BlockId`, `InstId`),
+//! so walking a function is deterministic and every lookup an index.
+//!
+//! An instruction's result is a value named by the instruction's `InstId`,
+//! defined once; what changes is kept in the function's locals, which
+//! `Load` reads and `Store` writes. A `Call` runs another function of the
+//! module, with its arguments in the callee's first locals, and has the
+//! value of the callee's result local when it returns. Integers are carried
+//! as 64-bit values whatever their source type, as they are on the stacks of
+//! `plugin_api`'s instructions; instructions whose result depends on the
+//! width, such as `Cast`, say which width they work in.
+//!
+//! `plugin_api` emits a module's TIR as the stack instructions of a
+//! `CompiledModule` and lifts those back to TIR, so each backend starts
+//! from whichever of the two suits it.
+
+pub mod cfg;
+pub mod text;
+pub mod verify;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A C type, as a value crosses the boundary of an extern function, and
+/// the type of a TIR value.
+///
+/// Each value travels on the stack of its kind; strings are passed as
+/// NUL‑terminated `const char *`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FfiType {
+    Int { bits: u8, signed: bool },
+    /// `float` for 32 bits, `double` for 64.
+    Float { bits: u8 },
+    Bool,
+    Str,
+}
+
+impl FfiType {
+    /// The type integer values are carried as.
+    pub const INT: FfiType = FfiType::Int { bits: 64, signed: true };
+    /// The type float values are carried as.
+    pub const FLOAT: FfiType = FfiType::Float { bits: 64 };
+
+    /// The type C passes this as in the variable part of a variadic call:
+    /// integers narrower than `int` become `int`, and `float` becomes `double`.
+    /// `bool` becomes `int` as well but stays on its own stack, so backends
+    /// that must spell that promotion convert it at the call.
+    pub fn promoted(self) -> FfiType {
+        match self {
+            FfiType::Int { bits, .. } if bits < 32 => FfiType::Int { bits: 32, signed: true },
+            FfiType::Float { .. } => FfiType::Float { bits: 64 },
+            other => other,
+        }
+    }
+
+    /// The type a value of this type is carried as: `INT` or `FLOAT` for
+    /// numbers, itself otherwise.
+    pub fn carried(self) -> FfiType {
+        match self {
+            FfiType::Int { .. } => FfiType::INT,
+            FfiType::Float { .. } => FfiType::FLOAT,
+            other => other,
+        }
+    }
+}
+
+/// A function defined outside the module and called with the C ABI.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternFunction {
+    pub name: String,
+    pub params: Vec<FfiType>,
+    /// `None` for a function returning `void`.
+    pub return_type: Option<FfiType>,
+    /// Whether further arguments may follow `params`, as for `printf`.
+    pub variadic: bool,
+}
+
+/// A value known at compile time, such as the initializer of a `Global`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Constant {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl Constant {
+    /// The type values of this constant are carried as.
+    pub fn ty(&self) -> FfiType {
+        match self {
+            Constant::Int(_) => FfiType::INT,
+            Constant::Float(_) => FfiType::FLOAT,
+            Constant::Bool(_) => FfiType::Bool,
+            Constant::Str(_) => FfiType::Str,
+        }
+    }
+}
+
+/// A global variable the module defines: a `static` of the source.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Global {
+    pub name: String,
+    pub ty: FfiType,
+    /// The value it starts with.
+    pub value: Constant,
+    /// Whether the program may change it, as for a `static mut`.
+    pub mutable: bool,
+}
+
+/// Index of a block in its function's `blocks`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BlockId(pub u32);
+
+impl BlockId {
+    /// The block a function starts at.
+    pub const ENTRY: BlockId = BlockId(0);
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Index of an instruction in its function's `insts`, and so the name of
+/// the value it defines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct InstId(pub u32);
+
+impl InstId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Index of a local variable in its function's `locals`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LocalId(pub u32);
+
+impl LocalId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
 
 /// A compilation unit in TIR.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Module {
-    // your module fields here, e.g. functions, globals, etc.
+    pub functions: Vec<Function>,
+    /// Extern functions the module declares; every `CallExtern` names one.
+    pub externs: Vec<ExternFunction>,
+    /// Global variables the module defines.
+    pub globals: Vec<Global>,
+}
+
+impl Module {
+    /// The function called `name`, if the module has one.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// The declaration of the extern function `name`, if the module has one.
+    pub fn extern_function(&self, name: &str) -> Option<&ExternFunction> {
+        self.externs.iter().find(|function| function.name == name)
+    }
+}
+
+/// A function: blocks of instructions, starting at `BlockId::ENTRY`.
+///
+/// The first function of a module is its entry point, which takes no
+/// arguments, and whose `Return` ends the program; the others are what its
+/// `Call`s run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    pub name: String,
+    /// Blocks by `BlockId`, in the order they are laid out.
+    pub blocks: Vec<Block>,
+    /// Instructions of all blocks by `InstId`; each is in one block.
+    pub insts: Vec<Inst>,
+    /// Types of the local variables by `LocalId`.
+    pub locals: Vec<FfiType>,
+    /// How many of the first locals hold the arguments of a `Call`, in order.
+    pub params: usize,
+    /// The local whose value a `Return` gives back to the `Call`, if the
+    /// function returns a value.
+    pub result: Option<LocalId>,
+    /// What the source asks of inlining the function.
+    pub inline: Inline,
+}
+
+/// An `#[inline]` attribute: whether calls to a function are to be
+/// replaced by its body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Inline {
+    /// As the optimizer finds worth it.
+    #[default]
+    Auto,
+    /// `#[inline]`: wherever it can.
+    Always,
+    /// `#[inline(never)]`: nowhere.
+    Never,
+}
+
+impl Function {
+    /// A function whose body is one empty block that returns.
+    pub fn new(name: impl Into<String>) -> Self {
+        Function {
+            name: name.into(),
+            blocks: vec![Block::default()],
+            insts: Vec::new(),
+            locals: Vec::new(),
+            params: 0,
+            result: None,
+            inline: Inline::Auto,
+        }
+    }
+
+    /// The types of the arguments a `Call` passes.
+    pub fn param_types(&self) -> &[FfiType] {
+        &self.locals[..self.params.min(self.locals.len())]
+    }
+
+    /// The type of the value a `Call` of the function has, if it has one.
+    pub fn result_type(&self) -> Option<FfiType> {
+        self.result.and_then(|local| self.locals.get(local.index()).copied())
+    }
+
+    /// Add a local variable of type `ty`.
+    pub fn add_local(&mut self, ty: FfiType) -> LocalId {
+        self.locals.push(ty);
+        LocalId(self.locals.len() as u32 - 1)
+    }
+
+    /// Add an empty block that returns, laid out after the others.
+    pub fn add_block(&mut self) -> BlockId {
+        self.blocks.push(Block::default());
+        BlockId(self.blocks.len() as u32 - 1)
+    }
+
+    /// Append an instruction of `kind`, defining a value of type `ty` if it
+    /// has one, to the end of `block`.
+    pub fn push(&mut self, block: BlockId, kind: InstKind, ty: Option<FfiType>) -> InstId {
+        let id = InstId(self.insts.len() as u32);
+        self.insts.push(Inst { kind, ty });
+        self.blocks[block.index()].insts.push(id);
+        id
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.index()]
+    }
+
+    pub fn inst(&self, id: InstId) -> &Inst {
+        &self.insts[id.index()]
+    }
+
+    /// End `block` with `terminator`, in place of how it ended.
+    pub fn terminate(&mut self, block: BlockId, terminator: Terminator) {
+        self.blocks[block.index()].terminator = terminator;
+    }
+
+    /// The type of the value `value` names, if it names one.
+    pub fn ty(&self, value: InstId) -> Option<FfiType> {
+        self.insts.get(value.index()).and_then(|inst| inst.ty)
+    }
+
+    /// Ids and blocks in layout order.
+    pub fn iter_blocks(&self) -> impl Iterator<Item = (BlockId, &Block)> {
+        self.blocks.iter().enumerate().map(|(i, block)| (BlockId(i as u32), block))
+    }
+
+    /// The instructions whose values are read, or whose effects are seen:
+    /// everything but dead code.
+    pub fn live_insts(&self) -> HashSet<InstId> {
+        let mut work = Vec::new();
+        for (_, block) in self.iter_blocks() {
+            work.extend(block.insts.iter().filter(|&&inst| self.inst(inst).kind.has_effects()));
+            work.extend(block.terminator.operands());
+        }
+        let mut live = HashSet::new();
+        while let Some(inst) = work.pop() {
+            if live.insert(inst) {
+                work.extend(self.inst(inst).kind.operands());
+            }
+        }
+        live
+    }
+
+    /// The values live instructions and terminators read.
+    pub fn used_values(&self) -> HashSet<InstId> {
+        let live = self.live_insts();
+        let mut used = HashSet::new();
+        for (_, block) in self.iter_blocks() {
+            for inst in block.insts.iter().filter(|inst| live.contains(inst)) {
+                used.extend(self.inst(*inst).kind.operands());
+            }
+            used.extend(block.terminator.operands());
+        }
+        used
+    }
+
+    /// The function as it runs without `Alloca`s, if it has any: each
+    /// calls its allocation's constructor and keeps the handle in a local
+    /// of its own, and each `Return` first calls `FREE_COLLECTION` with
+    /// those locals. They start at -1, a handle of no collection, for the
+    /// `Alloca`s a call does not get to.
+    pub fn expand_allocas(&self) -> Option<Function> {
+        let allocas: Vec<(BlockId, InstId, Allocation)> = self
+            .iter_blocks()
+            .flat_map(|(id, block)| block.insts.iter().map(move |&inst| (id, inst)))
+            .filter_map(|(id, inst)| match self.inst(inst).kind {
+                InstKind::Alloca(allocation) => Some((id, inst, allocation)),
+                _ => None,
+            })
+            .collect();
+        if allocas.is_empty() {
+            return None;
+        }
+        let mut function = self.clone();
+        let add = |function: &mut Function, kind: InstKind, ty: Option<FfiType>| {
+            function.insts.push(Inst { kind, ty });
+            InstId(function.insts.len() as u32 - 1)
+        };
+        let handles: Vec<LocalId> = allocas.iter().map(|_| function.add_local(FfiType::INT)).collect();
+        let none = add(&mut function, InstKind::Const(Constant::Int(-1)), Some(FfiType::INT));
+        let mut start = vec![none];
+        for &local in &handles {
+            start.push(add(&mut function, InstKind::Store { local, value: none }, None));
+        }
+        for (&(block, alloca, allocation), &local) in allocas.iter().zip(&handles) {
+            let name = allocation.constructor();
+            function.insts[alloca.index()].kind = InstKind::CallExtern { name, args: Vec::new(), types: Vec::new() };
+            let store = add(&mut function, InstKind::Store { local, value: alloca }, None);
+            let insts = &mut function.blocks[block.index()].insts;
+            let at = insts.iter().position(|&inst| inst == alloca).expect("the block holds the alloca");
+            insts.insert(at + 1, store);
+        }
+        for i in 0..function.blocks.len() {
+            if function.blocks[i].terminator != Terminator::Return {
+                continue;
+            }
+            for &local in &handles {
+                let handle = add(&mut function, InstKind::Load(local), Some(FfiType::INT));
+                let args = vec![handle];
+                let free = InstKind::CallExtern { name: FREE_COLLECTION.to_string(), args, types: vec![FfiType::INT] };
+                let free = add(&mut function, free, None);
+                function.blocks[i].insts.extend([handle, free]);
+            }
+        }
+        function.blocks[BlockId::ENTRY.index()].insts.splice(0..0, start);
+        Some(function)
+    }
+
+    /// Drop the instructions no block holds, and the blocks but the entry
+    /// that hold nothing, end in `Unreachable` and that nothing goes to,
+    /// numbering what is left in the order it was.
+    pub fn compact(&mut self) {
+        let targets: HashSet<BlockId> = self.blocks.iter().flat_map(|block| block.terminator.successors()).collect();
+        let mut block_ids = Vec::with_capacity(self.blocks.len());
+        let mut kept = 0;
+        for (id, block) in self.iter_blocks() {
+            let dropped = id != BlockId::ENTRY
+                && block.insts.is_empty()
+                && block.terminator == Terminator::Unreachable
+                && !targets.contains(&id);
+            block_ids.push((!dropped).then_some(BlockId(kept)));
+            kept += u32::from(!dropped);
+        }
+        let mut placed = vec![false; self.insts.len()];
+        for block in &self.blocks {
+            for inst in &block.insts {
+                placed[inst.index()] = true;
+            }
+        }
+        let mut inst_ids = Vec::with_capacity(self.insts.len());
+        let mut kept = 0;
+        for &placed in &placed {
+            inst_ids.push(InstId(kept));
+            kept += u32::from(placed);
+        }
+        let renumber = |value: InstId| inst_ids[value.index()];
+        let insts = std::mem::take(&mut self.insts);
+        self.insts = insts.into_iter().zip(&placed).filter(|&(_, &placed)| placed).map(|(inst, _)| inst).collect();
+        for inst in &mut self.insts {
+            inst.kind.map_operands(renumber);
+        }
+        let blocks = std::mem::take(&mut self.blocks);
+        for (mut block, id) in blocks.into_iter().zip(&block_ids) {
+            if id.is_none() {
+                continue;
+            }
+            block.insts.iter_mut().for_each(|inst| *inst = renumber(*inst));
+            block.terminator.map_operands(renumber);
+            block.terminator.map_successors(|target| block_ids[target.index()].unwrap_or(target));
+            self.blocks.push(block);
+        }
+    }
+}
+
+/// A basic block: instructions run in order, then the terminator.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    pub insts: Vec<InstId>,
+    pub terminator: Terminator,
+}
+
+/// An instruction and the type of the value it defines, if it defines one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inst {
+    pub kind: InstKind,
+    pub ty: Option<FfiType>,
+}
+
+/// What an instruction does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InstKind {
+    /// The constant, as a value.
+    Const(Constant),
+    /// Print `value` as the stack instruction printing its type does.
+    Print(InstId),
+    /// `value` converted to the type `to`: an integer to a float, a float
+    /// to an integer of `to`'s width (see `plugin_api::float_to_int`), or
+    /// an integer wrapped to `to`'s width (see `plugin_api::wrap_int`).
+    Cast { value: InstId, to: FfiType },
+    /// Call the extern function `name` with `args`, each passed as the C
+    /// type of the same index in `types`. The result is the instruction's
+    /// value if its type is `Some`, and discarded otherwise.
+    CallExtern { name: String, args: Vec<InstId>, types: Vec<FfiType> },
+    /// Run the module's function `function` with `args` in its parameters.
+    /// The value it returns is the instruction's if its type is `Some`, and
+    /// discarded otherwise.
+    Call { function: String, args: Vec<InstId> },
+    /// The value last stored in the local.
+    Load(LocalId),
+    /// Store `value` in `local`.
+    Store { local: LocalId, value: InstId },
+    /// The `i64` handle of a new, empty collection the function owns: it
+    /// is made as the allocation's constructor makes one, and freed with
+    /// `FREE_COLLECTION` when the function returns (see `expand_allocas`).
+    Alloca(Allocation),
+    /// `lhs op rhs` of two integers or two floats; `Rem` takes integers
+    /// only. Integer results wrap around at 64 bits; narrower types `Cast`
+    /// them to their width.
+    Arith { op: ArithOp, lhs: InstId, rhs: InstId },
+    /// Whether `lhs op rhs` holds, for two integers, floats or booleans.
+    Compare { op: CompareOp, lhs: InstId, rhs: InstId },
+    /// The boolean `value` negated.
+    Not(InstId),
+    /// Mark number `id`: where the code emitted for the instructions from
+    /// here on starts, as the rows of a line table need. Emits nothing.
+    Marker(u32),
+    /// Whether `lhs op rhs` of two integers of type `ty` overflows it (see
+    /// `ArithOp::overflows`).
+    Overflows { op: ArithOp, lhs: InstId, rhs: InstId, ty: FfiType },
+    /// If the boolean `condition` is true, panic: flush stdout, write
+    /// `message` to stderr, and `backtrace` if `TLANG_BACKTRACE` asks for
+    /// it, as `tlang_panic` does, then exit with 101; under `abort`, abort
+    /// without flushing instead.
+    Trap { condition: InstId, message: String, backtrace: String, abort: bool },
+}
+
+impl InstKind {
+    /// The values the instruction reads, in order.
+    pub fn operands(&self) -> Vec<InstId> {
+        match self {
+            InstKind::Print(value)
+            | InstKind::Cast { value, .. }
+            | InstKind::Store { value, .. }
+            | InstKind::Not(value)
+            | InstKind::Trap { condition: value, .. } => vec![*value],
+            InstKind::Arith { lhs, rhs, .. }
+            | InstKind::Compare { lhs, rhs, .. }
+            | InstKind::Overflows { lhs, rhs, .. } => vec![*lhs, *rhs],
+            InstKind::CallExtern { args, .. } | InstKind::Call { args, .. } => args.clone(),
+            InstKind::Const(_) | InstKind::Load(_) | InstKind::Marker(_) | InstKind::Alloca(_) => Vec::new(),
+        }
+    }
+
+    /// Replace each value the instruction reads by `f` of it.
+    pub fn map_operands(&mut self, mut f: impl FnMut(InstId) -> InstId) {
+        match self {
+            InstKind::Print(value)
+            | InstKind::Cast { value, .. }
+            | InstKind::Store { value, .. }
+            | InstKind::Not(value)
+            | InstKind::Trap { condition: value, .. } => *value = f(*value),
+            InstKind::Arith { lhs, rhs, .. }
+            | InstKind::Compare { lhs, rhs, .. }
+            | InstKind::Overflows { lhs, rhs, .. } => {
+                *lhs = f(*lhs);
+                *rhs = f(*rhs);
+            }
+            InstKind::CallExtern { args, .. } | InstKind::Call { args, .. } => {
+                args.iter_mut().for_each(|arg| *arg = f(*arg))
+            }
+            InstKind::Const(_) | InstKind::Load(_) | InstKind::Marker(_) | InstKind::Alloca(_) => {}
+        }
+    }
+
+    /// Whether running the instruction can be seen from outside the
+    /// function, so it stays even if its value is never read.
+    pub fn has_effects(&self) -> bool {
+        matches!(
+            self,
+            InstKind::Print(_)
+                | InstKind::CallExtern { .. }
+                | InstKind::Call { .. }
+                | InstKind::Store { .. }
+                | InstKind::Marker(_)
+                | InstKind::Trap { .. }
+        )
+    }
+}
+
+/// The `tstd` function freeing the collection a handle names, which an
+/// `Alloca` is freed with; a handle of none, such as -1, it leaves alone.
+pub const FREE_COLLECTION: &str = "tlang_collection_free";
+
+/// The kind of collection an `InstKind::Alloca` makes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Allocation {
+    Vec,
+    Map,
+    Set,
+}
+
+impl Allocation {
+    pub const ALL: [Allocation; 3] = [Allocation::Vec, Allocation::Map, Allocation::Set];
+
+    /// The kind as the names of the `tstd` functions for it spell it, as in
+    /// `tlang_vec_push`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Allocation::Vec => "vec",
+            Allocation::Map => "map",
+            Allocation::Set => "set",
+        }
+    }
+
+    /// The extern function making a collection of the kind, which gives
+    /// back its handle.
+    pub fn constructor(self) -> String {
+        format!("tlang_{}_new", self.name())
+    }
+
+    /// Whether `name` is of one of the `tstd` functions for the kind, which
+    /// take the handle as their first argument.
+    pub fn is_method(self, name: &str) -> bool {
+        name.strip_prefix("tlang_").and_then(|name| name.strip_prefix(self.name())).is_some_and(|name| {
+            name.starts_with('_') && name != "_new"
+        })
+    }
+}
+
+/// An arithmetic operator of `InstKind::Arith`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    /// Division, rounding toward zero.
+    Div,
+    /// Remainder of `Div`, with the sign of the dividend.
+    Rem,
+}
+
+impl ArithOp {
+    /// `a op b` of integers, wrapping around at 64 bits. A divisor of zero
+    /// gives 0, as it never runs: the lowering traps on it first.
+    pub fn int(self, a: i64, b: i64) -> i64 {
+        match self {
+            ArithOp::Add => a.wrapping_add(b),
+            ArithOp::Sub => a.wrapping_sub(b),
+            ArithOp::Mul => a.wrapping_mul(b),
+            ArithOp::Div | ArithOp::Rem if b == 0 => 0,
+            ArithOp::Div => a.wrapping_div(b),
+            ArithOp::Rem => a.wrapping_rem(b),
+        }
+    }
+
+    /// `a op b` of floats.
+    pub fn float(self, a: f64, b: f64) -> f64 {
+        match self {
+            ArithOp::Add => a + b,
+            ArithOp::Sub => a - b,
+            ArithOp::Mul => a * b,
+            ArithOp::Div => a / b,
+            ArithOp::Rem => a % b,
+        }
+    }
+
+    /// Whether the exact result of `a op b`, of integers of type `ty`, is
+    /// out of `ty`'s range, which a 64-bit unsigned type takes as the bits
+    /// the int stack holds. Nothing overflows a divisor of zero.
+    pub fn overflows(self, a: i64, b: i64, ty: FfiType) -> bool {
+        let FfiType::Int { bits, signed } = ty else {
+            return false;
+        };
+        let widen = |n: i64| if signed || bits < 64 { i128::from(n) } else { i128::from(n as u64) };
+        let (a, b) = (widen(a), widen(b));
+        let exact = match self {
+            ArithOp::Add => a.checked_add(b),
+            ArithOp::Sub => a.checked_sub(b),
+            ArithOp::Mul => a.checked_mul(b),
+            ArithOp::Div => a.checked_div(b),
+            ArithOp::Rem => a.checked_rem(b),
+        };
+        let bits = u32::from(bits.min(64));
+        let (min, max) = if signed { (-(1 << (bits - 1)), (1 << (bits - 1)) - 1) } else { (0, (1 << bits) - 1) };
+        match exact {
+            Some(n) => !(min..=max).contains(&n),
+            None => b != 0,
+        }
+    }
+
+    /// The operator as C and Rust spell it; Python divides integers with
+    /// `//`, rounding down.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+            ArithOp::Div => "/",
+            ArithOp::Rem => "%",
+        }
+    }
+}
+
+/// A comparison of `InstKind::Compare`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    /// Whether `a op b` holds. Every comparison with a float NaN is false
+    /// but `Ne`, as in C.
+    pub fn holds<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            CompareOp::Eq => a == b,
+            CompareOp::Ne => a != b,
+            CompareOp::Lt => a < b,
+            CompareOp::Le => a <= b,
+            CompareOp::Gt => a > b,
+            CompareOp::Ge => a >= b,
+        }
+    }
+
+    /// The operator as C, Rust and Python spell it.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+/// How a block ends.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Terminator {
+    /// Return from the function; the entry function ends the program, with
+    /// exit code 0.
+    #[default]
+    Return,
+    /// Continue at the block.
+    Jump(BlockId),
+    /// Continue at `then` if the boolean `condition` is true, else at
+    /// `otherwise`.
+    Branch { condition: InstId, then: BlockId, otherwise: BlockId },
+    /// End the program with the integer `code` as its exit code.
+    Exit(InstId),
+    /// Continue at the block of the first of `cases` whose value the
+    /// integer `value` is, else at `default`.
+    Switch { value: InstId, cases: Vec<(i64, BlockId)>, default: BlockId },
+    /// Never reached, as after a call that does not return.
+    Unreachable,
+}
+
+impl Terminator {
+    /// The values the terminator reads.
+    pub fn operands(&self) -> Vec<InstId> {
+        match self {
+            Terminator::Branch { condition, .. } => vec![*condition],
+            Terminator::Exit(code) => vec![*code],
+            Terminator::Switch { value, .. } => vec![*value],
+            Terminator::Return | Terminator::Jump(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    /// The blocks control may go to next: for a `Switch`, those of its
+    /// cases in order, then its default.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch { then, otherwise, .. } => vec![*then, *otherwise],
+            Terminator::Switch { cases, default, .. } => {
+                cases.iter().map(|&(_, target)| target).chain([*default]).collect()
+            }
+            Terminator::Return | Terminator::Exit(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    /// Replace each value the terminator reads by `f` of it.
+    pub fn map_operands(&mut self, mut f: impl FnMut(InstId) -> InstId) {
+        match self {
+            Terminator::Branch { condition: value, .. }
+            | Terminator::Exit(value)
+            | Terminator::Switch { value, .. } => {
+                *value = f(*value)
+            }
+            Terminator::Return | Terminator::Jump(_) | Terminator::Unreachable => {}
+        }
+    }
+
+    /// Replace each block the terminator may go to by `f` of it.
+    pub fn map_successors(&mut self, mut f: impl FnMut(BlockId) -> BlockId) {
+        match self {
+            Terminator::Jump(target) => *target = f(*target),
+            Terminator::Branch { then, otherwise, .. } => {
+                *then = f(*then);
+                *otherwise = f(*otherwise);
+            }
+            Terminator::Switch { cases, default, .. } => {
+                for (_, target) in cases {
+                    *target = f(*target);
+                }
+                *default = f(*default);
+            }
+            Terminator::Return | Terminator::Exit(_) | Terminator::Unreachable => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_and_instructions_are_indexed_in_order() {
+        let mut function = Function::new("main");
+        let exit = function.add_block();
+        let one = function.push(BlockId::ENTRY, InstKind::Const(Constant::Int(1)), Some(FfiType::INT));
+        function.push(BlockId::ENTRY, InstKind::Print(one), None);
+        function.terminate(BlockId::ENTRY, Terminator::Jump(exit));
+        function.terminate(exit, Terminator::Exit(one));
+
+        assert_eq!(exit, BlockId(1));
+        assert_eq!(function.block(BlockId::ENTRY).insts, [InstId(0), InstId(1)]);
+        assert_eq!(function.inst(InstId(1)).kind.operands(), [one]);
+        assert_eq!(function.ty(one), Some(FfiType::INT));
+        assert_eq!(function.block(BlockId::ENTRY).terminator.successors(), [exit]);
+        let ids: Vec<BlockId> = function.iter_blocks().map(|(id, _)| id).collect();
+        assert_eq!(ids, [BlockId::ENTRY, exit]);
+    }
+
+    #[test]
+    fn overflow_is_out_of_the_range_of_the_type() {
+        let i8 = FfiType::Int { bits: 8, signed: true };
+        assert!(ArithOp::Add.overflows(100, 28, i8));
+        assert!(!ArithOp::Add.overflows(100, 27, i8));
+        assert!(ArithOp::Div.overflows(-128, -1, i8));
+        assert!(!ArithOp::Rem.overflows(-128, -1, i8));
+        assert!(ArithOp::Sub.overflows(0, 1, FfiType::Int { bits: 32, signed: false }));
+        assert!(ArithOp::Mul.overflows(i64::MAX, 2, FfiType::INT));
+        // The bits of a `u64` above `i64::MAX`, read as unsigned.
+        let u64 = FfiType::Int { bits: 64, signed: false };
+        assert!(!ArithOp::Add.overflows(i64::MAX, 1, u64));
+        assert!(ArithOp::Add.overflows(-1, 1, u64));
+        assert!(!ArithOp::Div.overflows(1, 0, FfiType::INT));
+        assert_eq!(ArithOp::Div.int(-7, 2), -3);
+        assert_eq!(ArithOp::Rem.int(-7, 2), -1);
+    }
 }
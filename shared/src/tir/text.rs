@@ -0,0 +1,745 @@
+//! The textual form of TIR: what `Display` prints and `FromStr` parses
+//! back, to the same module.
+//!
+//! A module lists its extern functions, its globals, then its functions:
+//!
+//! ```text
+//! extern fn printf(str, ...) -> i32
+//! global mut count: i64 = 0
+//!
+//! fn main {
+//!     local l0: i64
+//! bb0:
+//!     %0: i64 = const 2
+//!     %1 = store l0, %0
+//!     %2: bool = lt %0, %0
+//!     branch %2, bb1, bb1
+//! bb1:
+//!     %3: i64 = load l0
+//!     exit %3
+//! }
+//! ```
+//!
+//! A function other than the entry point names the locals its parameters
+//! are and the one it returns, and its inline attribute, as in
+//! `#[inline(never)] fn add(l0, l1) -> l2 {`, and a `Call` of it is written
+//! `call @add(%3, %4)`. A function declares its locals first, then its
+//! blocks in layout order, each a label, its instructions, and its
+//! terminator. Every instruction is
+//! written with its id, and its type if it has a value, so the ids a module
+//! is read back with are those it was written with. Types are spelled as in
+//! Rust, `str` for a C string. A `Switch` names its default block before
+//! its cases, as in `switch %4, bb3 [0: bb1, 7: bb2]`. Strings are quoted
+//! and escaped as Rust's `Debug` does, and floats written as it does, so
+//! they keep every bit.
+
+use super::{
+    Allocation, ArithOp, Block, BlockId, CompareOp, Constant, ExternFunction, FfiType, Function, Global, Inline, Inst,
+    InstId, InstKind, LocalId, Module, Terminator,
+};
+use std::fmt;
+use std::str::FromStr;
+
+impl fmt::Display for FfiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FfiType::Int { bits, signed: true } => write!(f, "i{}", bits),
+            FfiType::Int { bits, signed: false } => write!(f, "u{}", bits),
+            FfiType::Float { bits } => write!(f, "f{}", bits),
+            FfiType::Bool => f.write_str("bool"),
+            FfiType::Str => f.write_str("str"),
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Int(n) => write!(f, "{}", n),
+            Constant::Float(x) => write!(f, "{:?}", x),
+            Constant::Bool(b) => write!(f, "{}", b),
+            Constant::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+impl ArithOp {
+    /// The operator's name in textual TIR.
+    pub fn name(self) -> &'static str {
+        match self {
+            ArithOp::Add => "add",
+            ArithOp::Sub => "sub",
+            ArithOp::Mul => "mul",
+            ArithOp::Div => "div",
+            ArithOp::Rem => "rem",
+        }
+    }
+}
+
+impl CompareOp {
+    /// The comparison's name in textual TIR.
+    pub fn name(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "eq",
+            CompareOp::Ne => "ne",
+            CompareOp::Lt => "lt",
+            CompareOp::Le => "le",
+            CompareOp::Gt => "gt",
+            CompareOp::Ge => "ge",
+        }
+    }
+}
+
+const ARITH_OPS: [ArithOp; 5] = [ArithOp::Add, ArithOp::Sub, ArithOp::Mul, ArithOp::Div, ArithOp::Rem];
+const COMPARE_OPS: [CompareOp; 6] =
+    [CompareOp::Eq, CompareOp::Ne, CompareOp::Lt, CompareOp::Le, CompareOp::Gt, CompareOp::Ge];
+
+impl fmt::Display for InstKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstKind::Const(constant) => write!(f, "const {}", constant),
+            InstKind::Print(value) => write!(f, "print %{}", value.0),
+            InstKind::Cast { value, to } => write!(f, "cast %{} to {}", value.0, to),
+            InstKind::CallExtern { name, args, types } => {
+                write!(f, "call {}(", name)?;
+                for (i, (arg, ty)) in args.iter().zip(types).enumerate() {
+                    write!(f, "{}%{}: {}", if i == 0 { "" } else { ", " }, arg.0, ty)?;
+                }
+                f.write_str(")")
+            }
+            InstKind::Call { function, args } => {
+                let args: Vec<String> = args.iter().map(|arg| format!("%{}", arg.0)).collect();
+                write!(f, "call @{}({})", function, args.join(", "))
+            }
+            InstKind::Load(local) => write!(f, "load l{}", local.0),
+            InstKind::Store { local, value } => write!(f, "store l{}, %{}", local.0, value.0),
+            InstKind::Alloca(allocation) => write!(f, "alloca {}", allocation.name()),
+            InstKind::Arith { op, lhs, rhs } => write!(f, "{} %{}, %{}", op.name(), lhs.0, rhs.0),
+            InstKind::Compare { op, lhs, rhs } => write!(f, "{} %{}, %{}", op.name(), lhs.0, rhs.0),
+            InstKind::Not(value) => write!(f, "not %{}", value.0),
+            InstKind::Marker(n) => write!(f, "marker {}", n),
+            InstKind::Overflows { op, lhs, rhs, ty } => {
+                write!(f, "overflows {} %{}, %{} as {}", op.name(), lhs.0, rhs.0, ty)
+            }
+            InstKind::Trap { condition, message, backtrace, abort } => {
+                write!(f, "trap %{}, {:?}, {:?}{}", condition.0, message, backtrace, if *abort { " abort" } else { "" })
+            }
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Return => f.write_str("return"),
+            Terminator::Jump(target) => write!(f, "jump bb{}", target.0),
+            Terminator::Branch { condition, then, otherwise } => {
+                write!(f, "branch %{}, bb{}, bb{}", condition.0, then.0, otherwise.0)
+            }
+            Terminator::Exit(code) => write!(f, "exit %{}", code.0),
+            Terminator::Switch { value, cases, default } => {
+                write!(f, "switch %{}, bb{} [", value.0, default.0)?;
+                for (i, (n, target)) in cases.iter().enumerate() {
+                    write!(f, "{}{}: bb{}", if i == 0 { "" } else { ", " }, n, target.0)?;
+                }
+                f.write_str("]")
+            }
+            Terminator::Unreachable => f.write_str("unreachable"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.inline {
+            Inline::Auto => {}
+            Inline::Always => f.write_str("#[inline] ")?,
+            Inline::Never => f.write_str("#[inline(never)] ")?,
+        }
+        write!(f, "fn {}", self.name)?;
+        if self.params > 0 || self.result.is_some() {
+            let params: Vec<String> = (0..self.params).map(|i| format!("l{}", i)).collect();
+            write!(f, "({})", params.join(", "))?;
+        }
+        if let Some(result) = self.result {
+            write!(f, " -> l{}", result.0)?;
+        }
+        writeln!(f, " {{")?;
+        for (i, ty) in self.locals.iter().enumerate() {
+            writeln!(f, "    local l{}: {}", i, ty)?;
+        }
+        for (id, block) in self.iter_blocks() {
+            writeln!(f, "bb{}:", id.0)?;
+            for &inst in &block.insts {
+                let Inst { kind, ty } = self.inst(inst);
+                match ty {
+                    Some(ty) => writeln!(f, "    %{}: {} = {}", inst.0, ty, kind)?,
+                    None => writeln!(f, "    %{} = {}", inst.0, kind)?,
+                }
+            }
+            writeln!(f, "    {}", block.terminator)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in &self.externs {
+            let mut params: Vec<String> = function.params.iter().map(ToString::to_string).collect();
+            if function.variadic {
+                params.push("...".to_string());
+            }
+            write!(f, "extern fn {}({})", function.name, params.join(", "))?;
+            match function.return_type {
+                Some(ty) => writeln!(f, " -> {}", ty)?,
+                None => writeln!(f)?,
+            }
+        }
+        for global in &self.globals {
+            let mutable = if global.mutable { "mut " } else { "" };
+            writeln!(f, "global {}{}: {} = {}", mutable, global.name, global.ty, global.value)?;
+        }
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 || !self.externs.is_empty() || !self.globals.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Module {
+    type Err = String;
+
+    /// The module `text` writes, or what is wrong with it, and on which line.
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut module = Module::default();
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim()));
+        while let Some((n, line)) = lines.next() {
+            let located = |message: String| format!("line {}: {}", n, message);
+            if line.is_empty() {
+                continue;
+            }
+            let mut cursor = Cursor(line);
+            if cursor.eat("extern fn") {
+                let function = extern_function(&mut cursor).map_err(located)?;
+                module.externs.push(function);
+            } else if cursor.eat("global") {
+                let global = global(&mut cursor).map_err(located)?;
+                module.globals.push(global);
+            } else if line.starts_with("fn") || line.starts_with("#[") {
+                let header = header(&mut cursor).map_err(located)?;
+                let function = function(header, &mut lines)?;
+                module.functions.push(function);
+            } else {
+                return Err(located(format!("expected `extern fn`, `global` or `fn`, found `{}`", line)));
+            }
+        }
+        Ok(module)
+    }
+}
+
+/// The rest of an `extern fn` line.
+fn extern_function(cursor: &mut Cursor) -> Result<ExternFunction, String> {
+    let name = cursor.word()?;
+    cursor.expect("(")?;
+    let (mut params, mut variadic) = (Vec::new(), false);
+    while !cursor.eat(")") {
+        if !params.is_empty() || variadic {
+            cursor.expect(",")?;
+        }
+        if cursor.eat("...") {
+            variadic = true;
+        } else if variadic {
+            return Err("`...` must come last".to_string());
+        } else {
+            params.push(cursor.ty()?);
+        }
+    }
+    let return_type = if cursor.eat("->") { Some(cursor.ty()?) } else { None };
+    cursor.end()?;
+    Ok(ExternFunction { name, params, return_type, variadic })
+}
+
+/// The rest of a `global` line.
+fn global(cursor: &mut Cursor) -> Result<Global, String> {
+    let mutable = cursor.eat("mut ");
+    let name = cursor.word()?;
+    cursor.expect(":")?;
+    let ty = cursor.ty()?;
+    cursor.expect("=")?;
+    let value = cursor.constant()?;
+    cursor.end()?;
+    Ok(Global { name, ty, value, mutable })
+}
+
+/// A `fn` line, as the function it starts with nothing in it yet.
+fn header(cursor: &mut Cursor) -> Result<Function, String> {
+    let inline = if cursor.eat("#[inline(never)]") {
+        Inline::Never
+    } else if cursor.eat("#[inline]") {
+        Inline::Always
+    } else {
+        Inline::Auto
+    };
+    cursor.expect("fn")?;
+    let mut function = Function::new(cursor.word()?);
+    function.inline = inline;
+    if cursor.eat("(") {
+        while !cursor.eat(")") {
+            if function.params > 0 {
+                cursor.expect(",")?;
+            }
+            let param = cursor.local()?;
+            if param.index() != function.params {
+                return Err(format!("parameter l{} is where l{} is next", param.0, function.params));
+            }
+            function.params += 1;
+        }
+    }
+    if cursor.eat("->") {
+        function.result = Some(cursor.local()?);
+    }
+    cursor.expect("{")?;
+    cursor.end()?;
+    Ok(function)
+}
+
+/// The function `header` starts, from the lines after its `fn` line to its
+/// `}`.
+fn function<'a>(header: Function, lines: &mut impl Iterator<Item = (usize, &'a str)>) -> Result<Function, String> {
+    let name = header.name.clone();
+    let mut locals = Vec::new();
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut insts: Vec<Option<Inst>> = Vec::new();
+    // Whether the last block has its terminator yet.
+    let mut terminated = true;
+    for (n, line) in lines.by_ref() {
+        let located = |message: String| format!("line {}: {}", n, message);
+        let mut cursor = Cursor(line);
+        if line.is_empty() {
+            continue;
+        }
+        if line == "}" {
+            if !terminated {
+                return Err(located(format!("bb{} has no terminator", blocks.len() - 1)));
+            }
+            if blocks.is_empty() {
+                return Err(located(format!("`{}` has no blocks", name)));
+            }
+            let mut placed = Vec::with_capacity(insts.len());
+            for (i, inst) in insts.into_iter().enumerate() {
+                placed.push(inst.ok_or_else(|| located(format!("`{}` has no instruction %{}", name, i)))?);
+            }
+            let declared = header.params.max(header.result.map_or(0, |result| result.index() + 1));
+            if declared > locals.len() {
+                return Err(located(format!("`{}` has no local l{}", name, declared - 1)));
+            }
+            return Ok(Function { blocks, insts: placed, locals, ..header });
+        }
+        let parsed = if cursor.eat("local") {
+            cursor.local().and_then(|local| {
+                if local.index() != locals.len() {
+                    return Err(format!("local l{} is declared where l{} is next", local.0, locals.len()));
+                }
+                if !blocks.is_empty() {
+                    return Err("locals must be declared before the blocks".to_string());
+                }
+                cursor.expect(":")?;
+                locals.push(cursor.ty()?);
+                cursor.end()
+            })
+        } else if let Some(label) = line.strip_suffix(':').filter(|label| label.starts_with("bb")) {
+            Cursor(label).block().and_then(|block| {
+                if block.index() != blocks.len() {
+                    return Err(format!("bb{} is labelled where bb{} is next", block.0, blocks.len()));
+                }
+                if !terminated {
+                    return Err(format!("bb{} has no terminator", blocks.len() - 1));
+                }
+                blocks.push(Block::default());
+                terminated = false;
+                Ok(())
+            })
+        } else if blocks.is_empty() || terminated {
+            Err(format!("`{}` is outside of any block", line))
+        } else if line.starts_with('%') {
+            instruction(&mut cursor).and_then(|(id, inst)| {
+                if insts.len() <= id.index() {
+                    insts.resize(id.index() + 1, None);
+                }
+                if insts[id.index()].replace(inst).is_some() {
+                    return Err(format!("%{} is defined twice", id.0));
+                }
+                blocks.last_mut().expect("a block is open").insts.push(id);
+                Ok(())
+            })
+        } else {
+            terminator(&mut cursor).map(|terminator| {
+                blocks.last_mut().expect("a block is open").terminator = terminator;
+                terminated = true;
+            })
+        };
+        parsed.map_err(located)?;
+    }
+    Err(format!("`{}` has no closing `}}`", name))
+}
+
+/// An instruction line.
+fn instruction(cursor: &mut Cursor) -> Result<(InstId, Inst), String> {
+    let id = cursor.value()?;
+    let ty = if cursor.eat(":") { Some(cursor.ty()?) } else { None };
+    cursor.expect("=")?;
+    let op = cursor.word()?;
+    let binary = |cursor: &mut Cursor| -> Result<(InstId, InstId), String> {
+        let lhs = cursor.value()?;
+        cursor.expect(",")?;
+        Ok((lhs, cursor.value()?))
+    };
+    let kind = match op.as_str() {
+        "const" => InstKind::Const(cursor.constant()?),
+        "print" => InstKind::Print(cursor.value()?),
+        "cast" => {
+            let value = cursor.value()?;
+            cursor.expect("to")?;
+            InstKind::Cast { value, to: cursor.ty()? }
+        }
+        "call" if cursor.eat("@") => {
+            let function = cursor.word()?;
+            cursor.expect("(")?;
+            let mut args = Vec::new();
+            while !cursor.eat(")") {
+                if !args.is_empty() {
+                    cursor.expect(",")?;
+                }
+                args.push(cursor.value()?);
+            }
+            InstKind::Call { function, args }
+        }
+        "call" => {
+            let name = cursor.word()?;
+            cursor.expect("(")?;
+            let (mut args, mut types) = (Vec::new(), Vec::new());
+            while !cursor.eat(")") {
+                if !args.is_empty() {
+                    cursor.expect(",")?;
+                }
+                args.push(cursor.value()?);
+                cursor.expect(":")?;
+                types.push(cursor.ty()?);
+            }
+            InstKind::CallExtern { name, args, types }
+        }
+        "load" => InstKind::Load(cursor.local()?),
+        "store" => {
+            let local = cursor.local()?;
+            cursor.expect(",")?;
+            InstKind::Store { local, value: cursor.value()? }
+        }
+        "alloca" => {
+            let name = cursor.word()?;
+            let allocation = Allocation::ALL.into_iter().find(|a| a.name() == name);
+            InstKind::Alloca(allocation.ok_or(format!("no collection `{}`", name))?)
+        }
+        "not" => InstKind::Not(cursor.value()?),
+        "marker" => InstKind::Marker(cursor.number()?),
+        "overflows" => {
+            let op = cursor.word()?;
+            let op = ARITH_OPS.into_iter().find(|o| o.name() == op).ok_or(format!("no operator `{}`", op))?;
+            let (lhs, rhs) = binary(cursor)?;
+            cursor.expect("as")?;
+            InstKind::Overflows { op, lhs, rhs, ty: cursor.ty()? }
+        }
+        "trap" => {
+            let condition = cursor.value()?;
+            cursor.expect(",")?;
+            let message = cursor.string()?;
+            cursor.expect(",")?;
+            let backtrace = cursor.string()?;
+            InstKind::Trap { condition, message, backtrace, abort: cursor.eat("abort") }
+        }
+        op => {
+            if let Some(op) = ARITH_OPS.into_iter().find(|o| o.name() == op) {
+                let (lhs, rhs) = binary(cursor)?;
+                InstKind::Arith { op, lhs, rhs }
+            } else if let Some(op) = COMPARE_OPS.into_iter().find(|o| o.name() == op) {
+                let (lhs, rhs) = binary(cursor)?;
+                InstKind::Compare { op, lhs, rhs }
+            } else {
+                return Err(format!("no instruction `{}`", op));
+            }
+        }
+    };
+    cursor.end()?;
+    Ok((id, Inst { kind, ty }))
+}
+
+/// A terminator line.
+fn terminator(cursor: &mut Cursor) -> Result<Terminator, String> {
+    let terminator = match cursor.word()?.as_str() {
+        "return" => Terminator::Return,
+        "jump" => Terminator::Jump(cursor.block()?),
+        "branch" => {
+            let condition = cursor.value()?;
+            cursor.expect(",")?;
+            let then = cursor.block()?;
+            cursor.expect(",")?;
+            Terminator::Branch { condition, then, otherwise: cursor.block()? }
+        }
+        "exit" => Terminator::Exit(cursor.value()?),
+        "switch" => {
+            let value = cursor.value()?;
+            cursor.expect(",")?;
+            let default = cursor.block()?;
+            cursor.expect("[")?;
+            let mut cases = Vec::new();
+            while !cursor.eat("]") {
+                if !cases.is_empty() {
+                    cursor.expect(",")?;
+                }
+                let n = cursor.number()?;
+                cursor.expect(":")?;
+                cases.push((n, cursor.block()?));
+            }
+            Terminator::Switch { value, cases, default }
+        }
+        "unreachable" => Terminator::Unreachable,
+        word => return Err(format!("expected an instruction or a terminator, found `{}`", word)),
+    };
+    cursor.end()?;
+    Ok(terminator)
+}
+
+/// What is left of a line to parse.
+struct Cursor<'a>(&'a str);
+
+impl Cursor<'_> {
+    /// Skip `token`, and the whitespace before it, if the line goes on with it.
+    fn eat(&mut self, token: &str) -> bool {
+        let rest = self.0.trim_start();
+        match rest.strip_prefix(token) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) { Ok(()) } else { Err(format!("expected `{}` at `{}`", token, self.0.trim())) }
+    }
+
+    /// That nothing is left.
+    fn end(&self) -> Result<(), String> {
+        match self.0.trim() {
+            "" => Ok(()),
+            rest => Err(format!("unexpected `{}`", rest)),
+        }
+    }
+
+    /// A name, keyword or number: a run of letters, digits, `_`, `.`, `-` and `+`.
+    fn word(&mut self) -> Result<String, String> {
+        let rest = self.0.trim_start();
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || "_.-+".contains(c))).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(format!("expected a word at `{}`", rest));
+        }
+        self.0 = &rest[len..];
+        Ok(rest[..len].to_string())
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, String> {
+        let word = self.word()?;
+        word.parse().map_err(|_| format!("`{}` is not a number", word))
+    }
+
+    /// `%n`
+    fn value(&mut self) -> Result<InstId, String> {
+        self.expect("%")?;
+        self.number().map(InstId)
+    }
+
+    /// `ln`
+    fn local(&mut self) -> Result<LocalId, String> {
+        self.expect("l")?;
+        self.number().map(LocalId)
+    }
+
+    /// `bbn`
+    fn block(&mut self) -> Result<BlockId, String> {
+        self.expect("bb")?;
+        self.number().map(BlockId)
+    }
+
+    fn ty(&mut self) -> Result<FfiType, String> {
+        let word = self.word()?;
+        let bits = |digits: &str| digits.parse::<u8>().ok().filter(|bits| [8, 16, 32, 64].contains(bits));
+        let ty = match word.as_str() {
+            "bool" => Some(FfiType::Bool),
+            "str" => Some(FfiType::Str),
+            "f32" => Some(FfiType::Float { bits: 32 }),
+            "f64" => Some(FfiType::FLOAT),
+            _ => match word.split_at_checked(1) {
+                Some(("i", digits)) => bits(digits).map(|bits| FfiType::Int { bits, signed: true }),
+                Some(("u", digits)) => bits(digits).map(|bits| FfiType::Int { bits, signed: false }),
+                _ => None,
+            },
+        };
+        ty.ok_or_else(|| format!("no type `{}`", word))
+    }
+
+    fn constant(&mut self) -> Result<Constant, String> {
+        if self.0.trim_start().starts_with('"') {
+            return self.string().map(Constant::Str);
+        }
+        let word = self.word()?;
+        match word.as_str() {
+            "true" => Ok(Constant::Bool(true)),
+            "false" => Ok(Constant::Bool(false)),
+            _ if word.contains(['.', 'e', 'N', 'i']) => {
+                word.parse().map(Constant::Float).map_err(|_| format!("`{}` is not a float", word))
+            }
+            _ => word.parse().map(Constant::Int).map_err(|_| format!("`{}` is not an integer", word)),
+        }
+    }
+
+    /// A string quoted and escaped as Rust's `Debug` writes it.
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut string = String::new();
+        let mut chars = self.0.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.0 = &self.0[i + 1..];
+                    return Ok(string);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('0') => '\0',
+                        Some(c @ ('\\' | '"' | '\'')) => c,
+                        Some('u') => {
+                            let digits: String = chars.by_ref().map(|(_, c)| c).take_while(|&c| c != '}').collect();
+                            let code = digits.strip_prefix('{').and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                            code.and_then(char::from_u32).ok_or(format!("bad escape `\\u{}}}`", digits))?
+                        }
+                        other => return Err(format!("bad escape `\\{}`", other.map(String::from).unwrap_or_default())),
+                    };
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+        Err("the string has no closing quote".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modules_read_back_as_they_were_written() {
+        let mut function = Function::new("main");
+        let x = function.add_local(FfiType::Int { bits: 32, signed: false });
+        let (exit, pick) = (function.add_block(), function.add_block());
+        let entry = BlockId::ENTRY;
+        let text = function.push(entry, InstKind::Const(Constant::Str("a \"b\"\n\u{7f}é".into())), Some(FfiType::Str));
+        let half = function.push(entry, InstKind::Const(Constant::Float(-0.5e-20)), Some(FfiType::FLOAT));
+        let two = function.push(entry, InstKind::Const(Constant::Int(-2)), Some(FfiType::INT));
+        function.push(entry, InstKind::Marker(0), None);
+        let args = vec![text, half];
+        let types = vec![FfiType::Str, FfiType::Float { bits: 64 }];
+        function.push(
+            entry,
+            InstKind::CallExtern { name: "printf".into(), args, types },
+            Some(FfiType::Int { bits: 32, signed: true }),
+        );
+        let narrow = function.push(
+            entry,
+            InstKind::Cast { value: two, to: FfiType::Int { bits: 32, signed: false } },
+            Some(FfiType::INT),
+        );
+        function.push(entry, InstKind::Store { local: x, value: narrow }, None);
+        let sum = function.push(entry, InstKind::Arith { op: ArithOp::Rem, lhs: two, rhs: narrow }, Some(FfiType::INT));
+        let over = function.push(
+            entry,
+            InstKind::Overflows { op: ArithOp::Mul, lhs: sum, rhs: two, ty: FfiType::Int { bits: 8, signed: true } },
+            Some(FfiType::Bool),
+        );
+        function.push(
+            entry,
+            InstKind::Trap { condition: over, message: "overflow".into(), backtrace: String::new(), abort: true },
+            None,
+        );
+        let less =
+            function.push(entry, InstKind::Compare { op: CompareOp::Ge, lhs: sum, rhs: two }, Some(FfiType::Bool));
+        let more = function.push(entry, InstKind::Not(less), Some(FfiType::Bool));
+        function.terminate(entry, Terminator::Branch { condition: more, then: exit, otherwise: pick });
+        let code = function.push(exit, InstKind::Load(x), Some(FfiType::INT));
+        function.push(exit, InstKind::Print(code), None);
+        function.terminate(exit, Terminator::Exit(code));
+        let value = function.push(pick, InstKind::Load(x), Some(FfiType::INT));
+        function.push(pick, InstKind::Call { function: "add".into(), args: vec![two, two] }, Some(FfiType::INT));
+        function.terminate(pick, Terminator::Switch { value, cases: vec![(-1, exit), (3, pick)], default: exit });
+        let mut add = Function::new("add");
+        let (a, b) = (add.add_local(FfiType::INT), add.add_local(FfiType::INT));
+        let sum = add.add_local(FfiType::INT);
+        (add.params, add.result, add.inline) = (2, Some(sum), Inline::Never);
+        let a = add.push(entry, InstKind::Load(a), Some(FfiType::INT));
+        let b = add.push(entry, InstKind::Load(b), Some(FfiType::INT));
+        let value = add.push(entry, InstKind::Arith { op: ArithOp::Add, lhs: a, rhs: b }, Some(FfiType::INT));
+        add.push(entry, InstKind::Store { local: sum, value }, None);
+        add.push(entry, InstKind::Alloca(Allocation::Set), Some(FfiType::INT));
+        let module = Module {
+            functions: vec![function, add],
+            externs: vec![ExternFunction {
+                name: "printf".into(),
+                params: vec![FfiType::Str],
+                return_type: Some(FfiType::Int { bits: 32, signed: true }),
+                variadic: true,
+            }],
+            globals: vec![Global {
+                name: "LIMIT".into(),
+                ty: FfiType::Bool,
+                value: Constant::Bool(false),
+                mutable: true,
+            }],
+        };
+
+        let text = module.to_string();
+        assert!(
+            text.starts_with("extern fn printf(str, ...) -> i32\nglobal mut LIMIT: bool = false\n\nfn main {\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("    %4: i32 = call printf(%0: str, %1: f64)\n"), "{}", text);
+        assert!(text.contains("    %9 = trap %8, \"overflow\", \"\" abort\n"), "{}", text);
+        assert!(text.contains("    switch %14, bb1 [-1: bb1, 3: bb2]\n"), "{}", text);
+        assert!(text.contains("    %15: i64 = call @add(%2, %2)\n"), "{}", text);
+        assert!(text.contains("\n#[inline(never)] fn add(l0, l1) -> l2 {\n"), "{}", text);
+        assert!(text.contains("    %4: i64 = alloca set\n"), "{}", text);
+        assert_eq!(text.parse::<Module>(), Ok(module));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let parse = |text: &str| text.parse::<Module>().unwrap_err();
+        assert_eq!(parse("fn main {\nbb0:\n    %0: i64 = const 1\n}"), "line 4: bb0 has no terminator");
+        assert_eq!(
+            parse("fn main {\nbb0:\n    %1: i64 = const 1\n    return\n}"),
+            "line 5: `main` has no instruction %0"
+        );
+        assert_eq!(parse("fn main {\nbb0:\n    %0: i7 = const 1\n"), "line 3: no type `i7`");
+        assert_eq!(parse("fn main {\nbb1:\n"), "line 2: bb1 is labelled where bb0 is next");
+        assert_eq!(parse("fn main {\nbb0:\n    return\n"), "`main` has no closing `}`");
+        assert_eq!(parse("fn add(l1) {\n"), "line 1: parameter l1 is where l0 is next");
+        assert_eq!(parse("fn add(l0) {\nbb0:\n    return\n}"), "line 4: `add` has no local l0");
+    }
+}
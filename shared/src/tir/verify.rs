@@ -0,0 +1,317 @@
+//! Checks that a TIR module is well formed, so a pass that breaks it is
+//! caught where it ran rather than in whichever backend trips over it.
+//!
+//! A function is well formed if each instruction is in at most one block;
+//! each operand names an instruction with a value, defined before it in its
+//! block or in a block that dominates it; each terminator goes to blocks
+//! the function has and reads values of the types it needs, and a `Switch`
+//! has each case value once; the entry has no predecessors; and each `Load`
+//! and `Store` names a local of the type it moves, each `Alloca` gives an
+//! integer handle, and the parameters and result are locals the function
+//! has. Blocks control cannot reach are
+//! held to all of it but dominance, which means nothing there.
+
+use super::cfg::Cfg;
+use super::{BlockId, FfiType, Function, InstId, InstKind, LocalId, Module, Terminator, FREE_COLLECTION};
+
+/// Check every function of `module`; that the entry point takes no
+/// arguments; that each `CallExtern` calls an extern function it declares
+/// with as many arguments as it has types, and each `Alloca` one it
+/// declares the allocation's constructor and `FREE_COLLECTION`, which it
+/// is expanded to call; and that each `Call` calls a
+/// function it has with arguments of its parameters' types, taking a value
+/// of its result's type if it takes one. The error says what is wrong, and
+/// where.
+pub fn verify(module: &Module) -> Result<(), String> {
+    if let Some(entry) = module.functions.first()
+        && entry.params > 0
+    {
+        return Err(format!("the entry point `{}` takes arguments", entry.name));
+    }
+    for function in &module.functions {
+        verify_function(function)?;
+        for &id in function.blocks.iter().flat_map(|block| &block.insts) {
+            let inst = function.inst(id);
+            if let InstKind::Call { function: name, args } = &inst.kind {
+                let fail = |message: String| Err(format!("`{}`: {:?} {}", function.name, id, message));
+                let Some(callee) = module.function(name) else {
+                    return fail(format!("calls `{}`, which the module does not have", name));
+                };
+                let types: Vec<Option<FfiType>> = args.iter().map(|&arg| function.ty(arg)).collect();
+                let params: Vec<Option<FfiType>> = callee.param_types().iter().map(|&ty| Some(ty)).collect();
+                if carried(&types) != carried(&params) {
+                    return fail(format!("calls `{}` with {:?} for parameters of {:?}", name, types, params));
+                }
+                let result = callee.result_type();
+                if inst.ty.is_some() && carried(&[inst.ty]) != carried(&[result]) {
+                    return fail(format!("takes {:?} from `{}`, which returns {:?}", inst.ty, name, result));
+                }
+            }
+            if let InstKind::Alloca(allocation) = inst.kind
+                && let Some(name) = [allocation.constructor(), FREE_COLLECTION.to_string()]
+                    .into_iter()
+                    .find(|name| module.extern_function(name).is_none())
+            {
+                return Err(format!("`{}` allocates with `{}`, which the module does not declare", function.name, name));
+            }
+            if let InstKind::CallExtern { name, args, types } = &inst.kind {
+                if module.extern_function(name).is_none() {
+                    return Err(format!("`{}` calls `{}`, which the module does not declare", function.name, name));
+                }
+                if args.len() != types.len() {
+                    return Err(format!(
+                        "`{}` calls `{}` with {} arguments of {} types",
+                        function.name,
+                        name,
+                        args.len(),
+                        types.len()
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The types `types` are carried as.
+fn carried(types: &[Option<FfiType>]) -> Vec<Option<FfiType>> {
+    types.iter().map(|ty| ty.map(FfiType::carried)).collect()
+}
+
+/// Check that `function` is well formed.
+pub fn verify_function(function: &Function) -> Result<(), String> {
+    let fail = |message: String| Err(format!("`{}`: {}", function.name, message));
+    let n = function.blocks.len() as u32;
+    if n == 0 {
+        return fail("a function without blocks has no entry".to_string());
+    }
+    if function.params > function.locals.len() {
+        return fail(format!("{} parameters are more than its {} locals", function.params, function.locals.len()));
+    }
+    if let Some(result) = function.result
+        && result.index() >= function.locals.len()
+    {
+        return fail(format!("its result is local {}, which it does not have", result.0));
+    }
+
+    // Where each instruction is: its block and its place in it.
+    let mut place: Vec<Option<(BlockId, usize)>> = vec![None; function.insts.len()];
+    for (id, block) in function.iter_blocks() {
+        for (i, &inst) in block.insts.iter().enumerate() {
+            match place.get_mut(inst.index()) {
+                None => return fail(format!("{:?} of {:?} is not an instruction of the function", inst, id)),
+                Some(Some((other, _))) => return fail(format!("{:?} is in both {:?} and {:?}", inst, other, id)),
+                Some(slot) => *slot = Some((id, i)),
+            }
+        }
+        if let Some(target) = block.terminator.successors().into_iter().find(|target| target.0 >= n) {
+            return fail(format!("{:?} goes to {:?}, which the function does not have", id, target));
+        }
+    }
+
+    let cfg = Cfg::new(function);
+    if !cfg.predecessors(BlockId::ENTRY).is_empty() {
+        return fail("the entry block is the target of a branch".to_string());
+    }
+
+    // Whether `value` can be read at place `at` of `block`, the end of it
+    // for its terminator, and the type it has.
+    let read = |value: InstId, block: BlockId, at: usize| -> Result<FfiType, String> {
+        let Some(&Some((defined, i))) = place.get(value.index()) else {
+            return Err(format!("{:?} in {:?} reads {:?}, which no block defines", value, block, value));
+        };
+        let Some(ty) = function.inst(value).ty else {
+            return Err(format!("{:?} in {:?} reads {:?}, which has no value", value, block, value));
+        };
+        let before = if defined == block { i < at } else { cfg.dominates(defined, block) };
+        if cfg.is_reachable(block) && !before {
+            return Err(format!("{:?} is read in {:?} where its definition does not dominate it", value, block));
+        }
+        Ok(ty)
+    };
+    let local = |local: LocalId| -> Result<FfiType, String> {
+        function.locals.get(local.index()).copied().ok_or_else(|| format!("there is no local {}", local.0))
+    };
+    let expect = |value: InstId, ty: FfiType, what: &str| -> Result<(), String> {
+        match function.ty(value) {
+            Some(found) if found.carried() == ty.carried() => Ok(()),
+            found => Err(format!("{} {:?} is {:?}, not {:?}", what, value, found, ty)),
+        }
+    };
+
+    for (id, block) in function.iter_blocks() {
+        for (i, &inst) in block.insts.iter().enumerate() {
+            let kind = &function.inst(inst).kind;
+            let checked = kind.operands().into_iter().try_for_each(|value| read(value, id, i).map(drop)).and_then(
+                |()| match *kind {
+                    InstKind::Load(slot) => {
+                        let ty = local(slot)?;
+                        match function.inst(inst).ty {
+                            Some(found) if found.carried() == ty.carried() => Ok(()),
+                            found => Err(format!("{:?} loads local {} of {:?} as {:?}", inst, slot.0, ty, found)),
+                        }
+                    }
+                    InstKind::Store { local: slot, value } => expect(value, local(slot)?, "the stored value"),
+                    InstKind::Alloca(_) => match function.inst(inst).ty {
+                        Some(FfiType::Int { .. }) => Ok(()),
+                        found => Err(format!("{:?} allocates with a handle of {:?}, not an integer", inst, found)),
+                    },
+                    InstKind::Not(value) => expect(value, FfiType::Bool, "the negated value"),
+                    InstKind::Trap { condition, .. } => expect(condition, FfiType::Bool, "the trap condition"),
+                    _ => Ok(()),
+                },
+            );
+            if let Err(message) = checked {
+                return fail(message);
+            }
+        }
+        let end = block.insts.len();
+        let checked = match block.terminator {
+            Terminator::Branch { condition, .. } => {
+                read(condition, id, end).and_then(|_| expect(condition, FfiType::Bool, "the branch condition"))
+            }
+            Terminator::Exit(code) => read(code, id, end).and_then(|_| expect(code, FfiType::INT, "the exit code")),
+            Terminator::Switch { value, ref cases, .. } => read(value, id, end).and_then(|ty| {
+                if !matches!(ty, FfiType::Int { .. }) {
+                    return Err(format!("the switched value {:?} is {:?}, not an integer", value, ty));
+                }
+                match cases.iter().enumerate().find(|&(i, (n, _))| cases[..i].iter().any(|(m, _)| m == n)) {
+                    Some((_, (n, _))) => Err(format!("{:?} switches on {} twice", id, n)),
+                    None => Ok(()),
+                }
+            }),
+            Terminator::Return | Terminator::Jump(_) | Terminator::Unreachable => Ok(()),
+        };
+        if let Err(message) = checked {
+            return fail(message);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tir::Constant;
+
+    /// `main` with a local `x`, branching on a constant to two blocks that
+    /// store to `x` and join to read it.
+    fn diamond() -> (Function, [BlockId; 3]) {
+        let mut function = Function::new("main");
+        let x = function.add_local(FfiType::INT);
+        let (then, otherwise, join) = (function.add_block(), function.add_block(), function.add_block());
+        let condition = function.push(BlockId::ENTRY, InstKind::Const(Constant::Bool(true)), Some(FfiType::Bool));
+        function.terminate(BlockId::ENTRY, Terminator::Branch { condition, then, otherwise });
+        for (block, n) in [(then, 1), (otherwise, 2)] {
+            let value = function.push(block, InstKind::Const(Constant::Int(n)), Some(FfiType::INT));
+            function.push(block, InstKind::Store { local: x, value }, None);
+            function.terminate(block, Terminator::Jump(join));
+        }
+        let value = function.push(join, InstKind::Load(x), Some(FfiType::INT));
+        function.push(join, InstKind::Print(value), None);
+        (function, [then, otherwise, join])
+    }
+
+    #[test]
+    fn dominators_of_a_diamond() {
+        let (function, [then, otherwise, join]) = diamond();
+        let cfg = Cfg::new(&function);
+        assert_eq!(cfg.reverse_postorder()[0], BlockId::ENTRY);
+        assert_eq!([cfg.idom(then), cfg.idom(otherwise), cfg.idom(join)], [Some(BlockId::ENTRY); 3]);
+        assert!(cfg.dominates(BlockId::ENTRY, join) && !cfg.dominates(then, join));
+        let frontiers = cfg.frontiers();
+        assert_eq!((&frontiers[then.index()], &frontiers[otherwise.index()]), (&vec![join], &vec![join]));
+        assert!(frontiers[BlockId::ENTRY.index()].is_empty());
+        assert!(verify_function(&function).is_ok());
+    }
+
+    #[test]
+    fn nested_loops_come_innermost_first() {
+        // bb1 heads the outer loop and bb2 the inner, which bb3 goes back to.
+        let mut function = Function::new("main");
+        let blocks: Vec<BlockId> = (0..4).map(|_| function.add_block()).collect();
+        let [outer, inner, body, exit] = blocks[..] else { unreachable!() };
+        let condition = function.push(BlockId::ENTRY, InstKind::Const(Constant::Bool(true)), Some(FfiType::Bool));
+        function.terminate(BlockId::ENTRY, Terminator::Jump(outer));
+        function.terminate(outer, Terminator::Branch { condition, then: inner, otherwise: exit });
+        function.terminate(inner, Terminator::Branch { condition, then: body, otherwise: outer });
+        function.terminate(body, Terminator::Jump(inner));
+
+        let loops = Cfg::new(&function).loops();
+        assert_eq!(loops.len(), 2);
+        assert_eq!((loops[0].header, &loops[0].blocks), (inner, &vec![inner, body]));
+        assert_eq!((loops[1].header, &loops[1].blocks), (outer, &vec![outer, inner, body]));
+        assert!(!loops[1].contains(exit));
+        assert!(verify_function(&function).is_ok());
+    }
+
+    #[test]
+    fn values_must_be_defined_where_they_are_read() {
+        // The value one side of the diamond defines is read at the join.
+        let (mut function, [then, _, join]) = diamond();
+        let value = function.block(then).insts[0];
+        function.push(join, InstKind::Print(value), None);
+        let error = verify_function(&function).unwrap_err();
+        assert!(error.contains("does not dominate"), "{}", error);
+
+        // A branch on an integer, and a store of a float to an integer local.
+        let (mut function, _) = diamond();
+        let one = function.push(BlockId::ENTRY, InstKind::Const(Constant::Int(1)), Some(FfiType::INT));
+        let (then, otherwise) = (BlockId(1), BlockId(2));
+        function.terminate(BlockId::ENTRY, Terminator::Branch { condition: one, then, otherwise });
+        assert!(verify_function(&function).unwrap_err().contains("the branch condition"));
+        let (mut function, _) = diamond();
+        let half = function.push(BlockId::ENTRY, InstKind::Const(Constant::Float(0.5)), Some(FfiType::FLOAT));
+        function.push(BlockId::ENTRY, InstKind::Store { local: LocalId(0), value: half }, None);
+        assert!(verify_function(&function).unwrap_err().contains("the stored value"));
+
+        // A switch on a float, and one with a case twice.
+        let (mut function, [then, otherwise, _]) = diamond();
+        let cases = vec![(1, then), (1, otherwise)];
+        let half = function.push(BlockId::ENTRY, InstKind::Const(Constant::Float(0.5)), Some(FfiType::FLOAT));
+        function.terminate(BlockId::ENTRY, Terminator::Switch { value: half, cases: cases.clone(), default: then });
+        assert!(verify_function(&function).unwrap_err().contains("not an integer"));
+        let x = function.push(BlockId::ENTRY, InstKind::Load(LocalId(0)), Some(FfiType::INT));
+        function.terminate(BlockId::ENTRY, Terminator::Switch { value: x, cases, default: then });
+        assert!(verify_function(&function).unwrap_err().contains("switches on 1 twice"));
+
+        // A jump back to the entry, and one to a block there is not.
+        let (mut function, [_, _, join]) = diamond();
+        function.terminate(join, Terminator::Jump(BlockId::ENTRY));
+        assert!(verify_function(&function).unwrap_err().contains("entry block"));
+        function.terminate(join, Terminator::Jump(BlockId(9)));
+        assert!(verify_function(&function).unwrap_err().contains("does not have"));
+    }
+
+    #[test]
+    fn calls_must_match_their_callee() {
+        // `twice(x: i64) -> i64`, called from `main`.
+        let mut twice = Function::new("twice");
+        let x = twice.add_local(FfiType::INT);
+        (twice.params, twice.result) = (1, Some(x));
+        let mut main = Function::new("main");
+        let one = main.push(BlockId::ENTRY, InstKind::Const(Constant::Int(1)), Some(FfiType::INT));
+        let call = |args| InstKind::Call { function: "twice".into(), args };
+        main.push(BlockId::ENTRY, call(vec![one]), Some(FfiType::INT));
+        let mut module = Module { functions: vec![main, twice], ..Module::default() };
+        assert_eq!(verify(&module), Ok(()));
+
+        module.functions[0].push(BlockId::ENTRY, call(vec![one, one]), None);
+        assert!(verify(&module).unwrap_err().contains("for parameters of"));
+        module.functions[0].blocks[0].insts.pop();
+        let half = InstKind::Const(Constant::Float(0.5));
+        let half = module.functions[0].push(BlockId::ENTRY, half, Some(FfiType::FLOAT));
+        module.functions[0].push(BlockId::ENTRY, call(vec![half]), None);
+        assert!(verify(&module).unwrap_err().contains("for parameters of"));
+        module.functions[0].blocks[0].insts.pop();
+        module.functions[0].push(BlockId::ENTRY, call(vec![one]), Some(FfiType::Bool));
+        assert!(verify(&module).unwrap_err().contains("which returns"));
+
+        module.functions.swap(0, 1);
+        assert!(verify(&module).unwrap_err().contains("takes arguments"));
+        module.functions.truncate(1);
+        module.functions[0].params = 0;
+        module.functions[0].result = Some(LocalId(1));
+        assert!(verify(&module).unwrap_err().contains("does not have"));
+    }
+}
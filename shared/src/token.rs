@@ -1,7 +1,10 @@
 // shared/src/token.rs
 //! Token definitions for T-Lang.
 //! Represents all possible tokens that can appear in T-Lang source code.
+//! `TokenType` is the one token type, keywords included, that the lexer,
+//! the parser, the formatter and the macro expander share.
 
+use crate::ast::PrimitiveType;
 use miette::SourceSpan;
 use serde::{Deserialize, Serialize};
 
@@ -17,8 +20,12 @@ pub struct Token {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TokenType {
     // Literals
-    Integer(i64),
+    Integer(i128),
     Float(f64),
+    /// Integer with a type suffix: `42u8`, `0xFFi64`.
+    TypedInteger(i128, PrimitiveType),
+    /// Float with a type suffix: `3.0f32`, `1f64`.
+    TypedFloat(f64, PrimitiveType),
     String(String),
     Char(char),
     True,
@@ -34,8 +41,10 @@ pub enum TokenType {
     Break,
     Const,
     Continue,
+    Defer,
     Else,
     Enum,
+    Extern,
     Fn,
     For,
     If,
@@ -52,6 +61,7 @@ pub enum TokenType {
     Return,
     SelfValue, // self
     SelfType,  // Self
+    Spawn,
     Static,
     Struct,
     Super,
@@ -124,6 +134,10 @@ pub enum TokenType {
     ShlEq,       // <<=
     ShrEq,       // >>=
 
+    // Comments
+    /// `///` doc comment: the text after the slashes, less one leading space.
+    DocComment(String),
+
     // Special
     Eof,
     Invalid(String), // For error recovery
@@ -143,7 +157,8 @@ impl Token {
     pub fn is_literal(&self) -> bool {
         matches!(
             self.token_type,
-            TokenType::Integer(_) | TokenType::Float(_) | TokenType::String(_) |
+            TokenType::Integer(_) | TokenType::Float(_) | TokenType::TypedInteger(..) |
+            TokenType::TypedFloat(..) | TokenType::String(_) |
             TokenType::Char(_) | TokenType::True | TokenType::False
         )
     }
@@ -153,12 +168,12 @@ impl Token {
         matches!(
             self.token_type,
             TokenType::As | TokenType::Async | TokenType::Await | TokenType::Break |
-            TokenType::Const | TokenType::Continue | TokenType::Else | TokenType::Enum |
-            TokenType::Fn | TokenType::For | TokenType::If | TokenType::Impl |
+            TokenType::Const | TokenType::Continue | TokenType::Defer | TokenType::Else | TokenType::Enum |
+            TokenType::Extern | TokenType::Fn | TokenType::For | TokenType::If | TokenType::Impl |
             TokenType::In | TokenType::Let | TokenType::Loop | TokenType::Match |
             TokenType::Mod | TokenType::Move | TokenType::Mut | TokenType::Pub |
             TokenType::Ref | TokenType::Return | TokenType::SelfValue | TokenType::SelfType |
-            TokenType::Static | TokenType::Struct | TokenType::Super | TokenType::Trait |
+            TokenType::Spawn | TokenType::Static | TokenType::Struct | TokenType::Super | TokenType::Trait |
             TokenType::Type | TokenType::Union | TokenType::Unsafe | TokenType::Use |
             TokenType::Where | TokenType::While
         )
@@ -217,8 +232,8 @@ impl Token {
     /// Get a human-readable description of this token type.
     pub fn type_description(&self) -> &'static str {
         match self.token_type {
-            TokenType::Integer(_) => "integer literal",
-            TokenType::Float(_) => "float literal",
+            TokenType::Integer(_) | TokenType::TypedInteger(..) => "integer literal",
+            TokenType::Float(_) | TokenType::TypedFloat(..) => "float literal",
             TokenType::String(_) => "string literal",
             TokenType::Char(_) => "character literal",
             TokenType::True | TokenType::False => "boolean literal",
@@ -249,6 +264,7 @@ impl Token {
             TokenType::Bang => "'!'",
             TokenType::AndAnd => "'&&'",
             TokenType::OrOr => "'||'",
+            TokenType::DocComment(_) => "doc comment",
             TokenType::Eof => "end of file",
             TokenType::Invalid(_) => "invalid token",
             _ => {
@@ -267,9 +283,12 @@ impl std::fmt::Display for TokenType {
         match self {
             TokenType::Integer(n) => write!(f, "{}", n),
             TokenType::Float(n) => write!(f, "{}", n),
+            TokenType::TypedInteger(n, ty) => write!(f, "{}{}", n, format!("{:?}", ty).to_lowercase()),
+            TokenType::TypedFloat(n, ty) => write!(f, "{}{}", n, format!("{:?}", ty).to_lowercase()),
             TokenType::String(s) => write!(f, "\"{}\"", s),
             TokenType::Char(c) => write!(f, "'{}'", c),
             TokenType::Identifier(name) => write!(f, "{}", name),
+            TokenType::DocComment(text) => write!(f, "/// {}", text),
             TokenType::Invalid(s) => write!(f, "Invalid({})", s),
             _ => {
                 let token = Token::new(self.clone(), String::new(), SourceSpan::new(0.into(), 0));
@@ -287,4 +306,116 @@ impl std::fmt::Display for Token {
             write!(f, "{}", self.lexeme)
         }
     }
-}
\ No newline at end of file
+}
+
+/// What a piece of trivia is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriviaKind {
+    /// A run of spaces, tabs and newlines
+    Whitespace,
+    /// `// ...`, up to the end of its line
+    LineComment,
+    /// `/* ... */`, nested ones included
+    BlockComment,
+}
+
+/// Source text between tokens that the grammar ignores. `///` doc comments
+/// are not trivia but `DocComment` tokens.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: SourceSpan,
+}
+
+impl Trivia {
+    pub fn is_comment(&self) -> bool {
+        matches!(self.kind, TriviaKind::LineComment | TriviaKind::BlockComment)
+    }
+}
+
+/// A place in a `TokenStream` to rewind to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+/// Lexed tokens, read front to back with any number of tokens of lookahead
+/// and checkpoints to backtrack to.
+///
+/// The stream always ends in `Eof`, which reading never moves past. Lexed
+/// with `lex`, each token keeps the trivia before it, and `Eof` the trivia
+/// at the end of the source, so the source can be rebuilt exactly.
+#[derive(Debug, Clone)]
+pub struct TokenStream {
+    tokens: Vec<Token>,
+    /// The trivia before each token, by index; empty when none was kept
+    trivia: Vec<Vec<Trivia>>,
+    position: usize,
+}
+
+impl TokenStream {
+    /// A stream of `tokens`, without trivia. An `Eof` is added if they do
+    /// not end in one.
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self::with_trivia(tokens, Vec::new())
+    }
+
+    /// Lex `source`, keeping its trivia.
+    pub fn lex(source: &str) -> crate::Result<Self> {
+        crate::tokenizer::Tokenizer::new(source).token_stream()
+    }
+
+    /// A stream of `tokens`, `trivia[i]` before `tokens[i]`.
+    pub(crate) fn with_trivia(mut tokens: Vec<Token>, mut trivia: Vec<Vec<Trivia>>) -> Self {
+        if tokens.last().is_none_or(|token| token.token_type != TokenType::Eof) {
+            let end = tokens.last().map_or(0, |token| token.span.offset() + token.span.len());
+            tokens.push(Token::new(TokenType::Eof, String::new(), SourceSpan::new(end.into(), 0)));
+        }
+        trivia.resize_with(tokens.len(), Vec::new);
+        Self { tokens, trivia, position: 0 }
+    }
+
+    /// The next token.
+    pub fn peek(&self) -> &Token {
+        self.peek_nth(0)
+    }
+
+    /// The token `n` past the next one; `Eof` past the end.
+    pub fn peek_nth(&self, n: usize) -> &Token {
+        &self.tokens[(self.position + n).min(self.tokens.len() - 1)]
+    }
+
+    /// Read the next token.
+    pub fn advance(&mut self) -> &Token {
+        let index = self.position;
+        self.position = (index + 1).min(self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.peek().token_type == TokenType::Eof
+    }
+
+    /// The trivia before the next token.
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        &self.trivia[self.position]
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.position)
+    }
+
+    /// Go back to where `checkpoint` was taken, to read from there again.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.0.min(self.tokens.len() - 1);
+    }
+
+    /// All the tokens, read or not, `Eof` last.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The trivia before `tokens()[index]`.
+    pub fn trivia(&self, index: usize) -> &[Trivia] {
+        self.trivia.get(index).map_or(&[], Vec::as_slice)
+    }
+}
@@ -9,63 +9,91 @@
 //! - Support for all T-Lang token types
 //! - Unicode-aware string handling
 
-use crate::token::{Token, TokenType};
+use crate::ast::PrimitiveType;
+use crate::token::{Token, TokenStream, TokenType, Trivia, TriviaKind};
 use errors::{Result, TlError};
 use miette::SourceSpan;
 
 /// Main tokenizer struct that processes source code.
-pub struct Tokenizer {
-    input: Vec<char>,
-    position: usize,
+///
+/// A tokenizer borrows its source and lexes a token at a time as it is
+/// iterated, so a caller that reads tokens as they come never holds them
+/// all. `tokenize` collects them. Its errors do not copy the source; a
+/// caller that shows them against it attaches it with
+/// `TlError::with_source_code`.
+pub struct Tokenizer<'a> {
+    source: &'a str,
+    /// Byte offset of the cursor, which spans count in
+    offset: usize,
     line: usize,
     column: usize,
-    source: String,
+    /// Whether `Eof` or an error has been returned
+    finished: bool,
+    /// Trivia since the last token, when it is kept
+    trivia: Option<Vec<Trivia>>,
+    /// The trivia before each token so far, when it is kept
+    leading: Vec<Vec<Trivia>>,
 }
 
-impl Tokenizer {
+impl<'a> Tokenizer<'a> {
     /// Create a new tokenizer for the given source code.
-    pub fn new(source: String) -> Self {
-        let input: Vec<char> = source.chars().collect();
+    pub fn new(source: &'a str) -> Self {
         Self {
-            input,
-            position: 0,
+            source,
+            offset: 0,
             line: 1,
             column: 1,
-            source,
+            finished: false,
+            trivia: None,
+            leading: Vec::new(),
         }
     }
 
     /// Tokenize the entire input into a vector of tokens.
     pub fn tokenize(&mut self) -> Result<Vec<Token>> {
-        let mut tokens = Vec::new();
-
-        while !self.is_at_end() {
-            match self.next_token() {
-                Ok(Some(token)) => tokens.push(token),
-                Ok(None) => continue, // Skip whitespace/comments
-                Err(e) => return Err(e),
-            }
+        match self.tokenize_partial() {
+            (tokens, None) => Ok(tokens),
+            (_, Some(e)) => Err(e),
         }
+    }
 
-        // Add EOF token
-        tokens.push(Token::new(
-            TokenType::Eof,
-            String::new(),
-            self.current_span(0),
-        ));
+    /// Tokenize the entire input into a stream that keeps the trivia before
+    /// each token.
+    pub fn token_stream(&mut self) -> Result<TokenStream> {
+        self.trivia = Some(Vec::new());
+        let tokens = self.tokenize()?;
+        Ok(TokenStream::with_trivia(tokens, std::mem::take(&mut self.leading)))
+    }
 
-        Ok(tokens)
+    /// Tokenize up to the first lexical error.
+    ///
+    /// Returns the tokens before the error, still ending in `Eof`, so callers
+    /// that recover (the IDE) can work with what did lex.
+    pub fn tokenize_partial(&mut self) -> (Vec<Token>, Option<TlError>) {
+        let mut tokens = Vec::new();
+        for token in self.by_ref() {
+            match token {
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    tokens.push(Token::new(TokenType::Eof, String::new(), self.current_span(0)));
+                    return (tokens, Some(e));
+                }
+            }
+        }
+        (tokens, None)
     }
 
     /// Get the next token from the input.
     fn next_token(&mut self) -> Result<Option<Token>> {
+        let whitespace = self.offset;
         self.skip_whitespace();
+        self.keep_trivia(TriviaKind::Whitespace, whitespace);
 
         if self.is_at_end() {
             return Ok(None);
         }
 
-        let start_pos = self.position;
+        let start_pos = self.offset;
         let ch = self.advance();
 
         let token_type = match ch {
@@ -78,7 +106,7 @@ impl Tokenizer {
             ']' => TokenType::RBracket,
             ',' => TokenType::Comma,
             ';' => TokenType::Semicolon,
-            '+' => self.match_char('=').then_some(TokenType::PlusEq).unwrap_or(TokenType::Plus),
+            '+' => if self.match_char('=') { TokenType::PlusEq } else { TokenType::Plus },
             '-' => {
                 if self.match_char('=') {
                     TokenType::MinusEq
@@ -88,13 +116,19 @@ impl Tokenizer {
                     TokenType::Minus
                 }
             },
-            '*' => self.match_char('=').then_some(TokenType::StarEq).unwrap_or(TokenType::Star),
+            '*' => if self.match_char('=') { TokenType::StarEq } else { TokenType::Star },
             '/' => {
                 if self.match_char('/') {
+                    // `///` is a doc comment, `////` and longer plain ones.
+                    if self.peek() == '/' && self.peek_next() != Some('/') {
+                        return self.doc_comment(start_pos);
+                    }
                     self.skip_line_comment();
+                    self.keep_trivia(TriviaKind::LineComment, start_pos);
                     return Ok(None);
                 } else if self.match_char('*') {
-                    self.skip_block_comment()?;
+                    self.skip_block_comment(start_pos)?;
+                    self.keep_trivia(TriviaKind::BlockComment, start_pos);
                     return Ok(None);
                 } else if self.match_char('=') {
                     TokenType::SlashEq
@@ -102,9 +136,9 @@ impl Tokenizer {
                     TokenType::Slash
                 }
             },
-            '%' => self.match_char('=').then_some(TokenType::PercentEq).unwrap_or(TokenType::Percent),
-            '^' => self.match_char('=').then_some(TokenType::CaretEq).unwrap_or(TokenType::Caret),
-            '!' => self.match_char('=').then_some(TokenType::Ne).unwrap_or(TokenType::Bang),
+            '%' => if self.match_char('=') { TokenType::PercentEq } else { TokenType::Percent },
+            '^' => if self.match_char('=') { TokenType::CaretEq } else { TokenType::Caret },
+            '!' => if self.match_char('=') { TokenType::Ne } else { TokenType::Bang },
             '=' => {
                 if self.match_char('=') {
                     TokenType::EqEq
@@ -118,7 +152,7 @@ impl Tokenizer {
                 if self.match_char('=') {
                     TokenType::Le
                 } else if self.match_char('<') {
-                    self.match_char('=').then_some(TokenType::ShlEq).unwrap_or(TokenType::Shl)
+                    if self.match_char('=') { TokenType::ShlEq } else { TokenType::Shl }
                 } else {
                     TokenType::Lt
                 }
@@ -127,7 +161,7 @@ impl Tokenizer {
                 if self.match_char('=') {
                     TokenType::Ge
                 } else if self.match_char('>') {
-                    self.match_char('=').then_some(TokenType::ShrEq).unwrap_or(TokenType::Shr)
+                    if self.match_char('=') { TokenType::ShrEq } else { TokenType::Shr }
                 } else {
                     TokenType::Gt
                 }
@@ -183,16 +217,19 @@ impl Tokenizer {
             // Numbers
             '0'..='9' => return self.number_literal(start_pos),
 
-            // Identifiers and keywords
-            'a'..='z' | 'A'..='Z' | '_' => return self.identifier_or_keyword(start_pos),
+            // Identifiers and keywords; `r"` and `r#"` open raw strings
+            'a'..='z' | 'A'..='Z' | '_' => {
+                if ch == 'r'
+                    && let Some(hashes) = self.raw_string_hashes()
+                {
+                    return self.raw_string_literal(start_pos, hashes);
+                }
+                return self.identifier_or_keyword(start_pos);
+            }
 
             // Invalid character
             _ => {
-                return Err(TlError::lexer(
-                    self.source.clone(),
-                    self.current_span(1),
-                    format!("Unexpected character: '{}'", ch),
-                ));
+                return Err(lexer_error(self.current_span(1), format!("Unexpected character: '{}'", ch)));
             }
         };
 
@@ -202,50 +239,25 @@ impl Tokenizer {
         Ok(Some(Token::new(token_type, lexeme, span)))
     }
 
-    /// Parse a string literal.
+    /// Parse a string literal. Strings may span lines; a backslash at the
+    /// end of a line drops the newline and the next line's leading whitespace.
     fn string_literal(&mut self) -> Result<Option<Token>> {
-        let start_pos = self.position - 1; // Include opening quote
+        let start_pos = self.offset - 1; // Include opening quote
         let mut value = String::new();
 
         while !self.is_at_end() && self.peek() != '"' {
-            if self.peek() == '\\' {
+            if self.peek() == '\\' && matches!(self.peek_next(), Some('\n' | '\r')) {
                 self.advance(); // consume backslash
-                if self.is_at_end() {
-                    return Err(TlError::lexer(
-                        self.source.clone(),
-                        self.current_span(1),
-                        "Unterminated string literal",
-                    ));
-                }
-
-                let escaped = match self.advance() {
-                    'n' => '\n',
-                    't' => '\t',
-                    'r' => '\r',
-                    '\\' => '\\',
-                    '"' => '"',
-                    '\'' => '\'',
-                    '0' => '\0',
-                    c => {
-                        return Err(TlError::lexer(
-                            self.source.clone(),
-                            self.current_span(1),
-                            format!("Invalid escape sequence: \\{}", c),
-                        ));
-                    }
-                };
-                value.push(escaped);
+                self.skip_whitespace();
+            } else if self.peek() == '\\' {
+                value.push(self.escape_sequence("string literal")?);
             } else {
                 value.push(self.advance());
             }
         }
 
         if self.is_at_end() {
-            return Err(TlError::lexer(
-                self.source.clone(),
-                self.current_span(1),
-                "Unterminated string literal",
-            ));
+            return Err(lexer_error(SourceSpan::new(start_pos.into(), 1), "Unterminated string literal"));
         }
 
         self.advance(); // closing quote
@@ -254,54 +266,132 @@ impl Tokenizer {
         Ok(Some(Token::new(TokenType::String(value), self.get_lexeme(start_pos), span)))
     }
 
-    /// Parse a character literal.
-    fn char_literal(&mut self) -> Result<Option<Token>> {
-        let start_pos = self.position - 1;
+    /// Number of `#`s if a raw string (`r"..."`, `r#"..."#`, ...) starts
+    /// after the `r` just consumed.
+    fn raw_string_hashes(&self) -> Option<usize> {
+        let hashes = self.rest().chars().take_while(|&c| c == '#').count();
+        (self.rest().chars().nth(hashes) == Some('"')).then_some(hashes)
+    }
 
-        if self.is_at_end() {
-            return Err(TlError::lexer(
-                self.source.clone(),
-                self.current_span(1),
-                "Unterminated character literal",
-            ));
+    /// Parse a raw string literal. Its text is taken verbatim, newlines
+    /// included, up to a `"` followed by as many `#`s as opened it.
+    fn raw_string_literal(&mut self, start_pos: usize, hashes: usize) -> Result<Option<Token>> {
+        for _ in 0..=hashes {
+            self.advance(); // `#`s and opening quote
         }
 
-        let ch = if self.peek() == '\\' {
-            self.advance(); // consume backslash
+        let mut value = String::new();
+        loop {
             if self.is_at_end() {
-                return Err(TlError::lexer(
-                    self.source.clone(),
-                    self.current_span(1),
-                    "Unterminated character literal",
-                ));
+                return Err(lexer_error(SourceSpan::new(start_pos.into(), 1), "Unterminated raw string literal"));
             }
+            let ch = self.advance();
+            if ch == '"' {
+                let closing = self.rest().chars().take(hashes).take_while(|&c| c == '#').count();
+                if closing == hashes {
+                    for _ in 0..hashes {
+                        self.advance();
+                    }
+                    break;
+                }
+            }
+            value.push(ch);
+        }
 
-            match self.advance() {
-                'n' => '\n',
-                't' => '\t',
-                'r' => '\r',
-                '\\' => '\\',
-                '"' => '"',
-                '\'' => '\'',
-                '0' => '\0',
-                c => {
-                    return Err(TlError::lexer(
-                        self.source.clone(),
-                        self.current_span(1),
-                        format!("Invalid escape sequence: \\{}", c),
+        let span = self.span_from(start_pos);
+        Ok(Some(Token::new(TokenType::String(value), self.get_lexeme(start_pos), span)))
+    }
+
+    /// Parse the escape sequence whose backslash is the next character.
+    /// Errors cover the whole sequence, backslash included.
+    fn escape_sequence(&mut self, literal: &str) -> Result<char> {
+        let start_pos = self.offset;
+        self.advance(); // consume backslash
+        if self.is_at_end() {
+            return Err(lexer_error(self.current_span(1), format!("Unterminated {}", literal)));
+        }
+
+        let escaped = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            '0' => '\0',
+            'x' => {
+                let digits = self.hex_digits(2);
+                if digits.len() != 2 {
+                    return Err(self.invalid_escape(start_pos, "\\x must be followed by two hex digits"));
+                }
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if byte.is_ascii() => char::from(byte),
+                    _ => {
+                        return Err(self.invalid_escape(
+                            start_pos,
+                            format!("\\x{} is out of range; hex escapes go up to \\x7F", digits),
+                        ));
+                    }
+                }
+            }
+            'u' => {
+                if !self.match_char('{') {
+                    return Err(self.invalid_escape(start_pos, "\\u must be followed by `{`"));
+                }
+                let digits = self.hex_digits(6);
+                if digits.is_empty() || !self.match_char('}') {
+                    return Err(self.invalid_escape(
+                        start_pos,
+                        "\\u{...} takes one to six hex digits and a closing `}`",
                     ));
                 }
+                match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => {
+                        return Err(self.invalid_escape(
+                            start_pos,
+                            format!("\\u{{{}}} is not a Unicode scalar value", digits),
+                        ));
+                    }
+                }
+            }
+            c => {
+                return Err(self.invalid_escape(start_pos, format!("Unknown escape sequence: \\{}", c)));
             }
+        };
+        Ok(escaped)
+    }
+
+    /// Consume up to `max` hex digits.
+    fn hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
+        while digits.len() < max && self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+        digits
+    }
+
+    /// An `InvalidEscape` error spanning from `start_pos` to the cursor.
+    fn invalid_escape(&self, start_pos: usize, message: impl Into<String>) -> TlError {
+        TlError::invalid_escape(String::new(), self.span_from(start_pos), message)
+    }
+
+    /// Parse a character literal.
+    fn char_literal(&mut self) -> Result<Option<Token>> {
+        let start_pos = self.offset - 1;
+
+        if self.is_at_end() {
+            return Err(lexer_error(self.current_span(1), "Unterminated character literal"));
+        }
+
+        let ch = if self.peek() == '\\' {
+            self.escape_sequence("character literal")?
         } else {
             self.advance()
         };
 
         if self.is_at_end() || self.peek() != '\'' {
-            return Err(TlError::lexer(
-                self.source.clone(),
-                self.current_span(1),
-                "Unterminated character literal",
-            ));
+            return Err(lexer_error(self.current_span(1), "Unterminated character literal"));
         }
 
         self.advance(); // closing quote
@@ -310,58 +400,95 @@ impl Tokenizer {
         Ok(Some(Token::new(TokenType::Char(ch), self.get_lexeme(start_pos), span)))
     }
 
-    /// Parse a number literal.
+    /// Parse a number literal: decimal, `0x`, `0o` or `0b` digits with `_`
+    /// separators, then an optional type suffix (`42u8`, `3.0f32`).
     fn number_literal(&mut self, start_pos: usize) -> Result<Option<Token>> {
-        // Consume digits
-        while self.peek().is_ascii_digit() {
-            self.advance();
-        }
+        let radix = match (self.text_from(start_pos).chars().next(), self.peek()) {
+            (Some('0'), 'x') => 16,
+            (Some('0'), 'o') => 8,
+            (Some('0'), 'b') => 2,
+            _ => 10,
+        };
 
-        // Look for decimal point
         let mut is_float = false;
-        if self.peek() == '.' && self.peek_next().map_or(false, |c| c.is_ascii_digit()) {
-            is_float = true;
-            self.advance(); // consume '.'
-            while self.peek().is_ascii_digit() {
+        let digits_start = if radix == 10 {
+            start_pos
+        } else {
+            self.advance(); // radix prefix
+            self.offset
+        };
+        self.digits(radix);
+
+        if radix != 10 {
+            if self.peek().is_ascii_digit() {
+                let kind = if radix == 2 { "binary" } else { "octal" };
+                let message = format!("Invalid digit '{}' in {} literal", self.peek(), kind);
+                return Err(lexer_error(self.current_span(1), message));
+            }
+            if !self.text_from(digits_start).chars().any(|c| c.is_digit(radix)) {
+                let message = format!("Missing digits after {}", self.get_lexeme(start_pos));
+                return Err(lexer_error(self.span_from(start_pos), message));
+            }
+        } else {
+            // Look for decimal point
+            if self.peek() == '.' && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+                is_float = true;
+                self.advance(); // consume '.'
+                self.digits(10);
+            }
+
+            // Look for exponent; an `e` not followed by digits starts a suffix.
+            let exponent_digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+            if matches!(self.peek(), 'e' | 'E')
+                && (exponent_digit(self.rest().chars().nth(1))
+                    || (matches!(self.peek_next(), Some('+' | '-'))
+                        && exponent_digit(self.rest().chars().nth(2))))
+            {
+                is_float = true;
                 self.advance();
+                if matches!(self.peek(), '+' | '-') {
+                    self.advance();
+                }
+                self.digits(10);
             }
         }
 
-        // Look for exponent
-        if matches!(self.peek(), 'e' | 'E') {
-            is_float = true;
+        let digits: String = self.text_from(digits_start).chars().filter(|&c| c != '_').collect();
+
+        let suffix_start = self.offset;
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
             self.advance();
-            if matches!(self.peek(), '+' | '-') {
-                self.advance();
+        }
+        let suffix = self.get_lexeme(suffix_start);
+        // Floats take float suffixes only, and hex, octal and binary
+        // literals integer suffixes only.
+        let suffix_type = match numeric_suffix(&suffix) {
+            _ if suffix.is_empty() => None,
+            Some(ty) if (!is_float || is_float_suffix(&ty)) && (radix == 10 || !is_float_suffix(&ty)) => {
+                Some(ty)
             }
-            while self.peek().is_ascii_digit() {
-                self.advance();
+            _ => {
+                let message = format!("Invalid suffix `{}` for number literal", suffix);
+                return Err(lexer_error(self.span_from(suffix_start), message));
             }
-        }
+        };
 
         let lexeme = self.get_lexeme(start_pos);
         let span = self.span_from(start_pos);
 
-        let token_type = if is_float {
-            match lexeme.parse::<f64>() {
-                Ok(value) => TokenType::Float(value),
-                Err(_) => {
-                    return Err(TlError::lexer(
-                        self.source.clone(),
-                        span,
-                        format!("Invalid float literal: {}", lexeme),
-                    ));
-                }
-            }
-        } else {
-            match lexeme.parse::<i64>() {
-                Ok(value) => TokenType::Integer(value),
-                Err(_) => {
-                    return Err(TlError::lexer(
-                        self.source.clone(),
-                        span,
-                        format!("Invalid integer literal: {}", lexeme),
-                    ));
+        let token_type = match suffix_type {
+            Some(ty) if is_float_suffix(&ty) => TokenType::TypedFloat(self.float_value(&digits, span)?, ty),
+            None if is_float => TokenType::Float(self.float_value(&digits, span)?),
+            suffix_type => {
+                let value = u128::from_str_radix(&digits, radix)
+                    .ok()
+                    .and_then(|value| i128::try_from(value).ok())
+                    .ok_or_else(|| {
+                        lexer_error(span, format!("Integer literal is too large: {}", lexeme))
+                    })?;
+                match suffix_type {
+                    Some(ty) => TokenType::TypedInteger(value, ty),
+                    None => TokenType::Integer(value),
                 }
             }
         };
@@ -369,6 +496,23 @@ impl Tokenizer {
         Ok(Some(Token::new(token_type, lexeme, span)))
     }
 
+    /// Consume digits of `radix` and `_` separators.
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    /// Value of the float literal `digits`, which must be finite.
+    fn float_value(&self, digits: &str, span: SourceSpan) -> Result<f64> {
+        let message = match digits.parse::<f64>() {
+            Ok(value) if value.is_finite() => return Ok(value),
+            Ok(_) => format!("Float literal is too large: {}", self.get_lexeme(span.offset())),
+            Err(_) => format!("Invalid float literal: {}", self.get_lexeme(span.offset())),
+        };
+        Err(lexer_error(span, message))
+    }
+
     /// Parse an identifier or keyword.
     fn identifier_or_keyword(&mut self, start_pos: usize) -> Result<Option<Token>> {
         while self.peek().is_alphanumeric() || self.peek() == '_' {
@@ -386,8 +530,10 @@ impl Tokenizer {
             "break" => TokenType::Break,
             "const" => TokenType::Const,
             "continue" => TokenType::Continue,
+            "defer" => TokenType::Defer,
             "else" => TokenType::Else,
             "enum" => TokenType::Enum,
+            "extern" => TokenType::Extern,
             "false" => TokenType::False,
             "fn" => TokenType::Fn,
             "for" => TokenType::For,
@@ -405,6 +551,7 @@ impl Tokenizer {
             "return" => TokenType::Return,
             "self" => TokenType::SelfValue,
             "Self" => TokenType::SelfType,
+            "spawn" => TokenType::Spawn,
             "static" => TokenType::Static,
             "struct" => TokenType::Struct,
             "super" => TokenType::Super,
@@ -427,12 +574,22 @@ impl Tokenizer {
     // Helper methods
 
     fn is_at_end(&self) -> bool {
-        self.position >= self.input.len()
+        self.offset >= self.source.len()
+    }
+
+    /// The source from the cursor on.
+    fn rest(&self) -> &'a str {
+        self.source.get(self.offset..).unwrap_or_default()
+    }
+
+    /// The source from the offset `start_pos` to the cursor.
+    fn text_from(&self, start_pos: usize) -> &'a str {
+        &self.source[start_pos..self.offset]
     }
 
     fn advance(&mut self) -> char {
-        let ch = self.input[self.position];
-        self.position += 1;
+        let ch = self.peek();
+        self.offset += ch.len_utf8();
 
         if ch == '\n' {
             self.line += 1;
@@ -445,19 +602,11 @@ impl Tokenizer {
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.input[self.position]
-        }
+        self.rest().chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> Option<char> {
-        if self.position + 1 >= self.input.len() {
-            None
-        } else {
-            Some(self.input[self.position + 1])
-        }
+        self.rest().chars().nth(1)
     }
 
     fn match_char(&mut self, expected: char) -> bool {
@@ -481,7 +630,20 @@ impl Tokenizer {
         }
     }
 
-    fn skip_block_comment(&mut self) -> Result<()> {
+    /// Parse a `///` doc comment, up to the end of its line.
+    fn doc_comment(&mut self, start_pos: usize) -> Result<Option<Token>> {
+        self.advance(); // third slash
+        self.skip_line_comment();
+        let text = self.get_lexeme(start_pos + 3);
+        let text = text.trim_end();
+        let text = text.strip_prefix(' ').unwrap_or(text).to_string();
+        let span = self.span_from(start_pos);
+        Ok(Some(Token::new(TokenType::DocComment(text), self.get_lexeme(start_pos), span)))
+    }
+
+    /// Skip a block comment, whose `/*` starts at `start_pos`. Block
+    /// comments nest.
+    fn skip_block_comment(&mut self, start_pos: usize) -> Result<()> {
         let mut depth = 1;
 
         while !self.is_at_end() && depth > 0 {
@@ -499,31 +661,271 @@ impl Tokenizer {
         }
 
         if depth > 0 {
-            return Err(TlError::lexer(
-                self.source.clone(),
-                self.current_span(1),
-                "Unterminated block comment",
-            ));
+            return Err(lexer_error(SourceSpan::new(start_pos.into(), 2), "Unterminated block comment"));
         }
 
         Ok(())
     }
 
+    /// Keep the text from `start_pos` to the cursor as trivia, if trivia is
+    /// kept and there is any.
+    fn keep_trivia(&mut self, kind: TriviaKind, start_pos: usize) {
+        if self.offset > start_pos && self.trivia.is_some() {
+            let trivia = Trivia { kind, text: self.get_lexeme(start_pos), span: self.span_from(start_pos) };
+            self.trivia.get_or_insert_default().push(trivia);
+        }
+    }
+
+    /// Attach the trivia kept since the last token to the token about to be
+    /// pushed.
+    fn end_trivia(&mut self) {
+        if let Some(trivia) = &mut self.trivia {
+            self.leading.push(std::mem::take(trivia));
+        }
+    }
+
     fn get_lexeme(&self, start_pos: usize) -> String {
-        self.input[start_pos..self.position].iter().collect()
+        self.text_from(start_pos).to_string()
     }
 
     fn span_from(&self, start_pos: usize) -> SourceSpan {
-        SourceSpan::new(start_pos.into(), (self.position - start_pos))
+        SourceSpan::new(start_pos.into(), self.offset - start_pos)
     }
 
+    /// The span of the next `len` characters; past the end of the source,
+    /// of `len` bytes.
     fn current_span(&self, len: usize) -> SourceSpan {
-        SourceSpan::new(self.position.into(), len)
+        let bytes: usize = self.rest().chars().take(len).map(char::len_utf8).sum();
+        SourceSpan::new(self.offset.into(), if self.is_at_end() { len } else { bytes })
     }
 }
 
+/// A lexical error at `span`, without the source.
+fn lexer_error(span: SourceSpan, message: impl Into<String>) -> TlError {
+    TlError::lexer(String::new(), span, message)
+}
+
+/// Tokens come one at a time as they are lexed: every token then `Eof`, or
+/// the tokens before a lexical error then the error.
+impl Iterator for Tokenizer<'_> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Result<Token>> {
+        while !self.finished {
+            if self.is_at_end() {
+                self.finished = true;
+                self.end_trivia();
+                return Some(Ok(Token::new(TokenType::Eof, String::new(), self.current_span(0))));
+            }
+            match self.next_token() {
+                Ok(Some(token)) => {
+                    self.end_trivia();
+                    return Some(Ok(token));
+                }
+                Ok(None) => continue, // Skip whitespace/comments
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The type a number literal suffix names.
+fn numeric_suffix(suffix: &str) -> Option<PrimitiveType> {
+    Some(match suffix {
+        "i8" => PrimitiveType::I8,
+        "i16" => PrimitiveType::I16,
+        "i32" => PrimitiveType::I32,
+        "i64" => PrimitiveType::I64,
+        "i128" => PrimitiveType::I128,
+        "isize" => PrimitiveType::ISize,
+        "u8" => PrimitiveType::U8,
+        "u16" => PrimitiveType::U16,
+        "u32" => PrimitiveType::U32,
+        "u64" => PrimitiveType::U64,
+        "u128" => PrimitiveType::U128,
+        "usize" => PrimitiveType::USize,
+        "f32" => PrimitiveType::F32,
+        "f64" => PrimitiveType::F64,
+        _ => return None,
+    })
+}
+
+fn is_float_suffix(ty: &PrimitiveType) -> bool {
+    matches!(ty, PrimitiveType::F32 | PrimitiveType::F64)
+}
+
 /// Convenience function to tokenize a string.
 pub fn tokenize(source: String) -> Result<Vec<Token>> {
-    let mut tokenizer = Tokenizer::new(source);
+    let mut tokenizer = Tokenizer::new(&source);
     tokenizer.tokenize()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(source: &str) -> String {
+        match tokenize(source.to_string()).map(|tokens| tokens[0].token_type.clone()) {
+            Ok(TokenType::String(value)) => value,
+            other => panic!("expected a string literal, got {:?}", other),
+        }
+    }
+
+    fn escape_error(source: &str) -> (usize, usize) {
+        match tokenize(source.to_string()) {
+            Err(TlError::InvalidEscape { span, .. }) => (span.offset(), span.len()),
+            other => panic!("expected an invalid escape, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(string(r#""a\tb\n\\\"\0""#), "a\tb\n\\\"\0");
+        assert_eq!(string(r#""\x41\u{e9}\u{1F600}""#), "A\u{e9}\u{1F600}");
+    }
+
+    #[test]
+    fn test_invalid_escapes_span_the_sequence() {
+        assert_eq!(escape_error(r#"let s = "ab\q";"#), (11, 2));
+        assert_eq!(escape_error(r#""\x80""#), (1, 4));
+        assert_eq!(escape_error(r#""\u{D800}""#), (1, 8));
+        assert_eq!(escape_error(r#""\u12""#), (1, 2));
+        assert_eq!(escape_error(r"'\z'"), (1, 2));
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        assert_eq!(string(r#"r"C:\path\n""#), r"C:\path\n");
+        assert_eq!(string(r###"r##"say "#hi"#"##"###), r##"say "#hi"#"##);
+        let tokens = tokenize(r##"r#"x"# r"##.to_string()).unwrap();
+        assert_eq!(tokens[0].lexeme, r##"r#"x"#"##);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier("r".to_string()));
+        assert!(tokenize(r##"r#"open""##.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_multi_line_strings() {
+        assert_eq!(string("\"one\ntwo\""), "one\ntwo");
+        assert_eq!(string("\"one \\\n     two\""), "one two");
+        assert_eq!(string("r\"one\ntwo\""), "one\ntwo");
+
+        let tokens = tokenize("\"a\nb\" x".to_string()).unwrap();
+        assert_eq!(tokens[1].span.offset(), 6);
+    }
+
+    fn number(source: &str) -> TokenType {
+        tokenize(source.to_string()).unwrap()[0].token_type.clone()
+    }
+
+    #[test]
+    fn test_radix_and_separated_integers() {
+        assert_eq!(number("0xFF"), TokenType::Integer(255));
+        assert_eq!(number("0o77"), TokenType::Integer(63));
+        assert_eq!(number("0b1010"), TokenType::Integer(10));
+        assert_eq!(number("1_000_000"), TokenType::Integer(1_000_000));
+        assert_eq!(number("0xFFFF_FFFF_FFFF_FFFF"), TokenType::Integer(u64::MAX as i128));
+        assert_eq!(number("1_0.5e1_0"), TokenType::Float(10.5e10));
+    }
+
+    #[test]
+    fn test_typed_suffixes() {
+        assert_eq!(number("42u8"), TokenType::TypedInteger(42, PrimitiveType::U8));
+        assert_eq!(number("0xFFi64"), TokenType::TypedInteger(255, PrimitiveType::I64));
+        assert_eq!(number("3.0f32"), TokenType::TypedFloat(3.0, PrimitiveType::F32));
+        assert_eq!(number("1f64"), TokenType::TypedFloat(1.0, PrimitiveType::F64));
+        assert_eq!(number("0x1f32"), TokenType::Integer(0x1f32));
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        let error = |source: &str| match tokenize(source.to_string()) {
+            Err(TlError::Lexer { span, message, .. }) => (span.offset(), span.len(), message),
+            other => panic!("expected a lexer error, got {:?}", other),
+        };
+        assert_eq!(error("0b102").0, 4);
+        assert_eq!(error("0x").2, "Missing digits after 0x");
+        let (offset, len, _) = error("1.5u8");
+        assert_eq!((offset, len), (3, 2));
+        assert!(error("0b1f32").2.contains("suffix"));
+        assert!(error("12abc").2.contains("`abc`"));
+        assert!(error("170141183460469231731687303715884105728").2.contains("too large"));
+        assert!(error("1e400").2.contains("too large"));
+    }
+
+    #[test]
+    fn test_block_comments_nest() {
+        let tokens = tokenize("a /* one /* two */ still one */ b".to_string()).unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier("b".to_string()));
+
+        match tokenize("a /* one /* two */".to_string()) {
+            Err(TlError::Lexer { span, .. }) => assert_eq!((span.offset(), span.len()), (2, 2)),
+            other => panic!("expected an unterminated comment, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let tokens = tokenize("/// Adds.  \n//// rule\n// plain\nfn".to_string()).unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::DocComment("Adds.".to_string()));
+        assert_eq!(tokens[0].span.len(), 11);
+        assert_eq!(tokens[1].token_type, TokenType::Fn);
+    }
+
+    #[test]
+    fn test_tokens_are_lexed_as_they_are_read() {
+        let mut tokenizer = Tokenizer::new("let s = \"é\"; ` never lexed");
+        let first: Vec<Token> = tokenizer.by_ref().take(4).map(|token| token.unwrap()).collect();
+        assert_eq!(first[3].token_type, TokenType::String("é".to_string()));
+        assert_eq!((first[3].span.offset(), first[3].span.len(), first[3].lexeme.as_str()), (8, 4, "\"é\""));
+        assert_eq!(tokenizer.next().unwrap().unwrap().span.offset(), 12);
+        assert!(tokenizer.next().unwrap().is_err());
+        assert!(tokenizer.next().is_none());
+
+        let eof: Vec<TokenType> = Tokenizer::new("x").map(|token| token.unwrap().token_type).collect();
+        assert_eq!(eof, [TokenType::Identifier("x".to_string()), TokenType::Eof]);
+    }
+
+    #[test]
+    fn test_spans_are_byte_ranges_of_the_source() {
+        let source = "let café = \"𝄞\"; // ü\n'é' naïve";
+        for token in tokenize(source.to_string()).unwrap() {
+            let start = token.span.offset();
+            assert_eq!(&source[start..start + token.span.len()], token.lexeme);
+        }
+        match tokenize("\"é".to_string()) {
+            Err(TlError::Lexer { span, .. }) => assert_eq!((span.offset(), span.len()), (0, 1)),
+            other => panic!("expected an unterminated string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_token_stream_keeps_trivia_and_backtracks() {
+        let source = "let x /* a /* b */ */ = 1; // one\n";
+        let mut stream = TokenStream::lex(source).unwrap();
+        let rebuilt: String = (0..stream.tokens().len())
+            .flat_map(|i| stream.trivia(i).iter().map(|t| t.text.clone()).chain([stream.tokens()[i].lexeme.clone()]))
+            .collect();
+        assert_eq!(rebuilt, source);
+
+        assert_eq!(stream.advance().token_type, TokenType::Let);
+        assert_eq!(stream.peek_nth(1).token_type, TokenType::Eq);
+        let checkpoint = stream.checkpoint();
+        stream.advance();
+        let kinds: Vec<TriviaKind> = stream.leading_trivia().iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [TriviaKind::Whitespace, TriviaKind::BlockComment, TriviaKind::Whitespace]);
+        stream.rewind(checkpoint);
+        assert_eq!(stream.advance().token_type, TokenType::Identifier("x".to_string()));
+
+        while !stream.is_at_end() {
+            stream.advance();
+        }
+        assert_eq!(stream.advance().token_type, TokenType::Eof);
+        assert_eq!(stream.peek_nth(5).token_type, TokenType::Eof);
+        assert!(stream.leading_trivia()[1].is_comment());
+        assert!(TokenStream::new(tokenize(source.to_string()).unwrap()).leading_trivia().is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+// tlang-dap/src/machine.rs
+//! Stepping interpreter for the IR, driven by the debug adapter.
+//!
+//! Executes a `CompiledModule` one instruction at a time on the same value
+//! stacks the backends use, and uses its `DebugInfo` line table to stop at
+//! source lines: breakpoints and `next` are both expressed as "run until an
+//! instruction that starts a line".
+
+use plugin_api::{
+    format_bool, format_float, BackendError, CompiledModule, DebugInfo, FunctionInfo, Instruction, Layout,
+    LineInfo, ValueStacks, VariableInfo,
+};
+use std::collections::BTreeSet;
+
+/// Why execution stopped before the end of the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint,
+    Step,
+}
+
+impl StopReason {
+    /// The `reason` of the DAP `stopped` event.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Step => "step",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Machine {
+    instrs: Vec<Instruction>,
+    /// Where the labels and functions of `instrs` are
+    layout: Layout,
+    debug: DebugInfo,
+    pc: usize,
+    stacks: ValueStacks,
+    /// Lines with a verified breakpoint.
+    breakpoints: BTreeSet<u32>,
+}
+
+impl Machine {
+    pub fn new(module: &CompiledModule) -> Result<Self, BackendError> {
+        let instrs = module.decode()?;
+        Ok(Machine {
+            layout: Layout::of(&instrs),
+            instrs,
+            debug: module.debug_info.clone(),
+            pc: 0,
+            stacks: ValueStacks::default(),
+            breakpoints: BTreeSet::new(),
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        self.pc >= self.instrs.len() || self.stacks.exit_code.is_some()
+    }
+
+    /// Line table row of the next instruction to execute.
+    pub fn current_row(&self) -> Option<&LineInfo> {
+        self.debug.line_info.iter().rev().find(|row| row.instruction <= self.pc)
+    }
+
+    /// Function whose body holds the next instruction to execute.
+    pub fn current_function(&self) -> Option<&FunctionInfo> {
+        self.debug.functions.iter().find(|f| f.instructions.contains(&self.pc))
+    }
+
+    /// Replace all breakpoints. Each requested line moves to the first line
+    /// at or after it that has code; `None` means there is no such line.
+    pub fn set_breakpoints(&mut self, lines: &[u32]) -> Vec<Option<u32>> {
+        self.breakpoints.clear();
+        lines
+            .iter()
+            .map(|&line| {
+                let actual = self
+                    .debug
+                    .line_info
+                    .iter()
+                    .map(|row| row.line)
+                    .filter(|&l| l >= line)
+                    .min()?;
+                self.breakpoints.insert(actual);
+                Some(actual)
+            })
+            .collect()
+    }
+
+    /// Whether the next instruction starts a line with a breakpoint.
+    pub fn at_breakpoint(&self) -> bool {
+        self.debug
+            .row_starting_at(self.pc)
+            .is_some_and(|row| self.breakpoints.contains(&row.line))
+    }
+
+    /// Run until a breakpoint or the end of the program, appending anything
+    /// printed to `out`. The current instruction always executes.
+    pub fn resume(&mut self, out: &mut String) -> Result<Option<StopReason>, String> {
+        while !self.finished() {
+            self.step_instruction(out)?;
+            if self.at_breakpoint() {
+                return Ok(Some(StopReason::Breakpoint));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run to the start of the next source line.
+    pub fn next_line(&mut self, out: &mut String) -> Result<Option<StopReason>, String> {
+        let line = self.current_row().map(|row| row.line);
+        while !self.finished() {
+            self.step_instruction(out)?;
+            let starts_line = self
+                .debug
+                .row_starting_at(self.pc)
+                .is_some_and(|row| Some(row.line) != line);
+            if starts_line && !self.finished() {
+                return Ok(Some(StopReason::Step));
+            }
+        }
+        Ok(None)
+    }
+
+    fn step_instruction(&mut self, out: &mut String) -> Result<(), String> {
+        self.stacks
+            .execute(&self.instrs[self.pc], out)
+            .map_err(|e| e.to_string())?;
+        self.pc = self.stacks.next_pc(self.pc, &self.layout).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Variables of the current function declared before the current line.
+    pub fn locals(&self) -> Vec<&VariableInfo> {
+        let Some(line) = self.current_row().map(|row| row.line) else {
+            return Vec::new();
+        };
+        let start = self.current_function().map_or(0, |f| f.line);
+        let end = self
+            .debug
+            .functions
+            .iter()
+            .map(|f| f.line)
+            .filter(|&l| l > start)
+            .min()
+            .unwrap_or(u32::MAX);
+        self.debug
+            .variables
+            .iter()
+            .filter(|v| v.line >= start && v.line < end && v.line < line)
+            .collect()
+    }
+
+    /// Contents of the value stacks as `(name, value)` pairs, bottom first.
+    pub fn stack_values(&self) -> Vec<(String, String)> {
+        let mut values = Vec::new();
+        let mut push = |kind: &str, items: Vec<String>| {
+            for (i, value) in items.into_iter().enumerate() {
+                values.push((format!("{}[{}]", kind, i), value));
+            }
+        };
+        let stacks = &self.stacks;
+        push("int", stacks.ints.iter().map(i64::to_string).collect());
+        push("float", stacks.floats.iter().map(|v| format_float(*v)).collect());
+        push("bool", stacks.bools.iter().map(|v| format_bool(*v).to_string()).collect());
+        push("str", stacks.strs.iter().map(|v| format!("{:?}", v)).collect());
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `print(1)` on line 2 and `print("a")` on line 3 of `fn main` on line 1.
+    fn module() -> CompiledModule {
+        let instrs = vec![
+            Instruction::PushInt(1),
+            Instruction::PrintInt,
+            Instruction::PushStr("a".to_string()),
+            Instruction::PrintStr,
+        ];
+        CompiledModule::from_instructions(instrs).unwrap().with_debug_info(DebugInfo {
+            source_file: Some("main.t".to_string()),
+            line_info: vec![
+                LineInfo { instruction: 0, line: 2, column: 5 },
+                LineInfo { instruction: 2, line: 3, column: 5 },
+            ],
+            variables: vec![VariableInfo {
+                name: "x".to_string(),
+                ty: "i64".to_string(),
+                const_value: Some("1".to_string()),
+                line: 2,
+                column: 9,
+            }],
+            functions: vec![FunctionInfo {
+                name: "main".to_string(),
+                line: 1,
+                column: 1,
+                instructions: 0..4,
+            }],
+            counters: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn breakpoints_stop_at_the_next_line_with_code() {
+        let mut machine = Machine::new(&module()).unwrap();
+        assert_eq!(machine.set_breakpoints(&[3, 9]), vec![Some(3), None]);
+
+        let mut out = String::new();
+        assert_eq!(machine.resume(&mut out).unwrap(), Some(StopReason::Breakpoint));
+        assert_eq!(out, "1");
+        assert_eq!(machine.current_row().map(|r| r.line), Some(3));
+        assert_eq!(machine.locals().len(), 1);
+
+        assert_eq!(machine.resume(&mut out).unwrap(), None);
+        assert_eq!(out, "1a");
+        assert!(machine.finished());
+    }
+
+    #[test]
+    fn next_runs_one_source_line() {
+        let mut machine = Machine::new(&module()).unwrap();
+        let mut out = String::new();
+        assert_eq!(machine.next_line(&mut out).unwrap(), Some(StopReason::Step));
+        assert_eq!(machine.current_row().map(|r| r.line), Some(3));
+        assert_eq!(machine.next_line(&mut out).unwrap(), None);
+        assert_eq!(out, "1a");
+    }
+}
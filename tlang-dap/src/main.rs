@@ -0,0 +1,25 @@
+// tlang-dap/src/main.rs
+//! Debug Adapter Protocol server for T-Lang programs.
+//!
+//! Speaks DAP over stdin/stdout. `launch` compiles a `.t` file to IR with
+//! debug info and runs it on a stepping interpreter, with breakpoints by
+//! source line, `next`/`continue`, a stack frame for the current function
+//! and inspection of its variables and the IR value stacks.
+
+mod machine;
+mod protocol;
+mod session;
+
+use session::Session;
+use std::io;
+
+fn main() -> io::Result<()> {
+    let mut stdin = io::stdin().lock();
+    let mut session = Session::new(io::stdout().lock());
+    while let Some(request) = protocol::read_message(&mut stdin)? {
+        if !session.handle(&request)? {
+            break;
+        }
+    }
+    Ok(())
+}
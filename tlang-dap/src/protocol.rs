@@ -0,0 +1,79 @@
+// tlang-dap/src/protocol.rs
+//! Debug Adapter Protocol framing: `Content-Length` headers around JSON bodies.
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+/// Read one message, or `None` at end of input.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            let value = value.trim().parse().map_err(|_| invalid("bad Content-Length"))?;
+            length = Some(value);
+        }
+    }
+
+    let length = length.ok_or_else(|| invalid("missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| invalid(&e.to_string()))
+}
+
+/// Write one message and flush it.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Response to `request`; a failed response carries `message` for the user.
+pub fn response(seq: i64, request: &Value, result: Result<Value, String>) -> Value {
+    let mut msg = json!({
+        "seq": seq,
+        "type": "response",
+        "request_seq": request["seq"],
+        "command": request["command"],
+        "success": result.is_ok(),
+    });
+    match result {
+        Ok(body) => msg["body"] = body,
+        Err(message) => msg["message"] = Value::String(message),
+    }
+    msg
+}
+
+/// An event with the given body.
+pub fn event(seq: i64, name: &str, body: Value) -> Value {
+    json!({ "seq": seq, "type": "event", "event": name, "body": body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_through_framing() {
+        let msg = json!({ "seq": 1, "type": "request", "command": "threads" });
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &msg).unwrap();
+        write_message(&mut bytes, &msg).unwrap();
+
+        let mut reader = io::Cursor::new(bytes);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(msg.clone()));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(msg));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
@@ -0,0 +1,260 @@
+// tlang-dap/src/session.rs
+//! One debugging session: answers DAP requests for a single launched program.
+//!
+//! Requests are handled synchronously. A request that runs the program
+//! (`configurationDone`, `continue`, `next`, ...) executes it to the next
+//! stop before replying, then sends the output and the `stopped` or
+//! `terminated` events after its response.
+
+use crate::machine::{Machine, StopReason};
+use crate::protocol::{event, response, write_message};
+use plugin_api::CompiledModule;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// T-Lang programs are single-threaded.
+const THREAD_ID: i64 = 1;
+
+/// `variablesReference` of the "Locals" scope.
+const LOCALS_REF: i64 = 1;
+
+/// `variablesReference` of the "Stack" scope (the IR value stacks).
+const STACK_REF: i64 = 2;
+
+/// How far a run request lets the program go.
+#[derive(Clone, Copy)]
+enum Run {
+    Continue,
+    Line,
+}
+
+pub struct Session<W: Write> {
+    writer: W,
+    seq: i64,
+    machine: Option<Machine>,
+    program: Option<String>,
+    stop_on_entry: bool,
+    /// Events to send after the current response, without their `seq`.
+    events: Vec<(&'static str, Value)>,
+}
+
+/// Parse and lower the program at `path`.
+fn compile(path: &str) -> Result<CompiledModule, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let program = compiler::Parser::new(source.clone())
+        .parse()
+        .map_err(|e| e.to_string())?;
+    compiler::lower_program(&program, &source, path).map_err(|e| e.to_string())
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+impl<W: Write> Session<W> {
+    pub fn new(writer: W) -> Self {
+        Session {
+            writer,
+            seq: 0,
+            machine: None,
+            program: None,
+            stop_on_entry: false,
+            events: Vec::new(),
+        }
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    /// Handle one request; returns `false` once the client has disconnected.
+    pub fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(args),
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => self.configuration_done(),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Locals", "variablesReference": LOCALS_REF, "expensive": false },
+                { "name": "Stack", "variablesReference": STACK_REF, "expensive": false },
+            ]})),
+            "variables" => self.variables(args),
+            "continue" => self.run(Run::Continue).map(|_| json!({ "allThreadsContinued": true })),
+            // There is a single frame, so stepping in is stepping over and
+            // stepping out runs to the end of `main`.
+            "next" | "stepIn" => self.run(Run::Line).map(|_| json!({})),
+            "stepOut" => self.run(Run::Continue).map(|_| json!({})),
+            // The program only runs inside a request, so it is always paused.
+            "pause" => Ok(json!({})),
+            "disconnect" | "terminate" => Ok(json!({})),
+            _ => Err(format!("unsupported request '{}'", command)),
+        };
+
+        let seq = self.next_seq();
+        write_message(&mut self.writer, &response(seq, request, result))?;
+        for (name, body) in std::mem::take(&mut self.events) {
+            let seq = self.next_seq();
+            write_message(&mut self.writer, &event(seq, name, body))?;
+        }
+        Ok(!matches!(command, "disconnect" | "terminate"))
+    }
+
+    fn machine(&mut self) -> Result<&mut Machine, String> {
+        self.machine.as_mut().ok_or_else(|| "no program launched".to_string())
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let program = args["program"]
+            .as_str()
+            .ok_or("launch needs a 'program' path")?
+            .to_string();
+        let module = compile(&program)?;
+        if module.debug_info.is_empty() {
+            return Err(format!("{} has no code to debug", program));
+        }
+        self.machine = Some(Machine::new(&module).map_err(|e| e.to_string())?);
+        self.program = Some(program);
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.events.push(("initialized", json!({})));
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["source"]["path"].as_str().unwrap_or_default();
+        let lines: Vec<u32> = args["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|bp| bp["line"].as_u64())
+            .map(|line| line as u32)
+            .collect();
+
+        let ours = self.program.as_deref().is_some_and(|p| same_file(p, path));
+        let machine = self.machine()?;
+        let verified = if ours {
+            machine.set_breakpoints(&lines)
+        } else {
+            vec![None; lines.len()]
+        };
+        let breakpoints: Vec<Value> = lines
+            .iter()
+            .zip(verified)
+            .map(|(&requested, actual)| match actual {
+                Some(line) => json!({ "verified": true, "line": line }),
+                None => json!({
+                    "verified": false,
+                    "line": requested,
+                    "message": "no code on or after this line",
+                }),
+            })
+            .collect();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn configuration_done(&mut self) -> Result<Value, String> {
+        let stop_on_entry = self.stop_on_entry;
+        let machine = self.machine()?;
+        if stop_on_entry {
+            self.stopped("entry");
+        } else if machine.at_breakpoint() {
+            self.stopped(StopReason::Breakpoint.as_str());
+        } else {
+            self.run(Run::Continue)?;
+        }
+        Ok(json!({}))
+    }
+
+    /// Run the program and queue the events describing where it stopped.
+    fn run(&mut self, run: Run) -> Result<(), String> {
+        let machine = self.machine()?;
+        let mut out = String::new();
+        let stop = match run {
+            Run::Continue => machine.resume(&mut out),
+            Run::Line => machine.next_line(&mut out),
+        };
+
+        if !out.is_empty() {
+            self.events.push(("output", json!({ "category": "stdout", "output": out })));
+        }
+        match stop {
+            Ok(Some(reason)) => self.stopped(reason.as_str()),
+            Ok(None) => self.terminated(0),
+            Err(msg) => {
+                self.events.push(("output", json!({ "category": "stderr", "output": msg + "\n" })));
+                self.terminated(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn stopped(&mut self, reason: &str) {
+        self.events.push((
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        ));
+    }
+
+    fn terminated(&mut self, exit_code: i32) {
+        self.events.push(("exited", json!({ "exitCode": exit_code })));
+        self.events.push(("terminated", json!({})));
+    }
+
+    fn stack_trace(&mut self) -> Result<Value, String> {
+        let path = self.program.clone().unwrap_or_default();
+        let machine = self.machine()?;
+        let frames: Vec<Value> = match machine.current_row() {
+            Some(row) if !machine.finished() => {
+                let name = machine.current_function().map_or("main", |f| f.name.as_str());
+                let file = Path::new(&path).file_name().map(|f| f.to_string_lossy());
+                vec![json!({
+                    "id": 1,
+                    "name": name,
+                    "source": { "name": file, "path": path },
+                    "line": row.line,
+                    "column": row.column,
+                })]
+            }
+            _ => Vec::new(),
+        };
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&mut self, args: &Value) -> Result<Value, String> {
+        let machine = self.machine()?;
+        let variables: Vec<Value> = match args["variablesReference"].as_i64() {
+            Some(LOCALS_REF) => machine
+                .locals()
+                .into_iter()
+                .map(|v| {
+                    json!({
+                        "name": v.name,
+                        "value": v.const_value.as_deref().unwrap_or("<unavailable>"),
+                        "type": v.ty,
+                        "variablesReference": 0,
+                    })
+                })
+                .collect(),
+            Some(STACK_REF) => machine
+                .stack_values()
+                .into_iter()
+                .map(|(name, value)| json!({ "name": name, "value": value, "variablesReference": 0 }))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+}
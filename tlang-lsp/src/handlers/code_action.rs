@@ -1,66 +1,73 @@
 // tlang-lsp/src/handlers/code_action.rs
+//! Quick-fixes for published diagnostics.
+//!
+//! A diagnostic for an undefined name carries the names the compiler
+//! suggests in its `data` (see `diagnostics::to_diagnostic`); each becomes a
+//! fix replacing the name, with the nearest one preferred. A lint warning
+//! carries its fix there whole: a title and the edit to make.
 
-use tower_lsp::lsp_types::*;
-use tower_lsp::jsonrpc::Result;
-use crate::handlers::diagnostics::Backend;
-use shared::tokenizer::tokenize;
-use compiler::parser::Parser;
-use shared::ast::*;
-use errors::tl::TlError;  // our rich diagnostics type
+use std::collections::HashMap;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, TextEdit, Url, WorkspaceEdit,
+};
 
-/// Handle `textDocument/codeAction` requests.
-pub async fn handle_code_action(
-    backend: &Backend,
-    params: CodeActionParams
-) -> Result<Option<CodeActionResponse>> {
-    let uri = params.text_document.uri.clone();
-    let range = params.range;
-    let context = params.context;
+/// Key of the suggested names in a diagnostic's `data`.
+pub const SUGGESTIONS: &str = "suggestions";
 
-    // Only respond if there's at least one diagnostic in this range
-    let mut actions = Vec::new();
-    for diag in &context.diagnostics {
-        // We expect our TlError’s code and span in the diagnostic’s data
-        if let Some(data) = &diag.data {
-            if let Some(err) = backend.err_map.read().await.get(&uri).and_then(|v| v.get(data.as_u64().unwrap() as usize)) {
-                // Generate a “Fix” text edit for simple cases (e.g. missing semicolon)
-                if err.code == ErrorCode::MissingToken && err.message.contains("`;`") {
-                    let fix = WorkspaceEdit {
-                        changes: Some({
-                            let mut m = std::collections::HashMap::new();
-                            m.insert(
-                                uri.clone(),
-                                vec![TextEdit {
-                                    range: Range {
-                                        start: Position::new(range.end.line, range.end.character),
-                                        end:   Position::new(range.end.line, range.end.character),
-                                    },
-                                    new_text: ";".to_string(),
-                                }],
-                            );
-                            m
-                        }),
-                        ..Default::default()
-                    };
-                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                        title: "Insert missing `;`".into(),
-                        kind: Some(CodeActionKind::QUICKFIX),
-                        diagnostics: Some(vec![diag.clone()]),
-                        edit: Some(fix),
-                        command: None,
-                        is_preferred: Some(true),
-                        ..Default::default()
-                    }));
-                }
+/// Key of a lint warning's fix in a diagnostic's `data`.
+pub const FIX: &str = "fix";
 
-                // You can add more fix patterns here for other ErrorCodes...
-            }
-        }
-    }
+/// Names suggested by `diagnostic`, nearest first.
+fn suggestions(diagnostic: &Diagnostic) -> Vec<&str> {
+    diagnostic
+        .data
+        .as_ref()
+        .and_then(|data| data.get(SUGGESTIONS))
+        .and_then(|names| names.as_array())
+        .map(|names| names.iter().filter_map(|name| name.as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// The title and edit of the fix `diagnostic` carries, if any.
+fn fix(diagnostic: &Diagnostic) -> Option<(String, TextEdit)> {
+    let fix = diagnostic.data.as_ref()?.get(FIX)?;
+    let title = fix.get("title")?.as_str()?.to_string();
+    let edit = serde_json::from_value(fix.get("edit")?.clone()).ok()?;
+    Some((title, edit))
+}
 
-    if actions.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(actions))
+/// A quick-fix for `diagnostic` making `edit` to the document at `uri`.
+fn quick_fix(
+    uri: &Url,
+    diagnostic: &Diagnostic,
+    title: String,
+    edit: TextEdit,
+    preferred: bool,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..WorkspaceEdit::default()
+        }),
+        is_preferred: Some(preferred),
+        ..CodeAction::default()
+    })
+}
+
+/// The quick-fixes for `diagnostics` of the document at `uri`.
+pub fn code_actions(uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+    for diagnostic in diagnostics {
+        for (i, name) in suggestions(diagnostic).into_iter().enumerate() {
+            let edit = TextEdit::new(diagnostic.range, name.to_string());
+            actions.push(quick_fix(uri, diagnostic, format!("Replace with `{}`", name), edit, i == 0));
+        }
+        if let Some((title, edit)) = fix(diagnostic) {
+            actions.push(quick_fix(uri, diagnostic, title, edit, true));
+        }
     }
+    actions
 }
@@ -1,102 +1,151 @@
 // tlang-lsp/src/handlers/completion.rs
+//! Completion from the compiler's name resolution tables.
+//!
+//! After `.` the receiver is resolved in the `SymbolTable` and the fields and
+//! methods of its type are offered; anywhere else every name visible at the
+//! cursor is offered, followed by the keywords.
 
-use tower_lsp::lsp_types::*;
-use tower_lsp::jsonrpc::Result;
-use std::collections::HashSet;
-use std::sync::Arc;
-
-use shared::tokenizer::tokenize;
-use compiler::{parser::Parser, sema};
-use crate::handlers::diagnostics::Backend;
+use compiler::resolve::{type_name, Member};
+use compiler::{Symbol, SymbolKind, SymbolTable};
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind};
 
 /// The fixed list of T‑Lang keywords to always suggest.
 const KEYWORDS: &[&str] = &[
     "let", "const", "fn", "if", "else", "while", "loop", "for",
     "return", "match", "struct", "enum", "trait", "impl", "use",
     "async", "await", "move", "pub", "mod", "static", "extern",
+    "spawn", "defer",
 ];
 
-/// Handle “textDocument/completion” requests.
-pub async fn handle_completion(
-    backend: &Backend,
-    params: CompletionParams,
-) -> Result<Option<CompletionResponse>> {
-    let uri = &params.text_document_position.text_document.uri;
-    let pos = &params.text_document_position.position;
-
-    // 1) Get current buffer
-    let source = {
-        let docs = backend.docs.read().await;
-        if let Some(src) = docs.get(uri) {
-            src.clone()
-        } else {
-            return Ok(None);
-        }
-    };
-
-    // 2) Tokenize entire file
-    let tokens = match tokenize(&source) {
-        Ok(t) => t,
-        Err(_) => return Ok(None),
-    };
-
-    // 3) Build AST + semantic table
-    let mut parser = match Parser::from_tokens(tokens.clone()) {
-        Ok(p) => p,
-        Err(_) => return Ok(None),
-    };
-    let stmts = match parser.parse_all() {
-        Ok(s) => s,
-        Err(_) => return Ok(None),
-    };
-    let mut checker = sema::Checker::new();
-    let _ = checker.check(&stmts); // ignore errors here
-
-    // 4) Get all in‑scope names: variables and functions
-    let mut names = HashSet::new();
-    // a) local & param names from current scopes
-    for scope in checker.scopes() {
-        for var in &scope.locals {
-            names.insert(var.clone());
-        }
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Start of the identifier that ends at `offset`.
+fn word_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_ident(c))
+        .last()
+        .map_or(offset, |(i, _)| i)
+}
+
+/// Receiver name when the cursor follows `name.` (possibly mid-member).
+fn receiver(text: &str, offset: usize) -> Option<&str> {
+    let dot = word_start(text, offset).checked_sub(1)?;
+    if !text[dot..].starts_with('.') {
+        return None;
     }
-    // b) all functions
-    for func in checker.functions() {
-        names.insert(func.name.clone());
+    let start = word_start(text, dot);
+    (start < dot).then(|| &text[start..dot])
+}
+
+fn symbol_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::Function => CompletionItemKind::FUNCTION,
+        SymbolKind::Struct | SymbolKind::Union => CompletionItemKind::STRUCT,
+        SymbolKind::Enum => CompletionItemKind::ENUM,
+        SymbolKind::Trait => CompletionItemKind::INTERFACE,
+        SymbolKind::TypeAlias => CompletionItemKind::TYPE_PARAMETER,
+        SymbolKind::Const | SymbolKind::Static => CompletionItemKind::CONSTANT,
+        SymbolKind::Module => CompletionItemKind::MODULE,
+        SymbolKind::Macro => CompletionItemKind::SNIPPET,
+        SymbolKind::Parameter | SymbolKind::Variable => CompletionItemKind::VARIABLE,
+    }
+}
+
+fn symbol_item(symbol: &Symbol) -> CompletionItem {
+    CompletionItem {
+        label: symbol.name.clone(),
+        kind: Some(symbol_kind(symbol.kind)),
+        detail: symbol.ty.as_ref().map(type_name),
+        ..Default::default()
     }
+}
 
-    // 5) Build CompletionItems
-    let mut items = Vec::new();
-    // Keywords
-    for &kw in KEYWORDS {
-        items.push(CompletionItem {
-            label: kw.into(),
-            kind: Some(CompletionItemKind::KEYWORD),
-            insert_text: Some(format!("{} ", kw)),
-            detail: Some("keyword".into()),
-            ..Default::default()
-        });
+fn member_item(member: &Member) -> CompletionItem {
+    let kind = if member.is_method { CompletionItemKind::METHOD } else { CompletionItemKind::FIELD };
+    CompletionItem {
+        label: member.name.clone(),
+        kind: Some(kind),
+        detail: member.ty.as_ref().map(type_name),
+        ..Default::default()
     }
-    // Scope names
-    for name in names {
-        items.push(CompletionItem {
-            label: name.clone().into(),
-            kind: Some(CompletionItemKind::VARIABLE),
-            insert_text: Some(name.clone()),
-            detail: Some("symbol".into()),
-            ..Default::default()
-        });
+}
+
+/// Completion items for the cursor at byte `offset` of `text`.
+pub fn completions(text: &str, symbols: &SymbolTable, offset: usize) -> Vec<CompletionItem> {
+    let offset = offset.min(text.len());
+
+    if let Some(name) = receiver(text, offset) {
+        // Members only: an unresolved receiver offers nothing rather than guesses.
+        let members = symbols
+            .resolve(name, offset)
+            .and_then(|symbol| symbol.ty.as_ref())
+            .map_or(&[][..], |ty| symbols.members_of_type(ty));
+        return members.iter().map(member_item).collect();
     }
 
-    Ok(Some(CompletionResponse::Array(items)))
+    let mut items: Vec<CompletionItem> =
+        symbols.visible_at(offset).into_iter().map(symbol_item).collect();
+    items.extend(KEYWORDS.iter().map(|&kw| CompletionItem {
+        label: kw.into(),
+        kind: Some(CompletionItemKind::KEYWORD),
+        insert_text: Some(format!("{} ", kw)),
+        detail: Some("keyword".into()),
+        ..Default::default()
+    }));
+    items
 }
 
-#[tower_lsp::async_trait]
-impl tower_lsp::lsp_types::LanguageServer for Backend {
-    async fn completion(
-        &self,
-        params: CompletionParams,
-    ) -> Result<Option<CompletionResponse>, tower_lsp::jsonrpc::Error> {
-        handle_completion(self, params).await
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compiler::Database;
+
+    const SOURCE: &str = "struct Point {\n    x: i64,\n    y: i64,\n}\n\n\
+                          fn other(a: i64) -> i64 {\n    let hidden = a;\n    hidden\n}\n\n\
+                          fn main() {\n    let p = Point { x: 1, y: 2 };\n    println(p.x);\n    \
+                          let late = 3;\n    println(other(late));\n}\n";
+
+    fn symbols(text: &str) -> SymbolTable {
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        db.set_text(file, text);
+        let (program, diagnostics) = crate::handlers::diagnostics::check_document(&mut db, file);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        SymbolTable::build(&program)
+    }
+
+    /// Labels of the completions at `offset` that are not keywords.
+    fn names(symbols: &SymbolTable, offset: usize) -> Vec<String> {
+        let items = completions(SOURCE, symbols, offset).into_iter();
+        items.filter(|item| item.kind != Some(CompletionItemKind::KEYWORD)).map(|item| item.label).collect()
+    }
+
+    #[test]
+    fn only_names_in_scope_at_the_cursor_are_offered() {
+        let symbols = symbols(SOURCE);
+        // At the start of `println(p.x)`: `late` is declared after it, and
+        // `a` and `hidden` belong to `other`.
+        let before = names(&symbols, SOURCE.find("println(p").unwrap());
+        for name in ["p", "main", "other", "Point"] {
+            assert!(before.iter().any(|n| n == name), "{} missing from {:?}", name, before);
+        }
+        for name in ["late", "a", "hidden"] {
+            assert!(!before.iter().any(|n| n == name), "{} offered in {:?}", name, before);
+        }
+        let after = names(&symbols, SOURCE.find("println(other").unwrap());
+        assert!(after.iter().any(|n| n == "late"), "{:?}", after);
+        assert!(completions(SOURCE, &symbols, 0).iter().any(|item| item.label == "let"));
+    }
+
+    #[test]
+    fn members_of_the_receiver_type_follow_a_dot() {
+        let symbols = symbols(SOURCE);
+        let members = completions(SOURCE, &symbols, SOURCE.find("p.x").unwrap() + 2);
+        let members: Vec<_> = members.iter().map(|item| (item.label.as_str(), item.kind)).collect();
+        assert_eq!(members, [("x", Some(CompletionItemKind::FIELD)), ("y", Some(CompletionItemKind::FIELD))]);
     }
 }
@@ -1,180 +1,241 @@
 // tlang-lsp/src/handlers/document_symbols.rs
+//! Outline and symbol search.
+//!
+//! `document_symbols` turns a program's items into the nested outline shown
+//! by editors; `workspace_symbols` flattens the outlines of many documents
+//! and keeps the entries whose name fuzzily matches a query.
 
-use tower_lsp::lsp_types::*;
-use tower_lsp::jsonrpc::Result;
-use crate::handlers::diagnostics::Backend;
-use shared::tokenizer::tokenize;
-use compiler::parser::Parser;
-use std::sync::Arc;
-
-/// Handle “textDocument/documentSymbol” requests.
-pub async fn handle_document_symbols(
-    backend: &Backend,
-    params: DocumentSymbolParams,
-) -> Result<Option<DocumentSymbolResponse>> {
-    let uri = params.text_document.uri.clone();
-
-    // 1) Fetch the source
-    let docs = backend.docs.read().await;
-    let source = match docs.get(&uri) {
-        Some(text) => text.clone(),
-        None => return Ok(None),
-    };
-    drop(docs);
-
-    // 2) Tokenize + parse
-    let tokens = match tokenize(&source) {
-        Ok(t) => t,
-        Err(_) => return Ok(None),
-    };
-    let mut parser = match Parser::from_tokens(tokens.clone()) {
-        Ok(p) => p,
-        Err(_) => return Ok(None),
-    };
-    let items = match parser.parse_module() {
-        Ok(stmts) => stmts,
-        Err(_) => return Ok(None),
-    };
+use crate::utils::offset_to_range;
+use miette::SourceSpan;
+use shared::ast::stmt::{ImplItem, StructFields, TraitItem};
+use shared::{Item, ItemKind, Program, TypeKind};
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{DocumentSymbol, Location, SymbolInformation, SymbolKind, Url};
 
-    // 3) Convert AST items into DocumentSymbols
-    let mut symbols = Vec::new();
-    for item in items {
-        if let Some(sym) = to_symbol(&item, &source) {
-            symbols.push(sym);
-        }
+#[allow(deprecated)] // `deprecated` is a required field of the LSP type.
+fn symbol(
+    text: &str,
+    name: String,
+    kind: SymbolKind,
+    span: SourceSpan,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    let range = offset_to_range(text, span);
+    DocumentSymbol {
+        name,
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: (!children.is_empty()).then_some(children),
     }
+}
 
-    Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+fn fields(text: &str, fields: &StructFields) -> Vec<DocumentSymbol> {
+    match fields {
+        StructFields::Named(fields) => fields
+            .iter()
+            .map(|f| symbol(text, f.name.clone(), SymbolKind::FIELD, f.span, Vec::new()))
+            .collect(),
+        StructFields::Unnamed(_) | StructFields::Unit => Vec::new(),
+    }
 }
 
-/// Recursively map an AST `Item` into a `DocumentSymbol`
-fn to_symbol(item: &shared::ast::Item, src: &str) -> Option<DocumentSymbol> {
-    use shared::ast::Item::*;
-    match item {
-        Module { name, items, span, .. } |
-        Struct  { name, fields: _, span, .. } |
-        Enum    { name, variants: _, span, .. } |
-        Trait   { name, items: _, span, .. } |
-        ExternBlock { abi: name, items: _, span, .. } |
-        TypeAlias { name, .. } |
-        Const   { name, .. } |
-        Static  { name, .. } => {
-            let range = to_range(*span, src);
-            // Gather children for nested items (e.g. module contents, impl members)
-            let children = match item {
-                Module { items, .. } => items.iter().filter_map(|i| to_symbol(i, src)).collect(),
-                Trait { items, .. }  => items.iter().filter_map(|i| to_symbol(i, src)).collect(),
-                ExternBlock { items, .. } => items.iter().filter_map(|i| to_symbol(i, src)).collect(),
-                _ => Vec::new(),
-            };
-            Some(DocumentSymbol {
-                name: name.clone(),
-                detail: Some(item_kind(item)),
-                kind: symbol_kind(item),
-                range,
-                selection_range: range,
-                children: if children.is_empty() { None } else { Some(children) },
-                deprecated: None,
-            })
+/// Outline entry for an item, if it is one the outline shows.
+fn item_symbol(text: &str, item: &Item) -> Option<DocumentSymbol> {
+    let span = item.span;
+    let leaf = |name: &str, kind| symbol(text, name.to_string(), kind, span, Vec::new());
+    let entry = match &item.kind {
+        ItemKind::Function { name, .. } => leaf(name, SymbolKind::FUNCTION),
+        ItemKind::Struct { name, fields: f, .. } => {
+            symbol(text, name.clone(), SymbolKind::STRUCT, span, fields(text, f))
+        }
+        ItemKind::Union { name, fields: f, .. } => {
+            let f = StructFields::Named(f.clone());
+            symbol(text, name.clone(), SymbolKind::STRUCT, span, fields(text, &f))
         }
-        Function { signature, body, span, .. } => {
-            let range = to_range(*span, src);
-            let sel = to_range(signature.span, src);
-            let children = body.stmts.iter()
-                .filter_map(|stmt| match stmt {
-                    shared::ast::Stmt::Item(it) => to_symbol(it, src),
+        ItemKind::Enum { name, variants, .. } => {
+            let variants = variants
+                .iter()
+                .map(|v| symbol(text, v.name.clone(), SymbolKind::ENUM_MEMBER, v.span, Vec::new()))
+                .collect();
+            symbol(text, name.clone(), SymbolKind::ENUM, span, variants)
+        }
+        ItemKind::Trait { name, items, .. } => {
+            let methods = items
+                .iter()
+                .filter_map(|i| match i {
+                    TraitItem::Function { name, .. } => Some(leaf(name, SymbolKind::METHOD)),
                     _ => None,
                 })
                 .collect();
-            Some(DocumentSymbol {
-                name: signature.name.clone(),
-                detail: Some(format!("fn({}) → {}",
-                                     signature.params.iter().map(|p| p.pat.to_string()).collect::<Vec<_>>().join(", "),
-                                     signature.return_ty.as_ref().map(|t| t.display()).unwrap_or_default()
-                )),
-                kind: SymbolKind::FUNCTION,
-                range,
-                selection_range: sel,
-                children: if children.is_empty() { None } else { Some(children) },
-                deprecated: None,
-            })
+            symbol(text, name.clone(), SymbolKind::INTERFACE, span, methods)
         }
-        Impl { items, span, .. } => {
-            let range = to_range(*span, src);
-            let children = items.iter().filter_map(|i| to_symbol(i, src)).collect();
-            Some(DocumentSymbol {
-                name: "impl".into(),
-                detail: None,
-                kind: SymbolKind::INTERFACE, // or STRUCT
-                range,
-                selection_range: range,
-                children: if children.is_empty() { None } else { Some(children) },
-                deprecated: None,
-            })
+        ItemKind::Impl { self_ty, items, .. } => {
+            let TypeKind::Named { path, .. } = &self_ty.kind else {
+                return None;
+            };
+            let methods = items
+                .iter()
+                .filter_map(|i| match i {
+                    ImplItem::Function { name, .. } => Some(leaf(name, SymbolKind::METHOD)),
+                    _ => None,
+                })
+                .collect();
+            symbol(text, format!("impl {}", path.join("::")), SymbolKind::OBJECT, span, methods)
         }
-        _ => None,
-    }
+        ItemKind::TypeAlias { name, .. } => leaf(name, SymbolKind::TYPE_PARAMETER),
+        ItemKind::Const { name, .. } => leaf(name, SymbolKind::CONSTANT),
+        ItemKind::Static { name, .. } => leaf(name, SymbolKind::VARIABLE),
+        ItemKind::Macro { name, .. } => leaf(name, SymbolKind::FUNCTION),
+        ItemKind::Module { name, items, .. } => {
+            let children = items.iter().filter_map(|i| item_symbol(text, i)).collect();
+            symbol(text, name.clone(), SymbolKind::MODULE, span, children)
+        }
+        ItemKind::Use { .. } | ItemKind::Extern { .. } | ItemKind::Error => return None,
+    };
+    Some(entry)
+}
+
+/// Outline of a document.
+pub fn document_symbols(text: &str, program: &Program) -> Vec<DocumentSymbol> {
+    program.items.iter().filter_map(|item| item_symbol(text, item)).collect()
+}
+
+/// Whether every character of `query` appears in `name`, in order,
+/// ignoring case.
+pub fn fuzzy_match(query: &str, name: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| name.any(|c| c == q))
 }
 
-/// Heuristic: map AST item to `SymbolKind`
-fn symbol_kind(item: &shared::ast::Item) -> SymbolKind {
-    use shared::ast::Item::*;
-    match item {
-        Module { .. }      => SymbolKind::MODULE,
-        Struct { .. }      => SymbolKind::STRUCT,
-        Enum { .. }        => SymbolKind::ENUM,
-        Trait { .. }       => SymbolKind::INTERFACE,
-        Function { .. }    => SymbolKind::FUNCTION,
-        Const { .. }       => SymbolKind::CONSTANT,
-        Static { .. }      => SymbolKind::VARIABLE,
-        TypeAlias { .. }   => SymbolKind::TYPE_PARAMETER,
-        ExternBlock { .. } => SymbolKind::MODULE,
-        _                  => SymbolKind::VARIABLE,
+#[allow(deprecated)] // `deprecated` is a required field of the LSP type.
+fn flatten(
+    uri: &Url,
+    symbols: Vec<DocumentSymbol>,
+    container: Option<&str>,
+    query: &str,
+    out: &mut Vec<SymbolInformation>,
+) {
+    for sym in symbols {
+        if fuzzy_match(query, &sym.name) {
+            out.push(SymbolInformation {
+                name: sym.name.clone(),
+                kind: sym.kind,
+                tags: None,
+                deprecated: None,
+                location: Location::new(uri.clone(), sym.range),
+                container_name: container.map(str::to_string),
+            });
+        }
+        if let Some(children) = sym.children {
+            flatten(uri, children, Some(&sym.name), query, out);
+        }
     }
 }
 
-/// A short description for hover/detail
-fn item_kind(item: &shared::ast::Item) -> String {
-    use shared::ast::Item::*;
-    match item {
-        Module { .. }      => "module",
-        Struct { .. }      => "struct",
-        Enum { .. }        => "enum",
-        Trait { .. }       => "trait",
-        Function { .. }    => "function",
-        Const { .. }       => "const",
-        Static { .. }      => "static",
-        TypeAlias { .. }   => "type alias",
-        ExternBlock { .. } => "extern",
-        _                  => "",
-    }.to_string()
+/// Symbols of `documents` (URI, text, program) whose name matches `query`.
+pub fn workspace_symbols<'a>(
+    query: &str,
+    documents: impl IntoIterator<Item = (&'a Url, &'a str, &'a Program)>,
+) -> Vec<SymbolInformation> {
+    let mut out = Vec::new();
+    for (uri, text, program) in documents {
+        flatten(uri, document_symbols(text, program), None, query, &mut out);
+    }
+    out
 }
 
-/// Convert our `Span` into an LSP `Range`
-fn to_range(span: shared::ast::Span, src: &str) -> Range {
-    // naive: count newlines up to `start`, then count columns
-    let (line, col) = {
-        let mut l = 0;
-        let mut c = 0;
-        for (i, ch) in src.char_indices() {
-            if i == span.start { break; }
-            if ch == '\n' { l += 1; c = 0; }
-            else { c += 1; }
+/// `.t` files under `root`, skipping hidden directories and build output.
+pub fn source_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if path.is_dir() {
+                if !name.starts_with('.') && name != "target" {
+                    dirs.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "t") {
+                files.push(path);
+            }
         }
-        (l as u32, c as u32)
-    };
-    let start = Position::new(line, col);
-    // end position: advance by span.len in same line (approx)
-    let end = Position::new(line, col + span.len as u32);
-    Range::new(start, end)
+    }
+    files.sort();
+    files
 }
 
-#[tower_lsp::async_trait]
-impl tower_lsp::lsp_types::LanguageServer for Backend {
-    async fn document_symbol(
-        &self,
-        params: DocumentSymbolParams,
-    ) -> Result<Option<DocumentSymbolResponse>, tower_lsp::jsonrpc::Error> {
-        handle_document_symbols(self, params).await
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPES: &str = "struct Point {\n    x: i32,\n    y: i32,\n}\n\nenum Shape {\n    Square,\n    Circle,\n}\n\nmod geometry {\n    fn area() -> i32 {\n        0\n    }\n}\n";
+
+    /// Name and kind of an outline entry.
+    type Entry = (String, SymbolKind);
+
+    /// Name, kind and children of each entry of an outline.
+    fn outline(symbols: &[DocumentSymbol]) -> Vec<(String, SymbolKind, Vec<Entry>)> {
+        let entry = |sym: &DocumentSymbol| (sym.name.clone(), sym.kind);
+        symbols
+            .iter()
+            .map(|sym| {
+                let (name, kind) = entry(sym);
+                (name, kind, sym.children.iter().flatten().map(entry).collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn outlines_nest_members_under_their_items() {
+        let program = compiler::parse_source(SHAPES).unwrap();
+        let symbols = document_symbols(SHAPES, &program);
+        let field = |name: &str| (name.to_string(), SymbolKind::FIELD);
+        let variant = |name: &str| (name.to_string(), SymbolKind::ENUM_MEMBER);
+        assert_eq!(
+            outline(&symbols),
+            [
+                ("Point".to_string(), SymbolKind::STRUCT, vec![field("x"), field("y")]),
+                ("Shape".to_string(), SymbolKind::ENUM, vec![variant("Square"), variant("Circle")]),
+                ("geometry".to_string(), SymbolKind::MODULE, vec![("area".to_string(), SymbolKind::FUNCTION)]),
+            ]
+        );
+        assert_eq!((symbols[0].range.start.line, symbols[0].range.end.line), (0, 3));
+    }
+
+    #[test]
+    fn queries_match_characters_in_order_ignoring_case() {
+        assert!(fuzzy_match("pt", "Point"));
+        assert!(fuzzy_match("PNT", "point"));
+        assert!(fuzzy_match("", "anything"));
+        assert!(!fuzzy_match("tp", "Point"));
+        assert!(!fuzzy_match("points", "Point"));
+    }
+
+    #[test]
+    fn workspace_symbols_search_every_document() {
+        let shapes_uri = Url::parse("file:///shapes.t").unwrap();
+        let main_uri = Url::parse("file:///main.t").unwrap();
+        let main = "fn main() {\n}\n\nconst AREA: i32 = 4;\n";
+        let (shapes, main_program) = (compiler::parse_source(SHAPES).unwrap(), compiler::parse_source(main).unwrap());
+        let documents = [(&shapes_uri, SHAPES, &shapes), (&main_uri, main, &main_program)];
+
+        let found = workspace_symbols("ar", documents);
+        let found: Vec<_> =
+            found.iter().map(|s| (s.name.as_str(), s.container_name.as_deref(), s.location.uri.path())).collect();
+        let square = ("Square", Some("Shape"), "/shapes.t");
+        assert_eq!(found, [square, ("area", Some("geometry"), "/shapes.t"), ("AREA", None, "/main.t")]);
+        assert_eq!(workspace_symbols("", documents).len(), 10);
     }
 }
@@ -0,0 +1,63 @@
+// tlang-lsp/src/handlers/formatting.rs
+//! `textDocument/formatting` and `rangeFormatting` through the compiler's
+//! formatter.
+//!
+//! Indentation comes from the client's `tabSize` and `insertSpaces`; the
+//! line length limit from a `maxLineLength` property when the client sends
+//! one.
+
+use crate::utils::offset_to_position;
+use compiler::{format_lines, format_source, FormatOptions};
+use tower_lsp::lsp_types::{FormattingOptions, FormattingProperty, Range, TextEdit};
+
+/// Formatter settings for the client's options.
+pub fn format_options(options: &FormattingOptions) -> FormatOptions {
+    let defaults = FormatOptions::default();
+    let max_width = match options.properties.get("maxLineLength") {
+        Some(FormattingProperty::Number(n)) if *n > 0 => *n as usize,
+        _ => defaults.max_width,
+    };
+    FormatOptions {
+        indent_width: if options.tab_size > 0 { options.tab_size as usize } else { defaults.indent_width },
+        max_width,
+        hard_tabs: !options.insert_spaces,
+    }
+}
+
+/// A single edit replacing the whole document, if formatting changes it.
+/// `None` when the text does not tokenize.
+pub fn format_document(text: &str, options: &FormattingOptions) -> Option<Vec<TextEdit>> {
+    let formatted = format_source(text, &format_options(options)).ok()?;
+    if formatted == text {
+        return Some(Vec::new());
+    }
+    let range = Range::new(offset_to_position(text, 0), offset_to_position(text, text.len()));
+    Some(vec![TextEdit::new(range, formatted)])
+}
+
+/// An edit replacing the whole lines touched by `range`.
+pub fn format_range(text: &str, range: Range, options: &FormattingOptions) -> Option<Vec<TextEdit>> {
+    let first = range.start.line as usize;
+    // A selection ending at the start of a line does not include that line.
+    let mut last = range.end.line as usize;
+    if range.end.character == 0 && last > first {
+        last -= 1;
+    }
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let start = *line_starts.get(first)?;
+    let end = line_starts.get(last + 1).copied().unwrap_or(text.len());
+
+    let mut formatted = format_lines(text, &format_options(options), first..last + 1).ok()?;
+    // The last line of the file may have no newline to replace.
+    if !text[start..end].ends_with('\n') {
+        formatted.truncate(formatted.trim_end_matches('\n').len());
+    }
+    if formatted == text[start..end] {
+        return Some(Vec::new());
+    }
+    let range = Range::new(offset_to_position(text, start), offset_to_position(text, end));
+    Some(vec![TextEdit::new(range, formatted)])
+}
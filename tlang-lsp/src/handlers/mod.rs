@@ -1,18 +1,5 @@
-pub mod definition;
-pub mod hover;
+pub mod code_action;
 pub mod completion;
-pub mod rename;
 pub mod diagnostics;
-pub mod signature;
-pub mod references;
-pub mod code_actions;
-pub mod workspace_symbols;
-pub mod formatting;
-pub mod signature_help;
 pub mod document_symbols;
-pub mod semantic_tokens;
-pub mod inlay_hints;
-pub mod folding_ranges;
-pub mod code_lens;
-pub mod call_hierarchy;
-pub mod type_definition;
\ No newline at end of file
+pub mod formatting;
@@ -1,81 +1,155 @@
 // This file is part of the Tlang project, which is licensed under the MIT License.
 // tlang-lsp/src/main.rs
 
-use shared::ast::{AST, Statement};
-use std::{fs, sync::Arc};
+use compiler::{Database, SymbolTable};
+use shared::Program;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError};
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use tower_lsp::{
     jsonrpc::Result,
     lsp_types::*,
+    Client,
     LspService,
     Server,
 };
-use tracing;
-use crate::utils::{extract_identifier, item_name_and_span, lookup_hover, offset_to_range, position_to_offset};
+use crate::utils::{apply_change, extract_identifier, item_name_and_span, lookup_hover, offset_to_range, position_to_offset};
 
+mod handlers;
 mod utils;
 
-mod compiler {
-    use crate::Statement;
+/// Quiet period after an edit before the document is re-analysed, so a burst
+/// of keystrokes costs one parse.
+const DEBOUNCE: Duration = Duration::from_millis(250);
 
-    pub mod parser {
-        use crate::Statement;
+/// An open document and what the compiler made of it.
+#[derive(Debug)]
+struct Document {
+    /// Current text, with every edit applied.
+    text: String,
+    version: i32,
+    /// Parsed (and, when possible, type-checked) program of the last analysed
+    /// version; parts that failed to parse are `Error` nodes. `None` until the
+    /// first analysis finishes.
+    program: Option<Program>,
+    /// Names declared by `program`.
+    symbols: SymbolTable,
+    /// Pending or running analysis of the current version.
+    analysis: Option<JoinHandle<()>>,
+}
 
-        pub struct Parser {
-            src: String,
-        }
+type Documents = Arc<Mutex<HashMap<Url, Document>>>;
 
-        impl Parser {
-            pub fn new(src: String) -> Self {
-                Self { src }
-            }
+/// What the compiler knows of every file the server has seen, by URI. Held
+/// only while compiling, off the async runtime where that takes long.
+type SharedDatabase = Arc<std::sync::Mutex<Database>>;
 
-            pub fn parse(&mut self) -> Result<Vec<Statement>, String> {
-                // Simplified implementation for now
-                Ok(Vec::new())
-            }
-        }
-    }
+/// Lock `db`. Answers are only stored once complete, so one left behind by a
+/// compiler panic is still sound.
+fn lock(db: &SharedDatabase) -> std::sync::MutexGuard<'_, Database> {
+    db.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
-    pub fn check(_stmts: &[Statement]) -> Result<(), String> {
-        // Simplified implementation for now
-        Ok(())
-    }
+#[derive(Debug)]
+struct Backend {
+    client: Client,
+    /// Open documents by URI.
+    documents: Documents,
+    db: SharedDatabase,
+    /// Workspace folder searched by `workspace/symbol`.
+    root: Mutex<Option<PathBuf>>,
+}
 
-    pub mod codegen {
-        pub fn generate() {
-            // Simplified implementation for now
+impl Backend {
+    /// Replace the document's analysis task with one that starts after `delay`.
+    async fn schedule_analysis(&self, uri: Url, delay: Duration) {
+        let mut documents = self.documents.lock().await;
+        let Some(doc) = documents.get_mut(&uri) else {
+            return;
+        };
+        if let Some(task) = doc.analysis.take() {
+            task.abort();
         }
-    }
-
-    pub mod runtime {
-        pub struct Evaluator;
+        let task = analyze(
+            self.client.clone(),
+            Arc::clone(&self.documents),
+            Arc::clone(&self.db),
+            uri,
+            doc.version,
+            delay,
+        );
+        doc.analysis = Some(tokio::spawn(task));
     }
 }
 
-use self::compiler::check;
-use self::compiler::codegen::generate;
-use self::compiler::parser::Parser;
-use self::compiler::runtime::Evaluator;
+/// Check `version` of a document off the request path and publish its
+/// diagnostics, unless a newer edit arrives first.
+async fn analyze(
+    client: Client,
+    documents: Documents,
+    db: SharedDatabase,
+    uri: Url,
+    version: i32,
+    delay: Duration,
+) {
+    tokio::time::sleep(delay).await;
+    let text = match documents.lock().await.get(&uri) {
+        Some(doc) if doc.version == version => doc.text.clone(),
+        _ => return,
+    };
 
-#[derive(Debug)]
-struct Backend {
-    /// The text of the currently open document.
-    text: Mutex<String>,
-    /// Parsed AST for that text.
-    ast: Mutex<Option<AST>>,
+    let path = uri.to_string();
+    let checked = tokio::task::spawn_blocking(move || {
+        let mut db = lock(&db);
+        let file = db.file(&path);
+        db.set_text(file, &text);
+        handlers::diagnostics::check_document(&mut db, file)
+    });
+    let Ok((program, diagnostics)) = checked.await else {
+        return;
+    };
+
+    let mut docs = documents.lock().await;
+    // The document may have changed or closed while it was being checked.
+    let Some(doc) = docs.get_mut(&uri).filter(|doc| doc.version == version) else {
+        return;
+    };
+    doc.symbols = SymbolTable::build(&program);
+    doc.program = Some(program);
+    drop(docs);
+    client.publish_diagnostics(uri, diagnostics, Some(version)).await;
 }
 
 #[tower_lsp::async_trait]
 impl tower_lsp::LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        #[allow(deprecated)] // Older clients only send `root_uri`.
+        let root = params
+            .workspace_folders
+            .and_then(|folders| folders.into_iter().next().map(|f| f.uri))
+            .or(params.root_uri)
+            .and_then(|uri| uri.to_file_path().ok());
+        *self.root.lock().await = root;
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 definition_provider: Some(OneOf::Left(true)),
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![".".into()]),
+                    ..Default::default()
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
                 signature_help_provider: Some(SignatureHelpOptions {
                     trigger_characters: Some(vec![",".into(), "(".into()]),
                     retrigger_characters: Some(vec![",".into()]),
@@ -96,48 +170,63 @@ impl tower_lsp::LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let doc = params.text_document;
+        let document = Document {
+            text: doc.text,
+            version: doc.version,
+            program: None,
+            symbols: SymbolTable::default(),
+            analysis: None,
+        };
+        self.documents.lock().await.insert(doc.uri.clone(), document);
+        self.schedule_analysis(doc.uri, Duration::ZERO).await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri;
-        if uri.scheme() == "file" {
-            if let Ok(text) = fs::read_to_string(uri.to_file_path().unwrap()) {
-                *self.text.lock().await = text.clone();
-                let ast = parse_and_check(&text);
-                *self.ast.lock().await = ast;
+        {
+            let mut documents = self.documents.lock().await;
+            let Some(doc) = documents.get_mut(&uri) else {
+                return;
+            };
+            // Edits apply in order, each to the result of the previous one.
+            for change in params.content_changes {
+                apply_change(&mut doc.text, change);
             }
+            doc.version = params.text_document.version;
         }
+        self.schedule_analysis(uri, DEBOUNCE).await;
     }
 
-    async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let text = params.content_changes[0].text.clone();
-        *self.text.lock().await = text.clone();
-        let ast = parse_and_check(&text);
-        *self.ast.lock().await = ast;
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if let Some(task) = self.documents.lock().await.remove(&uri).and_then(|doc| doc.analysis) {
+            task.abort();
+        }
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        let text = self.text.lock().await.clone();
-        let file_ast = self.ast.lock().await;
-        let offset = position_to_offset(&text, params.text_document_position_params.position);
-
-        if let Some(ast) = file_ast.as_ref() {
-            if let Some(name) = extract_identifier(&text, offset) {
-                for item in &ast.items {
-                    if let Some((def_name, span)) = item_name_and_span(item) {
-                        if def_name == name {
-                            let target_range = offset_to_range(&text, span);
-                            let loc = Location {
-                                uri: params
-                                    .text_document_position_params
-                                    .text_document
-                                    .uri
-                                    .clone(),
-                                range: target_range,
-                            };
-                            return Ok(Some(GotoDefinitionResponse::Scalar(loc)));
-                        }
-                    }
+        let uri = &params.text_document_position_params.text_document.uri;
+        let documents = self.documents.lock().await;
+        let Some(Document { text, program: Some(program), .. }) = documents.get(uri) else {
+            return Ok(None);
+        };
+        let offset = position_to_offset(text, params.text_document_position_params.position);
+
+        if let Some(name) = extract_identifier(text, offset) {
+            for item in &program.items {
+                if let Some((def_name, span)) = item_name_and_span(item)
+                    && def_name == name
+                {
+                    let loc = Location {
+                        uri: uri.clone(),
+                        range: offset_to_range(text, span),
+                    };
+                    return Ok(Some(GotoDefinitionResponse::Scalar(loc)));
                 }
             }
         }
@@ -145,54 +234,121 @@ impl tower_lsp::LanguageServer for Backend {
         Ok(None)
     }
 
-    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        let text = self.text.lock().await.clone();
-        let file_ast = self.ast.lock().await;
-        let offset = position_to_offset(&text, params.text_document_position_params.position);
-
-        if let Some(ast) = file_ast.as_ref() {
-            if let Some(info) = lookup_hover(ast, offset) {
-                let range = offset_to_range(&text, info.span);
-                let contents = HoverContents::Scalar(MarkedString::String(info.message));
-                return Ok(Some(Hover {
-                    contents,
-                    range: Some(range),
-                }));
-            }
-        }
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let position = params.text_document_position;
+        let documents = self.documents.lock().await;
+        let Some(doc) = documents.get(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let offset = position_to_offset(&doc.text, position.position);
+        let items = handlers::completion::completions(&doc.text, &doc.symbols, offset);
+        Ok(Some(CompletionResponse::Array(items)))
+    }
 
-        Ok(None)
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let actions =
+            handlers::code_action::code_actions(&params.text_document.uri, &params.context.diagnostics);
+        Ok(Some(actions))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let documents = self.documents.lock().await;
+        let Some(Document { text, program: Some(program), .. }) =
+            documents.get(&params.text_document.uri)
+        else {
+            return Ok(None);
+        };
+        let symbols = handlers::document_symbols::document_symbols(text, program);
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
     }
-}
 
-/// Parse the source and run semantic check + codegen to build AST, or return None.
-fn parse_and_check(src: &str) -> Option<AST> {
-    let mut parser = Parser::new(src.to_string());
-    match parser.parse() {
-        Ok(stmts) => {
-            let ast = AST::new(src.to_string(), stmts.clone(), Vec::new());
-            if check(&stmts).is_ok() {
-                Some(ast)
-            } else {
-                None
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let documents = self.documents.lock().await;
+
+        // Files that are not open are read from disk, and parsed unless they
+        // are unchanged since last time.
+        let mut on_disk = Vec::new();
+        if let Some(root) = self.root.lock().await.as_deref() {
+            let mut db = lock(&self.db);
+            for path in handlers::document_symbols::source_files(root) {
+                let Ok(uri) = Url::from_file_path(&path) else {
+                    continue;
+                };
+                if documents.contains_key(&uri) {
+                    continue;
+                }
+                if let Ok(text) = std::fs::read_to_string(&path) {
+                    let file = db.file(uri.as_str());
+                    db.set_text(file, &text);
+                    on_disk.push((uri, text, db.parse(file)));
+                }
             }
         }
-        Err(_) => None,
+
+        let open = documents.iter().filter_map(|(uri, doc)| {
+            doc.program.as_ref().map(|program| (uri, doc.text.as_str(), program))
+        });
+        let disk = on_disk.iter().map(|(uri, text, parsed)| (uri, text.as_str(), &parsed.program));
+        let symbols = handlers::document_symbols::workspace_symbols(&params.query, open.chain(disk));
+        Ok(Some(symbols))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.documents.lock().await;
+        let Some(doc) = documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(handlers::formatting::format_document(&doc.text, &params.options))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.documents.lock().await;
+        let Some(doc) = documents.get(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(handlers::formatting::format_range(&doc.text, params.range, &params.options))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let documents = self.documents.lock().await;
+        let Some(Document { text, program: Some(program), symbols, .. }) = documents.get(uri) else {
+            return Ok(None);
+        };
+        let offset = position_to_offset(text, params.text_document_position_params.position);
+
+        Ok(lookup_hover(text, program, symbols, offset).map(|info| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: info.message,
+            }),
+            range: Some(offset_to_range(text, info.span)),
+        }))
     }
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt().init();
+    // stdout carries the protocol, so logs go to stderr.
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
 
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let backend = Backend {
-        text: Mutex::new(String::new()),
-        ast: Mutex::new(None),
-    };
-
-    let (service, socket) = LspService::new(|_client| backend);
+    let (service, socket) = LspService::new(|client| Backend {
+        client,
+        documents: Arc::new(Mutex::new(HashMap::new())),
+        db: SharedDatabase::default(),
+        root: Mutex::new(None),
+    });
     Server::new(stdin, stdout, socket).serve(service).await;
 }
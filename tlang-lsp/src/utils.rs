@@ -1,76 +1,212 @@
 // tlang-lsp/src/utils.rs
 // Utility functions for mapping between LSP positions, offsets, and AST
 
-//! File: compiler/src/ir.rs
-//!
-//! Intermediate representation (IR) instructions emitted by the compiler backend.
-
-use shared::ast::{AST, Expr, ExprKind, Literal, Stmt};
-
-/// A low‑level instruction in the compiler IR.
-#[derive(Debug, Clone, PartialEq)]
-pub enum Instr {
-    /// Push a string literal onto the stack.
-    PushStr(String),
-    /// Push an integer literal onto the stack.
-    PushInt(i64),
-    /// Push a float literal onto the stack.
-    PushFloat(f64),
-    /// Push a boolean literal onto the stack.
-    PushBool(bool),
-    /// Call the built‑in print function.
-    CallPrint,
-    // TODO: extend with other instructions as needed
+use compiler::resolve::{expr_at, type_name};
+use compiler::{Symbol, SymbolKind, SymbolTable};
+use miette::SourceSpan;
+use shared::source::LineIndex;
+use shared::{Item, ItemKind, Program, Type};
+use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+/// Byte offset of an LSP position (UTF-16 columns), clamped to the text.
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    LineIndex::new(text).utf16_to_offset(position.line, position.character)
+}
+
+/// LSP position (UTF-16 columns) of a byte offset.
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    position(&LineIndex::new(text), offset)
+}
+
+fn position(index: &LineIndex, offset: usize) -> Position {
+    let (line, character) = index.offset_to_utf16(offset);
+    Position::new(line, character)
+}
+
+/// LSP range covering a source span.
+pub fn offset_to_range(text: &str, span: SourceSpan) -> Range {
+    let index = LineIndex::new(text);
+    Range::new(position(&index, span.offset()), position(&index, span.offset() + span.len()))
 }
 
-/// Lower an AST into IR instructions.
-pub fn lower_program(ast: &AST) -> Vec<Instr> {
-    let mut instrs = Vec::new();
-    for stmt in &ast.items {
-        match stmt {
-            Stmt::Expr { expr, .. } | Stmt::Semi { expr, .. } => {
-                lower_expr(expr, &mut instrs);
-                // If this was a call to `print`, emit the print instruction
-                if let ExprKind::Call { func, .. } = &expr.kind {
-                    if let ExprKind::Variable(path, _) = &func.kind {
-                        if path == &vec!["print".to_string()] {
-                            instrs.push(Instr::CallPrint);
-                        }
-                    }
-                }
-            }
-            _ => {}
+/// Apply one `didChange` edit to `text`; a change without a range replaces
+/// the whole document.
+pub fn apply_change(text: &mut String, change: TextDocumentContentChangeEvent) {
+    match change.range {
+        Some(range) => {
+            let start = position_to_offset(text, range.start);
+            let end = position_to_offset(text, range.end).max(start);
+            text.replace_range(start..end, &change.text);
         }
+        None => *text = change.text,
     }
-    instrs
 }
 
-fn lower_expr(expr: &Expr, instrs: &mut Vec<Instr>) {
-    match &expr.kind {
-        ExprKind::Literal(lit, _) => match lit {
-            Literal::String(s) => instrs.push(Instr::PushStr(s.clone())),
-            Literal::Integer(i) => instrs.push(Instr::PushInt(*i as i64)),
-            Literal::Float(f) => instrs.push(Instr::PushFloat(*f)),
-            Literal::Boolean(b) => instrs.push(Instr::PushBool(*b)),
-            _ => {}
+/// Byte range of the identifier touching `offset`, if any.
+pub fn identifier_bounds(text: &str, offset: usize) -> Option<(usize, usize)> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let offset = offset.min(text.len());
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_ident(c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..]
+        .char_indices()
+        .find(|&(_, c)| !is_ident(c))
+        .map_or(text.len(), |(i, _)| offset + i);
+    (start < end).then_some((start, end))
+}
+
+/// The identifier touching `offset`, if any.
+pub fn extract_identifier(text: &str, offset: usize) -> Option<String> {
+    identifier_bounds(text, offset).map(|(start, end)| text[start..end].to_string())
+}
+
+/// Name an item declares, if it declares one.
+pub fn item_name(kind: &ItemKind) -> Option<&str> {
+    match kind {
+        ItemKind::Function { name, .. }
+        | ItemKind::Struct { name, .. }
+        | ItemKind::Enum { name, .. }
+        | ItemKind::Union { name, .. }
+        | ItemKind::Trait { name, .. }
+        | ItemKind::TypeAlias { name, .. }
+        | ItemKind::Const { name, .. }
+        | ItemKind::Static { name, .. }
+        | ItemKind::Module { name, .. }
+        | ItemKind::Macro { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// Name and span of a named item.
+pub fn item_name_and_span(item: &Item) -> Option<(&str, SourceSpan)> {
+    item_name(&item.kind).map(|name| (name, item.span))
+}
+
+/// What to show when hovering.
+pub struct HoverInfo {
+    /// Markdown text.
+    pub message: String,
+    pub span: SourceSpan,
+}
+
+/// How a declaration reads in hover text; `inferred` stands in for a
+/// binding's missing annotation.
+fn declaration(symbol: &Symbol, inferred: Option<&Type>) -> String {
+    let name = &symbol.name;
+    let ty = symbol.ty.as_ref().or(inferred).map(type_name);
+    let typed = |prefix: &str| match &ty {
+        Some(ty) => format!("{}{}: {}", prefix, name, ty),
+        None => format!("{}{}", prefix, name),
+    };
+    match symbol.kind {
+        // `type_name` spells function types `fn(..) -> ..`.
+        SymbolKind::Function => match &ty {
+            Some(ty) => format!("fn {}{}", name, ty.trim_start_matches("fn")),
+            None => format!("fn {}", name),
+        },
+        SymbolKind::Struct => format!("struct {}", name),
+        SymbolKind::Enum => format!("enum {}", name),
+        SymbolKind::Union => format!("union {}", name),
+        SymbolKind::Trait => format!("trait {}", name),
+        SymbolKind::Module => format!("mod {}", name),
+        SymbolKind::Macro => format!("macro_rules! {}", name),
+        SymbolKind::TypeAlias => match &ty {
+            Some(ty) => format!("type {} = {}", name, ty),
+            None => format!("type {}", name),
         },
-        ExprKind::Grouping(inner, _) => lower_expr(inner, instrs),
-        _ => {}
+        SymbolKind::Const => typed("const "),
+        SymbolKind::Static => typed("static "),
+        SymbolKind::Variable => typed("let "),
+        SymbolKind::Parameter => typed(""),
     }
 }
 
-/// A compiled module: its emitted instructions along with raw bytecode.
-#[derive(Debug, Clone)]
-pub struct CompiledModule {
-    /// Sequence of IR instructions.
-    pub instructions: Vec<Instr>,
-    /// Raw bytecode for VM execution.
-    pub bytecode: Vec<u8>,
+/// Hover for the cursor at `offset`: the declaration and doc comment of the
+/// name under it, or else the inferred type of the innermost expression.
+pub fn lookup_hover(
+    text: &str,
+    program: &Program,
+    symbols: &SymbolTable,
+    offset: usize,
+) -> Option<HoverInfo> {
+    let expr = expr_at(program, offset);
+    let inferred = expr.and_then(|e| e.ty.as_ref());
+
+    let symbol = identifier_bounds(text, offset).and_then(|(start, end)| {
+        let name = &text[start..end];
+        // A use of the name, or else its declaration.
+        let symbol = symbols.resolve(name, offset).or_else(|| {
+            symbols.symbols().iter().find(|s| {
+                s.name == name && (s.span.offset()..s.span.offset() + s.span.len()).contains(&start)
+            })
+        })?;
+        Some((symbol, start, end))
+    });
+    if let Some((symbol, start, end)) = symbol {
+        let mut message = format!("```tlang\n{}\n```", declaration(symbol, inferred));
+        if let Some(doc) = &symbol.docs {
+            message.push_str("\n\n---\n\n");
+            message.push_str(doc);
+        }
+        return Some(HoverInfo { message, span: SourceSpan::new(start.into(), end - start) });
+    }
+
+    let expr = expr?;
+    let ty = expr.ty.as_ref()?;
+    Some(HoverInfo { message: format!("```tlang\n{}\n```", type_name(ty)), span: expr.span })
 }
 
-impl CompiledModule {
-    /// Create a new module from IR and bytecode.
-    pub fn new(instructions: Vec<Instr>, bytecode: Vec<u8>) -> Self {
-        Self { instructions, bytecode }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compiler::Database;
+
+    #[test]
+    fn incremental_edits_use_utf16_positions() {
+        let mut text = String::from("let é = 1;\nlet b = 2;\n");
+        let edit = |line, start, end, new: &str| TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(line, start), Position::new(line, end))),
+            range_length: None,
+            text: new.to_string(),
+        };
+        apply_change(&mut text, edit(0, 8, 9, "42"));
+        apply_change(&mut text, edit(1, 4, 5, "bb"));
+        assert_eq!(text, "let é = 42;\nlet bb = 2;\n");
+    }
+
+    #[test]
+    fn ranges_count_utf16_columns() {
+        let text = "let 𝄞 = 1;\nlet b = 2;\n";
+        let (start, end) = (text.find('=').unwrap(), text.rfind('b').unwrap() + 1);
+        let range = offset_to_range(text, SourceSpan::new(start.into(), end - start));
+        assert_eq!(range, Range::new(Position::new(0, 7), Position::new(1, 5)));
+        assert_eq!(position_to_offset(text, range.end), end);
+    }
+
+    #[test]
+    fn hover_shows_inferred_types_and_doc_comments() {
+        let text = "/// Doubles `n`.\nfn double(n: i64) -> i64 {\n    n * 2\n}\n\n\
+                    fn main() {\n    let x = double(21);\n    println(x);\n}\n";
+        let mut db = Database::new();
+        let file = db.file("main.t");
+        db.set_text(file, text);
+        let (program, diagnostics) = crate::handlers::diagnostics::check_document(&mut db, file);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let symbols = SymbolTable::build(&program);
+        // Hover over the last use of `name`, which the hover covers.
+        let hover = |name: &str| {
+            let offset = text.rfind(name).unwrap();
+            let info = lookup_hover(text, &program, &symbols, offset).unwrap();
+            assert_eq!(&text[info.span.offset()..info.span.offset() + info.span.len()], name);
+            info.message
+        };
+
+        // `x` has no annotation; its type is the one the checker inferred.
+        assert_eq!(hover("x"), "```tlang\nlet x: i64\n```");
+        assert_eq!(hover("double"), "```tlang\nfn double(i64) -> i64\n```\n\n---\n\nDoubles `n`.");
     }
 }
@@ -0,0 +1,104 @@
+// File: tlang/src/bench.rs
+
+//! Benchmarking of T-Lang source files.
+//!
+//! Each `#[bench]` function of a file is lowered in place of `main`,
+//! compiled once with the Cranelift JIT backend and run repeatedly
+//! in-process; what it prints goes to stdout as it runs. A line per
+//! benchmark reports the mean, median and standard deviation of a run,
+//! and, against a saved baseline, how much the mean moved.
+
+use compiler::backends::cranelift_jit::CraneliftJitBackend;
+use compiler::bench::{self, Comparison};
+use compiler::{Baseline, Database, LoweringOptions};
+use plugin_api::Backend;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::runner::check_error;
+
+/// How `bench_file` runs and reports benchmarks.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Only the benchmarks whose names contain this
+    pub filter: Option<String>,
+    /// Untimed runs before the timed ones
+    pub warmup: usize,
+    /// Timed runs
+    pub iterations: usize,
+    /// How the benchmarks are lowered
+    pub lowering: LoweringOptions,
+    /// A baseline to compare the results with
+    pub baseline: Option<PathBuf>,
+    /// Where to save the results as a baseline
+    pub save_baseline: Option<PathBuf>,
+    /// The change of a mean, as a fraction, that counts as none
+    pub noise: f64,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions {
+            filter: None,
+            warmup: 10,
+            iterations: 100,
+            lowering: LoweringOptions::default(),
+            baseline: None,
+            save_baseline: None,
+            noise: 0.05,
+        }
+    }
+}
+
+/// Run the benchmarks of the file at `path` as `options` say, printing a
+/// line for each, and return their results.
+///
+/// # Errors
+/// Returns an error if the file cannot be read or does not compile, a
+/// benchmark cannot be lowered, or a baseline cannot be read or written.
+pub fn bench_file(path: &Path, options: &BenchOptions) -> Result<Baseline, Box<dyn Error>> {
+    let src = fs::read_to_string(path)?;
+    let mut db = Database::new();
+    let file = db.file(&path.to_string_lossy());
+    db.set_text(file, &src);
+    if let Some(error) = check_error(&mut db, path, file) {
+        return Err(error.into());
+    }
+    let program = &db.type_check(file).program;
+    let baseline = options.baseline.as_deref().map(Baseline::read).transpose()?;
+
+    let mut results = Baseline::default();
+    for benchmark in bench::benchmarks(program, &src)? {
+        if options.filter.as_ref().is_some_and(|filter| !benchmark.name.contains(filter.as_str())) {
+            continue;
+        }
+        let module =
+            compiler::lower_function(program, &src, &path.to_string_lossy(), &benchmark.name, options.lowering)
+                .map_err(|err| format!("cannot lower benchmark `{}`: {}", benchmark.name, err))?;
+        let compiled = CraneliftJitBackend.compile(module)?;
+        let stats = bench::measure(options.warmup, options.iterations, || {
+            compiled.run();
+        });
+
+        let mut line = format!(
+            "{:<32} mean {:>12}  median {:>12}  stddev {:>12}",
+            benchmark.name,
+            bench::format_duration(stats.mean),
+            bench::format_duration(stats.median),
+            bench::format_duration(stats.stddev),
+        );
+        match baseline.as_ref().map(|baseline| baseline.benchmarks.get(&benchmark.name)) {
+            Some(Some(before)) => line.push_str(&format!("  {}", Comparison::new(before, &stats, options.noise))),
+            Some(None) => line.push_str("  (not in the baseline)"),
+            None => {}
+        }
+        println!("{}", line);
+        results.benchmarks.insert(benchmark.name, stats);
+    }
+
+    if let Some(output) = &options.save_baseline {
+        results.write(output)?;
+    }
+    Ok(results)
+}
@@ -0,0 +1,307 @@
+// File: tlang/src/build.rs
+
+//! Build scripts: a T program run before its project's files are compiled.
+//!
+//! The script is the `script` of the `[build]` section of the `tlang.toml`
+//! beside the file compiled, or `build.t` beside it if the manifest names
+//! none. It runs in the interpreter, and what it prints tells the build what
+//! to do, one `tlang:` directive per line:
+//!
+//! * `tlang:feature=NAME` turns on the feature `NAME`, keeping the items
+//!   marked `#[cfg(NAME)]`;
+//! * `tlang:generated=PATH` writes the lines that follow, up to the next
+//!   directive, to `PATH` under the project's directory; a generated `.t`
+//!   file's items are compiled along with the file's own;
+//! * `tlang:rerun-if-changed=PATH` reruns the script when `PATH` changes.
+//!
+//! Other lines are the script's to print. The `features` of the `[build]`
+//! section are on too. What the script printed is kept in `STAMP`, with a
+//! fingerprint of the script, the manifest and each path it named, and the
+//! script reruns only when one of those changes; until then its output is
+//! read back from the stamp.
+
+use compiler::LoweringOptions;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Where the output of a project's build script is kept, beside its manifest.
+pub const STAMP: &str = ".tlang-build";
+
+/// What a project's build script asks of its build.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Build {
+    /// The features turned on
+    pub features: BTreeSet<String>,
+    /// Each file generated, relative to the project's directory, with its
+    /// contents
+    pub generated: Vec<(PathBuf, String)>,
+    /// The paths whose changes rerun the script
+    pub rerun_if_changed: Vec<PathBuf>,
+    /// Whether the script ran, rather than its output being read from
+    /// `STAMP`
+    pub ran: bool,
+}
+
+impl Build {
+    /// `text`, the source of the file compiled, followed by that of each
+    /// generated `.t` file.
+    pub fn source(&self, mut text: String) -> String {
+        for (path, contents) in &self.generated {
+            if path.extension().is_some_and(|extension| extension == "t") {
+                text.push('\n');
+                text.push_str(contents);
+            }
+        }
+        text
+    }
+}
+
+/// Prepare the build of the file at `path`: run its project's build script
+/// unless nothing it depends on changed since it last ran, and write the
+/// files it generates. A project without one builds with nothing on.
+///
+/// # Errors
+/// Returns an error if the manifest is invalid, or the script fails to
+/// compile, exits with a code other than 0 or prints a bad directive.
+pub fn prepare(path: &Path) -> Result<Build, Box<dyn Error>> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let manifest = dir.join("tlang.toml");
+    let (script, mut features) = build_section(&manifest)?;
+    let script = script.unwrap_or_else(|| PathBuf::from("build.t"));
+    if !dir.join(&script).exists() || same_file(&dir.join(&script), path) {
+        return Ok(Build { features, ..Build::default() });
+    }
+
+    let stamp = dir.join(STAMP);
+    let (output, ran) = match fresh_output(&stamp, dir) {
+        Some(output) => (output, false),
+        None => (run_script(&dir.join(&script))?, true),
+    };
+    let mut build = parse_directives(&output)?;
+    if ran {
+        let mut inputs = vec![script, PathBuf::from("tlang.toml")];
+        inputs.extend(build.rerun_if_changed.iter().cloned());
+        fs::write(&stamp, stamp_text(dir, &inputs, &output))?;
+    }
+    for (path, contents) in &build.generated {
+        let path = dir.join(path);
+        if fs::read_to_string(&path).ok().as_deref() != Some(contents.as_str()) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, contents)?;
+        }
+    }
+    build.features.append(&mut features);
+    build.ran = ran;
+    Ok(build)
+}
+
+/// The `script` and `features` of the `[build]` section of the manifest at
+/// `manifest`, if there is one.
+fn build_section(manifest: &Path) -> Result<(Option<PathBuf>, BTreeSet<String>), Box<dyn Error>> {
+    if !manifest.exists() {
+        return Ok((None, BTreeSet::new()));
+    }
+    let invalid = |message: String| format!("invalid manifest {}: {}", manifest.display(), message);
+    let table: toml::Table = fs::read_to_string(manifest)?.parse().map_err(|err| invalid(format!("{}", err)))?;
+    let Some(section) = table.get("build") else {
+        return Ok((None, BTreeSet::new()));
+    };
+    let section = section.as_table().ok_or_else(|| invalid("`build` is not a table".to_string()))?;
+    let mut script = None;
+    let mut features = BTreeSet::new();
+    for (key, value) in section {
+        match (key.as_str(), value) {
+            ("script", toml::Value::String(path)) => script = Some(project_path(path)?),
+            ("features", toml::Value::Array(names)) => {
+                for name in names {
+                    let name = name.as_str().ok_or_else(|| invalid("a build feature is not a string".to_string()))?;
+                    features.insert(name.to_string());
+                }
+            }
+            ("script" | "features", _) => return Err(invalid(format!("`build.{}` has the wrong type", key)).into()),
+            _ => return Err(invalid(format!("unknown key `build.{}`", key)).into()),
+        }
+    }
+    Ok((script, features))
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Compile the build script at `script` and run it in the interpreter,
+/// returning what it printed. Its compile errors name it by their position;
+/// the other failures say which script failed.
+fn run_script(script: &Path) -> Result<String, Box<dyn Error>> {
+    let failed = |err: &dyn Error| format!("build script {} failed: {}", script.display(), err);
+    let source = fs::read_to_string(script).map_err(|err| failed(&err))?;
+    let module = crate::runner::lower_source(script, &source, BTreeSet::new(), LoweringOptions::default())?;
+    let (output, code) = plugin_api::interpret_with_exit_code(&module).map_err(|err| failed(&err))?;
+    if code != 0 {
+        return Err(format!("build script {} exited with code {}\n{}", script.display(), code, output).into());
+    }
+    Ok(output)
+}
+
+/// The directives `output`, what a build script printed, gives.
+///
+/// # Errors
+/// Returns an error for a `tlang:` line that is no directive, or one naming
+/// a path outside the project's directory.
+pub fn parse_directives(output: &str) -> Result<Build, Box<dyn Error>> {
+    let mut build = Build::default();
+    let mut generating = false;
+    for line in output.lines() {
+        let Some(directive) = line.strip_prefix("tlang:") else {
+            if generating && let Some((_, contents)) = build.generated.last_mut() {
+                contents.push_str(line);
+                contents.push('\n');
+            }
+            continue;
+        };
+        generating = false;
+        let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+        match name {
+            "feature" if !value.is_empty() => {
+                build.features.insert(value.to_string());
+            }
+            "generated" => {
+                build.generated.push((project_path(value)?, String::new()));
+                generating = true;
+            }
+            "rerun-if-changed" => build.rerun_if_changed.push(project_path(value)?),
+            _ => return Err(format!("unknown build script directive `{}`", line).into()),
+        }
+    }
+    Ok(build)
+}
+
+/// `path`, if it is a relative path that stays in the project's directory.
+fn project_path(path: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = PathBuf::from(path);
+    let inside = path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if path.as_os_str().is_empty() || !inside {
+        return Err(format!("build script path `{}` is outside the project", path.display()).into());
+    }
+    Ok(path)
+}
+
+/// A fingerprint of the contents of the file at `path`, FNV-1a of its bytes,
+/// or `-` if there is none.
+fn fingerprint(path: &Path) -> String {
+    let Ok(bytes) = fs::read(path) else {
+        return "-".to_string();
+    };
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// `input FINGERPRINT PATH` for each of `inputs`, paths in `dir`, then
+/// `output` and the script's output on the lines after it.
+fn stamp_text(dir: &Path, inputs: &[PathBuf], output: &str) -> String {
+    let line = |path: &PathBuf| format!("input {} {}\n", fingerprint(&dir.join(path)), path.display());
+    let mut text: String = inputs.iter().map(line).collect();
+    text.push_str("output\n");
+    text.push_str(output);
+    text
+}
+
+/// The output the stamp at `stamp` keeps, if every input it lists, a path
+/// in `dir`, is as it was when the script ran.
+fn fresh_output(stamp: &Path, dir: &Path) -> Option<String> {
+    let text = fs::read_to_string(stamp).ok()?;
+    let (inputs, output) = text.split_once("output\n")?;
+    for line in inputs.lines() {
+        let (fingerprint_then, path) = line.strip_prefix("input ")?.split_once(' ')?;
+        if fingerprint(&dir.join(path)) != fingerprint_then {
+            return None;
+        }
+    }
+    Some(output.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directives_turn_on_features_and_generate_files() {
+        let output = "generating\ntlang:feature=simd\ntlang:generated=src/tables.t\nfn size() -> i64 {\n    4\n}\ntlang:rerun-if-changed=tables.csv\ntlang:feature=avx\n";
+        let build = parse_directives(output).unwrap();
+        assert_eq!(build.features, BTreeSet::from(["avx".to_string(), "simd".to_string()]));
+        assert_eq!(build.generated, [(PathBuf::from("src/tables.t"), "fn size() -> i64 {\n    4\n}\n".to_string())]);
+        assert_eq!(build.rerun_if_changed, [PathBuf::from("tables.csv")]);
+        assert_eq!(build.source("fn main() { }\n".to_string()), "fn main() { }\n\nfn size() -> i64 {\n    4\n}\n");
+
+        assert!(parse_directives("tlang:feature\n").is_err());
+        assert!(parse_directives("tlang:generated=../outside.t\n").is_err());
+        assert!(parse_directives("tlang:generated=/etc/outside.t\n").is_err());
+    }
+
+    #[test]
+    fn the_script_reruns_only_when_an_input_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.t");
+        fs::write(&main, "fn main() { }\n").unwrap();
+        fs::write(dir.path().join("tlang.toml"), "[build]\nscript = \"gen.t\"\nfeatures = [\"fast\"]\n").unwrap();
+        fs::write(dir.path().join("limit.txt"), "3\n").unwrap();
+        let script = "fn main() {\n    println(\"tlang:rerun-if-changed=limit.txt\");\n    println(\"tlang:generated=gen/limit.t\");\n    println(\"fn limit() -> i64 { \", 3, \" }\");\n}\n";
+        fs::write(dir.path().join("gen.t"), script).unwrap();
+
+        let build = prepare(&main).unwrap();
+        assert!(build.ran);
+        assert_eq!(build.features, BTreeSet::from(["fast".to_string()]));
+        let generated = dir.path().join("gen/limit.t");
+        assert_eq!(fs::read_to_string(&generated).unwrap(), "fn limit() -> i64 { 3 }\n");
+
+        // A deleted generated file is written again from the stamp
+        fs::remove_file(&generated).unwrap();
+        let again = prepare(&main).unwrap();
+        assert!(!again.ran);
+        assert_eq!(again.generated, build.generated);
+        assert!(generated.exists());
+
+        fs::write(dir.path().join("limit.txt"), "4\n").unwrap();
+        assert!(prepare(&main).unwrap().ran);
+        assert!(!prepare(&main).unwrap().ran);
+    }
+
+    #[test]
+    fn failing_scripts_fail_the_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.t");
+        fs::write(&main, "fn main() { }\n").unwrap();
+        fs::write(dir.path().join("build.t"), "fn main() -> i32 {\n    println(\"no tables\");\n    2\n}\n").unwrap();
+        let err = prepare(&main).unwrap_err().to_string();
+        assert!(err.contains("exited with code 2") && err.contains("no tables"), "{}", err);
+        assert!(!dir.path().join(STAMP).exists());
+    }
+
+    #[test]
+    fn scripts_read_the_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.t");
+        fs::write(&main, "fn main() { }\n").unwrap();
+        let script = "fn main() {\n    println(\"tlang:generated=home.txt\");\n    println(env::var(\"HOME\"));\n}\n";
+        fs::write(dir.path().join("build.t"), script).unwrap();
+        let build = prepare(&main).unwrap();
+        let home = format!("{}\n", std::env::var("HOME").unwrap_or_default());
+        assert_eq!(fs::read_to_string(dir.path().join("home.txt")).unwrap(), home);
+        assert!(build.ran);
+
+        fs::write(dir.path().join("build.t"), "fn main() {\n    println(cuont);\n}\n").unwrap();
+        let err = prepare(&main).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("{}:2:13: error", dir.path().join("build.t").display())), "{}", err);
+
+        let script = "extern \"C\" { fn abs(n: i32) -> i32; }\nfn main() {\n    println(unsafe { abs(-2) });\n}\n";
+        fs::write(dir.path().join("build.t"), script).unwrap();
+        let err = prepare(&main).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("build script {} failed: ", dir.path().join("build.t").display())), "{}", err);
+    }
+}
@@ -1,6 +1,8 @@
 // tlang/src/cli.rs
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use compiler::{LoweringOptions, Thresholds};
+use shared::dump::DumpFormat;
 
 /// Top-level CLI definition for T-Lang.
 #[derive(Parser)]
@@ -16,9 +18,253 @@ pub enum Command {
     Run {
         /// Path to the `.tl` script file
         script: String,
+        /// Whether arithmetic that overflows panics
+        #[arg(long, value_enum, default_value_t = Checks::On)]
+        checks: Checks,
+        /// Count how often each line runs, for `tlang cov report`
+        #[arg(long)]
+        coverage: bool,
+        /// Arguments for the program, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Compile a source file with a backend, to code or an executable.
+    Compile {
+        /// Path to the source file
+        script: String,
+        /// Backend to generate code with; `tlang targets` lists them
+        #[arg(long, default_value = "c")]
+        target: String,
+        /// The stage to stop at, and write what it produced
+        #[arg(long, value_enum, default_value_t = Emit::Ir)]
+        emit: Emit,
+        /// Where to write it; stdout for tokens, ast and tir if not given
+        #[arg(short, long)]
+        output: Option<String>,
+        /// How ast and tir are written: json (ast's default), sexpr or
+        /// debug; tir is the text a `.tir` file holds if not given
+        #[arg(long)]
+        format: Option<DumpFormat>,
+        /// Whether arithmetic that overflows panics
+        #[arg(long, value_enum, default_value_t = Checks::On)]
+        checks: Checks,
+        /// Optimization level the registered optimizers are enabled for, 0 to 3
+        #[arg(short = 'O', default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=3))]
+        opt_level: u8,
+        /// Run this optimizer whatever the level; may be repeated
+        #[arg(long = "enable-opt")]
+        enable_opt: Vec<String>,
+        /// Skip this optimizer whatever the level; may be repeated
+        #[arg(long = "disable-opt")]
+        disable_opt: Vec<String>,
+        /// Compiler driver that links the executable, e.g. `clang`
+        #[arg(long)]
+        linker: Option<String>,
+        /// Extra argument for the linker; may be repeated
+        #[arg(long = "link-arg", allow_hyphen_values = true)]
+        link_args: Vec<String>,
+        /// Write the same bytes wherever and whenever the file is compiled
+        #[arg(long)]
+        deterministic: bool,
+        /// Make the program count how often each line runs, for `tlang cov report`
+        #[arg(long)]
+        coverage: bool,
+        /// Print the TIR to stderr after the named optimizer runs; for
+        /// working on the optimizers
+        #[arg(long, hide = true, value_name = "PASS")]
+        dump_tir_after: Option<String>,
+    },
+    /// Recompile a source file every time it changes.
+    Watch {
+        /// Path to the source file
+        script: String,
+        /// Run the program after every build without errors
+        #[arg(long)]
+        run: bool,
+        /// Whether arithmetic that overflows panics
+        #[arg(long, value_enum, default_value_t = Checks::On)]
+        checks: Checks,
+    },
+    /// Warn about unused code and, with `--metrics`, overly complex functions.
+    Lint {
+        /// Path to the source file
+        script: String,
+        /// Measure the complexity, nesting depth and length of every function
+        #[arg(long)]
+        metrics: bool,
+        /// Cyclomatic complexity a function may have before it is warned about
+        #[arg(long, default_value_t = Thresholds::default().complexity)]
+        max_complexity: usize,
+        /// How deeply a function's branches and loops may nest
+        #[arg(long, default_value_t = Thresholds::default().nesting)]
+        max_nesting: usize,
+        /// Lines of code a function may have, not counting blank lines and comments
+        #[arg(long, default_value_t = Thresholds::default().lines)]
+        max_lines: usize,
+        /// Write the metrics to this file as JSON instead of printing them
+        #[arg(long, requires = "metrics")]
+        report: Option<String>,
+    },
+    /// Export the call graph or module dependency graph of a source file.
+    Graph {
+        /// Path to the source file
+        script: String,
+        /// Which graph to export
+        #[arg(long, value_enum, default_value_t = GraphKind::Calls)]
+        graph: GraphKind,
+        /// How to write it
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// Where to write it; stdout if not given
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Time the `#[bench]` functions of a source file.
+    Bench {
+        /// Path to the source file
+        script: String,
+        /// Only run the benchmarks whose names contain this
+        filter: Option<String>,
+        /// Untimed runs of each benchmark before the timed ones
+        #[arg(long, default_value_t = 10)]
+        warmup: usize,
+        /// Timed runs of each benchmark
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+        /// Compare the results with a baseline saved by `--save-baseline`
+        #[arg(long)]
+        baseline: Option<String>,
+        /// Save the results as a baseline to compare later runs with
+        #[arg(long)]
+        save_baseline: Option<String>,
+        /// How many percent a mean may move by before it counts as a change
+        #[arg(long, default_value_t = 5.0)]
+        noise: f64,
+        /// Whether arithmetic that overflows panics
+        #[arg(long, value_enum, default_value_t = Checks::On)]
+        checks: Checks,
+    },
+    /// Report on the runs of a program built with `--coverage`.
+    Cov {
+        #[command(subcommand)]
+        cmd: CovCommand,
+    },
+    /// Commands for compiler developers.
+    Internal {
+        #[command(subcommand)]
+        cmd: InternalCommand,
     },
     /// Launch the interactive REPL.
     Repl,
+    /// Explain a diagnostic code, or list all codes when none is given.
+    Explain {
+        /// The code to explain, e.g. `E0003`
+        code: Option<String>,
+    },
+    /// List the backends code can be generated for.
+    Targets,
+}
+
+/// What `tlang cov` reports.
+#[derive(Subcommand)]
+pub enum CovCommand {
+    /// Print a source file with how often each line ran.
+    Report {
+        /// Path to the source file
+        script: String,
+        /// The map `--coverage` wrote; `<name>.covmap` if not given
+        #[arg(long)]
+        map: Option<String>,
+        /// The counts the program wrote; `$TLANG_COVERAGE_FILE`, else `coverage.counts`, if not given
+        #[arg(long)]
+        counts: Option<String>,
+    },
+}
+
+/// Commands for working on the compiler itself.
+#[derive(Subcommand)]
+pub enum InternalCommand {
+    /// Diff the syntax trees and lowered instructions of two versions of a
+    /// file, ignoring where things are in the source.
+    DiffIr {
+        /// The old version of the file
+        old: String,
+        /// The new version of the file
+        new: String,
+        /// Which stages to diff
+        #[arg(long, value_enum, default_value_t = DiffStage::All)]
+        stage: DiffStage,
+        /// Whether arithmetic that overflows panics
+        #[arg(long, value_enum, default_value_t = Checks::On)]
+        checks: Checks,
+    },
+}
+
+/// The stages `tlang internal diff-ir` compares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DiffStage {
+    /// The syntax trees, after macro expansion
+    Ast,
+    /// The instructions the checked programs lower to
+    Tir,
+    /// Both
+    All,
+}
+
+/// The stage `tlang compile` stops at, in pipeline order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Emit {
+    /// The tokens of the source, one per line
+    Tokens,
+    /// The syntax tree the parser builds, as JSON unless `--format` says
+    Ast,
+    /// The TIR the checked program lowers to, as text a `.tir` file holds
+    /// unless `--format` says
+    Tir,
+    /// The code the backend generates, with its build files
+    #[value(alias = "source")]
+    Ir,
+    /// Assembly for the backend's main file
+    Asm,
+    /// An object file for the backend's main file
+    Obj,
+    /// An executable, linked from C, assembly or LLVM IR
+    Exe,
+}
+
+/// A graph `tlang graph` exports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GraphKind {
+    /// Which functions of the program call which
+    Calls,
+    /// Which modules import from or call into which
+    Modules,
+}
+
+/// How `tlang graph` writes a graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Nodes with where they are declared, and edges with their counts
+    Json,
+}
+
+/// Runtime checks of integer arithmetic. Division and remainder by zero
+/// panic either way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Checks {
+    /// Overflowing `+`, `-` and `*` panic with where they are
+    On,
+    /// Overflowing `+`, `-` and `*` wrap around
+    Off,
+}
+
+impl Checks {
+    /// How programs are lowered under these checks.
+    pub fn lowering_options(self) -> LoweringOptions {
+        LoweringOptions { overflow_checks: self == Checks::On, ..LoweringOptions::default() }
+    }
 }
 
 #[cfg(test)]
@@ -28,19 +274,189 @@ mod tests {
 
     #[test]
     fn parse_run_command() {
-        let args = Cli::parse_from(&["tlang", "run", "file.tl"]);
+        let args = Cli::parse_from(["tlang", "run", "file.tl"]);
         match args.cmd {
-            Command::Run { script } => assert_eq!(script, "file.tl"),
+            Command::Run { script, checks, coverage, args } => {
+                assert_eq!(script, "file.tl");
+                assert_eq!(checks, Checks::On);
+                assert!(!coverage);
+                assert!(args.is_empty());
+            }
             _ => panic!("Expected Run command"),
         }
     }
 
+    #[test]
+    fn parse_run_command_without_checks() {
+        let args = Cli::parse_from(["tlang", "run", "file.tl", "--checks=off"]);
+        match args.cmd {
+            Command::Run { checks, .. } => assert!(!checks.lowering_options().overflow_checks),
+            _ => panic!("Expected Run command"),
+        }
+    }
+
+    #[test]
+    fn parse_run_command_with_program_arguments() {
+        let args = Cli::parse_from(["tlang", "run", "file.tl", "--", "input.txt", "--verbose"]);
+        match args.cmd {
+            Command::Run { script, args, .. } => {
+                assert_eq!(script, "file.tl");
+                assert_eq!(args, ["input.txt", "--verbose"]);
+            }
+            _ => panic!("Expected Run command"),
+        }
+    }
+
+    #[test]
+    fn parse_compile_command() {
+        let args = Cli::parse_from([
+            "tlang", "compile", "file.tl", "--emit", "exe", "-o", "app", "--link-arg", "-lm",
+            "--deterministic", "-O", "2", "--enable-opt", "inline", "--disable-opt", "licm",
+        ]);
+        match args.cmd {
+            Command::Compile {
+                script, target, emit, output, link_args, deterministic, opt_level, enable_opt, disable_opt, ..
+            } => {
+                assert_eq!(script, "file.tl");
+                assert_eq!(target, "c");
+                assert_eq!(emit, Emit::Exe);
+                assert_eq!(output.as_deref(), Some("app"));
+                assert_eq!(link_args, ["-lm"]);
+                assert!(deterministic);
+                assert_eq!(opt_level, 2);
+                assert_eq!(enable_opt, ["inline"]);
+                assert_eq!(disable_opt, ["licm"]);
+            }
+            _ => panic!("Expected Compile command"),
+        }
+        let args = Cli::parse_from(["tlang", "compile", "file.tl"]);
+        assert!(matches!(args.cmd, Command::Compile { emit: Emit::Ir, opt_level: 1, .. }));
+        assert!(Cli::try_parse_from(["tlang", "compile", "file.tl", "-O", "4"]).is_err());
+        let args = Cli::parse_from(["tlang", "compile", "file.tl", "--emit", "tir"]);
+        assert!(matches!(args.cmd, Command::Compile { emit: Emit::Tir, format: None, .. }));
+        let args = Cli::parse_from(["tlang", "compile", "file.tl", "--emit", "ast", "--format", "sexpr"]);
+        assert!(matches!(args.cmd, Command::Compile { emit: Emit::Ast, format: Some(DumpFormat::Sexpr), .. }));
+        assert!(Cli::try_parse_from(["tlang", "compile", "file.tl", "--format", "yaml"]).is_err());
+        let args = Cli::parse_from(["tlang", "compile", "file.tl", "-O", "2", "--dump-tir-after=licm"]);
+        assert!(matches!(args.cmd, Command::Compile { dump_tir_after: Some(pass), .. } if pass == "licm"));
+    }
+
+    #[test]
+    fn parse_watch_command() {
+        let args = Cli::parse_from(["tlang", "watch", "file.tl", "--run"]);
+        match args.cmd {
+            Command::Watch { script, run, .. } => {
+                assert_eq!(script, "file.tl");
+                assert!(run);
+            }
+            _ => panic!("Expected Watch command"),
+        }
+    }
+
+    #[test]
+    fn parse_lint_command() {
+        let args =
+            Cli::parse_from(["tlang", "lint", "file.tl", "--metrics", "--max-nesting", "2", "--report", "m.json"]);
+        match args.cmd {
+            Command::Lint { script, metrics, max_complexity, max_nesting, report, .. } => {
+                assert_eq!(script, "file.tl");
+                assert!(metrics);
+                assert_eq!(max_complexity, 10);
+                assert_eq!(max_nesting, 2);
+                assert_eq!(report.as_deref(), Some("m.json"));
+            }
+            _ => panic!("Expected Lint command"),
+        }
+        assert!(Cli::try_parse_from(["tlang", "lint", "file.tl", "--report", "m.json"]).is_err());
+    }
+
+    #[test]
+    fn parse_graph_command() {
+        let args =
+            Cli::parse_from(["tlang", "graph", "file.tl", "--graph", "modules", "--format", "json", "-o", "g.json"]);
+        match args.cmd {
+            Command::Graph { script, graph, format, output } => {
+                assert_eq!(script, "file.tl");
+                assert_eq!(graph, GraphKind::Modules);
+                assert_eq!(format, GraphFormat::Json);
+                assert_eq!(output.as_deref(), Some("g.json"));
+            }
+            _ => panic!("Expected Graph command"),
+        }
+        let args = Cli::parse_from(["tlang", "graph", "file.tl"]);
+        assert!(matches!(args.cmd, Command::Graph { graph: GraphKind::Calls, format: GraphFormat::Dot, .. }));
+    }
+
+    #[test]
+    fn parse_bench_command() {
+        let args = Cli::parse_from([
+            "tlang", "bench", "file.tl", "sort", "--iterations", "20", "--baseline", "before.json", "--noise", "2.5",
+        ]);
+        match args.cmd {
+            Command::Bench { script, filter, warmup, iterations, baseline, save_baseline, noise, .. } => {
+                assert_eq!(script, "file.tl");
+                assert_eq!(filter.as_deref(), Some("sort"));
+                assert_eq!((warmup, iterations), (10, 20));
+                assert_eq!(baseline.as_deref(), Some("before.json"));
+                assert_eq!(save_baseline, None);
+                assert_eq!(noise, 2.5);
+            }
+            _ => panic!("Expected Bench command"),
+        }
+    }
+
+    #[test]
+    fn parse_coverage_commands() {
+        let args = Cli::parse_from(["tlang", "run", "--coverage", "file.tl"]);
+        assert!(matches!(args.cmd, Command::Run { coverage: true, .. }));
+        let args = Cli::parse_from(["tlang", "compile", "file.tl", "--emit", "exe", "--coverage"]);
+        assert!(matches!(args.cmd, Command::Compile { coverage: true, .. }));
+
+        let args = Cli::parse_from(["tlang", "cov", "report", "file.tl", "--counts", "run.counts"]);
+        match args.cmd {
+            Command::Cov { cmd: CovCommand::Report { script, map, counts } } => {
+                assert_eq!(script, "file.tl");
+                assert_eq!(map, None);
+                assert_eq!(counts.as_deref(), Some("run.counts"));
+            }
+            _ => panic!("Expected Cov Report command"),
+        }
+    }
+
+    #[test]
+    fn parse_internal_diff_ir_command() {
+        let args = Cli::parse_from(["tlang", "internal", "diff-ir", "old.t", "new.t", "--stage", "tir"]);
+        match args.cmd {
+            Command::Internal { cmd: InternalCommand::DiffIr { old, new, stage, checks } } => {
+                assert_eq!((old.as_str(), new.as_str()), ("old.t", "new.t"));
+                assert_eq!(stage, DiffStage::Tir);
+                assert_eq!(checks, Checks::On);
+            }
+            _ => panic!("Expected Internal DiffIr command"),
+        }
+    }
+
     #[test]
     fn parse_repl_command() {
-        let args = Cli::parse_from(&["tlang", "repl"]);
+        let args = Cli::parse_from(["tlang", "repl"]);
         match args.cmd {
             Command::Repl => (),
             _ => panic!("Expected Repl command"),
         }
     }
+
+    #[test]
+    fn parse_explain_command() {
+        let args = Cli::parse_from(["tlang", "explain", "E0003"]);
+        match args.cmd {
+            Command::Explain { code } => assert_eq!(code.as_deref(), Some("E0003")),
+            _ => panic!("Expected Explain command"),
+        }
+    }
+
+    #[test]
+    fn parse_targets_command() {
+        let args = Cli::parse_from(["tlang", "targets"]);
+        assert!(matches!(args.cmd, Command::Targets));
+    }
 }
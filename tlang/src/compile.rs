@@ -0,0 +1,168 @@
+// File: tlang/src/compile.rs
+
+//! Ahead-of-time compilation of T-Lang source files.
+//! Runs a file through the pipeline up to the stage `Emit` names and writes
+//! what that stage produced: the tokens, the syntax tree or the TIR as
+//! text or a `shared::dump` format, the code a backend generates, or what a C compiler
+//! driver makes of that code.
+//!
+//! Nothing the pipeline writes depends on when it runs. What depends on
+//! where it runs, the path of the source file in debug info and the
+//! directory the driver builds in, `CompileOptions::deterministic` takes
+//! out.
+
+use std::{collections::HashMap, env, error::Error, fs, path::{Path, PathBuf}, process};
+use compiler::link::{self, LinkOutput};
+use compiler::{CompilerOptions, Database, GeneratedCode, LinkOptions, LoweringOptions};
+use shared::dump::{self, DumpFormat};
+
+use crate::cli::Emit;
+use crate::runner::{lower_file, Diagnostic};
+
+/// What `compile_file` makes of a source file.
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// Name of the backend to generate code with
+    pub target: String,
+    /// The stage to stop at
+    pub emit: Emit,
+    /// Where to write what the stage produced (`None` = stdout for text,
+    /// else named after the source file, in the current directory)
+    pub output: Option<PathBuf>,
+    /// How the syntax tree or TIR is written (`None` = JSON for the syntax
+    /// tree, the text a `.tir` file holds for the TIR)
+    pub format: Option<DumpFormat>,
+    /// How the program is lowered
+    pub lowering: LoweringOptions,
+    /// Optimization level the registered optimizers are enabled for
+    pub opt_level: u8,
+    /// Optimizers turned on (`true`) or off by name, whatever `opt_level`
+    pub optimizers: HashMap<String, bool>,
+    /// How the driver is invoked for assembly, objects and executables
+    pub link: LinkOptions,
+    /// Record the source file by its name alone, and map the build
+    /// directory to `.` in what the driver writes
+    pub deterministic: bool,
+    /// Optimizer after which to print the TIR to stderr, if it runs
+    pub dump_tir_after: Option<String>,
+}
+
+/// Compile the file at `path` as `options` say and return the path of what
+/// was written, or `None` if it went to stdout.
+///
+/// Without an output, code goes to `<name>-<target>/`, assembly to
+/// `<name>.s`, an object file to `<name>.o` and an executable to `<name>`.
+///
+/// # Errors
+/// Returns an error if the target is unknown, or file I/O, compilation or
+/// the driver fails.
+pub fn compile_file(path: &Path, options: &CompileOptions) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let text = match options.emit {
+        Emit::Tokens => Some(tokens(&fs::read_to_string(path)?)?),
+        Emit::Ast => Some(ast(path, options.format.unwrap_or(DumpFormat::Json))?),
+        Emit::Tir => {
+            let tir = lower_file(path, options.lowering)?.tir()?;
+            Some(match options.format {
+                Some(format) => format!("{}\n", dump::dump(&tir, format)?),
+                None => tir.to_string(),
+            })
+        }
+        Emit::Ir | Emit::Asm | Emit::Obj | Emit::Exe => None,
+    };
+    if let Some(text) = text {
+        return match &options.output {
+            Some(output) => {
+                fs::write(output, text)?;
+                Ok(Some(output.clone()))
+            }
+            None => {
+                print!("{}", text);
+                Ok(None)
+            }
+        };
+    }
+
+    let compiler_options = CompilerOptions {
+        target: options.target.clone(),
+        optimization_level: options.opt_level,
+        optimizers: options.optimizers.clone(),
+        ..CompilerOptions::default()
+    };
+    compiler_options.validate_target().map_err(|diagnostic| match diagnostic.suggestion {
+        Some(suggestion) => format!("{}; {}", diagnostic.message, suggestion),
+        None => diagnostic.message,
+    })?;
+    let backend = plugin_api::find_backend(&options.target).ok_or("the target has no backend")?;
+    compiler::optimizer::register_builtin_optimizers();
+    let known = plugin_api::list_optimizers();
+    let names = options.optimizers.keys().chain(&options.dump_tir_after);
+    if let Some(name) = names.into_iter().find(|name| !known.iter().any(|o| o.name() == name.as_str())) {
+        return Err(format!("unknown optimizer `{}`", name).into());
+    }
+
+    let mut module = lower_file(path, options.lowering)?;
+    if options.deterministic {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        module.debug_info.source_file = name;
+    }
+    if options.lowering.coverage {
+        crate::cov::write_map(path, &module)?;
+    }
+    for optimizer in known {
+        if plugin_api::optimizer_enabled(optimizer, options.opt_level, &options.optimizers) {
+            module = optimizer.optimize_at_level(module, options.opt_level)?;
+            if options.dump_tir_after.as_deref() == Some(optimizer.name()) {
+                eprint!("{}", module.tir()?);
+            }
+        }
+    }
+    let artifact = backend.compile_erased_with(module, &compiler_options.backend_config())?;
+    let code = GeneratedCode::from(artifact);
+
+    let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("main");
+    let (kind, default_output) = match options.emit {
+        Emit::Asm => (LinkOutput::Assembly, format!("{}.s", name)),
+        Emit::Obj => (LinkOutput::Object, format!("{}.o", name)),
+        Emit::Exe => (LinkOutput::Executable, name.to_string()),
+        _ => {
+            let dir = options.output.clone().unwrap_or_else(|| PathBuf::from(format!("{}-{}", name, options.target)));
+            code.write_to(&dir)?;
+            return Ok(Some(dir));
+        }
+    };
+    let build_dir = env::temp_dir().join(format!("tlang-{}-{}", name, process::id()));
+    let output = options.output.clone().unwrap_or_else(|| PathBuf::from(default_output));
+    let mut link_options = options.link.clone();
+    if options.deterministic {
+        // The directory is named after this process.
+        link_options.flags.push(format!("-ffile-prefix-map={}=.", build_dir.display()));
+    }
+    let built = link::build(&code, &build_dir, &output, kind, &link_options);
+    let _ = fs::remove_dir_all(&build_dir);
+    Ok(Some(built?))
+}
+
+/// One line per token: its byte range and kind.
+fn tokens(src: &str) -> Result<String, Box<dyn Error>> {
+    let lines = shared::Tokenizer::new(src).map(|token| {
+        token.map(|token| {
+            let start = token.span.offset();
+            format!("{}..{} {:?}\n", start, start + token.span.len(), token.token_type)
+        })
+    });
+    Ok(lines.collect::<Result<String, _>>()?)
+}
+
+/// The syntax tree of the file at `path` in `format`, failing with the
+/// first parse error there is.
+fn ast(path: &Path, format: DumpFormat) -> Result<String, Box<dyn Error>> {
+    let src = fs::read_to_string(path)?;
+    let mut db = Database::new();
+    let file = db.file(&path.to_string_lossy());
+    db.set_text(file, &src);
+    let parsed = db.parse(file);
+    if let Some(err) = parsed.errors.first() {
+        return Err(Diagnostic::new(path, &src, err).into());
+    }
+    Ok(format!("{}\n", dump::dump(&parsed.program, format)?))
+}
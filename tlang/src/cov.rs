@@ -0,0 +1,70 @@
+// File: tlang/src/cov.rs
+
+//! Line coverage of T-Lang programs.
+//!
+//! Run or compiled with `--coverage`, a program counts every run of each of
+//! its statements, and where its counters are is written to `<name>.covmap`
+//! in the current directory. The program writes the counts when it exits,
+//! as `tstd::coverage` says. `report_file` then prints the source with how
+//! often each line ran.
+
+use compiler::coverage::read_counts;
+use compiler::CoverageMap;
+use plugin_api::CompiledModule;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where `--coverage` writes the map of the file at `path`.
+pub fn map_path(path: &Path) -> PathBuf {
+    let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("main");
+    PathBuf::from(format!("{}.covmap", name))
+}
+
+/// Write the coverage map of `module`, lowered for coverage from the file
+/// at `path`, to `map_path`.
+pub(crate) fn write_map(path: &Path, module: &CompiledModule) -> Result<PathBuf, Box<dyn Error>> {
+    let src = fs::read_to_string(path)?;
+    let program = compiler::parse_source(&src)?;
+    let map_path = map_path(path);
+    CoverageMap::new(module, &program, &src).write(&map_path)?;
+    Ok(map_path)
+}
+
+/// Where `report_file` reads from.
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+    /// The map `--coverage` wrote (`None` = `map_path`)
+    pub map: Option<PathBuf>,
+    /// The counts the program wrote (`None` = `tstd::coverage::counts_path`)
+    pub counts: Option<PathBuf>,
+}
+
+/// Print the file at `path` a line at a time, each after how often it ran:
+/// `-` for a line with no statement, and `#####` for one that never ran.
+/// A summary of how many lines ran ends it. Returns how many lines never
+/// ran.
+///
+/// # Errors
+/// Returns an error if a file cannot be read, or the counts do not fit the
+/// map.
+pub fn report_file(path: &Path, options: &ReportOptions) -> Result<usize, Box<dyn Error>> {
+    let src = fs::read_to_string(path)?;
+    let map = CoverageMap::read(&options.map.clone().unwrap_or_else(|| map_path(path)))?;
+    let counts = read_counts(&options.counts.clone().unwrap_or_else(tstd::coverage::counts_path))?;
+    let lines = map.line_counts(&counts)?;
+
+    for (index, text) in src.lines().enumerate() {
+        let count = match lines.get(&(index as u32 + 1)) {
+            Some(0) => "#####".to_string(),
+            Some(count) => count.to_string(),
+            None => "-".to_string(),
+        };
+        println!("{:>9}:{:>5}:{}", count, index + 1, text);
+    }
+    let missed = lines.values().filter(|&&count| count == 0).count();
+    let ran = lines.len() - missed;
+    let percent = if lines.is_empty() { 100.0 } else { 100.0 * ran as f64 / lines.len() as f64 };
+    println!("{} of {} lines ran ({:.1}%)", ran, lines.len(), percent);
+    Ok(missed)
+}
@@ -0,0 +1,67 @@
+// File: tlang/src/graph.rs
+
+//! Export of a T-Lang source file's call graph or module dependency graph,
+//! as Graphviz DOT to render, e.g. with `dot -Tsvg`, or as JSON for other
+//! tools.
+
+use compiler::{Database, ProgramGraphs};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{GraphFormat, GraphKind};
+
+/// What `graph_file` exports.
+#[derive(Debug, Clone)]
+pub struct GraphOptions {
+    /// Which of the program's graphs
+    pub graph: GraphKind,
+    /// How it is written
+    pub format: GraphFormat,
+    /// Where to write it (`None` = stdout)
+    pub output: Option<PathBuf>,
+}
+
+/// Export a graph of the file at `path` as `options` say, and return the
+/// path it was written to, or `None` if it went to stdout.
+///
+/// The file only needs to parse. Method calls are only edges in a file
+/// that also type checks, since their receivers' types tell which method
+/// is called.
+///
+/// # Errors
+/// Returns an error if file I/O fails or the file does not parse; its
+/// first syntax error is printed.
+pub fn graph_file(path: &Path, options: &GraphOptions) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let src = fs::read_to_string(path)?;
+    let mut db = Database::new();
+    let file = db.file(&path.to_string_lossy());
+    db.set_text(file, &src);
+    let parsed = db.parse(file);
+    if let Some(err) = parsed.errors.first() {
+        eprintln!("Compilation error: {}", err);
+        return Err(err.to_string().into());
+    }
+    let checked = db.type_check(file);
+    let program = if checked.error.is_none() { &checked.program } else { &parsed.program };
+
+    let graphs = ProgramGraphs::new(program, &src);
+    let (graph, name) = match options.graph {
+        GraphKind::Calls => (&graphs.calls, "calls"),
+        GraphKind::Modules => (&graphs.modules, "modules"),
+    };
+    let text = match options.format {
+        GraphFormat::Dot => graph.to_dot(name),
+        GraphFormat::Json => graph.to_json() + "\n",
+    };
+    match &options.output {
+        Some(output) => {
+            fs::write(output, text)?;
+            Ok(Some(output.clone()))
+        }
+        None => {
+            print!("{}", text);
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// File: tlang/src/internal.rs
+
+//! Commands for working on the compiler itself.
+//!
+//! `diff-ir` compares two versions of a file the way the compiler sees
+//! them: their syntax trees and the TIR they lower to, without
+//! where anything is in the source. Run on a file before and after a
+//! change to the compiler's input, or on one file with two builds of the
+//! compiler, it shows what an edit really changed.
+
+use compiler::{diff_modules, diff_programs, Change, Database, LoweringOptions};
+use shared::Program;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::cli::DiffStage;
+use crate::runner::{lower_file, Diagnostic};
+
+/// Print how the stages `stage` selects differ between the files at `old`
+/// and `new`, lowering with `options`, and return how many changes there
+/// are.
+///
+/// # Errors
+/// Returns an error if either file cannot be read or does not compile as
+/// far as the stages need, or if printing fails other than by the reader
+/// going away.
+pub fn diff_ir(old: &Path, new: &Path, stage: DiffStage, options: LoweringOptions) -> Result<usize, Box<dyn Error>> {
+    let mut stages = Vec::new();
+    if stage != DiffStage::Tir {
+        stages.push(("ast", diff_programs(&parse_file(old)?, &parse_file(new)?)));
+    }
+    if stage != DiffStage::Ast {
+        stages.push(("tir", diff_modules(&lower_file(old, options)?, &lower_file(new, options)?)?));
+    }
+    // A reader that stops early, as `head` does, is no error.
+    match report(&mut io::stdout().lock(), old, new, &stages) {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        result => result?,
+    }
+    Ok(stages.iter().map(|(_, changes)| changes.len()).sum())
+}
+
+/// Write the changes of each stage to `out`, under the name of the stage
+/// and the files compared.
+fn report(out: &mut impl Write, old: &Path, new: &Path, stages: &[(&str, Vec<Change>)]) -> io::Result<()> {
+    writeln!(out, "--- {}", old.display())?;
+    writeln!(out, "+++ {}", new.display())?;
+    for (stage, changes) in stages {
+        match changes.len() {
+            0 => writeln!(out, "{}: no differences", stage)?,
+            1 => writeln!(out, "{}: 1 change", stage)?,
+            count => writeln!(out, "{}: {} changes", stage, count)?,
+        }
+        for change in changes {
+            writeln!(out, "{}", change)?;
+        }
+    }
+    Ok(())
+}
+
+/// The syntax tree of the file at `path`, failing with the first parse
+/// error there is.
+fn parse_file(path: &Path) -> Result<Program, Box<dyn Error>> {
+    let src = fs::read_to_string(path)?;
+    let mut db = Database::new();
+    let file = db.file(&path.to_string_lossy());
+    db.set_text(file, &src);
+    let parsed = db.parse(file);
+    if let Some(err) = parsed.errors.first() {
+        return Err(Diagnostic::new(path, &src, err).into());
+    }
+    Ok(parsed.program.clone())
+}
@@ -1,12 +1,27 @@
 // tlang/src/lib.rs
 
-//! T-Lang library: exposes runner and REPL functionality.
+//! T-Lang library: exposes the CLI, runner and REPL functionality.
 
+pub mod bench;
+pub mod build;
+pub mod cli;
+pub mod compile;
+pub mod cov;
+pub mod graph;
+pub mod internal;
+pub mod lint;
 pub mod runner;
 pub mod repl;
+pub mod watch;
 
+pub use bench::{bench_file, BenchOptions};
+pub use compile::{compile_file, CompileOptions};
+pub use cov::{report_file, ReportOptions};
+pub use graph::{graph_file, GraphOptions};
+pub use lint::{lint_file, LintOptions};
 pub use runner::run_file;
 pub use repl::start_repl;
+pub use watch::watch;
 
 /// This is the entry point for your evaluator.
 /// Adjust the signature and body to call into your compiler/runtime.
@@ -0,0 +1,78 @@
+// File: tlang/src/lint.rs
+
+//! Linting of T-Lang source files.
+//!
+//! A file that parses and type checks is reported on one line per warning:
+//! unused variables, imports and functions, and with `metrics`, functions
+//! over a size or complexity threshold. The metrics of every function can
+//! also be written as a JSON report, for CI to track over time.
+
+use compiler::{Database, MetricsReport, Thresholds};
+use errors::TlError;
+use shared::source::line_col_from_offset;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::runner::check_error;
+
+/// What `lint_file` checks and writes.
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+    /// Measure the size and complexity of every function
+    pub metrics: bool,
+    /// The most each metric may be before a function is warned about
+    pub thresholds: Thresholds,
+    /// Where to write the metrics as JSON, instead of a table on stdout
+    pub report: Option<PathBuf>,
+}
+
+/// Lint the file at `path` as `options` say, printing each warning, and
+/// return how many there were.
+///
+/// # Errors
+/// Returns an error if the file cannot be read or the report written, or
+/// the file does not compile, with its first error.
+pub fn lint_file(path: &Path, options: &LintOptions) -> Result<usize, Box<dyn Error>> {
+    let src = fs::read_to_string(path)?;
+    let mut db = Database::new();
+    let file = db.file(&path.to_string_lossy());
+    db.set_text(file, &src);
+    if let Some(error) = check_error(&mut db, path, file) {
+        return Err(error.into());
+    }
+
+    let lints = db.lint(file);
+    let mut warnings: Vec<&TlError> = lints.diagnostics().iter().map(|(_, warning)| warning).collect();
+    let metrics = options.metrics.then(|| MetricsReport::new(&db.type_check(file).program, &src, options.thresholds));
+    let over = metrics.as_ref().map(|report| report.warnings(&src));
+    warnings.extend(over.iter().flat_map(|over| over.diagnostics()).map(|(_, warning)| warning));
+    warnings.sort_by_key(|warning| warning.span().map(|span| span.offset()));
+    for warning in &warnings {
+        println!("{}", one_line(path, &src, warning));
+    }
+
+    if let Some(report) = &metrics {
+        match &options.report {
+            Some(output) => report.write(output)?,
+            None => print_table(report),
+        }
+    }
+    Ok(warnings.len())
+}
+
+/// `path:line:col: warning[code]: message`
+fn one_line(path: &Path, text: &str, warning: &TlError) -> String {
+    let (line, col) = warning.span().map_or((1, 1), |span| line_col_from_offset(text, span.offset()));
+    let message = warning.to_string();
+    let message = message.lines().next().unwrap_or_default();
+    format!("{}:{}:{}: warning[{}]: {}", path.display(), line, col, warning.code(), message)
+}
+
+/// Print the metrics of every function, a line each.
+fn print_table(report: &MetricsReport) {
+    println!("{:<32} {:>10} {:>7} {:>5}", "function", "complexity", "nesting", "lines");
+    for function in &report.functions {
+        println!("{:<32} {:>10} {:>7} {:>5}", function.name, function.complexity, function.nesting, function.lines);
+    }
+}
@@ -1,46 +1,160 @@
-// tlang/src/main.rs 
+// tlang/src/main.rs
 
-use std::io::{self, Write};
+use clap::Parser;
+use compiler::{LinkOptions, LoweringOptions, Thresholds};
+use plugin_api::BackendCapabilities;
+use std::error::Error;
+use std::path::{Path, PathBuf};
 use std::process;
+use tlang::cli::{Cli, Command, CovCommand, InternalCommand};
+use tlang::runner::Diagnostic;
+use tlang::{BenchOptions, CompileOptions, GraphOptions, LintOptions, ReportOptions};
 
-/// Reads a line of input from stdin, displaying a prompt.
-/// Returns `None` on EOF or error.
-fn read_line(prompt: &str) -> Option<String> {
-    // Print prompt
-    print!("{}", prompt);
-    // Flush to ensure prompt is shown immediately
-    if io::stdout().flush().is_err() {
-        return None;
+/// Print the explanation of `code`, or the list of codes when there is none.
+fn explain(code: Option<&str>) -> Result<(), String> {
+    let Some(code) = code else {
+        for explanation in errors::explain::EXPLANATIONS {
+            println!("{}  {}", explanation.code, explanation.title);
+        }
+        return Ok(());
+    };
+    match errors::explain(code) {
+        Some(explanation) => {
+            println!("{}", explanation);
+            Ok(())
+        }
+        None => Err(format!(
+            "no explanation for `{}`; run `tlang explain` to list the known codes",
+            code
+        )),
     }
+}
 
-    let mut buffer = String::new();
-    match io::stdin().read_line(&mut buffer) {
-        Ok(0) => None, // EOF reached
-        Ok(_) => Some(buffer.trim_end().to_owned()),
-        Err(_) => None,
+/// Print each registered backend with its version and what it supports.
+fn targets() -> Result<(), String> {
+    compiler::backends::register_builtin_backends();
+    let mut backends = plugin_api::list_backends();
+    backends.sort_by_key(|backend| backend.name());
+    for backend in backends {
+        println!(
+            "{:<12} {:<8} {}",
+            backend.name(),
+            backend.version(),
+            describe(backend.capabilities())
+        );
     }
+    Ok(())
 }
 
-fn process_input(line: &str) -> Result<String, String> {
-    // TODO: Plug in your evaluation or compilation logic here.
-    // For now, just echo the input.
-    Ok(line.to_string())
+/// The features in `capabilities`, or `-` when there are none.
+fn describe(capabilities: BackendCapabilities) -> String {
+    let mut features = Vec::new();
+    if capabilities.supports_jit {
+        features.push("jit".to_string());
+    }
+    if capabilities.max_optimization_level < 3 {
+        features.push(format!("up to -O{}", capabilities.max_optimization_level));
+    }
+    if capabilities.supports_debug_info {
+        features.push("debug-info".to_string());
+    }
+    if capabilities.supports_cross_compilation {
+        features.push("cross-compilation".to_string());
+    }
+    if features.is_empty() {
+        "-".to_string()
+    } else {
+        features.join(", ")
+    }
 }
 
 fn main() {
-    println!("Welcome to T-Lang REPL (std::io edition)");
+    let cli = Cli::parse();
 
-    while let Some(line) = read_line("» ") {
-        if line.trim().is_empty() {
-            continue;
+    let result: Result<(), Box<dyn Error>> = match cli.cmd {
+        Command::Run { script, checks, coverage, args } => {
+            let options = LoweringOptions { coverage, ..checks.lowering_options() };
+            match tlang::run_file(Path::new(&script), options, &args) {
+                Ok(code) => process::exit(code),
+                Err(e) => Err(e),
+            }
         }
+        Command::Compile {
+            script, target, emit, output, format, checks, opt_level, enable_opt, disable_opt, linker, link_args,
+            deterministic, coverage, dump_tir_after,
+        } => {
+            // A name given to both flags is disabled.
+            let enabled = enable_opt.into_iter().map(|name| (name, true));
+            let optimizers = enabled.chain(disable_opt.into_iter().map(|name| (name, false))).collect();
+            let options = CompileOptions {
+                target,
+                emit,
+                output: output.map(PathBuf::from),
+                format,
+                lowering: LoweringOptions { coverage, ..checks.lowering_options() },
+                opt_level,
+                optimizers,
+                link: LinkOptions { linker, flags: link_args },
+                deterministic,
+                dump_tir_after,
+            };
+            tlang::compile_file(Path::new(&script), &options)
+                .map(|written| {
+                    if let Some(written) = written {
+                        println!("wrote {}", written.display());
+                    }
+                })
+        }
+        Command::Watch { script, run, checks } => {
+            tlang::watch(Path::new(&script), run, checks.lowering_options())
+        }
+        Command::Lint { script, metrics, max_complexity, max_nesting, max_lines, report } => {
+            let options = LintOptions {
+                metrics,
+                thresholds: Thresholds { complexity: max_complexity, nesting: max_nesting, lines: max_lines },
+                report: report.map(PathBuf::from),
+            };
+            tlang::lint_file(Path::new(&script), &options).map(|_| ())
+        }
+        Command::Graph { script, graph, format, output } => {
+            let options = GraphOptions { graph, format, output: output.map(PathBuf::from) };
+            tlang::graph_file(Path::new(&script), &options)
+                .map(|written| {
+                    if let Some(written) = written {
+                        println!("wrote {}", written.display());
+                    }
+                })
+        }
+        Command::Bench { script, filter, warmup, iterations, baseline, save_baseline, noise, checks } => {
+            let options = BenchOptions {
+                filter,
+                warmup,
+                iterations,
+                lowering: checks.lowering_options(),
+                baseline: baseline.map(PathBuf::from),
+                save_baseline: save_baseline.map(PathBuf::from),
+                noise: noise / 100.0,
+            };
+            tlang::bench_file(Path::new(&script), &options).map(|_| ())
+        }
+        Command::Cov { cmd: CovCommand::Report { script, map, counts } } => {
+            let options = ReportOptions { map: map.map(PathBuf::from), counts: counts.map(PathBuf::from) };
+            tlang::report_file(Path::new(&script), &options).map(|_| ())
+        }
+        Command::Internal { cmd: InternalCommand::DiffIr { old, new, stage, checks } } => {
+            tlang::internal::diff_ir(Path::new(&old), Path::new(&new), stage, checks.lowering_options())
+                .map(|_| ())
+        }
+        Command::Repl => tlang::start_repl().map_err(Into::into),
+        Command::Explain { code } => explain(code.as_deref()).map_err(Into::into),
+        Command::Targets => targets().map_err(Into::into),
+    };
 
-        match process_input(&line) {
-            Ok(output) => println!("{}", output),
-            Err(err) => eprintln!("Error: {}", err),
+    if let Err(err) = result {
+        match err.downcast_ref::<Diagnostic>() {
+            Some(diagnostic) => eprintln!("{}", diagnostic),
+            None => eprintln!("Error: {}", err),
         }
+        process::exit(1);
     }
-
-    println!("Goodbye!");
-    process::exit(0);
 }
@@ -1,39 +1,422 @@
 // --- src/repl.rs ---
-use std::io::{self, Write};
+//! The interactive REPL.
+//!
+//! A `Session` keeps what has been entered: items, such as functions and
+//! structs, `let` bindings, and statements that changed them without
+//! printing anything, such as `v.push(1)`. Any other line is run as the body
+//! of a `main` that starts with those statements, and if it is an
+//! expression with a value, the value is printed. Lines starting with `:`
+//! are commands:
+//!
+//! - `:type <expr>` prints the type of an expression;
+//! - `:ast <input>` prints the syntax tree of an expression, or of items,
+//!   as S-expressions;
+//! - `:tir <input>` prints the TIR the line lowers to, printing the value
+//!   of an expression as the line would, with the types the session gives
+//!   its values;
+//! - `:load <path>` adds the items of a file, except its `main`;
+//! - `:reset` forgets everything entered.
+//!
+//! Lines are read with rustyline: arrow keys walk the history, which is
+//! kept in `~/.tlang_history` between runs, and Tab completes the names
+//! the session defines, keywords and commands.
 
-/// Starts the REPL loop using only std::io.
-pub fn start_repl() -> io::Result<()> {
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
+use compiler::resolve::type_name;
+use compiler::{parse_expression, parse_source, Database, LoweringOptions, SymbolKind};
+use plugin_api::CompiledModule;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+use shared::dump::{self, DumpFormat};
+use shared::{ExprKind, ItemKind, Program, StmtKind, Type};
+use std::path::PathBuf;
 
-    loop {
-        // Print prompt
-        write!(stdout, "tlang> ")?;
-        stdout.flush()?;
-
-        // Read one line of user input
-        let mut line = String::new();
-        let bytes_read = stdin.read_line(&mut line)?;
-        if bytes_read == 0 {
-            // EOF (Ctrl-D)
-            println!();
-            break;
+/// What `:help` prints.
+const HELP: &str = "\
+:type <expr>   show the type of an expression
+:ast <input>   show the syntax tree of an expression or items
+:tir <input>   show the TIR a line lowers to
+:load <path>   add the items of a file, except its `main`
+:reset         forget everything entered
+:help          show this list";
+
+/// The commands, for completion.
+const COMMANDS: &[&str] = &["type", "ast", "tir", "load", "reset", "help"];
+
+/// Keywords completed along with the session's names.
+const KEYWORDS: &[&str] = &[
+    "let", "const", "fn", "if", "else", "while", "loop", "for",
+    "return", "match", "struct", "enum", "trait", "impl", "use",
+    "async", "await", "move", "pub", "mod", "static", "extern",
+    "spawn", "defer",
+];
+
+/// The binding `:type` gives the expression it is asked about.
+const VALUE: &str = "__repl_value";
+
+/// Items and statements entered so far.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Source of the items, one after another
+    items: String,
+    /// `let` bindings and other statements kept, in the order they were
+    /// entered
+    statements: Vec<String>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// Handle one line of input, returning what to print.
+    ///
+    /// # Errors
+    /// Returns the first error in the line, or in running it.
+    pub fn eval(&mut self, line: &str) -> Result<String, String> {
+        let line = line.trim();
+        if let Some(command) = line.strip_prefix(':') {
+            return self.command(command);
+        }
+        if line.starts_with("let ") {
+            let binding = format!("{};", line.trim_end_matches(';'));
+            self.check(&binding)?;
+            self.statements.push(binding);
+            return Ok(String::new());
+        }
+        if parse_source(line).is_ok_and(|program| !program.items.is_empty()) {
+            let items = std::mem::take(&mut self.items);
+            self.items = format!("{}{}\n", items, line);
+            return match self.check("") {
+                Ok(_) => Ok(String::new()),
+                Err(err) => {
+                    self.items = items;
+                    Err(err)
+                }
+            };
+        }
+
+        let statement = self.statement(line);
+        let output = self.run(&statement)?;
+        // Later lines see what it did, and rerunning it prints nothing.
+        if output.is_empty() {
+            self.statements.push(statement);
+        }
+        Ok(output)
+    }
+
+    /// `line` as a statement of `main`: printing its value, if it is an
+    /// expression with one, and otherwise as it is.
+    fn statement(&self, line: &str) -> String {
+        let expr = line.trim_end_matches(';');
+        if let Ok(ty) = self.type_of(expr)
+            && type_name(&ty) != "()"
+        {
+            return format!("println({});", expr);
+        }
+        // `for` loops and other blocks take no `;`.
+        if expr.ends_with('}') { expr.to_string() } else { format!("{};", expr) }
+    }
+
+    /// Run `statement` after the kept ones, returning what it printed.
+    fn run(&self, statement: &str) -> Result<String, String> {
+        let module = self.lower(statement)?;
+        plugin_api::interpret(&module).map_err(|err| err.to_string())
+    }
+
+    fn command(&mut self, command: &str) -> Result<String, String> {
+        let (name, arg) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let arg = arg.trim();
+        match name {
+            "type" => self.type_of(arg).map(|ty| type_name(&ty)),
+            "ast" => ast(arg),
+            "tir" => self.tir(arg),
+            "load" => self.load(arg),
+            "reset" => {
+                *self = Session::new();
+                Ok("session reset".to_string())
+            }
+            "help" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command `:{}`; `:help` lists the commands", name)),
+        }
+    }
+
+    /// The TIR `line` lowers to after the kept statements, as `eval` runs
+    /// it, with the types the session gives its values.
+    fn tir(&self, line: &str) -> Result<String, String> {
+        if line.is_empty() {
+            return Err("`:tir` needs an expression".to_string());
+        }
+        let statement = self.statement(line);
+        let program = self.check(&statement)?;
+        let tir = compiler::lower_program_tir(&program, &self.source(&statement), "<repl>", LoweringOptions::default())
+            .map_err(|err| err.to_string())?;
+        Ok(tir.to_string().trim_end().to_string())
+    }
+
+    /// The type of `expr`, after the kept statements.
+    fn type_of(&self, expr: &str) -> Result<Type, String> {
+        if expr.is_empty() {
+            return Err("`:type` needs an expression".to_string());
+        }
+        let program = self.check(&format!("let {} = {};", VALUE, expr))?;
+        let value = program.items.iter().find_map(|item| match &item.kind {
+            ItemKind::Function { name, body: Some(body), .. } if name == "main" => match &body.kind {
+                ExprKind::Block(block) => match &block.statements.last()?.kind {
+                    StmtKind::Let { initializer: Some(init), .. } => init.ty.clone(),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        });
+        value.ok_or_else(|| format!("cannot tell the type of `{}`", expr))
+    }
+
+    /// Add the items of the file at `path`, leaving out its `main`.
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let program = parse_source(&text).map_err(|err| format!("{}: {}", path, err))?;
+        let items = std::mem::take(&mut self.items);
+        let mut loaded = items.clone();
+        let mut count = 0;
+        for item in &program.items {
+            if matches!(&item.kind, ItemKind::Function { name, .. } if name == "main") {
+                continue;
+            }
+            let start = item.span.offset();
+            loaded.push_str(&text[start..start + item.span.len()]);
+            loaded.push('\n');
+            count += 1;
+        }
+        self.items = loaded;
+        if let Err(err) = self.check("") {
+            self.items = items;
+            return Err(err);
+        }
+        Ok(format!("loaded {} item{} from {}", count, if count == 1 { "" } else { "s" }, path))
+    }
+
+    /// The names the next line can use: the items and what the kept
+    /// statements bind.
+    pub fn names(&self) -> Vec<String> {
+        let (mut db, file) = self.database("");
+        let symbols = db.symbols(file);
+        // Variables of `main` are the session's; those of items are not.
+        let main = symbols.symbols().iter().rfind(|symbol| symbol.name == "main");
+        let main_start = main.map_or(0, |main| main.span.offset());
+        let mut names: Vec<String> = symbols
+            .symbols()
+            .iter()
+            .filter(|symbol| match symbol.kind {
+                SymbolKind::Parameter => false,
+                SymbolKind::Variable => symbol.span.offset() > main_start,
+                _ => symbol.name != "main",
+            })
+            .map(|symbol| symbol.name.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The session as a program whose `main` runs `statement` after the
+    /// kept ones.
+    fn source(&self, statement: &str) -> String {
+        let mut source = self.items.clone();
+        source.push_str("fn main() {\n");
+        for kept in &self.statements {
+            source.push_str(&format!("    {}\n", kept));
         }
+        source.push_str(&format!("    {}\n}}\n", statement));
+        source
+    }
+
+    /// The session's program with `statement`, type checked.
+    fn check(&self, statement: &str) -> Result<Program, String> {
+        let (mut db, file) = self.database(statement);
+        if let Some(err) = db.parse(file).errors.first() {
+            return Err(err.to_string());
+        }
+        let checked = db.type_check(file);
+        match &checked.error {
+            Some(err) => Err(err.to_string()),
+            None => Ok(checked.program.clone()),
+        }
+    }
+
+    /// The module the session's program with `statement` lowers to.
+    fn lower(&self, statement: &str) -> Result<CompiledModule, String> {
+        self.check(statement)?;
+        let (mut db, file) = self.database(statement);
+        match &*db.lower(file) {
+            Ok(module) => Ok(module.clone()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn database(&self, statement: &str) -> (Database, compiler::FileId) {
+        let mut db = Database::new();
+        let file = db.file("<repl>");
+        db.set_text(file, &self.source(statement));
+        (db, file)
+    }
+}
+
+/// The syntax tree of `input`, an expression or else items, as
+/// S-expressions.
+fn ast(input: &str) -> Result<String, String> {
+    let tree = match parse_expression(input) {
+        Ok(expr) => dump::dump(&expr, DumpFormat::Sexpr),
+        Err(err) => match parse_source(input) {
+            Ok(program) if !program.items.is_empty() => dump::dump(&program.items, DumpFormat::Sexpr),
+            _ => return Err(err.to_string()),
+        },
+    };
+    tree.map_err(|err| err.to_string())
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Completions of the word before byte `pos` of `line`, and where the word
+/// starts: a command after a leading `:`, and otherwise one of `names` or a
+/// keyword.
+fn completions(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let before = &line[..pos.min(line.len())];
+    let start = before.rfind(|c: char| !is_ident(c)).map_or(0, |i| i + 1);
+    let prefix = &before[start..];
+    let candidates: Vec<&str> = if start == 1 && before.starts_with(':') {
+        COMMANDS.to_vec()
+    } else {
+        names.iter().map(String::as_str).chain(KEYWORDS.iter().copied()).collect()
+    };
+    let matches = candidates.into_iter().filter(|c| c.starts_with(prefix)).map(str::to_string).collect();
+    (start, matches)
+}
+
+/// Completion for the line editor, from the names the session defines.
+#[derive(Default, Helper, Highlighter, Hinter, Validator)]
+struct ReplHelper {
+    names: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(completions(line, pos, &self.names))
+    }
+}
+
+/// Where the lines entered are kept between runs.
+fn history_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".tlang_history"))
+}
+
+/// Starts the REPL loop.
+///
+/// # Errors
+/// Returns an error if the terminal cannot be read or the history cannot
+/// be saved.
+pub fn start_repl() -> Result<(), ReadlineError> {
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ReplHelper::default()));
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is none before the first run.
+        let _ = editor.load_history(path);
+    }
+    let mut session = Session::new();
+
+    loop {
+        let line = match editor.readline("tlang> ") {
+            Ok(line) => line,
+            // Ctrl-C abandons the line, Ctrl-D the session.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
+        };
 
         let input = line.trim();
         if input.is_empty() {
             continue;
         }
+        editor.add_history_entry(input)?;
         if input.eq_ignore_ascii_case("exit") || input.eq_ignore_ascii_case("quit") {
             break;
         }
 
-        // Dispatch to your evaluator/compiler
-        match crate::evaluate(input) {
-            Ok(output) => println!("{}", output),
-            Err(err)    => eprintln!("Error: {}", err),
+        match session.eval(input) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output.trim_end_matches('\n')),
+            Err(err) => eprintln!("Error: {}", err),
+        }
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = session.names();
         }
     }
 
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_and_items_carry_over_between_lines() {
+        let mut session = Session::new();
+        assert_eq!(session.eval("let x: u8 = 200"), Ok(String::new()));
+        assert_eq!(session.eval("x.wrapping_add(100)"), Ok("44\n".to_string()));
+        assert_eq!(session.eval(":type x.checked_add(1)"), Ok("Option<u8>".to_string()));
+        assert_eq!(session.eval("fn double(n: i32) -> i32 { n * 2 }"), Ok(String::new()));
+        assert_eq!(session.eval(":type double(x as i32)"), Ok("i32".to_string()));
+        assert!(session.eval(":tir x").unwrap().contains(": u8 = const 200\n"));
+        session.eval("let v = Vec::new()").unwrap();
+        assert_eq!(session.eval("v.push(3)"), Ok(String::new()));
+        assert_eq!(session.eval("for i in 0..2 { print(i) }"), Ok("01".to_string()));
+        assert_eq!(session.eval("v.len()"), Ok("1\n".to_string()));
+
+        session.eval(":reset").unwrap();
+        assert!(session.eval(":type x").is_err());
+        session.eval("let x: i32 = 5").unwrap();
+        let tir = session.eval(":tir x + 1").unwrap();
+        assert!(tir.contains("%1: i32 = const 6\n"), "{}", tir);
+        assert_eq!(session.eval(":tir println(x)").unwrap().matches("print ").count(), 2);
+        assert!(session.eval(":ast 1 + x").unwrap().starts_with("(Expr"));
+        assert!(session.eval(":frobnicate").unwrap_err().contains(":help"));
+    }
+
+    #[test]
+    fn completion_offers_session_names_keywords_and_commands() {
+        let mut session = Session::new();
+        session.eval("fn square(n: i32) -> i32 { n * n }").unwrap();
+        session.eval("let side = 3").unwrap();
+        let names = session.names();
+        assert_eq!(names, ["side", "square"]);
+
+        let (start, words) = completions("print(s", 7, &names);
+        assert_eq!((start, words), (6, ["side", "square", "struct", "static", "spawn"].map(String::from).to_vec()));
+        assert_eq!(completions(":lo", 3, &names), (1, vec!["load".to_string()]));
+        assert_eq!(completions("wh", 2, &names).1, ["while"]);
+    }
+
+    #[test]
+    fn load_adds_the_items_of_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.t");
+        std::fs::write(&path, "fn answer() -> i32 { 42 }\nfn main() { }\n").unwrap();
+
+        let mut session = Session::new();
+        let loaded = session.eval(&format!(":load {}", path.display())).unwrap();
+        assert!(loaded.starts_with("loaded 1 item from"), "{}", loaded);
+        assert_eq!(session.eval(":type answer()"), Ok("i32".to_string()));
+    }
+}
@@ -1,26 +1,135 @@
 // File: tlang/src/runner.rs
 
 //! File runner for T-Lang source files.
-//! Reads a source file, compiles it, and prints bytecode or errors.
+//! Reads a source file, compiles it, and executes it in-process with the
+//! Cranelift JIT backend.
+//!
+//! The program sees the path of its file and the arguments it was given as
+//! its command line, as `env::arg` reads it. A program exits with the
+//! `i32` its `main` returns, or 0 if `main` returns nothing;
+//! `process::exit` and panics end the program with their own code, which
+//! the JIT returns rather than exiting the process. Self tail calls are
+//! made loops first, as `#[tailcall]` functions need at every level.
+//!
+//! Lowered for coverage, the program's coverage map is written before it
+//! runs, as `crate::cov` describes.
 
-use std::{error::Error, fs, path::Path};
-use compiler::compile_source;
+use std::{collections::BTreeSet, error::Error, fmt, fs, iter, path::Path};
+use compiler::backends::cranelift_jit::CraneliftJitBackend;
+use compiler::optimizer::tailcall::TailCalls;
+use compiler::{Database, LoweringOptions, SafetyConfig, SafetySeverity};
+use errors::TlError;
+use shared::source::{diagnostic, line_col_from_offset};
+use plugin_api::{Backend, CompiledModule, DebugInfo, Optimizer};
 
-/// Run T-Lang on the specified file path.
+/// Run T-Lang on the specified file path, lowering it with `options` and
+/// passing it `args`, and return its exit code.
 ///
 /// # Errors
 /// Returns an error if file I/O or compilation fails.
-pub fn run_file(path: &Path) -> Result<(), Box<dyn Error>> {
+pub fn run_file(path: &Path, options: LoweringOptions, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let module = TailCalls.optimize(lower_file(path, options)?)?;
+    if options.coverage {
+        crate::cov::write_map(path, &module)?;
+    }
+    let program = CraneliftJitBackend.compile(module)?;
+    let command_line = iter::once(path.display().to_string()).chain(args.iter().cloned());
+    tstd::env::set_args(command_line.collect());
+    Ok(program.run())
+}
+
+/// Parse, check and lower the file at `path` with `options`, failing with
+/// the first error there is, once its project's build script has run, as
+/// `crate::build` describes. A `.tir` file holds textual TIR, as `tlang
+/// compile --emit tir` writes it, and is read as it is, without debug info.
+pub(crate) fn lower_file(path: &Path, options: LoweringOptions) -> Result<CompiledModule, Box<dyn Error>> {
     let src = fs::read_to_string(path)?;
-    match compile_source(&src) {
-        Ok(module) => {
-            let out = String::from_utf8_lossy(&module.bytecode);
-            println!("{}", out);
-            Ok(())
-        }
-        Err(e) => {
-            eprintln!("Compilation error: {:#?}", e);
-            Err(Box::new(e))
+    if path.extension().is_some_and(|extension| extension == "tir") {
+        let module: shared::tir::Module = src.parse()?;
+        return Ok(CompiledModule::from_tir(&module, DebugInfo::default())?);
+    }
+    let build = crate::build::prepare(path)?;
+    lower_source(path, &build.source(src), build.features, options)
+}
+
+/// Parse, check and lower `src`, the source of the file at `path`, with
+/// `features` on and `options`, failing with the first error there is. The
+/// safety analyses run on the checked program at the usual lint levels:
+/// their warnings are printed, and an error is the build's error.
+pub(crate) fn lower_source(
+    path: &Path,
+    src: &str,
+    features: BTreeSet<String>,
+    options: LoweringOptions,
+) -> Result<CompiledModule, Box<dyn Error>> {
+    let mut db = Database::with_lowering_options(options);
+    db.set_features(features);
+    let file = db.file(&path.to_string_lossy());
+    db.set_text(file, src);
+    if let Some(error) = check_error(&mut db, path, file) {
+        return Err(error.into());
+    }
+    if let Some(error) = safety_error(&mut db, path, file) {
+        return Err(error.into());
+    }
+    match &*db.lower(file) {
+        Ok(module) => Ok(module.clone()),
+        Err(err) => Err(err.to_string().into()),
+    }
+}
+
+/// An error in a source file, as the one line `path:line:col:
+/// error[code]: message` that `tlang` prints as it is.
+#[derive(Debug)]
+pub struct Diagnostic(String);
+
+impl Diagnostic {
+    /// `err`, made from `text`, the source of the file at `path`.
+    pub(crate) fn new(path: &Path, text: &str, err: &TlError) -> Self {
+        Self(diagnostic(&path.to_string_lossy(), text, err))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for Diagnostic {}
+
+/// The first parse error in `file`, at `path`, or its first type error when
+/// it parses.
+pub(crate) fn check_error(db: &mut Database, path: &Path, file: compiler::FileId) -> Option<Diagnostic> {
+    let parsed = db.parse(file);
+    let checked = if parsed.errors.is_empty() { Some(db.type_check(file)) } else { None };
+    let error = parsed.errors.first().or_else(|| checked.as_ref()?.error.as_ref())?;
+    Some(Diagnostic::new(path, db.text(file), error))
+}
+
+/// Print the safety warnings about `file`, at `path`, and return its first
+/// safety error, if it has one.
+fn safety_error(db: &mut Database, path: &Path, file: compiler::FileId) -> Option<Diagnostic> {
+    let safety = db.safety(file);
+    let violations = match &*safety {
+        Ok(violations) => violations,
+        Err(err) => return Some(Diagnostic::new(path, db.text(file), err)),
+    };
+    let text = db.text(file);
+    let config = SafetyConfig::default();
+    for violation in violations {
+        match config.severity(violation) {
+            Some(severity) if severity >= SafetySeverity::Error => {
+                let error = TlError::safety(text, violation.span(), violation.description());
+                return Some(Diagnostic::new(path, text, &error));
+            }
+            Some(_) => {
+                let (line, col) = line_col_from_offset(text, violation.span().offset());
+                let (code, message) = (violation.code(), violation.description());
+                eprintln!("{}:{}:{}: warning[{}]: {}", path.display(), line, col, code, message);
+            }
+            None => {}
         }
     }
+    None
 }
@@ -0,0 +1,148 @@
+// File: tlang/src/watch.rs
+
+//! Watch mode: recompile a T-Lang source file every time it changes.
+//!
+//! The file's directory is watched rather than the file, since many editors
+//! save by replacing it. Every rebuild goes through one query `Database`, so
+//! a save without changes costs nothing, and prints one line per diagnostic
+//! and a summary of what the edit fixed or broke. With `run`, a file that
+//! compiles cleanly is run with the Cranelift JIT backend; watching resumes
+//! when the program returns. Each rebuild runs the project's build script
+//! first, if anything it depends on changed, as `crate::build` describes.
+
+use compiler::backends::cranelift_jit::CraneliftJitBackend;
+use compiler::{Database, FileId, LoweringOptions};
+use errors::TlError;
+use notify::{RecursiveMode, Watcher};
+use plugin_api::Backend;
+use shared::source::diagnostic;
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// File system events this close together belong to the same save.
+const SETTLE: Duration = Duration::from_millis(50);
+
+/// Compile `path`, then recompile it whenever it changes, until interrupted.
+/// Programs that are run are lowered with `options`.
+///
+/// # Errors
+/// Returns an error if the file's directory cannot be watched.
+pub fn watch(path: &Path, run: bool, options: LoweringOptions) -> Result<(), Box<dyn Error>> {
+    let path = path.canonicalize()?;
+    let dir = path.parent().ok_or("the file has no parent directory")?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    let mut session = Session::new(path, run, options);
+    session.rebuild();
+    println!("watching {} for changes", session.path.display());
+    loop {
+        let event = rx.recv()??;
+        if event.kind.is_access() || !event.paths.contains(&session.path) {
+            continue;
+        }
+        while rx.recv_timeout(SETTLE).is_ok() {}
+        session.rebuild();
+    }
+}
+
+/// What a watched file compiled to last time.
+struct Session {
+    db: Database,
+    file: FileId,
+    path: PathBuf,
+    run: bool,
+    built: bool,
+    /// The features the build script turned on last time
+    features: BTreeSet<String>,
+    /// Code and message of each diagnostic reported by the last build, to
+    /// tell which ones an edit fixed
+    reported: HashSet<(&'static str, String)>,
+}
+
+impl Session {
+    fn new(path: PathBuf, run: bool, options: LoweringOptions) -> Self {
+        let mut db = Database::with_lowering_options(options);
+        let file = db.file(&path.to_string_lossy());
+        Session { db, file, path, run, built: false, features: BTreeSet::new(), reported: HashSet::new() }
+    }
+
+    /// Recompile the file if its text changed, and report the outcome.
+    fn rebuild(&mut self) {
+        let (text, features) = match self.source() {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: {}", self.path.display(), err);
+                return;
+            }
+        };
+        if self.built && self.db.text(self.file) == text && self.features == features {
+            return;
+        }
+        self.built = true;
+
+        let start = Instant::now();
+        self.db.set_features(features.clone());
+        self.features = features;
+        self.db.set_text(self.file, &text);
+        let parsed = self.db.parse(self.file);
+        let mut errors: Vec<&TlError> = parsed.errors.iter().collect();
+        // Type errors in a partial program are mostly fallout of the syntax errors.
+        let checked = errors.is_empty().then(|| self.db.type_check(self.file));
+        errors.extend(checked.iter().filter_map(|checked| checked.error.as_ref()));
+        let elapsed = start.elapsed();
+
+        for err in &errors {
+            eprintln!("{}", diagnostic(&self.path.to_string_lossy(), &text, err));
+        }
+        let reported: HashSet<_> = errors.iter().map(|err| (err.code(), err.to_string())).collect();
+        let fixed = self.reported.difference(&reported).count();
+        let new = reported.difference(&self.reported).count();
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        match errors.len() {
+            0 => println!("{}: ok in {:.1?} ({} fixed)", name, elapsed, fixed),
+            count => println!(
+                "{}: {} error{} in {:.1?} ({} fixed, {} new)",
+                name,
+                count,
+                if count == 1 { "" } else { "s" },
+                elapsed,
+                fixed,
+                new
+            ),
+        }
+        self.reported = reported;
+
+        if self.run && errors.is_empty() {
+            self.run_program();
+        }
+    }
+
+    /// The text to compile, the file's own with what the build script
+    /// generated, and the features it turned on.
+    fn source(&self) -> Result<(String, BTreeSet<String>), Box<dyn Error>> {
+        let text = std::fs::read_to_string(&self.path)?;
+        let build = crate::build::prepare(&self.path)?;
+        Ok((build.source(text), build.features))
+    }
+
+    fn run_program(&mut self) {
+        let lowered = self.db.lower(self.file);
+        let program = match &*lowered {
+            Ok(module) => CraneliftJitBackend.compile(module.clone()),
+            Err(err) => {
+                eprintln!("{}: {}", self.path.display(), err);
+                return;
+            }
+        };
+        match program.map(|program| program.run()) {
+            Ok(0) => {}
+            Ok(code) => eprintln!("{}: exited with code {}", self.path.display(), code),
+            Err(err) => eprintln!("{}: {}", self.path.display(), err),
+        }
+    }
+}
@@ -0,0 +1,4 @@
+fn main() {
+    println(env::arg(1));
+    process::exit(env::arg_count());
+}
@@ -0,0 +1,15 @@
+fn main() {
+    println("not a benchmark");
+}
+
+#[bench]
+fn bench_loop() {
+    for i in 0..50 {
+        let _x = i * 2;
+    }
+}
+
+#[bench]
+fn bench_print() {
+    print("");
+}
@@ -0,0 +1,6 @@
+fn main() {
+    let text = io::read_file(env::arg(1));
+    io::write(text);
+    let count = env::arg_count();
+    process::exit(count);
+}
@@ -0,0 +1,9 @@
+fn main() {
+    let start = time::now_ms();
+    random::seed(start);
+    let roll = random::below(6);
+    let later = time::now_ms();
+    println(later >= start, " ", roll >= 0 && roll < 6);
+    let code = (roll - roll + 7) as i32;
+    process::exit(code);
+}
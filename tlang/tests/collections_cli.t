@@ -0,0 +1,11 @@
+// A collection changed in a loop decided at run time, which `tstd` holds.
+fn main() {
+    let v = Vec::new();
+    v.push(4);
+    let mut n = 0;
+    while n < 3 {
+        v.push(n * 10);
+        n += 1;
+    }
+    println("len ", v.len(), ", second ", v.get(1));
+}
@@ -0,0 +1,4 @@
+fn main() -> i32 {
+    println("compiled ahead of time");
+    4
+}
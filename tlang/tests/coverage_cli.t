@@ -0,0 +1,11 @@
+fn main() {
+    // Count to three.
+    for i in 0..3 {
+        println(i);
+    }
+    println("done");
+}
+
+fn never() {
+    println("never");
+}
@@ -1 +1,3 @@
-"CLI Integration Test!";
+fn main() {
+    println("CLI Integration Test!");
+}
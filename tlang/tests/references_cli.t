@@ -0,0 +1,50 @@
+// References read and assign what they borrow: a `&` parameter gets the
+// value, and a call of a function taking a `&mut` assigns the caller's place.
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+fn getx(p: &Point) -> i64 {
+    p.x
+}
+
+fn bump(n: &mut i64) {
+    *n = *n + 1;
+}
+
+fn shift(p: &mut Point, by: i64) {
+    p.x = p.x + by;
+    if p.x > 10 {
+        return;
+    };
+    p.y = p.y + by;
+}
+
+fn clamp(n: &mut i64, limit: i64) -> bool {
+    if *n > limit {
+        *n = limit;
+        return true;
+    };
+    false
+}
+
+fn main() {
+    let mut x = 5;
+    bump(&mut x);
+    let r = &x;
+    println(*r);
+
+    let mut p = Point { x: 1, y: 1 };
+    let mut i = 0;
+    while i < 6 {
+        shift(&mut p, 2);
+        i += 1;
+    }
+    let s = &p;
+    println(s.x, " ", s.y, " ", getx(&p));
+
+    let m = &mut x;
+    *m += 10;
+    println(clamp(m, 12), " ", x);
+}
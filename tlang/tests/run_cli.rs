@@ -6,8 +6,6 @@ use std::thread;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 
-const MAX_RECURSION_DEPTH: usize = 512;
-
 #[test]
 fn run_string_literal_script() {
     let start_time = Instant::now();
@@ -60,6 +58,266 @@ fn run_string_literal_script() {
     let _ = stderr_handle.join();
 }
 
+#[test]
+fn run_passes_arguments_and_exits_with_the_programs_code() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["run", "tests/args_cli.t", "--", "hello", "world"])
+        .output()
+        .expect("Failed to run tlang executable");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+    // The file's path and both arguments
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn run_reuses_bound_intrinsic_results() {
+    let input = std::env::temp_dir().join(format!("tlang-bound-cli-{}", std::process::id()));
+    std::fs::write(&input, "read back\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["run", "tests/bound_cli.t", "--"])
+        .arg(&input)
+        .output()
+        .expect("Failed to run tlang executable");
+    let _ = std::fs::remove_file(&input);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "read back\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn run_reuses_bound_clock_and_random_values() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["run", "tests/clock_cli.t"])
+        .output()
+        .expect("Failed to run tlang executable");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "true true\n");
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn run_loops_deep_tail_recursion() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["run", "tests/tailcall_cli.t"])
+        .output()
+        .expect("Failed to run tlang executable");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1000000\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn run_reads_and_assigns_through_references() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["run", "tests/references_cli.t"])
+        .output()
+        .expect("Failed to run tlang executable");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "6\n13 9 13\ntrue 12\n", "{}", stderr);
+    assert!(output.status.success());
+}
+
+#[test]
+fn run_reports_an_error_once_with_what_was_meant() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["run", "tests/typo_cli.t"])
+        .output()
+        .expect("Failed to run tlang executable");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "tests/typo_cli.t:3:13: error[E0003]: Type error: Undefined variable: cuont\n  = help: did you mean `count`?\n"
+    );
+}
+
+#[test]
+fn compile_links_an_executable() {
+    let exe = std::env::temp_dir().join(format!("tlang-compile-cli-{}", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["compile", "tests/compile_cli.t", "--emit", "exe", "-o"])
+        .arg(&exe)
+        .output()
+        .expect("Failed to run tlang executable");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let linked = String::from_utf8_lossy(&output.stdout).trim().trim_start_matches("wrote ").to_string();
+
+    let run = Command::new(&linked).output().expect("Failed to run the executable");
+    let _ = std::fs::remove_file(&linked);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "compiled ahead of time\n");
+    assert_eq!(run.status.code(), Some(4));
+}
+
+#[test]
+fn compile_links_the_runtime_library() {
+    let exe = std::env::temp_dir().join(format!("tlang-collections-cli-{}", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["compile", "tests/collections_cli.t", "--target", "c", "--emit", "exe", "-o"])
+        .arg(&exe)
+        .output()
+        .expect("Failed to run tlang executable");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let linked = String::from_utf8_lossy(&output.stdout).trim().trim_start_matches("wrote ").to_string();
+
+    let run = Command::new(&linked).output().expect("Failed to run the executable");
+    let _ = std::fs::remove_file(&linked);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "len 4, second 0\n");
+}
+
+#[test]
+fn compile_stops_at_the_stage_to_emit() {
+    let emit = |stage: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+            .args(["compile", "tests/compile_cli.t", "--emit"])
+            .args(stage.split(' '))
+            .output()
+            .expect("Failed to run tlang executable");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    assert_eq!(emit("tokens").lines().next(), Some("0..2 Fn"));
+    assert!(emit("ast").contains("\"main\""));
+    assert!(emit("tir").lines().count() > 1);
+
+    // Syntax trees and TIR dump as JSON or S-expressions, the same every time.
+    let ast = emit("ast");
+    assert!(ast.starts_with("{\n"), "{}", ast);
+    assert_eq!(emit("ast --format json"), ast);
+    assert!(emit("ast --format sexpr").starts_with("(Program\n"));
+    let tir = emit("tir --format json");
+    assert!(tir.starts_with("{\n  \"functions\": ["), "{}", tir);
+    assert_eq!(emit("tir --format json"), tir);
+    assert!(emit("tir --format sexpr").starts_with("(Module\n"));
+}
+
+#[test]
+fn emitted_tir_runs_as_the_program() {
+    let tir = std::env::temp_dir().join(format!("tlang-tir-cli-{}.tir", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["compile", "tests/compile_cli.t", "--emit", "tir", "-o"])
+        .arg(&tir)
+        .output()
+        .expect("Failed to run tlang executable");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(env!("CARGO_BIN_EXE_tlang")).arg("run").arg(&tir).output();
+    let _ = std::fs::remove_file(&tir);
+    let run = run.expect("Failed to run tlang executable");
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "compiled ahead of time\n");
+    assert_eq!(run.status.code(), Some(4));
+}
+
+#[test]
+fn compile_rejects_unknown_optimizers() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .args(["compile", "tests/compile_cli.t", "-O", "3", "--disable-opt", "no-such-pass", "-o"])
+        .arg(std::env::temp_dir().join(format!("tlang-optimizers-cli-{}", std::process::id())))
+        .output()
+        .expect("Failed to run tlang executable");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown optimizer `no-such-pass`"));
+}
+
+#[test]
+fn deterministic_builds_are_byte_identical() {
+    let build = |stage: &str, output: &str| {
+        let path = std::env::temp_dir().join(format!("tlang-deterministic-{}-{}", std::process::id(), output));
+        let status = Command::new(env!("CARGO_BIN_EXE_tlang"))
+            .args(["compile", "--deterministic", "--emit", stage, "--link-arg", "-g", "-o"])
+            .arg(&path)
+            .arg(std::fs::canonicalize("tests/compile_cli.t").unwrap())
+            .status()
+            .expect("Failed to run tlang executable");
+        assert!(status.success());
+        let bytes = std::fs::read(&path).expect("nothing was written");
+        let _ = std::fs::remove_file(&path);
+        bytes
+    };
+    assert_eq!(build("asm", "a.s"), build("asm", "b.s"));
+    assert_eq!(build("exe", "a"), build("exe", "b"));
+}
+
+#[test]
+fn coverage_reports_how_often_each_line_ran() {
+    let dir = std::env::temp_dir().join(format!("tlang-coverage-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = std::fs::canonicalize("tests/coverage_cli.t").unwrap();
+    let tlang = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+            .args(args)
+            .arg(&script)
+            .current_dir(&dir)
+            .env_remove("TLANG_COVERAGE_FILE")
+            .output()
+            .expect("Failed to run tlang executable");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    assert_eq!(tlang(&["run", "--coverage"]), "0\n1\n2\ndone\n");
+    let report = tlang(&["cov", "report"]);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[2], "        1:    3:    for i in 0..3 {");
+    assert_eq!(lines[3], "        3:    4:        println(i);");
+    assert_eq!(lines[4], "        -:    5:    }");
+    assert_eq!(lines[9], "    #####:   10:    println(\"never\");");
+    assert_eq!(lines.last(), Some(&"3 of 4 lines ran (75.0%)"));
+}
+
+#[test]
+fn bench_times_each_benchmark_and_compares_with_a_baseline() {
+    let baseline = std::env::temp_dir().join(format!("tlang-bench-cli-{}.json", std::process::id()));
+    let bench = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+            .args(["bench", "tests/bench_cli.t", "--iterations", "5", "--warmup", "1"])
+            .args(args)
+            .output()
+            .expect("Failed to run tlang executable");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let saved = bench(&["--save-baseline", baseline.to_str().unwrap()]);
+    let names: Vec<&str> = saved.lines().filter_map(|line| line.split_whitespace().next()).collect();
+    assert_eq!(names, ["bench_loop", "bench_print"]);
+    assert!(saved.lines().all(|line| line.contains(" mean ") && line.contains(" stddev ")), "{}", saved);
+
+    let compared = bench(&["loop", "--baseline", baseline.to_str().unwrap()]);
+    let _ = std::fs::remove_file(&baseline);
+    assert_eq!(compared.lines().count(), 1, "{}", compared);
+    assert!(compared.starts_with("bench_loop") && compared.trim_end().ends_with(')'), "{}", compared);
+}
+
+#[test]
+fn build_scripts_generate_sources_and_turn_on_features() {
+    let dir = std::env::temp_dir().join(format!("tlang-build-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let main = "#[cfg(fast)]\nfn speed() -> i64 {\n    2\n}\n\n#[cfg(slow)]\nfn speed() -> i64 {\n    1\n}\n\nfn main() {\n    println(speed() * table_size());\n}\n";
+    std::fs::write(dir.join("main.t"), main).unwrap();
+    let script = "fn main() {\n    println(\"tlang:feature=fast\");\n    println(\"tlang:generated=gen/table.t\");\n    println(\"fn table_size() -> i64 { \", 8 * 8, \" }\");\n}\n";
+    std::fs::write(dir.join("build.t"), script).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .arg("run")
+        .arg(dir.join("main.t"))
+        .output()
+        .expect("Failed to run tlang executable");
+    let generated = std::fs::read_to_string(dir.join("gen/table.t"));
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "128\n");
+    assert_eq!(generated.unwrap(), "fn table_size() -> i64 { 64 }\n");
+}
+
+#[test]
+fn targets_lists_every_backend_with_its_capabilities() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlang"))
+        .arg("targets")
+        .output()
+        .expect("Failed to run tlang executable");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let c = stdout.lines().find(|line| line.starts_with("c ")).expect("no c backend");
+    assert!(c.ends_with("cross-compilation"), "{}", c);
+    assert!(stdout.lines().any(|line| line.starts_with("rust ")));
+}
+
 // To use recursion depth protection, update your eval logic like this:
 //
 // fn eval_expr(&mut self, expr: Expr, depth: usize) -> Result<Value, RuntimeError> {
@@ -0,0 +1,12 @@
+#[tailcall]
+fn count(n: i64, acc: i64) -> i64 {
+    if n == 0 {
+        acc
+    } else {
+        count(n - 1, acc + 1)
+    }
+}
+
+fn main() {
+    println(count(1000000, 0));
+}
@@ -0,0 +1,53 @@
+//! Checked, wrapping and saturating arithmetic on `i64` for T-Lang.
+//!
+//! Integer methods such as `x.checked_add(y)` fold to constants when the
+//! compiler can compute them; these are what backends call when it can
+//! not. A checked operation returns whether it fitted and writes the result
+//! through `out` only if it did, which is how its `Option` crosses the C
+//! boundary.
+
+macro_rules! arithmetic {
+    ($($op:ident: $checked:ident, $wrapping:ident, $saturating:ident;)*) => {$(
+        /// `a.checked_op(b)`: whether the result fits, stored in `out` if so.
+        ///
+        /// # Safety
+        /// `out` must be null or valid for writes.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn $checked(a: i64, b: i64, out: *mut i64) -> bool {
+            match a.$op(b) {
+                Some(result) => {
+                    if !out.is_null() {
+                        // Safety: the caller passes a writable `out`
+                        unsafe { *out = result };
+                    }
+                    true
+                }
+                None => false,
+            }
+        }
+
+        /// `a.wrapping_op(b)`
+        #[unsafe(no_mangle)]
+        pub extern "C" fn $wrapping(a: i64, b: i64) -> i64 {
+            arithmetic!(@wrapping $op, a, b)
+        }
+
+        /// `a.saturating_op(b)`
+        #[unsafe(no_mangle)]
+        pub extern "C" fn $saturating(a: i64, b: i64) -> i64 {
+            arithmetic!(@saturating $op, a, b)
+        }
+    )*};
+    (@wrapping checked_add, $a:expr, $b:expr) => { $a.wrapping_add($b) };
+    (@wrapping checked_sub, $a:expr, $b:expr) => { $a.wrapping_sub($b) };
+    (@wrapping checked_mul, $a:expr, $b:expr) => { $a.wrapping_mul($b) };
+    (@saturating checked_add, $a:expr, $b:expr) => { $a.saturating_add($b) };
+    (@saturating checked_sub, $a:expr, $b:expr) => { $a.saturating_sub($b) };
+    (@saturating checked_mul, $a:expr, $b:expr) => { $a.saturating_mul($b) };
+}
+
+arithmetic! {
+    checked_add: tlang_checked_add, tlang_wrapping_add, tlang_saturating_add;
+    checked_sub: tlang_checked_sub, tlang_wrapping_sub, tlang_saturating_sub;
+    checked_mul: tlang_checked_mul, tlang_wrapping_mul, tlang_saturating_mul;
+}
@@ -1,46 +1,419 @@
 //! Fundamental collection types for T-Lang.
-//! For now, re-export or wrap Rust’s own; you can replace with custom structures later.
+//!
+//! `HashMap` and `HashSet` keep their entries in the order their keys were
+//! first inserted, as the lowering's constant collections do, so iterating
+//! over one gives the same order on every backend.
+//!
+//! Programs whose collections change at run time hold them by handle: the
+//! `tlang_vec_*`, `tlang_map_*` and `tlang_set_*` C functions keep every
+//! collection the program made in one table, and a handle is its index
+//! there. A collection only one function's frame sees is freed with
+//! `tlang_collection_free` when the function returns, and the next one made
+//! takes its place. Items, keys and values are `i64`s. The functions do not panic:
+//! the compiled program checks an index, a key or that a `Vec` is not
+//! empty before the call, and a call that finds nothing returns 0.
 
 use std::collections::HashMap as StdHashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
 
 /// A dynamically-sized, growable vector.
 pub type Vec<T> = std::vec::Vec<T>;
 
 /// A hash map from one type to another.
 pub struct HashMap<K, V> {
-    inner: StdHashMap<K, V>,
+    entries: Vec<(K, V)>,
+    /// Where each key's entry is in `entries`
+    index: StdHashMap<K, usize>,
 }
 
-impl<K: std::hash::Hash + Eq, V> HashMap<K, V> {
+impl<K: Hash + Eq + Clone, V> HashMap<K, V> {
     /// Create a new, empty `HashMap`.
     pub fn new() -> Self {
-        Self { inner: StdHashMap::new() }
+        Self { entries: Vec::new(), index: StdHashMap::new() }
     }
 
     /// Insert a value into the map, returning the old value (if any).
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.inner.insert(key, value)
+        match self.index.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
     }
 
     /// Get a reference to a value by key.
     pub fn get(&self, key: &K) -> Option<&V> {
-        self.inner.get(key)
+        self.index.get(key).map(|&i| &self.entries[i].1)
     }
 
     /// Remove a key from the map, returning the value if it existed.
     pub fn remove(&mut self, key: &K) -> Option<V> {
-        self.inner.remove(key)
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            if let Some(position) = self.index.get_mut(key) {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    /// Returns true if the map contains the key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// The entries, in the order their keys were first inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
     }
 
     /// Number of elements in the map.
     pub fn len(&self) -> usize {
-        self.inner.len()
+        self.entries.len()
     }
 
     /// Returns true if the map contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> Default for HashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A set of distinct values.
+pub struct HashSet<T> {
+    inner: HashMap<T, ()>,
+}
+
+impl<T: Hash + Eq + Clone> HashSet<T> {
+    /// Create a new, empty `HashSet`.
+    pub fn new() -> Self {
+        Self { inner: HashMap::new() }
+    }
+
+    /// Add a value to the set, returning true if it was not already there.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.inner.insert(value, ()).is_none()
+    }
+
+    /// Returns true if the set contains the value.
+    pub fn contains(&self, value: &T) -> bool {
+        self.inner.contains_key(value)
+    }
+
+    /// Remove a value from the set, returning true if it was there.
+    pub fn remove(&mut self, value: &T) -> bool {
+        self.inner.remove(value).is_some()
+    }
+
+    /// The values, in the order they were first inserted.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.inner.iter().map(|(value, _)| value)
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the set contains no elements.
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
 }
 
-// Future collection types (String utilities, Set, etc.) go here.
+impl<T: Hash + Eq + Clone> Default for HashSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A collection a program holds by handle.
+enum Collection {
+    Vec(Vec<i64>),
+    Map(HashMap<i64, i64>),
+    Set(HashSet<i64>),
+}
+
+/// The collections of the program, by handle.
+struct Table {
+    /// `None` where a collection was freed
+    collections: Vec<Option<Collection>>,
+    /// The handles of the freed collections, for those made next
+    free: Vec<usize>,
+}
+
+static COLLECTIONS: Mutex<Table> = Mutex::new(Table { collections: Vec::new(), free: Vec::new() });
+
+fn table() -> std::sync::MutexGuard<'static, Table> {
+    COLLECTIONS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Add `collection` to the table, returning its handle.
+fn add(collection: Collection) -> i64 {
+    let mut table = table();
+    match table.free.pop() {
+        Some(i) => {
+            table.collections[i] = Some(collection);
+            i as i64
+        }
+        None => {
+            table.collections.push(Some(collection));
+            table.collections.len() as i64 - 1
+        }
+    }
+}
+
+/// `f` of the collection `handle` names, or `T::default()` if it names none.
+fn with<T: Default>(handle: i64, f: impl FnOnce(&mut Collection) -> T) -> T {
+    let mut table = table();
+    usize::try_from(handle).ok().and_then(|i| table.collections.get_mut(i)?.as_mut()).map(f).unwrap_or_default()
+}
+
+/// Free the collection `handle` names, if it names one, for the next one
+/// made to take its place.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_collection_free(handle: i64) {
+    let mut table = table();
+    if let Some(i) = usize::try_from(handle).ok().filter(|&i| table.collections.get(i).is_some_and(Option::is_some)) {
+        table.collections[i] = None;
+        table.free.push(i);
+    }
+}
+
+/// The item of `items` at `index`, or 0.
+fn item(items: &[i64], index: i64) -> i64 {
+    usize::try_from(index).ok().and_then(|i| items.get(i)).copied().unwrap_or(0)
+}
+
+/// `Vec::new()`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_vec_new() -> i64 {
+    add(Collection::Vec(Vec::new()))
+}
+
+/// `v.len()`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_vec_len(v: i64) -> i64 {
+    with(v, |c| match c {
+        Collection::Vec(items) => items.len() as i64,
+        _ => 0,
+    })
+}
+
+/// `v.push(item)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_vec_push(v: i64, item: i64) {
+    with(v, |c| {
+        if let Collection::Vec(items) = c {
+            items.push(item);
+        }
+    })
+}
+
+/// `v.pop()`: the last item, removed, or 0 if there is none.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_vec_pop(v: i64) -> i64 {
+    with(v, |c| match c {
+        Collection::Vec(items) => items.pop().unwrap_or(0),
+        _ => 0,
+    })
+}
+
+/// `v.get(index)`, or 0 past the end.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_vec_get(v: i64, index: i64) -> i64 {
+    with(v, |c| match c {
+        Collection::Vec(items) => item(items, index),
+        _ => 0,
+    })
+}
+
+/// `v.set(index, item)`, which does nothing past the end.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_vec_set(v: i64, index: i64, item: i64) {
+    with(v, |c| {
+        if let Collection::Vec(items) = c
+            && let Some(slot) = usize::try_from(index).ok().and_then(|i| items.get_mut(i))
+        {
+            *slot = item;
+        }
+    })
+}
+
+/// `v.contains(item)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_vec_contains(v: i64, item: i64) -> bool {
+    with(v, |c| matches!(c, Collection::Vec(items) if items.contains(&item)))
+}
+
+/// `HashMap::new()`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_map_new() -> i64 {
+    add(Collection::Map(HashMap::new()))
+}
+
+/// `m.len()`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_map_len(m: i64) -> i64 {
+    with(m, |c| match c {
+        Collection::Map(entries) => entries.len() as i64,
+        _ => 0,
+    })
+}
+
+/// `m.insert(key, value)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_map_insert(m: i64, key: i64, value: i64) {
+    with(m, |c| {
+        if let Collection::Map(entries) = c {
+            entries.insert(key, value);
+        }
+    })
+}
+
+/// `m.get(key)`, or 0 if the key is not there.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_map_get(m: i64, key: i64) -> i64 {
+    with(m, |c| match c {
+        Collection::Map(entries) => entries.get(&key).copied().unwrap_or(0),
+        _ => 0,
+    })
+}
+
+/// `m.remove(key)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_map_remove(m: i64, key: i64) {
+    with(m, |c| {
+        if let Collection::Map(entries) = c {
+            entries.remove(&key);
+        }
+    })
+}
+
+/// `m.contains_key(key)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_map_contains_key(m: i64, key: i64) -> bool {
+    with(m, |c| matches!(c, Collection::Map(entries) if entries.contains_key(&key)))
+}
+
+/// `HashSet::new()`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_set_new() -> i64 {
+    add(Collection::Set(HashSet::new()))
+}
+
+/// `s.len()`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_set_len(s: i64) -> i64 {
+    with(s, |c| match c {
+        Collection::Set(items) => items.len() as i64,
+        _ => 0,
+    })
+}
+
+/// `s.insert(item)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_set_insert(s: i64, item: i64) {
+    with(s, |c| {
+        if let Collection::Set(items) = c {
+            items.insert(item);
+        }
+    })
+}
+
+/// `s.remove(item)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_set_remove(s: i64, item: i64) {
+    with(s, |c| {
+        if let Collection::Set(items) = c {
+            items.remove(&item);
+        }
+    })
+}
+
+/// `s.contains(item)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_set_contains(s: i64, item: i64) -> bool {
+    with(s, |c| matches!(c, Collection::Set(items) if items.contains(&item)))
+}
+
+/// The item of the set inserted `index`th of those it holds, for a `for`
+/// loop over it; 0 past the end.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_set_item(s: i64, index: i64) -> i64 {
+    with(s, |c| match c {
+        Collection::Set(items) => {
+            usize::try_from(index).ok().and_then(|i| items.iter().nth(i)).copied().unwrap_or(0)
+        }
+        _ => 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_and_sets_keep_the_order_of_first_insertion() {
+        let mut map = HashMap::new();
+        map.insert("b", 1);
+        map.insert("a", 2);
+        assert_eq!(map.insert("b", 3), Some(1));
+        map.insert("c", 4);
+        assert_eq!(map.remove(&"a"), Some(2));
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&"b", &3), (&"c", &4)]);
+        assert_eq!(map.get(&"c"), Some(&4));
+
+        let mut set = HashSet::new();
+        assert!(set.insert(3) && set.insert(1) && !set.insert(3));
+        assert!(set.remove(&3) && set.insert(3));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [&1, &3]);
+    }
+
+    #[test]
+    fn handles_name_collections_of_the_table() {
+        let v = tlang_vec_new();
+        tlang_vec_push(v, 7);
+        tlang_vec_push(v, 8);
+        tlang_vec_set(v, 0, 9);
+        assert_eq!((tlang_vec_len(v), tlang_vec_get(v, 0), tlang_vec_get(v, 5)), (2, 9, 0));
+        assert_eq!(tlang_vec_pop(v), 8);
+        assert!(tlang_vec_contains(v, 9));
+
+        let m = tlang_map_new();
+        tlang_map_insert(m, 1, 10);
+        tlang_map_insert(m, 1, 11);
+        assert_eq!((tlang_map_len(m), tlang_map_get(m, 1)), (1, 11));
+        tlang_map_remove(m, 1);
+        assert!(!tlang_map_contains_key(m, 1));
+
+        let s = tlang_set_new();
+        for item in [5, 2, 5] {
+            tlang_set_insert(s, item);
+        }
+        assert_eq!((tlang_set_len(s), tlang_set_item(s, 1)), (2, 2));
+        // A handle of another kind of collection, or of none, finds nothing.
+        assert!(!tlang_set_contains(v, 9));
+        assert_eq!(tlang_vec_len(-1), 0);
+
+        // A freed handle names nothing until the next collection made
+        // takes it; freeing it twice, or a handle of nothing, does nothing.
+        tlang_collection_free(v);
+        assert_eq!(tlang_vec_len(v), 0);
+        tlang_collection_free(v);
+        tlang_collection_free(-1);
+        let w = tlang_map_new();
+        tlang_map_insert(w, 2, 3);
+        assert_eq!((w, tlang_map_get(w, 2), tlang_vec_len(w)), (v, 3, 0));
+    }
+}
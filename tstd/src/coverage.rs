@@ -0,0 +1,57 @@
+//! Coverage counters of programs compiled with `--coverage`.
+//!
+//! The counts are written when the program exits, by `exit` or a panic, to
+//! the file `TLANG_COVERAGE_FILE` names, else to `COUNTS_FILE` in the
+//! current directory, replacing what a previous run wrote there. A program
+//! that aborts writes none.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, Once};
+
+/// Where the counts go when `TLANG_COVERAGE_FILE` is not set.
+pub const COUNTS_FILE: &str = "coverage.counts";
+
+/// How often each counter ran, by its number.
+static COUNTS: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+/// Registers `write_at_exit` with the first count.
+static AT_EXIT: Once = Once::new();
+
+unsafe extern "C" {
+    fn atexit(function: extern "C" fn()) -> i32;
+}
+
+/// The file the counts are written to.
+pub fn counts_path() -> PathBuf {
+    std::env::var_os("TLANG_COVERAGE_FILE").map_or_else(|| PathBuf::from(COUNTS_FILE), PathBuf::from)
+}
+
+/// Write the counts so far to `counts_path`, one per line, counter 0 first.
+pub fn write_counts() -> io::Result<()> {
+    let counts = COUNTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let text: String = counts.iter().map(|count| format!("{}\n", count)).collect();
+    fs::write(counts_path(), text)
+}
+
+extern "C" fn write_at_exit() {
+    if let Err(error) = write_counts() {
+        eprintln!("cannot write coverage counts to {}: {}", counts_path().display(), error);
+    }
+}
+
+/// Count a run of the code `counter` counts.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_coverage_hit(counter: i32) {
+    // Safety: `write_at_exit` is an `extern "C" fn()` that lives as long as the program
+    AT_EXIT.call_once(|| unsafe {
+        atexit(write_at_exit);
+    });
+    let Ok(counter) = usize::try_from(counter) else { return };
+    let mut counts = COUNTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if counts.len() <= counter {
+        counts.resize(counter + 1, 0);
+    }
+    counts[counter] += 1;
+}
@@ -0,0 +1,55 @@
+//! Command-line arguments and environment variables for T-Lang.
+
+use std::ffi::c_char;
+use std::sync::OnceLock;
+
+/// Arguments set by `set_args`, in place of the process's own.
+static ARGS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Give a program run in-process, as `tlang run` runs it, `args` as its
+/// command-line arguments instead of its host's. Only the first call has
+/// an effect; it returns whether it was this one.
+pub fn set_args(args: Vec<String>) -> bool {
+    ARGS.set(args).is_ok()
+}
+
+/// The program's command-line arguments, starting with its own name.
+pub fn args() -> Vec<String> {
+    match ARGS.get() {
+        Some(args) => args.clone(),
+        None => std::env::args().collect(),
+    }
+}
+
+/// The value of the environment variable `name`, if it is set.
+pub fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// `env::arg_count()`: how many command-line arguments there are.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_env_arg_count() -> i32 {
+    match ARGS.get() {
+        Some(args) => args.len() as i32,
+        None => std::env::args_os().len() as i32,
+    }
+}
+
+/// `env::arg(index)`: the command-line argument at `index`, or an empty
+/// string past the last one.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_env_arg(index: i32) -> *mut c_char {
+    let arg = usize::try_from(index).ok().and_then(|i| args().into_iter().nth(i));
+    crate::c_string(&arg.unwrap_or_default())
+}
+
+/// `env::var(name)`: the value of an environment variable, or an empty
+/// string if it is not set.
+///
+/// # Safety
+/// `name` is a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_env_var(name: *const c_char) -> *mut c_char {
+    let name = unsafe { crate::rust_string(name) };
+    crate::c_string(&var(&name).unwrap_or_default())
+}
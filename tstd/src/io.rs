@@ -1,6 +1,8 @@
 //! Basic I/O functions for T-Lang.
 //! You can replace the underlying implementation once the runtime is ready.
 
+use std::ffi::c_char;
+
 /// Print without a trailing newline.
 pub fn print(s: &str) {
     // TODO: Hook into T-Lang’s runtime printing.
@@ -24,3 +26,68 @@ pub fn read_line() -> String {
         .expect("Failed to read line from stdin");
     buffer.trim_end().to_string()
 }
+
+/// Read the whole file at `path`.
+pub fn read_file(path: &str) -> std::io::Result<String> {
+    std::fs::read_to_string(path)
+}
+
+/// Replace the file at `path` with `contents`.
+pub fn write_file(path: &str, contents: &str) -> std::io::Result<()> {
+    std::fs::write(path, contents)
+}
+
+/// `io::read_file(path)`: the file's text, or an empty string if it
+/// cannot be read.
+///
+/// # Safety
+/// `path` is a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_io_read_file(path: *const c_char) -> *mut c_char {
+    let path = unsafe { crate::rust_string(path) };
+    crate::c_string(&read_file(&path).unwrap_or_default())
+}
+
+/// `io::write_file(path, contents)`: whether the file was written.
+///
+/// # Safety
+/// `path` and `contents` are NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_io_write_file(path: *const c_char, contents: *const c_char) -> bool {
+    let (path, contents) = unsafe { (crate::rust_string(path), crate::rust_string(contents)) };
+    write_file(&path, &contents).is_ok()
+}
+
+/// `io::read_line()`: a line of stdin without its line ending, or an empty
+/// string at the end of input.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_io_read_line() -> *mut c_char {
+    let mut buffer = String::new();
+    let _ = std::io::stdin().read_line(&mut buffer);
+    crate::c_string(buffer.trim_end_matches(['\n', '\r']))
+}
+
+/// `io::write(text)`: print `text` without a trailing newline.
+///
+/// # Safety
+/// `text` is a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_io_write(text: *const c_char) {
+    use std::io::Write;
+    let text = unsafe { crate::rust_string(text) };
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(text.as_bytes());
+    // C code printing after the call shares the terminal, not this buffer.
+    let _ = stdout.flush();
+}
+
+/// `eprint` and `eprintln`: write `text` to stderr.
+///
+/// # Safety
+/// `text` is a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_eprint(text: *const c_char) {
+    use std::io::Write;
+    let text = unsafe { crate::rust_string(text) };
+    let _ = std::io::stderr().write_all(text.as_bytes());
+}
@@ -1,7 +1,121 @@
 //! T-Lang standard library: exposes `tlang_print` and `tlang_println` for backends.
+//!
+//! `io`, `env`, `time`, `random` and `process` export the C functions
+//! behind the compiler's intrinsics of the same modules, such as
+//! `io::read_file`. They take NUL-terminated strings and return ones
+//! allocated with `malloc`, which the caller frees. `arith` exports
+//! checked, wrapping and saturating `i64` arithmetic, and `coverage` the
+//! counters of programs compiled with `--coverage`. `collections` exports
+//! the `Vec`, `HashMap` and `HashSet` of programs that change them at run
+//! time, held by handle.
+//!
+//! Programs compiled ahead of time link the static library; ones run
+//! in-process, as by the Cranelift JIT, find the functions through
+//! `exports`.
+
+use std::ffi::{c_char, c_void, CStr};
+
+pub mod arith;
+pub mod collections;
+pub mod coverage;
+pub mod env;
+pub mod io;
+pub mod process;
+pub mod random;
+pub mod time;
+
+unsafe extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+}
+
+/// `s` as a NUL-terminated string allocated with `malloc`, cut short at
+/// its first NUL if it has one.
+pub(crate) fn c_string(s: &str) -> *mut c_char {
+    let bytes = s.as_bytes();
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    // Safety: the allocation holds `len` bytes and the terminator
+    unsafe {
+        let ptr = malloc(len + 1) as *mut u8;
+        if ptr.is_null() {
+            return std::ptr::null_mut();
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, len);
+        *ptr.add(len) = 0;
+        ptr as *mut c_char
+    }
+}
+
+/// The string at `ptr`, empty if it is null; invalid UTF-8 is replaced.
+///
+/// # Safety
+/// `ptr` is null or points to a NUL-terminated string.
+pub(crate) unsafe fn rust_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+}
+
+/// Every C function this crate exports, by name, with its address.
+pub fn exports() -> Vec<(&'static str, *const u8)> {
+    vec![
+        ("tlang_print", tlang_print as *const u8),
+        ("tlang_println", tlang_println as *const u8),
+        ("tlang_checked_add", arith::tlang_checked_add as *const u8),
+        ("tlang_checked_sub", arith::tlang_checked_sub as *const u8),
+        ("tlang_checked_mul", arith::tlang_checked_mul as *const u8),
+        ("tlang_wrapping_add", arith::tlang_wrapping_add as *const u8),
+        ("tlang_wrapping_sub", arith::tlang_wrapping_sub as *const u8),
+        ("tlang_wrapping_mul", arith::tlang_wrapping_mul as *const u8),
+        ("tlang_saturating_add", arith::tlang_saturating_add as *const u8),
+        ("tlang_saturating_sub", arith::tlang_saturating_sub as *const u8),
+        ("tlang_saturating_mul", arith::tlang_saturating_mul as *const u8),
+        ("tlang_collection_free", collections::tlang_collection_free as *const u8),
+        ("tlang_vec_new", collections::tlang_vec_new as *const u8),
+        ("tlang_vec_len", collections::tlang_vec_len as *const u8),
+        ("tlang_vec_push", collections::tlang_vec_push as *const u8),
+        ("tlang_vec_pop", collections::tlang_vec_pop as *const u8),
+        ("tlang_vec_get", collections::tlang_vec_get as *const u8),
+        ("tlang_vec_set", collections::tlang_vec_set as *const u8),
+        ("tlang_vec_contains", collections::tlang_vec_contains as *const u8),
+        ("tlang_map_new", collections::tlang_map_new as *const u8),
+        ("tlang_map_len", collections::tlang_map_len as *const u8),
+        ("tlang_map_insert", collections::tlang_map_insert as *const u8),
+        ("tlang_map_get", collections::tlang_map_get as *const u8),
+        ("tlang_map_remove", collections::tlang_map_remove as *const u8),
+        ("tlang_map_contains_key", collections::tlang_map_contains_key as *const u8),
+        ("tlang_set_new", collections::tlang_set_new as *const u8),
+        ("tlang_set_len", collections::tlang_set_len as *const u8),
+        ("tlang_set_insert", collections::tlang_set_insert as *const u8),
+        ("tlang_set_remove", collections::tlang_set_remove as *const u8),
+        ("tlang_set_contains", collections::tlang_set_contains as *const u8),
+        ("tlang_set_item", collections::tlang_set_item as *const u8),
+        ("tlang_env_arg_count", env::tlang_env_arg_count as *const u8),
+        ("tlang_env_arg", env::tlang_env_arg as *const u8),
+        ("tlang_env_var", env::tlang_env_var as *const u8),
+        ("tlang_io_read_file", io::tlang_io_read_file as *const u8),
+        ("tlang_io_write_file", io::tlang_io_write_file as *const u8),
+        ("tlang_io_read_line", io::tlang_io_read_line as *const u8),
+        ("tlang_io_write", io::tlang_io_write as *const u8),
+        ("tlang_eprint", io::tlang_eprint as *const u8),
+        ("tlang_process_exit", process::tlang_process_exit as *const u8),
+        ("tlang_process_spawn", process::tlang_process_spawn as *const u8),
+        ("tlang_panic", process::tlang_panic as *const u8),
+        ("tlang_abort", process::tlang_abort as *const u8),
+        ("tlang_random_seed", random::tlang_random_seed as *const u8),
+        ("tlang_random_next", random::tlang_random_next as *const u8),
+        ("tlang_random_below", random::tlang_random_below as *const u8),
+        ("tlang_time_now_ms", time::tlang_time_now_ms as *const u8),
+        ("tlang_time_sleep_ms", time::tlang_time_sleep_ms as *const u8),
+        ("tlang_coverage_hit", coverage::tlang_coverage_hit as *const u8),
+    ]
+}
 
 /// Print a UTF-8 string slice without a trailing newline.
 /// Backends call this via the FFI or link directly.
+///
+/// # Safety
+/// `ptr` is valid for reads of `len` bytes.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn tlang_print(ptr: *const u8, len: usize) {
     // Safety: assume backends pass a valid UTF-8 pointer+length
@@ -12,6 +126,9 @@ pub unsafe extern "C" fn tlang_print(ptr: *const u8, len: usize) {
 }
 
 /// Print a UTF-8 string slice with a trailing newline.
+///
+/// # Safety
+/// `ptr` is valid for reads of `len` bytes.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn tlang_println(ptr: *const u8, len: usize) {
     // Safety: We trust that the caller provides a valid UTF-8 pointer and length
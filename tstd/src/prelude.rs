@@ -3,7 +3,7 @@
 
 pub use crate::io::{print, println};
 pub use crate::math::{abs, pow};
-pub use crate::collections::{Vec, HashMap};
+pub use crate::collections::{Vec, HashMap, HashSet};
 
 // Add additional re-exports here as you build out tstd
 
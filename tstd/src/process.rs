@@ -0,0 +1,81 @@
+//! Exiting, panicking and running other programs for T-Lang.
+
+use std::ffi::{c_char, c_void};
+use std::io::Write;
+use std::process::Command;
+
+/// Run `command` with the system shell and wait for it; its exit code, or
+/// `None` if it could not run or was killed by a signal.
+pub fn run(command: &str) -> Option<i32> {
+    let status = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).status()
+    } else {
+        Command::new("sh").args(["-c", command]).status()
+    };
+    status.ok()?.code()
+}
+
+/// `process::exit(code)`: flush stdout and end the program with `code`.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_process_exit(code: i32) {
+    let _ = std::io::stdout().flush();
+    std::process::exit(code);
+}
+
+unsafe extern "C" {
+    fn fflush(stream: *mut c_void) -> i32;
+}
+
+/// Flush what both Rust and C's stdio have buffered for stdout.
+fn flush_stdout() {
+    let _ = std::io::stdout().flush();
+    // Safety: a null stream flushes every open stream
+    unsafe { fflush(std::ptr::null_mut()) };
+}
+
+/// Print a panic's `message` to stderr, and its `backtrace` if the
+/// `TLANG_BACKTRACE` variable is set to anything but `0`.
+fn report_panic(message: &str, backtrace: &str) {
+    eprintln!("{}", message);
+    match std::env::var("TLANG_BACKTRACE") {
+        Ok(value) if value != "0" => eprintln!("stack backtrace:\n{}", backtrace),
+        _ => eprintln!("note: run with `TLANG_BACKTRACE=1` environment variable to display a backtrace"),
+    }
+}
+
+/// `panic(message)`, failed `assert`s and the other panics of the unwind
+/// strategy: flush stdout, report the panic and exit with 101, as a Rust
+/// panic does.
+///
+/// # Safety
+/// `message` and `backtrace` are NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_panic(message: *const c_char, backtrace: *const c_char) {
+    let (message, backtrace) = unsafe { (crate::rust_string(message), crate::rust_string(backtrace)) };
+    flush_stdout();
+    report_panic(&message, &backtrace);
+    std::process::exit(101);
+}
+
+/// A panic under the abort strategy: report it and abort, leaving what
+/// stdout buffered unwritten.
+///
+/// # Safety
+/// `message` and `backtrace` are NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_abort(message: *const c_char, backtrace: *const c_char) {
+    let (message, backtrace) = unsafe { (crate::rust_string(message), crate::rust_string(backtrace)) };
+    report_panic(&message, &backtrace);
+    std::process::abort();
+}
+
+/// `process::spawn(command)`: run a shell command and wait for it; its exit
+/// code, or -1 if it could not run.
+///
+/// # Safety
+/// `command` is a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tlang_process_spawn(command: *const c_char) -> i32 {
+    let command = unsafe { crate::rust_string(command) };
+    run(&command).unwrap_or(-1)
+}
@@ -0,0 +1,43 @@
+//! A seeded pseudo-random number generator for T-Lang.
+//!
+//! It is SplitMix64, so a seed yields the same numbers whichever backend
+//! the program was compiled with. It is not for cryptography.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The generator's state; the seed until the first number is drawn.
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Restart the sequence from `seed`.
+pub fn seed(seed: u64) {
+    STATE.store(seed, Ordering::Relaxed);
+}
+
+/// The next number of the sequence.
+pub fn next_u64() -> u64 {
+    let mut z = STATE.fetch_add(GOLDEN_GAMMA, Ordering::Relaxed).wrapping_add(GOLDEN_GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// `random::seed(seed)`
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_random_seed(value: i64) {
+    seed(value as u64);
+}
+
+/// `random::next()`: a number from 0 to `i64::MAX`.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_random_next() -> i64 {
+    (next_u64() >> 1) as i64
+}
+
+/// `random::below(n)`: a number from 0 to `n - 1`, or 0 if `n` is not
+/// positive.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_random_below(n: i64) -> i64 {
+    if n <= 0 { 0 } else { tlang_random_next() % n }
+}
@@ -0,0 +1,30 @@
+//! A monotonic clock and sleeping for T-Lang.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// When the clock was first read.
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Time since the clock was first read; zero the first time.
+pub fn elapsed() -> Duration {
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// Block the calling thread for `duration`.
+pub fn sleep(duration: Duration) {
+    std::thread::sleep(duration);
+}
+
+/// `time::now_ms()`: milliseconds since the clock was first read.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_time_now_ms() -> i64 {
+    elapsed().as_millis() as i64
+}
+
+/// `time::sleep_ms(ms)`: sleep for `ms` milliseconds; not at all if `ms`
+/// is negative.
+#[unsafe(no_mangle)]
+pub extern "C" fn tlang_time_sleep_ms(ms: i64) {
+    sleep(Duration::from_millis(ms.max(0) as u64));
+}